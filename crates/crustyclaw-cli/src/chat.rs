@@ -0,0 +1,387 @@
+//! Interactive chat REPL for `crustyclaw chat`.
+//!
+//! Each line typed is sent to the daemon's agent loop over `POST /chat`
+//! and the reply is printed as it streams in, with light markdown
+//! rendering when stdout is a terminal. Lines starting with `/` are REPL
+//! commands: `/tools`, `/reset`, `/save [path]`, `/help`, and `/quit`.
+
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use crustyclaw_core::IpcClient;
+use crustyclaw_core::chat::ToolFilter;
+use crustyclaw_core::ipc::ChatEvent;
+use tokio::io::AsyncBufReadExt;
+
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const UNDERLINE: &str = "\x1b[4m";
+const CYAN: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+const HELP: &str = "Commands: /tools, /reset, /save [path], /help, /quit";
+
+/// One line of REPL input.
+#[derive(Debug, PartialEq, Eq)]
+enum Input {
+    /// A message for the agent.
+    Message(String),
+    /// `/tools`: list the tools offered to the caller.
+    Tools,
+    /// `/reset`: forget the session's history.
+    Reset,
+    /// `/save [path]`: write the transcript as markdown.
+    Save(Option<PathBuf>),
+    /// `/help`.
+    Help,
+    /// `/quit` or `/exit`.
+    Quit,
+    /// An unrecognised `/` command.
+    Unknown(String),
+}
+
+impl Input {
+    /// Parse a trimmed, non-empty input line.
+    fn parse(line: &str) -> Self {
+        let Some(command) = line.strip_prefix('/') else {
+            return Self::Message(line.to_string());
+        };
+        let (name, arg) = match command.split_once(char::is_whitespace) {
+            Some((name, arg)) => (name, Some(arg.trim()).filter(|a| !a.is_empty())),
+            None => (command, None),
+        };
+        match name {
+            "tools" => Self::Tools,
+            "reset" => Self::Reset,
+            "save" => Self::Save(arg.map(PathBuf::from)),
+            "help" => Self::Help,
+            "quit" | "exit" => Self::Quit,
+            _ => Self::Unknown(name.to_string()),
+        }
+    }
+}
+
+/// Line-buffered markdown-ish renderer for streamed text.
+///
+/// Deltas are buffered until a newline so that markup split across chunks
+/// renders correctly. Headings are bold and underlined, `**bold**` is bold,
+/// inline code and fenced blocks are coloured, list bullets become `•`, and
+/// quotes are dimmed. Without colour, text passes through unchanged.
+struct Markdown {
+    color: bool,
+    in_fence: bool,
+    line: String,
+}
+
+impl Markdown {
+    fn new(color: bool) -> Self {
+        Self {
+            color,
+            in_fence: false,
+            line: String::new(),
+        }
+    }
+
+    /// Add a delta and return the rendered text of any lines it completes.
+    fn push(&mut self, delta: &str) -> String {
+        self.line.push_str(delta);
+        let mut out = String::new();
+        while let Some(end) = self.line.find('\n') {
+            let line: String = self.line.drain(..=end).collect();
+            out.push_str(&self.render_line(&line[..end]));
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Render the buffered partial line, if any, and reset for a new reply.
+    fn finish(&mut self) -> String {
+        let line = std::mem::take(&mut self.line);
+        let out = if line.is_empty() {
+            line
+        } else {
+            format!("{}\n", self.render_line(&line))
+        };
+        self.in_fence = false;
+        out
+    }
+
+    fn render_line(&mut self, line: &str) -> String {
+        if line.trim_start().starts_with("```") {
+            self.in_fence = !self.in_fence;
+            return self.style(DIM, line);
+        }
+        if self.in_fence {
+            return self.style(CYAN, line);
+        }
+        if !self.color {
+            return line.to_string();
+        }
+
+        let trimmed = line.trim_start();
+        let indent = &line[..line.len() - trimmed.len()];
+        let hashes = trimmed.chars().take_while(|&c| c == '#').count();
+        if (1..=6).contains(&hashes) && trimmed[hashes..].starts_with(' ') {
+            let text = inline(trimmed[hashes..].trim());
+            return format!("{BOLD}{UNDERLINE}{text}{RESET}");
+        }
+        if let Some(item) = trimmed
+            .strip_prefix("- ")
+            .or_else(|| trimmed.strip_prefix("* "))
+        {
+            return format!("{indent}• {}", inline(item));
+        }
+        if let Some(quote) = trimmed.strip_prefix('>') {
+            return format!("{DIM}{indent}│ {}{RESET}", inline(quote.trim_start()));
+        }
+        inline(line)
+    }
+
+    fn style(&self, style: &str, text: &str) -> String {
+        if self.color {
+            format!("{style}{text}{RESET}")
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Render `**bold**` and `` `code` `` spans within a line.
+fn inline(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let (mut bold, mut code) = (false, false);
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if c == '`' {
+            code = !code;
+            out.push_str(if code { CYAN } else { RESET });
+            if !code && bold {
+                out.push_str(BOLD);
+            }
+            rest = &rest[1..];
+        } else if !code && rest.starts_with("**") {
+            bold = !bold;
+            out.push_str(if bold { BOLD } else { RESET });
+            rest = &rest[2..];
+        } else {
+            out.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    if bold || code {
+        out.push_str(RESET);
+    }
+    out
+}
+
+/// The exchanges of a chat session, for `/save`.
+#[derive(Default)]
+struct Transcript {
+    turns: Vec<(String, String)>,
+}
+
+impl Transcript {
+    fn push(&mut self, message: &str, reply: &str) {
+        self.turns.push((message.to_string(), reply.to_string()));
+    }
+
+    fn to_markdown(&self, session: Option<&str>) -> String {
+        let mut out = match session {
+            Some(session) => format!("# Chat {session}\n"),
+            None => "# Chat\n".to_string(),
+        };
+        for (message, reply) in &self.turns {
+            out.push_str(&format!("\n## You\n\n{message}\n\n## Agent\n\n{reply}\n"));
+        }
+        out
+    }
+}
+
+/// Run the REPL until `/quit` or end of input, continuing `session` if given.
+/// Every session it starts or continues is limited to the tools `tools`
+/// permits.
+pub async fn repl(
+    client: &IpcClient,
+    mut session: Option<String>,
+    tools: &ToolFilter,
+    color: bool,
+) -> Result<()> {
+    let dim = |text: &str| {
+        if color {
+            format!("{DIM}{text}{RESET}")
+        } else {
+            text.to_string()
+        }
+    };
+    let mut transcript = Transcript::default();
+    let mut lines = tokio::io::BufReader::new(tokio::io::stdin()).lines();
+
+    println!("{}", dim(HELP));
+    loop {
+        print!("> ");
+        std::io::stdout().flush()?;
+        let Some(line) = lines.next_line().await? else {
+            println!();
+            break;
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        match Input::parse(line) {
+            Input::Message(message) => {
+                match send(client, &mut session, tools, &message, color, &dim).await {
+                    Ok(Some(reply)) => transcript.push(&message, &reply),
+                    Ok(None) => {}
+                    Err(e) => eprintln!("error: {e}"),
+                }
+            }
+            Input::Tools => match client.chat_tools().await {
+                Ok(listing) if listing.tools.is_empty() => println!("No tools offered"),
+                Ok(listing) => {
+                    for tool in &listing.tools {
+                        println!("{:<20} {:<9} {}", tool.name, tool.trust, tool.description);
+                    }
+                }
+                Err(e) => eprintln!("error: failed to list tools: {e}"),
+            },
+            Input::Reset => {
+                if let Some(id) = session.take()
+                    && let Err(e) = client.chat_reset(&id).await
+                {
+                    eprintln!("error: failed to reset session {id}: {e}");
+                }
+                transcript = Transcript::default();
+                println!("{}", dim("Started a new session"));
+            }
+            Input::Save(path) => {
+                let path = path.unwrap_or_else(|| {
+                    PathBuf::from(format!(
+                        "crustyclaw-chat-{}.md",
+                        session.as_deref().unwrap_or("new")
+                    ))
+                });
+                match std::fs::write(&path, transcript.to_markdown(session.as_deref())) {
+                    Ok(()) => println!("{}", dim(&format!("Saved to {}", path.display()))),
+                    Err(e) => eprintln!("error: failed to write {}: {e}", path.display()),
+                }
+            }
+            Input::Help => println!("{}", dim(HELP)),
+            Input::Quit => break,
+            Input::Unknown(name) => eprintln!("Unknown command /{name}. {HELP}"),
+        }
+    }
+    Ok(())
+}
+
+/// Send one message and print the streamed run. Returns the final reply,
+/// or `None` if the run failed.
+async fn send(
+    client: &IpcClient,
+    session: &mut Option<String>,
+    tools: &ToolFilter,
+    message: &str,
+    color: bool,
+    dim: &impl Fn(&str) -> String,
+) -> Result<Option<String>> {
+    let mut stream = client.chat(session.as_deref(), message, tools).await?;
+    let mut markdown = Markdown::new(color);
+    let mut stdout = std::io::stdout();
+    let mut streamed = false;
+
+    while let Some(event) = stream.next().await? {
+        match event {
+            ChatEvent::Session { id } => *session = Some(id),
+            ChatEvent::Thinking { .. } => {}
+            ChatEvent::Text { delta } => {
+                streamed = true;
+                stdout.write_all(markdown.push(&delta).as_bytes())?;
+                stdout.flush()?;
+            }
+            ChatEvent::ToolCall { name } => {
+                stdout.write_all(markdown.finish().as_bytes())?;
+                println!("{}", dim(&format!("→ {name}")));
+            }
+            ChatEvent::ToolResult { name, ok } => {
+                let status = if ok { "ok" } else { "failed" };
+                println!("{}", dim(&format!("← {name} {status}")));
+            }
+            ChatEvent::Done {
+                reply,
+                iterations,
+                total_tokens,
+            } => {
+                if !streamed {
+                    stdout.write_all(markdown.push(&reply).as_bytes())?;
+                }
+                stdout.write_all(markdown.finish().as_bytes())?;
+                println!(
+                    "{}",
+                    dim(&format!("({iterations} iterations, {total_tokens} tokens)"))
+                );
+                return Ok(Some(reply));
+            }
+            ChatEvent::Error { error } => {
+                stdout.write_all(markdown.finish().as_bytes())?;
+                eprintln!("error: {error}");
+                return Ok(None);
+            }
+        }
+    }
+    stdout.write_all(markdown.finish().as_bytes())?;
+    eprintln!("error: the daemon closed the stream before the run finished");
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_input() {
+        assert_eq!(Input::parse("hello"), Input::Message("hello".into()));
+        assert_eq!(Input::parse("/tools"), Input::Tools);
+        assert_eq!(Input::parse("/reset"), Input::Reset);
+        assert_eq!(Input::parse("/save"), Input::Save(None));
+        assert_eq!(
+            Input::parse("/save  notes.md "),
+            Input::Save(Some(PathBuf::from("notes.md")))
+        );
+        assert_eq!(Input::parse("/exit"), Input::Quit);
+        assert_eq!(Input::parse("/nope x"), Input::Unknown("nope".into()));
+    }
+
+    #[test]
+    fn test_markdown_rendering() {
+        let mut md = Markdown::new(true);
+        // Markup split across deltas renders once the line completes.
+        assert_eq!(md.push("## Res"), "");
+        assert_eq!(
+            md.push("ult\n- a **b"),
+            format!("{BOLD}{UNDERLINE}Result{RESET}\n")
+        );
+        assert_eq!(md.push("**\n"), format!("• a {BOLD}b{RESET}\n"));
+        assert_eq!(
+            md.push("```\nlet x = `y`;\n```\nrun `ls`"),
+            format!("{DIM}```{RESET}\n{CYAN}let x = `y`;{RESET}\n{DIM}```{RESET}\n")
+        );
+        assert_eq!(md.finish(), format!("run {CYAN}ls{RESET}\n"));
+
+        let mut plain = Markdown::new(false);
+        let text = "# Title\n**bold** and `code`\n";
+        assert_eq!(plain.push(text), text);
+        assert_eq!(plain.finish(), "");
+    }
+
+    #[test]
+    fn test_transcript_markdown() {
+        let mut transcript = Transcript::default();
+        transcript.push("hi", "hello **there**");
+        assert_eq!(
+            transcript.to_markdown(Some("abc")),
+            "# Chat abc\n\n## You\n\nhi\n\n## Agent\n\nhello **there**\n"
+        );
+    }
+}
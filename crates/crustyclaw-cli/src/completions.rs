@@ -0,0 +1,139 @@
+//! Shell completions — `crustyclaw completions <shell>`.
+//!
+//! By default the script registers `crustyclaw` as its own completer: on
+//! each Tab the shell runs `COMPLETE=<shell> crustyclaw -- <words>`, which
+//! [`CompleteEnv`](clap_complete::CompleteEnv) answers from the command
+//! definitions before any other work. Skill and tool names are completed
+//! by asking the running daemon, found through `crustyclaw.toml` in the
+//! current directory; when no daemon answers within [`QUERY_TIMEOUT`] they
+//! are simply not offered. `--static` prints a self-contained script
+//! generated ahead of time instead, without skill or tool names.
+
+use std::future::Future;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::Result;
+use clap::CommandFactory;
+use clap_complete::env::Shells;
+use clap_complete::{CompletionCandidate, Shell};
+
+use crate::{Cli, ConfigSource};
+
+/// Name of the binary the scripts complete.
+const BIN: &str = "crustyclaw";
+
+/// Environment variable that switches the binary into completion mode.
+pub const COMPLETE_VAR: &str = "COMPLETE";
+
+/// How long a completion waits for the daemon.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Write the completion script for `shell` to `out`.
+pub fn write(shell: Shell, dynamic: bool, out: &mut dyn Write) -> Result<()> {
+    if !dynamic {
+        clap_complete::generate(shell, &mut Cli::command(), BIN, out);
+        return Ok(());
+    }
+    let name = shell.to_string();
+    let shells = Shells::builtins();
+    let completer = shells
+        .completer(&name)
+        .ok_or_else(|| anyhow::anyhow!("no dynamic completion for {name}"))?;
+    // Complete with the binary found on `PATH`, like the shell runs it.
+    completer.write_registration(COMPLETE_VAR, BIN, BIN, BIN, out)?;
+    Ok(())
+}
+
+/// Names of the skills registered with the running daemon.
+pub fn skill_names() -> Vec<CompletionCandidate> {
+    query(|client| async move {
+        let skills = client.skills().await.ok()?.skills;
+        Some(
+            skills
+                .into_iter()
+                .map(|skill| candidate(skill.name, skill.description))
+                .collect(),
+        )
+    })
+}
+
+/// Names of the tools the daemon offers the caller in chat sessions.
+pub fn tool_names() -> Vec<CompletionCandidate> {
+    query(|client| async move {
+        let tools = client.chat_tools().await.ok()?.tools;
+        Some(
+            tools
+                .into_iter()
+                .map(|tool| candidate(tool.name, tool.description))
+                .collect(),
+        )
+    })
+}
+
+fn candidate(name: String, description: String) -> CompletionCandidate {
+    let help = description.lines().next().unwrap_or_default().to_string();
+    CompletionCandidate::new(name).help((!help.is_empty()).then(|| help.into()))
+}
+
+/// Run `ask` against the daemon of the default config, returning no
+/// candidates if there is none or it does not answer in time.
+fn query<F, Fut>(ask: F) -> Vec<CompletionCandidate>
+where
+    F: FnOnce(crustyclaw_core::IpcClient) -> Fut,
+    Fut: Future<Output = Option<Vec<CompletionCandidate>>>,
+{
+    let source = ConfigSource {
+        path: PathBuf::from("crustyclaw.toml"),
+        set: Vec::new(),
+    };
+    let run = async {
+        let config = crate::load_config(&source).await.ok()?;
+        let client = crustyclaw_core::IpcClient::from_config(&config).ok()?;
+        if !client.daemon_available() {
+            return None;
+        }
+        tokio::time::timeout(QUERY_TIMEOUT, ask(client))
+            .await
+            .ok()
+            .flatten()
+    };
+    // Completion runs before `main` does anything else, but inside its
+    // runtime, so the query blocks this worker thread.
+    tokio::task::block_in_place(|| tokio::runtime::Handle::current().block_on(run))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script(shell: Shell, dynamic: bool) -> String {
+        let mut out = Vec::new();
+        write(shell, dynamic, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_static_scripts() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let script = script(shell, false);
+            assert!(script.contains("crustyclaw"), "{shell}");
+            assert!(script.contains("completions"), "{shell}");
+            assert!(!script.contains(COMPLETE_VAR), "{shell}");
+        }
+    }
+
+    #[test]
+    fn test_dynamic_scripts_call_back_into_the_binary() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let script = script(shell, true);
+            assert!(
+                script.contains(&format!("{COMPLETE_VAR}=")),
+                "{shell}: {script}"
+            );
+            assert!(script.contains(BIN), "{shell}");
+        }
+    }
+}
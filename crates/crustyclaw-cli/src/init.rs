@@ -0,0 +1,334 @@
+//! Starter configuration — `crustyclaw init`.
+//!
+//! Asks for the isolation backend (offering those available on this host),
+//! the LLM provider, and the auth mode, then writes a commented
+//! `crustyclaw.toml` with a deny-by-default policy that gives the operator
+//! the `admin` role. `--defaults` skips the questions. The file is created
+//! with mode 0600 and never holds an API key; the provider's key is read
+//! from `CRUSTYCLAW_LLM_API_KEY`.
+
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+use anyhow::Result;
+use crustyclaw_core::isolation::{BackendPreference, select_backend};
+
+/// Backends offered, strongest first.
+const BACKENDS: [BackendPreference; 6] = [
+    BackendPreference::Firecracker,
+    BackendPreference::AppleVz,
+    BackendPreference::Docker,
+    BackendPreference::Podman,
+    BackendPreference::LinuxNamespace,
+    BackendPreference::Noop,
+];
+
+const PROVIDERS: [&str; 3] = ["anthropic", "openai", "llamacpp"];
+
+const AUTH_MODES: [&str; 2] = ["local", "token"];
+
+/// The answers a starter config is generated from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InitChoices {
+    /// `isolation.backend`.
+    pub backend: String,
+    /// `llm.provider`.
+    pub provider: String,
+    /// `llm.model`.
+    pub model: String,
+    /// `auth.mode`.
+    pub auth_mode: String,
+    /// Identity mapped to the `admin` role, if any.
+    pub admin: Option<String>,
+}
+
+impl InitChoices {
+    /// The choices `--defaults` makes: the backend `auto` resolves to on
+    /// this host, Anthropic, local auth, and the current user as admin.
+    pub fn defaults() -> Self {
+        let identity = crustyclaw_core::auth::Session::new().authenticate_local();
+        Self {
+            backend: select_backend(&BackendPreference::Auto).name().to_string(),
+            provider: PROVIDERS[0].to_string(),
+            model: default_model(PROVIDERS[0]).to_string(),
+            auth_mode: AUTH_MODES[0].to_string(),
+            admin: Some(identity.identity().to_string()),
+        }
+    }
+
+    /// Ask for each choice on `input`, offering `self` as the defaults.
+    pub fn prompt(mut self, input: &mut dyn BufRead, out: &mut dyn Write) -> Result<Self> {
+        let available: Vec<String> = BACKENDS
+            .iter()
+            .filter(|pref| select_backend(pref).available())
+            .map(ToString::to_string)
+            .collect();
+        let available: Vec<&str> = available.iter().map(String::as_str).collect();
+        writeln!(
+            out,
+            "Isolation backends available here: {}",
+            available.join(", ")
+        )?;
+        self.backend = ask(input, out, "Isolation backend", &self.backend, &available)?;
+
+        let provider = ask(input, out, "LLM provider", &self.provider, &PROVIDERS)?;
+        if provider != self.provider {
+            self.model = default_model(&provider).to_string();
+        }
+        self.provider = provider;
+        self.model = ask(input, out, "Model", &self.model, &[])?;
+
+        self.auth_mode = ask(input, out, "Auth mode", &self.auth_mode, &AUTH_MODES)?;
+        if let Some(admin) = &self.admin {
+            let grant = ask(
+                input,
+                out,
+                &format!("Make {admin} an admin"),
+                "yes",
+                &["yes", "no"],
+            )?;
+            if grant == "no" {
+                self.admin = None;
+            }
+        }
+        Ok(self)
+    }
+}
+
+/// Ask `question` until the answer is empty (`default`) or, if `options`
+/// is not empty, one of them.
+fn ask(
+    input: &mut dyn BufRead,
+    out: &mut dyn Write,
+    question: &str,
+    default: &str,
+    options: &[&str],
+) -> Result<String> {
+    loop {
+        match options {
+            [] => write!(out, "{question} [{default}]: ")?,
+            _ => write!(out, "{question} ({}) [{default}]: ", options.join("/"))?,
+        }
+        out.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            anyhow::bail!("input ended before {question:?} was answered");
+        }
+        let answer = line.trim();
+        if answer.is_empty() {
+            return Ok(default.to_string());
+        }
+        if options.is_empty() || options.contains(&answer) {
+            return Ok(answer.to_string());
+        }
+        writeln!(out, "  choose one of: {}", options.join(", "))?;
+    }
+}
+
+fn default_model(provider: &str) -> &'static str {
+    match provider {
+        "openai" => "gpt-4o",
+        "llamacpp" => "local",
+        _ => "claude-sonnet-4-20250514",
+    }
+}
+
+/// The commented starter config for `choices`.
+pub fn render(choices: &InitChoices) -> String {
+    let mut toml = format!(
+        r#"# CrustyClaw configuration, generated by `crustyclaw init`.
+# Full reference: docs/configuration.md. Check it with `crustyclaw doctor`.
+
+[daemon]
+listen_addr = "127.0.0.1"
+listen_port = 9100
+
+[logging]
+level = "info"
+
+[isolation]
+# Where skills run: "auto", "firecracker", "apple-vz", "docker", "podman",
+# "linux-ns", or "noop" (no isolation; development only).
+backend = "{backend}"
+# Sandboxes get no network unless a skill asks for it.
+default_network = "none"
+
+[llm]
+provider = "{provider}"
+model = "{model}"
+# The API key is read from the CRUSTYCLAW_LLM_API_KEY environment variable;
+# keep it out of this file.
+"#,
+        backend = choices.backend,
+        provider = choices.provider,
+        model = choices.model,
+    );
+    if choices.provider == "llamacpp" {
+        toml.push_str("# llama-server's address.\nbase_url = \"http://127.0.0.1:8080\"\n");
+    }
+
+    toml.push_str(&format!(
+        r#"
+[auth]
+# "local" trusts the OS identity of the calling process; "token" requires
+# a session token from `crustyclaw login`.
+mode = "{}"
+"#,
+        choices.auth_mode
+    ));
+    if let Some(admin) = &choices.admin {
+        toml.push_str(&format!(
+            "\n# Identities granted a policy role in addition to their default one.\n\
+             [auth.role_map]\n{admin:?} = \"admin\"\n"
+        ));
+    }
+
+    toml.push_str(
+        r#"
+[policy]
+# Anything no rule allows is denied.
+default_effect = "deny"
+
+[[policy.rules]]
+id = "admin-all"
+role = "admin"
+action = "*"
+resource = "*"
+effect = "allow"
+reason = "administrators manage the daemon"
+
+[[policy.rules]]
+id = "read-status"
+role = "*"
+action = "read"
+resource = "status"
+effect = "allow"
+reason = "anyone may see whether the daemon is running"
+
+# Checked by `crustyclaw config lint` and `crustyclaw doctor`.
+[[policy.tests]]
+role = "admin"
+action = "write"
+resource = "config"
+expect = "allow"
+
+[[policy.tests]]
+role = "user"
+action = "read"
+resource = "secrets"
+expect = "deny"
+"#,
+    );
+    toml
+}
+
+/// Write `contents` to `path` with mode 0600, refusing to replace an
+/// existing file unless `force`.
+pub fn write(path: &Path, contents: &str, force: bool) -> Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true);
+    if force {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
+    }
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::AlreadyExists => anyhow::anyhow!(
+            "{} already exists; use --force to replace it",
+            path.display()
+        ),
+        _ => anyhow::anyhow!("cannot create {}: {e}", path.display()),
+    })?;
+    // An existing file keeps its mode when replaced.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(contents.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crustyclaw_config::AppConfig;
+
+    fn choices() -> InitChoices {
+        InitChoices {
+            backend: "noop".to_string(),
+            provider: "anthropic".to_string(),
+            model: "claude-sonnet-4-20250514".to_string(),
+            auth_mode: "local".to_string(),
+            admin: Some("alice".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_rendered_config_is_valid_and_denies_by_default() {
+        let config = AppConfig::parse(&render(&choices())).unwrap();
+        assert_eq!(config.isolation.backend, "noop");
+        assert_eq!(config.auth.role_map["alice"], "admin");
+        assert!(config.llm.api_key.is_empty());
+        assert!(config.run_policy_tests().is_empty());
+
+        let mut engine = config.build_policy_engine();
+        assert!(engine.is_allowed("admin", "write", "config"));
+        assert!(engine.is_allowed("alice", "read", "status"));
+        assert!(!engine.is_allowed("alice", "write", "skills"));
+
+        let llamacpp = InitChoices {
+            provider: "llamacpp".to_string(),
+            auth_mode: "token".to_string(),
+            admin: None,
+            ..choices()
+        };
+        let config = AppConfig::parse(&render(&llamacpp)).unwrap();
+        assert_eq!(
+            config.llm.base_url.as_deref(),
+            Some("http://127.0.0.1:8080")
+        );
+        assert!(config.auth.role_map.is_empty());
+    }
+
+    #[test]
+    fn test_prompt_keeps_defaults_and_reasks_invalid_answers() {
+        let mut input = std::io::Cursor::new("noop\nopenai\n\nkerberos\ntoken\nno\n");
+        let mut out = Vec::new();
+        let answers = choices().prompt(&mut input, &mut out).unwrap();
+        assert_eq!(answers.backend, "noop");
+        assert_eq!(answers.provider, "openai");
+        // Switching provider suggests that provider's model.
+        assert_eq!(answers.model, "gpt-4o");
+        assert_eq!(answers.auth_mode, "token");
+        assert_eq!(answers.admin, None);
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("choose one of: local, token"), "{out}");
+
+        let mut input = std::io::Cursor::new("");
+        assert!(choices().prompt(&mut input, &mut Vec::new()).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_is_private_and_does_not_overwrite() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("crustyclaw.toml");
+        write(&path, "a = 1\n", false).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let err = write(&path, "a = 2\n", false).unwrap_err();
+        assert!(err.to_string().contains("--force"));
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        write(&path, "a = 2\n", true).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a = 2\n");
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
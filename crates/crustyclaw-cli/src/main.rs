@@ -11,12 +11,25 @@
 //! process (Unix UID/username). No password, token, or user interaction is
 //! required. The `whoami` subcommand shows the resolved identity and roles.
 
+use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use anyhow::Result;
-use clap::{Parser, Subcommand};
-use tracing::info;
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::{ArgValueCandidates, CompleteEnv};
+use crustyclaw_config::layers::{self, Override};
+use tracing::{info, warn};
 use tracing_subscriber::EnvFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+mod chat;
+mod completions;
+mod init;
+mod output;
+
+use output::OutputFormat;
 
 /// CrustyClaw — a secure, Rust-based AI agent daemon.
 #[derive(Parser)]
@@ -29,22 +42,61 @@ use tracing_subscriber::EnvFilter;
         managed via this CLI or the interactive TUI."
 )]
 struct Cli {
-    /// Path to configuration file.
-    #[arg(short, long, default_value = "crustyclaw.toml")]
-    config: PathBuf,
+    #[command(flatten)]
+    config: ConfigSource,
 
     /// Increase log verbosity (-v, -vv, -vvv).
     #[arg(short, long, action = clap::ArgAction::Count)]
     verbose: u8,
 
+    /// Output format: `table` for people, `json` or `toml` for scripts
+    /// (status, doctor, policy, secrets, isolation, whoami, plugins, version).
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Table)]
+    format: OutputFormat,
+
+    /// Shorthand for `--format json`.
+    #[arg(long, global = true, conflicts_with = "format")]
+    json: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+/// Where the configuration comes from: the file plus `--set` overrides.
+///
+/// `CRUSTYCLAW__*` environment variables are applied between the two.
+#[derive(clap::Args)]
+struct ConfigSource {
+    /// Path to configuration file.
+    #[arg(short = 'c', long = "config", default_value = "crustyclaw.toml")]
+    path: PathBuf,
+
+    /// Override a config key, e.g. `--set daemon.listen_port=8080`
+    /// (repeatable). Takes precedence over `CRUSTYCLAW__*` environment
+    /// variables and the config file.
+    #[arg(long = "set", value_name = "KEY=VALUE", value_parser = parse_config_override)]
+    set: Vec<Override>,
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Start the CrustyClaw daemon.
-    Start,
+    ///
+    /// Runs preflight checks first (socket dir, secrets staging dir,
+    /// isolation backend, secrets, LLM reachability) and exits with a
+    /// consolidated report if any fail. Refuses to start while another
+    /// daemon holds the PID file (`daemon.pid_file`).
+    Start {
+        /// Start even if preflight checks fail.
+        #[arg(long)]
+        skip_preflight: bool,
+
+        /// Run in the background, logging to `<state_dir>/daemon.log`.
+        /// Returns once the daemon has passed preflight and written its
+        /// PID file.
+        #[arg(long)]
+        daemonize: bool,
+    },
 
     /// Stop a running CrustyClaw daemon.
     Stop,
@@ -52,47 +104,418 @@ enum Commands {
     /// Show daemon status.
     Status,
 
+    /// Change the running daemon's log level until it restarts.
+    LogLevel {
+        /// Filter in `RUST_LOG` syntax, e.g. `debug` or
+        /// `info,crustyclaw_core::skill=trace`.
+        level: String,
+    },
+
+    /// Generate a commented starter config at the `--config` path.
+    ///
+    /// Asks for the isolation backend, LLM provider, and auth mode, and
+    /// writes a deny-by-default policy. The file is created with mode 0600.
+    Init {
+        /// Don't ask; use the backend probed on this host, Anthropic, and
+        /// local auth.
+        #[arg(long)]
+        defaults: bool,
+
+        /// Replace an existing file.
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Diagnose the environment: config, socket, isolation backends,
+    /// secrets, LLM reachability, and Signal linking.
+    ///
+    /// Exits 0 if every check passes, 1 if any fails, and 2 if there are
+    /// only warnings.
+    Doctor,
+
     /// Validate and display configuration.
     Config {
         /// Show the resolved configuration as TOML.
         #[arg(long)]
         show: bool,
+
+        /// With `--show`, list every key with the layer it came from
+        /// (default, file, env, or flag) instead of TOML.
+        #[arg(long)]
+        origin: bool,
+
+        #[command(subcommand)]
+        command: Option<ConfigCommands>,
     },
 
-    /// Show build version, git hash, and build profile.
+    /// Show build version, git hash, profile, target, compiler, features,
+    /// and IPC API version.
     Version,
 
     /// Evaluate a policy access check.
+    #[command(subcommand_negates_reqs = true)]
     Policy {
         /// Role to check (e.g. "admin", "user").
-        #[arg(long)]
-        role: String,
+        #[arg(long, required = true)]
+        role: Option<String>,
         /// Action to check (e.g. "read", "write").
-        #[arg(long)]
-        action: String,
+        #[arg(long, required = true)]
+        action: Option<String>,
         /// Resource to check (e.g. "config", "secrets").
-        #[arg(long)]
-        resource: String,
+        #[arg(long, required = true)]
+        resource: Option<String>,
+
+        #[command(subcommand)]
+        command: Option<PolicyCommands>,
     },
 
     /// List registered plugins (from config).
     Plugins,
 
     /// Show isolation / sandbox configuration and backend status.
-    Isolation,
+    Isolation {
+        #[command(subcommand)]
+        command: Option<IsolationCommands>,
+    },
 
     /// Show current authentication identity, roles, and policy evaluation.
     ///
     /// Uses transparent local authentication — no password or token required.
     Whoami,
 
-    /// Show configured secrets (names and sources only, never values).
-    Secrets,
+    /// Mint a session token for `auth.mode = "token"`.
+    ///
+    /// Resolves your identity and roles like `whoami`, signs them with the
+    /// daemon key (`auth.token_key_path`), and saves the token to
+    /// `auth.token_path` for the CLI and TUI to send on each request.
+    Login {
+        /// Token lifetime in seconds (default and maximum: `auth.token_ttl_secs`).
+        #[arg(long)]
+        ttl: Option<u64>,
+    },
+
+    /// Show configured secrets (names and sources only, never values),
+    /// manage the encrypted keystore, or import secrets into the OS
+    /// keychain.
+    Secrets {
+        #[command(subcommand)]
+        command: Option<SecretsCommands>,
+    },
+
+    /// Run and inspect skills on the running daemon.
+    Skill {
+        #[command(subcommand)]
+        command: SkillCommands,
+    },
+
+    /// Transfer files to and from conversation workspaces.
+    Files {
+        #[command(subcommand)]
+        command: FilesCommands,
+    },
+
+    /// Show or forget a conversation's long-term memory.
+    Memory {
+        #[command(subcommand)]
+        command: MemoryCommands,
+    },
+
+    /// Review the agent's one-time tool trust elevation requests.
+    Elevation {
+        #[command(subcommand)]
+        command: ElevationCommands,
+    },
+
+    /// Chat with the daemon's agent in an interactive REPL.
+    ///
+    /// Replies stream in as the agent runs, with tools scoped to your
+    /// policy roles. Type `/tools`, `/reset`, `/save [path]`, or `/quit`.
+    Chat {
+        /// Continue an existing session instead of starting a new one.
+        #[arg(long)]
+        session: Option<String>,
+
+        /// Offer only this tool in the session (repeatable).
+        #[arg(long = "allow-tool", value_name = "NAME", add = ArgValueCandidates::new(completions::tool_names))]
+        allow_tools: Vec<String>,
+
+        /// Never offer this tool in the session (repeatable).
+        #[arg(long = "deny-tool", value_name = "NAME", add = ArgValueCandidates::new(completions::tool_names))]
+        deny_tools: Vec<String>,
+    },
+
+    /// Show per-role quota usage (LLM tokens per day, sandbox executions per hour).
+    Quotas,
+
+    /// Show LLM token usage and estimated cost (see `[llm.pricing]`).
+    Usage {
+        /// Number of UTC days to report, today included.
+        #[arg(long, default_value_t = 7)]
+        days: u32,
+
+        /// Group by `model`, `role`, `skill`, or `conversation`.
+        #[arg(long, default_value = "model", value_parser = ["model", "role", "skill", "conversation"])]
+        by: String,
+    },
+
+    /// List `[[schedules]]` and their recent runs, or run one now.
+    Schedule {
+        #[command(subcommand)]
+        command: ScheduleCommands,
+    },
+
+    /// Manage the Signal channel's account.
+    Signal {
+        #[command(subcommand)]
+        command: SignalCommands,
+    },
+
+    /// Print a shell completion script.
+    ///
+    /// The script completes skill and tool names by asking the running
+    /// daemon. Load it on shell startup, e.g. in `~/.bashrc`:
+    /// `source <(crustyclaw completions bash)`.
+    Completions {
+        /// Shell to complete in.
+        shell: clap_complete::Shell,
+
+        /// Print a self-contained script that never runs `crustyclaw`,
+        /// without skill or tool names.
+        #[arg(long = "static")]
+        static_script: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Validate the config and run its `[[policy.tests]]` table.
+    ///
+    /// Exits non-zero if validation fails or any policy test does not hold.
+    Lint,
+}
+
+#[derive(Subcommand)]
+enum PolicyCommands {
+    /// Check a file of expected decisions against the policy.
+    ///
+    /// Exits non-zero if any check does not hold.
+    Simulate {
+        /// TOML file of `[[checks]]` with role, action, resource, and
+        /// expect ("allow" or "deny").
+        #[arg(long)]
+        matrix: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum SecretsCommands {
+    /// Store a secret in the keystore, creating the keystore and its key
+    /// on first use.
+    ///
+    /// The value is read from stdin (one line), or from `--from-file`,
+    /// never from the command line.
+    Set {
+        /// Secret name, as used by `source = "keystore"` entries.
+        name: String,
+        /// Read the value from this file instead of stdin.
+        #[arg(long)]
+        from_file: Option<PathBuf>,
+    },
+    /// Print a secret from the keystore.
+    Get {
+        /// Secret name.
+        name: String,
+    },
+    /// Remove a secret from the keystore.
+    Rm {
+        /// Secret name.
+        name: String,
+    },
+    /// Copy env-sourced secrets into the OS keychain, for entries to read
+    /// with `source = "keychain"`.
+    ///
+    /// Reads each entry's environment variable in this shell and stores it
+    /// under the entry's keychain service and account. The config is not
+    /// changed; the edits to make are printed.
+    Import {
+        /// Entries to import (default: every entry with `source = "env"`).
+        names: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum IsolationCommands {
+    /// Pull a sandbox image ahead of its first run.
+    ///
+    /// Pulls with the configured engine (podman when `isolation.backend`
+    /// is "podman", docker otherwise), even if the image is present, and
+    /// prints the reference pinning it by digest for use as
+    /// `isolation.docker_image`. Does not need a running daemon.
+    PullImage {
+        /// Image to pull (default: `isolation.docker_image`).
+        image: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum SkillCommands {
+    /// Execute a registered skill and print its output.
+    ///
+    /// The skill's stdout and stderr are written to this process's stdout
+    /// and stderr, and the CLI exits with the skill's exit code.
+    Run {
+        /// Name of the skill to run.
+        #[arg(add = ArgValueCandidates::new(completions::skill_names))]
+        name: String,
+        /// Skill argument as `key=value` (repeatable). Values that parse as
+        /// JSON (numbers, booleans, arrays, objects) are passed typed;
+        /// anything else is passed as a string.
+        #[arg(long = "arg", value_name = "KEY=VALUE", value_parser = parse_skill_arg)]
+        args: Vec<(String, serde_json::Value)>,
+        /// Trust tier to run under: "trusted", "internal", "untrusted", "llm-generated".
+        #[arg(long)]
+        trust: Option<String>,
+        /// Print output lines as the skill produces them rather than once
+        /// it finishes. Interrupting the CLI cancels the run.
+        #[arg(short, long)]
+        follow: bool,
+    },
+    /// Build the sandbox image declared by a skill's manifest.
+    ///
+    /// Builds the manifest's `[image]` (base plus declared packages) with
+    /// docker or buildah, tags it with the skill version and content hash,
+    /// and records the image ID in `skills.lock` in `daemon.skills_dir`.
+    /// Does not need a running daemon.
+    BuildImage {
+        /// Name of the skill to build.
+        #[arg(add = ArgValueCandidates::new(completions::skill_names))]
+        name: String,
+        /// Builder to use: "docker" or "buildah" (default: the first available).
+        #[arg(long)]
+        builder: Option<String>,
+    },
+    /// Install a `.claw` skill package into `daemon.skills_dir`.
+    ///
+    /// Verifies the package signature against `[security.trusted_keys]`,
+    /// unpacks the skill (replacing an earlier version), and signals a
+    /// running daemon to register it.
+    Install {
+        /// Package file to install.
+        package: PathBuf,
+        /// Development mode: also install unsigned packages. Not allowed
+        /// with `security.strict`.
+        #[arg(long)]
+        dev: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum FilesCommands {
+    /// Upload a file into a conversation's workspace.
+    Put {
+        /// Conversation ID.
+        conversation: String,
+        /// Local file to upload.
+        path: PathBuf,
+        /// Name to store the file under (defaults to the local file name).
+        #[arg(long)]
+        name: Option<String>,
+        /// Content type to declare (inferred from the name if omitted).
+        #[arg(long)]
+        content_type: Option<String>,
+    },
+    /// Download a file from a conversation's workspace.
+    Get {
+        /// Conversation ID.
+        conversation: String,
+        /// Name of the file in the workspace.
+        name: String,
+        /// Where to write the file (defaults to the file name; "-" for stdout).
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// List the files in a conversation's workspace.
+    List {
+        /// Conversation ID.
+        conversation: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum MemoryCommands {
+    /// Show the summary and facts remembered for a conversation.
+    Show {
+        /// Conversation ID.
+        conversation: String,
+    },
+    /// Forget everything remembered for a conversation.
+    Clear {
+        /// Conversation ID.
+        conversation: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ElevationCommands {
+    /// List elevation requests and their status.
+    List,
+    /// Approve a pending request; the exact call it names may then run once.
+    Approve {
+        /// Request ID.
+        id: u64,
+    },
+    /// Deny a pending request.
+    Deny {
+        /// Request ID.
+        id: u64,
+        /// Reason passed back to the agent and kept in the audit log.
+        #[arg(long)]
+        reason: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ScheduleCommands {
+    /// List schedules with their next run and last result.
+    List {
+        /// Also print each schedule's run history.
+        #[arg(long)]
+        history: bool,
+    },
+    /// Start a schedule now, even if it is disabled. Fails if it is running.
+    RunNow {
+        /// Schedule name.
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum SignalCommands {
+    /// Link CrustyClaw as a secondary device of an existing Signal account.
+    ///
+    /// Prints a QR code to scan from the primary device (Settings → Linked
+    /// devices) and waits for it to approve. The session keys are stored in
+    /// `signal.data_dir`.
+    Link {
+        /// Name shown for this device in the primary device's list.
+        #[arg(long, default_value = "crustyclaw")]
+        device_name: String,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    // Answer the shell and exit when run by a completion script.
+    CompleteEnv::with_factory(Cli::command)
+        .var(completions::COMPLETE_VAR)
+        .complete();
+
     let cli = Cli::parse();
+    let format = if cli.json {
+        OutputFormat::Json
+    } else {
+        cli.format
+    };
 
     // Set up tracing subscriber with verbosity level
     let filter = match cli.verbose {
@@ -101,27 +524,123 @@ async fn main() -> Result<()> {
         _ => "trace",
     };
 
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(filter)),
+    // Also capture events in memory so the daemon can serve /logs/stream
+    let log_collector = crustyclaw_core::LogCollector::new(crustyclaw_core::DEFAULT_LOG_CAPACITY);
+    let log_reader = log_collector.reader();
+
+    // Keep stdout for the document when printing JSON or TOML.
+    let (stdout_logs, stderr_logs) = if format.is_structured() {
+        (
+            None,
+            Some(tracing_subscriber::fmt::layer().with_writer(std::io::stderr)),
         )
+    } else {
+        (Some(tracing_subscriber::fmt::layer()), None)
+    };
+    // `-v` and `RUST_LOG` take precedence over `logging.level`.
+    let level_from_flags = cli.verbose > 0 || std::env::var_os(EnvFilter::DEFAULT_ENV).is_some();
+    let (log_control, level_layer, file_layer) = crustyclaw_core::LogControl::new(
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(filter)),
+    );
+    tracing_subscriber::registry()
+        .with(level_layer)
+        .with(file_layer)
+        .with(stdout_logs)
+        .with(stderr_logs)
+        .with(log_collector)
         .init();
 
     match cli.command {
-        Commands::Start => cmd_start(&cli.config).await?,
+        Commands::Start {
+            daemonize: true, ..
+        } => cmd_start_detached(&cli.config).await?,
+        Commands::Start {
+            skip_preflight,
+            daemonize: false,
+        } => {
+            cmd_start(
+                &cli.config,
+                log_reader,
+                log_control,
+                !level_from_flags,
+                skip_preflight,
+            )
+            .await?
+        }
         Commands::Stop => cmd_stop(&cli.config).await?,
-        Commands::Status => cmd_status(&cli.config).await?,
-        Commands::Config { show } => cmd_config(&cli.config, show).await?,
-        Commands::Version => cmd_version(),
+        Commands::Status => cmd_status(&cli.config, format).await?,
+        Commands::LogLevel { level } => cmd_log_level(&cli.config, &level).await?,
+        Commands::Doctor => cmd_doctor(&cli.config, format).await?,
+        Commands::Init { defaults, force } => cmd_init(&cli.config, defaults, force)?,
+        Commands::Config {
+            command: Some(ConfigCommands::Lint),
+            ..
+        } => cmd_config_lint(&cli.config).await?,
+        Commands::Config {
+            show,
+            origin,
+            command: None,
+        } => cmd_config(&cli.config, show, origin).await?,
+        Commands::Version => cmd_version(format)?,
+        Commands::Policy {
+            command: Some(PolicyCommands::Simulate { matrix }),
+            ..
+        } => cmd_policy_simulate(&cli.config, &matrix, format).await?,
         Commands::Policy {
-            role,
-            action,
-            resource,
-        } => cmd_policy(&cli.config, &role, &action, &resource).await?,
-        Commands::Plugins => cmd_plugins(&cli.config).await?,
-        Commands::Isolation => cmd_isolation(&cli.config).await?,
-        Commands::Whoami => cmd_whoami(&cli.config).await?,
-        Commands::Secrets => cmd_secrets(&cli.config).await?,
+            role: Some(role),
+            action: Some(action),
+            resource: Some(resource),
+            command: None,
+        } => cmd_policy(&cli.config, &role, &action, &resource, format).await?,
+        Commands::Policy { .. } => unreachable!("clap requires role, action, and resource"),
+        Commands::Plugins => cmd_plugins(&cli.config, format).await?,
+        Commands::Isolation { command: None } => cmd_isolation(&cli.config, format).await?,
+        Commands::Isolation {
+            command: Some(IsolationCommands::PullImage { image }),
+        } => cmd_isolation_pull_image(&cli.config, image.as_deref()).await?,
+        Commands::Whoami => cmd_whoami(&cli.config, format).await?,
+        Commands::Login { ttl } => cmd_login(&cli.config, ttl).await?,
+        Commands::Secrets { command: None } => cmd_secrets(&cli.config, format).await?,
+        Commands::Secrets {
+            command: Some(command),
+        } => cmd_secrets_keystore(&cli.config, command).await?,
+        Commands::Skill {
+            command:
+                SkillCommands::Run {
+                    name,
+                    args,
+                    trust,
+                    follow,
+                },
+        } => cmd_skill_run(&cli.config, &name, args, trust.as_deref(), follow).await?,
+        Commands::Skill {
+            command: SkillCommands::BuildImage { name, builder },
+        } => cmd_skill_build_image(&cli.config, &name, builder.as_deref()).await?,
+        Commands::Skill {
+            command: SkillCommands::Install { package, dev },
+        } => cmd_skill_install(&cli.config, &package, dev).await?,
+        Commands::Files { command } => cmd_files(&cli.config, command).await?,
+        Commands::Memory { command } => cmd_memory(&cli.config, command).await?,
+        Commands::Elevation { command } => cmd_elevation(&cli.config, command).await?,
+        Commands::Chat {
+            session,
+            allow_tools,
+            deny_tools,
+        } => {
+            let allowed = (!allow_tools.is_empty()).then_some(allow_tools);
+            let tools = crustyclaw_core::chat::ToolFilter::new(allowed, deny_tools);
+            cmd_chat(&cli.config, session, tools).await?
+        }
+        Commands::Quotas => cmd_quotas(&cli.config).await?,
+        Commands::Usage { days, by } => cmd_usage(&cli.config, days, &by).await?,
+        Commands::Schedule { command } => cmd_schedule(&cli.config, command).await?,
+        Commands::Signal {
+            command: SignalCommands::Link { device_name },
+        } => cmd_signal_link(&cli.config, &device_name).await?,
+        Commands::Completions {
+            shell,
+            static_script,
+        } => completions::write(shell, !static_script, &mut std::io::stdout())?,
     }
 
     Ok(())
@@ -158,8 +677,17 @@ fn transparent_auth(
     }
 }
 
-async fn cmd_start(config_path: &Path) -> Result<()> {
-    let config = load_config(config_path).await?;
+async fn cmd_start(
+    source: &ConfigSource,
+    log_reader: crustyclaw_core::LogReader,
+    log_control: crustyclaw_core::LogControl,
+    config_level: bool,
+    skip_preflight: bool,
+) -> Result<()> {
+    let config = load_config(source).await?;
+    if config_level && let Err(e) = log_control.set_level(&config.logging.level) {
+        warn!("Ignoring logging.level: {e}");
+    }
 
     // Transparent auth — authenticate the operator starting the daemon
     let session = transparent_auth(&config);
@@ -171,168 +699,817 @@ async fn cmd_start(config_path: &Path) -> Result<()> {
 
     info!("Starting CrustyClaw daemon");
 
-    let daemon = crustyclaw_core::Daemon::with_config_path(config, config_path.to_path_buf());
+    let signal = config.signal.clone();
+    let matrix = config.matrix.clone();
+    let daemon = crustyclaw_core::Daemon::with_config_path(config, source.path.clone())
+        .with_config_overrides(source.set.clone())
+        .with_log_reader(log_reader)
+        .with_log_control(log_control)
+        .with_skip_preflight(skip_preflight);
+    let signal_handle = if signal.enabled {
+        start_signal(&signal, &daemon)
+    } else {
+        None
+    };
+    let matrix_handle = matrix.enabled.then(|| start_matrix(&matrix, &daemon));
+
     daemon.run().await.map_err(|e| anyhow::anyhow!(e))?;
 
+    if let Some(handle) = signal_handle {
+        let _ = handle.await;
+    }
+    if let Some(handle) = matrix_handle {
+        let _ = handle.await;
+    }
     Ok(())
 }
 
-async fn cmd_stop(config_path: &Path) -> Result<()> {
-    let config = load_config(config_path).await?;
-    let client = ipc_client(&config);
-
-    if !client.daemon_available() {
-        eprintln!("Daemon is not running (no socket found).");
-        std::process::exit(1);
-    }
+/// Log file of a daemon started with `--daemonize`, under `state_dir`.
+const DAEMON_LOG: &str = "daemon.log";
 
-    let resp = client
-        .stop()
-        .await
-        .map_err(|e| anyhow::anyhow!("Failed to stop daemon: {e}"))?;
-    println!("Daemon: {}", resp.message);
-    Ok(())
-}
+/// How long `start --daemonize` waits for the daemon to write its PID file.
+const DETACH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
 
-async fn cmd_status(config_path: &Path) -> Result<()> {
-    let config = load_config(config_path).await?;
-    let client = ipc_client(&config);
+/// Start the daemon in the background with the same arguments, and wait
+/// until it has written its PID file or exited.
+async fn cmd_start_detached(source: &ConfigSource) -> Result<()> {
+    use crustyclaw_core::daemonize::{self, PidState};
 
-    if !client.daemon_available() {
-        println!("Daemon: not running");
-        return Ok(());
+    let config = load_config(source).await?;
+    let pid_path = daemonize::pid_file_path(&config);
+    if let PidState::Running(pid) = daemonize::inspect(&pid_path) {
+        anyhow::bail!(
+            "crustyclaw is already running (PID {pid}, PID file {})",
+            pid_path.display()
+        );
     }
 
-    match client.status().await {
-        Ok(status) => {
-            println!("Daemon: running (PID {})", status.pid);
-            println!("  Version:    {} ({})", status.version, status.git_hash);
-            println!("  Uptime:     {}s", status.uptime_secs);
-            println!(
-                "  Listen:     {}:{}",
-                status.listen_addr, status.listen_port
+    let state_dir = Path::new(&config.daemon.state_dir);
+    std::fs::create_dir_all(state_dir)?;
+    let log_path = state_dir.join(DAEMON_LOG);
+    let log = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)
+        .map_err(|e| anyhow::anyhow!("cannot open {}: {e}", log_path.display()))?;
+    let args = std::env::args_os()
+        .skip(1)
+        .filter(|arg| arg != "--daemonize");
+    let mut child = daemonize::detach(
+        std::process::Command::new(std::env::current_exe()?)
+            .args(args)
+            .env("NO_COLOR", "1")
+            .stdout(log.try_clone()?)
+            .stderr(log),
+    )?;
+    let pid = child.id();
+
+    let deadline = tokio::time::Instant::now() + DETACH_TIMEOUT;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            anyhow::bail!(
+                "daemon exited during startup ({status}); see {}",
+                log_path.display()
             );
+        }
+        if daemonize::inspect(&pid_path) == PidState::Running(pid) {
+            println!("Daemon started (PID {pid})");
+            println!("  PID file: {}", pid_path.display());
+            println!("  Log:      {}", log_path.display());
+            return Ok(());
+        }
+        if tokio::time::Instant::now() >= deadline {
             println!(
-                "  Signal:     {}",
-                if status.signal_enabled {
-                    "enabled"
-                } else {
-                    "disabled"
-                }
+                "Daemon is still starting (PID {pid}); see {}",
+                log_path.display()
             );
-            println!("  Log level:  {}", status.log_level);
-            println!("  Isolation:  {}", status.isolation_backend);
-            println!("  Skills:     {}", status.skills_count);
-            println!("  Plugins:    {}", status.plugins_count);
-        }
-        Err(e) => {
-            eprintln!("Failed to query daemon status: {e}");
-            std::process::exit(1);
+            return Ok(());
         }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
     }
-    Ok(())
 }
 
-async fn cmd_config(config_path: &Path, show: bool) -> Result<()> {
-    let config = load_config(config_path).await?;
-    if show {
-        let toml_str =
-            toml::to_string_pretty(&config).map_err(|e| anyhow::anyhow!("TOML error: {e}"))?;
-        println!("{toml_str}");
-    } else {
-        println!("Configuration at '{}' is valid.", config_path.display());
-        println!(
-            "  Daemon: {}:{}",
-            config.daemon.listen_addr, config.daemon.listen_port
-        );
-        println!(
-            "  Signal: {}",
-            if config.signal.enabled {
-                "enabled"
-            } else {
-                "disabled"
+/// Start the Signal channel on the daemon's message bus, under the
+/// daemon's supervisor so a crashed channel is restarted with a fresh
+/// `signal-cli` process.
+///
+/// Failures are reported as daemon warnings rather than aborting startup.
+fn start_signal(
+    signal: &crustyclaw_config::SignalConfig,
+    daemon: &crustyclaw_core::Daemon,
+) -> Option<tokio::task::JoinHandle<()>> {
+    use crustyclaw_core::health::ComponentStatus;
+    use crustyclaw_core::warnings::WarningKind;
+
+    let Some(account) = signal.account.clone() else {
+        let message = "signal.account is not set";
+        warn!("Signal channel unavailable: {message}");
+        daemon
+            .health()
+            .report("signal", ComponentStatus::Down, message);
+        daemon
+            .warnings()
+            .push(WarningKind::Unavailable, "signal", message);
+        return None;
+    };
+    let state_dir = Path::new(&daemon.config().daemon.state_dir);
+    let access = crustyclaw_core::access::ChannelAccess::signal(signal)
+        .with_audit_log(crustyclaw_core::storage::AuditLog::new(
+            daemon.storage().clone(),
+            crustyclaw_core::access::AUDIT_FILE,
+        ))
+        .with_quarantine(Arc::new(crustyclaw_core::access::QuarantineLog::new(
+            state_dir.join(crustyclaw_core::access::QUARANTINE_FILE),
+        )));
+    let channel = SignalChannel {
+        config: signal.clone(),
+        account,
+        bus: daemon.message_sender(),
+        shutdown: daemon.shutdown_sender(),
+        workspaces: daemon.workspaces().clone(),
+        attachments: daemon.attachments().clone(),
+        access: Arc::new(access),
+        responses: daemon.response_pipeline().clone(),
+        health: daemon.health().clone(),
+        warnings: daemon.warnings().clone(),
+    };
+    Some(
+        daemon
+            .supervisor()
+            .spawn("signal", move || channel.clone().run()),
+    )
+}
+
+/// Everything needed to (re)start the Signal channel.
+#[derive(Clone)]
+struct SignalChannel {
+    config: crustyclaw_config::SignalConfig,
+    account: String,
+    bus: tokio::sync::broadcast::Sender<crustyclaw_core::message::Envelope>,
+    shutdown: tokio::sync::broadcast::Sender<crustyclaw_core::daemon::ShutdownSignal>,
+    workspaces: Arc<crustyclaw_core::WorkspaceStore>,
+    attachments: Arc<crustyclaw_core::attachment::AttachmentSpool>,
+    access: Arc<crustyclaw_core::access::ChannelAccess>,
+    responses: Arc<crustyclaw_core::response::ResponsePipeline>,
+    health: Arc<crustyclaw_core::health::HealthRegistry>,
+    warnings: Arc<crustyclaw_core::WarningCollector>,
+}
+
+impl SignalChannel {
+    /// Connect to Signal and serve until shutdown or failure.
+    async fn run(self) -> Result<(), String> {
+        use crustyclaw_core::health::ComponentStatus;
+        use crustyclaw_core::warnings::WarningKind;
+        use crustyclaw_signal::{PresenceConfig, SignalAdapter, SignalCliTransport, SignalService};
+
+        let mut shutdown_rx = self.shutdown.subscribe();
+        let unavailable = |message: String| {
+            warn!("Signal channel unavailable: {message}");
+            self.health
+                .report("signal", ComponentStatus::Down, message.clone());
+            self.warnings
+                .push(WarningKind::Unavailable, "signal", message.clone());
+            message
+        };
+
+        let data_dir = Path::new(&self.config.data_dir);
+        let transport = SignalCliTransport::spawn(&self.config.cli_path, data_dir, &self.account)
+            .map_err(|e| unavailable(e.to_string()))?;
+        let adapter = SignalAdapter::new()
+            .with_transport(Arc::new(transport))
+            .restore(data_dir, self.account.clone())
+            .await
+            .map_err(|e| unavailable(e.to_string()))?
+            .verify()
+            .await
+            .map_err(|e| unavailable(e.to_string()))?;
+        info!(account = adapter.phone_number(), "Signal channel started");
+
+        let (service, handle) = SignalService::new(self.bus.clone(), Default::default());
+        let run = service
+            .with_adapter(adapter)
+            .with_workspaces(self.workspaces.clone())
+            .with_attachment_spool(self.attachments.clone())
+            .with_access(self.access.clone())
+            .with_response_pipeline(self.responses.clone())
+            .with_health(self.health.clone())
+            .with_presence(PresenceConfig {
+                read_receipts: self.config.read_receipts,
+                typing_indicators: self.config.typing_indicators,
+            })
+            .run();
+        tokio::pin!(run);
+        let result = tokio::select! {
+            result = &mut run => result,
+            _ = shutdown_rx.recv() => {
+                let _ = handle.shutdown().await;
+                run.await
             }
-        );
-        println!("  Log level: {}", config.logging.level);
-        println!("  Policy rules: {}", config.policy.rules.len());
-        println!("  Isolation backend: {}", config.isolation.backend);
-        println!("  Secrets: {} configured", config.secrets.entries.len());
-        println!("  Auth mode: {}", config.auth.mode);
+        };
+        result.map_err(|e| e.to_string())
     }
-    Ok(())
 }
 
-fn cmd_version() {
-    println!(
-        "CrustyClaw {}",
-        crustyclaw_core::build_info::version_string()
-    );
-    println!("  Version:  {}", crustyclaw_core::build_info::VERSION);
-    println!("  Git hash: {}", crustyclaw_core::build_info::GIT_HASH);
-    println!("  Profile:  {}", crustyclaw_core::build_info::BUILD_PROFILE);
+/// Start the Matrix channel on the daemon's message bus, under the
+/// daemon's supervisor so a stopped sync loop is restarted with a fresh
+/// client session.
+///
+/// Failures are reported as daemon warnings rather than aborting startup.
+fn start_matrix(
+    matrix: &crustyclaw_config::MatrixConfig,
+    daemon: &crustyclaw_core::Daemon,
+) -> tokio::task::JoinHandle<()> {
+    let channel = MatrixChannel {
+        config: matrix.clone(),
+        bus: daemon.message_sender(),
+        shutdown: daemon.shutdown_sender(),
+        secrets: daemon.secrets().clone(),
+        secrets_rx: daemon.secrets_watcher(),
+        responses: daemon.response_pipeline().clone(),
+        health: daemon.health().clone(),
+        warnings: daemon.warnings().clone(),
+    };
+    daemon
+        .supervisor()
+        .spawn("matrix", move || channel.clone().run())
 }
 
-async fn cmd_policy(config_path: &Path, role: &str, action: &str, resource: &str) -> Result<()> {
-    let config = load_config(config_path).await?;
-    let mut engine = config.build_policy_engine();
+/// Everything needed to (re)start the Matrix channel.
+#[derive(Clone)]
+struct MatrixChannel {
+    config: crustyclaw_config::MatrixConfig,
+    bus: tokio::sync::broadcast::Sender<crustyclaw_core::message::Envelope>,
+    shutdown: tokio::sync::broadcast::Sender<crustyclaw_core::daemon::ShutdownSignal>,
+    secrets: Arc<std::sync::RwLock<crustyclaw_core::secrets::SecretStore>>,
+    secrets_rx: tokio::sync::watch::Receiver<crustyclaw_core::daemon::SecretsRevision>,
+    responses: Arc<crustyclaw_core::response::ResponsePipeline>,
+    health: Arc<crustyclaw_core::health::HealthRegistry>,
+    warnings: Arc<crustyclaw_core::WarningCollector>,
+}
 
-    let decision = engine.evaluate(role, action, resource);
-    let symbol = match decision {
-        crustyclaw_config::policy::PolicyDecision::Allowed => "ALLOWED",
-        crustyclaw_config::policy::PolicyDecision::Denied => "DENIED",
-        crustyclaw_config::policy::PolicyDecision::NoMatch => "NO MATCH (default deny)",
-    };
+impl MatrixChannel {
+    /// Log in to the homeserver and serve until shutdown or failure.
+    ///
+    /// Fails once a secret the credentials came from is reloaded or
+    /// rotated, so the supervisor logs in again with the new value.
+    async fn run(self) -> Result<(), String> {
+        use crustyclaw_core::health::ComponentStatus;
+        use crustyclaw_core::warnings::WarningKind;
+        use crustyclaw_matrix::{MatrixCredentials, MatrixService, MatrixTransport, SdkTransport};
 
-    println!("Policy check: role={role} action={action} resource={resource}");
-    println!("  Result: {symbol}");
-    println!("  Total rules: {}", engine.rule_count());
+        let unavailable = |message: String| {
+            warn!("Matrix channel unavailable: {message}");
+            self.health
+                .report("matrix", ComponentStatus::Down, message.clone());
+            self.warnings
+                .push(WarningKind::Unavailable, "matrix", message.clone());
+            message
+        };
 
-    Ok(())
+        // Changes from here on apply to the credentials read below.
+        let mut secrets_rx = self.secrets_rx.clone();
+        secrets_rx.borrow_and_update();
+        let credentials = {
+            let store = self.secrets.read().unwrap_or_else(|e| e.into_inner());
+            MatrixCredentials::from_store(&self.config, &store)
+        }
+        .map_err(|e| unavailable(e.to_string()))?;
+        let transport = SdkTransport::connect(&self.config, &credentials)
+            .await
+            .map_err(|e| unavailable(e.to_string()))?;
+        drop(credentials);
+        info!(user = transport.user_id(), "Matrix channel started");
+
+        let service = MatrixService::new(self.bus.clone(), &self.config)
+            .with_transport(Arc::new(transport))
+            .with_response_pipeline(self.responses.clone())
+            .with_health(self.health.clone())
+            .run(self.shutdown.subscribe());
+        let names = MatrixCredentials::secret_names(&self.config);
+        tokio::select! {
+            result = service => result.map_err(|e| e.to_string()),
+            () = crustyclaw_core::daemon::secrets_changed(&mut secrets_rx, &names) => {
+                info!("Matrix credentials changed; logging in again");
+                Err("credentials changed".to_string())
+            }
+        }
+    }
 }
 
-async fn cmd_plugins(config_path: &Path) -> Result<()> {
-    let config = load_config(config_path).await?;
-    let client = ipc_client(&config);
+async fn cmd_log_level(source: &ConfigSource, level: &str) -> Result<()> {
+    let config = load_config(source).await?;
+    let client = ipc_client(&config)?;
 
     if !client.daemon_available() {
-        println!("No plugins registered (daemon is not running).");
-        println!("  Plugins are discovered at daemon startup.");
-        return Ok(());
+        eprintln!("Daemon is not running (no socket found).");
+        std::process::exit(1);
     }
 
-    match client.plugins().await {
-        Ok(resp) => {
-            if resp.plugins.is_empty() {
-                println!("No plugins registered.");
-            } else {
-                println!("Registered plugins:");
-                for p in &resp.plugins {
-                    println!("  {} v{} — {}", p.name, p.version, p.description);
-                }
-            }
-        }
-        Err(e) => {
-            eprintln!("Failed to query plugins: {e}");
-        }
-    }
+    let changed = client
+        .set_log_level(level)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to set log level: {e}"))?;
+    println!("Log level: {} (was {})", changed.level, changed.previous);
+    println!("  Restarting the daemon restores logging.level.");
     Ok(())
 }
 
-async fn cmd_isolation(config_path: &Path) -> Result<()> {
-    let config = load_config(config_path).await?;
-    let iso = &config.isolation;
+async fn cmd_stop(source: &ConfigSource) -> Result<()> {
+    let config = load_config(source).await?;
+    let client = ipc_client(&config)?;
 
-    // Select backend and probe availability
-    let pref = match iso.backend.as_str() {
-        "docker" => crustyclaw_core::isolation::BackendPreference::Docker,
-        "firecracker" => crustyclaw_core::isolation::BackendPreference::Firecracker,
-        "apple-vz" => crustyclaw_core::isolation::BackendPreference::AppleVz,
-        "linux-ns" => crustyclaw_core::isolation::BackendPreference::LinuxNamespace,
-        "noop" => crustyclaw_core::isolation::BackendPreference::Noop,
-        _ => crustyclaw_core::isolation::BackendPreference::Auto,
+    if !client.daemon_available() {
+        use crustyclaw_core::daemonize::{self, PidState};
+
+        let pid_path = daemonize::pid_file_path(&config);
+        match daemonize::inspect(&pid_path) {
+            PidState::Missing => eprintln!("Daemon is not running (no socket found)."),
+            PidState::Stale(pid) => {
+                std::fs::remove_file(&pid_path).ok();
+                eprintln!(
+                    "Daemon is not running; removed stale PID file {}{}.",
+                    pid_path.display(),
+                    pid.map(|pid| format!(" (PID {pid})")).unwrap_or_default()
+                );
+            }
+            // Starting up, or its socket was removed: ask it to stop.
+            PidState::Running(pid) => {
+                daemonize::terminate(pid)
+                    .map_err(|e| anyhow::anyhow!("Failed to signal PID {pid}: {e}"))?;
+                println!("Daemon: sent SIGTERM to PID {pid} (no socket found)");
+                return Ok(());
+            }
+        }
+        std::process::exit(1);
+    }
+
+    let resp = client
+        .stop()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to stop daemon: {e}"))?;
+    println!("Daemon: {}", resp.message);
+    Ok(())
+}
+
+async fn cmd_status(source: &ConfigSource, format: OutputFormat) -> Result<()> {
+    let config = load_config(source).await?;
+    let client = ipc_client(&config)?;
+
+    if !client.daemon_available() {
+        use crustyclaw_core::daemonize::{self, PidState};
+
+        let pid_path = daemonize::pid_file_path(&config);
+        let state = daemonize::inspect(&pid_path);
+        let stopped = output::StatusOutput {
+            running: false,
+            daemon: None,
+            tasks: None,
+            pid_file: (state != PidState::Missing).then(|| output::PidFileInfo {
+                path: pid_path.display().to_string(),
+                pid: match state {
+                    PidState::Running(pid) => Some(pid),
+                    PidState::Stale(pid) => pid,
+                    PidState::Missing => None,
+                },
+                stale: matches!(state, PidState::Stale(_)),
+            }),
+        };
+        if !format.print(&stopped)? {
+            match state {
+                PidState::Missing => println!("Daemon: not running"),
+                PidState::Running(pid) => {
+                    println!("Daemon: starting or unresponsive (PID {pid}, no socket)")
+                }
+                PidState::Stale(pid) => println!(
+                    "Daemon: not running (stale PID file {}{})",
+                    pid_path.display(),
+                    pid.map(|pid| format!(" for PID {pid}")).unwrap_or_default()
+                ),
+            }
+        }
+        return Ok(());
+    }
+
+    let status = match client.status().await {
+        Ok(status) => status,
+        Err(e) => {
+            eprintln!("Failed to query daemon status: {e}");
+            std::process::exit(1);
+        }
+    };
+    // Tasks are only listed by daemons new enough to supervise them.
+    let tasks = client.supervisor().await.ok().map(|s| s.tasks);
+    let output = output::StatusOutput {
+        running: true,
+        daemon: Some(status),
+        tasks,
+        pid_file: None,
+    };
+    if format.print(&output)? {
+        return Ok(());
+    }
+
+    let status = output.daemon.as_ref().expect("running daemon has a status");
+    println!("Daemon: running (PID {})", status.pid);
+    println!("  Version:    {} ({})", status.version, status.git_hash);
+    println!("  Uptime:     {}s", status.uptime_secs);
+    println!(
+        "  Listen:     {}:{}",
+        status.listen_addr, status.listen_port
+    );
+    println!(
+        "  Signal:     {}",
+        if status.signal_enabled {
+            "enabled"
+        } else {
+            "disabled"
+        }
+    );
+    println!("  Log level:  {}", status.log_level);
+    println!("  Isolation:  {}", status.isolation_backend);
+    println!("  Skills:     {}", status.skills_count);
+    println!("  Plugins:    {}", status.plugins_count);
+    if !status.warnings.is_empty() {
+        println!("\nWarnings ({}):", status.warnings.len());
+        for w in &status.warnings {
+            println!("  [{}] {}: {}", w.kind, w.key, w.message);
+        }
+    }
+    if let Some(tasks) = &output.tasks {
+        println!("\nTasks:");
+        for task in tasks {
+            print!(
+                "  {:<10} {:<8} restarts: {}",
+                task.name, task.state, task.restarts
+            );
+            match &task.last_error {
+                Some(error) => println!("  (last error: {error})"),
+                None => println!(),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn cmd_init(source: &ConfigSource, defaults: bool, force: bool) -> Result<()> {
+    let path = source.path.as_path();
+    if path.exists() && !force {
+        anyhow::bail!(
+            "{} already exists; use --force to replace it",
+            path.display()
+        );
+    }
+    let mut choices = init::InitChoices::defaults();
+    if !defaults {
+        choices = choices.prompt(&mut std::io::stdin().lock(), &mut std::io::stdout())?;
+    }
+    init::write(path, &init::render(&choices), force)?;
+
+    println!("Wrote {} (mode 0600).", path.display());
+    println!("  Isolation backend: {}", choices.backend);
+    println!("  LLM: {} ({})", choices.provider, choices.model);
+    println!("  Auth mode: {}", choices.auth_mode);
+    if choices.provider != "llamacpp" {
+        println!("  Set CRUSTYCLAW_LLM_API_KEY before starting the daemon.");
+    }
+    println!("  Run `crustyclaw doctor` to check the environment.");
+    Ok(())
+}
+
+async fn cmd_doctor(source: &ConfigSource, format: OutputFormat) -> Result<()> {
+    use crustyclaw_core::doctor::{self, Diagnostic, DiagnosticStatus, DoctorReport};
+
+    let report = match load_config(source).await {
+        Ok(config) => {
+            let file = source.path.exists().then_some(source.path.as_path());
+            doctor::run(&config, file).await
+        }
+        // Nothing else can be checked without a config.
+        Err(e) => DoctorReport {
+            diagnostics: vec![Diagnostic::fail("config", e.to_string())],
+        },
+    };
+    if !format.print(&report)? {
+        for diagnostic in &report.diagnostics {
+            println!("{diagnostic}");
+        }
+        println!(
+            "\n{} passed, {} warnings, {} failed",
+            report.count(DiagnosticStatus::Pass),
+            report.count(DiagnosticStatus::Warn),
+            report.count(DiagnosticStatus::Fail)
+        );
+    }
+    match report.exit_code() {
+        0 => Ok(()),
+        code => std::process::exit(code),
+    }
+}
+
+async fn cmd_config(source: &ConfigSource, show: bool, origin: bool) -> Result<()> {
+    let (config, origins) = load_config_layered(source).await?;
+    if origin {
+        let entries: Vec<(String, String)> = layers::flatten(&config)
+            .into_iter()
+            .map(|(key, value)| {
+                let origin = origins.origin(&key);
+                (format!("{key} = {value}"), origin.to_string())
+            })
+            .collect();
+        // Align origins, but don't let one long value push every line out.
+        let width = entries
+            .iter()
+            .map(|(line, _)| line.len())
+            .filter(|&len| len <= 60)
+            .max()
+            .unwrap_or(0);
+        for (line, origin) in entries {
+            println!("{line:<width$}  # {origin}");
+        }
+    } else if show {
+        let toml_str =
+            toml::to_string_pretty(&config).map_err(|e| anyhow::anyhow!("TOML error: {e}"))?;
+        println!("{toml_str}");
+    } else {
+        println!("Configuration at '{}' is valid.", source.path.display());
+        println!(
+            "  Daemon: {}:{}",
+            config.daemon.listen_addr, config.daemon.listen_port
+        );
+        println!(
+            "  Signal: {}",
+            if config.signal.enabled {
+                "enabled"
+            } else {
+                "disabled"
+            }
+        );
+        println!("  Log level: {}", config.logging.level);
+        println!("  Policy rules: {}", config.policy.rules.len());
+        println!("  Isolation backend: {}", config.isolation.backend);
+        println!("  Secrets: {} configured", config.secrets.entries.len());
+        println!("  Auth mode: {}", config.auth.mode);
+    }
+    Ok(())
+}
+
+async fn cmd_config_lint(source: &ConfigSource) -> Result<()> {
+    let config = load_config(source).await?;
+    println!("Configuration at '{}' is valid.", source.path.display());
+
+    let mut warnings = config.lint();
+    warnings.extend(config.lint_host(&crustyclaw_config::lint::LintHost::current()));
+    if !warnings.is_empty() {
+        println!("\nWarnings:");
+        for warning in &warnings {
+            println!("  {warning}");
+        }
+        println!();
+    }
+
+    let tests = &config.policy.tests;
+    if tests.is_empty() {
+        println!("  No policy tests defined.");
+        return Ok(());
+    }
+
+    let failures = config.run_policy_tests();
+    println!("\nPolicy tests:");
+    for (i, test) in tests.iter().enumerate() {
+        let result = match failures.iter().find(|f| f.index == i) {
+            Some(failure) => format!("FAIL (got {:?})", failure.actual),
+            None => "PASS".to_string(),
+        };
+        println!(
+            "  [{i}] {} {} {} → expect {}: {result}",
+            test.role, test.action, test.resource, test.expect
+        );
+    }
+    println!(
+        "\n{} passed, {} failed",
+        tests.len() - failures.len(),
+        failures.len()
+    );
+
+    if !failures.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn cmd_version(format: OutputFormat) -> Result<()> {
+    let info = crustyclaw_core::ipc::VersionResponse::current();
+    if format.print(&info)? {
+        return Ok(());
+    }
+    println!(
+        "CrustyClaw {}",
+        crustyclaw_core::build_info::version_string()
+    );
+    println!("  Version:  {}", info.version);
+    println!("  Git hash: {}", info.git_hash);
+    println!("  Profile:  {}", info.build_profile);
+    println!("  Built:    {}", info.build_timestamp);
+    println!("  Target:   {}", info.target);
+    println!("  Compiler: {}", info.rustc_version);
+    let features = if info.features.is_empty() {
+        "(none)".to_string()
+    } else {
+        info.features.join(", ")
+    };
+    println!("  Features: {features}");
+    println!("  IPC API:  {}", info.api_version);
+    Ok(())
+}
+
+async fn cmd_policy(
+    source: &ConfigSource,
+    role: &str,
+    action: &str,
+    resource: &str,
+    format: OutputFormat,
+) -> Result<()> {
+    let config = load_config(source).await?;
+    let mut engine = config.build_policy_engine();
+
+    let verdict = engine.evaluate(role, action, resource);
+    let symbol = match verdict.decision {
+        crustyclaw_config::policy::PolicyDecision::Allowed => "ALLOWED",
+        crustyclaw_config::policy::PolicyDecision::Denied => "DENIED",
+        crustyclaw_config::policy::PolicyDecision::NoMatch => "NO MATCH (default deny)",
+    };
+    let output = output::PolicyOutput {
+        request: crustyclaw_core::ipc::PolicyEvalRequest {
+            role: role.to_string(),
+            action: action.to_string(),
+            resource: resource.to_string(),
+        },
+        response: crustyclaw_core::ipc::PolicyEvalResponse::from_verdict(
+            verdict,
+            engine.rule_count(),
+        ),
+    };
+    if format.print(&output)? {
+        return Ok(());
+    }
+
+    println!("Policy check: role={role} action={action} resource={resource}");
+    println!("  Result: {symbol}");
+    if let Some(id) = &output.response.rule_id {
+        println!("  Rule: {id}");
+    }
+    if let Some(reason) = &output.response.reason {
+        println!("  Reason: {reason}");
+    }
+    println!("  Total rules: {}", output.response.rule_count);
+
+    Ok(())
+}
+
+async fn cmd_policy_simulate(
+    source: &ConfigSource,
+    matrix_path: &Path,
+    format: OutputFormat,
+) -> Result<()> {
+    let config = load_config(source).await?;
+    let text = std::fs::read_to_string(matrix_path)
+        .map_err(|e| anyhow::anyhow!("cannot read {}: {e}", matrix_path.display()))?;
+    let matrix = crustyclaw_config::AccessMatrix::parse(&text)
+        .map_err(|e| anyhow::anyhow!("invalid access matrix {}: {e}", matrix_path.display()))?;
+
+    let failures = config.check_policy(&matrix.checks);
+    let output = output::SimulationOutput {
+        matrix: matrix_path.display().to_string(),
+        passed: matrix.checks.len() - failures.len(),
+        failed: failures.len(),
+        mismatches: failures
+            .iter()
+            .map(|f| output::SimulationMismatch {
+                index: f.index,
+                role: f.role.clone(),
+                action: f.action.clone(),
+                resource: f.resource.clone(),
+                expected: f.expected.clone(),
+                actual: f.actual.as_str().to_string(),
+                rule_id: f.rule_id.clone(),
+            })
+            .collect(),
+    };
+    if !format.print(&output)? {
+        println!("Access matrix '{}':", output.matrix);
+        for (i, check) in matrix.checks.iter().enumerate() {
+            let result = match output.mismatches.iter().find(|m| m.index == i) {
+                Some(m) => match &m.rule_id {
+                    Some(id) => format!("FAIL (got {}, rule {id:?})", m.actual),
+                    None => format!("FAIL (got {})", m.actual),
+                },
+                None => "PASS".to_string(),
+            };
+            println!(
+                "  [{i}] {} {} {} → expect {}: {result}",
+                check.role, check.action, check.resource, check.expect
+            );
+        }
+        println!("\n{} passed, {} failed", output.passed, output.failed);
+    }
+
+    if output.failed > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+async fn cmd_plugins(source: &ConfigSource, format: OutputFormat) -> Result<()> {
+    let config = load_config(source).await?;
+    let client = ipc_client(&config)?;
+
+    if !client.daemon_available() {
+        let none = crustyclaw_core::ipc::PluginsResponse {
+            plugins: Vec::new(),
+        };
+        if !format.print(&none)? {
+            println!("No plugins registered (daemon is not running).");
+            println!("  Plugins are discovered at daemon startup.");
+        }
+        return Ok(());
+    }
+
+    match client.plugins().await {
+        Ok(resp) => {
+            if format.print(&resp)? {
+                return Ok(());
+            }
+            if resp.plugins.is_empty() {
+                println!("No plugins registered.");
+            } else {
+                println!("Registered plugins:");
+                for p in &resp.plugins {
+                    println!("  {} v{} — {}", p.name, p.version, p.description);
+                }
+            }
+        }
+        // A script must not mistake a failed query for an empty list.
+        Err(e) if format.is_structured() => anyhow::bail!("Failed to query plugins: {e}"),
+        Err(e) => {
+            eprintln!("Failed to query plugins: {e}");
+        }
+    }
+    Ok(())
+}
+
+async fn cmd_isolation(source: &ConfigSource, format: OutputFormat) -> Result<()> {
+    let config = load_config(source).await?;
+    let iso = &config.isolation;
+
+    // Select backend and probe availability
+    let pref = match iso.backend.as_str() {
+        "docker" => crustyclaw_core::isolation::BackendPreference::Docker,
+        "podman" => crustyclaw_core::isolation::BackendPreference::Podman,
+        "firecracker" => crustyclaw_core::isolation::BackendPreference::Firecracker,
+        "apple-vz" => crustyclaw_core::isolation::BackendPreference::AppleVz,
+        "linux-ns" => crustyclaw_core::isolation::BackendPreference::LinuxNamespace,
+        "noop" => crustyclaw_core::isolation::BackendPreference::Noop,
+        _ => crustyclaw_core::isolation::BackendPreference::Auto,
     };
     let backend = crustyclaw_core::isolation::select_backend(&pref);
 
+    // Trust-based isolation
+    let selector = crustyclaw_core::TrustBasedSelector::from_config(iso);
+    let default_tier = iso
+        .default_trust_tier
+        .as_deref()
+        .and_then(crustyclaw_core::TrustTier::from_str_loose);
+    let trust_tiers: Vec<output::TrustTierInfo> = crustyclaw_core::TrustTier::ALL
+        .into_iter()
+        .map(|tier| output::TrustTierInfo {
+            tier: tier.to_string(),
+            required_level: selector.required_level(tier).to_string(),
+            configured_backend: selector
+                .tier_backend(tier)
+                .map_or("auto".to_string(), |pref| pref.to_string()),
+            backend: selector.select(tier).name().to_string(),
+            default: default_tier == Some(tier),
+        })
+        .collect();
+    let output = output::IsolationOutput {
+        configured_backend: iso.backend.clone(),
+        backend: backend.name().to_string(),
+        available: backend.available(),
+        memory_mb: iso.default_memory_bytes / (1024 * 1024),
+        cpu_fraction: iso.default_cpu_fraction,
+        timeout_secs: iso.default_timeout_secs,
+        network_policy: iso.default_network.clone(),
+        max_concurrent: iso.max_concurrent,
+        docker_image: iso.docker_image.clone(),
+        pull_images: iso.pull_images,
+        strict_images: iso.strict_images,
+        credential_proxy: iso.credential_proxy,
+        trust_tiers,
+    };
+    if format.print(&output)? {
+        return Ok(());
+    }
+
     println!("Isolation configuration:");
     println!("  Backend (config): {}", iso.backend);
     println!(
@@ -360,43 +1537,125 @@ async fn cmd_isolation(config_path: &Path) -> Result<()> {
     println!("  Max concurrent sandboxes: {}", iso.max_concurrent);
     println!("  Docker image: {}", iso.docker_image);
     println!(
-        "  Credential proxy: {}",
-        if iso.credential_proxy {
-            "enabled"
+        "  Image policy: {}, {}",
+        if iso.pull_images {
+            "pull missing images"
         } else {
-            "disabled"
+            "require local images"
+        },
+        if iso.strict_images {
+            "strict (no :latest)"
+        } else {
+            "any tag"
         }
     );
+    if iso.credential_proxy {
+        println!(
+            "  Credential proxy: enabled (egress proxy on {}, {} allowed host(s))",
+            iso.egress_proxy_addr,
+            iso.egress_allowed_hosts.len()
+        );
+    } else {
+        println!("  Credential proxy: disabled");
+    }
 
-    // Trust-based isolation
-    if let Some(ref tier) = iso.default_trust_tier
-        && let Some(trust) = crustyclaw_core::TrustTier::from_str_loose(tier)
-    {
-        let level = crustyclaw_core::TrustBasedSelector::required_level(trust);
-        let selector = crustyclaw_core::TrustBasedSelector::new();
-        let trust_backend = selector.select(trust);
+    println!("  Trust tiers:");
+    for tier in &output.trust_tiers {
         println!(
-            "  Trust tier: {} → {} (backend: {})",
-            trust,
-            level,
-            trust_backend.name()
+            "    {:<14} {} → {} (backend: {}){}",
+            tier.tier,
+            tier.required_level,
+            tier.configured_backend,
+            tier.backend,
+            if tier.default { " [default]" } else { "" }
+        );
+    }
+
+    Ok(())
+}
+
+async fn cmd_login(source: &ConfigSource, ttl: Option<u64>) -> Result<()> {
+    use crustyclaw_core::auth::token;
+
+    let config = load_config(source).await?;
+    if config.auth.mode != "token" {
+        anyhow::bail!(
+            "auth.mode is {:?}; login is only needed with auth.mode = \"token\"",
+            config.auth.mode
         );
     }
+    let max_ttl = config.auth.token_ttl_secs;
+    let ttl = ttl.unwrap_or(max_ttl);
+    if ttl == 0 || ttl > max_ttl {
+        anyhow::bail!("--ttl must be between 1 and auth.token_ttl_secs ({max_ttl})");
+    }
 
+    // The daemon mints the token for whoever it sees at the other end of
+    // the socket; its key never leaves it.
+    let client = crustyclaw_core::IpcClient::new(
+        crustyclaw_core::ipc::server::socket_path_from_config(&config),
+    );
+    if !client.daemon_available() {
+        eprintln!("Daemon is not running (no socket found).");
+        std::process::exit(1);
+    }
+    let login = client
+        .login(Some(ttl))
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to log in: {e}"))?;
+    let path = token::token_path(&config);
+    token::save_token(&path, &login.token)
+        .map_err(|e| anyhow::anyhow!("failed to write {}: {e}", path.display()))?;
+
+    println!("Logged in as {}", login.identity);
+    println!("  Roles:   {:?}", login.roles);
+    println!("  Expires: in {ttl}s");
+    println!("  Token:   {}", path.display());
     Ok(())
 }
 
-async fn cmd_whoami(config_path: &Path) -> Result<()> {
-    let config = load_config(config_path).await?;
+async fn cmd_whoami(source: &ConfigSource, format: OutputFormat) -> Result<()> {
+    let config = load_config(source).await?;
 
     // Perform transparent authentication
     let session = transparent_auth(&config);
 
+    // Show what this identity can do according to the policy engine
+    let mut engine = config.build_policy_engine();
+    let role = session.roles().first().map(|r| r.as_str()).unwrap_or("*");
+    let checks = [
+        ("read", "config"),
+        ("write", "config"),
+        ("read", "secrets"),
+        ("execute", "skills"),
+        ("read", "messages"),
+    ]
+    .into_iter()
+    .map(|(action, resource)| output::PermissionCheck {
+        action: action.to_string(),
+        resource: resource.to_string(),
+        allowed: engine.is_allowed(role, action, resource),
+    })
+    .collect();
+    let local = session.local_identity();
+    let output = output::WhoamiOutput {
+        identity: session.identity().to_string(),
+        roles: session.roles().to_vec(),
+        uid: local.map(|l| l.uid),
+        gid: local.map(|l| l.gid),
+        privileged: local.map(|l| l.is_privileged),
+        role: role.to_string(),
+        checks,
+    };
+    if format.print(&output)? {
+        return Ok(());
+    }
+
     println!("Authentication: transparent (local OS identity)");
-    println!("  Identity: {}", session.identity());
-    println!("  Roles:    {:?}", session.roles());
+    println!("  Identity: {}", output.identity);
+    println!("  Roles:    {:?}", output.roles);
 
-    if let Some(local) = session.local_identity() {
+    if let Some(local) = local {
         println!("  UID:      {}", local.uid);
         println!("  GID:      {}", local.gid);
         println!(
@@ -405,40 +1664,49 @@ async fn cmd_whoami(config_path: &Path) -> Result<()> {
         );
     }
 
-    // Show what this identity can do according to the policy engine
-    let mut engine = config.build_policy_engine();
-    let role = session.roles().first().map(|r| r.as_str()).unwrap_or("*");
-
     println!("\nPolicy evaluation (role={role}):");
-    for (action, resource) in &[
-        ("read", "config"),
-        ("write", "config"),
-        ("read", "secrets"),
-        ("execute", "skills"),
-        ("read", "messages"),
-    ] {
-        let allowed = engine.is_allowed(role, action, resource);
-        let symbol = if allowed { "ALLOW" } else { "DENY" };
-        println!("  {action:>8} {resource:<12} {symbol}");
+    for check in &output.checks {
+        let symbol = if check.allowed { "ALLOW" } else { "DENY" };
+        println!("  {:>8} {:<12} {symbol}", check.action, check.resource);
     }
 
     Ok(())
 }
 
-async fn cmd_secrets(config_path: &Path) -> Result<()> {
-    let config = load_config(config_path).await?;
+async fn cmd_secrets(source: &ConfigSource, format: OutputFormat) -> Result<()> {
+    let config = load_config(source).await?;
+
+    let output = output::SecretsOutput {
+        staging_dir: config.secrets.staging_dir.clone(),
+        secrets: config
+            .secrets
+            .entries
+            .iter()
+            .map(|entry| output::SecretInfo {
+                name: entry.name.clone(),
+                source: entry.source.clone(),
+                inject_as: entry.inject_as.clone(),
+                inject_env: entry.inject_env.clone(),
+                inject_path: entry.inject_path.clone(),
+                description: entry.description.clone(),
+            })
+            .collect(),
+    };
+    if format.print(&output)? {
+        return Ok(());
+    }
 
-    if config.secrets.entries.is_empty() {
+    if output.secrets.is_empty() {
         println!("No secrets configured.");
         println!("  Add secrets to [secrets.entries] in crustyclaw.toml");
         return Ok(());
     }
 
-    println!("Secrets ({} configured):", config.secrets.entries.len());
-    println!("  Staging directory: {}", config.secrets.staging_dir);
+    println!("Secrets ({} configured):", output.secrets.len());
+    println!("  Staging directory: {}", output.staging_dir);
     println!();
 
-    for entry in &config.secrets.entries {
+    for entry in &output.secrets {
         println!("  {}:", entry.name);
         println!("    Source:    {}", entry.source);
         println!("    Inject as: {}", entry.inject_as);
@@ -456,19 +1724,789 @@ async fn cmd_secrets(config_path: &Path) -> Result<()> {
     Ok(())
 }
 
-/// Create an IPC client from the loaded config.
-fn ipc_client(config: &crustyclaw_config::AppConfig) -> crustyclaw_core::IpcClient {
-    let socket_path = crustyclaw_core::ipc::server::socket_path_from_config(config);
-    crustyclaw_core::IpcClient::new(socket_path)
+async fn cmd_secrets_keystore(source: &ConfigSource, command: SecretsCommands) -> Result<()> {
+    use crustyclaw_core::secrets::SecretValue;
+    use crustyclaw_core::secrets::keystore::Keystore;
+
+    let config = load_config(source).await?;
+    let keystore_config = &config.secrets.keystore;
+    match command {
+        SecretsCommands::Set { name, from_file } => {
+            let value = match from_file {
+                Some(path) => std::fs::read_to_string(&path)
+                    .map_err(|e| anyhow::anyhow!("cannot read {}: {e}", path.display()))?,
+                None => {
+                    use std::io::IsTerminal;
+                    if std::io::stdin().is_terminal() {
+                        eprint!("Value for {name} (input is echoed): ");
+                    }
+                    let mut line = String::new();
+                    std::io::stdin().read_line(&mut line)?;
+                    line
+                }
+            };
+            let value = SecretValue::new(value.trim_end_matches(['\r', '\n']));
+            if value.is_empty() {
+                anyhow::bail!("refusing to store an empty value for {name}");
+            }
+            let mut keystore = Keystore::open_or_create(keystore_config)?;
+            keystore.set(&name, value);
+            keystore.save()?;
+            println!("Stored {name} in {}", keystore.path().display());
+            if !config
+                .secrets
+                .entries
+                .iter()
+                .any(|entry| entry.name == name && entry.source == "keystore")
+            {
+                println!(
+                    "  Add a [[secrets.entries]] entry with name = {name:?} and source = \"keystore\" to use it."
+                );
+            }
+            println!("  A running daemon picks it up on SIGHUP or restart.");
+        }
+        SecretsCommands::Get { name } => {
+            let keystore = Keystore::open(keystore_config)?;
+            let value = keystore
+                .get(&name)
+                .ok_or_else(|| anyhow::anyhow!("{name} is not in {}", keystore.path().display()))?;
+            println!("{}", value.expose());
+        }
+        SecretsCommands::Rm { name } => {
+            let mut keystore = Keystore::open(keystore_config)?;
+            if !keystore.remove(&name) {
+                anyhow::bail!("{name} is not in {}", keystore.path().display());
+            }
+            keystore.save()?;
+            println!("Removed {name} from {}", keystore.path().display());
+        }
+        SecretsCommands::Import { names } => secrets_import(&config, names)?,
+    }
+    Ok(())
 }
 
-async fn load_config(path: &Path) -> Result<crustyclaw_config::AppConfig> {
-    if tokio::fs::try_exists(path).await.unwrap_or(false) {
-        crustyclaw_config::AppConfig::load(path)
+/// `crustyclaw secrets import`.
+fn secrets_import(config: &crustyclaw_config::AppConfig, names: Vec<String>) -> Result<()> {
+    use crustyclaw_core::secrets::{SecretValue, keychain, keychain_item};
+
+    let entries: Vec<_> = if names.is_empty() {
+        config
+            .secrets
+            .entries
+            .iter()
+            .filter(|entry| entry.source == "env")
+            .collect()
+    } else {
+        names
+            .iter()
+            .map(
+                |name| match config.secrets.entries.iter().find(|e| &e.name == name) {
+                    Some(entry) if entry.source == "env" => Ok(entry),
+                    Some(entry) => Err(anyhow::anyhow!(
+                        "{name} has source = {:?}; only env-sourced secrets are imported",
+                        entry.source
+                    )),
+                    None => Err(anyhow::anyhow!("{name} is not in [[secrets.entries]]")),
+                },
+            )
+            .collect::<Result<_>>()?
+    };
+    if entries.is_empty() {
+        println!("No env-sourced secrets to import.");
+        return Ok(());
+    }
+
+    let mut imported = Vec::new();
+    let mut failed = 0;
+    for entry in entries {
+        let var = entry
+            .env_var
+            .clone()
+            .unwrap_or_else(|| format!("CRUSTYCLAW_SECRET_{}", entry.name.to_uppercase()));
+        let Some(value) = std::env::var(&var)
+            .ok()
+            .map(SecretValue::new)
+            .filter(|v| !v.is_empty())
+        else {
+            eprintln!("  {}: {var} is not set; skipped", entry.name);
+            failed += 1;
+            continue;
+        };
+        let (service, account) = keychain_item(entry);
+        match keychain::set(&service, &account, value.expose()) {
+            Ok(()) => {
+                println!(
+                    "Imported {} from {var} into keychain {service}/{account}",
+                    entry.name
+                );
+                imported.push((entry.name.clone(), var));
+            }
+            Err(e) => {
+                eprintln!("  {}: {e}", entry.name);
+                failed += 1;
+            }
+        }
+    }
+
+    if !imported.is_empty() {
+        println!();
+        println!("In crustyclaw.toml, change these entries to read from the keychain:");
+        for (name, _) in &imported {
+            println!("  name = {name:?}: set source = \"keychain\" and remove env_var");
+        }
+        let vars: Vec<&str> = imported.iter().map(|(_, var)| var.as_str()).collect();
+        println!("Then unset {} and restart the daemon.", vars.join(", "));
+    }
+    if failed > 0 {
+        anyhow::bail!("{failed} secret(s) not imported");
+    }
+    Ok(())
+}
+
+async fn cmd_skill_run(
+    source: &ConfigSource,
+    name: &str,
+    args: Vec<(String, serde_json::Value)>,
+    trust: Option<&str>,
+    follow: bool,
+) -> Result<()> {
+    let config = load_config(source).await?;
+    let client = ipc_client(&config)?;
+
+    if !client.daemon_available() {
+        eprintln!("Daemon is not running (no socket found).");
+        std::process::exit(1);
+    }
+
+    let args = args.into_iter().collect();
+    if follow {
+        return cmd_skill_follow(&client, name, args, trust).await;
+    }
+    let result = client
+        .execute_skill(name, args, trust)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to run skill '{name}': {e}"))?;
+
+    std::io::stdout().write_all(result.stdout.as_bytes())?;
+    std::io::stdout().flush()?;
+    std::io::stderr().write_all(result.stderr.as_bytes())?;
+
+    eprintln!(
+        "\nSkill '{}' exited with code {} in {}ms{}",
+        result.name,
+        result.exit_code,
+        result.elapsed_ms,
+        usage_summary(result.peak_memory_bytes, result.cpu_time_ms)
+    );
+
+    if result.exit_code != 0 {
+        std::process::exit(result.exit_code);
+    }
+    Ok(())
+}
+
+/// The measured resource usage of a skill run, as a parenthesized suffix
+/// for its summary line; empty when the backend measured nothing.
+fn usage_summary(peak_memory_bytes: Option<u64>, cpu_time_ms: Option<u64>) -> String {
+    let parts: Vec<String> = [
+        peak_memory_bytes.map(|b| format!("peak memory {} KiB", b / 1024)),
+        cpu_time_ms.map(|ms| format!("CPU {ms}ms")),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    if parts.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", parts.join(", "))
+    }
+}
+
+/// Run a skill, printing its output lines as they arrive.
+async fn cmd_skill_follow(
+    client: &crustyclaw_core::IpcClient,
+    name: &str,
+    args: serde_json::Map<String, serde_json::Value>,
+    trust: Option<&str>,
+) -> Result<()> {
+    use crustyclaw_core::ipc::SkillRunEvent;
+
+    let mut stream = client
+        .execute_skill_stream(name, args, trust)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to run skill '{name}': {e}"))?;
+
+    loop {
+        let event = stream
+            .next()
+            .await
+            .map_err(|e| anyhow::anyhow!("Skill '{name}' output interrupted: {e}"))?;
+        match event {
+            Some(SkillRunEvent::Output { stream, line }) if stream == "stderr" => {
+                eprintln!("{line}");
+            }
+            Some(SkillRunEvent::Output { line, .. }) => println!("{line}"),
+            Some(SkillRunEvent::Done {
+                exit_code,
+                elapsed_ms,
+                peak_memory_bytes,
+                cpu_time_ms,
+            }) => {
+                eprintln!(
+                    "\nSkill '{name}' exited with code {exit_code} in {elapsed_ms}ms{}",
+                    usage_summary(peak_memory_bytes, cpu_time_ms)
+                );
+                if exit_code != 0 {
+                    std::process::exit(exit_code);
+                }
+                return Ok(());
+            }
+            Some(SkillRunEvent::Error { error }) => {
+                anyhow::bail!("Failed to run skill '{name}': {error}")
+            }
+            None => anyhow::bail!("Skill '{name}' output ended before the skill finished"),
+        }
+    }
+}
+
+async fn cmd_isolation_pull_image(source: &ConfigSource, image: Option<&str>) -> Result<()> {
+    use crustyclaw_core::isolation::{DockerSandboxBackend, PodmanSandboxBackend};
+
+    let config = load_config(source).await?;
+    let docker = DockerSandboxBackend::from_config(&config.isolation);
+    let image = image.unwrap_or(docker.default_image()).to_string();
+    let pinned = if config.isolation.backend == "podman" {
+        PodmanSandboxBackend::from_docker(docker)
+            .pull_image(Some(&image))
             .await
-            .map_err(|e| anyhow::anyhow!(e))
     } else {
-        info!(path = %path.display(), "Config file not found, using defaults");
-        Ok(crustyclaw_config::AppConfig::default())
+        docker.pull_image(Some(&image)).await
+    }
+    .map_err(|e| anyhow::anyhow!("Failed to pull {image}: {e}"))?;
+
+    println!("Pulled {image}");
+    println!("  Pinned: {pinned}");
+    if pinned != image && image == config.isolation.docker_image {
+        println!("Set isolation.docker_image = {pinned:?} to run exactly this image.");
+    }
+    Ok(())
+}
+
+async fn cmd_skill_build_image(
+    source: &ConfigSource,
+    name: &str,
+    builder: Option<&str>,
+) -> Result<()> {
+    use crustyclaw_core::skill::image::{self, ImageBuilder, SkillLock};
+
+    let config = load_config(source).await?;
+    let skills_dir = config
+        .daemon
+        .skills_dir
+        .as_deref()
+        .map(Path::new)
+        .ok_or_else(|| anyhow::anyhow!("daemon.skills_dir is not set"))?;
+
+    let (skill_dir, manifest) = crustyclaw_core::skill::manifest::find_manifest(skills_dir, name)
+        .map_err(|e| anyhow::anyhow!("Failed to scan {}: {e}", skills_dir.display()))?
+        .ok_or_else(|| anyhow::anyhow!("No skill named '{name}' in {}", skills_dir.display()))?;
+    manifest
+        .validate()
+        .map_err(|e| anyhow::anyhow!("Skill '{name}' in {}: {e}", skill_dir.display()))?;
+
+    let builder = match builder {
+        Some(builder) => ImageBuilder::from_name(builder)?,
+        None => ImageBuilder::detect()?,
+    };
+    let locked = image::build(&manifest, &builder)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to build image for '{name}': {e}"))?;
+
+    let mut lock = SkillLock::load(skills_dir)?;
+    lock.insert(name, locked.clone());
+    lock.save(skills_dir)?;
+
+    println!("Built {} ({})", locked.image, locked.digest);
+    println!(
+        "Recorded in {}",
+        skills_dir.join(image::LOCK_FILE).display()
+    );
+    println!("Reload the daemon (SIGHUP) to run '{name}' in the new image.");
+    Ok(())
+}
+
+async fn cmd_skill_install(source: &ConfigSource, package: &Path, dev: bool) -> Result<()> {
+    use crustyclaw_core::daemonize::{self, PidState};
+
+    let config = load_config(source).await?;
+    let skills_dir = config
+        .daemon
+        .skills_dir
+        .as_deref()
+        .map(Path::new)
+        .ok_or_else(|| anyhow::anyhow!("daemon.skills_dir is not set"))?;
+
+    let installed =
+        crustyclaw_core::skill::package::install(package, skills_dir, &config.security, dev)
+            .map_err(|e| anyhow::anyhow!("Failed to install {}: {e}", package.display()))?;
+    match &installed.signer {
+        Some(key) => println!("Signature verified (trusted key '{key}')"),
+        None => eprintln!(
+            "warning: {} is unsigned (development mode)",
+            package.display()
+        ),
+    }
+    match &installed.replaced {
+        Some(previous) => println!(
+            "Upgraded '{}' from {previous} to {} in {}",
+            installed.name,
+            installed.version,
+            installed.dir.display()
+        ),
+        None => println!(
+            "Installed '{}' {} in {}",
+            installed.name,
+            installed.version,
+            installed.dir.display()
+        ),
+    }
+
+    match daemonize::inspect(&daemonize::pid_file_path(&config)) {
+        PidState::Running(pid) => {
+            daemonize::reload(pid)
+                .map_err(|e| anyhow::anyhow!("Failed to signal PID {pid}: {e}"))?;
+            println!("Daemon: sent SIGHUP to PID {pid} to register the skill");
+        }
+        _ => println!("The daemon registers the skill when it next starts."),
+    }
+    Ok(())
+}
+
+async fn cmd_files(source: &ConfigSource, command: FilesCommands) -> Result<()> {
+    let config = load_config(source).await?;
+    let client = ipc_client(&config)?;
+
+    if !client.daemon_available() {
+        eprintln!("Daemon is not running (no socket found).");
+        std::process::exit(1);
+    }
+
+    match command {
+        FilesCommands::Put {
+            conversation,
+            path,
+            name,
+            content_type,
+        } => {
+            let name = match name {
+                Some(name) => name,
+                None => path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .ok_or_else(|| anyhow::anyhow!("{} has no file name", path.display()))?,
+            };
+            let data = tokio::fs::read(&path)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to read {}: {e}", path.display()))?;
+            let file = client
+                .files_put(&conversation, &name, content_type.as_deref(), &data)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to upload '{name}': {e}"))?;
+            println!(
+                "Uploaded {conversation}/{} ({} bytes, {})",
+                file.name, file.size, file.content_type
+            );
+        }
+        FilesCommands::Get {
+            conversation,
+            name,
+            output,
+        } => {
+            let (file, data) = client
+                .files_get(&conversation, &name)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to download '{name}': {e}"))?;
+            let output = output.unwrap_or_else(|| PathBuf::from(&file.name));
+            if output == Path::new("-") {
+                std::io::stdout().write_all(&data)?;
+                std::io::stdout().flush()?;
+            } else {
+                tokio::fs::write(&output, &data)
+                    .await
+                    .map_err(|e| anyhow::anyhow!("Failed to write {}: {e}", output.display()))?;
+                eprintln!(
+                    "Saved {conversation}/{} to {} ({} bytes, {})",
+                    file.name,
+                    output.display(),
+                    file.size,
+                    file.content_type
+                );
+            }
+        }
+        FilesCommands::List { conversation } => {
+            let listing = client
+                .files_list(&conversation)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to list files: {e}"))?;
+            if listing.files.is_empty() {
+                println!("No files in {}", listing.conversation);
+            }
+            for file in &listing.files {
+                println!(
+                    "{:>10}  {:<24}  {}",
+                    file.size, file.content_type, file.name
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn cmd_chat(
+    source: &ConfigSource,
+    session: Option<String>,
+    tools: crustyclaw_core::chat::ToolFilter,
+) -> Result<()> {
+    use std::io::IsTerminal;
+
+    let config = load_config(source).await?;
+    let client = ipc_client(&config)?;
+
+    if !client.daemon_available() {
+        eprintln!("Daemon is not running (no socket found).");
+        std::process::exit(1);
+    }
+
+    chat::repl(&client, session, &tools, std::io::stdout().is_terminal()).await
+}
+
+async fn cmd_quotas(source: &ConfigSource) -> Result<()> {
+    let config = load_config(source).await?;
+    let client = ipc_client(&config)?;
+
+    if !client.daemon_available() {
+        eprintln!("Daemon is not running (no socket found).");
+        std::process::exit(1);
+    }
+
+    let listing = client
+        .quotas()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to query quotas: {e}"))?;
+    if listing.quotas.is_empty() {
+        println!("No quotas configured and no usage recorded");
+        return Ok(());
+    }
+    println!(
+        "{:<12} {:<20} {:>10} {:>10}  RESETS IN",
+        "ROLE", "KIND", "USED", "LIMIT"
+    );
+    for quota in &listing.quotas {
+        let limit = quota
+            .limit
+            .map_or_else(|| "-".to_string(), |limit| limit.to_string());
+        let resets = quota.resets_in_secs;
+        println!(
+            "{:<12} {:<20} {:>10} {:>10}  {}h{:02}m",
+            quota.role,
+            quota.kind,
+            quota.used,
+            limit,
+            resets / 3600,
+            resets % 3600 / 60
+        );
     }
+    Ok(())
+}
+
+async fn cmd_usage(source: &ConfigSource, days: u32, by: &str) -> Result<()> {
+    let config = load_config(source).await?;
+    let client = ipc_client(&config)?;
+
+    if !client.daemon_available() {
+        eprintln!("Daemon is not running (no socket found).");
+        std::process::exit(1);
+    }
+
+    let report = client
+        .usage(Some(days), Some(by))
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to query usage: {e}"))?;
+    if report.usage.is_empty() {
+        println!("No token usage recorded since {}", report.since);
+        return Ok(());
+    }
+    println!(
+        "{:<32} {:>8} {:>8} {:>12} {:>12} {:>10}",
+        report.by.to_uppercase(),
+        "REQUESTS",
+        "RETRIES",
+        "PROMPT",
+        "COMPLETION",
+        "COST"
+    );
+    let mut unpriced = false;
+    for row in &report.usage {
+        let cost = if row.unpriced_tokens > 0 {
+            unpriced = true;
+            format!("${:.2}*", row.cost_usd)
+        } else {
+            format!("${:.2}", row.cost_usd)
+        };
+        println!(
+            "{:<32} {:>8} {:>8} {:>12} {:>12} {:>10}",
+            row.key, row.requests, row.retries, row.prompt_tokens, row.completion_tokens, cost
+        );
+    }
+    let cost: f64 = report.usage.iter().map(|row| row.cost_usd).sum();
+    println!("\nSince {} (UTC): ${cost:.2} estimated", report.since);
+    if unpriced {
+        println!("* excludes tokens of models without an [llm.pricing] entry");
+    }
+    Ok(())
+}
+
+async fn cmd_memory(source: &ConfigSource, command: MemoryCommands) -> Result<()> {
+    let config = load_config(source).await?;
+    let client = ipc_client(&config)?;
+
+    if !client.daemon_available() {
+        eprintln!("Daemon is not running (no socket found).");
+        std::process::exit(1);
+    }
+
+    match command {
+        MemoryCommands::Show { conversation } => {
+            let memory = client
+                .memory(&conversation)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to show memory: {e}"))?;
+            println!("Memory of {} ({} runs)", memory.id, memory.runs);
+            if !memory.summary.is_empty() {
+                println!("\n{}", memory.summary);
+            }
+            if !memory.facts.is_empty() {
+                println!();
+                let width = memory.facts.iter().map(|f| f.key.len()).max().unwrap_or(0);
+                for fact in &memory.facts {
+                    println!("  {:<width$}  {}", fact.key, fact.value);
+                }
+            }
+        }
+        MemoryCommands::Clear { conversation } => {
+            client
+                .memory_clear(&conversation)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to clear memory: {e}"))?;
+            println!("Cleared memory of {conversation}");
+        }
+    }
+    Ok(())
+}
+
+async fn cmd_elevation(source: &ConfigSource, command: ElevationCommands) -> Result<()> {
+    let config = load_config(source).await?;
+    let client = ipc_client(&config)?;
+
+    if !client.daemon_available() {
+        eprintln!("Daemon is not running (no socket found).");
+        std::process::exit(1);
+    }
+
+    let operator = crustyclaw_core::LocalIdentity::detect().username;
+    match command {
+        ElevationCommands::List => {
+            let listing = client
+                .elevations()
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to list elevation requests: {e}"))?;
+            if listing.requests.is_empty() {
+                println!("No elevation requests");
+            }
+            for req in &listing.requests {
+                println!(
+                    "#{:<4} {:<9} {} → {}  {}",
+                    req.id, req.status, req.current, req.requested, req.preview
+                );
+                println!("      reason: {}", req.reason);
+                if let Some(by) = &req.decided_by {
+                    println!("      decided by: {by}");
+                }
+            }
+        }
+        ElevationCommands::Approve { id } => {
+            let req = client
+                .approve_elevation(id, &operator)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to approve #{id}: {e}"))?;
+            println!(
+                "Approved #{id}: {} may run once at {}",
+                req.preview, req.requested
+            );
+        }
+        ElevationCommands::Deny { id, reason } => {
+            client
+                .deny_elevation(id, &operator, reason.as_deref())
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to deny #{id}: {e}"))?;
+            println!("Denied #{id}");
+        }
+    }
+    Ok(())
+}
+
+async fn cmd_schedule(source: &ConfigSource, command: ScheduleCommands) -> Result<()> {
+    let config = load_config(source).await?;
+    let client = ipc_client(&config)?;
+
+    if !client.daemon_available() {
+        eprintln!("Daemon is not running (no socket found).");
+        std::process::exit(1);
+    }
+
+    match command {
+        ScheduleCommands::List { history } => {
+            let listing = client
+                .schedules()
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to list schedules: {e}"))?;
+            if listing.schedules.is_empty() {
+                println!("No schedules configured");
+                return Ok(());
+            }
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default();
+            println!(
+                "{:<20} {:<16} {:<20} {:<10} LAST RUN",
+                "NAME", "CRON", "TARGET", "NEXT IN"
+            );
+            for schedule in &listing.schedules {
+                let next = match (schedule.running, schedule.next_run) {
+                    (true, _) => "running".to_string(),
+                    (false, Some(at)) => {
+                        let secs = at.saturating_sub(now);
+                        format!("{}h{:02}m", secs / 3600, secs % 3600 / 60)
+                    }
+                    (false, None) if !schedule.enabled => "disabled".to_string(),
+                    (false, None) => "-".to_string(),
+                };
+                let last = schedule.history.first().map_or_else(
+                    || "-".to_string(),
+                    |run| {
+                        format!(
+                            "{} ({}, {}s ago)",
+                            run.outcome,
+                            run.trigger,
+                            now.saturating_sub(run.started_at)
+                        )
+                    },
+                );
+                println!(
+                    "{:<20} {:<16} {:<20} {:<10} {}",
+                    schedule.name, schedule.cron, schedule.target, next, last
+                );
+                if history {
+                    for run in &schedule.history {
+                        println!(
+                            "      {:<9} {:<6} {}s ago, {}ms  {}",
+                            run.outcome,
+                            run.trigger,
+                            now.saturating_sub(run.started_at),
+                            run.duration_ms,
+                            run.message.lines().next().unwrap_or_default()
+                        );
+                    }
+                }
+            }
+        }
+        ScheduleCommands::RunNow { name } => {
+            client
+                .run_schedule(&name)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to run {name}: {e}"))?;
+            println!("Started {name}; see `crustyclaw schedule list --history` for the result");
+        }
+    }
+    Ok(())
+}
+
+async fn cmd_signal_link(source: &ConfigSource, device_name: &str) -> Result<()> {
+    use crustyclaw_signal::qr::QrCode;
+    use crustyclaw_signal::{SignalAdapter, SignalCliTransport};
+
+    let config = load_config(source).await?;
+    let data_dir = Path::new(&config.signal.data_dir);
+    let transport = SignalCliTransport::spawn_provisioning(&config.signal.cli_path, data_dir)
+        .map_err(|e| anyhow::anyhow!("Failed to start signal-cli: {e}"))?;
+
+    let linked = SignalAdapter::new()
+        .link(&transport, device_name, |uri| {
+            println!("Scan this code from Signal on your phone (Settings → Linked devices):\n");
+            match QrCode::encode(uri.as_bytes()) {
+                Some(code) => println!("{}", code.to_terminal_string()),
+                None => println!("(link URI too long for a QR code)\n"),
+            }
+            println!("Or open: {uri}\n");
+            println!("Waiting for approval...");
+        })
+        .await
+        .map_err(|e| anyhow::anyhow!("{e}"))?;
+
+    let number = linked.phone_number();
+    println!(
+        "Linked as {number}; session stored in {}",
+        data_dir.display()
+    );
+    if config.signal.account.as_deref() != Some(number) {
+        println!("Set `account = \"{number}\"` under [signal] in your config to use it.");
+    }
+    Ok(())
+}
+
+/// Parse a `--set key=value` config override.
+fn parse_config_override(s: &str) -> Result<Override, String> {
+    Override::parse_flag(s).map_err(|e| e.to_string())
+}
+
+/// Parse a `--arg key=value` pair for `skill run`.
+fn parse_skill_arg(s: &str) -> Result<(String, serde_json::Value), String> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got {s:?}"))?;
+    if key.is_empty() {
+        return Err(format!("empty argument name in {s:?}"));
+    }
+    let value = serde_json::from_str(value)
+        .unwrap_or_else(|_| serde_json::Value::String(value.to_string()));
+    Ok((key.to_string(), value))
+}
+
+/// Create an IPC client from the loaded config: TLS to `[remote]` when
+/// `remote.addr` is set, otherwise the local socket.
+fn ipc_client(config: &crustyclaw_config::AppConfig) -> Result<crustyclaw_core::IpcClient> {
+    crustyclaw_core::IpcClient::from_config(config)
+        .map_err(|e| anyhow::anyhow!("Failed to set up IPC client: {e}"))
+}
+
+async fn load_config(source: &ConfigSource) -> Result<crustyclaw_config::AppConfig> {
+    Ok(load_config_layered(source).await?.0)
+}
+
+/// Load the config file (defaults if it does not exist), then apply
+/// `CRUSTYCLAW__*` environment overrides and `--set` flags.
+async fn load_config_layered(
+    source: &ConfigSource,
+) -> Result<(crustyclaw_config::AppConfig, layers::ConfigOrigins)> {
+    let path = source.path.as_path();
+    let file = if tokio::fs::try_exists(path).await.unwrap_or(false) {
+        Some(path)
+    } else {
+        info!(path = %path.display(), "Config file not found, using defaults");
+        None
+    };
+    crustyclaw_config::AppConfig::load_layered(file, &source.set)
+        .await
+        .map_err(|e| anyhow::anyhow!(e))
 }
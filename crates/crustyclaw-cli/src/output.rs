@@ -0,0 +1,280 @@
+//! Machine-readable output — the global `--format table|json|toml` flag.
+//!
+//! `table` is the human-readable text each command has always printed.
+//! `json` and `toml` print one document built from the same types the
+//! daemon's IPC API returns, so scripts see the same field names whether
+//! they ask the CLI or the socket. Logs go to stderr in those formats, so
+//! stdout holds only the document.
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crustyclaw_core::ipc::{
+    PolicyEvalRequest, PolicyEvalResponse, StatusResponse, SupervisedTaskInfo,
+};
+
+/// How a command prints its result.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Aligned text for people.
+    #[default]
+    Table,
+    /// A JSON document.
+    Json,
+    /// A TOML document.
+    Toml,
+}
+
+impl OutputFormat {
+    /// Whether this is a machine-readable format.
+    pub fn is_structured(self) -> bool {
+        self != Self::Table
+    }
+
+    /// Render `value` as a JSON or TOML document; `None` for `table`.
+    pub fn render<T: Serialize>(self, value: &T) -> Result<Option<String>> {
+        Ok(match self {
+            Self::Table => None,
+            Self::Json => Some(serde_json::to_string_pretty(value)?),
+            Self::Toml => Some(
+                toml::to_string_pretty(value).map_err(|e| anyhow::anyhow!("TOML error: {e}"))?,
+            ),
+        })
+    }
+
+    /// Print `value` as a JSON or TOML document, returning `false` for
+    /// `table` so the caller prints its text instead.
+    pub fn print<T: Serialize>(self, value: &T) -> Result<bool> {
+        match self.render(value)? {
+            Some(document) => {
+                println!("{}", document.trim_end());
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+/// `crustyclaw status`.
+#[derive(Debug, Serialize)]
+pub struct StatusOutput {
+    pub running: bool,
+    /// The daemon's status, when it is running.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub daemon: Option<StatusResponse>,
+    /// Supervised tasks, when the daemon is new enough to list them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tasks: Option<Vec<SupervisedTaskInfo>>,
+    /// The PID file, when the daemon does not answer but one exists.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pid_file: Option<PidFileInfo>,
+}
+
+/// A PID file left without a daemon answering on the socket.
+#[derive(Debug, Serialize)]
+pub struct PidFileInfo {
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+    /// The process it names has exited.
+    pub stale: bool,
+}
+
+/// `crustyclaw policy`: the request and the daemon's answer to it.
+#[derive(Debug, Serialize)]
+pub struct PolicyOutput {
+    #[serde(flatten)]
+    pub request: PolicyEvalRequest,
+    #[serde(flatten)]
+    pub response: PolicyEvalResponse,
+}
+
+/// `crustyclaw secrets`. Never includes secret values.
+#[derive(Debug, Serialize)]
+pub struct SecretsOutput {
+    pub staging_dir: String,
+    pub secrets: Vec<SecretInfo>,
+}
+
+/// A configured secret, without its value.
+#[derive(Debug, Serialize)]
+pub struct SecretInfo {
+    pub name: String,
+    pub source: String,
+    pub inject_as: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inject_env: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inject_path: Option<String>,
+    pub description: String,
+}
+
+/// `crustyclaw isolation`.
+#[derive(Debug, Serialize)]
+pub struct IsolationOutput {
+    /// Backend named in the config.
+    pub configured_backend: String,
+    pub backend: String,
+    pub available: bool,
+    pub memory_mb: u64,
+    pub cpu_fraction: f64,
+    /// `0` means no timeout.
+    pub timeout_secs: u64,
+    pub network_policy: String,
+    pub max_concurrent: usize,
+    pub docker_image: String,
+    pub pull_images: bool,
+    pub strict_images: bool,
+    pub credential_proxy: bool,
+    pub trust_tiers: Vec<TrustTierInfo>,
+}
+
+/// How one trust tier is isolated.
+#[derive(Debug, Serialize)]
+pub struct TrustTierInfo {
+    pub tier: String,
+    pub required_level: String,
+    /// Backend configured for the tier, or `auto`.
+    pub configured_backend: String,
+    pub backend: String,
+    pub default: bool,
+}
+
+/// `crustyclaw whoami`.
+#[derive(Debug, Serialize)]
+pub struct WhoamiOutput {
+    pub identity: String,
+    pub roles: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub privileged: Option<bool>,
+    /// The role the checks below were evaluated for.
+    pub role: String,
+    pub checks: Vec<PermissionCheck>,
+}
+
+/// Whether the policy allows an action on a resource.
+#[derive(Debug, Serialize)]
+pub struct PermissionCheck {
+    pub action: String,
+    pub resource: String,
+    pub allowed: bool,
+}
+
+/// `crustyclaw policy simulate`.
+#[derive(Debug, Serialize)]
+pub struct SimulationOutput {
+    /// Path of the access matrix file.
+    pub matrix: String,
+    pub passed: usize,
+    pub failed: usize,
+    pub mismatches: Vec<SimulationMismatch>,
+}
+
+/// An access matrix check the policy does not satisfy.
+#[derive(Debug, Serialize)]
+pub struct SimulationMismatch {
+    /// Index into the matrix's `[[checks]]`.
+    pub index: usize,
+    pub role: String,
+    pub action: String,
+    pub resource: String,
+    /// `allow` or `deny`.
+    pub expected: String,
+    /// `allowed`, `denied`, or `no_match`.
+    pub actual: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule_id: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status() -> StatusOutput {
+        StatusOutput {
+            running: true,
+            daemon: Some(StatusResponse {
+                running: true,
+                version: "0.1.0".to_string(),
+                git_hash: "abc123".to_string(),
+                uptime_secs: 42,
+                listen_addr: "127.0.0.1".to_string(),
+                listen_port: 9100,
+                signal_enabled: false,
+                log_level: "info".to_string(),
+                isolation_backend: "docker".to_string(),
+                skills_count: 2,
+                plugins_count: 0,
+                pid: 1234,
+                warnings: Vec::new(),
+            }),
+            tasks: Some(vec![SupervisedTaskInfo {
+                name: "ipc".to_string(),
+                state: "running".to_string(),
+                restarts: 0,
+                last_error: None,
+                state_secs: 42,
+            }]),
+            pid_file: None,
+        }
+    }
+
+    #[test]
+    fn test_table_renders_nothing() {
+        assert!(OutputFormat::Table.render(&status()).unwrap().is_none());
+        assert!(!OutputFormat::Table.is_structured());
+    }
+
+    #[test]
+    fn test_json_and_toml_documents() {
+        let json = OutputFormat::Json.render(&status()).unwrap().unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["daemon"]["pid"], 1234);
+        assert_eq!(value["tasks"][0]["name"], "ipc");
+        assert!(value["tasks"][0].get("last_error").unwrap().is_null());
+
+        // TOML has no null: absent options are left out.
+        let toml = OutputFormat::Toml.render(&status()).unwrap().unwrap();
+        let value: toml::Table = toml.parse().unwrap();
+        assert_eq!(value["daemon"]["listen_port"].as_integer(), Some(9100));
+        assert!(value["tasks"][0].get("last_error").is_none());
+
+        let stopped = StatusOutput {
+            running: false,
+            daemon: None,
+            tasks: None,
+            pid_file: None,
+        };
+        assert_eq!(
+            OutputFormat::Json.render(&stopped).unwrap().unwrap(),
+            "{\n  \"running\": false\n}"
+        );
+    }
+
+    #[test]
+    fn test_policy_output_is_flat() {
+        let output = PolicyOutput {
+            request: PolicyEvalRequest {
+                role: "user".to_string(),
+                action: "read".to_string(),
+                resource: "secrets".to_string(),
+            },
+            response: PolicyEvalResponse {
+                decision: "denied".to_string(),
+                rule_count: 3,
+                rule_id: Some("no-secrets".to_string()),
+                reason: None,
+            },
+        };
+        let json = OutputFormat::Json.render(&output).unwrap().unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["role"], "user");
+        assert_eq!(value["decision"], "denied");
+        assert_eq!(value["rule_id"], "no-secrets");
+        assert!(value.get("reason").is_none());
+    }
+}
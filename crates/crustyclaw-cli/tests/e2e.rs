@@ -0,0 +1,250 @@
+//! End-to-end tests: a real daemon driven through the real CLI binary.
+//!
+//! Each test writes a config and a skill manifest into a temp directory,
+//! starts `crustyclaw start` as a child process, and exercises it with
+//! `status`, `skill run`, and `stop` over the IPC socket. This covers the
+//! config → daemon → IPC → skill → sandbox path across crates, which the
+//! unit tests only see piecewise.
+//!
+//! Built only with the `e2e` feature:
+//!
+//! ```text
+//! cargo test -p crustyclaw-cli --features e2e --test e2e
+//! ```
+//!
+//! The Docker test is skipped when no Docker daemon is reachable.
+
+#![cfg(feature = "e2e")]
+
+use std::path::{Path, PathBuf};
+use std::process::{Child, Stdio};
+use std::time::{Duration, Instant};
+
+use assert_cmd::Command;
+use assert_cmd::cargo::{cargo_bin, cargo_bin_cmd};
+use tempfile::TempDir;
+
+/// How long the daemon gets to open its socket or to exit after `stop`.
+const DAEMON_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// A daemon running from a temp directory.
+struct Harness {
+    dir: TempDir,
+    config: PathBuf,
+    daemon: Child,
+}
+
+impl Harness {
+    /// Write a config for `backend` with the `greet` and `fail` skills, then
+    /// start the daemon and wait for its socket.
+    fn start(backend: &str) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write_skill(
+            root,
+            "greet",
+            r#"["sh", "-c", "echo hello $CRUSTYCLAW_ARG_WHO"]"#,
+        );
+        write_skill(root, "fail", r#"["sh", "-c", "echo oops >&2; exit 3"]"#);
+
+        let config = root.join("crustyclaw.toml");
+        std::fs::write(
+            &config,
+            format!(
+                "[daemon]\n\
+                 socket_path = {socket:?}\n\
+                 state_dir = {state:?}\n\
+                 skills_dir = {skills:?}\n\
+                 \n\
+                 [isolation]\n\
+                 backend = {backend:?}\n\
+                 \n\
+                 [secrets]\n\
+                 staging_dir = {staging:?}\n",
+                socket = root.join("run/crustyclaw.sock").display().to_string(),
+                state = root.join("state").display().to_string(),
+                skills = root.join("skills").display().to_string(),
+                staging = root.join("staging").display().to_string(),
+            ),
+        )
+        .unwrap();
+
+        let daemon = std::process::Command::new(cargo_bin!("crustyclaw"))
+            .arg("--config")
+            .arg(&config)
+            .arg("start")
+            .env("RUST_BACKTRACE", "0")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let mut harness = Self {
+            dir,
+            config,
+            daemon,
+        };
+        harness.wait_for_socket();
+        harness
+    }
+
+    /// A CLI command against this daemon's config.
+    fn cli(&self) -> Command {
+        let mut cmd = cargo_bin_cmd!("crustyclaw");
+        cmd.arg("--config")
+            .arg(&self.config)
+            .env("RUST_BACKTRACE", "0")
+            .timeout(DAEMON_TIMEOUT);
+        cmd
+    }
+
+    fn wait_for_socket(&mut self) {
+        let socket = self.dir.path().join("run/crustyclaw.sock");
+        let started = Instant::now();
+        while !socket.exists() {
+            if let Some(status) = self.daemon.try_wait().unwrap() {
+                panic!("daemon exited during startup: {status}");
+            }
+            assert!(
+                started.elapsed() < DAEMON_TIMEOUT,
+                "daemon did not open {} in time",
+                socket.display()
+            );
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    /// Stop the daemon through the CLI and wait for the process to exit.
+    fn stop(mut self) {
+        self.cli().arg("stop").assert().success();
+        let started = Instant::now();
+        let status = loop {
+            if let Some(status) = self.daemon.try_wait().unwrap() {
+                break status;
+            }
+            assert!(
+                started.elapsed() < DAEMON_TIMEOUT,
+                "daemon did not exit after stop"
+            );
+            std::thread::sleep(Duration::from_millis(50));
+        };
+        assert!(status.success(), "daemon exited with {status}");
+        self.cli()
+            .arg("status")
+            .assert()
+            .success()
+            .stdout("Daemon: not running\n");
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        // Never leave a daemon behind when an assertion fails mid-test.
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
+
+fn write_skill(root: &Path, name: &str, command: &str) {
+    let dir = root.join("skills").join(name);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("skill.toml"),
+        format!(
+            "name = {name:?}\n\
+             description = \"e2e {name}\"\n\
+             command = {command}\n\
+             trust_tier = \"trusted\"\n"
+        ),
+    )
+    .unwrap();
+}
+
+fn stdout(cmd: &mut Command) -> String {
+    let output = cmd.assert().success().get_output().stdout.clone();
+    String::from_utf8(output).unwrap()
+}
+
+/// Run the full lifecycle against `backend`.
+fn lifecycle(backend: &str) {
+    let harness = Harness::start(backend);
+
+    let status = stdout(harness.cli().arg("status"));
+    assert!(status.starts_with("Daemon: running"), "{status}");
+    assert!(
+        status.contains(&format!("Isolation:  {backend}")),
+        "{status}"
+    );
+    assert!(status.contains("Skills:     2"), "{status}");
+
+    harness
+        .cli()
+        .args(["skill", "run", "greet", "--arg", "who=world"])
+        .assert()
+        .success()
+        .stdout("hello world\n");
+
+    let failed = harness
+        .cli()
+        .args(["skill", "run", "fail"])
+        .assert()
+        .code(3)
+        .get_output()
+        .stderr
+        .clone();
+    assert!(String::from_utf8_lossy(&failed).contains("oops"));
+
+    harness
+        .cli()
+        .args(["skill", "run", "missing"])
+        .assert()
+        .failure();
+
+    harness.stop();
+}
+
+#[test]
+fn test_noop_lifecycle() {
+    lifecycle("noop");
+}
+
+#[test]
+fn test_docker_lifecycle() {
+    let docker = std::process::Command::new("docker")
+        .arg("info")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    if !docker.is_ok_and(|s| s.success()) {
+        eprintln!("skipping: no reachable Docker daemon");
+        return;
+    }
+    lifecycle("docker");
+}
+
+#[test]
+fn test_cli_without_daemon() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("crustyclaw.toml");
+    std::fs::write(
+        &config,
+        format!(
+            "[daemon]\nsocket_path = {:?}\n",
+            dir.path().join("none.sock").display().to_string()
+        ),
+    )
+    .unwrap();
+
+    cargo_bin_cmd!("crustyclaw")
+        .arg("--config")
+        .arg(&config)
+        .arg("status")
+        .assert()
+        .success()
+        .stdout("Daemon: not running\n");
+    cargo_bin_cmd!("crustyclaw")
+        .arg("--config")
+        .arg(&config)
+        .args(["skill", "run", "greet"])
+        .assert()
+        .code(1);
+}
@@ -0,0 +1,262 @@
+//! Signed policy bundles — the `[policy]` section in a file of its own.
+//!
+//! With `policy.bundle` set, the rules, tests, and default effect come from
+//! that file instead of the main config:
+//!
+//! ```toml
+//! [policy]
+//! bundle = "policy.toml"
+//!
+//! [security]
+//! strict = true
+//!
+//! [security.trusted_keys]
+//! release = "RWRVIi7rHtkIeomkwzC1akZsNw4Jy1nvbcyvGpTKyWdXj1ZTf0NKmJ9U"
+//! ```
+//!
+//! The bundle holds the keys of a `[policy]` table at its top level. Its
+//! detached minisign signature, `<bundle>.minisig`, is verified against
+//! `[security.trusted_keys]` before the policy is applied (see
+//! [`crate::minisign`]). A bundle whose signature does not verify is always
+//! rejected; one without a signature is rejected when `security.strict` is
+//! set and loaded with a warning otherwise.
+//!
+//! Only [`AppConfig::load`] follows `policy.bundle`. The loaded bundle's
+//! path, digest, and signer are kept in [`AppConfig::policy_bundle`].
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::{AppConfig, ConfigError, PolicyConfig, SecurityConfig};
+
+/// Extension appended to a bundle's path to find its signature.
+pub const SIGNATURE_EXTENSION: &str = "minisig";
+
+/// The policy bundle a config was loaded with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PolicyBundle {
+    /// Where the bundle was read from.
+    pub path: PathBuf,
+    /// `sha256:<hex>` of the bundle file.
+    pub digest: String,
+    /// Name of the `[security.trusted_keys]` entry that signed the bundle,
+    /// or `None` if it is unsigned.
+    pub signer: Option<String>,
+}
+
+/// Path of the signature of the bundle at `path`.
+pub fn signature_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(SIGNATURE_EXTENSION);
+    PathBuf::from(name)
+}
+
+/// The `sha256:<hex>` digest of a bundle's contents.
+pub fn digest(bytes: &[u8]) -> String {
+    let hex: String = Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    format!("sha256:{hex}")
+}
+
+/// Replace the policy of `config` with its `policy.bundle`, if it has one,
+/// after checking the bundle's signature. Relative bundle paths are
+/// resolved against `base`.
+pub(crate) async fn apply(config: &mut AppConfig, base: &Path) -> Result<(), ConfigError> {
+    let Some(bundle) = config.policy.bundle.clone() else {
+        return Ok(());
+    };
+    if !config.policy.rules.is_empty() || !config.policy.tests.is_empty() {
+        return Err(ConfigError::Validation(
+            "policy.rules and policy.tests must be empty when policy.bundle is set".to_string(),
+        ));
+    }
+    let path = base.join(&bundle);
+    let bytes = tokio::fs::read(&path)
+        .await
+        .map_err(|e| in_file(&path, e.into()))?;
+    let sig_path = signature_path(&path);
+    let signature = match tokio::fs::read_to_string(&sig_path).await {
+        Ok(signature) => Some(signature),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(in_file(&sig_path, e.into())),
+    };
+
+    let signer = verify(&bytes, signature.as_deref(), &config.security)
+        .map_err(|e| in_file(&sig_path, e))?;
+    if signer.is_none() {
+        warn!(path = %path.display(), "Policy bundle is unsigned");
+    }
+    let policy = parse(&bytes).map_err(|e| in_file(&path, e))?;
+    config.policy = PolicyConfig {
+        bundle: Some(bundle),
+        ..policy
+    };
+    config.validate().map_err(|e| in_file(&path, e))?;
+    config.policy_bundle = Some(PolicyBundle {
+        path,
+        digest: digest(&bytes),
+        signer,
+    });
+    Ok(())
+}
+
+/// Check the signature of a bundle, returning the name of the trusted key
+/// that made it, or `None` for an unsigned bundle outside strict mode.
+fn verify(
+    bundle: &[u8],
+    signature: Option<&str>,
+    security: &SecurityConfig,
+) -> Result<Option<String>, ConfigError> {
+    let Some(signature) = signature else {
+        if security.strict {
+            return Err(ConfigError::Validation(
+                "not found; security.strict refuses unsigned policy bundles".to_string(),
+            ));
+        }
+        return Ok(None);
+    };
+    security
+        .verify(bundle, signature)
+        .map(Some)
+        .map_err(|e| ConfigError::Validation(e.to_string()))
+}
+
+/// Parse a bundle: the keys of a `[policy]` table.
+fn parse(bytes: &[u8]) -> Result<PolicyConfig, ConfigError> {
+    let text = std::str::from_utf8(bytes)
+        .map_err(|_| ConfigError::Validation("policy bundle is not UTF-8".to_string()))?;
+    let policy: PolicyConfig = toml::from_str(text)?;
+    if policy.bundle.is_some() {
+        return Err(ConfigError::Validation(
+            "a policy bundle may not set bundle".to_string(),
+        ));
+    }
+    Ok(policy)
+}
+
+fn in_file(path: &Path, source: ConfigError) -> ConfigError {
+    ConfigError::InFile {
+        path: path.to_path_buf(),
+        source: Box::new(source),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::minisign::tests::{MESSAGE, OTHER_KEY, PREHASHED_SIG, PUBLIC_KEY};
+
+    fn security(strict: bool, keys: &[(&str, &str)]) -> SecurityConfig {
+        SecurityConfig {
+            strict,
+            trusted_keys: keys
+                .iter()
+                .map(|(name, key)| (name.to_string(), key.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Write the test bundle (and its signature, if `signed`) and a main
+    /// config naming it, returning the config's path.
+    fn write_config(dir: &Path, signed: bool, security: &str) -> PathBuf {
+        std::fs::write(dir.join("policy.toml"), MESSAGE).unwrap();
+        if signed {
+            std::fs::write(dir.join("policy.toml.minisig"), PREHASHED_SIG).unwrap();
+        }
+        let config = dir.join("crustyclaw.toml");
+        std::fs::write(
+            &config,
+            format!("[policy]\nbundle = \"policy.toml\"\n\n{security}"),
+        )
+        .unwrap();
+        config
+    }
+
+    fn trusted(strict: bool) -> String {
+        let key = PUBLIC_KEY.lines().nth(1).unwrap();
+        format!("[security]\nstrict = {strict}\n\n[security.trusted_keys]\nrelease = \"{key}\"\n")
+    }
+
+    #[test]
+    fn test_verify() {
+        let sig = Some(PREHASHED_SIG);
+        let bundle = MESSAGE.as_bytes();
+        let both = security(true, &[("other", OTHER_KEY), ("release", PUBLIC_KEY)]);
+        assert_eq!(
+            verify(bundle, sig, &both).unwrap().as_deref(),
+            Some("release")
+        );
+
+        let err = verify(bundle, sig, &security(false, &[("other", OTHER_KEY)])).unwrap_err();
+        assert!(err.to_string().contains("not trusted"), "{err}");
+        let err = verify(b"tampered", sig, &both).unwrap_err();
+        assert!(err.to_string().contains("does not match"), "{err}");
+
+        assert_eq!(verify(bundle, None, &security(false, &[])).unwrap(), None);
+        let err = verify(bundle, None, &security(true, &[])).unwrap_err();
+        assert!(err.to_string().contains("security.strict"), "{err}");
+    }
+
+    #[test]
+    fn test_signature_path() {
+        assert_eq!(
+            signature_path(Path::new("/etc/crustyclaw/policy.toml")),
+            Path::new("/etc/crustyclaw/policy.toml.minisig")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_signed_bundle() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_config(dir.path(), true, &trusted(true));
+
+        let config = AppConfig::load(&path).await.unwrap();
+        assert_eq!(config.policy.bundle.as_deref(), Some("policy.toml"));
+        assert_eq!(config.policy.rules.len(), 1);
+        assert_eq!(config.policy.rules[0].role, "admin");
+        let bundle = config.policy_bundle.unwrap();
+        assert_eq!(bundle.path, dir.path().join("policy.toml"));
+        assert_eq!(bundle.digest, digest(MESSAGE.as_bytes()));
+        assert_eq!(bundle.signer.as_deref(), Some("release"));
+    }
+
+    #[tokio::test]
+    async fn test_load_rejects_bad_bundles() {
+        // Unsigned in strict mode.
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_config(dir.path(), false, &trusted(true));
+        let err = AppConfig::load(&path).await.unwrap_err();
+        assert!(err.to_string().contains("policy.toml.minisig"), "{err}");
+
+        // Unsigned is allowed otherwise.
+        let path = write_config(dir.path(), false, &trusted(false));
+        let config = AppConfig::load(&path).await.unwrap();
+        assert_eq!(config.policy_bundle.unwrap().signer, None);
+
+        // Modified after signing.
+        let path = write_config(dir.path(), true, &trusted(false));
+        std::fs::write(
+            dir.path().join("policy.toml"),
+            MESSAGE.replace("admin", "user"),
+        )
+        .unwrap();
+        let err = AppConfig::load(&path).await.unwrap_err();
+        assert!(err.to_string().contains("does not match"), "{err}");
+
+        // Inline rules alongside a bundle.
+        std::fs::write(
+            &path,
+            "[policy]\nbundle = \"policy.toml\"\n\n[[policy.rules]]\nrole = \"a\"\naction = \"b\"\nresource = \"c\"\neffect = \"allow\"\n",
+        )
+        .unwrap();
+        let err = AppConfig::load(&path).await.unwrap_err();
+        assert!(err.to_string().contains("must be empty"), "{err}");
+    }
+}
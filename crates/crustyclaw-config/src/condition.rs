@@ -0,0 +1,598 @@
+//! Boolean condition expressions for policy rules.
+//!
+//! A policy rule may carry a `when` clause that must evaluate to `true`
+//! against the request context for the rule to match:
+//!
+//! ```toml
+//! [[policy.rules]]
+//! role = "operator"
+//! action = "deploy"
+//! resource = "*"
+//! effect = "allow"
+//! when = 'time.hour >= 9 && time.hour < 17 && resource.tag == "prod"'
+//! ```
+//!
+//! ## Grammar
+//!
+//! ```text
+//! expr       := or
+//! or         := and ( "||" and )*
+//! and        := unary ( "&&" unary )*
+//! unary      := "!" unary | primary
+//! primary    := "(" expr ")" | comparison
+//! comparison := operand ( ( "==" | "!=" | "<" | "<=" | ">" | ">=" ) operand )?
+//! operand    := path | string | number | "true" | "false"
+//! path       := ident ( "." ident )*
+//! ```
+//!
+//! A bare operand is truthy when it is the boolean `true`. A comparison of
+//! values of different types evaluates to `false`. A comparison or bare
+//! operand that references a missing attribute is *unresolved*: `!` keeps
+//! it unresolved, and `&&` / `||` only resolve it when the other side
+//! decides the result (`false && x`, `true || x`). [`Condition::check`]
+//! reports an unresolved result as `None`, so callers can fail closed.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Errors from parsing a condition expression.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("invalid condition at offset {offset}: {message}")]
+pub struct ConditionError {
+    /// Byte offset in the source where the error was detected.
+    pub offset: usize,
+    /// Description of the problem.
+    pub message: String,
+}
+
+/// A value in the request context or a condition literal.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// A string value.
+    Str(String),
+    /// A numeric value.
+    Num(f64),
+    /// A boolean value.
+    Bool(bool),
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::Str(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::Str(s)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Self {
+        Value::Num(n)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(n: i64) -> Self {
+        Value::Num(n as f64)
+    }
+}
+
+impl From<u32> for Value {
+    fn from(n: u32) -> Self {
+        Value::Num(f64::from(n))
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+/// Attributes describing an access request, keyed by dotted path
+/// (e.g. `"resource.tag"`, `"time.hour"`).
+#[derive(Debug, Clone, Default)]
+pub struct RequestContext {
+    attrs: HashMap<String, Value>,
+}
+
+impl RequestContext {
+    /// Create an empty context.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a context pre-populated with the current UTC time:
+    /// `time.hour` (0–23), `time.minute` (0–59), and `time.weekday`
+    /// (0 = Sunday … 6 = Saturday).
+    pub fn now() -> Self {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Self::at_unix(secs)
+    }
+
+    /// Create a context with time attributes for the given Unix timestamp (UTC).
+    pub fn at_unix(secs: u64) -> Self {
+        let days = secs / 86_400;
+        let day_secs = secs % 86_400;
+        Self::new()
+            .with("time.hour", (day_secs / 3600) as i64)
+            .with("time.minute", ((day_secs % 3600) / 60) as i64)
+            // 1970-01-01 was a Thursday (weekday 4).
+            .with("time.weekday", ((days + 4) % 7) as i64)
+    }
+
+    /// Create a context for a request to `action` `resource` at the current
+    /// time: the attributes of [`now`](Self::now), plus `request.action`
+    /// and `request.resource`.
+    pub fn for_request(action: &str, resource: &str) -> Self {
+        Self::now()
+            .with("request.action", action)
+            .with("request.resource", resource)
+    }
+
+    /// Set an attribute, returning the context (builder style).
+    pub fn with(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.insert(key, value);
+        self
+    }
+
+    /// Set an attribute.
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<Value>) {
+        self.attrs.insert(key.into(), value.into());
+    }
+
+    /// Look up an attribute by dotted path.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.attrs.get(key)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Path(String),
+    Literal(Value),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Truthy(Operand),
+    Cmp(Operand, CmpOp, Operand),
+}
+
+/// A parsed, reusable condition expression.
+#[derive(Debug, Clone)]
+pub struct Condition {
+    source: String,
+    expr: Expr,
+}
+
+impl Condition {
+    /// Parse a condition expression.
+    pub fn parse(source: &str) -> Result<Self, ConditionError> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            end: source.len(),
+        };
+        let expr = parser.expr()?;
+        if let Some((offset, tok)) = parser.tokens.get(parser.pos) {
+            return Err(ConditionError {
+                offset: *offset,
+                message: format!("unexpected {tok}"),
+            });
+        }
+        Ok(Self {
+            source: source.to_string(),
+            expr,
+        })
+    }
+
+    /// The original expression text.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Evaluate the condition against a request context, treating an
+    /// unresolved result as `false`.
+    pub fn evaluate(&self, ctx: &RequestContext) -> bool {
+        self.check(ctx).unwrap_or(false)
+    }
+
+    /// Evaluate the condition against a request context, or `None` if it
+    /// depends on an attribute `ctx` does not have.
+    pub fn check(&self, ctx: &RequestContext) -> Option<bool> {
+        eval(&self.expr, ctx)
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+fn resolve<'a>(operand: &'a Operand, ctx: &'a RequestContext) -> Option<&'a Value> {
+    match operand {
+        Operand::Path(path) => ctx.get(path),
+        Operand::Literal(value) => Some(value),
+    }
+}
+
+/// Evaluate `expr`, or `None` if it is unresolved.
+fn eval(expr: &Expr, ctx: &RequestContext) -> Option<bool> {
+    match expr {
+        Expr::Or(a, b) => match (eval(a, ctx), eval(b, ctx)) {
+            (Some(true), _) | (_, Some(true)) => Some(true),
+            (Some(false), Some(false)) => Some(false),
+            _ => None,
+        },
+        Expr::And(a, b) => match (eval(a, ctx), eval(b, ctx)) {
+            (Some(false), _) | (_, Some(false)) => Some(false),
+            (Some(true), Some(true)) => Some(true),
+            _ => None,
+        },
+        Expr::Not(inner) => eval(inner, ctx).map(|b| !b),
+        Expr::Truthy(operand) => resolve(operand, ctx).map(|v| *v == Value::Bool(true)),
+        Expr::Cmp(lhs, op, rhs) => {
+            let (lhs, rhs) = (resolve(lhs, ctx)?, resolve(rhs, ctx)?);
+            let ordering = match (lhs, rhs) {
+                (Value::Str(a), Value::Str(b)) => a.partial_cmp(b),
+                (Value::Num(a), Value::Num(b)) => a.partial_cmp(b),
+                (Value::Bool(a), Value::Bool(b)) => a.partial_cmp(b),
+                _ => None,
+            };
+            let Some(ordering) = ordering else {
+                return Some(false);
+            };
+            Some(match op {
+                CmpOp::Eq => ordering.is_eq(),
+                CmpOp::Ne => ordering.is_ne(),
+                CmpOp::Lt => ordering.is_lt(),
+                CmpOp::Le => ordering.is_le(),
+                CmpOp::Gt => ordering.is_gt(),
+                CmpOp::Ge => ordering.is_ge(),
+            })
+        }
+    }
+}
+
+// ── Tokenizer ───────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Num(f64),
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+    Cmp(CmpOp),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(s) => write!(f, "identifier `{s}`"),
+            Token::Str(s) => write!(f, "string {s:?}"),
+            Token::Num(n) => write!(f, "number {n}"),
+            Token::And => write!(f, "`&&`"),
+            Token::Or => write!(f, "`||`"),
+            Token::Not => write!(f, "`!`"),
+            Token::LParen => write!(f, "`(`"),
+            Token::RParen => write!(f, "`)`"),
+            Token::Cmp(_) => write!(f, "comparison operator"),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, ConditionError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+
+    while let Some(&(offset, c)) = chars.peek() {
+        let err = |message: &str| ConditionError {
+            offset,
+            message: message.to_string(),
+        };
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' => {
+                chars.next();
+                tokens.push((
+                    offset,
+                    if c == '(' {
+                        Token::LParen
+                    } else {
+                        Token::RParen
+                    },
+                ));
+            }
+            '&' | '|' => {
+                chars.next();
+                if chars.next_if(|&(_, n)| n == c).is_none() {
+                    return Err(err(&format!("expected `{c}{c}`")));
+                }
+                tokens.push((offset, if c == '&' { Token::And } else { Token::Or }));
+            }
+            '!' | '=' | '<' | '>' => {
+                chars.next();
+                let has_eq = chars.next_if(|&(_, n)| n == '=').is_some();
+                let tok = match (c, has_eq) {
+                    ('!', false) => Token::Not,
+                    ('!', true) => Token::Cmp(CmpOp::Ne),
+                    ('=', true) => Token::Cmp(CmpOp::Eq),
+                    ('=', false) => return Err(err("expected `==`")),
+                    ('<', false) => Token::Cmp(CmpOp::Lt),
+                    ('<', true) => Token::Cmp(CmpOp::Le),
+                    ('>', false) => Token::Cmp(CmpOp::Gt),
+                    _ => Token::Cmp(CmpOp::Ge),
+                };
+                tokens.push((offset, tok));
+            }
+            '"' | '\'' => {
+                chars.next();
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, q)) if q == c => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, escaped)) => value.push(escaped),
+                            None => return Err(err("unterminated string")),
+                        },
+                        Some((_, ch)) => value.push(ch),
+                        None => return Err(err("unterminated string")),
+                    }
+                }
+                tokens.push((offset, Token::Str(value)));
+            }
+            c if c.is_ascii_digit() || c == '-' => {
+                let mut text = String::new();
+                text.push(c);
+                chars.next();
+                while let Some((_, d)) = chars.next_if(|&(_, d)| d.is_ascii_digit() || d == '.') {
+                    text.push(d);
+                }
+                let num = text
+                    .parse::<f64>()
+                    .map_err(|_| err(&format!("invalid number `{text}`")))?;
+                tokens.push((offset, Token::Num(num)));
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut ident = String::new();
+                while let Some((_, ch)) =
+                    chars.next_if(|&(_, ch)| ch.is_alphanumeric() || ch == '_' || ch == '.')
+                {
+                    ident.push(ch);
+                }
+                if ident.ends_with('.') || ident.contains("..") {
+                    return Err(err(&format!("invalid attribute path `{ident}`")));
+                }
+                tokens.push((offset, Token::Ident(ident)));
+            }
+            other => return Err(err(&format!("unexpected character `{other}`"))),
+        }
+    }
+
+    Ok(tokens)
+}
+
+// ── Parser ──────────────────────────────────────────────────────────────
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    /// Source length, reported as the offset of end-of-input errors.
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn advance(&mut self) -> Option<(usize, Token)> {
+        let tok = self.tokens.get(self.pos).cloned();
+        if tok.is_some() {
+            self.pos += 1;
+        }
+        tok
+    }
+
+    fn error_here(&self, message: impl Into<String>) -> ConditionError {
+        ConditionError {
+            offset: self.tokens.get(self.pos).map_or(self.end, |(o, _)| *o),
+            message: message.into(),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, ConditionError> {
+        let mut lhs = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.advance();
+            let rhs = self.and()?;
+            lhs = Expr::Or(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn and(&mut self) -> Result<Expr, ConditionError> {
+        let mut lhs = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.advance();
+            let rhs = self.unary()?;
+            lhs = Expr::And(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, ConditionError> {
+        if self.peek() == Some(&Token::Not) {
+            self.advance();
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, ConditionError> {
+        if self.peek() == Some(&Token::LParen) {
+            self.advance();
+            let inner = self.expr()?;
+            if self.peek() != Some(&Token::RParen) {
+                return Err(self.error_here("expected `)`"));
+            }
+            self.advance();
+            return Ok(inner);
+        }
+
+        let lhs = self.operand()?;
+        if let Some(Token::Cmp(op)) = self.peek().cloned() {
+            self.advance();
+            let rhs = self.operand()?;
+            return Ok(Expr::Cmp(lhs, op, rhs));
+        }
+        Ok(Expr::Truthy(lhs))
+    }
+
+    fn operand(&mut self) -> Result<Operand, ConditionError> {
+        match self.advance() {
+            Some((_, Token::Ident(name))) => Ok(match name.as_str() {
+                "true" => Operand::Literal(Value::Bool(true)),
+                "false" => Operand::Literal(Value::Bool(false)),
+                _ => Operand::Path(name),
+            }),
+            Some((_, Token::Str(s))) => Ok(Operand::Literal(Value::Str(s))),
+            Some((_, Token::Num(n))) => Ok(Operand::Literal(Value::Num(n))),
+            Some((offset, tok)) => Err(ConditionError {
+                offset,
+                message: format!("expected a value, found {tok}"),
+            }),
+            None => Err(self.error_here("unexpected end of expression")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(expr: &str, ctx: &RequestContext) -> bool {
+        Condition::parse(expr).unwrap().evaluate(ctx)
+    }
+
+    #[test]
+    fn test_comparisons() {
+        let ctx = RequestContext::new()
+            .with("time.hour", 10i64)
+            .with("resource.tag", "prod");
+
+        assert!(check("time.hour >= 9", &ctx));
+        assert!(check("time.hour < 17", &ctx));
+        assert!(!check("time.hour > 10", &ctx));
+        assert!(check("time.hour <= 10", &ctx));
+        assert!(check("resource.tag == \"prod\"", &ctx));
+        assert!(check("resource.tag != 'dev'", &ctx));
+    }
+
+    #[test]
+    fn test_boolean_operators_and_precedence() {
+        let ctx = RequestContext::new()
+            .with("time.hour", 10i64)
+            .with("resource.tag", "prod")
+            .with("request.mfa", true);
+
+        assert!(check("time.hour >= 9 && resource.tag == \"prod\"", &ctx));
+        assert!(check("false || request.mfa", &ctx));
+        // && binds tighter than ||
+        assert!(check("true || false && false", &ctx));
+        assert!(!check("(true || false) && false", &ctx));
+        assert!(check("!(resource.tag == \"dev\")", &ctx));
+        assert!(!check("!request.mfa", &ctx));
+    }
+
+    #[test]
+    fn test_missing_and_mismatched_attributes_are_false() {
+        let ctx = RequestContext::new().with("time.hour", 10i64);
+        assert!(!check("resource.tag == \"prod\"", &ctx));
+        assert!(!check("resource.tag != \"prod\"", &ctx));
+        assert!(!check("time.hour == \"10\"", &ctx));
+        assert!(!check("request.mfa", &ctx));
+    }
+
+    #[test]
+    fn test_missing_attributes_are_unresolved() {
+        let ctx = RequestContext::new().with("time.hour", 10i64);
+        let resolve = |expr: &str| Condition::parse(expr).unwrap().check(&ctx);
+        assert_eq!(resolve("resource.tag == \"prod\""), None);
+        assert_eq!(resolve("!(resource.tag == \"prod\")"), None);
+        assert_eq!(resolve("!request.mfa"), None);
+        assert_eq!(resolve("time.hour == \"10\""), Some(false));
+        // A resolved side that decides the result resolves the whole.
+        assert_eq!(resolve("time.hour > 12 && request.mfa"), Some(false));
+        assert_eq!(resolve("time.hour < 12 || request.mfa"), Some(true));
+        assert_eq!(resolve("time.hour < 12 && request.mfa"), None);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for (expr, offset) in [
+            ("", 0),
+            ("time.hour >=", 12),
+            ("a & b", 2),
+            ("a = b", 2),
+            ("(a == 1", 7),
+            ("a == 1 b", 7),
+            ("\"unterminated", 0),
+            ("a.b. == 1", 0),
+            ("a == #", 5),
+        ] {
+            let err = Condition::parse(expr).unwrap_err();
+            assert_eq!(err.offset, offset, "offset for {expr:?}: {err}");
+        }
+    }
+
+    #[test]
+    fn test_source_round_trip() {
+        let cond = Condition::parse("time.hour >= 9").unwrap();
+        assert_eq!(cond.source(), "time.hour >= 9");
+        assert_eq!(cond.to_string(), "time.hour >= 9");
+    }
+
+    #[test]
+    fn test_time_context() {
+        // 2024-01-01T13:45:00Z was a Monday.
+        let ctx = RequestContext::at_unix(1_704_116_700);
+        assert_eq!(ctx.get("time.hour"), Some(&Value::Num(13.0)));
+        assert_eq!(ctx.get("time.minute"), Some(&Value::Num(45.0)));
+        assert_eq!(ctx.get("time.weekday"), Some(&Value::Num(1.0)));
+
+        let ctx = RequestContext::for_request("write", "config");
+        assert!(ctx.get("time.hour").is_some());
+        assert_eq!(ctx.get("request.action"), Some(&Value::from("write")));
+        assert_eq!(ctx.get("request.resource"), Some(&Value::from("config")));
+    }
+}
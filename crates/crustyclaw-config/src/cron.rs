@@ -0,0 +1,371 @@
+//! Cron expressions for `[[schedules]]`.
+//!
+//! The classic five-field syntax, evaluated in UTC:
+//!
+//! ```text
+//! ┌───────── minute        0-59
+//! │ ┌─────── hour          0-23
+//! │ │ ┌───── day of month  1-31
+//! │ │ │ ┌─── month         1-12 or jan-dec
+//! │ │ │ │ ┌─ day of week   0-7 or sun-sat (0 and 7 are Sunday)
+//! * * * * *
+//! ```
+//!
+//! Each field is a comma-separated list of `*`, `n`, or `a-b`, optionally
+//! followed by `/step`. A single value with a step (`5/15`) runs from that
+//! value to the end of the field's range. The macros `@yearly`
+//! (`@annually`), `@monthly`, `@weekly`, `@daily` (`@midnight`), and
+//! `@hourly` are also accepted.
+//!
+//! As in vixie cron, when both day of month and day of week are restricted
+//! a day matches if *either* does: `0 12 1 * mon` fires on the first of
+//! every month and on every Monday.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Errors from parsing a cron expression.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("invalid cron expression {expr:?}: {message}")]
+pub struct CronError {
+    /// The expression as written.
+    pub expr: String,
+    /// Description of the problem.
+    pub message: String,
+}
+
+/// A parsed cron expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    source: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+/// Days scanned before giving up; covers the eight-year gap between leap
+/// days around 2100.
+const MAX_SCAN_DAYS: u64 = 366 * 9;
+
+const MONTH_NAMES: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+const WEEKDAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// Longest length of each month, allowing for leap years.
+const MONTH_DAYS: [u32; 12] = [31, 29, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+
+impl CronSchedule {
+    /// Parse a five-field expression or `@` macro.
+    pub fn parse(expr: &str) -> Result<Self, CronError> {
+        let err = |message: String| CronError {
+            expr: expr.to_string(),
+            message,
+        };
+        let trimmed = expr.trim();
+        let expanded = match trimmed.to_ascii_lowercase().as_str() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            other if other.starts_with('@') => {
+                return Err(err(format!("unknown macro {trimmed:?}")));
+            }
+            _ => trimmed,
+        };
+
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(err(format!("expected 5 fields, got {}", fields.len())));
+        };
+
+        let minutes = parse_field(minute, 0, 59, &[]).map_err(|m| err(format!("minute: {m}")))?;
+        let hours = parse_field(hour, 0, 23, &[]).map_err(|m| err(format!("hour: {m}")))?;
+        let days = parse_field(day, 1, 31, &[]).map_err(|m| err(format!("day of month: {m}")))?;
+        let months =
+            parse_field(month, 1, 12, &MONTH_NAMES).map_err(|m| err(format!("month: {m}")))?;
+        let mut weekdays = parse_field(weekday, 0, 7, &WEEKDAY_NAMES)
+            .map_err(|m| err(format!("day of week: {m}")))?;
+        // 7 is an alias for Sunday.
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays & !(1 << 7)) | 1;
+        }
+
+        let schedule = Self {
+            source: trimmed.to_string(),
+            minutes,
+            hours,
+            days,
+            months,
+            weekdays,
+            any_day: day.starts_with('*'),
+            any_weekday: weekday.starts_with('*'),
+        };
+
+        // With only the day of month restricted, some selected day must
+        // exist in some selected month, or the schedule never fires.
+        if schedule.any_weekday
+            && !(1..=12u32).any(|m| {
+                schedule.months & (1 << m) != 0
+                    && (1..=MONTH_DAYS[m as usize - 1]).any(|d| schedule.days & (1 << d) != 0)
+            })
+        {
+            return Err(err("no selected day exists in the selected months".into()));
+        }
+        Ok(schedule)
+    }
+
+    /// The expression as written.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// The first matching minute strictly after `unix_secs`, as Unix seconds.
+    ///
+    /// Returns `None` only if nothing matches within the next nine years.
+    pub fn next_after(&self, unix_secs: u64) -> Option<u64> {
+        let start = unix_secs / 60 + 1;
+        let first_day = start / 1440;
+        for day in first_day..first_day + MAX_SCAN_DAYS {
+            let from_minute = if day == first_day {
+                (start % 1440) as u32
+            } else {
+                0
+            };
+            if self.matches_day(day)
+                && let Some(minute) = self.first_minute_from(from_minute)
+            {
+                return Some(day * 86_400 + u64::from(minute) * 60);
+            }
+        }
+        None
+    }
+
+    fn matches_day(&self, days_since_epoch: u64) -> bool {
+        let (_, month, day) = civil_from_days(days_since_epoch);
+        if self.months & (1 << month) == 0 {
+            return false;
+        }
+        // 1970-01-01 was a Thursday (weekday 4).
+        let weekday = (days_since_epoch + 4) % 7;
+        let day_ok = self.days & (1 << day) != 0;
+        let weekday_ok = self.weekdays & (1 << weekday) != 0;
+        if self.any_day || self.any_weekday {
+            day_ok && weekday_ok
+        } else {
+            day_ok || weekday_ok
+        }
+    }
+
+    /// First selected minute of the day at or after `from` (minutes since
+    /// midnight).
+    fn first_minute_from(&self, from: u32) -> Option<u32> {
+        (from / 60..24)
+            .filter(|h| self.hours & (1 << h) != 0)
+            .find_map(|h| {
+                let first = if h == from / 60 { from % 60 } else { 0 };
+                (first..60)
+                    .find(|m| self.minutes & (1 << m) != 0)
+                    .map(|m| h * 60 + m)
+            })
+    }
+}
+
+impl FromStr for CronSchedule {
+    type Err = CronError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for CronSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// Parse one field into a bitmask of selected values.
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let mut mask = 0u64;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step.parse().map_err(|_| format!("invalid step {step:?}"))?;
+                if step == 0 {
+                    return Err("step must be non-zero".into());
+                }
+                (range, Some(step))
+            }
+            None => (item, None),
+        };
+
+        let (lo, hi) = if range == "*" {
+            (min, max)
+        } else if let Some((a, b)) = range.split_once('-') {
+            (
+                parse_value(a, min, max, names)?,
+                parse_value(b, min, max, names)?,
+            )
+        } else {
+            let value = parse_value(range, min, max, names)?;
+            (value, if step.is_some() { max } else { value })
+        };
+        if lo > hi {
+            return Err(format!("range {range:?} is backwards"));
+        }
+
+        for value in (lo..=hi).step_by(step.unwrap_or(1) as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+fn parse_value(text: &str, min: u32, max: u32, names: &[&str]) -> Result<u32, String> {
+    let lower = text.to_ascii_lowercase();
+    if let Some(index) = names.iter().position(|name| *name == lower) {
+        // Month names start at 1, weekday names at 0.
+        return Ok(index as u32 + min);
+    }
+    let value: u32 = text
+        .parse()
+        .map_err(|_| format!("invalid value {text:?}"))?;
+    if !(min..=max).contains(&value) {
+        return Err(format!("{value} is outside {min}-{max}"));
+    }
+    Ok(value)
+}
+
+/// Convert days since the Unix epoch to a (year, month, day) civil date.
+fn civil_from_days(days: u64) -> (u64, u32, u32) {
+    // Howard Hinnant's `civil_from_days`, restricted to dates after 1970.
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = era * 400 + yoe + u64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2024-01-01 13:45:00 UTC, a Monday.
+    const NEW_YEAR: u64 = 1_704_116_700;
+
+    fn next(expr: &str, after: u64) -> u64 {
+        CronSchedule::parse(expr)
+            .unwrap()
+            .next_after(after)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_every_quarter_hour() {
+        assert_eq!(next("*/15 * * * *", NEW_YEAR), 1_704_117_600);
+        assert_eq!(next("*/15 * * * *", NEW_YEAR + 30), 1_704_117_600);
+        assert_eq!(next("0,30 * * * *", NEW_YEAR), 1_704_117_600);
+    }
+
+    #[test]
+    fn test_weekdays_at_nine() {
+        // Tuesday 2024-01-02 09:00.
+        assert_eq!(next("0 9 * * 1-5", NEW_YEAR), 1_704_186_000);
+        assert_eq!(next("0 9 * * mon-fri", NEW_YEAR), 1_704_186_000);
+        // Saturday 2024-01-06 09:00.
+        assert_eq!(next("0 9 * * sat", NEW_YEAR), 1_704_531_600);
+    }
+
+    #[test]
+    fn test_sunday_is_zero_or_seven() {
+        let sunday = next("0 0 * * 0", NEW_YEAR);
+        assert_eq!(sunday, next("0 0 * * 7", NEW_YEAR));
+        assert_eq!(sunday, next("@weekly", NEW_YEAR));
+        // Sunday 2024-01-07 00:00.
+        assert_eq!(sunday, 1_704_585_600);
+    }
+
+    #[test]
+    fn test_leap_day() {
+        assert_eq!(next("0 0 29 2 *", NEW_YEAR), 1_709_164_800);
+        // The next one is 2028-02-29.
+        assert_eq!(next("0 0 29 2 *", 1_709_164_800), 1_835_395_200);
+    }
+
+    #[test]
+    fn test_day_of_month_or_weekday() {
+        // Monday 2024-01-08 comes before Thursday 2024-02-01.
+        assert_eq!(next("0 12 1 * mon", NEW_YEAR), 1_704_715_200);
+        // With the weekday unrestricted only the 1st matches.
+        assert_eq!(next("0 12 1 * *", NEW_YEAR), 1_706_788_800);
+    }
+
+    #[test]
+    fn test_macros() {
+        assert_eq!(next("@yearly", NEW_YEAR), 1_735_689_600);
+        assert_eq!(next("@annually", NEW_YEAR), 1_735_689_600);
+        assert_eq!(next("@daily", NEW_YEAR), 1_704_153_600);
+        assert_eq!(next("@hourly", NEW_YEAR), 1_704_117_600);
+        assert_eq!(next("@monthly", NEW_YEAR), 1_706_745_600);
+    }
+
+    #[test]
+    fn test_value_with_step_runs_to_end() {
+        let schedule = CronSchedule::parse("5/20 * * * *").unwrap();
+        // 13:45 → 14:05 → 14:25.
+        assert_eq!(schedule.next_after(NEW_YEAR), Some(NEW_YEAR + 20 * 60));
+        assert_eq!(
+            schedule.next_after(NEW_YEAR + 20 * 60),
+            Some(NEW_YEAR + 40 * 60)
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for expr in [
+            "",
+            "* * * *",
+            "* * * * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "x * * * *",
+            "@fortnightly",
+            "0 0 30 feb *",
+        ] {
+            assert!(CronSchedule::parse(expr).is_err(), "{expr:?} should fail");
+        }
+        let err = CronSchedule::parse("61 * * * *").unwrap_err();
+        assert!(err.to_string().contains("minute"));
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        let schedule: CronSchedule = " 0 9 * * mon ".parse().unwrap();
+        assert_eq!(schedule.to_string(), "0 9 * * mon");
+        assert_eq!(schedule.as_str(), "0 9 * * mon");
+    }
+
+    #[test]
+    fn test_civil_from_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(19_723), (2024, 1, 1));
+        assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+    }
+}
@@ -0,0 +1,269 @@
+//! Config includes — splitting one configuration across several files.
+//!
+//! The main config file may list other files to merge into it:
+//!
+//! ```toml
+//! include = ["policies/*.toml", "secrets.local.toml"]
+//!
+//! [daemon]
+//! listen_port = 9100
+//! ```
+//!
+//! Entries are paths relative to the main file's directory (or absolute).
+//! The last path component may contain `*` and `?` wildcards; wildcards in a
+//! directory component are also honoured, but `**` is not supported. A glob
+//! that matches nothing is skipped, while a plain path must exist.
+//!
+//! Files are merged in a fixed order: the main file first, then each entry
+//! in the order listed, with the matches of a glob sorted by path. A file
+//! matched more than once is merged only at its first position. See
+//! [`crate::layers`] for how values from different files combine. Included
+//! files may not `include` further files.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::ConfigError;
+use crate::layers::ConfigFile;
+
+/// Key listing the files to include.
+pub const INCLUDE_KEY: &str = "include";
+
+/// Read the config file at `path` followed by every file it includes.
+///
+/// Errors in an included file name that file.
+pub async fn read_files(path: &Path) -> Result<Vec<ConfigFile>, ConfigError> {
+    let main = ConfigFile::parse(path, &tokio::fs::read_to_string(path).await?)?;
+    let patterns = include_patterns(&main).map_err(|e| in_file(path, e))?;
+    let base = path.parent().unwrap_or(Path::new(""));
+
+    let mut files = vec![main];
+    for pattern in patterns {
+        let paths = expand(base, &pattern)
+            .await
+            .map_err(|e| in_file(path, ConfigError::Io(e)))?;
+        for include in paths {
+            if files.iter().any(|f| f.path == include) {
+                continue;
+            }
+            let file = read_include(&include)
+                .await
+                .map_err(|e| in_file(&include, e))?;
+            files.push(file);
+        }
+    }
+    Ok(files)
+}
+
+async fn read_include(path: &Path) -> Result<ConfigFile, ConfigError> {
+    let file = ConfigFile::parse(path, &tokio::fs::read_to_string(path).await?)?;
+    if file.table.contains_key(INCLUDE_KEY) {
+        return Err(ConfigError::Validation(
+            "include is only allowed in the main config file".to_string(),
+        ));
+    }
+    Ok(file)
+}
+
+fn in_file(path: &Path, source: ConfigError) -> ConfigError {
+    ConfigError::InFile {
+        path: path.to_path_buf(),
+        source: Box::new(source),
+    }
+}
+
+/// The `include` entries of `file`.
+fn include_patterns(file: &ConfigFile) -> Result<Vec<String>, ConfigError> {
+    let Some(value) = file.table.get(INCLUDE_KEY) else {
+        return Ok(Vec::new());
+    };
+    let invalid =
+        || ConfigError::Validation("include must be an array of file paths or globs".to_string());
+    value
+        .as_array()
+        .ok_or_else(invalid)?
+        .iter()
+        .map(|entry| entry.as_str().map(str::to_string).ok_or_else(invalid))
+        .collect()
+}
+
+/// The files matching `pattern`, relative to `base`, sorted by path.
+///
+/// A pattern without wildcards is returned as is, whether or not it exists.
+async fn expand(base: &Path, pattern: &str) -> io::Result<Vec<PathBuf>> {
+    let full = base.join(pattern);
+    if !has_wildcard(pattern) {
+        return Ok(vec![full]);
+    }
+
+    let mut candidates = vec![PathBuf::new()];
+    for component in full.components() {
+        let part = component.as_os_str().to_string_lossy();
+        if !has_wildcard(&part) {
+            for candidate in &mut candidates {
+                candidate.push(component);
+            }
+            continue;
+        }
+        let mut matched = Vec::new();
+        for dir in candidates {
+            let listing = if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir.as_path()
+            };
+            let mut entries = match tokio::fs::read_dir(listing).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            while let Some(entry) = entries.next_entry().await? {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                // Like a shell, `*` does not match hidden files.
+                let hidden = name.starts_with('.') && !part.starts_with('.');
+                if !hidden && wildcard_match(&part, &name) {
+                    matched.push(dir.join(name));
+                }
+            }
+        }
+        candidates = matched;
+    }
+
+    let mut files = Vec::new();
+    for candidate in candidates {
+        if tokio::fs::metadata(&candidate)
+            .await
+            .is_ok_and(|m| m.is_file())
+        {
+            files.push(candidate);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn has_wildcard(s: &str) -> bool {
+    s.contains(['*', '?'])
+}
+
+/// Match `name` against a pattern where `*` matches any run of characters
+/// and `?` any one character.
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    fn matches(p: &[char], n: &[char]) -> bool {
+        match p.split_first() {
+            None => n.is_empty(),
+            Some(('*', rest)) => (0..=n.len()).any(|i| matches(rest, &n[i..])),
+            Some(('?', rest)) => !n.is_empty() && matches(rest, &n[1..]),
+            Some((c, rest)) => n.first() == Some(c) && matches(rest, &n[1..]),
+        }
+    }
+    let p: Vec<char> = pattern.chars().collect();
+    let n: Vec<char> = name.chars().collect();
+    matches(&p, &n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    async fn write(dir: &Path, name: &str, text: &str) {
+        let path = dir.join(name);
+        tokio::fs::create_dir_all(path.parent().unwrap())
+            .await
+            .unwrap();
+        tokio::fs::write(path, text).await.unwrap();
+    }
+
+    fn names(files: &[ConfigFile], dir: &Path) -> Vec<String> {
+        files
+            .iter()
+            .map(|f| {
+                f.path
+                    .strip_prefix(dir)
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*.toml", "admin.toml"));
+        assert!(wildcard_match("rules-?.toml", "rules-1.toml"));
+        assert!(wildcard_match("*", ""));
+        assert!(!wildcard_match("*.toml", "admin.toml.bak"));
+        assert!(!wildcard_match("rules-?.toml", "rules-10.toml"));
+    }
+
+    #[tokio::test]
+    async fn test_read_files_order() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path();
+        write(
+            dir,
+            "crustyclaw.toml",
+            "include = [\"policies/*.toml\", \"local.toml\", \"policies/a.toml\"]\n",
+        )
+        .await;
+        write(dir, "policies/b.toml", "").await;
+        write(dir, "policies/a.toml", "").await;
+        write(dir, "policies/.hidden.toml", "").await;
+        write(dir, "policies/notes.md", "").await;
+        write(dir, "local.toml", "").await;
+
+        let files = read_files(&dir.join("crustyclaw.toml")).await.unwrap();
+        assert_eq!(
+            names(&files, dir),
+            [
+                "crustyclaw.toml",
+                "policies/a.toml",
+                "policies/b.toml",
+                "local.toml"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_files_errors() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path();
+        let main = dir.join("crustyclaw.toml");
+
+        // A glob may match nothing; a plain path must exist.
+        write(dir, "crustyclaw.toml", "include = [\"none/*.toml\"]\n").await;
+        assert_eq!(read_files(&main).await.unwrap().len(), 1);
+
+        for (include, text, culprit, message) in [
+            ("missing.toml", None, "missing.toml", "failed to read"),
+            ("bad.toml", Some("[[["), "bad.toml", "failed to parse"),
+            (
+                "nested.toml",
+                Some("include = [\"x.toml\"]\n"),
+                "nested.toml",
+                "only allowed in the main config file",
+            ),
+        ] {
+            write(
+                dir,
+                "crustyclaw.toml",
+                &format!("include = [{include:?}]\n"),
+            )
+            .await;
+            if let Some(text) = text {
+                write(dir, include, text).await;
+            }
+            let err = read_files(&main).await.unwrap_err();
+            let ConfigError::InFile { path, .. } = &err else {
+                panic!("{include}: {err}");
+            };
+            assert_eq!(path, &dir.join(culprit));
+            assert!(err.to_string().contains(message), "{err}");
+        }
+
+        write(dir, "crustyclaw.toml", "include = \"local.toml\"\n").await;
+        let err = read_files(&main).await.unwrap_err();
+        assert!(err.to_string().contains("must be an array"), "{err}");
+    }
+}
@@ -0,0 +1,671 @@
+//! Layered configuration — defaults, the TOML file, environment variables,
+//! and `--set` flags.
+//!
+//! Each layer overrides the one before it:
+//!
+//! ```text
+//! --set flag  >  CRUSTYCLAW__* env var  >  config file  >  built-in default
+//! ```
+//!
+//! The config file layer is itself the main file merged with the files it
+//! `include`s (see [`crate::include`]): tables merge key by key, arrays of
+//! tables (`[[policy.rules]]`, `[[secrets.entries]]`, …) are concatenated,
+//! and any other value is replaced by the later file.
+//!
+//! An environment variable names a key by its path, upper-cased, with `__`
+//! between segments and the `CRUSTYCLAW__` prefix:
+//!
+//! ```text
+//! CRUSTYCLAW__DAEMON__LISTEN_PORT=8080          # [daemon] listen_port = 8080
+//! CRUSTYCLAW__ISOLATION__BACKEND=docker         # [isolation] backend = "docker"
+//! CRUSTYCLAW__DAEMON__TLS__ENABLED=true         # [daemon.tls] enabled = true
+//! ```
+//!
+//! Values take the type of the key they replace: a string key gets the raw
+//! text, while numbers and booleans are parsed. A key with no default (an
+//! unset optional, or a new map entry) takes the value as a TOML literal if
+//! it parses as one, and as a string otherwise; arrays and tables are
+//! always written as TOML literals (`'["a", "b"]'`, `'{ enabled = true }'`).
+//!
+//! [`ConfigOrigins`] records which layer each value came from, for
+//! `crustyclaw config --show --origin`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use toml::{Table, Value};
+
+use crate::{AppConfig, ConfigError};
+
+/// Prefix of environment variables that override config keys.
+pub const ENV_PREFIX: &str = "CRUSTYCLAW__";
+
+/// Where a config value came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    /// The built-in default.
+    Default,
+    /// The named config file: the main file or one it includes.
+    File(PathBuf),
+    /// The named environment variable.
+    Env(String),
+    /// A `--set` command-line flag.
+    Flag,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => f.write_str("default"),
+            Self::File(path) => write!(f, "file {}", path.display()),
+            Self::Env(var) => write!(f, "env {var}"),
+            Self::Flag => f.write_str("flag --set"),
+        }
+    }
+}
+
+/// One key set by an environment variable or `--set` flag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Override {
+    /// Key path, e.g. `["daemon", "listen_port"]`.
+    pub path: Vec<String>,
+    /// The value as given.
+    pub value: String,
+    /// [`Origin::Env`] or [`Origin::Flag`].
+    pub origin: Origin,
+}
+
+impl Override {
+    /// The dotted key, e.g. `daemon.listen_port`.
+    pub fn key(&self) -> String {
+        self.path.join(".")
+    }
+
+    /// Parse a `--set` flag: `daemon.listen_port=8080`.
+    pub fn parse_flag(flag: &str) -> Result<Self, ConfigError> {
+        let (key, value) = flag
+            .split_once('=')
+            .ok_or_else(|| ConfigError::Validation(format!("--set {flag:?} must be KEY=VALUE")))?;
+        let path: Vec<String> = key.trim().split('.').map(str::to_string).collect();
+        if path.iter().any(|s| s.is_empty()) {
+            return Err(ConfigError::Validation(format!(
+                "--set {flag:?} has an empty key segment"
+            )));
+        }
+        Ok(Self {
+            path,
+            value: value.to_string(),
+            origin: Origin::Flag,
+        })
+    }
+
+    /// The overrides in `vars` (e.g. `std::env::vars()`), sorted by
+    /// variable name. Variables without the [`ENV_PREFIX`] are ignored.
+    pub fn from_env(
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Vec<Self>, ConfigError> {
+        let mut overrides: Vec<Self> = vars
+            .into_iter()
+            .filter_map(|(var, value)| {
+                let key = var.strip_prefix(ENV_PREFIX)?.to_string();
+                Some((var, key, value))
+            })
+            .map(|(var, key, value)| {
+                let path: Vec<String> = key.split("__").map(str::to_ascii_lowercase).collect();
+                if path.iter().any(|s| s.is_empty()) {
+                    return Err(ConfigError::Validation(format!(
+                        "{var} does not name a config key (use {ENV_PREFIX}SECTION__KEY)"
+                    )));
+                }
+                Ok(Self {
+                    path,
+                    value,
+                    origin: Origin::Env(var),
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        overrides.sort_by_key(Override::source);
+        Ok(overrides)
+    }
+
+    /// How the override was given, for error messages.
+    fn source(&self) -> String {
+        match &self.origin {
+            Origin::Env(var) => var.clone(),
+            _ => format!("--set {}", self.key()),
+        }
+    }
+}
+
+/// A parsed config file.
+#[derive(Debug, Clone)]
+pub struct ConfigFile {
+    /// Where it was read from.
+    pub path: PathBuf,
+    /// Its contents.
+    pub table: Table,
+}
+
+impl ConfigFile {
+    /// Parse `text`, read from `path`.
+    pub fn parse(path: impl Into<PathBuf>, text: &str) -> Result<Self, ConfigError> {
+        Ok(Self {
+            path: path.into(),
+            table: toml::from_str(text)?,
+        })
+    }
+}
+
+/// Which layer each config value came from.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigOrigins {
+    /// Leaf keys (and array elements) by the file that set them.
+    files: BTreeMap<String, PathBuf>,
+    /// Overrides in the order they were applied.
+    overrides: Vec<(String, Origin)>,
+}
+
+impl ConfigOrigins {
+    /// The origin of the value at `key`, e.g. `daemon.listen_port` or
+    /// `policy.rules[2].role`.
+    ///
+    /// An override of a parent table (e.g. `daemon.tls`) counts for every
+    /// key beneath it.
+    pub fn origin(&self, key: &str) -> Origin {
+        let covers = |prefix: &str| {
+            key == prefix
+                || key
+                    .strip_prefix(prefix)
+                    .is_some_and(|rest| rest.starts_with(['.', '[']))
+        };
+        if let Some((_, origin)) = self.overrides.iter().rev().find(|(k, _)| covers(k)) {
+            return origin.clone();
+        }
+        // The key itself, then each enclosing table or array element.
+        let mut candidate = key;
+        loop {
+            if let Some(path) = self.files.get(candidate) {
+                return Origin::File(path.clone());
+            }
+            match candidate.rfind(['.', '[']) {
+                Some(end) => candidate = &candidate[..end],
+                None => break,
+            }
+        }
+        // A table or array element whose keys came from a file.
+        let beneath = |k: &str| {
+            k.strip_prefix(key)
+                .is_some_and(|rest| rest.starts_with(['.', '[']))
+        };
+        match self.files.iter().find(|(k, _)| beneath(k)) {
+            Some((_, path)) => Origin::File(path.clone()),
+            None => Origin::Default,
+        }
+    }
+
+    /// Attach the file that set the offending key to a validation error.
+    ///
+    /// Validation messages start with the key they are about
+    /// (`policy.rules[3].effect must be …`); errors about keys that came
+    /// from elsewhere are returned unchanged.
+    fn attribute(&self, err: ConfigError) -> ConfigError {
+        let ConfigError::Validation(message) = &err else {
+            return err;
+        };
+        let key = message
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .trim_end_matches([':', ',']);
+        match self.origin(key) {
+            Origin::File(path) => ConfigError::InFile {
+                path,
+                source: Box::new(err),
+            },
+            _ => err,
+        }
+    }
+
+    /// Record every leaf of `value` at `key` as set by `file`, replacing
+    /// whatever was recorded beneath `key` before.
+    fn record(&mut self, key: &str, value: &Value, file: &Path) {
+        self.files.retain(|k, _| {
+            !(k == key
+                || k.strip_prefix(key)
+                    .is_some_and(|rest| rest.starts_with(['.', '['])))
+        });
+        let mut leaves = Vec::new();
+        flatten_value(key.to_string(), value, &mut leaves);
+        for (leaf, _) in leaves {
+            self.files.insert(leaf, file.to_path_buf());
+        }
+    }
+}
+
+/// Build the config from `files` (the main file, then its includes) and
+/// `overrides` applied in order, then validate it.
+///
+/// Validation errors about a key set in a file name that file.
+pub fn resolve(
+    files: &[ConfigFile],
+    overrides: &[Override],
+) -> Result<(AppConfig, ConfigOrigins), ConfigError> {
+    let mut table = Table::new();
+    let mut origins = ConfigOrigins::default();
+    for file in files {
+        merge(&mut table, &file.table, "", &file.path, &mut origins);
+    }
+
+    let defaults = match Value::try_from(AppConfig::default()) {
+        Ok(Value::Table(defaults)) => defaults,
+        _ => Table::new(),
+    };
+    for o in overrides {
+        if !defaults.contains_key(&o.path[0]) {
+            return Err(ConfigError::Validation(format!(
+                "{}: unknown config section {:?}",
+                o.source(),
+                o.path[0]
+            )));
+        }
+        let reference_at =
+            |path: &[String]| lookup(&table, path).or_else(|| lookup(&defaults, path));
+        for end in 1..o.path.len() {
+            if reference_at(&o.path[..end]).is_some_and(|v| !v.is_table()) {
+                return Err(ConfigError::Validation(format!(
+                    "{}: {} is not a table",
+                    o.source(),
+                    o.path[..end].join(".")
+                )));
+            }
+        }
+        let reference = reference_at(&o.path);
+        let value = typed_value(&o.value, reference).map_err(|expected| {
+            ConfigError::Validation(format!(
+                "{}: expected {expected}, got {:?}",
+                o.source(),
+                o.value
+            ))
+        })?;
+        insert(&mut table, &o.path, value);
+        origins.overrides.push((o.key(), o.origin.clone()));
+    }
+
+    let config: AppConfig = Value::Table(table).try_into()?;
+    config.validate().map_err(|e| origins.attribute(e))?;
+    Ok((config, origins))
+}
+
+/// Merge `src` from `file` into `dest`: tables key by key, arrays of tables
+/// by concatenation, anything else by replacement.
+fn merge(dest: &mut Table, src: &Table, prefix: &str, file: &Path, origins: &mut ConfigOrigins) {
+    for (key, value) in src {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{prefix}.{key}")
+        };
+        match (dest.get_mut(key), value) {
+            (Some(Value::Table(dest)), Value::Table(src)) => {
+                merge(dest, src, &path, file, origins);
+            }
+            (Some(Value::Array(dest)), Value::Array(src))
+                if is_table_array(dest) && is_table_array(src) =>
+            {
+                for element in src {
+                    origins.record(&format!("{path}[{}]", dest.len()), element, file);
+                    dest.push(element.clone());
+                }
+            }
+            _ => {
+                origins.record(&path, value, file);
+                dest.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+fn is_table_array(array: &[Value]) -> bool {
+    !array.is_empty() && array.iter().all(Value::is_table)
+}
+
+/// Every leaf value in `config` by key, in table order. Arrays of tables are
+/// flattened per element (`policy.rules[0].role`); other arrays are leaves.
+pub fn flatten(config: &AppConfig) -> Vec<(String, Value)> {
+    match Value::try_from(config) {
+        Ok(Value::Table(table)) => flatten_table(&table),
+        _ => Vec::new(),
+    }
+}
+
+fn flatten_table(table: &Table) -> Vec<(String, Value)> {
+    let mut out = Vec::new();
+    for (key, value) in table {
+        flatten_value(key.clone(), value, &mut out);
+    }
+    out
+}
+
+fn flatten_value(key: String, value: &Value, out: &mut Vec<(String, Value)>) {
+    match value {
+        Value::Table(table) => {
+            for (child, value) in table {
+                flatten_value(format!("{key}.{child}"), value, out);
+            }
+        }
+        Value::Array(array) if is_table_array(array) => {
+            for (i, element) in array.iter().enumerate() {
+                flatten_value(format!("{key}[{i}]"), element, out);
+            }
+        }
+        other => out.push((key, other.clone())),
+    }
+}
+
+fn lookup<'a>(table: &'a Table, path: &[String]) -> Option<&'a Value> {
+    let (last, parents) = path.split_last()?;
+    let mut table = table;
+    for key in parents {
+        table = table.get(key)?.as_table()?;
+    }
+    table.get(last)
+}
+
+/// Set `path` in `table`, creating parent tables. Parents must already be
+/// tables or absent.
+fn insert(table: &mut Table, path: &[String], value: Value) {
+    let Some((last, parents)) = path.split_last() else {
+        return;
+    };
+    let mut table = table;
+    for key in parents {
+        let entry = table
+            .entry(key.clone())
+            .or_insert_with(|| Value::Table(Table::new()));
+        let Some(child) = entry.as_table_mut() else {
+            return;
+        };
+        table = child;
+    }
+    table.insert(last.clone(), value);
+}
+
+/// Convert `raw` to the type of `reference`. Fails with a description of
+/// the expected type.
+fn typed_value(raw: &str, reference: Option<&Value>) -> Result<Value, &'static str> {
+    let trimmed = raw.trim();
+    match reference {
+        Some(Value::String(_)) => Ok(Value::String(raw.to_string())),
+        Some(Value::Integer(_)) => trimmed
+            .parse()
+            .map(Value::Integer)
+            .map_err(|_| "an integer"),
+        Some(Value::Float(_)) => trimmed.parse().map(Value::Float).map_err(|_| "a number"),
+        Some(Value::Boolean(_)) => match trimmed.to_ascii_lowercase().as_str() {
+            "true" => Ok(Value::Boolean(true)),
+            "false" => Ok(Value::Boolean(false)),
+            _ => Err("true or false"),
+        },
+        Some(Value::Array(_)) => match literal(trimmed) {
+            Some(value @ Value::Array(_)) => Ok(value),
+            _ => Err("a TOML array"),
+        },
+        Some(Value::Table(_)) => match literal(trimmed) {
+            Some(value @ Value::Table(_)) => Ok(value),
+            _ => Err("a TOML inline table"),
+        },
+        Some(Value::Datetime(_)) | None => {
+            Ok(literal(trimmed).unwrap_or_else(|| Value::String(raw.to_string())))
+        }
+    }
+}
+
+/// Parse `raw` as a TOML value literal.
+fn literal(raw: &str) -> Option<Value> {
+    let mut table: Table = toml::from_str(&format!("v = {raw}")).ok()?;
+    table.remove("v")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn file(path: &str, text: &str) -> ConfigFile {
+        ConfigFile::parse(path, text).unwrap()
+    }
+
+    fn env(vars: &[(&str, &str)]) -> Vec<Override> {
+        Override::from_env(vars.iter().map(|(k, v)| (k.to_string(), v.to_string()))).unwrap()
+    }
+
+    #[test]
+    fn test_from_env() {
+        let overrides = env(&[
+            ("PATH", "/usr/bin"),
+            ("CRUSTYCLAW_LLM_API_KEY", "not-an-override"),
+            ("CRUSTYCLAW__DAEMON__TLS__ENABLED", "true"),
+            ("CRUSTYCLAW__DAEMON__LISTEN_PORT", "8080"),
+        ]);
+        let keys: Vec<String> = overrides.iter().map(Override::key).collect();
+        assert_eq!(keys, ["daemon.listen_port", "daemon.tls.enabled"]);
+        assert_eq!(
+            overrides[0].origin,
+            Origin::Env("CRUSTYCLAW__DAEMON__LISTEN_PORT".to_string())
+        );
+
+        for bad in ["CRUSTYCLAW__", "CRUSTYCLAW__DAEMON____PORT"] {
+            let vars = [(bad.to_string(), "1".to_string())];
+            assert!(Override::from_env(vars).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_parse_flag() {
+        let flag = Override::parse_flag("isolation.backend=docker").unwrap();
+        assert_eq!(flag.path, ["isolation", "backend"]);
+        assert_eq!(flag.value, "docker");
+        assert_eq!(flag.origin, Origin::Flag);
+        assert_eq!(
+            Override::parse_flag("logging.level=a=b").unwrap().value,
+            "a=b"
+        );
+
+        assert!(Override::parse_flag("daemon.listen_port").is_err());
+        assert!(Override::parse_flag("daemon..listen_port=1").is_err());
+    }
+
+    #[test]
+    fn test_precedence_and_origins() {
+        let toml = "[daemon]\nlisten_port = 9200\nlisten_addr = \"0.0.0.0\"\n";
+        let mut overrides = env(&[
+            ("CRUSTYCLAW__DAEMON__LISTEN_PORT", "8080"),
+            ("CRUSTYCLAW__LOGGING__LEVEL", "debug"),
+        ]);
+        overrides.push(Override::parse_flag("logging.level=trace").unwrap());
+
+        let (config, origins) = resolve(&[file("crustyclaw.toml", toml)], &overrides).unwrap();
+        assert_eq!(config.daemon.listen_port, 8080);
+        assert_eq!(config.daemon.listen_addr, "0.0.0.0");
+        assert_eq!(config.logging.level, "trace");
+
+        assert_eq!(
+            origins.origin("daemon.listen_port"),
+            Origin::Env("CRUSTYCLAW__DAEMON__LISTEN_PORT".to_string())
+        );
+        assert_eq!(
+            origins.origin("daemon.listen_addr"),
+            Origin::File(PathBuf::from("crustyclaw.toml"))
+        );
+        assert_eq!(origins.origin("logging.level"), Origin::Flag);
+        assert_eq!(origins.origin("isolation.backend"), Origin::Default);
+    }
+
+    #[test]
+    fn test_typed_values() {
+        let overrides = env(&[
+            // A string key keeps digits as text.
+            ("CRUSTYCLAW__LOGGING__LEVEL", "5"),
+            ("CRUSTYCLAW__ISOLATION__DEFAULT_CPU_FRACTION", "0.5"),
+            ("CRUSTYCLAW__SIGNAL__ENABLED", "TRUE"),
+            // Unset optional: not a TOML literal, so a string.
+            ("CRUSTYCLAW__DAEMON__METRICS_ADDR", "127.0.0.1:9464"),
+            (
+                "CRUSTYCLAW__CONTEXT__ENVIRONMENT__FACTS",
+                r#"["os", "time"]"#,
+            ),
+            // New map entry under a table.
+            (
+                "CRUSTYCLAW__QUOTAS__ROLES__USER",
+                "{ llm_tokens_per_day = 100 }",
+            ),
+        ]);
+        let (config, _) = resolve(&[], &overrides).unwrap_or_else(|e| panic!("{e}"));
+        assert_eq!(config.logging.level, "5");
+        assert_eq!(config.isolation.default_cpu_fraction, 0.5);
+        assert!(config.signal.enabled);
+        assert_eq!(
+            config.daemon.metrics_addr.as_deref(),
+            Some("127.0.0.1:9464")
+        );
+        assert_eq!(config.context.environment.facts, ["os", "time"]);
+        assert_eq!(config.quotas.roles["user"].llm_tokens_per_day, Some(100));
+    }
+
+    #[test]
+    fn test_bad_overrides() {
+        for (var, value, message) in [
+            (
+                "CRUSTYCLAW__DAEMON__LISTEN_PORT",
+                "eighty",
+                "expected an integer",
+            ),
+            (
+                "CRUSTYCLAW__SIGNAL__ENABLED",
+                "yes",
+                "expected true or false",
+            ),
+            ("CRUSTYCLAW__NOPE__KEY", "1", "unknown config section"),
+            (
+                "CRUSTYCLAW__DAEMON__LISTEN_PORT__X",
+                "1",
+                "daemon.listen_port is not a table",
+            ),
+            // Validation still runs on the result.
+            ("CRUSTYCLAW__DAEMON__LISTEN_PORT", "0", "must be non-zero"),
+        ] {
+            let err = resolve(&[], &env(&[(var, value)])).unwrap_err().to_string();
+            assert!(err.contains(message), "{var}={value}: {err}");
+        }
+    }
+
+    #[test]
+    fn test_merge_files() {
+        let main = file(
+            "crustyclaw.toml",
+            r#"
+            [daemon]
+            listen_port = 9200
+
+            [[policy.rules]]
+            role = "admin"
+            action = "*"
+            resource = "*"
+            effect = "allow"
+            "#,
+        );
+        let include = file(
+            "policies/ops.toml",
+            r#"
+            [daemon]
+            listen_addr = "0.0.0.0"
+
+            [logging]
+            level = "debug"
+
+            [[policy.rules]]
+            role = "ops"
+            action = "read"
+            resource = "*"
+            effect = "allow"
+            "#,
+        );
+        let local = file("local.toml", "[logging]\nlevel = \"warn\"\n");
+
+        let (config, origins) = resolve(&[main, include, local], &[]).unwrap();
+        assert_eq!(config.daemon.listen_port, 9200);
+        assert_eq!(config.daemon.listen_addr, "0.0.0.0");
+        assert_eq!(config.logging.level, "warn");
+        let roles: Vec<&str> = config
+            .policy
+            .rules
+            .iter()
+            .map(|r| r.role.as_str())
+            .collect();
+        assert_eq!(roles, ["admin", "ops"]);
+
+        let from = |path: &str| Origin::File(PathBuf::from(path));
+        assert_eq!(
+            origins.origin("daemon.listen_port"),
+            from("crustyclaw.toml")
+        );
+        assert_eq!(
+            origins.origin("daemon.listen_addr"),
+            from("policies/ops.toml")
+        );
+        assert_eq!(origins.origin("logging.level"), from("local.toml"));
+        assert_eq!(
+            origins.origin("policy.rules[0].role"),
+            from("crustyclaw.toml")
+        );
+        assert_eq!(
+            origins.origin("policy.rules[1].role"),
+            from("policies/ops.toml")
+        );
+        assert_eq!(origins.origin("policy.rules[1]"), from("policies/ops.toml"));
+    }
+
+    #[test]
+    fn test_validation_error_names_file() {
+        let main = file("crustyclaw.toml", "[daemon]\nlisten_port = 9200\n");
+        let include = file(
+            "policies/bad.toml",
+            "[[policy.rules]]\nrole = \"x\"\naction = \"*\"\nresource = \"*\"\neffect = \"maybe\"\n",
+        );
+        let err = resolve(&[main.clone(), include], &[]).unwrap_err();
+        assert!(
+            matches!(&err, ConfigError::InFile { path, .. } if path == Path::new("policies/bad.toml")),
+            "{err}"
+        );
+        assert!(
+            err.to_string()
+                .starts_with("policies/bad.toml: validation error: policy.rules[0].effect")
+        );
+
+        // Errors about overridden keys are not attributed to a file.
+        let flag = Override::parse_flag("daemon.listen_port=0").unwrap();
+        let err = resolve(&[main], &[flag]).unwrap_err();
+        assert!(matches!(err, ConfigError::Validation(_)), "{err}");
+    }
+
+    #[test]
+    fn test_flatten() {
+        let config = AppConfig::parse(
+            "[daemon]\nlisten_port = 9200\n\n[[policy.rules]]\nrole = \"admin\"\naction = \"*\"\nresource = \"*\"\neffect = \"allow\"\n",
+        )
+        .unwrap();
+        let entries = flatten(&config);
+        let value = |key: &str| {
+            entries
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.clone())
+        };
+        assert_eq!(value("daemon.listen_port"), Some(Value::Integer(9200)));
+        assert_eq!(
+            value("policy.rules[0].role"),
+            Some(Value::String("admin".to_string()))
+        );
+    }
+}
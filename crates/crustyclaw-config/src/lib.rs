@@ -6,9 +6,27 @@
 //! Provides the [`AppConfig`] type as the central configuration structure,
 //! and the [`policy`] module for role-based access control.
 
+/// Signed policy bundles (`policy.bundle`).
+pub mod bundle;
+/// Boolean condition expressions for policy `when` clauses.
+pub mod condition;
+/// Cron expressions for `[[schedules]]`.
+pub mod cron;
+/// Splitting the config across files with `include`.
+pub mod include;
+/// Layered configuration: file, `CRUSTYCLAW__*` env vars, and `--set` flags.
+pub mod layers;
+/// Non-fatal config diagnostics ([`AppConfig::lint`]).
+pub mod lint;
+/// Minisign signature verification, for policy bundles.
+pub mod minisign;
+/// Glob patterns for policy rule fields.
+pub mod pattern;
 /// Role-based access control policy engine.
 pub mod policy;
 
+use std::collections::BTreeMap;
+use std::fmt;
 use std::path::Path;
 
 use serde::{Deserialize, Serialize};
@@ -24,11 +42,22 @@ pub enum ConfigError {
 
     #[error("validation error: {0}")]
     Validation(String),
+
+    #[error("{}: {source}", path.display())]
+    InFile {
+        path: std::path::PathBuf,
+        source: Box<ConfigError>,
+    },
 }
 
 /// Top-level application configuration.
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
+    /// Other config files merged into this one, as paths or globs relative
+    /// to this file. Only honoured by [`AppConfig::load`]; see [`include`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+
     /// Daemon configuration.
     #[serde(default)]
     pub daemon: DaemonConfig,
@@ -37,6 +66,14 @@ pub struct AppConfig {
     #[serde(default)]
     pub signal: SignalConfig,
 
+    /// Generic HTTP webhook channel configuration.
+    #[serde(default)]
+    pub webhook: WebhookConfig,
+
+    /// Matrix channel configuration.
+    #[serde(default)]
+    pub matrix: MatrixConfig,
+
     /// Logging configuration.
     #[serde(default)]
     pub logging: LoggingConfig,
@@ -60,6 +97,87 @@ pub struct AppConfig {
     /// LLM provider configuration.
     #[serde(default)]
     pub llm: LlmConfig,
+
+    /// Context engine configuration.
+    #[serde(default)]
+    pub context: ContextConfig,
+
+    /// Conversation file transfer configuration.
+    #[serde(default)]
+    pub files: FilesConfig,
+
+    /// Attachment spool for files passed from channels to skills and tools.
+    #[serde(default)]
+    pub attachments: AttachmentsConfig,
+
+    /// Outbound response post-processing.
+    #[serde(default)]
+    pub response: ResponseConfig,
+
+    /// Anonymous usage telemetry (off unless explicitly enabled).
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+
+    /// External MCP tool servers.
+    #[serde(default)]
+    pub mcp: McpConfig,
+
+    /// Native plugins loaded from shared libraries.
+    #[serde(default)]
+    pub plugins: PluginsConfig,
+
+    /// Operator-defined chat command aliases.
+    #[serde(default)]
+    pub commands: CommandsConfig,
+
+    /// Skills and agent prompts run on a cron schedule.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schedules: Vec<ScheduleConfig>,
+
+    /// Custom agent tools, each run as a command in a sandbox.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<ToolConfig>,
+
+    /// Interactive `crustyclaw chat` sessions.
+    #[serde(default)]
+    pub chat: ChatConfig,
+
+    /// Chat history recording.
+    #[serde(default)]
+    pub conversations: ConversationsConfig,
+
+    /// Long-term memory per conversation.
+    #[serde(default)]
+    pub memory: MemoryConfig,
+
+    /// Backing store for daemon state.
+    #[serde(default)]
+    pub storage: StorageConfig,
+
+    /// Per-role usage quotas.
+    #[serde(default)]
+    pub quotas: QuotasConfig,
+
+    /// Readiness probe settings.
+    #[serde(default)]
+    pub health: HealthConfig,
+
+    /// Remote daemon the CLI connects to over TLS instead of the local socket.
+    #[serde(default)]
+    pub remote: RemoteConfig,
+
+    /// Terminal UI settings.
+    #[serde(default)]
+    pub tui: TuiConfig,
+
+    /// Signature verification settings.
+    #[serde(default)]
+    pub security: SecurityConfig,
+
+    /// The policy bundle [`policy`](Self::policy) was loaded from, if
+    /// `policy.bundle` is set. Filled in by [`AppConfig::load`].
+    #[serde(skip)]
+    pub policy_bundle: Option<bundle::PolicyBundle>,
 }
 
 /// Security policy rules that can be defined in TOML.
@@ -72,6 +190,204 @@ pub struct PolicyConfig {
     /// Policy rules.
     #[serde(default)]
     pub rules: Vec<PolicyRuleConfig>,
+
+    /// Expected decisions the policy must satisfy (see [`AppConfig::run_policy_tests`]).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tests: Vec<PolicyTestConfig>,
+
+    /// Load the rest of this section from a signed bundle file, relative to
+    /// the main config file's directory. See [`bundle`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bundle: Option<String>,
+}
+
+/// Keys trusted to sign policy bundles, and whether a signature is required.
+///
+/// ## TOML Example
+///
+/// ```toml
+/// [security]
+/// strict = true
+///
+/// [security.trusted_keys]
+/// release = "RWRVIi7rHtkIeomkwzC1akZsNw4Jy1nvbcyvGpTKyWdXj1ZTf0NKmJ9U"
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SecurityConfig {
+    /// Require `policy.bundle`, and refuse to load it without a valid
+    /// signature.
+    #[serde(default)]
+    pub strict: bool,
+
+    /// Key name → minisign public key (the base64 line of `minisign.pub`).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub trusted_keys: BTreeMap<String, String>,
+}
+
+impl SecurityConfig {
+    /// Check the minisign `signature` of `message` against the trusted
+    /// keys, returning the name of the key that made it.
+    pub fn verify(
+        &self,
+        message: &[u8],
+        signature: &str,
+    ) -> Result<String, minisign::MinisignError> {
+        let signature = minisign::Signature::parse(signature)?;
+        let (names, keys): (Vec<_>, Vec<_>) = self
+            .trusted_keys
+            .iter()
+            .map(|(name, key)| Ok((name, minisign::PublicKey::parse(key)?)))
+            .collect::<Result<Vec<_>, minisign::MinisignError>>()?
+            .into_iter()
+            .unzip();
+        let index = signature.verify(message, &keys)?;
+        Ok(names[index].clone())
+    }
+}
+
+/// An embedded policy assertion: evaluating the request must yield `expect`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyTestConfig {
+    /// Role to evaluate.
+    pub role: String,
+    /// Action to evaluate.
+    pub action: String,
+    /// Resource to evaluate.
+    pub resource: String,
+    /// Expected outcome ("allow" or "deny"). A request matching no rule
+    /// counts as "deny".
+    pub expect: String,
+    /// Request context for rules with a `when` clause. Nested tables are
+    /// flattened into dotted paths (`{ time = { hour = 10 } }` → `time.hour`).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub context: BTreeMap<String, toml::Value>,
+}
+
+impl PolicyTestConfig {
+    /// Build the request context for this test.
+    fn request_context(&self) -> Result<condition::RequestContext, String> {
+        fn flatten(
+            prefix: &str,
+            value: &toml::Value,
+            ctx: &mut condition::RequestContext,
+        ) -> Result<(), String> {
+            let value = match value {
+                toml::Value::String(s) => condition::Value::Str(s.clone()),
+                toml::Value::Integer(n) => condition::Value::Num(*n as f64),
+                toml::Value::Float(n) => condition::Value::Num(*n),
+                toml::Value::Boolean(b) => condition::Value::Bool(*b),
+                toml::Value::Table(table) => {
+                    for (key, nested) in table {
+                        flatten(&format!("{prefix}.{key}"), nested, ctx)?;
+                    }
+                    return Ok(());
+                }
+                other => {
+                    return Err(format!(
+                        "context.{prefix} has unsupported type {}",
+                        other.type_str()
+                    ));
+                }
+            };
+            ctx.insert(prefix, value);
+            Ok(())
+        }
+
+        let mut ctx = condition::RequestContext::new();
+        for (key, value) in &self.context {
+            flatten(key, value, &mut ctx)?;
+        }
+        Ok(ctx)
+    }
+}
+
+/// A `[[policy.tests]]` entry (or [`AccessMatrix`] check) the live policy
+/// does not satisfy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyTestFailure {
+    /// Index into `policy.tests`, or into the checks passed to
+    /// [`AppConfig::check_policy`].
+    pub index: usize,
+    /// Role that was evaluated.
+    pub role: String,
+    /// Action that was evaluated.
+    pub action: String,
+    /// Resource that was evaluated.
+    pub resource: String,
+    /// Expected outcome ("allow" or "deny").
+    pub expected: String,
+    /// Actual decision from the policy engine.
+    pub actual: policy::PolicyDecision,
+    /// ID of the rule that decided, if it has one.
+    pub rule_id: Option<String>,
+}
+
+impl fmt::Display for PolicyTestFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "policy.tests[{}]: role={} action={} resource={} expected {}, got {:?}",
+            self.index, self.role, self.action, self.resource, self.expected, self.actual
+        )?;
+        if let Some(id) = &self.rule_id {
+            write!(f, " (rule {id:?})")?;
+        }
+        Ok(())
+    }
+}
+
+/// A file of expected policy decisions, checked against the loaded policy
+/// with `crustyclaw policy simulate --matrix <file>`.
+///
+/// Each check has the fields of a `[[policy.tests]]` entry.
+///
+/// ## TOML Example
+///
+/// ```toml
+/// [[checks]]
+/// role = "user"
+/// action = "write"
+/// resource = "secrets"
+/// expect = "deny"
+///
+/// [[checks]]
+/// role = "ops"
+/// action = "execute"
+/// resource = "skills/deploy"
+/// expect = "allow"
+/// context = { time = { hour = 10 } }
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AccessMatrix {
+    /// Expected decisions, in file order.
+    #[serde(default)]
+    pub checks: Vec<PolicyTestConfig>,
+}
+
+impl AccessMatrix {
+    /// Parse and validate an access matrix from a TOML string.
+    pub fn parse(s: &str) -> Result<Self, ConfigError> {
+        let matrix: AccessMatrix = toml::from_str(s)?;
+        validate_policy_tests(&matrix.checks, "checks")?;
+        Ok(matrix)
+    }
+}
+
+/// Check the `expect` and `context` of each test in `tests`, reporting
+/// errors under `key`.
+fn validate_policy_tests(tests: &[PolicyTestConfig], key: &str) -> Result<(), ConfigError> {
+    for (i, test) in tests.iter().enumerate() {
+        if test.expect != "allow" && test.expect != "deny" {
+            return Err(ConfigError::Validation(format!(
+                "{key}[{i}].expect must be \"allow\" or \"deny\", got {:?}",
+                test.expect
+            )));
+        }
+        test.request_context()
+            .map_err(|e| ConfigError::Validation(format!("{key}[{i}].{e}")))?;
+    }
+    Ok(())
 }
 
 /// A single policy rule as expressed in TOML.
@@ -88,6 +404,16 @@ pub struct PolicyRuleConfig {
     /// Priority (higher = evaluated first).
     #[serde(default)]
     pub priority: u32,
+    /// Optional condition expression (e.g. `time.hour >= 9 && resource.tag == "prod"`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub when: Option<String>,
+    /// Optional identifier, unique among the rules, reported when this
+    /// rule decides a request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Optional explanation reported when this rule decides a request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
 }
 
 fn default_policy_default() -> String {
@@ -101,7 +427,8 @@ fn default_policy_default() -> String {
 /// Linux namespaces, and a no-op development backend.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IsolationConfig {
-    /// Isolation backend: "auto", "docker", "firecracker", "apple-vz", "linux-ns", or "noop".
+    /// Isolation backend: "auto", "docker", "podman", "firecracker", "apple-vz", "linux-ns", or
+    /// "noop".
     #[serde(default = "default_isolation_backend")]
     pub backend: String,
 
@@ -125,6 +452,11 @@ pub struct IsolationConfig {
     #[serde(default = "default_isolation_max_concurrent")]
     pub max_concurrent: usize,
 
+    /// Seconds running sandboxes get to finish at shutdown before they
+    /// are killed.
+    #[serde(default = "default_isolation_shutdown_grace_secs")]
+    pub shutdown_grace_secs: u64,
+
     /// Default trust tier for skills: "trusted", "internal", "untrusted", "llm-generated".
     /// When set, the trust-based selector overrides the `backend` field.
     #[serde(default)]
@@ -134,11 +466,36 @@ pub struct IsolationConfig {
     #[serde(default = "default_docker_image")]
     pub docker_image: String,
 
+    /// Pull sandbox images that are not present locally before their first
+    /// run. When false, a missing image fails the run.
+    #[serde(default)]
+    pub pull_images: bool,
+
+    /// Reject sandbox images referenced by `:latest` or without a tag,
+    /// unless pinned by digest (`name@sha256:…`).
+    #[serde(default)]
+    pub strict_images: bool,
+
     /// Enable credential proxying (sentinel-value swapping).
     /// When true, secrets are injected as sentinel placeholders
     /// rather than real values.
     #[serde(default)]
     pub credential_proxy: bool,
+
+    /// Address the egress proxy listens on when `credential_proxy` is
+    /// enabled. Sandboxes reach the proxy at this address, so it must be a
+    /// specific IP reachable from them (e.g. the Docker bridge gateway).
+    #[serde(default = "default_egress_proxy_addr")]
+    pub egress_proxy_addr: String,
+
+    /// Hosts sandboxes may reach through the egress proxy: exact names, or
+    /// `*.domain` for any subdomain of `domain`.
+    #[serde(default)]
+    pub egress_allowed_hosts: Vec<String>,
+
+    /// Per-trust-tier backends and required isolation levels.
+    #[serde(default, skip_serializing_if = "TrustConfig::is_empty")]
+    pub trust: TrustConfig,
 }
 
 impl Default for IsolationConfig {
@@ -150,13 +507,112 @@ impl Default for IsolationConfig {
             default_timeout_secs: default_isolation_timeout_secs(),
             default_network: default_isolation_network(),
             max_concurrent: default_isolation_max_concurrent(),
+            shutdown_grace_secs: default_isolation_shutdown_grace_secs(),
             default_trust_tier: None,
             docker_image: default_docker_image(),
+            pull_images: false,
+            strict_images: false,
             credential_proxy: false,
+            egress_proxy_addr: default_egress_proxy_addr(),
+            egress_allowed_hosts: Vec::new(),
+            trust: TrustConfig::default(),
+        }
+    }
+}
+
+/// Trust tiers, weakest requirement first.
+pub const TRUST_TIERS: &[&str] = &["trusted", "internal", "untrusted", "llm-generated"];
+
+/// `[isolation.trust]`: the backend each trust tier runs on, and the
+/// isolation level it requires.
+///
+/// Tiers left out select the best available backend for their level.
+/// Levels default to L1 for `trusted`, L2 for `internal`, and L3 for
+/// `untrusted` and `llm-generated`; a tier's backend must provide at least
+/// its level.
+///
+/// ```toml
+/// [isolation.trust]
+/// internal = "docker"
+/// untrusted = "firecracker"
+///
+/// [isolation.trust.levels]
+/// internal = "l3"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrustConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trusted: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub internal: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub untrusted: Option<String>,
+    #[serde(
+        default,
+        rename = "llm-generated",
+        alias = "llm_generated",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub llm_generated: Option<String>,
+
+    /// Trust tier → required isolation level: "l1", "l2", or "l3".
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub levels: BTreeMap<String, String>,
+}
+
+impl TrustConfig {
+    /// Whether nothing is configured.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The backend configured for `tier` (one of [`TRUST_TIERS`]).
+    pub fn backend(&self, tier: &str) -> Option<&str> {
+        match tier {
+            "trusted" => self.trusted.as_deref(),
+            "internal" => self.internal.as_deref(),
+            "untrusted" => self.untrusted.as_deref(),
+            "llm-generated" => self.llm_generated.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// The isolation level `tier` requires, 1–3, or `None` for an unknown
+    /// tier or level.
+    pub fn level(&self, tier: &str) -> Option<u8> {
+        match self.levels.get(tier) {
+            Some(level) => parse_isolation_level(level),
+            None => match tier {
+                "trusted" => Some(1),
+                "internal" => Some(2),
+                "untrusted" | "llm-generated" => Some(3),
+                _ => None,
+            },
         }
     }
 }
 
+/// Parse an isolation level name ("l1", "container", "l3", "microvm", …).
+fn parse_isolation_level(level: &str) -> Option<u8> {
+    match level.to_lowercase().as_str() {
+        "l1" | "l1-container" | "l1_container" | "container" => Some(1),
+        "l2" | "l2-namespace" | "l2_namespace" | "namespace" | "gvisor" => Some(2),
+        "l3" | "l3-microvm" | "l3_microvm" | "microvm" | "vm" => Some(3),
+        _ => None,
+    }
+}
+
+/// The isolation level a backend provides; `None` for "auto", which picks
+/// one that meets the level.
+fn backend_isolation_level(backend: &str) -> Option<u8> {
+    match backend {
+        "noop" | "podman" => Some(1),
+        "linux-ns" => Some(2),
+        "docker" | "firecracker" | "apple-vz" => Some(3),
+        _ => None,
+    }
+}
+
 fn default_isolation_backend() -> String {
     "auto".to_string()
 }
@@ -181,10 +637,34 @@ fn default_isolation_max_concurrent() -> usize {
     4
 }
 
+fn default_isolation_shutdown_grace_secs() -> u64 {
+    30
+}
+
+fn default_egress_proxy_addr() -> String {
+    "127.0.0.1:3128".to_string()
+}
+
 fn default_docker_image() -> String {
     "alpine:latest".to_string()
 }
 
+/// Whether an image reference floats with `:latest`: it has no digest,
+/// and no tag or the `latest` tag.
+fn image_floats(image: &str) -> bool {
+    if image.contains('@') || image.starts_with("sha256:") {
+        return false;
+    }
+    let name = image.rsplit('/').next().unwrap_or(image);
+    name.split_once(':').is_none_or(|(_, tag)| tag == "latest")
+}
+
+/// Whether `host` is a host name or a `*.domain` pattern.
+fn is_host_pattern(host: &str) -> bool {
+    let name = host.strip_prefix("*.").unwrap_or(host);
+    !name.is_empty() && !name.contains(['/', '*', ' '])
+}
+
 /// Configuration for the core daemon.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DaemonConfig {
@@ -200,6 +680,32 @@ pub struct DaemonConfig {
     /// Defaults to `/tmp/crustyclaw.sock`.
     #[serde(default)]
     pub socket_path: Option<String>,
+
+    /// Directory for daemon state that must survive restarts (e.g. the
+    /// in-flight run journal used for crash recovery).
+    #[serde(default = "default_state_dir")]
+    pub state_dir: String,
+
+    /// PID file written while the daemon runs; a second daemon with the
+    /// same PID file refuses to start. Defaults to
+    /// `<state_dir>/crustyclaw.pid`.
+    #[serde(default)]
+    pub pid_file: Option<String>,
+
+    /// Directory scanned for skill manifests (`<skill>/skill.toml`) at
+    /// startup and on SIGHUP. Unset disables skill discovery.
+    #[serde(default)]
+    pub skills_dir: Option<String>,
+
+    /// TCP address (`host:port`) to serve Prometheus metrics on, in addition
+    /// to `GET /metrics` on the IPC socket. Unset serves them on the socket
+    /// only.
+    #[serde(default)]
+    pub metrics_addr: Option<String>,
+
+    /// TLS listener for remote control on `listen_addr:listen_port`.
+    #[serde(default)]
+    pub tls: DaemonTlsConfig,
 }
 
 impl Default for DaemonConfig {
@@ -208,10 +714,54 @@ impl Default for DaemonConfig {
             listen_addr: default_listen_addr(),
             listen_port: default_listen_port(),
             socket_path: None,
+            state_dir: default_state_dir(),
+            pid_file: None,
+            skills_dir: None,
+            metrics_addr: None,
+            tls: DaemonTlsConfig::default(),
         }
     }
 }
 
+/// TLS settings for the remote control plane.
+///
+/// When enabled, the daemon serves the IPC API on
+/// `daemon.listen_addr:daemon.listen_port` in addition to the Unix socket.
+/// Clients must present a certificate issued by `client_ca_path` whose
+/// identity is mapped to a role in `[auth.cert_map]`.
+///
+/// ## TOML Example
+///
+/// ```toml
+/// [daemon.tls]
+/// enabled = true
+/// cert_path = "/etc/crustyclaw/tls/server.crt"
+/// key_path = "/etc/crustyclaw/tls/server.key"
+/// client_ca_path = "/etc/crustyclaw/tls/clients-ca.crt"
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DaemonTlsConfig {
+    /// Whether the TLS listener is started.
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// PEM server certificate chain.
+    #[serde(default)]
+    pub cert_path: Option<String>,
+
+    /// PEM private key for `cert_path`.
+    #[serde(default)]
+    pub key_path: Option<String>,
+
+    /// PEM CA certificate(s) that client certificates must chain to.
+    #[serde(default)]
+    pub client_ca_path: Option<String>,
+}
+
+fn default_state_dir() -> String {
+    "data/state".to_string()
+}
+
 fn default_listen_addr() -> String {
     "127.0.0.1".to_string()
 }
@@ -221,6 +771,26 @@ fn default_listen_port() -> u16 {
 }
 
 /// Configuration for the Signal channel adapter.
+///
+/// With `allowed_senders` or `allowed_groups` set, only listed senders, and
+/// anyone writing in a listed group, are served; messages from everyone else
+/// are handled as `unknown_senders` says. Each allowed sender's role is used
+/// for commands and quotas as if listed in `[commands.roles]`. Every access
+/// decision is written to the channel access audit log.
+///
+/// ## TOML Example
+///
+/// ```toml
+/// [signal]
+/// enabled = true
+/// account = "+15550000"
+/// allowed_groups = ["kT9x2QvXbFq8r0sYwZ1aBc=="]
+/// unknown_senders = "quarantine"
+///
+/// [signal.allowed_senders]
+/// "+15550001" = "operator"
+/// "+15550002" = "user"
+/// ```
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SignalConfig {
     /// Whether the Signal channel is enabled.
@@ -230,6 +800,54 @@ pub struct SignalConfig {
     /// Path to the Signal data directory.
     #[serde(default = "default_signal_data_dir")]
     pub data_dir: String,
+
+    /// Registered Signal account (phone number) to send and receive as.
+    #[serde(default)]
+    pub account: Option<String>,
+
+    /// Path to the `signal-cli` executable.
+    #[serde(default = "default_signal_cli_path")]
+    pub cli_path: String,
+
+    /// Send read receipts for incoming messages.
+    #[serde(default = "default_signal_read_receipts")]
+    pub read_receipts: bool,
+
+    /// Show a typing indicator to the sender while a reply is being prepared.
+    #[serde(default = "default_signal_typing_indicators")]
+    pub typing_indicators: bool,
+
+    /// Sender phone number or UUID → role. When this or `allowed_groups`
+    /// is non-empty, other senders are unknown.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub allowed_senders: BTreeMap<String, String>,
+
+    /// Group IDs whose members are all served, with the default role
+    /// unless listed in `allowed_senders`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_groups: Vec<String>,
+
+    /// What to do with messages from unknown senders.
+    #[serde(default)]
+    pub unknown_senders: UnknownSenderAction,
+}
+
+/// Handling of channel messages from senders outside the allowlists.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum UnknownSenderAction {
+    /// Discard the message.
+    #[default]
+    Drop,
+    /// Hold the message for operator review without processing it.
+    Quarantine,
+}
+
+impl SignalConfig {
+    /// Whether senders are restricted to the allowlists.
+    pub fn restricts_senders(&self) -> bool {
+        !self.allowed_senders.is_empty() || !self.allowed_groups.is_empty()
+    }
 }
 
 impl Default for SignalConfig {
@@ -237,6 +855,13 @@ impl Default for SignalConfig {
         Self {
             enabled: false,
             data_dir: default_signal_data_dir(),
+            account: None,
+            cli_path: default_signal_cli_path(),
+            read_receipts: default_signal_read_receipts(),
+            typing_indicators: default_signal_typing_indicators(),
+            allowed_senders: BTreeMap::new(),
+            allowed_groups: Vec::new(),
+            unknown_senders: UnknownSenderAction::default(),
         }
     }
 }
@@ -245,84 +870,353 @@ fn default_signal_data_dir() -> String {
     "data/signal".to_string()
 }
 
-/// Logging configuration.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct LoggingConfig {
-    /// Log level filter (e.g. "info", "debug", "trace").
-    #[serde(default = "default_log_level")]
-    pub level: String,
+fn default_signal_cli_path() -> String {
+    "signal-cli".to_string()
 }
 
-impl Default for LoggingConfig {
-    fn default() -> Self {
-        Self {
-            level: default_log_level(),
-        }
-    }
+fn default_signal_read_receipts() -> bool {
+    true
 }
 
-fn default_log_level() -> String {
-    "info".to_string()
+fn default_signal_typing_indicators() -> bool {
+    true
 }
 
-/// Secrets management configuration.
+/// Configuration for the generic webhook channel.
 ///
-/// Secrets can be defined inline, loaded from environment variables, or
-/// loaded from files. Each secret specifies how it should be injected
-/// into sandbox containers (as env vars, files, or both).
+/// Inbound messages are `POST`ed to `/channels/webhook` on the IPC API and
+/// signed with HMAC-SHA256 under the secret named by `secret`; replies on
+/// the channel are signed the same way and `POST`ed to `url`.
 ///
 /// ## TOML Example
 ///
 /// ```toml
-/// [secrets]
-/// staging_dir = "/run/crustyclaw/secrets"
+/// [webhook]
+/// enabled = true
+/// secret = "webhook_key"
+/// url = "https://chat.example.com/hooks/crustyclaw"
 ///
 /// [[secrets.entries]]
-/// name = "llm_api_key"
+/// name = "webhook_key"
 /// source = "env"
-/// env_var = "CRUSTYCLAW_SECRET_LLM_API_KEY"
-/// inject_as = "env"
-/// inject_env = "API_KEY"
-///
-/// [[secrets.entries]]
-/// name = "tls_cert"
-/// source = "file"
-/// file_path = "/etc/crustyclaw/tls.pem"
-/// inject_as = "file"
-/// inject_path = "/run/secrets/tls.pem"
+/// env_var = "CRUSTYCLAW_WEBHOOK_KEY"
+/// inject_env = "WEBHOOK_KEY"
 /// ```
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SecretsConfig {
-    /// Directory used to stage secret files before bind-mounting into containers.
-    /// Must be on a tmpfs or encrypted filesystem for production use.
-    #[serde(default = "default_secrets_staging_dir")]
-    pub staging_dir: String,
+pub struct WebhookConfig {
+    /// Whether the webhook channel is enabled.
+    #[serde(default)]
+    pub enabled: bool,
 
-    /// Named secret entries.
+    /// Name of the `[[secrets.entries]]` entry holding the HMAC key.
     #[serde(default)]
-    pub entries: Vec<SecretEntryConfig>,
+    pub secret: Option<String>,
+
+    /// URL replies are `POST`ed to. Without it, inbound messages are still
+    /// accepted but replies are dropped.
+    #[serde(default)]
+    pub url: Option<String>,
+
+    /// Timeout for each outbound `POST`, in seconds.
+    #[serde(default = "default_webhook_timeout_secs")]
+    pub timeout_secs: u64,
 }
 
-impl Default for SecretsConfig {
+impl Default for WebhookConfig {
     fn default() -> Self {
         Self {
-            staging_dir: default_secrets_staging_dir(),
-            entries: Vec::new(),
+            enabled: false,
+            secret: None,
+            url: None,
+            timeout_secs: default_webhook_timeout_secs(),
         }
     }
 }
 
-fn default_secrets_staging_dir() -> String {
-    "/run/crustyclaw/secrets".to_string()
+fn default_webhook_timeout_secs() -> u64 {
+    10
 }
 
-/// A single secret entry in the configuration.
+/// Configuration for the Matrix channel adapter.
+///
+/// The bot logs in once with a password (or adopts an existing access
+/// token) and keeps its session and end-to-end encryption keys in
+/// `data_dir`. Credentials are read from the secret store by name.
+///
+/// ## TOML Example
+///
+/// ```toml
+/// [matrix]
+/// enabled = true
+/// homeserver_url = "https://matrix.example.org"
+/// user_id = "@crustyclaw:example.org"
+/// password_secret = "matrix_password"
+///
+/// [matrix.rooms."!ops:example.org"]
+/// role = "operator"
+/// ```
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SecretEntryConfig {
-    /// Unique name for this secret (used as lookup key).
-    pub name: String,
+pub struct MatrixConfig {
+    /// Whether the Matrix channel is enabled.
+    #[serde(default)]
+    pub enabled: bool,
 
-    /// Source of the secret value: "env", "file", or "inline".
+    /// Base URL of the homeserver's client-server API.
+    #[serde(default)]
+    pub homeserver_url: Option<String>,
+
+    /// Fully qualified user ID of the bot account.
+    #[serde(default)]
+    pub user_id: Option<String>,
+
+    /// Secret entry holding the account password, used for the first login.
+    #[serde(default)]
+    pub password_secret: Option<String>,
+
+    /// Secret entry holding an existing access token, used instead of a
+    /// password login. Requires `device_id`.
+    #[serde(default)]
+    pub access_token_secret: Option<String>,
+
+    /// Device ID the access token belongs to.
+    #[serde(default)]
+    pub device_id: Option<String>,
+
+    /// Directory holding the session and the encrypted state and crypto
+    /// stores.
+    #[serde(default = "default_matrix_data_dir")]
+    pub data_dir: String,
+
+    /// Secret entry holding the passphrase the stores are encrypted with.
+    #[serde(default)]
+    pub store_passphrase_secret: Option<String>,
+
+    /// Ignore messages in rooms that are not end-to-end encrypted.
+    #[serde(default)]
+    pub encrypted_only: bool,
+
+    /// Room ID → room settings. When non-empty, only these rooms are
+    /// served, and invites to them are accepted automatically.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub rooms: BTreeMap<String, MatrixRoomConfig>,
+}
+
+impl Default for MatrixConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            homeserver_url: None,
+            user_id: None,
+            password_secret: None,
+            access_token_secret: None,
+            device_id: None,
+            data_dir: default_matrix_data_dir(),
+            store_passphrase_secret: None,
+            encrypted_only: false,
+            rooms: BTreeMap::new(),
+        }
+    }
+}
+
+fn default_matrix_data_dir() -> String {
+    "data/matrix".to_string()
+}
+
+/// A single `[matrix.rooms."<room id>"]` entry.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MatrixRoomConfig {
+    /// Command role of everyone in the room, as if the room ID were listed
+    /// in `[commands.roles]`.
+    #[serde(default)]
+    pub role: Option<String>,
+}
+
+/// Logging configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    /// Log level filter (e.g. "info", "debug", "trace").
+    #[serde(default = "default_log_level")]
+    pub level: String,
+
+    /// File the daemon also writes its log to, rotated by size.
+    #[serde(default)]
+    pub file: Option<String>,
+
+    /// Size at which the log file is rotated.
+    #[serde(default = "default_log_max_size_bytes")]
+    pub max_size_bytes: u64,
+
+    /// Log files kept, counting the one being written.
+    #[serde(default = "default_log_max_files")]
+    pub max_files: usize,
+
+    /// Format of the log file: "pretty" or "json".
+    #[serde(default = "default_log_format")]
+    pub format: String,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: default_log_level(),
+            file: None,
+            max_size_bytes: default_log_max_size_bytes(),
+            max_files: default_log_max_files(),
+            format: default_log_format(),
+        }
+    }
+}
+
+/// Formats `logging.format` accepts.
+pub const LOG_FORMATS: &[&str] = &["pretty", "json"];
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+fn default_log_max_size_bytes() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_log_max_files() -> usize {
+    5
+}
+
+fn default_log_format() -> String {
+    "pretty".to_string()
+}
+
+/// Secrets management configuration.
+///
+/// Secrets can be defined inline, loaded from environment variables, or
+/// loaded from files. Each secret specifies how it should be injected
+/// into sandbox containers (as env vars, files, or both).
+///
+/// ## TOML Example
+///
+/// ```toml
+/// [secrets]
+/// staging_dir = "/run/crustyclaw/secrets"
+/// rotation_interval_secs = 300
+///
+/// [[secrets.entries]]
+/// name = "llm_api_key"
+/// source = "env"
+/// env_var = "CRUSTYCLAW_SECRET_LLM_API_KEY"
+/// inject_as = "env"
+/// inject_env = "API_KEY"
+///
+/// [[secrets.entries]]
+/// name = "tls_cert"
+/// source = "file"
+/// file_path = "/etc/crustyclaw/tls.pem"
+/// inject_as = "file"
+/// inject_path = "/run/secrets/tls.pem"
+///
+/// [[secrets.entries]]
+/// name = "github_token"
+/// source = "keystore"
+/// inject_as = "env"
+/// inject_env = "GITHUB_TOKEN"
+///
+/// [[secrets.entries]]
+/// name = "anthropic_api_key"
+/// source = "keychain"
+/// inject_as = "env"
+/// inject_env = "ANTHROPIC_API_KEY"
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecretsConfig {
+    /// Directory used to stage secret files before bind-mounting into containers.
+    /// Must be on a tmpfs or encrypted filesystem for production use.
+    #[serde(default = "default_secrets_staging_dir")]
+    pub staging_dir: String,
+
+    /// Re-read env-, file-, and keychain-sourced secrets this often, in seconds.
+    /// `0` disables periodic rotation; secrets still reload on SIGHUP.
+    #[serde(default)]
+    pub rotation_interval_secs: u64,
+
+    /// Named secret entries.
+    #[serde(default)]
+    pub entries: Vec<SecretEntryConfig>,
+
+    /// The encrypted file read by `source = "keystore"` entries.
+    #[serde(default)]
+    pub keystore: KeystoreConfig,
+}
+
+impl Default for SecretsConfig {
+    fn default() -> Self {
+        Self {
+            staging_dir: default_secrets_staging_dir(),
+            rotation_interval_secs: 0,
+            entries: Vec::new(),
+            keystore: KeystoreConfig::default(),
+        }
+    }
+}
+
+fn default_secrets_staging_dir() -> String {
+    "/run/crustyclaw/secrets".to_string()
+}
+
+/// Sources `secrets.entries[].source` accepts.
+pub const SECRET_SOURCES: &[&str] = &["env", "file", "inline", "keystore", "keychain"];
+
+/// Where the keystore key can be kept.
+pub const KEYSTORE_KEY_SOURCES: &[&str] = &["file", "keychain"];
+
+/// `[secrets.keystore]` — secrets encrypted at rest, managed with
+/// `crustyclaw secrets set/get/rm`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeystoreConfig {
+    /// The encrypted secrets file.
+    #[serde(default = "default_keystore_path")]
+    pub path: String,
+
+    /// Where the key is kept: "file" (`key_file`) or "keychain" (the OS
+    /// keychain, via `secret-tool` or macOS `security`).
+    #[serde(default = "default_keystore_key_source")]
+    pub key_source: String,
+
+    /// Key file, when `key_source = "file"`. Created with mode 0600 by the
+    /// first `crustyclaw secrets set`.
+    #[serde(default = "default_keystore_key_file")]
+    pub key_file: String,
+}
+
+impl Default for KeystoreConfig {
+    fn default() -> Self {
+        Self {
+            path: default_keystore_path(),
+            key_source: default_keystore_key_source(),
+            key_file: default_keystore_key_file(),
+        }
+    }
+}
+
+fn default_keystore_path() -> String {
+    "data/secrets.keystore".to_string()
+}
+
+fn default_keystore_key_source() -> String {
+    "file".to_string()
+}
+
+fn default_keystore_key_file() -> String {
+    "data/secrets.key".to_string()
+}
+
+/// A single secret entry in the configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecretEntryConfig {
+    /// Unique name for this secret (used as lookup key).
+    pub name: String,
+
+    /// Source of the secret value: "env", "file", "inline", "keystore"
+    /// (`[secrets.keystore]`, under this entry's name), or "keychain" (the
+    /// OS keychain).
     #[serde(default = "default_secret_source")]
     pub source: String,
 
@@ -339,6 +1233,16 @@ pub struct SecretEntryConfig {
     #[serde(default)]
     pub value: Option<String>,
 
+    /// Keychain service (when source = "keychain"). Defaults to
+    /// `crustyclaw`.
+    #[serde(default)]
+    pub keychain_service: Option<String>,
+
+    /// Keychain account (when source = "keychain"). Defaults to the
+    /// entry's name.
+    #[serde(default)]
+    pub keychain_account: Option<String>,
+
     /// How to inject: "env", "file", or "both".
     #[serde(default = "default_inject_as")]
     pub inject_as: String,
@@ -351,6 +1255,11 @@ pub struct SecretEntryConfig {
     #[serde(default)]
     pub inject_path: Option<String>,
 
+    /// Hosts the egress proxy may send this secret to: exact names or
+    /// `*.domain`. Sentinels in requests to other hosts are left in place.
+    #[serde(default)]
+    pub egress_hosts: Vec<String>,
+
     /// Optional description for operator reference.
     #[serde(default)]
     pub description: String,
@@ -370,6 +1279,10 @@ fn default_inject_as() -> String {
 /// is "local" — the OS identity of the calling process is used as the
 /// credential, with no user interaction required.
 ///
+/// With `mode = "token"`, every request on the IPC socket must carry a
+/// session token minted by `crustyclaw login`, signed with the daemon key
+/// at `token_key_path`.
+///
 /// ## TOML Example
 ///
 /// ```toml
@@ -379,6 +1292,10 @@ fn default_inject_as() -> String {
 /// [auth.role_map]
 /// alice = "admin"
 /// bob = "operator"
+///
+/// [auth.cert_map]
+/// "ops-laptop.example.com" = "operator"
+/// "sha256:9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08" = "admin"
 /// ```
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthConfig {
@@ -386,11 +1303,34 @@ pub struct AuthConfig {
     #[serde(default = "default_auth_mode")]
     pub mode: String,
 
-    /// Optional mapping of OS usernames to policy roles.
-    /// If a username is not in this map, the default role from
-    /// `LocalIdentity::default_role()` is used.
+    /// Optional mapping of OS usernames to policy roles, for the CLI and
+    /// for IPC peers identified by UID (a UID without a `/etc/passwd`
+    /// entry is named `uid:<uid>`). The default role from
+    /// `LocalIdentity::default_role()` is always granted as well.
     #[serde(default)]
     pub role_map: std::collections::HashMap<String, String>,
+
+    /// Mapping of TLS client certificates to policy roles, for the remote
+    /// control plane. Keys are a DNS name from the certificate's subject
+    /// alternative names, or `sha256:<hex>` for the certificate's SHA-256
+    /// fingerprint. A client certificate that matches no key is rejected.
+    #[serde(default)]
+    pub cert_map: BTreeMap<String, String>,
+
+    /// HMAC key that signs session tokens in token mode; created (mode
+    /// 0600) on first use. Defaults to `<daemon.state_dir>/auth.key`.
+    #[serde(default)]
+    pub token_key_path: Option<String>,
+
+    /// Where `crustyclaw login` writes the session token and clients read
+    /// it. Defaults to `$XDG_CONFIG_HOME/crustyclaw/token`, falling back to
+    /// `~/.config/crustyclaw/token`.
+    #[serde(default)]
+    pub token_path: Option<String>,
+
+    /// Lifetime of tokens minted by `crustyclaw login`, in seconds.
+    #[serde(default = "default_token_ttl_secs")]
+    pub token_ttl_secs: u64,
 }
 
 impl Default for AuthConfig {
@@ -398,6 +1338,10 @@ impl Default for AuthConfig {
         Self {
             mode: default_auth_mode(),
             role_map: std::collections::HashMap::new(),
+            cert_map: BTreeMap::new(),
+            token_key_path: None,
+            token_path: None,
+            token_ttl_secs: default_token_ttl_secs(),
         }
     }
 }
@@ -406,6 +1350,10 @@ fn default_auth_mode() -> String {
     "local".to_string()
 }
 
+fn default_token_ttl_secs() -> u64 {
+    12 * 60 * 60
+}
+
 /// LLM provider configuration.
 ///
 /// Controls which LLM API to use, how to authenticate, and default parameters.
@@ -419,10 +1367,34 @@ fn default_auth_mode() -> String {
 /// # api_key is loaded from CRUSTYCLAW_LLM_API_KEY env var by default
 /// max_tokens = 4096
 /// temperature = 0.0
+/// # exact token counts for OpenAI models
+/// # tokenizer_vocab = "/usr/share/tiktoken/cl100k_base.tiktoken"
+///
+/// [llm.retry]
+/// max_retries = 2
+/// initial_backoff_ms = 500
+/// max_backoff_ms = 30000
+/// jitter = 0.5
+///
+/// [llm.http]
+/// connect_timeout_secs = 10
+/// request_timeout_secs = 300
+/// # proxy = "http://proxy.internal:3128"
+///
+/// # tried in order when the provider above keeps failing
+/// [[llm.fallbacks]]
+/// provider = "openai"
+/// model = "gpt-4o"
+/// api_key_env = "OPENAI_API_KEY"
+///
+/// # USD per million tokens, for `crustyclaw usage` cost estimates
+/// [llm.pricing."claude-sonnet-4-20250514"]
+/// prompt_per_mtok = 3.0
+/// completion_per_mtok = 15.0
 /// ```
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LlmConfig {
-    /// Provider: "anthropic" or "openai".
+    /// Provider: "anthropic", "openai", or "llamacpp".
     #[serde(default = "default_llm_provider")]
     pub provider: LlmProviderKind,
 
@@ -430,11 +1402,17 @@ pub struct LlmConfig {
     #[serde(default)]
     pub api_key: String,
 
+    /// Name of the `[[secrets.entries]]` entry holding the API key; wins
+    /// over `api_key`. The provider is rebuilt when the secret changes.
+    #[serde(default)]
+    pub api_key_secret: Option<String>,
+
     /// Default model identifier (e.g. "claude-sonnet-4-20250514", "gpt-4o").
     #[serde(default = "default_llm_model")]
     pub model: String,
 
-    /// Custom API base URL (for OpenAI-compatible providers like Ollama).
+    /// Custom API base URL (for OpenAI-compatible providers like Ollama,
+    /// or a llama.cpp server).
     #[serde(default)]
     pub base_url: Option<String>,
 
@@ -445,616 +1423,5146 @@ pub struct LlmConfig {
     /// Default temperature (0.0–2.0).
     #[serde(default)]
     pub temperature: f32,
+
+    /// Path to a tiktoken BPE rank file (e.g. `cl100k_base.tiktoken`) for
+    /// exact token counts; a per-model approximation is used when unset.
+    #[serde(default)]
+    pub tokenizer_vocab: Option<String>,
+
+    /// Retries of failed requests.
+    #[serde(default)]
+    pub retry: LlmRetryConfig,
+
+    /// HTTP client settings, shared by all providers.
+    #[serde(default)]
+    pub http: LlmHttpConfig,
+
+    /// Deprecated spelling of `retry.max_retries`; overrides it when set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_retries: Option<u32>,
+
+    /// Deprecated spelling of `retry.initial_backoff_ms`; overrides it when
+    /// set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_backoff_ms: Option<u64>,
+
+    /// Providers tried in order once the primary one has failed with
+    /// retryable errors.
+    #[serde(default)]
+    pub fallbacks: Vec<LlmFallbackConfig>,
+
+    /// Price of each model, keyed by model identifier, for estimating the
+    /// cost of recorded token usage. Models without a price are reported
+    /// without a cost.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub pricing: BTreeMap<String, ModelPricing>,
+
+    /// Options of the `llamacpp` provider, here or in `[[llm.fallbacks]]`.
+    #[serde(default)]
+    pub llamacpp: LlamaCppConfig,
 }
 
-/// Which LLM provider to use.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
-#[serde(rename_all = "lowercase")]
-#[derive(Default)]
-pub enum LlmProviderKind {
-    #[default]
-    Anthropic,
-    OpenAi,
+/// Retries of failed LLM requests (`[llm.retry]`).
+///
+/// A rate limit, network error, timeout, or 5xx response is retried on the
+/// same provider with jittered exponential backoff, then the request moves
+/// on to the next `[[llm.fallbacks]]` entry.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LlmRetryConfig {
+    /// Retries per provider before moving on to the next fallback.
+    #[serde(default = "default_llm_max_retries")]
+    pub max_retries: u32,
+
+    /// Delay before the first retry, in milliseconds; doubled for each
+    /// further retry.
+    #[serde(default = "default_llm_retry_backoff_ms")]
+    pub initial_backoff_ms: u64,
+
+    /// Longest delay before a retry, in milliseconds. A rate limit whose
+    /// `retry-after` is longer moves on to the next provider instead.
+    #[serde(default = "default_llm_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+
+    /// Fraction of each delay that is random, from 0.0 (none) to 1.0: a
+    /// delay `d` becomes a random wait between `d * (1 - jitter)` and `d`.
+    #[serde(default = "default_llm_retry_jitter")]
+    pub jitter: f64,
 }
 
-impl Default for LlmConfig {
+impl Default for LlmRetryConfig {
     fn default() -> Self {
         Self {
-            provider: default_llm_provider(),
-            api_key: String::new(),
-            model: default_llm_model(),
-            base_url: None,
-            max_tokens: default_max_tokens(),
-            temperature: 0.0,
+            max_retries: default_llm_max_retries(),
+            initial_backoff_ms: default_llm_retry_backoff_ms(),
+            max_backoff_ms: default_llm_max_backoff_ms(),
+            jitter: default_llm_retry_jitter(),
         }
     }
 }
 
-fn default_llm_provider() -> LlmProviderKind {
-    LlmProviderKind::Anthropic
+/// HTTP client of the LLM providers (`[llm.http]`).
+///
+/// One client is shared by the primary provider and every fallback, so
+/// their connections are pooled together.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LlmHttpConfig {
+    /// Time allowed to establish a connection, in seconds.
+    #[serde(default = "default_llm_connect_timeout_secs")]
+    pub connect_timeout_secs: u64,
+
+    /// Time allowed for a whole request, response included, in seconds.
+    #[serde(default = "default_llm_request_timeout_secs")]
+    pub request_timeout_secs: u64,
+
+    /// Proxy for all provider requests (`http://`, `https://`). The
+    /// `HTTPS_PROXY`/`HTTP_PROXY` environment variables apply when unset.
+    #[serde(default)]
+    pub proxy: Option<String>,
+
+    /// Hosts, domains (`.example.com`), or CIDR ranges reached without
+    /// `proxy`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub no_proxy: Vec<String>,
+
+    /// Idle connections kept open per host.
+    #[serde(default = "default_llm_pool_max_idle_per_host")]
+    pub pool_max_idle_per_host: usize,
+
+    /// Seconds an idle connection is kept open; 0 closes it at once.
+    #[serde(default = "default_llm_pool_idle_timeout_secs")]
+    pub pool_idle_timeout_secs: u64,
+
+    /// Interval of TCP keep-alive probes, in seconds; 0 disables them.
+    #[serde(default = "default_llm_tcp_keepalive_secs")]
+    pub tcp_keepalive_secs: u64,
 }
 
-fn default_llm_model() -> String {
-    "claude-sonnet-4-20250514".to_string()
+impl Default for LlmHttpConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout_secs: default_llm_connect_timeout_secs(),
+            request_timeout_secs: default_llm_request_timeout_secs(),
+            proxy: None,
+            no_proxy: Vec::new(),
+            pool_max_idle_per_host: default_llm_pool_max_idle_per_host(),
+            pool_idle_timeout_secs: default_llm_pool_idle_timeout_secs(),
+            tcp_keepalive_secs: default_llm_tcp_keepalive_secs(),
+        }
+    }
 }
 
-fn default_max_tokens() -> u32 {
-    4096
+fn default_llm_connect_timeout_secs() -> u64 {
+    10
 }
 
-impl AppConfig {
-    /// Load configuration from a TOML file at the given path using async I/O.
-    pub async fn load(path: &Path) -> Result<Self, ConfigError> {
-        let content = tokio::fs::read_to_string(path).await?;
-        let config: AppConfig = toml::from_str(&content)?;
-        config.validate()?;
-        Ok(config)
-    }
+fn default_llm_request_timeout_secs() -> u64 {
+    300
+}
 
-    /// Parse configuration from a TOML string.
-    pub fn parse(s: &str) -> Result<Self, ConfigError> {
-        let config: AppConfig = toml::from_str(s)?;
-        config.validate()?;
-        Ok(config)
-    }
+fn default_llm_pool_max_idle_per_host() -> usize {
+    8
+}
 
-    /// Validate the configuration.
-    pub fn validate(&self) -> Result<(), ConfigError> {
-        if self.daemon.listen_port == 0 {
-            return Err(ConfigError::Validation(
-                "daemon.listen_port must be non-zero".to_string(),
-            ));
+fn default_llm_pool_idle_timeout_secs() -> u64 {
+    90
+}
+
+fn default_llm_tcp_keepalive_secs() -> u64 {
+    60
+}
+
+fn default_llm_max_backoff_ms() -> u64 {
+    30_000
+}
+
+fn default_llm_retry_jitter() -> f64 {
+    0.5
+}
+
+impl LlmConfig {
+    /// `[llm.retry]`, with the deprecated `llm.max_retries` and
+    /// `llm.retry_backoff_ms` applied.
+    pub fn effective_retry(&self) -> LlmRetryConfig {
+        let mut retry = self.retry.clone();
+        if let Some(max_retries) = self.max_retries {
+            retry.max_retries = max_retries;
         }
-        if self.daemon.listen_addr.is_empty() {
-            return Err(ConfigError::Validation(
-                "daemon.listen_addr must not be empty".to_string(),
-            ));
+        if let Some(backoff_ms) = self.retry_backoff_ms {
+            retry.initial_backoff_ms = backoff_ms;
         }
-        // Validate isolation config
-        let valid_backends = [
-            "auto",
-            "docker",
-            "firecracker",
-            "apple-vz",
-            "linux-ns",
-            "noop",
-        ];
-        if !valid_backends.contains(&self.isolation.backend.as_str()) {
-            return Err(ConfigError::Validation(format!(
-                "isolation.backend must be one of {:?}, got {:?}",
-                valid_backends, self.isolation.backend
-            )));
-        }
-        // Validate trust tier if specified
-        if let Some(ref tier) = self.isolation.default_trust_tier {
-            let valid_tiers = ["trusted", "internal", "untrusted", "llm-generated"];
-            if !valid_tiers.contains(&tier.as_str()) {
-                return Err(ConfigError::Validation(format!(
-                    "isolation.default_trust_tier must be one of {:?}, got {:?}",
-                    valid_tiers, tier
-                )));
-            }
-        }
-        if self.isolation.default_cpu_fraction <= 0.0 || self.isolation.default_cpu_fraction > 1.0 {
-            return Err(ConfigError::Validation(format!(
-                "isolation.default_cpu_fraction must be in (0.0, 1.0], got {}",
-                self.isolation.default_cpu_fraction
-            )));
-        }
-        if self.isolation.default_memory_bytes == 0 {
-            return Err(ConfigError::Validation(
-                "isolation.default_memory_bytes must be non-zero".to_string(),
-            ));
-        }
-        let valid_networks = ["none", "host-only", "outbound-only"];
-        if !valid_networks.contains(&self.isolation.default_network.as_str()) {
-            return Err(ConfigError::Validation(format!(
-                "isolation.default_network must be one of {:?}, got {:?}",
-                valid_networks, self.isolation.default_network
-            )));
+        retry
+    }
+}
+
+/// Price of a model (`[llm.pricing."<model>"]`), in USD per million tokens.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelPricing {
+    /// Price per million prompt (input) tokens.
+    #[serde(default)]
+    pub prompt_per_mtok: f64,
+
+    /// Price per million completion (output) tokens.
+    #[serde(default)]
+    pub completion_per_mtok: f64,
+}
+
+impl ModelPricing {
+    /// Estimated cost in USD of `prompt_tokens` and `completion_tokens`.
+    pub fn cost(&self, prompt_tokens: u64, completion_tokens: u64) -> f64 {
+        (prompt_tokens as f64 * self.prompt_per_mtok
+            + completion_tokens as f64 * self.completion_per_mtok)
+            / 1_000_000.0
+    }
+}
+
+/// A fallback LLM provider (`[[llm.fallbacks]]`).
+///
+/// Fallbacks use their own `model` rather than `llm.model`, and share the
+/// primary provider's `max_tokens` and `temperature`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LlmFallbackConfig {
+    /// Provider: "anthropic", "openai", or "llamacpp".
+    #[serde(default = "default_llm_provider")]
+    pub provider: LlmProviderKind,
+
+    /// API key for the provider.
+    #[serde(default)]
+    pub api_key: String,
+
+    /// Environment variable to read the API key from when `api_key` is empty.
+    #[serde(default)]
+    pub api_key_env: Option<String>,
+
+    /// Name of the `[[secrets.entries]]` entry holding the API key; wins
+    /// over `api_key` and `api_key_env`.
+    #[serde(default)]
+    pub api_key_secret: Option<String>,
+
+    /// Model identifier; the provider's default model when empty.
+    #[serde(default)]
+    pub model: String,
+
+    /// Custom API base URL (for OpenAI-compatible providers like Ollama,
+    /// or a llama.cpp server).
+    #[serde(default)]
+    pub base_url: Option<String>,
+}
+
+/// Which LLM provider to use.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+#[derive(Default)]
+pub enum LlmProviderKind {
+    #[default]
+    Anthropic,
+    OpenAi,
+    /// llama.cpp's native server (`llama-server`), via `/completion`.
+    LlamaCpp,
+}
+
+/// Options of the llama.cpp provider (`[llm.llamacpp]`).
+///
+/// ## TOML Example
+///
+/// ```toml
+/// [llm]
+/// provider = "llamacpp"
+/// base_url = "http://127.0.0.1:8080"
+///
+/// [llm.llamacpp]
+/// chat_template = "chatml"
+/// grammar = true
+/// # context_size = 8192   # detected from the server's /props when unset
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LlamaCppConfig {
+    /// Prompt format of the served model.
+    #[serde(default)]
+    pub chat_template: LlamaCppTemplate,
+
+    /// Constrain output with a grammar whenever tools are offered, so that
+    /// the model can only answer or call one of the offered tools.
+    #[serde(default = "default_llamacpp_grammar")]
+    pub grammar: bool,
+
+    /// Context window in tokens; detected from the server when unset.
+    #[serde(default)]
+    pub context_size: Option<u32>,
+}
+
+impl Default for LlamaCppConfig {
+    fn default() -> Self {
+        Self {
+            chat_template: LlamaCppTemplate::default(),
+            grammar: default_llamacpp_grammar(),
+            context_size: None,
         }
-        if self.isolation.max_concurrent == 0 {
-            return Err(ConfigError::Validation(
-                "isolation.max_concurrent must be at least 1".to_string(),
-            ));
+    }
+}
+
+fn default_llamacpp_grammar() -> bool {
+    true
+}
+
+/// Prompt format used with llama.cpp's `/completion` endpoint.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LlamaCppTemplate {
+    /// `<|im_start|>role ... <|im_end|>` (Qwen, Hermes, and most fine-tunes).
+    #[default]
+    ChatMl,
+    /// `<|start_header_id|>role<|end_header_id|> ... <|eot_id|>` (Llama 3).
+    Llama3,
+}
+
+impl Default for LlmConfig {
+    fn default() -> Self {
+        Self {
+            provider: default_llm_provider(),
+            api_key: String::new(),
+            api_key_secret: None,
+            model: default_llm_model(),
+            base_url: None,
+            max_tokens: default_max_tokens(),
+            temperature: 0.0,
+            tokenizer_vocab: None,
+            retry: LlmRetryConfig::default(),
+            http: LlmHttpConfig::default(),
+            max_retries: None,
+            retry_backoff_ms: None,
+            fallbacks: Vec::new(),
+            pricing: BTreeMap::new(),
+            llamacpp: LlamaCppConfig::default(),
         }
+    }
+}
 
-        // Validate policy rules
-        for (i, rule) in self.policy.rules.iter().enumerate() {
-            if rule.effect != "allow" && rule.effect != "deny" {
-                return Err(ConfigError::Validation(format!(
-                    "policy.rules[{i}].effect must be \"allow\" or \"deny\", got {:?}",
-                    rule.effect
-                )));
-            }
-            if rule.role.is_empty() {
-                return Err(ConfigError::Validation(format!(
-                    "policy.rules[{i}].role must not be empty"
-                )));
-            }
+/// Upper bound of `llm.retry.max_retries`.
+pub const MAX_LLM_RETRIES: u32 = 10;
+
+fn default_llm_max_retries() -> u32 {
+    2
+}
+
+fn default_llm_retry_backoff_ms() -> u64 {
+    500
+}
+
+fn default_llm_provider() -> LlmProviderKind {
+    LlmProviderKind::Anthropic
+}
+
+fn default_llm_model() -> String {
+    "claude-sonnet-4-20250514".to_string()
+}
+
+fn default_max_tokens() -> u32 {
+    4096
+}
+
+/// Context engine configuration.
+///
+/// `.env` files, `*.pem`, `id_*`, and the secrets staging directory are always
+/// excluded from indexing and filesystem tools; `sensitive_globs` adds to
+/// that list.
+///
+/// ## TOML Example
+///
+/// ```toml
+/// [context]
+/// sensitive_globs = ["*.key", "*.p12", "config/credentials*"]
+///
+/// [context.environment]
+/// facts = ["hostname", "os", "time", "tools"]
+/// persona = "release engineer"
+///
+/// [context.elevation]
+/// ttl_secs = 600
+/// max_trust = "trusted"
+///
+/// [context.relevance]
+/// query_weight = 0.6
+/// recency_weight = 0.25
+/// usage_weight = 0.15
+///
+/// [context.compaction]
+/// summary_tokens = 512
+/// ```
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ContextConfig {
+    /// Extra glob patterns for paths that must never be read into context.
+    #[serde(default)]
+    pub sensitive_globs: Vec<String>,
+
+    /// Environment preamble injected into every system prompt.
+    #[serde(default)]
+    pub environment: EnvironmentConfig,
+
+    /// One-time tool trust elevation requests.
+    #[serde(default)]
+    pub elevation: ElevationConfig,
+
+    /// Weights for ranking dynamic context items.
+    #[serde(default)]
+    pub relevance: RelevanceConfig,
+
+    /// Summarizing conversation history that does not fit the window.
+    #[serde(default)]
+    pub compaction: CompactionConfig,
+}
+
+/// Facts the environment preamble can report, in rendering order.
+pub const ENVIRONMENT_FACTS: &[&str] = &["hostname", "os", "repo_root", "time", "persona", "tools"];
+
+/// Environment preamble configuration.
+///
+/// The preamble tells the model where it is running so skills don't have to
+/// describe the host themselves. `facts` selects what is reported; a
+/// `template` replaces the default layout, with `{fact}` placeholders for
+/// each entry in [`ENVIRONMENT_FACTS`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnvironmentConfig {
+    /// Inject the preamble at all.
+    #[serde(default = "default_environment_enabled")]
+    pub enabled: bool,
+
+    /// Facts to include (defaults to all of [`ENVIRONMENT_FACTS`]).
+    #[serde(default = "default_environment_facts")]
+    pub facts: Vec<String>,
+
+    /// Custom layout; `None` renders an `<environment>` block.
+    #[serde(default)]
+    pub template: Option<String>,
+
+    /// Active persona reported to the model.
+    #[serde(default)]
+    pub persona: Option<String>,
+
+    /// Repository root; discovered from the working directory when unset.
+    #[serde(default)]
+    pub repo_root: Option<String>,
+}
+
+impl Default for EnvironmentConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            facts: default_environment_facts(),
+            template: None,
+            persona: None,
+            repo_root: None,
         }
+    }
+}
 
-        // Validate secrets config
-        for (i, entry) in self.secrets.entries.iter().enumerate() {
-            if entry.name.is_empty() {
-                return Err(ConfigError::Validation(format!(
-                    "secrets.entries[{i}].name must not be empty"
-                )));
-            }
-            let valid_sources = ["env", "file", "inline"];
-            if !valid_sources.contains(&entry.source.as_str()) {
-                return Err(ConfigError::Validation(format!(
-                    "secrets.entries[{i}].source must be one of {:?}, got {:?}",
-                    valid_sources, entry.source
-                )));
-            }
-            let valid_inject = ["env", "file", "both"];
-            if !valid_inject.contains(&entry.inject_as.as_str()) {
-                return Err(ConfigError::Validation(format!(
-                    "secrets.entries[{i}].inject_as must be one of {:?}, got {:?}",
-                    valid_inject, entry.inject_as
-                )));
-            }
-            // Require inject_env when injecting as env
-            if (entry.inject_as == "env" || entry.inject_as == "both") && entry.inject_env.is_none()
-            {
-                return Err(ConfigError::Validation(format!(
-                    "secrets.entries[{i}].inject_env is required when inject_as is \"{}\"",
-                    entry.inject_as
-                )));
-            }
-            // Require inject_path when injecting as file
-            if (entry.inject_as == "file" || entry.inject_as == "both")
-                && entry.inject_path.is_none()
-            {
-                return Err(ConfigError::Validation(format!(
-                    "secrets.entries[{i}].inject_path is required when inject_as is \"{}\"",
-                    entry.inject_as
-                )));
-            }
-            // Require file_path when source is file
-            if entry.source == "file" && entry.file_path.is_none() {
-                return Err(ConfigError::Validation(format!(
-                    "secrets.entries[{i}].file_path is required when source is \"file\""
-                )));
-            }
+fn default_environment_enabled() -> bool {
+    true
+}
+
+fn default_environment_facts() -> Vec<String> {
+    ENVIRONMENT_FACTS.iter().map(|f| f.to_string()).collect()
+}
+
+/// Tool trust levels, lowest first.
+pub const TOOL_TRUST_LEVELS: &[&str] = &["public", "internal", "trusted", "system"];
+
+/// One-time tool trust elevation.
+///
+/// The agent may ask to run a single tool call above its trust level. Each
+/// request waits for an operator to approve or deny it; an approved request
+/// authorizes exactly that call once.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ElevationConfig {
+    /// Accept elevation requests at all.
+    #[serde(default = "default_elevation_enabled")]
+    pub enabled: bool,
+
+    /// Seconds a request (or an unused approval) stays valid.
+    #[serde(default = "default_elevation_ttl_secs")]
+    pub ttl_secs: u64,
+
+    /// Highest trust level that may be requested.
+    #[serde(default = "default_elevation_max_trust")]
+    pub max_trust: String,
+}
+
+impl Default for ElevationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_elevation_enabled(),
+            ttl_secs: default_elevation_ttl_secs(),
+            max_trust: default_elevation_max_trust(),
         }
+    }
+}
 
-        // Validate auth config
-        let valid_auth_modes = ["local", "token"];
-        if !valid_auth_modes.contains(&self.auth.mode.as_str()) {
-            return Err(ConfigError::Validation(format!(
-                "auth.mode must be one of {:?}, got {:?}",
-                valid_auth_modes, self.auth.mode
-            )));
+fn default_elevation_enabled() -> bool {
+    true
+}
+
+fn default_elevation_ttl_secs() -> u64 {
+    900
+}
+
+fn default_elevation_max_trust() -> String {
+    "trusted".to_string()
+}
+
+/// Relevance scoring for dynamic context (conversation, code, retrieval).
+///
+/// An item's score is the weighted sum of three signals in `[0, 1]`: how many
+/// query terms it contains, how recently its file changed (by mtime or git
+/// history, halving every `recency_half_life_hours`), and how often it was
+/// packed in prior turns. System prompts and tool definitions are always
+/// packed first and are not scored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelevanceConfig {
+    /// Weight of query term overlap.
+    #[serde(default = "default_query_weight")]
+    pub query_weight: f64,
+
+    /// Weight of file recency.
+    #[serde(default = "default_recency_weight")]
+    pub recency_weight: f64,
+
+    /// Weight of prior-turn usage frequency.
+    #[serde(default = "default_usage_weight")]
+    pub usage_weight: f64,
+
+    /// Age at which the recency signal drops to one half.
+    #[serde(default = "default_recency_half_life_hours")]
+    pub recency_half_life_hours: u64,
+}
+
+impl Default for RelevanceConfig {
+    fn default() -> Self {
+        Self {
+            query_weight: default_query_weight(),
+            recency_weight: default_recency_weight(),
+            usage_weight: default_usage_weight(),
+            recency_half_life_hours: default_recency_half_life_hours(),
         }
+    }
+}
 
-        Ok(())
+fn default_query_weight() -> f64 {
+    0.6
+}
+
+fn default_recency_weight() -> f64 {
+    0.25
+}
+
+fn default_usage_weight() -> f64 {
+    0.15
+}
+
+fn default_recency_half_life_hours() -> u64 {
+    72
+}
+
+/// Context compaction.
+///
+/// When the items offered to a context window exceed its budget, the
+/// lowest-priority conversation items are summarized by the LLM and
+/// replaced with the summary, instead of being dropped.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompactionConfig {
+    /// Summarize history that does not fit, instead of dropping it.
+    #[serde(default = "default_compaction_enabled")]
+    pub enabled: bool,
+
+    /// Longest summary, in tokens; also the room kept for it in the window.
+    #[serde(default = "default_compaction_summary_tokens")]
+    pub summary_tokens: u32,
+
+    /// Fewest items worth summarizing; below this they are dropped.
+    #[serde(default = "default_compaction_min_items")]
+    pub min_items: usize,
+}
+
+impl Default for CompactionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_compaction_enabled(),
+            summary_tokens: default_compaction_summary_tokens(),
+            min_items: default_compaction_min_items(),
+        }
+    }
+}
+
+fn default_compaction_enabled() -> bool {
+    true
+}
+
+fn default_compaction_summary_tokens() -> u32 {
+    512
+}
+
+fn default_compaction_min_items() -> usize {
+    2
+}
+
+/// Conversation file transfer configuration.
+///
+/// Files uploaded by operators (`crustyclaw files put`) or received as channel
+/// attachments are stored in a per-conversation workspace under
+/// `workspace_root`, where the agent can also leave files for operators to
+/// fetch with `crustyclaw files get`.
+///
+/// ## TOML Example
+///
+/// ```toml
+/// [files]
+/// workspace_root = "/var/lib/crustyclaw/workspaces"
+/// max_file_bytes = 10485760
+/// allowed_content_types = ["text/*", "image/png", "application/pdf"]
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilesConfig {
+    /// Directory holding one workspace per conversation.
+    #[serde(default = "default_workspace_root")]
+    pub workspace_root: String,
+
+    /// Largest file accepted or served, in bytes.
+    #[serde(default = "default_max_file_bytes")]
+    pub max_file_bytes: u64,
+
+    /// Content types that may be transferred. `"type/*"` matches any subtype.
+    #[serde(default = "default_allowed_content_types")]
+    pub allowed_content_types: Vec<String>,
+}
+
+impl Default for FilesConfig {
+    fn default() -> Self {
+        Self {
+            workspace_root: default_workspace_root(),
+            max_file_bytes: default_max_file_bytes(),
+            allowed_content_types: default_allowed_content_types(),
+        }
+    }
+}
+
+fn default_workspace_root() -> String {
+    "data/workspaces".to_string()
+}
+
+fn default_max_file_bytes() -> u64 {
+    10 * 1024 * 1024 // 10 MiB
+}
+
+fn default_allowed_content_types() -> Vec<String> {
+    ["text/*", "image/*", "application/pdf", "application/json"]
+        .iter()
+        .map(|t| t.to_string())
+        .collect()
+}
+
+/// Attachment spool.
+///
+/// Files received on a channel are copied into a per-message directory under
+/// `spool_dir` and travel with the message as attachment metadata. Skills and
+/// tools run for the message see them read-only under `/attachments`.
+/// Messages with more than `max_per_message` attachments, files larger than
+/// `max_attachment_bytes`, or files that would take the spool past
+/// `max_spool_bytes` have the excess attachments dropped. Spooled files older
+/// than `retention_hours` are deleted at startup and hourly after that.
+///
+/// ## TOML Example
+///
+/// ```toml
+/// [attachments]
+/// spool_dir = "/var/lib/crustyclaw/attachments"
+/// max_attachment_bytes = 26214400
+/// max_per_message = 10
+/// max_spool_bytes = 1073741824
+/// retention_hours = 24
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttachmentsConfig {
+    /// Directory holding one subdirectory of files per message.
+    #[serde(default = "default_attachments_spool_dir")]
+    pub spool_dir: String,
+
+    /// Largest attachment accepted, in bytes.
+    #[serde(default = "default_max_attachment_bytes")]
+    pub max_attachment_bytes: u64,
+
+    /// Most attachments kept from a single message.
+    #[serde(default = "default_max_attachments_per_message")]
+    pub max_per_message: usize,
+
+    /// Total size of the spool, in bytes.
+    #[serde(default = "default_max_spool_bytes")]
+    pub max_spool_bytes: u64,
+
+    /// Hours after which spooled attachments are deleted.
+    #[serde(default = "default_attachment_retention_hours")]
+    pub retention_hours: u64,
+}
+
+impl Default for AttachmentsConfig {
+    fn default() -> Self {
+        Self {
+            spool_dir: default_attachments_spool_dir(),
+            max_attachment_bytes: default_max_attachment_bytes(),
+            max_per_message: default_max_attachments_per_message(),
+            max_spool_bytes: default_max_spool_bytes(),
+            retention_hours: default_attachment_retention_hours(),
+        }
+    }
+}
+
+fn default_attachments_spool_dir() -> String {
+    "data/attachments".to_string()
+}
+
+fn default_max_attachment_bytes() -> u64 {
+    25 * 1024 * 1024 // 25 MiB
+}
+
+fn default_max_attachments_per_message() -> usize {
+    10
+}
+
+fn default_max_spool_bytes() -> u64 {
+    1024 * 1024 * 1024 // 1 GiB
+}
+
+fn default_attachment_retention_hours() -> u64 {
+    24
+}
+
+/// Chat history recording.
+///
+/// Every message on the bus is appended to a per-conversation JSONL file
+/// under `dir`, keyed by channel and peer. Conversations can be listed and
+/// resumed over IPC, and those idle for longer than `retention_days` are
+/// pruned at startup and daily after that (`0` keeps them forever).
+///
+/// ## TOML Example
+///
+/// ```toml
+/// [conversations]
+/// dir = "/var/lib/crustyclaw/conversations"
+/// retention_days = 90
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationsConfig {
+    /// Whether to record conversations.
+    #[serde(default = "default_conversations_enabled")]
+    pub enabled: bool,
+
+    /// Directory holding one history file per conversation.
+    #[serde(default = "default_conversations_dir")]
+    pub dir: String,
+
+    /// Days without activity after which a conversation is deleted.
+    #[serde(default = "default_conversation_retention_days")]
+    pub retention_days: u32,
+}
+
+impl Default for ConversationsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_conversations_enabled(),
+            dir: default_conversations_dir(),
+            retention_days: default_conversation_retention_days(),
+        }
+    }
+}
+
+fn default_conversations_enabled() -> bool {
+    true
+}
+
+fn default_conversations_dir() -> String {
+    "data/conversations".to_string()
+}
+
+fn default_conversation_retention_days() -> u32 {
+    30
+}
+
+/// Long-term conversation memory.
+///
+/// After each agent run, the LLM folds the exchange into a rolling summary
+/// of the conversation and a set of key facts, stored as one file per
+/// conversation under `dir`. Later runs in the same conversation get the
+/// summary and the facts most relevant to the new message in their system
+/// prompt, within `max_tokens`.
+///
+/// ## TOML Example
+///
+/// ```toml
+/// [memory]
+/// dir = "/var/lib/crustyclaw/memory"
+/// max_tokens = 1024
+/// max_facts = 64
+/// summary_tokens = 256
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryConfig {
+    /// Whether to keep and inject conversation memory.
+    #[serde(default = "default_memory_enabled")]
+    pub enabled: bool,
+
+    /// Directory holding one memory file per conversation.
+    #[serde(default = "default_memory_dir")]
+    pub dir: String,
+
+    /// Most tokens of memory injected into a run's system prompt.
+    #[serde(default = "default_memory_max_tokens")]
+    pub max_tokens: u32,
+
+    /// Most facts kept per conversation; the least recently updated are
+    /// forgotten first.
+    #[serde(default = "default_memory_max_facts")]
+    pub max_facts: usize,
+
+    /// Longest rolling summary, in tokens.
+    #[serde(default = "default_memory_summary_tokens")]
+    pub summary_tokens: u32,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_memory_enabled(),
+            dir: default_memory_dir(),
+            max_tokens: default_memory_max_tokens(),
+            max_facts: default_memory_max_facts(),
+            summary_tokens: default_memory_summary_tokens(),
+        }
+    }
+}
+
+fn default_memory_enabled() -> bool {
+    true
+}
+
+fn default_memory_dir() -> String {
+    "data/memory".to_string()
+}
+
+fn default_memory_max_tokens() -> u32 {
+    1024
+}
+
+fn default_memory_max_facts() -> usize {
+    64
+}
+
+fn default_memory_summary_tokens() -> u32 {
+    256
+}
+
+/// Backing store for daemon state.
+///
+/// Conversations, token usage, schedule run history, and the audit logs are
+/// kept in one store. `filesystem` keeps them as files: conversations under
+/// `conversations.dir`, the rest under `daemon.state_dir`. `sqlite` keeps
+/// them all in one database file at `path`, by default
+/// `<daemon.state_dir>/state.db`.
+///
+/// ## TOML Example
+///
+/// ```toml
+/// [storage]
+/// backend = "sqlite"
+/// path = "/var/lib/crustyclaw/state.db"
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StorageConfig {
+    /// Which store to use.
+    #[serde(default)]
+    pub backend: StorageBackend,
+
+    /// SQLite database file; defaults to `<daemon.state_dir>/state.db`.
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+/// Kinds of backing store for daemon state.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// One file per record, in directories.
+    #[default]
+    Filesystem,
+    /// One SQLite database file.
+    Sqlite,
+}
+
+/// Readiness probe settings for `GET /health/ready`.
+///
+/// The isolation backend, Signal channel, and secrets staging directory are
+/// always checked. Connecting to the LLM endpoint is opt-in, since a probe
+/// on every readiness poll reaches out to the provider.
+///
+/// ## TOML Example
+///
+/// ```toml
+/// [health]
+/// probe_llm = true
+/// probe_timeout_secs = 2
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthConfig {
+    /// Check that the LLM endpoint accepts a connection.
+    #[serde(default)]
+    pub probe_llm: bool,
+
+    /// Seconds to wait for the LLM endpoint.
+    #[serde(default = "default_probe_timeout_secs")]
+    pub probe_timeout_secs: u64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            probe_llm: false,
+            probe_timeout_secs: default_probe_timeout_secs(),
+        }
+    }
+}
+
+fn default_probe_timeout_secs() -> u64 {
+    5
+}
+
+/// A remote daemon for the CLI to manage over TLS.
+///
+/// When `addr` is set, the CLI connects to that address with the given
+/// client certificate instead of the local Unix socket. The daemon side is
+/// configured with `[daemon.tls]` and `[auth.cert_map]`.
+///
+/// ## TOML Example
+///
+/// ```toml
+/// [remote]
+/// addr = "agent.example.com:9100"
+/// ca_path = "/home/ops/.config/crustyclaw/ca.crt"
+/// cert_path = "/home/ops/.config/crustyclaw/client.crt"
+/// key_path = "/home/ops/.config/crustyclaw/client.key"
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RemoteConfig {
+    /// Daemon address (`host:port`). Unset uses the local socket.
+    #[serde(default)]
+    pub addr: Option<String>,
+
+    /// Name to verify the server certificate against. Defaults to the host
+    /// part of `addr`.
+    #[serde(default)]
+    pub server_name: Option<String>,
+
+    /// PEM CA certificate(s) the server certificate must chain to.
+    #[serde(default)]
+    pub ca_path: Option<String>,
+
+    /// PEM client certificate chain.
+    #[serde(default)]
+    pub cert_path: Option<String>,
+
+    /// PEM private key for `cert_path`.
+    #[serde(default)]
+    pub key_path: Option<String>,
+}
+
+/// Actions the TUI's keys can be bound to in `[tui.keys]`.
+pub const TUI_ACTIONS: &[&str] = &[
+    "quit",
+    "help",
+    "next_panel",
+    "prev_panel",
+    "panel_1",
+    "panel_2",
+    "panel_3",
+    "panel_4",
+    "panel_5",
+    "scroll_down",
+    "scroll_up",
+    "half_page_down",
+    "half_page_up",
+    "scroll_to_top",
+    "scroll_to_bottom",
+    "cycle_log_level",
+    "next_conversation",
+    "prev_conversation",
+    "toggle_view",
+    "toggle_pause",
+    "search",
+    "next_section",
+    "prev_section",
+    "edit_field",
+    "open",
+];
+
+/// Named keys accepted in `[tui.keys]`, besides single characters.
+pub const TUI_KEY_NAMES: &[&str] = &[
+    "Tab",
+    "BackTab",
+    "Enter",
+    "Esc",
+    "Backspace",
+    "Space",
+    "Up",
+    "Down",
+    "Left",
+    "Right",
+    "PageUp",
+    "PageDown",
+    "Home",
+    "End",
+];
+
+/// Terminal UI settings.
+///
+/// `keys` maps a TUI action to the keys that trigger it, replacing that
+/// action's defaults. A key is a single character (`"j"`, `"G"`, `"?"`),
+/// a name from [`TUI_KEY_NAMES`], or a two-character sequence such as
+/// `"gg"`. Names win over sequences, so `"Up"` is the arrow key.
+///
+/// ## TOML Example
+///
+/// ```toml
+/// [tui.keys]
+/// scroll_down = ["n", "Down"]
+/// scroll_up = ["e", "Up"]
+/// quit = ["Q"]
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TuiConfig {
+    /// Action name (see [`TUI_ACTIONS`]) → the keys bound to it.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub keys: BTreeMap<String, Vec<String>>,
+}
+
+/// Whether `key` is valid in `[tui.keys]`.
+pub fn is_tui_key(key: &str) -> bool {
+    TUI_KEY_NAMES.contains(&key) || matches!(key.chars().count(), 1 | 2)
+}
+
+impl RemoteConfig {
+    /// The name the server certificate must be valid for.
+    pub fn server_name(&self) -> Option<&str> {
+        self.server_name
+            .as_deref()
+            .or_else(|| self.addr.as_deref().map(host_of))
+    }
+}
+
+/// The host part of `host:port`, without brackets around an IPv6 literal.
+fn host_of(addr: &str) -> &str {
+    let host = addr.rsplit_once(':').map_or(addr, |(host, _)| host);
+    host.trim_start_matches('[').trim_end_matches(']')
+}
+
+/// Per-role usage quotas.
+///
+/// Each `[quotas.roles.<role>]` table caps what senders with that role may
+/// consume: LLM tokens per UTC day and sandbox executions per UTC hour.
+/// Unset limits, and roles without a table, are unlimited. Roles are the
+/// ones assigned in `[commands.roles]`.
+///
+/// ## TOML Example
+///
+/// ```toml
+/// [quotas.roles.user]
+/// llm_tokens_per_day = 200000
+/// sandbox_executions_per_hour = 20
+///
+/// [quotas.roles.viewer]
+/// sandbox_executions_per_hour = 0
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QuotasConfig {
+    /// Role → its limits.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub roles: BTreeMap<String, RoleQuotaConfig>,
+}
+
+/// A single `[quotas.roles.<role>]` entry.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoleQuotaConfig {
+    /// LLM tokens (prompt and completion) per UTC day.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub llm_tokens_per_day: Option<u64>,
+
+    /// Sandboxed skill and command executions per UTC hour.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sandbox_executions_per_hour: Option<u64>,
+}
+
+/// Built-in response hooks, usable by name in `response.hooks`.
+pub const BUILTIN_RESPONSE_HOOKS: &[&str] =
+    &["pii", "profanity", "secret_scan", "signature", "split"];
+
+/// Outbound response post-processing configuration.
+///
+/// Every message the agent sends to a channel passes through `hooks` in
+/// order. Names other than the [built-ins](BUILTIN_RESPONSE_HOOKS) refer to
+/// hooks registered by plugins; plugin hooks that are not listed run before
+/// the listed ones.
+///
+/// ## TOML Example
+///
+/// ```toml
+/// [response]
+/// hooks = ["pii", "profanity", "secret_scan", "signature", "split"]
+/// profanity_words = ["darn"]
+/// signature = "— crustyclaw"
+/// max_message_chars = 2000
+/// on_secret = "block"
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponseConfig {
+    /// Hooks to run, in order.
+    #[serde(default = "default_response_hooks")]
+    pub hooks: Vec<String>,
+
+    /// Words masked by the `profanity` hook (case-insensitive, whole words).
+    #[serde(default)]
+    pub profanity_words: Vec<String>,
+
+    /// Footer appended by the `signature` hook.
+    #[serde(default)]
+    pub signature: Option<String>,
+
+    /// Longest message part produced by the `split` hook, in characters.
+    #[serde(default = "default_max_message_chars")]
+    pub max_message_chars: usize,
+
+    /// What `secret_scan` does on a match: "redact" or "block".
+    #[serde(default = "default_on_secret")]
+    pub on_secret: String,
+}
+
+impl Default for ResponseConfig {
+    fn default() -> Self {
+        Self {
+            hooks: default_response_hooks(),
+            profanity_words: Vec::new(),
+            signature: None,
+            max_message_chars: default_max_message_chars(),
+            on_secret: default_on_secret(),
+        }
+    }
+}
+
+fn default_response_hooks() -> Vec<String> {
+    vec!["secret_scan".to_string(), "split".to_string()]
+}
+
+fn default_max_message_chars() -> usize {
+    2000
+}
+
+fn default_on_secret() -> String {
+    "redact".to_string()
+}
+
+/// Opt-in anonymous usage telemetry.
+///
+/// When enabled, the daemon periodically sends coarse counters — version,
+/// enabled features, isolation backend, skill and plugin counts — with local
+/// differential-privacy noise applied before anything leaves the host. No
+/// message content, identifiers, or config values are reported.
+///
+/// ## TOML Example
+///
+/// ```toml
+/// [telemetry]
+/// enabled = true
+/// endpoint = "https://telemetry.example.com/v1/report"
+/// interval_secs = 86400
+/// epsilon = 1.0
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelemetryConfig {
+    /// Whether to send reports at all.
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// URL reports are POSTed to (required when enabled).
+    #[serde(default)]
+    pub endpoint: Option<String>,
+
+    /// Seconds between reports.
+    #[serde(default = "default_telemetry_interval_secs")]
+    pub interval_secs: u64,
+
+    /// Privacy budget per report; smaller values add more noise.
+    #[serde(default = "default_telemetry_epsilon")]
+    pub epsilon: f64,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: None,
+            interval_secs: default_telemetry_interval_secs(),
+            epsilon: default_telemetry_epsilon(),
+        }
+    }
+}
+
+fn default_telemetry_interval_secs() -> u64 {
+    86400
+}
+
+fn default_telemetry_epsilon() -> f64 {
+    1.0
+}
+
+/// External Model Context Protocol (MCP) servers whose tools are imported
+/// into the tool registry.
+///
+/// Each server is reached over stdio (`command`) or streamable HTTP (`url`).
+/// Its tools are registered as `<server>__<tool>` at the server's `trust`
+/// level and tagged with `tag_prefix` and `<tag_prefix>:<server>`, so they
+/// are scoped like any built-in tool.
+///
+/// ## TOML Example
+///
+/// ```toml
+/// [[mcp.servers]]
+/// name = "github"
+/// command = ["github-mcp-server", "stdio"]
+/// env = { GITHUB_TOOLSETS = "repos,issues" }
+/// trust = "internal"
+///
+/// [[mcp.servers]]
+/// name = "search"
+/// url = "http://127.0.0.1:8931/mcp"
+/// trust = "public"
+/// tag_prefix = "web"
+/// ```
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct McpConfig {
+    /// Servers to connect to.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub servers: Vec<McpServerConfig>,
+}
+
+/// A single `[[mcp.servers]]` entry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpServerConfig {
+    /// Server name; prefixes its tool names (letters, digits, `-` and `_`).
+    pub name: String,
+
+    /// Program and arguments to spawn for the stdio transport.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub command: Vec<String>,
+
+    /// Extra environment variables for `command`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+
+    /// Endpoint for the streamable HTTP transport.
+    #[serde(default)]
+    pub url: Option<String>,
+
+    /// Trust level required to use the server's tools.
+    #[serde(default = "default_mcp_trust")]
+    pub trust: String,
+
+    /// Tag prefix applied to the server's tools.
+    #[serde(default = "default_mcp_tag_prefix")]
+    pub tag_prefix: String,
+
+    /// Seconds to wait for each response from the server.
+    #[serde(default = "default_mcp_timeout_secs")]
+    pub timeout_secs: u64,
+
+    /// Connect to this server at all.
+    #[serde(default = "default_mcp_enabled")]
+    pub enabled: bool,
+}
+
+fn default_mcp_trust() -> String {
+    "internal".to_string()
+}
+
+fn default_mcp_tag_prefix() -> String {
+    "mcp".to_string()
+}
+
+fn default_mcp_timeout_secs() -> u64 {
+    30
+}
+
+fn default_mcp_enabled() -> bool {
+    true
+}
+
+/// Native plugins loaded from shared libraries at daemon startup.
+///
+/// Every `*.so` (`*.dylib` on macOS) in `dir` is a candidate. A library is
+/// only opened when the policy allows role `daemon` to `load` the resource
+/// `plugins/<name>`, where `<name>` is the file stem without a `lib` prefix,
+/// and it must export the C ABI version the daemon was built with. Its tools
+/// are registered as `<name>__<tool>` at `trust` and tagged `plugin` and
+/// `plugin:<name>`; its skills are registered under their own names.
+///
+/// Loading requires a daemon built with the `dylib-plugins` feature.
+/// Plugins run in the daemon process with its privileges and are not
+/// unloaded until it exits; changes take effect on restart.
+///
+/// ## TOML Example
+///
+/// ```toml
+/// [plugins]
+/// enabled = true
+/// dir = "/var/lib/crustyclaw/plugins"
+/// trust = "trusted"
+///
+/// [[policy.rules]]
+/// role = "daemon"
+/// action = "load"
+/// resource = "plugins/weather"
+/// effect = "allow"
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginsConfig {
+    /// Load plugins at all.
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Directory scanned for plugin libraries. Relative paths are resolved
+    /// against `daemon.state_dir`.
+    #[serde(default = "default_plugins_dir")]
+    pub dir: String,
+
+    /// Trust level required to use plugin tools.
+    #[serde(default = "default_plugins_trust")]
+    pub trust: String,
+
+    /// Forgejo Action plugins shipped as WASM modules.
+    #[serde(default)]
+    pub wasm: WasmPluginsConfig,
+}
+
+impl Default for PluginsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: default_plugins_dir(),
+            trust: default_plugins_trust(),
+            wasm: WasmPluginsConfig::default(),
+        }
+    }
+}
+
+/// Forgejo Action plugins run as WASM modules in a sandboxed runtime.
+///
+/// Every `*.wasm` in `dir` is compiled at startup and offered to the LLM as
+/// the tool `action__<name>`. A run gets no filesystem, network, or clock
+/// access; it sees only its inputs and the tools its caller may use, and is
+/// stopped once it has used `fuel` units or grows its memory past
+/// `memory_bytes`. With `hot_reload`, `dir` is polled every
+/// `reload_interval_secs` and changed modules are recompiled.
+///
+/// Requires a daemon built with the `wasm-plugins` feature.
+///
+/// ## TOML Example
+///
+/// ```toml
+/// [plugins.wasm]
+/// enabled = true
+/// dir = "/var/lib/crustyclaw/actions"
+/// fuel = 50000000
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WasmPluginsConfig {
+    /// Load WASM plugins at all.
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Directory scanned for `*.wasm` modules. Relative paths are resolved
+    /// against `daemon.state_dir`.
+    #[serde(default = "default_wasm_plugins_dir")]
+    pub dir: String,
+
+    /// Trust level required to use the plugin tools.
+    #[serde(default = "default_wasm_plugins_trust")]
+    pub trust: String,
+
+    /// Fuel (roughly, WASM instructions) a single run may consume.
+    #[serde(default = "default_wasm_plugins_fuel")]
+    pub fuel: u64,
+
+    /// Maximum linear memory of a single run, in bytes.
+    #[serde(default = "default_wasm_plugins_memory")]
+    pub memory_bytes: u64,
+
+    /// Recompile modules when files in `dir` change.
+    #[serde(default = "default_wasm_plugins_hot_reload")]
+    pub hot_reload: bool,
+
+    /// How often `dir` is checked for changes.
+    #[serde(default = "default_wasm_plugins_reload_interval")]
+    pub reload_interval_secs: u64,
+}
+
+impl Default for WasmPluginsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: default_wasm_plugins_dir(),
+            trust: default_wasm_plugins_trust(),
+            fuel: default_wasm_plugins_fuel(),
+            memory_bytes: default_wasm_plugins_memory(),
+            hot_reload: default_wasm_plugins_hot_reload(),
+            reload_interval_secs: default_wasm_plugins_reload_interval(),
+        }
+    }
+}
+
+fn default_wasm_plugins_dir() -> String {
+    "actions".to_string()
+}
+
+fn default_wasm_plugins_trust() -> String {
+    "internal".to_string()
+}
+
+fn default_wasm_plugins_fuel() -> u64 {
+    100_000_000
+}
+
+fn default_wasm_plugins_memory() -> u64 {
+    64 * 1024 * 1024 // 64 MiB
+}
+
+fn default_wasm_plugins_hot_reload() -> bool {
+    true
+}
+
+fn default_wasm_plugins_reload_interval() -> u64 {
+    2
+}
+
+fn default_plugins_dir() -> String {
+    "plugins".to_string()
+}
+
+fn default_plugins_trust() -> String {
+    "trusted".to_string()
+}
+
+/// Chat command aliases that run a skill directly, without the LLM.
+///
+/// A message whose whole text matches an alias (ignoring case, surrounding
+/// whitespace, and a leading `/`) invokes the alias's skill with its preset
+/// `args`, provided the sender's role is at least `role`. Senders are given
+/// roles by their channel peer ID in `roles`; everyone else has
+/// `default_role`. `/help` lists the aliases the sender may run.
+///
+/// Messages starting with `prefix` are built-in commands (`!status`,
+/// `!run <skill>`, `!policy check ...`), gated by the sender's role and the
+/// `[policy]` rules.
+///
+/// ## TOML Example
+///
+/// ```toml
+/// [commands]
+/// default_role = "viewer"
+/// prefix = "!"
+///
+/// [commands.roles]
+/// "+15550001" = "operator"
+///
+/// [commands.aliases."deploy staging"]
+/// skill = "deploy"
+/// args = { env = "staging", branch = "main" }
+/// role = "operator"
+/// description = "Deploy main to staging"
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandsConfig {
+    /// Alias text → the invocation it stands for.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, CommandAliasConfig>,
+
+    /// Channel peer ID (e.g. a Signal phone number) → role.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub roles: BTreeMap<String, String>,
+
+    /// Role of senders not listed in `roles`.
+    #[serde(default = "default_commands_default_role")]
+    pub default_role: String,
+
+    /// Prefix of the built-in commands (`!status`, `!run`, `!policy`).
+    /// Empty disables them.
+    #[serde(default = "default_commands_prefix")]
+    pub prefix: String,
+}
+
+impl Default for CommandsConfig {
+    fn default() -> Self {
+        Self {
+            aliases: BTreeMap::new(),
+            roles: BTreeMap::new(),
+            default_role: default_commands_default_role(),
+            prefix: default_commands_prefix(),
+        }
+    }
+}
+
+fn default_commands_default_role() -> String {
+    "user".to_string()
+}
+
+fn default_commands_prefix() -> String {
+    "!".to_string()
+}
+
+/// A single `[commands.aliases."<alias>"]` entry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandAliasConfig {
+    /// Skill to invoke.
+    pub skill: String,
+
+    /// Preset arguments passed to the skill.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub args: BTreeMap<String, toml::Value>,
+
+    /// Minimum role required to run the alias.
+    #[serde(default = "default_commands_default_role")]
+    pub role: String,
+
+    /// One-line description shown by `/help`.
+    #[serde(default)]
+    pub description: String,
+}
+
+/// A `[[schedules]]` entry: run a skill or an agent prompt on a cron
+/// schedule.
+///
+/// Cron expressions are evaluated in UTC; see [`cron`] for the syntax. A
+/// run that is still going when the next one comes due is skipped rather
+/// than overlapped.
+///
+/// ## TOML Example
+///
+/// ```toml
+/// [[schedules]]
+/// name = "nightly-backup"
+/// cron = "0 3 * * *"
+/// skill = "backup"
+/// args = { target = "s3" }
+/// jitter_secs = 300
+///
+/// [[schedules]]
+/// name = "standup-digest"
+/// cron = "30 8 * * mon-fri"
+/// prompt = "Summarise yesterday's failed builds."
+/// role = "operator"
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleConfig {
+    /// Unique name, used by `crustyclaw schedule run-now`.
+    pub name: String,
+
+    /// Five-field cron expression or `@daily`-style macro.
+    pub cron: String,
+
+    /// Skill to invoke. Exactly one of `skill` and `prompt` must be set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skill: Option<String>,
+
+    /// Arguments passed to the skill.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub args: BTreeMap<String, toml::Value>,
+
+    /// Message sent to the agent in a fresh chat session.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+
+    /// Role the agent session runs as, which decides the tools it may use.
+    #[serde(default = "default_commands_default_role")]
+    pub role: String,
+
+    /// Delay each run by a random 0..=`jitter_secs` seconds.
+    #[serde(default)]
+    pub jitter_secs: u64,
+
+    /// Whether the schedule fires. Disabled schedules can still be run by
+    /// hand.
+    #[serde(default = "default_schedule_enabled")]
+    pub enabled: bool,
+}
+
+fn default_schedule_enabled() -> bool {
+    true
+}
+
+/// Names of the built-in agent tools, which `[[tools]]` entries may not take.
+pub const BUILTIN_TOOLS: &[&str] = &[
+    "search_code",
+    "read_file",
+    "list_files",
+    "list_symbols",
+    "run_command",
+    "request_elevation",
+    "daemon_status",
+];
+
+/// A `[[tools]]` entry: a tool the agent can call, implemented by a command
+/// run in the chat sandbox.
+///
+/// The command runs with the workspace mounted at `/workspace`. The call's
+/// arguments are passed as JSON in `CRUSTYCLAW_ARGS`, and each string,
+/// number, or boolean argument also as `CRUSTYCLAW_ARG_<NAME>`. Standard
+/// output is the tool result; a non-zero exit reports standard error.
+///
+/// ## TOML Example
+///
+/// ```toml
+/// [[tools]]
+/// name = "lint"
+/// description = "Run the linter on a path and report findings."
+/// command = ["sh", "-c", "ruff check \"$CRUSTYCLAW_ARG_PATH\""]
+/// trust = "public"
+/// tags = ["code"]
+/// timeout_secs = 120
+///
+/// [tools.parameters]
+/// type = "object"
+/// required = ["path"]
+///
+/// [tools.parameters.properties.path]
+/// type = "string"
+/// description = "File or directory to lint"
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolConfig {
+    /// Tool name offered to the model (letters, digits, `-` and `_`).
+    pub name: String,
+
+    /// What the tool does, for the model.
+    pub description: String,
+
+    /// JSON Schema of the arguments; must describe an object.
+    #[serde(default = "default_tool_parameters")]
+    pub parameters: serde_json::Value,
+
+    /// Program and arguments run in the sandbox.
+    pub command: Vec<String>,
+
+    /// Trust level required to call the tool.
+    #[serde(default = "default_tool_trust")]
+    pub trust: String,
+
+    /// Tags for scoping the tool to tasks.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    /// Seconds the command may run; the chat sandbox's limit when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+
+    /// Offer the tool at all.
+    #[serde(default = "default_tool_enabled")]
+    pub enabled: bool,
+}
+
+fn default_tool_parameters() -> serde_json::Value {
+    serde_json::json!({"type": "object", "properties": {}})
+}
+
+fn default_tool_trust() -> String {
+    "internal".to_string()
+}
+
+fn default_tool_enabled() -> bool {
+    true
+}
+
+/// JSON Schema type names.
+const JSON_SCHEMA_TYPES: &[&str] = &[
+    "string", "number", "integer", "boolean", "array", "object", "null",
+];
+
+/// Check that `schema` is a JSON Schema for tool arguments: an object
+/// schema whose properties are schemas with known types, whose `required`
+/// names only declared properties, and that compiles.
+pub fn check_tool_schema(schema: &serde_json::Value) -> Result<(), String> {
+    let object = schema.as_object().ok_or("must be a table")?;
+    if object.get("type").and_then(|t| t.as_str()) != Some("object") {
+        return Err("type must be \"object\"".to_string());
+    }
+    let empty = serde_json::Map::new();
+    let properties = match object.get("properties") {
+        None => &empty,
+        Some(properties) => properties.as_object().ok_or("properties must be a table")?,
+    };
+    for (name, property) in properties {
+        check_property_schema(property).map_err(|e| format!("properties.{name}: {e}"))?;
+    }
+    if let Some(required) = object.get("required") {
+        let required = required
+            .as_array()
+            .ok_or("required must be an array of property names")?;
+        for name in required {
+            let name = name
+                .as_str()
+                .ok_or("required must be an array of property names")?;
+            if !properties.contains_key(name) {
+                return Err(format!("required property {name:?} is not in properties"));
+            }
+        }
+    }
+    jsonschema::validator_for(schema).map_err(|e| format!("invalid JSON Schema: {e}"))?;
+    Ok(())
+}
+
+fn check_property_schema(schema: &serde_json::Value) -> Result<(), String> {
+    let object = schema.as_object().ok_or("must be a table")?;
+    let known = |t: &serde_json::Value| t.as_str().is_some_and(|t| JSON_SCHEMA_TYPES.contains(&t));
+    match object.get("type") {
+        None => {}
+        Some(serde_json::Value::Array(types)) if !types.is_empty() && types.iter().all(known) => {}
+        Some(t) if known(t) => {}
+        Some(t) => {
+            return Err(format!(
+                "type must be one of {JSON_SCHEMA_TYPES:?} or an array of them, got {t}"
+            ));
+        }
+    }
+    if object.get("enum").is_some_and(|e| !e.is_array()) {
+        return Err("enum must be an array".to_string());
+    }
+    if let Some(items) = object.get("items") {
+        check_property_schema(items).map_err(|e| format!("items: {e}"))?;
+    }
+    if let Some(properties) = object.get("properties") {
+        for (name, property) in properties.as_object().ok_or("properties must be a table")? {
+            check_property_schema(property).map_err(|e| format!("properties.{name}: {e}"))?;
+        }
+    }
+    Ok(())
+}
+
+/// Interactive agent sessions opened with `crustyclaw chat`.
+///
+/// Each session runs the agent loop with the tools its caller may use. A
+/// `[[policy.rules]]` entry for the `call` action on `tools/<name>` allows
+/// or denies a tool outright; otherwise the tool is offered when its trust
+/// level is at most the caller's, from `tool_trust`.
+///
+/// ## TOML Example
+///
+/// ```toml
+/// [chat]
+/// system_prompt = "You are the on-call assistant for the build farm."
+/// max_iterations = 8
+///
+/// [chat.tool_trust]
+/// admin = "trusted"
+/// operator = "internal"
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatConfig {
+    /// System prompt sent with every request.
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+
+    /// Model round trips allowed per message.
+    #[serde(default = "default_chat_max_iterations")]
+    pub max_iterations: u32,
+
+    /// Tokens allowed per message across all round trips; unlimited when unset.
+    #[serde(default)]
+    pub token_budget: Option<u32>,
+
+    /// Seconds allowed per message across all round trips; each model
+    /// request is cut short at this deadline. Unlimited when unset.
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+
+    /// Role → highest tool trust level its holders get. Roles not listed
+    /// get `public`.
+    #[serde(default = "default_chat_tool_trust")]
+    pub tool_trust: BTreeMap<String, String>,
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        Self {
+            system_prompt: None,
+            max_iterations: default_chat_max_iterations(),
+            token_budget: None,
+            timeout_secs: None,
+            tool_trust: default_chat_tool_trust(),
+        }
+    }
+}
+
+fn default_chat_max_iterations() -> u32 {
+    16
+}
+
+fn default_chat_tool_trust() -> BTreeMap<String, String> {
+    BTreeMap::from([
+        ("admin".to_string(), "trusted".to_string()),
+        ("operator".to_string(), "internal".to_string()),
+    ])
+}
+
+/// Normalize chat text for alias matching: trimmed, without a leading `/`,
+/// lowercased, with runs of whitespace collapsed to one space.
+pub fn normalize_command(text: &str) -> String {
+    let text = text.trim();
+    text.strip_prefix('/')
+        .unwrap_or(text)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Stands in for secret values in [`AppConfig::redacted`].
+pub const REDACTED: &str = "[REDACTED]";
+
+impl AppConfig {
+    /// Load configuration from a TOML file at the given path using async I/O,
+    /// with `CRUSTYCLAW__*` environment overrides applied on top.
+    pub async fn load(path: &Path) -> Result<Self, ConfigError> {
+        Ok(Self::load_layered(Some(path), &[]).await?.0)
+    }
+
+    /// Load configuration from the file at `path` (if given) and the files
+    /// it includes, then apply
+    /// `CRUSTYCLAW__*` environment overrides and `flags`, in that order.
+    /// Finally, load and verify the `policy.bundle`, if any; see [`bundle`].
+    ///
+    /// Also returns where each value came from; see [`layers`].
+    pub async fn load_layered(
+        path: Option<&Path>,
+        flags: &[layers::Override],
+    ) -> Result<(Self, layers::ConfigOrigins), ConfigError> {
+        let files = match path {
+            Some(path) => include::read_files(path).await?,
+            None => Vec::new(),
+        };
+        let mut overrides = layers::Override::from_env(std::env::vars())?;
+        overrides.extend_from_slice(flags);
+        let (mut config, origins) = layers::resolve(&files, &overrides)?;
+        let base = path.and_then(Path::parent).unwrap_or(Path::new(""));
+        bundle::apply(&mut config, base).await?;
+        Ok((config, origins))
+    }
+
+    /// Parse configuration from a TOML string.
+    ///
+    /// `include` entries are recorded but not followed.
+    pub fn parse(s: &str) -> Result<Self, ConfigError> {
+        let config: AppConfig = toml::from_str(s)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// A copy for display to other users, with the secrets the config can
+    /// hold inline (`llm.api_key`, `llm.fallbacks[].api_key`, and inline
+    /// `secrets.entries[].value`) replaced by [`REDACTED`].
+    pub fn redacted(&self) -> Self {
+        let mut config = self.clone();
+        let redact = |value: &mut String| {
+            if !value.is_empty() {
+                *value = REDACTED.to_string();
+            }
+        };
+        redact(&mut config.llm.api_key);
+        for fallback in &mut config.llm.fallbacks {
+            redact(&mut fallback.api_key);
+        }
+        for entry in &mut config.secrets.entries {
+            if let Some(value) = &mut entry.value {
+                redact(value);
+            }
+        }
+        config
+    }
+
+    /// Validate the configuration.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        for (i, pattern) in self.include.iter().enumerate() {
+            if pattern.trim().is_empty() {
+                return Err(ConfigError::Validation(format!(
+                    "include[{i}] must not be empty"
+                )));
+            }
+            if pattern.contains("**") {
+                return Err(ConfigError::Validation(format!(
+                    "include[{i}] {pattern:?}: `**` is not supported"
+                )));
+            }
+        }
+        if self.daemon.listen_port == 0 {
+            return Err(ConfigError::Validation(
+                "daemon.listen_port must be non-zero".to_string(),
+            ));
+        }
+        if self.daemon.listen_addr.is_empty() {
+            return Err(ConfigError::Validation(
+                "daemon.listen_addr must not be empty".to_string(),
+            ));
+        }
+        if self.daemon.skills_dir.as_deref() == Some("") {
+            return Err(ConfigError::Validation(
+                "daemon.skills_dir must not be empty when set".to_string(),
+            ));
+        }
+        if self.daemon.pid_file.as_deref() == Some("") {
+            return Err(ConfigError::Validation(
+                "daemon.pid_file must not be empty when set".to_string(),
+            ));
+        }
+        if self.logging.file.as_deref() == Some("") {
+            return Err(ConfigError::Validation(
+                "logging.file must not be empty when set".to_string(),
+            ));
+        }
+        if self.logging.max_size_bytes == 0 {
+            return Err(ConfigError::Validation(
+                "logging.max_size_bytes must be non-zero".to_string(),
+            ));
+        }
+        if self.logging.max_files == 0 {
+            return Err(ConfigError::Validation(
+                "logging.max_files must be at least 1".to_string(),
+            ));
+        }
+        if !LOG_FORMATS.contains(&self.logging.format.as_str()) {
+            return Err(ConfigError::Validation(format!(
+                "logging.format must be one of {LOG_FORMATS:?}, got {:?}",
+                self.logging.format
+            )));
+        }
+        if let Some(addr) = &self.daemon.metrics_addr
+            && addr.parse::<std::net::SocketAddr>().is_err()
+        {
+            return Err(ConfigError::Validation(format!(
+                "daemon.metrics_addr must be a host:port socket address, got {addr:?}"
+            )));
+        }
+        if self.daemon.tls.enabled {
+            for (key, value) in [
+                ("cert_path", &self.daemon.tls.cert_path),
+                ("key_path", &self.daemon.tls.key_path),
+                ("client_ca_path", &self.daemon.tls.client_ca_path),
+            ] {
+                if value.as_deref().is_none_or(|v| v.trim().is_empty()) {
+                    return Err(ConfigError::Validation(format!(
+                        "daemon.tls.{key} is required when daemon.tls.enabled is true"
+                    )));
+                }
+            }
+        }
+        // Validate isolation config
+        let valid_backends = [
+            "auto",
+            "docker",
+            "podman",
+            "firecracker",
+            "apple-vz",
+            "linux-ns",
+            "noop",
+        ];
+        if !valid_backends.contains(&self.isolation.backend.as_str()) {
+            return Err(ConfigError::Validation(format!(
+                "isolation.backend must be one of {:?}, got {:?}",
+                valid_backends, self.isolation.backend
+            )));
+        }
+        // Validate trust tier if specified
+        if let Some(ref tier) = self.isolation.default_trust_tier
+            && !TRUST_TIERS.contains(&tier.as_str())
+        {
+            return Err(ConfigError::Validation(format!(
+                "isolation.default_trust_tier must be one of {:?}, got {:?}",
+                TRUST_TIERS, tier
+            )));
+        }
+        let trust = &self.isolation.trust;
+        for (tier, level) in &trust.levels {
+            if !TRUST_TIERS.contains(&tier.as_str()) {
+                return Err(ConfigError::Validation(format!(
+                    "isolation.trust.levels keys must be one of {TRUST_TIERS:?}, got {tier:?}"
+                )));
+            }
+            if parse_isolation_level(level).is_none() {
+                return Err(ConfigError::Validation(format!(
+                    "isolation.trust.levels.{tier} must be \"l1\", \"l2\", or \"l3\", got {level:?}"
+                )));
+            }
+        }
+        for tier in TRUST_TIERS {
+            let Some(backend) = trust.backend(tier) else {
+                continue;
+            };
+            if !valid_backends.contains(&backend) {
+                return Err(ConfigError::Validation(format!(
+                    "isolation.trust.{tier} must be one of {valid_backends:?}, got {backend:?}"
+                )));
+            }
+            let (Some(provided), Some(required)) =
+                (backend_isolation_level(backend), trust.level(tier))
+            else {
+                continue;
+            };
+            if provided < required {
+                return Err(ConfigError::Validation(format!(
+                    "isolation.trust.{tier} = {backend:?} provides L{provided} isolation, but \
+                     the {tier} tier requires L{required}; lower isolation.trust.levels.{tier} \
+                     to allow it"
+                )));
+            }
+        }
+        if self.isolation.default_cpu_fraction <= 0.0 || self.isolation.default_cpu_fraction > 1.0 {
+            return Err(ConfigError::Validation(format!(
+                "isolation.default_cpu_fraction must be in (0.0, 1.0], got {}",
+                self.isolation.default_cpu_fraction
+            )));
+        }
+        if self.isolation.default_memory_bytes == 0 {
+            return Err(ConfigError::Validation(
+                "isolation.default_memory_bytes must be non-zero".to_string(),
+            ));
+        }
+        let valid_networks = ["none", "host-only", "outbound-only"];
+        if !valid_networks.contains(&self.isolation.default_network.as_str()) {
+            return Err(ConfigError::Validation(format!(
+                "isolation.default_network must be one of {:?}, got {:?}",
+                valid_networks, self.isolation.default_network
+            )));
+        }
+        if self.isolation.max_concurrent == 0 {
+            return Err(ConfigError::Validation(
+                "isolation.max_concurrent must be at least 1".to_string(),
+            ));
+        }
+        if self.isolation.strict_images && image_floats(&self.isolation.docker_image) {
+            return Err(ConfigError::Validation(format!(
+                "isolation.docker_image must name a specific tag or digest when \
+                 isolation.strict_images is true, got {:?}",
+                self.isolation.docker_image
+            )));
+        }
+        if self.isolation.credential_proxy
+            && !self
+                .isolation
+                .egress_proxy_addr
+                .parse::<std::net::SocketAddr>()
+                .is_ok_and(|addr| !addr.ip().is_unspecified())
+        {
+            return Err(ConfigError::Validation(format!(
+                "isolation.egress_proxy_addr must be a socket address with a specific IP, got {:?}",
+                self.isolation.egress_proxy_addr
+            )));
+        }
+        for host in &self.isolation.egress_allowed_hosts {
+            if !is_host_pattern(host) {
+                return Err(ConfigError::Validation(format!(
+                    "isolation.egress_allowed_hosts entries must be host names or \"*.domain\", got {host:?}"
+                )));
+            }
+        }
+
+        // Validate policy rules
+        for (i, rule) in self.policy.rules.iter().enumerate() {
+            if rule.effect != "allow" && rule.effect != "deny" {
+                return Err(ConfigError::Validation(format!(
+                    "policy.rules[{i}].effect must be \"allow\" or \"deny\", got {:?}",
+                    rule.effect
+                )));
+            }
+            if rule.role.is_empty() {
+                return Err(ConfigError::Validation(format!(
+                    "policy.rules[{i}].role must not be empty"
+                )));
+            }
+            for (field, value) in [
+                ("role", &rule.role),
+                ("action", &rule.action),
+                ("resource", &rule.resource),
+            ] {
+                pattern::Pattern::parse(value).map_err(|e| {
+                    ConfigError::Validation(format!("policy.rules[{i}].{field} {value:?}: {e}"))
+                })?;
+            }
+            if let Some(when) = &rule.when {
+                condition::Condition::parse(when)
+                    .map_err(|e| ConfigError::Validation(format!("policy.rules[{i}].when: {e}")))?;
+            }
+            if let Some(id) = &rule.id {
+                if id.trim().is_empty() {
+                    return Err(ConfigError::Validation(format!(
+                        "policy.rules[{i}].id must not be empty"
+                    )));
+                }
+                if self.policy.rules[..i]
+                    .iter()
+                    .any(|other| other.id.as_ref() == Some(id))
+                {
+                    return Err(ConfigError::Validation(format!(
+                        "policy.rules[{i}].id {id:?} is used by an earlier rule"
+                    )));
+                }
+            }
+        }
+
+        validate_policy_tests(&self.policy.tests, "policy.tests")?;
+        if let Some(bundle) = &self.policy.bundle
+            && bundle.trim().is_empty()
+        {
+            return Err(ConfigError::Validation(
+                "policy.bundle must not be empty".to_string(),
+            ));
+        }
+
+        for (name, key) in &self.security.trusted_keys {
+            minisign::PublicKey::parse(key).map_err(|e| {
+                ConfigError::Validation(format!("security.trusted_keys.{name}: {e}"))
+            })?;
+        }
+        if self.security.strict {
+            if self.policy.bundle.is_none() {
+                return Err(ConfigError::Validation(
+                    "security.strict requires policy.bundle".to_string(),
+                ));
+            }
+            if self.security.trusted_keys.is_empty() {
+                return Err(ConfigError::Validation(
+                    "security.strict requires at least one security.trusted_keys entry".to_string(),
+                ));
+            }
+        }
+
+        // Validate secrets config
+        for (i, entry) in self.secrets.entries.iter().enumerate() {
+            if entry.name.is_empty() {
+                return Err(ConfigError::Validation(format!(
+                    "secrets.entries[{i}].name must not be empty"
+                )));
+            }
+            if !SECRET_SOURCES.contains(&entry.source.as_str()) {
+                return Err(ConfigError::Validation(format!(
+                    "secrets.entries[{i}].source must be one of {SECRET_SOURCES:?}, got {:?}",
+                    entry.source
+                )));
+            }
+            let valid_inject = ["env", "file", "both"];
+            if !valid_inject.contains(&entry.inject_as.as_str()) {
+                return Err(ConfigError::Validation(format!(
+                    "secrets.entries[{i}].inject_as must be one of {:?}, got {:?}",
+                    valid_inject, entry.inject_as
+                )));
+            }
+            // Require inject_env when injecting as env
+            if (entry.inject_as == "env" || entry.inject_as == "both") && entry.inject_env.is_none()
+            {
+                return Err(ConfigError::Validation(format!(
+                    "secrets.entries[{i}].inject_env is required when inject_as is \"{}\"",
+                    entry.inject_as
+                )));
+            }
+            // Require inject_path when injecting as file
+            if (entry.inject_as == "file" || entry.inject_as == "both")
+                && entry.inject_path.is_none()
+            {
+                return Err(ConfigError::Validation(format!(
+                    "secrets.entries[{i}].inject_path is required when inject_as is \"{}\"",
+                    entry.inject_as
+                )));
+            }
+            // Require file_path when source is file
+            if entry.source == "file" && entry.file_path.is_none() {
+                return Err(ConfigError::Validation(format!(
+                    "secrets.entries[{i}].file_path is required when source is \"file\""
+                )));
+            }
+            if let Some(host) = entry.egress_hosts.iter().find(|h| !is_host_pattern(h)) {
+                return Err(ConfigError::Validation(format!(
+                    "secrets.entries[{i}].egress_hosts entries must be host names or \"*.domain\", got {host:?}"
+                )));
+            }
+            for (field, value) in [
+                ("keychain_service", &entry.keychain_service),
+                ("keychain_account", &entry.keychain_account),
+            ] {
+                if value.as_deref() == Some("") {
+                    return Err(ConfigError::Validation(format!(
+                        "secrets.entries[{i}].{field} must not be empty"
+                    )));
+                }
+            }
+        }
+
+        let keystore = &self.secrets.keystore;
+        if keystore.path.is_empty() {
+            return Err(ConfigError::Validation(
+                "secrets.keystore.path must not be empty".to_string(),
+            ));
+        }
+        if !KEYSTORE_KEY_SOURCES.contains(&keystore.key_source.as_str()) {
+            return Err(ConfigError::Validation(format!(
+                "secrets.keystore.key_source must be one of {KEYSTORE_KEY_SOURCES:?}, got {:?}",
+                keystore.key_source
+            )));
+        }
+        if keystore.key_source == "file" && keystore.key_file.is_empty() {
+            return Err(ConfigError::Validation(
+                "secrets.keystore.key_file must not be empty".to_string(),
+            ));
+        }
+
+        for (i, glob) in self.context.sensitive_globs.iter().enumerate() {
+            if glob.trim().is_empty() {
+                return Err(ConfigError::Validation(format!(
+                    "context.sensitive_globs[{i}] must not be empty"
+                )));
+            }
+        }
+
+        let env = &self.context.environment;
+        for (i, fact) in env.facts.iter().enumerate() {
+            if !ENVIRONMENT_FACTS.contains(&fact.as_str()) {
+                return Err(ConfigError::Validation(format!(
+                    "context.environment.facts[{i}] is not a known fact: \"{fact}\""
+                )));
+            }
+        }
+        if let Some(template) = &env.template {
+            let mut rest = template.as_str();
+            while let Some(start) = rest.find('{') {
+                let Some(len) = rest[start..].find('}') else {
+                    return Err(ConfigError::Validation(
+                        "context.environment.template has an unclosed placeholder".to_string(),
+                    ));
+                };
+                let name = &rest[start + 1..start + len];
+                if !ENVIRONMENT_FACTS.contains(&name) {
+                    return Err(ConfigError::Validation(format!(
+                        "context.environment.template uses unknown placeholder {{{name}}}"
+                    )));
+                }
+                rest = &rest[start + len + 1..];
+            }
+        }
+
+        let elevation = &self.context.elevation;
+        if elevation.ttl_secs == 0 {
+            return Err(ConfigError::Validation(
+                "context.elevation.ttl_secs must be non-zero".to_string(),
+            ));
+        }
+        if !TOOL_TRUST_LEVELS.contains(&elevation.max_trust.as_str()) {
+            return Err(ConfigError::Validation(format!(
+                "context.elevation.max_trust must be one of {:?}, got {:?}",
+                TOOL_TRUST_LEVELS, elevation.max_trust
+            )));
+        }
+
+        let relevance = &self.context.relevance;
+        for (name, weight) in [
+            ("query_weight", relevance.query_weight),
+            ("recency_weight", relevance.recency_weight),
+            ("usage_weight", relevance.usage_weight),
+        ] {
+            if !weight.is_finite() || weight < 0.0 {
+                return Err(ConfigError::Validation(format!(
+                    "context.relevance.{name} must be a non-negative number, got {weight}"
+                )));
+            }
+        }
+        if relevance.query_weight + relevance.recency_weight + relevance.usage_weight == 0.0 {
+            return Err(ConfigError::Validation(
+                "context.relevance weights must not all be zero".to_string(),
+            ));
+        }
+        if relevance.recency_half_life_hours == 0 {
+            return Err(ConfigError::Validation(
+                "context.relevance.recency_half_life_hours must be non-zero".to_string(),
+            ));
+        }
+        if self.context.compaction.summary_tokens == 0 {
+            return Err(ConfigError::Validation(
+                "context.compaction.summary_tokens must be non-zero".to_string(),
+            ));
+        }
+        if self.context.compaction.min_items == 0 {
+            return Err(ConfigError::Validation(
+                "context.compaction.min_items must be at least 1".to_string(),
+            ));
+        }
+
+        // Validate file transfer config
+        if self.files.max_file_bytes == 0 {
+            return Err(ConfigError::Validation(
+                "files.max_file_bytes must be non-zero".to_string(),
+            ));
+        }
+        for (i, content_type) in self.files.allowed_content_types.iter().enumerate() {
+            if !content_type.contains('/') {
+                return Err(ConfigError::Validation(format!(
+                    "files.allowed_content_types[{i}] must be \"type/subtype\" or \"type/*\", got {content_type:?}"
+                )));
+            }
+        }
+
+        // Validate the attachment spool
+        if self.attachments.spool_dir.trim().is_empty() {
+            return Err(ConfigError::Validation(
+                "attachments.spool_dir must not be empty".to_string(),
+            ));
+        }
+        if self.attachments.max_attachment_bytes == 0 {
+            return Err(ConfigError::Validation(
+                "attachments.max_attachment_bytes must be non-zero".to_string(),
+            ));
+        }
+        if self.attachments.max_spool_bytes < self.attachments.max_attachment_bytes {
+            return Err(ConfigError::Validation(
+                "attachments.max_spool_bytes must be at least attachments.max_attachment_bytes"
+                    .to_string(),
+            ));
+        }
+        if self.attachments.retention_hours == 0 {
+            return Err(ConfigError::Validation(
+                "attachments.retention_hours must be at least 1".to_string(),
+            ));
+        }
+
+        // Validate response hooks
+        for (i, hook) in self.response.hooks.iter().enumerate() {
+            if hook.trim().is_empty() {
+                return Err(ConfigError::Validation(format!(
+                    "response.hooks[{i}] must not be empty"
+                )));
+            }
+            if self.response.hooks[..i].contains(hook) {
+                return Err(ConfigError::Validation(format!(
+                    "response.hooks lists \"{hook}\" more than once"
+                )));
+            }
+        }
+        if self.response.hooks.iter().any(|h| h == "signature")
+            && self.response.signature.as_deref().is_none_or(str::is_empty)
+        {
+            return Err(ConfigError::Validation(
+                "response.signature must be set when the signature hook is enabled".to_string(),
+            ));
+        }
+        if self.response.max_message_chars == 0 {
+            return Err(ConfigError::Validation(
+                "response.max_message_chars must be non-zero".to_string(),
+            ));
+        }
+        let valid_secret_actions = ["redact", "block"];
+        if !valid_secret_actions.contains(&self.response.on_secret.as_str()) {
+            return Err(ConfigError::Validation(format!(
+                "response.on_secret must be one of {:?}, got {:?}",
+                valid_secret_actions, self.response.on_secret
+            )));
+        }
+
+        // Validate telemetry config
+        if !(self.telemetry.epsilon.is_finite() && self.telemetry.epsilon > 0.0) {
+            return Err(ConfigError::Validation(format!(
+                "telemetry.epsilon must be a positive number, got {}",
+                self.telemetry.epsilon
+            )));
+        }
+        if self.telemetry.enabled {
+            if self.telemetry.interval_secs == 0 {
+                return Err(ConfigError::Validation(
+                    "telemetry.interval_secs must be non-zero".to_string(),
+                ));
+            }
+            match self.telemetry.endpoint.as_deref() {
+                Some(url) if url.starts_with("https://") || url.starts_with("http://") => {}
+                Some(url) => {
+                    return Err(ConfigError::Validation(format!(
+                        "telemetry.endpoint must be an http(s) URL, got {url:?}"
+                    )));
+                }
+                None => {
+                    return Err(ConfigError::Validation(
+                        "telemetry.endpoint must be set when telemetry is enabled".to_string(),
+                    ));
+                }
+            }
+        }
+
+        if self.webhook.enabled {
+            match self.webhook.secret.as_deref() {
+                Some(name) if !name.trim().is_empty() => {
+                    if !self.secrets.entries.iter().any(|entry| entry.name == name) {
+                        return Err(ConfigError::Validation(format!(
+                            "webhook.secret {name:?} does not name a [[secrets.entries]] entry"
+                        )));
+                    }
+                }
+                _ => {
+                    return Err(ConfigError::Validation(
+                        "webhook.secret must be set when the webhook channel is enabled"
+                            .to_string(),
+                    ));
+                }
+            }
+        }
+        if let Some(url) = self.webhook.url.as_deref()
+            && !(url.starts_with("https://") || url.starts_with("http://"))
+        {
+            return Err(ConfigError::Validation(format!(
+                "webhook.url must be an http(s) URL, got {url:?}"
+            )));
+        }
+        if self.webhook.timeout_secs == 0 {
+            return Err(ConfigError::Validation(
+                "webhook.timeout_secs must be non-zero".to_string(),
+            ));
+        }
+
+        self.validate_matrix()?;
+
+        // Validate MCP servers
+        let mut mcp_names = std::collections::HashSet::new();
+        for (i, server) in self.mcp.servers.iter().enumerate() {
+            if server.name.is_empty()
+                || !server
+                    .name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                return Err(ConfigError::Validation(format!(
+                    "mcp.servers[{i}].name must be non-empty and contain only letters, digits, '-' and '_', got {:?}",
+                    server.name
+                )));
+            }
+            if !mcp_names.insert(server.name.as_str()) {
+                return Err(ConfigError::Validation(format!(
+                    "mcp.servers[{i}].name {:?} is used by another server",
+                    server.name
+                )));
+            }
+            match (server.command.is_empty(), &server.url) {
+                (false, None) => {}
+                (true, Some(url)) if url.starts_with("https://") || url.starts_with("http://") => {}
+                (true, Some(url)) => {
+                    return Err(ConfigError::Validation(format!(
+                        "mcp.servers[{i}].url must be an http(s) URL, got {url:?}"
+                    )));
+                }
+                _ => {
+                    return Err(ConfigError::Validation(format!(
+                        "mcp.servers[{i}] must set exactly one of command or url"
+                    )));
+                }
+            }
+            if !TOOL_TRUST_LEVELS.contains(&server.trust.as_str()) {
+                return Err(ConfigError::Validation(format!(
+                    "mcp.servers[{i}].trust must be one of {:?}, got {:?}",
+                    TOOL_TRUST_LEVELS, server.trust
+                )));
+            }
+            if server.tag_prefix.trim().is_empty() {
+                return Err(ConfigError::Validation(format!(
+                    "mcp.servers[{i}].tag_prefix must not be empty"
+                )));
+            }
+            if server.timeout_secs == 0 {
+                return Err(ConfigError::Validation(format!(
+                    "mcp.servers[{i}].timeout_secs must be non-zero"
+                )));
+            }
+        }
+
+        // Validate custom tools
+        let mut tool_names = std::collections::HashSet::new();
+        for (i, tool) in self.tools.iter().enumerate() {
+            if !(1..=64).contains(&tool.name.len())
+                || !tool
+                    .name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                return Err(ConfigError::Validation(format!(
+                    "tools[{i}].name must be 1-64 letters, digits, '-' and '_', got {:?}",
+                    tool.name
+                )));
+            }
+            if BUILTIN_TOOLS.contains(&tool.name.as_str()) {
+                return Err(ConfigError::Validation(format!(
+                    "tools[{i}].name {:?} is a built-in tool",
+                    tool.name
+                )));
+            }
+            if !tool_names.insert(tool.name.as_str()) {
+                return Err(ConfigError::Validation(format!(
+                    "tools[{i}].name {:?} is used by another tool",
+                    tool.name
+                )));
+            }
+            if tool.description.trim().is_empty() {
+                return Err(ConfigError::Validation(format!(
+                    "tools[{i}] ({}) must have a description",
+                    tool.name
+                )));
+            }
+            if tool.command.is_empty() {
+                return Err(ConfigError::Validation(format!(
+                    "tools[{i}] ({}) must have a command",
+                    tool.name
+                )));
+            }
+            if !TOOL_TRUST_LEVELS.contains(&tool.trust.as_str()) {
+                return Err(ConfigError::Validation(format!(
+                    "tools[{i}].trust must be one of {:?}, got {:?}",
+                    TOOL_TRUST_LEVELS, tool.trust
+                )));
+            }
+            if tool.timeout_secs == Some(0) {
+                return Err(ConfigError::Validation(format!(
+                    "tools[{i}].timeout_secs must be non-zero"
+                )));
+            }
+            check_tool_schema(&tool.parameters).map_err(|e| {
+                ConfigError::Validation(format!("tools[{i}].parameters ({}): {e}", tool.name))
+            })?;
+        }
+
+        // Validate native plugins
+        if self.plugins.dir.trim().is_empty() {
+            return Err(ConfigError::Validation(
+                "plugins.dir must not be empty".to_string(),
+            ));
+        }
+        if !TOOL_TRUST_LEVELS.contains(&self.plugins.trust.as_str()) {
+            return Err(ConfigError::Validation(format!(
+                "plugins.trust must be one of {:?}, got {:?}",
+                TOOL_TRUST_LEVELS, self.plugins.trust
+            )));
+        }
+        let wasm = &self.plugins.wasm;
+        if wasm.dir.trim().is_empty() {
+            return Err(ConfigError::Validation(
+                "plugins.wasm.dir must not be empty".to_string(),
+            ));
+        }
+        if !TOOL_TRUST_LEVELS.contains(&wasm.trust.as_str()) {
+            return Err(ConfigError::Validation(format!(
+                "plugins.wasm.trust must be one of {:?}, got {:?}",
+                TOOL_TRUST_LEVELS, wasm.trust
+            )));
+        }
+        if wasm.fuel == 0 || wasm.memory_bytes == 0 || wasm.reload_interval_secs == 0 {
+            return Err(ConfigError::Validation(
+                "plugins.wasm.fuel, memory_bytes, and reload_interval_secs must be non-zero"
+                    .to_string(),
+            ));
+        }
+
+        // Validate command aliases
+        let mut command_names = std::collections::HashSet::new();
+        for (alias, command) in &self.commands.aliases {
+            let normalized = normalize_command(alias);
+            if normalized.is_empty() || normalized == "help" {
+                return Err(ConfigError::Validation(format!(
+                    "commands.aliases: {alias:?} is not a usable alias"
+                )));
+            }
+            if !command_names.insert(normalized) {
+                return Err(ConfigError::Validation(format!(
+                    "commands.aliases: {alias:?} duplicates another alias"
+                )));
+            }
+            if command.skill.trim().is_empty() {
+                return Err(ConfigError::Validation(format!(
+                    "commands.aliases.{alias:?}.skill must not be empty"
+                )));
+            }
+            if command.role.trim().is_empty() {
+                return Err(ConfigError::Validation(format!(
+                    "commands.aliases.{alias:?}.role must not be empty"
+                )));
+            }
+        }
+        if self.commands.default_role.trim().is_empty()
+            || self.commands.roles.values().any(|r| r.trim().is_empty())
+        {
+            return Err(ConfigError::Validation(
+                "commands roles must not be empty".to_string(),
+            ));
+        }
+        if self
+            .commands
+            .prefix
+            .chars()
+            .any(|c| c.is_alphanumeric() || c.is_whitespace())
+        {
+            return Err(ConfigError::Validation(format!(
+                "commands.prefix must be punctuation, got {:?}",
+                self.commands.prefix
+            )));
+        }
+        if !self.commands.prefix.is_empty()
+            && let Some(alias) = self
+                .commands
+                .aliases
+                .keys()
+                .find(|a| a.trim_start().starts_with(&self.commands.prefix))
+        {
+            return Err(ConfigError::Validation(format!(
+                "commands.aliases.{alias:?} starts with the built-in command prefix {:?}",
+                self.commands.prefix
+            )));
+        }
+        for (sender, role) in &self.signal.allowed_senders {
+            if sender.trim().is_empty() || role.trim().is_empty() {
+                return Err(ConfigError::Validation(format!(
+                    "signal.allowed_senders entries need a sender and a role, got {sender:?} = {role:?}"
+                )));
+            }
+        }
+        if self
+            .signal
+            .allowed_groups
+            .iter()
+            .any(|g| g.trim().is_empty())
+        {
+            return Err(ConfigError::Validation(
+                "signal.allowed_groups entries must not be empty".to_string(),
+            ));
+        }
+
+        let mut schedule_names = std::collections::HashSet::new();
+        for (i, schedule) in self.schedules.iter().enumerate() {
+            if schedule.name.is_empty()
+                || !schedule
+                    .name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                return Err(ConfigError::Validation(format!(
+                    "schedules[{i}].name {:?} must be non-empty letters, digits, '-' or '_'",
+                    schedule.name
+                )));
+            }
+            if !schedule_names.insert(schedule.name.as_str()) {
+                return Err(ConfigError::Validation(format!(
+                    "schedules: duplicate name {:?}",
+                    schedule.name
+                )));
+            }
+            if let Err(e) = cron::CronSchedule::parse(&schedule.cron) {
+                return Err(ConfigError::Validation(format!(
+                    "schedules.{}.cron: {e}",
+                    schedule.name
+                )));
+            }
+            let has_skill = schedule
+                .skill
+                .as_deref()
+                .is_some_and(|s| !s.trim().is_empty());
+            let has_prompt = schedule
+                .prompt
+                .as_deref()
+                .is_some_and(|p| !p.trim().is_empty());
+            if has_skill == has_prompt {
+                return Err(ConfigError::Validation(format!(
+                    "schedules.{}: set exactly one of skill and prompt",
+                    schedule.name
+                )));
+            }
+            if schedule.role.trim().is_empty() {
+                return Err(ConfigError::Validation(format!(
+                    "schedules.{}.role must not be empty",
+                    schedule.name
+                )));
+            }
+        }
+
+        let retry = self.llm.effective_retry();
+        if retry.max_retries > MAX_LLM_RETRIES {
+            return Err(ConfigError::Validation(format!(
+                "llm.retry.max_retries must be at most {MAX_LLM_RETRIES}, got {}",
+                retry.max_retries
+            )));
+        }
+        if retry.initial_backoff_ms > retry.max_backoff_ms {
+            return Err(ConfigError::Validation(format!(
+                "llm.retry.initial_backoff_ms ({}) must not exceed max_backoff_ms ({})",
+                retry.initial_backoff_ms, retry.max_backoff_ms
+            )));
+        }
+        if !(0.0..=1.0).contains(&retry.jitter) {
+            return Err(ConfigError::Validation(format!(
+                "llm.retry.jitter must be between 0.0 and 1.0, got {}",
+                retry.jitter
+            )));
+        }
+        let http = &self.llm.http;
+        if http.connect_timeout_secs == 0 || http.request_timeout_secs == 0 {
+            return Err(ConfigError::Validation(
+                "llm.http.connect_timeout_secs and request_timeout_secs must be non-zero"
+                    .to_string(),
+            ));
+        }
+        if let Some(proxy) = &http.proxy
+            && !(proxy.starts_with("http://") || proxy.starts_with("https://"))
+        {
+            return Err(ConfigError::Validation(format!(
+                "llm.http.proxy must be an http:// or https:// URL, got {proxy:?}"
+            )));
+        }
+        for (i, fallback) in self.llm.fallbacks.iter().enumerate() {
+            if fallback
+                .api_key_env
+                .as_deref()
+                .is_some_and(|var| var.trim().is_empty())
+            {
+                return Err(ConfigError::Validation(format!(
+                    "llm.fallbacks[{i}].api_key_env must not be empty"
+                )));
+            }
+        }
+        let key_secrets = std::iter::once(("llm".to_string(), &self.llm.api_key_secret)).chain(
+            self.llm
+                .fallbacks
+                .iter()
+                .enumerate()
+                .map(|(i, fallback)| (format!("llm.fallbacks[{i}]"), &fallback.api_key_secret)),
+        );
+        for (key, name) in key_secrets {
+            if let Some(name) = name
+                && !self.secrets.entries.iter().any(|entry| &entry.name == name)
+            {
+                return Err(ConfigError::Validation(format!(
+                    "{key}.api_key_secret {name:?} does not name a [[secrets.entries]] entry"
+                )));
+            }
+        }
+        if self.llm.llamacpp.context_size == Some(0) {
+            return Err(ConfigError::Validation(
+                "llm.llamacpp.context_size must be non-zero".to_string(),
+            ));
+        }
+        for (model, pricing) in &self.llm.pricing {
+            for (key, price) in [
+                ("prompt_per_mtok", pricing.prompt_per_mtok),
+                ("completion_per_mtok", pricing.completion_per_mtok),
+            ] {
+                if !price.is_finite() || price < 0.0 {
+                    return Err(ConfigError::Validation(format!(
+                        "llm.pricing.\"{model}\".{key} must be a non-negative number, got {price}"
+                    )));
+                }
+            }
+        }
+
+        if self.chat.max_iterations == 0 {
+            return Err(ConfigError::Validation(
+                "chat.max_iterations must be non-zero".to_string(),
+            ));
+        }
+        if self.chat.token_budget == Some(0) {
+            return Err(ConfigError::Validation(
+                "chat.token_budget must be non-zero".to_string(),
+            ));
+        }
+        if self.chat.timeout_secs == Some(0) {
+            return Err(ConfigError::Validation(
+                "chat.timeout_secs must be non-zero".to_string(),
+            ));
+        }
+        for (role, trust) in &self.chat.tool_trust {
+            if !TOOL_TRUST_LEVELS.contains(&trust.as_str()) {
+                return Err(ConfigError::Validation(format!(
+                    "chat.tool_trust.{role:?} must be one of {:?}, got {trust:?}",
+                    TOOL_TRUST_LEVELS
+                )));
+            }
+        }
+
+        if self.conversations.enabled && self.conversations.dir.trim().is_empty() {
+            return Err(ConfigError::Validation(
+                "conversations.dir must not be empty".to_string(),
+            ));
+        }
+
+        if self
+            .storage
+            .path
+            .as_deref()
+            .is_some_and(|path| path.trim().is_empty())
+        {
+            return Err(ConfigError::Validation(
+                "storage.path must not be empty".to_string(),
+            ));
+        }
+
+        if self.memory.enabled {
+            if self.memory.dir.trim().is_empty() {
+                return Err(ConfigError::Validation(
+                    "memory.dir must not be empty".to_string(),
+                ));
+            }
+            if self.memory.max_tokens == 0 {
+                return Err(ConfigError::Validation(
+                    "memory.max_tokens must be non-zero".to_string(),
+                ));
+            }
+            if self.memory.max_facts == 0 {
+                return Err(ConfigError::Validation(
+                    "memory.max_facts must be non-zero".to_string(),
+                ));
+            }
+            if self.memory.summary_tokens == 0 {
+                return Err(ConfigError::Validation(
+                    "memory.summary_tokens must be non-zero".to_string(),
+                ));
+            }
+        }
+
+        if self.health.probe_timeout_secs == 0 {
+            return Err(ConfigError::Validation(
+                "health.probe_timeout_secs must be non-zero".to_string(),
+            ));
+        }
+
+        if self.quotas.roles.keys().any(|r| r.trim().is_empty()) {
+            return Err(ConfigError::Validation(
+                "quotas.roles names must not be empty".to_string(),
+            ));
+        }
+
+        // Validate auth config
+        let valid_auth_modes = ["local", "token"];
+        if !valid_auth_modes.contains(&self.auth.mode.as_str()) {
+            return Err(ConfigError::Validation(format!(
+                "auth.mode must be one of {:?}, got {:?}",
+                valid_auth_modes, self.auth.mode
+            )));
+        }
+        if self.auth.token_ttl_secs == 0 {
+            return Err(ConfigError::Validation(
+                "auth.token_ttl_secs must be non-zero".to_string(),
+            ));
+        }
+        for (key, role) in &self.auth.cert_map {
+            if let Some(hex) = key.strip_prefix("sha256:") {
+                if hex.len() != 64 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    return Err(ConfigError::Validation(format!(
+                        "auth.cert_map key {key:?} must be sha256: followed by 64 hex digits"
+                    )));
+                }
+            } else if key.trim().is_empty() {
+                return Err(ConfigError::Validation(
+                    "auth.cert_map names must not be empty".to_string(),
+                ));
+            }
+            if role.trim().is_empty() {
+                return Err(ConfigError::Validation(format!(
+                    "auth.cert_map role for {key:?} must not be empty"
+                )));
+            }
+        }
+
+        if let Some(addr) = &self.remote.addr {
+            if !addr
+                .rsplit_once(':')
+                .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok())
+            {
+                return Err(ConfigError::Validation(format!(
+                    "remote.addr must be host:port, got {addr:?}"
+                )));
+            }
+            for (key, value) in [
+                ("ca_path", &self.remote.ca_path),
+                ("cert_path", &self.remote.cert_path),
+                ("key_path", &self.remote.key_path),
+            ] {
+                if value.as_deref().is_none_or(|v| v.trim().is_empty()) {
+                    return Err(ConfigError::Validation(format!(
+                        "remote.{key} is required when remote.addr is set"
+                    )));
+                }
+            }
+        }
+
+        let mut bound = BTreeMap::new();
+        for (action, keys) in &self.tui.keys {
+            if !TUI_ACTIONS.contains(&action.as_str()) {
+                return Err(ConfigError::Validation(format!(
+                    "tui.keys: unknown action {action:?} (expected one of: {})",
+                    TUI_ACTIONS.join(", ")
+                )));
+            }
+            for key in keys {
+                if !is_tui_key(key) {
+                    return Err(ConfigError::Validation(format!(
+                        "tui.keys.{action}: {key:?} is not a character, a two-key \
+                         sequence, or one of: {}",
+                        TUI_KEY_NAMES.join(", ")
+                    )));
+                }
+                if let Some(other) = bound.insert(key.as_str(), action) {
+                    return Err(ConfigError::Validation(format!(
+                        "tui.keys: {key:?} is bound to both {other} and {action}"
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_matrix(&self) -> Result<(), ConfigError> {
+        let matrix = &self.matrix;
+        let invalid = |message: String| Err(ConfigError::Validation(message));
+        for (room, settings) in &matrix.rooms {
+            if !room.starts_with('!') || !room.contains(':') {
+                return invalid(format!(
+                    "matrix.rooms key {room:?} must be a room ID like \"!abc:example.org\""
+                ));
+            }
+            if settings
+                .role
+                .as_deref()
+                .is_some_and(|r| r.trim().is_empty())
+            {
+                return invalid(format!("matrix.rooms.{room:?}.role must not be empty"));
+            }
+        }
+        if !matrix.enabled {
+            return Ok(());
+        }
+        match matrix.homeserver_url.as_deref() {
+            Some(url) if url.starts_with("https://") || url.starts_with("http://") => {}
+            Some(url) => {
+                return invalid(format!(
+                    "matrix.homeserver_url must be an http(s) URL, got {url:?}"
+                ));
+            }
+            None => {
+                return invalid(
+                    "matrix.homeserver_url must be set when Matrix is enabled".to_string(),
+                );
+            }
+        }
+        match matrix.user_id.as_deref() {
+            Some(user) if user.starts_with('@') && user.contains(':') => {}
+            Some(user) => {
+                return invalid(format!(
+                    "matrix.user_id must be a user ID like \"@bot:example.org\", got {user:?}"
+                ));
+            }
+            None => {
+                return invalid("matrix.user_id must be set when Matrix is enabled".to_string());
+            }
+        }
+        match (&matrix.password_secret, &matrix.access_token_secret) {
+            (Some(_), None) => {}
+            (None, Some(_)) if matrix.device_id.is_some() => {}
+            (None, Some(_)) => {
+                return invalid(
+                    "matrix.device_id is required with matrix.access_token_secret".to_string(),
+                );
+            }
+            _ => {
+                return invalid(
+                    "matrix must set exactly one of password_secret or access_token_secret"
+                        .to_string(),
+                );
+            }
+        }
+        for (key, name) in [
+            ("password_secret", &matrix.password_secret),
+            ("access_token_secret", &matrix.access_token_secret),
+            ("store_passphrase_secret", &matrix.store_passphrase_secret),
+        ] {
+            if let Some(name) = name
+                && !self.secrets.entries.iter().any(|entry| &entry.name == name)
+            {
+                return invalid(format!(
+                    "matrix.{key} {name:?} does not name a [[secrets.entries]] entry"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// `[commands]` with the `role` of each `[matrix.rooms]` entry added to
+    /// `roles` under its room ID, and each `[signal.allowed_senders]` role
+    /// under the sender. Entries already in `[commands.roles]` win.
+    pub fn effective_commands(&self) -> CommandsConfig {
+        let mut commands = self.commands.clone();
+        for (sender, role) in &self.signal.allowed_senders {
+            commands
+                .roles
+                .entry(sender.clone())
+                .or_insert_with(|| role.clone());
+        }
+        for (room, settings) in &self.matrix.rooms {
+            if let Some(role) = &settings.role {
+                commands
+                    .roles
+                    .entry(room.clone())
+                    .or_insert_with(|| role.clone());
+            }
+        }
+        commands
+    }
+
+    /// Build a [`PolicyEngine`](policy::PolicyEngine) from the loaded policy config.
+    pub fn build_policy_engine(&self) -> policy::PolicyEngine {
+        let rules: Vec<policy::PolicyRule> = self
+            .policy
+            .rules
+            .iter()
+            .filter_map(|r| {
+                let effect = if r.effect == "allow" {
+                    policy::Effect::Allow
+                } else {
+                    policy::Effect::Deny
+                };
+                // `validate()` rejects unparsable conditions; if one slips
+                // through, drop the rule rather than make it unconditional.
+                let condition = match r.when.as_deref().map(condition::Condition::parse) {
+                    None => None,
+                    Some(Ok(c)) => Some(c),
+                    Some(Err(e)) => {
+                        tracing::warn!(role = %r.role, error = %e, "Skipping policy rule with invalid condition");
+                        return None;
+                    }
+                };
+                Some(policy::PolicyRule {
+                    role: r.role.clone(),
+                    action: r.action.clone(),
+                    resource: r.resource.clone(),
+                    effect,
+                    priority: r.priority,
+                    condition,
+                    id: r.id.clone(),
+                    reason: r.reason.clone(),
+                })
+            })
+            .collect();
+
+        let mut engine = policy::build_policy(rules);
+
+        // Add default deny/allow rule at lowest priority
+        if self.policy.default_effect == "allow" {
+            engine.add_rule(
+                policy::PolicyRule::allow("*", "*", "*")
+                    .with_priority(0)
+                    .with_reason("policy.default_effect is \"allow\""),
+            );
+        }
+
+        engine
+    }
+
+    /// Evaluate every `[[policy.tests]]` entry against the policy built
+    /// from this config, returning the ones that do not hold.
+    ///
+    /// The daemon refuses to start (or to apply a reloaded config) when
+    /// this is non-empty.
+    pub fn run_policy_tests(&self) -> Vec<PolicyTestFailure> {
+        self.check_policy(&self.policy.tests)
+    }
+
+    /// Evaluate each of `tests` against the policy built from this config,
+    /// returning the ones that do not hold. Indices refer to `tests`.
+    pub fn check_policy(&self, tests: &[PolicyTestConfig]) -> Vec<PolicyTestFailure> {
+        let mut engine = self.build_policy_engine();
+        tests
+            .iter()
+            .enumerate()
+            .filter_map(|(index, test)| {
+                let ctx = test.request_context().unwrap_or_default();
+                let verdict =
+                    engine.evaluate_with_context(&test.role, &test.action, &test.resource, &ctx);
+                if verdict.is_allowed() == (test.expect == "allow") {
+                    return None;
+                }
+                Some(PolicyTestFailure {
+                    index,
+                    role: test.role.clone(),
+                    action: test.action.clone(),
+                    resource: test.resource.clone(),
+                    expected: test.expect.clone(),
+                    actual: verdict.decision,
+                    rule_id: verdict.rule_id,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_default_config() {
+        let config = AppConfig::default();
+        assert_eq!(config.daemon.listen_addr, "127.0.0.1");
+        assert_eq!(config.daemon.listen_port, 9100);
+        assert!(!config.signal.enabled);
+        assert_eq!(config.logging.level, "info");
+        assert_eq!(config.isolation.shutdown_grace_secs, 30);
+    }
+
+    #[test]
+    fn test_parse_minimal_toml() {
+        let toml = "";
+        let config = AppConfig::parse(toml).unwrap();
+        assert_eq!(config.daemon.listen_port, 9100);
+    }
+
+    #[test]
+    fn test_parse_full_toml() {
+        let toml = r#"
+            [daemon]
+            listen_addr = "0.0.0.0"
+            listen_port = 8080
+
+            [signal]
+            enabled = true
+            data_dir = "/var/lib/crustyclaw/signal"
+            account = "+15550001"
+            typing_indicators = false
+
+            [logging]
+            level = "debug"
+        "#;
+        let config = AppConfig::parse(toml).unwrap();
+        assert_eq!(config.daemon.listen_addr, "0.0.0.0");
+        assert_eq!(config.daemon.listen_port, 8080);
+        assert!(config.signal.enabled);
+        assert_eq!(config.signal.account.as_deref(), Some("+15550001"));
+        assert_eq!(config.signal.cli_path, "signal-cli");
+        assert!(config.signal.read_receipts);
+        assert!(!config.signal.typing_indicators);
+        assert_eq!(config.logging.level, "debug");
+    }
+
+    #[test]
+    fn test_signal_access_config() {
+        let signal = AppConfig::default().signal;
+        assert!(!signal.restricts_senders());
+        assert_eq!(signal.unknown_senders, UnknownSenderAction::Drop);
+
+        let config = AppConfig::parse(
+            r#"
+            [signal]
+            allowed_groups = ["group-1"]
+            unknown_senders = "quarantine"
+
+            [signal.allowed_senders]
+            "+15550001" = "operator"
+            "+15550002" = "user"
+
+            [commands.roles]
+            "+15550002" = "admin"
+        "#,
+        )
+        .unwrap();
+        assert!(config.signal.restricts_senders());
+        assert_eq!(
+            config.signal.unknown_senders,
+            UnknownSenderAction::Quarantine
+        );
+        let commands = config.effective_commands();
+        assert_eq!(commands.roles["+15550001"], "operator");
+        assert_eq!(commands.roles["+15550002"], "admin");
+
+        assert!(AppConfig::parse("[signal.allowed_senders]\n\"+15550001\" = \"\"\n").is_err());
+        assert!(AppConfig::parse("[signal]\nallowed_groups = [\" \"]\n").is_err());
+        assert!(AppConfig::parse("[signal]\nunknown_senders = \"reply\"\n").is_err());
+    }
+
+    #[test]
+    fn test_validation_rejects_zero_port() {
+        let toml = r#"
+            [daemon]
+            listen_port = 0
+        "#;
+        let result = AppConfig::parse(toml);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_validation_rejects_empty_addr() {
+        let toml = r#"
+            [daemon]
+            listen_addr = ""
+        "#;
+        let result = AppConfig::parse(toml);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_skills_dir() {
+        assert!(AppConfig::default().daemon.skills_dir.is_none());
+        let config =
+            AppConfig::parse("[daemon]\nskills_dir = \"/etc/crustyclaw/skills\"\n").unwrap();
+        assert_eq!(
+            config.daemon.skills_dir.as_deref(),
+            Some("/etc/crustyclaw/skills")
+        );
+        assert!(AppConfig::parse("[daemon]\nskills_dir = \"\"\n").is_err());
+    }
+
+    #[test]
+    fn test_logging_file() {
+        let config = AppConfig::default();
+        assert!(config.logging.file.is_none());
+        assert_eq!(config.logging.max_files, 5);
+        assert_eq!(config.logging.format, "pretty");
+
+        let config = AppConfig::parse(
+            "[logging]\nfile = \"/var/log/crustyclaw.log\"\nmax_size_bytes = 1024\nformat = \"json\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.logging.file.as_deref(),
+            Some("/var/log/crustyclaw.log")
+        );
+        assert_eq!(config.logging.max_size_bytes, 1024);
+        assert_eq!(config.logging.format, "json");
+
+        assert!(AppConfig::parse("[logging]\nfile = \"\"\n").is_err());
+        assert!(AppConfig::parse("[logging]\nmax_size_bytes = 0\n").is_err());
+        assert!(AppConfig::parse("[logging]\nmax_files = 0\n").is_err());
+        assert!(AppConfig::parse("[logging]\nformat = \"xml\"\n").is_err());
+    }
+
+    #[test]
+    fn test_pid_file() {
+        assert!(AppConfig::default().daemon.pid_file.is_none());
+        let config =
+            AppConfig::parse("[daemon]\npid_file = \"/run/crustyclaw/crustyclaw.pid\"\n").unwrap();
+        assert_eq!(
+            config.daemon.pid_file.as_deref(),
+            Some("/run/crustyclaw/crustyclaw.pid")
+        );
+        assert!(AppConfig::parse("[daemon]\npid_file = \"\"\n").is_err());
+    }
+
+    #[test]
+    fn test_metrics_addr() {
+        assert!(AppConfig::default().daemon.metrics_addr.is_none());
+        let config = AppConfig::parse("[daemon]\nmetrics_addr = \"127.0.0.1:9464\"\n").unwrap();
+        assert_eq!(
+            config.daemon.metrics_addr.as_deref(),
+            Some("127.0.0.1:9464")
+        );
+        for bad in ["", "localhost", "127.0.0.1", "[::1]:port"] {
+            let toml = format!("[daemon]\nmetrics_addr = {bad:?}\n");
+            assert!(
+                AppConfig::parse(&toml).is_err(),
+                "{bad:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn test_policy_config_from_toml() {
+        let toml = r#"
+            [policy]
+            default_effect = "deny"
+
+            [[policy.rules]]
+            role = "admin"
+            action = "*"
+            resource = "*"
+            effect = "allow"
+            priority = 10
+
+            [[policy.rules]]
+            role = "user"
+            action = "read"
+            resource = "config"
+            effect = "allow"
+            priority = 5
+        "#;
+        let config = AppConfig::parse(toml).unwrap();
+        assert_eq!(config.policy.rules.len(), 2);
+        assert_eq!(config.policy.default_effect, "deny");
+
+        let mut engine = config.build_policy_engine();
+        assert!(engine.is_allowed("admin", "write", "secrets"));
+        assert!(engine.is_allowed("user", "read", "config"));
+        assert!(!engine.is_allowed("user", "write", "config"));
+    }
+
+    #[test]
+    fn test_policy_validation_rejects_bad_effect() {
+        let toml = r#"
+            [[policy.rules]]
+            role = "admin"
+            action = "*"
+            resource = "*"
+            effect = "maybe"
+        "#;
+        let result = AppConfig::parse(toml);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_policy_default_allow() {
+        let toml = r#"
+            [policy]
+            default_effect = "allow"
+        "#;
+        let config = AppConfig::parse(toml).unwrap();
+        let mut engine = config.build_policy_engine();
+        // With default allow, everything is permitted
+        assert!(engine.is_allowed("anyone", "anything", "anywhere"));
+    }
+
+    #[test]
+    fn test_policy_rule_when_clause() {
+        let toml = r#"
+            [[policy.rules]]
+            role = "operator"
+            action = "deploy"
+            resource = "*"
+            effect = "allow"
+            when = 'time.hour >= 9 && resource.tag == "prod"'
+        "#;
+        let config = AppConfig::parse(toml).unwrap();
+        let mut engine = config.build_policy_engine();
+
+        let ctx = policy::RequestContext::new()
+            .with("time.hour", 9i64)
+            .with("resource.tag", "prod");
+        assert!(engine.is_allowed_with_context("operator", "deploy", "api", &ctx));
+        assert!(!engine.is_allowed("operator", "deploy", "api"));
+    }
+
+    #[test]
+    fn test_policy_validation_rejects_bad_when() {
+        let toml = r#"
+            [[policy.rules]]
+            role = "operator"
+            action = "deploy"
+            resource = "*"
+            effect = "allow"
+            when = "time.hour >="
+        "#;
+        let err = AppConfig::parse(toml).unwrap_err();
+        assert!(err.to_string().contains("policy.rules[0].when"));
+    }
+
+    #[test]
+    fn test_policy_rule_patterns() {
+        let toml = r#"
+            [[policy.rules]]
+            role = "ops|oncall"
+            action = "read|list"
+            resource = "skills/git-*"
+            effect = "allow"
+        "#;
+        let config = AppConfig::parse(toml).unwrap();
+        let mut engine = config.build_policy_engine();
+        assert!(engine.is_allowed("oncall", "list", "skills/git-log"));
+        assert!(!engine.is_allowed("oncall", "write", "skills/git-log"));
+
+        for (role, action, resource, culprit) in [
+            ("ops|", "read", "*", "role"),
+            ("ops", "read|[a-", "*", "action"),
+            ("ops", "read", "skills/[]", "resource"),
+            ("ops", "read", "", "resource"),
+        ] {
+            let toml = format!(
+                "[[policy.rules]]\nrole = {role:?}\naction = {action:?}\nresource = {resource:?}\neffect = \"allow\"\n"
+            );
+            let err = AppConfig::parse(&toml).unwrap_err().to_string();
+            assert!(
+                err.contains(&format!("policy.rules[0].{culprit} "))
+                    && err.contains("invalid pattern"),
+                "{toml}: {err}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_policy_tests_pass_and_fail() {
+        let toml = r#"
+            [[policy.rules]]
+            role = "admin"
+            action = "*"
+            resource = "*"
+            effect = "allow"
+
+            [[policy.tests]]
+            role = "admin"
+            action = "write"
+            resource = "secrets"
+            expect = "allow"
+
+            [[policy.tests]]
+            role = "user"
+            action = "read"
+            resource = "config"
+            expect = "deny"
+
+            [[policy.tests]]
+            role = "user"
+            action = "write"
+            resource = "config"
+            expect = "allow"
+        "#;
+        let config = AppConfig::parse(toml).unwrap();
+        let failures = config.run_policy_tests();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].index, 2);
+        assert_eq!(failures[0].actual, policy::PolicyDecision::NoMatch);
+        assert!(failures[0].to_string().starts_with("policy.tests[2]:"));
+    }
+
+    #[test]
+    fn test_security_validation() {
+        let key = minisign::tests::OTHER_KEY;
+        let config = AppConfig::parse(&format!(
+            "[policy]\nbundle = \"policy.toml\"\n\n[security]\nstrict = true\n\n[security.trusted_keys]\nrelease = \"{key}\"\n"
+        ))
+        .unwrap();
+        assert!(config.security.strict);
+        assert!(config.policy_bundle.is_none());
+
+        for (bad, expected) in [
+            ("[security]\nstrict = true\n", "requires policy.bundle"),
+            (
+                "[policy]\nbundle = \"p.toml\"\n\n[security]\nstrict = true\n",
+                "requires at least one",
+            ),
+            (
+                "[security.trusted_keys]\nrelease = \"not a key\"\n",
+                "security.trusted_keys.release",
+            ),
+            ("[policy]\nbundle = \" \"\n", "policy.bundle"),
+        ] {
+            let err = AppConfig::parse(bad).unwrap_err().to_string();
+            assert!(err.contains(expected), "{bad:?}: {err}");
+        }
+    }
+
+    #[test]
+    fn test_access_matrix() {
+        let config = AppConfig::parse(
+            r#"
+            [[policy.rules]]
+            role = "ops"
+            action = "read|execute"
+            resource = "skills/*"
+            effect = "allow"
+        "#,
+        )
+        .unwrap();
+        let matrix = AccessMatrix::parse(
+            r#"
+            [[checks]]
+            role = "ops"
+            action = "execute"
+            resource = "skills/deploy"
+            expect = "allow"
+
+            [[checks]]
+            role = "ops"
+            action = "write"
+            resource = "skills/deploy"
+            expect = "allow"
+
+            [[checks]]
+            role = "user"
+            action = "read"
+            resource = "skills/deploy"
+            expect = "deny"
+        "#,
+        )
+        .unwrap();
+        let failures = config.check_policy(&matrix.checks);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].index, 1);
+        assert_eq!(failures[0].actual, policy::PolicyDecision::NoMatch);
+
+        let err = AccessMatrix::parse(
+            "[[checks]]\nrole = \"a\"\naction = \"b\"\nresource = \"c\"\nexpect = \"maybe\"\n",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("checks[0].expect"), "{err}");
+        assert!(AccessMatrix::parse("[[tests]]\nrole = \"a\"\n").is_err());
+    }
+
+    #[test]
+    fn test_policy_rule_ids_and_reasons() {
+        let toml = r#"
+            [[policy.rules]]
+            id = "no-secret-writes"
+            reason = "secrets are synced from the vault"
+            role = "*"
+            action = "write"
+            resource = "secrets"
+            effect = "deny"
+            priority = 10
+
+            [[policy.rules]]
+            role = "admin"
+            action = "*"
+            resource = "*"
+            effect = "allow"
+
+            [[policy.tests]]
+            role = "admin"
+            action = "write"
+            resource = "secrets"
+            expect = "allow"
+        "#;
+        let config = AppConfig::parse(toml).unwrap();
+        let verdict = config
+            .build_policy_engine()
+            .evaluate("admin", "write", "secrets");
+        assert_eq!(verdict.rule_id.as_deref(), Some("no-secret-writes"));
+        assert_eq!(
+            verdict.reason.as_deref(),
+            Some("secrets are synced from the vault")
+        );
+        let failures = config.run_policy_tests();
+        assert!(
+            failures[0]
+                .to_string()
+                .ends_with("got Denied (rule \"no-secret-writes\")")
+        );
+
+        let duplicate = toml.replace(
+            "role = \"admin\"\n            action = \"*\"",
+            "id = \"no-secret-writes\"\n            role = \"admin\"\n            action = \"*\"",
+        );
+        let err = AppConfig::parse(&duplicate).unwrap_err().to_string();
+        assert!(
+            err.contains("policy.rules[1].id \"no-secret-writes\" is used"),
+            "{err}"
+        );
+
+        let config = AppConfig::parse("[policy]\ndefault_effect = \"allow\"").unwrap();
+        let verdict = config.build_policy_engine().evaluate("guest", "read", "x");
+        assert_eq!(
+            verdict.to_string(),
+            "allowed: policy.default_effect is \"allow\""
+        );
+    }
+
+    #[test]
+    fn test_policy_tests_with_context() {
+        let toml = r#"
+            [[policy.rules]]
+            role = "operator"
+            action = "deploy"
+            resource = "*"
+            effect = "allow"
+            when = 'time.hour >= 9 && resource.tag == "prod"'
+
+            [[policy.tests]]
+            role = "operator"
+            action = "deploy"
+            resource = "api"
+            expect = "allow"
+            context = { time = { hour = 10 }, "resource.tag" = "prod" }
+
+            [[policy.tests]]
+            role = "operator"
+            action = "deploy"
+            resource = "api"
+            expect = "deny"
+            context = { "time.hour" = 3, "resource.tag" = "prod" }
+        "#;
+        let config = AppConfig::parse(toml).unwrap();
+        assert!(config.run_policy_tests().is_empty());
+    }
+
+    #[test]
+    fn test_policy_tests_validation() {
+        let bad_expect = r#"
+            [[policy.tests]]
+            role = "admin"
+            action = "read"
+            resource = "config"
+            expect = "maybe"
+        "#;
+        let err = AppConfig::parse(bad_expect).unwrap_err();
+        assert!(err.to_string().contains("policy.tests[0].expect"));
+
+        let bad_context = r#"
+            [[policy.tests]]
+            role = "admin"
+            action = "read"
+            resource = "config"
+            expect = "deny"
+            context = { tags = ["a"] }
+        "#;
+        let err = AppConfig::parse(bad_context).unwrap_err();
+        assert!(err.to_string().contains("policy.tests[0].context.tags"));
+    }
+
+    // ── Secrets config ──────────────────────────────────────────────
+
+    #[test]
+    fn test_secrets_config_from_toml() {
+        let toml = r#"
+            [secrets]
+            staging_dir = "/tmp/secrets"
+            rotation_interval_secs = 300
+
+            [[secrets.entries]]
+            name = "api_key"
+            source = "env"
+            inject_as = "env"
+            inject_env = "API_KEY"
+            description = "LLM API key"
+
+            [[secrets.entries]]
+            name = "tls_cert"
+            source = "file"
+            file_path = "/etc/crustyclaw/tls.pem"
+            inject_as = "file"
+            inject_path = "/run/secrets/tls.pem"
+        "#;
+        let config = AppConfig::parse(toml).unwrap();
+        assert_eq!(config.secrets.staging_dir, "/tmp/secrets");
+        assert_eq!(config.secrets.rotation_interval_secs, 300);
+        assert_eq!(AppConfig::default().secrets.rotation_interval_secs, 0);
+        assert_eq!(config.secrets.entries.len(), 2);
+        assert_eq!(config.secrets.entries[0].name, "api_key");
+        assert_eq!(config.secrets.entries[0].source, "env");
+        assert_eq!(config.secrets.entries[1].inject_as, "file");
+    }
+
+    #[test]
+    fn test_secrets_keystore_config() {
+        let config = AppConfig::default();
+        assert_eq!(config.secrets.keystore.path, "data/secrets.keystore");
+        assert_eq!(config.secrets.keystore.key_source, "file");
+
+        let config = AppConfig::parse(
+            r#"
+            [secrets.keystore]
+            path = "/var/lib/crustyclaw/secrets.keystore"
+            key_source = "keychain"
+
+            [[secrets.entries]]
+            name = "github_token"
+            source = "keystore"
+            inject_as = "env"
+            inject_env = "GITHUB_TOKEN"
+        "#,
+        )
+        .unwrap();
+        assert_eq!(config.secrets.keystore.key_source, "keychain");
+        assert_eq!(config.secrets.entries[0].source, "keystore");
+
+        assert!(AppConfig::parse("[secrets.keystore]\nkey_source = \"tpm\"\n").is_err());
+        assert!(AppConfig::parse("[secrets.keystore]\nkey_file = \"\"\n").is_err());
+        assert!(AppConfig::parse("[secrets.keystore]\npath = \"\"\n").is_err());
+    }
+
+    #[test]
+    fn test_secrets_keychain_entry() {
+        let config = AppConfig::parse(
+            r#"
+            [[secrets.entries]]
+            name = "anthropic_api_key"
+            source = "keychain"
+            inject_env = "ANTHROPIC_API_KEY"
+
+            [[secrets.entries]]
+            name = "github_token"
+            source = "keychain"
+            keychain_service = "github"
+            keychain_account = "ci-bot"
+            inject_env = "GITHUB_TOKEN"
+        "#,
+        )
+        .unwrap();
+        assert_eq!(config.secrets.entries[0].source, "keychain");
+        assert_eq!(config.secrets.entries[0].keychain_service, None);
+        assert_eq!(
+            config.secrets.entries[1].keychain_service.as_deref(),
+            Some("github")
+        );
+        assert_eq!(
+            config.secrets.entries[1].keychain_account.as_deref(),
+            Some("ci-bot")
+        );
+
+        let empty = r#"
+            [[secrets.entries]]
+            name = "k"
+            source = "keychain"
+            keychain_account = ""
+            inject_env = "K"
+        "#;
+        let err = AppConfig::parse(empty).unwrap_err();
+        assert!(err.to_string().contains("keychain_account"), "{err}");
+    }
+
+    #[test]
+    fn test_secrets_validation_rejects_empty_name() {
+        let toml = r#"
+            [[secrets.entries]]
+            name = ""
+            source = "env"
+            inject_as = "env"
+            inject_env = "KEY"
+        "#;
+        let result = AppConfig::parse(toml);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_secrets_validation_rejects_bad_source() {
+        let toml = r#"
+            [[secrets.entries]]
+            name = "key"
+            source = "database"
+            inject_as = "env"
+            inject_env = "KEY"
+        "#;
+        let result = AppConfig::parse(toml);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_secrets_validation_requires_inject_env() {
+        let toml = r#"
+            [[secrets.entries]]
+            name = "key"
+            source = "env"
+            inject_as = "env"
+        "#;
+        let result = AppConfig::parse(toml);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_secrets_validation_requires_inject_path() {
+        let toml = r#"
+            [[secrets.entries]]
+            name = "cert"
+            source = "file"
+            file_path = "/etc/cert.pem"
+            inject_as = "file"
+        "#;
+        let result = AppConfig::parse(toml);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_secrets_validation_requires_file_path() {
+        let toml = r#"
+            [[secrets.entries]]
+            name = "cert"
+            source = "file"
+            inject_as = "env"
+            inject_env = "CERT"
+        "#;
+        let result = AppConfig::parse(toml);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_secrets_both_injection() {
+        let toml = r#"
+            [[secrets.entries]]
+            name = "dual_key"
+            source = "inline"
+            value = "secret-value"
+            inject_as = "both"
+            inject_env = "DUAL_KEY"
+            inject_path = "/run/secrets/dual_key"
+        "#;
+        let config = AppConfig::parse(toml).unwrap();
+        assert_eq!(config.secrets.entries[0].inject_as, "both");
+        assert_eq!(
+            config.secrets.entries[0].inject_env.as_ref().unwrap(),
+            "DUAL_KEY"
+        );
+        assert_eq!(
+            config.secrets.entries[0].inject_path.as_ref().unwrap(),
+            "/run/secrets/dual_key"
+        );
+    }
+
+    // ── Auth config ───────────────────────────────────────────────────
+
+    #[test]
+    fn test_auth_config_default() {
+        let config = AppConfig::default();
+        assert_eq!(config.auth.mode, "local");
+        assert!(config.auth.role_map.is_empty());
+    }
+
+    #[test]
+    fn test_auth_config_from_toml() {
+        let toml = r#"
+            [auth]
+            mode = "local"
+
+            [auth.role_map]
+            alice = "admin"
+            bob = "operator"
+        "#;
+        let config = AppConfig::parse(toml).unwrap();
+        assert_eq!(config.auth.mode, "local");
+        assert_eq!(config.auth.role_map.get("alice").unwrap(), "admin");
+        assert_eq!(config.auth.role_map.get("bob").unwrap(), "operator");
+    }
+
+    #[test]
+    fn test_auth_cert_map() {
+        let config = AppConfig::parse(
+            r#"
+            [auth.cert_map]
+            "ops-laptop" = "operator"
+            "sha256:9F86D081884C7D659A2FEAA0C55AD015A3BF4F1B2B0B822CD15D6C15B0F00A08" = "admin"
+        "#,
+        )
+        .unwrap();
+        assert_eq!(config.auth.cert_map["ops-laptop"], "operator");
+        assert_eq!(config.auth.cert_map.len(), 2);
+
+        for bad in [
+            "\"\" = \"admin\"",
+            "\"ops-laptop\" = \"\"",
+            "\"sha256:abc\" = \"admin\"",
+            "\"sha256:9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00azz\" = \"admin\"",
+        ] {
+            let toml = format!("[auth.cert_map]\n{bad}\n");
+            assert!(AppConfig::parse(&toml).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_auth_token_mode() {
+        let config = AppConfig::default();
+        assert_eq!(config.auth.token_ttl_secs, 43_200);
+        assert!(config.auth.token_key_path.is_none());
+
+        let config = AppConfig::parse(
+            r#"
+            [auth]
+            mode = "token"
+            token_key_path = "/var/lib/crustyclaw/auth.key"
+            token_path = "/home/alice/.crustyclaw-token"
+            token_ttl_secs = 3600
+        "#,
+        )
+        .unwrap();
+        assert_eq!(config.auth.mode, "token");
+        assert_eq!(
+            config.auth.token_key_path.as_deref(),
+            Some("/var/lib/crustyclaw/auth.key")
+        );
+        assert_eq!(config.auth.token_ttl_secs, 3600);
+
+        assert!(AppConfig::parse("[auth]\ntoken_ttl_secs = 0\n").is_err());
+    }
+
+    #[test]
+    fn test_auth_validation_rejects_bad_mode() {
+        let toml = r#"
+            [auth]
+            mode = "oauth"
+        "#;
+        let result = AppConfig::parse(toml);
+        assert!(result.is_err());
+    }
+
+    // ── Async file-based loading ──────────────────────────────────────
+
+    #[tokio::test]
+    async fn test_load_from_file() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("crustyclaw.toml");
+        tokio::fs::write(
+            &path,
+            b"[daemon]\nlisten_port = 4242\nlisten_addr = \"0.0.0.0\"\n",
+        )
+        .await
+        .unwrap();
+
+        let config = AppConfig::load(&path).await.unwrap();
+        assert_eq!(config.daemon.listen_port, 4242);
+        assert_eq!(config.daemon.listen_addr, "0.0.0.0");
+    }
+
+    #[tokio::test]
+    async fn test_load_nonexistent_file() {
+        let result = AppConfig::load(Path::new("/nonexistent/file.toml")).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_load_invalid_toml_file() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("bad.toml");
+        tokio::fs::write(&path, b"not valid toml [[[")
+            .await
+            .unwrap();
+
+        let result = AppConfig::load(&path).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_load_with_includes() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("crustyclaw.toml");
+        tokio::fs::create_dir(tmp.path().join("policies"))
+            .await
+            .unwrap();
+        tokio::fs::write(
+            &path,
+            b"include = [\"policies/*.toml\"]\n\n[daemon]\nlisten_port = 4242\n",
+        )
+        .await
+        .unwrap();
+        let rule = |role: &str, effect: &str| {
+            format!(
+                "[[policy.rules]]\nrole = {role:?}\naction = \"*\"\nresource = \"*\"\neffect = {effect:?}\n"
+            )
+        };
+        let admin = tmp.path().join("policies/admin.toml");
+        tokio::fs::write(&admin, rule("admin", "allow"))
+            .await
+            .unwrap();
+        tokio::fs::write(tmp.path().join("policies/user.toml"), rule("user", "deny"))
+            .await
+            .unwrap();
+
+        let config = AppConfig::load(&path).await.unwrap();
+        assert_eq!(config.daemon.listen_port, 4242);
+        assert_eq!(config.include, ["policies/*.toml"]);
+        let roles: Vec<&str> = config
+            .policy
+            .rules
+            .iter()
+            .map(|r| r.role.as_str())
+            .collect();
+        assert_eq!(roles, ["admin", "user"]);
+
+        tokio::fs::write(&admin, rule("admin", "sometimes"))
+            .await
+            .unwrap();
+        let err = AppConfig::load(&path).await.unwrap_err();
+        assert!(
+            err.to_string().starts_with(&format!(
+                "{}: validation error: policy.rules[0]",
+                admin.display()
+            )),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_include_validation() {
+        let config = AppConfig::parse("include = [\"local.toml\"]\n").unwrap();
+        assert_eq!(config.include, ["local.toml"]);
+        for bad in ["include = [\"\"]", "include = [\"policies/**/*.toml\"]"] {
+            assert!(AppConfig::parse(bad).is_err(), "{bad}");
+        }
+    }
+
+    // ── Error display ─────────────────────────────────────────────────
+
+    #[test]
+    fn test_config_error_display() {
+        let err = ConfigError::Validation("bad value".to_string());
+        assert_eq!(err.to_string(), "validation error: bad value");
+    }
+
+    // ── Isolation validation ──────────────────────────────────────────
+
+    #[test]
+    fn test_validation_rejects_invalid_backend() {
+        let toml = r#"
+            [isolation]
+            backend = "hyperv"
+        "#;
+        let result = AppConfig::parse(toml);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_validation_accepts_docker_backend() {
+        let toml = r#"
+            [isolation]
+            backend = "docker"
+        "#;
+        let config = AppConfig::parse(toml).unwrap();
+        assert_eq!(config.isolation.backend, "docker");
+    }
+
+    #[test]
+    fn test_validation_accepts_firecracker_backend() {
+        let toml = r#"
+            [isolation]
+            backend = "firecracker"
+        "#;
+        let config = AppConfig::parse(toml).unwrap();
+        assert_eq!(config.isolation.backend, "firecracker");
+    }
+
+    #[test]
+    fn test_trust_tier_config() {
+        let toml = r#"
+            [isolation]
+            default_trust_tier = "untrusted"
+            credential_proxy = true
+            docker_image = "ubuntu:22.04"
+        "#;
+        let config = AppConfig::parse(toml).unwrap();
+        assert_eq!(
+            config.isolation.default_trust_tier.as_ref().unwrap(),
+            "untrusted"
+        );
+        assert!(config.isolation.credential_proxy);
+        assert_eq!(config.isolation.docker_image, "ubuntu:22.04");
     }
 
-    /// Build a [`PolicyEngine`](policy::PolicyEngine) from the loaded policy config.
-    pub fn build_policy_engine(&self) -> policy::PolicyEngine {
-        let rules: Vec<policy::PolicyRule> = self
-            .policy
-            .rules
-            .iter()
-            .map(|r| {
-                let effect = if r.effect == "allow" {
-                    policy::Effect::Allow
-                } else {
-                    policy::Effect::Deny
-                };
-                policy::PolicyRule {
-                    role: r.role.clone(),
-                    action: r.action.clone(),
-                    resource: r.resource.clone(),
-                    effect,
-                    priority: r.priority,
-                }
-            })
-            .collect();
+    #[test]
+    fn test_egress_proxy_config() {
+        let config = AppConfig::default();
+        assert_eq!(config.isolation.egress_proxy_addr, "127.0.0.1:3128");
+        assert!(config.isolation.egress_allowed_hosts.is_empty());
 
-        let mut engine = policy::build_policy(rules);
+        let toml = r#"
+            [isolation]
+            credential_proxy = true
+            egress_proxy_addr = "172.17.0.1:3128"
+            egress_allowed_hosts = ["api.openai.com", "*.anthropic.com"]
+        "#;
+        let config = AppConfig::parse(toml).unwrap();
+        assert_eq!(config.isolation.egress_proxy_addr, "172.17.0.1:3128");
+        assert_eq!(config.isolation.egress_allowed_hosts.len(), 2);
 
-        // Add default deny/allow rule at lowest priority
-        if self.policy.default_effect == "allow" {
-            engine.add_rule(policy::PolicyRule::allow("*", "*", "*").with_priority(0));
+        for bad in ["0.0.0.0:3128", "localhost:3128", "127.0.0.1"] {
+            let toml =
+                format!("[isolation]\ncredential_proxy = true\negress_proxy_addr = {bad:?}\n");
+            assert!(AppConfig::parse(&toml).is_err(), "{bad}");
+        }
+        for bad in ["", "*.", "https://api.openai.com", "a.*.com"] {
+            let toml = format!("[isolation]\negress_allowed_hosts = [{bad:?}]\n");
+            assert!(AppConfig::parse(&toml).is_err(), "{bad}");
         }
 
-        engine
+        let entry = r#"
+            [[secrets.entries]]
+            name = "openai"
+            inject_env = "OPENAI_API_KEY"
+        "#;
+        let config =
+            AppConfig::parse(&format!("{entry}egress_hosts = [\"api.openai.com\"]\n")).unwrap();
+        assert_eq!(config.secrets.entries[0].egress_hosts, ["api.openai.com"]);
+        assert!(AppConfig::parse(&format!("{entry}egress_hosts = [\"*\"]\n")).is_err());
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use pretty_assertions::assert_eq;
-    use tempfile::TempDir;
 
     #[test]
-    fn test_default_config() {
+    fn test_strict_images_config() {
         let config = AppConfig::default();
-        assert_eq!(config.daemon.listen_addr, "127.0.0.1");
-        assert_eq!(config.daemon.listen_port, 9100);
-        assert!(!config.signal.enabled);
-        assert_eq!(config.logging.level, "info");
+        assert!(!config.isolation.pull_images);
+        assert!(!config.isolation.strict_images);
+
+        // The default image floats with :latest.
+        assert!(AppConfig::parse("[isolation]\nstrict_images = true\n").is_err());
+        for image in [
+            "alpine:3.20",
+            "registry.local:5000/tool:1.0",
+            "alpine@sha256:0123",
+        ] {
+            let toml = format!(
+                "[isolation]\nstrict_images = true\npull_images = true\ndocker_image = {image:?}\n"
+            );
+            let config = AppConfig::parse(&toml).unwrap();
+            assert!(config.isolation.pull_images, "{image}");
+        }
+        for image in ["alpine", "alpine:latest", "registry.local:5000/tool"] {
+            let toml = format!("[isolation]\nstrict_images = true\ndocker_image = {image:?}\n");
+            assert!(AppConfig::parse(&toml).is_err(), "{image}");
+        }
     }
 
     #[test]
-    fn test_parse_minimal_toml() {
-        let toml = "";
-        let config = AppConfig::parse(toml).unwrap();
-        assert_eq!(config.daemon.listen_port, 9100);
+    fn test_trust_config() {
+        let config = AppConfig::parse(
+            r#"
+            [isolation.trust]
+            internal = "docker"
+            untrusted = "firecracker"
+            llm_generated = "auto"
+
+            [isolation.trust.levels]
+            internal = "l3"
+            trusted = "container"
+            "#,
+        )
+        .unwrap();
+        let trust = &config.isolation.trust;
+        assert_eq!(trust.backend("internal"), Some("docker"));
+        assert_eq!(trust.backend("untrusted"), Some("firecracker"));
+        assert_eq!(trust.backend("llm-generated"), Some("auto"));
+        assert_eq!(trust.backend("trusted"), None);
+        assert_eq!(trust.level("internal"), Some(3));
+        assert_eq!(trust.level("trusted"), Some(1));
+        assert_eq!(trust.level("untrusted"), Some(3));
+        assert!(AppConfig::default().isolation.trust.is_empty());
+
+        for bad in [
+            "[isolation.trust]\nuntrusted = \"kvm\"\n",
+            "[isolation.trust.levels]\nsketchy = \"l3\"\n",
+            "[isolation.trust.levels]\ntrusted = \"l4\"\n",
+            // Podman is L1; untrusted requires L3 unless lowered.
+            "[isolation.trust]\nuntrusted = \"podman\"\n",
+            "[isolation.trust]\ninternal = \"noop\"\n",
+        ] {
+            assert!(AppConfig::parse(bad).is_err(), "{bad}");
+        }
+        AppConfig::parse(
+            "[isolation.trust]\ninternal = \"podman\"\n[isolation.trust.levels]\ninternal = \"l1\"\n",
+        )
+        .unwrap();
     }
 
     #[test]
-    fn test_parse_full_toml() {
+    fn test_validation_rejects_invalid_trust_tier() {
         let toml = r#"
-            [daemon]
-            listen_addr = "0.0.0.0"
-            listen_port = 8080
-
-            [signal]
-            enabled = true
-            data_dir = "/var/lib/crustyclaw/signal"
-
-            [logging]
-            level = "debug"
+            [isolation]
+            default_trust_tier = "suspicious"
         "#;
-        let config = AppConfig::parse(toml).unwrap();
-        assert_eq!(config.daemon.listen_addr, "0.0.0.0");
-        assert_eq!(config.daemon.listen_port, 8080);
-        assert!(config.signal.enabled);
-        assert_eq!(config.logging.level, "debug");
+        let result = AppConfig::parse(toml);
+        assert!(result.is_err());
     }
 
     #[test]
-    fn test_validation_rejects_zero_port() {
+    fn test_validation_rejects_zero_memory() {
         let toml = r#"
-            [daemon]
-            listen_port = 0
+            [isolation]
+            default_memory_bytes = 0
         "#;
         let result = AppConfig::parse(toml);
         assert!(result.is_err());
     }
 
     #[test]
-    fn test_validation_rejects_empty_addr() {
+    fn test_validation_rejects_bad_cpu_fraction() {
         let toml = r#"
-            [daemon]
-            listen_addr = ""
+            [isolation]
+            default_cpu_fraction = 0.0
         "#;
         let result = AppConfig::parse(toml);
         assert!(result.is_err());
     }
 
     #[test]
-    fn test_policy_config_from_toml() {
-        let toml = r#"
-            [policy]
-            default_effect = "deny"
+    fn test_context_sensitive_globs() {
+        let config = AppConfig::parse(
+            r#"
+            [context]
+            sensitive_globs = ["*.key", "config/credentials*"]
+        "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.context.sensitive_globs,
+            vec!["*.key", "config/credentials*"]
+        );
+        assert!(AppConfig::default().context.sensitive_globs.is_empty());
 
-            [[policy.rules]]
-            role = "admin"
-            action = "*"
-            resource = "*"
-            effect = "allow"
-            priority = 10
+        let result = AppConfig::parse("[context]\nsensitive_globs = [\" \"]\n");
+        assert!(result.is_err());
+    }
 
-            [[policy.rules]]
-            role = "user"
-            action = "read"
-            resource = "config"
-            effect = "allow"
-            priority = 5
-        "#;
-        let config = AppConfig::parse(toml).unwrap();
-        assert_eq!(config.policy.rules.len(), 2);
-        assert_eq!(config.policy.default_effect, "deny");
+    #[test]
+    fn test_context_environment() {
+        let config = AppConfig::default();
+        assert!(config.context.environment.enabled);
+        assert_eq!(config.context.environment.facts, ENVIRONMENT_FACTS);
+
+        let config = AppConfig::parse(
+            r#"
+            [context.environment]
+            facts = ["hostname", "time"]
+            template = "Running on {hostname} at {time}."
+            persona = "ops"
+        "#,
+        )
+        .unwrap();
+        assert_eq!(config.context.environment.facts, vec!["hostname", "time"]);
+        assert_eq!(config.context.environment.persona.as_deref(), Some("ops"));
 
-        let mut engine = config.build_policy_engine();
-        assert!(engine.is_allowed("admin", "write", "secrets"));
-        assert!(engine.is_allowed("user", "read", "config"));
-        assert!(!engine.is_allowed("user", "write", "config"));
+        let result = AppConfig::parse("[context.environment]\nfacts = [\"kernel\"]\n");
+        assert!(result.is_err());
+        let result = AppConfig::parse("[context.environment]\ntemplate = \"{uptime}\"\n");
+        assert!(result.is_err());
+        let result = AppConfig::parse("[context.environment]\ntemplate = \"{time\"\n");
+        assert!(result.is_err());
     }
 
     #[test]
-    fn test_policy_validation_rejects_bad_effect() {
-        let toml = r#"
-            [[policy.rules]]
-            role = "admin"
-            action = "*"
-            resource = "*"
-            effect = "maybe"
-        "#;
-        let result = AppConfig::parse(toml);
-        assert!(result.is_err());
+    fn test_context_relevance() {
+        let config = AppConfig::default();
+        assert_eq!(config.context.relevance.query_weight, 0.6);
+        assert_eq!(config.context.relevance.recency_half_life_hours, 72);
+
+        let config = AppConfig::parse(
+            "[context.relevance]\nquery_weight = 1.0\nrecency_weight = 0.0\nusage_weight = 0.5\n",
+        )
+        .unwrap();
+        assert_eq!(config.context.relevance.recency_weight, 0.0);
+        assert_eq!(config.context.relevance.usage_weight, 0.5);
+
+        assert!(AppConfig::parse("[context.relevance]\nusage_weight = -0.1\n").is_err());
+        assert!(
+            AppConfig::parse(
+                "[context.relevance]\nquery_weight = 0.0\nrecency_weight = 0.0\nusage_weight = 0.0\n"
+            )
+            .is_err()
+        );
+        assert!(AppConfig::parse("[context.relevance]\nrecency_half_life_hours = 0\n").is_err());
     }
 
     #[test]
-    fn test_policy_default_allow() {
-        let toml = r#"
-            [policy]
-            default_effect = "allow"
-        "#;
-        let config = AppConfig::parse(toml).unwrap();
-        let mut engine = config.build_policy_engine();
-        // With default allow, everything is permitted
-        assert!(engine.is_allowed("anyone", "anything", "anywhere"));
+    fn test_context_compaction() {
+        let config = AppConfig::default();
+        assert!(config.context.compaction.enabled);
+        assert_eq!(config.context.compaction.summary_tokens, 512);
+        assert_eq!(config.context.compaction.min_items, 2);
+
+        let config =
+            AppConfig::parse("[context.compaction]\nenabled = false\nsummary_tokens = 200\n")
+                .unwrap();
+        assert!(!config.context.compaction.enabled);
+        assert_eq!(config.context.compaction.summary_tokens, 200);
+
+        assert!(AppConfig::parse("[context.compaction]\nsummary_tokens = 0\n").is_err());
+        assert!(AppConfig::parse("[context.compaction]\nmin_items = 0\n").is_err());
     }
 
-    // ── Secrets config ──────────────────────────────────────────────
+    #[test]
+    fn test_context_elevation() {
+        let config = AppConfig::default();
+        assert!(config.context.elevation.enabled);
+        assert_eq!(config.context.elevation.ttl_secs, 900);
+        assert_eq!(config.context.elevation.max_trust, "trusted");
+
+        let config = AppConfig::parse("[context.elevation]\nmax_trust = \"system\"\n").unwrap();
+        assert_eq!(config.context.elevation.max_trust, "system");
+
+        assert!(AppConfig::parse("[context.elevation]\nttl_secs = 0\n").is_err());
+        assert!(AppConfig::parse("[context.elevation]\nmax_trust = \"root\"\n").is_err());
+    }
 
     #[test]
-    fn test_secrets_config_from_toml() {
-        let toml = r#"
-            [secrets]
-            staging_dir = "/tmp/secrets"
+    fn test_response_config() {
+        let config = AppConfig::default();
+        assert_eq!(config.response.hooks, vec!["secret_scan", "split"]);
+        assert_eq!(config.response.max_message_chars, 2000);
+        assert_eq!(config.response.on_secret, "redact");
+
+        let config = AppConfig::parse(
+            r#"
+            [response]
+            hooks = ["pii", "my_plugin_hook", "signature"]
+            signature = "bye"
+            on_secret = "block"
+        "#,
+        )
+        .unwrap();
+        assert_eq!(config.response.hooks.len(), 3);
+        assert_eq!(config.response.signature.as_deref(), Some("bye"));
+
+        for bad in [
+            "[response]\nhooks = [\"signature\"]\n",
+            "[response]\nhooks = [\"split\", \"split\"]\n",
+            "[response]\nmax_message_chars = 0\n",
+            "[response]\non_secret = \"ignore\"\n",
+        ] {
+            assert!(AppConfig::parse(bad).is_err(), "{bad}");
+        }
+    }
 
-            [[secrets.entries]]
-            name = "api_key"
-            source = "env"
-            inject_as = "env"
-            inject_env = "API_KEY"
-            description = "LLM API key"
+    #[test]
+    fn test_telemetry_config() {
+        let config = AppConfig::default();
+        assert!(!config.telemetry.enabled);
+        assert_eq!(config.telemetry.interval_secs, 86400);
 
-            [[secrets.entries]]
-            name = "tls_cert"
-            source = "file"
-            file_path = "/etc/crustyclaw/tls.pem"
-            inject_as = "file"
-            inject_path = "/run/secrets/tls.pem"
-        "#;
-        let config = AppConfig::parse(toml).unwrap();
-        assert_eq!(config.secrets.staging_dir, "/tmp/secrets");
-        assert_eq!(config.secrets.entries.len(), 2);
-        assert_eq!(config.secrets.entries[0].name, "api_key");
-        assert_eq!(config.secrets.entries[0].source, "env");
-        assert_eq!(config.secrets.entries[1].inject_as, "file");
+        let config = AppConfig::parse(
+            r#"
+            [telemetry]
+            enabled = true
+            endpoint = "https://telemetry.example.com/v1/report"
+            epsilon = 0.5
+        "#,
+        )
+        .unwrap();
+        assert!(config.telemetry.enabled);
+        assert_eq!(config.telemetry.epsilon, 0.5);
+
+        for bad in [
+            "[telemetry]\nenabled = true\n",
+            "[telemetry]\nenabled = true\nendpoint = \"ftp://x\"\n",
+            "[telemetry]\nenabled = true\nendpoint = \"https://x\"\ninterval_secs = 0\n",
+            "[telemetry]\nepsilon = 0.0\n",
+        ] {
+            assert!(AppConfig::parse(bad).is_err(), "{bad}");
+        }
     }
 
     #[test]
-    fn test_secrets_validation_rejects_empty_name() {
-        let toml = r#"
+    fn test_webhook_config() {
+        let config = AppConfig::default();
+        assert!(!config.webhook.enabled);
+        assert_eq!(config.webhook.timeout_secs, 10);
+
+        let config = AppConfig::parse(
+            r#"
+            [webhook]
+            enabled = true
+            secret = "webhook_key"
+            url = "https://chat.example.com/hooks/crustyclaw"
+
             [[secrets.entries]]
-            name = ""
-            source = "env"
-            inject_as = "env"
-            inject_env = "KEY"
-        "#;
-        let result = AppConfig::parse(toml);
-        assert!(result.is_err());
+            name = "webhook_key"
+            source = "inline"
+            value = "k"
+            inject_env = "WEBHOOK_KEY"
+        "#,
+        )
+        .unwrap();
+        assert!(config.webhook.enabled);
+        assert_eq!(config.webhook.secret.as_deref(), Some("webhook_key"));
+
+        for bad in [
+            "[webhook]\nenabled = true\n",
+            "[webhook]\nenabled = true\nsecret = \"missing\"\n",
+            "[webhook]\nurl = \"ftp://x\"\n",
+            "[webhook]\ntimeout_secs = 0\n",
+        ] {
+            assert!(AppConfig::parse(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_matrix_config() {
+        let config = AppConfig::default();
+        assert!(!config.matrix.enabled);
+        assert_eq!(config.matrix.data_dir, "data/matrix");
+
+        let secret = "[[secrets.entries]]\nname = \"pw\"\nsource = \"inline\"\nvalue = \"x\"\ninject_env = \"PW\"\n";
+        let config = AppConfig::parse(&format!(
+            r#"
+            [matrix]
+            enabled = true
+            homeserver_url = "https://matrix.example.org"
+            user_id = "@crustyclaw:example.org"
+            password_secret = "pw"
+
+            [matrix.rooms."!ops:example.org"]
+            role = "operator"
+
+            [matrix.rooms."!lobby:example.org"]
+
+            [commands.roles]
+            "!lobby:example.org" = "viewer"
+
+            {secret}
+        "#
+        ))
+        .unwrap();
+        assert!(config.matrix.enabled);
+        let commands = config.effective_commands();
+        assert_eq!(commands.roles["!ops:example.org"], "operator");
+        assert_eq!(commands.roles["!lobby:example.org"], "viewer");
+
+        let enabled = "[matrix]\nenabled = true\nhomeserver_url = \"https://m.org\"\nuser_id = \"@b:m.org\"\n";
+        for bad in [
+            format!("{enabled}password_secret = \"missing\"\n"),
+            format!("{enabled}access_token_secret = \"pw\"\n{secret}"),
+            format!(
+                "{enabled}password_secret = \"pw\"\naccess_token_secret = \"pw\"\ndevice_id = \"D\"\n{secret}"
+            ),
+            format!("{enabled}\n{secret}"),
+            "[matrix]\nenabled = true\nhomeserver_url = \"matrix.org\"\n".to_string(),
+            "[matrix]\nenabled = true\nhomeserver_url = \"https://m.org\"\nuser_id = \"bot\"\n"
+                .to_string(),
+            "[matrix.rooms.\"#ops:example.org\"]\n".to_string(),
+        ] {
+            assert!(AppConfig::parse(&bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_tools_config() {
+        let config = AppConfig::parse(
+            r#"
+            [[tools]]
+            name = "lint"
+            description = "Run the linter."
+            command = ["sh", "-c", "ruff check \"$CRUSTYCLAW_ARG_PATH\""]
+            trust = "public"
+            tags = ["code"]
+
+            [tools.parameters]
+            type = "object"
+            required = ["path"]
+
+            [tools.parameters.properties.path]
+            type = "string"
+
+            [[tools]]
+            name = "uptime"
+            description = "Report uptime."
+            command = ["uptime"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.tools.len(), 2);
+        let lint = &config.tools[0];
+        assert_eq!(lint.trust, "public");
+        assert_eq!(lint.parameters["required"][0], "path");
+        assert_eq!(lint.parameters["properties"]["path"]["type"], "string");
+        let uptime = &config.tools[1];
+        assert_eq!(uptime.trust, "internal");
+        assert!(uptime.enabled);
+        assert_eq!(uptime.parameters["type"], "object");
+
+        let tool = |extra: &str| {
+            format!("[[tools]]\nname = \"t\"\ndescription = \"d\"\ncommand = [\"true\"]\n{extra}")
+        };
+        for bad in [
+            "[[tools]]\nname = \"read_file\"\ndescription = \"d\"\ncommand = [\"true\"]\n"
+                .to_string(),
+            "[[tools]]\nname = \"a b\"\ndescription = \"d\"\ncommand = [\"true\"]\n".to_string(),
+            "[[tools]]\nname = \"t\"\ndescription = \"d\"\ncommand = []\n".to_string(),
+            tool("trust = \"root\"\n"),
+            tool("timeout_secs = 0\n"),
+            tool("[tools.parameters]\ntype = \"string\"\n"),
+            tool("[tools.parameters]\ntype = \"object\"\nrequired = [\"missing\"]\n"),
+            tool("[tools.parameters]\ntype = \"object\"\nproperties.n.type = \"float\"\n"),
+            tool("[tools.parameters]\ntype = \"object\"\nproperties.n.enum = \"a\"\n"),
+            tool("[tools.parameters]\ntype = \"object\"\nproperties.n.pattern = \"(a\"\n"),
+            tool("[tools.parameters]\ntype = \"object\"\nproperties.n.\"$ref\" = \"#/$defs/n\"\n"),
+            tool("") + &tool(""),
+        ] {
+            assert!(AppConfig::parse(&bad).is_err(), "{bad}");
+        }
+        AppConfig::parse(&tool(
+            "[tools.parameters]\ntype = \"object\"\nproperties.n.type = [\"integer\", \"null\"]\n",
+        ))
+        .unwrap();
+    }
+
+    #[test]
+    fn test_mcp_config() {
+        assert!(AppConfig::default().mcp.servers.is_empty());
+
+        let config = AppConfig::parse(
+            r#"
+            [[mcp.servers]]
+            name = "github"
+            command = ["github-mcp-server", "stdio"]
+            env = { GITHUB_TOOLSETS = "repos" }
+
+            [[mcp.servers]]
+            name = "search"
+            url = "http://127.0.0.1:8931/mcp"
+            trust = "public"
+            tag_prefix = "web"
+        "#,
+        )
+        .unwrap();
+        let github = &config.mcp.servers[0];
+        assert_eq!(github.command, vec!["github-mcp-server", "stdio"]);
+        assert_eq!(github.env["GITHUB_TOOLSETS"], "repos");
+        assert_eq!(github.trust, "internal");
+        assert_eq!(github.tag_prefix, "mcp");
+        assert_eq!(github.timeout_secs, 30);
+        assert!(github.enabled);
+        assert_eq!(config.mcp.servers[1].tag_prefix, "web");
+
+        for bad in [
+            "[[mcp.servers]]\nname = \"a\"\n",
+            "[[mcp.servers]]\nname = \"a\"\ncommand = [\"x\"]\nurl = \"http://x\"\n",
+            "[[mcp.servers]]\nname = \"a\"\nurl = \"ws://x\"\n",
+            "[[mcp.servers]]\nname = \"a b\"\ncommand = [\"x\"]\n",
+            "[[mcp.servers]]\nname = \"a\"\ncommand = [\"x\"]\ntrust = \"root\"\n",
+            "[[mcp.servers]]\nname = \"a\"\ncommand = [\"x\"]\ntimeout_secs = 0\n",
+            "[[mcp.servers]]\nname = \"a\"\ncommand = [\"x\"]\n[[mcp.servers]]\nname = \"a\"\ncommand = [\"y\"]\n",
+        ] {
+            assert!(AppConfig::parse(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_plugins_config() {
+        let config = AppConfig::default();
+        assert!(!config.plugins.enabled);
+        assert_eq!(config.plugins.dir, "plugins");
+        assert_eq!(config.plugins.trust, "trusted");
+
+        let config = AppConfig::parse(
+            r#"
+            [plugins]
+            enabled = true
+            dir = "/opt/crustyclaw/plugins"
+            trust = "internal"
+        "#,
+        )
+        .unwrap();
+        assert!(config.plugins.enabled);
+        assert_eq!(config.plugins.dir, "/opt/crustyclaw/plugins");
+        assert_eq!(config.plugins.trust, "internal");
+
+        for bad in ["[plugins]\ndir = \" \"\n", "[plugins]\ntrust = \"root\"\n"] {
+            assert!(AppConfig::parse(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_schedules_config() {
+        assert!(AppConfig::default().schedules.is_empty());
+
+        let config = AppConfig::parse(
+            r#"
+            [[schedules]]
+            name = "nightly-backup"
+            cron = "0 3 * * *"
+            skill = "backup"
+            args = { target = "s3" }
+            jitter_secs = 300
+
+            [[schedules]]
+            name = "digest"
+            cron = "@daily"
+            prompt = "Summarise yesterday."
+            role = "operator"
+            enabled = false
+        "#,
+        )
+        .unwrap();
+        assert_eq!(config.schedules.len(), 2);
+        let backup = &config.schedules[0];
+        assert_eq!(backup.skill.as_deref(), Some("backup"));
+        assert_eq!(backup.args["target"].as_str(), Some("s3"));
+        assert_eq!(backup.role, "user");
+        assert_eq!(backup.jitter_secs, 300);
+        assert!(backup.enabled);
+        let digest = &config.schedules[1];
+        assert_eq!(digest.prompt.as_deref(), Some("Summarise yesterday."));
+        assert_eq!(digest.role, "operator");
+        assert!(!digest.enabled);
+
+        for bad in [
+            "[[schedules]]\nname = \"a\"\ncron = \"* * *\"\nskill = \"s\"\n",
+            "[[schedules]]\nname = \"a\"\ncron = \"@daily\"\n",
+            "[[schedules]]\nname = \"a\"\ncron = \"@daily\"\nskill = \"s\"\nprompt = \"p\"\n",
+            "[[schedules]]\nname = \"a b\"\ncron = \"@daily\"\nskill = \"s\"\n",
+            "[[schedules]]\nname = \"a\"\ncron = \"@daily\"\nskill = \"s\"\nrole = \"\"\n",
+            "[[schedules]]\nname = \"a\"\ncron = \"@daily\"\nskill = \"s\"\n\
+             [[schedules]]\nname = \"a\"\ncron = \"@hourly\"\nskill = \"t\"\n",
+        ] {
+            assert!(AppConfig::parse(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_wasm_plugins_config() {
+        let config = AppConfig::default();
+        let wasm = &config.plugins.wasm;
+        assert!(!wasm.enabled);
+        assert_eq!(wasm.dir, "actions");
+        assert_eq!(wasm.trust, "internal");
+        assert_eq!(wasm.fuel, 100_000_000);
+        assert!(wasm.hot_reload);
+
+        let config = AppConfig::parse(
+            r#"
+            [plugins.wasm]
+            enabled = true
+            fuel = 5000
+            memory_bytes = 1048576
+            hot_reload = false
+        "#,
+        )
+        .unwrap();
+        assert!(config.plugins.wasm.enabled);
+        assert!(!config.plugins.enabled);
+        assert_eq!(config.plugins.wasm.fuel, 5000);
+        assert_eq!(config.plugins.wasm.memory_bytes, 1048576);
+        assert!(!config.plugins.wasm.hot_reload);
+
+        for bad in [
+            "[plugins.wasm]\nfuel = 0\n",
+            "[plugins.wasm]\ntrust = \"root\"\n",
+            "[plugins.wasm]\ndir = \"\"\n",
+        ] {
+            assert!(AppConfig::parse(bad).is_err(), "{bad}");
+        }
     }
 
     #[test]
-    fn test_secrets_validation_rejects_bad_source() {
-        let toml = r#"
-            [[secrets.entries]]
-            name = "key"
-            source = "database"
-            inject_as = "env"
-            inject_env = "KEY"
-        "#;
-        let result = AppConfig::parse(toml);
-        assert!(result.is_err());
+    fn test_commands_config() {
+        let config = AppConfig::default();
+        assert!(config.commands.aliases.is_empty());
+        assert_eq!(config.commands.default_role, "user");
+        assert_eq!(config.commands.prefix, "!");
+
+        let config = AppConfig::parse(
+            r#"
+            [commands.roles]
+            "+15550001" = "operator"
+
+            [commands.aliases."deploy staging"]
+            skill = "deploy"
+            args = { env = "staging", dry_run = false }
+            role = "operator"
+            description = "Deploy main to staging"
+
+            [commands.aliases.uptime]
+            skill = "status"
+        "#,
+        )
+        .unwrap();
+        let deploy = &config.commands.aliases["deploy staging"];
+        assert_eq!(deploy.skill, "deploy");
+        assert_eq!(deploy.args["env"].as_str(), Some("staging"));
+        assert_eq!(deploy.args["dry_run"].as_bool(), Some(false));
+        assert_eq!(config.commands.aliases["uptime"].role, "user");
+        assert_eq!(config.commands.roles["+15550001"], "operator");
+
+        assert_eq!(
+            normalize_command("  /Deploy   Staging \n"),
+            "deploy staging"
+        );
+
+        for bad in [
+            "[commands.aliases.\"/help\"]\nskill = \"x\"\n",
+            "[commands.aliases.\" \"]\nskill = \"x\"\n",
+            "[commands.aliases.a]\nskill = \"\"\n",
+            "[commands.aliases.a]\nskill = \"x\"\nrole = \"\"\n",
+            "[commands.aliases.a]\nskill = \"x\"\n[commands.aliases.\"/A\"]\nskill = \"y\"\n",
+            "[commands]\ndefault_role = \"\"\n",
+            "[commands]\nprefix = \"cmd\"\n",
+            "[commands]\nprefix = \"! \"\n",
+            "[commands.aliases.\"!status\"]\nskill = \"x\"\n",
+        ] {
+            assert!(AppConfig::parse(bad).is_err(), "{bad}");
+        }
+        for good in [
+            "[commands]\nprefix = \"\"\n[commands.aliases.\"!status\"]\nskill = \"x\"\n",
+            "[commands]\nprefix = \"::\"\n",
+        ] {
+            assert!(AppConfig::parse(good).is_ok(), "{good}");
+        }
     }
 
     #[test]
-    fn test_secrets_validation_requires_inject_env() {
-        let toml = r#"
+    fn test_llm_fallbacks_config() {
+        let config = AppConfig::default();
+        assert_eq!(config.llm.effective_retry(), LlmRetryConfig::default());
+        assert_eq!(config.llm.retry.max_retries, 2);
+        assert_eq!(config.llm.retry.initial_backoff_ms, 500);
+        assert!(config.llm.fallbacks.is_empty());
+
+        let config = AppConfig::parse(
+            r#"
+            [llm]
+            max_retries = 0
+
+            [[llm.fallbacks]]
+            provider = "openai"
+            model = "gpt-4o"
+            api_key_env = "OPENAI_API_KEY"
+
+            [[llm.fallbacks]]
+            provider = "openai"
+            base_url = "http://localhost:11434/v1"
+        "#,
+        )
+        .unwrap();
+        assert_eq!(config.llm.effective_retry().max_retries, 0);
+        assert_eq!(config.llm.fallbacks.len(), 2);
+        assert_eq!(config.llm.fallbacks[0].provider, LlmProviderKind::OpenAi);
+        assert_eq!(
+            config.llm.fallbacks[0].api_key_env.as_deref(),
+            Some("OPENAI_API_KEY")
+        );
+        assert!(config.llm.fallbacks[1].model.is_empty());
+
+        for bad in [
+            "[llm]\nmax_retries = 11\n",
+            "[llm.retry]\nmax_retries = 11\n",
+            "[llm.retry]\ninitial_backoff_ms = 2000\nmax_backoff_ms = 1000\n",
+            "[llm.retry]\njitter = 1.5\n",
+            "[[llm.fallbacks]]\napi_key_env = \" \"\n",
+            "[llm]\napi_key_secret = \"missing\"\n",
+            "[[llm.fallbacks]]\napi_key_secret = \"missing\"\n",
+        ] {
+            assert!(AppConfig::parse(bad).is_err(), "{bad}");
+        }
+
+        let config = AppConfig::parse(
+            r#"
+            [llm]
+            api_key_secret = "anthropic_key"
+
+            [[llm.fallbacks]]
+            provider = "openai"
+            api_key_secret = "openai_key"
+
             [[secrets.entries]]
-            name = "key"
+            name = "anthropic_key"
             source = "env"
-            inject_as = "env"
-        "#;
-        let result = AppConfig::parse(toml);
-        assert!(result.is_err());
-    }
+            env_var = "ANTHROPIC_API_KEY"
+            inject_env = "ANTHROPIC_API_KEY"
 
-    #[test]
-    fn test_secrets_validation_requires_inject_path() {
-        let toml = r#"
             [[secrets.entries]]
-            name = "cert"
-            source = "file"
-            file_path = "/etc/cert.pem"
-            inject_as = "file"
-        "#;
-        let result = AppConfig::parse(toml);
-        assert!(result.is_err());
+            name = "openai_key"
+            source = "env"
+            env_var = "OPENAI_API_KEY"
+            inject_env = "OPENAI_API_KEY"
+        "#,
+        )
+        .unwrap();
+        assert_eq!(config.llm.api_key_secret.as_deref(), Some("anthropic_key"));
+        assert_eq!(
+            config.llm.fallbacks[0].api_key_secret.as_deref(),
+            Some("openai_key")
+        );
     }
 
     #[test]
-    fn test_secrets_validation_requires_file_path() {
-        let toml = r#"
+    fn test_redacted() {
+        let config = AppConfig::parse(
+            r#"
+            [llm]
+            api_key = "sk-primary"
+
+            [[llm.fallbacks]]
+            provider = "openai"
+            api_key = "sk-fallback"
+
+            [[llm.fallbacks]]
+            provider = "openai"
+            api_key_env = "OPENAI_API_KEY"
+
             [[secrets.entries]]
-            name = "cert"
-            source = "file"
-            inject_as = "env"
-            inject_env = "CERT"
-        "#;
-        let result = AppConfig::parse(toml);
-        assert!(result.is_err());
+            name = "token"
+            source = "inline"
+            value = "hunter2"
+            inject_env = "TOKEN"
+        "#,
+        )
+        .unwrap();
+        let redacted = config.redacted();
+        assert_eq!(redacted.llm.api_key, REDACTED);
+        assert_eq!(redacted.llm.fallbacks[0].api_key, REDACTED);
+        // Empty keys stay empty, so it is still visible that none is set.
+        assert_eq!(redacted.llm.fallbacks[1].api_key, "");
+        assert_eq!(redacted.secrets.entries[0].value.as_deref(), Some(REDACTED));
+        assert_eq!(config.llm.api_key, "sk-primary");
     }
 
     #[test]
-    fn test_secrets_both_injection() {
-        let toml = r#"
-            [[secrets.entries]]
-            name = "dual_key"
-            source = "inline"
-            value = "secret-value"
-            inject_as = "both"
-            inject_env = "DUAL_KEY"
-            inject_path = "/run/secrets/dual_key"
-        "#;
-        let config = AppConfig::parse(toml).unwrap();
-        assert_eq!(config.secrets.entries[0].inject_as, "both");
-        assert_eq!(
-            config.secrets.entries[0].inject_env.as_ref().unwrap(),
-            "DUAL_KEY"
-        );
+    fn test_llm_retry_config() {
+        let config = AppConfig::parse(
+            "[llm.retry]\nmax_retries = 4\ninitial_backoff_ms = 100\nmax_backoff_ms = 5000\njitter = 0.0\n",
+        )
+        .unwrap();
         assert_eq!(
-            config.secrets.entries[0].inject_path.as_ref().unwrap(),
-            "/run/secrets/dual_key"
+            config.llm.effective_retry(),
+            LlmRetryConfig {
+                max_retries: 4,
+                initial_backoff_ms: 100,
+                max_backoff_ms: 5000,
+                jitter: 0.0,
+            }
         );
-    }
 
-    // ── Auth config ───────────────────────────────────────────────────
+        // The old top-level keys still apply, over `[llm.retry]`.
+        let config = AppConfig::parse(
+            "[llm]\nmax_retries = 1\nretry_backoff_ms = 250\n[llm.retry]\nmax_retries = 4\n",
+        )
+        .unwrap();
+        let retry = config.llm.effective_retry();
+        assert_eq!((retry.max_retries, retry.initial_backoff_ms), (1, 250));
+    }
 
     #[test]
-    fn test_auth_config_default() {
+    fn test_llm_http_config() {
         let config = AppConfig::default();
-        assert_eq!(config.auth.mode, "local");
-        assert!(config.auth.role_map.is_empty());
+        assert_eq!(config.llm.http, LlmHttpConfig::default());
+        assert_eq!(config.chat.timeout_secs, None);
+
+        let config = AppConfig::parse(
+            r#"
+            [llm.http]
+            connect_timeout_secs = 5
+            proxy = "http://proxy.internal:3128"
+            no_proxy = ["localhost", ".internal"]
+            tcp_keepalive_secs = 0
+
+            [chat]
+            timeout_secs = 120
+        "#,
+        )
+        .unwrap();
+        assert_eq!(config.llm.http.connect_timeout_secs, 5);
+        assert_eq!(config.llm.http.request_timeout_secs, 300);
+        assert_eq!(
+            config.llm.http.proxy.as_deref(),
+            Some("http://proxy.internal:3128")
+        );
+        assert_eq!(config.llm.http.no_proxy, ["localhost", ".internal"]);
+        assert_eq!(config.llm.http.tcp_keepalive_secs, 0);
+        assert_eq!(config.chat.timeout_secs, Some(120));
+
+        for bad in [
+            "[llm.http]\nconnect_timeout_secs = 0\n",
+            "[llm.http]\nrequest_timeout_secs = 0\n",
+            "[llm.http]\nproxy = \"proxy.internal:3128\"\n",
+            "[chat]\ntimeout_secs = 0\n",
+        ] {
+            assert!(AppConfig::parse(bad).is_err(), "{bad}");
+        }
     }
 
     #[test]
-    fn test_auth_config_from_toml() {
-        let toml = r#"
-            [auth]
-            mode = "local"
+    fn test_llamacpp_config() {
+        let config = AppConfig::default();
+        assert_eq!(config.llm.llamacpp, LlamaCppConfig::default());
+        assert!(config.llm.llamacpp.grammar);
+
+        let config = AppConfig::parse(
+            r#"
+            [llm]
+            provider = "llamacpp"
+            base_url = "http://127.0.0.1:8080"
+
+            [llm.llamacpp]
+            chat_template = "llama3"
+            grammar = false
+            context_size = 8192
+        "#,
+        )
+        .unwrap();
+        assert_eq!(config.llm.provider, LlmProviderKind::LlamaCpp);
+        assert_eq!(config.llm.llamacpp.chat_template, LlamaCppTemplate::Llama3);
+        assert!(!config.llm.llamacpp.grammar);
+        assert_eq!(config.llm.llamacpp.context_size, Some(8192));
 
-            [auth.role_map]
-            alice = "admin"
-            bob = "operator"
-        "#;
-        let config = AppConfig::parse(toml).unwrap();
-        assert_eq!(config.auth.mode, "local");
-        assert_eq!(config.auth.role_map.get("alice").unwrap(), "admin");
-        assert_eq!(config.auth.role_map.get("bob").unwrap(), "operator");
+        assert!(AppConfig::parse("[llm.llamacpp]\ncontext_size = 0\n").is_err());
+        assert!(AppConfig::parse("[llm.llamacpp]\nchat_template = \"alpaca\"\n").is_err());
     }
 
     #[test]
-    fn test_auth_validation_rejects_bad_mode() {
-        let toml = r#"
-            [auth]
-            mode = "oauth"
-        "#;
-        let result = AppConfig::parse(toml);
-        assert!(result.is_err());
-    }
-
-    // ── Async file-based loading ──────────────────────────────────────
-
-    #[tokio::test]
-    async fn test_load_from_file() {
-        let tmp = TempDir::new().unwrap();
-        let path = tmp.path().join("crustyclaw.toml");
-        tokio::fs::write(
-            &path,
-            b"[daemon]\nlisten_port = 4242\nlisten_addr = \"0.0.0.0\"\n",
+    fn test_llm_pricing_config() {
+        assert!(AppConfig::default().llm.pricing.is_empty());
+
+        let config = AppConfig::parse(
+            r#"
+            [llm.pricing."claude-sonnet-4-20250514"]
+            prompt_per_mtok = 3.0
+            completion_per_mtok = 15.0
+
+            [llm.pricing."gpt-4o"]
+            prompt_per_mtok = 2.5
+        "#,
         )
-        .await
         .unwrap();
+        let sonnet = config.llm.pricing["claude-sonnet-4-20250514"];
+        assert_eq!(sonnet.cost(1_000_000, 100_000), 4.5);
+        assert_eq!(config.llm.pricing["gpt-4o"].completion_per_mtok, 0.0);
 
-        let config = AppConfig::load(&path).await.unwrap();
-        assert_eq!(config.daemon.listen_port, 4242);
-        assert_eq!(config.daemon.listen_addr, "0.0.0.0");
+        let bad = "[llm.pricing.\"gpt-4o\"]\nprompt_per_mtok = -1.0\n";
+        assert!(AppConfig::parse(bad).is_err());
     }
 
-    #[tokio::test]
-    async fn test_load_nonexistent_file() {
-        let result = AppConfig::load(Path::new("/nonexistent/file.toml")).await;
-        assert!(result.is_err());
+    #[test]
+    fn test_chat_config() {
+        let config = AppConfig::default();
+        assert_eq!(config.chat.max_iterations, 16);
+        assert_eq!(config.chat.tool_trust["admin"], "trusted");
+        assert_eq!(config.chat.tool_trust["operator"], "internal");
+
+        let config = AppConfig::parse(
+            r#"
+            [chat]
+            system_prompt = "Be brief."
+            token_budget = 20000
+
+            [chat.tool_trust]
+            ops = "internal"
+        "#,
+        )
+        .unwrap();
+        assert_eq!(config.chat.system_prompt.as_deref(), Some("Be brief."));
+        assert_eq!(config.chat.token_budget, Some(20000));
+        // A listed table replaces the defaults.
+        assert_eq!(config.chat.tool_trust.len(), 1);
+
+        for bad in [
+            "[chat]\nmax_iterations = 0\n",
+            "[chat]\ntoken_budget = 0\n",
+            "[chat.tool_trust]\nops = \"root\"\n",
+        ] {
+            assert!(AppConfig::parse(bad).is_err(), "{bad}");
+        }
     }
 
-    #[tokio::test]
-    async fn test_load_invalid_toml_file() {
-        let tmp = TempDir::new().unwrap();
-        let path = tmp.path().join("bad.toml");
-        tokio::fs::write(&path, b"not valid toml [[[")
-            .await
-            .unwrap();
-
-        let result = AppConfig::load(&path).await;
-        assert!(result.is_err());
+    #[test]
+    fn test_conversations_config() {
+        let config = AppConfig::default();
+        assert!(config.conversations.enabled);
+        assert_eq!(config.conversations.dir, "data/conversations");
+        assert_eq!(config.conversations.retention_days, 30);
+
+        let config =
+            AppConfig::parse("[conversations]\ndir = \"/srv/history\"\nretention_days = 0\n")
+                .unwrap();
+        assert_eq!(config.conversations.dir, "/srv/history");
+        assert_eq!(config.conversations.retention_days, 0);
+
+        assert!(AppConfig::parse("[conversations]\ndir = \" \"\n").is_err());
+        assert!(AppConfig::parse("[conversations]\nenabled = false\ndir = \"\"\n").is_ok());
     }
 
-    // ── Error display ─────────────────────────────────────────────────
-
     #[test]
-    fn test_config_error_display() {
-        let err = ConfigError::Validation("bad value".to_string());
-        assert_eq!(err.to_string(), "validation error: bad value");
+    fn test_memory_config() {
+        let memory = AppConfig::default().memory;
+        assert!(memory.enabled);
+        assert_eq!(memory.dir, "data/memory");
+        assert_eq!(memory.max_tokens, 1024);
+        assert_eq!(memory.max_facts, 64);
+        assert_eq!(memory.summary_tokens, 256);
+
+        let config =
+            AppConfig::parse("[memory]\ndir = \"/srv/memory\"\nmax_tokens = 512\nmax_facts = 8\n")
+                .unwrap();
+        assert_eq!(config.memory.dir, "/srv/memory");
+        assert_eq!(config.memory.max_tokens, 512);
+        assert_eq!(config.memory.max_facts, 8);
+
+        for bad in [
+            "[memory]\ndir = \" \"\n",
+            "[memory]\nmax_tokens = 0\n",
+            "[memory]\nmax_facts = 0\n",
+            "[memory]\nsummary_tokens = 0\n",
+        ] {
+            assert!(AppConfig::parse(bad).is_err(), "{bad}");
+        }
+        assert!(AppConfig::parse("[memory]\nenabled = false\nmax_tokens = 0\n").is_ok());
     }
 
-    // ── Isolation validation ──────────────────────────────────────────
-
     #[test]
-    fn test_validation_rejects_invalid_backend() {
-        let toml = r#"
-            [isolation]
-            backend = "hyperv"
-        "#;
-        let result = AppConfig::parse(toml);
-        assert!(result.is_err());
+    fn test_storage_config() {
+        let storage = AppConfig::default().storage;
+        assert_eq!(storage.backend, StorageBackend::Filesystem);
+        assert_eq!(storage.path, None);
+
+        let config =
+            AppConfig::parse("[storage]\nbackend = \"sqlite\"\npath = \"/srv/state.db\"\n")
+                .unwrap();
+        assert_eq!(config.storage.backend, StorageBackend::Sqlite);
+        assert_eq!(config.storage.path.as_deref(), Some("/srv/state.db"));
+
+        assert!(AppConfig::parse("[storage]\nbackend = \"postgres\"\n").is_err());
+        assert!(AppConfig::parse("[storage]\npath = \" \"\n").is_err());
     }
 
     #[test]
-    fn test_validation_accepts_docker_backend() {
-        let toml = r#"
-            [isolation]
-            backend = "docker"
-        "#;
-        let config = AppConfig::parse(toml).unwrap();
-        assert_eq!(config.isolation.backend, "docker");
+    fn test_attachments_config() {
+        let attachments = AppConfig::default().attachments;
+        assert_eq!(attachments.spool_dir, "data/attachments");
+        assert_eq!(attachments.max_attachment_bytes, 25 * 1024 * 1024);
+        assert_eq!(attachments.max_per_message, 10);
+        assert_eq!(attachments.retention_hours, 24);
+
+        let config = AppConfig::parse(
+            "[attachments]\nspool_dir = \"/srv/spool\"\nmax_per_message = 0\nretention_hours = 1\n",
+        )
+        .unwrap();
+        assert_eq!(config.attachments.spool_dir, "/srv/spool");
+        assert_eq!(config.attachments.max_per_message, 0);
+
+        assert!(AppConfig::parse("[attachments]\nspool_dir = \"\"\n").is_err());
+        assert!(AppConfig::parse("[attachments]\nmax_attachment_bytes = 0\n").is_err());
+        assert!(
+            AppConfig::parse(
+                "[attachments]\nmax_attachment_bytes = 2048\nmax_spool_bytes = 1024\n"
+            )
+            .is_err()
+        );
+        assert!(AppConfig::parse("[attachments]\nretention_hours = 0\n").is_err());
     }
 
     #[test]
-    fn test_validation_accepts_firecracker_backend() {
-        let toml = r#"
-            [isolation]
-            backend = "firecracker"
-        "#;
-        let config = AppConfig::parse(toml).unwrap();
-        assert_eq!(config.isolation.backend, "firecracker");
+    fn test_health_config() {
+        let health = AppConfig::default().health;
+        assert!(!health.probe_llm);
+        assert_eq!(health.probe_timeout_secs, 5);
+
+        let config =
+            AppConfig::parse("[health]\nprobe_llm = true\nprobe_timeout_secs = 2\n").unwrap();
+        assert!(config.health.probe_llm);
+        assert_eq!(config.health.probe_timeout_secs, 2);
+
+        assert!(AppConfig::parse("[health]\nprobe_timeout_secs = 0\n").is_err());
     }
 
     #[test]
-    fn test_trust_tier_config() {
-        let toml = r#"
-            [isolation]
-            default_trust_tier = "untrusted"
-            credential_proxy = true
-            docker_image = "ubuntu:22.04"
-        "#;
-        let config = AppConfig::parse(toml).unwrap();
+    fn test_daemon_tls_config() {
+        assert!(!AppConfig::default().daemon.tls.enabled);
+
+        let config = AppConfig::parse(
+            r#"
+            [daemon.tls]
+            enabled = true
+            cert_path = "/etc/crustyclaw/server.crt"
+            key_path = "/etc/crustyclaw/server.key"
+            client_ca_path = "/etc/crustyclaw/ca.crt"
+        "#,
+        )
+        .unwrap();
+        assert!(config.daemon.tls.enabled);
         assert_eq!(
-            config.isolation.default_trust_tier.as_ref().unwrap(),
-            "untrusted"
+            config.daemon.tls.client_ca_path.as_deref(),
+            Some("/etc/crustyclaw/ca.crt")
         );
-        assert!(config.isolation.credential_proxy);
-        assert_eq!(config.isolation.docker_image, "ubuntu:22.04");
+
+        // Paths are only required once the listener is enabled.
+        assert!(AppConfig::parse("[daemon.tls]\nenabled = false\n").is_ok());
+        for missing in ["cert_path", "key_path", "client_ca_path"] {
+            let toml: String = ["cert_path", "key_path", "client_ca_path"]
+                .iter()
+                .filter(|k| **k != missing)
+                .map(|k| format!("{k} = \"/x\"\n"))
+                .collect();
+            let toml = format!("[daemon.tls]\nenabled = true\n{toml}");
+            assert!(AppConfig::parse(&toml).is_err(), "{missing}");
+        }
     }
 
     #[test]
-    fn test_validation_rejects_invalid_trust_tier() {
-        let toml = r#"
-            [isolation]
-            default_trust_tier = "suspicious"
-        "#;
-        let result = AppConfig::parse(toml);
-        assert!(result.is_err());
+    fn test_tui_keys() {
+        assert!(AppConfig::default().tui.keys.is_empty());
+        let config = AppConfig::parse(
+            "[tui.keys]\nscroll_down = [\"n\", \"Down\"]\nscroll_to_top = [\"tt\"]\n",
+        )
+        .unwrap();
+        assert_eq!(config.tui.keys["scroll_down"], ["n", "Down"]);
+
+        for bad in [
+            "jump = [\"j\"]",
+            "quit = [\"\"]",
+            "quit = [\"Ctrl-c\"]",
+            "quit = [\"x\"]\nhelp = [\"x\"]",
+        ] {
+            assert!(
+                AppConfig::parse(&format!("[tui.keys]\n{bad}\n")).is_err(),
+                "{bad}"
+            );
+        }
     }
 
     #[test]
-    fn test_validation_rejects_zero_memory() {
-        let toml = r#"
-            [isolation]
-            default_memory_bytes = 0
-        "#;
-        let result = AppConfig::parse(toml);
-        assert!(result.is_err());
+    fn test_remote_config() {
+        let remote = AppConfig::default().remote;
+        assert!(remote.addr.is_none());
+        assert!(remote.server_name().is_none());
+
+        let files = "ca_path = \"ca.crt\"\ncert_path = \"c.crt\"\nkey_path = \"c.key\"\n";
+        let config = AppConfig::parse(&format!(
+            "[remote]\naddr = \"agent.example.com:9100\"\n{files}"
+        ))
+        .unwrap();
+        assert_eq!(config.remote.server_name(), Some("agent.example.com"));
+        let config = AppConfig::parse(&format!(
+            "[remote]\naddr = \"[::1]:9100\"\nserver_name = \"agent\"\n{files}"
+        ))
+        .unwrap();
+        assert_eq!(config.remote.server_name(), Some("agent"));
+        assert_eq!(host_of("[::1]:9100"), "::1");
+
+        for bad in ["agent.example.com", ":9100", "agent:http"] {
+            let toml = format!("[remote]\naddr = {bad:?}\n{files}");
+            assert!(AppConfig::parse(&toml).is_err(), "{bad}");
+        }
+        assert!(AppConfig::parse("[remote]\naddr = \"agent:9100\"\n").is_err());
     }
 
     #[test]
-    fn test_validation_rejects_bad_cpu_fraction() {
-        let toml = r#"
-            [isolation]
-            default_cpu_fraction = 0.0
-        "#;
-        let result = AppConfig::parse(toml);
-        assert!(result.is_err());
+    fn test_quotas_config() {
+        assert!(AppConfig::default().quotas.roles.is_empty());
+
+        let config = AppConfig::parse(
+            r#"
+            [quotas.roles.user]
+            llm_tokens_per_day = 200000
+            sandbox_executions_per_hour = 20
+
+            [quotas.roles.viewer]
+            sandbox_executions_per_hour = 0
+        "#,
+        )
+        .unwrap();
+        let user = &config.quotas.roles["user"];
+        assert_eq!(user.llm_tokens_per_day, Some(200_000));
+        assert_eq!(user.sandbox_executions_per_hour, Some(20));
+        let viewer = &config.quotas.roles["viewer"];
+        assert_eq!(viewer.llm_tokens_per_day, None);
+        assert_eq!(viewer.sandbox_executions_per_hour, Some(0));
+
+        assert!(AppConfig::parse("[quotas.roles.\" \"]\n").is_err());
+        assert!(AppConfig::parse("[quotas.roles.user]\nllm_tokens_per_day = -1\n").is_err());
+    }
+
+    #[test]
+    fn test_files_config() {
+        let config = AppConfig::default();
+        assert_eq!(config.files.workspace_root, "data/workspaces");
+        assert_eq!(config.files.max_file_bytes, 10 * 1024 * 1024);
+        assert!(
+            config
+                .files
+                .allowed_content_types
+                .contains(&"text/*".to_string())
+        );
+
+        let config = AppConfig::parse(
+            r#"
+            [files]
+            max_file_bytes = 1024
+            allowed_content_types = ["image/png"]
+        "#,
+        )
+        .unwrap();
+        assert_eq!(config.files.max_file_bytes, 1024);
+        assert_eq!(config.files.allowed_content_types, vec!["image/png"]);
+
+        assert!(AppConfig::parse("[files]\nmax_file_bytes = 0\n").is_err());
+        assert!(AppConfig::parse("[files]\nallowed_content_types = [\"png\"]\n").is_err());
     }
 }
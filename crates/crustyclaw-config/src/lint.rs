@@ -0,0 +1,388 @@
+//! Config linting — settings that are valid but probably a mistake.
+//!
+//! [`AppConfig::validate`] rejects configs the daemon cannot run with.
+//! [`AppConfig::lint`] reports the ones it can run with but likely should
+//! not, as [`LintWarning`]s that never stop the daemon:
+//!
+//! - `isolation.backend = "noop"`
+//! - a `daemon.listen_addr` that is not loopback
+//! - `policy.default_effect = "allow"`
+//! - an `llm.api_key` in the config file
+//! - secrets with `source = "inline"` values
+//! - a `policy.bundle` loaded without a signature
+//! - policy rules with equal priority and opposite effects that may match
+//!   the same request, so only their order in the file decides
+//!
+//! [`AppConfig::lint_host`] adds the lints that depend on the machine the
+//! config runs on, described by a [`LintHost`]:
+//!
+//! - a `secrets.staging_dir` that is not on a tmpfs (checked on Linux)
+//!
+//! `crustyclaw config lint` prints both, and the daemon records them as
+//! startup warnings.
+
+use std::fmt;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use crate::AppConfig;
+use crate::pattern::Pattern;
+
+/// Mount table read to find the filesystem of the staging directory.
+const MOUNTS_FILE: &str = "/proc/self/mounts";
+
+/// A non-fatal config diagnostic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintWarning {
+    /// Dotted config key the warning refers to.
+    pub key: String,
+    /// What is wrong and how to resolve it.
+    pub message: String,
+}
+
+impl LintWarning {
+    fn new(key: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.key, self.message)
+    }
+}
+
+/// What the host lints need to know about the machine.
+///
+/// [`LintHost::default`] knows nothing, so no host lint fires.
+#[derive(Debug, Clone, Default)]
+pub struct LintHost {
+    /// Contents of the mount table, if there is one.
+    mounts: Option<String>,
+}
+
+impl LintHost {
+    /// The machine this process runs on.
+    pub fn current() -> Self {
+        Self {
+            mounts: std::fs::read_to_string(MOUNTS_FILE).ok(),
+        }
+    }
+
+    /// Builder: use `mounts`, in `/proc/mounts` format, as the mount table.
+    pub fn with_mounts(mut self, mounts: impl Into<String>) -> Self {
+        self.mounts = Some(mounts.into());
+        self
+    }
+}
+
+impl AppConfig {
+    /// Report settings that are valid but probably a mistake.
+    pub fn lint(&self) -> Vec<LintWarning> {
+        let mut warnings = Vec::new();
+
+        if self.isolation.backend == "noop" {
+            warnings.push(LintWarning::new(
+                "isolation.backend",
+                "\"noop\" runs skills on the host without isolation; use it for development \
+                 only, or choose a sandboxing backend or \"auto\"",
+            ));
+        }
+
+        let addr = self.daemon.listen_addr.as_str();
+        if addr != "localhost" && !addr.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback()) {
+            warnings.push(LintWarning::new(
+                "daemon.listen_addr",
+                format!("listening on non-loopback address {addr}"),
+            ));
+        }
+
+        if self.policy.default_effect == "allow" {
+            warnings.push(LintWarning::new(
+                "policy.default_effect",
+                "requests matching no rule are allowed; prefer \"deny\" with explicit allow rules",
+            ));
+        }
+
+        if let Some(bundle) = &self.policy_bundle
+            && bundle.signer.is_none()
+        {
+            warnings.push(LintWarning::new(
+                "policy.bundle",
+                format!(
+                    "{} is unsigned; sign it with minisign and set security.strict",
+                    bundle.path.display()
+                ),
+            ));
+        }
+
+        if !self.llm.api_key.is_empty() {
+            warnings.push(LintWarning::new(
+                "llm.api_key",
+                "API key stored in plaintext config; use llm.api_key_secret or CRUSTYCLAW_LLM_API_KEY",
+            ));
+        }
+
+        for (i, entry) in self.secrets.entries.iter().enumerate() {
+            if entry.source == "inline" {
+                warnings.push(LintWarning::new(
+                    format!("secrets.entries[{i}].value"),
+                    format!(
+                        "secret '{}' is stored inline in the config; use an env, file, or keystore source",
+                        entry.name
+                    ),
+                ));
+            }
+        }
+
+        warnings.extend(self.lint_policy_overlaps());
+        warnings
+    }
+
+    /// Report settings that are probably a mistake on `host`.
+    pub fn lint_host(&self, host: &LintHost) -> Vec<LintWarning> {
+        let mut warnings = Vec::new();
+        if let Some(mounts) = &host.mounts {
+            let dir = std::path::absolute(&self.secrets.staging_dir)
+                .unwrap_or_else(|_| PathBuf::from(&self.secrets.staging_dir));
+            match mount_type(mounts, &dir) {
+                Some("tmpfs" | "ramfs") => {}
+                Some(fs) => warnings.push(LintWarning::new(
+                    "secrets.staging_dir",
+                    format!(
+                        "{} is on {fs}, not tmpfs; staged secret files may reach disk",
+                        dir.display()
+                    ),
+                )),
+                None => {}
+            }
+        }
+        warnings
+    }
+
+    /// Rules with equal priority and opposite effects that may decide the
+    /// same request.
+    fn lint_policy_overlaps(&self) -> Vec<LintWarning> {
+        let parse = |source: &str| Pattern::parse(source).ok();
+        let rules: Vec<_> = self
+            .policy
+            .rules
+            .iter()
+            .map(|rule| {
+                (
+                    rule,
+                    parse(&rule.role),
+                    parse(&rule.action),
+                    parse(&rule.resource),
+                )
+            })
+            .collect();
+
+        let mut warnings = Vec::new();
+        for (j, (later, role, action, resource)) in rules.iter().enumerate() {
+            let (Some(role), Some(action), Some(resource)) = (role, action, resource) else {
+                continue;
+            };
+            let earlier = rules[..j].iter().position(|(rule, r, a, res)| {
+                rule.priority == later.priority
+                    && !rule.effect.eq_ignore_ascii_case(&later.effect)
+                    && r.as_ref().is_some_and(|r| r.may_overlap(role))
+                    && a.as_ref().is_some_and(|a| a.may_overlap(action))
+                    && res.as_ref().is_some_and(|res| res.may_overlap(resource))
+            });
+            if let Some(i) = earlier {
+                warnings.push(LintWarning::new(
+                    format!("policy.rules[{j}]"),
+                    format!(
+                        "may match the same requests as policy.rules[{i}] with equal priority {} \
+                         and the opposite effect, so the earlier rule wins; give them distinct priorities",
+                        later.priority
+                    ),
+                ));
+            }
+        }
+        warnings
+    }
+}
+
+/// The filesystem type of the mount holding `path`, from a mount table in
+/// `/proc/mounts` format.
+fn mount_type<'a>(mounts: &'a str, path: &Path) -> Option<&'a str> {
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let point = unescape_mount(fields.nth(1)?);
+            let fs = fields.next()?;
+            path.starts_with(&point)
+                .then(|| (point.components().count(), fs))
+        })
+        .max_by_key(|(depth, _)| *depth)
+        .map(|(_, fs)| fs)
+}
+
+/// Undo the octal escapes (`\040` for a space) of a mount table field.
+fn unescape_mount(field: &str) -> PathBuf {
+    let mut out = String::with_capacity(field.len());
+    let mut rest = field;
+    while let Some(at) = rest.find('\\') {
+        out.push_str(&rest[..at]);
+        let code = rest.get(at + 1..at + 4);
+        match code.and_then(|code| u8::from_str_radix(code, 8).ok()) {
+            Some(byte) => {
+                out.push(char::from(byte));
+                rest = &rest[at + 4..];
+            }
+            None => {
+                out.push('\\');
+                rest = &rest[at + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    PathBuf::from(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOUNTS: &str = "\
+/dev/sda1 / ext4 rw,relatime 0 0
+tmpfs /run tmpfs rw,nosuid,nodev 0 0
+/dev/sdb1 /run/my\\040disk xfs rw 0 0
+";
+
+    fn keys(warnings: Vec<LintWarning>) -> Vec<String> {
+        warnings.into_iter().map(|w| w.key).collect()
+    }
+
+    #[test]
+    fn test_default_config_is_clean() {
+        let config = AppConfig::default();
+        assert!(config.lint().is_empty());
+        assert!(config.lint_host(&LintHost::default()).is_empty());
+    }
+
+    #[test]
+    fn test_lints() {
+        let config = AppConfig::parse(
+            r#"
+[daemon]
+listen_addr = "0.0.0.0"
+
+[isolation]
+backend = "noop"
+
+[policy]
+default_effect = "allow"
+
+[llm]
+api_key = "sk-inline"
+
+[secrets]
+staging_dir = "/var/lib/crustyclaw/staging"
+
+[[secrets.entries]]
+name = "token"
+source = "inline"
+value = "abc"
+inject_env = "TOKEN"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            keys(config.lint()),
+            [
+                "isolation.backend",
+                "daemon.listen_addr",
+                "policy.default_effect",
+                "llm.api_key",
+                "secrets.entries[0].value",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_staging_dir_mount() {
+        let mut config = AppConfig::default();
+        config.secrets.staging_dir = "/var/lib/crustyclaw/staging".to_string();
+        let host = LintHost::default().with_mounts(MOUNTS);
+        assert_eq!(keys(config.lint_host(&host)), ["secrets.staging_dir"]);
+
+        config.secrets.staging_dir = "/run/crustyclaw/secrets".to_string();
+        assert!(config.lint_host(&host).is_empty());
+        // Without a mount table the staging directory is not checked.
+        config.secrets.staging_dir = "/var/lib/crustyclaw/staging".to_string();
+        assert!(config.lint_host(&LintHost::default()).is_empty());
+    }
+
+    #[test]
+    fn test_unsigned_bundle() {
+        let mut config = AppConfig {
+            policy_bundle: Some(crate::bundle::PolicyBundle {
+                path: PathBuf::from("/etc/crustyclaw/policy.toml"),
+                digest: crate::bundle::digest(b""),
+                signer: None,
+            }),
+            ..AppConfig::default()
+        };
+        assert_eq!(keys(config.lint()), ["policy.bundle"]);
+
+        config.policy_bundle.as_mut().unwrap().signer = Some("release".to_string());
+        assert!(config.lint().is_empty());
+    }
+
+    #[test]
+    fn test_overlapping_rules() {
+        let config = AppConfig::parse(
+            r#"
+[[policy.rules]]
+role = "ops|admin"
+action = "*"
+resource = "skills/*"
+effect = "allow"
+
+[[policy.rules]]
+role = "ops"
+action = "write"
+resource = "skills/deploy"
+effect = "deny"
+
+[[policy.rules]]
+role = "ops"
+action = "write"
+resource = "skills/deploy"
+effect = "deny"
+priority = 10
+
+[[policy.rules]]
+role = "ops"
+action = "read"
+resource = "config"
+effect = "deny"
+"#,
+        )
+        .unwrap();
+
+        let warnings = config.lint();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].key, "policy.rules[1]");
+        assert!(warnings[0].message.contains("policy.rules[0]"));
+    }
+
+    #[test]
+    fn test_mount_type() {
+        assert_eq!(
+            mount_type(MOUNTS, Path::new("/run/crustyclaw")),
+            Some("tmpfs")
+        );
+        assert_eq!(mount_type(MOUNTS, Path::new("/running")), Some("ext4"));
+        assert_eq!(mount_type(MOUNTS, Path::new("/run/my disk/x")), Some("xfs"));
+        assert_eq!(mount_type("", Path::new("/run")), None);
+    }
+}
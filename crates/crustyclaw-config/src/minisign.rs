@@ -0,0 +1,237 @@
+//! Minisign signature verification.
+//!
+//! [Minisign](https://jedisct1.github.io/minisign/) signs files with Ed25519.
+//! A public key is the base64 line printed by `minisign -G` (also the
+//! second line of `minisign.pub`); a signature is the `<file>.minisig` file
+//! written by `minisign -S`. Both the default prehashed signatures (`ED`,
+//! over the BLAKE2b-512 hash of the file) and legacy ones (`Ed`, over the
+//! file itself, from `minisign -S -l`) are accepted.
+//!
+//! Verifying checks the file signature and the global signature, which
+//! covers the trusted comment, against the key whose ID the signature names.
+
+use std::fmt;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use blake2::{Blake2b512, Digest};
+use ring::signature::{ED25519, UnparsedPublicKey};
+
+/// Algorithm tag of public keys and legacy signatures.
+const ALG_ED25519: &[u8; 2] = b"Ed";
+/// Algorithm tag of signatures over the BLAKE2b-512 hash of the file.
+const ALG_PREHASHED: &[u8; 2] = b"ED";
+
+const UNTRUSTED_PREFIX: &str = "untrusted comment: ";
+const TRUSTED_PREFIX: &str = "trusted comment: ";
+
+/// Why a key or signature was rejected.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum MinisignError {
+    #[error("malformed public key: {0}")]
+    PublicKey(&'static str),
+
+    #[error("malformed signature: {0}")]
+    Signature(&'static str),
+
+    #[error("signed by key {0}, which is not trusted")]
+    UnknownKey(KeyId),
+
+    #[error("signature by key {0} does not match")]
+    Invalid(KeyId),
+}
+
+/// The 8-byte ID tying a signature to the key that made it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyId([u8; 8]);
+
+impl fmt::Display for KeyId {
+    /// Upper-case hex, as `minisign` prints it.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for b in self.0.iter().rev() {
+            write!(f, "{b:02X}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A minisign Ed25519 public key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicKey {
+    id: KeyId,
+    key: [u8; 32],
+}
+
+impl PublicKey {
+    /// Parse a key from its base64 line, or from the contents of a
+    /// `minisign.pub` file.
+    pub fn parse(s: &str) -> Result<Self, MinisignError> {
+        let line = s
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with(UNTRUSTED_PREFIX))
+            .ok_or(MinisignError::PublicKey("empty"))?;
+        let bytes = BASE64
+            .decode(line)
+            .map_err(|_| MinisignError::PublicKey("not base64"))?;
+        let bytes: [u8; 42] = bytes
+            .try_into()
+            .map_err(|_| MinisignError::PublicKey("wrong length"))?;
+        if &bytes[..2] != ALG_ED25519 {
+            return Err(MinisignError::PublicKey("not an Ed25519 key"));
+        }
+        Ok(Self {
+            id: KeyId(bytes[2..10].try_into().expect("8 bytes")),
+            key: bytes[10..].try_into().expect("32 bytes"),
+        })
+    }
+
+    /// The key's ID.
+    pub fn id(&self) -> KeyId {
+        self.id
+    }
+
+    fn verify(&self, message: &[u8], signature: &[u8; 64]) -> bool {
+        UnparsedPublicKey::new(&ED25519, self.key)
+            .verify(message, signature)
+            .is_ok()
+    }
+}
+
+/// A parsed `.minisig` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    prehashed: bool,
+    key_id: KeyId,
+    signature: [u8; 64],
+    trusted_comment: String,
+    global_signature: [u8; 64],
+}
+
+impl Signature {
+    /// Parse the contents of a `.minisig` file.
+    pub fn parse(s: &str) -> Result<Self, MinisignError> {
+        let mut lines = s.lines();
+        let mut next = |what| lines.next().ok_or(MinisignError::Signature(what));
+        if !next("missing untrusted comment")?.starts_with(UNTRUSTED_PREFIX) {
+            return Err(MinisignError::Signature("missing untrusted comment"));
+        }
+        let bytes = BASE64
+            .decode(next("missing signature")?.trim())
+            .map_err(|_| MinisignError::Signature("signature is not base64"))?;
+        let bytes: [u8; 74] = bytes
+            .try_into()
+            .map_err(|_| MinisignError::Signature("signature has the wrong length"))?;
+        let prehashed = match &bytes[..2] {
+            alg if alg == ALG_PREHASHED => true,
+            alg if alg == ALG_ED25519 => false,
+            _ => return Err(MinisignError::Signature("unknown algorithm")),
+        };
+        let trusted_comment = next("missing trusted comment")?
+            .strip_prefix(TRUSTED_PREFIX)
+            .ok_or(MinisignError::Signature("missing trusted comment"))?
+            .to_string();
+        let global_signature = BASE64
+            .decode(next("missing global signature")?.trim())
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or(MinisignError::Signature("malformed global signature"))?;
+        Ok(Self {
+            prehashed,
+            key_id: KeyId(bytes[2..10].try_into().expect("8 bytes")),
+            signature: bytes[10..].try_into().expect("64 bytes"),
+            trusted_comment,
+            global_signature,
+        })
+    }
+
+    /// ID of the key that made the signature.
+    pub fn key_id(&self) -> KeyId {
+        self.key_id
+    }
+
+    /// The signed comment, usually a timestamp and the file name.
+    pub fn trusted_comment(&self) -> &str {
+        &self.trusted_comment
+    }
+
+    /// Check that `message` was signed by one of `keys`, returning the
+    /// index of the key.
+    pub fn verify(&self, message: &[u8], keys: &[PublicKey]) -> Result<usize, MinisignError> {
+        let index = keys
+            .iter()
+            .position(|key| key.id == self.key_id)
+            .ok_or(MinisignError::UnknownKey(self.key_id))?;
+        let key = &keys[index];
+
+        let signed = if self.prehashed {
+            key.verify(&Blake2b512::digest(message), &self.signature)
+        } else {
+            key.verify(message, &self.signature)
+        };
+        let mut global = self.signature.to_vec();
+        global.extend_from_slice(self.trusted_comment.as_bytes());
+        if signed && key.verify(&global, &self.global_signature) {
+            Ok(index)
+        } else {
+            Err(MinisignError::Invalid(self.key_id))
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// `minisign.pub` of the test key.
+    pub(crate) const PUBLIC_KEY: &str = "untrusted comment: minisign public key 88796A5B4C3D2E1F\nRWQfLj1MW2p5iAOhB7/zzhC+HXDdGOdLwJln5NYwm6UNXx3chmQSVTG4\n";
+    /// The message signed by [`PREHASHED_SIG`] and [`LEGACY_SIG`].
+    pub(crate) const MESSAGE: &str = "default_effect = \"deny\"\n\n[[rules]]\nrole = \"admin\"\naction = \"*\"\nresource = \"*\"\neffect = \"allow\"\n";
+    /// `minisign -S` signature of [`MESSAGE`].
+    pub(crate) const PREHASHED_SIG: &str = "untrusted comment: signature from minisign secret key\nRUQfLj1MW2p5iMFsME8r1MH7ytCfk1eQ2iOO6pSOhIf57kYxK9ORUAEQBFOoxgdT/Qkkk9TGgIiXH0PQYcJW2Xb3qlMdR/u3QA0=\ntrusted comment: timestamp:1760000000\tfile:policy.toml\thashed\nJPiYJZaWXmcxp/JpMJYpFkG3GVe7MYjhFY0R2UTvwajGoprO324cpdk4oxi8C07fgjIbziaEeBYTynbNW8hWAg==\n";
+    /// `minisign -S -l` signature of [`MESSAGE`].
+    const LEGACY_SIG: &str = "untrusted comment: signature from minisign secret key\nRWQfLj1MW2p5iPgzr3BZ7GoeZMn+CQOJAQ63cotioVKATQI8NMfAiOSgBHH3JXvCE+7jjwuJ6RepvPJ5r0Qma/6FtxXtx9rJyAc=\ntrusted comment: timestamp:1760000000\tfile:policy.toml\nm245ZMFEzSjCjwbTS06Z/5fGiGB9wgfPf9buK7P0Ui8hQkkLsjM5oQC1t27JPP7hSHD9juFtZN7rR5olDEKPAg==\n";
+    /// A key other than [`PUBLIC_KEY`].
+    pub(crate) const OTHER_KEY: &str = "RWQBAgMEBQYHCHm1Vi6P5lT5QHixEuipi6eQH4U65pW+1+DjkQutBJZk";
+
+    #[test]
+    fn test_verify() {
+        let key = PublicKey::parse(PUBLIC_KEY).unwrap();
+        let other = PublicKey::parse(OTHER_KEY).unwrap();
+        assert_eq!(key.id().to_string(), "88796A5B4C3D2E1F");
+
+        for sig in [PREHASHED_SIG, LEGACY_SIG] {
+            let sig = Signature::parse(sig).unwrap();
+            assert_eq!(sig.key_id(), key.id());
+            assert!(sig.trusted_comment().contains("policy.toml"));
+            assert_eq!(
+                sig.verify(MESSAGE.as_bytes(), &[other.clone(), key.clone()]),
+                Ok(1)
+            );
+            assert_eq!(
+                sig.verify(b"tampered", std::slice::from_ref(&key)),
+                Err(MinisignError::Invalid(key.id()))
+            );
+            assert_eq!(
+                sig.verify(MESSAGE.as_bytes(), std::slice::from_ref(&other)),
+                Err(MinisignError::UnknownKey(key.id()))
+            );
+        }
+
+        // Editing the trusted comment breaks the global signature.
+        let forged = PREHASHED_SIG.replace("trusted comment: ", "trusted comment: x");
+        let sig = Signature::parse(&forged).unwrap();
+        assert!(sig.verify(MESSAGE.as_bytes(), &[key]).is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(PublicKey::parse("").is_err());
+        assert!(PublicKey::parse("TWFu").is_err());
+        assert!(Signature::parse("").is_err());
+        let no_comment: String = PREHASHED_SIG.lines().skip(1).collect::<Vec<_>>().join("\n");
+        assert!(Signature::parse(&no_comment).is_err());
+        let truncated: String = PREHASHED_SIG.lines().take(3).collect::<Vec<_>>().join("\n");
+        assert!(Signature::parse(&truncated).is_err());
+    }
+}
@@ -0,0 +1,353 @@
+//! Glob patterns for the `role`, `action`, and `resource` of policy rules.
+//!
+//! | Syntax | Matches |
+//! |--------|---------|
+//! | `*` | any run of characters, including none |
+//! | `?` | exactly one character |
+//! | `[abc]`, `[a-z]` | one character from the set or range |
+//! | `[!abc]` | one character not in the set |
+//! | `read\|list` | either alternative |
+//!
+//! Any other character matches itself, so a plain name like `admin` only
+//! matches `admin`, and `*` on its own still matches everything:
+//!
+//! ```toml
+//! [[policy.rules]]
+//! role = "ops|oncall"
+//! action = "read|list"
+//! resource = "skills/git-*"
+//! effect = "allow"
+//! ```
+//!
+//! Patterns are compiled once by [`Pattern::parse`]; the policy engine
+//! compiles each rule when it is built, not on every request.
+
+use std::fmt;
+
+/// A malformed pattern.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("invalid pattern at offset {offset}: {message}")]
+pub struct PatternError {
+    /// Byte offset of the problem in the pattern.
+    pub offset: usize,
+    /// What is wrong.
+    pub message: String,
+}
+
+/// A compiled pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    source: String,
+    alternatives: Vec<Alternative>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Alternative {
+    /// Matches everything (`*`).
+    Any,
+    /// No special characters: compared directly.
+    Exact(String),
+    Glob(Vec<Token>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Char(char),
+    AnyChar,
+    Star,
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+impl Token {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Self::Char(expected) => *expected == c,
+            Self::AnyChar => true,
+            Self::Star => false,
+            Self::Class { negated, ranges } => {
+                ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != *negated
+            }
+        }
+    }
+}
+
+impl Pattern {
+    /// Compile `source`.
+    pub fn parse(source: &str) -> Result<Self, PatternError> {
+        if source.is_empty() {
+            return Err(PatternError {
+                offset: 0,
+                message: "pattern is empty".to_string(),
+            });
+        }
+        let mut alternatives = Vec::new();
+        let mut start = 0;
+        for part in source.split('|') {
+            alternatives.push(parse_alternative(part, start)?);
+            start += part.len() + 1;
+        }
+        Ok(Self {
+            source: source.to_string(),
+            alternatives,
+        })
+    }
+
+    /// The pattern as written.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Whether `text` matches any alternative.
+    pub fn matches(&self, text: &str) -> bool {
+        self.alternatives.iter().any(|alt| match alt {
+            Alternative::Any => true,
+            Alternative::Exact(exact) => exact == text,
+            Alternative::Glob(tokens) => glob_matches(tokens, text),
+        })
+    }
+
+    /// Whether some text may match both this pattern and `other`.
+    ///
+    /// Conservative: wildcard alternatives are compared by their literal
+    /// starts and ends only, so two globs can be reported as overlapping
+    /// when no text matches both, but never the other way round.
+    pub fn may_overlap(&self, other: &Pattern) -> bool {
+        self.alternatives.iter().any(|a| {
+            other.alternatives.iter().any(|b| match (a, b) {
+                (Alternative::Any, _) | (_, Alternative::Any) => true,
+                (Alternative::Exact(a), Alternative::Exact(b)) => a == b,
+                (Alternative::Exact(text), Alternative::Glob(tokens))
+                | (Alternative::Glob(tokens), Alternative::Exact(text)) => {
+                    glob_matches(tokens, text)
+                }
+                (Alternative::Glob(a), Alternative::Glob(b)) => {
+                    let compatible = |x: &[char], y: &[char]| x.iter().zip(y).all(|(x, y)| x == y);
+                    compatible(&literal_start(a), &literal_start(b))
+                        && compatible(&literal_end(a), &literal_end(b))
+                }
+            })
+        })
+    }
+
+    /// The alternatives that are plain names, without wildcards.
+    pub fn literals(&self) -> impl Iterator<Item = &str> {
+        self.alternatives.iter().filter_map(|alt| match alt {
+            Alternative::Exact(exact) => Some(exact.as_str()),
+            _ => None,
+        })
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// Parse one `|`-separated alternative starting at byte `base` of the
+/// pattern.
+fn parse_alternative(part: &str, base: usize) -> Result<Alternative, PatternError> {
+    let error = |offset: usize, message: &str| PatternError {
+        offset: base + offset,
+        message: message.to_string(),
+    };
+    if part.is_empty() {
+        return Err(error(0, "empty alternative"));
+    }
+    if part == "*" {
+        return Ok(Alternative::Any);
+    }
+    if !part.contains(['*', '?', '[', ']']) {
+        return Ok(Alternative::Exact(part.to_string()));
+    }
+
+    let mut tokens = Vec::new();
+    let mut chars = part.char_indices().peekable();
+    while let Some((offset, c)) = chars.next() {
+        let token = match c {
+            // Runs of `*` are equivalent to one.
+            '*' if tokens.last() == Some(&Token::Star) => continue,
+            '*' => Token::Star,
+            '?' => Token::AnyChar,
+            ']' => return Err(error(offset, "unmatched ']'")),
+            '[' => {
+                let negated = chars.next_if(|&(_, c)| c == '!').is_some();
+                let mut ranges = Vec::new();
+                loop {
+                    let Some((at, lo)) = chars.next() else {
+                        return Err(error(offset, "unclosed '['"));
+                    };
+                    match lo {
+                        ']' if ranges.is_empty() => {
+                            return Err(error(at, "empty character class"));
+                        }
+                        ']' => break,
+                        '[' => return Err(error(at, "'[' inside a character class")),
+                        _ => {}
+                    }
+                    if chars.next_if(|&(_, c)| c == '-').is_none() {
+                        ranges.push((lo, lo));
+                        continue;
+                    }
+                    match chars.next() {
+                        Some((at, hi)) if hi != ']' => {
+                            if hi < lo {
+                                return Err(error(at, "character range is reversed"));
+                            }
+                            ranges.push((lo, hi));
+                        }
+                        _ => return Err(error(at, "unfinished character range")),
+                    }
+                }
+                Token::Class { negated, ranges }
+            }
+            c => Token::Char(c),
+        };
+        tokens.push(token);
+    }
+    Ok(Alternative::Glob(tokens))
+}
+
+/// The literal characters `tokens` start with, up to the first wildcard.
+fn literal_start(tokens: &[Token]) -> Vec<char> {
+    tokens
+        .iter()
+        .map_while(|token| match token {
+            Token::Char(c) => Some(*c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The literal characters `tokens` end with, after the last wildcard, last
+/// first.
+fn literal_end(tokens: &[Token]) -> Vec<char> {
+    tokens
+        .iter()
+        .rev()
+        .map_while(|token| match token {
+            Token::Char(c) => Some(*c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Match `text` against `tokens`, backtracking to the most recent `*` on a
+/// mismatch.
+fn glob_matches(tokens: &[Token], text: &str) -> bool {
+    let text: Vec<char> = text.chars().collect();
+    let (mut t, mut s) = (0, 0);
+    // Token index after the last `*`, and the text position it resumes at.
+    let mut star: Option<(usize, usize)> = None;
+    while s < text.len() {
+        match tokens.get(t) {
+            Some(Token::Star) => {
+                star = Some((t + 1, s));
+                t += 1;
+                continue;
+            }
+            Some(token) if token.matches(text[s]) => {
+                t += 1;
+                s += 1;
+                continue;
+            }
+            _ => {}
+        }
+        match star {
+            Some((after, from)) => {
+                t = after;
+                s = from + 1;
+                star = Some((after, from + 1));
+            }
+            None => return false,
+        }
+    }
+    tokens[t..].iter().all(|token| *token == Token::Star)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        Pattern::parse(pattern).unwrap().matches(text)
+    }
+
+    #[test]
+    fn test_exact_and_any() {
+        assert!(matches("admin", "admin"));
+        assert!(!matches("admin", "admins"));
+        assert!(matches("*", "anything"));
+        assert!(matches("*", ""));
+    }
+
+    #[test]
+    fn test_globs() {
+        assert!(matches("skills/git-*", "skills/git-commit"));
+        assert!(matches("skills/git-*", "skills/git-"));
+        assert!(!matches("skills/git-*", "skills/gh-pr"));
+        assert!(matches("*-prod", "db-prod"));
+        assert!(matches("a*b*c", "aXXbYYc"));
+        assert!(!matches("a*b*c", "aXXbYY"));
+        assert!(matches("node-?", "node-1"));
+        assert!(!matches("node-?", "node-10"));
+        assert!(matches("node-[0-9]", "node-7"));
+        assert!(!matches("node-[0-9]", "node-x"));
+        assert!(matches("node-[!0-9]", "node-x"));
+        assert!(matches("[abc]*", "beta"));
+        assert!(matches("**", "x/y"));
+    }
+
+    #[test]
+    fn test_may_overlap() {
+        let overlap = |a: &str, b: &str| {
+            let (a, b) = (Pattern::parse(a).unwrap(), Pattern::parse(b).unwrap());
+            assert_eq!(a.may_overlap(&b), b.may_overlap(&a));
+            a.may_overlap(&b)
+        };
+        assert!(overlap("*", "admin"));
+        assert!(overlap("admin|ops", "ops"));
+        assert!(!overlap("admin", "ops"));
+        assert!(overlap("skills/git-*", "skills/git-commit"));
+        assert!(!overlap("skills/git-*", "config"));
+        assert!(overlap("skills/*", "skills/git-*"));
+        assert!(!overlap("skills/*", "config/*"));
+        assert!(overlap("*-prod", "db-*"));
+        assert!(!overlap("*-prod", "*-staging"));
+    }
+
+    #[test]
+    fn test_alternatives() {
+        let pattern = Pattern::parse("read|list|skills/*").unwrap();
+        assert!(pattern.matches("read"));
+        assert!(pattern.matches("list"));
+        assert!(pattern.matches("skills/git"));
+        assert!(!pattern.matches("write"));
+        assert_eq!(pattern.literals().collect::<Vec<_>>(), ["read", "list"]);
+        assert_eq!(pattern.to_string(), "read|list|skills/*");
+    }
+
+    #[test]
+    fn test_malformed() {
+        for (pattern, offset, message) in [
+            ("", 0, "pattern is empty"),
+            ("read|", 5, "empty alternative"),
+            ("|read", 0, "empty alternative"),
+            ("node-[0-9", 5, "unclosed '['"),
+            ("node-[]", 6, "empty character class"),
+            ("node-[9-0]", 8, "character range is reversed"),
+            ("node-[0-]", 6, "unfinished character range"),
+            ("read|a]", 6, "unmatched ']'"),
+        ] {
+            let err = Pattern::parse(pattern).unwrap_err();
+            assert_eq!(
+                (err.offset, err.message.as_str()),
+                (offset, message),
+                "{pattern}"
+            );
+        }
+    }
+}
@@ -4,11 +4,27 @@
 //! to an [`Effect`] (allow or deny). The [`PolicyEngine`] evaluates these rules
 //! in priority order.
 //!
+//! The `role`, `action`, and `resource` of a rule are [patterns](crate::pattern)
+//! (`skills/git-*`, `read|list`), compiled once when the engine is built.
+//! A rule with a malformed pattern never matches.
+//!
+//! A rule may also carry a [`Condition`] (the `when` clause in config) that
+//! is evaluated against a [`RequestContext`]; the rule only matches when the
+//! condition holds. A condition left unresolved by a missing attribute fails
+//! closed: a deny rule matches and an allow rule does not.
+//!
+//! Rules may be named with an `id` and explain themselves with a `reason`.
+//! Evaluation returns a [`PolicyVerdict`] carrying both from the rule that
+//! decided, so a denial can say why without the operator diffing config.
+//!
 //! Policies can be defined programmatically or via the `security_policy!` macro
 //! in `crustyclaw-macros`.
 
 use std::collections::HashMap;
 
+pub use crate::condition::{Condition, ConditionError, RequestContext, Value};
+pub use crate::pattern::{Pattern, PatternError};
+
 /// The effect of a policy rule.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Effect {
@@ -21,17 +37,23 @@ pub enum Effect {
 /// A single policy rule.
 #[derive(Debug, Clone)]
 pub struct PolicyRule {
-    /// Role this rule applies to (e.g. "admin", "user", "*").
+    /// Role pattern this rule applies to (e.g. "admin", "ops|oncall", "*").
     pub role: String,
-    /// Action being controlled (e.g. "read", "write", "execute", "*").
+    /// Action pattern being controlled (e.g. "read", "read|list", "*").
     pub action: String,
-    /// Resource being accessed (e.g. "config", "skills", "messages", "*").
+    /// Resource pattern being accessed (e.g. "config", "skills/git-*", "*").
     pub resource: String,
     /// Whether to allow or deny.
     pub effect: Effect,
     /// Priority (higher = evaluated first). Rules with equal priority
     /// are evaluated in insertion order.
     pub priority: u32,
+    /// Optional condition that must hold for the rule to match.
+    pub condition: Option<Condition>,
+    /// Optional identifier reported when this rule decides.
+    pub id: Option<String>,
+    /// Optional explanation reported when this rule decides.
+    pub reason: Option<String>,
 }
 
 impl PolicyRule {
@@ -43,6 +65,9 @@ impl PolicyRule {
             resource: resource.to_string(),
             effect: Effect::Allow,
             priority: 0,
+            condition: None,
+            id: None,
+            reason: None,
         }
     }
 
@@ -54,6 +79,9 @@ impl PolicyRule {
             resource: resource.to_string(),
             effect: Effect::Deny,
             priority: 0,
+            condition: None,
+            id: None,
+            reason: None,
         }
     }
 
@@ -63,11 +91,60 @@ impl PolicyRule {
         self
     }
 
+    /// Attach a condition that must hold for the rule to match.
+    pub fn with_condition(mut self, condition: Condition) -> Self {
+        self.condition = Some(condition);
+        self
+    }
+
+    /// Name the rule, for verdicts it decides.
+    pub fn with_id(mut self, id: &str) -> Self {
+        self.id = Some(id.to_string());
+        self
+    }
+
+    /// Explain the rule, for verdicts it decides.
+    pub fn with_reason(mut self, reason: &str) -> Self {
+        self.reason = Some(reason.to_string());
+        self
+    }
+
+    /// Compile the role, action, and resource patterns.
+    fn compile(&self) -> Result<CompiledRule, PatternError> {
+        Ok(CompiledRule {
+            role: Pattern::parse(&self.role)?,
+            action: Pattern::parse(&self.action)?,
+            resource: Pattern::parse(&self.resource)?,
+            effect: self.effect,
+            condition: self.condition.clone(),
+            id: self.id.clone(),
+            reason: self.reason.clone(),
+        })
+    }
+}
+
+/// A [`PolicyRule`] with its patterns compiled.
+#[derive(Debug, Clone)]
+struct CompiledRule {
+    role: Pattern,
+    action: Pattern,
+    resource: Pattern,
+    effect: Effect,
+    condition: Option<Condition>,
+    id: Option<String>,
+    reason: Option<String>,
+}
+
+impl CompiledRule {
     /// Check whether this rule matches the given request.
-    fn matches(&self, role: &str, action: &str, resource: &str) -> bool {
-        (self.role == "*" || self.role == role)
-            && (self.action == "*" || self.action == action)
-            && (self.resource == "*" || self.resource == resource)
+    fn matches(&self, role: &str, action: &str, resource: &str, ctx: &RequestContext) -> bool {
+        self.role.matches(role)
+            && self.action.matches(action)
+            && self.resource.matches(resource)
+            && self
+                .condition
+                .as_ref()
+                .is_none_or(|c| c.check(ctx).unwrap_or(self.effect == Effect::Deny))
     }
 }
 
@@ -82,6 +159,62 @@ pub enum PolicyDecision {
     NoMatch,
 }
 
+impl PolicyDecision {
+    /// The snake_case name used in IPC and CLI output.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PolicyDecision::Allowed => "allowed",
+            PolicyDecision::Denied => "denied",
+            PolicyDecision::NoMatch => "no_match",
+        }
+    }
+}
+
+/// A [`PolicyDecision`] and the rule that made it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyVerdict {
+    /// The outcome.
+    pub decision: PolicyDecision,
+    /// ID of the deciding rule, if it has one.
+    pub rule_id: Option<String>,
+    /// Reason given by the deciding rule, if any.
+    pub reason: Option<String>,
+}
+
+impl PolicyVerdict {
+    /// A verdict no rule made.
+    fn no_match() -> Self {
+        Self {
+            decision: PolicyDecision::NoMatch,
+            rule_id: None,
+            reason: None,
+        }
+    }
+
+    /// Whether a rule allowed the request.
+    pub fn is_allowed(&self) -> bool {
+        self.decision == PolicyDecision::Allowed
+    }
+}
+
+impl std::fmt::Display for PolicyVerdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let outcome = match self.decision {
+            PolicyDecision::Allowed => "allowed",
+            PolicyDecision::Denied => "denied",
+            PolicyDecision::NoMatch => "no rule matches",
+        };
+        f.write_str(outcome)?;
+        if let Some(id) = &self.rule_id {
+            write!(f, " by rule {id:?}")?;
+        }
+        if let Some(reason) = &self.reason {
+            write!(f, ": {reason}")?;
+        }
+        Ok(())
+    }
+}
+
 /// A compiled policy engine that evaluates access requests.
 ///
 /// Rules are sorted by priority (descending) at evaluation time.
@@ -90,7 +223,7 @@ pub enum PolicyDecision {
 pub struct PolicyEngine {
     rules: Vec<PolicyRule>,
     /// Cache of compiled (sorted) rules. Rebuilt when dirty.
-    sorted: Vec<PolicyRule>,
+    sorted: Vec<CompiledRule>,
     dirty: bool,
 }
 
@@ -112,29 +245,61 @@ impl PolicyEngine {
 
     /// Evaluate an access request against the policy rules.
     ///
-    /// Returns the decision (Allowed, Denied, or NoMatch).
-    pub fn evaluate(&mut self, role: &str, action: &str, resource: &str) -> PolicyDecision {
+    /// Returns the decision (Allowed, Denied, or NoMatch) with the ID and
+    /// reason of the rule that made it. Conditions are evaluated against
+    /// [`RequestContext::for_request`]; use
+    /// [`evaluate_with_context`](Self::evaluate_with_context) to supply
+    /// caller attributes.
+    pub fn evaluate(&mut self, role: &str, action: &str, resource: &str) -> PolicyVerdict {
+        let ctx = RequestContext::for_request(action, resource);
+        self.evaluate_with_context(role, action, resource, &ctx)
+    }
+
+    /// Evaluate an access request, checking rule conditions against `ctx`.
+    pub fn evaluate_with_context(
+        &mut self,
+        role: &str,
+        action: &str,
+        resource: &str,
+        ctx: &RequestContext,
+    ) -> PolicyVerdict {
         if self.dirty {
             self.rebuild();
         }
 
         for rule in &self.sorted {
-            if rule.matches(role, action, resource) {
-                return match rule.effect {
-                    Effect::Allow => PolicyDecision::Allowed,
-                    Effect::Deny => PolicyDecision::Denied,
+            if rule.matches(role, action, resource, ctx) {
+                return PolicyVerdict {
+                    decision: match rule.effect {
+                        Effect::Allow => PolicyDecision::Allowed,
+                        Effect::Deny => PolicyDecision::Denied,
+                    },
+                    rule_id: rule.id.clone(),
+                    reason: rule.reason.clone(),
                 };
             }
         }
 
-        PolicyDecision::NoMatch
+        PolicyVerdict::no_match()
     }
 
     /// Check whether the given request is allowed (convenience method).
     ///
     /// Returns `true` only if a rule explicitly allows it.
     pub fn is_allowed(&mut self, role: &str, action: &str, resource: &str) -> bool {
-        self.evaluate(role, action, resource) == PolicyDecision::Allowed
+        self.evaluate(role, action, resource).is_allowed()
+    }
+
+    /// Like [`is_allowed`](Self::is_allowed), checking rule conditions against `ctx`.
+    pub fn is_allowed_with_context(
+        &mut self,
+        role: &str,
+        action: &str,
+        resource: &str,
+        ctx: &RequestContext,
+    ) -> bool {
+        self.evaluate_with_context(role, action, resource, ctx)
+            .is_allowed()
     }
 
     /// Return the number of rules in the engine.
@@ -142,21 +307,42 @@ impl PolicyEngine {
         self.rules.len()
     }
 
-    /// Return a summary of all roles referenced by rules.
+    /// Return a summary of all roles named by rules.
+    ///
+    /// Only plain role names count; `*` and other wildcard alternatives in a
+    /// role pattern are skipped.
     pub fn roles(&self) -> Vec<&str> {
         let mut seen = HashMap::new();
         for rule in &self.rules {
-            if rule.role != "*" {
-                seen.entry(rule.role.as_str()).or_insert(());
+            for role in rule.role.split('|') {
+                if !role.is_empty() && !role.contains(['*', '?', '[', ']']) {
+                    seen.entry(role).or_insert(());
+                }
             }
         }
         seen.into_keys().collect()
     }
 
     fn rebuild(&mut self) {
-        self.sorted = self.rules.clone();
+        let mut rules: Vec<&PolicyRule> = self.rules.iter().collect();
         // Sort by priority descending (higher priority first)
-        self.sorted.sort_by(|a, b| b.priority.cmp(&a.priority));
+        rules.sort_by_key(|r| std::cmp::Reverse(r.priority));
+        self.sorted = rules
+            .into_iter()
+            .filter_map(|rule| match rule.compile() {
+                Ok(compiled) => Some(compiled),
+                Err(e) => {
+                    tracing::warn!(
+                        role = %rule.role,
+                        action = %rule.action,
+                        resource = %rule.resource,
+                        error = %e,
+                        "Skipping policy rule with invalid pattern"
+                    );
+                    None
+                }
+            })
+            .collect();
         self.dirty = false;
     }
 }
@@ -170,11 +356,15 @@ impl Default for PolicyEngine {
 /// Create a pre-configured policy engine from a list of rules.
 ///
 /// This is the runtime companion to the `security_policy!` macro.
+///
+/// The rules' patterns are compiled here, once; rules added later are
+/// compiled on the next evaluation.
 pub fn build_policy(rules: Vec<PolicyRule>) -> PolicyEngine {
     let mut engine = PolicyEngine::new();
     for rule in rules {
         engine.add_rule(rule);
     }
+    engine.rebuild();
     engine
 }
 
@@ -207,7 +397,7 @@ mod tests {
         engine.add_rule(PolicyRule::allow("admin", "*", "*"));
 
         assert_eq!(
-            engine.evaluate("unknown", "read", "anything"),
+            engine.evaluate("unknown", "read", "anything").decision,
             PolicyDecision::NoMatch
         );
         assert!(!engine.is_allowed("unknown", "read", "anything"));
@@ -257,6 +447,40 @@ mod tests {
         assert_eq!(roles, vec!["admin", "user"]);
     }
 
+    #[test]
+    fn test_pattern_rules() {
+        let mut engine = build_policy(vec![
+            PolicyRule::allow("ops|oncall", "read|list", "skills/git-*"),
+            PolicyRule::deny("*", "*", "skills/git-push").with_priority(10),
+        ]);
+
+        assert!(engine.is_allowed("ops", "read", "skills/git-status"));
+        assert!(engine.is_allowed("oncall", "list", "skills/git-log"));
+        assert!(!engine.is_allowed("ops", "write", "skills/git-status"));
+        assert!(!engine.is_allowed("ops", "read", "skills/deploy"));
+        assert!(!engine.is_allowed("guest", "read", "skills/git-status"));
+        assert_eq!(
+            engine.evaluate("ops", "read", "skills/git-push").decision,
+            PolicyDecision::Denied
+        );
+
+        let mut roles = engine.roles();
+        roles.sort();
+        assert_eq!(roles, vec!["oncall", "ops"]);
+    }
+
+    #[test]
+    fn test_invalid_pattern_never_matches() {
+        let mut engine = build_policy(vec![
+            PolicyRule::allow("admin", "*", "skills/[git"),
+            PolicyRule::allow("admin", "read", "*"),
+        ]);
+
+        assert!(!engine.is_allowed("admin", "write", "skills/[git"));
+        assert!(engine.is_allowed("admin", "read", "skills/[git"));
+        assert_eq!(engine.rule_count(), 2);
+    }
+
     #[test]
     fn test_rule_count() {
         let engine = build_policy(vec![
@@ -281,4 +505,118 @@ mod tests {
         // Allow-all (priority 1) works for non-secrets
         assert!(engine.is_allowed("user", "read", "config"));
     }
+
+    #[test]
+    fn test_condition_gates_rule() {
+        let mut engine = build_policy(vec![
+            PolicyRule::allow("operator", "deploy", "*").with_condition(
+                Condition::parse("time.hour >= 9 && resource.tag == \"prod\"").unwrap(),
+            ),
+        ]);
+
+        let in_hours = RequestContext::new()
+            .with("time.hour", 10i64)
+            .with("resource.tag", "prod");
+        let after_hours = RequestContext::new()
+            .with("time.hour", 7i64)
+            .with("resource.tag", "prod");
+
+        assert!(engine.is_allowed_with_context("operator", "deploy", "api", &in_hours));
+        assert_eq!(
+            engine
+                .evaluate_with_context("operator", "deploy", "api", &after_hours)
+                .decision,
+            PolicyDecision::NoMatch
+        );
+        // An allow rule whose attributes are missing never matches.
+        assert!(!engine.is_allowed("operator", "deploy", "api"));
+    }
+
+    #[test]
+    fn test_unresolved_condition_fails_closed() {
+        let mut engine = build_policy(vec![
+            PolicyRule::deny("user", "write", "*")
+                .with_priority(100)
+                .with_condition(Condition::parse("!(request.mfa == true)").unwrap()),
+            PolicyRule::allow("user", "*", "*")
+                .with_condition(Condition::parse("caller.team == \"ops\"").unwrap()),
+        ]);
+
+        // Neither attribute is known: the deny rule applies, the allow
+        // rule does not.
+        let empty = RequestContext::new();
+        assert_eq!(
+            engine
+                .evaluate_with_context("user", "write", "config", &empty)
+                .decision,
+            PolicyDecision::Denied
+        );
+        assert_eq!(
+            engine
+                .evaluate_with_context("user", "read", "config", &empty)
+                .decision,
+            PolicyDecision::NoMatch
+        );
+
+        let known = RequestContext::new()
+            .with("request.mfa", true)
+            .with("caller.team", "ops");
+        assert!(engine.is_allowed_with_context("user", "write", "config", &known));
+    }
+
+    #[test]
+    fn test_evaluate_uses_current_time() {
+        let mut engine = build_policy(vec![PolicyRule::allow("user", "read", "*").with_condition(
+            Condition::parse("time.hour >= 0 && request.resource == \"x\"").unwrap(),
+        )]);
+        assert!(engine.is_allowed("user", "read", "x"));
+        assert!(!engine.is_allowed("user", "read", "y"));
+    }
+
+    #[test]
+    fn test_failed_condition_falls_through() {
+        let mut engine = build_policy(vec![
+            PolicyRule::deny("user", "*", "*")
+                .with_priority(100)
+                .with_condition(Condition::parse("request.mfa != true").unwrap()),
+            PolicyRule::allow("user", "*", "*"),
+        ]);
+
+        let mfa = RequestContext::new().with("request.mfa", true);
+        let no_mfa = RequestContext::new().with("request.mfa", false);
+        assert!(engine.is_allowed_with_context("user", "read", "config", &mfa));
+        assert!(!engine.is_allowed_with_context("user", "read", "config", &no_mfa));
+    }
+
+    #[test]
+    fn test_verdict_names_deciding_rule() {
+        let mut engine = build_policy(vec![
+            PolicyRule::deny("*", "write", "secrets")
+                .with_priority(10)
+                .with_id("no-secret-writes")
+                .with_reason("secrets are managed by the vault sync job"),
+            PolicyRule::allow("admin", "*", "*").with_id("admin-all"),
+            PolicyRule::allow("user", "read", "*"),
+        ]);
+
+        let verdict = engine.evaluate("admin", "write", "secrets");
+        assert_eq!(verdict.decision, PolicyDecision::Denied);
+        assert_eq!(verdict.rule_id.as_deref(), Some("no-secret-writes"));
+        assert_eq!(
+            verdict.to_string(),
+            "denied by rule \"no-secret-writes\": secrets are managed by the vault sync job"
+        );
+
+        let verdict = engine.evaluate("admin", "read", "config");
+        assert_eq!(verdict.rule_id.as_deref(), Some("admin-all"));
+        assert_eq!(verdict.reason, None);
+        assert_eq!(
+            engine.evaluate("user", "read", "config").to_string(),
+            "allowed"
+        );
+        assert_eq!(
+            engine.evaluate("guest", "read", "config").to_string(),
+            "no rule matches"
+        );
+    }
 }
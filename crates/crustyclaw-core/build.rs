@@ -27,6 +27,32 @@ fn main() {
     let profile = std::env::var("PROFILE").unwrap_or_else(|_| "unknown".to_string());
     println!("cargo:rustc-env=CRUSTYCLAW_BUILD_PROFILE={profile}");
 
+    // Embed the target triple and the compiler that built it
+    let target = std::env::var("TARGET").unwrap_or_else(|_| "unknown".to_string());
+    println!("cargo:rustc-env=CRUSTYCLAW_TARGET={target}");
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let rustc_version = Command::new(rustc)
+        .arg("--version")
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .and_then(|o| String::from_utf8(o.stdout).ok())
+        .unwrap_or_else(|| "unknown".to_string());
+    println!(
+        "cargo:rustc-env=CRUSTYCLAW_RUSTC_VERSION={}",
+        rustc_version.trim()
+    );
+
+    // Embed the enabled cargo features, as a sorted comma-separated list
+    let mut features: Vec<String> = std::env::vars()
+        .filter_map(|(key, _)| {
+            key.strip_prefix("CARGO_FEATURE_")
+                .map(|name| name.to_lowercase().replace('_', "-"))
+        })
+        .collect();
+    features.sort();
+    println!("cargo:rustc-env=CRUSTYCLAW_FEATURES={}", features.join(","));
+
     // Re-run if git HEAD changes
     println!("cargo:rerun-if-changed=../../.git/HEAD");
     println!("cargo:rerun-if-changed=../../.git/refs");
@@ -0,0 +1,349 @@
+//! Channel access policy — sender allowlists, roles, and quarantine.
+//!
+//! A [`ChannelAccess`] decides, before anything else happens to an inbound
+//! message, whether its sender may use the channel at all. With no
+//! allowlist configured every sender is admitted. Otherwise a message is
+//! admitted when its sender is listed, or when it was posted in a listed
+//! group; anything else is dropped or handed to a [`QuarantineHandler`],
+//! as `unknown_senders` says.
+//!
+//! Each decision made under an allowlist is appended to the [`AUDIT_FILE`]
+//! audit log as an [`AccessAuditRecord`].
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crustyclaw_config::{SignalConfig, UnknownSenderAction};
+
+use crate::message::Envelope;
+use crate::storage::AuditLog;
+
+/// Key of the access audit log in the audit namespace; a file in the
+/// daemon's state directory with filesystem storage.
+pub const AUDIT_FILE: &str = "channel-access.jsonl";
+
+/// File name of the quarantined message log, relative to the daemon's state
+/// directory.
+pub const QUARANTINE_FILE: &str = "quarantine.jsonl";
+
+/// What happens to an inbound message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccessDecision {
+    /// Process the message. `role` is the sender's configured role, if any.
+    Allow { role: Option<String> },
+    /// Discard the message.
+    Drop,
+    /// Hold the message for review without processing it.
+    Quarantine,
+}
+
+impl AccessDecision {
+    /// Whether the message may be processed.
+    pub fn is_allowed(&self) -> bool {
+        matches!(self, Self::Allow { .. })
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Allow { .. } => "allow",
+            Self::Drop => "drop",
+            Self::Quarantine => "quarantine",
+        }
+    }
+}
+
+/// One entry in the channel access audit trail.
+#[derive(Debug, Clone, Serialize)]
+pub struct AccessAuditRecord {
+    /// Channel the message arrived on.
+    pub channel: String,
+    /// Sender identity on the channel.
+    pub sender: String,
+    /// Group the message was posted in, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// `allow`, `drop`, or `quarantine`.
+    pub decision: &'static str,
+    /// The sender's configured role, for an allowed message.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    /// Unix time of the decision, in milliseconds.
+    pub at_ms: u64,
+}
+
+/// Receives the messages of unknown senders under `unknown_senders = "quarantine"`.
+pub trait QuarantineHandler: Send + Sync {
+    /// Hold `envelope` for review. It is not published on the message bus.
+    fn quarantine(&self, envelope: &Envelope);
+}
+
+/// A [`QuarantineHandler`] appending messages to a JSON lines file.
+#[derive(Debug, Clone)]
+pub struct QuarantineLog {
+    path: PathBuf,
+}
+
+/// One quarantined message, as written by [`QuarantineLog`].
+#[derive(Debug, Clone, Serialize)]
+struct QuarantinedMessage<'a> {
+    channel: &'a str,
+    sender: Option<&'a str>,
+    body: &'a str,
+    attachments: usize,
+    at_ms: u64,
+}
+
+impl QuarantineLog {
+    /// Create a log writing to `path`.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl QuarantineHandler for QuarantineLog {
+    fn quarantine(&self, envelope: &Envelope) {
+        let message = QuarantinedMessage {
+            channel: &envelope.channel,
+            sender: envelope.peer.as_deref(),
+            body: &envelope.body,
+            attachments: envelope.attachments.len() + envelope.images.len(),
+            at_ms: now_ms(),
+        };
+        if let Err(e) = append_json(&self.path, &message) {
+            warn!(path = %self.path.display(), error = %e, "Failed to write quarantined message");
+        }
+    }
+}
+
+/// Per-channel sender allowlist with role mapping.
+pub struct ChannelAccess {
+    channel: String,
+    senders: BTreeMap<String, String>,
+    groups: Vec<String>,
+    unknown: UnknownSenderAction,
+    audit_log: Option<AuditLog>,
+    quarantine: Option<Arc<dyn QuarantineHandler>>,
+}
+
+impl ChannelAccess {
+    /// A policy for `channel` admitting every sender.
+    pub fn open(channel: &str) -> Self {
+        Self {
+            channel: channel.to_string(),
+            senders: BTreeMap::new(),
+            groups: Vec::new(),
+            unknown: UnknownSenderAction::Drop,
+            audit_log: None,
+            quarantine: None,
+        }
+    }
+
+    /// The policy of the `[signal]` section.
+    pub fn signal(config: &SignalConfig) -> Self {
+        Self {
+            senders: config.allowed_senders.clone(),
+            groups: config.allowed_groups.clone(),
+            unknown: config.unknown_senders,
+            ..Self::open("signal")
+        }
+    }
+
+    /// Append audit records to `log`.
+    pub fn with_audit_log(mut self, log: AuditLog) -> Self {
+        self.audit_log = Some(log);
+        self
+    }
+
+    /// Hand quarantined messages to `handler`. Without one, quarantined
+    /// messages are dropped.
+    pub fn with_quarantine(mut self, handler: Arc<dyn QuarantineHandler>) -> Self {
+        self.quarantine = Some(handler);
+        self
+    }
+
+    /// Whether senders are restricted to the allowlists.
+    pub fn is_restricted(&self) -> bool {
+        !self.senders.is_empty() || !self.groups.is_empty()
+    }
+
+    /// Decide what to do with a message from `sender`, posted in `group` if
+    /// it is a group message, and record the decision.
+    pub fn check(&self, sender: &str, group: Option<&str>) -> AccessDecision {
+        if !self.is_restricted() {
+            return AccessDecision::Allow { role: None };
+        }
+        let role = self.senders.get(sender).cloned();
+        let decision =
+            if role.is_some() || group.is_some_and(|g| self.groups.iter().any(|a| a == g)) {
+                AccessDecision::Allow { role }
+            } else {
+                match self.unknown {
+                    UnknownSenderAction::Drop => AccessDecision::Drop,
+                    UnknownSenderAction::Quarantine => AccessDecision::Quarantine,
+                }
+            };
+        if !decision.is_allowed() {
+            info!(channel = %self.channel, sender, group, decision = decision.name(), "Message from unknown sender refused");
+        }
+        self.record(sender, group, &decision);
+        decision
+    }
+
+    /// Pass a refused message to the quarantine handler, if there is one.
+    pub fn quarantine(&self, envelope: &Envelope) {
+        if let Some(handler) = &self.quarantine {
+            handler.quarantine(envelope);
+        }
+    }
+
+    fn record(&self, sender: &str, group: Option<&str>, decision: &AccessDecision) {
+        let Some(log) = &self.audit_log else {
+            return;
+        };
+        let record = AccessAuditRecord {
+            channel: self.channel.clone(),
+            sender: sender.to_string(),
+            group: group.map(str::to_string),
+            decision: decision.name(),
+            role: match decision {
+                AccessDecision::Allow { role } => role.clone(),
+                _ => None,
+            },
+            at_ms: now_ms(),
+        };
+        if let Err(e) = log.append(&record) {
+            warn!(log = log.key(), error = %e, "Failed to write access audit record");
+        }
+    }
+}
+
+fn append_json(path: &Path, record: &impl Serialize) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    let line = serde_json::to_string(record).unwrap_or_default();
+    writeln!(file, "{line}")
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    fn signal_config(unknown: UnknownSenderAction) -> SignalConfig {
+        SignalConfig {
+            allowed_senders: [("+15550001".to_string(), "operator".to_string())].into(),
+            allowed_groups: vec!["group-1".to_string()],
+            unknown_senders: unknown,
+            ..SignalConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_open_admits_everyone() {
+        let access = ChannelAccess::signal(&SignalConfig::default());
+        assert!(!access.is_restricted());
+        assert_eq!(
+            access.check("+15559999", None),
+            AccessDecision::Allow { role: None }
+        );
+    }
+
+    #[test]
+    fn test_allowlists() {
+        let access = ChannelAccess::signal(&signal_config(UnknownSenderAction::Drop));
+        assert_eq!(
+            access.check("+15550001", None),
+            AccessDecision::Allow {
+                role: Some("operator".to_string())
+            }
+        );
+        assert_eq!(
+            access.check("+15550001", Some("group-2")),
+            AccessDecision::Allow {
+                role: Some("operator".to_string())
+            }
+        );
+        assert_eq!(
+            access.check("+15559999", Some("group-1")),
+            AccessDecision::Allow { role: None }
+        );
+        assert_eq!(access.check("+15559999", None), AccessDecision::Drop);
+        assert_eq!(
+            access.check("+15559999", Some("group-2")),
+            AccessDecision::Drop
+        );
+
+        let access = ChannelAccess::signal(&signal_config(UnknownSenderAction::Quarantine));
+        assert_eq!(access.check("+15559999", None), AccessDecision::Quarantine);
+    }
+
+    #[test]
+    fn test_decisions_audited() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(AUDIT_FILE);
+        let access = ChannelAccess::signal(&signal_config(UnknownSenderAction::Quarantine))
+            .with_audit_log(AuditLog::file(&path));
+        access.check("+15550001", None);
+        access.check("+15559999", Some("group-2"));
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        let records: Vec<serde_json::Value> = log
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["decision"], "allow");
+        assert_eq!(records[0]["role"], "operator");
+        assert_eq!(records[1]["decision"], "quarantine");
+        assert_eq!(records[1]["group"], "group-2");
+        assert_eq!(records[1]["channel"], "signal");
+    }
+
+    #[test]
+    fn test_quarantine_handler() {
+        #[derive(Default)]
+        struct Held(Mutex<Vec<String>>);
+        impl QuarantineHandler for Held {
+            fn quarantine(&self, envelope: &Envelope) {
+                self.0.lock().unwrap().push(envelope.body.clone());
+            }
+        }
+
+        let held = Arc::new(Held::default());
+        let access = ChannelAccess::signal(&signal_config(UnknownSenderAction::Quarantine))
+            .with_quarantine(held.clone());
+        access.quarantine(&Envelope::new("signal", "who is this?"));
+        assert_eq!(*held.0.lock().unwrap(), ["who is this?"]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(QUARANTINE_FILE);
+        QuarantineLog::new(&path).quarantine(&Envelope::new("signal", "hi").with_peer("+15559999"));
+        let record: serde_json::Value =
+            serde_json::from_str(std::fs::read_to_string(&path).unwrap().trim()).unwrap();
+        assert_eq!(record["sender"], "+15559999");
+        assert_eq!(record["body"], "hi");
+    }
+}
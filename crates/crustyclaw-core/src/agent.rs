@@ -0,0 +1,1013 @@
+//! Agent loop — drives an LLM conversation through tool use to a final answer.
+//!
+//! [`AgentRunner`] sends the conversation to the [`LlmProvider`] with the
+//! tools the caller's trust level allows, runs any tool calls in the reply
+//! through the [`ToolExecutor`] (which applies the registry's trust checks,
+//! path allowlists, and the sandbox), appends the results, and asks again.
+//! The loop ends when the model stops calling tools (finish reason
+//! `"stop"`), or fails once it reaches the iteration cap, spends the token
+//! budget, or runs past its timeout. The timeout is passed to every model
+//! request as its deadline, so a slow request or its retries are cut short.
+//!
+//! With a [`QuotaManager`], the run is charged to the sender's role: each
+//! request's tokens count against its daily LLM quota, and each
+//! `run_command` call against its hourly sandbox quota. An exhausted token
+//! quota ends the run; a denied command is reported to the model as a
+//! failed tool call.
+//!
+//! With [`Metrics`], each model request's tokens and latency are counted,
+//! as are quota and tool trust refusals. With a [`UsageLedger`], each
+//! request's tokens are recorded against the model and the run's
+//! [`UsageAttribution`].
+//!
+//! When given the message bus, the runner publishes an [`AgentEvent`] per
+//! step as an outbound reply to the originating message, so the sender can
+//! follow long-running work. An event channel ([`AgentRunner::with_events`])
+//! receives the same steps plus the model's text as it streams in.
+//!
+//! With a [`MemoryStore`], the conversation's long-term memory relevant to
+//! the latest user message is appended to the system prompt, and each
+//! completed run is folded back into it.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::sync::{broadcast, mpsc};
+use tracing::{debug, info, warn};
+
+use crustyclaw_config::LlmConfig;
+
+use crate::context::{ToolError, ToolExecutor, ToolTrust};
+use crate::llm::{
+    ChatMessage, ChatRequest, ChatResponse, LlmError, LlmProvider, StreamChunk, TokenUsage,
+    ToolCall,
+};
+use crate::memory::MemoryStore;
+use crate::message::Envelope;
+use crate::metrics::{Denial, Metrics};
+use crate::quota::{QuotaError, QuotaKind, QuotaManager};
+use crate::usage::{UsageAttribution, UsageLedger};
+
+/// Default cap on model round trips per run.
+pub const DEFAULT_MAX_ITERATIONS: u32 = 16;
+
+/// Why a run ended without a final answer.
+#[derive(Debug, thiserror::Error)]
+pub enum AgentError {
+    #[error(transparent)]
+    Llm(#[from] LlmError),
+
+    #[error("no final answer after {0} iterations")]
+    IterationLimit(u32),
+
+    #[error("token budget of {budget} exhausted ({used} used)")]
+    TokenBudget { budget: u32, used: u32 },
+
+    #[error("no final answer within {0:?}")]
+    Timeout(Duration),
+
+    #[error(transparent)]
+    Quota(#[from] QuotaError),
+}
+
+/// A step in a run, published on the message bus.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AgentEvent {
+    /// A request is being sent to the model.
+    Thinking { iteration: u32 },
+    /// The model asked for a tool.
+    ToolCall { name: String },
+    /// A tool call finished.
+    ToolResult { name: String, ok: bool },
+    /// The model gave its final answer.
+    Finished { iterations: u32, total_tokens: u32 },
+    /// Part of the model's text, as it streams in. Only sent to the event
+    /// channel, not the bus.
+    Text { delta: String },
+}
+
+impl std::fmt::Display for AgentEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Thinking { iteration } => write!(f, "[agent] thinking (step {iteration})"),
+            Self::ToolCall { name } => write!(f, "[agent] running {name}"),
+            Self::ToolResult { name, ok: true } => write!(f, "[agent] {name} done"),
+            Self::ToolResult { name, ok: false } => write!(f, "[agent] {name} failed"),
+            Self::Finished {
+                iterations,
+                total_tokens,
+            } => write!(
+                f,
+                "[agent] finished after {iterations} step(s), {total_tokens} tokens"
+            ),
+            Self::Text { delta } => f.write_str(delta),
+        }
+    }
+}
+
+/// The result of a completed run.
+#[derive(Debug, Clone)]
+pub struct AgentOutcome {
+    /// The model's final answer.
+    pub reply: String,
+    /// The full conversation, including tool calls and results.
+    pub messages: Vec<ChatMessage>,
+    /// Model round trips taken.
+    pub iterations: u32,
+    /// Tokens used across all round trips.
+    pub usage: TokenUsage,
+}
+
+/// Runs the LLM tool-use loop.
+pub struct AgentRunner {
+    provider: Arc<dyn LlmProvider>,
+    executor: Arc<ToolExecutor>,
+    model: String,
+    max_tokens: u32,
+    temperature: f32,
+    system: Option<String>,
+    trust: ToolTrust,
+    tools: Option<BTreeSet<String>>,
+    max_iterations: u32,
+    token_budget: Option<u32>,
+    timeout: Option<Duration>,
+    bus: Option<broadcast::Sender<Envelope>>,
+    events: Option<mpsc::UnboundedSender<AgentEvent>>,
+    quota: Option<(Arc<QuotaManager>, String)>,
+    metrics: Option<Arc<Metrics>>,
+    usage: Option<(Arc<UsageLedger>, UsageAttribution)>,
+    memory: Option<(Arc<MemoryStore>, String)>,
+}
+
+impl AgentRunner {
+    /// Create a runner using the model settings from `[llm]`, offering the
+    /// model only [`ToolTrust::Public`] tools.
+    pub fn new(
+        provider: Arc<dyn LlmProvider>,
+        executor: Arc<ToolExecutor>,
+        config: &LlmConfig,
+    ) -> Self {
+        Self {
+            provider,
+            executor,
+            model: config.model.clone(),
+            max_tokens: config.max_tokens,
+            temperature: config.temperature,
+            system: None,
+            trust: ToolTrust::Public,
+            tools: None,
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+            token_budget: None,
+            timeout: None,
+            bus: None,
+            events: None,
+            quota: None,
+            metrics: None,
+            usage: None,
+            memory: None,
+        }
+    }
+
+    /// Builder: send `prompt` as the system prompt.
+    pub fn with_system(mut self, prompt: impl Into<String>) -> Self {
+        self.system = Some(prompt.into());
+        self
+    }
+
+    /// Builder: offer and run tools up to `trust`.
+    pub fn with_trust(mut self, trust: ToolTrust) -> Self {
+        self.trust = trust;
+        self
+    }
+
+    /// Builder: offer and run only the tools named in `tools`, of those
+    /// `trust` allows.
+    pub fn with_tools(mut self, tools: BTreeSet<String>) -> Self {
+        self.tools = Some(tools);
+        self
+    }
+
+    /// Builder: fail after `max` model round trips without a final answer.
+    pub fn with_max_iterations(mut self, max: u32) -> Self {
+        self.max_iterations = max;
+        self
+    }
+
+    /// Builder: fail once the run has used `budget` tokens in total.
+    pub fn with_token_budget(mut self, budget: u32) -> Self {
+        self.token_budget = Some(budget);
+        self
+    }
+
+    /// Builder: fail once the run has taken `timeout`, cutting short the
+    /// model request in flight.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Builder: publish progress events on `bus`.
+    pub fn with_bus(mut self, bus: broadcast::Sender<Envelope>) -> Self {
+        self.bus = Some(bus);
+        self
+    }
+
+    /// Builder: send each step, and the model's text as it streams in, to
+    /// `events`. Requests are made with [`LlmProvider::chat_stream`].
+    pub fn with_events(mut self, events: mpsc::UnboundedSender<AgentEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Builder: charge the run's tokens and sandbox executions to `role`.
+    pub fn with_quota(mut self, quotas: Arc<QuotaManager>, role: impl Into<String>) -> Self {
+        self.quota = Some((quotas, role.into()));
+        self
+    }
+
+    /// Builder: count requests, tokens, latency, and refusals in `metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Builder: record each request's tokens in `ledger`, attributed to
+    /// `attribution`.
+    pub fn with_usage(mut self, ledger: Arc<UsageLedger>, attribution: UsageAttribution) -> Self {
+        self.usage = Some((ledger, attribution));
+        self
+    }
+
+    /// Builder: remember `conversation` across runs in `store`.
+    pub fn with_memory(mut self, store: Arc<MemoryStore>, conversation: impl Into<String>) -> Self {
+        self.memory = Some((store, conversation.into()));
+        self
+    }
+
+    /// Run the conversation in `messages` to a final answer.
+    ///
+    /// Progress events are addressed to the sender of `origin`.
+    pub async fn run(
+        &self,
+        origin: &Envelope,
+        mut messages: Vec<ChatMessage>,
+    ) -> Result<AgentOutcome, AgentError> {
+        let mut tools = self.executor.definitions(self.trust);
+        if let Some(offered) = &self.tools {
+            tools.retain(|tool| offered.contains(&tool.name));
+        }
+        let mut usage = TokenUsage::default();
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        let system = self.system_prompt(&messages);
+
+        for iteration in 1..=self.max_iterations {
+            if let Some((quotas, role)) = &self.quota
+                && let Err(e) = quotas.check(role, QuotaKind::LlmTokens)
+            {
+                self.record_denial(Denial::Quota);
+                return Err(e.into());
+            }
+            self.emit(origin, AgentEvent::Thinking { iteration });
+            let max_tokens = match self.token_budget {
+                Some(budget) => self
+                    .max_tokens
+                    .min(budget.saturating_sub(usage.total_tokens)),
+                None => self.max_tokens,
+            };
+            let request = ChatRequest {
+                model: self.model.clone(),
+                messages: messages.clone(),
+                tools: tools.clone(),
+                max_tokens,
+                temperature: self.temperature,
+                system: system.clone(),
+                deadline,
+            };
+            let started = Instant::now();
+            let response = match &self.events {
+                Some(events) => self.chat_stream(&request, events).await,
+                None => self.provider.chat(&request).await,
+            };
+            let response = match (response, self.timeout) {
+                (Err(LlmError::Timeout), Some(timeout))
+                    if deadline.is_some_and(|deadline| Instant::now() >= deadline) =>
+                {
+                    return Err(AgentError::Timeout(timeout));
+                }
+                (response, _) => response?,
+            };
+            if let Some(metrics) = &self.metrics {
+                metrics.record_llm_request(&response.usage, started.elapsed());
+            }
+            if let Some((ledger, attribution)) = &self.usage {
+                ledger.record(
+                    attribution,
+                    &response.model,
+                    &response.usage,
+                    response.attempts,
+                );
+            }
+            usage.prompt_tokens += response.usage.prompt_tokens;
+            usage.completion_tokens += response.usage.completion_tokens;
+            usage.total_tokens += response.usage.total_tokens;
+            if let Some((quotas, role)) = &self.quota {
+                quotas.record(
+                    role,
+                    QuotaKind::LlmTokens,
+                    u64::from(response.usage.total_tokens),
+                );
+            }
+            debug!(
+                iteration,
+                finish_reason = %response.finish_reason,
+                attempts = response.attempts,
+                total_tokens = usage.total_tokens,
+                "Agent step"
+            );
+
+            let calls = response.message.tool_calls.clone().unwrap_or_default();
+            messages.push(response.message);
+            if response.finish_reason == "stop" || calls.is_empty() {
+                self.emit(
+                    origin,
+                    AgentEvent::Finished {
+                        iterations: iteration,
+                        total_tokens: usage.total_tokens,
+                    },
+                );
+                info!(
+                    iterations = iteration,
+                    total_tokens = usage.total_tokens,
+                    "Agent run finished"
+                );
+                let reply = messages
+                    .last()
+                    .and_then(|m| m.content.clone())
+                    .unwrap_or_default();
+                self.remember(&messages).await;
+                return Ok(AgentOutcome {
+                    reply,
+                    messages,
+                    iterations: iteration,
+                    usage,
+                });
+            }
+            if let Some(budget) = self.token_budget
+                && usage.total_tokens >= budget
+            {
+                return Err(AgentError::TokenBudget {
+                    budget,
+                    used: usage.total_tokens,
+                });
+            }
+
+            for call in &calls {
+                self.emit(
+                    origin,
+                    AgentEvent::ToolCall {
+                        name: call.name.clone(),
+                    },
+                );
+                let result = match self.check_offered(call) {
+                    Ok(()) => match self.charge_command(call) {
+                        Ok(()) => self.executor.run(call, self.trust).await,
+                        Err(e) => Err(e.into()),
+                    },
+                    Err(e) => Err(e),
+                };
+                match &result {
+                    Err(ToolError::Quota(_)) => self.record_denial(Denial::Quota),
+                    Err(ToolError::Forbidden { .. } | ToolError::NotOffered(_)) => {
+                        self.record_denial(Denial::ToolTrust)
+                    }
+                    _ => {}
+                }
+                let ok = result.is_ok();
+                messages.push(ToolExecutor::result_message(call, result));
+                self.emit(
+                    origin,
+                    AgentEvent::ToolResult {
+                        name: call.name.clone(),
+                        ok,
+                    },
+                );
+            }
+        }
+        Err(AgentError::IterationLimit(self.max_iterations))
+    }
+
+    /// The system prompt, followed by the conversation memory relevant to
+    /// the latest user message.
+    fn system_prompt(&self, messages: &[ChatMessage]) -> Option<String> {
+        let Some((store, conversation)) = &self.memory else {
+            return self.system.clone();
+        };
+        let query = messages
+            .iter()
+            .rev()
+            .find(|m| m.role == "user")
+            .and_then(|m| m.content.as_deref())
+            .unwrap_or_default();
+        match store.context(conversation, query) {
+            Ok(Some(memory)) => Some(match &self.system {
+                Some(system) => format!("{system}\n\n{memory}"),
+                None => memory,
+            }),
+            Ok(None) => self.system.clone(),
+            Err(e) => {
+                warn!(conversation, error = %e, "Failed to load conversation memory");
+                self.system.clone()
+            }
+        }
+    }
+
+    /// Fold a completed run into the conversation memory.
+    async fn remember(&self, messages: &[ChatMessage]) {
+        let Some((store, conversation)) = &self.memory else {
+            return;
+        };
+        if let Err(e) = store
+            .update(conversation, self.provider.as_ref(), &self.model, messages)
+            .await
+        {
+            warn!(conversation, error = %e, "Failed to update conversation memory");
+        }
+    }
+
+    /// Send `request` with [`LlmProvider::chat_stream`], forwarding text to
+    /// `events` as it arrives, and assemble the chunks into a response.
+    async fn chat_stream(
+        &self,
+        request: &ChatRequest,
+        events: &mpsc::UnboundedSender<AgentEvent>,
+    ) -> Result<ChatResponse, LlmError> {
+        let mut chunks = self.provider.chat_stream(request).await?;
+        let mut content = String::new();
+        // (id, name, arguments JSON) per tool call, in order.
+        let mut calls: Vec<(String, String, String)> = Vec::new();
+        let mut finish_reason = String::new();
+        let mut usage = TokenUsage::default();
+        let mut attempts = 1;
+        while let Some(chunk) = chunks.recv().await {
+            match chunk? {
+                StreamChunk::Text(delta) => {
+                    content.push_str(&delta);
+                    let _ = events.send(AgentEvent::Text { delta });
+                }
+                StreamChunk::ToolCallStart { id, name } => calls.push((id, name, String::new())),
+                StreamChunk::ToolCallDelta {
+                    id,
+                    arguments_delta,
+                } => {
+                    if let Some(call) = calls.iter_mut().find(|call| call.0 == id) {
+                        call.2.push_str(&arguments_delta);
+                    }
+                }
+                StreamChunk::Done {
+                    finish_reason: reason,
+                    usage: reported,
+                    attempts: tries,
+                } => {
+                    finish_reason = reason;
+                    usage = reported.unwrap_or_default();
+                    attempts = tries;
+                }
+            }
+        }
+
+        let tool_calls = calls
+            .into_iter()
+            .map(|(id, name, arguments)| {
+                let arguments = match arguments.trim() {
+                    "" => serde_json::json!({}),
+                    json => serde_json::from_str(json).map_err(|e| {
+                        LlmError::Parse(format!("arguments of tool call {name}: {e}"))
+                    })?,
+                };
+                Ok(ToolCall {
+                    id,
+                    name,
+                    arguments,
+                })
+            })
+            .collect::<Result<Vec<_>, LlmError>>()?;
+        let mut message = ChatMessage::assistant(content);
+        if !tool_calls.is_empty() {
+            message.tool_calls = Some(tool_calls);
+        }
+        Ok(ChatResponse {
+            message,
+            finish_reason,
+            usage,
+            model: request.model.clone(),
+            attempts,
+        })
+    }
+
+    /// Refuse a call to a tool outside [`with_tools`](Self::with_tools).
+    fn check_offered(&self, call: &ToolCall) -> Result<(), ToolError> {
+        match &self.tools {
+            Some(offered) if !offered.contains(&call.name) => {
+                Err(ToolError::NotOffered(call.name.clone()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Count a `run_command` call against the role's sandbox quota.
+    fn charge_command(&self, call: &ToolCall) -> Result<(), QuotaError> {
+        match &self.quota {
+            Some((quotas, role)) if call.name == "run_command" => {
+                quotas.try_consume(role, QuotaKind::SandboxExecutions, 1)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn record_denial(&self, reason: Denial) {
+        if let Some(metrics) = &self.metrics {
+            metrics.record_denial(reason);
+        }
+    }
+
+    fn emit(&self, origin: &Envelope, event: AgentEvent) {
+        if let Some(bus) = &self.bus {
+            let _ = bus.send(origin.reply(&event.to_string()));
+        }
+        if let Some(events) = &self.events {
+            let _ = events.send(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::sync::{Mutex, RwLock};
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::BoxFuture;
+    use crate::context::ToolRegistry;
+    use crate::llm::{ChatResponse, StreamChunk};
+
+    /// Replays scripted responses and records the requests it was sent.
+    struct ScriptedProvider {
+        responses: Mutex<VecDeque<ChatResponse>>,
+        requests: Mutex<Vec<ChatRequest>>,
+    }
+
+    impl ScriptedProvider {
+        fn new(responses: Vec<ChatResponse>) -> Arc<Self> {
+            Arc::new(Self {
+                responses: Mutex::new(responses.into()),
+                requests: Mutex::new(Vec::new()),
+            })
+        }
+    }
+
+    impl LlmProvider for ScriptedProvider {
+        fn name(&self) -> &str {
+            "scripted"
+        }
+
+        fn chat(&self, request: &ChatRequest) -> BoxFuture<'_, Result<ChatResponse, LlmError>> {
+            self.requests.lock().unwrap().push(request.clone());
+            let response = self.responses.lock().unwrap().pop_front();
+            Box::pin(async move { response.ok_or(LlmError::Timeout) })
+        }
+
+        /// Streams the next scripted response: its text in two parts, then
+        /// each tool call with its arguments split in two.
+        fn chat_stream(
+            &self,
+            request: &ChatRequest,
+        ) -> BoxFuture<
+            '_,
+            Result<tokio::sync::mpsc::Receiver<Result<StreamChunk, LlmError>>, LlmError>,
+        > {
+            self.requests.lock().unwrap().push(request.clone());
+            let response = self.responses.lock().unwrap().pop_front();
+            Box::pin(async move {
+                let response = response.ok_or(LlmError::Timeout)?;
+                let (tx, rx) = tokio::sync::mpsc::channel(64);
+                let text = response.message.content.clone().unwrap_or_default();
+                let (head, tail) = text.split_at(text.len() / 2);
+                let mut chunks = vec![
+                    StreamChunk::Text(head.to_string()),
+                    StreamChunk::Text(tail.to_string()),
+                ];
+                for call in response.message.tool_calls.iter().flatten() {
+                    let arguments = call.arguments.to_string();
+                    let (head, tail) = arguments.split_at(arguments.len() / 2);
+                    chunks.push(StreamChunk::ToolCallStart {
+                        id: call.id.clone(),
+                        name: call.name.clone(),
+                    });
+                    for part in [head, tail] {
+                        chunks.push(StreamChunk::ToolCallDelta {
+                            id: call.id.clone(),
+                            arguments_delta: part.to_string(),
+                        });
+                    }
+                }
+                chunks.push(StreamChunk::Done {
+                    finish_reason: response.finish_reason,
+                    usage: Some(response.usage),
+                    attempts: 1,
+                });
+                for chunk in chunks {
+                    let _ = tx.send(Ok(chunk)).await;
+                }
+                Ok(rx)
+            })
+        }
+    }
+
+    fn response(finish_reason: &str, content: &str, calls: Vec<ToolCall>) -> ChatResponse {
+        let mut message = ChatMessage::assistant(content);
+        if !calls.is_empty() {
+            message.tool_calls = Some(calls);
+        }
+        ChatResponse {
+            message,
+            finish_reason: finish_reason.to_string(),
+            usage: TokenUsage {
+                prompt_tokens: 80,
+                completion_tokens: 20,
+                total_tokens: 100,
+            },
+            model: "scripted".to_string(),
+            attempts: 1,
+        }
+    }
+
+    fn read_file(id: &str, path: &str) -> ToolCall {
+        ToolCall {
+            id: id.to_string(),
+            name: "read_file".to_string(),
+            arguments: json!({ "path": path }),
+        }
+    }
+
+    fn runner(dir: &tempfile::TempDir, provider: Arc<ScriptedProvider>) -> AgentRunner {
+        std::fs::write(dir.path().join("notes.txt"), "the answer is 42").unwrap();
+        let registry = Arc::new(RwLock::new(ToolRegistry::with_defaults()));
+        let executor = Arc::new(ToolExecutor::new(registry, dir.path()));
+        AgentRunner::new(provider, executor, &LlmConfig::default())
+    }
+
+    #[tokio::test]
+    async fn test_runs_tools_until_stop() {
+        let dir = tempfile::tempdir().unwrap();
+        let provider = ScriptedProvider::new(vec![
+            response(
+                "tool_use",
+                "",
+                vec![read_file("c1", "notes.txt"), read_file("c2", "missing.txt")],
+            ),
+            response("stop", "It is 42.", vec![]),
+        ]);
+        let (bus, mut events) = broadcast::channel(32);
+        let runner = runner(&dir, provider.clone()).with_bus(bus);
+        let origin = Envelope::new("signal", "what is the answer?").with_peer("+15550001");
+
+        let outcome = runner
+            .run(&origin, vec![origin.chat_message()])
+            .await
+            .unwrap();
+        assert_eq!(outcome.reply, "It is 42.");
+        assert_eq!(outcome.iterations, 2);
+        assert_eq!(outcome.usage.total_tokens, 200);
+        let roles: Vec<&str> = outcome.messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, ["user", "assistant", "tool", "tool", "assistant"]);
+        assert_eq!(
+            outcome.messages[2].content.as_deref(),
+            Some("the answer is 42")
+        );
+        assert!(
+            outcome.messages[3]
+                .content
+                .as_deref()
+                .unwrap()
+                .starts_with("error:")
+        );
+
+        // The second request carries the tool results; only public tools are offered.
+        let requests = provider.requests.lock().unwrap();
+        assert_eq!(requests[1].messages.len(), 4);
+        assert!(requests[0].tools.iter().any(|t| t.name == "read_file"));
+        assert!(!requests[0].tools.iter().any(|t| t.name == "run_command"));
+
+        let mut progress = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            assert_eq!(event.peer.as_deref(), Some("+15550001"));
+            progress.push(event.body);
+        }
+        assert_eq!(
+            progress,
+            [
+                "[agent] thinking (step 1)",
+                "[agent] running read_file",
+                "[agent] read_file done",
+                "[agent] running read_file",
+                "[agent] read_file failed",
+                "[agent] thinking (step 2)",
+                "[agent] finished after 2 step(s), 200 tokens",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_events_and_offered_tools() {
+        let dir = tempfile::tempdir().unwrap();
+        let provider = ScriptedProvider::new(vec![
+            response(
+                "tool_use",
+                "Looking.",
+                vec![read_file("c1", "notes.txt"), read_file("c2", "notes.txt")],
+            ),
+            response("stop", "It is 42.", vec![]),
+        ]);
+        let (events_tx, mut events) = mpsc::unbounded_channel();
+        let metrics = Arc::new(Metrics::new());
+        let mut c2 = read_file("c2", "notes.txt");
+        c2.name = "list_files".to_string();
+        let provider_with_list = ScriptedProvider::new(vec![
+            response("tool_use", "", vec![read_file("c1", "notes.txt"), c2]),
+            response("stop", "It is 42.", vec![]),
+        ]);
+
+        // Streamed text and steps arrive on the event channel.
+        let outcome = runner(&dir, provider.clone())
+            .with_events(events_tx)
+            .run(
+                &Envelope::new("cli", "answer?"),
+                vec![ChatMessage::user("answer?")],
+            )
+            .await
+            .unwrap();
+        assert_eq!(outcome.reply, "It is 42.");
+        assert_eq!(outcome.messages[1].tool_calls.as_ref().unwrap().len(), 2);
+        assert_eq!(
+            outcome.messages[2].content.as_deref(),
+            Some("the answer is 42")
+        );
+        let mut seen = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            seen.push(event);
+        }
+        let text: String = seen
+            .iter()
+            .filter_map(|e| match e {
+                AgentEvent::Text { delta } => Some(delta.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(text, "Looking.It is 42.");
+        assert_eq!(seen[0], AgentEvent::Thinking { iteration: 1 });
+        assert_eq!(
+            seen.last(),
+            Some(&AgentEvent::Finished {
+                iterations: 2,
+                total_tokens: 200
+            })
+        );
+
+        // Tools outside the offered set are neither offered nor run.
+        let outcome = runner(&dir, provider_with_list.clone())
+            .with_tools(BTreeSet::from(["read_file".to_string()]))
+            .with_metrics(metrics.clone())
+            .run(
+                &Envelope::new("cli", "answer?"),
+                vec![ChatMessage::user("answer?")],
+            )
+            .await
+            .unwrap();
+        let offered: Vec<String> = provider_with_list.requests.lock().unwrap()[0]
+            .tools
+            .iter()
+            .map(|t| t.name.clone())
+            .collect();
+        assert_eq!(offered, ["read_file"]);
+        assert_eq!(
+            outcome.messages[3].content.as_deref(),
+            Some("error: tool list_files is not offered to this caller")
+        );
+        assert!(
+            metrics
+                .render()
+                .contains("crustyclaw_policy_denials_total{reason=\"tool_trust\"} 1")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_iteration_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let looping = || response("tool_use", "", vec![read_file("c", "notes.txt")]);
+        let provider = ScriptedProvider::new(vec![looping(), looping(), looping()]);
+        let runner = runner(&dir, provider).with_max_iterations(2);
+
+        let err = runner
+            .run(
+                &Envelope::new("cli", "loop"),
+                vec![ChatMessage::user("loop")],
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AgentError::IterationLimit(2)));
+    }
+
+    #[tokio::test]
+    async fn test_token_budget() {
+        let dir = tempfile::tempdir().unwrap();
+        let looping = || response("tool_use", "", vec![read_file("c", "notes.txt")]);
+        let provider = ScriptedProvider::new(vec![looping(), looping(), looping()]);
+        let runner = runner(&dir, provider.clone()).with_token_budget(150);
+
+        let err = runner
+            .run(
+                &Envelope::new("cli", "loop"),
+                vec![ChatMessage::user("loop")],
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            AgentError::TokenBudget {
+                budget: 150,
+                used: 200
+            }
+        ));
+        // The second request may only spend what is left of the budget.
+        assert_eq!(provider.requests.lock().unwrap()[1].max_tokens, 50);
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        let dir = tempfile::tempdir().unwrap();
+        // Out of responses, the provider times out as a deadline would.
+        let provider = ScriptedProvider::new(vec![]);
+        let timed = runner(&dir, provider.clone()).with_timeout(Duration::ZERO);
+
+        let err = timed
+            .run(&Envelope::new("cli", "hi"), vec![ChatMessage::user("hi")])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AgentError::Timeout(timeout) if timeout == Duration::ZERO));
+        assert!(provider.requests.lock().unwrap()[0].deadline.is_some());
+
+        // Without a timeout, requests have no deadline.
+        let provider = ScriptedProvider::new(vec![response("stop", "done", vec![])]);
+        runner(&dir, provider.clone())
+            .run(&Envelope::new("cli", "hi"), vec![ChatMessage::user("hi")])
+            .await
+            .unwrap();
+        assert!(provider.requests.lock().unwrap()[0].deadline.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_quota() {
+        let config = crustyclaw_config::AppConfig::parse(
+            r#"
+            [quotas.roles.user]
+            llm_tokens_per_day = 150
+            sandbox_executions_per_hour = 0
+        "#,
+        )
+        .unwrap();
+        let quotas = Arc::new(QuotaManager::from_config(&config.quotas));
+        let dir = tempfile::tempdir().unwrap();
+        let command = ToolCall {
+            id: "c".to_string(),
+            name: "run_command".to_string(),
+            arguments: json!({ "command": "ls" }),
+        };
+        let provider = ScriptedProvider::new(vec![
+            response("tool_use", "", vec![command.clone()]),
+            response("tool_use", "", vec![command]),
+            response("stop", "done", vec![]),
+        ]);
+        let metrics = Arc::new(Metrics::new());
+        let runner = runner(&dir, provider.clone())
+            .with_trust(ToolTrust::Trusted)
+            .with_quota(quotas.clone(), "user")
+            .with_metrics(metrics.clone());
+
+        let err = runner
+            .run(&Envelope::new("cli", "ls"), vec![ChatMessage::user("ls")])
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            AgentError::Quota(QuotaError::Exceeded {
+                kind: QuotaKind::LlmTokens,
+                used: 200,
+                ..
+            })
+        ));
+        let requests = provider.requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        let denied = requests[1].messages[2].content.as_deref().unwrap();
+        assert!(denied.contains("sandbox_executions quota"), "{denied}");
+        let text = metrics.render();
+        assert!(text.contains("crustyclaw_llm_requests_total 2"), "{text}");
+        assert!(text.contains("crustyclaw_llm_tokens_total{kind=\"prompt\"} 160"));
+        assert!(text.contains("crustyclaw_policy_denials_total{reason=\"quota\"} 3"));
+    }
+
+    #[tokio::test]
+    async fn test_usage_attribution() {
+        use crate::usage::UsageGroup;
+
+        let dir = tempfile::tempdir().unwrap();
+        let provider = ScriptedProvider::new(vec![
+            response("tool_use", "", vec![read_file("c1", "notes.txt")]),
+            response("stop", "It is 42.", vec![]),
+        ]);
+        let ledger = Arc::new(UsageLedger::in_memory());
+        let attribution = UsageAttribution {
+            role: "operator".to_string(),
+            skill: Some("answer".to_string()),
+            conversation: Some("signal-_15550001".to_string()),
+        };
+        let runner = runner(&dir, provider).with_usage(ledger.clone(), attribution);
+        runner
+            .run(&Envelope::new("cli", "hi"), vec![ChatMessage::user("hi")])
+            .await
+            .unwrap();
+
+        let pricing = Default::default();
+        let rows = ledger.report(1, UsageGroup::Model, &pricing);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].key, "scripted");
+        assert_eq!(rows[0].requests, 2);
+        assert_eq!(rows[0].prompt_tokens, 160);
+        assert_eq!(
+            ledger.report(1, UsageGroup::Skill, &pricing)[0].key,
+            "answer"
+        );
+        assert_eq!(
+            ledger.report(1, UsageGroup::Conversation, &pricing)[0].key,
+            "signal-_15550001"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_provider_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let runner = runner(&dir, ScriptedProvider::new(vec![]));
+        let err = runner
+            .run(&Envelope::new("cli", "hi"), vec![ChatMessage::user("hi")])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AgentError::Llm(LlmError::Timeout)));
+    }
+
+    #[tokio::test]
+    async fn test_memory_injected_and_updated() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(MemoryStore::new(dir.path().join("memory")));
+        let provider = ScriptedProvider::new(vec![
+            response("stop", "Hello Ada.", vec![]),
+            response(
+                "stop",
+                r#"{"summary": "Ada introduced herself.", "facts": {"name": "Ada"}}"#,
+                vec![],
+            ),
+            response("stop", "You are Ada.", vec![]),
+            response("stop", "{}", vec![]),
+        ]);
+        let runner = runner(&dir, provider.clone())
+            .with_system("Be brief.")
+            .with_memory(store.clone(), "chat-1");
+
+        runner
+            .run(
+                &Envelope::new("cli", "hi"),
+                vec![ChatMessage::user("I'm Ada")],
+            )
+            .await
+            .unwrap();
+        let memory = store.load("chat-1").unwrap().unwrap();
+        assert_eq!(memory.facts["name"].value, "Ada");
+
+        // A later run, even in a fresh history, is told what was remembered.
+        runner
+            .run(
+                &Envelope::new("cli", "hi"),
+                vec![ChatMessage::user("What is my name?")],
+            )
+            .await
+            .unwrap();
+        let requests = provider.requests.lock().unwrap();
+        assert_eq!(requests[0].system.as_deref(), Some("Be brief."));
+        let system = requests[2].system.as_deref().unwrap();
+        assert!(system.starts_with("Be brief.\n\n"));
+        assert!(system.contains("Summary: Ada introduced herself."));
+        assert!(system.contains("- name: Ada"));
+        assert_eq!(store.load("chat-1").unwrap().unwrap().runs, 2);
+    }
+}
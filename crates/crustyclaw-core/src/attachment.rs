@@ -0,0 +1,491 @@
+//! Attachment spool — files passed from channels to skills and tools.
+//!
+//! A channel that receives files copies them into the spool with
+//! [`AttachmentSpool::store`], one directory per message under
+//! `[attachments] spool_dir`, and sends the resulting [`Attachment`]s along
+//! with the message's [`Envelope`](crate::message::Envelope). Skills and
+//! tools never see the spool itself: each attachment is bind-mounted
+//! read-only at `/attachments/<name>` in the sandbox that runs for the
+//! message (see [`SandboxConfig::with_attachments`]).
+//!
+//! The spool enforces its limits on every store:
+//!
+//! - message keys and file names are single `[A-Za-z0-9._-]` path components
+//! - files larger than `max_attachment_bytes` are rejected
+//! - at most `max_per_message` files are kept per message
+//! - the spool never grows past `max_spool_bytes`
+//!
+//! Spooled files are made read-only on the host as well, and message
+//! directories older than `retention_hours` are deleted by [`spawn`].
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use crustyclaw_config::AttachmentsConfig;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::daemon::ShutdownSignal;
+use crate::isolation::{SandboxConfig, SharedMount};
+use crate::workspace::{sanitize_name, sniff};
+
+/// Directory inside the sandbox where attachments are mounted.
+pub const ATTACHMENTS_MOUNT: &str = "/attachments";
+
+/// How often expired attachments are looked for.
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Content type used when nothing better is known.
+const OCTET_STREAM: &str = "application/octet-stream";
+
+/// Errors from spooling attachments.
+#[derive(Debug, thiserror::Error)]
+pub enum AttachmentError {
+    #[error("invalid {kind} {name:?}: use only letters, digits, '.', '_' and '-'")]
+    InvalidName { kind: &'static str, name: String },
+
+    #[error("attachment is {size} bytes, larger than the {limit}-byte limit")]
+    TooLarge { size: u64, limit: u64 },
+
+    #[error("message already has the maximum of {limit} attachments")]
+    TooMany { limit: usize },
+
+    #[error("attachment spool is full ({used} of {limit} bytes used, {size} more needed)")]
+    SpoolFull { size: u64, used: u64, limit: u64 },
+
+    #[error("message already has an attachment named {0:?}")]
+    Exists(String),
+
+    #[error("attachment source {0} is not a regular file")]
+    NotAFile(PathBuf),
+
+    #[error("attachment spool I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// A file received with a message, held in the spool.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    /// File name, unique within the message.
+    pub name: String,
+    /// Content type, from the file's magic bytes when recognized and the
+    /// sender's declaration otherwise.
+    pub content_type: String,
+    /// Size in bytes.
+    pub size: u64,
+    /// Location of the spooled file on the host.
+    pub path: PathBuf,
+}
+
+impl Attachment {
+    /// Where the attachment appears inside a sandbox.
+    pub fn guest_path(&self) -> PathBuf {
+        Path::new(ATTACHMENTS_MOUNT).join(&self.name)
+    }
+
+    /// The read-only mount exposing the attachment at its [`guest_path`](Self::guest_path).
+    pub fn mount(&self) -> SharedMount {
+        SharedMount::read_only(&self.path, self.guest_path())
+    }
+}
+
+impl SandboxConfig {
+    /// Builder: mount `attachments` read-only under `/attachments`.
+    pub fn with_attachments(self, attachments: &[Attachment]) -> Self {
+        attachments.iter().fold(self, |config, attachment| {
+            config.with_mount(attachment.mount())
+        })
+    }
+}
+
+/// Per-message attachment storage with centrally enforced quotas.
+#[derive(Debug)]
+pub struct AttachmentSpool {
+    dir: PathBuf,
+    max_attachment_bytes: u64,
+    max_per_message: usize,
+    max_spool_bytes: u64,
+    retention: Duration,
+    /// Held while checking the quotas and writing, so concurrent stores
+    /// cannot overrun them together.
+    lock: Mutex<()>,
+}
+
+impl AttachmentSpool {
+    /// Create a spool in `dir` with the default limits.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        let defaults = AttachmentsConfig::default();
+        Self {
+            dir: dir.into(),
+            max_attachment_bytes: defaults.max_attachment_bytes,
+            max_per_message: defaults.max_per_message,
+            max_spool_bytes: defaults.max_spool_bytes,
+            retention: Duration::from_secs(defaults.retention_hours * 60 * 60),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Create a spool from the `[attachments]` config section.
+    pub fn from_config(config: &AttachmentsConfig) -> Self {
+        Self::new(&config.spool_dir)
+            .with_max_attachment_bytes(config.max_attachment_bytes)
+            .with_max_per_message(config.max_per_message)
+            .with_max_spool_bytes(config.max_spool_bytes)
+            .with_retention(Duration::from_secs(config.retention_hours * 60 * 60))
+    }
+
+    /// Set the largest attachment accepted.
+    pub fn with_max_attachment_bytes(mut self, limit: u64) -> Self {
+        self.max_attachment_bytes = limit;
+        self
+    }
+
+    /// Set the most attachments kept per message.
+    pub fn with_max_per_message(mut self, limit: usize) -> Self {
+        self.max_per_message = limit;
+        self
+    }
+
+    /// Set the total size the spool may grow to.
+    pub fn with_max_spool_bytes(mut self, limit: u64) -> Self {
+        self.max_spool_bytes = limit;
+        self
+    }
+
+    /// Set how long attachments are kept.
+    pub fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Directory holding the spool.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Copy the host file `source` into the spool as attachment `name` of
+    /// the message identified by `message`.
+    ///
+    /// `name` is sanitized the way workspace file names are; `message` must
+    /// already be a valid key (see [`message_key`]). `content_type` is the
+    /// type declared by the sender, if any.
+    pub fn store(
+        &self,
+        message: &str,
+        name: &str,
+        content_type: Option<&str>,
+        source: &Path,
+    ) -> Result<Attachment, AttachmentError> {
+        validate_name("message key", message)?;
+        let name = sanitize_name(name);
+        let metadata = std::fs::metadata(source)?;
+        if !metadata.is_file() {
+            return Err(AttachmentError::NotAFile(source.to_path_buf()));
+        }
+        let size = metadata.len();
+        if size > self.max_attachment_bytes {
+            return Err(AttachmentError::TooLarge {
+                size,
+                limit: self.max_attachment_bytes,
+            });
+        }
+
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = self.dir.join(message);
+        let existing = count_files(&dir)?;
+        if existing >= self.max_per_message {
+            return Err(AttachmentError::TooMany {
+                limit: self.max_per_message,
+            });
+        }
+        let path = dir.join(&name);
+        if path.exists() {
+            return Err(AttachmentError::Exists(name));
+        }
+        let used = self.usage()?;
+        if used + size > self.max_spool_bytes {
+            return Err(AttachmentError::SpoolFull {
+                size,
+                used,
+                limit: self.max_spool_bytes,
+            });
+        }
+
+        std::fs::create_dir_all(&dir)?;
+        // Copy to a temporary name first so readers never see a partial file.
+        let tmp = dir.join(format!(".{name}.partial"));
+        std::fs::copy(source, &tmp)?;
+        let mut permissions = std::fs::metadata(&tmp)?.permissions();
+        permissions.set_readonly(true);
+        std::fs::set_permissions(&tmp, permissions)?;
+        std::fs::rename(&tmp, &path)?;
+
+        let content_type = detect_content_type(&path, content_type)?;
+        info!(message, %name, size, %content_type, "Attachment spooled");
+        Ok(Attachment {
+            name,
+            content_type,
+            size,
+            path,
+        })
+    }
+
+    /// Total size of the spooled files, in bytes.
+    pub fn usage(&self) -> std::io::Result<u64> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e),
+        };
+        let mut total = 0;
+        for entry in entries {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            for file in std::fs::read_dir(entry.path())? {
+                let metadata = file?.metadata()?;
+                if metadata.is_file() {
+                    total += metadata.len();
+                }
+            }
+        }
+        Ok(total)
+    }
+
+    /// Delete the message directories last written to longer than the
+    /// retention period ago. Returns the number deleted.
+    pub fn cleanup(&self) -> std::io::Result<usize> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e),
+        };
+        let now = SystemTime::now();
+        let mut removed = 0;
+        for entry in entries {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if !metadata.is_dir() {
+                continue;
+            }
+            let age = now.duration_since(metadata.modified()?).unwrap_or_default();
+            if age >= self.retention {
+                std::fs::remove_dir_all(entry.path())?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// A spool key for a message from `sender` on `channel`, identified on
+/// that channel by `id` (e.g. a Signal timestamp).
+pub fn message_key(channel: &str, sender: &str, id: impl std::fmt::Display) -> String {
+    sanitize_name(&format!("{channel}-{sender}-{id}"))
+}
+
+/// Spawn the cleanup task: expired attachments are deleted on start and
+/// every hour after that until shutdown. File I/O runs on the blocking pool.
+pub fn spawn(
+    spool: Arc<AttachmentSpool>,
+    mut shutdown_rx: broadcast::Receiver<ShutdownSignal>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut cleanup = tokio::time::interval(CLEANUP_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.recv() => break,
+                _ = cleanup.tick() => {
+                    let spool = spool.clone();
+                    match tokio::task::spawn_blocking(move || spool.cleanup()).await {
+                        Ok(Ok(0)) => {}
+                        Ok(Ok(n)) => info!(removed = n, "Removed expired attachments"),
+                        Ok(Err(e)) => warn!(error = %e, "Failed to clean up attachments"),
+                        Err(e) => warn!(error = %e, "Attachment cleanup panicked"),
+                    }
+                }
+            }
+        }
+        debug!("Attachment cleanup stopped");
+    })
+}
+
+/// Number of attachments already spooled in a message directory.
+fn count_files(dir: &Path) -> std::io::Result<usize> {
+    match std::fs::read_dir(dir) {
+        Ok(entries) => Ok(entries
+            .filter_map(Result::ok)
+            .filter(|e| !e.file_name().to_string_lossy().starts_with('.'))
+            .count()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(e),
+    }
+}
+
+/// The content type of a spooled file: its magic bytes when recognized,
+/// else the declared type, else `application/octet-stream`.
+fn detect_content_type(path: &Path, declared: Option<&str>) -> std::io::Result<String> {
+    use std::io::Read;
+
+    let mut head = Vec::with_capacity(512);
+    std::fs::File::open(path)?
+        .take(512)
+        .read_to_end(&mut head)?;
+    let declared = declared
+        .and_then(|t| t.split(';').next())
+        .map(|t| t.trim().to_ascii_lowercase())
+        .filter(|t| t.contains('/'));
+    Ok(sniff(&head)
+        .map(str::to_string)
+        .or(declared)
+        .unwrap_or_else(|| OCTET_STREAM.to_string()))
+}
+
+/// Check a message key is a single safe path component.
+fn validate_name(kind: &'static str, name: &str) -> Result<(), AttachmentError> {
+    if !name.is_empty() && !name.starts_with('.') && sanitize_name(name) == name {
+        Ok(())
+    } else {
+        Err(AttachmentError::InvalidName {
+            kind,
+            name: name.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spool() -> (tempfile::TempDir, AttachmentSpool) {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = AttachmentSpool::new(dir.path().join("attachments"));
+        (dir, spool)
+    }
+
+    fn source(dir: &tempfile::TempDir, name: &str, data: &[u8]) -> PathBuf {
+        let path = dir.path().join(name);
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_store_attachment() {
+        let (tmp, spool) = spool();
+        let png = source(&tmp, "download", b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR");
+        let key = message_key("signal", "+15550001", 1_700_000_000_000u64);
+        assert_eq!(key, "signal-_15550001-1700000000000");
+
+        let attachment = spool
+            .store(
+                &key,
+                "../holiday photo.png",
+                Some("application/octet-stream"),
+                &png,
+            )
+            .unwrap();
+        assert_eq!(attachment.name, "_holiday_photo.png");
+        assert_eq!(attachment.content_type, "image/png");
+        assert_eq!(attachment.size, 16);
+        assert!(attachment.path.starts_with(spool.dir().join(&key)));
+        assert!(
+            std::fs::metadata(&attachment.path)
+                .unwrap()
+                .permissions()
+                .readonly()
+        );
+        assert_eq!(spool.usage().unwrap(), 16);
+
+        let text = source(&tmp, "notes", b"meeting notes");
+        let notes = spool
+            .store(&key, "notes.txt", Some("text/plain; charset=utf-8"), &text)
+            .unwrap();
+        assert_eq!(notes.content_type, "text/plain");
+
+        assert!(matches!(
+            spool.store(&key, "notes.txt", None, &text),
+            Err(AttachmentError::Exists(_))
+        ));
+        assert!(matches!(
+            spool.store("../escape", "notes.txt", None, &text),
+            Err(AttachmentError::InvalidName { .. })
+        ));
+        assert!(matches!(
+            spool.store(&key, "dir", None, tmp.path()),
+            Err(AttachmentError::NotAFile(_))
+        ));
+    }
+
+    #[test]
+    fn test_store_enforces_quotas() {
+        let (tmp, spool) = spool();
+        let spool = spool
+            .with_max_attachment_bytes(10)
+            .with_max_per_message(2)
+            .with_max_spool_bytes(15);
+        let small = source(&tmp, "small", b"12345");
+        let large = source(&tmp, "large", b"12345678901");
+
+        assert!(matches!(
+            spool.store("m1", "large", None, &large),
+            Err(AttachmentError::TooLarge {
+                size: 11,
+                limit: 10
+            })
+        ));
+        spool.store("m1", "a", None, &small).unwrap();
+        spool.store("m1", "b", None, &small).unwrap();
+        assert!(matches!(
+            spool.store("m1", "c", None, &small),
+            Err(AttachmentError::TooMany { limit: 2 })
+        ));
+        spool.store("m2", "a", None, &small).unwrap();
+        assert!(matches!(
+            spool.store("m3", "a", None, &small),
+            Err(AttachmentError::SpoolFull {
+                size: 5,
+                used: 15,
+                limit: 15
+            })
+        ));
+    }
+
+    #[test]
+    fn test_cleanup_removes_expired_messages() {
+        let (tmp, spool) = spool();
+        let file = source(&tmp, "file", b"data");
+        spool.store("m1", "file.txt", None, &file).unwrap();
+
+        assert_eq!(spool.cleanup().unwrap(), 0);
+        assert_eq!(spool.usage().unwrap(), 4);
+
+        let spool = spool.with_retention(Duration::ZERO);
+        assert_eq!(spool.cleanup().unwrap(), 1);
+        assert_eq!(spool.usage().unwrap(), 0);
+        assert_eq!(
+            AttachmentSpool::new(tmp.path().join("none"))
+                .cleanup()
+                .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_sandbox_mounts_are_read_only() {
+        let attachment = Attachment {
+            name: "notes.txt".to_string(),
+            content_type: "text/plain".to_string(),
+            size: 13,
+            path: PathBuf::from("/spool/m1/notes.txt"),
+        };
+        let config = SandboxConfig::new("skill").with_attachments(&[attachment]);
+        assert_eq!(config.mounts.len(), 1);
+        let mount = &config.mounts[0];
+        assert_eq!(mount.host_path, Path::new("/spool/m1/notes.txt"));
+        assert_eq!(mount.guest_path, Path::new("/attachments/notes.txt"));
+        assert_eq!(mount.access, crate::isolation::MountAccess::ReadOnly);
+    }
+}
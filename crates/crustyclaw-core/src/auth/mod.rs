@@ -18,10 +18,29 @@
 //!             → .authorize_with_policy() // evaluates against policy engine
 //!                 → Session<Authorized>
 //! ```
+//!
+//! ## Session Tokens
+//!
+//! With `auth.mode = "token"`, `crustyclaw login` runs the flow above once
+//! and saves the resulting roles in a signed token (see [`token`]). The IPC
+//! server then authenticates each request from its token instead:
+//!
+//! ```text
+//! Session<Unauthenticated>
+//!     → .authenticate_token(token, key) // verifies signature and expiry
+//!         → Session<Authenticated>
+//!             → .authorize_from_token()  // grants the roles in the token
+//!                 → Session<Authorized>
+//! ```
+
+pub mod token;
 
+use std::collections::HashMap;
 use std::marker::PhantomData;
 
-use crustyclaw_config::policy::PolicyEngine;
+use crustyclaw_config::policy::{PolicyEngine, RequestContext};
+
+use self::token::{TokenClaims, TokenError, TokenKey};
 
 /// The local OS identity of the current process owner.
 ///
@@ -89,6 +108,30 @@ impl LocalIdentity {
         }
     }
 
+    /// Resolve the identity of another local user from their UID, e.g. the
+    /// peer of an IPC connection.
+    ///
+    /// The username is looked up in `/etc/passwd`; a UID without an entry
+    /// is named `uid:<uid>`.
+    pub fn from_uid(uid: u32, gid: u32) -> Self {
+        let username = std::fs::read_to_string("/etc/passwd")
+            .ok()
+            .and_then(|passwd| username_for_uid(&passwd, uid))
+            .unwrap_or_else(|| format!("uid:{uid}"));
+        Self::from_parts(username, uid, gid)
+    }
+
+    /// The policy roles for this identity: the `role_map` entry for the
+    /// username, if any, followed by [`default_role`](Self::default_role).
+    pub fn roles(&self, role_map: &HashMap<String, String>) -> Vec<String> {
+        let mut roles: Vec<String> = role_map.get(&self.username).cloned().into_iter().collect();
+        let default = self.default_role().to_string();
+        if !roles.contains(&default) {
+            roles.push(default);
+        }
+        roles
+    }
+
     /// Map this OS identity to a policy role name.
     ///
     /// Mapping rules (in priority order):
@@ -136,6 +179,16 @@ impl LocalIdentity {
     }
 }
 
+/// Find the username for `uid` in the text of `/etc/passwd`.
+fn username_for_uid(passwd: &str, uid: u32) -> Option<String> {
+    passwd.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        let name = fields.next()?;
+        let entry_uid = fields.nth(1)?.parse::<u32>().ok()?;
+        (entry_uid == uid && !name.is_empty()).then(|| name.to_string())
+    })
+}
+
 /// Unauthenticated state — no credentials have been verified.
 pub struct Unauthenticated;
 
@@ -144,6 +197,8 @@ pub struct Authenticated {
     pub identity: String,
     /// The local OS identity, if authenticated via `authenticate_local`.
     pub local_identity: Option<LocalIdentity>,
+    /// The verified token claims, if authenticated via `authenticate_token`.
+    pub token: Option<TokenClaims>,
 }
 
 /// Authorized state — identity verified and permissions granted.
@@ -182,6 +237,7 @@ impl Session<Unauthenticated> {
             state: Authenticated {
                 identity,
                 local_identity: None,
+                token: None,
             },
         }
     }
@@ -199,9 +255,30 @@ impl Session<Unauthenticated> {
             state: Authenticated {
                 identity,
                 local_identity: Some(local),
+                token: None,
             },
         }
     }
+
+    /// Authenticate with a session token minted by the daemon for
+    /// `crustyclaw login`.
+    ///
+    /// Fails if the token was not signed by `key` or has expired.
+    pub fn authenticate_token(
+        self,
+        token: &str,
+        key: &TokenKey,
+    ) -> Result<Session<Authenticated>, TokenError> {
+        let claims = key.verify(token, token::now())?;
+        Ok(Session {
+            _state: PhantomData,
+            state: Authenticated {
+                identity: claims.identity.clone(),
+                local_identity: None,
+                token: Some(claims),
+            },
+        })
+    }
 }
 
 impl Default for Session<Unauthenticated> {
@@ -221,6 +298,11 @@ impl Session<Authenticated> {
         self.state.local_identity.as_ref()
     }
 
+    /// Get the token claims, if this session was authenticated by token.
+    pub fn token_claims(&self) -> Option<&TokenClaims> {
+        self.state.token.as_ref()
+    }
+
     /// Authorize the session with the given roles.
     pub fn authorize(self, roles: Vec<String>) -> Session<Authorized> {
         Session {
@@ -253,13 +335,17 @@ impl Session<Authenticated> {
         // Check if the policy grants additional roles.
         // Convention: a rule allowing (role, "auth", "session") means
         // that identity can assume that role.
+        let identity = &self.state.identity;
+        let session = RequestContext::for_request("auth", "session")
+            .with("caller.identity", identity.as_str());
         for candidate in &["admin", "operator", "user", "viewer"] {
-            if engine.is_allowed(candidate, "auth", "session")
+            if engine.is_allowed_with_context(candidate, "auth", "session", &session)
                 && !roles.contains(&candidate.to_string())
             {
                 // Only grant if the identity matches or a wildcard rule applies
-                let identity = &self.state.identity;
-                if engine.is_allowed(identity, "assume", candidate) {
+                let assume = RequestContext::for_request("assume", candidate)
+                    .with("caller.identity", identity.as_str());
+                if engine.is_allowed_with_context(identity, "assume", candidate, &assume) {
                     roles.push(candidate.to_string());
                 }
             }
@@ -274,6 +360,21 @@ impl Session<Authenticated> {
             },
         }
     }
+
+    /// Authorize the session with the roles recorded in its token.
+    ///
+    /// The daemon resolved the roles from `auth.role_map` when it minted
+    /// the token, and only it holds the signing key, so they are granted
+    /// as-is. A session not authenticated by token gets no roles.
+    pub fn authorize_from_token(self) -> Session<Authorized> {
+        let roles = self
+            .state
+            .token
+            .as_ref()
+            .map(|claims| claims.roles.clone())
+            .unwrap_or_default();
+        self.authorize(roles)
+    }
 }
 
 impl Session<Authorized> {
@@ -382,4 +483,70 @@ mod tests {
         assert!(authorized.local_identity().is_some());
         assert_eq!(authorized.local_identity().unwrap().username, username);
     }
+
+    #[test]
+    fn test_authenticate_token() {
+        use token::TokenClaims;
+
+        let key = TokenKey::from_bytes(&[3; 32]);
+        let claims = TokenClaims {
+            identity: "alice".to_string(),
+            roles: vec!["alice".to_string(), "operator".to_string()],
+            issued_at: token::now(),
+            expires_at: token::now() + 60,
+        };
+        let authed = Session::new()
+            .authenticate_token(&key.sign(&claims), &key)
+            .unwrap();
+        assert_eq!(authed.identity(), "alice");
+        assert_eq!(authed.token_claims(), Some(&claims));
+        assert!(authed.local_identity().is_none());
+
+        let authorized = authed.authorize_from_token();
+        assert_eq!(authorized.roles(), &["alice", "operator"]);
+
+        let expired = TokenClaims {
+            expires_at: token::now() - 1,
+            ..claims
+        };
+        assert!(matches!(
+            Session::new().authenticate_token(&key.sign(&expired), &key),
+            Err(TokenError::Expired)
+        ));
+    }
+
+    #[test]
+    fn test_authorize_from_token_without_token() {
+        let session = Session::new()
+            .authenticate("bob".to_string())
+            .authorize_from_token();
+        assert!(session.roles().is_empty());
+    }
+
+    #[test]
+    fn test_username_for_uid() {
+        let passwd = "root:x:0:0:root:/root:/bin/bash\n\
+                      # comment\n\
+                      alice:x:1000:1000:Alice:/home/alice:/bin/sh\n";
+        assert_eq!(username_for_uid(passwd, 0).as_deref(), Some("root"));
+        assert_eq!(username_for_uid(passwd, 1000).as_deref(), Some("alice"));
+        assert_eq!(username_for_uid(passwd, 1001), None);
+
+        let unknown = LocalIdentity::from_uid(u32::MAX - 1, 0);
+        assert_eq!(unknown.username, format!("uid:{}", u32::MAX - 1));
+    }
+
+    #[test]
+    fn test_local_identity_roles() {
+        let role_map = HashMap::from([
+            ("alice".to_string(), "operator".to_string()),
+            ("root".to_string(), "admin".to_string()),
+        ]);
+        let alice = LocalIdentity::from_parts("alice", 1000, 1000);
+        assert_eq!(alice.roles(&role_map), ["operator", "alice"]);
+        let root = LocalIdentity::from_parts("root", 0, 0);
+        assert_eq!(root.roles(&role_map), ["admin"]);
+        let bob = LocalIdentity::from_parts("bob", 1001, 1001);
+        assert_eq!(bob.roles(&role_map), ["bob"]);
+    }
 }
@@ -0,0 +1,339 @@
+//! Session tokens for `auth.mode = "token"`.
+//!
+//! `crustyclaw login` asks the daemon for a token over `POST /auth/login`.
+//! The daemon identifies the caller by the peer credentials of the socket
+//! connection, resolves their roles through `auth.role_map` the same way
+//! local authentication does, and signs them; the CLI writes the token to
+//! `auth.token_path`. Clients send it as `Authorization: Bearer <token>` on
+//! every IPC request, and the daemon verifies it with
+//! [`Session::authenticate_token`](super::Session::authenticate_token).
+//!
+//! A token is `<claims>.<signature>`: the hex-encoded JSON [`TokenClaims`],
+//! and the hex-encoded HMAC-SHA256 of that first part under the daemon key
+//! at `auth.token_key_path`. Anyone who can read the key can mint tokens,
+//! so the key file is created with mode `0600` and should stay readable only
+//! by the daemon user.
+
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crustyclaw_config::AppConfig;
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+/// Name of the key file under `daemon.state_dir` when `auth.token_key_path`
+/// is unset.
+pub const KEY_FILE: &str = "auth.key";
+
+/// Length of a generated key, in bytes.
+const KEY_LEN: usize = 32;
+
+/// What a session token asserts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenClaims {
+    /// Who logged in (the OS username).
+    pub identity: String,
+    /// Roles the daemon resolved for `identity` at login.
+    pub roles: Vec<String>,
+    /// Unix time the token was minted.
+    pub issued_at: u64,
+    /// Unix time after which the token is rejected.
+    pub expires_at: u64,
+}
+
+/// Errors from minting or verifying session tokens.
+#[derive(Debug, thiserror::Error)]
+pub enum TokenError {
+    #[error("malformed session token")]
+    Malformed,
+
+    #[error("session token signature is invalid")]
+    BadSignature,
+
+    #[error("session token expired; run `crustyclaw login`")]
+    Expired,
+
+    #[error("token key {path}: {source}")]
+    KeyIo { path: PathBuf, source: io::Error },
+
+    #[error("token key {path} must contain at least {KEY_LEN} bytes of hex")]
+    BadKey { path: PathBuf },
+}
+
+/// The daemon key that signs and verifies session tokens.
+#[derive(Debug)]
+pub struct TokenKey {
+    key: hmac::Key,
+}
+
+impl TokenKey {
+    /// A key from raw bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        Self {
+            key: hmac::Key::new(hmac::HMAC_SHA256, bytes),
+        }
+    }
+
+    /// Load the hex-encoded key at `path`, generating and writing a new
+    /// random key (mode `0600`) if the file does not exist.
+    pub fn load_or_create(path: &Path) -> Result<Self, TokenError> {
+        let io_error = |source| TokenError::KeyIo {
+            path: path.to_path_buf(),
+            source,
+        };
+        match std::fs::read_to_string(path) {
+            Ok(mut text) => {
+                let bytes = decode_hex(text.trim());
+                text.zeroize();
+                match bytes {
+                    Some(mut bytes) if bytes.len() >= KEY_LEN => {
+                        let key = Self::from_bytes(&bytes);
+                        bytes.zeroize();
+                        Ok(key)
+                    }
+                    _ => Err(TokenError::BadKey {
+                        path: path.to_path_buf(),
+                    }),
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let mut bytes = [0u8; KEY_LEN];
+                SystemRandom::new()
+                    .fill(&mut bytes)
+                    .map_err(|_| io_error(io::Error::other("no system randomness")))?;
+                let mut text = encode_hex(&bytes);
+                let written = write_private(path, text.as_bytes(), false);
+                text.zeroize();
+                let key = Self::from_bytes(&bytes);
+                bytes.zeroize();
+                match written {
+                    Ok(()) => Ok(key),
+                    // Another daemon process created it first.
+                    Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                        Self::load_or_create(path)
+                    }
+                    Err(e) => Err(io_error(e)),
+                }
+            }
+            Err(e) => Err(io_error(e)),
+        }
+    }
+
+    /// Sign `claims` into a token.
+    pub fn sign(&self, claims: &TokenClaims) -> String {
+        let payload = encode_hex(&serde_json::to_vec(claims).unwrap_or_default());
+        let tag = hmac::sign(&self.key, payload.as_bytes());
+        format!("{payload}.{}", encode_hex(tag.as_ref()))
+    }
+
+    /// Check the signature and expiry of `token` at Unix time `now`.
+    pub fn verify(&self, token: &str, now: u64) -> Result<TokenClaims, TokenError> {
+        let (payload, signature) = token.trim().split_once('.').ok_or(TokenError::Malformed)?;
+        let signature = decode_hex(signature).ok_or(TokenError::Malformed)?;
+        hmac::verify(&self.key, payload.as_bytes(), &signature)
+            .map_err(|_| TokenError::BadSignature)?;
+        let claims: TokenClaims = decode_hex(payload)
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .ok_or(TokenError::Malformed)?;
+        if now >= claims.expires_at {
+            return Err(TokenError::Expired);
+        }
+        Ok(claims)
+    }
+}
+
+/// The daemon key file for `config`.
+pub fn key_path(config: &AppConfig) -> PathBuf {
+    match &config.auth.token_key_path {
+        Some(path) => PathBuf::from(path),
+        None => Path::new(&config.daemon.state_dir).join(KEY_FILE),
+    }
+}
+
+/// The session token file for `config`.
+pub fn token_path(config: &AppConfig) -> PathBuf {
+    if let Some(path) = &config.auth.token_path {
+        return PathBuf::from(path);
+    }
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_else(|| PathBuf::from("."));
+    config_home.join("crustyclaw").join("token")
+}
+
+/// The token saved by `crustyclaw login`, if `config` is in token mode and
+/// one has been saved.
+pub fn load_token(config: &AppConfig) -> Option<String> {
+    if config.auth.mode != "token" {
+        return None;
+    }
+    let token = std::fs::read_to_string(token_path(config)).ok()?;
+    Some(token.trim().to_string()).filter(|t| !t.is_empty())
+}
+
+/// Save `token` to `path` with mode `0600`, replacing any previous token.
+pub fn save_token(path: &Path, token: &str) -> io::Result<()> {
+    write_private(path, format!("{token}\n").as_bytes(), true)
+}
+
+/// The current Unix time in seconds.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Write `content` to `path` readable only by the owner, creating parent
+/// directories. Fails with `AlreadyExists` if the file exists, unless
+/// `replace` is set.
+pub(crate) fn write_private(path: &Path, content: &[u8], replace: bool) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let tmp = path.with_file_name(format!(".{file_name}.{}.tmp", std::process::id()));
+    let _ = std::fs::remove_file(&tmp);
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let result = options
+        .open(&tmp)
+        .and_then(|mut file| file.write_all(content))
+        .and_then(|()| {
+            if replace {
+                std::fs::rename(&tmp, path)
+            } else {
+                // Fails if `path` exists, so a concurrent writer's key wins.
+                std::fs::hard_link(&tmp, path)
+            }
+        });
+    let _ = std::fs::remove_file(&tmp);
+    result
+}
+
+pub(crate) fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+pub(crate) fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(expires_at: u64) -> TokenClaims {
+        TokenClaims {
+            identity: "alice".to_string(),
+            roles: vec!["alice".to_string(), "admin".to_string()],
+            issued_at: 1_000,
+            expires_at,
+        }
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let key = TokenKey::from_bytes(&[7; KEY_LEN]);
+        let token = key.sign(&claims(2_000));
+        assert_eq!(key.verify(&token, 1_500).unwrap(), claims(2_000));
+        // Surrounding whitespace from the token file is ignored.
+        assert!(key.verify(&format!("{token}\n"), 1_500).is_ok());
+
+        assert!(matches!(
+            key.verify(&token, 2_000),
+            Err(TokenError::Expired)
+        ));
+        let other = TokenKey::from_bytes(&[8; KEY_LEN]);
+        assert!(matches!(
+            other.verify(&token, 1_500),
+            Err(TokenError::BadSignature)
+        ));
+    }
+
+    #[test]
+    fn test_tampered_token() {
+        let key = TokenKey::from_bytes(&[7; KEY_LEN]);
+        let token = key.sign(&claims(2_000));
+        let (_, signature) = token.split_once('.').unwrap();
+        let forged = TokenClaims {
+            roles: vec!["admin".to_string()],
+            ..claims(u64::MAX)
+        };
+        let payload = encode_hex(&serde_json::to_vec(&forged).unwrap());
+        assert!(matches!(
+            key.verify(&format!("{payload}.{signature}"), 1_500),
+            Err(TokenError::BadSignature)
+        ));
+
+        for bad in ["", "no-dot", "zz.zz", "abc.00"] {
+            assert!(key.verify(bad, 1_500).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_load_or_create_key() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("state/auth.key");
+
+        let key = TokenKey::load_or_create(&path).unwrap();
+        let token = key.sign(&claims(2_000));
+        // Loading again gives the same key.
+        let again = TokenKey::load_or_create(&path).unwrap();
+        assert!(again.verify(&token, 1_500).is_ok());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        std::fs::write(&path, "abcd\n").unwrap();
+        assert!(matches!(
+            TokenKey::load_or_create(&path),
+            Err(TokenError::BadKey { .. })
+        ));
+    }
+
+    #[test]
+    fn test_paths_and_saved_token() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let mut config = AppConfig::default();
+        config.daemon.state_dir = "/var/lib/crustyclaw".to_string();
+        assert_eq!(
+            key_path(&config),
+            PathBuf::from("/var/lib/crustyclaw/auth.key")
+        );
+
+        let token_file = tmp.path().join("token");
+        config.auth.token_path = Some(token_file.display().to_string());
+        assert_eq!(token_path(&config), token_file);
+
+        save_token(&token_file, "abc.def").unwrap();
+        // Only read in token mode.
+        assert_eq!(load_token(&config), None);
+        config.auth.mode = "token".to_string();
+        assert_eq!(load_token(&config).as_deref(), Some("abc.def"));
+    }
+}
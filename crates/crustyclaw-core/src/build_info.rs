@@ -1,7 +1,8 @@
 //! Build-time metadata embedded by the build script.
 //!
-//! Provides version, git commit hash, and build timestamp for use in
-//! status displays, logging, and diagnostics.
+//! Provides version, git commit hash, build timestamp, target, compiler
+//! version, and enabled cargo features for use in status displays, logging,
+//! diagnostics, and `GET /version`.
 
 /// The git commit hash at build time (short form).
 pub const GIT_HASH: &str = env!("CRUSTYCLAW_GIT_HASH");
@@ -15,6 +16,25 @@ pub const BUILD_PROFILE: &str = env!("CRUSTYCLAW_BUILD_PROFILE");
 /// The crate version from Cargo.toml.
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// The target triple the daemon was built for.
+pub const TARGET: &str = env!("CRUSTYCLAW_TARGET");
+
+/// The `rustc --version` of the compiler that built the daemon.
+pub const RUSTC_VERSION: &str = env!("CRUSTYCLAW_RUSTC_VERSION");
+
+/// The enabled cargo features of the core crate, comma-separated and sorted.
+pub const FEATURES: &str = env!("CRUSTYCLAW_FEATURES");
+
+/// The enabled cargo features of the core crate, sorted.
+pub fn features() -> Vec<&'static str> {
+    FEATURES.split(',').filter(|f| !f.is_empty()).collect()
+}
+
+/// The build timestamp as Unix epoch seconds; `0` if unknown.
+pub fn build_timestamp() -> u64 {
+    BUILD_TIMESTAMP.parse().unwrap_or_default()
+}
+
 /// Return a formatted version string including git hash and profile.
 ///
 /// Example: `"0.1.0 (abc1234, debug)"`
@@ -38,6 +58,26 @@ mod tests {
         assert!(!GIT_HASH.is_empty());
     }
 
+    #[test]
+    fn test_target_and_compiler() {
+        assert!(!TARGET.is_empty());
+        assert!(RUSTC_VERSION.starts_with("rustc "), "{RUSTC_VERSION}");
+        assert!(build_timestamp() > 0);
+    }
+
+    #[test]
+    fn test_features_match_cfg() {
+        let features = features();
+        assert_eq!(
+            features.contains(&"wasm-plugins"),
+            cfg!(feature = "wasm-plugins")
+        );
+        assert_eq!(features.contains(&"mlock"), cfg!(feature = "mlock"));
+        let mut sorted = features.clone();
+        sorted.sort();
+        assert_eq!(features, sorted);
+    }
+
     #[test]
     fn test_build_profile() {
         // In test mode, profile is "debug"
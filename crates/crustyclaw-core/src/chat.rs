@@ -0,0 +1,961 @@
+//! Interactive chat sessions — the agent loop behind `crustyclaw chat`.
+//!
+//! [`ChatService`] keeps each session's history in memory, owned by the
+//! identity that opened it, and runs every message through an
+//! [`AgentRunner`] offering only the tools the caller's roles may use (see
+//! [`tool_scope`]), narrowed further by the session's [`ToolFilter`]. Steps and streamed text are sent to an event channel as
+//! the run progresses, and a run in flight can be cancelled. Sessions idle
+//! for an hour are dropped. With a [`MemoryStore`], a session's summary
+//! and key facts outlive its history: they are kept under `chat-<session>`
+//! and given to the agent in later runs.
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use ring::rand::{SecureRandom, SystemRandom};
+use tokio::sync::{Notify, mpsc, watch};
+use tracing::info;
+
+use crustyclaw_config::AppConfig;
+use crustyclaw_config::policy::PolicyDecision;
+
+use crate::agent::{AgentError, AgentEvent, AgentOutcome, AgentRunner};
+use crate::context::{ToolExecutor, ToolRegistry, ToolTrust};
+use crate::daemon::SecretsRevision;
+use crate::isolation::{SandboxBackend, SandboxConfig, SandboxPool};
+use crate::llm::{self, ChatMessage, LlmProvider};
+use crate::mcp::McpHub;
+use crate::memory::MemoryStore;
+use crate::message::Envelope;
+use crate::metrics::Metrics;
+use crate::plugin::loader::PluginHost;
+#[cfg(feature = "wasm-plugins")]
+use crate::plugin::wasm::WasmPluginHost;
+use crate::quota::QuotaManager;
+use crate::secrets::SecretStore;
+use crate::storage::{AuditLog, Storage};
+use crate::usage::{UsageAttribution, UsageLedger};
+use crate::workspace::{WorkspaceError, WorkspaceStore};
+
+/// Channel name of chat envelopes.
+pub const CHANNEL: &str = "chat";
+
+/// Policy action checked for each tool; the resource is `tools/<name>`.
+pub const TOOL_ACTION: &str = "call";
+
+/// How long a session is kept without messages.
+const SESSION_IDLE: Duration = Duration::from_secs(60 * 60);
+
+/// Errors from chat sessions.
+#[derive(Debug, thiserror::Error)]
+pub enum ChatError {
+    #[error("chat session not found: {0}")]
+    NotFound(String),
+
+    #[error("chat message cancelled")]
+    Cancelled,
+
+    #[error(transparent)]
+    Agent(#[from] AgentError),
+
+    #[error(transparent)]
+    Workspace(#[from] WorkspaceError),
+}
+
+/// A tool a caller may use in a chat.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScopedTool {
+    pub name: String,
+    pub description: String,
+    pub trust: ToolTrust,
+}
+
+/// The tools offered to a caller, and the trust level needed to run them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolScope {
+    /// Highest trust level among `tools`.
+    pub trust: ToolTrust,
+    /// Names of the offered tools.
+    pub tools: BTreeSet<String>,
+}
+
+impl ToolScope {
+    /// Drop the tools `filter` does not permit, lowering `trust` to the
+    /// highest level among those left.
+    pub fn restrict(&mut self, filter: &ToolFilter, registry: &ToolRegistry) {
+        self.tools.retain(|name| filter.permits(name));
+        self.trust = self
+            .tools
+            .iter()
+            .filter_map(|name| registry.get(name))
+            .map(|tool| tool.trust)
+            .max()
+            .unwrap_or(ToolTrust::Public);
+    }
+}
+
+/// A session's own limits on the tools its caller may use.
+///
+/// A filter only ever narrows [`tool_scope`]: naming a tool in `allowed`
+/// does not offer it to a caller whose roles may not use it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ToolFilter {
+    /// Only these tools are offered; every tool in scope when `None`.
+    pub allowed: Option<BTreeSet<String>>,
+    /// These tools are never offered, even if `allowed`.
+    pub denied: BTreeSet<String>,
+}
+
+impl ToolFilter {
+    /// A filter from request lists; an absent allow list permits every tool.
+    pub fn new(allowed: Option<Vec<String>>, denied: Vec<String>) -> Self {
+        Self {
+            allowed: allowed.map(|names| names.into_iter().collect()),
+            denied: denied.into_iter().collect(),
+        }
+    }
+
+    /// Whether `name` may be offered.
+    pub fn permits(&self, name: &str) -> bool {
+        !self.denied.contains(name)
+            && self
+                .allowed
+                .as_ref()
+                .is_none_or(|allowed| allowed.contains(name))
+    }
+
+    /// Narrow this filter by `other`: the allow lists intersect and the
+    /// deny lists combine, so a resumed session never gains tools.
+    pub fn narrow(&mut self, other: &ToolFilter) {
+        if let Some(allowed) = &other.allowed {
+            self.allowed = Some(match self.allowed.take() {
+                Some(current) => current.intersection(allowed).cloned().collect(),
+                None => allowed.clone(),
+            });
+        }
+        self.denied.extend(other.denied.iter().cloned());
+    }
+}
+
+/// The enabled tools in `registry` a caller holding `roles` may use.
+///
+/// A `[[policy.rules]]` entry matching `(role, "call", "tools/<name>")`
+/// decides: the tool is offered if any role is allowed, and withheld if a
+/// rule denies and none allows. With no matching rule, the tool is offered
+/// when its trust level is at most the highest `[chat.tool_trust]` level of
+/// the caller's roles (`public` for unlisted roles).
+pub fn tool_scope(config: &AppConfig, roles: &[String], registry: &ToolRegistry) -> ToolScope {
+    let max_trust = roles
+        .iter()
+        .filter_map(|role| config.chat.tool_trust.get(role))
+        .filter_map(|name| ToolTrust::from_name(name))
+        .max()
+        .unwrap_or(ToolTrust::Public);
+    let mut engine = config.build_policy_engine();
+    let mut scope = ToolScope {
+        trust: ToolTrust::Public,
+        tools: BTreeSet::new(),
+    };
+    for name in registry.names() {
+        let Some(tool) = registry.get(&name).filter(|tool| tool.enabled) else {
+            continue;
+        };
+        let resource = format!("tools/{name}");
+        let decisions: Vec<PolicyDecision> = roles
+            .iter()
+            .map(|role| engine.evaluate(role, TOOL_ACTION, &resource).decision)
+            .collect();
+        let offered = if decisions.contains(&PolicyDecision::Allowed) {
+            true
+        } else if decisions.contains(&PolicyDecision::Denied) {
+            false
+        } else {
+            tool.trust <= max_trust
+        };
+        if offered {
+            scope.trust = scope.trust.max(tool.trust);
+            scope.tools.insert(name);
+        }
+    }
+    scope
+}
+
+/// The result of one chat message.
+#[derive(Debug, Clone)]
+pub struct ChatReply {
+    /// The agent's final answer.
+    pub reply: String,
+    /// Model round trips taken.
+    pub iterations: u32,
+    /// Tokens used across all round trips.
+    pub total_tokens: u32,
+}
+
+/// One session's history.
+struct ChatSession {
+    owner: String,
+    messages: Vec<ChatMessage>,
+    last_active: Instant,
+    /// Limits on the tools offered in this session.
+    filter: ToolFilter,
+    /// Wakes the session's runs in flight to cancel them.
+    cancel: Arc<Notify>,
+}
+
+/// The provider built from `[llm]`, kept until the config or one of the
+/// secrets holding its API keys changes.
+struct LlmCache {
+    config: watch::Receiver<AppConfig>,
+    secrets: Option<watch::Receiver<SecretsRevision>>,
+    /// The [`SecretsRevision::generation`] the provider was built after.
+    generation: u64,
+    provider: Option<Arc<dyn LlmProvider>>,
+}
+
+/// Runs chat sessions against the daemon's LLM provider and tools.
+pub struct ChatService {
+    config: watch::Receiver<AppConfig>,
+    tools: Arc<RwLock<ToolRegistry>>,
+    workspaces: Arc<WorkspaceStore>,
+    provider: Option<Arc<dyn LlmProvider>>,
+    llm: Mutex<LlmCache>,
+    mcp: Option<Arc<McpHub>>,
+    plugins: Option<Arc<PluginHost>>,
+    #[cfg(feature = "wasm-plugins")]
+    wasm: Option<Arc<WasmPluginHost>>,
+    sandbox: Option<(Arc<dyn SandboxBackend>, SandboxConfig)>,
+    pool: Option<Arc<SandboxPool>>,
+    secrets: Option<Arc<RwLock<SecretStore>>>,
+    quotas: Option<Arc<QuotaManager>>,
+    metrics: Option<Arc<Metrics>>,
+    usage: Option<Arc<UsageLedger>>,
+    memory: Option<Arc<MemoryStore>>,
+    storage: Option<Arc<dyn Storage>>,
+    sessions: Mutex<HashMap<String, ChatSession>>,
+}
+
+impl ChatService {
+    /// Create a service using the `[llm]` and `[chat]` settings of the
+    /// current config, the tools in `tools`, and a workspace per session
+    /// from `workspaces` as the root of the filesystem tools.
+    pub fn new(
+        config: watch::Receiver<AppConfig>,
+        tools: Arc<RwLock<ToolRegistry>>,
+        workspaces: Arc<WorkspaceStore>,
+    ) -> Self {
+        Self {
+            llm: Mutex::new(LlmCache {
+                config: config.clone(),
+                secrets: None,
+                generation: 0,
+                provider: None,
+            }),
+            config,
+            tools,
+            workspaces,
+            provider: None,
+            mcp: None,
+            plugins: None,
+            #[cfg(feature = "wasm-plugins")]
+            wasm: None,
+            sandbox: None,
+            pool: None,
+            secrets: None,
+            quotas: None,
+            metrics: None,
+            usage: None,
+            memory: None,
+            storage: None,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Builder: use `provider` instead of one built from `[llm]`.
+    pub fn with_provider(mut self, provider: Arc<dyn LlmProvider>) -> Self {
+        self.provider = Some(provider);
+        self
+    }
+
+    /// Builder: forward calls to tools imported from MCP servers.
+    pub fn with_mcp(mut self, mcp: Arc<McpHub>) -> Self {
+        self.mcp = Some(mcp);
+        self
+    }
+
+    /// Builder: forward calls to tools provided by native plugins.
+    pub fn with_plugins(mut self, plugins: Arc<PluginHost>) -> Self {
+        self.plugins = Some(plugins);
+        self
+    }
+
+    /// Builder: run tools provided by WASM action plugins.
+    #[cfg(feature = "wasm-plugins")]
+    pub fn with_wasm(mut self, wasm: Arc<WasmPluginHost>) -> Self {
+        self.wasm = Some(wasm);
+        self
+    }
+
+    /// Builder: run `run_command` with `backend`, starting from `config`.
+    pub fn with_sandbox(mut self, backend: Arc<dyn SandboxBackend>, config: SandboxConfig) -> Self {
+        self.sandbox = Some((backend, config));
+        self
+    }
+
+    /// Builder: take a slot in `pool` for each `run_command`.
+    pub fn with_pool(mut self, pool: Arc<SandboxPool>) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Builder: redact the secrets in `store` from `run_command` output
+    /// before it reaches the model.
+    pub fn with_secrets(mut self, store: Arc<RwLock<SecretStore>>) -> Self {
+        self.secrets = Some(store);
+        self
+    }
+
+    /// Builder: rebuild the `[llm]` provider when a secret named by
+    /// `api_key_secret` is reloaded or rotated. Secrets are read from the
+    /// store given to [`with_secrets`](Self::with_secrets).
+    pub fn with_secrets_watcher(mut self, secrets: watch::Receiver<SecretsRevision>) -> Self {
+        let cache = self.llm.get_mut().unwrap_or_else(|e| e.into_inner());
+        cache.generation = secrets.borrow().generation;
+        cache.secrets = Some(secrets);
+        self
+    }
+
+    /// Builder: charge each message's tokens and sandbox executions to the
+    /// caller's first role.
+    pub fn with_quotas(mut self, quotas: Arc<QuotaManager>) -> Self {
+        self.quotas = Some(quotas);
+        self
+    }
+
+    /// Builder: count requests, tokens, latency, and refusals in `metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Builder: record each message's tokens in `ledger`, attributed to the
+    /// caller's first role and the session's conversation, `chat-<session>`.
+    pub fn with_usage(mut self, ledger: Arc<UsageLedger>) -> Self {
+        self.usage = Some(ledger);
+        self
+    }
+
+    /// Builder: keep each session's long-term memory in `store`, under the
+    /// session's conversation, `chat-<session>`.
+    pub fn with_memory(mut self, store: Arc<MemoryStore>) -> Self {
+        self.memory = Some(store);
+        self
+    }
+
+    /// Builder: append the LLM request log to the audit namespace of
+    /// `storage`. Without it the log is a file in the state directory.
+    pub fn with_storage(mut self, storage: Arc<dyn Storage>) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    /// The tools a caller holding `roles` may use, sorted by name.
+    pub fn tools(&self, roles: &[String]) -> Vec<ScopedTool> {
+        let config = self.config.borrow().clone();
+        let registry = self.tools.read().unwrap_or_else(|e| e.into_inner());
+        let scope = tool_scope(&config, roles, &registry);
+        scope
+            .tools
+            .iter()
+            .filter_map(|name| registry.get(name))
+            .map(|tool| ScopedTool {
+                name: tool.definition.name.clone(),
+                description: tool.definition.description.clone(),
+                trust: tool.trust,
+            })
+            .collect()
+    }
+
+    /// Resume `session` for `owner`, or start a new session when it is
+    /// `None`. Returns the session ID.
+    ///
+    /// A new session keeps `filter` for all its messages; resuming a
+    /// session narrows its filter by `filter`. A session owned by someone
+    /// else is reported as not found.
+    pub fn open(
+        &self,
+        owner: &str,
+        session: Option<&str>,
+        filter: &ToolFilter,
+    ) -> Result<String, ChatError> {
+        let mut sessions = self.sessions();
+        match session {
+            Some(id) => match sessions.get_mut(id) {
+                Some(existing) if existing.owner == owner => {
+                    existing.filter.narrow(filter);
+                    existing.last_active = Instant::now();
+                    Ok(id.to_string())
+                }
+                _ => Err(ChatError::NotFound(id.to_string())),
+            },
+            None => {
+                let id = new_session_id();
+                sessions.insert(
+                    id.clone(),
+                    ChatSession {
+                        owner: owner.to_string(),
+                        messages: Vec::new(),
+                        last_active: Instant::now(),
+                        filter: filter.clone(),
+                        cancel: Arc::new(Notify::new()),
+                    },
+                );
+                info!(session = %id, owner, "Chat session started");
+                Ok(id)
+            }
+        }
+    }
+
+    /// Run `message` in `session` to a final answer, sending each step and
+    /// the streamed text to `events`.
+    ///
+    /// The message and the agent's work are added to the session's history
+    /// only when the run succeeds. [`cancel`](Self::cancel) stops the run
+    /// with [`ChatError::Cancelled`].
+    pub async fn send(
+        &self,
+        owner: &str,
+        roles: &[String],
+        session: &str,
+        message: &str,
+        events: mpsc::UnboundedSender<AgentEvent>,
+    ) -> Result<ChatReply, ChatError> {
+        let (mut messages, filter, cancel) = {
+            let sessions = self.sessions();
+            match sessions.get(session) {
+                Some(existing) if existing.owner == owner => (
+                    existing.messages.clone(),
+                    existing.filter.clone(),
+                    existing.cancel.clone(),
+                ),
+                _ => return Err(ChatError::NotFound(session.to_string())),
+            }
+        };
+        messages.push(ChatMessage::user(message));
+
+        let runner = self.runner(roles, session, &filter, events)?;
+        let origin = Envelope::new(CHANNEL, message).with_peer(owner);
+        let AgentOutcome {
+            reply,
+            messages,
+            iterations,
+            usage,
+        } = tokio::select! {
+            outcome = runner.run(&origin, messages) => outcome?,
+            () = cancel.notified() => {
+                info!(session, "Chat message cancelled");
+                return Err(ChatError::Cancelled);
+            }
+        };
+
+        if let Some(existing) = self.sessions().get_mut(session) {
+            existing.messages = messages;
+            existing.last_active = Instant::now();
+        }
+        Ok(ChatReply {
+            reply,
+            iterations,
+            total_tokens: usage.total_tokens,
+        })
+    }
+
+    /// Cancel the runs in flight in `owner`'s `session`, if any. Their
+    /// messages are not added to the history.
+    pub fn cancel(&self, owner: &str, session: &str) -> Result<(), ChatError> {
+        match self.sessions().get(session) {
+            Some(existing) if existing.owner == owner => {
+                existing.cancel.notify_waiters();
+                Ok(())
+            }
+            _ => Err(ChatError::NotFound(session.to_string())),
+        }
+    }
+
+    /// Clear the history of `owner`'s `session`.
+    pub fn reset(&self, owner: &str, session: &str) -> Result<(), ChatError> {
+        match self.sessions().get_mut(session) {
+            Some(existing) if existing.owner == owner => {
+                existing.messages.clear();
+                existing.last_active = Instant::now();
+                Ok(())
+            }
+            _ => Err(ChatError::NotFound(session.to_string())),
+        }
+    }
+
+    /// Build the runner for one message, offering the tools in the
+    /// caller's scope that `filter` permits. Calls to any other tool are
+    /// refused when the model makes them.
+    fn runner(
+        &self,
+        roles: &[String],
+        session: &str,
+        filter: &ToolFilter,
+        events: mpsc::UnboundedSender<AgentEvent>,
+    ) -> Result<AgentRunner, ChatError> {
+        let config = self.config.borrow().clone();
+        let scope = {
+            let registry = self.tools.read().unwrap_or_else(|e| e.into_inner());
+            let mut scope = tool_scope(&config, roles, &registry);
+            scope.restrict(filter, &registry);
+            scope
+        };
+        let provider = self.provider.clone().unwrap_or_else(|| self.llm_provider());
+
+        let root = self.workspace(session)?;
+        let mut executor = ToolExecutor::new(self.tools.clone(), root);
+        if let Some(mcp) = &self.mcp {
+            executor = executor.with_mcp(mcp.clone());
+        }
+        if let Some(plugins) = &self.plugins {
+            executor = executor.with_plugins(plugins.clone());
+        }
+        #[cfg(feature = "wasm-plugins")]
+        if let Some(wasm) = &self.wasm {
+            executor = executor.with_wasm(wasm.clone());
+        }
+        if let Some((backend, sandbox)) = &self.sandbox {
+            executor = executor.with_sandbox(backend.clone(), sandbox.clone());
+        }
+        if let Some(pool) = &self.pool {
+            executor = executor.with_pool(pool.clone());
+        }
+        if let Some(secrets) = &self.secrets {
+            executor = executor.with_secrets(secrets.clone());
+        }
+
+        let mut runner = AgentRunner::new(provider, Arc::new(executor), &config.llm)
+            .with_trust(scope.trust)
+            .with_tools(scope.tools)
+            .with_max_iterations(config.chat.max_iterations)
+            .with_events(events);
+        if let Some(prompt) = &config.chat.system_prompt {
+            runner = runner.with_system(prompt);
+        }
+        if let Some(budget) = config.chat.token_budget {
+            runner = runner.with_token_budget(budget);
+        }
+        if let Some(secs) = config.chat.timeout_secs {
+            runner = runner.with_timeout(Duration::from_secs(secs));
+        }
+        if let (Some(quotas), Some(role)) = (&self.quotas, roles.first()) {
+            runner = runner.with_quota(quotas.clone(), role);
+        }
+        if let Some(metrics) = &self.metrics {
+            runner = runner.with_metrics(metrics.clone());
+        }
+        if let Some(ledger) = &self.usage {
+            let attribution = UsageAttribution {
+                role: roles.first().cloned().unwrap_or_default(),
+                skill: None,
+                conversation: Some(format!("{CHANNEL}-{session}")),
+            };
+            runner = runner.with_usage(ledger.clone(), attribution);
+        }
+        if let Some(memory) = &self.memory {
+            runner = runner.with_memory(memory.clone(), format!("{CHANNEL}-{session}"));
+        }
+        Ok(runner)
+    }
+
+    /// The session's workspace directory, created if missing.
+    fn workspace(&self, session: &str) -> Result<PathBuf, ChatError> {
+        let dir = self
+            .workspaces
+            .workspace_dir(&format!("{CHANNEL}-{session}"))?;
+        std::fs::create_dir_all(&dir).map_err(WorkspaceError::Io)?;
+        Ok(dir)
+    }
+
+    /// Lock the sessions, dropping idle ones.
+    /// The provider built from `[llm]`, rebuilt after a config reload or a
+    /// change to one of its key secrets.
+    fn llm_provider(&self) -> Arc<dyn LlmProvider> {
+        let mut cache = self.llm.lock().unwrap_or_else(|e| e.into_inner());
+        let LlmCache {
+            config,
+            secrets,
+            generation,
+            provider,
+        } = &mut *cache;
+        let mut stale = config.has_changed().unwrap_or(false);
+        let config = config.borrow_and_update().clone();
+        if let Some(secrets) = secrets {
+            let revision = secrets.borrow_and_update();
+            // Only the latest reload's names are published; if more than
+            // one went by, assume the keys changed.
+            if revision.generation > *generation + 1
+                || (revision.generation == *generation + 1
+                    && revision.affects(llm::key_secrets(&config.llm)))
+            {
+                stale = true;
+            }
+            *generation = revision.generation;
+        }
+        if let Some(provider) = provider.as_ref().filter(|_| !stale) {
+            return provider.clone();
+        }
+
+        let mut llm_config = config.llm.clone();
+        if let Some(store) = &self.secrets {
+            let store = store.read().unwrap_or_else(|e| e.into_inner());
+            llm::resolve_key_secrets(&mut llm_config, &store);
+        }
+        if llm_config.api_key.is_empty() {
+            llm_config.api_key = std::env::var("CRUSTYCLAW_LLM_API_KEY").unwrap_or_default();
+        }
+        let audit_log = match &self.storage {
+            Some(storage) => AuditLog::new(storage.clone(), llm::failover::AUDIT_FILE),
+            None => AuditLog::file(
+                PathBuf::from(&config.daemon.state_dir).join(llm::failover::AUDIT_FILE),
+            ),
+        };
+        let mut built = llm::failover_provider(&llm_config).with_audit_log(audit_log);
+        if let Some(metrics) = &self.metrics {
+            built = built.with_metrics(metrics.clone());
+        }
+        let built: Arc<dyn LlmProvider> = Arc::new(built);
+        *provider = Some(built.clone());
+        built
+    }
+
+    fn sessions(&self) -> std::sync::MutexGuard<'_, HashMap<String, ChatSession>> {
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        sessions.retain(|_, session| session.last_active.elapsed() < SESSION_IDLE);
+        sessions
+    }
+}
+
+/// A random 128-bit session ID, hex encoded.
+fn new_session_id() -> String {
+    let mut bytes = [0u8; 16];
+    // SystemRandom only fails if the OS has no entropy source.
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .expect("system random number generator failed");
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BoxFuture;
+    use crate::llm::{ChatRequest, ChatResponse, LlmError, StreamChunk, TokenUsage};
+
+    /// Streams a fixed answer and records the tools each request offered.
+    struct EchoProvider {
+        offered: Mutex<Vec<Vec<String>>>,
+    }
+
+    impl LlmProvider for EchoProvider {
+        fn name(&self) -> &str {
+            "echo"
+        }
+
+        fn chat(&self, _request: &ChatRequest) -> BoxFuture<'_, Result<ChatResponse, LlmError>> {
+            Box::pin(async { Err(LlmError::Request("streaming only".to_string())) })
+        }
+
+        fn chat_stream(
+            &self,
+            request: &ChatRequest,
+        ) -> BoxFuture<'_, Result<mpsc::Receiver<Result<StreamChunk, LlmError>>, LlmError>>
+        {
+            self.offered
+                .lock()
+                .unwrap()
+                .push(request.tools.iter().map(|t| t.name.clone()).collect());
+            let turns = request.messages.len();
+            Box::pin(async move {
+                let (tx, rx) = mpsc::channel(8);
+                tx.send(Ok(StreamChunk::Text(format!("{turns} message(s)"))))
+                    .await
+                    .unwrap();
+                tx.send(Ok(StreamChunk::Done {
+                    finish_reason: "stop".to_string(),
+                    usage: Some(TokenUsage {
+                        prompt_tokens: 5,
+                        completion_tokens: 5,
+                        total_tokens: 10,
+                    }),
+                    attempts: 1,
+                }))
+                .await
+                .unwrap();
+                Ok(rx)
+            })
+        }
+    }
+
+    /// Never answers.
+    struct StalledProvider;
+
+    impl LlmProvider for StalledProvider {
+        fn name(&self) -> &str {
+            "stalled"
+        }
+
+        fn chat(&self, _request: &ChatRequest) -> BoxFuture<'_, Result<ChatResponse, LlmError>> {
+            Box::pin(std::future::pending())
+        }
+
+        fn chat_stream(
+            &self,
+            _request: &ChatRequest,
+        ) -> BoxFuture<'_, Result<mpsc::Receiver<Result<StreamChunk, LlmError>>, LlmError>>
+        {
+            Box::pin(std::future::pending())
+        }
+    }
+
+    fn roles(names: &[&str]) -> Vec<String> {
+        names.iter().map(|r| r.to_string()).collect()
+    }
+
+    #[test]
+    fn test_tool_scope() {
+        let registry = ToolRegistry::with_defaults();
+        let config = AppConfig::default();
+
+        let user = tool_scope(&config, &roles(&["user"]), &registry);
+        assert_eq!(user.trust, ToolTrust::Public);
+        assert!(user.tools.contains("read_file"));
+        assert!(!user.tools.contains("run_command"));
+
+        let operator = tool_scope(&config, &roles(&["user", "operator"]), &registry);
+        assert_eq!(operator.trust, ToolTrust::Internal);
+        assert!(operator.tools.contains("run_command"));
+        assert!(!operator.tools.contains("daemon_status"));
+
+        // Policy rules override the trust levels in both directions.
+        let config = AppConfig::parse(
+            r#"
+            [[policy.rules]]
+            role = "user"
+            action = "call"
+            resource = "tools/daemon_status"
+            effect = "allow"
+
+            [[policy.rules]]
+            role = "*"
+            action = "call"
+            resource = "tools/search_code|tools/run_command"
+            effect = "deny"
+        "#,
+        )
+        .unwrap();
+        let user = tool_scope(&config, &roles(&["user"]), &registry);
+        assert_eq!(user.trust, ToolTrust::System);
+        assert!(user.tools.contains("daemon_status"));
+        assert!(!user.tools.contains("search_code"));
+        let admin = tool_scope(&config, &roles(&["admin"]), &registry);
+        assert!(!admin.tools.contains("run_command"));
+        assert!(!admin.tools.contains("daemon_status"));
+    }
+
+    #[tokio::test]
+    async fn test_sessions() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_config_tx, config_rx) = watch::channel(AppConfig::default());
+        let provider = Arc::new(EchoProvider {
+            offered: Mutex::new(Vec::new()),
+        });
+        let chat = ChatService::new(
+            config_rx,
+            Arc::new(RwLock::new(ToolRegistry::with_defaults())),
+            Arc::new(WorkspaceStore::new(dir.path())),
+        )
+        .with_provider(provider.clone());
+        let viewer = roles(&["viewer"]);
+
+        let session = chat.open("alice", None, &ToolFilter::default()).unwrap();
+        assert_eq!(session.len(), 32);
+        let (events_tx, mut events) = mpsc::unbounded_channel();
+        let reply = chat
+            .send("alice", &viewer, &session, "hi", events_tx.clone())
+            .await
+            .unwrap();
+        assert_eq!(reply.reply, "1 message(s)");
+        assert_eq!(reply.total_tokens, 10);
+        assert!(dir.path().join(format!("chat-{session}")).is_dir());
+        let mut streamed = String::new();
+        while let Ok(event) = events.try_recv() {
+            if let AgentEvent::Text { delta } = event {
+                streamed.push_str(&delta);
+            }
+        }
+        assert_eq!(streamed, "1 message(s)");
+
+        // History carries over: user, assistant, then the new user message.
+        let reply = chat
+            .send("alice", &viewer, &session, "again", events_tx.clone())
+            .await
+            .unwrap();
+        assert_eq!(reply.reply, "3 message(s)");
+        let offered = provider.offered.lock().unwrap()[0].clone();
+        assert!(offered.contains(&"read_file".to_string()));
+        assert!(!offered.contains(&"run_command".to_string()));
+
+        // Other identities cannot see the session.
+        assert!(matches!(
+            chat.open("mallory", Some(&session), &ToolFilter::default()),
+            Err(ChatError::NotFound(_))
+        ));
+        assert!(matches!(
+            chat.send("mallory", &viewer, &session, "hi", events_tx.clone())
+                .await,
+            Err(ChatError::NotFound(_))
+        ));
+        assert!(chat.reset("mallory", &session).is_err());
+
+        chat.reset("alice", &session).unwrap();
+        assert_eq!(
+            chat.open("alice", Some(&session), &ToolFilter::default())
+                .unwrap(),
+            session
+        );
+        let reply = chat
+            .send("alice", &viewer, &session, "fresh", events_tx)
+            .await
+            .unwrap();
+        assert_eq!(reply.reply, "1 message(s)");
+    }
+
+    #[tokio::test]
+    async fn test_session_tool_filter() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_config_tx, config_rx) = watch::channel(AppConfig::default());
+        let provider = Arc::new(EchoProvider {
+            offered: Mutex::new(Vec::new()),
+        });
+        let chat = ChatService::new(
+            config_rx,
+            Arc::new(RwLock::new(ToolRegistry::with_defaults())),
+            Arc::new(WorkspaceStore::new(dir.path())),
+        )
+        .with_provider(provider.clone());
+        let viewer = roles(&["viewer"]);
+        let (events_tx, _events) = mpsc::unbounded_channel();
+
+        // The allow list cannot add run_command to a viewer's scope.
+        let filter = ToolFilter::new(
+            Some(roles(&["read_file", "list_files", "run_command"])),
+            roles(&["list_files"]),
+        );
+        let session = chat.open("alice", None, &filter).unwrap();
+        chat.send("alice", &viewer, &session, "hi", events_tx.clone())
+            .await
+            .unwrap();
+        assert_eq!(provider.offered.lock().unwrap()[0], ["read_file"]);
+
+        // Resuming narrows the filter; it never widens it.
+        let widen = ToolFilter::new(Some(roles(&["list_files", "search_code"])), Vec::new());
+        chat.open("alice", Some(&session), &widen).unwrap();
+        chat.send("alice", &viewer, &session, "again", events_tx)
+            .await
+            .unwrap();
+        assert!(provider.offered.lock().unwrap()[1].is_empty());
+
+        let mut filter = ToolFilter::new(None, roles(&["run_command"]));
+        assert!(filter.permits("read_file"));
+        assert!(!filter.permits("run_command"));
+        filter.narrow(&ToolFilter::new(Some(roles(&["read_file"])), Vec::new()));
+        assert!(!filter.permits("list_files"));
+    }
+
+    #[tokio::test]
+    async fn test_cancel() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_config_tx, config_rx) = watch::channel(AppConfig::default());
+        let chat = ChatService::new(
+            config_rx,
+            Arc::new(RwLock::new(ToolRegistry::with_defaults())),
+            Arc::new(WorkspaceStore::new(dir.path())),
+        )
+        .with_provider(Arc::new(StalledProvider));
+        let user = roles(&["user"]);
+        let session = chat.open("alice", None, &ToolFilter::default()).unwrap();
+
+        let (events_tx, _events) = mpsc::unbounded_channel();
+        let (result, ()) = tokio::join!(
+            chat.send("alice", &user, &session, "hi", events_tx),
+            async {
+                // Only the owner may cancel; the first poll of `send` has
+                // started the run by now.
+                assert!(chat.cancel("mallory", &session).is_err());
+                chat.cancel("alice", &session).unwrap();
+            }
+        );
+        assert!(matches!(result, Err(ChatError::Cancelled)));
+
+        // Nothing was recorded, and cancelling an idle session is a no-op.
+        assert!(chat.sessions().get(&session).unwrap().messages.is_empty());
+        chat.cancel("alice", &session).unwrap();
+    }
+
+    #[test]
+    fn test_llm_provider_rebuilt_on_key_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = AppConfig::parse(
+            r#"
+            [llm]
+            api_key_secret = "llm_key"
+
+            [[secrets.entries]]
+            name = "llm_key"
+            source = "env"
+            env_var = "CRUSTYCLAW_TEST_LLM_KEY"
+            inject_env = "CRUSTYCLAW_TEST_LLM_KEY"
+        "#,
+        )
+        .unwrap();
+        let (config_tx, config_rx) = watch::channel(config);
+        let (secrets_tx, secrets_rx) = watch::channel(SecretsRevision::default());
+        let chat = ChatService::new(
+            config_rx,
+            Arc::new(RwLock::new(ToolRegistry::with_defaults())),
+            Arc::new(WorkspaceStore::new(dir.path())),
+        )
+        .with_secrets(Arc::new(RwLock::new(SecretStore::new())))
+        .with_secrets_watcher(secrets_rx);
+        let rotate = |names: &[&str]| {
+            secrets_tx.send_modify(|rev| {
+                rev.generation += 1;
+                rev.changed = names.iter().map(|n| n.to_string()).collect();
+            })
+        };
+
+        let first = chat.llm_provider();
+        assert!(Arc::ptr_eq(&first, &chat.llm_provider()));
+
+        // Another secret changed: the provider is kept.
+        rotate(&["webhook_key"]);
+        assert!(Arc::ptr_eq(&first, &chat.llm_provider()));
+
+        // Its key changed: rebuilt.
+        rotate(&["llm_key"]);
+        let second = chat.llm_provider();
+        assert!(!Arc::ptr_eq(&first, &second));
+
+        // Two reloads went by unseen: rebuilt, whatever the latest changed.
+        rotate(&["llm_key"]);
+        rotate(&["webhook_key"]);
+        let third = chat.llm_provider();
+        assert!(!Arc::ptr_eq(&second, &third));
+
+        // A config reload also rebuilds it.
+        config_tx.send_modify(|_| {});
+        assert!(!Arc::ptr_eq(&third, &chat.llm_provider()));
+    }
+}
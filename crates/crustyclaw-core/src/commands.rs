@@ -0,0 +1,867 @@
+//! Canned chat commands — operator-defined aliases that bypass the LLM.
+//!
+//! The [`CommandRouter`] watches inbound messages on every channel. A message
+//! whose whole text matches a `[commands.aliases]` entry runs the alias's
+//! skill with its preset arguments and answers with the skill's output;
+//! `/help` lists the aliases the sender may run. Anything else is left for
+//! the normal message flow.
+//!
+//! Senders are assigned roles by channel peer ID in `[commands.roles]`. The
+//! built-in roles are ranked `viewer` < `user` < `operator` < `admin`, and a
+//! sender may run any alias requiring their role or a lower one. Other role
+//! names must match exactly.
+//!
+//! Refusals are counted in [`Metrics`] when the router has them.
+//!
+//! With a [`QuotaManager`], each alias run counts as one sandbox execution
+//! for the sender's role, and is refused once the hourly quota is used up.
+//!
+//! Messages starting with the `[commands] prefix` (`!` by default) are
+//! [`Builtin`] commands: `!status`, `!run <skill> [key=value ...]`,
+//! `!policy check <action> <resource> [role]`, and `!help`. Each is
+//! authorized against the [`PolicyEngine`] for the sender's role; when no
+//! rule matches, reading is open to every role and running a skill needs
+//! `operator`.
+
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crustyclaw_config::policy::{PolicyDecision, PolicyEngine, PolicyVerdict, RequestContext};
+use crustyclaw_config::{CommandAliasConfig, CommandsConfig, normalize_command};
+
+use crate::build_info;
+use crate::conversation::conversation_id;
+use crate::daemon::ShutdownSignal;
+use crate::message::{Direction, Envelope};
+use crate::metrics::{Denial, Metrics};
+use crate::quota::{QuotaKind, QuotaManager};
+use crate::recovery::RunOrigin;
+use crate::skill::{SkillInvocation, SkillRegistry};
+
+/// Built-in roles, lowest first.
+pub const ROLE_RANKS: &[&str] = &["viewer", "user", "operator", "admin"];
+
+/// What an inbound message asks the router to do.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Route {
+    /// List the sender's commands.
+    Help,
+    /// Run a configured alias.
+    Alias {
+        /// The alias as written in the config.
+        name: String,
+        /// Whether the sender's role allows running it.
+        allowed: bool,
+    },
+    /// Run a built-in command.
+    Builtin(Builtin),
+}
+
+/// A built-in command, written after the `[commands] prefix`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Builtin {
+    /// `help`: list the commands.
+    Help,
+    /// `status`: the daemon's version, uptime, and skills.
+    Status,
+    /// `run <skill> [key=value ...]`: invoke a skill with string arguments.
+    Run {
+        /// Skill to invoke.
+        skill: String,
+        /// Arguments, in the order given.
+        args: Vec<(String, String)>,
+    },
+    /// `policy check <action> <resource> [role]`: evaluate the policy for
+    /// `role`, or the sender's own role.
+    PolicyCheck {
+        /// Action to check.
+        action: String,
+        /// Resource to check.
+        resource: String,
+        /// Role to check for, if not the sender's.
+        role: Option<String>,
+    },
+    /// An unknown or malformed command, with the reply explaining why.
+    Invalid(String),
+}
+
+impl Builtin {
+    /// Parse the text following the prefix.
+    pub fn parse(text: &str) -> Self {
+        let mut words = text.split_whitespace();
+        let Some(command) = words.next() else {
+            return Self::Help;
+        };
+        let rest: Vec<&str> = words.collect();
+        match (command.to_ascii_lowercase().as_str(), rest.as_slice()) {
+            ("help", []) => Self::Help,
+            ("status", []) => Self::Status,
+            ("run", [skill, args @ ..]) => {
+                let mut pairs = Vec::new();
+                for arg in args {
+                    match arg.split_once('=') {
+                        Some((key, value)) if !key.is_empty() => {
+                            pairs.push((key.to_string(), value.to_string()));
+                        }
+                        _ => {
+                            return Self::Invalid(format!(
+                                "Arguments are written key=value, got \"{arg}\"."
+                            ));
+                        }
+                    }
+                }
+                Self::Run {
+                    skill: skill.to_string(),
+                    args: pairs,
+                }
+            }
+            ("policy", ["check", action, resource]) => Self::PolicyCheck {
+                action: action.to_string(),
+                resource: resource.to_string(),
+                role: None,
+            },
+            ("policy", ["check", action, resource, role]) => Self::PolicyCheck {
+                action: action.to_string(),
+                resource: resource.to_string(),
+                role: Some(role.to_string()),
+            },
+            ("run", _) => Self::Invalid("Usage: run <skill> [key=value ...]".to_string()),
+            ("policy", _) => {
+                Self::Invalid("Usage: policy check <action> <resource> [role]".to_string())
+            }
+            (command, _) => Self::Invalid(format!("Unknown command \"{command}\".")),
+        }
+    }
+
+    /// The command's name, as typed after the prefix.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Help => "help",
+            Self::Status => "status",
+            Self::Run { .. } => "run",
+            Self::PolicyCheck { .. } => "policy",
+            Self::Invalid(_) => "invalid",
+        }
+    }
+
+    /// The policy action and resource the command needs, if any.
+    pub fn permission(&self) -> Option<(&'static str, String)> {
+        match self {
+            Self::Help | Self::Invalid(_) => None,
+            Self::Status => Some(("read", "status".to_string())),
+            Self::Run { skill, .. } => Some(("run", format!("skills/{skill}"))),
+            Self::PolicyCheck { .. } => Some(("read", "policy".to_string())),
+        }
+    }
+}
+
+/// Matches inbound messages against the configured aliases.
+pub struct CommandRouter {
+    config: RwLock<CommandsConfig>,
+    policy: Mutex<Option<PolicyEngine>>,
+    quotas: Option<Arc<QuotaManager>>,
+    metrics: Option<Arc<Metrics>>,
+    started: Instant,
+}
+
+impl CommandRouter {
+    /// Create a router for the `[commands]` section.
+    pub fn from_config(config: &CommandsConfig) -> Self {
+        Self {
+            config: RwLock::new(config.clone()),
+            policy: Mutex::new(None),
+            quotas: None,
+            metrics: None,
+            started: Instant::now(),
+        }
+    }
+
+    /// Builder: authorize built-in commands against `policy`.
+    pub fn with_policy(self, policy: PolicyEngine) -> Self {
+        self.set_policy(policy);
+        self
+    }
+
+    /// Builder: charge alias runs to the sender's sandbox execution quota.
+    pub fn with_quotas(mut self, quotas: Arc<QuotaManager>) -> Self {
+        self.quotas = Some(quotas);
+        self
+    }
+
+    /// Builder: count refused commands in `metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Replace the aliases and roles, e.g. after a config reload.
+    pub fn reconfigure(&self, config: &CommandsConfig) {
+        *self.config.write().unwrap_or_else(|e| e.into_inner()) = config.clone();
+    }
+
+    /// Replace the policy built-in commands are authorized against.
+    pub fn set_policy(&self, policy: PolicyEngine) {
+        *self.policy.lock().unwrap_or_else(|e| e.into_inner()) = Some(policy);
+    }
+
+    /// The role of `peer`, or the default role for unknown senders.
+    pub fn role_of(&self, peer: Option<&str>) -> String {
+        let config = self.config.read().unwrap_or_else(|e| e.into_inner());
+        peer.and_then(|p| config.roles.get(p))
+            .unwrap_or(&config.default_role)
+            .clone()
+    }
+
+    /// Match an inbound message to a command, if it is one.
+    pub fn route(&self, envelope: &Envelope) -> Option<Route> {
+        if envelope.direction != Direction::Inbound || envelope.redacts.is_some() {
+            return None;
+        }
+        let role = self.role_of(envelope.peer.as_deref());
+        let config = self.config.read().unwrap_or_else(|e| e.into_inner());
+        if !config.prefix.is_empty()
+            && let Some(rest) = envelope.body.trim_start().strip_prefix(&config.prefix)
+        {
+            return Some(Route::Builtin(Builtin::parse(rest)));
+        }
+        let text = normalize_command(&envelope.body);
+        if text == "help" && envelope.body.trim_start().starts_with('/') {
+            return Some(Route::Help);
+        }
+        let (name, command) = config
+            .aliases
+            .iter()
+            .find(|(alias, _)| normalize_command(alias) == text)?;
+        Some(Route::Alias {
+            name: name.clone(),
+            allowed: role_satisfies(&role, &command.role),
+        })
+    }
+
+    /// The `/help` text for a sender with `role`.
+    pub fn help(&self, role: &str) -> String {
+        let config = self.config.read().unwrap_or_else(|e| e.into_inner());
+        let lines: Vec<String> = config
+            .aliases
+            .iter()
+            .filter(|(_, c)| role_satisfies(role, &c.role))
+            .map(|(alias, c)| match c.description.as_str() {
+                "" => alias.clone(),
+                description => format!("{alias} — {description}"),
+            })
+            .collect();
+        if lines.is_empty() {
+            return "No commands are available to you.".to_string();
+        }
+        format!("Commands:\n{}", lines.join("\n"))
+    }
+
+    /// Handle `envelope` if it is a command, returning the reply to send.
+    pub async fn handle(&self, envelope: &Envelope, skills: &SkillRegistry) -> Option<Envelope> {
+        let body = match self.route(envelope)? {
+            Route::Help => self.help(&self.role_of(envelope.peer.as_deref())),
+            Route::Alias {
+                name,
+                allowed: false,
+            } => {
+                warn!(alias = %name, peer = ?envelope.peer, "Command denied for the sender's role");
+                self.record_denial(Denial::Role);
+                format!("You are not allowed to run \"{name}\".")
+            }
+            Route::Alias {
+                name,
+                allowed: true,
+            } => {
+                let command = self.alias(&name)?;
+                let role = self.role_of(envelope.peer.as_deref());
+                if let Some(quotas) = &self.quotas
+                    && let Err(e) = quotas.try_consume(&role, QuotaKind::SandboxExecutions, 1)
+                {
+                    warn!(alias = %name, peer = ?envelope.peer, error = %e, "Command denied by quota");
+                    self.record_denial(Denial::Quota);
+                    return Some(envelope.reply(&format!("\"{name}\" was not run: {e}.")));
+                }
+                info!(alias = %name, skill = %command.skill, peer = ?envelope.peer, "Running command");
+                run_alias(&name, &command, envelope, skills).await
+            }
+            Route::Builtin(builtin) => self.run_builtin(builtin, envelope, skills).await,
+        };
+        Some(envelope.reply(&body))
+    }
+
+    /// Authorize and run a built-in command, returning the reply text.
+    async fn run_builtin(
+        &self,
+        builtin: Builtin,
+        envelope: &Envelope,
+        skills: &SkillRegistry,
+    ) -> String {
+        let role = self.role_of(envelope.peer.as_deref());
+        if let Some((action, resource)) = builtin.permission()
+            && let Err(reason) = self.authorize(envelope.peer.as_deref(), &role, action, &resource)
+        {
+            warn!(peer = ?envelope.peer, %role, action, %resource, %reason, "Built-in command denied by policy");
+            self.record_denial(Denial::Role);
+            return format!("You are not allowed to {action} {resource}: {reason}.");
+        }
+        let prefix = self.prefix();
+        match builtin {
+            Builtin::Help => format!(
+                "Built-in commands:\n\
+                 {prefix}status — daemon status\n\
+                 {prefix}run <skill> [key=value ...] — run a skill\n\
+                 {prefix}policy check <action> <resource> [role] — evaluate the policy\n\n{}",
+                self.help(&role)
+            ),
+            Builtin::Status => format!(
+                "CrustyClaw {} — up {}, {} skills loaded.",
+                build_info::VERSION,
+                format_uptime(self.started.elapsed()),
+                skills.list().len()
+            ),
+            Builtin::Run { skill, args } => {
+                if let Some(quotas) = &self.quotas
+                    && let Err(e) = quotas.try_consume(&role, QuotaKind::SandboxExecutions, 1)
+                {
+                    warn!(%skill, peer = ?envelope.peer, error = %e, "Command denied by quota");
+                    self.record_denial(Denial::Quota);
+                    return format!("\"{skill}\" was not run: {e}.");
+                }
+                info!(%skill, peer = ?envelope.peer, "Running built-in command");
+                let invocation = args
+                    .into_iter()
+                    .fold(invocation(envelope), |invocation, (key, value)| {
+                        invocation.with_arg(key, value)
+                    });
+                run_skill(&skill, &skill, &invocation, skills).await
+            }
+            Builtin::PolicyCheck {
+                action,
+                resource,
+                role: checked,
+            } => {
+                let checked = checked.unwrap_or(role);
+                match self.evaluate(envelope.peer.as_deref(), &checked, &action, &resource) {
+                    Some(verdict) => {
+                        format!("Role \"{checked}\" may {action} {resource}? {verdict}.")
+                    }
+                    None => "No policy is loaded.".to_string(),
+                }
+            }
+            Builtin::Invalid(reason) => {
+                format!("{reason} Send {prefix}help for the commands.")
+            }
+        }
+    }
+
+    /// Check `role` may `action` on `resource` by the policy, falling back to
+    /// open reads and `operator` runs when no rule matches.
+    fn authorize(
+        &self,
+        peer: Option<&str>,
+        role: &str,
+        action: &str,
+        resource: &str,
+    ) -> Result<(), String> {
+        match self.evaluate(peer, role, action, resource) {
+            Some(verdict) if verdict.decision != PolicyDecision::NoMatch => {
+                if verdict.is_allowed() {
+                    Ok(())
+                } else {
+                    Err(verdict.to_string())
+                }
+            }
+            _ if action == "read" || role_satisfies(role, "operator") => Ok(()),
+            _ => Err(
+                "no [[policy.rules]] entry matches, and only operator may run skills by default"
+                    .to_string(),
+            ),
+        }
+    }
+
+    /// Evaluate the policy for `peer`, whose address is `caller.identity`
+    /// to `when` clauses.
+    fn evaluate(
+        &self,
+        peer: Option<&str>,
+        role: &str,
+        action: &str,
+        resource: &str,
+    ) -> Option<PolicyVerdict> {
+        let mut ctx = RequestContext::for_request(action, resource);
+        if let Some(peer) = peer {
+            ctx.insert("caller.identity", peer);
+        }
+        let mut policy = self.policy.lock().unwrap_or_else(|e| e.into_inner());
+        policy
+            .as_mut()
+            .map(|engine| engine.evaluate_with_context(role, action, resource, &ctx))
+    }
+
+    fn prefix(&self) -> String {
+        let config = self.config.read().unwrap_or_else(|e| e.into_inner());
+        config.prefix.clone()
+    }
+
+    fn record_denial(&self, reason: Denial) {
+        if let Some(metrics) = &self.metrics {
+            metrics.record_denial(reason);
+        }
+    }
+
+    fn alias(&self, name: &str) -> Option<CommandAliasConfig> {
+        let config = self.config.read().unwrap_or_else(|e| e.into_inner());
+        config.aliases.get(name).cloned()
+    }
+}
+
+/// Invoke the alias's skill and format its result as a chat reply.
+async fn run_alias(
+    name: &str,
+    command: &CommandAliasConfig,
+    envelope: &Envelope,
+    skills: &SkillRegistry,
+) -> String {
+    let mut invocation = invocation(envelope);
+    for (key, value) in &command.args {
+        match serde_json::to_value(value) {
+            Ok(value) => invocation = invocation.with_arg(key, value),
+            Err(e) => return format!("\"{name}\" has an invalid argument {key}: {e}"),
+        }
+    }
+    run_skill(name, &command.skill, &invocation, skills).await
+}
+
+/// A skill invocation attributed to the sender of `envelope`.
+fn invocation(envelope: &Envelope) -> SkillInvocation {
+    SkillInvocation::new().with_origin(RunOrigin {
+        conversation: conversation_id(envelope),
+        channel: envelope.channel.clone(),
+        peer: envelope.peer.clone(),
+    })
+}
+
+/// Invoke `skill` and format its result as a chat reply about `name`.
+async fn run_skill(
+    name: &str,
+    skill: &str,
+    invocation: &SkillInvocation,
+    skills: &SkillRegistry,
+) -> String {
+    match skills.invoke(skill, invocation).await {
+        Ok(result) if result.success() => match result.stdout.trim() {
+            "" => format!("\"{name}\" done."),
+            stdout => stdout.to_string(),
+        },
+        Ok(result) => format!(
+            "\"{name}\" failed (exit {}): {}",
+            result.exit_code,
+            result.stderr.trim()
+        ),
+        Err(e) => format!("\"{name}\" failed: {e}"),
+    }
+}
+
+/// Render an uptime as days, hours, and minutes.
+fn format_uptime(uptime: Duration) -> String {
+    let minutes = uptime.as_secs() / 60;
+    let (days, hours, minutes) = (minutes / (24 * 60), minutes / 60 % 24, minutes % 60);
+    match (days, hours) {
+        (0, 0) => format!("{minutes}m"),
+        (0, _) => format!("{hours}h {minutes}m"),
+        _ => format!("{days}d {hours}h {minutes}m"),
+    }
+}
+
+/// Whether a sender with role `have` may run a command requiring `need`.
+pub fn role_satisfies(have: &str, need: &str) -> bool {
+    let rank = |role| ROLE_RANKS.iter().position(|r| *r == role);
+    match (rank(have), rank(need)) {
+        (Some(have), Some(need)) => have >= need,
+        _ => have == need,
+    }
+}
+
+/// Spawn the router over the message bus until shutdown.
+///
+/// Each command runs in its own task, so a slow skill does not hold up
+/// other senders; its reply is published on the bus as an outbound message.
+pub fn spawn(
+    router: Arc<CommandRouter>,
+    bus: broadcast::Sender<Envelope>,
+    skills: Arc<SkillRegistry>,
+    mut shutdown_rx: broadcast::Receiver<ShutdownSignal>,
+) -> JoinHandle<()> {
+    let mut inbound = bus.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let envelope = tokio::select! {
+                _ = shutdown_rx.recv() => break,
+                msg = inbound.recv() => match msg {
+                    Ok(envelope) => envelope,
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!(skipped = n, "Command router fell behind the message bus");
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            };
+            if router.route(&envelope).is_none() {
+                continue;
+            }
+            let (router, bus, skills) = (router.clone(), bus.clone(), skills.clone());
+            tokio::spawn(async move {
+                if let Some(reply) = router.handle(&envelope, &skills).await {
+                    debug!(channel = %reply.channel, "Command reply published");
+                    let _ = bus.send(reply);
+                }
+            });
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BoxFuture;
+    use crate::skill::{Skill, SkillError};
+
+    /// Echoes its JSON arguments.
+    struct EchoSkill;
+
+    impl Skill for EchoSkill {
+        fn name(&self) -> &str {
+            "echo"
+        }
+
+        fn description(&self) -> &str {
+            "Echoes its arguments"
+        }
+
+        fn execute(&self, message: &Envelope) -> BoxFuture<'_, Result<String, SkillError>> {
+            let body = message.body.clone();
+            Box::pin(async move { Ok(body) })
+        }
+    }
+
+    fn router() -> CommandRouter {
+        let config = crustyclaw_config::AppConfig::parse(
+            r#"
+            [commands.roles]
+            "+15550001" = "operator"
+            "+15550002" = "auditor"
+
+            [commands.aliases."deploy staging"]
+            skill = "echo"
+            args = { env = "staging" }
+            role = "operator"
+            description = "Deploy main to staging"
+
+            [commands.aliases.ping]
+            skill = "echo"
+            role = "viewer"
+
+            [commands.aliases.audit]
+            skill = "missing"
+            role = "auditor"
+        "#,
+        )
+        .unwrap();
+        CommandRouter::from_config(&config.commands)
+    }
+
+    fn from(peer: &str, body: &str) -> Envelope {
+        Envelope::new("signal", body).with_peer(peer)
+    }
+
+    #[test]
+    fn test_role_ranks() {
+        assert!(role_satisfies("admin", "operator"));
+        assert!(role_satisfies("operator", "operator"));
+        assert!(!role_satisfies("user", "operator"));
+        assert!(role_satisfies("auditor", "auditor"));
+        assert!(!role_satisfies("admin", "auditor"));
+    }
+
+    #[test]
+    fn test_route() {
+        let router = router();
+        let alias = |name: &str, allowed| {
+            Some(Route::Alias {
+                name: name.to_string(),
+                allowed,
+            })
+        };
+        assert_eq!(
+            router.route(&from("+15550001", "  /Deploy  STAGING ")),
+            alias("deploy staging", true)
+        );
+        assert_eq!(
+            router.route(&from("+15559999", "deploy staging")),
+            alias("deploy staging", false)
+        );
+        assert_eq!(
+            router.route(&from("+15550002", "audit")),
+            alias("audit", true)
+        );
+        assert_eq!(router.route(&from("+15559999", "/help")), Some(Route::Help));
+        assert_eq!(router.route(&from("+15559999", "help")), None);
+        assert_eq!(router.route(&from("+15550001", "deploy staging now")), None);
+        assert_eq!(
+            router.route(&from("+15550001", "deploy staging").reply("ping")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_help_lists_allowed_commands() {
+        let router = router();
+        assert_eq!(
+            router.help("operator"),
+            "Commands:\ndeploy staging — Deploy main to staging\nping"
+        );
+        assert_eq!(router.help("viewer"), "Commands:\nping");
+        assert_eq!(router.help("nobody"), "No commands are available to you.");
+    }
+
+    #[tokio::test]
+    async fn test_handle_runs_skill_with_preset_args() {
+        let router = router();
+        let mut skills = SkillRegistry::new();
+        skills.register(Box::new(EchoSkill));
+
+        let message = from("+15550001", "deploy staging");
+        let reply = router.handle(&message, &skills).await.unwrap();
+        assert_eq!(reply.direction, Direction::Outbound);
+        assert_eq!(reply.peer.as_deref(), Some("+15550001"));
+        assert_eq!(reply.body, r#"{"env":"staging"}"#);
+
+        let reply = router
+            .handle(&from("+15559999", "deploy staging"), &skills)
+            .await
+            .unwrap();
+        assert!(reply.body.contains("not allowed"), "{}", reply.body);
+
+        let reply = router
+            .handle(&from("+15550002", "audit"), &skills)
+            .await
+            .unwrap();
+        assert!(reply.body.starts_with("\"audit\" failed"), "{}", reply.body);
+
+        assert!(
+            router
+                .handle(&from("+15550001", "hello"), &skills)
+                .await
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_builtin_parse() {
+        assert_eq!(Builtin::parse(""), Builtin::Help);
+        assert_eq!(Builtin::parse("STATUS"), Builtin::Status);
+        assert_eq!(
+            Builtin::parse("run deploy env=staging branch=main"),
+            Builtin::Run {
+                skill: "deploy".to_string(),
+                args: vec![
+                    ("env".to_string(), "staging".to_string()),
+                    ("branch".to_string(), "main".to_string()),
+                ],
+            }
+        );
+        assert_eq!(
+            Builtin::parse("policy check write secrets viewer"),
+            Builtin::PolicyCheck {
+                action: "write".to_string(),
+                resource: "secrets".to_string(),
+                role: Some("viewer".to_string()),
+            }
+        );
+        for invalid in [
+            "run",
+            "run deploy staging",
+            "run deploy =x",
+            "policy",
+            "reboot",
+        ] {
+            assert!(
+                matches!(Builtin::parse(invalid), Builtin::Invalid(_)),
+                "{invalid}"
+            );
+        }
+        assert_eq!(Builtin::parse("help").permission(), None);
+        assert_eq!(
+            Builtin::parse("run deploy").permission(),
+            Some(("run", "skills/deploy".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_route_builtin() {
+        let router = router();
+        assert_eq!(
+            router.route(&from("+15559999", "  !status")),
+            Some(Route::Builtin(Builtin::Status))
+        );
+        assert_eq!(router.route(&from("+15559999", "status")), None);
+
+        let config = crustyclaw_config::AppConfig::parse("[commands]\nprefix = \"\"\n").unwrap();
+        let router = CommandRouter::from_config(&config.commands);
+        assert_eq!(router.route(&from("+15559999", "!status")), None);
+    }
+
+    #[tokio::test]
+    async fn test_builtin_commands_gated_by_policy() {
+        let policy = crustyclaw_config::AppConfig::parse(
+            r#"
+            [[policy.rules]]
+            role = "user"
+            action = "run"
+            resource = "skills/echo"
+            effect = "allow"
+
+            [[policy.rules]]
+            id = "quiet"
+            role = "viewer"
+            action = "read"
+            resource = "status"
+            effect = "deny"
+        "#,
+        )
+        .unwrap()
+        .build_policy_engine();
+        let metrics = Arc::new(Metrics::new());
+        let router = router().with_policy(policy).with_metrics(metrics.clone());
+        let mut skills = SkillRegistry::new();
+        skills.register(Box::new(EchoSkill));
+        let reply = |peer: &'static str, body: &'static str| {
+            let (router, skills) = (&router, &skills);
+            async move { router.handle(&from(peer, body), skills).await.unwrap() }
+        };
+
+        // A user may run echo by rule; other skills need operator by default.
+        let echoed = reply("+15559999", "!run echo target=prod").await;
+        assert_eq!(echoed.body, r#"{"target":"prod"}"#);
+        assert_eq!(echoed.peer.as_deref(), Some("+15559999"));
+        let denied = reply("+15559999", "!run deploy").await;
+        assert!(
+            denied.body.contains("not allowed to run skills/deploy"),
+            "{}",
+            denied.body
+        );
+        let missing = reply("+15550001", "!run deploy").await;
+        assert!(
+            missing.body.starts_with("\"deploy\" failed"),
+            "{}",
+            missing.body
+        );
+
+        let status = reply("+15559999", "!status").await;
+        assert!(status.body.starts_with("CrustyClaw "), "{}", status.body);
+        assert!(status.body.contains("1 skills loaded"), "{}", status.body);
+
+        let check = reply("+15559999", "!policy check run skills/echo").await;
+        assert_eq!(check.body, "Role \"user\" may run skills/echo? allowed.");
+        let check = reply("+15559999", "!policy check read status viewer").await;
+        assert_eq!(
+            check.body,
+            "Role \"viewer\" may read status? denied by rule \"quiet\"."
+        );
+
+        let help = reply("+15559999", "!help").await;
+        assert!(
+            help.body.starts_with("Built-in commands:\n!status"),
+            "{}",
+            help.body
+        );
+        let unknown = reply("+15559999", "!reboot").await;
+        assert_eq!(
+            unknown.body,
+            "Unknown command \"reboot\". Send !help for the commands."
+        );
+        assert!(
+            metrics
+                .render()
+                .contains("crustyclaw_policy_denials_total{reason=\"role\"} 1")
+        );
+    }
+
+    #[test]
+    fn test_format_uptime() {
+        assert_eq!(format_uptime(Duration::from_secs(59)), "0m");
+        assert_eq!(format_uptime(Duration::from_secs(3 * 3600 + 120)), "3h 2m");
+        assert_eq!(format_uptime(Duration::from_secs(26 * 3600)), "1d 2h 0m");
+    }
+
+    #[tokio::test]
+    async fn test_handle_enforces_quota() {
+        let config = crustyclaw_config::AppConfig::parse(
+            "[quotas.roles.operator]\nsandbox_executions_per_hour = 1\n",
+        )
+        .unwrap();
+        let quotas = Arc::new(QuotaManager::from_config(&config.quotas));
+        let metrics = Arc::new(Metrics::new());
+        let router = router()
+            .with_quotas(quotas.clone())
+            .with_metrics(metrics.clone());
+        let mut skills = SkillRegistry::new();
+        skills.register(Box::new(EchoSkill));
+
+        let message = from("+15550001", "deploy staging");
+        let reply = router.handle(&message, &skills).await.unwrap();
+        assert_eq!(reply.body, r#"{"env":"staging"}"#);
+        let reply = router.handle(&message, &skills).await.unwrap();
+        assert!(reply.body.contains("was not run"), "{}", reply.body);
+
+        // Help and other roles are not charged.
+        let reply = router
+            .handle(&from("+15550001", "/help"), &skills)
+            .await
+            .unwrap();
+        assert!(reply.body.starts_with("Commands:"));
+        assert!(
+            router
+                .handle(&from("+15559999", "ping"), &skills)
+                .await
+                .is_some()
+        );
+        assert_eq!(quotas.usage()[0].used, 1);
+        assert!(
+            metrics
+                .render()
+                .contains("crustyclaw_policy_denials_total{reason=\"quota\"} 1")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_spawn_replies_on_bus() {
+        let (bus, _) = broadcast::channel(16);
+        let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+        let mut skills = SkillRegistry::new();
+        skills.register(Box::new(EchoSkill));
+        let mut watcher = bus.subscribe();
+        let handle = spawn(
+            Arc::new(router()),
+            bus.clone(),
+            Arc::new(skills),
+            shutdown_rx,
+        );
+
+        bus.send(from("+15559999", "/ping")).unwrap();
+        let reply = loop {
+            let msg = watcher.recv().await.unwrap();
+            if msg.direction == Direction::Outbound {
+                break msg;
+            }
+        };
+        assert_eq!(reply.body, "{}");
+
+        shutdown_tx.send(ShutdownSignal).unwrap();
+        handle.await.unwrap();
+    }
+}
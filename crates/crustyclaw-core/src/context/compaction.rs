@@ -0,0 +1,365 @@
+//! Context compaction — summarizing history that does not fit the window.
+//!
+//! When the items offered to a [`ContextWindow`] need more tokens than it
+//! has, packing would drop the lowest-ranked ones. A [`Compactor`] first
+//! replaces the lowest-priority conversation items — oldest first among
+//! equal priorities — with a single summary written by the configured
+//! [`LlmProvider`], taking just enough of them that the rest and the
+//! summary fit. System prompts, tool definitions, code, and retrieval
+//! results are never summarized.
+//!
+//! Each compaction is logged, counted in [`Metrics`], and recorded on the
+//! window, where [`ContextWindow::explain`] shows it. If the summary request
+//! fails, the items are left as they were and packing drops what does not
+//! fit.
+
+use std::sync::Arc;
+
+use crustyclaw_config::CompactionConfig;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use super::window::{ContextItem, ContextKind, ContextWindow};
+use crate::llm::{ChatMessage, ChatRequest, LlmError, LlmProvider};
+use crate::metrics::Metrics;
+
+/// Source of the summary items a compaction produces.
+pub const SUMMARY_SOURCE: &str = "summary";
+
+/// One compaction: which items were summarized, and the tokens saved.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Compaction {
+    /// Sources of the summarized items, in the order they were offered.
+    pub sources: Vec<String>,
+    /// Tokens the summarized items took.
+    pub tokens_before: u32,
+    /// Tokens the summary takes.
+    pub tokens_after: u32,
+}
+
+impl Compaction {
+    /// Tokens saved by the summary.
+    pub fn saved_tokens(&self) -> u32 {
+        self.tokens_before.saturating_sub(self.tokens_after)
+    }
+}
+
+/// Summarizes conversation history that does not fit a context window.
+pub struct Compactor {
+    provider: Arc<dyn LlmProvider>,
+    model: String,
+    config: CompactionConfig,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl Compactor {
+    /// Create a compactor asking `model` on `provider` for summaries.
+    pub fn new(
+        provider: Arc<dyn LlmProvider>,
+        model: impl Into<String>,
+        config: &CompactionConfig,
+    ) -> Self {
+        Self {
+            provider,
+            model: model.into(),
+            config: config.clone(),
+            metrics: None,
+        }
+    }
+
+    /// Builder: count compactions in `metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Summarize the lowest-priority conversation items in `items` if they
+    /// do not all fit in `window`, replacing them with one summary item.
+    ///
+    /// Returns the items to pack and the compaction, if one was made.
+    pub async fn compact(
+        &self,
+        mut items: Vec<ContextItem>,
+        window: &ContextWindow,
+    ) -> (Vec<ContextItem>, Option<Compaction>) {
+        if !self.config.enabled {
+            return (items, None);
+        }
+        let counts: Vec<u32> = items
+            .iter()
+            .map(|item| window.count_tokens(&item.content))
+            .collect();
+        let available = window.available();
+        let mut remaining: u32 = counts.iter().sum();
+        if remaining <= available {
+            return (items, None);
+        }
+
+        let mut history: Vec<usize> = (0..items.len())
+            .filter(|&i| items[i].kind == ContextKind::Conversation)
+            .collect();
+        history.sort_by_key(|&i| items[i].priority);
+        let mut chosen = Vec::new();
+        for i in history {
+            if remaining.saturating_add(self.config.summary_tokens) <= available {
+                break;
+            }
+            chosen.push(i);
+            remaining -= counts[i];
+        }
+        if chosen.len() < self.config.min_items {
+            return (items, None);
+        }
+
+        chosen.sort_unstable();
+        let tokens_before = chosen.iter().map(|&i| counts[i]).sum();
+        let mut summarized: Vec<ContextItem> =
+            chosen.iter().rev().map(|&i| items.remove(i)).collect();
+        summarized.reverse();
+
+        match self.summarize(&summarized).await {
+            Ok(mut summary) => {
+                summary.estimated_tokens = window.count_tokens(&summary.content);
+                let compaction = Compaction {
+                    sources: summarized.into_iter().map(|item| item.source).collect(),
+                    tokens_before,
+                    tokens_after: summary.estimated_tokens,
+                };
+                info!(
+                    items = compaction.sources.len(),
+                    tokens_before = compaction.tokens_before,
+                    tokens_after = compaction.tokens_after,
+                    "Compacted context history into a summary"
+                );
+                if let Some(metrics) = &self.metrics {
+                    metrics.record_compaction(compaction.sources.len(), compaction.saved_tokens());
+                }
+                items.push(summary);
+                (items, Some(compaction))
+            }
+            Err(e) => {
+                warn!(error = %e, items = summarized.len(), "Context compaction failed; history that does not fit will be dropped");
+                if let Some(metrics) = &self.metrics {
+                    metrics.record_compaction_failure();
+                }
+                items.extend(summarized);
+                (items, None)
+            }
+        }
+    }
+
+    /// Ask the provider to summarize `items` into one conversation item,
+    /// with the highest priority and latest modification time among them.
+    async fn summarize(&self, items: &[ContextItem]) -> Result<ContextItem, LlmError> {
+        let excerpts: Vec<String> = items
+            .iter()
+            .map(|item| format!("[{}]\n{}", item.source, item.content))
+            .collect();
+        let request = ChatRequest {
+            model: self.model.clone(),
+            messages: vec![ChatMessage::user(excerpts.join("\n\n"))],
+            max_tokens: self.config.summary_tokens,
+            system: Some(format!(
+                "You compress conversation history for an assistant's context window. \
+                 Summarize the excerpts below in at most {} tokens, keeping facts, \
+                 decisions, names, and open questions. Reply with the summary only.",
+                self.config.summary_tokens
+            )),
+            ..ChatRequest::default()
+        };
+        let response = self.provider.chat(&request).await?;
+        let text = response.message.content.unwrap_or_default();
+        if text.trim().is_empty() {
+            return Err(LlmError::Parse("empty summary".to_string()));
+        }
+        let mut summary = ContextWindow::item(
+            ContextKind::Conversation,
+            format!("Summary of earlier conversation:\n{}", text.trim()),
+            items
+                .iter()
+                .map(|item| item.priority)
+                .max()
+                .unwrap_or_default(),
+            SUMMARY_SOURCE.to_string(),
+        );
+        summary.modified_secs = items.iter().filter_map(|item| item.modified_secs).max();
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    use crate::BoxFuture;
+    use crate::llm::{ChatResponse, StreamChunk, TokenUsage};
+
+    /// Answers every request with `summary` (or fails when `None`),
+    /// recording the prompts.
+    struct Summarizer {
+        summary: Option<&'static str>,
+        prompts: Mutex<Vec<String>>,
+    }
+
+    impl Summarizer {
+        fn new(summary: Option<&'static str>) -> Arc<Self> {
+            Arc::new(Self {
+                summary,
+                prompts: Mutex::new(Vec::new()),
+            })
+        }
+    }
+
+    impl LlmProvider for Summarizer {
+        fn name(&self) -> &str {
+            "summarizer"
+        }
+
+        fn chat(&self, request: &ChatRequest) -> BoxFuture<'_, Result<ChatResponse, LlmError>> {
+            self.prompts
+                .lock()
+                .unwrap()
+                .push(request.messages[0].content.clone().unwrap_or_default());
+            let result = match self.summary {
+                Some(summary) => Ok(ChatResponse {
+                    message: ChatMessage::assistant(summary),
+                    finish_reason: "stop".to_string(),
+                    usage: TokenUsage::default(),
+                    model: request.model.clone(),
+                    attempts: 1,
+                }),
+                None => Err(LlmError::Timeout),
+            };
+            Box::pin(async move { result })
+        }
+
+        fn chat_stream(
+            &self,
+            _request: &ChatRequest,
+        ) -> BoxFuture<
+            '_,
+            Result<tokio::sync::mpsc::Receiver<Result<StreamChunk, LlmError>>, LlmError>,
+        > {
+            Box::pin(async { Err(LlmError::Request("not streamed".to_string())) })
+        }
+    }
+
+    fn config(summary_tokens: u32) -> CompactionConfig {
+        CompactionConfig {
+            summary_tokens,
+            ..CompactionConfig::default()
+        }
+    }
+
+    /// A 25-token conversation item.
+    fn turn(text: &str, priority: u32) -> ContextItem {
+        ContextWindow::item(
+            ContextKind::Conversation,
+            format!("{text:<100}"),
+            priority,
+            text.to_string(),
+        )
+    }
+
+    fn history() -> Vec<ContextItem> {
+        vec![
+            ContextWindow::item(
+                ContextKind::System,
+                "x".repeat(40),
+                100,
+                "system".to_string(),
+            ),
+            turn("turn-1", 10),
+            turn("turn-2", 10),
+            turn("turn-3", 20),
+            turn("turn-4", 30),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_compacts_lowest_priority_history() {
+        let provider = Summarizer::new(Some("The user asked about turns."));
+        let metrics = Arc::new(Metrics::new());
+        let compactor =
+            Compactor::new(provider.clone(), "model", &config(20)).with_metrics(metrics.clone());
+        // 10 system + 100 conversation tokens offered; 80 available.
+        let mut window = ContextWindow::new(80, 0);
+
+        assert_eq!(window.pack_compacted(history(), &compactor).await, 4);
+
+        // Summarizing turn-1 and turn-2 (the lowest priorities) leaves
+        // 60 tokens plus room for a 20-token summary.
+        let compaction = &window.compactions()[0];
+        assert_eq!(compaction.sources, ["turn-1", "turn-2"]);
+        assert_eq!(compaction.tokens_before, 50);
+        assert!(window.dropped().is_empty());
+        let summary = window
+            .items()
+            .iter()
+            .find(|item| item.source == SUMMARY_SOURCE)
+            .unwrap();
+        assert!(summary.content.contains("The user asked about turns."));
+        assert_eq!(summary.priority, 10);
+        assert_eq!(summary.estimated_tokens, compaction.tokens_after);
+
+        let prompts = provider.prompts.lock().unwrap();
+        assert!(prompts[0].starts_with("[turn-1]\nturn-1"));
+        assert!(prompts[0].contains("[turn-2]"));
+        assert!(!prompts[0].contains("turn-3"));
+
+        assert!(window.explain().contains("compacted: 2 items (50 → "));
+        assert!(
+            metrics
+                .render()
+                .contains("crustyclaw_context_compacted_items_total 2")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_no_compaction_when_everything_fits() {
+        let provider = Summarizer::new(Some("unused"));
+        let compactor = Compactor::new(provider.clone(), "model", &config(20));
+        let mut window = ContextWindow::new(1000, 0);
+
+        assert_eq!(window.pack_compacted(history(), &compactor).await, 5);
+        assert!(window.compactions().is_empty());
+        assert!(provider.prompts.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failed_summary_drops_instead() {
+        let metrics = Arc::new(Metrics::new());
+        let compactor = Compactor::new(Summarizer::new(None), "model", &config(20))
+            .with_metrics(metrics.clone());
+        let mut window = ContextWindow::new(80, 0);
+
+        assert_eq!(window.pack_compacted(history(), &compactor).await, 3);
+        assert!(window.compactions().is_empty());
+        assert_eq!(window.dropped().len(), 2);
+        assert!(
+            metrics
+                .render()
+                .contains("crustyclaw_context_compactions_total{outcome=\"failure\"} 1")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_disabled_or_too_few_items() {
+        let provider = Summarizer::new(Some("unused"));
+        let disabled = CompactionConfig {
+            enabled: false,
+            ..config(20)
+        };
+        let compactor = Compactor::new(provider.clone(), "model", &disabled);
+        let mut window = ContextWindow::new(80, 0);
+        assert_eq!(window.pack_compacted(history(), &compactor).await, 3);
+
+        // One item over budget is not worth a summary with min_items = 2.
+        let compactor = Compactor::new(provider.clone(), "model", &config(20));
+        let mut window = ContextWindow::new(105, 0);
+        assert_eq!(window.pack_compacted(history(), &compactor).await, 4);
+        assert!(window.compactions().is_empty());
+        assert!(provider.prompts.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,782 @@
+//! One-time tool trust elevation with operator approval.
+//!
+//! When a tool call needs more trust than its caller has, the agent files an
+//! [`ElevationRequest`] through the `request_elevation` tool, naming the exact
+//! call and why it needs the higher level. An operator reviews the command
+//! preview and approves or denies it (`crustyclaw elevation approve <id>`).
+//! An approval authorizes that single call — same tool, same arguments — once;
+//! the caller's standing trust level never changes.
+//!
+//! Every step (requested, approved, denied, executed, expired) is kept as an
+//! [`ElevationAuditRecord`] and, when an audit log is attached, appended to it
+//! as a JSON line.
+
+use std::collections::BTreeMap;
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crustyclaw_config::ElevationConfig;
+
+use super::tools::{ToolRegistry, ToolTrust};
+use crate::llm::types::ToolCall;
+use crate::storage::AuditLog;
+
+/// Name of the built-in tool the agent uses to request elevation.
+pub const REQUEST_ELEVATION_TOOL: &str = "request_elevation";
+
+/// Key of the audit log in the audit namespace; a file in the daemon's
+/// state directory with filesystem storage.
+pub const AUDIT_FILE: &str = "elevations.jsonl";
+
+/// Errors from filing, deciding, or using an elevation.
+#[derive(Debug, thiserror::Error)]
+pub enum ElevationError {
+    #[error("trust elevation is disabled")]
+    Disabled,
+
+    #[error("unknown tool: {0}")]
+    UnknownTool(String),
+
+    #[error("invalid elevation request: {0}")]
+    InvalidRequest(String),
+
+    #[error("{tool} only needs {required} trust, which the caller already has")]
+    NotNeeded { tool: String, required: ToolTrust },
+
+    #[error("{tool} requires {required} trust, but {requested} was requested")]
+    TooLow {
+        tool: String,
+        required: ToolTrust,
+        requested: ToolTrust,
+    },
+
+    #[error("elevation to {requested} exceeds the configured maximum of {max}")]
+    ExceedsMax {
+        requested: ToolTrust,
+        max: ToolTrust,
+    },
+
+    #[error("elevation request {0} not found")]
+    NotFound(u64),
+
+    #[error("elevation request {id} is already {status}")]
+    NotPending { id: u64, status: &'static str },
+
+    #[error("{tool} requires {required} trust, caller has {caller}")]
+    InsufficientTrust {
+        tool: String,
+        required: ToolTrust,
+        caller: ToolTrust,
+    },
+}
+
+/// Lifecycle of an elevation request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ElevationStatus {
+    /// Waiting for an operator.
+    Pending,
+    /// Approved and not yet used.
+    Approved { by: String },
+    /// Rejected by an operator.
+    Denied { by: String, reason: Option<String> },
+    /// The approved call has run.
+    Used,
+    /// Not decided, or not used, within the TTL.
+    Expired,
+}
+
+impl ElevationStatus {
+    /// Short status name for display.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Approved { .. } => "approved",
+            Self::Denied { .. } => "denied",
+            Self::Used => "used",
+            Self::Expired => "expired",
+        }
+    }
+}
+
+/// What the agent asks for: one tool call at one trust level.
+#[derive(Debug, Clone)]
+pub struct ElevationAsk {
+    /// Tool to run.
+    pub tool: String,
+    /// Exact arguments for the call.
+    pub arguments: serde_json::Value,
+    /// Level the call needs.
+    pub requested: ToolTrust,
+    /// Why the call needs it.
+    pub reason: String,
+}
+
+impl ElevationAsk {
+    /// Parse the arguments of a `request_elevation` tool call.
+    pub fn from_call(call: &ToolCall) -> Result<Self, ElevationError> {
+        let args = &call.arguments;
+        let field = |name: &str| {
+            args.get(name)
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| ElevationError::InvalidRequest(format!("missing \"{name}\"")))
+        };
+        let level = field("level")?;
+        let requested = ToolTrust::from_name(level)
+            .ok_or_else(|| ElevationError::InvalidRequest(format!("unknown level {level:?}")))?;
+        Ok(Self {
+            tool: field("tool")?.to_string(),
+            arguments: args
+                .get("arguments")
+                .cloned()
+                .unwrap_or_else(|| serde_json::json!({})),
+            requested,
+            reason: field("reason")?.to_string(),
+        })
+    }
+}
+
+/// A request to run one tool call at a higher trust level.
+#[derive(Debug, Clone)]
+pub struct ElevationRequest {
+    /// Identifier operators use to approve or deny.
+    pub id: u64,
+    /// Tool to run.
+    pub tool: String,
+    /// Exact arguments the approved call must use.
+    pub arguments: serde_json::Value,
+    /// Human-readable preview of the call.
+    pub preview: String,
+    /// The caller's standing trust level.
+    pub current: ToolTrust,
+    /// Level the single call runs at once approved.
+    pub requested: ToolTrust,
+    /// The agent's justification.
+    pub reason: String,
+    /// Conversation the request came from, if any.
+    pub conversation: Option<String>,
+    /// Where the request is in its lifecycle.
+    pub status: ElevationStatus,
+    /// When the request was filed.
+    pub requested_at: SystemTime,
+}
+
+/// One entry in the elevation audit trail.
+#[derive(Debug, Clone, Serialize)]
+pub struct ElevationAuditRecord {
+    /// Request the event belongs to.
+    pub id: u64,
+    /// `requested`, `approved`, `denied`, `executed`, or `expired`.
+    pub event: &'static str,
+    /// Who caused the event: `agent`, an operator name, or `daemon`.
+    pub actor: String,
+    /// Tool the request is for.
+    pub tool: String,
+    /// Call preview shown to the operator.
+    pub preview: String,
+    /// Caller's standing trust level.
+    pub from: ToolTrust,
+    /// Elevated level.
+    pub to: ToolTrust,
+    /// Agent reason or denial reason.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// Unix time of the event, in milliseconds.
+    pub at_ms: u64,
+}
+
+/// The operator approval queue for trust elevations.
+pub struct ElevationQueue {
+    settings: RwLock<Settings>,
+    state: Mutex<State>,
+    audit_log: Option<AuditLog>,
+}
+
+struct Settings {
+    enabled: bool,
+    ttl: Duration,
+    max: ToolTrust,
+}
+
+#[derive(Default)]
+struct State {
+    next_id: u64,
+    requests: BTreeMap<u64, ElevationRequest>,
+    audit: Vec<ElevationAuditRecord>,
+}
+
+impl ElevationQueue {
+    /// Create a queue with the default settings.
+    pub fn new() -> Self {
+        Self::from_config(&ElevationConfig::default())
+    }
+
+    /// Create a queue from `[context.elevation]`.
+    pub fn from_config(config: &ElevationConfig) -> Self {
+        Self {
+            settings: RwLock::new(Settings::from_config(config)),
+            state: Mutex::new(State {
+                next_id: 1,
+                ..State::default()
+            }),
+            audit_log: None,
+        }
+    }
+
+    /// Append audit records to `log`.
+    pub fn with_audit_log(mut self, log: AuditLog) -> Self {
+        self.audit_log = Some(log);
+        self
+    }
+
+    /// Apply reloaded settings. Existing requests are kept.
+    pub fn reconfigure(&self, config: &ElevationConfig) {
+        *self.settings.write().unwrap_or_else(|e| e.into_inner()) = Settings::from_config(config);
+    }
+
+    /// File a request for `ask` from a caller at `current` trust.
+    ///
+    /// Fails if elevation is disabled, the tool is unknown, the caller already
+    /// has enough trust, or the requested level is below what the tool needs
+    /// or above the configured maximum.
+    pub fn request(
+        &self,
+        registry: &ToolRegistry,
+        ask: ElevationAsk,
+        current: ToolTrust,
+        conversation: Option<&str>,
+    ) -> Result<u64, ElevationError> {
+        let ElevationAsk {
+            tool,
+            arguments,
+            requested,
+            reason,
+        } = ask;
+        let (enabled, max) = {
+            let settings = self.settings.read().unwrap_or_else(|e| e.into_inner());
+            (settings.enabled, settings.max)
+        };
+        if !enabled {
+            return Err(ElevationError::Disabled);
+        }
+        let required = registry
+            .get(&tool)
+            .filter(|t| t.enabled)
+            .map(|t| t.trust)
+            .ok_or_else(|| ElevationError::UnknownTool(tool.clone()))?;
+        if required <= current {
+            return Err(ElevationError::NotNeeded { tool, required });
+        }
+        if requested < required {
+            return Err(ElevationError::TooLow {
+                tool,
+                required,
+                requested,
+            });
+        }
+        if requested > max {
+            return Err(ElevationError::ExceedsMax { requested, max });
+        }
+        if reason.trim().is_empty() {
+            return Err(ElevationError::InvalidRequest(
+                "a reason is required".to_string(),
+            ));
+        }
+
+        let mut state = self.lock();
+        let id = state.next_id;
+        state.next_id += 1;
+        let request = ElevationRequest {
+            id,
+            preview: preview(&tool, &arguments),
+            tool,
+            arguments,
+            current,
+            requested,
+            reason,
+            conversation: conversation.map(str::to_string),
+            status: ElevationStatus::Pending,
+            requested_at: SystemTime::now(),
+        };
+        info!(
+            id,
+            tool = %request.tool,
+            from = %current,
+            to = %requested,
+            preview = %request.preview,
+            "Trust elevation requested"
+        );
+        self.record(
+            &mut state,
+            &request,
+            "requested",
+            "agent",
+            Some(&request.reason),
+        );
+        state.requests.insert(id, request);
+        Ok(id)
+    }
+
+    /// File a request from a `request_elevation` tool call.
+    pub fn request_from_call(
+        &self,
+        registry: &ToolRegistry,
+        call: &ToolCall,
+        current: ToolTrust,
+        conversation: Option<&str>,
+    ) -> Result<u64, ElevationError> {
+        self.request(
+            registry,
+            ElevationAsk::from_call(call)?,
+            current,
+            conversation,
+        )
+    }
+
+    /// Approve a pending request on behalf of `operator`.
+    pub fn approve(&self, id: u64, operator: &str) -> Result<ElevationRequest, ElevationError> {
+        self.decide(
+            id,
+            operator,
+            ElevationStatus::Approved {
+                by: operator.to_string(),
+            },
+        )
+    }
+
+    /// Deny a pending request on behalf of `operator`.
+    pub fn deny(
+        &self,
+        id: u64,
+        operator: &str,
+        reason: Option<&str>,
+    ) -> Result<ElevationRequest, ElevationError> {
+        self.decide(
+            id,
+            operator,
+            ElevationStatus::Denied {
+                by: operator.to_string(),
+                reason: reason.map(str::to_string),
+            },
+        )
+    }
+
+    fn decide(
+        &self,
+        id: u64,
+        operator: &str,
+        status: ElevationStatus,
+    ) -> Result<ElevationRequest, ElevationError> {
+        let mut state = self.lock();
+        self.expire_stale(&mut state);
+        let request = state
+            .requests
+            .get_mut(&id)
+            .ok_or(ElevationError::NotFound(id))?;
+        if request.status != ElevationStatus::Pending {
+            return Err(ElevationError::NotPending {
+                id,
+                status: request.status.name(),
+            });
+        }
+        let detail = match &status {
+            ElevationStatus::Denied { reason, .. } => reason.clone(),
+            _ => None,
+        };
+        request.status = status;
+        let request = request.clone();
+        let event = request.status.name();
+        info!(id, operator, tool = %request.tool, "Trust elevation {event}");
+        self.record(&mut state, &request, event, operator, detail.as_deref());
+        Ok(request)
+    }
+
+    /// Decide the trust level `call` runs at.
+    ///
+    /// A caller with enough trust runs at its own level. Otherwise an approved
+    /// request for the identical call is consumed and its level returned; it
+    /// cannot be used again.
+    pub fn authorize(
+        &self,
+        registry: &ToolRegistry,
+        call: &ToolCall,
+        caller: ToolTrust,
+    ) -> Result<ToolTrust, ElevationError> {
+        let required = registry
+            .get(&call.name)
+            .filter(|t| t.enabled)
+            .map(|t| t.trust)
+            .ok_or_else(|| ElevationError::UnknownTool(call.name.clone()))?;
+        if required <= caller {
+            return Ok(caller);
+        }
+
+        let mut state = self.lock();
+        self.expire_stale(&mut state);
+        let grant = state.requests.values_mut().find(|r| {
+            matches!(r.status, ElevationStatus::Approved { .. })
+                && r.tool == call.name
+                && r.arguments == call.arguments
+                && r.requested >= required
+        });
+        let Some(grant) = grant else {
+            return Err(ElevationError::InsufficientTrust {
+                tool: call.name.clone(),
+                required,
+                caller,
+            });
+        };
+        grant.status = ElevationStatus::Used;
+        let grant = grant.clone();
+        info!(id = grant.id, tool = %grant.tool, level = %grant.requested, "Running elevated tool call");
+        self.record(&mut state, &grant, "executed", "daemon", None);
+        Ok(grant.requested)
+    }
+
+    /// All requests, oldest first.
+    pub fn list(&self) -> Vec<ElevationRequest> {
+        let mut state = self.lock();
+        self.expire_stale(&mut state);
+        state.requests.values().cloned().collect()
+    }
+
+    /// Requests waiting for an operator.
+    pub fn pending(&self) -> Vec<ElevationRequest> {
+        self.list()
+            .into_iter()
+            .filter(|r| r.status == ElevationStatus::Pending)
+            .collect()
+    }
+
+    /// The audit trail, oldest first.
+    pub fn audit(&self) -> Vec<ElevationAuditRecord> {
+        self.lock().audit.clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Expire pending requests and unused approvals older than the TTL.
+    fn expire_stale(&self, state: &mut State) {
+        let ttl = self.settings.read().unwrap_or_else(|e| e.into_inner()).ttl;
+        let expired: Vec<ElevationRequest> = state
+            .requests
+            .values_mut()
+            .filter(|r| {
+                matches!(
+                    r.status,
+                    ElevationStatus::Pending | ElevationStatus::Approved { .. }
+                )
+            })
+            .filter(|r| r.requested_at.elapsed().is_ok_and(|age| age >= ttl))
+            .map(|r| {
+                r.status = ElevationStatus::Expired;
+                r.clone()
+            })
+            .collect();
+        for request in &expired {
+            self.record(state, request, "expired", "daemon", None);
+        }
+    }
+
+    fn record(
+        &self,
+        state: &mut State,
+        request: &ElevationRequest,
+        event: &'static str,
+        actor: &str,
+        detail: Option<&str>,
+    ) {
+        let record = ElevationAuditRecord {
+            id: request.id,
+            event,
+            actor: actor.to_string(),
+            tool: request.tool.clone(),
+            preview: request.preview.clone(),
+            from: request.current,
+            to: request.requested,
+            detail: detail.map(str::to_string),
+            at_ms: now_ms(),
+        };
+        if let Some(log) = &self.audit_log
+            && let Err(e) = log.append(&record)
+        {
+            warn!(log = log.key(), error = %e, "Failed to write elevation audit record");
+        }
+        state.audit.push(record);
+    }
+}
+
+impl Default for ElevationQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Settings {
+    fn from_config(config: &ElevationConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            ttl: Duration::from_secs(config.ttl_secs),
+            max: ToolTrust::from_name(&config.max_trust).unwrap_or(ToolTrust::Trusted),
+        }
+    }
+}
+
+/// Render a call for operator review: the command line for `run_command`-style
+/// tools, otherwise the tool name and its JSON arguments.
+pub fn preview(tool: &str, arguments: &serde_json::Value) -> String {
+    match arguments.get("command").and_then(|c| c.as_str()) {
+        Some(command) => {
+            let mut preview = format!("{tool}: {command}");
+            if let Some(dir) = arguments.get("working_dir").and_then(|d| d.as_str()) {
+                preview.push_str(&format!(" (in {dir})"));
+            }
+            preview
+        }
+        None => format!("{tool} {arguments}"),
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn install_call() -> ToolCall {
+        ToolCall {
+            id: "call_1".to_string(),
+            name: "run_command".to_string(),
+            arguments: serde_json::json!({"command": "apt-get install -y jq"}),
+        }
+    }
+
+    fn file_request(queue: &ElevationQueue, registry: &ToolRegistry) -> u64 {
+        let ask = ElevationAsk {
+            tool: "run_command".to_string(),
+            arguments: install_call().arguments,
+            requested: ToolTrust::Trusted,
+            reason: "need jq to parse the build output".to_string(),
+        };
+        queue
+            .request(registry, ask, ToolTrust::Public, Some("signal:+1555"))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_request_shows_exact_command() {
+        let registry = ToolRegistry::with_defaults();
+        let queue = ElevationQueue::new();
+        let id = file_request(&queue, &registry);
+
+        let pending = queue.pending();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].id, id);
+        assert_eq!(pending[0].preview, "run_command: apt-get install -y jq");
+        assert_eq!(pending[0].conversation.as_deref(), Some("signal:+1555"));
+    }
+
+    #[test]
+    fn test_approved_call_runs_elevated_exactly_once() {
+        let registry = ToolRegistry::with_defaults();
+        let queue = ElevationQueue::new();
+        let id = file_request(&queue, &registry);
+        let call = install_call();
+
+        // Not approved yet
+        assert!(matches!(
+            queue.authorize(&registry, &call, ToolTrust::Public),
+            Err(ElevationError::InsufficientTrust { .. })
+        ));
+
+        queue.approve(id, "alice").unwrap();
+        assert_eq!(
+            queue
+                .authorize(&registry, &call, ToolTrust::Public)
+                .unwrap(),
+            ToolTrust::Trusted
+        );
+        // One-time: the second identical call is refused again
+        assert!(
+            queue
+                .authorize(&registry, &call, ToolTrust::Public)
+                .is_err()
+        );
+
+        let events: Vec<_> = queue
+            .audit()
+            .iter()
+            .map(|r| (r.event, r.actor.clone()))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                ("requested", "agent".to_string()),
+                ("approved", "alice".to_string()),
+                ("executed", "daemon".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_approval_does_not_cover_other_arguments() {
+        let registry = ToolRegistry::with_defaults();
+        let queue = ElevationQueue::new();
+        let id = file_request(&queue, &registry);
+        queue.approve(id, "alice").unwrap();
+
+        let mut other = install_call();
+        other.arguments = serde_json::json!({"command": "rm -rf /"});
+        assert!(
+            queue
+                .authorize(&registry, &other, ToolTrust::Public)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_denied_request_is_final() {
+        let registry = ToolRegistry::with_defaults();
+        let queue = ElevationQueue::new();
+        let id = file_request(&queue, &registry);
+
+        let denied = queue.deny(id, "bob", Some("use the jq skill")).unwrap();
+        assert_eq!(denied.status.name(), "denied");
+        assert!(matches!(
+            queue.approve(id, "alice"),
+            Err(ElevationError::NotPending {
+                status: "denied",
+                ..
+            })
+        ));
+        assert!(
+            queue
+                .authorize(&registry, &install_call(), ToolTrust::Public)
+                .is_err()
+        );
+        assert_eq!(
+            queue.audit().last().unwrap().detail.as_deref(),
+            Some("use the jq skill")
+        );
+    }
+
+    #[test]
+    fn test_request_validation() {
+        let registry = ToolRegistry::with_defaults();
+        let queue = ElevationQueue::new();
+        let request = |tool: &str, requested, current| {
+            let ask = ElevationAsk {
+                tool: tool.to_string(),
+                arguments: serde_json::json!({}),
+                requested,
+                reason: "why".to_string(),
+            };
+            queue.request(&registry, ask, current, None)
+        };
+
+        assert!(matches!(
+            request("nope", ToolTrust::Trusted, ToolTrust::Public),
+            Err(ElevationError::UnknownTool(_))
+        ));
+        assert!(matches!(
+            request("run_command", ToolTrust::Trusted, ToolTrust::Trusted),
+            Err(ElevationError::NotNeeded { .. })
+        ));
+        assert!(matches!(
+            request("daemon_status", ToolTrust::Trusted, ToolTrust::Public),
+            Err(ElevationError::TooLow { .. })
+        ));
+        assert!(matches!(
+            request("daemon_status", ToolTrust::System, ToolTrust::Public),
+            Err(ElevationError::ExceedsMax { .. })
+        ));
+
+        queue.reconfigure(&ElevationConfig {
+            enabled: false,
+            ..ElevationConfig::default()
+        });
+        assert!(matches!(
+            request("run_command", ToolTrust::Trusted, ToolTrust::Public),
+            Err(ElevationError::Disabled)
+        ));
+    }
+
+    #[test]
+    fn test_request_from_tool_call() {
+        let registry = ToolRegistry::with_defaults();
+        let queue = ElevationQueue::new();
+        let call = ToolCall {
+            id: "call_2".to_string(),
+            name: REQUEST_ELEVATION_TOOL.to_string(),
+            arguments: serde_json::json!({
+                "tool": "run_command",
+                "arguments": {"command": "make install"},
+                "level": "trusted",
+                "reason": "install the built binary",
+            }),
+        };
+        let id = queue
+            .request_from_call(&registry, &call, ToolTrust::Public, None)
+            .unwrap();
+        let request = &queue.list()[0];
+        assert_eq!(request.id, id);
+        assert_eq!(request.requested, ToolTrust::Trusted);
+        assert_eq!(request.preview, "run_command: make install");
+
+        let mut bad = call.clone();
+        bad.arguments["level"] = serde_json::json!("root");
+        assert!(matches!(
+            queue.request_from_call(&registry, &bad, ToolTrust::Public, None),
+            Err(ElevationError::InvalidRequest(_))
+        ));
+    }
+
+    #[test]
+    fn test_stale_requests_expire() {
+        let registry = ToolRegistry::with_defaults();
+        let queue = ElevationQueue::new();
+        let id = file_request(&queue, &registry);
+        queue.reconfigure(&ElevationConfig {
+            ttl_secs: 0,
+            ..ElevationConfig::default()
+        });
+
+        assert!(matches!(
+            queue.approve(id, "alice"),
+            Err(ElevationError::NotPending {
+                status: "expired",
+                ..
+            })
+        ));
+        assert!(queue.pending().is_empty());
+    }
+
+    #[test]
+    fn test_audit_log_written() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("elevations.jsonl");
+        let registry = ToolRegistry::with_defaults();
+        let queue = ElevationQueue::new().with_audit_log(AuditLog::file(&path));
+        let id = file_request(&queue, &registry);
+        queue.approve(id, "alice").unwrap();
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<serde_json::Value> = log
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["event"], "approved");
+        assert_eq!(lines[1]["to"], "trusted");
+        assert_eq!(lines[1]["preview"], "run_command: apt-get install -y jq");
+    }
+}
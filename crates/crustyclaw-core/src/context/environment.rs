@@ -0,0 +1,454 @@
+//! Environment preamble — tells the model where it is running.
+//!
+//! Without this every skill had to describe the host itself, and models that
+//! weren't told simply guessed. [`EnvironmentProvider`] gathers a fixed set of
+//! facts (hostname, OS, repository root, current time, active persona, and a
+//! summary of the available tools) and renders them into a block that is
+//! prepended to each conversation's system prompt.
+//!
+//! Operators choose which facts appear with `[context.environment] facts` and
+//! can replace the default layout with a `template` using `{fact}`
+//! placeholders:
+//!
+//! ```text
+//! <environment>
+//! hostname: build-01
+//! os: linux x86_64 (Debian GNU/Linux 12 (bookworm))
+//! repo_root: /srv/app
+//! time: 2026-03-01T09:30:00Z
+//! persona: release engineer
+//! tools: read_file (Read a file), run_command (Run a shell command)
+//! </environment>
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crustyclaw_config::EnvironmentConfig;
+
+use super::window::{ContextItem, ContextKind, ContextWindow};
+use crate::llm::types::{ChatRequest, ToolDefinition};
+
+/// Priority of the environment item in the context window (just below the
+/// system prompt itself).
+pub const ENVIRONMENT_PRIORITY: u32 = 900;
+
+/// A fact the environment preamble can report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvironmentFact {
+    /// Machine hostname.
+    Hostname,
+    /// Operating system, architecture, and distribution name.
+    Os,
+    /// Root of the repository the daemon is working in.
+    RepoRoot,
+    /// Current UTC time in RFC 3339 format.
+    Time,
+    /// Active persona.
+    Persona,
+    /// Names and one-line descriptions of the tools offered to the model.
+    Tools,
+}
+
+impl EnvironmentFact {
+    /// All facts, in rendering order.
+    pub const ALL: [EnvironmentFact; 6] = [
+        Self::Hostname,
+        Self::Os,
+        Self::RepoRoot,
+        Self::Time,
+        Self::Persona,
+        Self::Tools,
+    ];
+
+    /// The name used in config and template placeholders.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Hostname => "hostname",
+            Self::Os => "os",
+            Self::RepoRoot => "repo_root",
+            Self::Time => "time",
+            Self::Persona => "persona",
+            Self::Tools => "tools",
+        }
+    }
+
+    /// Parse a fact name; `None` if it is not recognised.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|f| f.name() == name)
+    }
+}
+
+/// Builds the environment preamble from configuration and host state.
+///
+/// Host facts that don't change while the daemon runs (hostname, OS, repo
+/// root) are collected once at construction; time and tools are resolved on
+/// every render.
+#[derive(Debug, Clone)]
+pub struct EnvironmentProvider {
+    enabled: bool,
+    facts: Vec<EnvironmentFact>,
+    template: Option<String>,
+    persona: Option<String>,
+    hostname: String,
+    os: String,
+    repo_root: Option<PathBuf>,
+}
+
+impl EnvironmentProvider {
+    /// Create a provider from `[context.environment]`, probing the host.
+    ///
+    /// Unknown fact names are ignored; config validation rejects them before
+    /// this point.
+    pub fn from_config(config: &EnvironmentConfig) -> Self {
+        let repo_root = match &config.repo_root {
+            Some(root) => Some(PathBuf::from(root)),
+            None => std::env::current_dir()
+                .ok()
+                .and_then(|cwd| find_repo_root(&cwd)),
+        };
+        Self {
+            enabled: config.enabled,
+            facts: config
+                .facts
+                .iter()
+                .filter_map(|f| EnvironmentFact::from_name(f))
+                .collect(),
+            template: config.template.clone(),
+            persona: config.persona.clone(),
+            hostname: detect_hostname(),
+            os: detect_os(),
+            repo_root,
+        }
+    }
+
+    /// Override the detected hostname.
+    pub fn with_hostname(mut self, hostname: impl Into<String>) -> Self {
+        self.hostname = hostname.into();
+        self
+    }
+
+    /// Override the detected OS description.
+    pub fn with_os(mut self, os: impl Into<String>) -> Self {
+        self.os = os.into();
+        self
+    }
+
+    /// Override the repository root.
+    pub fn with_repo_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.repo_root = Some(root.into());
+        self
+    }
+
+    /// Set the active persona.
+    pub fn with_persona(mut self, persona: impl Into<String>) -> Self {
+        self.persona = Some(persona.into());
+        self
+    }
+
+    /// Whether the preamble is injected at all.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Resolve a single fact to its display value.
+    pub fn fact(&self, fact: EnvironmentFact, tools: &[ToolDefinition]) -> String {
+        match fact {
+            EnvironmentFact::Hostname => self.hostname.clone(),
+            EnvironmentFact::Os => self.os.clone(),
+            EnvironmentFact::RepoRoot => self
+                .repo_root
+                .as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_else(|| "none".to_string()),
+            EnvironmentFact::Time => format_rfc3339(SystemTime::now()),
+            EnvironmentFact::Persona => self
+                .persona
+                .clone()
+                .unwrap_or_else(|| "default".to_string()),
+            EnvironmentFact::Tools => summarize_tools(tools),
+        }
+    }
+
+    /// Render the preamble, or `None` when disabled.
+    ///
+    /// With a template, each `{fact}` placeholder for an enabled fact is
+    /// replaced; placeholders for facts that were not selected are replaced
+    /// with an empty string so nothing the operator excluded leaks through.
+    pub fn render(&self, tools: &[ToolDefinition]) -> Option<String> {
+        if !self.enabled {
+            return None;
+        }
+        match &self.template {
+            Some(template) => {
+                let mut out = template.clone();
+                for fact in EnvironmentFact::ALL {
+                    let placeholder = format!("{{{}}}", fact.name());
+                    if !out.contains(&placeholder) {
+                        continue;
+                    }
+                    let value = if self.facts.contains(&fact) {
+                        self.fact(fact, tools)
+                    } else {
+                        String::new()
+                    };
+                    out = out.replace(&placeholder, &value);
+                }
+                Some(out)
+            }
+            None => {
+                let mut out = String::from("<environment>\n");
+                for fact in EnvironmentFact::ALL {
+                    if self.facts.contains(&fact) {
+                        out.push_str(&format!("{}: {}\n", fact.name(), self.fact(fact, tools)));
+                    }
+                }
+                out.push_str("</environment>");
+                Some(out)
+            }
+        }
+    }
+
+    /// Render the preamble as a system context item for a [`ContextWindow`].
+    pub fn context_item(&self, tools: &[ToolDefinition]) -> Option<ContextItem> {
+        self.render(tools).map(|content| {
+            ContextWindow::item(
+                ContextKind::System,
+                content,
+                ENVIRONMENT_PRIORITY,
+                "environment".to_string(),
+            )
+        })
+    }
+
+    /// Prepend the preamble to a request's system prompt, using the request's
+    /// own tool list for the tools summary.
+    pub fn apply(&self, request: &mut ChatRequest) {
+        let Some(preamble) = self.render(&request.tools) else {
+            return;
+        };
+        request.system = Some(match request.system.take() {
+            Some(system) if !system.is_empty() => format!("{preamble}\n\n{system}"),
+            _ => preamble,
+        });
+    }
+}
+
+/// Walk up from `start` to the nearest directory containing `.git`.
+fn find_repo_root(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+fn detect_hostname() -> String {
+    for path in ["/proc/sys/kernel/hostname", "/etc/hostname"] {
+        if let Ok(name) = std::fs::read_to_string(path) {
+            let name = name.trim();
+            if !name.is_empty() {
+                return name.to_string();
+            }
+        }
+    }
+    std::process::Command::new("hostname")
+        .output()
+        .ok()
+        .map(|out| String::from_utf8_lossy(&out.stdout).trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn detect_os() -> String {
+    let base = format!("{} {}", std::env::consts::OS, std::env::consts::ARCH);
+    let pretty = std::fs::read_to_string("/etc/os-release")
+        .ok()
+        .and_then(|release| parse_pretty_name(&release));
+    match pretty {
+        Some(name) => format!("{base} ({name})"),
+        None => base,
+    }
+}
+
+fn parse_pretty_name(os_release: &str) -> Option<String> {
+    os_release
+        .lines()
+        .find_map(|line| line.strip_prefix("PRETTY_NAME="))
+        .map(|v| v.trim().trim_matches('"').to_string())
+        .filter(|v| !v.is_empty())
+}
+
+fn summarize_tools(tools: &[ToolDefinition]) -> String {
+    if tools.is_empty() {
+        return "none".to_string();
+    }
+    tools
+        .iter()
+        .map(|t| {
+            let summary = t.description.lines().next().unwrap_or("").trim();
+            let summary = summary.strip_suffix('.').unwrap_or(summary);
+            if summary.is_empty() {
+                t.name.clone()
+            } else {
+                format!("{} ({summary})", t.name)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Format a timestamp as RFC 3339 UTC with second precision.
+fn format_rfc3339(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+/// Convert days since 1970-01-01 to a proleptic Gregorian (year, month, day).
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn tool(name: &str, description: &str) -> ToolDefinition {
+        ToolDefinition {
+            name: name.to_string(),
+            description: description.to_string(),
+            parameters: serde_json::json!({}),
+        }
+    }
+
+    fn provider(config: &EnvironmentConfig) -> EnvironmentProvider {
+        EnvironmentProvider::from_config(config)
+            .with_hostname("build-01")
+            .with_os("linux x86_64")
+            .with_repo_root("/srv/app")
+    }
+
+    #[test]
+    fn test_default_block_includes_all_facts() {
+        let env = provider(&EnvironmentConfig::default()).with_persona("reviewer");
+        let text = env
+            .render(&[tool("read_file", "Read a file.\nMore detail.")])
+            .unwrap();
+
+        assert!(text.starts_with("<environment>\n"));
+        assert!(text.ends_with("</environment>"));
+        assert!(text.contains("hostname: build-01\n"));
+        assert!(text.contains("os: linux x86_64\n"));
+        assert!(text.contains("repo_root: /srv/app\n"));
+        assert!(text.contains("persona: reviewer\n"));
+        assert!(text.contains("tools: read_file (Read a file)\n"));
+        assert!(text.contains("time: "));
+    }
+
+    #[test]
+    fn test_fact_selection() {
+        let config = EnvironmentConfig {
+            facts: vec!["os".to_string(), "tools".to_string()],
+            ..Default::default()
+        };
+        let text = provider(&config).render(&[]).unwrap();
+        assert_eq!(
+            text,
+            "<environment>\nos: linux x86_64\ntools: none\n</environment>"
+        );
+    }
+
+    #[test]
+    fn test_template_blanks_unselected_facts() {
+        let config = EnvironmentConfig {
+            facts: vec!["hostname".to_string()],
+            template: Some("host={hostname} root={repo_root}".to_string()),
+            ..Default::default()
+        };
+        let text = provider(&config).render(&[]).unwrap();
+        assert_eq!(text, "host=build-01 root=");
+    }
+
+    #[test]
+    fn test_disabled_renders_nothing() {
+        let config = EnvironmentConfig {
+            enabled: false,
+            ..Default::default()
+        };
+        let env = provider(&config);
+        assert!(env.render(&[]).is_none());
+        assert!(env.context_item(&[]).is_none());
+
+        let mut request = ChatRequest::default();
+        env.apply(&mut request);
+        assert!(request.system.is_none());
+    }
+
+    #[test]
+    fn test_apply_prepends_to_system_prompt() {
+        let config = EnvironmentConfig {
+            facts: vec!["hostname".to_string()],
+            ..Default::default()
+        };
+        let env = provider(&config);
+        let mut request = ChatRequest {
+            system: Some("You are helpful.".to_string()),
+            ..Default::default()
+        };
+        env.apply(&mut request);
+        assert_eq!(
+            request.system.as_deref(),
+            Some("<environment>\nhostname: build-01\n</environment>\n\nYou are helpful.")
+        );
+
+        let item = env.context_item(&[]).unwrap();
+        assert_eq!(item.kind, ContextKind::System);
+        assert_eq!(item.priority, ENVIRONMENT_PRIORITY);
+    }
+
+    #[test]
+    fn test_find_repo_root() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join(".git")).unwrap();
+        let nested = dir.path().join("src/deep");
+        std::fs::create_dir_all(&nested).unwrap();
+        assert_eq!(find_repo_root(&nested).as_deref(), Some(dir.path()));
+    }
+
+    #[test]
+    fn test_format_rfc3339() {
+        assert_eq!(format_rfc3339(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        let t = UNIX_EPOCH + Duration::from_secs(1_709_251_199);
+        assert_eq!(format_rfc3339(t), "2024-02-29T23:59:59Z");
+    }
+
+    #[test]
+    fn test_parse_pretty_name() {
+        let release = "NAME=\"Debian\"\nPRETTY_NAME=\"Debian GNU/Linux 12 (bookworm)\"\n";
+        assert_eq!(
+            parse_pretty_name(release).as_deref(),
+            Some("Debian GNU/Linux 12 (bookworm)")
+        );
+        assert!(parse_pretty_name("NAME=x\n").is_none());
+    }
+}
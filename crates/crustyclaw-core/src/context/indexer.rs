@@ -9,6 +9,8 @@
 //! The indexer walks a directory tree, identifies source files by extension,
 //! and extracts symbols using language-specific patterns. The extracted symbols
 //! are stored in an in-memory index that can be queried by name, kind, or path.
+//! Files and directories matched by the index's [`SensitivePaths`] are skipped
+//! without being read.
 //!
 //! In a future version, this will use tree-sitter AST parsing for more accurate
 //! symbol extraction. The current implementation uses regex patterns as a
@@ -19,6 +21,8 @@ use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
 
+use super::sensitive::SensitivePaths;
+
 /// A code symbol extracted from a source file.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Symbol {
@@ -73,6 +77,8 @@ pub struct SymbolIndex {
     by_path: HashMap<PathBuf, Vec<Symbol>>,
     /// Flat list of all symbols.
     all: Vec<Symbol>,
+    /// Paths that are never indexed.
+    sensitive: SensitivePaths,
 }
 
 impl SymbolIndex {
@@ -81,11 +87,29 @@ impl SymbolIndex {
         Self {
             by_path: HashMap::new(),
             all: Vec::new(),
+            sensitive: SensitivePaths::new(),
         }
     }
 
+    /// Replace the sensitive path filter (the built-in patterns are always
+    /// part of a [`SensitivePaths`]).
+    pub fn with_sensitive_paths(mut self, sensitive: SensitivePaths) -> Self {
+        self.sensitive = sensitive;
+        self
+    }
+
+    /// The sensitive path filter applied by this index.
+    pub fn sensitive_paths(&self) -> &SensitivePaths {
+        &self.sensitive
+    }
+
     /// Index a single file by extracting symbols from its content.
+    ///
+    /// Sensitive paths are ignored.
     pub fn index_file(&mut self, path: &Path, content: &str) {
+        if self.sensitive.is_sensitive(path) {
+            return;
+        }
         let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
 
         let symbols = match ext {
@@ -107,7 +131,8 @@ impl SymbolIndex {
     /// Index all supported files under a directory tree.
     pub fn index_directory(&mut self, root: &Path) -> std::io::Result<usize> {
         let mut count = 0;
-        index_dir_recursive(root, &mut |path, content| {
+        let sensitive = self.sensitive.clone();
+        index_dir_recursive(root, &sensitive, &mut |path, content| {
             self.index_file(path, content);
             count += 1;
         })?;
@@ -183,8 +208,13 @@ impl Default for SymbolIndex {
     }
 }
 
-/// Walk a directory recursively, calling `visitor` for each supported source file.
-fn index_dir_recursive(dir: &Path, visitor: &mut dyn FnMut(&Path, &str)) -> std::io::Result<()> {
+/// Walk a directory recursively, calling `visitor` for each supported source
+/// file that is not matched by `sensitive`.
+fn index_dir_recursive(
+    dir: &Path,
+    sensitive: &SensitivePaths,
+    visitor: &mut dyn FnMut(&Path, &str),
+) -> std::io::Result<()> {
     let supported_extensions = ["rs", "ts", "tsx", "js", "jsx", "py", "go"];
 
     if !dir.is_dir() {
@@ -201,9 +231,12 @@ fn index_dir_recursive(dir: &Path, visitor: &mut dyn FnMut(&Path, &str)) -> std:
         {
             continue;
         }
+        if sensitive.is_sensitive(&path) {
+            continue;
+        }
 
         if path.is_dir() {
-            index_dir_recursive(&path, visitor)?;
+            index_dir_recursive(&path, sensitive, visitor)?;
         } else if let Some(ext) = path.extension().and_then(|e| e.to_str())
             && supported_extensions.contains(&ext)
             && let Ok(content) = std::fs::read_to_string(&path)
@@ -571,6 +604,39 @@ mod tests {
         assert!(summary.contains("1 files"));
     }
 
+    #[test]
+    fn test_index_file_skips_sensitive() {
+        let mut index =
+            SymbolIndex::new().with_sensitive_paths(SensitivePaths::new().with_glob("secret_*"));
+        index.index_file(Path::new("src/secret_keys.rs"), "pub fn key() {}\n");
+        index.index_file(Path::new("id_tool/lib.rs"), "pub fn key() {}\n");
+        index.index_file(Path::new("src/lib.rs"), "pub fn run() {}\n");
+        assert_eq!(index.file_count(), 1);
+        assert_eq!(index.symbols()[0].name, "run");
+    }
+
+    #[test]
+    fn test_index_directory_skips_sensitive() {
+        let root = std::env::temp_dir().join(format!("crustyclaw-index-{}", std::process::id()));
+        let staging = root.join("staging");
+        std::fs::create_dir_all(&staging).unwrap();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/lib.rs"), "pub fn run() {}\n").unwrap();
+        std::fs::write(root.join("src/creds.rs"), "pub fn leak() {}\n").unwrap();
+        std::fs::write(staging.join("token.rs"), "pub fn token() {}\n").unwrap();
+
+        let mut index = SymbolIndex::new().with_sensitive_paths(
+            SensitivePaths::new()
+                .with_dir(&staging)
+                .with_glob("creds.rs"),
+        );
+        let count = index.index_directory(&root).unwrap();
+        assert_eq!(count, 1);
+        assert!(index.search("leak").is_empty());
+        assert!(index.search("token").is_empty());
+        std::fs::remove_dir_all(&root).ok();
+    }
+
     #[test]
     fn test_extract_after_keyword() {
         assert_eq!(
@@ -9,8 +9,22 @@
 //!    types, etc.) for static context. Uses pattern matching with a future upgrade
 //!    path to tree-sitter AST parsing.
 //!
-//! 3. **Context Window** — Token budget management and priority-based context packing.
-//!    Ensures the LLM receives the most relevant context within its token limit.
+//! 3. **Context Window** — Token budget management and relevance-ranked context
+//!    packing. Ensures the LLM receives the most relevant context within its
+//!    token limit, counting tokens with a per-model [`Tokenizer`] and ranking
+//!    dynamic items with a [`RelevanceScorer`]. History that does not fit is
+//!    summarized by a [`Compactor`] rather than dropped.
+//!
+//! Paths matched by [`SensitivePaths`] (secret staging directories, `.env`
+//! files, key material, and `[context] sensitive_globs`) are never indexed or
+//! read, even inside allowed roots.
+//!
+//! [`EnvironmentProvider`] renders a preamble describing the host (hostname,
+//! OS, repository root, time, persona, and available tools) that is prepended
+//! to every system prompt, so skills don't each describe the environment.
+//!
+//! A tool call above the caller's trust level can run once through an
+//! operator-approved [`ElevationRequest`]; see [`elevation`].
 //!
 //! ## Architecture
 //!
@@ -30,10 +44,27 @@
 //! └──────────────────────────────────────────────┘
 //! ```
 
+pub mod compaction;
+pub mod elevation;
+pub mod environment;
 pub mod indexer;
+pub mod relevance;
+pub mod schema;
+pub mod sensitive;
+pub mod tokenizer;
 pub mod tools;
 pub mod window;
 
+pub use compaction::{Compaction, Compactor};
+pub use elevation::{
+    ElevationAsk, ElevationAuditRecord, ElevationError, ElevationQueue, ElevationRequest,
+    ElevationStatus,
+};
+pub use environment::{EnvironmentFact, EnvironmentProvider};
 pub use indexer::{Symbol, SymbolIndex, SymbolKind};
-pub use tools::{RegisteredTool, ToolRegistry, ToolTrust};
+pub use relevance::{RelevanceScore, RelevanceScorer};
+pub use schema::{SchemaError, SchemaViolation, ToolSchema};
+pub use sensitive::{SensitivePathError, SensitivePaths};
+pub use tokenizer::{ApproxTokenizer, BpeTokenizer, HeuristicTokenizer, Tokenizer, TokenizerError};
+pub use tools::{RegisteredTool, ToolError, ToolExecutor, ToolRegistry, ToolTrust};
 pub use window::{ContextItem, ContextKind, ContextWindow};
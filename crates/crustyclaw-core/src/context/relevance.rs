@@ -0,0 +1,263 @@
+//! Relevance scoring for dynamic context items.
+//!
+//! [`RelevanceScorer`] ranks conversation, code, and retrieval items for
+//! [`ContextWindow::pack_ranked`](super::ContextWindow::pack_ranked) by a
+//! weighted sum of three signals, each in `[0, 1]`:
+//!
+//! - **query** — the fraction of the query's terms found in the item's
+//!   content or source;
+//! - **recency** — how recently the item changed, halving every
+//!   `recency_half_life_hours`. Taken from [`ContextItem::modified_secs`],
+//!   or for file sources from the newer of the file's mtime and its last
+//!   commit in `git log`;
+//! - **usage** — how often the item's source was packed in prior turns,
+//!   relative to the most-used source.
+//!
+//! Weights come from `[context.relevance]`. The per-signal breakdown is kept
+//! on each item and shown by [`ContextWindow::explain`](super::ContextWindow::explain).
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crustyclaw_config::RelevanceConfig;
+use serde::{Deserialize, Serialize};
+
+use super::window::ContextItem;
+
+/// An item's relevance score and the signals it was computed from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct RelevanceScore {
+    /// Query term overlap in `[0, 1]`.
+    pub query: f64,
+    /// File recency in `[0, 1]`.
+    pub recency: f64,
+    /// Prior-turn usage frequency in `[0, 1]`.
+    pub usage: f64,
+    /// Weighted sum of the signals.
+    pub total: f64,
+}
+
+/// Scores context items against a query.
+pub struct RelevanceScorer {
+    config: RelevanceConfig,
+    /// Times each source was packed in prior turns.
+    usage: HashMap<String, u32>,
+    /// Fixed "now" for tests; the wall clock when `None`.
+    now: Option<SystemTime>,
+    /// Looked-up modification times by source path.
+    modified: Mutex<HashMap<String, Option<u64>>>,
+}
+
+impl RelevanceScorer {
+    /// Create a scorer with the configured weights and no usage history.
+    pub fn new(config: &RelevanceConfig) -> Self {
+        Self {
+            config: config.clone(),
+            usage: HashMap::new(),
+            now: None,
+            modified: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Builder: measure recency against `now` instead of the wall clock.
+    pub fn with_now(mut self, now: SystemTime) -> Self {
+        self.now = Some(now);
+        self
+    }
+
+    /// Replace the weights, keeping the usage history.
+    pub fn reconfigure(&mut self, config: &RelevanceConfig) {
+        self.config = config.clone();
+    }
+
+    /// Count one use of each packed item's source, for later turns.
+    pub fn record_usage(&mut self, items: &[ContextItem]) {
+        let sources: HashSet<&str> = items.iter().map(|i| i.source.as_str()).collect();
+        for source in sources {
+            *self.usage.entry(source.to_string()).or_default() += 1;
+        }
+    }
+
+    /// Times `source` has been packed in prior turns.
+    pub fn usage_count(&self, source: &str) -> u32 {
+        self.usage.get(source).copied().unwrap_or_default()
+    }
+
+    /// Score `item` against `query`.
+    pub fn score(&self, item: &ContextItem, query: &str) -> RelevanceScore {
+        let query = query_signal(query, item);
+        let recency = self.recency_signal(item);
+        let usage = self.usage_signal(&item.source);
+        RelevanceScore {
+            query,
+            recency,
+            usage,
+            total: self.config.query_weight * query
+                + self.config.recency_weight * recency
+                + self.config.usage_weight * usage,
+        }
+    }
+
+    fn recency_signal(&self, item: &ContextItem) -> f64 {
+        let Some(modified) = item
+            .modified_secs
+            .or_else(|| self.modified_secs(&item.source))
+        else {
+            return 0.0;
+        };
+        let now = self
+            .now
+            .unwrap_or_else(SystemTime::now)
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let age_hours = now.saturating_sub(modified) as f64 / 3600.0;
+        0.5f64.powf(age_hours / self.config.recency_half_life_hours as f64)
+    }
+
+    fn usage_signal(&self, source: &str) -> f64 {
+        let max = self.usage.values().copied().max().unwrap_or_default();
+        if max == 0 {
+            return 0.0;
+        }
+        f64::from(self.usage_count(source)) / f64::from(max)
+    }
+
+    /// The last change to the file at `source`, cached per scorer.
+    fn modified_secs(&self, source: &str) -> Option<u64> {
+        let mut cache = self.modified.lock().unwrap_or_else(|e| e.into_inner());
+        *cache
+            .entry(source.to_string())
+            .or_insert_with(|| last_modified(Path::new(source)))
+    }
+}
+
+/// The newer of `path`'s mtime and its last commit time, in unix seconds.
+fn last_modified(path: &Path) -> Option<u64> {
+    let mtime = std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())?;
+    // The mtime covers uncommitted edits; git covers checkouts that reset it.
+    let dir = path.parent().filter(|p| !p.as_os_str().is_empty());
+    let committed = std::process::Command::new("git")
+        .arg("-C")
+        .arg(dir.unwrap_or(Path::new(".")))
+        .args(["log", "-1", "--format=%ct", "--"])
+        .arg(path.file_name()?)
+        .stderr(std::process::Stdio::null())
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .and_then(|o| {
+            String::from_utf8_lossy(&o.stdout)
+                .trim()
+                .parse::<u64>()
+                .ok()
+        });
+    Some(committed.map_or(mtime, |c| c.max(mtime)))
+}
+
+/// Fraction of the distinct terms of `query` that appear in `item`.
+fn query_signal(query: &str, item: &ContextItem) -> f64 {
+    let wanted = terms(query);
+    if wanted.is_empty() {
+        return 0.0;
+    }
+    let mut present = terms(&item.content);
+    present.extend(terms(&item.source));
+    let hits = wanted.iter().filter(|t| present.contains(*t)).count();
+    hits as f64 / wanted.len() as f64
+}
+
+/// Lowercased identifier-like words of two or more characters.
+fn terms(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|w| w.chars().count() >= 2)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::context::{ContextKind, ContextWindow};
+
+    /// Unix seconds `age` before `now`.
+    fn secs_before(now: SystemTime, age: Duration) -> u64 {
+        (now - age).duration_since(UNIX_EPOCH).unwrap().as_secs()
+    }
+
+    fn code(source: &str, content: &str) -> ContextItem {
+        ContextWindow::item(
+            ContextKind::Code,
+            content.to_string(),
+            0,
+            source.to_string(),
+        )
+    }
+
+    #[test]
+    fn test_query_signal() {
+        let item = code("src/daemon.rs", "fn reload_config(&self) { /* SIGHUP */ }");
+        assert_eq!(query_signal("SIGHUP handler", &item), 0.5);
+        assert_eq!(query_signal("daemon reload_config", &item), 1.0);
+        assert_eq!(query_signal("", &item), 0.0);
+    }
+
+    #[test]
+    fn test_recency_halves_per_half_life() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        let config = RelevanceConfig {
+            recency_half_life_hours: 24,
+            ..RelevanceConfig::default()
+        };
+        let scorer = RelevanceScorer::new(&config).with_now(now);
+
+        let mut item = code("generated", "");
+        item.modified_secs = Some(secs_before(now, Duration::ZERO));
+        assert_eq!(scorer.score(&item, "").recency, 1.0);
+        item.modified_secs = Some(secs_before(now, Duration::from_secs(48 * 3600)));
+        assert_eq!(scorer.score(&item, "").recency, 0.25);
+
+        // Without a timestamp or a file to look up, recency contributes nothing.
+        item.modified_secs = None;
+        assert_eq!(scorer.score(&item, "").recency, 0.0);
+    }
+
+    #[test]
+    fn test_recency_from_file_mtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fresh.rs");
+        std::fs::write(&path, "fn fresh() {}").unwrap();
+
+        let scorer = RelevanceScorer::new(&RelevanceConfig::default());
+        let score = scorer.score(&code(&path.display().to_string(), ""), "");
+        assert!(score.recency > 0.99, "{score:?}");
+    }
+
+    #[test]
+    fn test_usage_and_weights() {
+        let config = RelevanceConfig {
+            query_weight: 0.0,
+            recency_weight: 0.0,
+            usage_weight: 2.0,
+            ..RelevanceConfig::default()
+        };
+        let mut scorer = RelevanceScorer::new(&config);
+        let hot = code("hot.rs", "");
+        let warm = code("warm.rs", "");
+        scorer.record_usage(&[hot.clone(), warm.clone()]);
+        scorer.record_usage(&[hot.clone(), hot.clone()]);
+        assert_eq!(scorer.usage_count("hot.rs"), 2);
+
+        assert_eq!(scorer.score(&hot, "").total, 2.0);
+        assert_eq!(scorer.score(&warm, "").usage, 0.5);
+        assert_eq!(scorer.score(&code("cold.rs", ""), "").total, 0.0);
+    }
+}
@@ -0,0 +1,203 @@
+//! Tool argument validation against the tool's JSON Schema.
+//!
+//! Models get arguments wrong: a number passed as a string, a required
+//! property left out, a hallucinated option. Each tool's `parameters`
+//! schema is compiled into a [`ToolSchema`] when the tool is registered,
+//! and a tool whose schema does not compile is refused. The
+//! [`ToolExecutor`](super::ToolExecutor) checks a call's arguments against
+//! it before running the tool and reports every [`SchemaViolation`] with
+//! the JSON pointer of the offending value, so the model can correct the
+//! call instead of a malformed value reaching a sandboxed command.
+//!
+//! Validation is done by the `jsonschema` crate, so every keyword of the
+//! schema's draft applies, including `$ref` into `$defs`. An optional
+//! top-level argument passed as `null` is treated as absent, as the
+//! built-in tools do.
+
+use std::sync::Arc;
+
+use serde_json::{Map, Value};
+
+/// A schema that failed to compile.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("invalid parameter schema: {0}")]
+pub struct SchemaError(String);
+
+/// One way a value does not match its schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaViolation {
+    /// JSON pointer to the value, empty for the arguments object itself.
+    pub pointer: String,
+    /// What is wrong with it.
+    pub message: String,
+}
+
+impl std::fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.pointer.as_str() {
+            "" => f.write_str(&self.message),
+            pointer => write!(f, "{pointer}: {}", self.message),
+        }
+    }
+}
+
+/// A tool's compiled parameter schema.
+#[derive(Clone)]
+pub struct ToolSchema {
+    validator: Arc<jsonschema::Validator>,
+    /// Top-level properties a call must pass.
+    required: Vec<String>,
+}
+
+impl ToolSchema {
+    /// Compile `schema`, refusing one that is not a valid JSON Schema
+    /// (an unknown type, a malformed `pattern`, a dangling `$ref`).
+    pub fn compile(schema: &Value) -> Result<Self, SchemaError> {
+        let validator =
+            jsonschema::validator_for(schema).map_err(|e| SchemaError(e.to_string()))?;
+        let required = schema
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .map(str::to_string)
+            .collect();
+        Ok(Self {
+            validator: Arc::new(validator),
+            required,
+        })
+    }
+
+    /// Every way `args` does not match the schema; empty when it matches.
+    pub fn validate(&self, args: &Value) -> Vec<SchemaViolation> {
+        let stripped;
+        let args = match args {
+            Value::Object(object) if object.values().any(Value::is_null) => {
+                stripped = Value::Object(self.without_optional_nulls(object));
+                &stripped
+            }
+            _ => args,
+        };
+        self.validator
+            .iter_errors(args)
+            .map(|error| SchemaViolation {
+                pointer: error.instance_path().to_string(),
+                message: error.to_string(),
+            })
+            .collect()
+    }
+
+    fn without_optional_nulls(&self, object: &Map<String, Value>) -> Map<String, Value> {
+        object
+            .iter()
+            .filter(|(name, value)| !value.is_null() || self.required.contains(name))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect()
+    }
+}
+
+impl std::fmt::Debug for ToolSchema {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ToolSchema")
+            .field("required", &self.required)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// The violations as sorted messages.
+    fn messages(schema: &Value, value: Value) -> Vec<String> {
+        let mut messages: Vec<String> = ToolSchema::compile(schema)
+            .unwrap()
+            .validate(&value)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        messages.sort();
+        messages
+    }
+
+    #[test]
+    fn test_validate_object() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "path": {"type": "string", "minLength": 1},
+                "start_line": {"type": "integer", "minimum": 1},
+                "mode": {"enum": ["fast", "full"]},
+                "tags": {"type": "array", "items": {"type": "string"}, "maxItems": 2}
+            },
+            "required": ["path"],
+            "additionalProperties": false
+        });
+
+        assert!(messages(&schema, json!({"path": "src/lib.rs"})).is_empty());
+        // Optional properties may be null; required ones may not.
+        assert!(messages(&schema, json!({"path": "a", "start_line": null})).is_empty());
+        assert_eq!(
+            messages(&schema, json!({"path": null})),
+            ["/path: null is not of type \"string\""]
+        );
+
+        let violations = ToolSchema::compile(&schema).unwrap().validate(
+            &json!({"start_line": "2", "mode": "slow", "tags": ["a", 1, "c"], "verbose": true}),
+        );
+        let mut pointers: Vec<&str> = violations.iter().map(|v| v.pointer.as_str()).collect();
+        pointers.sort();
+        assert_eq!(
+            pointers,
+            ["", "", "/mode", "/start_line", "/tags", "/tags/1"]
+        );
+        assert_eq!(
+            messages(&schema, json!({"path": "", "start_line": 0})),
+            [
+                "/path: \"\" is shorter than 1 character",
+                "/start_line: 0 is less than the minimum of 1",
+            ]
+        );
+        assert_eq!(
+            messages(&schema, json!(["src/lib.rs"])),
+            ["[\"src/lib.rs\"] is not of type \"object\""]
+        );
+    }
+
+    #[test]
+    fn test_validate_combinators_and_refs() {
+        let schema = json!({
+            "anyOf": [{"type": "string", "pattern": "^[a-z]+$"}, {"type": "integer"}]
+        });
+        assert!(messages(&schema, json!("abc")).is_empty());
+        assert!(messages(&schema, json!(7)).is_empty());
+        assert_eq!(messages(&schema, json!("ABC")).len(), 1);
+
+        let schema = json!({"oneOf": [{"type": "number"}, {"type": "integer"}]});
+        assert_eq!(messages(&schema, json!(3)).len(), 1);
+
+        let schema = json!({
+            "type": "object",
+            "properties": {"target": {"$ref": "#/$defs/target"}},
+            "$defs": {"target": {"type": "string", "enum": ["web", "db"]}}
+        });
+        assert!(messages(&schema, json!({"target": "web"})).is_empty());
+        assert_eq!(
+            messages(&schema, json!({"target": "cache"})),
+            ["/target: \"cache\" is not one of \"web\" or \"db\""]
+        );
+    }
+
+    #[test]
+    fn test_compile_rejects_invalid_schemas() {
+        for schema in [
+            json!({"type": "string", "pattern": "(unclosed"}),
+            json!({"type": "strnig"}),
+            json!({"properties": {"a": {"$ref": "#/$defs/missing"}}}),
+        ] {
+            assert!(ToolSchema::compile(&schema).is_err(), "{schema}");
+        }
+    }
+}
@@ -0,0 +1,470 @@
+//! Token counting — per-model tokenizers for context budget packing.
+//!
+//! The [`ContextWindow`](super::ContextWindow) packs items against the
+//! provider's real context limit, so its token counts need to track the
+//! provider's tokenizer closely enough that a packed prompt is never rejected
+//! as too long. The tokenizer is selected from `[llm] model`:
+//!
+//! | Model | Tokenizer |
+//! |-------|-----------|
+//! | `gpt-*`, `o1*`, `o3*`, `o4*` | [`BpeTokenizer`] when `llm.tokenizer_vocab` points at a tiktoken rank file, otherwise [`ApproxTokenizer::openai`] |
+//! | `claude-*` | [`ApproxTokenizer::anthropic`] (the Claude vocabulary is not published) |
+//! | anything else | [`HeuristicTokenizer`] (~4 bytes per token) |
+//!
+//! Text is first split into pieces the way tiktoken's `cl100k_base` pattern
+//! does (contractions, letter runs with an optional leading symbol, 1–3
+//! digit groups, punctuation runs, whitespace). [`BpeTokenizer`] then runs
+//! byte-pair merges over each piece; [`ApproxTokenizer`] estimates each piece
+//! from its length, which is much closer than a flat character ratio for
+//! code and non-English text.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use base64::Engine;
+use base64::alphabet;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use crustyclaw_config::LlmConfig;
+
+/// Standard base64 for tiktoken rank files, with or without padding.
+const TOKEN_BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// Counts the tokens a model would see for a piece of text.
+pub trait Tokenizer: Send + Sync + fmt::Debug {
+    /// Short identifier for logs (e.g. "bpe", "anthropic-approx").
+    fn name(&self) -> &str;
+
+    /// Number of tokens in `text`.
+    fn count(&self, text: &str) -> u32;
+}
+
+/// Errors loading a tokenizer vocabulary.
+#[derive(Debug, thiserror::Error)]
+pub enum TokenizerError {
+    #[error("failed to read tokenizer vocabulary '{}': {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("invalid tokenizer vocabulary at line {line}: {reason}")]
+    Vocab { line: usize, reason: String },
+}
+
+/// Flat estimate of one token per four bytes.
+///
+/// Used for models with no known tokenizer.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HeuristicTokenizer;
+
+impl Tokenizer for HeuristicTokenizer {
+    fn name(&self) -> &str {
+        "heuristic"
+    }
+
+    fn count(&self, text: &str) -> u32 {
+        (text.len() as u32).div_ceil(4)
+    }
+}
+
+/// Piece-based estimate for providers whose vocabulary is unavailable.
+///
+/// Each pre-tokenized piece costs at least one token; ASCII characters add
+/// `1 / chars_per_token` each and non-ASCII characters one token each.
+#[derive(Debug, Clone)]
+pub struct ApproxTokenizer {
+    name: &'static str,
+    chars_per_token: f32,
+}
+
+impl ApproxTokenizer {
+    /// Approximation of OpenAI's `cl100k_base` / `o200k_base`.
+    pub fn openai() -> Self {
+        Self {
+            name: "openai-approx",
+            chars_per_token: 4.0,
+        }
+    }
+
+    /// Approximation of the Claude tokenizer, which produces slightly more
+    /// tokens than `cl100k_base` for the same English text.
+    pub fn anthropic() -> Self {
+        Self {
+            name: "anthropic-approx",
+            chars_per_token: 3.5,
+        }
+    }
+}
+
+impl Tokenizer for ApproxTokenizer {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn count(&self, text: &str) -> u32 {
+        split_pieces(text)
+            .into_iter()
+            .map(|piece| {
+                let ascii = piece.bytes().filter(u8::is_ascii).count() as f32;
+                let other = piece.chars().filter(|c| !c.is_ascii()).count() as u32;
+                ((ascii / self.chars_per_token).ceil() as u32 + other).max(1)
+            })
+            .sum()
+    }
+}
+
+/// Byte-level BPE using a tiktoken rank file (e.g. `cl100k_base.tiktoken`).
+///
+/// Counts match tiktoken for ordinary text; special tokens are not
+/// recognised and are counted as plain text.
+#[derive(Clone)]
+pub struct BpeTokenizer {
+    ranks: HashMap<Vec<u8>, u32>,
+}
+
+impl fmt::Debug for BpeTokenizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BpeTokenizer")
+            .field("vocab_size", &self.ranks.len())
+            .finish()
+    }
+}
+
+impl BpeTokenizer {
+    /// Build from a rank table of byte sequences.
+    pub fn from_ranks(ranks: HashMap<Vec<u8>, u32>) -> Self {
+        Self { ranks }
+    }
+
+    /// Parse a tiktoken rank file: one `<base64 token> <rank>` per line.
+    pub fn from_tiktoken(contents: &str) -> Result<Self, TokenizerError> {
+        let mut ranks = HashMap::new();
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let vocab_err = |reason: &str| TokenizerError::Vocab {
+                line: i + 1,
+                reason: reason.to_string(),
+            };
+            let (token, rank) = line
+                .split_once(' ')
+                .ok_or_else(|| vocab_err("expected '<token> <rank>'"))?;
+            let bytes = TOKEN_BASE64
+                .decode(token)
+                .map_err(|_| vocab_err("invalid base64 token"))?;
+            let rank = rank
+                .trim()
+                .parse::<u32>()
+                .map_err(|_| vocab_err("rank is not an integer"))?;
+            ranks.insert(bytes, rank);
+        }
+        Ok(Self { ranks })
+    }
+
+    /// Load a tiktoken rank file from disk.
+    pub fn load(path: &Path) -> Result<Self, TokenizerError> {
+        let contents = std::fs::read_to_string(path).map_err(|e| TokenizerError::Io {
+            path: path.to_path_buf(),
+            source: e,
+        })?;
+        Self::from_tiktoken(&contents)
+    }
+
+    /// Number of BPE tokens for a single pre-tokenized piece.
+    fn count_piece(&self, piece: &[u8]) -> u32 {
+        if piece.len() <= 1 || self.ranks.contains_key(piece) {
+            return piece.len().min(1) as u32;
+        }
+
+        // Token boundaries; merge the lowest-ranked adjacent pair until no
+        // adjacent pair is in the vocabulary.
+        let mut bounds: Vec<usize> = (0..=piece.len()).collect();
+        loop {
+            let best = (0..bounds.len().saturating_sub(2))
+                .filter_map(|i| {
+                    self.ranks
+                        .get(&piece[bounds[i]..bounds[i + 2]])
+                        .map(|&rank| (rank, i))
+                })
+                .min();
+            match best {
+                Some((_, i)) => {
+                    bounds.remove(i + 1);
+                }
+                None => break,
+            }
+        }
+        (bounds.len() - 1) as u32
+    }
+}
+
+impl Tokenizer for BpeTokenizer {
+    fn name(&self) -> &str {
+        "bpe"
+    }
+
+    fn count(&self, text: &str) -> u32 {
+        split_pieces(text)
+            .into_iter()
+            .map(|piece| self.count_piece(piece.as_bytes()))
+            .sum()
+    }
+}
+
+/// Which tokenizer family a model identifier belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ModelFamily {
+    OpenAi,
+    Anthropic,
+    Unknown,
+}
+
+fn model_family(model: &str) -> ModelFamily {
+    let model = model.to_ascii_lowercase();
+    if model.starts_with("claude") {
+        ModelFamily::Anthropic
+    } else if ["gpt-", "chatgpt", "o1", "o3", "o4"]
+        .iter()
+        .any(|p| model.starts_with(p))
+    {
+        ModelFamily::OpenAi
+    } else {
+        ModelFamily::Unknown
+    }
+}
+
+/// Pick the built-in tokenizer for a model identifier.
+pub fn tokenizer_for_model(model: &str) -> Arc<dyn Tokenizer> {
+    match model_family(model) {
+        ModelFamily::OpenAi => Arc::new(ApproxTokenizer::openai()),
+        ModelFamily::Anthropic => Arc::new(ApproxTokenizer::anthropic()),
+        ModelFamily::Unknown => Arc::new(HeuristicTokenizer),
+    }
+}
+
+/// Pick the tokenizer for `[llm]`, loading `tokenizer_vocab` if set.
+pub fn tokenizer_for_config(config: &LlmConfig) -> Result<Arc<dyn Tokenizer>, TokenizerError> {
+    match &config.tokenizer_vocab {
+        Some(path) => Ok(Arc::new(BpeTokenizer::load(Path::new(path))?)),
+        None => Ok(tokenizer_for_model(&config.model)),
+    }
+}
+
+/// Context window size, in tokens, of well-known models.
+pub fn context_limit(model: &str) -> Option<u32> {
+    let model = model.to_ascii_lowercase();
+    let limit = match model_family(&model) {
+        ModelFamily::Anthropic => 200_000,
+        ModelFamily::OpenAi => {
+            if model.starts_with("gpt-4.1") {
+                1_047_576
+            } else if model.starts_with("o1-mini") {
+                128_000
+            } else if model.starts_with('o') {
+                200_000
+            } else if model.starts_with("gpt-4o") || model.starts_with("gpt-4-turbo") {
+                128_000
+            } else if model.starts_with("gpt-4-32k") {
+                32_768
+            } else if model.starts_with("gpt-4") {
+                8_192
+            } else if model.starts_with("gpt-3.5-turbo") {
+                16_385
+            } else {
+                return None;
+            }
+        }
+        ModelFamily::Unknown => return None,
+    };
+    Some(limit)
+}
+
+/// Split text into pieces following tiktoken's `cl100k_base` pattern.
+fn split_pieces(text: &str) -> Vec<&str> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let end_of = |i: usize| chars.get(i).map(|&(b, _)| b).unwrap_or(text.len());
+    let is_letter = |c: char| c.is_alphabetic();
+    let is_number = |c: char| c.is_numeric();
+    let is_newline = |c: char| c == '\r' || c == '\n';
+    let is_symbol = |c: char| !c.is_whitespace() && !is_letter(c) && !is_number(c);
+
+    let mut pieces = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i].1;
+        let next = chars.get(i + 1).map(|&(_, c)| c);
+        let start = i;
+
+        if c == '\''
+            && let Some(len) = contraction_len(&chars[i + 1..])
+        {
+            // 's 't 're 've 'm 'll 'd
+            i += 1 + len;
+        } else if is_letter(c) || (!is_newline(c) && !is_number(c) && next.is_some_and(is_letter)) {
+            // Letter run, optionally led by one space or symbol.
+            i += 1;
+            while i < chars.len() && is_letter(chars[i].1) {
+                i += 1;
+            }
+        } else if is_number(c) {
+            // Digits in groups of up to three.
+            while i < chars.len() && i - start < 3 && is_number(chars[i].1) {
+                i += 1;
+            }
+        } else if is_symbol(c) || (c == ' ' && next.is_some_and(is_symbol)) {
+            // Symbol run, optionally led by a space, plus trailing newlines.
+            i += 1;
+            while i < chars.len() && is_symbol(chars[i].1) {
+                i += 1;
+            }
+            while i < chars.len() && is_newline(chars[i].1) {
+                i += 1;
+            }
+        } else {
+            // Whitespace run.
+            let mut run_end = i;
+            while run_end < chars.len() && chars[run_end].1.is_whitespace() {
+                run_end += 1;
+            }
+            let last_newline = (i..run_end).rev().find(|&j| is_newline(chars[j].1));
+            i = match last_newline {
+                // Whitespace ending in newlines stands alone.
+                Some(j) => j + 1,
+                // Trailing whitespace is one piece.
+                None if run_end == chars.len() => run_end,
+                // Otherwise leave the last space to lead the next piece.
+                None if run_end - i > 1 => run_end - 1,
+                None => run_end,
+            };
+        }
+
+        pieces.push(&text[chars[start].0..end_of(i)]);
+    }
+    pieces
+}
+
+/// Length of an English contraction suffix at the start of `rest` (after
+/// the apostrophe), matched case-insensitively.
+fn contraction_len(rest: &[(usize, char)]) -> Option<usize> {
+    let lower = |i: usize| rest.get(i).map(|&(_, c)| c.to_ascii_lowercase());
+    match (lower(0), lower(1)) {
+        (Some('r'), Some('e')) | (Some('v'), Some('e')) | (Some('l'), Some('l')) => Some(2),
+        (Some('s' | 't' | 'm' | 'd'), _) => Some(1),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_pieces_like_cl100k() {
+        assert_eq!(
+            split_pieces("Hello, world! It's 12345 ok"),
+            vec![
+                "Hello", ",", " world", "!", " It", "'s", " ", "123", "45", " ok"
+            ]
+        );
+        assert_eq!(
+            split_pieces("fn main() {\n    x\n}\n"),
+            vec!["fn", " main", "()", " {\n", "   ", " x", "\n", "}\n"]
+        );
+        assert_eq!(split_pieces("a  "), vec!["a", "  "]);
+        assert!(split_pieces("").is_empty());
+    }
+
+    fn tiny_vocab() -> BpeTokenizer {
+        // Single bytes plus a few merges, in tiktoken file format.
+        let mut file = String::new();
+        let mut rank = 0;
+        for b in 0u8..=255 {
+            file.push_str(&format!("{} {rank}\n", TOKEN_BASE64.encode([b])));
+            rank += 1;
+        }
+        for token in ["he", "ll", "hell", "hello", " w", "or", " wor"] {
+            file.push_str(&format!("{} {rank}\n", TOKEN_BASE64.encode(token)));
+            rank += 1;
+        }
+        BpeTokenizer::from_tiktoken(&file).unwrap()
+    }
+
+    #[test]
+    fn test_bpe_merges() {
+        let bpe = tiny_vocab();
+        // "hello" is a single token; " world" merges " w" + "or" into " wor",
+        // leaving " wor" + "l" + "d".
+        assert_eq!(bpe.count("hello"), 1);
+        assert_eq!(bpe.count(" world"), 3);
+        assert_eq!(bpe.count("hello world"), 4);
+        assert_eq!(bpe.count(""), 0);
+    }
+
+    #[test]
+    fn test_bpe_rejects_bad_vocab() {
+        let err = BpeTokenizer::from_tiktoken("aGk= 0\nnot-a-line\n").unwrap_err();
+        assert!(matches!(err, TokenizerError::Vocab { line: 2, .. }));
+        let err = BpeTokenizer::from_tiktoken("aGk= x\n").unwrap_err();
+        assert!(matches!(err, TokenizerError::Vocab { line: 1, .. }));
+    }
+
+    #[test]
+    fn test_approx_counts_per_piece() {
+        let openai = ApproxTokenizer::openai();
+        // "Hello" (2) "," (1) " world" (2) "!" (1)
+        assert_eq!(openai.count("Hello, world!"), 6);
+        // Non-ASCII characters count individually.
+        assert_eq!(openai.count("日本語"), 3);
+        let anthropic = ApproxTokenizer::anthropic();
+        assert!(
+            anthropic.count(&"tokenization ".repeat(50))
+                >= openai.count(&"tokenization ".repeat(50))
+        );
+    }
+
+    #[test]
+    fn test_model_selection() {
+        assert_eq!(tokenizer_for_model("gpt-4o").name(), "openai-approx");
+        assert_eq!(tokenizer_for_model("o3-mini").name(), "openai-approx");
+        assert_eq!(
+            tokenizer_for_model("claude-sonnet-4-20250514").name(),
+            "anthropic-approx"
+        );
+        assert_eq!(tokenizer_for_model("llama3").name(), "heuristic");
+
+        assert_eq!(context_limit("claude-sonnet-4-20250514"), Some(200_000));
+        assert_eq!(context_limit("gpt-4o-mini"), Some(128_000));
+        assert_eq!(context_limit("gpt-4"), Some(8_192));
+        assert_eq!(context_limit("llama3"), None);
+    }
+
+    #[test]
+    fn test_tokenizer_for_config_loads_vocab() {
+        let dir = tempfile::tempdir().unwrap();
+        let vocab = dir.path().join("tiny.tiktoken");
+        std::fs::write(&vocab, "aGk= 0\n").unwrap();
+
+        let config = LlmConfig {
+            model: "gpt-4o".to_string(),
+            tokenizer_vocab: Some(vocab.display().to_string()),
+            ..LlmConfig::default()
+        };
+        assert_eq!(tokenizer_for_config(&config).unwrap().name(), "bpe");
+
+        let config = LlmConfig {
+            tokenizer_vocab: Some(dir.path().join("missing").display().to_string()),
+            ..LlmConfig::default()
+        };
+        assert!(matches!(
+            tokenizer_for_config(&config),
+            Err(TokenizerError::Io { .. })
+        ));
+    }
+}
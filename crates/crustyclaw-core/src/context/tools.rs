@@ -3,12 +3,43 @@
 //! Tools are the actions an LLM can invoke during skill execution.
 //! Each tool has a name, description, JSON Schema for parameters, and
 //! a trust level that determines which isolation contexts can use it.
+//!
+//! The registry also owns the [`SensitivePaths`] filter that filesystem tools
+//! (`read_file`, `list_files`, `search_code`, `list_symbols`) must apply
+//! before touching a path.
+//!
+//! Operators can add tools without writing Rust: each `[[tools]]` entry in
+//! the config is registered with [`ToolRegistry::register_configured`] and
+//! runs its command in the executor's sandbox.
+//!
+//! [`ToolExecutor`] runs the tool calls in a [`ChatResponse`] and returns
+//! their results as `tool` messages.
 
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
 
+use crustyclaw_config::ToolConfig;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-use crate::llm::types::ToolDefinition;
+use super::indexer::{SymbolIndex, SymbolKind};
+use super::schema::{SchemaError, SchemaViolation, ToolSchema};
+use super::sensitive::{self, SensitivePathError, SensitivePaths};
+use crate::attachment::Attachment;
+use crate::isolation::{
+    IsolationError, LeakScanner, SandboxBackend, SandboxConfig, SandboxPool, SandboxResult,
+    SharedMount,
+};
+use crate::llm::types::{ChatMessage, ChatResponse, ToolCall, ToolDefinition};
+use crate::mcp::{McpError, McpHub};
+use crate::plugin::loader::{PluginError, PluginHost};
+#[cfg(feature = "wasm-plugins")]
+use crate::plugin::wasm::{WasmError, WasmPluginHost};
+use crate::quota::QuotaError;
+use crate::secrets::SecretStore;
+use crate::skill::arg_env_name;
 
 /// Trust level required to invoke a tool.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
@@ -26,6 +57,34 @@ pub enum ToolTrust {
     System,
 }
 
+impl ToolTrust {
+    /// All levels, lowest first.
+    pub const ALL: [ToolTrust; 4] = [Self::Public, Self::Internal, Self::Trusted, Self::System];
+
+    /// The level's config name.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Public => "public",
+            Self::Internal => "internal",
+            Self::Trusted => "trusted",
+            Self::System => "system",
+        }
+    }
+
+    /// Parse a config name (case-insensitive).
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|t| t.name().eq_ignore_ascii_case(name))
+    }
+}
+
+impl std::fmt::Display for ToolTrust {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
 /// A registered tool with metadata.
 #[derive(Debug, Clone)]
 pub struct RegisteredTool {
@@ -45,6 +104,11 @@ pub struct RegisteredTool {
 /// scoped tool sets.
 pub struct ToolRegistry {
     tools: HashMap<String, RegisteredTool>,
+    /// Compiled parameter schemas, by tool name.
+    schemas: HashMap<String, ToolSchema>,
+    sensitive: SensitivePaths,
+    /// Tools registered from `[[tools]]`, with their commands.
+    configured: HashMap<String, ToolConfig>,
 }
 
 impl ToolRegistry {
@@ -52,6 +116,9 @@ impl ToolRegistry {
     pub fn new() -> Self {
         Self {
             tools: HashMap::new(),
+            schemas: HashMap::new(),
+            sensitive: SensitivePaths::new(),
+            configured: HashMap::new(),
         }
     }
 
@@ -62,9 +129,36 @@ impl ToolRegistry {
         registry
     }
 
-    /// Register a tool.
-    pub fn register(&mut self, tool: RegisteredTool) {
-        self.tools.insert(tool.definition.name.clone(), tool);
+    /// Replace the sensitive path filter used by filesystem tools.
+    pub fn with_sensitive_paths(mut self, sensitive: SensitivePaths) -> Self {
+        self.sensitive = sensitive;
+        self
+    }
+
+    /// The sensitive path filter used by filesystem tools.
+    pub fn sensitive_paths(&self) -> &SensitivePaths {
+        &self.sensitive
+    }
+
+    /// Check that a filesystem tool may access `path`.
+    pub fn check_path(&self, path: &std::path::Path) -> Result<(), SensitivePathError> {
+        self.sensitive.check(path)
+    }
+
+    /// Register a tool, compiling its parameter schema. A tool whose
+    /// schema does not compile is refused.
+    pub fn register(&mut self, tool: RegisteredTool) -> Result<(), SchemaError> {
+        let schema = ToolSchema::compile(&tool.definition.parameters)?;
+        let name = tool.definition.name.clone();
+        self.schemas.insert(name.clone(), schema);
+        self.tools.insert(name, tool);
+        Ok(())
+    }
+
+    /// Remove a tool, returning it if it was registered.
+    pub fn unregister(&mut self, name: &str) -> Option<RegisteredTool> {
+        self.schemas.remove(name);
+        self.tools.remove(name)
     }
 
     /// Get a tool by name.
@@ -72,6 +166,11 @@ impl ToolRegistry {
         self.tools.get(name)
     }
 
+    /// The compiled parameter schema of a tool.
+    pub fn schema(&self, name: &str) -> Option<&ToolSchema> {
+        self.schemas.get(name)
+    }
+
     /// List all registered tool names.
     pub fn names(&self) -> Vec<String> {
         let mut names: Vec<_> = self.tools.keys().cloned().collect();
@@ -88,6 +187,44 @@ impl ToolRegistry {
             .collect()
     }
 
+    /// Register the `[[tools]]` from config, replacing the ones registered
+    /// by the previous call. A tool whose name is already taken is skipped
+    /// with a warning. Returns the names registered.
+    pub fn register_configured(&mut self, tools: &[ToolConfig]) -> Vec<String> {
+        for name in std::mem::take(&mut self.configured).into_keys() {
+            self.unregister(&name);
+        }
+        let mut names = Vec::new();
+        for tool in tools {
+            if self.tools.contains_key(&tool.name) {
+                tracing::warn!(tool = %tool.name, "Skipping configured tool that shadows a registered tool");
+                continue;
+            }
+            let registered = self.register(RegisteredTool {
+                definition: ToolDefinition {
+                    name: tool.name.clone(),
+                    description: tool.description.clone(),
+                    parameters: tool.parameters.clone(),
+                },
+                trust: ToolTrust::from_name(&tool.trust).unwrap_or_default(),
+                tags: tool.tags.clone(),
+                enabled: tool.enabled,
+            });
+            if let Err(e) = registered {
+                tracing::warn!(tool = %tool.name, error = %e, "Skipping configured tool");
+                continue;
+            }
+            self.configured.insert(tool.name.clone(), tool.clone());
+            names.push(tool.name.clone());
+        }
+        names
+    }
+
+    /// The `[[tools]]` entry behind a configured tool.
+    pub fn configured(&self, name: &str) -> Option<&ToolConfig> {
+        self.configured.get(name)
+    }
+
     /// Get tool definitions filtered by trust level and optional tags.
     ///
     /// Returns only tools where the caller's trust level meets or exceeds
@@ -113,10 +250,15 @@ impl ToolRegistry {
             .collect()
     }
 
+    /// Register a built-in tool, whose schema is known to compile.
+    fn register_builtin(&mut self, tool: RegisteredTool) {
+        self.register(tool).expect("built-in tool schemas compile");
+    }
+
     /// Register the built-in tools.
     fn register_defaults(&mut self) {
         // Code search tools
-        self.register(RegisteredTool {
+        self.register_builtin(RegisteredTool {
             definition: ToolDefinition {
                 name: "search_code".to_string(),
                 description: "Search the codebase for a pattern using regex or literal string."
@@ -145,10 +287,11 @@ impl ToolRegistry {
             enabled: true,
         });
 
-        self.register(RegisteredTool {
+        self.register_builtin(RegisteredTool {
             definition: ToolDefinition {
                 name: "read_file".to_string(),
-                description: "Read the contents of a file.".to_string(),
+                description: "Read the contents of a file. Secrets, .env files, and key material cannot be read."
+                    .to_string(),
                 parameters: serde_json::json!({
                     "type": "object",
                     "properties": {
@@ -173,10 +316,12 @@ impl ToolRegistry {
             enabled: true,
         });
 
-        self.register(RegisteredTool {
+        self.register_builtin(RegisteredTool {
             definition: ToolDefinition {
                 name: "list_files".to_string(),
-                description: "List files matching a glob pattern.".to_string(),
+                description:
+                    "List files matching a glob pattern. Sensitive paths are omitted from results."
+                        .to_string(),
                 parameters: serde_json::json!({
                     "type": "object",
                     "properties": {
@@ -193,7 +338,7 @@ impl ToolRegistry {
             enabled: true,
         });
 
-        self.register(RegisteredTool {
+        self.register_builtin(RegisteredTool {
             definition: ToolDefinition {
                 name: "list_symbols".to_string(),
                 description: "List code symbols (functions, structs, types) in a file or directory."
@@ -220,7 +365,7 @@ impl ToolRegistry {
         });
 
         // Execution tools
-        self.register(RegisteredTool {
+        self.register_builtin(RegisteredTool {
             definition: ToolDefinition {
                 name: "run_command".to_string(),
                 description: "Execute a shell command in a sandboxed environment.".to_string(),
@@ -248,8 +393,45 @@ impl ToolRegistry {
             enabled: true,
         });
 
+        // Trust elevation; see `context::elevation`
+        self.register_builtin(RegisteredTool {
+            definition: ToolDefinition {
+                name: super::elevation::REQUEST_ELEVATION_TOOL.to_string(),
+                description: "Ask an operator to run one tool call at a higher trust level. \
+                              Describe exactly why the call needs it; the call runs only once, \
+                              with the same arguments, after approval."
+                    .to_string(),
+                parameters: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "tool": {
+                            "type": "string",
+                            "description": "Name of the tool to run"
+                        },
+                        "arguments": {
+                            "type": "object",
+                            "description": "Exact arguments for the call"
+                        },
+                        "level": {
+                            "type": "string",
+                            "enum": ["internal", "trusted", "system"],
+                            "description": "Trust level the call needs"
+                        },
+                        "reason": {
+                            "type": "string",
+                            "description": "Why the call needs elevated trust"
+                        }
+                    },
+                    "required": ["tool", "arguments", "level", "reason"]
+                }),
+            },
+            trust: ToolTrust::Public,
+            tags: vec!["system".to_string()],
+            enabled: true,
+        });
+
         // System tools (daemon management)
-        self.register(RegisteredTool {
+        self.register_builtin(RegisteredTool {
             definition: ToolDefinition {
                 name: "daemon_status".to_string(),
                 description: "Get the current daemon status and configuration.".to_string(),
@@ -272,8 +454,669 @@ impl Default for ToolRegistry {
     }
 }
 
+/// Largest tool output returned to the model, in bytes; longer output is
+/// truncated with a marker.
+pub const MAX_TOOL_OUTPUT: usize = 64 * 1024;
+
+/// Most matches `search_code` and `list_files` return.
+pub const MAX_TOOL_MATCHES: usize = 200;
+
+/// Default `run_command` timeout when the call does not set one.
+const DEFAULT_COMMAND_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Where the allowed root is mounted for `run_command`.
+const COMMAND_WORKSPACE: &str = "/workspace";
+
+/// Why a tool call failed. The message is returned to the model as the
+/// tool result.
+#[derive(Debug, thiserror::Error)]
+pub enum ToolError {
+    #[error("unknown tool: {0}")]
+    UnknownTool(String),
+
+    #[error("tool {0} is disabled")]
+    Disabled(String),
+
+    #[error("tool {tool} requires {required} trust")]
+    Forbidden { tool: String, required: ToolTrust },
+
+    #[error("tool {0} is not offered to this caller")]
+    NotOffered(String),
+
+    #[error("invalid arguments: {0}")]
+    InvalidArguments(String),
+
+    /// The arguments do not match the tool's parameter schema.
+    #[error(
+        "invalid arguments for {tool}: {}; correct them and call the tool again",
+        join_violations(violations)
+    )]
+    SchemaViolation {
+        tool: String,
+        violations: Vec<SchemaViolation>,
+    },
+
+    #[error("{0} is outside the allowed directories")]
+    OutsideRoots(PathBuf),
+
+    #[error(transparent)]
+    Sensitive(#[from] SensitivePathError),
+
+    #[error("{path}: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("sandbox error: {0}")]
+    Sandbox(#[from] IsolationError),
+
+    #[error("MCP error: {0}")]
+    Mcp(#[from] McpError),
+
+    #[error("plugin error: {0}")]
+    Plugin(#[from] PluginError),
+
+    #[cfg(feature = "wasm-plugins")]
+    #[error("WASM plugin error: {0}")]
+    Wasm(#[from] WasmError),
+
+    #[error(transparent)]
+    Quota(#[from] QuotaError),
+
+    #[error("tool {0} cannot be run by the executor")]
+    Unsupported(String),
+}
+
+/// Runs the model's tool calls against real implementations.
+///
+/// Calls are checked against the [`ToolRegistry`] (existence, enabled, and
+/// the caller's trust level), and their arguments against the tool's
+/// parameter schema, before running. Filesystem tools resolve paths
+/// against the first allowed directory, refuse anything outside the allowed
+/// directories, and apply the registry's [`SensitivePaths`]. `run_command`
+/// runs `sh -c` in the configured sandbox with the first allowed directory
+/// mounted at `/workspace`, as do the commands of configured `[[tools]]`,
+/// with their arguments in the environment, and any message attachments
+/// mounted read-only under `/attachments`. Tools imported from MCP servers are forwarded
+/// to the [`McpHub`], and native plugin tools to the [`PluginHost`]. WASM
+/// action plugins run on a blocking thread and call tools through a copy
+/// of the executor without them, at the same caller trust.
+///
+/// `request_elevation` and `daemon_status` are answered by the agent
+/// itself, not the executor.
+#[derive(Clone)]
+pub struct ToolExecutor {
+    registry: Arc<RwLock<ToolRegistry>>,
+    roots: Vec<PathBuf>,
+    index: Arc<RwLock<SymbolIndex>>,
+    sandbox: Option<(Arc<dyn SandboxBackend>, SandboxConfig)>,
+    pool: Option<Arc<SandboxPool>>,
+    attachments: Vec<Attachment>,
+    secrets: Option<Arc<RwLock<SecretStore>>>,
+    mcp: Option<Arc<McpHub>>,
+    plugins: Option<Arc<PluginHost>>,
+    #[cfg(feature = "wasm-plugins")]
+    wasm: Option<Arc<WasmPluginHost>>,
+}
+
+impl ToolExecutor {
+    /// Create an executor whose filesystem tools may access `root` and
+    /// the paths below it.
+    pub fn new(registry: Arc<RwLock<ToolRegistry>>, root: impl Into<PathBuf>) -> Self {
+        Self {
+            registry,
+            roots: vec![root.into()],
+            index: Arc::new(RwLock::new(SymbolIndex::new())),
+            sandbox: None,
+            pool: None,
+            attachments: Vec::new(),
+            secrets: None,
+            mcp: None,
+            plugins: None,
+            #[cfg(feature = "wasm-plugins")]
+            wasm: None,
+        }
+    }
+
+    /// Builder: also allow access to `dir`.
+    pub fn with_allowed_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.roots.push(dir.into());
+        self
+    }
+
+    /// Builder: answer `list_symbols` from `index`. Paths not yet indexed
+    /// are indexed on first use.
+    pub fn with_index(mut self, index: Arc<RwLock<SymbolIndex>>) -> Self {
+        self.index = index;
+        self
+    }
+
+    /// Builder: run `run_command` with `backend`, starting from `config`.
+    pub fn with_sandbox(mut self, backend: Arc<dyn SandboxBackend>, config: SandboxConfig) -> Self {
+        self.sandbox = Some((backend, config));
+        self
+    }
+
+    /// Builder: take a slot in `pool` for each `run_command`.
+    pub fn with_pool(mut self, pool: Arc<SandboxPool>) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Builder: mount the attachments of the message being answered
+    /// read-only under `/attachments` in `run_command` and configured tools.
+    pub fn with_attachments(mut self, attachments: Vec<Attachment>) -> Self {
+        self.attachments = attachments;
+        self
+    }
+
+    /// Builder: redact the values and sentinels of the secrets in `store`
+    /// from `run_command` output and the workspace files it writes.
+    pub fn with_secrets(mut self, store: Arc<RwLock<SecretStore>>) -> Self {
+        self.secrets = Some(store);
+        self
+    }
+
+    /// Builder: forward calls to tools imported from MCP servers.
+    pub fn with_mcp(mut self, mcp: Arc<McpHub>) -> Self {
+        self.mcp = Some(mcp);
+        self
+    }
+
+    /// Builder: forward calls to tools provided by native plugins.
+    pub fn with_plugins(mut self, plugins: Arc<PluginHost>) -> Self {
+        self.plugins = Some(plugins);
+        self
+    }
+
+    /// Builder: run tools provided by WASM action plugins.
+    #[cfg(feature = "wasm-plugins")]
+    pub fn with_wasm(mut self, wasm: Arc<WasmPluginHost>) -> Self {
+        self.wasm = Some(wasm);
+        self
+    }
+
+    /// Run every tool call in `response`, in order, returning one `tool`
+    /// message per call.
+    pub async fn execute_response(
+        &self,
+        response: &ChatResponse,
+        caller_trust: ToolTrust,
+    ) -> Vec<ChatMessage> {
+        let mut results = Vec::new();
+        for call in response.message.tool_calls.iter().flatten() {
+            results.push(self.execute(call, caller_trust).await);
+        }
+        results
+    }
+
+    /// Run one tool call, returning its result as a `tool` message.
+    ///
+    /// Failures are reported in the message content so the model can
+    /// react to them.
+    pub async fn execute(&self, call: &ToolCall, caller_trust: ToolTrust) -> ChatMessage {
+        Self::result_message(call, self.run(call, caller_trust).await)
+    }
+
+    /// The `tool` message reporting `result` for `call`.
+    pub fn result_message(call: &ToolCall, result: Result<String, ToolError>) -> ChatMessage {
+        let content = match result {
+            Ok(output) => truncate_output(output),
+            Err(e) => {
+                tracing::debug!(tool = %call.name, error = %e, "Tool call failed");
+                format!("error: {e}")
+            }
+        };
+        ChatMessage::tool_result(&call.id, content)
+    }
+
+    /// The definitions of the tools a caller with `caller_trust` may use.
+    pub fn definitions(&self, caller_trust: ToolTrust) -> Vec<ToolDefinition> {
+        let registry = self.registry.read().unwrap_or_else(|e| e.into_inner());
+        registry.scoped_definitions(caller_trust, None)
+    }
+
+    /// Run one tool call after checking it against the registry and its
+    /// arguments against the tool's parameter schema.
+    pub async fn run(&self, call: &ToolCall, caller_trust: ToolTrust) -> Result<String, ToolError> {
+        let schema = self.authorize(&call.name, caller_trust)?;
+        let args = &call.arguments;
+        let violations = schema.validate(args);
+        if !violations.is_empty() {
+            return Err(ToolError::SchemaViolation {
+                tool: call.name.clone(),
+                violations,
+            });
+        }
+        if let Some(tool) = self.configured(&call.name) {
+            return self.run_configured(&tool, args).await;
+        }
+        match call.name.as_str() {
+            "read_file" => self.read_file(args),
+            "list_files" => self.list_files(args),
+            "search_code" => self.search_code(args),
+            "list_symbols" => self.list_symbols(args),
+            "run_command" => self.run_command(args).await,
+            #[cfg(feature = "wasm-plugins")]
+            name if self
+                .wasm
+                .as_ref()
+                .is_some_and(|wasm| wasm.tool_names().iter().any(|t| t == name)) =>
+            {
+                self.run_wasm(name, args.clone(), caller_trust).await
+            }
+            name => match &self.mcp {
+                Some(mcp) if mcp.tool_names().iter().any(|t| t == name) => {
+                    let result = mcp.call(name, args.clone()).await?;
+                    match result.is_error {
+                        true => Ok(format!("error: {}", result.text())),
+                        false => Ok(result.text()),
+                    }
+                }
+                _ => match &self.plugins {
+                    Some(plugins) if plugins.tool_names().iter().any(|t| t == name) => {
+                        match plugins.call_tool(name, args.clone()).await {
+                            Err(PluginError::Call(message)) => Ok(format!("error: {message}")),
+                            result => Ok(result?),
+                        }
+                    }
+                    _ => Err(ToolError::Unsupported(name.to_string())),
+                },
+            },
+        }
+    }
+
+    /// Run a WASM action plugin tool. Its own tool calls go through a copy
+    /// of this executor without WASM plugins, blocking the plugin's thread.
+    #[cfg(feature = "wasm-plugins")]
+    async fn run_wasm(
+        &self,
+        name: &str,
+        args: serde_json::Value,
+        caller_trust: ToolTrust,
+    ) -> Result<String, ToolError> {
+        let Some(wasm) = self.wasm.clone() else {
+            return Err(ToolError::Unsupported(name.to_string()));
+        };
+        let mut nested = self.clone();
+        nested.wasm = None;
+        let handle = tokio::runtime::Handle::current();
+        let caller = move |tool: &str, arguments: serde_json::Value| {
+            let call = ToolCall {
+                id: format!("wasm-{tool}"),
+                name: tool.to_string(),
+                arguments,
+            };
+            handle
+                .block_on(nested.run(&call, caller_trust))
+                .map_err(|e| e.to_string())
+        };
+        let name = name.to_string();
+        tokio::task::spawn_blocking(move || wasm.run_tool(&name, &args, Arc::new(caller)))
+            .await
+            .map_err(|e| ToolError::Unsupported(format!("WASM plugin panicked: {e}")))?
+            .map_err(ToolError::from)
+    }
+
+    /// Check that the caller may run `name`, returning its parameter schema.
+    fn authorize(&self, name: &str, caller_trust: ToolTrust) -> Result<ToolSchema, ToolError> {
+        let registry = self.registry.read().unwrap_or_else(|e| e.into_inner());
+        let tool = registry
+            .get(name)
+            .ok_or_else(|| ToolError::UnknownTool(name.to_string()))?;
+        if !tool.enabled {
+            return Err(ToolError::Disabled(name.to_string()));
+        }
+        if tool.trust > caller_trust {
+            return Err(ToolError::Forbidden {
+                tool: name.to_string(),
+                required: tool.trust,
+            });
+        }
+        registry
+            .schema(name)
+            .cloned()
+            .ok_or_else(|| ToolError::UnknownTool(name.to_string()))
+    }
+
+    /// Resolve a path argument to an existing, allowed, non-sensitive path.
+    fn resolve(&self, raw: Option<&str>) -> Result<PathBuf, ToolError> {
+        let joined = match raw {
+            Some(raw) => self.roots[0].join(raw),
+            None => self.roots[0].clone(),
+        };
+        let path = joined.canonicalize().map_err(|source| ToolError::Io {
+            path: joined.clone(),
+            source,
+        })?;
+        let allowed = self
+            .roots
+            .iter()
+            .filter_map(|root| root.canonicalize().ok())
+            .any(|root| path.starts_with(root));
+        if !allowed {
+            return Err(ToolError::OutsideRoots(joined));
+        }
+        let registry = self.registry.read().unwrap_or_else(|e| e.into_inner());
+        registry.check_path(&joined)?;
+        registry.check_path(&path)?;
+        Ok(path)
+    }
+
+    /// `path` relative to the first allowed directory, for display.
+    fn display(&self, path: &Path) -> String {
+        let root = self.roots[0].canonicalize().unwrap_or_default();
+        path.strip_prefix(&root)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    }
+
+    fn read_file(&self, args: &Value) -> Result<String, ToolError> {
+        let path = self.resolve(Some(str_arg(args, "path")?))?;
+        let content = std::fs::read_to_string(&path).map_err(|source| ToolError::Io {
+            path: path.clone(),
+            source,
+        })?;
+        let start = optional_u64(args, "start_line")?.unwrap_or(1).max(1) as usize;
+        let end = optional_u64(args, "end_line")?.map(|n| n as usize);
+        if start == 1 && end.is_none() {
+            return Ok(content);
+        }
+        Ok(content
+            .lines()
+            .enumerate()
+            .skip(start - 1)
+            .take_while(|(i, _)| end.is_none_or(|end| *i < end))
+            .map(|(i, line)| format!("{:>6}  {line}", i + 1))
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
+    fn list_files(&self, args: &Value) -> Result<String, ToolError> {
+        let pattern = str_arg(args, "pattern")?;
+        let root = self.resolve(None)?;
+        let sensitive = self.sensitive();
+        let mut files = Vec::new();
+        walk(&root, &sensitive, &mut |path| {
+            let relative = self.display(path);
+            if sensitive::glob_match(pattern, &relative) {
+                files.push(relative);
+            }
+            files.len() < MAX_TOOL_MATCHES
+        });
+        files.sort();
+        Ok(files.join("\n"))
+    }
+
+    fn search_code(&self, args: &Value) -> Result<String, ToolError> {
+        let pattern = str_arg(args, "pattern")?;
+        let regex = regex::Regex::new(pattern)
+            .or_else(|_| regex::Regex::new(&regex::escape(pattern)))
+            .map_err(|e| ToolError::InvalidArguments(e.to_string()))?;
+        let dir = self.resolve(optional_str(args, "path")?)?;
+        let extension = optional_str(args, "file_type")?;
+        let sensitive = self.sensitive();
+        let mut matches = Vec::new();
+        walk(&dir, &sensitive, &mut |path| {
+            if extension.is_some_and(|ext| path.extension().and_then(|e| e.to_str()) != Some(ext)) {
+                return true;
+            }
+            let Ok(content) = std::fs::read_to_string(path) else {
+                return true;
+            };
+            for (i, line) in content.lines().enumerate() {
+                if regex.is_match(line) {
+                    matches.push(format!("{}:{}: {}", self.display(path), i + 1, line.trim()));
+                    if matches.len() == MAX_TOOL_MATCHES {
+                        return false;
+                    }
+                }
+            }
+            true
+        });
+        if matches.is_empty() {
+            return Ok("no matches".to_string());
+        }
+        Ok(matches.join("\n"))
+    }
+
+    fn list_symbols(&self, args: &Value) -> Result<String, ToolError> {
+        let path = self.resolve(Some(str_arg(args, "path")?))?;
+        let kind = match optional_str(args, "kind")? {
+            None | Some("all") => None,
+            Some("function") => Some(SymbolKind::Function),
+            Some("struct") => Some(SymbolKind::Struct),
+            Some("type") => Some(SymbolKind::Type),
+            Some("impl") => Some(SymbolKind::Impl),
+            Some(other) => {
+                return Err(ToolError::InvalidArguments(format!(
+                    "unknown symbol kind {other:?}"
+                )));
+            }
+        };
+        let indexed =
+            |index: &SymbolIndex| index.symbols().iter().any(|s| s.path.starts_with(&path));
+        if !indexed(&self.index.read().unwrap_or_else(|e| e.into_inner())) {
+            let mut index = self.index.write().unwrap_or_else(|e| e.into_inner());
+            if path.is_dir() {
+                index
+                    .index_directory(&path)
+                    .map_err(|source| ToolError::Io {
+                        path: path.clone(),
+                        source,
+                    })?;
+            } else if let Ok(content) = std::fs::read_to_string(&path) {
+                index.index_file(&path, &content);
+            }
+        }
+        let index = self.index.read().unwrap_or_else(|e| e.into_inner());
+        let symbols: Vec<String> = index
+            .symbols()
+            .iter()
+            .filter(|s| s.path.starts_with(&path))
+            .filter(|s| kind.is_none_or(|k| s.kind == k))
+            .take(MAX_TOOL_MATCHES)
+            .map(|s| {
+                format!(
+                    "{}:{} {} {}",
+                    self.display(&s.path),
+                    s.line,
+                    s.kind,
+                    s.signature
+                )
+            })
+            .collect();
+        if symbols.is_empty() {
+            return Ok("no symbols".to_string());
+        }
+        Ok(symbols.join("\n"))
+    }
+
+    async fn run_command(&self, args: &Value) -> Result<String, ToolError> {
+        let command = str_arg(args, "command")?;
+        let Some((backend, base)) = &self.sandbox else {
+            return Err(ToolError::Unsupported("run_command".to_string()));
+        };
+        let root = self.resolve(None)?;
+        let dir = self.resolve(optional_str(args, "working_dir")?)?;
+        if !dir.starts_with(&root) {
+            return Err(ToolError::OutsideRoots(dir));
+        }
+        let workdir = Path::new(COMMAND_WORKSPACE).join(dir.strip_prefix(&root).unwrap_or(&dir));
+        let timeout = optional_u64(args, "timeout_secs")?
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_COMMAND_TIMEOUT);
+        let config = base
+            .clone()
+            .with_mount(SharedMount::read_write(&root, COMMAND_WORKSPACE))
+            .with_attachments(&self.attachments)
+            .with_workdir(workdir)
+            .with_timeout(base.limits.timeout.map_or(timeout, |max| max.min(timeout)));
+        let argv = ["sh".to_string(), "-c".to_string(), command.to_string()];
+        let result = self
+            .execute_sandboxed(backend.as_ref(), &config, &argv)
+            .await?;
+        Ok(format!(
+            "exit code {}\n--- stdout ---\n{}\n--- stderr ---\n{}",
+            result.exit_code,
+            result.stdout.trim_end(),
+            result.stderr.trim_end()
+        ))
+    }
+
+    /// The `[[tools]]` entry for `name`, if it is a configured tool.
+    fn configured(&self, name: &str) -> Option<ToolConfig> {
+        let registry = self.registry.read().unwrap_or_else(|e| e.into_inner());
+        registry.configured(name).cloned()
+    }
+
+    /// Run a configured tool's command with the workspace at `/workspace`
+    /// and the arguments in `CRUSTYCLAW_ARGS` and `CRUSTYCLAW_ARG_<NAME>`.
+    /// Its stdout is the result; a failure reports its exit code and stderr.
+    async fn run_configured(&self, tool: &ToolConfig, args: &Value) -> Result<String, ToolError> {
+        let Some((backend, base)) = &self.sandbox else {
+            return Err(ToolError::Unsupported(tool.name.clone()));
+        };
+        let root = self.resolve(None)?;
+        let mut config = base
+            .clone()
+            .with_mount(SharedMount::read_write(&root, COMMAND_WORKSPACE))
+            .with_attachments(&self.attachments)
+            .with_workdir(COMMAND_WORKSPACE)
+            .with_env("CRUSTYCLAW_ARGS", args.to_string());
+        if let Some(timeout) = tool.timeout_secs.map(Duration::from_secs) {
+            config =
+                config.with_timeout(base.limits.timeout.map_or(timeout, |max| max.min(timeout)));
+        }
+        for (key, value) in args.as_object().into_iter().flatten() {
+            let value = match value {
+                Value::String(s) => s.clone(),
+                Value::Number(_) | Value::Bool(_) => value.to_string(),
+                _ => continue,
+            };
+            config = config.with_env(arg_env_name(key), value);
+        }
+        let result = self
+            .execute_sandboxed(backend.as_ref(), &config, &tool.command)
+            .await?;
+        if result.exit_code == 0 {
+            return Ok(result.stdout);
+        }
+        Ok(format!(
+            "error: exit code {}\n{}",
+            result.exit_code,
+            result.stderr.trim_end()
+        ))
+    }
+
+    /// Run `argv` in the sandbox, through the pool if there is one, and
+    /// redact leaked secrets from the result and the workspace.
+    async fn execute_sandboxed(
+        &self,
+        backend: &dyn SandboxBackend,
+        config: &SandboxConfig,
+        argv: &[String],
+    ) -> Result<SandboxResult, ToolError> {
+        let started = SystemTime::now();
+        let mut result = match &self.pool {
+            Some(pool) => pool.execute(backend, config, argv).await?,
+            None => backend.execute(config, argv).await?,
+        };
+        if let Some(store) = &self.secrets {
+            let scanner = {
+                let store = store.read().unwrap_or_else(|e| e.into_inner());
+                LeakScanner::from_store(&store)
+            };
+            scanner.scan_run(&config.label, &config.mounts, &mut result, started);
+        }
+        Ok(result)
+    }
+
+    fn sensitive(&self) -> SensitivePaths {
+        let registry = self.registry.read().unwrap_or_else(|e| e.into_inner());
+        registry.sensitive_paths().clone()
+    }
+}
+
+/// Visit the files under `dir`, skipping hidden and build directories and
+/// sensitive paths, until `visit` returns `false`.
+fn walk(dir: &Path, sensitive: &SensitivePaths, visit: &mut dyn FnMut(&Path) -> bool) -> bool {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return true;
+    };
+    let mut paths: Vec<PathBuf> = entries.filter_map(|e| e.ok().map(|e| e.path())).collect();
+    paths.sort();
+    for path in paths {
+        if let Some(name) = path.file_name().and_then(|n| n.to_str())
+            && (name.starts_with('.') || name == "target" || name == "node_modules")
+        {
+            continue;
+        }
+        if sensitive.is_sensitive(&path) {
+            continue;
+        }
+        let keep_going = if path.is_dir() {
+            walk(&path, sensitive, visit)
+        } else {
+            visit(&path)
+        };
+        if !keep_going {
+            return false;
+        }
+    }
+    true
+}
+
+/// The violations of a [`ToolError::SchemaViolation`], separated by `; `.
+fn join_violations(violations: &[SchemaViolation]) -> String {
+    let messages: Vec<String> = violations.iter().map(ToString::to_string).collect();
+    messages.join("; ")
+}
+
+fn str_arg<'a>(args: &'a Value, key: &str) -> Result<&'a str, ToolError> {
+    optional_str(args, key)?
+        .ok_or_else(|| ToolError::InvalidArguments(format!("missing string argument {key:?}")))
+}
+
+fn optional_str<'a>(args: &'a Value, key: &str) -> Result<Option<&'a str>, ToolError> {
+    match args.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(s)) => Ok(Some(s)),
+        Some(_) => Err(ToolError::InvalidArguments(format!(
+            "argument {key:?} must be a string"
+        ))),
+    }
+}
+
+fn optional_u64(args: &Value, key: &str) -> Result<Option<u64>, ToolError> {
+    match args.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => value.as_u64().map(Some).ok_or_else(|| {
+            ToolError::InvalidArguments(format!("argument {key:?} must be a non-negative integer"))
+        }),
+    }
+}
+
+/// Cut `output` to [`MAX_TOOL_OUTPUT`] bytes on a character boundary.
+fn truncate_output(mut output: String) -> String {
+    if output.len() <= MAX_TOOL_OUTPUT {
+        return output;
+    }
+    let mut cut = MAX_TOOL_OUTPUT;
+    while !output.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    output.truncate(cut);
+    output.push_str("\n[output truncated]");
+    output
+}
+
 #[cfg(test)]
 mod tests {
+    use serde_json::json;
+
     use super::*;
 
     #[test]
@@ -293,6 +1136,119 @@ mod tests {
         assert!(names.contains(&"list_symbols".to_string()));
         assert!(names.contains(&"run_command".to_string()));
         assert!(names.contains(&"daemon_status".to_string()));
+        assert!(names.contains(&"request_elevation".to_string()));
+        assert_eq!(names.len(), crustyclaw_config::BUILTIN_TOOLS.len());
+        for name in crustyclaw_config::BUILTIN_TOOLS {
+            assert!(names.contains(&name.to_string()), "{name}");
+        }
+    }
+
+    fn configured_tools() -> Vec<ToolConfig> {
+        let config = crustyclaw_config::AppConfig::parse(
+            r#"
+            [[tools]]
+            name = "greet"
+            description = "Greet someone."
+            command = ["sh", "-c", "echo \"hello $CRUSTYCLAW_ARG_WHO ($CRUSTYCLAW_ARG_TIMES) $CRUSTYCLAW_ARGS\"; ls"]
+            trust = "public"
+            tags = ["demo"]
+
+            [tools.parameters]
+            type = "object"
+            required = ["who"]
+            properties.who.type = "string"
+            properties.times.type = "integer"
+
+            [[tools]]
+            name = "fail"
+            description = "Always fails."
+            command = ["sh", "-c", "echo broken >&2; exit 3"]
+            trust = "trusted"
+            "#,
+        )
+        .unwrap();
+        config.tools
+    }
+
+    #[test]
+    fn test_register_configured_tools() {
+        let mut reg = ToolRegistry::with_defaults();
+        let mut tools = configured_tools();
+        tools.push(ToolConfig {
+            name: "read_file".to_string(),
+            ..tools[1].clone()
+        });
+        assert_eq!(reg.register_configured(&tools), ["greet", "fail"]);
+
+        let greet = reg.get("greet").unwrap();
+        assert_eq!(greet.trust, ToolTrust::Public);
+        assert_eq!(greet.tags, ["demo"]);
+        assert_eq!(greet.definition.parameters["required"][0], "who");
+        // The built-in is kept.
+        assert!(reg.configured("read_file").is_none());
+        assert_eq!(reg.get("read_file").unwrap().trust, ToolTrust::Public);
+        let public: Vec<String> = reg
+            .scoped_definitions(ToolTrust::Public, Some(&["demo"]))
+            .into_iter()
+            .map(|d| d.name)
+            .collect();
+        assert_eq!(public, ["greet"]);
+
+        // Re-registering replaces the previous set.
+        assert_eq!(reg.register_configured(&tools[..1]), ["greet"]);
+        assert!(reg.get("fail").is_none());
+        assert!(reg.register_configured(&[]).is_empty());
+        assert!(reg.get("greet").is_none());
+        assert!(reg.get("read_file").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_run_configured_tool() {
+        let (_dir, executor) = workspace();
+        executor
+            .registry
+            .write()
+            .unwrap()
+            .register_configured(&configured_tools());
+
+        let output = run(&executor, "greet", json!({"who": "ops", "times": 2})).await;
+        assert!(output.starts_with("hello ops (2) {"), "{output}");
+        assert!(output.contains("\"who\":\"ops\""), "{output}");
+        // Runs in the workspace.
+        assert!(output.contains("src"), "{output}");
+
+        let output = run(&executor, "fail", json!({})).await;
+        assert_eq!(output, "error: exit code 3\nbroken");
+
+        let output = run(&executor, "greet", json!({"times": 2})).await;
+        assert!(
+            output.contains("\"who\" is a required property"),
+            "{output}"
+        );
+        let output = run(&executor, "greet", json!({"who": "ops", "times": "two"})).await;
+        assert!(
+            output.contains("/times: \"two\" is not of type \"integer\""),
+            "{output}"
+        );
+
+        let forbidden = executor
+            .execute(&call("fail", json!({})), ToolTrust::Internal)
+            .await;
+        assert!(
+            forbidden
+                .content
+                .unwrap()
+                .contains("requires trusted trust")
+        );
+    }
+
+    #[test]
+    fn test_trust_names_round_trip() {
+        for trust in ToolTrust::ALL {
+            assert_eq!(ToolTrust::from_name(trust.name()), Some(trust));
+        }
+        assert_eq!(ToolTrust::from_name("Trusted"), Some(ToolTrust::Trusted));
+        assert_eq!(ToolTrust::from_name("root"), None);
     }
 
     #[test]
@@ -347,6 +1303,18 @@ mod tests {
         assert!(tool.tags.contains(&"code".to_string()));
     }
 
+    #[test]
+    fn test_check_path_rejects_sensitive() {
+        let reg = ToolRegistry::with_defaults()
+            .with_sensitive_paths(SensitivePaths::new().with_dir("/run/crustyclaw/secrets"));
+        assert!(reg.check_path(std::path::Path::new("src/main.rs")).is_ok());
+        assert!(reg.check_path(std::path::Path::new("deploy/.env")).is_err());
+        assert!(
+            reg.check_path(std::path::Path::new("/run/crustyclaw/secrets/llm_api_key"))
+                .is_err()
+        );
+    }
+
     #[test]
     fn test_disabled_tool_excluded() {
         let mut reg = ToolRegistry::with_defaults();
@@ -356,4 +1324,251 @@ mod tests {
         let defs = reg.definitions();
         assert!(!defs.iter().any(|d| d.name == "search_code"));
     }
+
+    fn workspace() -> (tempfile::TempDir, ToolExecutor) {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::write(
+            dir.path().join("src/lib.rs"),
+            "/// Adds.\npub fn add(a: i32, b: i32) -> i32 {\n    a + b\n}\n\npub struct Point;\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join(".env"), "API_KEY=secret\n").unwrap();
+        let registry = Arc::new(RwLock::new(ToolRegistry::with_defaults()));
+        let executor = ToolExecutor::new(registry, dir.path()).with_sandbox(
+            Arc::new(crate::isolation::NoopBackend),
+            SandboxConfig::new("tools-test"),
+        );
+        (dir, executor)
+    }
+
+    fn call(name: &str, arguments: Value) -> ToolCall {
+        ToolCall {
+            id: format!("call-{name}"),
+            name: name.to_string(),
+            arguments,
+        }
+    }
+
+    async fn run(executor: &ToolExecutor, name: &str, arguments: Value) -> String {
+        let message = executor
+            .execute(&call(name, arguments), ToolTrust::System)
+            .await;
+        assert_eq!(message.role, "tool");
+        assert_eq!(
+            message.tool_call_id.as_deref(),
+            Some(&*format!("call-{name}"))
+        );
+        message.content.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_executor_filesystem_tools() {
+        let (_dir, executor) = workspace();
+
+        assert!(
+            run(&executor, "read_file", json!({"path": "src/lib.rs"}))
+                .await
+                .starts_with("/// Adds.")
+        );
+        assert_eq!(
+            run(
+                &executor,
+                "read_file",
+                json!({"path": "src/lib.rs", "start_line": 2, "end_line": 2})
+            )
+            .await,
+            "     2  pub fn add(a: i32, b: i32) -> i32 {"
+        );
+        assert_eq!(
+            run(&executor, "list_files", json!({"pattern": "**/*.rs"})).await,
+            "src/lib.rs"
+        );
+        assert_eq!(
+            run(&executor, "search_code", json!({"pattern": "a \\+ b"})).await,
+            "src/lib.rs:3: a + b"
+        );
+        assert_eq!(
+            run(
+                &executor,
+                "search_code",
+                json!({"pattern": "secret", "file_type": "rs"})
+            )
+            .await,
+            "no matches"
+        );
+
+        let symbols = run(
+            &executor,
+            "list_symbols",
+            json!({"path": "src", "kind": "struct"}),
+        )
+        .await;
+        assert!(symbols.starts_with("src/lib.rs:6 struct"), "{symbols}");
+        assert!(!symbols.contains("add"), "{symbols}");
+    }
+
+    #[tokio::test]
+    async fn test_arguments_checked_against_schema() {
+        let (_dir, executor) = workspace();
+
+        let call = call("read_file", json!({"start_line": "2"}));
+        let err = executor.run(&call, ToolTrust::System).await.unwrap_err();
+        let ToolError::SchemaViolation { tool, violations } = &err else {
+            panic!("expected a schema violation: {err}");
+        };
+        assert_eq!(tool, "read_file");
+        assert_eq!(violations.len(), 2);
+        let message = executor.execute(&call, ToolTrust::System).await.content;
+        assert_eq!(
+            message.as_deref(),
+            Some(
+                "error: invalid arguments for read_file: \"path\" is a required property; \
+                 /start_line: \"2\" is not of type \"integer\"; correct them and call the tool again"
+            )
+        );
+
+        // Optional arguments may be null.
+        assert!(
+            run(
+                &executor,
+                "read_file",
+                json!({"path": "src/lib.rs", "end_line": null})
+            )
+            .await
+            .starts_with("/// Adds.")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_executor_refuses_paths() {
+        let (_dir, executor) = workspace();
+
+        let outside = run(&executor, "read_file", json!({"path": "../../etc/passwd"})).await;
+        assert!(outside.starts_with("error:"), "{outside}");
+        let secret = run(&executor, "read_file", json!({"path": ".env"})).await;
+        assert!(secret.starts_with("error:"), "{secret}");
+        assert!(!secret.contains("API_KEY"), "{secret}");
+        let missing = run(&executor, "read_file", json!({})).await;
+        assert!(missing.contains("invalid arguments"), "{missing}");
+    }
+
+    #[tokio::test]
+    async fn test_executor_checks_registry() {
+        let (_dir, executor) = workspace();
+        let unknown = executor
+            .run(&call("rm_rf", Value::Null), ToolTrust::System)
+            .await;
+        assert!(matches!(unknown, Err(ToolError::UnknownTool(_))));
+
+        let forbidden = executor
+            .run(
+                &call("run_command", json!({"command": "true"})),
+                ToolTrust::Public,
+            )
+            .await;
+        assert!(matches!(forbidden, Err(ToolError::Forbidden { .. })));
+
+        executor
+            .registry
+            .write()
+            .unwrap()
+            .tools
+            .get_mut("read_file")
+            .unwrap()
+            .enabled = false;
+        let disabled = executor
+            .run(
+                &call("read_file", json!({"path": "src/lib.rs"})),
+                ToolTrust::System,
+            )
+            .await;
+        assert!(matches!(disabled, Err(ToolError::Disabled(_))));
+    }
+
+    #[tokio::test]
+    async fn test_executor_runs_response() {
+        let (_dir, executor) = workspace();
+        let mut message = ChatMessage::assistant("");
+        message.tool_calls = Some(vec![
+            call(
+                "run_command",
+                json!({"command": "ls", "working_dir": "src"}),
+            ),
+            call("daemon_status", json!({})),
+        ]);
+        let response = ChatResponse {
+            message,
+            finish_reason: "tool_use".to_string(),
+            usage: Default::default(),
+            model: "test".to_string(),
+            attempts: 1,
+        };
+
+        let results = executor
+            .execute_response(&response, ToolTrust::System)
+            .await;
+        assert_eq!(results.len(), 2);
+        let output = results[0].content.as_deref().unwrap();
+        assert!(output.starts_with("exit code 0"), "{output}");
+        assert!(output.contains("lib.rs"), "{output}");
+        assert_eq!(
+            results[1].tool_call_id.as_deref(),
+            Some("call-daemon_status")
+        );
+        assert!(results[1].content.as_deref().unwrap().starts_with("error:"));
+    }
+
+    #[tokio::test]
+    async fn test_run_command_redacts_secrets() {
+        use crate::secrets::{InjectionMethod, SecretEntry, SecretSource, SecretValue};
+
+        let (dir, executor) = workspace();
+        let token = dir.path().join("token.txt");
+        std::fs::write(&token, "tok-0123456789").unwrap();
+        // Written well before the command runs, so it is not scanned.
+        std::fs::File::options()
+            .write(true)
+            .open(&token)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now() - Duration::from_secs(60))
+            .unwrap();
+        let mut store = SecretStore::new();
+        store
+            .insert(
+                SecretEntry {
+                    name: "token".to_string(),
+                    value: SecretValue::new("tok-0123456789"),
+                    injection: InjectionMethod::Env("TOKEN".to_string()),
+                    description: String::new(),
+                },
+                SecretSource::Config,
+            )
+            .unwrap();
+        let executor = executor.with_secrets(Arc::new(RwLock::new(store)));
+
+        let output = run(
+            &executor,
+            "run_command",
+            json!({"command": "cat token.txt; cp token.txt copy.txt"}),
+        )
+        .await;
+        assert!(output.contains("[REDACTED]"), "{output}");
+        assert!(!output.contains("tok-0123456789"), "{output}");
+        // The copy written by the command is redacted; the original is not.
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("copy.txt")).unwrap(),
+            "[REDACTED]"
+        );
+        assert_eq!(std::fs::read_to_string(&token).unwrap(), "tok-0123456789");
+    }
+
+    #[test]
+    fn test_truncate_output() {
+        let long = "é".repeat(MAX_TOOL_OUTPUT);
+        let truncated = truncate_output(long);
+        assert!(truncated.ends_with("[output truncated]"));
+        assert!(truncated.len() <= MAX_TOOL_OUTPUT + 20);
+        assert_eq!(truncate_output("short".to_string()), "short");
+    }
 }
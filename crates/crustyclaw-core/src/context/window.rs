@@ -8,11 +8,29 @@
 //! - **Conversation history** (dynamic, medium priority)
 //! - **Code context** (dynamic, from tree-sitter index, lower priority)
 //! - **RAG results** (dynamic, lowest priority)
+//! - **Conversation memory** (dynamic, see [`crate::memory`])
 //!
-//! Context items are packed greedily by priority until the budget is exhausted.
+//! [`ContextWindow::pack_ranked`] packs system prompts and tool definitions
+//! first, by priority, then the dynamic items in order of their
+//! [`RelevanceScore`] for the current query. Items are added greedily until
+//! the budget is exhausted; [`ContextWindow::explain`] shows what was packed
+//! and dropped, with each score's breakdown. Items are counted with the window's [`Tokenizer`], so a window built with
+//! [`ContextWindow::for_llm`] packs against the model's real limit.
+//! [`ContextWindow::pack_compacted`] summarizes history that would not fit
+//! with a [`Compactor`] instead of dropping it.
 
+use std::sync::Arc;
+
+use crustyclaw_config::LlmConfig;
 use serde::{Deserialize, Serialize};
 
+use super::compaction::{Compaction, Compactor};
+use super::relevance::{RelevanceScore, RelevanceScorer};
+use super::tokenizer::{self, HeuristicTokenizer, Tokenizer, TokenizerError};
+
+/// Budget used when the model's context limit is not known.
+pub const DEFAULT_CONTEXT_LIMIT: u32 = 8_192;
+
 /// A chunk of context with priority and estimated token count.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContextItem {
@@ -20,12 +38,21 @@ pub struct ContextItem {
     pub kind: ContextKind,
     /// The text content.
     pub content: String,
-    /// Estimated token count (rough: ~4 chars per token).
+    /// Estimated token count; recounted with the window's tokenizer when
+    /// the item is added.
     pub estimated_tokens: u32,
-    /// Priority (higher = packed first).
+    /// Priority (higher = packed first). Orders fixed items, and dynamic
+    /// items with equal scores.
     pub priority: u32,
     /// Source identifier (e.g. file path, "conversation", "system").
     pub source: String,
+    /// When the content last changed (unix seconds). For file sources this
+    /// is looked up when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified_secs: Option<u64>,
+    /// Relevance score assigned by [`ContextWindow::pack_ranked`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<RelevanceScore>,
 }
 
 /// The kind of context item.
@@ -41,6 +68,27 @@ pub enum ContextKind {
     Code,
     /// RAG retrieval result.
     Retrieval,
+    /// Long-term conversation memory (summary or fact).
+    Memory,
+}
+
+impl ContextKind {
+    /// Whether items of this kind are always packed ahead of scored ones.
+    pub fn is_fixed(&self) -> bool {
+        matches!(self, Self::System | Self::Tools)
+    }
+
+    /// Short label used in [`ContextWindow::explain`].
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::System => "system",
+            Self::Tools => "tools",
+            Self::Conversation => "conversation",
+            Self::Code => "code",
+            Self::Retrieval => "retrieval",
+            Self::Memory => "memory",
+        }
+    }
 }
 
 /// The context window manager.
@@ -54,8 +102,14 @@ pub struct ContextWindow {
     reserved_for_response: u32,
     /// Items packed into the window.
     items: Vec<ContextItem>,
+    /// Items that did not fit.
+    dropped: Vec<ContextItem>,
+    /// History summarized to make room.
+    compactions: Vec<Compaction>,
     /// Total tokens used.
     used_tokens: u32,
+    /// Counts tokens for packed items.
+    tokenizer: Arc<dyn Tokenizer>,
 }
 
 impl ContextWindow {
@@ -67,10 +121,38 @@ impl ContextWindow {
             budget,
             reserved_for_response,
             items: Vec::new(),
+            dropped: Vec::new(),
+            compactions: Vec::new(),
             used_tokens: 0,
+            tokenizer: Arc::new(HeuristicTokenizer),
         }
     }
 
+    /// Create a window sized for the configured model: the model's context
+    /// limit as budget, `max_tokens` reserved for the response, and the
+    /// model's tokenizer.
+    pub fn for_llm(config: &LlmConfig) -> Result<Self, TokenizerError> {
+        let budget = tokenizer::context_limit(&config.model).unwrap_or(DEFAULT_CONTEXT_LIMIT);
+        Ok(Self::new(budget, config.max_tokens)
+            .with_tokenizer(tokenizer::tokenizer_for_config(config)?))
+    }
+
+    /// Builder: count tokens with `tokenizer` instead of the byte heuristic.
+    pub fn with_tokenizer(mut self, tokenizer: Arc<dyn Tokenizer>) -> Self {
+        self.tokenizer = tokenizer;
+        self
+    }
+
+    /// The tokenizer used to count packed items.
+    pub fn tokenizer(&self) -> &Arc<dyn Tokenizer> {
+        &self.tokenizer
+    }
+
+    /// Count the tokens in `text` with this window's tokenizer.
+    pub fn count_tokens(&self, text: &str) -> u32 {
+        self.tokenizer.count(text)
+    }
+
     /// Available tokens for context (budget minus response reservation).
     pub fn available(&self) -> u32 {
         self.budget
@@ -95,13 +177,16 @@ impl ContextWindow {
 
     /// Add a context item if it fits within the budget.
     ///
+    /// The item's token count is recomputed with the window's tokenizer.
     /// Returns `true` if the item was added, `false` if it didn't fit.
-    pub fn add(&mut self, item: ContextItem) -> bool {
+    pub fn add(&mut self, mut item: ContextItem) -> bool {
+        item.estimated_tokens = self.tokenizer.count(&item.content);
         if item.estimated_tokens <= self.available() {
             self.used_tokens += item.estimated_tokens;
             self.items.push(item);
             true
         } else {
+            self.dropped.push(item);
             false
         }
     }
@@ -111,7 +196,7 @@ impl ContextWindow {
     /// Items are sorted by priority (descending) and added greedily.
     /// Returns the number of items that were packed.
     pub fn pack(&mut self, mut items: Vec<ContextItem>) -> usize {
-        items.sort_by(|a, b| b.priority.cmp(&a.priority));
+        items.sort_by_key(|i| std::cmp::Reverse(i.priority));
         let mut packed = 0;
         for item in items {
             if self.add(item) {
@@ -121,15 +206,113 @@ impl ContextWindow {
         packed
     }
 
+    /// Pack items for `query`: fixed items (system prompt, tools) first by
+    /// priority, then dynamic items by descending relevance score.
+    ///
+    /// Each dynamic item's score is stored on it for [`explain`](Self::explain).
+    /// Returns the number of items that were packed.
+    pub fn pack_ranked(
+        &mut self,
+        items: Vec<ContextItem>,
+        scorer: &RelevanceScorer,
+        query: &str,
+    ) -> usize {
+        let (mut fixed, mut dynamic): (Vec<_>, Vec<_>) =
+            items.into_iter().partition(|i| i.kind.is_fixed());
+        fixed.sort_by_key(|i| std::cmp::Reverse(i.priority));
+        for item in &mut dynamic {
+            item.score = Some(scorer.score(item, query));
+        }
+        let total = |i: &ContextItem| i.score.map(|s| s.total).unwrap_or_default();
+        dynamic.sort_by(|a, b| {
+            total(b)
+                .total_cmp(&total(a))
+                .then(b.priority.cmp(&a.priority))
+        });
+        let mut packed = 0;
+        for item in fixed.into_iter().chain(dynamic) {
+            if self.add(item) {
+                packed += 1;
+            }
+        }
+        packed
+    }
+
+    /// Pack items by priority like [`pack`](Self::pack), first summarizing
+    /// the lowest-priority conversation items with `compactor` if the items
+    /// would not all fit.
+    ///
+    /// Returns the number of items that were packed, counting the summary.
+    pub async fn pack_compacted(
+        &mut self,
+        items: Vec<ContextItem>,
+        compactor: &Compactor,
+    ) -> usize {
+        let (items, compaction) = compactor.compact(items, self).await;
+        self.compactions.extend(compaction);
+        self.pack(items)
+    }
+
+    /// History summarized by [`pack_compacted`](Self::pack_compacted).
+    pub fn compactions(&self) -> &[Compaction] {
+        &self.compactions
+    }
+
     /// Get the packed items, sorted by kind for consistent prompt assembly.
     pub fn items(&self) -> &[ContextItem] {
         &self.items
     }
 
+    /// Items that were offered but did not fit.
+    pub fn dropped(&self) -> &[ContextItem] {
+        &self.dropped
+    }
+
+    /// Describe the packing decisions: budget use, then each packed and
+    /// dropped item in the order it was considered, with its score breakdown
+    /// (or priority, for unscored items).
+    pub fn explain(&self) -> String {
+        let mut out = format!(
+            "context window: {} of {} tokens used ({} reserved for response)\n",
+            self.used_tokens, self.budget, self.reserved_for_response
+        );
+        for compaction in &self.compactions {
+            out.push_str(&format!(
+                "compacted: {} items ({} → {} tok) from {}\n",
+                compaction.sources.len(),
+                compaction.tokens_before,
+                compaction.tokens_after,
+                compaction.sources.join(", ")
+            ));
+        }
+        for (heading, items) in [("packed", &self.items), ("dropped", &self.dropped)] {
+            if items.is_empty() {
+                continue;
+            }
+            out.push_str(&format!("{heading}:\n"));
+            for item in items {
+                let rank = match &item.score {
+                    Some(s) => format!(
+                        "score {:.3} (query {:.2}, recency {:.2}, usage {:.2})",
+                        s.total, s.query, s.recency, s.usage
+                    ),
+                    None => format!("priority {}", item.priority),
+                };
+                out.push_str(&format!(
+                    "  {:<12} {:<32} {:>6} tok  {rank}\n",
+                    item.kind.label(),
+                    item.source,
+                    item.estimated_tokens
+                ));
+            }
+        }
+        out
+    }
+
     /// Assemble the packed context into ordered sections for the prompt.
     ///
     /// Returns items grouped by kind in the order:
-    /// System → Tools → Code → Retrieval → Conversation
+    /// System → Tools → Code → Retrieval → Memory → Conversation
     pub fn assemble(&self) -> Vec<&ContextItem> {
         let kind_order = |k: &ContextKind| -> u8 {
             match k {
@@ -137,7 +320,8 @@ impl ContextWindow {
                 ContextKind::Tools => 1,
                 ContextKind::Code => 2,
                 ContextKind::Retrieval => 3,
-                ContextKind::Conversation => 4,
+                ContextKind::Memory => 4,
+                ContextKind::Conversation => 5,
             }
         };
 
@@ -147,9 +331,11 @@ impl ContextWindow {
     }
 
     /// Estimate the token count for a string (~4 chars per token).
+    ///
+    /// Model-independent; use [`count_tokens`](Self::count_tokens) for the
+    /// window's tokenizer.
     pub fn estimate_tokens(text: &str) -> u32 {
-        // Rough approximation: 1 token ≈ 4 characters
-        (text.len() as u32).div_ceil(4)
+        HeuristicTokenizer.count(text)
     }
 
     /// Create a ContextItem from text with automatic token estimation.
@@ -161,6 +347,8 @@ impl ContextWindow {
             estimated_tokens,
             priority,
             source,
+            modified_secs: None,
+            score: None,
         }
     }
 }
@@ -263,6 +451,63 @@ mod tests {
         assert_eq!(assembled[2].kind, ContextKind::Conversation);
     }
 
+    #[test]
+    fn test_pack_ranked_orders_by_score() {
+        use crustyclaw_config::RelevanceConfig;
+
+        // Room for the system prompt and one 25-token code item.
+        let mut window = ContextWindow::new(40, 0);
+        let config = RelevanceConfig {
+            recency_weight: 0.0,
+            ..RelevanceConfig::default()
+        };
+        let mut scorer = RelevanceScorer::new(&config);
+        scorer.record_usage(&[ContextWindow::item(
+            ContextKind::Code,
+            String::new(),
+            0,
+            "config.rs".to_string(),
+        )]);
+
+        let items = vec![
+            // A high priority no longer wins on its own.
+            ContextWindow::item(
+                ContextKind::Code,
+                format!("fn unrelated() {{}}{}", " ".repeat(81)),
+                500,
+                "other.rs".to_string(),
+            ),
+            ContextWindow::item(
+                ContextKind::Code,
+                format!("fn reload_config() {{}}{}", " ".repeat(78)),
+                1,
+                "config.rs".to_string(),
+            ),
+            ContextWindow::item(
+                ContextKind::System,
+                "System prompt".to_string(),
+                100,
+                "system".to_string(),
+            ),
+        ];
+        assert_eq!(window.pack_ranked(items, &scorer, "reload_config"), 2);
+
+        assert_eq!(window.items()[0].kind, ContextKind::System);
+        assert!(window.items()[0].score.is_none());
+        assert_eq!(window.items()[1].source, "config.rs");
+        let score = window.items()[1].score.unwrap();
+        assert_eq!(score.query, 1.0);
+        assert_eq!(score.usage, 1.0);
+        assert_eq!(window.dropped()[0].source, "other.rs");
+
+        let explain = window.explain();
+        assert!(explain.starts_with("context window: 29 of 40 tokens used"));
+        assert!(explain.contains("priority 100"));
+        assert!(explain.contains("score 0.750 (query 1.00, recency 0.00, usage 1.00)"));
+        let dropped = explain.split("dropped:").nth(1).unwrap();
+        assert!(dropped.contains("other.rs"));
+    }
+
     #[test]
     fn test_token_estimation() {
         // "hello world" = 11 chars ≈ 3 tokens
@@ -272,4 +517,42 @@ mod tests {
         // 100 chars ≈ 25 tokens
         assert_eq!(ContextWindow::estimate_tokens(&"x".repeat(100)), 25);
     }
+
+    #[test]
+    fn test_for_llm_uses_model_limit_and_tokenizer() {
+        let config = LlmConfig {
+            model: "claude-sonnet-4-20250514".to_string(),
+            max_tokens: 4096,
+            ..LlmConfig::default()
+        };
+        let window = ContextWindow::for_llm(&config).unwrap();
+        assert_eq!(window.budget(), 200_000);
+        assert_eq!(window.available(), 200_000 - 4096);
+        assert_eq!(window.tokenizer().name(), "anthropic-approx");
+
+        let config = LlmConfig {
+            model: "local-model".to_string(),
+            ..LlmConfig::default()
+        };
+        let window = ContextWindow::for_llm(&config).unwrap();
+        assert_eq!(window.budget(), DEFAULT_CONTEXT_LIMIT);
+        assert_eq!(window.tokenizer().name(), "heuristic");
+    }
+
+    #[test]
+    fn test_add_recounts_with_tokenizer() {
+        let mut window = ContextWindow::new(1000, 0)
+            .with_tokenizer(Arc::new(tokenizer::ApproxTokenizer::openai()));
+        // 16 bytes ≈ 4 heuristic tokens, but 16 single-character pieces.
+        let item = ContextWindow::item(
+            ContextKind::Code,
+            "1,2,3,4,5,6,7,8,".to_string(),
+            10,
+            "code".to_string(),
+        );
+        assert_eq!(item.estimated_tokens, 4);
+        assert!(window.add(item));
+        assert_eq!(window.used(), 16);
+        assert_eq!(window.items()[0].estimated_tokens, 16);
+    }
 }
@@ -0,0 +1,474 @@
+//! Conversation history — a JSONL transcript per channel and peer.
+//!
+//! [`ConversationStore`] appends every message seen on the bus to the
+//! `<conversation>.jsonl` key of the daemon's [`Storage`], where the
+//! conversation ID is derived from the envelope's channel and peer by
+//! [`conversation_id`]. Stored
+//! conversations can be listed, loaded, resumed as LLM chat history, and
+//! pruned once idle for longer than the configured retention.
+//!
+//! Redactions (e.g. a Signal remote delete) remove the targeted message from
+//! the transcript rather than being recorded.
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::daemon::ShutdownSignal;
+use crate::llm::ChatMessage;
+use crate::message::{Direction, Envelope};
+use crate::storage::{self, FsStorage, Storage, StorageError};
+use crate::workspace::sanitize_name;
+
+/// Extension of transcript keys.
+const TRANSCRIPT_EXTENSION: &str = ".jsonl";
+
+/// How often the recorder prunes idle conversations.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Longest message preview in a [`ConversationSummary`], in characters.
+const PREVIEW_CHARS: usize = 80;
+
+/// Errors from conversation store operations.
+#[derive(Debug, thiserror::Error)]
+pub enum ConversationError {
+    #[error("invalid conversation ID {0:?}")]
+    InvalidId(String),
+
+    #[error("conversation not found: {0}")]
+    NotFound(String),
+
+    #[error("conversation I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl From<StorageError> for ConversationError {
+    fn from(e: StorageError) -> Self {
+        match e {
+            StorageError::InvalidKey(key) => Self::InvalidId(key),
+            StorageError::Io(e) => Self::Io(e),
+            StorageError::Sqlite(e) => Self::Io(std::io::Error::other(e)),
+        }
+    }
+}
+
+/// One recorded message.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConversationEntry {
+    /// The envelope's ID.
+    pub id: u64,
+    /// When the message was created, in unix seconds.
+    pub timestamp_secs: u64,
+    /// `true` for messages from the peer, `false` for replies to them.
+    pub inbound: bool,
+    /// Channel the message was sent on.
+    pub channel: String,
+    /// The remote party, if the channel has one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peer: Option<String>,
+    /// Message text.
+    pub body: String,
+}
+
+impl ConversationEntry {
+    fn from_envelope(envelope: &Envelope) -> Self {
+        Self {
+            id: envelope.id,
+            timestamp_secs: unix_secs(envelope.timestamp),
+            inbound: envelope.direction == Direction::Inbound,
+            channel: envelope.channel.clone(),
+            peer: envelope.peer.clone(),
+            body: envelope.body.clone(),
+        }
+    }
+}
+
+/// A stored conversation, as listed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConversationSummary {
+    /// Conversation ID.
+    pub id: String,
+    /// Channel of the conversation.
+    pub channel: String,
+    /// The remote party, if any.
+    pub peer: Option<String>,
+    /// Number of recorded messages.
+    pub messages: usize,
+    /// Time of the first message, in unix seconds.
+    pub started_secs: u64,
+    /// Time of the latest message, in unix seconds.
+    pub updated_secs: u64,
+    /// Start of the latest message.
+    pub preview: String,
+}
+
+/// The conversation an envelope belongs to: its channel, plus the peer when
+/// there is one (e.g. `signal-_15550001`).
+pub fn conversation_id(envelope: &Envelope) -> String {
+    match &envelope.peer {
+        Some(peer) => sanitize_name(&format!("{}-{peer}", envelope.channel)),
+        None => sanitize_name(&envelope.channel),
+    }
+}
+
+/// Append-only transcript storage, one blob per conversation.
+#[derive(Debug)]
+pub struct ConversationStore {
+    storage: Arc<dyn Storage>,
+    /// Serializes appends and rewrites within the daemon.
+    write_lock: Mutex<()>,
+}
+
+impl ConversationStore {
+    /// Create a store keeping transcripts as files in `dir`.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        let dir = dir.into();
+        Self::from_storage(Arc::new(
+            FsStorage::new(&dir).with_namespace(storage::CONVERSATIONS, dir),
+        ))
+    }
+
+    /// Create a store keeping transcripts in the conversations namespace of
+    /// `storage`.
+    pub fn from_storage(storage: Arc<dyn Storage>) -> Self {
+        Self {
+            storage,
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    /// Record `envelope` in its conversation's transcript, or apply it if it
+    /// is a redaction.
+    pub fn record(&self, envelope: &Envelope) -> Result<(), ConversationError> {
+        let id = conversation_id(envelope);
+        if let Some(target) = envelope.redacts {
+            return self.redact(&id, target);
+        }
+        let mut line = serde_json::to_vec(&ConversationEntry::from_envelope(envelope))
+            .map_err(std::io::Error::other)?;
+        line.push(b'\n');
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        self.storage
+            .append(storage::CONVERSATIONS, &Self::key(&id)?, &line)?;
+        Ok(())
+    }
+
+    /// Remove the inbound message `target` from conversation `id`.
+    ///
+    /// Returns `Ok` when the message is not present.
+    pub fn redact(&self, id: &str, target: u64) -> Result<(), ConversationError> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let entries = match self.messages(id) {
+            Ok(entries) => entries,
+            Err(ConversationError::NotFound(_)) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let kept: Vec<&ConversationEntry> = entries
+            .iter()
+            .filter(|e| !(e.inbound && e.id == target))
+            .collect();
+        if kept.len() == entries.len() {
+            return Ok(());
+        }
+        let mut data = String::new();
+        for entry in kept {
+            data.push_str(&serde_json::to_string(entry).map_err(std::io::Error::other)?);
+            data.push('\n');
+        }
+        self.storage
+            .put(storage::CONVERSATIONS, &Self::key(id)?, data.as_bytes())?;
+        info!(
+            conversation = id,
+            message = target,
+            "Message redacted from history"
+        );
+        Ok(())
+    }
+
+    /// All stored conversations, most recently active first.
+    pub fn list(&self) -> Result<Vec<ConversationSummary>, ConversationError> {
+        let mut conversations = Vec::new();
+        for key in self.storage.list(storage::CONVERSATIONS)? {
+            let Some(id) = key.strip_suffix(TRANSCRIPT_EXTENSION) else {
+                continue;
+            };
+            let messages = match self.messages(id) {
+                Ok(messages) => messages,
+                // Deleted or pruned since the keys were listed, or not a
+                // conversation ID.
+                Err(ConversationError::NotFound(_) | ConversationError::InvalidId(_)) => continue,
+                Err(e) => return Err(e),
+            };
+            let (Some(first), Some(last)) = (messages.first(), messages.last()) else {
+                continue;
+            };
+            conversations.push(ConversationSummary {
+                id: id.to_string(),
+                channel: first.channel.clone(),
+                peer: first.peer.clone(),
+                messages: messages.len(),
+                started_secs: first.timestamp_secs,
+                updated_secs: last.timestamp_secs,
+                preview: last.body.chars().take(PREVIEW_CHARS).collect(),
+            });
+        }
+        conversations.sort_by(|a, b| {
+            b.updated_secs
+                .cmp(&a.updated_secs)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        Ok(conversations)
+    }
+
+    /// The recorded messages of conversation `id`, oldest first.
+    ///
+    /// Lines that do not parse (e.g. one being appended) are skipped.
+    pub fn messages(&self, id: &str) -> Result<Vec<ConversationEntry>, ConversationError> {
+        let Some(content) = self.storage.get(storage::CONVERSATIONS, &Self::key(id)?)? else {
+            return Err(ConversationError::NotFound(id.to_string()));
+        };
+        Ok(String::from_utf8_lossy(&content)
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
+    /// Conversation `id` as chat history for the LLM: the peer's messages
+    /// as `user` turns and replies as `assistant` turns.
+    pub fn resume(&self, id: &str) -> Result<Vec<ChatMessage>, ConversationError> {
+        Ok(self
+            .messages(id)?
+            .into_iter()
+            .map(|entry| match entry.inbound {
+                true => ChatMessage::user(entry.body),
+                false => ChatMessage::assistant(entry.body),
+            })
+            .collect())
+    }
+
+    /// Delete conversation `id`.
+    pub fn delete(&self, id: &str) -> Result<(), ConversationError> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        match self
+            .storage
+            .delete(storage::CONVERSATIONS, &Self::key(id)?)?
+        {
+            true => Ok(()),
+            false => Err(ConversationError::NotFound(id.to_string())),
+        }
+    }
+
+    /// Delete conversations with no message newer than `max_age`, returning
+    /// the number deleted.
+    pub fn prune(&self, max_age: Duration) -> Result<usize, ConversationError> {
+        let cutoff = unix_secs(SystemTime::now()).saturating_sub(max_age.as_secs());
+        let mut pruned = 0;
+        for conversation in self.list()? {
+            if conversation.updated_secs < cutoff {
+                self.delete(&conversation.id)?;
+                pruned += 1;
+            }
+        }
+        Ok(pruned)
+    }
+
+    fn key(id: &str) -> Result<String, ConversationError> {
+        if id.is_empty() || sanitize_name(id) != id {
+            return Err(ConversationError::InvalidId(id.to_string()));
+        }
+        Ok(format!("{id}{TRANSCRIPT_EXTENSION}"))
+    }
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Spawn the recorder: every message on `bus` is appended to its
+/// conversation until shutdown.
+///
+/// With a non-zero `retention_days`, idle conversations are pruned on start
+/// and every day after that. Storage I/O runs on the blocking pool.
+pub fn spawn(
+    store: Arc<ConversationStore>,
+    bus: broadcast::Sender<Envelope>,
+    retention_days: u32,
+    mut shutdown_rx: broadcast::Receiver<ShutdownSignal>,
+) -> JoinHandle<()> {
+    let mut messages = bus.subscribe();
+    let retention = Duration::from_secs(u64::from(retention_days) * 24 * 60 * 60);
+    tokio::spawn(async move {
+        let mut prune = tokio::time::interval(PRUNE_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.recv() => break,
+                _ = prune.tick(), if retention_days > 0 => {
+                    let store = store.clone();
+                    match tokio::task::spawn_blocking(move || store.prune(retention)).await {
+                        Ok(Ok(0)) => {}
+                        Ok(Ok(n)) => info!(pruned = n, "Pruned idle conversations"),
+                        Ok(Err(e)) => warn!(error = %e, "Failed to prune conversations"),
+                        Err(e) => warn!(error = %e, "Conversation pruning panicked"),
+                    }
+                }
+                msg = messages.recv() => match msg {
+                    Ok(envelope) => {
+                        let store = store.clone();
+                        let result = tokio::task::spawn_blocking(move || store.record(&envelope)).await;
+                        if let Ok(Err(e)) = result {
+                            warn!(error = %e, "Failed to record message");
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!(skipped = n, "Conversation recorder fell behind; messages not recorded");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            }
+        }
+        debug!("Conversation recorder stopped");
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> (tempfile::TempDir, ConversationStore) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ConversationStore::new(dir.path().join("conversations"));
+        (dir, store)
+    }
+
+    #[test]
+    fn test_conversation_id() {
+        let message = Envelope::new("signal", "hi").with_peer("+15550001");
+        assert_eq!(conversation_id(&message), "signal-_15550001");
+        assert_eq!(conversation_id(&message.reply("hello")), "signal-_15550001");
+        assert_eq!(conversation_id(&Envelope::new("cli", "hi")), "cli");
+    }
+
+    #[test]
+    fn test_record_list_and_resume() {
+        let (_dir, store) = store();
+        assert!(store.list().unwrap().is_empty());
+
+        let question = Envelope::new("signal", "status?").with_peer("+15550001");
+        store.record(&question).unwrap();
+        store.record(&question.reply("all green")).unwrap();
+        store.record(&Envelope::new("cli", "ping")).unwrap();
+
+        let list = store.list().unwrap();
+        assert_eq!(list.len(), 2);
+        let signal = list.iter().find(|c| c.channel == "signal").unwrap();
+        assert_eq!(signal.id, "signal-_15550001");
+        assert_eq!(signal.peer.as_deref(), Some("+15550001"));
+        assert_eq!(signal.messages, 2);
+        assert_eq!(signal.preview, "all green");
+
+        let history = store.resume("signal-_15550001").unwrap();
+        let turns: Vec<(&str, &str)> = history
+            .iter()
+            .map(|m| (m.role.as_str(), m.content.as_deref().unwrap()))
+            .collect();
+        assert_eq!(turns, [("user", "status?"), ("assistant", "all green")]);
+
+        assert!(matches!(
+            store.messages("missing"),
+            Err(ConversationError::NotFound(_))
+        ));
+        assert!(matches!(
+            store.messages("../etc/passwd"),
+            Err(ConversationError::InvalidId(_))
+        ));
+    }
+
+    #[test]
+    fn test_redaction_removes_message() {
+        let (_dir, store) = store();
+        let oops = Envelope::new("signal", "my password is hunter2").with_peer("+15550001");
+        let fine = Envelope::new("signal", "never mind").with_peer("+15550001");
+        store.record(&oops).unwrap();
+        store.record(&fine).unwrap();
+
+        store
+            .record(&Envelope::redaction("signal", oops.id).with_peer("+15550001"))
+            .unwrap();
+        let messages = store.messages("signal-_15550001").unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].body, "never mind");
+
+        // Redacting a conversation with no history is a no-op.
+        store
+            .record(&Envelope::redaction("signal", 1).with_peer("+15559999"))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_prune_and_delete() {
+        let (_dir, store) = store();
+        let mut old = Envelope::new("signal", "ancient").with_peer("+15550001");
+        old.timestamp = SystemTime::now() - Duration::from_secs(40 * 24 * 60 * 60);
+        store.record(&old).unwrap();
+        store.record(&Envelope::new("cli", "recent")).unwrap();
+
+        assert_eq!(
+            store.prune(Duration::from_secs(30 * 24 * 60 * 60)).unwrap(),
+            1
+        );
+        let list = store.list().unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].id, "cli");
+
+        store.delete("cli").unwrap();
+        assert!(store.list().unwrap().is_empty());
+        assert!(matches!(
+            store.delete("cli"),
+            Err(ConversationError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_sqlite_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = storage::SqliteStorage::open(dir.path().join("state.db")).unwrap();
+        let store = ConversationStore::from_storage(Arc::new(storage));
+        let question = Envelope::new("signal", "status?").with_peer("+15550001");
+        store.record(&question).unwrap();
+        store.record(&question.reply("all green")).unwrap();
+
+        let list = store.list().unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].messages, 2);
+        store.delete("signal-_15550001").unwrap();
+        assert!(store.list().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_spawn_records_bus() {
+        let (_dir, store) = store();
+        let store = Arc::new(store);
+        let (bus, _) = broadcast::channel(16);
+        let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+        let handle = spawn(store.clone(), bus.clone(), 30, shutdown_rx);
+
+        bus.send(Envelope::new("tui", "hello")).unwrap();
+        for _ in 0..100 {
+            if store.messages("tui").is_ok() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(store.messages("tui").unwrap()[0].body, "hello");
+
+        shutdown_tx.send(ShutdownSignal).unwrap();
+        handle.await.unwrap();
+    }
+}
@@ -4,32 +4,114 @@
 //!
 //! | Signal | Behaviour |
 //! |--------|-----------|
-//! | **SIGHUP** | Async-reload config from disk. Published via a `watch` channel so running skills are **never** interrupted — consumers pick up the new config at their next pause / compaction point. |
+//! | **SIGHUP** | Async-reload config from disk. Published via a `watch` channel so running skills are **never** interrupted — consumers pick up the new config at their next pause / compaction point. Secrets are re-resolved and `daemon.skills_dir` is rescanned at the same time; see [`Daemon::secrets_watcher`]. |
 //! | **SIGTERM** | Initiate graceful shutdown — finish in-flight work, then exit. |
 //! | **SIGINT** (Ctrl-C) | Same as SIGTERM. |
+//!
+//! Independently of SIGHUP, env-, file-, and keychain-sourced secrets are
+//! re-read every `secrets.rotation_interval_secs` when that is non-zero.
+//!
+//! Under systemd the daemon reports readiness and shutdown via sd_notify,
+//! sends watchdog keepalives from the event loop, and serves IPC on a
+//! socket-activated listener when one is passed; see [`crate::systemd`].
 
-use std::path::PathBuf;
-use std::sync::Arc;
-use std::time::Instant;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::{Duration, Instant};
 
-use tokio::sync::{broadcast, watch};
+use tokio::sync::{broadcast, mpsc, watch};
 use tracing::{error, info, warn};
 
-use crustyclaw_config::AppConfig;
+use crustyclaw_config::layers::Override;
+use crustyclaw_config::lint::LintHost;
+use crustyclaw_config::{AppConfig, SecretsConfig};
 
+use crate::attachment::{self, AttachmentSpool};
+use crate::auth::token::{self, TokenKey};
+use crate::chat::{self, ChatService};
+use crate::commands::{self, CommandRouter};
+use crate::context::{
+    ElevationQueue, EnvironmentProvider, SensitivePaths, ToolRegistry, elevation,
+};
+use crate::conversation::{self, ConversationStore};
+use crate::daemonize::{self, PidFile, PidFileError};
+use crate::events::{self, DaemonEvent, EventBus};
+use crate::health::HealthRegistry;
+use crate::host::HostSampler;
 use crate::ipc;
-use crate::message::Envelope;
+use crate::isolation::{self as isolation, CredentialProxy, EgressProxy, SandboxPool};
+use crate::logging::{DEFAULT_LOG_CAPACITY, LogCollector, LogControl, LogReader};
+use crate::mcp::McpHub;
+use crate::memory::MemoryStore;
+use crate::message::{Direction, Envelope};
+use crate::metrics::{self, Metrics};
 use crate::plugin::PluginRegistry;
-use crate::skill::SkillRegistry;
+use crate::plugin::loader::PluginHost;
+#[cfg(feature = "wasm-plugins")]
+use crate::plugin::wasm::{self, WasmPluginHost};
+use crate::preflight::{self, PreflightReport};
+use crate::provenance;
+use crate::quota::QuotaManager;
+use crate::recovery::{self, FailedRun, RunJournal};
+use crate::response::ResponsePipeline;
+use crate::scheduler::{self, Scheduler};
+use crate::secrets::{SecretDiff, SecretStore};
+use crate::skill::manifest::SkillLoader;
+use crate::skill::{Skill, SkillRegistry};
+use crate::storage::{self, AuditLog, Storage};
+use crate::supervisor::Supervisor;
+use crate::systemd;
+use crate::telemetry;
+use crate::usage::UsageLedger;
+use crate::warnings::{self, WarningCollector, WarningKind};
+use crate::webhook::{self, WebhookChannel};
+use crate::workspace::WorkspaceStore;
 
 /// Shutdown signal sent via broadcast channel.
 #[derive(Debug, Clone)]
 pub struct ShutdownSignal;
 
+/// Published on the secrets watch channel after a reload changes secrets.
+///
+/// Components holding credentials (LLM providers, channel adapters) should
+/// rebuild their clients when `changed` names a secret they use.
+#[derive(Debug, Clone, Default)]
+pub struct SecretsRevision {
+    /// Incremented on every reload that changed at least one secret.
+    pub generation: u64,
+    /// Names of secrets added, changed, or removed by the latest reload.
+    pub changed: Vec<String>,
+}
+
+impl SecretsRevision {
+    /// Whether the latest reload touched any of `names`.
+    pub fn affects<'a>(&self, names: impl IntoIterator<Item = &'a str>) -> bool {
+        names
+            .into_iter()
+            .any(|name| self.changed.iter().any(|changed| changed == name))
+    }
+}
+
+/// Wait until a reload published on `rx` touches one of `names`.
+///
+/// Never returns if the daemon is dropped first.
+pub async fn secrets_changed(rx: &mut watch::Receiver<SecretsRevision>, names: &[&str]) {
+    loop {
+        if rx.changed().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+        if rx.borrow_and_update().affects(names.iter().copied()) {
+            return;
+        }
+    }
+}
+
 /// The main CrustyClaw daemon.
 pub struct Daemon {
     config: AppConfig,
     config_path: PathBuf,
+    /// `--set` flags, then `PUT /config` changes, re-applied on every reload.
+    config_overrides: RwLock<Vec<Override>>,
     config_tx: watch::Sender<AppConfig>,
     config_rx: watch::Receiver<AppConfig>,
     shutdown_tx: broadcast::Sender<ShutdownSignal>,
@@ -38,6 +120,37 @@ pub struct Daemon {
     _message_rx: broadcast::Receiver<Envelope>,
     skills: Arc<SkillRegistry>,
     plugins: Arc<PluginRegistry>,
+    sandbox_pool: Arc<SandboxPool>,
+    warnings: Arc<WarningCollector>,
+    secrets: Arc<RwLock<SecretStore>>,
+    credential_proxy: Arc<RwLock<CredentialProxy>>,
+    /// Egress proxy for sandboxes, once started (`isolation.credential_proxy`).
+    egress_proxy: OnceLock<Arc<EgressProxy>>,
+    secrets_tx: watch::Sender<SecretsRevision>,
+    secrets_rx: watch::Receiver<SecretsRevision>,
+    logs: LogReader,
+    log_control: Option<LogControl>,
+    workspaces: Arc<WorkspaceStore>,
+    attachments: Arc<AttachmentSpool>,
+    storage: Arc<dyn Storage>,
+    conversations: Arc<ConversationStore>,
+    memory: Arc<MemoryStore>,
+    journal: Arc<RunJournal>,
+    responses: Arc<ResponsePipeline>,
+    elevations: Arc<ElevationQueue>,
+    tools: Arc<RwLock<ToolRegistry>>,
+    mcp: Arc<McpHub>,
+    plugin_host: Arc<PluginHost>,
+    #[cfg(feature = "wasm-plugins")]
+    wasm_plugins: Arc<WasmPluginHost>,
+    commands: Arc<CommandRouter>,
+    quotas: Arc<QuotaManager>,
+    metrics: Arc<Metrics>,
+    usage: Arc<UsageLedger>,
+    health: Arc<HealthRegistry>,
+    supervisor: Arc<Supervisor>,
+    events: EventBus,
+    skip_preflight: bool,
     started_at: Instant,
 }
 
@@ -50,24 +163,168 @@ impl Daemon {
     /// Create a new daemon with an explicit config file path for SIGHUP reloads.
     pub fn with_config_path(config: AppConfig, config_path: PathBuf) -> Self {
         let (shutdown_tx, _shutdown_rx) = broadcast::channel(1);
+        let supervisor = Arc::new(Supervisor::new(shutdown_tx.clone()));
         let (message_tx, _message_rx) = broadcast::channel(256);
         let (config_tx, config_rx) = watch::channel(config.clone());
+        let sandbox_pool = Arc::new(SandboxPool::new(config.isolation.max_concurrent));
+        let warnings = Arc::new(WarningCollector::new());
+        warnings::check_lint(&config, &warnings);
+        let secrets = SecretStore::from_config(&config.secrets).unwrap_or_else(|e| {
+            warnings.push(
+                WarningKind::Unavailable,
+                "secrets",
+                format!("failed to load secrets, starting with none: {e}"),
+            );
+            SecretStore::new()
+        });
+        let credential_proxy = CredentialProxy::from_store(&secrets);
+        let workspaces = Arc::new(WorkspaceStore::from_config(&config.files));
+        let attachments = Arc::new(AttachmentSpool::from_config(&config.attachments));
+        let storage = storage::open(&config).unwrap_or_else(|e| {
+            warnings.push(
+                WarningKind::Unavailable,
+                "storage",
+                format!("cannot open the state database, keeping state in files: {e}"),
+            );
+            Arc::new(storage::filesystem(&config))
+        });
+        let conversations = Arc::new(ConversationStore::from_storage(storage.clone()));
+        let memory = Arc::new(
+            MemoryStore::from_config(&config.memory).with_relevance(&config.context.relevance),
+        );
+        let journal_path = Path::new(&config.daemon.state_dir).join(recovery::JOURNAL_FILE);
+        let journal = Arc::new(RunJournal::open(journal_path).unwrap_or_else(|e| {
+            warnings.push(
+                WarningKind::Unavailable,
+                "recovery",
+                format!(
+                    "cannot open the run journal, runs will not be recovered after a crash: {e}"
+                ),
+            );
+            RunJournal::in_memory()
+        }));
+        let elevations = Arc::new(
+            ElevationQueue::from_config(&config.context.elevation)
+                .with_audit_log(AuditLog::new(storage.clone(), elevation::AUDIT_FILE)),
+        );
+        if let Some(bundle) = &config.policy_bundle {
+            let log = AuditLog::new(storage.clone(), provenance::AUDIT_FILE);
+            provenance::record(&log, "loaded", bundle);
+        }
+        let mut tools = ToolRegistry::with_defaults()
+            .with_sensitive_paths(SensitivePaths::from_config(&config));
+        tools.register_configured(&config.tools);
+        let (secrets_tx, secrets_rx) = watch::channel(SecretsRevision::default());
+        let secrets = Arc::new(RwLock::new(secrets));
+        let responses = Arc::new(ResponsePipeline::from_config(
+            &config.response,
+            secrets.clone(),
+        ));
+        let quotas = Arc::new(QuotaManager::from_config(&config.quotas));
+        let metrics = Arc::new(Metrics::new());
+        let events = EventBus::new();
+        let usage = Arc::new(
+            UsageLedger::from_storage(storage.clone()).unwrap_or_else(|e| {
+                warnings.push(
+                    WarningKind::Unavailable,
+                    "usage",
+                    format!("cannot load recorded token usage, starting from none: {e}"),
+                );
+                UsageLedger::in_memory()
+            }),
+        );
+        let commands = Arc::new(
+            CommandRouter::from_config(&config.effective_commands())
+                .with_policy(config.build_policy_engine())
+                .with_quotas(quotas.clone())
+                .with_metrics(metrics.clone()),
+        );
 
         Self {
             config,
             config_path,
+            config_overrides: RwLock::new(Vec::new()),
             config_tx,
             config_rx,
             shutdown_tx,
             _shutdown_rx,
             message_tx,
             _message_rx,
-            skills: Arc::new(SkillRegistry::new()),
+            skills: Arc::new(
+                SkillRegistry::new()
+                    .with_journal(journal.clone())
+                    .with_metrics(metrics.clone())
+                    .with_events(events.clone()),
+            ),
             plugins: Arc::new(PluginRegistry::new()),
+            sandbox_pool,
+            warnings,
+            secrets,
+            credential_proxy: Arc::new(RwLock::new(credential_proxy)),
+            egress_proxy: OnceLock::new(),
+            secrets_tx,
+            secrets_rx,
+            logs: LogCollector::new(DEFAULT_LOG_CAPACITY).reader(),
+            log_control: None,
+            workspaces,
+            attachments,
+            storage,
+            conversations,
+            memory,
+            journal,
+            responses,
+            elevations,
+            tools: Arc::new(RwLock::new(tools)),
+            mcp: Arc::new(McpHub::new()),
+            plugin_host: Arc::new(PluginHost::new()),
+            #[cfg(feature = "wasm-plugins")]
+            wasm_plugins: Arc::new(WasmPluginHost::new()),
+            commands,
+            quotas,
+            metrics,
+            usage,
+            health: Arc::new(HealthRegistry::new()),
+            supervisor,
+            events,
+            skip_preflight: false,
             started_at: Instant::now(),
         }
     }
 
+    /// Serve logs from `logs` on `GET /logs/stream`.
+    ///
+    /// The reader should belong to a [`LogCollector`] registered with the
+    /// process's tracing subscriber; by default the daemon uses a detached
+    /// collector and the stream stays empty.
+    pub fn with_log_reader(mut self, logs: LogReader) -> Self {
+        self.logs = logs;
+        self
+    }
+
+    /// Serve `PUT /logging/level` and write `[logging] file` through
+    /// `control`, whose layers should be in the process's tracing
+    /// subscriber.
+    pub fn with_log_control(mut self, control: LogControl) -> Self {
+        self.log_control = Some(control);
+        self
+    }
+
+    /// Re-apply `overrides` (from `--set` flags) on every config reload, on
+    /// top of the file and `CRUSTYCLAW__*` environment variables.
+    pub fn with_config_overrides(mut self, overrides: Vec<Override>) -> Self {
+        self.config_overrides = RwLock::new(overrides);
+        self
+    }
+
+    /// Skip the startup [preflight](crate::preflight) checks.
+    ///
+    /// The skip is recorded as an insecure-settings warning so it shows up
+    /// in `crustyclaw status`.
+    pub fn with_skip_preflight(mut self, skip: bool) -> Self {
+        self.skip_preflight = skip;
+        self
+    }
+
     /// Run the daemon until a shutdown signal is received.
     ///
     /// Listens for OS signals:
@@ -80,26 +337,216 @@ impl Daemon {
             "CrustyClaw daemon starting"
         );
 
-        // Start the IPC server on a Unix domain socket
+        // Fail fast if the policy does not satisfy its own test table
+        let failures = self.config.run_policy_tests();
+        if !failures.is_empty() {
+            for failure in &failures {
+                error!("{failure}");
+            }
+            return Err(DaemonError::Startup(format!(
+                "{} of {} policy tests failed",
+                failures.len(),
+                self.config.policy.tests.len()
+            )));
+        }
+
+        self.run_preflight().await?;
+        // Held until `run` returns, so a second daemon cannot take over.
+        let pid_file = PidFile::acquire(&daemonize::pid_file_path(&self.config))?;
+        info!(path = %pid_file.path().display(), "Wrote PID file");
+        if let Some(control) = &self.log_control
+            && let Some(path) = control.open_file(&self.config.logging).map_err(|e| {
+                DaemonError::Startup(format!(
+                    "cannot open log file {}: {e}",
+                    self.config.logging.file.as_deref().unwrap_or_default()
+                ))
+            })?
+        {
+            info!(path = %path.display(), "Writing log file");
+        }
+        self.recover_interrupted_runs().await;
+        self.collect_startup_warnings().await;
+        let egress_handle = self.spawn_egress_proxy().await?;
+        self.discover_skills(&self.config);
+        self.load_plugins(&self.config);
+        self.load_wasm_plugins(&self.config);
+        self.import_mcp_tools(&self.config).await;
+
+        // Start the IPC server on the socket passed by systemd socket
+        // activation, or bind our own Unix domain socket
         let socket_path = ipc::server::socket_path_from_config(&self.config);
+        let activated = systemd::activated_listener().map_err(DaemonError::Io)?;
+        let token_key = self.load_token_key()?;
+        let webhook = self.config.webhook.enabled.then(|| {
+            Arc::new(WebhookChannel::new(
+                self.config_rx.clone(),
+                self.secrets.clone(),
+                self.message_tx.clone(),
+                self.responses.clone(),
+            ))
+        });
+        let chat = Arc::new(self.chat_service());
+        let scheduler = Arc::new(
+            Scheduler::new(self.config_rx.clone(), self.skills.clone())
+                .with_chat(chat.clone())
+                .with_storage(self.storage.clone()),
+        );
+        let (config_change_tx, mut config_changes) = mpsc::channel(8);
         let ipc_state = Arc::new(ipc::IpcState {
             config: self.config_rx.clone(),
+            config_changes: Some(config_change_tx),
             shutdown_tx: self.shutdown_tx.clone(),
             skills: self.skills.clone(),
             plugins: self.plugins.clone(),
+            sandboxes: self.sandbox_pool.clone(),
+            warnings: self.warnings.clone(),
+            host: Arc::new(HostSampler::new()),
+            logs: self.logs.clone(),
+            log_control: self.log_control.clone(),
+            workspaces: self.workspaces.clone(),
+            conversations: self.conversations.clone(),
+            memory: self.memory.clone(),
+            elevations: self.elevations.clone(),
+            quotas: self.quotas.clone(),
+            metrics: self.metrics.clone(),
+            usage: self.usage.clone(),
+            health: self.health.clone(),
+            supervisor: self.supervisor.clone(),
+            token_key,
+            webhook: webhook.clone(),
+            chat,
+            scheduler: scheduler.clone(),
+            events: self.events.clone(),
+            messages: self.message_tx.clone(),
+            commands: self.commands.clone(),
             started_at: self.started_at,
         });
-        let ipc_shutdown_rx = self.shutdown_tx.subscribe();
-        let ipc_handle = tokio::spawn({
-            let socket_path = socket_path.clone();
-            async move {
-                if let Err(e) = ipc::server::serve(&socket_path, ipc_state, ipc_shutdown_rx).await {
-                    error!(error = %e, "IPC server error");
+        let tls_handle = self.spawn_tls_server(&ipc_state).await?;
+        let ipc_handle = self.supervisor.spawn("ipc", {
+            let shutdown_tx = self.shutdown_tx.clone();
+            move || {
+                let state = ipc_state.clone();
+                let shutdown_rx = shutdown_tx.subscribe();
+                let socket_path = socket_path.clone();
+                // A restart serves the same activated socket again.
+                let activated = activated.as_ref().map(|listener| listener.try_clone());
+                async move {
+                    match activated {
+                        Some(listener) => {
+                            match listener.and_then(tokio::net::UnixListener::from_std) {
+                                Ok(listener) => {
+                                    info!("IPC server listening on socket-activated listener");
+                                    ipc::server::serve_on(listener, state, shutdown_rx).await
+                                }
+                                Err(e) => Err(e),
+                            }
+                        }
+                        None => ipc::server::serve(&socket_path, state, shutdown_rx).await,
+                    }
+                    .map_err(|e| format!("IPC server error: {e}"))
                 }
             }
         });
 
+        let metrics_handle = metrics::spawn(
+            self.metrics.clone(),
+            self.message_tx.clone(),
+            self.shutdown_tx.subscribe(),
+        );
+        let metrics_listener = self
+            .config
+            .daemon
+            .metrics_addr
+            .as_deref()
+            .and_then(|addr| addr.parse().ok())
+            .map(|addr| {
+                let metrics = self.metrics.clone();
+                let shutdown_rx = self.shutdown_tx.subscribe();
+                tokio::spawn(async move {
+                    if let Err(e) = metrics::serve(addr, metrics, shutdown_rx).await {
+                        error!(error = %e, %addr, "Metrics listener error");
+                    }
+                })
+            });
+
+        let events_handle = events::spawn(
+            self.events.clone(),
+            self.message_tx.clone(),
+            self.shutdown_tx.subscribe(),
+        );
+
+        let telemetry_handle = telemetry::spawn(
+            self.config_rx.clone(),
+            self.skills.clone(),
+            self.plugins.clone(),
+            self.shutdown_tx.subscribe(),
+        );
+
+        let commands_handle = self.supervisor.spawn("commands", {
+            let (router, bus, skills) = (
+                self.commands.clone(),
+                self.message_tx.clone(),
+                self.skills.clone(),
+            );
+            let shutdown_tx = self.shutdown_tx.clone();
+            move || {
+                let worker = commands::spawn(
+                    router.clone(),
+                    bus.clone(),
+                    skills.clone(),
+                    shutdown_tx.subscribe(),
+                );
+                async move { worker.await.map_err(|e| format!("command router: {e}")) }
+            }
+        });
+
+        let scheduler_handle = self.supervisor.spawn("scheduler", {
+            let shutdown_tx = self.shutdown_tx.clone();
+            move || {
+                let worker = scheduler::spawn(scheduler.clone(), shutdown_tx.subscribe());
+                async move { worker.await.map_err(|e| format!("scheduler: {e}")) }
+            }
+        });
+
+        let webhook_handle = webhook.map(|channel| {
+            info!("Webhook channel enabled");
+            let shutdown_tx = self.shutdown_tx.clone();
+            self.supervisor.spawn("webhook", move || {
+                let worker = webhook::spawn(channel.clone(), shutdown_tx.subscribe());
+                async move { worker.await.map_err(|e| format!("webhook channel: {e}")) }
+            })
+        });
+
+        #[cfg(feature = "wasm-plugins")]
+        let wasm_reloader = {
+            let wasm = &self.config.plugins.wasm;
+            (wasm.enabled && wasm.hot_reload).then(|| {
+                wasm::spawn_reloader(
+                    self.wasm_plugins.clone(),
+                    self.tools.clone(),
+                    Duration::from_secs(wasm.reload_interval_secs),
+                    self.shutdown_tx.subscribe(),
+                )
+            })
+        };
+
+        let conversations_handle = self.config.conversations.enabled.then(|| {
+            conversation::spawn(
+                self.conversations.clone(),
+                self.message_tx.clone(),
+                self.config.conversations.retention_days,
+                self.shutdown_tx.subscribe(),
+            )
+        });
+
+        let attachments_handle =
+            attachment::spawn(self.attachments.clone(), self.shutdown_tx.subscribe());
+
         let mut shutdown_rx = self.shutdown_tx.subscribe();
+        let mut rotation = rotation_ticker(self.config.secrets.rotation_interval_secs);
+        let mut watchdog = systemd::watchdog_interval().map(tokio::time::interval);
+
+        notify_systemd("READY=1\nSTATUS=Serving");
 
         #[cfg(unix)]
         {
@@ -127,6 +574,19 @@ impl Daemon {
                     _ = sighup.recv() => {
                         info!(path = %self.config_path.display(), "SIGHUP received, reloading config");
                         self.reload_config().await;
+                        let secs = self.config_rx.borrow().secrets.rotation_interval_secs;
+                        rotation = rotation_ticker(secs);
+                    }
+                    Some(change) = config_changes.recv() => {
+                        let _ = change.reply.send(self.apply_config_change(change.overrides).await);
+                        let secs = self.config_rx.borrow().secrets.rotation_interval_secs;
+                        rotation = rotation_ticker(secs);
+                    }
+                    _ = next_tick(&mut rotation) => {
+                        self.rotate_secrets();
+                    }
+                    _ = next_tick(&mut watchdog) => {
+                        notify_systemd("WATCHDOG=1");
                     }
                 }
             }
@@ -135,44 +595,606 @@ impl Daemon {
         #[cfg(not(unix))]
         {
             // Non-Unix: only ctrl-c + internal shutdown
-            tokio::select! {
-                _ = shutdown_rx.recv() => {
-                    info!("Shutdown signal received, stopping daemon");
-                }
-                _ = tokio::signal::ctrl_c() => {
-                    warn!("Ctrl-C received, initiating graceful shutdown");
-                    let _ = self.shutdown_tx.send(ShutdownSignal);
+            loop {
+                tokio::select! {
+                    _ = shutdown_rx.recv() => {
+                        info!("Shutdown signal received, stopping daemon");
+                        break;
+                    }
+                    _ = tokio::signal::ctrl_c() => {
+                        warn!("Ctrl-C received, initiating graceful shutdown");
+                        let _ = self.shutdown_tx.send(ShutdownSignal);
+                        break;
+                    }
+                    Some(change) = config_changes.recv() => {
+                        let _ = change.reply.send(self.apply_config_change(change.overrides).await);
+                    }
+                    _ = next_tick(&mut rotation) => {
+                        self.rotate_secrets();
+                    }
                 }
             }
         }
 
+        notify_systemd("STOPPING=1\nSTATUS=Shutting down");
+        self.drain_sandboxes().await;
+
         // Wait for IPC server to finish
         let _ = ipc_handle.await;
+        if let Some(handle) = tls_handle {
+            let _ = handle.await;
+        }
+        let _ = commands_handle.await;
+        let _ = scheduler_handle.await;
+        if let Some(handle) = webhook_handle {
+            let _ = handle.await;
+        }
+        let _ = metrics_handle.await;
+        let _ = events_handle.await;
+        if let Some(handle) = metrics_listener {
+            let _ = handle.await;
+        }
+        if let Some(handle) = egress_handle {
+            let _ = handle.await;
+        }
+        if let Some(handle) = conversations_handle {
+            let _ = handle.await;
+        }
+        let _ = attachments_handle.await;
+        #[cfg(feature = "wasm-plugins")]
+        if let Some(handle) = wasm_reloader {
+            let _ = handle.await;
+        }
+        if let Some(handle) = telemetry_handle {
+            let _ = handle.await;
+        }
 
         info!("Daemon stopped");
         Ok(())
     }
 
+    /// Refuse new sandbox executions, give running ones
+    /// `isolation.shutdown_grace_secs` to exit, then kill the rest and
+    /// report what was terminated.
+    async fn drain_sandboxes(&self) {
+        let grace = self.config_rx.borrow().isolation.shutdown_grace_secs;
+        let running = self.sandbox_pool.running();
+        if running > 0 {
+            info!(
+                running,
+                grace_secs = grace,
+                "Waiting for running sandboxes to exit"
+            );
+            notify_systemd(&format!("STATUS=Waiting for {running} sandbox(es)"));
+        }
+        let report = self.sandbox_pool.drain(Duration::from_secs(grace)).await;
+        for run in &report.killed {
+            warn!(
+                id = run.id,
+                label = %run.label,
+                backend = %run.backend,
+                age_secs = run.age.as_secs(),
+                "Killed sandbox still running after the shutdown grace period"
+            );
+        }
+        if running > 0 || report.refused > 0 {
+            info!(
+                completed = report.completed,
+                killed = report.killed.len(),
+                refused = report.refused,
+                "Sandboxes drained"
+            );
+        }
+    }
+
+    /// Start the egress proxy sandboxes are forced through when
+    /// `isolation.credential_proxy` is enabled. A busy address fails
+    /// startup: skills must not run with real secrets instead.
+    async fn spawn_egress_proxy(&self) -> Result<Option<tokio::task::JoinHandle<()>>, DaemonError> {
+        let iso = &self.config.isolation;
+        if !iso.credential_proxy {
+            return Ok(None);
+        }
+        let listener = tokio::net::TcpListener::bind(&iso.egress_proxy_addr)
+            .await
+            .and_then(|listener| Ok((listener.local_addr()?, listener)));
+        let (addr, listener) = listener.map_err(|e| {
+            DaemonError::Startup(format!(
+                "failed to bind the egress proxy on {}: {e}",
+                iso.egress_proxy_addr
+            ))
+        })?;
+        let proxy = Arc::new(
+            EgressProxy::new(addr, self.secrets.clone())
+                .with_allowed_hosts(iso.egress_allowed_hosts.clone())
+                .with_secret_hosts(egress_secret_hosts(&self.config.secrets)),
+        );
+        let _ = self.egress_proxy.set(proxy.clone());
+        let shutdown_rx = self.shutdown_tx.subscribe();
+        Ok(Some(tokio::spawn(async move {
+            if let Err(e) = isolation::serve_egress_proxy(proxy, listener, shutdown_rx).await {
+                error!(error = %e, %addr, "Egress proxy error");
+            }
+        })))
+    }
+
+    /// Load (or create) the session token key when `auth.mode = "token"`.
+    ///
+    /// The mode is read once at startup; changing it needs a restart.
+    fn load_token_key(&self) -> Result<Option<Arc<TokenKey>>, DaemonError> {
+        if self.config.auth.mode != "token" {
+            return Ok(None);
+        }
+        let path = token::key_path(&self.config);
+        let key =
+            TokenKey::load_or_create(&path).map_err(|e| DaemonError::Startup(e.to_string()))?;
+        info!(path = %path.display(), "Session token authentication enabled");
+        Ok(Some(Arc::new(key)))
+    }
+
+    /// Start the remote control plane on `listen_addr:listen_port` when
+    /// `[daemon.tls]` is enabled. Bad certificates or a busy port fail
+    /// startup rather than leaving the daemon unreachable remotely.
+    async fn spawn_tls_server(
+        &self,
+        ipc_state: &Arc<ipc::IpcState>,
+    ) -> Result<Option<tokio::task::JoinHandle<()>>, DaemonError> {
+        let daemon = &self.config.daemon;
+        if !daemon.tls.enabled {
+            return Ok(None);
+        }
+        let tls = ipc::tls::server_config(&daemon.tls)
+            .map_err(|e| DaemonError::Startup(e.to_string()))?;
+        let listener = ipc::tls::TlsListener::bind(
+            (daemon.listen_addr.as_str(), daemon.listen_port),
+            tls,
+            self.config_rx.clone(),
+        )
+        .await
+        .map_err(|e| {
+            DaemonError::Startup(format!(
+                "failed to bind {}:{}: {e}",
+                daemon.listen_addr, daemon.listen_port
+            ))
+        })?;
+        let state = ipc_state.clone();
+        let shutdown_rx = self.shutdown_tx.subscribe();
+        Ok(Some(tokio::spawn(async move {
+            if let Err(e) = ipc::server::serve_tls(listener, state, shutdown_rx).await {
+                error!(error = %e, "IPC TLS server error");
+            }
+        })))
+    }
+
+    /// Run the preflight checks, failing with a consolidated report.
+    async fn run_preflight(&self) -> Result<(), DaemonError> {
+        if self.skip_preflight {
+            self.warnings.push(
+                WarningKind::Insecure,
+                "preflight",
+                "startup preflight checks were skipped (--skip-preflight)",
+            );
+            return Ok(());
+        }
+
+        let report = preflight::run(&self.config).await;
+        for check in &report.checks {
+            if check.passed {
+                info!(check = check.name, "Preflight: {}", check.detail);
+            } else {
+                error!(check = check.name, "Preflight: {}", check.detail);
+            }
+        }
+        if report.passed() {
+            Ok(())
+        } else {
+            Err(DaemonError::Preflight(report))
+        }
+    }
+
+    /// The service running `crustyclaw chat` sessions with the daemon's
+    /// tools, sandbox, quotas, metrics, and usage ledger.
+    fn chat_service(&self) -> ChatService {
+        let pref = match self.config.isolation.backend.as_str() {
+            "docker" => isolation::BackendPreference::Docker,
+            "podman" => isolation::BackendPreference::Podman,
+            "firecracker" => isolation::BackendPreference::Firecracker,
+            "apple-vz" => isolation::BackendPreference::AppleVz,
+            "linux-ns" => isolation::BackendPreference::LinuxNamespace,
+            "noop" => isolation::BackendPreference::Noop,
+            _ => isolation::BackendPreference::Auto,
+        };
+        let docker = isolation::DockerSandboxBackend::from_config(&self.config.isolation);
+        let backend: Arc<dyn isolation::SandboxBackend> =
+            Arc::from(isolation::select_backend_with(&pref, docker));
+        let chat = ChatService::new(
+            self.config_rx.clone(),
+            self.tools.clone(),
+            self.workspaces.clone(),
+        )
+        .with_mcp(self.mcp.clone())
+        .with_plugins(self.plugin_host.clone())
+        .with_sandbox(backend, isolation::SandboxConfig::new(chat::CHANNEL))
+        .with_pool(self.sandbox_pool.clone())
+        .with_secrets(self.secrets.clone())
+        .with_secrets_watcher(self.secrets_watcher())
+        .with_quotas(self.quotas.clone())
+        .with_metrics(self.metrics.clone())
+        .with_usage(self.usage.clone())
+        .with_storage(self.storage.clone());
+        let chat = if self.config.memory.enabled {
+            chat.with_memory(self.memory.clone())
+        } else {
+            chat
+        };
+        #[cfg(feature = "wasm-plugins")]
+        let chat = chat.with_wasm(self.wasm_plugins.clone());
+        chat
+    }
+
+    /// Clean up after runs interrupted by an unclean shutdown of the
+    /// previous daemon: reap orphaned sandboxes, mark the runs failed, and
+    /// notify the conversations they came from.
+    async fn recover_interrupted_runs(&self) {
+        let orphans = self.journal.take_orphans();
+
+        let pref = match self.config.isolation.backend.as_str() {
+            "docker" => isolation::BackendPreference::Docker,
+            "podman" => isolation::BackendPreference::Podman,
+            "firecracker" => isolation::BackendPreference::Firecracker,
+            "apple-vz" => isolation::BackendPreference::AppleVz,
+            "linux-ns" => isolation::BackendPreference::LinuxNamespace,
+            "noop" => isolation::BackendPreference::Noop,
+            _ => isolation::BackendPreference::Auto,
+        };
+        let backend = isolation::select_backend(&pref);
+        let reaped = if backend.available() {
+            backend.reap_orphans().await.unwrap_or_else(|e| {
+                self.warnings.push(
+                    WarningKind::Unavailable,
+                    "recovery",
+                    format!("failed to reap orphaned {} sandboxes: {e}", backend.name()),
+                );
+                Vec::new()
+            })
+        } else {
+            Vec::new()
+        };
+        if !reaped.is_empty() {
+            warn!(
+                backend = backend.name(),
+                count = reaped.len(),
+                "Reaped orphaned sandboxes"
+            );
+        }
+        if orphans.is_empty() {
+            return;
+        }
+
+        let failed: Vec<FailedRun> = orphans
+            .into_iter()
+            .map(|run| FailedRun::interrupted(run, &reaped))
+            .collect();
+        let log_path = Path::new(&self.config.daemon.state_dir).join(recovery::FAILED_RUNS_FILE);
+        if let Err(e) = recovery::record_failed(&log_path, &failed) {
+            error!(path = %log_path.display(), error = %e, "Failed to record interrupted runs");
+        }
+
+        for run in &failed {
+            warn!(run_id = %run.run.run_id, skill = %run.run.skill, "Interrupted run marked failed: {}", run.reason);
+            if let Some(origin) = &run.run.origin {
+                let mut notice = Envelope::new(&origin.channel, &run.notice());
+                notice.direction = Direction::Outbound;
+                notice.peer = origin.peer.clone();
+                let _ = self.message_tx.send(notice);
+            }
+        }
+        self.warnings.push(
+            WarningKind::Recovered,
+            "recovery",
+            format!(
+                "{} run(s) were interrupted by an unclean shutdown and marked failed (see {})",
+                failed.len(),
+                log_path.display()
+            ),
+        );
+    }
+
+    /// Run the startup checks that need I/O: deprecated keys in the raw
+    /// config file and availability of the configured isolation backend.
+    async fn collect_startup_warnings(&self) {
+        if let Ok(raw) = tokio::fs::read_to_string(&self.config_path).await {
+            warnings::check_deprecated_keys(&raw, warnings::DEPRECATED_KEYS, &self.warnings);
+        }
+        warnings::check_host_lint(&self.config, &LintHost::current(), &self.warnings);
+        warnings::check_backend_availability(&self.config, &self.warnings);
+        if !self.warnings.is_empty() {
+            warn!(
+                count = self.warnings.len(),
+                "Daemon started with warnings (see `crustyclaw status`)"
+            );
+        }
+    }
+
     /// Reload config from disk and publish to watchers.
     ///
     /// This is non-interruptive: the new config is written to a `watch` channel.
     /// Consumers (skill engine, signal service, etc.) observe the update at their
     /// next natural pause / compaction point — running skills are never interrupted.
     async fn reload_config(&self) {
-        match AppConfig::load(&self.config_path).await {
-            Ok(new_config) => {
+        let _ = self.try_reload_config().await;
+    }
+
+    /// [`reload_config`](Self::reload_config), returning why the new config
+    /// was rejected.
+    async fn try_reload_config(&self) -> Result<(), String> {
+        let overrides = self
+            .config_overrides
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        match AppConfig::load_layered(Some(&self.config_path), &overrides).await {
+            Ok((new_config, _)) => {
+                let failures = new_config.run_policy_tests();
+                if !failures.is_empty() {
+                    for failure in &failures {
+                        error!("{failure}");
+                    }
+                    error!(
+                        failed = failures.len(),
+                        "Reloaded policy fails its tests, keeping current config"
+                    );
+                    return Err(format!(
+                        "{} of {} policy tests failed: {}",
+                        failures.len(),
+                        new_config.policy.tests.len(),
+                        failures[0]
+                    ));
+                }
                 info!("Config reloaded successfully");
+                if let Some(bundle) = &new_config.policy_bundle {
+                    let log = AuditLog::new(self.storage.clone(), provenance::AUDIT_FILE);
+                    provenance::record(&log, "reloaded", bundle);
+                }
+                self.reload_secrets(&new_config.secrets);
+                self.discover_skills(&new_config);
+                self.tools
+                    .write()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .register_configured(&new_config.tools);
+                self.import_mcp_tools(&new_config).await;
+                self.responses.reconfigure(&new_config.response);
+                self.elevations.reconfigure(&new_config.context.elevation);
+                self.commands.reconfigure(&new_config.effective_commands());
+                self.commands.set_policy(new_config.build_policy_engine());
+                self.quotas.reconfigure(&new_config.quotas);
+                if let Some(proxy) = self.egress_proxy.get() {
+                    proxy.set_allowed_hosts(new_config.isolation.egress_allowed_hosts.clone());
+                    proxy.set_secret_hosts(egress_secret_hosts(&new_config.secrets));
+                }
                 // Publish to all watchers — they pick it up when they're ready,
                 // not mid-execution.
                 let _ = self.config_tx.send(new_config);
+                self.events.publish(DaemonEvent::ConfigReloaded);
+                Ok(())
             }
             Err(e) => {
                 error!(
                     error = %e,
                     "Config reload failed, keeping current config"
                 );
+                Err(e.to_string())
+            }
+        }
+    }
+
+    /// Apply a `PUT /config` change: add its overrides, replacing earlier
+    /// ones for the same keys, and reload. If the reload is rejected the
+    /// overrides are dropped again.
+    async fn apply_config_change(&self, change: Vec<Override>) -> Result<(), String> {
+        let previous = {
+            let mut overrides = self
+                .config_overrides
+                .write()
+                .unwrap_or_else(|e| e.into_inner());
+            let previous = overrides.clone();
+            overrides.retain(|o| !change.iter().any(|c| c.path == o.path));
+            overrides.extend(change);
+            previous
+        };
+        info!(path = %self.config_path.display(), "Config change requested over IPC, reloading config");
+        let result = self.try_reload_config().await;
+        if result.is_err() {
+            *self
+                .config_overrides
+                .write()
+                .unwrap_or_else(|e| e.into_inner()) = previous;
+        }
+        result
+    }
+
+    /// Rescan `daemon.skills_dir` and replace the discovered skills.
+    ///
+    /// Rejected manifests are recorded as warnings; the valid ones are
+    /// registered regardless. Runs already in progress are unaffected.
+    fn discover_skills(&self, config: &AppConfig) {
+        let Some(dir) = config.daemon.skills_dir.as_deref() else {
+            self.skills.replace_discovered(Vec::new());
+            return;
+        };
+        let mut loader = SkillLoader::new(&config.isolation)
+            .with_pool(self.sandbox_pool.clone())
+            .with_secrets(self.secrets.clone(), &config.secrets.staging_dir);
+        if let Some(proxy) = self.egress_proxy.get() {
+            loader = loader.with_egress_proxy(proxy.clone());
+        }
+        let discovery = loader.discover(Path::new(dir));
+        for (path, e) in &discovery.errors {
+            self.warnings.push(
+                WarningKind::Unavailable,
+                format!("skills:{}", path.display()),
+                format!("skill manifest {} was rejected: {e}", path.display()),
+            );
+        }
+        let count = discovery.skills.len();
+        let skills = discovery
+            .skills
+            .into_iter()
+            .map(|skill| Box::new(skill) as Box<dyn Skill>)
+            .collect();
+        for name in self.skills.replace_discovered(skills) {
+            warn!(skill = %name, "Discovered skill has the name of a built-in skill, ignoring it");
+        }
+        info!(dir, count, "Skills discovered");
+    }
+
+    /// Load the native plugins in `[plugins].dir` and register their tools
+    /// and skills.
+    ///
+    /// Only done at startup: loaded libraries cannot be unloaded, so a
+    /// config reload does not rescan the directory. Plugins that fail to
+    /// load are recorded as warnings.
+    fn load_plugins(&self, config: &AppConfig) {
+        if !config.plugins.enabled {
+            return;
+        }
+        let load = self.plugin_host.load(config, &self.tools, &self.skills);
+        for (path, e) in &load.errors {
+            self.warnings.push(
+                WarningKind::Unavailable,
+                format!("plugin:{}", path.display()),
+                format!("plugin {} was not loaded: {e}", path.display()),
+            );
+        }
+        for path in &load.denied {
+            info!(path = %path.display(), "Plugin not allowed by policy, skipping it");
+        }
+        for name in &load.shadowed {
+            warn!(skill = %name, "Plugin skill has the name of an existing skill, ignoring it");
+        }
+        info!(loaded = ?load.loaded, "Plugins loaded");
+    }
+
+    /// Compile the WASM action plugins in `[plugins.wasm].dir` and register
+    /// their tools. Modules that fail to compile are recorded as warnings.
+    #[cfg(feature = "wasm-plugins")]
+    fn load_wasm_plugins(&self, config: &AppConfig) {
+        if !config.plugins.wasm.enabled {
+            return;
+        }
+        let state_dir = Path::new(&config.daemon.state_dir);
+        let load = self
+            .wasm_plugins
+            .load(&config.plugins.wasm, state_dir, &self.tools);
+        for (path, e) in &load.errors {
+            self.warnings.push(
+                WarningKind::Unavailable,
+                format!("wasm-plugin:{}", path.display()),
+                format!("WASM plugin {} was not loaded: {e}", path.display()),
+            );
+        }
+        info!(loaded = ?load.loaded, "WASM plugins loaded");
+    }
+
+    #[cfg(not(feature = "wasm-plugins"))]
+    fn load_wasm_plugins(&self, config: &AppConfig) {
+        if config.plugins.wasm.enabled {
+            self.warnings.push(
+                WarningKind::Unavailable,
+                "plugins.wasm".to_string(),
+                "WASM plugins need a daemon built with the wasm-plugins feature".to_string(),
+            );
+        }
+    }
+
+    /// Reconnect to the `[[mcp.servers]]` and re-import their tools.
+    ///
+    /// Servers that cannot be reached are recorded as warnings; tools from
+    /// the others are registered regardless.
+    async fn import_mcp_tools(&self, config: &AppConfig) {
+        if config.mcp.servers.is_empty() && self.mcp.tool_names().is_empty() {
+            return;
+        }
+        let import = self.mcp.import(&config.mcp, &self.tools).await;
+        for (server, e) in &import.errors {
+            self.warnings.push(
+                WarningKind::Unavailable,
+                format!("mcp:{server}"),
+                format!("MCP server {server} is unavailable, its tools are not registered: {e}"),
+            );
+        }
+    }
+
+    /// Re-resolve all secret sources and swap in changed values.
+    ///
+    /// On any resolution error the current secrets are kept. When something
+    /// changed, the credential proxy's sentinel mappings are rebuilt and a
+    /// new [`SecretsRevision`] is published.
+    fn reload_secrets(&self, config: &SecretsConfig) {
+        let fresh = match SecretStore::from_config(config) {
+            Ok(store) => store,
+            Err(e) => {
+                error!(error = %e, "Secrets reload failed, keeping current secrets");
+                return;
             }
+        };
+
+        let mut store = self.secrets.write().unwrap_or_else(|e| e.into_inner());
+        let diff = store.apply(fresh);
+        if diff.is_empty() {
+            info!("Secrets unchanged");
+            return;
         }
+
+        info!(
+            added = diff.added.len(),
+            changed = diff.changed.len(),
+            removed = diff.removed.len(),
+            "Secrets reloaded"
+        );
+        self.publish_secret_changes(&store, &diff, Path::new(&config.staging_dir));
+    }
+
+    /// Re-read env-, file-, and keychain-sourced secrets from their sources.
+    ///
+    /// Runs every `secrets.rotation_interval_secs`. Secrets whose source
+    /// cannot be read keep their current value.
+    fn rotate_secrets(&self) {
+        let mut store = self.secrets.write().unwrap_or_else(|e| e.into_inner());
+        let (diff, failures) = store.rotate_all();
+        for (name, e) in &failures {
+            warn!(secret = %name, error = %e, "Secret rotation failed, keeping current value");
+        }
+        if diff.is_empty() {
+            return;
+        }
+
+        info!(changed = diff.changed.len(), "Secrets rotated");
+        let staging_dir = self.config_rx.borrow().secrets.staging_dir.clone();
+        self.publish_secret_changes(&store, &diff, Path::new(&staging_dir));
+    }
+
+    /// Propagate a secrets change: re-stage affected secret files, rebuild
+    /// the credential proxy's sentinel mappings, and publish a new
+    /// [`SecretsRevision`].
+    fn publish_secret_changes(&self, store: &SecretStore, diff: &SecretDiff, staging_dir: &Path) {
+        let affected = diff.affected();
+        if let Err(e) = store.restage(staging_dir, &affected) {
+            error!(error = %e, "Failed to re-stage rotated secret files");
+        }
+        for name in &diff.removed {
+            let _ = std::fs::remove_file(staging_dir.join(name));
+        }
+
+        *self
+            .credential_proxy
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = CredentialProxy::from_store(store);
+
+        self.secrets_tx.send_modify(|rev| {
+            rev.generation += 1;
+            rev.changed = affected;
+        });
     }
 
     /// Request a graceful shutdown of the daemon.
@@ -215,11 +1237,176 @@ impl Daemon {
         &self.skills
     }
 
+    /// Get the tool registry, including tools imported from MCP servers.
+    pub fn tools(&self) -> &Arc<RwLock<ToolRegistry>> {
+        &self.tools
+    }
+
+    /// Get the MCP hub that routes calls to imported tools.
+    pub fn mcp(&self) -> &Arc<McpHub> {
+        &self.mcp
+    }
+
+    /// Get the host that routes calls to native plugin tools.
+    pub fn plugin_host(&self) -> &Arc<PluginHost> {
+        &self.plugin_host
+    }
+
+    /// Get the host that runs WASM action plugins.
+    #[cfg(feature = "wasm-plugins")]
+    pub fn wasm_plugins(&self) -> &Arc<WasmPluginHost> {
+        &self.wasm_plugins
+    }
+
+    /// Get the chat command router.
+    pub fn commands(&self) -> &Arc<CommandRouter> {
+        &self.commands
+    }
+
+    /// Get the per-role quota manager.
+    ///
+    /// Command aliases are charged to it; agent runs should be too, via
+    /// [`AgentRunner::with_quota`](crate::agent::AgentRunner::with_quota).
+    pub fn quotas(&self) -> &Arc<QuotaManager> {
+        &self.quotas
+    }
+
+    /// Get the daemon's metrics.
+    ///
+    /// Served at `GET /metrics` on the IPC socket, and on
+    /// `daemon.metrics_addr` as of startup. Agent runs should count into it
+    /// via [`AgentRunner::with_metrics`](crate::agent::AgentRunner::with_metrics).
+    pub fn metrics(&self) -> &Arc<Metrics> {
+        &self.metrics
+    }
+
+    /// Get the token usage ledger.
+    ///
+    /// Served at `GET /usage`. Agent runs should record into it via
+    /// [`AgentRunner::with_usage`](crate::agent::AgentRunner::with_usage).
+    pub fn usage(&self) -> &Arc<UsageLedger> {
+        &self.usage
+    }
+
+    /// Get the registry that out-of-process components report health to.
+    ///
+    /// Channel adapters report their connection state here so
+    /// `GET /health/ready` can include it.
+    pub fn health(&self) -> &Arc<HealthRegistry> {
+        &self.health
+    }
+
+    /// Get the supervisor running the daemon's long-lived tasks.
+    ///
+    /// Components started outside the daemon (the Signal channel) can run
+    /// under it to be restarted when they crash.
+    pub fn supervisor(&self) -> &Arc<Supervisor> {
+        &self.supervisor
+    }
+
     /// Get the plugin registry.
     pub fn plugins(&self) -> &Arc<PluginRegistry> {
         &self.plugins
     }
 
+    /// Get the shared sandbox pool.
+    ///
+    /// Sized from `isolation.max_concurrent` at startup. Isolated skills
+    /// should be attached to this pool so the limit applies daemon-wide.
+    pub fn sandbox_pool(&self) -> &Arc<SandboxPool> {
+        &self.sandbox_pool
+    }
+
+    /// Build the environment preamble provider from the current config.
+    ///
+    /// Reads the latest reloaded `[context.environment]`, so persona and fact
+    /// selection changes apply to the next conversation turn.
+    pub fn environment(&self) -> EnvironmentProvider {
+        EnvironmentProvider::from_config(&self.config_rx.borrow().context.environment)
+    }
+
+    /// Get the outbound response hook pipeline.
+    ///
+    /// Channel adapters run every agent message through it before sending;
+    /// the hook list follows `[response]` across reloads.
+    pub fn response_pipeline(&self) -> &Arc<ResponsePipeline> {
+        &self.responses
+    }
+
+    /// Get the tool trust elevation queue.
+    ///
+    /// The agent files requests here; operators decide them over IPC.
+    pub fn elevations(&self) -> &Arc<ElevationQueue> {
+        &self.elevations
+    }
+
+    /// Get the journal of in-flight skill runs.
+    pub fn journal(&self) -> &Arc<RunJournal> {
+        &self.journal
+    }
+
+    /// Get the daemon state store chosen by `[storage]`.
+    ///
+    /// Conversations, token usage, schedule histories, and the audit logs
+    /// are kept in it.
+    pub fn storage(&self) -> &Arc<dyn Storage> {
+        &self.storage
+    }
+
+    /// Get the conversation history store.
+    ///
+    /// Recording follows `[conversations]` as of startup; the agent resumes
+    /// conversations from it.
+    pub fn conversations(&self) -> &Arc<ConversationStore> {
+        &self.conversations
+    }
+
+    /// Get the long-term conversation memory store.
+    ///
+    /// Chat runs read and update it when `[memory]` was enabled at startup.
+    pub fn memory(&self) -> &Arc<MemoryStore> {
+        &self.memory
+    }
+
+    /// Get the conversation workspace store.
+    ///
+    /// Channel adapters use it to land incoming attachments in the
+    /// conversation's workspace.
+    pub fn workspaces(&self) -> &Arc<WorkspaceStore> {
+        &self.workspaces
+    }
+
+    /// Get the attachment spool.
+    ///
+    /// Channel adapters use it to hand incoming files to skills and tools,
+    /// which see them read-only under `/attachments`.
+    pub fn attachments(&self) -> &Arc<AttachmentSpool> {
+        &self.attachments
+    }
+
+    /// Get the startup warnings collector.
+    pub fn warnings(&self) -> &Arc<WarningCollector> {
+        &self.warnings
+    }
+
+    /// Get the secret store.
+    pub fn secrets(&self) -> &Arc<RwLock<SecretStore>> {
+        &self.secrets
+    }
+
+    /// Get the credential proxy, rebuilt whenever secrets change.
+    pub fn credential_proxy(&self) -> &Arc<RwLock<CredentialProxy>> {
+        &self.credential_proxy
+    }
+
+    /// Subscribe to secrets reloads.
+    ///
+    /// Like [`config_watcher`](Self::config_watcher), consumers pick up the
+    /// new revision at their own pace and rebuild any credentialed clients.
+    pub fn secrets_watcher(&self) -> watch::Receiver<SecretsRevision> {
+        self.secrets_rx.clone()
+    }
+
     /// Get the shutdown sender (for IPC or programmatic shutdown).
     pub fn shutdown_sender(&self) -> broadcast::Sender<ShutdownSignal> {
         self.shutdown_tx.clone()
@@ -232,10 +1419,53 @@ pub enum DaemonError {
     #[error("daemon startup failed: {0}")]
     Startup(String),
 
+    #[error("daemon startup failed: {0}")]
+    Preflight(PreflightReport),
+
+    #[error("daemon startup failed: {0}")]
+    PidFile(#[from] PidFileError),
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 }
 
+/// Periodic secret rotation timer, or `None` when rotation is disabled.
+/// Send `state` to systemd when running as a `Type=notify` service.
+fn notify_systemd(state: &str) {
+    if let Err(e) = systemd::notify(state) {
+        warn!(error = %e, state, "Failed to notify systemd");
+    }
+}
+
+fn rotation_ticker(secs: u64) -> Option<tokio::time::Interval> {
+    (secs > 0).then(|| {
+        let period = Duration::from_secs(secs);
+        let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        ticker
+    })
+}
+
+/// The hosts each secret may be sent to through the egress proxy.
+fn egress_secret_hosts(config: &SecretsConfig) -> std::collections::HashMap<String, Vec<String>> {
+    config
+        .entries
+        .iter()
+        .filter(|entry| !entry.egress_hosts.is_empty())
+        .map(|entry| (entry.name.clone(), entry.egress_hosts.clone()))
+        .collect()
+}
+
+/// Wait for the next tick; never completes when the ticker is off.
+async fn next_tick(ticker: &mut Option<tokio::time::Interval>) {
+    match ticker {
+        Some(ticker) => {
+            ticker.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -280,6 +1510,24 @@ mod tests {
         assert_eq!(received.body, "Hello, world!");
     }
 
+    #[tokio::test]
+    async fn test_sandbox_pool_sized_from_config() {
+        let mut config = AppConfig::default();
+        config.isolation.max_concurrent = 2;
+        let daemon = Daemon::new(config);
+        assert_eq!(daemon.sandbox_pool().max_concurrent(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_insecure_config_records_warning() {
+        let mut config = AppConfig::default();
+        config.isolation.backend = "noop".to_string();
+        let daemon = Daemon::new(config);
+        let warnings = daemon.warnings().list();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].key, "isolation.backend");
+    }
+
     #[tokio::test]
     async fn test_config_watcher() {
         let config = AppConfig::default();
@@ -318,6 +1566,7 @@ mod tests {
         let daemon = Daemon::with_config_path(config, path);
 
         let mut rx = daemon.config_watcher();
+        let mut events = daemon.events.subscribe();
         assert_eq!(rx.borrow().daemon.listen_port, 9100);
 
         daemon.reload_config().await;
@@ -326,6 +1575,112 @@ mod tests {
         rx.changed().await.unwrap();
         assert_eq!(rx.borrow().daemon.listen_port, 8080);
         assert_eq!(rx.borrow().daemon.listen_addr, "0.0.0.0");
+        assert_eq!(events.try_recv().unwrap(), DaemonEvent::ConfigReloaded);
+    }
+
+    #[tokio::test]
+    async fn test_config_reload_keeps_flag_overrides() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("crustyclaw.toml");
+        tokio::fs::write(&path, b"[daemon]\nlisten_port = 8080\n")
+            .await
+            .unwrap();
+
+        let flag = Override::parse_flag("daemon.listen_port=8181").unwrap();
+        let daemon =
+            Daemon::with_config_path(AppConfig::default(), path).with_config_overrides(vec![flag]);
+        let mut rx = daemon.config_watcher();
+        daemon.reload_config().await;
+
+        rx.changed().await.unwrap();
+        assert_eq!(rx.borrow().daemon.listen_port, 8181);
+    }
+
+    #[tokio::test]
+    async fn test_config_change_survives_reloads() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("crustyclaw.toml");
+        tokio::fs::write(&path, b"[daemon]\nlisten_port = 8080\n")
+            .await
+            .unwrap();
+        let daemon = Daemon::with_config_path(AppConfig::default(), path);
+        let rx = daemon.config_watcher();
+
+        let change = vec![Override::parse_flag("daemon.listen_port=8282").unwrap()];
+        daemon.apply_config_change(change).await.unwrap();
+        assert_eq!(rx.borrow().daemon.listen_port, 8282);
+        daemon.reload_config().await;
+        assert_eq!(rx.borrow().daemon.listen_port, 8282);
+
+        // A rejected change is dropped and the config is kept.
+        let change = vec![Override::parse_flag("isolation.backend=bogus").unwrap()];
+        assert!(daemon.apply_config_change(change).await.is_err());
+        assert_eq!(rx.borrow().daemon.listen_port, 8282);
+        daemon.reload_config().await;
+        assert_eq!(
+            rx.borrow().isolation.backend,
+            AppConfig::default().isolation.backend
+        );
+    }
+
+    #[tokio::test]
+    async fn test_config_reload_rescans_skills_dir() {
+        let tmp = TempDir::new().unwrap();
+        let skills_dir = tmp.path().join("skills");
+        std::fs::create_dir_all(skills_dir.join("echo")).unwrap();
+        std::fs::write(
+            skills_dir.join("echo").join("skill.toml"),
+            "name = \"echo\"\ndescription = \"Echoes\"\ncommand = [\"echo\"]\n",
+        )
+        .unwrap();
+        let path = tmp.path().join("crustyclaw.toml");
+        std::fs::write(&path, "").unwrap();
+
+        let daemon = Daemon::with_config_path(AppConfig::default(), path.clone());
+        daemon.reload_config().await;
+        assert!(daemon.skills().list().is_empty());
+
+        std::fs::write(
+            &path,
+            format!(
+                "[daemon]\nskills_dir = {:?}\n",
+                skills_dir.display().to_string()
+            ),
+        )
+        .unwrap();
+        daemon.reload_config().await;
+        assert_eq!(daemon.skills().list(), vec!["echo".to_string()]);
+        assert!(daemon.skills().get("echo").unwrap().isolated());
+
+        // A broken manifest is reported without dropping the valid skills.
+        std::fs::create_dir_all(skills_dir.join("broken")).unwrap();
+        std::fs::write(skills_dir.join("broken").join("skill.toml"), "name = 1").unwrap();
+        daemon.reload_config().await;
+        assert_eq!(daemon.skills().list(), vec!["echo".to_string()]);
+        assert!(
+            daemon
+                .warnings()
+                .list()
+                .iter()
+                .any(|w| w.key.starts_with("skills:") && w.key.contains("broken"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_config_reload_warns_on_unreachable_mcp_server() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("crustyclaw.toml");
+        std::fs::write(
+            &path,
+            "[[mcp.servers]]\nname = \"gone\"\ncommand = [\"/nonexistent/mcp-server\"]\n",
+        )
+        .unwrap();
+
+        let daemon = Daemon::with_config_path(AppConfig::default(), path);
+        daemon.reload_config().await;
+        assert!(daemon.mcp().tool_names().is_empty());
+        assert!(daemon.tools().read().unwrap().get("read_file").is_some());
+        assert!(daemon.warnings().list().iter().any(|w| w.key == "mcp:gone"));
     }
 
     #[tokio::test]
@@ -352,4 +1707,228 @@ mod tests {
         let rx = daemon.config_watcher();
         assert_eq!(rx.borrow().daemon.listen_port, 7777);
     }
+
+    const FAILING_POLICY_TEST: &str = r#"
+[daemon]
+listen_port = 7778
+
+[[policy.tests]]
+role = "admin"
+action = "read"
+resource = "config"
+expect = "allow"
+"#;
+
+    #[tokio::test]
+    async fn test_run_fails_fast_on_policy_test_failure() {
+        let config = AppConfig::parse(FAILING_POLICY_TEST).unwrap();
+        let daemon = Daemon::new(config);
+        let err = daemon.run().await.unwrap_err();
+        assert!(err.to_string().contains("1 of 1 policy tests failed"));
+    }
+
+    #[tokio::test]
+    async fn test_run_fails_fast_on_preflight() {
+        let tmp = TempDir::new().unwrap();
+        let config = AppConfig::parse(&format!(
+            r#"
+[daemon]
+socket_path = "{dir}/run/crustyclaw.sock"
+
+[[secrets.entries]]
+name = "missing"
+env_var = "CRUSTYCLAW_TEST_DAEMON_PREFLIGHT_UNSET"
+inject_env = "X"
+
+[llm]
+base_url = "http://127.0.0.1:1"
+"#,
+            dir = tmp.path().display()
+        ))
+        .unwrap();
+        let daemon = Daemon::new(config);
+        let err = daemon.run().await.unwrap_err();
+        let DaemonError::Preflight(report) = &err else {
+            panic!("expected preflight failure, got {err}");
+        };
+        let failed: Vec<&str> = report.failures().map(|c| c.name).collect();
+        assert_eq!(failed, vec!["secrets", "llm"]);
+        assert!(err.to_string().contains("2 of 5 preflight checks failed"));
+    }
+
+    #[tokio::test]
+    async fn test_config_reload_rejects_failing_policy_tests() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("crustyclaw.toml");
+        tokio::fs::write(&path, b"[daemon]\nlisten_port = 7777\n")
+            .await
+            .unwrap();
+
+        let config = AppConfig::load(&path).await.unwrap();
+        let daemon = Daemon::with_config_path(config, path.clone());
+
+        tokio::fs::write(&path, FAILING_POLICY_TEST).await.unwrap();
+        daemon.reload_config().await;
+
+        let rx = daemon.config_watcher();
+        assert_eq!(rx.borrow().daemon.listen_port, 7777);
+    }
+
+    #[tokio::test]
+    async fn test_secrets_reload_on_config_reload() {
+        let tmp = TempDir::new().unwrap();
+        let secret_path = tmp.path().join("api_key");
+        tokio::fs::write(&secret_path, b"first").await.unwrap();
+
+        let config_path = tmp.path().join("crustyclaw.toml");
+        let toml = format!(
+            "[[secrets.entries]]\nname = \"api_key\"\nsource = \"file\"\nfile_path = \"{}\"\ninject_env = \"API_KEY\"\n",
+            secret_path.display()
+        );
+        tokio::fs::write(&config_path, toml.as_bytes())
+            .await
+            .unwrap();
+
+        let config = AppConfig::load(&config_path).await.unwrap();
+        let daemon = Daemon::with_config_path(config, config_path);
+        assert_eq!(daemon.credential_proxy().read().unwrap().len(), 1);
+        let mut rx = daemon.secrets_watcher();
+
+        // Unchanged reload publishes nothing
+        daemon.reload_config().await;
+        assert!(!rx.has_changed().unwrap());
+
+        // Rotated value is swapped in and announced
+        tokio::fs::write(&secret_path, b"second").await.unwrap();
+        daemon.reload_config().await;
+        rx.changed().await.unwrap();
+        assert_eq!(rx.borrow().generation, 1);
+        assert_eq!(rx.borrow().changed, vec!["api_key"]);
+        assert_eq!(
+            daemon
+                .secrets()
+                .read()
+                .unwrap()
+                .get("api_key")
+                .unwrap()
+                .value
+                .expose(),
+            "second"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_secrets_reload_failure_keeps_current() {
+        let tmp = TempDir::new().unwrap();
+        let secret_path = tmp.path().join("api_key");
+        tokio::fs::write(&secret_path, b"kept").await.unwrap();
+
+        let config_path = tmp.path().join("crustyclaw.toml");
+        let toml = format!(
+            "[[secrets.entries]]\nname = \"api_key\"\nsource = \"file\"\nfile_path = \"{}\"\ninject_env = \"API_KEY\"\n",
+            secret_path.display()
+        );
+        tokio::fs::write(&config_path, toml.as_bytes())
+            .await
+            .unwrap();
+
+        let config = AppConfig::load(&config_path).await.unwrap();
+        let daemon = Daemon::with_config_path(config, config_path);
+
+        tokio::fs::remove_file(&secret_path).await.unwrap();
+        daemon.reload_config().await;
+
+        let store = daemon.secrets().read().unwrap();
+        assert_eq!(store.get("api_key").unwrap().value.expose(), "kept");
+    }
+
+    #[tokio::test]
+    async fn test_rotate_secrets_restages_files() {
+        let tmp = TempDir::new().unwrap();
+        let secret_path = tmp.path().join("tls_key");
+        let staging = tmp.path().join("staging");
+        std::fs::create_dir(&staging).unwrap();
+        std::fs::write(&secret_path, b"old-key").unwrap();
+
+        let config = AppConfig::parse(&format!(
+            "[secrets]\nstaging_dir = \"{}\"\nrotation_interval_secs = 60\n\n[[secrets.entries]]\nname = \"tls_key\"\nsource = \"file\"\nfile_path = \"{}\"\ninject_as = \"file\"\ninject_path = \"/run/secrets/tls.key\"\n",
+            staging.display(),
+            secret_path.display()
+        ))
+        .unwrap();
+        let daemon = Daemon::new(config);
+        let staged = daemon
+            .secrets()
+            .read()
+            .unwrap()
+            .stage_file_injections(&staging)
+            .unwrap();
+        let mut rx = daemon.secrets_watcher();
+
+        // Nothing changed on disk: no revision.
+        daemon.rotate_secrets();
+        assert!(!rx.has_changed().unwrap());
+
+        std::fs::write(&secret_path, b"new-key").unwrap();
+        daemon.rotate_secrets();
+        rx.changed().await.unwrap();
+        assert_eq!(rx.borrow().changed, vec!["tls_key"]);
+        assert_eq!(
+            std::fs::read_to_string(&staged[0].host_path).unwrap(),
+            "new-key"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_runs_are_recovered() {
+        use crate::recovery::RunOrigin;
+
+        let tmp = TempDir::new().unwrap();
+        let state_dir = tmp.path().join("state");
+        let config = AppConfig::parse(&format!(
+            "[daemon]\nstate_dir = \"{}\"\n\n[isolation]\nbackend = \"noop\"\n",
+            state_dir.display()
+        ))
+        .unwrap();
+
+        // A previous daemon crashed mid-run.
+        let previous = Daemon::new(config.clone());
+        let origin = RunOrigin {
+            conversation: "signal-_15550001".to_string(),
+            channel: "signal".to_string(),
+            peer: Some("+15550001".to_string()),
+        };
+        let entry = previous
+            .journal()
+            .begin("deploy", Some(origin), Some("deploy".to_string()));
+        entry.set_step("running");
+        std::mem::forget(entry);
+        drop(previous);
+
+        let daemon = Daemon::new(config);
+        let mut bus = daemon.message_subscriber();
+        daemon.recover_interrupted_runs().await;
+
+        let notice = bus.try_recv().unwrap();
+        assert_eq!(notice.channel, "signal");
+        assert_eq!(notice.direction, Direction::Outbound);
+        assert_eq!(notice.peer.as_deref(), Some("+15550001"));
+        assert!(notice.body.contains("skill 'deploy' did not complete"));
+
+        let log = std::fs::read_to_string(state_dir.join(recovery::FAILED_RUNS_FILE)).unwrap();
+        let failed: FailedRun = serde_json::from_str(log.lines().next().unwrap()).unwrap();
+        assert_eq!(failed.run.skill, "deploy");
+        assert!(failed.reason.contains("while the run was running"));
+        assert!(
+            daemon
+                .warnings()
+                .list()
+                .iter()
+                .any(|w| w.kind == WarningKind::Recovered)
+        );
+
+        // Recovery runs once: the journal no longer holds the run.
+        let again = Daemon::new(daemon.config().clone());
+        assert!(again.journal().take_orphans().is_empty());
+    }
 }
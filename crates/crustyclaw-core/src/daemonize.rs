@@ -0,0 +1,300 @@
+//! PID file and detaching — one daemon per PID file, running in the
+//! background if asked.
+//!
+//! [`Daemon::run`](crate::Daemon::run) takes a [`PidFile`] once preflight
+//! passes and holds it until it exits, so a second daemon started with the
+//! same config refuses to start instead of replacing the first one's IPC
+//! socket. A PID file left behind by a crashed daemon names a process that
+//! no longer exists; [`inspect`] reports it as stale and the next start
+//! replaces it. `crustyclaw start --daemonize` starts the daemon with
+//! [`detach`] and waits until it has written its PID file.
+
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+
+use crustyclaw_config::AppConfig;
+use tracing::warn;
+
+/// PID file name under `daemon.state_dir` when `daemon.pid_file` is unset.
+pub const PID_FILE_NAME: &str = "crustyclaw.pid";
+
+/// The PID file for `config`.
+pub fn pid_file_path(config: &AppConfig) -> PathBuf {
+    match &config.daemon.pid_file {
+        Some(path) => PathBuf::from(path),
+        None => Path::new(&config.daemon.state_dir).join(PID_FILE_NAME),
+    }
+}
+
+/// What a PID file says about the daemon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PidState {
+    /// There is no PID file.
+    Missing,
+    /// The PID file names a running process.
+    Running(u32),
+    /// The PID file names a process that has exited (`None` when the file
+    /// does not hold a PID at all).
+    Stale(Option<u32>),
+}
+
+/// Read the PID file at `path` and check whether its process is alive.
+///
+/// A PID reused by an unrelated process after a crash reads as running.
+pub fn inspect(path: &Path) -> PidState {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => match contents.trim().parse::<u32>() {
+            Ok(pid) if process_alive(pid) => PidState::Running(pid),
+            Ok(pid) => PidState::Stale(Some(pid)),
+            Err(_) => PidState::Stale(None),
+        },
+        Err(_) => PidState::Missing,
+    }
+}
+
+/// Errors taking the PID file.
+#[derive(Debug, thiserror::Error)]
+pub enum PidFileError {
+    #[error("crustyclaw is already running (PID {pid}, PID file {})", path.display())]
+    AlreadyRunning { pid: u32, path: PathBuf },
+
+    #[error("cannot write PID file {}: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+}
+
+/// The PID file of the running daemon, removed when dropped.
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+    pid: u32,
+}
+
+impl PidFile {
+    /// Write this process's PID to `path`, replacing a stale PID file.
+    ///
+    /// Fails if the file names another running process.
+    pub fn acquire(path: &Path) -> Result<Self, PidFileError> {
+        let io_error = |source| PidFileError::Io {
+            path: path.to_path_buf(),
+            source,
+        };
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(io_error)?;
+        }
+        let pid = std::process::id();
+        // Two attempts: the second follows removing a stale file.
+        for _ in 0..2 {
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(path)
+            {
+                Ok(mut file) => {
+                    writeln!(file, "{pid}").map_err(io_error)?;
+                    return Ok(Self {
+                        path: path.to_path_buf(),
+                        pid,
+                    });
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => match inspect(path) {
+                    PidState::Running(other) if other != pid => {
+                        return Err(PidFileError::AlreadyRunning {
+                            pid: other,
+                            path: path.to_path_buf(),
+                        });
+                    }
+                    state => {
+                        warn!(path = %path.display(), ?state, "Replacing stale PID file");
+                        match std::fs::remove_file(path) {
+                            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                                return Err(io_error(e));
+                            }
+                            _ => {}
+                        }
+                    }
+                },
+                Err(e) => return Err(io_error(e)),
+            }
+        }
+        Err(io_error(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "another daemon created it while the stale one was replaced",
+        )))
+    }
+
+    /// Where the PID file is.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        // Leave a file another daemon has since taken over.
+        if inspect(&self.path) == PidState::Running(self.pid) {
+            std::fs::remove_file(&self.path).ok();
+        }
+    }
+}
+
+/// Whether a process with `pid` exists.
+#[cfg(unix)]
+#[allow(unsafe_code)]
+pub fn process_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    if pid <= 0 {
+        return false;
+    }
+    // SAFETY: signal 0 only checks that `pid` exists and may be signalled;
+    // nothing is delivered.
+    let result = unsafe { libc::kill(pid, 0) };
+    // EPERM: the process exists but belongs to another user.
+    result == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+pub fn process_alive(_pid: u32) -> bool {
+    false
+}
+
+/// Ask the process `pid` to shut down with `SIGTERM`.
+#[cfg(unix)]
+pub fn terminate(pid: u32) -> io::Result<()> {
+    send_signal(pid, libc::SIGTERM)
+}
+
+/// Ask the process `pid` to reload its config with `SIGHUP`.
+#[cfg(unix)]
+pub fn reload(pid: u32) -> io::Result<()> {
+    send_signal(pid, libc::SIGHUP)
+}
+
+#[cfg(unix)]
+#[allow(unsafe_code)]
+fn send_signal(pid: u32, signal: libc::c_int) -> io::Result<()> {
+    let pid = libc::pid_t::try_from(pid)
+        .ok()
+        .filter(|&pid| pid > 0)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid PID"))?;
+    // SAFETY: kill has no memory effects; `pid` is a single positive PID,
+    // never a process group.
+    match unsafe { libc::kill(pid, signal) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+#[cfg(not(unix))]
+pub fn terminate(_pid: u32) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "signals are only supported on Unix",
+    ))
+}
+
+#[cfg(not(unix))]
+pub fn reload(_pid: u32) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "signals are only supported on Unix",
+    ))
+}
+
+/// Spawn `command` detached from the terminal, in a new session so it
+/// survives the shell that started it and gets no terminal signals.
+#[cfg(unix)]
+#[allow(unsafe_code)]
+pub fn detach(command: &mut Command) -> io::Result<Child> {
+    use std::os::unix::process::CommandExt;
+
+    // SAFETY: the closure runs in the forked child before exec and only
+    // calls setsid, which is async-signal-safe and allocates nothing.
+    unsafe {
+        command.pre_exec(|| match libc::setsid() {
+            -1 => Err(io::Error::last_os_error()),
+            _ => Ok(()),
+        });
+    }
+    command.stdin(std::process::Stdio::null()).spawn()
+}
+
+#[cfg(not(unix))]
+pub fn detach(command: &mut Command) -> io::Result<Child> {
+    command.stdin(std::process::Stdio::null()).spawn()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pid_file_lifecycle() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("run/crustyclaw.pid");
+        assert_eq!(inspect(&path), PidState::Missing);
+
+        let pid_file = PidFile::acquire(&path).unwrap();
+        assert_eq!(inspect(&path), PidState::Running(std::process::id()));
+        drop(pid_file);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_running_pid_blocks_a_second_daemon() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("crustyclaw.pid");
+        let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+        std::fs::write(&path, format!("{}\n", child.id())).unwrap();
+
+        let err = PidFile::acquire(&path).unwrap_err();
+        assert!(
+            matches!(err, PidFileError::AlreadyRunning { pid, .. } if pid == child.id()),
+            "{err}"
+        );
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert_eq!(inspect(&path), PidState::Stale(Some(child.id())));
+        // A stale PID file is replaced.
+        let pid_file = PidFile::acquire(&path).unwrap();
+        assert_eq!(
+            inspect(pid_file.path()),
+            PidState::Running(std::process::id())
+        );
+    }
+
+    #[test]
+    fn test_garbage_pid_file_is_stale() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("crustyclaw.pid");
+        std::fs::write(&path, "not a pid").unwrap();
+        assert_eq!(inspect(&path), PidState::Stale(None));
+        assert!(PidFile::acquire(&path).is_ok());
+    }
+
+    #[test]
+    fn test_pid_file_path() {
+        let mut config = AppConfig::default();
+        config.daemon.state_dir = "/var/lib/crustyclaw".to_string();
+        assert_eq!(
+            pid_file_path(&config),
+            Path::new("/var/lib/crustyclaw/crustyclaw.pid")
+        );
+        config.daemon.pid_file = Some("/run/crustyclaw.pid".to_string());
+        assert_eq!(pid_file_path(&config), Path::new("/run/crustyclaw.pid"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_terminate_and_detach() {
+        let mut child = detach(Command::new("sleep").arg("30")).unwrap();
+        assert!(process_alive(child.id()));
+        terminate(child.id()).unwrap();
+        let status = child.wait().unwrap();
+        assert!(!status.success());
+        assert!(!process_alive(child.id()));
+    }
+}
@@ -0,0 +1,496 @@
+//! Environment diagnostics — `crustyclaw doctor`.
+//!
+//! Where [preflight](crate::preflight) only decides whether the daemon may
+//! start, the doctor also reports what works but deserves attention, and
+//! checks things preflight does not need (policy tests, a stale socket,
+//! Signal linking). Every [`Diagnostic`] is a pass, a warning, or a
+//! failure:
+//!
+//! | Check | Warns when | Fails when |
+//! |-------|-----------|------------|
+//! | `config` | no config file was found | a `[[policy.tests]]` case fails |
+//! | `socket` | the socket is stale or open to other users | the socket directory is not writable |
+//! | `staging_dir` | | the secrets staging directory is not private |
+//! | `isolation` | the default backend is `noop` | the backend is unavailable or too weak |
+//! | `backends` | no sandbox backend is available | |
+//! | `secrets` | a secret is stored inline in the config | a secret cannot be resolved |
+//! | `llm` | no provider is configured | the endpoint is unreachable |
+//! | `signal` | the data directory is open to other users | `signal-cli`, the account, or its link is missing |
+//!
+//! Secret values are never printed; only whether each source resolves.
+
+use std::fmt;
+use std::path::Path;
+
+use crustyclaw_config::{AppConfig, SecretsConfig, SignalConfig};
+use serde::Serialize;
+
+use crate::ipc::server::socket_path_from_config;
+use crate::isolation::{BackendPreference, select_backend};
+use crate::preflight::{self, LLM_PROBE_TIMEOUT, PreflightCheck};
+
+/// Outcome of a single diagnostic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagnosticStatus {
+    /// Works as configured.
+    Pass,
+    /// Works, but deserves attention.
+    Warn,
+    /// Broken; the daemon or a feature will not work.
+    Fail,
+}
+
+impl fmt::Display for DiagnosticStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Pass => "pass",
+            Self::Warn => "warn",
+            Self::Fail => "FAIL",
+        })
+    }
+}
+
+/// One diagnostic result.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    /// Short check name (e.g. `"socket"`).
+    pub name: &'static str,
+    /// The outcome.
+    pub status: DiagnosticStatus,
+    /// What was found, and how to fix it if it is not a pass.
+    pub detail: String,
+}
+
+impl Diagnostic {
+    /// A passing diagnostic.
+    pub fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self::new(name, DiagnosticStatus::Pass, detail)
+    }
+
+    /// A warning.
+    pub fn warn(name: &'static str, detail: impl Into<String>) -> Self {
+        Self::new(name, DiagnosticStatus::Warn, detail)
+    }
+
+    /// A failure.
+    pub fn fail(name: &'static str, detail: impl Into<String>) -> Self {
+        Self::new(name, DiagnosticStatus::Fail, detail)
+    }
+
+    fn new(name: &'static str, status: DiagnosticStatus, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+impl From<PreflightCheck> for Diagnostic {
+    fn from(check: PreflightCheck) -> Self {
+        if check.passed {
+            Self::pass(check.name, check.detail)
+        } else {
+            Self::fail(check.name, check.detail)
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}: {}", self.status, self.name, self.detail)
+    }
+}
+
+/// Results of every diagnostic, in the order they ran.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DoctorReport {
+    /// Every diagnostic that ran.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl DoctorReport {
+    /// The worst status among the diagnostics.
+    pub fn status(&self) -> DiagnosticStatus {
+        self.diagnostics
+            .iter()
+            .map(|d| d.status)
+            .max()
+            .unwrap_or(DiagnosticStatus::Pass)
+    }
+
+    /// Process exit code: `0` if everything passed, `1` if anything
+    /// failed, `2` if there are only warnings.
+    pub fn exit_code(&self) -> i32 {
+        match self.status() {
+            DiagnosticStatus::Pass => 0,
+            DiagnosticStatus::Fail => 1,
+            DiagnosticStatus::Warn => 2,
+        }
+    }
+
+    /// How many diagnostics ended with `status`.
+    pub fn count(&self, status: DiagnosticStatus) -> usize {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.status == status)
+            .count()
+    }
+}
+
+/// Run every diagnostic against `config`, loaded from `config_file`
+/// (`None` when no file was found and defaults are in use).
+pub async fn run(config: &AppConfig, config_file: Option<&Path>) -> DoctorReport {
+    DoctorReport {
+        diagnostics: vec![
+            check_config(config, config_file),
+            check_socket(&socket_path_from_config(config)),
+            preflight::check_staging_dir(Path::new(&config.secrets.staging_dir)).into(),
+            check_isolation(config),
+            check_backends(),
+            check_secrets(&config.secrets),
+            check_llm(config).await,
+            check_signal(&config.signal),
+        ],
+    }
+}
+
+/// The config loaded (it was validated on load) and its policy tests pass.
+fn check_config(config: &AppConfig, file: Option<&Path>) -> Diagnostic {
+    const NAME: &str = "config";
+    let failures = config.run_policy_tests();
+    if !failures.is_empty() {
+        return Diagnostic::fail(
+            NAME,
+            format!(
+                "{} of {} policy tests fail; run `crustyclaw config lint`",
+                failures.len(),
+                config.policy.tests.len()
+            ),
+        );
+    }
+    match file {
+        Some(file) => Diagnostic::pass(
+            NAME,
+            format!(
+                "{} is valid ({} policy rules)",
+                file.display(),
+                config.policy.rules.len()
+            ),
+        ),
+        None => Diagnostic::warn(NAME, "no config file found; using defaults"),
+    }
+}
+
+/// The socket directory must be writable; an existing socket should answer
+/// and should not be open to other users.
+fn check_socket(socket_path: &Path) -> Diagnostic {
+    let dir = preflight::check_socket_dir(socket_path);
+    if !dir.passed {
+        return Diagnostic::fail("socket", dir.detail);
+    }
+    check_socket_file(socket_path)
+}
+
+#[cfg(unix)]
+fn check_socket_file(socket_path: &Path) -> Diagnostic {
+    use std::os::unix::fs::PermissionsExt;
+
+    const NAME: &str = "socket";
+    let Ok(metadata) = std::fs::metadata(socket_path) else {
+        return Diagnostic::pass(
+            NAME,
+            format!("{} (daemon not running)", socket_path.display()),
+        );
+    };
+    if std::os::unix::net::UnixStream::connect(socket_path).is_err() {
+        return Diagnostic::warn(
+            NAME,
+            format!(
+                "{} exists but nothing is listening; remove it or start the daemon",
+                socket_path.display()
+            ),
+        );
+    }
+    let mode = metadata.permissions().mode() & 0o777;
+    if mode & 0o007 != 0 {
+        return Diagnostic::warn(
+            NAME,
+            format!(
+                "{} has mode {mode:03o}; any local user can connect (chmod o-rwx)",
+                socket_path.display()
+            ),
+        );
+    }
+    Diagnostic::pass(
+        NAME,
+        format!("{} (daemon listening)", socket_path.display()),
+    )
+}
+
+#[cfg(not(unix))]
+fn check_socket_file(socket_path: &Path) -> Diagnostic {
+    Diagnostic::pass("socket", socket_path.display().to_string())
+}
+
+/// The default backend must be available and strong enough; `noop` runs
+/// skills without isolation.
+fn check_isolation(config: &AppConfig) -> Diagnostic {
+    let (backend, required) = preflight::resolve_isolation(config);
+    let check = preflight::check_backend_level(backend.as_ref(), required);
+    if check.passed && backend.name() == "noop" {
+        return Diagnostic::warn(
+            check.name,
+            "backend 'noop' runs skills without isolation; development only",
+        );
+    }
+    check.into()
+}
+
+/// Probe every sandbox backend, so operators see what they could switch to.
+fn check_backends() -> Diagnostic {
+    const NAME: &str = "backends";
+    let (available, unavailable): (Vec<_>, Vec<_>) = [
+        BackendPreference::Docker,
+        BackendPreference::Podman,
+        BackendPreference::Firecracker,
+        BackendPreference::LinuxNamespace,
+        BackendPreference::AppleVz,
+    ]
+    .iter()
+    .map(|pref| (pref.to_string(), select_backend(pref).available()))
+    .partition(|(_, available)| *available);
+    let names = |backends: Vec<(String, bool)>| {
+        backends
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    if available.is_empty() {
+        return Diagnostic::warn(NAME, "no sandbox backend is available on this host");
+    }
+    let mut detail = format!("available: {}", names(available));
+    if !unavailable.is_empty() {
+        detail.push_str(&format!("; unavailable: {}", names(unavailable)));
+    }
+    Diagnostic::pass(NAME, detail)
+}
+
+/// Every secret must resolve; inline values are flagged.
+fn check_secrets(config: &SecretsConfig) -> Diagnostic {
+    let check = preflight::check_secrets(config);
+    if !check.passed {
+        return check.into();
+    }
+    let inline: Vec<&str> = config
+        .entries
+        .iter()
+        .filter(|entry| entry.source == "inline")
+        .map(|entry| entry.name.as_str())
+        .collect();
+    if !inline.is_empty() {
+        return Diagnostic::warn(
+            check.name,
+            format!(
+                "{}; stored inline in the config: {} (prefer env or file)",
+                check.detail,
+                inline.join(", ")
+            ),
+        );
+    }
+    check.into()
+}
+
+/// The LLM endpoint must be reachable; without a provider, chat and agent
+/// skills do not work.
+async fn check_llm(config: &AppConfig) -> Diagnostic {
+    if !preflight::llm_configured(&config.llm) {
+        return Diagnostic::warn(
+            "llm",
+            "no provider configured; set llm.api_key or CRUSTYCLAW_LLM_API_KEY",
+        );
+    }
+    preflight::check_llm(&config.llm, LLM_PROBE_TIMEOUT)
+        .await
+        .into()
+}
+
+/// When Signal is enabled, `signal-cli` must be installed and the account
+/// linked in the data directory, which should be private.
+fn check_signal(config: &SignalConfig) -> Diagnostic {
+    const NAME: &str = "signal";
+    if !config.enabled {
+        return Diagnostic::pass(NAME, "disabled");
+    }
+    if !is_executable(&config.cli_path) {
+        return Diagnostic::fail(
+            NAME,
+            format!(
+                "{} not found; install signal-cli or set signal.cli_path",
+                config.cli_path
+            ),
+        );
+    }
+    let Some(account) = &config.account else {
+        return Diagnostic::fail(
+            NAME,
+            "signal.account is not set; run `crustyclaw signal link`",
+        );
+    };
+    let data_dir = Path::new(&config.data_dir);
+    let accounts = data_dir.join("data").join("accounts.json");
+    let linked = std::fs::read_to_string(&accounts)
+        .ok()
+        .and_then(|contents| serde_json::from_str::<serde_json::Value>(&contents).ok())
+        .and_then(|index| {
+            index["accounts"].as_array().map(|accounts| {
+                accounts
+                    .iter()
+                    .any(|a| a["number"].as_str() == Some(account.as_str()))
+            })
+        })
+        .unwrap_or(false);
+    if !linked {
+        return Diagnostic::fail(
+            NAME,
+            format!(
+                "{account} is not linked in {}; run `crustyclaw signal link`",
+                data_dir.display()
+            ),
+        );
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if let Ok(metadata) = std::fs::metadata(data_dir) {
+            let mode = metadata.permissions().mode() & 0o777;
+            if mode & 0o077 != 0 {
+                return Diagnostic::warn(
+                    NAME,
+                    format!(
+                        "{} has mode {mode:03o}; it holds the account's keys (chmod 700)",
+                        data_dir.display()
+                    ),
+                );
+            }
+        }
+    }
+    Diagnostic::pass(NAME, format!("{account} linked in {}", data_dir.display()))
+}
+
+/// Whether `program` is a path to a file, or found on `PATH`.
+fn is_executable(program: &str) -> bool {
+    if program.contains(std::path::MAIN_SEPARATOR) {
+        return Path::new(program).is_file();
+    }
+    std::env::var_os("PATH")
+        .is_some_and(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_code_follows_worst_status() {
+        let mut report = DoctorReport {
+            diagnostics: vec![Diagnostic::pass("config", "ok")],
+        };
+        assert_eq!(report.exit_code(), 0);
+        report.diagnostics.push(Diagnostic::warn("llm", "none"));
+        assert_eq!(report.exit_code(), 2);
+        report
+            .diagnostics
+            .push(Diagnostic::fail("secrets", "missing"));
+        assert_eq!(report.exit_code(), 1);
+        assert_eq!(report.count(DiagnosticStatus::Warn), 1);
+        assert_eq!(report.diagnostics[2].to_string(), "[FAIL] secrets: missing");
+    }
+
+    #[test]
+    fn test_config_policy_tests_and_missing_file() {
+        let config = AppConfig::parse(
+            "[[policy.rules]]\nrole = \"user\"\naction = \"read\"\nresource = \"*\"\neffect = \"allow\"\n\n\
+             [[policy.tests]]\nrole = \"user\"\naction = \"write\"\nresource = \"config\"\nexpect = \"allow\"\n",
+        )
+        .unwrap();
+        let check = check_config(&config, Some(Path::new("crustyclaw.toml")));
+        assert_eq!(check.status, DiagnosticStatus::Fail);
+        assert!(check.detail.contains("1 of 1 policy tests fail"));
+
+        let check = check_config(&AppConfig::default(), None);
+        assert_eq!(check.status, DiagnosticStatus::Warn);
+        let check = check_config(&AppConfig::default(), Some(Path::new("a.toml")));
+        assert_eq!(check.status, DiagnosticStatus::Pass, "{check}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stale_socket_warns() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("crustyclaw.sock");
+        assert_eq!(check_socket(&path).status, DiagnosticStatus::Pass);
+
+        let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        std::fs::set_permissions(&path, std::os::unix::fs::PermissionsExt::from_mode(0o600))
+            .unwrap();
+        let check = check_socket(&path);
+        assert_eq!(check.status, DiagnosticStatus::Pass, "{check}");
+
+        drop(listener);
+        let check = check_socket(&path);
+        assert_eq!(check.status, DiagnosticStatus::Warn);
+        assert!(check.detail.contains("nothing is listening"));
+    }
+
+    #[test]
+    fn test_inline_secrets_warn() {
+        let config = AppConfig::parse(
+            "[[secrets.entries]]\nname = \"token\"\nsource = \"inline\"\nvalue = \"hunter2\"\ninject_env = \"TOKEN\"\n",
+        )
+        .unwrap();
+        let check = check_secrets(&config.secrets);
+        assert_eq!(check.status, DiagnosticStatus::Warn);
+        assert!(check.detail.contains("token"));
+        assert!(!check.detail.contains("hunter2"));
+    }
+
+    #[test]
+    fn test_signal_link_state() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let mut config = SignalConfig {
+            enabled: true,
+            data_dir: tmp.path().display().to_string(),
+            account: None,
+            cli_path: "/bin/sh".to_string(),
+            ..SignalConfig::default()
+        };
+        assert_eq!(check_signal(&SignalConfig::default()).detail, "disabled");
+        assert!(check_signal(&config).detail.contains("signal.account"));
+
+        config.account = Some("+15550001111".to_string());
+        let check = check_signal(&config);
+        assert_eq!(check.status, DiagnosticStatus::Fail);
+        assert!(check.detail.contains("is not linked"));
+
+        std::fs::create_dir(tmp.path().join("data")).unwrap();
+        std::fs::write(
+            tmp.path().join("data/accounts.json"),
+            r#"{"accounts": [{"number": "+15550001111"}]}"#,
+        )
+        .unwrap();
+        #[cfg(unix)]
+        std::fs::set_permissions(
+            tmp.path(),
+            std::os::unix::fs::PermissionsExt::from_mode(0o700),
+        )
+        .unwrap();
+        let check = check_signal(&config);
+        assert_eq!(check.status, DiagnosticStatus::Pass, "{check}");
+
+        config.cli_path = "crustyclaw-no-such-signal-cli".to_string();
+        assert!(check_signal(&config).detail.contains("not found"));
+    }
+}
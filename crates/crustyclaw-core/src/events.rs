@@ -0,0 +1,169 @@
+//! Daemon events — a live feed of what the daemon is doing.
+//!
+//! Components publish [`DaemonEvent`]s on the daemon's [`EventBus`] as
+//! things happen: a config reload, a skill run starting or finishing, a
+//! request denied by `[policy]`, or a message arriving on a channel. The
+//! bus is served as server-sent events on `GET /events`, so the TUI and
+//! external integrations can react immediately instead of polling.
+//!
+//! Events are fire-and-forget: with no subscriber they are dropped, and a
+//! subscriber that falls behind skips the oldest ones.
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use crate::daemon::ShutdownSignal;
+use crate::message::{Direction, Envelope};
+
+/// Events buffered for each subscriber before it starts skipping.
+pub const EVENT_CAPACITY: usize = 256;
+
+/// Something that happened in the daemon.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DaemonEvent {
+    /// The config was reloaded from disk (SIGHUP) and published.
+    ConfigReloaded,
+    /// A skill run started.
+    SkillStarted { skill: String },
+    /// A skill run finished. `exit_code` is `None` when the skill could not
+    /// be run at all.
+    SkillFinished {
+        skill: String,
+        success: bool,
+        exit_code: Option<i32>,
+        elapsed_ms: u64,
+    },
+    /// An IPC request was denied by `[policy]`.
+    PolicyDenied {
+        identity: String,
+        action: String,
+        resource: String,
+        reason: String,
+    },
+    /// A message arrived on a channel such as `signal`. The body is left
+    /// out; read it from the conversation history.
+    MessageReceived {
+        channel: String,
+        peer: Option<String>,
+    },
+}
+
+/// Broadcasts [`DaemonEvent`]s to every subscriber. Clones share the bus.
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    tx: broadcast::Sender<DaemonEvent>,
+}
+
+impl EventBus {
+    /// Create a bus buffering [`EVENT_CAPACITY`] events per subscriber.
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(EVENT_CAPACITY);
+        Self { tx }
+    }
+
+    /// Publish `event` to the current subscribers.
+    pub fn publish(&self, event: DaemonEvent) {
+        let _ = self.tx.send(event);
+    }
+
+    /// Receive the events published from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<DaemonEvent> {
+        self.tx.subscribe()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The event for `envelope`, if it is a message received from a channel.
+fn message_event(envelope: &Envelope) -> Option<DaemonEvent> {
+    (envelope.direction == Direction::Inbound && envelope.redacts.is_none()).then(|| {
+        DaemonEvent::MessageReceived {
+            channel: envelope.channel.clone(),
+            peer: envelope.peer.clone(),
+        }
+    })
+}
+
+/// Publish a [`DaemonEvent::MessageReceived`] on `events` for every inbound
+/// message on `bus` until shutdown.
+pub fn spawn(
+    events: EventBus,
+    bus: broadcast::Sender<Envelope>,
+    mut shutdown_rx: broadcast::Receiver<ShutdownSignal>,
+) -> JoinHandle<()> {
+    let mut messages = bus.subscribe();
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.recv() => break,
+                msg = messages.recv() => match msg {
+                    Ok(envelope) => {
+                        if let Some(event) = message_event(&envelope) {
+                            events.publish(event);
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!(skipped = n, "Event feed fell behind the message bus");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_serialization() {
+        let event = DaemonEvent::SkillFinished {
+            skill: "echo".to_string(),
+            success: true,
+            exit_code: Some(0),
+            elapsed_ms: 12,
+        };
+        let json = serde_json::to_string(&event).unwrap();
+        assert_eq!(
+            json,
+            r#"{"type":"skill_finished","skill":"echo","success":true,"exit_code":0,"elapsed_ms":12}"#
+        );
+        assert_eq!(serde_json::from_str::<DaemonEvent>(&json).unwrap(), event);
+        assert_eq!(
+            serde_json::to_string(&DaemonEvent::ConfigReloaded).unwrap(),
+            r#"{"type":"config_reloaded"}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_inbound_messages_are_published() {
+        let events = EventBus::new();
+        let mut rx = events.subscribe();
+        let (bus, _) = broadcast::channel(16);
+        let (shutdown_tx, _) = broadcast::channel(1);
+        let handle = spawn(events.clone(), bus.clone(), shutdown_tx.subscribe());
+
+        let inbound = Envelope::new("signal", "hello").with_peer("+15550001");
+        bus.send(inbound.reply("hi")).unwrap();
+        bus.send(inbound).unwrap();
+        assert_eq!(
+            rx.recv().await.unwrap(),
+            DaemonEvent::MessageReceived {
+                channel: "signal".to_string(),
+                peer: Some("+15550001".to_string()),
+            }
+        );
+
+        shutdown_tx.send(ShutdownSignal).unwrap();
+        handle.await.unwrap();
+        assert!(rx.try_recv().is_err());
+    }
+}
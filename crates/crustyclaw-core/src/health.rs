@@ -0,0 +1,264 @@
+//! Liveness and readiness — per-component health for `/health/live` and
+//! `/health/ready`.
+//!
+//! Liveness only says the daemon's event loop is answering. Readiness runs
+//! a check per component on every request, so wrappers (systemd,
+//! Kubernetes-style probes) can hold traffic until the daemon can actually
+//! serve it:
+//!
+//! | Component | Down when |
+//! |-----------|-----------|
+//! | `isolation` | the configured backend is unavailable, or weaker than `default_trust_tier` requires |
+//! | `llm` | `health.probe_llm` is set and the LLM endpoint does not accept a connection |
+//! | `signal` | the Signal channel is enabled but not connected |
+//! | `staging_dir` | the secrets staging directory is not private or not writable |
+//!
+//! Components that run outside the daemon (the Signal channel) report their
+//! state to the [`HealthRegistry`]; the rest are checked directly, reusing
+//! the [preflight](crate::preflight) checks.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use crustyclaw_config::AppConfig;
+
+use crate::preflight::{self, PreflightCheck};
+
+/// State of one component.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComponentStatus {
+    /// Working.
+    Up,
+    /// Not working; the daemon is not ready.
+    Down,
+    /// Turned off or not checked; does not affect readiness.
+    Disabled,
+}
+
+impl ComponentStatus {
+    /// The status as shown in `/health/ready`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Up => "up",
+            Self::Down => "down",
+            Self::Disabled => "disabled",
+        }
+    }
+}
+
+/// Health of one component.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentHealth {
+    /// Component name (e.g. `"isolation"`).
+    pub name: String,
+    /// Its state.
+    pub status: ComponentStatus,
+    /// What was checked, or why it is down.
+    pub detail: String,
+}
+
+impl ComponentHealth {
+    /// A component in `status`.
+    pub fn new(name: &str, status: ComponentStatus, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+impl From<PreflightCheck> for ComponentHealth {
+    fn from(check: PreflightCheck) -> Self {
+        let status = if check.passed {
+            ComponentStatus::Up
+        } else {
+            ComponentStatus::Down
+        };
+        Self::new(check.name, status, check.detail)
+    }
+}
+
+/// Health reported by components that run outside the daemon.
+#[derive(Debug, Default)]
+pub struct HealthRegistry {
+    reported: RwLock<BTreeMap<String, ComponentHealth>>,
+}
+
+impl HealthRegistry {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the current state of the component `name`.
+    pub fn report(&self, name: &str, status: ComponentStatus, detail: impl Into<String>) {
+        self.reported
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(name.to_string(), ComponentHealth::new(name, status, detail));
+    }
+
+    /// The last state reported for `name`.
+    pub fn get(&self, name: &str) -> Option<ComponentHealth> {
+        self.reported
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(name)
+            .cloned()
+    }
+}
+
+/// Check every component against `config`.
+///
+/// The isolation probe may shell out (e.g. `docker info`), so it runs on the
+/// blocking pool.
+pub async fn readiness(config: &AppConfig, registry: &HealthRegistry) -> Vec<ComponentHealth> {
+    let isolation = {
+        let config = config.clone();
+        tokio::task::spawn_blocking(move || preflight::check_isolation(&config).into())
+            .await
+            .unwrap_or_else(|e| {
+                ComponentHealth::new("isolation", ComponentStatus::Down, e.to_string())
+            })
+    };
+    let llm = if config.health.probe_llm {
+        let timeout = Duration::from_secs(config.health.probe_timeout_secs);
+        preflight::check_llm(&config.llm, timeout).await.into()
+    } else {
+        ComponentHealth::new("llm", ComponentStatus::Disabled, "not probed")
+    };
+    let signal = if config.signal.enabled {
+        registry
+            .get("signal")
+            .unwrap_or_else(|| ComponentHealth::new("signal", ComponentStatus::Down, "not started"))
+    } else {
+        ComponentHealth::new(
+            "signal",
+            ComponentStatus::Disabled,
+            "signal.enabled is false",
+        )
+    };
+    let staging = check_staging_dir(Path::new(&config.secrets.staging_dir));
+    vec![isolation, llm, signal, staging]
+}
+
+/// Whether none of `components` is down.
+pub fn is_ready(components: &[ComponentHealth]) -> bool {
+    components.iter().all(|c| c.status != ComponentStatus::Down)
+}
+
+/// The staging directory must pass the preflight permission check and, once
+/// it exists, accept a new file.
+fn check_staging_dir(dir: &Path) -> ComponentHealth {
+    let check = preflight::check_staging_dir(dir);
+    if !check.passed || !dir.exists() {
+        return check.into();
+    }
+    let probe = dir.join(format!(".crustyclaw-health-{}", std::process::id()));
+    match std::fs::write(&probe, b"") {
+        Ok(()) => {
+            std::fs::remove_file(&probe).ok();
+            ComponentHealth::new(
+                check.name,
+                ComponentStatus::Up,
+                format!("{} is private and writable", dir.display()),
+            )
+        }
+        Err(e) => ComponentHealth::new(
+            check.name,
+            ComponentStatus::Down,
+            format!("{} is not writable: {e}", dir.display()),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(extra: &str, staging: &Path) -> AppConfig {
+        AppConfig::parse(&format!(
+            "[isolation]\nbackend = \"noop\"\n\n[secrets]\nstaging_dir = {:?}\n\n{extra}",
+            staging.display().to_string()
+        ))
+        .unwrap()
+    }
+
+    fn statuses(components: &[ComponentHealth]) -> Vec<(&str, ComponentStatus)> {
+        components
+            .iter()
+            .map(|c| (c.name.as_str(), c.status))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_readiness_defaults() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let registry = HealthRegistry::new();
+        let components = readiness(&config("", &tmp.path().join("staging")), &registry).await;
+        assert_eq!(
+            statuses(&components),
+            [
+                ("isolation", ComponentStatus::Up),
+                ("llm", ComponentStatus::Disabled),
+                ("signal", ComponentStatus::Disabled),
+                ("staging_dir", ComponentStatus::Up),
+            ]
+        );
+        assert!(is_ready(&components));
+    }
+
+    #[tokio::test]
+    async fn test_signal_reported_state() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let config = config("[signal]\nenabled = true\n", &tmp.path().join("staging"));
+        let registry = HealthRegistry::new();
+
+        let components = readiness(&config, &registry).await;
+        assert_eq!(components[2].status, ComponentStatus::Down);
+        assert_eq!(components[2].detail, "not started");
+        assert!(!is_ready(&components));
+
+        registry.report("signal", ComponentStatus::Up, "connected as +15550001");
+        let components = readiness(&config, &registry).await;
+        assert_eq!(components[2].detail, "connected as +15550001");
+        assert!(is_ready(&components));
+    }
+
+    #[tokio::test]
+    async fn test_llm_probe() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        // Reserve a port and close it so the connection is refused.
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let config = config(
+            &format!("[health]\nprobe_llm = true\n\n[llm]\nbase_url = \"http://{addr}\"\n"),
+            &tmp.path().join("staging"),
+        );
+        let components = readiness(&config, &HealthRegistry::new()).await;
+        assert_eq!(components[1].status, ComponentStatus::Down);
+        assert!(components[1].detail.contains("unreachable"));
+        assert!(!is_ready(&components));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_staging_dir_writable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dir = tmp.path().join("staging");
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700)).unwrap();
+        let check = check_staging_dir(&dir);
+        assert_eq!(check.status, ComponentStatus::Up, "{}", check.detail);
+
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(check_staging_dir(&dir).status, ComponentStatus::Down);
+    }
+}
@@ -0,0 +1,240 @@
+//! Host metrics — a lightweight sampler for dashboard and scheduling context.
+//!
+//! [`HostSampler`] collects CPU load, memory, free disk space on the
+//! daemon's data directories, and the daemon's open file descriptor count.
+//! Linux values come from `/proc`; disk usage is read via `df -Pk` so that
+//! no platform-specific syscalls are needed. Any metric that cannot be read
+//! on the current platform is reported as `None` rather than as an error.
+//!
+//! Samples are cached for [`HostSampler::min_interval`] so that frequent
+//! polling (e.g. from several TUI instances) does not spawn a `df` process
+//! per request.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Default minimum interval between fresh samples.
+const DEFAULT_MIN_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Disk usage for the filesystem containing a monitored path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiskUsage {
+    /// Short label for the path (e.g. `"signal.data_dir"`).
+    pub label: String,
+    /// The monitored path.
+    pub path: PathBuf,
+    /// Total filesystem size in bytes.
+    pub total_bytes: Option<u64>,
+    /// Bytes available to unprivileged users.
+    pub free_bytes: Option<u64>,
+}
+
+/// A point-in-time sample of host metrics.
+#[derive(Debug, Clone, Default)]
+pub struct HostMetrics {
+    /// 1, 5, and 15 minute load averages.
+    pub load_avg: Option<[f64; 3]>,
+    /// Number of logical CPUs available to the daemon.
+    pub cpu_count: usize,
+    /// Total physical memory in bytes.
+    pub memory_total_bytes: Option<u64>,
+    /// Memory available for new allocations without swapping, in bytes.
+    pub memory_available_bytes: Option<u64>,
+    /// Disk usage for each monitored path.
+    pub disks: Vec<DiskUsage>,
+    /// Open file descriptors held by the daemon process.
+    pub open_fds: Option<usize>,
+}
+
+impl HostMetrics {
+    /// Take a fresh sample, reporting disk usage for each `(label, path)`.
+    pub fn sample(paths: &[(String, PathBuf)]) -> Self {
+        let (memory_total_bytes, memory_available_bytes) = std::fs::read_to_string("/proc/meminfo")
+            .map(|s| parse_meminfo(&s))
+            .unwrap_or((None, None));
+
+        Self {
+            load_avg: std::fs::read_to_string("/proc/loadavg")
+                .ok()
+                .and_then(|s| parse_loadavg(&s)),
+            cpu_count: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            memory_total_bytes,
+            memory_available_bytes,
+            disks: paths
+                .iter()
+                .map(|(label, path)| disk_usage(label, path))
+                .collect(),
+            open_fds: count_open_fds(),
+        }
+    }
+}
+
+/// Caching host metrics sampler shared by the IPC server.
+pub struct HostSampler {
+    min_interval: Duration,
+    last: Mutex<Option<(Instant, HostMetrics)>>,
+}
+
+impl HostSampler {
+    /// Create a sampler with the default minimum interval (2s).
+    pub fn new() -> Self {
+        Self {
+            min_interval: DEFAULT_MIN_INTERVAL,
+            last: Mutex::new(None),
+        }
+    }
+
+    /// Set the minimum interval between fresh samples.
+    pub fn with_min_interval(mut self, interval: Duration) -> Self {
+        self.min_interval = interval;
+        self
+    }
+
+    /// Minimum interval between fresh samples.
+    pub fn min_interval(&self) -> Duration {
+        self.min_interval
+    }
+
+    /// Return the cached sample if it is recent enough, otherwise take a new one.
+    ///
+    /// This may spawn `df`; call it from a blocking context.
+    pub fn sample(&self, paths: &[(String, PathBuf)]) -> HostMetrics {
+        {
+            let last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some((at, metrics)) = last.as_ref()
+                && at.elapsed() < self.min_interval
+            {
+                return metrics.clone();
+            }
+        }
+
+        let metrics = HostMetrics::sample(paths);
+        *self.last.lock().unwrap_or_else(|e| e.into_inner()) =
+            Some((Instant::now(), metrics.clone()));
+        metrics
+    }
+}
+
+impl Default for HostSampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Parse the first three fields of `/proc/loadavg`.
+fn parse_loadavg(s: &str) -> Option<[f64; 3]> {
+    let mut fields = s.split_whitespace().map(|f| f.parse::<f64>().ok());
+    Some([fields.next()??, fields.next()??, fields.next()??])
+}
+
+/// Parse `MemTotal` and `MemAvailable` (in kB) from `/proc/meminfo`, as bytes.
+fn parse_meminfo(s: &str) -> (Option<u64>, Option<u64>) {
+    let field = |name: &str| {
+        s.lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+            .and_then(|rest| rest.split_whitespace().next()?.parse::<u64>().ok())
+            .map(|kb| kb * 1024)
+    };
+    (field("MemTotal"), field("MemAvailable"))
+}
+
+/// Parse POSIX `df -Pk` output into `(total_bytes, free_bytes)`.
+fn parse_df(s: &str) -> Option<(u64, u64)> {
+    let line = s.lines().nth(1)?;
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    // Filesystem 1024-blocks Used Available Capacity Mounted-on
+    let total = fields.get(1)?.parse::<u64>().ok()?;
+    let available = fields.get(3)?.parse::<u64>().ok()?;
+    Some((total * 1024, available * 1024))
+}
+
+/// Disk usage for `path`, measured on its nearest existing ancestor so that
+/// directories which have not been created yet still report their filesystem.
+fn disk_usage(label: &str, path: &Path) -> DiskUsage {
+    let probe = path.ancestors().find(|p| p.exists()).map(Path::to_path_buf);
+    let usage = probe.and_then(|probe| {
+        let output = std::process::Command::new("df")
+            .arg("-Pk")
+            .arg(&probe)
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        parse_df(&String::from_utf8_lossy(&output.stdout))
+    });
+
+    DiskUsage {
+        label: label.to_string(),
+        path: path.to_path_buf(),
+        total_bytes: usage.map(|(total, _)| total),
+        free_bytes: usage.map(|(_, free)| free),
+    }
+}
+
+/// Count this process's open file descriptors.
+fn count_open_fds() -> Option<usize> {
+    ["/proc/self/fd", "/dev/fd"]
+        .iter()
+        .find_map(|dir| std::fs::read_dir(dir).ok())
+        .map(|entries| entries.count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_loadavg() {
+        assert_eq!(
+            parse_loadavg("0.67 0.64 0.57 2/71 5760\n"),
+            Some([0.67, 0.64, 0.57])
+        );
+        assert_eq!(parse_loadavg("0.5 garbage"), None);
+        assert_eq!(parse_loadavg(""), None);
+    }
+
+    #[test]
+    fn test_parse_meminfo() {
+        let meminfo = "MemTotal:        6158152 kB\nMemFree:         1270184 kB\nMemAvailable:    5503084 kB\n";
+        assert_eq!(
+            parse_meminfo(meminfo),
+            (Some(6158152 * 1024), Some(5503084 * 1024))
+        );
+        assert_eq!(parse_meminfo("MemTotal: 10 kB\n"), (Some(10240), None));
+    }
+
+    #[test]
+    fn test_parse_df() {
+        let df = "Filesystem     1024-blocks     Used Available Capacity Mounted on\n\
+                  /dev/vda         264212084 15437656  80619752      17% /\n";
+        assert_eq!(parse_df(df), Some((264212084 * 1024, 80619752 * 1024)));
+        assert_eq!(parse_df("header only\n"), None);
+    }
+
+    #[test]
+    fn test_sample_reports_requested_disks() {
+        let paths = vec![
+            ("tmp".to_string(), std::env::temp_dir()),
+            (
+                "missing".to_string(),
+                std::env::temp_dir().join("crustyclaw-host-missing/nested"),
+            ),
+        ];
+        let metrics = HostMetrics::sample(&paths);
+        assert!(metrics.cpu_count >= 1);
+        assert_eq!(metrics.disks.len(), 2);
+        assert_eq!(metrics.disks[0].label, "tmp");
+        assert_eq!(metrics.disks[1].label, "missing");
+    }
+
+    #[test]
+    fn test_sampler_caches_within_interval() {
+        let sampler = HostSampler::new().with_min_interval(Duration::from_secs(60));
+        let first = sampler.sample(&[("tmp".to_string(), std::env::temp_dir())]);
+        // A different path list within the interval still returns the cached sample.
+        let second = sampler.sample(&[]);
+        assert_eq!(first.disks, second.disks);
+    }
+}
@@ -1,17 +1,31 @@
-//! IPC client — connects to the daemon over a Unix domain socket.
+//! IPC client — connects to the daemon over a Unix domain socket, or to a
+//! remote daemon over mutual TLS.
 //!
 //! Provides a typed client for CLI and TUI to query daemon status,
 //! request shutdown, evaluate policies, and inspect runtime state.
-//! Uses `hyper` for proper HTTP/1.1 over the Unix socket.
+//! Uses `hyper` for proper HTTP/1.1 over the chosen transport.
+//!
+//! Every request carries this build's [`API_VERSION`], and every response
+//! is checked for the daemon's: a daemon of another major version fails
+//! the request with [`IpcClientError::VersionMismatch`].
 
 use std::path::PathBuf;
+use std::sync::Arc;
 
-use hyper::body::Bytes;
+use crustyclaw_config::AppConfig;
+use hyper::body::{Bytes, Incoming};
 use hyper_util::rt::TokioIo;
-use tokio::net::UnixStream;
+use rustls::pki_types::ServerName;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpStream, UnixStream};
+use tokio_rustls::TlsConnector;
 use tracing::debug;
 
+use super::tls::{self, TlsError};
 use super::types::*;
+use crate::auth::token;
+use crate::chat::ToolFilter;
+use crate::events::DaemonEvent;
 
 /// Errors from the IPC client.
 #[derive(Debug, thiserror::Error)]
@@ -25,6 +39,15 @@ pub enum IpcClientError {
     #[error("daemon is not running (socket not found at {0})")]
     NotRunning(PathBuf),
 
+    #[error("failed to connect to daemon at {addr}: {source}")]
+    ConnectRemote {
+        addr: String,
+        source: std::io::Error,
+    },
+
+    #[error("TLS error: {0}")]
+    Tls(String),
+
     #[error("request failed: {0}")]
     Request(String),
 
@@ -36,46 +59,199 @@ pub enum IpcClientError {
 
     #[error("daemon returned error: {0}")]
     DaemonError(String),
+
+    #[error("{}", version_mismatch(*client, *daemon))]
+    VersionMismatch {
+        client: ApiVersion,
+        daemon: ApiVersion,
+    },
+}
+
+impl From<TlsError> for IpcClientError {
+    fn from(e: TlsError) -> Self {
+        Self::Tls(e.to_string())
+    }
 }
 
-/// Client for communicating with the CrustyClaw daemon via Unix socket.
+/// Fail if `resp` comes from a daemon of another major API version.
+///
+/// Daemons that predate versioning send no header and are trusted.
+fn check_api_version<B>(resp: &hyper::Response<B>) -> Result<(), IpcClientError> {
+    let Some(daemon) = resp
+        .headers()
+        .get(API_VERSION_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(ApiVersion::parse)
+    else {
+        return Ok(());
+    };
+    let client = ApiVersion::current();
+    if client.is_compatible(daemon) {
+        Ok(())
+    } else {
+        Err(IpcClientError::VersionMismatch { client, daemon })
+    }
+}
+
+/// Client for communicating with the CrustyClaw daemon via Unix socket or TLS.
 pub struct IpcClient {
-    socket_path: PathBuf,
+    transport: Transport,
+    token: Option<String>,
 }
 
+enum Transport {
+    Unix(PathBuf),
+    Tls {
+        addr: String,
+        server_name: ServerName<'static>,
+        connector: TlsConnector,
+    },
+}
+
+/// A connected byte stream of either transport.
+trait Connection: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Connection for T {}
+
 impl IpcClient {
     /// Create a new IPC client targeting the given socket path.
     pub fn new(socket_path: impl Into<PathBuf>) -> Self {
         Self {
-            socket_path: socket_path.into(),
+            transport: Transport::Unix(socket_path.into()),
+            token: None,
         }
     }
 
+    /// Send `token` (from `crustyclaw login`) as a bearer token on every
+    /// request.
+    pub fn with_token(mut self, token: Option<String>) -> Self {
+        self.token = token;
+        self
+    }
+
+    /// Create a client for a remote daemon at `addr` (`host:port`),
+    /// verifying its certificate against `server_name`.
+    pub fn tls(
+        addr: impl Into<String>,
+        server_name: &str,
+        config: Arc<rustls::ClientConfig>,
+    ) -> Result<Self, IpcClientError> {
+        let server_name = ServerName::try_from(server_name.to_string()).map_err(|e| {
+            IpcClientError::Tls(format!("invalid server name {server_name:?}: {e}"))
+        })?;
+        Ok(Self {
+            transport: Transport::Tls {
+                addr: addr.into(),
+                server_name,
+                connector: TlsConnector::from(config),
+            },
+            token: None,
+        })
+    }
+
+    /// Create the client `config` asks for: TLS to `remote.addr` when set,
+    /// otherwise the local socket, sending the saved session token when
+    /// `auth.mode = "token"`.
+    pub fn from_config(config: &AppConfig) -> Result<Self, IpcClientError> {
+        let Some(addr) = &config.remote.addr else {
+            return Ok(Self::new(super::server::socket_path_from_config(config))
+                .with_token(token::load_token(config)));
+        };
+        let server_name = config.remote.server_name().unwrap_or_default();
+        Self::tls(addr, server_name, tls::client_config(&config.remote)?)
+    }
+
     /// Check if the daemon socket exists (daemon is likely running).
+    ///
+    /// Always `true` for a remote daemon; whether it answers is only known
+    /// once a request is made.
     pub fn daemon_available(&self) -> bool {
-        self.socket_path.exists()
+        match &self.transport {
+            Transport::Unix(path) => path.exists(),
+            Transport::Tls { .. } => true,
+        }
     }
 
-    /// Send an HTTP request over the Unix socket using hyper and return the response body.
-    async fn request(
+    async fn connect(&self) -> Result<Box<dyn Connection>, IpcClientError> {
+        match &self.transport {
+            Transport::Unix(path) => {
+                if !path.exists() {
+                    return Err(IpcClientError::NotRunning(path.clone()));
+                }
+                let stream =
+                    UnixStream::connect(path)
+                        .await
+                        .map_err(|e| IpcClientError::Connect {
+                            path: path.clone(),
+                            source: e,
+                        })?;
+                Ok(Box::new(stream))
+            }
+            Transport::Tls {
+                addr,
+                server_name,
+                connector,
+            } => {
+                let connect_error = |e| IpcClientError::ConnectRemote {
+                    addr: addr.clone(),
+                    source: e,
+                };
+                let tcp = TcpStream::connect(addr.as_str())
+                    .await
+                    .map_err(connect_error)?;
+                let stream = connector
+                    .connect(server_name.clone(), tcp)
+                    .await
+                    .map_err(connect_error)?;
+                Ok(Box::new(stream))
+            }
+        }
+    }
+
+    /// Send an HTTP request over the Unix socket using hyper and return the
+    /// response with its body unread.
+    ///
+    /// Non-success statuses are turned into errors.
+    async fn send(
         &self,
         method: &str,
         path: &str,
         body: Option<&[u8]>,
-    ) -> Result<Bytes, IpcClientError> {
-        if !self.daemon_available() {
-            return Err(IpcClientError::NotRunning(self.socket_path.clone()));
-        }
+    ) -> Result<hyper::Response<Incoming>, IpcClientError> {
+        self.send_as(method, path, body, "application/json").await
+    }
 
-        let stream =
-            UnixStream::connect(&self.socket_path)
-                .await
-                .map_err(|e| IpcClientError::Connect {
-                    path: self.socket_path.clone(),
-                    source: e,
-                })?;
+    /// Like [`send`](Self::send), with an explicit request content type.
+    async fn send_as(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<&[u8]>,
+        content_type: &str,
+    ) -> Result<hyper::Response<Incoming>, IpcClientError> {
+        let resp = self.exchange(method, path, body, content_type).await?;
+        let status = resp.status();
+        if !status.is_success() {
+            let resp_body = read_body(resp.into_body()).await?;
+            if let Ok(err) = serde_json::from_slice::<ErrorResponse>(&resp_body) {
+                return Err(IpcClientError::DaemonError(err.error));
+            }
+            return Err(IpcClientError::Request(format!(
+                "unexpected status: {status}"
+            )));
+        }
+        Ok(resp)
+    }
 
-        let io = TokioIo::new(stream);
+    /// Send a request and return the response whatever its status.
+    async fn exchange(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<&[u8]>,
+        content_type: &str,
+    ) -> Result<hyper::Response<Incoming>, IpcClientError> {
+        let io = TokioIo::new(self.connect().await?);
 
         let (mut sender, conn) =
             hyper::client::conn::http1::handshake::<_, http_body_util::Full<Bytes>>(io)
@@ -104,10 +280,14 @@ impl IpcClient {
         let mut builder = hyper::Request::builder()
             .method(http_method)
             .uri(path)
-            .header("host", "localhost");
+            .header("host", "localhost")
+            .header(API_VERSION_HEADER, API_VERSION);
 
         if body.is_some() {
-            builder = builder.header("content-type", "application/json");
+            builder = builder.header("content-type", content_type);
+        }
+        if let Some(token) = &self.token {
+            builder = builder.header("authorization", format!("Bearer {token}"));
         }
 
         let req = builder
@@ -118,24 +298,19 @@ impl IpcClient {
             .send_request(req)
             .await
             .map_err(|e| IpcClientError::Request(format!("request failed: {e}")))?;
+        check_api_version(&resp)?;
+        Ok(resp)
+    }
 
-        let status = resp.status();
-
-        let resp_body = http_body_util::BodyExt::collect(resp.into_body())
-            .await
-            .map_err(|e| IpcClientError::Request(format!("failed to read response body: {e}")))?
-            .to_bytes();
-
-        if !status.is_success() {
-            if let Ok(err) = serde_json::from_slice::<ErrorResponse>(&resp_body) {
-                return Err(IpcClientError::DaemonError(err.error));
-            }
-            return Err(IpcClientError::Request(format!(
-                "unexpected status: {status}"
-            )));
-        }
-
-        Ok(resp_body)
+    /// Send an HTTP request and return the full response body.
+    async fn request(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<&[u8]>,
+    ) -> Result<Bytes, IpcClientError> {
+        let resp = self.send(method, path, body).await?;
+        read_body(resp.into_body()).await
     }
 
     // ── Typed API methods ──────────────────────────────────────────────
@@ -146,12 +321,92 @@ impl IpcClient {
         serde_json::from_slice(&body).map_err(|e| IpcClientError::Parse(format!("health: {e}")))
     }
 
+    /// The daemon's build information: version, target, compiler, and
+    /// enabled features.
+    pub async fn version(&self) -> Result<VersionResponse, IpcClientError> {
+        let body = self.request("GET", "/version", None).await?;
+        serde_json::from_slice(&body).map_err(|e| IpcClientError::Parse(format!("version: {e}")))
+    }
+
+    /// Liveness probe — is the daemon's event loop answering?
+    pub async fn live(&self) -> Result<LivenessResponse, IpcClientError> {
+        let body = self.request("GET", "/health/live", None).await?;
+        serde_json::from_slice(&body).map_err(|e| IpcClientError::Parse(format!("live: {e}")))
+    }
+
+    /// Readiness probe with per-component status.
+    ///
+    /// A not-ready daemon answers 503 with the same body, so this returns
+    /// `Ok` with `ready: false` rather than an error.
+    pub async fn ready(&self) -> Result<ReadinessResponse, IpcClientError> {
+        let resp = self
+            .exchange("GET", "/health/ready", None, "application/json")
+            .await?;
+        let status = resp.status();
+        let body = read_body(resp.into_body()).await?;
+        if !status.is_success() && status != hyper::StatusCode::SERVICE_UNAVAILABLE {
+            return Err(IpcClientError::Request(format!(
+                "unexpected status: {status}"
+            )));
+        }
+        serde_json::from_slice(&body).map_err(|e| IpcClientError::Parse(format!("ready: {e}")))
+    }
+
     /// Get daemon status.
     pub async fn status(&self) -> Result<StatusResponse, IpcClientError> {
         let body = self.request("GET", "/status", None).await?;
         serde_json::from_slice(&body).map_err(|e| IpcClientError::Parse(format!("status: {e}")))
     }
 
+    /// Get host metrics (load, memory, disk, open FDs) from the daemon.
+    pub async fn host_status(&self) -> Result<HostStatusResponse, IpcClientError> {
+        let body = self.request("GET", "/status/host", None).await?;
+        serde_json::from_slice(&body)
+            .map_err(|e| IpcClientError::Parse(format!("host_status: {e}")))
+    }
+
+    /// Fetch several endpoints' responses in one round trip.
+    ///
+    /// `include` lists [`SNAPSHOT_SECTIONS`] (every one when empty);
+    /// `conversation` picks the `history` section's conversation, and
+    /// `logs_after` skips log entries already seen.
+    pub async fn snapshot(
+        &self,
+        include: &[&str],
+        conversation: Option<&str>,
+        logs_after: Option<u64>,
+    ) -> Result<SnapshotResponse, IpcClientError> {
+        let mut query = Vec::new();
+        if !include.is_empty() {
+            query.push(format!("include={}", include.join(",")));
+        }
+        if let Some(id) = conversation {
+            query.push(format!("conversation={id}"));
+        }
+        if let Some(seq) = logs_after {
+            query.push(format!("logs_after={seq}"));
+        }
+        let path = if query.is_empty() {
+            "/snapshot".to_string()
+        } else {
+            format!("/snapshot?{}", query.join("&"))
+        };
+        let body = self.request("GET", &path, None).await?;
+        serde_json::from_slice(&body).map_err(|e| IpcClientError::Parse(format!("snapshot: {e}")))
+    }
+
+    /// Have the daemon mint a session token for the calling user
+    /// (`auth.mode = "token"`), valid for `ttl_secs` or
+    /// `auth.token_ttl_secs`.
+    pub async fn login(&self, ttl_secs: Option<u64>) -> Result<LoginResponse, IpcClientError> {
+        let body_bytes = serde_json::to_vec(&LoginRequest { ttl_secs })
+            .map_err(|e| IpcClientError::Parse(format!("failed to serialize request: {e}")))?;
+        let body = self
+            .request("POST", "/auth/login", Some(&body_bytes))
+            .await?;
+        serde_json::from_slice(&body).map_err(|e| IpcClientError::Parse(format!("login: {e}")))
+    }
+
     /// Request daemon shutdown.
     pub async fn stop(&self) -> Result<StopResponse, IpcClientError> {
         let body = self.request("POST", "/stop", None).await?;
@@ -164,6 +419,17 @@ impl IpcClient {
         serde_json::from_slice(&body).map_err(|e| IpcClientError::Parse(format!("config: {e}")))
     }
 
+    /// Set config keys (`KEY=VALUE`, as for `--set`) and hot-reload the
+    /// daemon, returning the new config.
+    pub async fn update_config(&self, set: &[String]) -> Result<ConfigResponse, IpcClientError> {
+        let req = ConfigUpdateRequest { set: set.to_vec() };
+        let body_bytes = serde_json::to_vec(&req)
+            .map_err(|e| IpcClientError::Parse(format!("failed to serialize request: {e}")))?;
+        let body = self.request("PUT", "/config", Some(&body_bytes)).await?;
+        serde_json::from_slice(&body)
+            .map_err(|e| IpcClientError::Parse(format!("update_config: {e}")))
+    }
+
     /// Evaluate a policy rule against the running daemon's policy engine.
     pub async fn policy_eval(
         &self,
@@ -197,11 +463,504 @@ impl IpcClient {
         serde_json::from_slice(&body).map_err(|e| IpcClientError::Parse(format!("skills: {e}")))
     }
 
+    /// Execute a registered skill with named arguments.
+    ///
+    /// A non-zero skill exit code is reported in the response, not as an error.
+    pub async fn execute_skill(
+        &self,
+        name: &str,
+        args: serde_json::Map<String, serde_json::Value>,
+        trust_tier: Option<&str>,
+    ) -> Result<SkillExecuteResponse, IpcClientError> {
+        let req = SkillExecuteRequest {
+            name: name.to_string(),
+            args,
+            trust_tier: trust_tier.map(str::to_string),
+        };
+        let body_bytes = serde_json::to_vec(&req)
+            .map_err(|e| IpcClientError::Parse(format!("failed to serialize request: {e}")))?;
+        let body = self
+            .request("POST", "/skills/execute", Some(&body_bytes))
+            .await?;
+        serde_json::from_slice(&body)
+            .map_err(|e| IpcClientError::Parse(format!("execute_skill: {e}")))
+    }
+
+    /// Execute a registered skill, streaming its output as it runs.
+    ///
+    /// An unknown skill or trust tier fails here; errors once the skill
+    /// has started arrive as an `error` event.
+    pub async fn execute_skill_stream(
+        &self,
+        name: &str,
+        args: serde_json::Map<String, serde_json::Value>,
+        trust_tier: Option<&str>,
+    ) -> Result<SkillRunStream, IpcClientError> {
+        let req = SkillExecuteRequest {
+            name: name.to_string(),
+            args,
+            trust_tier: trust_tier.map(str::to_string),
+        };
+        let body = serde_json::to_vec(&req)
+            .map_err(|e| IpcClientError::Parse(format!("failed to serialize request: {e}")))?;
+        let resp = self
+            .send("POST", "/skills/execute/stream", Some(&body))
+            .await?;
+        Ok(SkillRunStream {
+            events: EventBody::new(resp.into_body()),
+        })
+    }
+
+    /// List tool trust elevation requests.
+    pub async fn elevations(&self) -> Result<ElevationsResponse, IpcClientError> {
+        let body = self.request("GET", "/elevations", None).await?;
+        serde_json::from_slice(&body).map_err(|e| IpcClientError::Parse(format!("elevations: {e}")))
+    }
+
+    /// Approve a pending elevation request as `operator`.
+    pub async fn approve_elevation(
+        &self,
+        id: u64,
+        operator: &str,
+    ) -> Result<ElevationInfo, IpcClientError> {
+        self.decide_elevation(id, "approve", operator, None).await
+    }
+
+    /// Deny a pending elevation request as `operator`.
+    pub async fn deny_elevation(
+        &self,
+        id: u64,
+        operator: &str,
+        reason: Option<&str>,
+    ) -> Result<ElevationInfo, IpcClientError> {
+        self.decide_elevation(id, "deny", operator, reason).await
+    }
+
+    async fn decide_elevation(
+        &self,
+        id: u64,
+        decision: &str,
+        operator: &str,
+        reason: Option<&str>,
+    ) -> Result<ElevationInfo, IpcClientError> {
+        let req = ElevationDecisionRequest {
+            operator: operator.to_string(),
+            reason: reason.map(str::to_string),
+        };
+        let body_bytes = serde_json::to_vec(&req)
+            .map_err(|e| IpcClientError::Parse(format!("failed to serialize request: {e}")))?;
+        let body = self
+            .request(
+                "POST",
+                &format!("/elevations/{id}/{decision}"),
+                Some(&body_bytes),
+            )
+            .await?;
+        serde_json::from_slice(&body)
+            .map_err(|e| IpcClientError::Parse(format!("{decision}_elevation: {e}")))
+    }
+
     /// Get isolation backend status.
     pub async fn isolation(&self) -> Result<IsolationStatusResponse, IpcClientError> {
         let body = self.request("GET", "/isolation", None).await?;
         serde_json::from_slice(&body).map_err(|e| IpcClientError::Parse(format!("isolation: {e}")))
     }
+
+    /// List sandbox executions tracked by the daemon's sandbox pool.
+    pub async fn sandboxes(&self) -> Result<SandboxesResponse, IpcClientError> {
+        let body = self.request("GET", "/isolation/sandboxes", None).await?;
+        serde_json::from_slice(&body).map_err(|e| IpcClientError::Parse(format!("sandboxes: {e}")))
+    }
+
+    /// Open the daemon's live log stream.
+    ///
+    /// Buffered entries with a sequence number above `after` are replayed
+    /// first (all of them when `after` is `None`), then new entries follow
+    /// as they are logged.
+    pub async fn logs_stream(&self, after: Option<u64>) -> Result<LogStream, IpcClientError> {
+        let path = match after {
+            Some(seq) => format!("/logs/stream?after={seq}"),
+            None => "/logs/stream".to_string(),
+        };
+        let resp = self.send("GET", &path, None).await?;
+        Ok(LogStream {
+            body: resp.into_body(),
+            buf: String::new(),
+        })
+    }
+
+    /// Open the daemon's [event feed](crate::events), receiving the events
+    /// published from now on.
+    pub async fn events(&self) -> Result<EventStream, IpcClientError> {
+        let resp = self.send("GET", "/events", None).await?;
+        Ok(EventStream {
+            events: EventBody::new(resp.into_body()),
+        })
+    }
+
+    /// Watch the daemon's message bus, receiving every message sent or
+    /// received from now on with how it was routed.
+    pub async fn messages_stream(&self) -> Result<MessageStream, IpcClientError> {
+        let resp = self.send("GET", "/messages/stream", None).await?;
+        Ok(MessageStream {
+            events: EventBody::new(resp.into_body()),
+        })
+    }
+
+    /// Change the daemon's log level until it restarts, in `RUST_LOG`
+    /// syntax.
+    pub async fn set_log_level(&self, level: &str) -> Result<LogLevelResponse, IpcClientError> {
+        let req = LogLevelRequest {
+            level: level.to_string(),
+        };
+        let body_bytes = serde_json::to_vec(&req)
+            .map_err(|e| IpcClientError::Parse(format!("failed to serialize request: {e}")))?;
+        let body = self
+            .request("PUT", "/logging/level", Some(&body_bytes))
+            .await?;
+        serde_json::from_slice(&body)
+            .map_err(|e| IpcClientError::Parse(format!("set_log_level: {e}")))
+    }
+
+    /// List stored conversations, most recently active first.
+    pub async fn conversations(&self) -> Result<ConversationsResponse, IpcClientError> {
+        let body = self.request("GET", "/conversations", None).await?;
+        serde_json::from_slice(&body)
+            .map_err(|e| IpcClientError::Parse(format!("conversations: {e}")))
+    }
+
+    /// Fetch a conversation's recorded messages.
+    pub async fn conversation(&self, id: &str) -> Result<ConversationResponse, IpcClientError> {
+        let body = self
+            .request("GET", &format!("/conversations/{id}"), None)
+            .await?;
+        serde_json::from_slice(&body)
+            .map_err(|e| IpcClientError::Parse(format!("conversation: {e}")))
+    }
+
+    /// Fetch a conversation's long-term memory.
+    pub async fn memory(&self, id: &str) -> Result<MemoryResponse, IpcClientError> {
+        let body = self.request("GET", &format!("/memory/{id}"), None).await?;
+        serde_json::from_slice(&body).map_err(|e| IpcClientError::Parse(format!("memory: {e}")))
+    }
+
+    /// Forget a conversation's long-term memory.
+    pub async fn memory_clear(&self, id: &str) -> Result<(), IpcClientError> {
+        self.request("DELETE", &format!("/memory/{id}"), None)
+            .await?;
+        Ok(())
+    }
+
+    /// The daemon's metrics in the Prometheus text format.
+    pub async fn metrics(&self) -> Result<String, IpcClientError> {
+        let body = self.request("GET", "/metrics", None).await?;
+        String::from_utf8(body.to_vec()).map_err(|e| IpcClientError::Parse(format!("metrics: {e}")))
+    }
+
+    /// Per-role quota usage in the current windows.
+    pub async fn quotas(&self) -> Result<QuotasResponse, IpcClientError> {
+        let body = self.request("GET", "/quotas", None).await?;
+        serde_json::from_slice(&body).map_err(|e| IpcClientError::Parse(format!("quotas: {e}")))
+    }
+
+    /// Token usage and estimated cost of the last `days` days, grouped `by`
+    /// model, role, skill, or conversation (the daemon's defaults if unset).
+    pub async fn usage(
+        &self,
+        days: Option<u32>,
+        by: Option<&str>,
+    ) -> Result<UsageResponse, IpcClientError> {
+        let mut query = Vec::new();
+        if let Some(days) = days {
+            query.push(format!("days={days}"));
+        }
+        if let Some(by) = by {
+            query.push(format!("by={by}"));
+        }
+        let path = if query.is_empty() {
+            "/usage".to_string()
+        } else {
+            format!("/usage?{}", query.join("&"))
+        };
+        let body = self.request("GET", &path, None).await?;
+        serde_json::from_slice(&body).map_err(|e| IpcClientError::Parse(format!("usage: {e}")))
+    }
+
+    /// List the daemon's supervised tasks with their state and restarts.
+    pub async fn supervisor(&self) -> Result<SupervisorResponse, IpcClientError> {
+        let body = self.request("GET", "/supervisor", None).await?;
+        serde_json::from_slice(&body).map_err(|e| IpcClientError::Parse(format!("supervisor: {e}")))
+    }
+
+    /// List the configured schedules and their recent runs.
+    pub async fn schedules(&self) -> Result<SchedulesResponse, IpcClientError> {
+        let body = self.request("GET", "/schedules", None).await?;
+        serde_json::from_slice(&body).map_err(|e| IpcClientError::Parse(format!("schedules: {e}")))
+    }
+
+    /// Start the schedule `name` now. The run continues in the background.
+    pub async fn run_schedule(&self, name: &str) -> Result<(), IpcClientError> {
+        self.request("POST", &format!("/schedules/{name}/run"), None)
+            .await
+            .map(|_| ())
+    }
+
+    /// List the tools the caller may use in chat sessions.
+    pub async fn chat_tools(&self) -> Result<ChatToolsResponse, IpcClientError> {
+        let body = self.request("GET", "/chat/tools", None).await?;
+        serde_json::from_slice(&body).map_err(|e| IpcClientError::Parse(format!("chat_tools: {e}")))
+    }
+
+    /// Send `message` to the agent, continuing `session` or starting a new
+    /// one, and stream the run's progress.
+    ///
+    /// `tools` limits the tools a new session offers, or narrows the limits
+    /// of the session being continued.
+    pub async fn chat(
+        &self,
+        session: Option<&str>,
+        message: &str,
+        tools: &ToolFilter,
+    ) -> Result<ChatStream, IpcClientError> {
+        let req = ChatSendRequest {
+            session: session.map(str::to_string),
+            message: message.to_string(),
+            allowed_tools: tools
+                .allowed
+                .as_ref()
+                .map(|names| names.iter().cloned().collect()),
+            denied_tools: tools.denied.iter().cloned().collect(),
+        };
+        let body = serde_json::to_vec(&req).map_err(|e| IpcClientError::Parse(e.to_string()))?;
+        let resp = self.send("POST", "/chat", Some(&body)).await?;
+        Ok(ChatStream {
+            events: EventBody::new(resp.into_body()),
+        })
+    }
+
+    /// Cancel the message in progress in a chat session; its stream ends
+    /// with an `error` event.
+    pub async fn chat_cancel(&self, session: &str) -> Result<(), IpcClientError> {
+        self.request("POST", &format!("/chat/{session}/cancel"), None)
+            .await?;
+        Ok(())
+    }
+
+    /// Clear a chat session's history.
+    pub async fn chat_reset(&self, session: &str) -> Result<(), IpcClientError> {
+        self.request("DELETE", &format!("/chat/{session}"), None)
+            .await?;
+        Ok(())
+    }
+
+    /// List the files in a conversation's workspace.
+    pub async fn files_list(&self, conversation: &str) -> Result<FileListResponse, IpcClientError> {
+        let body = self
+            .request("GET", &format!("/files/{conversation}"), None)
+            .await?;
+        serde_json::from_slice(&body).map_err(|e| IpcClientError::Parse(format!("files_list: {e}")))
+    }
+
+    /// Upload a file into a conversation's workspace.
+    ///
+    /// Without a `content_type` the daemon infers one from the file name.
+    pub async fn files_put(
+        &self,
+        conversation: &str,
+        name: &str,
+        content_type: Option<&str>,
+        data: &[u8],
+    ) -> Result<FileInfo, IpcClientError> {
+        let resp = self
+            .send_as(
+                "PUT",
+                &format!("/files/{conversation}/{name}"),
+                Some(data),
+                content_type.unwrap_or("application/octet-stream"),
+            )
+            .await?;
+        let body = read_body(resp.into_body()).await?;
+        serde_json::from_slice(&body).map_err(|e| IpcClientError::Parse(format!("files_put: {e}")))
+    }
+
+    /// Download a file from a conversation's workspace.
+    pub async fn files_get(
+        &self,
+        conversation: &str,
+        name: &str,
+    ) -> Result<(FileInfo, Bytes), IpcClientError> {
+        let resp = self
+            .send("GET", &format!("/files/{conversation}/{name}"), None)
+            .await?;
+        let content_type = resp
+            .headers()
+            .get(hyper::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("application/octet-stream")
+            .to_string();
+        let data = read_body(resp.into_body()).await?;
+        let info = FileInfo {
+            name: name.to_string(),
+            size: data.len() as u64,
+            content_type,
+        };
+        Ok((info, data))
+    }
+}
+
+/// Collect a response body into bytes.
+async fn read_body(body: Incoming) -> Result<Bytes, IpcClientError> {
+    Ok(http_body_util::BodyExt::collect(body)
+        .await
+        .map_err(|e| IpcClientError::Request(format!("failed to read response body: {e}")))?
+        .to_bytes())
+}
+
+/// A live stream of daemon log entries, returned by [`IpcClient::logs_stream`].
+pub struct LogStream {
+    body: Incoming,
+    /// Received text not yet terminated by a blank line.
+    buf: String,
+}
+
+impl LogStream {
+    /// Wait for the next log entry. Returns `Ok(None)` when the daemon
+    /// closes the stream (e.g. on shutdown).
+    pub async fn next(&mut self) -> Result<Option<LogEntry>, IpcClientError> {
+        loop {
+            while let Some(end) = self.buf.find("\n\n") {
+                let event: String = self.buf.drain(..end + 2).collect();
+                if let Some(entry) = parse_log_event(&event)? {
+                    return Ok(Some(entry));
+                }
+            }
+
+            let Some(frame) = http_body_util::BodyExt::frame(&mut self.body).await else {
+                return Ok(None);
+            };
+            let frame = frame
+                .map_err(|e| IpcClientError::Request(format!("log stream interrupted: {e}")))?;
+            if let Ok(data) = frame.into_data() {
+                self.buf.push_str(&String::from_utf8_lossy(&data));
+            }
+        }
+    }
+}
+
+/// A response body of JSON server-sent events, ending when the daemon
+/// closes it.
+struct EventBody {
+    body: Incoming,
+    /// Received text not yet terminated by a blank line.
+    buf: String,
+}
+
+impl EventBody {
+    fn new(body: Incoming) -> Self {
+        Self {
+            body,
+            buf: String::new(),
+        }
+    }
+
+    /// Wait for the next event; `what` names the stream in errors.
+    async fn next<T: serde::de::DeserializeOwned>(
+        &mut self,
+        what: &str,
+    ) -> Result<Option<T>, IpcClientError> {
+        loop {
+            while let Some(end) = self.buf.find("\n\n") {
+                let event: String = self.buf.drain(..end + 2).collect();
+                if let Some(data) = sse_data(&event) {
+                    return serde_json::from_str(&data)
+                        .map(Some)
+                        .map_err(|e| IpcClientError::Parse(format!("{what}: {e}")));
+                }
+            }
+
+            let Some(frame) = http_body_util::BodyExt::frame(&mut self.body).await else {
+                return Ok(None);
+            };
+            let frame =
+                frame.map_err(|e| IpcClientError::Request(format!("{what} interrupted: {e}")))?;
+            if let Ok(data) = frame.into_data() {
+                self.buf.push_str(&String::from_utf8_lossy(&data));
+            }
+        }
+    }
+}
+
+/// The progress of a chat message, returned by [`IpcClient::chat`].
+pub struct ChatStream {
+    events: EventBody,
+}
+
+impl ChatStream {
+    /// Wait for the next event. Returns `Ok(None)` once the daemon closes
+    /// the stream, after the final `done` or `error` event.
+    pub async fn next(&mut self) -> Result<Option<ChatEvent>, IpcClientError> {
+        self.events.next("chat").await
+    }
+}
+
+/// The output of a skill run, returned by
+/// [`IpcClient::execute_skill_stream`]. Dropping it cancels the run.
+pub struct SkillRunStream {
+    events: EventBody,
+}
+
+impl SkillRunStream {
+    /// Wait for the next event. Returns `Ok(None)` once the daemon closes
+    /// the stream, after the final `done` or `error` event.
+    pub async fn next(&mut self) -> Result<Option<SkillRunEvent>, IpcClientError> {
+        self.events.next("skill run").await
+    }
+}
+
+/// The daemon's live event feed, returned by [`IpcClient::events`].
+pub struct EventStream {
+    events: EventBody,
+}
+
+impl EventStream {
+    /// Wait for the next event. Returns `Ok(None)` when the daemon closes
+    /// the stream (e.g. on shutdown).
+    pub async fn next(&mut self) -> Result<Option<DaemonEvent>, IpcClientError> {
+        self.events.next("events").await
+    }
+}
+
+/// The daemon's message traffic, returned by [`IpcClient::messages_stream`].
+pub struct MessageStream {
+    events: EventBody,
+}
+
+impl MessageStream {
+    /// Wait for the next message. Returns `Ok(None)` when the daemon closes
+    /// the stream (e.g. on shutdown).
+    pub async fn next(&mut self) -> Result<Option<MessageEvent>, IpcClientError> {
+        self.events.next("messages").await
+    }
+}
+
+/// Parse one server-sent event. Comment-only events (keep-alives) yield `None`.
+fn parse_log_event(event: &str) -> Result<Option<LogEntry>, IpcClientError> {
+    let Some(data) = sse_data(event) else {
+        return Ok(None);
+    };
+    serde_json::from_str(&data)
+        .map(Some)
+        .map_err(|e| IpcClientError::Parse(format!("logs_stream: {e}")))
+}
+
+/// The data lines of one server-sent event, joined; `None` if it has none.
+fn sse_data(event: &str) -> Option<String> {
+    let data: Vec<&str> = event
+        .lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .map(str::trim_start)
+        .collect();
+    (!data.is_empty()).then(|| data.join("\n"))
 }
 
 #[cfg(test)]
@@ -214,6 +973,53 @@ mod tests {
         assert!(!client.daemon_available()); // socket doesn't exist
     }
 
+    #[test]
+    fn test_parse_log_event() {
+        let event = "id: 3\ndata: {\"seq\":3,\"elapsed_secs\":1.5,\"level\":\"INFO\",\"target\":\"crustyclaw\",\"message\":\"hi\"}\n\n";
+        let entry = parse_log_event(event).unwrap().unwrap();
+        assert_eq!(entry.seq, 3);
+        assert_eq!(entry.message, "hi");
+
+        assert!(parse_log_event(": keep-alive\n\n").unwrap().is_none());
+        assert!(matches!(
+            parse_log_event("data: not json\n\n"),
+            Err(IpcClientError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_api_version_check() {
+        let response = |version: Option<&str>| {
+            let mut builder = hyper::Response::builder();
+            if let Some(version) = version {
+                builder = builder.header(API_VERSION_HEADER, version);
+            }
+            builder.body(()).unwrap()
+        };
+        let current = ApiVersion::current();
+        assert!(check_api_version(&response(Some(API_VERSION))).is_ok());
+        assert!(check_api_version(&response(None)).is_ok());
+        let minor = format!("{}.{}", current.major, current.minor + 1);
+        assert!(check_api_version(&response(Some(&minor))).is_ok());
+
+        let newer = format!("{}.0", current.major + 1);
+        let err = check_api_version(&response(Some(&newer))).unwrap_err();
+        assert!(
+            matches!(err, IpcClientError::VersionMismatch { daemon, .. } if daemon.major == current.major + 1)
+        );
+        assert!(err.to_string().contains("upgrade the CLI"), "{err}");
+
+        assert_eq!(ApiVersion::parse("2.13").unwrap().to_string(), "2.13");
+        assert!(ApiVersion::parse("2").is_none());
+
+        // A daemon from before versioning reports no api_version.
+        let health: HealthResponse = serde_json::from_str(
+            r#"{"status":"ok","version":"0.1.0","git_hash":"abc","build_profile":"debug","added_later":1}"#,
+        )
+        .unwrap();
+        assert!(health.api_version.is_empty());
+    }
+
     #[tokio::test]
     async fn test_client_not_running_error() {
         let client = IpcClient::new("/tmp/nonexistent-crustyclaw.sock");
@@ -223,6 +1029,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_integration_server_client() {
+        use crate::isolation::SandboxPool;
         use crate::plugin::PluginRegistry;
         use crate::skill::SkillRegistry;
         use std::sync::Arc;
@@ -234,12 +1041,71 @@ mod tests {
         let config = crustyclaw_config::AppConfig::default();
         let (shutdown_tx, _) = broadcast::channel(1);
         let (_, config_rx) = watch::channel(config);
+        let workspace_root = tempfile::TempDir::new().unwrap();
+        let conversations = crate::conversation::ConversationStore::new(
+            workspace_root.path().join(".conversations"),
+        );
+        let question = crate::message::Envelope::new("signal", "hi").with_peer("+15550001");
+        conversations.record(&question).unwrap();
+        conversations.record(&question.reply("hello")).unwrap();
+        std::fs::create_dir_all(workspace_root.path().join(".memory")).unwrap();
+        std::fs::write(
+            workspace_root.path().join(".memory/signal-_15550001.json"),
+            r#"{"summary": "Said hi.", "facts": {"name": {"value": "Ada", "updated_secs": 1}}, "runs": 1, "updated_secs": 1}"#,
+        )
+        .unwrap();
+
+        let events = crate::events::EventBus::new();
+        let (messages, _) = broadcast::channel(16);
+        let mut skills = SkillRegistry::new();
+        skills.register(Box::new(crate::skill::IsolatedSkill::new(
+            "greet",
+            "Greets",
+            vec!["echo".to_string(), "hello".to_string()],
+            crate::isolation::SandboxConfig::new("client-greet").with_workdir("/tmp"),
+            Box::new(crate::isolation::NoopBackend),
+        )));
 
         let state = Arc::new(server::IpcState {
-            config: config_rx,
+            config: config_rx.clone(),
+            config_changes: None,
             shutdown_tx: shutdown_tx.clone(),
-            skills: Arc::new(SkillRegistry::new()),
+            skills: Arc::new(skills.with_events(events.clone())),
             plugins: Arc::new(PluginRegistry::new()),
+            sandboxes: Arc::new(SandboxPool::default()),
+            warnings: Arc::new(crate::warnings::WarningCollector::new()),
+            host: Arc::new(crate::host::HostSampler::new()),
+            logs: crate::logging::LogCollector::new(100).reader(),
+            log_control: None,
+            workspaces: Arc::new(crate::workspace::WorkspaceStore::new(workspace_root.path())),
+            conversations: Arc::new(conversations),
+            memory: Arc::new(crate::memory::MemoryStore::new(
+                workspace_root.path().join(".memory"),
+            )),
+            elevations: Arc::new(crate::context::ElevationQueue::new()),
+            quotas: Arc::new(crate::quota::QuotaManager::from_config(&Default::default())),
+            metrics: Arc::new(crate::metrics::Metrics::new()),
+            usage: Arc::new(crate::usage::UsageLedger::in_memory()),
+            health: Arc::new(crate::health::HealthRegistry::new()),
+            supervisor: Arc::new(crate::supervisor::Supervisor::new(shutdown_tx.clone())),
+            token_key: None,
+            webhook: None,
+            chat: Arc::new(crate::chat::ChatService::new(
+                config_rx.clone(),
+                Arc::new(std::sync::RwLock::new(
+                    crate::context::ToolRegistry::with_defaults(),
+                )),
+                Arc::new(crate::workspace::WorkspaceStore::new(workspace_root.path())),
+            )),
+            scheduler: Arc::new(crate::scheduler::Scheduler::new(
+                config_rx.clone(),
+                Arc::new(SkillRegistry::new()),
+            )),
+            events,
+            messages: messages.clone(),
+            commands: Arc::new(crate::commands::CommandRouter::from_config(
+                &Default::default(),
+            )),
             started_at: Instant::now(),
         });
 
@@ -267,13 +1133,79 @@ mod tests {
 
         let health = client.health().await.unwrap();
         assert_eq!(health.status, "ok");
+        assert_eq!(health.api_version, API_VERSION);
+        assert_eq!(client.live().await.unwrap().status, "alive");
+        // Ok whether or not this host's default backend is available.
+        let ready = client.ready().await.unwrap();
+        assert_eq!(ready.components.len(), 4);
 
         let status = client.status().await.unwrap();
         assert!(status.running);
         assert_eq!(status.listen_port, 9100);
 
+        let host = client.host_status().await.unwrap();
+        assert!(host.cpu_count >= 1);
+
+        assert!(client.logs_stream(None).await.is_ok());
+
         let skills = client.skills().await.unwrap();
-        assert!(skills.skills.is_empty());
+        assert_eq!(skills.skills.len(), 1);
+
+        let missing = client
+            .execute_skill("missing", serde_json::Map::new(), None)
+            .await;
+        assert!(matches!(missing, Err(IpcClientError::DaemonError(_))));
+
+        let mut events = client.events().await.unwrap();
+        let mut run = client
+            .execute_skill_stream("greet", serde_json::Map::new(), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            run.next().await.unwrap(),
+            Some(SkillRunEvent::Output {
+                stream: "stdout".to_string(),
+                line: "hello".to_string(),
+            })
+        );
+        assert!(matches!(
+            run.next().await.unwrap(),
+            Some(SkillRunEvent::Done { exit_code: 0, .. })
+        ));
+        assert_eq!(run.next().await.unwrap(), None);
+        assert_eq!(
+            events.next().await.unwrap(),
+            Some(DaemonEvent::SkillStarted {
+                skill: "greet".to_string()
+            })
+        );
+        assert!(matches!(
+            events.next().await.unwrap(),
+            Some(DaemonEvent::SkillFinished {
+                success: true,
+                exit_code: Some(0),
+                ..
+            })
+        ));
+        let mut traffic = client.messages_stream().await.unwrap();
+        let ping = crate::message::Envelope::new("signal", "/help").with_peer("+15550001");
+        messages.send(ping.clone()).unwrap();
+        messages.send(ping.reply("Commands: none")).unwrap();
+        let received = traffic.next().await.unwrap().unwrap();
+        assert_eq!(received.id, ping.id);
+        assert!(received.inbound);
+        assert_eq!(received.peer.as_deref(), Some("+15550001"));
+        assert_eq!(received.route, MessageRoute::Help);
+        let sent = traffic.next().await.unwrap().unwrap();
+        assert_eq!(sent.body, "Commands: none");
+        assert_eq!(sent.route, MessageRoute::Outbound);
+
+        assert!(matches!(
+            client
+                .execute_skill_stream("missing", serde_json::Map::new(), None)
+                .await,
+            Err(IpcClientError::DaemonError(_))
+        ));
 
         let plugins = client.plugins().await.unwrap();
         assert!(plugins.plugins.is_empty());
@@ -281,6 +1213,78 @@ mod tests {
         let isolation = client.isolation().await.unwrap();
         assert!(!isolation.backend.is_empty());
 
+        let sandboxes = client.sandboxes().await.unwrap();
+        assert!(sandboxes.sandboxes.is_empty());
+
+        let put = client
+            .files_put("conv-1", "notes.txt", None, b"hello")
+            .await
+            .unwrap();
+        assert_eq!(put.content_type, "text/plain");
+        let (info, data) = client.files_get("conv-1", "notes.txt").await.unwrap();
+        assert_eq!(&data[..], b"hello");
+        assert_eq!(info.content_type, "text/plain");
+        let listing = client.files_list("conv-1").await.unwrap();
+        assert_eq!(listing.files.len(), 1);
+        assert!(matches!(
+            client.files_get("conv-1", "missing.txt").await,
+            Err(IpcClientError::DaemonError(_))
+        ));
+
+        let conversations = client.conversations().await.unwrap();
+        assert_eq!(conversations.conversations.len(), 1);
+        let conversation = &conversations.conversations[0];
+        assert_eq!(conversation.id, "signal-_15550001");
+        assert_eq!(conversation.preview, "hello");
+        let history = client.conversation(&conversation.id).await.unwrap();
+        assert_eq!(history.messages.len(), 2);
+        assert!(history.messages[0].inbound);
+        assert!(matches!(
+            client.conversation("missing").await,
+            Err(IpcClientError::DaemonError(_))
+        ));
+
+        let memory = client.memory("signal-_15550001").await.unwrap();
+        assert_eq!(memory.summary, "Said hi.");
+        assert_eq!(memory.facts[0].key, "name");
+        assert_eq!(memory.facts[0].value, "Ada");
+        client.memory_clear("signal-_15550001").await.unwrap();
+        assert!(matches!(
+            client.memory("signal-_15550001").await,
+            Err(IpcClientError::DaemonError(_))
+        ));
+        assert!(matches!(
+            client.memory_clear("signal-_15550001").await,
+            Err(IpcClientError::DaemonError(_))
+        ));
+
+        // One round trip for the TUI's panels; history defaults to the most
+        // recent conversation.
+        let snapshot = client
+            .snapshot(&["status", "skills", "history"], None, None)
+            .await
+            .unwrap();
+        assert!(snapshot.status.unwrap().running);
+        assert_eq!(snapshot.skills.unwrap().skills[0].name, "greet");
+        assert_eq!(snapshot.history.unwrap().id, "signal-_15550001");
+        assert!(snapshot.isolation.is_none() && snapshot.logs.is_none());
+        assert!(snapshot.omitted.is_empty());
+        let snapshot = client.snapshot(&[], Some("missing"), None).await.unwrap();
+        assert!(snapshot.isolation.is_some() && snapshot.logs.is_some());
+        assert_eq!(snapshot.omitted.len(), 1);
+        assert_eq!(snapshot.omitted[0].section, "history");
+        assert!(matches!(
+            client.snapshot(&["weather"], None, None).await,
+            Err(IpcClientError::DaemonError(e)) if e.contains("unknown snapshot section")
+        ));
+
+        assert!(client.quotas().await.unwrap().quotas.is_empty());
+        let usage = client.usage(Some(30), Some("skill")).await.unwrap();
+        assert_eq!(usage.by, "skill");
+        assert!(usage.usage.is_empty());
+        let metrics = client.metrics().await.unwrap();
+        assert!(metrics.contains("# TYPE crustyclaw_messages_total counter"));
+
         // Stop the daemon via IPC
         let stop = client.stop().await.unwrap();
         assert!(stop.acknowledged);
@@ -2,7 +2,24 @@
 //!
 //! The daemon exposes an HTTP/JSON API over a Unix socket. The CLI and TUI
 //! connect as clients to query status, request shutdown, evaluate policies,
-//! and inspect runtime state.
+//! and inspect runtime state. `GET /logs/stream` streams the daemon's logs
+//! as server-sent events, `GET /events` streams its
+//! [events](crate::events) the same way, `GET /messages/stream` the
+//! traffic on the message bus, and `POST /chat` streams the progress of an agent
+//! run the same way, as does `POST /skills/execute/stream` for the output of
+//! a skill run. The
+//! `/files/{conversation}/{name}` endpoints carry raw file bytes rather than
+//! JSON. `/conversations` serves the recorded chat history, `/memory/{id}`
+//! a conversation's long-term memory, and
+//! `/quotas` the per-role quota usage, and `/usage` the recorded token usage
+//! with its estimated cost. `/version` reports the daemon's build: target,
+//! compiler, and enabled cargo features. `/metrics` returns Prometheus text
+//! rather than JSON. `/snapshot` bundles several read endpoints' responses
+//! into one, for clients that refresh them all on a timer.
+//!
+//! The same API can also be served to other machines over TCP with mutual
+//! TLS (`[daemon.tls]`); client certificates map to policy roles and each
+//! request is checked against the policy. See [`tls`].
 //!
 //! ## Architecture
 //!
@@ -20,8 +37,9 @@
 
 pub mod client;
 pub mod server;
+pub mod tls;
 pub mod types;
 
-pub use client::IpcClient;
+pub use client::{ChatStream, EventStream, IpcClient, LogStream, MessageStream, SkillRunStream};
 pub use server::{DEFAULT_SOCKET_PATH, IpcState};
 pub use types::*;
@@ -2,61 +2,188 @@
 //!
 //! The daemon binds a Unix socket and exposes a JSON API for
 //! the CLI and TUI to query status, request shutdown, evaluate
-//! policies, and inspect runtime state.
+//! policies, and inspect runtime state. With `[daemon.tls]` enabled the
+//! same router is also served to remote clients over mTLS; see
+//! [`serve_tls`]. With `auth.mode = "token"` every request on the Unix
+//! socket must carry a session token; see [`serve_on`]. Channel endpoints
+//! (`/channels/webhook`) authenticate each message themselves and skip
+//! both. Every response carries the daemon's API version, and requests
+//! from clients of another major version are refused; see
+//! [`types`](super::types#versioning).
 
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use std::time::Instant;
 
-use axum::Json;
-use axum::extract::State;
-use axum::http::StatusCode;
-use axum::routing::{get, post};
+use axum::body::{Body, Bytes};
+use axum::extract::connect_info::Connected;
+use axum::extract::{ConnectInfo, DefaultBodyLimit, Path as UrlPath, Query, Request, State};
+use axum::http::{HeaderMap, HeaderValue, Method, StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post, put};
+use axum::serve::IncomingStream;
+use axum::{Extension, Json};
 use tokio::net::UnixListener;
-use tokio::sync::{broadcast, watch};
-use tracing::info;
+use tokio::sync::{broadcast, mpsc, oneshot, watch};
+use tracing::{info, warn};
 
 use crustyclaw_config::AppConfig;
+use crustyclaw_config::layers::Override;
+use crustyclaw_config::policy::{PolicyDecision, PolicyVerdict, RequestContext};
 
+use super::tls::{RemotePeer, TlsListener};
 use super::types::*;
+use crate::agent::AgentEvent;
+use crate::auth::token::{self, TokenClaims, TokenKey};
+use crate::auth::{LocalIdentity, Session};
+use crate::chat::{ChatError, ChatService, ToolFilter};
+use crate::commands::{Builtin, CommandRouter, Route};
+use crate::context::{ElevationError, ElevationQueue, ElevationRequest, ElevationStatus};
+use crate::conversation::{ConversationError, ConversationStore};
 use crate::daemon::ShutdownSignal;
+use crate::events::{DaemonEvent, EventBus};
+use crate::health::{self, HealthRegistry};
+use crate::host::HostSampler;
+use crate::isolation::{OutputLine, SandboxPool, TrustTier};
+use crate::logging::{LogControl, LogControlError, LogReader};
+use crate::memory::{MemoryError, MemoryStore};
+use crate::message::{Direction, Envelope};
+use crate::metrics::{self, Denial, Metrics};
 use crate::plugin::PluginRegistry;
-use crate::skill::SkillRegistry;
+use crate::quota::QuotaManager;
+use crate::scheduler::{ScheduleError, ScheduleStatus, Scheduler};
+use crate::skill::{SkillError, SkillInvocation, SkillRegistry};
+use crate::supervisor::Supervisor;
+use crate::usage::{UsageGroup, UsageLedger};
+use crate::warnings::WarningCollector;
+use crate::webhook::{self, WebhookChannel, WebhookError};
+use crate::workspace::{WorkspaceError, WorkspaceStore};
+
+/// A `PUT /config` change for the daemon to apply.
+pub struct ConfigChange {
+    /// Keys to set, as `--set` flags would.
+    pub overrides: Vec<Override>,
+    /// Receives `Ok` once the new config is published, or why it was
+    /// rejected.
+    pub reply: oneshot::Sender<Result<(), String>>,
+}
 
 /// Shared state accessible to all IPC route handlers.
 pub struct IpcState {
     pub config: watch::Receiver<AppConfig>,
+    /// Where `PUT /config` sends changes; set when the daemon can reload.
+    pub config_changes: Option<mpsc::Sender<ConfigChange>>,
     pub shutdown_tx: broadcast::Sender<ShutdownSignal>,
     pub skills: Arc<SkillRegistry>,
     pub plugins: Arc<PluginRegistry>,
+    pub sandboxes: Arc<SandboxPool>,
+    pub warnings: Arc<WarningCollector>,
+    pub host: Arc<HostSampler>,
+    pub logs: LogReader,
+    /// The process's log level and file; set when the daemon's caller
+    /// installed the subscriber with a [`LogControl`].
+    pub log_control: Option<LogControl>,
+    pub workspaces: Arc<WorkspaceStore>,
+    pub conversations: Arc<ConversationStore>,
+    pub memory: Arc<MemoryStore>,
+    pub elevations: Arc<ElevationQueue>,
+    pub quotas: Arc<QuotaManager>,
+    pub metrics: Arc<Metrics>,
+    pub usage: Arc<UsageLedger>,
+    pub health: Arc<HealthRegistry>,
+    pub supervisor: Arc<Supervisor>,
+    /// Key for verifying session tokens; set when `auth.mode = "token"`.
+    pub token_key: Option<Arc<TokenKey>>,
+    /// The webhook channel; set when `webhook.enabled` is true.
+    pub webhook: Option<Arc<WebhookChannel>>,
+    pub chat: Arc<ChatService>,
+    pub scheduler: Arc<Scheduler>,
+    /// Served on `GET /events`; requests denied by policy are published here.
+    pub events: EventBus,
+    /// The message bus, served on `GET /messages/stream`.
+    pub messages: broadcast::Sender<Envelope>,
+    /// Routes inbound messages; reports each streamed message's route.
+    pub commands: Arc<CommandRouter>,
     pub started_at: Instant,
 }
 
+/// Interval between SSE keep-alive comments on idle log and event streams.
+const LOG_STREAM_KEEPALIVE: std::time::Duration = std::time::Duration::from_secs(15);
+
 /// Default Unix socket path for daemon IPC.
 pub const DEFAULT_SOCKET_PATH: &str = "/tmp/crustyclaw.sock";
 
 /// Build the axum router with all IPC routes.
 pub fn router(state: Arc<IpcState>) -> axum::Router {
+    let max_upload = usize::try_from(state.workspaces.max_file_bytes()).unwrap_or(usize::MAX);
     axum::Router::new()
         .route("/health", get(handle_health))
+        .route("/health/live", get(handle_live))
+        .route("/health/ready", get(handle_ready))
+        .route("/version", get(handle_version))
         .route("/status", get(handle_status))
+        .route("/status/host", get(handle_host_status))
+        .route("/snapshot", get(handle_snapshot))
         .route("/stop", post(handle_stop))
-        .route("/config", get(handle_config))
+        .route("/config", get(handle_config).put(handle_config_update))
         .route("/policy/evaluate", post(handle_policy_eval))
         .route("/plugins", get(handle_plugins))
         .route("/skills", get(handle_skills))
+        .route("/skills/execute", post(handle_skill_execute))
+        .route("/skills/execute/stream", post(handle_skill_execute_stream))
         .route("/isolation", get(handle_isolation))
+        .route("/isolation/sandboxes", get(handle_sandboxes))
+        .route("/logs/stream", get(handle_logs_stream))
+        .route("/events", get(handle_events))
+        .route("/messages/stream", get(handle_messages_stream))
+        .route("/logging/level", put(handle_log_level))
+        .route("/elevations", get(handle_elevations))
+        .route("/elevations/{id}/approve", post(handle_elevation_approve))
+        .route("/elevations/{id}/deny", post(handle_elevation_deny))
+        .route("/conversations", get(handle_conversations))
+        .route("/conversations/{id}", get(handle_conversation))
+        .route(
+            "/memory/{id}",
+            get(handle_memory).delete(handle_memory_clear),
+        )
+        .route("/quotas", get(handle_quotas))
+        .route("/usage", get(handle_usage))
+        .route("/supervisor", get(handle_supervisor))
+        .route("/schedules", get(handle_schedules))
+        .route("/schedules/{name}/run", post(handle_schedule_run))
+        .route("/chat", post(handle_chat_send))
+        .route("/chat/tools", get(handle_chat_tools))
+        .route("/chat/{session}", delete(handle_chat_reset))
+        .route("/chat/{session}/cancel", post(handle_chat_cancel))
+        .route("/metrics", get(handle_metrics))
+        .route("/files/{conversation}", get(handle_files_list))
+        .route(
+            "/files/{conversation}/{name}",
+            get(handle_files_get)
+                .put(handle_files_put)
+                .layer(DefaultBodyLimit::max(max_upload)),
+        )
+        .with_state(state)
+}
+
+/// Routes for inbound channel messages. They are not behind the IPC
+/// authentication layers: each message carries its own signature.
+fn channel_router(state: Arc<IpcState>) -> axum::Router {
+    axum::Router::new()
+        .route("/channels/webhook", post(handle_webhook))
         .with_state(state)
 }
 
 /// Start the IPC server on the given Unix socket path.
 ///
 /// Removes any stale socket file before binding. Runs until the
-/// shutdown signal is received.
+/// shutdown signal is received. Requests are authorized as described for
+/// [`serve_on`].
 pub async fn serve(
     socket_path: &Path,
     state: Arc<IpcState>,
-    mut shutdown_rx: broadcast::Receiver<ShutdownSignal>,
+    shutdown_rx: broadcast::Receiver<ShutdownSignal>,
 ) -> Result<(), std::io::Error> {
     // Remove stale socket file if it exists
     if socket_path.exists() {
@@ -71,21 +198,435 @@ pub async fn serve(
     let listener = UnixListener::bind(socket_path)?;
     info!(path = %socket_path.display(), "IPC server listening");
 
-    let app = router(state);
-
-    // Serve with graceful shutdown
-    axum::serve(listener, app)
-        .with_graceful_shutdown(async move {
-            let _ = shutdown_rx.recv().await;
-            info!("IPC server shutting down");
-        })
-        .await?;
+    serve_on(listener, state, shutdown_rx).await?;
 
     // Clean up socket file
     std::fs::remove_file(socket_path).ok();
     Ok(())
 }
 
+/// Serve the IPC API on an already-bound listener, e.g. one passed by
+/// systemd socket activation.
+///
+/// Every request except the health checks is authorized against the
+/// policy for the caller's roles (`403` if denied). By default the caller
+/// is the peer process: its UID (from `SO_PEERCRED`) is mapped to a
+/// username and through `auth.role_map`, and the daemon's own user is also
+/// `admin`. When the state has a [`TokenKey`], the caller is instead the
+/// session token sent as `Authorization: Bearer <token>` (`401` if missing
+/// or invalid). The action and resource are as for [`serve_tls`]; when no
+/// `[[policy.rules]]` entry matches, reads are allowed and writes need the
+/// `admin` role.
+///
+/// The socket file is left in place; whoever bound it owns it. Runs until
+/// the shutdown signal is received.
+pub async fn serve_on(
+    listener: UnixListener,
+    state: Arc<IpcState>,
+    mut shutdown_rx: broadcast::Receiver<ShutdownSignal>,
+) -> Result<(), std::io::Error> {
+    let app = local_router(state);
+
+    // Serve with graceful shutdown
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<LocalPeer>(),
+    )
+    .with_graceful_shutdown(async move {
+        let _ = shutdown_rx.recv().await;
+        info!("IPC server shutting down");
+    })
+    .await
+}
+
+/// Serve the IPC API to remote clients on a [`TlsListener`].
+///
+/// Every request is authorized against the policy for the role the client
+/// certificate mapped to: the action is `read` for `GET`/`HEAD` and `write`
+/// otherwise, and the resource is the first path segment (`status`,
+/// `skills`, `stop`, …). Denied requests get `403`. Runs until the shutdown
+/// signal is received.
+pub async fn serve_tls(
+    listener: TlsListener,
+    state: Arc<IpcState>,
+    mut shutdown_rx: broadcast::Receiver<ShutdownSignal>,
+) -> Result<(), std::io::Error> {
+    let app = router(state.clone())
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            authorize_remote,
+        ))
+        .layer(middleware::from_fn(check_api_version))
+        .merge(channel_router(state));
+
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<RemotePeer>(),
+    )
+    .with_graceful_shutdown(async move {
+        let _ = shutdown_rx.recv().await;
+        info!("IPC TLS server shutting down");
+    })
+    .await
+}
+
+/// Credentials of the process at the other end of a Unix socket
+/// connection, read with `SO_PEERCRED`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalPeer {
+    pub uid: u32,
+    pub gid: u32,
+    pub pid: Option<i32>,
+}
+
+impl LocalPeer {
+    /// A peer whose credentials could not be read; it is refused. UID
+    /// `u32::MAX` is `(uid_t)-1`, which no real user has.
+    pub const UNKNOWN: Self = Self {
+        uid: u32::MAX,
+        gid: u32::MAX,
+        pid: None,
+    };
+
+    /// Whether the peer's credentials were read.
+    pub fn is_known(&self) -> bool {
+        self.uid != Self::UNKNOWN.uid
+    }
+}
+
+impl Connected<IncomingStream<'_, UnixListener>> for LocalPeer {
+    fn connect_info(stream: IncomingStream<'_, UnixListener>) -> Self {
+        match stream.io().peer_cred() {
+            Ok(cred) => Self {
+                uid: cred.uid(),
+                gid: cred.gid(),
+                pid: cred.pid(),
+            },
+            Err(e) => {
+                warn!(error = %e, "Failed to read IPC peer credentials");
+                Self::UNKNOWN
+            }
+        }
+    }
+}
+
+/// The authenticated caller of a request, added to its extensions by the
+/// authentication layers. Requests that skip them (health checks, channel
+/// routes) have none.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Caller {
+    /// Username, token identity, or client address.
+    pub identity: String,
+    /// Policy roles the request was authorized with.
+    pub roles: Vec<String>,
+    /// Whether the request came over TLS rather than the Unix socket.
+    pub remote: bool,
+}
+
+/// The router for the Unix socket: [`router`] behind session token
+/// authentication when the state has a token key, and peer credential
+/// authentication otherwise.
+fn local_router(state: Arc<IpcState>) -> axum::Router {
+    let app = router(state.clone());
+    let app = if state.token_key.is_some() {
+        app.layer(middleware::from_fn_with_state(
+            state.clone(),
+            authenticate_token,
+        ))
+    } else {
+        app.layer(middleware::from_fn_with_state(
+            state.clone(),
+            authenticate_peer,
+        ))
+    };
+    let app = match state.token_key {
+        Some(_) => app.merge(login_router(state.clone())),
+        None => app,
+    };
+    app.layer(middleware::from_fn(check_api_version))
+        .merge(channel_router(state))
+}
+
+/// `POST /auth/login`, served on the Unix socket with `auth.mode = "token"`
+/// and authenticated by the peer's credentials rather than a token.
+fn login_router(state: Arc<IpcState>) -> axum::Router {
+    axum::Router::new()
+        .route("/auth/login", post(handle_login))
+        .with_state(state)
+}
+
+/// The policy `(action, resource)` for an IPC request: `read` for
+/// `GET`/`HEAD` and `write` otherwise, on the first path segment.
+fn request_action(request: &Request) -> (&'static str, String) {
+    let action = if matches!(*request.method(), Method::GET | Method::HEAD) {
+        "read"
+    } else {
+        "write"
+    };
+    let resource = request
+        .uri()
+        .path()
+        .trim_start_matches('/')
+        .split('/')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    (action, resource)
+}
+
+/// Refuse requests from clients of another major API version, before they
+/// are authenticated, and put the daemon's version on every response.
+///
+/// Requests without the header (curl, older clients) are served. Health
+/// checks are always served, so a mismatched client can still read the
+/// daemon's version from `/health`.
+async fn check_api_version(request: Request, next: Next) -> Response {
+    let client = request
+        .headers()
+        .get(API_VERSION_HEADER)
+        .map(|value| value.to_str().ok().and_then(ApiVersion::parse));
+    let daemon = ApiVersion::current();
+    let mut response = match client {
+        _ if is_health_check(&request) => next.run(request).await,
+        Some(None) => {
+            let body = Json(ErrorResponse {
+                error: format!("invalid {API_VERSION_HEADER} header; expected major.minor"),
+            });
+            (StatusCode::BAD_REQUEST, body).into_response()
+        }
+        Some(Some(client)) if !client.is_compatible(daemon) => {
+            warn!(%client, %daemon, "IPC request from an incompatible client refused");
+            let body = Json(ErrorResponse {
+                error: version_mismatch(client, daemon),
+            });
+            (StatusCode::BAD_REQUEST, body).into_response()
+        }
+        _ => next.run(request).await,
+    };
+    response
+        .headers_mut()
+        .insert(API_VERSION_HEADER, HeaderValue::from_static(API_VERSION));
+    response
+}
+
+/// Health checks stay open for supervisors and load balancers.
+fn is_health_check(request: &Request) -> bool {
+    matches!(
+        request.uri().path(),
+        "/health" | "/health/live" | "/health/ready"
+    )
+}
+
+async fn authorize_remote(
+    State(state): State<Arc<IpcState>>,
+    ConnectInfo(peer): ConnectInfo<RemotePeer>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let (action, resource) = request_action(&request);
+    let ctx = caller_context(&peer.addr.to_string(), true, action, &resource);
+    let verdict = state
+        .config
+        .borrow()
+        .build_policy_engine()
+        .evaluate_with_context(&peer.role, action, &resource, &ctx);
+    if !verdict.is_allowed() {
+        warn!(
+            addr = %peer.addr,
+            role = %peer.role,
+            action,
+            %resource,
+            rule = verdict.rule_id.as_deref(),
+            reason = verdict.reason.as_deref(),
+            "Remote IPC request denied by policy"
+        );
+        state.metrics.record_denial(Denial::Role);
+        state.events.publish(DaemonEvent::PolicyDenied {
+            identity: peer.addr.to_string(),
+            action: action.to_string(),
+            resource: resource.clone(),
+            reason: verdict.to_string(),
+        });
+        let body = Json(ErrorResponse {
+            error: match (&verdict.rule_id, &verdict.reason) {
+                (None, None) => format!("role {:?} may not {action} {resource:?}", peer.role),
+                _ => format!(
+                    "role {:?} may not {action} {resource:?}: {verdict}",
+                    peer.role
+                ),
+            },
+        });
+        return (StatusCode::FORBIDDEN, body).into_response();
+    }
+    request.extensions_mut().insert(Caller {
+        identity: peer.addr.to_string(),
+        roles: vec![peer.role],
+        remote: true,
+    });
+    next.run(request).await
+}
+
+/// Authorize a local request by `identity` holding `roles`, returning the
+/// `403` response explaining why if it is denied.
+///
+/// A matching `[[policy.rules]]` entry decides: the request is allowed if
+/// any role is allowed, and denied if a rule denies and none allows. With
+/// no matching rule, reads are open to every local user and writes need
+/// the `admin` role.
+fn local_denial(
+    state: &IpcState,
+    identity: &str,
+    roles: &[String],
+    request: &Request,
+) -> Option<Response> {
+    let (action, resource) = request_action(request);
+    let (reason, rule) = local_verdict(state, identity, roles, action, &resource)?;
+    warn!(identity, ?roles, action, %resource, rule = rule.as_deref(), %reason, "IPC request denied by policy");
+    state.metrics.record_denial(Denial::Role);
+    state.events.publish(DaemonEvent::PolicyDenied {
+        identity: identity.to_string(),
+        action: action.to_string(),
+        resource: resource.clone(),
+        reason: reason.clone(),
+    });
+    let body = Json(ErrorResponse {
+        error: format!("{identity} (roles {roles:?}) may not {action} {resource:?}: {reason}"),
+    });
+    Some((StatusCode::FORBIDDEN, body).into_response())
+}
+
+/// Why local `roles` may not `action` `resource`, with the denying rule's
+/// ID, or `None` if they may; see [`local_denial`].
+fn local_verdict(
+    state: &IpcState,
+    identity: &str,
+    roles: &[String],
+    action: &str,
+    resource: &str,
+) -> Option<(String, Option<String>)> {
+    let ctx = caller_context(identity, false, action, resource);
+    let verdicts: Vec<PolicyVerdict> = {
+        let mut engine = state.config.borrow().build_policy_engine();
+        roles
+            .iter()
+            .map(|role| engine.evaluate_with_context(role, action, resource, &ctx))
+            .collect()
+    };
+    let denial = verdicts
+        .iter()
+        .find(|verdict| verdict.decision == PolicyDecision::Denied);
+    let reason = if verdicts.iter().any(PolicyVerdict::is_allowed) {
+        return None;
+    } else if let Some(verdict) = denial {
+        match (&verdict.rule_id, &verdict.reason) {
+            (None, None) => "denied by a [[policy.rules]] entry".to_string(),
+            _ => verdict.to_string(),
+        }
+    } else if action == "read" || roles.iter().any(|role| role == "admin") {
+        return None;
+    } else {
+        "no [[policy.rules]] entry matches, and only admin may write by default".to_string()
+    };
+    Some((reason, denial.and_then(|verdict| verdict.rule_id.clone())))
+}
+
+/// The context `when` clauses see for a request by `identity`: the current
+/// time, the request, `caller.identity`, and `caller.remote`.
+fn caller_context(identity: &str, remote: bool, action: &str, resource: &str) -> RequestContext {
+    RequestContext::for_request(action, resource)
+        .with("caller.identity", identity)
+        .with("caller.remote", remote)
+}
+
+/// The UID the daemon runs as.
+fn daemon_uid() -> u32 {
+    static UID: OnceLock<u32> = OnceLock::new();
+    *UID.get_or_init(|| LocalIdentity::detect().uid)
+}
+
+async fn authenticate_peer(
+    State(state): State<Arc<IpcState>>,
+    ConnectInfo(peer): ConnectInfo<LocalPeer>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if is_health_check(&request) {
+        return next.run(request).await;
+    }
+    if !peer.is_known() {
+        state.metrics.record_denial(Denial::Role);
+        let body = Json(ErrorResponse {
+            error: "could not read the credentials of the connecting process".to_string(),
+        });
+        return (StatusCode::FORBIDDEN, body).into_response();
+    }
+
+    let (identity, roles) = peer_roles(&state, peer);
+    if let Some(denied) = local_denial(&state, &identity.username, &roles, &request) {
+        return denied;
+    }
+    request.extensions_mut().insert(Caller {
+        identity: identity.username,
+        roles,
+        remote: false,
+    });
+    next.run(request).await
+}
+
+/// The identity of a local peer and its roles, from `auth.role_map`.
+fn peer_roles(state: &IpcState, peer: LocalPeer) -> (LocalIdentity, Vec<String>) {
+    let identity = LocalIdentity::from_uid(peer.uid, peer.gid);
+    let mut roles = identity.roles(&state.config.borrow().auth.role_map);
+    // The daemon's own user can signal the process anyway.
+    if peer.uid == daemon_uid() && !roles.iter().any(|role| role == "admin") {
+        roles.push("admin".to_string());
+    }
+    (identity, roles)
+}
+
+async fn authenticate_token(
+    State(state): State<Arc<IpcState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if is_health_check(&request) {
+        return next.run(request).await;
+    }
+    let Some(key) = state.token_key.as_deref() else {
+        return next.run(request).await;
+    };
+
+    let token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let session = match token {
+        Some(token) => Session::new()
+            .authenticate_token(token, key)
+            .map_err(|e| e.to_string()),
+        None => Err("session token required; run `crustyclaw login`".to_string()),
+    };
+    let session = match session {
+        Ok(session) => session.authorize_from_token(),
+        Err(error) => {
+            warn!(%error, "IPC request rejected");
+            state.metrics.record_denial(Denial::Token);
+            return (StatusCode::UNAUTHORIZED, Json(ErrorResponse { error })).into_response();
+        }
+    };
+
+    if let Some(denied) = local_denial(&state, session.identity(), session.roles(), &request) {
+        return denied;
+    }
+    request.extensions_mut().insert(Caller {
+        identity: session.identity().to_string(),
+        roles: session.roles().to_vec(),
+        remote: false,
+    });
+    next.run(request).await
+}
+
 /// Resolve the socket path from config or use the default.
 pub fn socket_path_from_config(config: &AppConfig) -> PathBuf {
     config
@@ -105,9 +646,109 @@ async fn handle_health(State(state): State<Arc<IpcState>>) -> Json<HealthRespons
         version: crate::build_info::VERSION.to_string(),
         git_hash: crate::build_info::GIT_HASH.to_string(),
         build_profile: crate::build_info::BUILD_PROFILE.to_string(),
+        api_version: API_VERSION.to_string(),
+    })
+}
+
+/// Mint a session token for the connecting user, with the roles
+/// `auth.role_map` gives their UID. The token key never leaves the daemon.
+async fn handle_login(
+    State(state): State<Arc<IpcState>>,
+    ConnectInfo(peer): ConnectInfo<LocalPeer>,
+    body: Bytes,
+) -> Response {
+    let error = |status: StatusCode, error: String| (status, Json(ErrorResponse { error }));
+    let Some(key) = state.token_key.as_deref() else {
+        return error(
+            StatusCode::NOT_FOUND,
+            "auth.mode is not \"token\"".to_string(),
+        )
+        .into_response();
+    };
+    if !peer.is_known() {
+        state.metrics.record_denial(Denial::Role);
+        return error(
+            StatusCode::FORBIDDEN,
+            "could not read the credentials of the connecting process".to_string(),
+        )
+        .into_response();
+    }
+    let request: LoginRequest = if body.is_empty() {
+        LoginRequest::default()
+    } else {
+        match serde_json::from_slice(&body) {
+            Ok(request) => request,
+            Err(e) => {
+                return error(StatusCode::BAD_REQUEST, format!("invalid request: {e}"))
+                    .into_response();
+            }
+        }
+    };
+    let max_ttl = state.config.borrow().auth.token_ttl_secs;
+    let ttl = request.ttl_secs.unwrap_or(max_ttl);
+    if ttl == 0 || ttl > max_ttl {
+        return error(
+            StatusCode::BAD_REQUEST,
+            format!("ttl_secs must be between 1 and auth.token_ttl_secs ({max_ttl})"),
+        )
+        .into_response();
+    }
+
+    let (identity, roles) = peer_roles(&state, peer);
+    let issued_at = token::now();
+    let claims = TokenClaims {
+        identity: identity.username,
+        roles,
+        issued_at,
+        expires_at: issued_at.saturating_add(ttl),
+    };
+    info!(identity = %claims.identity, roles = ?claims.roles, ttl, "Session token issued");
+    Json(LoginResponse {
+        token: key.sign(&claims),
+        identity: claims.identity,
+        roles: claims.roles,
+        expires_at: claims.expires_at,
+    })
+    .into_response()
+}
+
+async fn handle_version() -> Json<VersionResponse> {
+    Json(VersionResponse::current())
+}
+
+async fn handle_live(State(state): State<Arc<IpcState>>) -> Json<LivenessResponse> {
+    Json(LivenessResponse {
+        status: "alive".to_string(),
+        uptime_secs: state.started_at.elapsed().as_secs(),
     })
 }
 
+/// Check every component; 503 if any is down.
+async fn handle_ready(State(state): State<Arc<IpcState>>) -> (StatusCode, Json<ReadinessResponse>) {
+    let config = state.config.borrow().clone();
+    let components = health::readiness(&config, &state.health).await;
+    let ready = health::is_ready(&components);
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        Json(ReadinessResponse {
+            ready,
+            components: components
+                .into_iter()
+                .map(|c| ComponentInfo {
+                    name: c.name,
+                    status: c.status.name().to_string(),
+                    detail: c.detail,
+                })
+                .collect(),
+        }),
+    )
+}
+
 async fn handle_status(State(state): State<Arc<IpcState>>) -> Json<StatusResponse> {
     let config = state.config.borrow().clone();
     let uptime = state.started_at.elapsed().as_secs();
@@ -120,14 +761,212 @@ async fn handle_status(State(state): State<Arc<IpcState>>) -> Json<StatusRespons
         listen_addr: config.daemon.listen_addr.clone(),
         listen_port: config.daemon.listen_port,
         signal_enabled: config.signal.enabled,
-        log_level: config.logging.level.clone(),
+        log_level: state
+            .log_control
+            .as_ref()
+            .map_or_else(|| config.logging.level.clone(), LogControl::level),
         isolation_backend: config.isolation.backend.clone(),
         skills_count: state.skills.list().len(),
         plugins_count: state.plugins.plugin_names().len(),
         pid: std::process::id(),
+        warnings: state
+            .warnings
+            .list()
+            .into_iter()
+            .map(|w| WarningInfo {
+                kind: w.kind.to_string(),
+                key: w.key,
+                message: w.message,
+            })
+            .collect(),
     })
 }
 
+/// The policy resource a `/snapshot` section is read from: the first path
+/// segment of its own endpoint.
+fn snapshot_resource(section: &str) -> &str {
+    match section {
+        "host" => "status",
+        "history" => "conversations",
+        section => section,
+    }
+}
+
+/// Why `caller` may not read the `/snapshot` section `section`, or `None`
+/// if it may. Each section is authorized as a read of its own endpoint.
+fn snapshot_denial(state: &IpcState, caller: &Caller, section: &str) -> Option<String> {
+    let resource = snapshot_resource(section);
+    if caller.remote {
+        let ctx = caller_context(&caller.identity, true, "read", resource);
+        let mut engine = state.config.borrow().build_policy_engine();
+        let allowed = caller.roles.iter().any(|role| {
+            engine
+                .evaluate_with_context(role, "read", resource, &ctx)
+                .is_allowed()
+        });
+        (!allowed).then(|| format!("roles {:?} may not read {resource:?}", caller.roles))
+    } else {
+        local_verdict(state, &caller.identity, &caller.roles, "read", resource)
+            .map(|(reason, _)| reason)
+    }
+}
+
+/// Several endpoints' responses in one round trip, for clients that refresh
+/// them all on a timer.
+///
+/// Sections the caller may not read, or whose endpoint fails, are listed in
+/// `omitted` rather than failing the whole snapshot.
+async fn handle_snapshot(
+    State(state): State<Arc<IpcState>>,
+    caller: Option<Extension<Caller>>,
+    Query(query): Query<SnapshotQuery>,
+) -> Result<Json<SnapshotResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let sections: Vec<&str> = match &query.include {
+        None => SNAPSHOT_SECTIONS.to_vec(),
+        Some(include) => include
+            .split(',')
+            .map(str::trim)
+            .filter(|section| !section.is_empty())
+            .map(|section| {
+                SNAPSHOT_SECTIONS
+                    .iter()
+                    .find(|known| **known == section)
+                    .copied()
+                    .ok_or_else(|| {
+                        let error = format!(
+                            "unknown snapshot section {section:?}; expected one of {SNAPSHOT_SECTIONS:?}"
+                        );
+                        (StatusCode::BAD_REQUEST, Json(ErrorResponse { error }))
+                    })
+            })
+            .collect::<Result<_, _>>()?,
+    };
+
+    let endpoint_error = |(_, Json(e)): (StatusCode, Json<ErrorResponse>)| e.error;
+    let mut snapshot = SnapshotResponse::default();
+    for section in sections {
+        let denial = caller
+            .as_ref()
+            .and_then(|Extension(caller)| snapshot_denial(&state, caller, section));
+        if let Some(reason) = denial {
+            snapshot.omitted.push(SnapshotOmission {
+                section: section.to_string(),
+                reason,
+            });
+            continue;
+        }
+        let result = match section {
+            "status" => {
+                snapshot.status = Some(handle_status(State(state.clone())).await.0);
+                Ok(())
+            }
+            "isolation" => {
+                snapshot.isolation = Some(handle_isolation(State(state.clone())).await.0);
+                Ok(())
+            }
+            "host" => handle_host_status(State(state.clone()))
+                .await
+                .map(|Json(host)| snapshot.host = Some(host))
+                .map_err(endpoint_error),
+            "skills" => {
+                snapshot.skills = Some(handle_skills(State(state.clone())).await.0);
+                Ok(())
+            }
+            "plugins" => {
+                snapshot.plugins = Some(handle_plugins(State(state.clone())).await.0);
+                Ok(())
+            }
+            "conversations" => handle_conversations(State(state.clone()))
+                .await
+                .map(|Json(list)| snapshot.conversations = Some(list))
+                .map_err(endpoint_error),
+            "history" => {
+                let id = match (&query.conversation, &snapshot.conversations) {
+                    (Some(id), _) => Ok(Some(id.clone())),
+                    (None, Some(list)) => Ok(list.conversations.first().map(|c| c.id.clone())),
+                    (None, None) => handle_conversations(State(state.clone()))
+                        .await
+                        .map(|Json(list)| list.conversations.into_iter().next().map(|c| c.id))
+                        .map_err(endpoint_error),
+                };
+                match id {
+                    Ok(Some(id)) => handle_conversation(State(state.clone()), UrlPath(id))
+                        .await
+                        .map(|Json(history)| snapshot.history = Some(history))
+                        .map_err(endpoint_error),
+                    Ok(None) => Ok(()),
+                    Err(e) => Err(e),
+                }
+            }
+            // "logs"
+            _ => {
+                let (entries, _) = state.logs.tail(query.logs_after);
+                snapshot.logs = Some(LogsResponse {
+                    entries: entries.into_iter().map(log_info).collect(),
+                    total: state.logs.len(),
+                });
+                Ok(())
+            }
+        };
+        if let Err(reason) = result {
+            snapshot.omitted.push(SnapshotOmission {
+                section: section.to_string(),
+                reason,
+            });
+        }
+    }
+    Ok(Json(snapshot))
+}
+
+async fn handle_host_status(
+    State(state): State<Arc<IpcState>>,
+) -> Result<Json<HostStatusResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let paths = {
+        let config = state.config.borrow();
+        vec![
+            (
+                "signal.data_dir".to_string(),
+                PathBuf::from(&config.signal.data_dir),
+            ),
+            (
+                "secrets.staging_dir".to_string(),
+                PathBuf::from(&config.secrets.staging_dir),
+            ),
+        ]
+    };
+
+    // Sampling reads /proc and may spawn `df`, so keep it off the async workers.
+    let sampler = state.host.clone();
+    let metrics = tokio::task::spawn_blocking(move || sampler.sample(&paths))
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: format!("host sampling failed: {e}"),
+                }),
+            )
+        })?;
+
+    Ok(Json(HostStatusResponse {
+        load_avg: metrics.load_avg,
+        cpu_count: metrics.cpu_count,
+        memory_total_bytes: metrics.memory_total_bytes,
+        memory_available_bytes: metrics.memory_available_bytes,
+        disks: metrics
+            .disks
+            .into_iter()
+            .map(|d| DiskInfo {
+                label: d.label,
+                path: d.path.display().to_string(),
+                total_bytes: d.total_bytes,
+                free_bytes: d.free_bytes,
+            })
+            .collect(),
+        open_fds: metrics.open_fds,
+    }))
+}
+
 async fn handle_stop(State(state): State<Arc<IpcState>>) -> (StatusCode, Json<StopResponse>) {
     info!("Stop requested via IPC");
     let _ = state.shutdown_tx.send(ShutdownSignal);
@@ -143,7 +982,8 @@ async fn handle_stop(State(state): State<Arc<IpcState>>) -> (StatusCode, Json<St
 async fn handle_config(
     State(state): State<Arc<IpcState>>,
 ) -> Result<Json<ConfigResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let config = state.config.borrow().clone();
+    // Any local peer may read the config, so secrets stay out of it.
+    let config = state.config.borrow().redacted();
     match toml::to_string_pretty(&config) {
         Ok(toml_str) => Ok(Json(ConfigResponse { toml: toml_str })),
         Err(e) => Err((
@@ -155,22 +995,67 @@ async fn handle_config(
     }
 }
 
+/// Set config keys as `--set` flags would and hot-reload, returning the
+/// new config. The keys stay set across later reloads until the daemon
+/// restarts.
+async fn handle_config_update(
+    State(state): State<Arc<IpcState>>,
+    Json(req): Json<ConfigUpdateRequest>,
+) -> Result<Json<ConfigResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let error = |status, e: String| (status, Json(ErrorResponse { error: e }));
+    let Some(changes) = &state.config_changes else {
+        return Err(error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "this daemon's config cannot be changed at runtime".to_string(),
+        ));
+    };
+    if req.set.is_empty() {
+        return Err(error(StatusCode::BAD_REQUEST, "no keys to set".to_string()));
+    }
+    let overrides = req
+        .set
+        .iter()
+        .map(|flag| Override::parse_flag(flag))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| error(StatusCode::BAD_REQUEST, e.to_string()))?;
+    let keys: Vec<String> = overrides.iter().map(Override::key).collect();
+    let (reply, outcome) = oneshot::channel();
+    changes
+        .send(ConfigChange { overrides, reply })
+        .await
+        .map_err(|_| {
+            error(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "the daemon is shutting down".to_string(),
+            )
+        })?;
+    match outcome.await {
+        Ok(Ok(())) => {
+            info!(?keys, "Config changed over IPC");
+            handle_config(State(state)).await
+        }
+        Ok(Err(e)) => Err(error(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("config change rejected, keeping current config: {e}"),
+        )),
+        Err(_) => Err(error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "the daemon is shutting down".to_string(),
+        )),
+    }
+}
+
 async fn handle_policy_eval(
     State(state): State<Arc<IpcState>>,
     Json(req): Json<PolicyEvalRequest>,
 ) -> Json<PolicyEvalResponse> {
     let config = state.config.borrow().clone();
     let mut engine = config.build_policy_engine();
-    let decision = engine.evaluate(&req.role, &req.action, &req.resource);
-    let decision_str = match decision {
-        crustyclaw_config::policy::PolicyDecision::Allowed => "allowed",
-        crustyclaw_config::policy::PolicyDecision::Denied => "denied",
-        crustyclaw_config::policy::PolicyDecision::NoMatch => "no_match",
-    };
-    Json(PolicyEvalResponse {
-        decision: decision_str.to_string(),
-        rule_count: engine.rule_count(),
-    })
+    let verdict = engine.evaluate(&req.role, &req.action, &req.resource);
+    Json(PolicyEvalResponse::from_verdict(
+        verdict,
+        engine.rule_count(),
+    ))
 }
 
 async fn handle_plugins(State(state): State<Arc<IpcState>>) -> Json<PluginsResponse> {
@@ -197,10 +1082,167 @@ async fn handle_skills(State(state): State<Arc<IpcState>>) -> Json<SkillsRespons
                 name: s.name().to_string(),
                 description: s.description().to_string(),
                 isolated: s.isolated(),
+                concurrency_class: s.concurrency_class().map(|c| c.name.clone()),
             })
         })
         .collect();
-    Json(SkillsResponse { skills })
+    let locks = state
+        .skills
+        .locks()
+        .status()
+        .into_iter()
+        .map(|lock| SkillLockInfo {
+            class: lock.class,
+            held_secs: lock.holder.as_ref().map(|(_, held)| held.as_secs()),
+            holder: lock.holder.map(|(skill, _)| skill),
+            queued: lock.queued,
+        })
+        .collect();
+    Json(SkillsResponse { skills, locks })
+}
+
+/// Check that the requested skill exists and build its invocation.
+///
+/// A trust tier may only raise the isolation a skill runs under: tiers
+/// weaker than the skill's own (or `isolation.default_trust_tier`, or
+/// `untrusted`, for skills without one) are refused.
+fn skill_invocation(
+    state: &IpcState,
+    req: &SkillExecuteRequest,
+) -> Result<SkillInvocation, (StatusCode, Json<ErrorResponse>)> {
+    let error = |status: StatusCode, error: String| (status, Json(ErrorResponse { error }));
+
+    let Some(skill) = state.skills.get(&req.name) else {
+        return Err(error(
+            StatusCode::NOT_FOUND,
+            SkillError::NotFound(req.name.clone()).to_string(),
+        ));
+    };
+
+    let trust_tier = match req.trust_tier.as_deref() {
+        None => None,
+        Some(tier) => Some(TrustTier::from_str_loose(tier).ok_or_else(|| {
+            error(
+                StatusCode::BAD_REQUEST,
+                format!("unknown trust tier {tier:?}"),
+            )
+        })?),
+    };
+    if let Some(tier) = trust_tier {
+        let floor = skill.trust_tier().unwrap_or_else(|| {
+            let config = state.config.borrow();
+            config
+                .isolation
+                .default_trust_tier
+                .as_deref()
+                .and_then(TrustTier::from_str_loose)
+                .unwrap_or(TrustTier::Untrusted)
+        });
+        if tier < floor {
+            return Err(error(
+                StatusCode::FORBIDDEN,
+                format!(
+                    "skill '{}' runs at trust tier {floor}; it cannot run as {tier}",
+                    req.name
+                ),
+            ));
+        }
+    }
+
+    info!(skill = %req.name, trust_tier = ?trust_tier, "Skill execution requested via IPC");
+    Ok(SkillInvocation {
+        args: req.args.clone(),
+        trust_tier,
+        origin: None,
+    })
+}
+
+async fn handle_skill_execute(
+    State(state): State<Arc<IpcState>>,
+    Json(req): Json<SkillExecuteRequest>,
+) -> Result<Json<SkillExecuteResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let error = |status: StatusCode, error: String| (status, Json(ErrorResponse { error }));
+
+    let invocation = skill_invocation(&state, &req)?;
+    let result = state
+        .skills
+        .invoke(&req.name, &invocation)
+        .await
+        .map_err(|e| match e {
+            SkillError::Busy { .. } => error(StatusCode::CONFLICT, e.to_string()),
+            _ => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+        })?;
+
+    Ok(Json(SkillExecuteResponse {
+        name: req.name,
+        exit_code: result.exit_code,
+        stdout: result.stdout,
+        stderr: result.stderr,
+        elapsed_ms: result.elapsed.as_millis() as u64,
+        peak_memory_bytes: result.peak_memory_bytes,
+        cpu_time_ms: result.cpu_time.map(|t| t.as_millis() as u64),
+    }))
+}
+
+/// Run a skill, streaming its output lines as server-sent events.
+///
+/// An unknown skill or trust tier is rejected before the stream starts;
+/// failures after that end the stream with an `error` event. Closing the
+/// stream cancels the run, killing its sandbox.
+async fn handle_skill_execute_stream(
+    State(state): State<Arc<IpcState>>,
+    Json(req): Json<SkillExecuteRequest>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let invocation = skill_invocation(&state, &req)?;
+
+    let (output_tx, mut output) = mpsc::unbounded_channel();
+    let (mut tx, body) = http_body_util::channel::Channel::<Bytes>::new(64);
+    tokio::spawn(async move {
+        let output_event = |line: OutputLine| SkillRunEvent::Output {
+            stream: line.stream.name().to_string(),
+            line: line.line,
+        };
+        let run = state
+            .skills
+            .invoke_streaming(&req.name, &invocation, output_tx);
+        tokio::pin!(run);
+        let result = loop {
+            tokio::select! {
+                result = &mut run => break result,
+                Some(line) = output.recv() => {
+                    if tx.send_data(sse_event(&output_event(line))).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        };
+        while let Ok(line) = output.try_recv() {
+            if tx.send_data(sse_event(&output_event(line))).await.is_err() {
+                return;
+            }
+        }
+        let last = match result {
+            Ok(result) => SkillRunEvent::Done {
+                exit_code: result.exit_code,
+                elapsed_ms: result.elapsed.as_millis() as u64,
+                peak_memory_bytes: result.peak_memory_bytes,
+                cpu_time_ms: result.cpu_time.map(|t| t.as_millis() as u64),
+            },
+            Err(e) => {
+                warn!(skill = %req.name, error = %e, "Streamed skill execution failed");
+                SkillRunEvent::Error {
+                    error: e.to_string(),
+                }
+            }
+        };
+        let _ = tx.send_data(sse_event(&last)).await;
+    });
+
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, "text/event-stream")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(Body::new(body))
+        .unwrap_or_default())
 }
 
 async fn handle_isolation(State(state): State<Arc<IpcState>>) -> Json<IsolationStatusResponse> {
@@ -209,6 +1251,7 @@ async fn handle_isolation(State(state): State<Arc<IpcState>>) -> Json<IsolationS
 
     let pref = match iso.backend.as_str() {
         "docker" => crate::isolation::BackendPreference::Docker,
+        "podman" => crate::isolation::BackendPreference::Podman,
         "firecracker" => crate::isolation::BackendPreference::Firecracker,
         "apple-vz" => crate::isolation::BackendPreference::AppleVz,
         "linux-ns" => crate::isolation::BackendPreference::LinuxNamespace,
@@ -224,79 +1267,1873 @@ async fn handle_isolation(State(state): State<Arc<IpcState>>) -> Json<IsolationS
         cpu_fraction: iso.default_cpu_fraction,
         timeout_secs: iso.default_timeout_secs,
         network_policy: iso.default_network.clone(),
-        max_concurrent: iso.max_concurrent,
+        max_concurrent: state.sandboxes.max_concurrent(),
+        running: state.sandboxes.running(),
+        queued: state.sandboxes.queued(),
     })
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use axum::body::Body;
-    use axum::http::Request;
-    use tower::ServiceExt;
-
-    fn test_state() -> Arc<IpcState> {
-        let config = AppConfig::default();
-        let (shutdown_tx, _shutdown_rx) = broadcast::channel(1);
-        let (_, config_rx) = watch::channel(config);
-
-        Arc::new(IpcState {
-            config: config_rx,
-            shutdown_tx,
-            skills: Arc::new(SkillRegistry::new()),
-            plugins: Arc::new(PluginRegistry::new()),
-            started_at: Instant::now(),
+async fn handle_sandboxes(State(state): State<Arc<IpcState>>) -> Json<SandboxesResponse> {
+    let sandboxes = state
+        .sandboxes
+        .runs()
+        .into_iter()
+        .map(|run| SandboxRunInfo {
+            id: run.id,
+            label: run.label,
+            backend: run.backend,
+            state: run.state.to_string(),
+            age_secs: run.age.as_secs(),
+            exit_code: run.exit_code,
+            error: run.error,
         })
+        .collect();
+    Json(SandboxesResponse {
+        max_concurrent: state.sandboxes.max_concurrent(),
+        sandboxes,
+    })
+}
+
+/// Format a log entry as a single server-sent event.
+fn log_info(entry: crate::logging::LogEntry) -> LogEntry {
+    LogEntry {
+        seq: entry.seq,
+        elapsed_secs: entry.elapsed_secs,
+        level: entry.level.to_string(),
+        target: entry.target,
+        message: entry.message,
     }
+}
 
-    #[tokio::test]
-    async fn test_health_endpoint() {
-        let app = router(test_state());
-        let req = Request::get("/health").body(Body::empty()).unwrap();
-        let resp = app.oneshot(req).await.unwrap();
-        assert_eq!(resp.status(), StatusCode::OK);
+fn log_event(entry: crate::logging::LogEntry) -> Bytes {
+    let info = log_info(entry);
+    let json = serde_json::to_string(&info).unwrap_or_default();
+    Bytes::from(format!("id: {}\ndata: {json}\n\n", info.seq))
+}
 
-        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
-            .await
-            .unwrap();
-        let health: HealthResponse = serde_json::from_slice(&body).unwrap();
-        assert_eq!(health.status, "ok");
-    }
+type FilesError = (StatusCode, Json<ErrorResponse>);
 
-    #[tokio::test]
-    async fn test_status_endpoint() {
-        let app = router(test_state());
-        let req = Request::get("/status").body(Body::empty()).unwrap();
-        let resp = app.oneshot(req).await.unwrap();
-        assert_eq!(resp.status(), StatusCode::OK);
+fn files_error(e: WorkspaceError) -> FilesError {
+    let status = match &e {
+        WorkspaceError::InvalidName { .. } => StatusCode::BAD_REQUEST,
+        WorkspaceError::TooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+        WorkspaceError::ContentTypeNotAllowed(_) | WorkspaceError::ContentMismatch { .. } => {
+            StatusCode::UNSUPPORTED_MEDIA_TYPE
+        }
+        WorkspaceError::NotFound { .. } => StatusCode::NOT_FOUND,
+        WorkspaceError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (
+        status,
+        Json(ErrorResponse {
+            error: e.to_string(),
+        }),
+    )
+}
 
-        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
-            .await
-            .unwrap();
-        let status: StatusResponse = serde_json::from_slice(&body).unwrap();
-        assert!(status.running);
-        assert_eq!(status.listen_port, 9100);
+/// Run a blocking workspace operation off the async runtime.
+async fn with_workspaces<T: Send + 'static>(
+    state: &IpcState,
+    op: impl FnOnce(&WorkspaceStore) -> Result<T, WorkspaceError> + Send + 'static,
+) -> Result<T, FilesError> {
+    let store = state.workspaces.clone();
+    tokio::task::spawn_blocking(move || op(&store))
+        .await
+        .map_err(|e| files_error(WorkspaceError::Io(std::io::Error::other(e))))?
+        .map_err(files_error)
+}
+
+fn file_info(file: crate::workspace::WorkspaceFile) -> FileInfo {
+    FileInfo {
+        name: file.name,
+        size: file.size,
+        content_type: file.content_type,
     }
+}
 
-    #[tokio::test]
-    async fn test_stop_endpoint() {
-        let state = test_state();
-        let mut rx = state.shutdown_tx.subscribe();
-        let app = router(state);
+async fn handle_elevations(State(state): State<Arc<IpcState>>) -> Json<ElevationsResponse> {
+    Json(ElevationsResponse {
+        requests: state.elevations.list().iter().map(elevation_info).collect(),
+    })
+}
 
-        let req = Request::post("/stop").body(Body::empty()).unwrap();
-        let resp = app.oneshot(req).await.unwrap();
-        assert_eq!(resp.status(), StatusCode::OK);
+async fn handle_elevation_approve(
+    State(state): State<Arc<IpcState>>,
+    UrlPath(id): UrlPath<u64>,
+    Json(req): Json<ElevationDecisionRequest>,
+) -> Result<Json<ElevationInfo>, (StatusCode, Json<ErrorResponse>)> {
+    info!(id, operator = %req.operator, "Elevation approval requested via IPC");
+    state
+        .elevations
+        .approve(id, &req.operator)
+        .map(|r| Json(elevation_info(&r)))
+        .map_err(elevation_error)
+}
 
-        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
-            .await
-            .unwrap();
-        let stop: StopResponse = serde_json::from_slice(&body).unwrap();
-        assert!(stop.acknowledged);
+async fn handle_elevation_deny(
+    State(state): State<Arc<IpcState>>,
+    UrlPath(id): UrlPath<u64>,
+    Json(req): Json<ElevationDecisionRequest>,
+) -> Result<Json<ElevationInfo>, (StatusCode, Json<ErrorResponse>)> {
+    info!(id, operator = %req.operator, "Elevation denial requested via IPC");
+    state
+        .elevations
+        .deny(id, &req.operator, req.reason.as_deref())
+        .map(|r| Json(elevation_info(&r)))
+        .map_err(elevation_error)
+}
 
-        // Verify shutdown signal was sent
-        let signal = rx.try_recv();
-        assert!(signal.is_ok());
+fn elevation_info(request: &ElevationRequest) -> ElevationInfo {
+    let (decided_by, denial_reason) = match &request.status {
+        ElevationStatus::Approved { by } => (Some(by.clone()), None),
+        ElevationStatus::Denied { by, reason } => (Some(by.clone()), reason.clone()),
+        _ => (None, None),
+    };
+    ElevationInfo {
+        id: request.id,
+        tool: request.tool.clone(),
+        preview: request.preview.clone(),
+        current: request.current.to_string(),
+        requested: request.requested.to_string(),
+        reason: request.reason.clone(),
+        conversation: request.conversation.clone(),
+        status: request.status.name().to_string(),
+        decided_by,
+        denial_reason,
+        age_secs: request
+            .requested_at
+            .elapsed()
+            .map(|d| d.as_secs())
+            .unwrap_or_default(),
+    }
+}
+
+fn elevation_error(e: ElevationError) -> (StatusCode, Json<ErrorResponse>) {
+    let status = match e {
+        ElevationError::NotFound(_) => StatusCode::NOT_FOUND,
+        ElevationError::NotPending { .. } => StatusCode::CONFLICT,
+        _ => StatusCode::BAD_REQUEST,
+    };
+    (
+        status,
+        Json(ErrorResponse {
+            error: e.to_string(),
+        }),
+    )
+}
+
+fn conversation_error(e: ConversationError) -> (StatusCode, Json<ErrorResponse>) {
+    let status = match e {
+        ConversationError::InvalidId(_) => StatusCode::BAD_REQUEST,
+        ConversationError::NotFound(_) => StatusCode::NOT_FOUND,
+        ConversationError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (
+        status,
+        Json(ErrorResponse {
+            error: e.to_string(),
+        }),
+    )
+}
+
+fn memory_error(e: MemoryError) -> (StatusCode, Json<ErrorResponse>) {
+    let status = match e {
+        MemoryError::InvalidId(_) => StatusCode::BAD_REQUEST,
+        MemoryError::NotFound(_) => StatusCode::NOT_FOUND,
+        MemoryError::Llm(_) | MemoryError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (
+        status,
+        Json(ErrorResponse {
+            error: e.to_string(),
+        }),
+    )
+}
+
+async fn handle_metrics(State(state): State<Arc<IpcState>>) -> Response {
+    Response::builder()
+        .header(header::CONTENT_TYPE, metrics::CONTENT_TYPE)
+        .body(Body::from(state.metrics.render()))
+        .unwrap_or_default()
+}
+
+async fn handle_quotas(State(state): State<Arc<IpcState>>) -> Json<QuotasResponse> {
+    Json(QuotasResponse {
+        quotas: state
+            .quotas
+            .usage()
+            .into_iter()
+            .map(|u| QuotaInfo {
+                role: u.role,
+                kind: u.kind.name().to_string(),
+                used: u.used,
+                limit: u.limit,
+                window_secs: u.window_secs,
+                resets_in_secs: u.resets_in_secs,
+            })
+            .collect(),
+    })
+}
+
+/// Default number of days reported by `GET /usage`.
+const DEFAULT_USAGE_DAYS: u32 = 7;
+
+async fn handle_usage(
+    State(state): State<Arc<IpcState>>,
+    Query(query): Query<UsageQuery>,
+) -> Result<Json<UsageResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let by = query.by.as_deref().unwrap_or("model");
+    let Some(group) = UsageGroup::parse(by) else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: format!(
+                    "unknown usage grouping '{by}' (expected model, role, skill, or conversation)"
+                ),
+            }),
+        ));
+    };
+    let days = query.days.unwrap_or(DEFAULT_USAGE_DAYS).max(1);
+    let pricing = state.config.borrow().llm.pricing.clone();
+    Ok(Json(UsageResponse {
+        since: UsageLedger::since(days),
+        by: group.name().to_string(),
+        usage: state
+            .usage
+            .report(days, group, &pricing)
+            .into_iter()
+            .map(|row| UsageInfo {
+                key: row.key,
+                requests: row.requests,
+                retries: row.retries,
+                prompt_tokens: row.prompt_tokens,
+                completion_tokens: row.completion_tokens,
+                cost_usd: row.cost_usd,
+                unpriced_tokens: row.unpriced_tokens,
+            })
+            .collect(),
+    }))
+}
+
+async fn handle_schedules(State(state): State<Arc<IpcState>>) -> Json<SchedulesResponse> {
+    Json(SchedulesResponse {
+        schedules: state
+            .scheduler
+            .status()
+            .into_iter()
+            .map(schedule_info)
+            .collect(),
+    })
+}
+
+async fn handle_schedule_run(
+    State(state): State<Arc<IpcState>>,
+    UrlPath(name): UrlPath<String>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    info!(schedule = %name, "Schedule run requested via IPC");
+    state
+        .scheduler
+        .run_now(&name)
+        .map(|()| StatusCode::ACCEPTED)
+        .map_err(|e| {
+            let status = match e {
+                ScheduleError::NotFound(_) => StatusCode::NOT_FOUND,
+                ScheduleError::Running(_) => StatusCode::CONFLICT,
+            };
+            (
+                status,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                }),
+            )
+        })
+}
+
+fn schedule_info(schedule: ScheduleStatus) -> ScheduleInfo {
+    ScheduleInfo {
+        name: schedule.name,
+        cron: schedule.cron,
+        target: schedule.target,
+        enabled: schedule.enabled,
+        running: schedule.running,
+        next_run: schedule.next_run,
+        history: schedule
+            .history
+            .into_iter()
+            .map(|run| ScheduleRunInfo {
+                started_at: run.started_at,
+                duration_ms: run.duration_ms,
+                trigger: run.trigger.name().to_string(),
+                outcome: run.outcome.name().to_string(),
+                message: run.message,
+            })
+            .collect(),
+    }
+}
+
+async fn handle_supervisor(State(state): State<Arc<IpcState>>) -> Json<SupervisorResponse> {
+    Json(SupervisorResponse {
+        tasks: state
+            .supervisor
+            .tasks()
+            .into_iter()
+            .map(|t| SupervisedTaskInfo {
+                name: t.name,
+                state: t.state.name().to_string(),
+                restarts: t.restarts,
+                last_error: t.last_error,
+                state_secs: t.since.elapsed().as_secs(),
+            })
+            .collect(),
+    })
+}
+
+/// Accept a signed message for the webhook channel.
+async fn handle_webhook(
+    State(state): State<Arc<IpcState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<(StatusCode, Json<WebhookAcceptedResponse>), (StatusCode, Json<ErrorResponse>)> {
+    let error = |status: StatusCode, e: WebhookError| {
+        (
+            status,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )
+    };
+    let Some(channel) = &state.webhook else {
+        return Err(error(StatusCode::NOT_FOUND, WebhookError::Disabled));
+    };
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    let timestamp = header(webhook::TIMESTAMP_HEADER);
+    let signature = header(webhook::SIGNATURE_HEADER);
+    match channel.receive(timestamp, signature, &body) {
+        Ok(id) => Ok((StatusCode::ACCEPTED, Json(WebhookAcceptedResponse { id }))),
+        Err(e @ (WebhookError::BadSignature | WebhookError::Stale | WebhookError::Replayed)) => {
+            warn!(error = %e, "Webhook message rejected");
+            state.metrics.record_denial(Denial::Webhook);
+            Err(error(StatusCode::UNAUTHORIZED, e))
+        }
+        Err(e @ WebhookError::Malformed(_)) => Err(error(StatusCode::BAD_REQUEST, e)),
+        Err(e @ WebhookError::Disabled) => Err(error(StatusCode::NOT_FOUND, e)),
+        Err(e) => {
+            warn!(error = %e, "Webhook message not accepted");
+            Err(error(StatusCode::SERVICE_UNAVAILABLE, e))
+        }
+    }
+}
+
+/// Run a blocking conversation store operation off the async runtime.
+async fn with_conversations<T: Send + 'static>(
+    state: &IpcState,
+    op: impl FnOnce(&ConversationStore) -> Result<T, ConversationError> + Send + 'static,
+) -> Result<T, (StatusCode, Json<ErrorResponse>)> {
+    let store = state.conversations.clone();
+    tokio::task::spawn_blocking(move || op(&store))
+        .await
+        .map_err(|e| conversation_error(ConversationError::Io(std::io::Error::other(e))))?
+        .map_err(conversation_error)
+}
+
+async fn handle_conversations(
+    State(state): State<Arc<IpcState>>,
+) -> Result<Json<ConversationsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let conversations = with_conversations(&state, |store| store.list()).await?;
+    Ok(Json(ConversationsResponse {
+        conversations: conversations
+            .into_iter()
+            .map(|c| ConversationInfo {
+                id: c.id,
+                channel: c.channel,
+                peer: c.peer,
+                messages: c.messages,
+                started_secs: c.started_secs,
+                updated_secs: c.updated_secs,
+                preview: c.preview,
+            })
+            .collect(),
+    }))
+}
+
+async fn handle_conversation(
+    State(state): State<Arc<IpcState>>,
+    UrlPath(id): UrlPath<String>,
+) -> Result<Json<ConversationResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let messages = with_conversations(&state, {
+        let id = id.clone();
+        move |store| store.messages(&id)
+    })
+    .await?;
+    Ok(Json(ConversationResponse {
+        id,
+        messages: messages
+            .into_iter()
+            .map(|m| ConversationMessage {
+                id: m.id,
+                timestamp_secs: m.timestamp_secs,
+                inbound: m.inbound,
+                channel: m.channel,
+                body: m.body,
+            })
+            .collect(),
+    }))
+}
+
+/// Run a blocking memory store operation off the async runtime.
+async fn with_memory<T: Send + 'static>(
+    state: &IpcState,
+    op: impl FnOnce(&MemoryStore) -> Result<T, MemoryError> + Send + 'static,
+) -> Result<T, (StatusCode, Json<ErrorResponse>)> {
+    let store = state.memory.clone();
+    tokio::task::spawn_blocking(move || op(&store))
+        .await
+        .map_err(|e| memory_error(MemoryError::Io(std::io::Error::other(e))))?
+        .map_err(memory_error)
+}
+
+async fn handle_memory(
+    State(state): State<Arc<IpcState>>,
+    UrlPath(id): UrlPath<String>,
+) -> Result<Json<MemoryResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let memory = with_memory(&state, {
+        let id = id.clone();
+        move |store| store.load(&id)?.ok_or(MemoryError::NotFound(id))
+    })
+    .await?;
+    Ok(Json(MemoryResponse {
+        id,
+        summary: memory.summary,
+        facts: memory
+            .facts
+            .into_iter()
+            .map(|(key, fact)| MemoryFact {
+                key,
+                value: fact.value,
+                updated_secs: fact.updated_secs,
+            })
+            .collect(),
+        runs: memory.runs,
+        updated_secs: memory.updated_secs,
+    }))
+}
+
+async fn handle_memory_clear(
+    State(state): State<Arc<IpcState>>,
+    UrlPath(id): UrlPath<String>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    with_memory(&state, move |store| {
+        if store.clear(&id)? {
+            Ok(())
+        } else {
+            Err(MemoryError::NotFound(id))
+        }
+    })
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn handle_files_list(
+    State(state): State<Arc<IpcState>>,
+    UrlPath(conversation): UrlPath<String>,
+) -> Result<Json<FileListResponse>, FilesError> {
+    let files = with_workspaces(&state, {
+        let conversation = conversation.clone();
+        move |store| store.list(&conversation)
+    })
+    .await?;
+    Ok(Json(FileListResponse {
+        conversation,
+        files: files.into_iter().map(file_info).collect(),
+    }))
+}
+
+async fn handle_files_put(
+    State(state): State<Arc<IpcState>>,
+    UrlPath((conversation, name)): UrlPath<(String, String)>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<FileInfo>, FilesError> {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    info!(%conversation, %name, size = body.len(), "File upload requested via IPC");
+    let file = with_workspaces(&state, move |store| {
+        store.put(&conversation, &name, content_type.as_deref(), &body)
+    })
+    .await?;
+    Ok(Json(file_info(file)))
+}
+
+async fn handle_files_get(
+    State(state): State<Arc<IpcState>>,
+    UrlPath((conversation, name)): UrlPath<(String, String)>,
+) -> Result<Response, FilesError> {
+    let (file, data) =
+        with_workspaces(&state, move |store| store.get(&conversation, &name)).await?;
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, file.content_type)
+        .body(Body::from(data))
+        .unwrap_or_default())
+}
+
+/// The tools the caller may use in chat sessions.
+async fn handle_chat_tools(
+    State(state): State<Arc<IpcState>>,
+    caller: Option<Extension<Caller>>,
+) -> Json<ChatToolsResponse> {
+    let Extension(caller) = caller.unwrap_or_default();
+    let tools = state
+        .chat
+        .tools(&caller.roles)
+        .into_iter()
+        .map(|tool| ChatToolInfo {
+            name: tool.name,
+            description: tool.description,
+            trust: tool.trust.to_string(),
+        })
+        .collect();
+    Json(ChatToolsResponse { tools })
+}
+
+/// Run a chat message, streaming its progress as server-sent events.
+///
+/// The stream starts with a `session` event and ends with `done` or
+/// `error`. Closing the connection cancels the run.
+async fn handle_chat_send(
+    State(state): State<Arc<IpcState>>,
+    caller: Option<Extension<Caller>>,
+    Json(req): Json<ChatSendRequest>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let Extension(caller) = caller.unwrap_or_default();
+    if req.message.trim().is_empty() {
+        let error = "message must not be empty".to_string();
+        return Err((StatusCode::BAD_REQUEST, Json(ErrorResponse { error })));
+    }
+    let session = state
+        .chat
+        .open(
+            &caller.identity,
+            req.session.as_deref(),
+            &ToolFilter::new(req.allowed_tools.clone(), req.denied_tools.clone()),
+        )
+        .map_err(chat_error)?;
+
+    let (events_tx, mut events) = mpsc::unbounded_channel();
+    let (mut tx, body) = http_body_util::channel::Channel::<Bytes>::new(64);
+    tokio::spawn(async move {
+        if tx
+            .send_data(sse_event(&ChatEvent::Session {
+                id: session.clone(),
+            }))
+            .await
+            .is_err()
+        {
+            return;
+        }
+        let run = state.chat.send(
+            &caller.identity,
+            &caller.roles,
+            &session,
+            &req.message,
+            events_tx,
+        );
+        tokio::pin!(run);
+        let result = loop {
+            tokio::select! {
+                result = &mut run => break result,
+                Some(event) = events.recv() => {
+                    if let Some(event) = chat_stream_event(event)
+                        && tx.send_data(sse_event(&event)).await.is_err()
+                    {
+                        return;
+                    }
+                }
+            }
+        };
+        while let Ok(event) = events.try_recv() {
+            if let Some(event) = chat_stream_event(event)
+                && tx.send_data(sse_event(&event)).await.is_err()
+            {
+                return;
+            }
+        }
+        let last = match result {
+            Ok(reply) => ChatEvent::Done {
+                reply: reply.reply,
+                iterations: reply.iterations,
+                total_tokens: reply.total_tokens,
+            },
+            Err(e) => {
+                warn!(session = %session, error = %e, "Chat message failed");
+                ChatEvent::Error {
+                    error: e.to_string(),
+                }
+            }
+        };
+        let _ = tx.send_data(sse_event(&last)).await;
+    });
+
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, "text/event-stream")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(Body::new(body))
+        .unwrap_or_default())
+}
+
+/// Clear a chat session's history.
+async fn handle_chat_reset(
+    State(state): State<Arc<IpcState>>,
+    caller: Option<Extension<Caller>>,
+    UrlPath(session): UrlPath<String>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let Extension(caller) = caller.unwrap_or_default();
+    state
+        .chat
+        .reset(&caller.identity, &session)
+        .map_err(chat_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Cancel the message in progress in a chat session.
+async fn handle_chat_cancel(
+    State(state): State<Arc<IpcState>>,
+    caller: Option<Extension<Caller>>,
+    UrlPath(session): UrlPath<String>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let Extension(caller) = caller.unwrap_or_default();
+    state
+        .chat
+        .cancel(&caller.identity, &session)
+        .map_err(chat_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn chat_error(e: ChatError) -> (StatusCode, Json<ErrorResponse>) {
+    let status = match &e {
+        ChatError::NotFound(_) => StatusCode::NOT_FOUND,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (
+        status,
+        Json(ErrorResponse {
+            error: e.to_string(),
+        }),
+    )
+}
+
+/// The stream event for an agent step; `Finished` is reported by the
+/// final `done` event instead.
+fn chat_stream_event(event: AgentEvent) -> Option<ChatEvent> {
+    Some(match event {
+        AgentEvent::Thinking { iteration } => ChatEvent::Thinking { iteration },
+        AgentEvent::Text { delta } => ChatEvent::Text { delta },
+        AgentEvent::ToolCall { name } => ChatEvent::ToolCall { name },
+        AgentEvent::ToolResult { name, ok } => ChatEvent::ToolResult { name, ok },
+        AgentEvent::Finished { .. } => return None,
+    })
+}
+
+/// Encode an event as a server-sent event frame.
+fn sse_event(event: &impl serde::Serialize) -> Bytes {
+    let json = serde_json::to_string(event).unwrap_or_default();
+    Bytes::from(format!("data: {json}\n\n"))
+}
+
+/// Stream the daemon's log collector as server-sent events.
+///
+/// Buffered entries (optionally only those after `?after=<seq>`) are sent
+/// first, followed by live entries until the client disconnects or the
+/// daemon shuts down.
+async fn handle_logs_stream(
+    State(state): State<Arc<IpcState>>,
+    Query(query): Query<LogStreamQuery>,
+) -> Response {
+    let (backlog, mut live) = state.logs.tail(query.after);
+    let mut shutdown_rx = state.shutdown_tx.subscribe();
+    let (mut tx, body) = http_body_util::channel::Channel::<Bytes>::new(64);
+
+    tokio::spawn(async move {
+        for entry in backlog {
+            if tx.send_data(log_event(entry)).await.is_err() {
+                return;
+            }
+        }
+        let mut keepalive = tokio::time::interval(LOG_STREAM_KEEPALIVE);
+        keepalive.reset();
+        loop {
+            let chunk = tokio::select! {
+                entry = live.recv() => match entry {
+                    Ok(entry) => log_event(entry),
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        Bytes::from(format!(": skipped {n} entries\n\n"))
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                },
+                _ = keepalive.tick() => Bytes::from_static(b": keep-alive\n\n"),
+                _ = shutdown_rx.recv() => return,
+            };
+            if tx.send_data(chunk).await.is_err() {
+                return;
+            }
+        }
+    });
+
+    Response::builder()
+        .header(header::CONTENT_TYPE, "text/event-stream")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(Body::new(body))
+        .unwrap_or_default()
+}
+
+/// Stream [`DaemonEvent`]s as server-sent events until the client
+/// disconnects or the daemon shuts down.
+///
+/// Only events published after the request are sent; there is no backlog.
+async fn handle_events(State(state): State<Arc<IpcState>>) -> Response {
+    let mut live = state.events.subscribe();
+    let mut shutdown_rx = state.shutdown_tx.subscribe();
+    let (mut tx, body) = http_body_util::channel::Channel::<Bytes>::new(64);
+
+    tokio::spawn(async move {
+        let mut keepalive = tokio::time::interval(LOG_STREAM_KEEPALIVE);
+        keepalive.reset();
+        loop {
+            let chunk = tokio::select! {
+                event = live.recv() => match event {
+                    Ok(event) => sse_event(&event),
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        Bytes::from(format!(": skipped {n} events\n\n"))
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                },
+                _ = keepalive.tick() => Bytes::from_static(b": keep-alive\n\n"),
+                _ = shutdown_rx.recv() => return,
+            };
+            if tx.send_data(chunk).await.is_err() {
+                return;
+            }
+        }
+    });
+
+    Response::builder()
+        .header(header::CONTENT_TYPE, "text/event-stream")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(Body::new(body))
+        .unwrap_or_default()
+}
+
+/// `envelope` as streamed, with the route `commands` gives it.
+fn message_event(envelope: &Envelope, commands: &CommandRouter) -> MessageEvent {
+    let route = if envelope.redacts.is_some() {
+        MessageRoute::Redaction
+    } else if envelope.direction == Direction::Outbound {
+        MessageRoute::Outbound
+    } else {
+        match commands.route(envelope) {
+            Some(Route::Help) => MessageRoute::Help,
+            Some(Route::Alias {
+                name,
+                allowed: true,
+            }) => MessageRoute::Command { name },
+            Some(Route::Alias {
+                name,
+                allowed: false,
+            }) => MessageRoute::Denied { name },
+            Some(Route::Builtin(Builtin::Help)) => MessageRoute::Help,
+            Some(Route::Builtin(Builtin::Invalid(_))) | None => MessageRoute::Unhandled,
+            Some(Route::Builtin(builtin)) => MessageRoute::Command {
+                name: builtin.name().to_string(),
+            },
+        }
+    };
+    MessageEvent {
+        id: envelope.id,
+        timestamp_secs: envelope
+            .timestamp
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default(),
+        channel: envelope.channel.clone(),
+        inbound: envelope.direction == Direction::Inbound,
+        peer: envelope.peer.clone(),
+        body: envelope.body.clone(),
+        redacts: envelope.redacts,
+        route,
+    }
+}
+
+/// Stream the messages on the bus from now on as server-sent events.
+async fn handle_messages_stream(State(state): State<Arc<IpcState>>) -> Response {
+    let mut live = state.messages.subscribe();
+    let mut shutdown_rx = state.shutdown_tx.subscribe();
+    let (mut tx, body) = http_body_util::channel::Channel::<Bytes>::new(64);
+
+    tokio::spawn(async move {
+        let mut keepalive = tokio::time::interval(LOG_STREAM_KEEPALIVE);
+        keepalive.reset();
+        loop {
+            let chunk = tokio::select! {
+                msg = live.recv() => match msg {
+                    Ok(envelope) => sse_event(&message_event(&envelope, &state.commands)),
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        Bytes::from(format!(": skipped {n} messages\n\n"))
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                },
+                _ = keepalive.tick() => Bytes::from_static(b": keep-alive\n\n"),
+                _ = shutdown_rx.recv() => return,
+            };
+            if tx.send_data(chunk).await.is_err() {
+                return;
+            }
+        }
+    });
+
+    Response::builder()
+        .header(header::CONTENT_TYPE, "text/event-stream")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(Body::new(body))
+        .unwrap_or_default()
+}
+
+/// Replace the daemon's log filter until the next restart.
+async fn handle_log_level(
+    State(state): State<Arc<IpcState>>,
+    Json(req): Json<LogLevelRequest>,
+) -> Result<Json<LogLevelResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let error = |status, e: String| (status, Json(ErrorResponse { error: e }));
+    let Some(control) = &state.log_control else {
+        return Err(error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "this daemon's log level cannot be changed at runtime".to_string(),
+        ));
+    };
+    let previous = control.level();
+    control.set_level(&req.level).map_err(|e| match e {
+        LogControlError::InvalidLevel { .. } => error(StatusCode::BAD_REQUEST, e.to_string()),
+        LogControlError::Reload(_) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    })?;
+    let level = control.level();
+    info!(%previous, %level, "Log level changed via IPC");
+    Ok(Json(LogLevelResponse { level, previous }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    fn test_state() -> Arc<IpcState> {
+        test_state_with_skills(SkillRegistry::new())
+    }
+
+    fn test_state_with_skills(skills: SkillRegistry) -> Arc<IpcState> {
+        test_state_with(
+            skills,
+            crate::logging::LogCollector::new(100).reader(),
+            WorkspaceStore::new(
+                std::env::temp_dir()
+                    .join(format!("crustyclaw-test-workspaces-{}", std::process::id())),
+            ),
+        )
+    }
+
+    fn test_state_with(
+        skills: SkillRegistry,
+        logs: LogReader,
+        workspaces: WorkspaceStore,
+    ) -> Arc<IpcState> {
+        test_state_from(AppConfig::default(), skills, logs, workspaces)
+    }
+
+    fn test_state_from(
+        config: AppConfig,
+        skills: SkillRegistry,
+        logs: LogReader,
+        workspaces: WorkspaceStore,
+    ) -> Arc<IpcState> {
+        let quotas = Arc::new(QuotaManager::from_config(&config.quotas));
+        let commands = Arc::new(CommandRouter::from_config(&config.effective_commands()));
+        let (shutdown_tx, _shutdown_rx) = broadcast::channel(1);
+        let (_, config_rx) = watch::channel(config);
+        let supervisor = Arc::new(Supervisor::new(shutdown_tx.clone()));
+        let workspaces = Arc::new(workspaces);
+        let skills = Arc::new(skills);
+        let scheduler = Arc::new(Scheduler::new(config_rx.clone(), skills.clone()));
+        let chat = Arc::new(ChatService::new(
+            config_rx.clone(),
+            Arc::new(std::sync::RwLock::new(
+                crate::context::ToolRegistry::with_defaults(),
+            )),
+            workspaces.clone(),
+        ));
+
+        Arc::new(IpcState {
+            config: config_rx,
+            config_changes: None,
+            shutdown_tx,
+            skills,
+            plugins: Arc::new(PluginRegistry::new()),
+            sandboxes: Arc::new(SandboxPool::default()),
+            warnings: Arc::new(WarningCollector::new()),
+            host: Arc::new(HostSampler::new()),
+            logs,
+            log_control: None,
+            conversations: Arc::new(ConversationStore::new(
+                workspaces.root().join(".conversations"),
+            )),
+            memory: Arc::new(MemoryStore::new(workspaces.root().join(".memory"))),
+            workspaces,
+            elevations: Arc::new(ElevationQueue::new()),
+            quotas,
+            metrics: Arc::new(Metrics::new()),
+            usage: Arc::new(UsageLedger::in_memory()),
+            health: Arc::new(HealthRegistry::new()),
+            supervisor,
+            token_key: None,
+            webhook: None,
+            chat,
+            scheduler,
+            events: EventBus::new(),
+            messages: broadcast::channel(16).0,
+            commands,
+            started_at: Instant::now(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_serve_tls_authorizes_by_cert_role() {
+        use crate::ipc::IpcClient;
+        use crate::ipc::client::IpcClientError;
+        use crate::ipc::tls;
+        use axum::serve::Listener;
+        use crustyclaw_config::{DaemonTlsConfig, RemoteConfig};
+        use crustyclaw_test_utils::tls::TlsFixture;
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        let files = TlsFixture::write(tmp.path());
+        let path = |p: &Path| Some(p.display().to_string());
+        let config = AppConfig::parse(
+            r#"
+            [auth.cert_map]
+            "ops-laptop" = "operator"
+
+            [[policy.rules]]
+            role = "operator"
+            action = "read"
+            resource = "*"
+            effect = "allow"
+        "#,
+        )
+        .unwrap();
+        let state = test_state_from(
+            config,
+            SkillRegistry::new(),
+            crate::logging::LogCollector::new(100).reader(),
+            WorkspaceStore::new(tmp.path().join("workspaces")),
+        );
+
+        let server_tls = tls::server_config(&DaemonTlsConfig {
+            enabled: true,
+            cert_path: path(&files.server_cert),
+            key_path: path(&files.server_key),
+            client_ca_path: path(&files.ca_cert),
+        })
+        .unwrap();
+        let listener = tls::TlsListener::bind(("127.0.0.1", 0), server_tls, state.config.clone())
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap().addr;
+        let shutdown_tx = state.shutdown_tx.clone();
+        let server = tokio::spawn(serve_tls(listener, state, shutdown_tx.subscribe()));
+
+        let client = |cert: &Path, key: &Path| {
+            let remote = RemoteConfig {
+                addr: Some(addr.to_string()),
+                server_name: None,
+                ca_path: path(&files.ca_cert),
+                cert_path: path(cert),
+                key_path: path(key),
+            };
+            IpcClient::tls(
+                addr.to_string(),
+                "localhost",
+                tls::client_config(&remote).unwrap(),
+            )
+            .unwrap()
+        };
+
+        // Mapped to "operator", which may read but not write.
+        let operator = client(&files.client_cert, &files.client_key);
+        assert_eq!(operator.health().await.unwrap().status, "ok");
+        assert!(operator.status().await.unwrap().running);
+        match operator.stop().await {
+            Err(IpcClientError::DaemonError(msg)) => {
+                assert_eq!(msg, "role \"operator\" may not write \"stop\"");
+            }
+            other => panic!("expected a policy denial, got {other:?}"),
+        }
+
+        // Valid certificate, but not in auth.cert_map.
+        let intruder = client(&files.other_client_cert, &files.other_client_key);
+        assert!(intruder.health().await.is_err());
+
+        shutdown_tx.send(ShutdownSignal).unwrap();
+        server.await.unwrap().unwrap();
+    }
+
+    fn execute_request(req: &SkillExecuteRequest) -> Request<Body> {
+        Request::post("/skills/execute")
+            .header("content-type", "application/json")
+            .body(Body::from(serde_json::to_string(req).unwrap()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_health_endpoint() {
+        let app = router(test_state());
+        let req = Request::get("/health").body(Body::empty()).unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let health: HealthResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(health.status, "ok");
+    }
+
+    #[tokio::test]
+    async fn test_version_endpoint() {
+        let app = router(test_state());
+        let req = Request::get("/version").body(Body::empty()).unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let version: VersionResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(version, VersionResponse::current());
+        assert_eq!(version.target, crate::build_info::TARGET);
+        assert_eq!(version.api_version, API_VERSION);
+    }
+
+    #[tokio::test]
+    async fn test_api_version_negotiation() {
+        let app = local_router(test_state());
+        let send = |path: &str, version: Option<&str>| {
+            let mut req = Request::get(path);
+            if let Some(version) = version {
+                req = req.header(API_VERSION_HEADER, version);
+            }
+            let mut req = req.body(Body::empty()).unwrap();
+            req.extensions_mut().insert(ConnectInfo(LocalPeer {
+                uid: daemon_uid(),
+                gid: 0,
+                pid: None,
+            }));
+            app.clone().oneshot(req)
+        };
+        let daemon = ApiVersion::current();
+
+        // Same major (any minor) and no header at all are served.
+        let minor = format!("{}.{}", daemon.major, daemon.minor + 7);
+        for version in [Some(API_VERSION), Some(minor.as_str()), None] {
+            let resp = send("/status", version).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK, "{version:?}");
+            assert_eq!(resp.headers()[API_VERSION_HEADER], API_VERSION);
+        }
+
+        let newer = format!("{}.0", daemon.major + 1);
+        let resp = send("/status", Some(&newer)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(resp.headers()[API_VERSION_HEADER], API_VERSION);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let error = serde_json::from_slice::<ErrorResponse>(&body)
+            .unwrap()
+            .error;
+        assert!(error.contains("upgrade the daemon"), "{error}");
+
+        let resp = send("/status", Some("one")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        // /health answers anyway, with the version to compare against.
+        let resp = send("/health", Some(&newer)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let health: HealthResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(health.api_version, API_VERSION);
+    }
+
+    #[tokio::test]
+    async fn test_policy_conditions_see_request_context() {
+        let config = AppConfig::parse(
+            r#"
+            [[policy.rules]]
+            id = "no-reads-in-any-hour"
+            role = "uid:4245"
+            action = "read"
+            resource = "usage"
+            effect = "deny"
+            when = "time.hour >= 0 && time.hour < 24"
+
+            [[policy.rules]]
+            role = "uid:4245"
+            action = "read"
+            resource = "conversations"
+            effect = "deny"
+            when = "request.mfa != true"
+
+            [[policy.rules]]
+            role = "uid:4245"
+            action = "write"
+            resource = "stop"
+            effect = "allow"
+            when = 'time.weekday >= 0 && request.resource == "stop" && !caller.remote'
+        "#,
+        )
+        .unwrap();
+        let tmp = tempfile::TempDir::new().unwrap();
+        let state = test_state_from(
+            config,
+            SkillRegistry::new(),
+            crate::logging::LogCollector::new(100).reader(),
+            WorkspaceStore::new(tmp.path()),
+        );
+        let app = local_router(state);
+        let send = |method: Method, path: &str| {
+            let mut req = Request::builder()
+                .method(method)
+                .uri(path)
+                .body(Body::empty())
+                .unwrap();
+            let peer = LocalPeer {
+                uid: 4245,
+                gid: 4245,
+                pid: Some(1),
+            };
+            req.extensions_mut().insert(ConnectInfo(peer));
+            app.clone().oneshot(req)
+        };
+
+        // A time-gated deny rule denies while its window is open.
+        let resp = send(Method::GET, "/usage").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        // An attribute no caller supplies fails closed for a deny rule...
+        let resp = send(Method::GET, "/conversations").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        // ...while time, request, and caller attributes reach allow rules.
+        let resp = send(Method::POST, "/stop").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = send(Method::GET, "/status").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_peer_authorization() {
+        let config = AppConfig::parse(
+            r#"
+            [auth.role_map]
+            "uid:4243" = "admin"
+
+            [[policy.rules]]
+            role = "uid:4244"
+            action = "read"
+            resource = "conversations"
+            effect = "deny"
+
+            [[policy.rules]]
+            id = "no-usage-reads"
+            reason = "usage is billing data"
+            role = "uid:4244"
+            action = "read"
+            resource = "usage"
+            effect = "deny"
+        "#,
+        )
+        .unwrap();
+        let tmp = tempfile::TempDir::new().unwrap();
+        let state = test_state_from(
+            config,
+            SkillRegistry::new(),
+            crate::logging::LogCollector::new(100).reader(),
+            WorkspaceStore::new(tmp.path()),
+        );
+        let app = local_router(state.clone());
+        let send = |method: Method, path: &str, uid: u32| {
+            let mut req = Request::builder()
+                .method(method)
+                .uri(path)
+                .body(Body::empty())
+                .unwrap();
+            let peer = LocalPeer {
+                uid,
+                gid: uid,
+                pid: Some(1),
+            };
+            req.extensions_mut().insert(ConnectInfo(peer));
+            app.clone().oneshot(req)
+        };
+        let error = |resp: Response| async {
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            serde_json::from_slice::<ErrorResponse>(&body)
+                .unwrap()
+                .error
+        };
+
+        // With no matching rule, anyone may read but only admin may write.
+        let mut events = state.events.subscribe();
+        let resp = send(Method::GET, "/status", 4242).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = send(Method::POST, "/stop", 4242).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            DaemonEvent::PolicyDenied { action, resource, .. }
+                if action == "write" && resource == "stop"
+        ));
+        let message = error(resp).await;
+        assert!(message.contains(r#"may not write "stop""#), "{message}");
+        assert!(message.contains("only admin may write"), "{message}");
+
+        // `auth.role_map` grants admin, and the daemon's own user is admin.
+        let resp = send(Method::POST, "/stop", 4243).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = send(Method::POST, "/stop", daemon_uid()).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // A deny rule overrides the default.
+        let resp = send(Method::GET, "/conversations", 4244).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        assert!(
+            error(resp)
+                .await
+                .contains("denied by a [[policy.rules]] entry")
+        );
+        let resp = send(Method::GET, "/usage", 4244).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let message = error(resp).await;
+        assert!(
+            message.ends_with(r#"denied by rule "no-usage-reads": usage is billing data"#),
+            "{message}"
+        );
+
+        // Snapshot sections are authorized one by one.
+        let resp = send(
+            Method::GET,
+            "/snapshot?include=status,conversations,history",
+            4244,
+        )
+        .await
+        .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let snapshot: SnapshotResponse = serde_json::from_slice(&body).unwrap();
+        assert!(snapshot.status.is_some());
+        assert!(snapshot.conversations.is_none() && snapshot.history.is_none());
+        let omitted: Vec<&str> = snapshot
+            .omitted
+            .iter()
+            .map(|o| o.section.as_str())
+            .collect();
+        assert_eq!(omitted, ["conversations", "history"]);
+        assert!(snapshot.omitted[0].reason.contains("denied"));
+
+        // Health checks are always open; unknown peers are refused.
+        let resp = send(Method::GET, "/health", u32::MAX).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = send(Method::GET, "/status", u32::MAX).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_chat_endpoints() {
+        use crate::BoxFuture;
+        use crate::llm::{
+            ChatRequest, ChatResponse, LlmError, LlmProvider, StreamChunk, TokenUsage,
+        };
+
+        /// Answers every request with "hello" in two parts.
+        struct Hello;
+
+        impl LlmProvider for Hello {
+            fn name(&self) -> &str {
+                "hello"
+            }
+
+            fn chat(&self, _: &ChatRequest) -> BoxFuture<'_, Result<ChatResponse, LlmError>> {
+                Box::pin(async { Err(LlmError::Timeout) })
+            }
+
+            fn chat_stream(
+                &self,
+                _: &ChatRequest,
+            ) -> BoxFuture<'_, Result<mpsc::Receiver<Result<StreamChunk, LlmError>>, LlmError>>
+            {
+                Box::pin(async {
+                    let (tx, rx) = mpsc::channel(4);
+                    for chunk in [
+                        StreamChunk::Text("hel".to_string()),
+                        StreamChunk::Text("lo".to_string()),
+                        StreamChunk::Done {
+                            finish_reason: "stop".to_string(),
+                            usage: Some(TokenUsage {
+                                prompt_tokens: 3,
+                                completion_tokens: 2,
+                                total_tokens: 5,
+                            }),
+                            attempts: 1,
+                        },
+                    ] {
+                        tx.send(Ok(chunk)).await.unwrap();
+                    }
+                    Ok(rx)
+                })
+            }
+        }
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        let state = test_state_from(
+            AppConfig::default(),
+            SkillRegistry::new(),
+            crate::logging::LogCollector::new(100).reader(),
+            WorkspaceStore::new(tmp.path()),
+        );
+        let mut state = Arc::try_unwrap(state).ok().unwrap();
+        state.chat = Arc::new(
+            ChatService::new(
+                state.config.clone(),
+                Arc::new(std::sync::RwLock::new(
+                    crate::context::ToolRegistry::with_defaults(),
+                )),
+                state.workspaces.clone(),
+            )
+            .with_provider(Arc::new(Hello)),
+        );
+        let app = router(Arc::new(state));
+        let as_caller = |identity: &str, role: &str, req: axum::http::request::Builder| {
+            req.extension(Caller {
+                identity: identity.to_string(),
+                roles: vec![role.to_string()],
+                remote: false,
+            })
+        };
+        let read = |resp: Response| async move {
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            String::from_utf8(body.to_vec()).unwrap()
+        };
+
+        // Tools are scoped by the caller's roles.
+        let tools = |role: &'static str| {
+            let req = as_caller("alice", role, Request::get("/chat/tools"));
+            app.clone().oneshot(req.body(Body::empty()).unwrap())
+        };
+        let body = read(tools("viewer").await.unwrap()).await;
+        let viewer: ChatToolsResponse = serde_json::from_str(&body).unwrap();
+        assert!(viewer.tools.iter().any(|t| t.name == "read_file"));
+        assert!(!viewer.tools.iter().any(|t| t.name == "run_command"));
+        let body = read(tools("operator").await.unwrap()).await;
+        let operator: ChatToolsResponse = serde_json::from_str(&body).unwrap();
+        let run_command = operator.tools.iter().find(|t| t.name == "run_command");
+        assert_eq!(run_command.unwrap().trust, "internal");
+
+        let send = |identity: &'static str, body: String| {
+            let req = as_caller(identity, "user", Request::post("/chat"))
+                .header(header::CONTENT_TYPE, "application/json");
+            app.clone().oneshot(req.body(Body::from(body)).unwrap())
+        };
+        let resp = send("alice", r#"{"message": "hi"}"#.to_string())
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/event-stream"
+        );
+        let events: Vec<ChatEvent> = read(resp)
+            .await
+            .split("\n\n")
+            .filter_map(|event| event.strip_prefix("data: "))
+            .map(|json| serde_json::from_str(json).unwrap())
+            .collect();
+        let ChatEvent::Session { id } = &events[0] else {
+            panic!("expected a session event first: {events:?}");
+        };
+        assert_eq!(
+            events[1..],
+            [
+                ChatEvent::Thinking { iteration: 1 },
+                ChatEvent::Text {
+                    delta: "hel".to_string()
+                },
+                ChatEvent::Text {
+                    delta: "lo".to_string()
+                },
+                ChatEvent::Done {
+                    reply: "hello".to_string(),
+                    iterations: 1,
+                    total_tokens: 5
+                },
+            ]
+        );
+
+        // Sessions belong to the identity that opened them.
+        let resume = format!(r#"{{"session": "{id}", "message": "again"}}"#);
+        let resp = send("mallory", resume.clone()).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let resp = send("alice", resume).await.unwrap();
+        assert!(read(resp).await.contains(r#""type":"done""#));
+        // Tool lists narrow the session's tools.
+        let resp = send(
+            "alice",
+            r#"{"message": "hi", "allowed_tools": ["read_file"], "denied_tools": ["read_file"]}"#
+                .to_string(),
+        )
+        .await
+        .unwrap();
+        assert!(read(resp).await.contains(r#""type":"done""#));
+        let resp = send("alice", r#"{"message": "  "}"#.to_string())
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let cancel = |identity: &str| {
+            let req = as_caller(
+                identity,
+                "user",
+                Request::post(format!("/chat/{id}/cancel")),
+            );
+            app.clone().oneshot(req.body(Body::empty()).unwrap())
+        };
+        assert_eq!(
+            cancel("mallory").await.unwrap().status(),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            cancel("alice").await.unwrap().status(),
+            StatusCode::NO_CONTENT
+        );
+
+        let reset = |identity: &str| {
+            let req = as_caller(identity, "user", Request::delete(format!("/chat/{id}")));
+            app.clone().oneshot(req.body(Body::empty()).unwrap())
+        };
+        assert_eq!(
+            reset("mallory").await.unwrap().status(),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            reset("alice").await.unwrap().status(),
+            StatusCode::NO_CONTENT
+        );
+    }
+
+    #[tokio::test]
+    async fn test_webhook_channel() {
+        use crate::response::ResponsePipeline;
+        use crate::secrets::SecretStore;
+        use std::sync::RwLock;
+
+        let config = AppConfig::parse(
+            r#"
+            [webhook]
+            enabled = true
+            secret = "webhook_key"
+
+            [[secrets.entries]]
+            name = "webhook_key"
+            source = "inline"
+            value = "s3cret-key"
+            inject_env = "WEBHOOK_KEY"
+        "#,
+        )
+        .unwrap();
+        let secrets = Arc::new(RwLock::new(
+            SecretStore::from_config(&config.secrets).unwrap(),
+        ));
+        let tmp = tempfile::TempDir::new().unwrap();
+        let state = test_state_from(
+            config.clone(),
+            SkillRegistry::new(),
+            crate::logging::LogCollector::new(100).reader(),
+            WorkspaceStore::new(tmp.path()),
+        );
+        let (bus, mut bus_rx) = broadcast::channel(16);
+        let channel = WebhookChannel::new(
+            state.config.clone(),
+            secrets.clone(),
+            bus,
+            Arc::new(ResponsePipeline::from_config(&config.response, secrets)),
+        );
+        let mut state = Arc::try_unwrap(state).ok().unwrap();
+        state.webhook = Some(Arc::new(channel));
+        let state = Arc::new(state);
+        let app = local_router(state.clone());
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        // No peer credentials: channel routes skip IPC authentication.
+        let send = |body: &'static str, signature: Option<String>| {
+            let mut req = Request::builder()
+                .method(Method::POST)
+                .uri("/channels/webhook")
+                .header(webhook::TIMESTAMP_HEADER, now.to_string());
+            if let Some(signature) = signature {
+                req = req.header(webhook::SIGNATURE_HEADER, signature);
+            }
+            app.clone().oneshot(req.body(Body::from(body)).unwrap())
+        };
+        let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, b"s3cret-key");
+
+        let body = r#"{"sender": "ticket-42", "text": "hello"}"#;
+        let signature = webhook::sign(&key, now, body.as_bytes());
+        let resp = send(body, Some(signature.clone())).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::ACCEPTED);
+        let envelope = bus_rx.recv().await.unwrap();
+        assert_eq!(envelope.channel, "webhook");
+        assert_eq!(envelope.peer.as_deref(), Some("ticket-42"));
+
+        let resp = send(body, None).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert!(state.metrics.render().contains(r#"reason="webhook"} 1"#));
+        // A captured delivery sent again.
+        let resp = send(body, Some(signature)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert!(state.metrics.render().contains(r#"reason="webhook"} 2"#));
+        let resp = send("not json", Some(webhook::sign(&key, now, b"not json")))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_token_authentication() {
+        let config = AppConfig::parse(
+            r#"
+            [auth]
+            mode = "token"
+
+            [[policy.rules]]
+            role = "viewer"
+            action = "read"
+            resource = "*"
+            effect = "allow"
+        "#,
+        )
+        .unwrap();
+        let tmp = tempfile::TempDir::new().unwrap();
+        let mut state = Arc::into_inner(test_state_from(
+            config,
+            SkillRegistry::new(),
+            crate::logging::LogCollector::new(100).reader(),
+            WorkspaceStore::new(tmp.path()),
+        ))
+        .unwrap();
+        let key = TokenKey::from_bytes(&[5; 32]);
+        let mint = |expires_at| {
+            key.sign(&TokenClaims {
+                identity: "alice".to_string(),
+                roles: vec!["alice".to_string(), "viewer".to_string()],
+                issued_at: token::now(),
+                expires_at,
+            })
+        };
+        let valid = mint(token::now() + 60);
+        let expired = mint(token::now() - 1);
+        let forged = TokenKey::from_bytes(&[6; 32]).sign(&TokenClaims {
+            identity: "mallory".to_string(),
+            roles: vec!["admin".to_string()],
+            issued_at: 0,
+            expires_at: u64::MAX,
+        });
+        state.token_key = Some(Arc::new(key));
+        let state = Arc::new(state);
+        let app = local_router(state.clone());
+
+        let send = |method: Method, path: &str, token: Option<&str>| {
+            let mut req = Request::builder().method(method).uri(path);
+            if let Some(token) = token {
+                req = req.header(header::AUTHORIZATION, format!("Bearer {token}"));
+            }
+            app.clone().oneshot(req.body(Body::empty()).unwrap())
+        };
+
+        // Health checks need no token.
+        let resp = send(Method::GET, "/health", None).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        for token in [None, Some(expired.as_str()), Some(forged.as_str())] {
+            let resp = send(Method::GET, "/status", token).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::UNAUTHORIZED, "{token:?}");
+        }
+
+        let resp = send(Method::GET, "/status", Some(&valid)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        // The token's roles may read but not write.
+        let resp = send(Method::POST, "/stop", Some(&valid)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let rendered = state.metrics.render();
+        assert!(rendered.contains(r#"crustyclaw_policy_denials_total{reason="token"} 3"#));
+        assert!(rendered.contains(r#"crustyclaw_policy_denials_total{reason="role"} 1"#));
+    }
+
+    #[tokio::test]
+    async fn test_login_mints_token() {
+        let config = AppConfig::parse(
+            r#"
+            [auth]
+            mode = "token"
+            token_ttl_secs = 600
+
+            [auth.role_map]
+            "uid:4243" = "admin"
+        "#,
+        )
+        .unwrap();
+        let tmp = tempfile::TempDir::new().unwrap();
+        let mut state = Arc::into_inner(test_state_from(
+            config,
+            SkillRegistry::new(),
+            crate::logging::LogCollector::new(100).reader(),
+            WorkspaceStore::new(tmp.path()),
+        ))
+        .unwrap();
+        let key = Arc::new(TokenKey::from_bytes(&[5; 32]));
+        state.token_key = Some(key.clone());
+        let app = local_router(Arc::new(state));
+
+        let send =
+            |method: Method, path: &str, peer: LocalPeer, body: &str, token: Option<&str>| {
+                let mut req = Request::builder().method(method).uri(path);
+                if let Some(token) = token {
+                    req = req.header(header::AUTHORIZATION, format!("Bearer {token}"));
+                }
+                let mut req = req.body(Body::from(body.to_string())).unwrap();
+                req.extensions_mut().insert(ConnectInfo(peer));
+                app.clone().oneshot(req)
+            };
+        let peer = |uid| LocalPeer {
+            uid,
+            gid: uid,
+            pid: Some(1),
+        };
+        let login = |resp: Response| async {
+            assert_eq!(resp.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            serde_json::from_slice::<LoginResponse>(&body).unwrap()
+        };
+
+        // Roles come from auth.role_map for the peer's UID, not the client.
+        let resp = send(Method::POST, "/auth/login", peer(4242), "", None);
+        let user = login(resp.await.unwrap()).await;
+        assert_eq!(user.identity, "uid:4242");
+        assert_eq!(user.roles, ["uid:4242"]);
+        let claims = key.verify(&user.token, token::now()).unwrap();
+        assert_eq!(claims.expires_at - claims.issued_at, 600);
+        let resp = send(Method::POST, "/stop", peer(4242), "", Some(&user.token));
+        assert_eq!(resp.await.unwrap().status(), StatusCode::FORBIDDEN);
+
+        let body = r#"{"ttl_secs": 60}"#;
+        let resp = send(Method::POST, "/auth/login", peer(4243), body, None);
+        let admin = login(resp.await.unwrap()).await;
+        assert_eq!(admin.roles, ["admin", "uid:4243"]);
+        let claims = key.verify(&admin.token, token::now()).unwrap();
+        assert_eq!(claims.expires_at - claims.issued_at, 60);
+
+        for (peer, body) in [
+            (LocalPeer::UNKNOWN, ""),
+            (peer(4242), r#"{"ttl_secs": 0}"#),
+            (peer(4242), "not json"),
+        ] {
+            let resp = send(Method::POST, "/auth/login", peer, body, None);
+            assert!(!resp.await.unwrap().status().is_success(), "{body}");
+        }
+
+        // A token may not outlive auth.token_ttl_secs.
+        for ttl in [601, u64::MAX] {
+            let body = format!(r#"{{"ttl_secs": {ttl}}}"#);
+            let resp = send(Method::POST, "/auth/login", peer(4243), &body, None);
+            let resp = resp.await.unwrap();
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{ttl}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_liveness_and_readiness_endpoints() {
+        let app = router(test_state());
+        let resp = app
+            .clone()
+            .oneshot(Request::get("/health/live").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let live: LivenessResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(live.status, "alive");
+
+        let resp = app
+            .oneshot(Request::get("/health/ready").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = resp.status();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let ready: ReadinessResponse = serde_json::from_slice(&body).unwrap();
+        let names: Vec<&str> = ready.components.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["isolation", "llm", "signal", "staging_dir"]);
+        assert_eq!(ready.components[2].status, "disabled");
+        // The default backend may be unavailable here; the code follows readiness.
+        let expected = if ready.ready {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        };
+        assert_eq!(status, expected);
+    }
+
+    #[tokio::test]
+    async fn test_status_endpoint() {
+        let app = router(test_state());
+        let req = Request::get("/status").body(Body::empty()).unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let status: StatusResponse = serde_json::from_slice(&body).unwrap();
+        assert!(status.running);
+        assert_eq!(status.listen_port, 9100);
+        assert!(status.warnings.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_status_reports_warnings() {
+        let state = test_state();
+        state.warnings.push(
+            crate::warnings::WarningKind::Insecure,
+            "isolation.backend",
+            "no isolation",
+        );
+        let app = router(state);
+        let req = Request::get("/status").body(Body::empty()).unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let status: StatusResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(status.warnings.len(), 1);
+        assert_eq!(status.warnings[0].kind, "insecure");
+        assert_eq!(status.warnings[0].key, "isolation.backend");
+    }
+
+    #[tokio::test]
+    async fn test_host_status_endpoint() {
+        let app = router(test_state());
+        let req = Request::get("/status/host").body(Body::empty()).unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let host: HostStatusResponse = serde_json::from_slice(&body).unwrap();
+        assert!(host.cpu_count >= 1);
+        let labels: Vec<&str> = host.disks.iter().map(|d| d.label.as_str()).collect();
+        assert_eq!(labels, vec!["signal.data_dir", "secrets.staging_dir"]);
+    }
+
+    #[tokio::test]
+    async fn test_stop_endpoint() {
+        let state = test_state();
+        let mut rx = state.shutdown_tx.subscribe();
+        let app = router(state);
+
+        let req = Request::post("/stop").body(Body::empty()).unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let stop: StopResponse = serde_json::from_slice(&body).unwrap();
+        assert!(stop.acknowledged);
+
+        // Verify shutdown signal was sent
+        let signal = rx.try_recv();
+        assert!(signal.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_log_level_endpoint() {
+        use tracing_subscriber::layer::SubscriberExt;
+        use tracing_subscriber::util::SubscriberInitExt;
+
+        let put_level = |state: Arc<IpcState>, level: &str| {
+            let req = Request::put("/logging/level")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    serde_json::to_string(&LogLevelRequest {
+                        level: level.to_string(),
+                    })
+                    .unwrap(),
+                ))
+                .unwrap();
+            router(state).oneshot(req)
+        };
+        let resp = put_level(test_state(), "debug").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let (control, level, file) = LogControl::new(tracing_subscriber::EnvFilter::new("info"));
+        let _guard = tracing_subscriber::registry()
+            .with(level)
+            .with(file)
+            .set_default();
+        let mut state = Arc::into_inner(test_state()).unwrap();
+        state.log_control = Some(control);
+        let state = Arc::new(state);
+
+        let resp = put_level(state.clone(), "debug,hyper=info").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let changed: LogLevelResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(changed.previous, "info");
+        assert_eq!(changed.level, "hyper=info,debug");
+
+        let resp = put_level(state.clone(), "").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let req = Request::get("/status").body(Body::empty()).unwrap();
+        let resp = router(state).oneshot(req).await.unwrap();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let status: StatusResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(status.log_level, "hyper=info,debug");
     }
 
     #[tokio::test]
@@ -313,6 +3150,107 @@ mod tests {
         assert!(config_resp.toml.contains("listen_port"));
     }
 
+    #[tokio::test]
+    async fn test_config_endpoint_redacts_secrets() {
+        let config = AppConfig::parse(
+            r#"
+            [llm]
+            api_key = "sk-primary"
+
+            [[llm.fallbacks]]
+            provider = "openai"
+            api_key = "sk-fallback"
+        "#,
+        )
+        .unwrap();
+        let tmp = tempfile::TempDir::new().unwrap();
+        let state = test_state_from(
+            config,
+            SkillRegistry::new(),
+            crate::logging::LogCollector::new(100).reader(),
+            WorkspaceStore::new(tmp.path()),
+        );
+        let mut req = Request::get("/config").body(Body::empty()).unwrap();
+        req.extensions_mut().insert(ConnectInfo(LocalPeer {
+            uid: 4246,
+            gid: 4246,
+            pid: Some(1),
+        }));
+        let resp = local_router(state).oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let config_resp: ConfigResponse = serde_json::from_slice(&body).unwrap();
+        assert!(!config_resp.toml.contains("sk-"), "{}", config_resp.toml);
+        assert!(config_resp.toml.contains(crustyclaw_config::REDACTED));
+    }
+
+    #[tokio::test]
+    async fn test_config_update_endpoint() {
+        let put_config = |state: Arc<IpcState>, set: &[&str]| {
+            let req = Request::put("/config")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    serde_json::to_string(&ConfigUpdateRequest {
+                        set: set.iter().map(|s| s.to_string()).collect(),
+                    })
+                    .unwrap(),
+                ))
+                .unwrap();
+            router(state).oneshot(req)
+        };
+        let resp = put_config(test_state(), &["daemon.listen_port=9200"])
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        // Stands in for the daemon, accepting only changes to the port.
+        let (tx, mut changes) = mpsc::channel::<ConfigChange>(1);
+        tokio::spawn(async move {
+            while let Some(change) = changes.recv().await {
+                let result = match change.overrides[0].key().as_str() {
+                    "daemon.listen_port" => Ok(()),
+                    key => Err(format!("{key} is invalid")),
+                };
+                let _ = change.reply.send(result);
+            }
+        });
+        let mut state = Arc::into_inner(test_state()).unwrap();
+        state.config_changes = Some(tx);
+        let state = Arc::new(state);
+
+        let resp = put_config(state.clone(), &["daemon.listen_port=9200"])
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let config: ConfigResponse = serde_json::from_slice(&body).unwrap();
+        assert!(config.toml.contains("listen_port"));
+
+        let resp = put_config(state.clone(), &["isolation.backend=bogus"])
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let error: ErrorResponse = serde_json::from_slice(&body).unwrap();
+        assert!(
+            error.error.contains("isolation.backend is invalid"),
+            "{}",
+            error.error
+        );
+
+        for bad in [&[][..], &["listen_port"][..]] {
+            let resp = put_config(state.clone(), bad).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        }
+    }
+
     #[tokio::test]
     async fn test_policy_eval_endpoint() {
         let app = router(test_state());
@@ -333,6 +3271,45 @@ mod tests {
             .unwrap();
         let eval: PolicyEvalResponse = serde_json::from_slice(&body).unwrap();
         assert!(!eval.decision.is_empty());
+
+        let config = AppConfig::parse(
+            r#"
+            [[policy.rules]]
+            id = "read-only-config"
+            reason = "config changes go through review"
+            role = "*"
+            action = "write"
+            resource = "config"
+            effect = "deny"
+        "#,
+        )
+        .unwrap();
+        let tmp = tempfile::TempDir::new().unwrap();
+        let app = router(test_state_from(
+            config,
+            SkillRegistry::new(),
+            crate::logging::LogCollector::new(100).reader(),
+            WorkspaceStore::new(tmp.path()),
+        ));
+        let req_body = PolicyEvalRequest {
+            role: "admin".to_string(),
+            action: "write".to_string(),
+            resource: "config".to_string(),
+        };
+        let req = Request::post("/policy/evaluate")
+            .header("content-type", "application/json")
+            .body(Body::from(serde_json::to_string(&req_body).unwrap()))
+            .unwrap();
+        let body = axum::body::to_bytes(app.oneshot(req).await.unwrap().into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let eval: PolicyEvalResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(eval.decision, "denied");
+        assert_eq!(eval.rule_id.as_deref(), Some("read-only-config"));
+        assert_eq!(
+            eval.reason.as_deref(),
+            Some("config changes go through review")
+        );
     }
 
     #[tokio::test]
@@ -361,6 +3338,462 @@ mod tests {
             .unwrap();
         let skills: SkillsResponse = serde_json::from_slice(&body).unwrap();
         assert!(skills.skills.is_empty());
+        assert!(skills.locks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_skills_endpoint_shows_lock_holder() {
+        use crate::skill::{ConcurrencyClass, ExclusionPolicy};
+
+        let deploy = |name: &str| {
+            crate::skill::IsolatedSkill::new(
+                name,
+                "Slow deploy",
+                vec!["sleep".to_string(), "0.5".to_string()],
+                crate::isolation::SandboxConfig::new(name).with_workdir("/tmp"),
+                Box::new(crate::isolation::NoopBackend),
+            )
+            .with_concurrency_class(
+                ConcurrencyClass::new("deploy").with_policy(ExclusionPolicy::Reject),
+            )
+        };
+        let mut skills = SkillRegistry::new();
+        skills.register(Box::new(deploy("deploy-web")));
+        skills.register(Box::new(deploy("deploy-db")));
+        let state = test_state_with_skills(skills);
+
+        let running = tokio::spawn(router(state.clone()).oneshot(execute_request(
+            &SkillExecuteRequest {
+                name: "deploy-web".to_string(),
+                args: Default::default(),
+                trust_tier: None,
+            },
+        )));
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let resp = router(state.clone())
+            .oneshot(Request::get("/skills").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let listing: SkillsResponse = serde_json::from_slice(&body).unwrap();
+        assert!(
+            listing
+                .skills
+                .iter()
+                .all(|s| s.concurrency_class.as_deref() == Some("deploy"))
+        );
+        assert_eq!(listing.locks.len(), 1);
+        assert_eq!(listing.locks[0].holder.as_deref(), Some("deploy-web"));
+
+        // A second deploy is rejected while the first holds the class.
+        let resp = router(state.clone())
+            .oneshot(execute_request(&SkillExecuteRequest {
+                name: "deploy-db".to_string(),
+                args: Default::default(),
+                trust_tier: None,
+            }))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+        assert_eq!(running.await.unwrap().unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_skill_execute_endpoint() {
+        let mut skills = SkillRegistry::new();
+        skills.register(Box::new(crate::skill::IsolatedSkill::new(
+            "echo-arg",
+            "Echoes an argument",
+            vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "echo $CRUSTYCLAW_ARG_WHO; exit 2".to_string(),
+            ],
+            crate::isolation::SandboxConfig::new("ipc-exec").with_workdir("/tmp"),
+            Box::new(crate::isolation::NoopBackend),
+        )));
+        let app = router(test_state_with_skills(skills));
+
+        let mut args = serde_json::Map::new();
+        args.insert("who".to_string(), "world".into());
+        let req = execute_request(&SkillExecuteRequest {
+            name: "echo-arg".to_string(),
+            args,
+            trust_tier: None,
+        });
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let result: SkillExecuteResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(result.name, "echo-arg");
+        assert_eq!(result.exit_code, 2);
+        assert_eq!(result.stdout.trim(), "world");
+    }
+
+    #[tokio::test]
+    async fn test_skill_execute_unknown_skill() {
+        let app = router(test_state());
+        let req = execute_request(&SkillExecuteRequest {
+            name: "missing".to_string(),
+            args: serde_json::Map::new(),
+            trust_tier: None,
+        });
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_skill_execute_bad_trust_tier() {
+        let mut skills = SkillRegistry::new();
+        skills.register(Box::new(crate::skill::IsolatedSkill::new(
+            "noop",
+            "Does nothing",
+            vec!["true".to_string()],
+            crate::isolation::SandboxConfig::new("ipc-tier").with_workdir("/tmp"),
+            Box::new(crate::isolation::NoopBackend),
+        )));
+        let app = router(test_state_with_skills(skills));
+        let req = execute_request(&SkillExecuteRequest {
+            name: "noop".to_string(),
+            args: serde_json::Map::new(),
+            trust_tier: Some("sketchy".to_string()),
+        });
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let err: ErrorResponse = serde_json::from_slice(&body).unwrap();
+        assert!(err.error.contains("sketchy"));
+    }
+
+    #[tokio::test]
+    async fn test_skill_execute_refuses_trust_downgrade() {
+        let skill = |name: &str| {
+            crate::skill::IsolatedSkill::new(
+                name,
+                "Does nothing",
+                vec!["true".to_string()],
+                crate::isolation::SandboxConfig::new(name).with_workdir("/tmp"),
+                Box::new(crate::isolation::NoopBackend),
+            )
+        };
+        let mut skills = SkillRegistry::new();
+        skills.register(Box::new(
+            skill("internal").with_trust_tier(TrustTier::Internal),
+        ));
+        skills.register(Box::new(skill("untiered")));
+        let app = router(test_state_with_skills(skills));
+        let execute = |name: &str, tier: &str| {
+            app.clone().oneshot(execute_request(&SkillExecuteRequest {
+                name: name.to_string(),
+                args: serde_json::Map::new(),
+                trust_tier: Some(tier.to_string()),
+            }))
+        };
+
+        let resp = execute("internal", "trusted").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        // Skills without a tier of their own count as untrusted.
+        let resp = execute("untiered", "internal").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        // The skill's own tier keeps the backend it was loaded with.
+        let resp = execute("internal", "internal").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_skill_execute_stream_endpoint() {
+        let mut skills = SkillRegistry::new();
+        skills.register(Box::new(crate::skill::IsolatedSkill::new(
+            "count",
+            "Counts to two",
+            vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "echo 1; echo 2; exit 3".to_string(),
+            ],
+            crate::isolation::SandboxConfig::new("ipc-stream").with_workdir("/tmp"),
+            Box::new(crate::isolation::NoopBackend),
+        )));
+        let state = test_state_with_skills(skills);
+        let stream_request = |name: &str| {
+            Request::post("/skills/execute/stream")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    serde_json::to_string(&SkillExecuteRequest {
+                        name: name.to_string(),
+                        args: serde_json::Map::new(),
+                        trust_tier: None,
+                    })
+                    .unwrap(),
+                ))
+                .unwrap()
+        };
+
+        let resp = router(state.clone())
+            .oneshot(stream_request("count"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[header::CONTENT_TYPE], "text/event-stream");
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let events: Vec<SkillRunEvent> = std::str::from_utf8(&body)
+            .unwrap()
+            .split_terminator("\n\n")
+            .map(|frame| serde_json::from_str(frame.strip_prefix("data: ").unwrap()).unwrap())
+            .collect();
+        let output = |line: &str| SkillRunEvent::Output {
+            stream: "stdout".to_string(),
+            line: line.to_string(),
+        };
+        assert_eq!(events[..2], [output("1"), output("2")]);
+        assert!(matches!(
+            events[2],
+            SkillRunEvent::Done { exit_code: 3, .. }
+        ));
+        assert_eq!(events.len(), 3);
+
+        // An unknown skill is rejected before the stream starts.
+        let resp = router(state)
+            .oneshot(stream_request("missing"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_usage_endpoint() {
+        use crate::llm::TokenUsage;
+        use crate::usage::UsageAttribution;
+
+        let config = AppConfig::parse(
+            r#"
+            [llm.pricing."gpt-4o"]
+            prompt_per_mtok = 2.5
+            completion_per_mtok = 10.0
+        "#,
+        )
+        .unwrap();
+        let state = test_state_from(
+            config,
+            SkillRegistry::new(),
+            crate::logging::LogCollector::new(10).reader(),
+            WorkspaceStore::new(std::env::temp_dir().join("crustyclaw-test-usage")),
+        );
+        let attribution = UsageAttribution {
+            role: "operator".to_string(),
+            skill: None,
+            conversation: Some("chat-abc".to_string()),
+        };
+        let tokens = TokenUsage {
+            prompt_tokens: 400_000,
+            completion_tokens: 100_000,
+            total_tokens: 500_000,
+        };
+        state.usage.record(&attribution, "gpt-4o", &tokens, 2);
+        state.usage.record(&attribution, "llama3.1", &tokens, 1);
+        let app = router(state);
+
+        let resp = app
+            .clone()
+            .oneshot(Request::get("/usage").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let usage: UsageResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(usage.by, "model");
+        assert_eq!(usage.usage.len(), 2);
+        assert_eq!(usage.usage[0].key, "gpt-4o");
+        assert_eq!(usage.usage[0].cost_usd, 2.0);
+        assert_eq!(usage.usage[0].retries, 1);
+        assert_eq!(usage.usage[1].unpriced_tokens, 500_000);
+
+        let resp = app
+            .clone()
+            .oneshot(
+                Request::get("/usage?days=1&by=role")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let usage: UsageResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(usage.usage.len(), 1);
+        assert_eq!(usage.usage[0].key, "operator");
+        assert_eq!(usage.usage[0].requests, 2);
+
+        let resp = app
+            .oneshot(Request::get("/usage?by=day").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_quotas_endpoint() {
+        use crate::quota::QuotaKind;
+
+        let state = test_state();
+        state.quotas.record("user", QuotaKind::LlmTokens, 1234);
+        let resp = router(state)
+            .oneshot(Request::get("/quotas").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let quotas: QuotasResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(quotas.quotas.len(), 1);
+        assert_eq!(quotas.quotas[0].role, "user");
+        assert_eq!(quotas.quotas[0].kind, "llm_tokens");
+        assert_eq!(quotas.quotas[0].used, 1234);
+        assert_eq!(quotas.quotas[0].limit, None);
+        assert_eq!(quotas.quotas[0].window_secs, 86_400);
+    }
+
+    #[tokio::test]
+    async fn test_schedule_endpoints() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config = AppConfig::parse(
+            "[[schedules]]\nname = \"nightly\"\ncron = \"0 3 * * *\"\nskill = \"missing\"\n",
+        )
+        .unwrap();
+        let state = test_state_from(
+            config,
+            SkillRegistry::new(),
+            crate::logging::LogCollector::new(10).reader(),
+            WorkspaceStore::new(tmp.path()),
+        );
+        let app = router(state.clone());
+
+        let resp = app
+            .clone()
+            .oneshot(Request::get("/schedules").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let listing: SchedulesResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(listing.schedules.len(), 1);
+        assert_eq!(listing.schedules[0].name, "nightly");
+        assert_eq!(listing.schedules[0].target, "skill:missing");
+        assert!(listing.schedules[0].history.is_empty());
+
+        let run = |name: &str| {
+            Request::post(format!("/schedules/{name}/run"))
+                .body(Body::empty())
+                .unwrap()
+        };
+        let resp = app.clone().oneshot(run("nightly")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::ACCEPTED);
+        let resp = app.clone().oneshot(run("weekly")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        for _ in 0..100 {
+            if !state.scheduler.status()[0].history.is_empty() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let history = &state.scheduler.status()[0].history;
+        assert_eq!(history[0].outcome, crate::scheduler::RunOutcome::Failed);
+    }
+
+    #[tokio::test]
+    async fn test_metrics_endpoint() {
+        let state = test_state();
+        state
+            .metrics
+            .record_message(&crate::message::Envelope::new("cli", "hi"));
+        let resp = router(state)
+            .oneshot(Request::get("/metrics").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[header::CONTENT_TYPE], metrics::CONTENT_TYPE);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        assert!(
+            text.contains("crustyclaw_messages_total{channel=\"cli\",direction=\"inbound\"} 1"),
+            "{text}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_elevation_endpoints() {
+        use crate::context::{ElevationAsk, ToolRegistry, ToolTrust};
+
+        let state = test_state();
+        let ask = ElevationAsk {
+            tool: "run_command".to_string(),
+            arguments: serde_json::json!({"command": "cargo install ripgrep"}),
+            requested: ToolTrust::Trusted,
+            reason: "need rg for search".to_string(),
+        };
+        let id = state
+            .elevations
+            .request(&ToolRegistry::with_defaults(), ask, ToolTrust::Public, None)
+            .unwrap();
+        let decide = |decision: &str, target: u64| {
+            Request::post(format!("/elevations/{target}/{decision}"))
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"operator":"alice"}"#))
+                .unwrap()
+        };
+
+        let app = router(state.clone());
+        let resp = app
+            .clone()
+            .oneshot(Request::get("/elevations").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let listing: ElevationsResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(listing.requests.len(), 1);
+        assert_eq!(
+            listing.requests[0].preview,
+            "run_command: cargo install ripgrep"
+        );
+        assert_eq!(listing.requests[0].status, "pending");
+
+        let resp = app.clone().oneshot(decide("approve", id)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let info: ElevationInfo = serde_json::from_slice(&body).unwrap();
+        assert_eq!(info.status, "approved");
+        assert_eq!(info.decided_by.as_deref(), Some("alice"));
+
+        let resp = app.clone().oneshot(decide("deny", id)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+        let resp = app.oneshot(decide("approve", 99)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
     }
 
     #[tokio::test]
@@ -376,4 +3809,191 @@ mod tests {
         let iso: IsolationStatusResponse = serde_json::from_slice(&body).unwrap();
         assert!(!iso.backend.is_empty());
     }
+
+    #[tokio::test]
+    async fn test_sandboxes_endpoint() {
+        let state = test_state();
+        let config = crate::isolation::SandboxConfig::new("ipc-pool").with_workdir("/tmp");
+        state
+            .sandboxes
+            .execute(
+                &crate::isolation::NoopBackend,
+                &config,
+                &["true".to_string()],
+            )
+            .await
+            .unwrap();
+
+        let app = router(state);
+        let req = Request::get("/isolation/sandboxes")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let sandboxes: SandboxesResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(sandboxes.max_concurrent, 4);
+        assert_eq!(sandboxes.sandboxes.len(), 1);
+        assert_eq!(sandboxes.sandboxes[0].label, "ipc-pool");
+        assert_eq!(sandboxes.sandboxes[0].state, "finished");
+    }
+
+    #[tokio::test]
+    async fn test_logs_stream_endpoint() {
+        use http_body_util::BodyExt;
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let collector = crate::logging::LogCollector::new(100);
+        let state = test_state_with(
+            SkillRegistry::new(),
+            collector.reader(),
+            WorkspaceStore::new(std::env::temp_dir()),
+        );
+        let subscriber = tracing_subscriber::registry().with(collector);
+        let _guard = tracing::subscriber::set_default(subscriber);
+        tracing::info!("first");
+        tracing::warn!("second");
+
+        let app = router(state.clone());
+        let req = Request::get("/logs/stream?after=1")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/event-stream"
+        );
+
+        let mut body = resp.into_body();
+        let frame = body.frame().await.unwrap().unwrap();
+        let chunk = String::from_utf8(frame.into_data().unwrap().to_vec()).unwrap();
+        let data = chunk
+            .lines()
+            .find_map(|l| l.strip_prefix("data: "))
+            .unwrap();
+        let entry: LogEntry = serde_json::from_str(data).unwrap();
+        assert_eq!(entry.seq, 2);
+        assert_eq!(entry.level, "WARN");
+        assert_eq!(entry.message, "second");
+
+        tracing::error!("third");
+        let frame = body.frame().await.unwrap().unwrap();
+        let chunk = String::from_utf8(frame.into_data().unwrap().to_vec()).unwrap();
+        assert!(chunk.contains("\"third\""));
+
+        // Shutdown ends the stream.
+        state.shutdown_tx.send(ShutdownSignal).unwrap();
+        assert!(body.frame().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_files_endpoints_enforce_limits() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let store = WorkspaceStore::new(tmp.path()).with_max_file_bytes(8);
+        let app = router(test_state_with(
+            SkillRegistry::new(),
+            crate::logging::LogCollector::new(100).reader(),
+            store,
+        ));
+
+        let put = |path: &str, content_type: &str, body: &'static [u8]| {
+            Request::put(path)
+                .header("content-type", content_type)
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let resp = app
+            .clone()
+            .oneshot(put("/files/conv/a.txt", "text/plain", b"hi"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let resp = app
+            .clone()
+            .oneshot(put("/files/conv/big.txt", "text/plain", b"0123456789"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let resp = app
+            .clone()
+            .oneshot(put(
+                "/files/conv/app.bin",
+                "application/x-executable",
+                b"\x7fELF",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let resp = app
+            .clone()
+            .oneshot(put("/files/conv/..hidden", "text/plain", b"x"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let req = Request::get("/files/conv/a.txt")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[header::CONTENT_TYPE], "text/plain");
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"hi");
+    }
+
+    #[test]
+    fn test_message_event_routes() {
+        let config = AppConfig::parse(
+            r#"
+            [commands.roles]
+            "+15550001" = "operator"
+
+            [commands.aliases.deploy]
+            skill = "echo"
+            role = "operator"
+        "#,
+        )
+        .unwrap();
+        let commands = CommandRouter::from_config(&config.effective_commands());
+        let route = |envelope: &Envelope| message_event(envelope, &commands).route;
+
+        let deploy = Envelope::new("signal", "/deploy").with_peer("+15550001");
+        let event = message_event(&deploy, &commands);
+        assert!(event.inbound);
+        assert_eq!(event.body, "/deploy");
+        assert_eq!(
+            event.route,
+            MessageRoute::Command {
+                name: "deploy".to_string()
+            }
+        );
+        assert_eq!(
+            route(&Envelope::new("signal", "deploy").with_peer("+15559999")),
+            MessageRoute::Denied {
+                name: "deploy".to_string()
+            }
+        );
+        assert_eq!(route(&Envelope::new("signal", "/help")), MessageRoute::Help);
+        assert_eq!(
+            route(&Envelope::new("signal", "hi")),
+            MessageRoute::Unhandled
+        );
+        assert_eq!(route(&deploy.reply("deployed")), MessageRoute::Outbound);
+        let redaction = Envelope::redaction("signal", deploy.id);
+        assert_eq!(route(&redaction), MessageRoute::Redaction);
+        assert_eq!(
+            message_event(&redaction, &commands).redacts,
+            Some(deploy.id)
+        );
+    }
 }
@@ -0,0 +1,352 @@
+//! TLS transport for the remote control plane.
+//!
+//! With `[daemon.tls]` enabled the daemon also serves the IPC API on
+//! `daemon.listen_addr:daemon.listen_port` over rustls, and every client must
+//! present a certificate issued by `daemon.tls.client_ca_path`. The
+//! certificate is mapped to a policy role through `[auth.cert_map]` during
+//! the handshake; a certificate that maps to no role is dropped before any
+//! request is read. Each request is then authorized against the policy as
+//! `(role, read|write, <first path segment>)` — see
+//! [`serve_tls`](super::server::serve_tls).
+//!
+//! The same module builds the client side for [`IpcClient::tls`](super::IpcClient::tls).
+
+use std::collections::BTreeMap;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::connect_info::Connected;
+use axum::serve::IncomingStream;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{ClientConfig, RootCertStore, ServerConfig};
+use sha2::{Digest, Sha256};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, watch};
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::server::TlsStream;
+use tracing::{debug, info, warn};
+
+use crustyclaw_config::{AppConfig, DaemonTlsConfig, RemoteConfig};
+
+/// How long a client has to complete the TLS handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Handshaken connections waiting for the server to pick them up.
+const ACCEPT_BACKLOG: usize = 64;
+
+/// Errors building a TLS configuration.
+#[derive(Debug, thiserror::Error)]
+pub enum TlsError {
+    #[error("{0} is not set")]
+    Missing(&'static str),
+
+    #[error("failed to load {path}: {message}")]
+    Pem { path: PathBuf, message: String },
+
+    #[error("invalid TLS configuration: {0}")]
+    Config(String),
+}
+
+/// Build the server configuration for `[daemon.tls]`: the daemon's
+/// certificate, and mandatory client certificates chaining to
+/// `client_ca_path`.
+pub fn server_config(tls: &DaemonTlsConfig) -> Result<Arc<ServerConfig>, TlsError> {
+    let certs = load_certs(required(&tls.cert_path, "daemon.tls.cert_path")?)?;
+    let key = load_key(required(&tls.key_path, "daemon.tls.key_path")?)?;
+    let roots = load_roots(required(&tls.client_ca_path, "daemon.tls.client_ca_path")?)?;
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+        .build()
+        .map_err(|e| TlsError::Config(e.to_string()))?;
+    let config = ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| TlsError::Config(e.to_string()))?
+        .with_client_cert_verifier(verifier)
+        .with_single_cert(certs, key)
+        .map_err(|e| TlsError::Config(e.to_string()))?;
+    Ok(Arc::new(config))
+}
+
+/// Build the client configuration for `[remote]`: trust `ca_path` and
+/// present `cert_path`.
+pub fn client_config(remote: &RemoteConfig) -> Result<Arc<ClientConfig>, TlsError> {
+    let roots = load_roots(required(&remote.ca_path, "remote.ca_path")?)?;
+    let certs = load_certs(required(&remote.cert_path, "remote.cert_path")?)?;
+    let key = load_key(required(&remote.key_path, "remote.key_path")?)?;
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let config = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| TlsError::Config(e.to_string()))?
+        .with_root_certificates(roots)
+        .with_client_auth_cert(certs, key)
+        .map_err(|e| TlsError::Config(e.to_string()))?;
+    Ok(Arc::new(config))
+}
+
+fn required<'a>(value: &'a Option<String>, key: &'static str) -> Result<&'a Path, TlsError> {
+    value
+        .as_deref()
+        .filter(|v| !v.trim().is_empty())
+        .map(Path::new)
+        .ok_or(TlsError::Missing(key))
+}
+
+fn pem_error(path: &Path, message: impl ToString) -> TlsError {
+    TlsError::Pem {
+        path: path.to_path_buf(),
+        message: message.to_string(),
+    }
+}
+
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .map_err(|e| pem_error(path, e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| pem_error(path, e))?;
+    if certs.is_empty() {
+        return Err(pem_error(path, "no certificates found"));
+    }
+    Ok(certs)
+}
+
+fn load_key(path: &Path) -> Result<PrivateKeyDer<'static>, TlsError> {
+    PrivateKeyDer::from_pem_file(path).map_err(|e| pem_error(path, e))
+}
+
+fn load_roots(path: &Path) -> Result<RootCertStore, TlsError> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(path)? {
+        roots.add(cert).map_err(|e| pem_error(path, e))?;
+    }
+    Ok(roots)
+}
+
+/// The `sha256:<hex>` fingerprint of a DER certificate, as used for
+/// `[auth.cert_map]` keys.
+pub fn fingerprint(cert: &CertificateDer<'_>) -> String {
+    let digest = Sha256::digest(cert.as_ref());
+    let hex: String = digest.iter().map(|b| format!("{b:02x}")).collect();
+    format!("sha256:{hex}")
+}
+
+/// The role `[auth.cert_map]` assigns to a verified client certificate.
+///
+/// A fingerprint entry wins over a name entry; names are tried in map order
+/// and match the certificate's subject alternative names.
+pub fn role_for_cert(
+    cert_map: &BTreeMap<String, String>,
+    cert: &CertificateDer<'_>,
+) -> Option<String> {
+    let fp = fingerprint(cert);
+    if let Some((_, role)) = cert_map
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(&fp))
+    {
+        return Some(role.clone());
+    }
+    let cert = webpki::EndEntityCert::try_from(cert).ok()?;
+    cert_map
+        .iter()
+        .filter(|(key, _)| !key.starts_with("sha256:"))
+        .find(|(key, _)| {
+            ServerName::try_from(key.as_str())
+                .is_ok_and(|name| cert.verify_is_valid_for_subject_name(&name).is_ok())
+        })
+        .map(|(_, role)| role.clone())
+}
+
+/// A remote client whose certificate passed verification and mapped to a
+/// role. Available to handlers as `ConnectInfo<RemotePeer>`.
+#[derive(Debug, Clone)]
+pub struct RemotePeer {
+    /// Client address.
+    pub addr: SocketAddr,
+    /// Role from `[auth.cert_map]`.
+    pub role: String,
+}
+
+impl Connected<IncomingStream<'_, TlsListener>> for RemotePeer {
+    fn connect_info(stream: IncomingStream<'_, TlsListener>) -> Self {
+        stream.remote_addr().clone()
+    }
+}
+
+/// TCP listener that completes the TLS handshake and role mapping before
+/// handing a connection to axum.
+///
+/// Handshakes run in their own tasks, so a slow or hostile client cannot
+/// hold up other connections.
+pub struct TlsListener {
+    local_addr: SocketAddr,
+    accepted: mpsc::Receiver<(TlsStream<TcpStream>, RemotePeer)>,
+}
+
+impl TlsListener {
+    /// Bind `addr` and start accepting. `[auth.cert_map]` is read from
+    /// `config` on every handshake, so reloads apply to new connections.
+    pub async fn bind(
+        addr: (&str, u16),
+        tls: Arc<ServerConfig>,
+        config: watch::Receiver<AppConfig>,
+    ) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+        let (tx, accepted) = mpsc::channel(ACCEPT_BACKLOG);
+        tokio::spawn(accept_loop(listener, TlsAcceptor::from(tls), config, tx));
+        info!(%local_addr, "IPC TLS listener bound");
+        Ok(Self {
+            local_addr,
+            accepted,
+        })
+    }
+}
+
+impl axum::serve::Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = RemotePeer;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.accepted.recv().await {
+            Some(conn) => conn,
+            // The accept loop only stops once this listener is dropped.
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(RemotePeer {
+            addr: self.local_addr,
+            role: String::new(),
+        })
+    }
+}
+
+async fn accept_loop(
+    listener: TcpListener,
+    acceptor: TlsAcceptor,
+    config: watch::Receiver<AppConfig>,
+    tx: mpsc::Sender<(TlsStream<TcpStream>, RemotePeer)>,
+) {
+    loop {
+        let (tcp, addr) = tokio::select! {
+            _ = tx.closed() => return,
+            accepted = listener.accept() => match accepted {
+                Ok(conn) => conn,
+                Err(e) => {
+                    warn!(error = %e, "IPC TLS accept failed");
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            },
+        };
+        let acceptor = acceptor.clone();
+        let config = config.clone();
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            match tokio::time::timeout(HANDSHAKE_TIMEOUT, handshake(&acceptor, tcp, addr, &config))
+                .await
+            {
+                Ok(Some(conn)) => {
+                    let _ = tx.send(conn).await;
+                }
+                Ok(None) => {}
+                Err(_) => debug!(%addr, "IPC TLS handshake timed out"),
+            }
+        });
+    }
+}
+
+async fn handshake(
+    acceptor: &TlsAcceptor,
+    tcp: TcpStream,
+    addr: SocketAddr,
+    config: &watch::Receiver<AppConfig>,
+) -> Option<(TlsStream<TcpStream>, RemotePeer)> {
+    let stream = match acceptor.accept(tcp).await {
+        Ok(stream) => stream,
+        Err(e) => {
+            warn!(%addr, error = %e, "IPC TLS handshake failed");
+            return None;
+        }
+    };
+    // The verifier requires a client certificate, so one is always present.
+    let cert = stream.get_ref().1.peer_certificates()?.first()?.clone();
+    let role = role_for_cert(&config.borrow().auth.cert_map, &cert);
+    let Some(role) = role else {
+        warn!(%addr, fingerprint = %fingerprint(&cert), "Rejected client certificate not in auth.cert_map");
+        return None;
+    };
+    info!(%addr, %role, "IPC TLS client connected");
+    Some((stream, RemotePeer { addr, role }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crustyclaw_test_utils::tls::{self, TlsFixture};
+
+    fn cert(pem: &str) -> CertificateDer<'static> {
+        CertificateDer::from_pem_slice(pem.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_role_for_cert() {
+        let client = cert(tls::CLIENT_CERT);
+        let other = cert(tls::OTHER_CLIENT_CERT);
+
+        let mut map = BTreeMap::from([("ops-laptop".to_string(), "operator".to_string())]);
+        assert_eq!(role_for_cert(&map, &client).as_deref(), Some("operator"));
+        assert_eq!(role_for_cert(&map, &other), None);
+
+        // A fingerprint entry takes precedence over the name.
+        map.insert(fingerprint(&client).to_uppercase(), "admin".to_string());
+        assert_eq!(role_for_cert(&map, &client).as_deref(), Some("admin"));
+        assert!(fingerprint(&client).starts_with("sha256:"));
+        assert_eq!(fingerprint(&client).len(), 7 + 64);
+    }
+
+    #[test]
+    fn test_configs_from_fixtures() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let files = TlsFixture::write(tmp.path());
+        let path = |p: &Path| Some(p.display().to_string());
+
+        let server = DaemonTlsConfig {
+            enabled: true,
+            cert_path: path(&files.server_cert),
+            key_path: path(&files.server_key),
+            client_ca_path: path(&files.ca_cert),
+        };
+        server_config(&server).unwrap();
+
+        let remote = RemoteConfig {
+            addr: Some("127.0.0.1:9100".to_string()),
+            server_name: None,
+            ca_path: path(&files.ca_cert),
+            cert_path: path(&files.client_cert),
+            key_path: path(&files.client_key),
+        };
+        client_config(&remote).unwrap();
+
+        let missing = DaemonTlsConfig {
+            key_path: None,
+            ..server.clone()
+        };
+        assert!(matches!(
+            server_config(&missing),
+            Err(TlsError::Missing("daemon.tls.key_path"))
+        ));
+        let not_pem = DaemonTlsConfig {
+            cert_path: path(&files.server_key),
+            ..server
+        };
+        assert!(matches!(server_config(&not_pem), Err(TlsError::Pem { .. })));
+    }
+}
@@ -3,9 +3,75 @@
 //! These types are serialized as JSON over the Unix domain socket
 //! transport. Both the IPC server (daemon) and client (CLI/TUI) use
 //! these types.
+//!
+//! ## Versioning
+//!
+//! The API has a `major.minor` [`API_VERSION`]. Clients send theirs in the
+//! [`API_VERSION_HEADER`] header, and the daemon puts its own on every
+//! response and in [`HealthResponse::api_version`]. A request from another
+//! major version is refused; either side of a mismatch reports it with
+//! [`version_mismatch`]. Minor versions only add: a field added in one is
+//! `#[serde(default)]`, so an older peer's messages still parse, and
+//! unknown fields are ignored, so a newer peer's do too.
+
+use std::fmt;
 
 use serde::{Deserialize, Serialize};
 
+/// The IPC API version this build speaks, as `major.minor`.
+pub const API_VERSION: &str = "1.0";
+
+/// Request and response header carrying the sender's [`API_VERSION`].
+pub const API_VERSION_HEADER: &str = "x-crustyclaw-api-version";
+
+/// A parsed `major.minor` API version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ApiVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl ApiVersion {
+    /// The version this build speaks.
+    pub fn current() -> Self {
+        Self::parse(API_VERSION).expect("API_VERSION is major.minor")
+    }
+
+    /// Parse `major.minor`.
+    pub fn parse(s: &str) -> Option<Self> {
+        let (major, minor) = s.trim().split_once('.')?;
+        Some(Self {
+            major: major.parse().ok()?,
+            minor: minor.parse().ok()?,
+        })
+    }
+
+    /// Whether peers at `self` and `other` can talk: the same major version.
+    pub fn is_compatible(self, other: Self) -> bool {
+        self.major == other.major
+    }
+}
+
+impl fmt::Display for ApiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// The error for a client at `client` talking to a daemon at `daemon`,
+/// naming the side to upgrade.
+pub fn version_mismatch(client: ApiVersion, daemon: ApiVersion) -> String {
+    let older = if client < daemon {
+        "the CLI"
+    } else {
+        "the daemon"
+    };
+    format!(
+        "IPC API version mismatch: the client speaks {client} and the daemon {daemon}; \
+         upgrade {older} so both have the same major version"
+    )
+}
+
 /// Daemon health check response.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HealthResponse {
@@ -13,6 +79,69 @@ pub struct HealthResponse {
     pub version: String,
     pub git_hash: String,
     pub build_profile: String,
+    /// The daemon's [`API_VERSION`]; empty from daemons that predate it.
+    #[serde(default)]
+    pub api_version: String,
+}
+
+/// Build information of the daemon, from `GET /version`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionResponse {
+    pub version: String,
+    pub git_hash: String,
+    pub build_profile: String,
+    /// Unix time of the build, in seconds.
+    pub build_timestamp: u64,
+    /// Target triple, e.g. `x86_64-unknown-linux-gnu`.
+    pub target: String,
+    /// `rustc --version` of the compiler that built it.
+    pub rustc_version: String,
+    /// Enabled cargo features, sorted.
+    pub features: Vec<String>,
+    pub api_version: String,
+}
+
+impl VersionResponse {
+    /// The build information of this binary.
+    pub fn current() -> Self {
+        use crate::build_info;
+        Self {
+            version: build_info::VERSION.to_string(),
+            git_hash: build_info::GIT_HASH.to_string(),
+            build_profile: build_info::BUILD_PROFILE.to_string(),
+            build_timestamp: build_info::build_timestamp(),
+            target: build_info::TARGET.to_string(),
+            rustc_version: build_info::RUSTC_VERSION.to_string(),
+            features: build_info::features()
+                .into_iter()
+                .map(str::to_string)
+                .collect(),
+            api_version: API_VERSION.to_string(),
+        }
+    }
+}
+
+/// Liveness probe response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LivenessResponse {
+    pub status: String,
+    pub uptime_secs: u64,
+}
+
+/// A component's readiness.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComponentInfo {
+    pub name: String,
+    /// `up`, `down`, or `disabled`.
+    pub status: String,
+    pub detail: String,
+}
+
+/// Readiness probe response, served with 503 when not ready.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadinessResponse {
+    pub ready: bool,
+    pub components: Vec<ComponentInfo>,
 }
 
 /// Daemon runtime status.
@@ -30,6 +159,35 @@ pub struct StatusResponse {
     pub skills_count: usize,
     pub plugins_count: usize,
     pub pid: u32,
+    pub warnings: Vec<WarningInfo>,
+}
+
+/// A non-fatal startup warning.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WarningInfo {
+    pub kind: String,
+    pub key: String,
+    pub message: String,
+}
+
+/// Disk usage for a monitored daemon directory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiskInfo {
+    pub label: String,
+    pub path: String,
+    pub total_bytes: Option<u64>,
+    pub free_bytes: Option<u64>,
+}
+
+/// Host metrics response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HostStatusResponse {
+    pub load_avg: Option<[f64; 3]>,
+    pub cpu_count: usize,
+    pub memory_total_bytes: Option<u64>,
+    pub memory_available_bytes: Option<u64>,
+    pub disks: Vec<DiskInfo>,
+    pub open_fds: Option<usize>,
 }
 
 /// Daemon shutdown response.
@@ -39,15 +197,63 @@ pub struct StopResponse {
     pub message: String,
 }
 
+/// `POST /auth/login` request.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LoginRequest {
+    /// Token lifetime in seconds; `auth.token_ttl_secs` when absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl_secs: Option<u64>,
+}
+
+/// `POST /auth/login` response: a session token minted by the daemon for
+/// the connecting user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginResponse {
+    /// The signed token, to send as `Authorization: Bearer <token>`.
+    pub token: String,
+    /// Who the token identifies.
+    pub identity: String,
+    /// Roles the token grants.
+    pub roles: Vec<String>,
+    /// Unix time after which the token is rejected.
+    pub expires_at: u64,
+}
+
+/// `PUT /logging/level` request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogLevelRequest {
+    /// New filter, in `RUST_LOG` syntax (e.g. `debug` or
+    /// `info,crustyclaw_core::skill=trace`).
+    pub level: String,
+}
+
+/// `PUT /logging/level` response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogLevelResponse {
+    pub level: String,
+    pub previous: String,
+}
+
 /// Log entry from the daemon.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LogEntry {
-    pub timestamp: String,
+    /// Sequence number assigned by the daemon's log collector.
+    pub seq: u64,
+    /// Seconds since the daemon's log collector was created.
+    pub elapsed_secs: f64,
     pub level: String,
     pub target: String,
     pub message: String,
 }
 
+/// Query parameters for `GET /logs/stream`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LogStreamQuery {
+    /// Only replay buffered entries with a sequence number above this one.
+    #[serde(default)]
+    pub after: Option<u64>,
+}
+
 /// Log listing response.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LogsResponse {
@@ -55,6 +261,78 @@ pub struct LogsResponse {
     pub total: usize,
 }
 
+/// Sections `GET /snapshot` can include.
+pub const SNAPSHOT_SECTIONS: &[&str] = &[
+    "status",
+    "isolation",
+    "host",
+    "skills",
+    "plugins",
+    "conversations",
+    "history",
+    "logs",
+];
+
+/// Query parameters for `GET /snapshot`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SnapshotQuery {
+    /// Comma-separated [`SNAPSHOT_SECTIONS`] to include; all of them when
+    /// unset.
+    #[serde(default)]
+    pub include: Option<String>,
+    /// Conversation whose history the `history` section holds; the most
+    /// recently active one when unset.
+    #[serde(default)]
+    pub conversation: Option<String>,
+    /// Only include log entries with a sequence number above this one.
+    #[serde(default)]
+    pub logs_after: Option<u64>,
+}
+
+/// Several endpoints' responses in one round trip: `GET /snapshot`.
+///
+/// Each section is what its own endpoint returns. Sections that were not
+/// requested are absent; requested ones that could not be produced are
+/// listed in `omitted` instead.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SnapshotResponse {
+    /// `GET /status`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<StatusResponse>,
+    /// `GET /isolation`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub isolation: Option<IsolationStatusResponse>,
+    /// `GET /status/host`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<HostStatusResponse>,
+    /// `GET /skills`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skills: Option<SkillsResponse>,
+    /// `GET /plugins`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plugins: Option<PluginsResponse>,
+    /// `GET /conversations`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conversations: Option<ConversationsResponse>,
+    /// `GET /conversations/{id}`; absent when there is no conversation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history: Option<ConversationResponse>,
+    /// Buffered log entries.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logs: Option<LogsResponse>,
+    /// Requested sections left out, and why.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub omitted: Vec<SnapshotOmission>,
+}
+
+/// A `GET /snapshot` section that was requested but left out.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotOmission {
+    pub section: String,
+    /// The policy denial or the error the section's endpoint returned.
+    pub reason: String,
+}
+
 /// Policy evaluation request.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PolicyEvalRequest {
@@ -68,6 +346,27 @@ pub struct PolicyEvalRequest {
 pub struct PolicyEvalResponse {
     pub decision: String,
     pub rule_count: usize,
+    /// ID of the rule that decided, if it has one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule_id: Option<String>,
+    /// Reason given by the rule that decided, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl PolicyEvalResponse {
+    /// The response for `verdict`, from an engine with `rule_count` rules.
+    pub fn from_verdict(
+        verdict: crustyclaw_config::policy::PolicyVerdict,
+        rule_count: usize,
+    ) -> Self {
+        Self {
+            decision: verdict.decision.as_str().to_string(),
+            rule_count,
+            rule_id: verdict.rule_id,
+            reason: verdict.reason,
+        }
+    }
 }
 
 /// Plugin info.
@@ -90,12 +389,76 @@ pub struct SkillInfo {
     pub name: String,
     pub description: String,
     pub isolated: bool,
+    /// Mutual-exclusion class, if the skill declares one.
+    #[serde(default)]
+    pub concurrency_class: Option<String>,
+}
+
+/// State of a skill concurrency class lock.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkillLockInfo {
+    pub class: String,
+    /// Skill currently holding the class.
+    pub holder: Option<String>,
+    /// Seconds the current holder has been running.
+    pub held_secs: Option<u64>,
+    /// Skills waiting for the class, in run order.
+    pub queued: Vec<String>,
 }
 
 /// Skill listing response.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SkillsResponse {
     pub skills: Vec<SkillInfo>,
+    /// Concurrency classes that have been used since startup.
+    #[serde(default)]
+    pub locks: Vec<SkillLockInfo>,
+}
+
+/// Skill execution request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkillExecuteRequest {
+    pub name: String,
+    #[serde(default)]
+    pub args: serde_json::Map<String, serde_json::Value>,
+    #[serde(default)]
+    pub trust_tier: Option<String>,
+}
+
+/// Skill execution result.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkillExecuteResponse {
+    pub name: String,
+    pub exit_code: i32,
+    pub stdout: String,
+    pub stderr: String,
+    pub elapsed_ms: u64,
+    pub peak_memory_bytes: Option<u64>,
+    /// CPU time used, user plus system, where the backend measures it.
+    #[serde(default)]
+    pub cpu_time_ms: Option<u64>,
+}
+
+/// One server-sent event of a `POST /skills/execute/stream` run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SkillRunEvent {
+    /// A line of output, without its line ending.
+    Output {
+        /// `stdout` or `stderr`.
+        stream: String,
+        line: String,
+    },
+    /// The skill exited; the last event of a completed run.
+    Done {
+        exit_code: i32,
+        elapsed_ms: u64,
+        peak_memory_bytes: Option<u64>,
+        #[serde(default)]
+        cpu_time_ms: Option<u64>,
+    },
+    /// The skill could not be run; the last event of a failed run.
+    Error { error: String },
 }
 
 /// Isolation backend status.
@@ -108,6 +471,27 @@ pub struct IsolationStatusResponse {
     pub timeout_secs: u64,
     pub network_policy: String,
     pub max_concurrent: usize,
+    pub running: usize,
+    pub queued: usize,
+}
+
+/// A single execution tracked by the sandbox pool.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SandboxRunInfo {
+    pub id: u64,
+    pub label: String,
+    pub backend: String,
+    pub state: String,
+    pub age_secs: u64,
+    pub exit_code: Option<i32>,
+    pub error: Option<String>,
+}
+
+/// Sandbox pool listing response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SandboxesResponse {
+    pub max_concurrent: usize,
+    pub sandboxes: Vec<SandboxRunInfo>,
 }
 
 /// Configuration response (serialized TOML).
@@ -116,6 +500,319 @@ pub struct ConfigResponse {
     pub toml: String,
 }
 
+/// `PUT /config` request: keys to set and hot-reload.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigUpdateRequest {
+    /// `KEY=VALUE` pairs in `--set` syntax, e.g. `daemon.listen_port=9200`.
+    pub set: Vec<String>,
+}
+
+/// Metadata for a file in a conversation workspace.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileInfo {
+    pub name: String,
+    pub size: u64,
+    pub content_type: String,
+}
+
+/// Conversation workspace listing response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileListResponse {
+    pub conversation: String,
+    pub files: Vec<FileInfo>,
+}
+
+/// A stored conversation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationInfo {
+    pub id: String,
+    pub channel: String,
+    pub peer: Option<String>,
+    pub messages: usize,
+    pub started_secs: u64,
+    pub updated_secs: u64,
+    pub preview: String,
+}
+
+/// Conversation listing response, most recently active first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationsResponse {
+    pub conversations: Vec<ConversationInfo>,
+}
+
+/// A recorded message in a conversation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationMessage {
+    pub id: u64,
+    pub timestamp_secs: u64,
+    pub inbound: bool,
+    pub channel: String,
+    pub body: String,
+}
+
+/// A conversation's history, oldest message first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationResponse {
+    pub id: String,
+    pub messages: Vec<ConversationMessage>,
+}
+
+/// A remembered fact.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryFact {
+    pub key: String,
+    pub value: String,
+    pub updated_secs: u64,
+}
+
+/// A conversation's long-term memory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryResponse {
+    pub id: String,
+    pub summary: String,
+    pub facts: Vec<MemoryFact>,
+    pub runs: u64,
+    pub updated_secs: u64,
+}
+
+/// How the daemon handled a message on the bus.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MessageRoute {
+    /// `/help`: the sender's commands are listed.
+    Help,
+    /// A `[commands]` alias the sender may run.
+    Command { name: String },
+    /// A `[commands]` alias the sender's role does not allow.
+    Denied { name: String },
+    /// No alias matched; the message is only recorded.
+    Unhandled,
+    /// A message the daemon sent.
+    Outbound,
+    /// The removal of an earlier message's content.
+    Redaction,
+}
+
+/// One server-sent event of `GET /messages/stream`: an envelope on the
+/// message bus.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MessageEvent {
+    pub id: u64,
+    pub timestamp_secs: u64,
+    pub channel: String,
+    pub inbound: bool,
+    /// The sender of an inbound message or the recipient of an outbound one.
+    pub peer: Option<String>,
+    pub body: String,
+    /// For a redaction, the ID of the message it removes.
+    #[serde(default)]
+    pub redacts: Option<u64>,
+    pub route: MessageRoute,
+}
+
+/// A role's consumption of one quota in the current window.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuotaInfo {
+    pub role: String,
+    /// `llm_tokens` or `sandbox_executions`.
+    pub kind: String,
+    pub used: u64,
+    /// `None` when the role has no limit for this kind.
+    pub limit: Option<u64>,
+    pub window_secs: u64,
+    pub resets_in_secs: u64,
+}
+
+/// Quota usage response, sorted by role and kind.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuotasResponse {
+    pub quotas: Vec<QuotaInfo>,
+}
+
+/// Query parameters for `GET /usage`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageQuery {
+    /// UTC days to report, today included (default 7).
+    #[serde(default)]
+    pub days: Option<u32>,
+    /// `model` (default), `role`, `skill`, or `conversation`.
+    #[serde(default)]
+    pub by: Option<String>,
+}
+
+/// Token usage of one model, role, skill, or conversation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageInfo {
+    /// The model, role, skill, or conversation; `-` for none.
+    pub key: String,
+    pub requests: u64,
+    /// Extra attempts those requests took: retries and fallbacks.
+    #[serde(default)]
+    pub retries: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Estimated cost in USD from `[llm.pricing]`.
+    pub cost_usd: f64,
+    /// Tokens of models without a price, not included in `cost_usd`.
+    pub unpriced_tokens: u64,
+}
+
+/// Token usage response, sorted by key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageResponse {
+    /// First UTC day covered, as `YYYY-MM-DD`.
+    pub since: String,
+    /// What the usage is grouped by.
+    pub by: String,
+    pub usage: Vec<UsageInfo>,
+}
+
+/// A task run by the daemon's supervisor.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SupervisedTaskInfo {
+    pub name: String,
+    /// `running`, `backoff`, or `stopped`.
+    pub state: String,
+    pub restarts: u32,
+    pub last_error: Option<String>,
+    /// Seconds since the task entered `state`.
+    pub state_secs: u64,
+}
+
+/// Supervised task listing response, sorted by name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SupervisorResponse {
+    pub tasks: Vec<SupervisedTaskInfo>,
+}
+
+/// Schedule listing response, in config order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchedulesResponse {
+    pub schedules: Vec<ScheduleInfo>,
+}
+
+/// A `[[schedules]]` entry and its recent runs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleInfo {
+    pub name: String,
+    pub cron: String,
+    /// `skill:<name>` or `prompt`.
+    pub target: String,
+    pub enabled: bool,
+    pub running: bool,
+    /// Unix time of the next cron run, if enabled.
+    pub next_run: Option<u64>,
+    /// Most recent first.
+    pub history: Vec<ScheduleRunInfo>,
+}
+
+/// One run of a schedule.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleRunInfo {
+    /// Unix time the run started.
+    pub started_at: u64,
+    pub duration_ms: u64,
+    /// `cron` or `manual`.
+    pub trigger: String,
+    /// `succeeded`, `failed`, or `skipped`.
+    pub outcome: String,
+    pub message: String,
+}
+
+/// A webhook message accepted onto the bus.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookAcceptedResponse {
+    /// ID of the published envelope.
+    pub id: u64,
+}
+
+/// Body of `POST /chat`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatSendRequest {
+    /// Session to continue; a new session is started when absent.
+    #[serde(default)]
+    pub session: Option<String>,
+    pub message: String,
+    /// Only these tools are offered in the session; every tool the caller
+    /// may use when absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_tools: Option<Vec<String>>,
+    /// Tools never offered in the session.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub denied_tools: Vec<String>,
+}
+
+/// One server-sent event of a `POST /chat` stream.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChatEvent {
+    /// The session the message runs in; always the first event.
+    Session { id: String },
+    /// A request is being sent to the model.
+    Thinking { iteration: u32 },
+    /// Part of the model's text.
+    Text { delta: String },
+    /// The model asked for a tool.
+    ToolCall { name: String },
+    /// A tool call finished.
+    ToolResult { name: String, ok: bool },
+    /// The final answer; the last event of a successful run.
+    Done {
+        reply: String,
+        iterations: u32,
+        total_tokens: u32,
+    },
+    /// The run failed; the last event of a failed run.
+    Error { error: String },
+}
+
+/// A tool offered to the caller in chat sessions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatToolInfo {
+    pub name: String,
+    pub description: String,
+    /// `public`, `internal`, `trusted`, or `system`.
+    pub trust: String,
+}
+
+/// Chat tool listing response, sorted by name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatToolsResponse {
+    pub tools: Vec<ChatToolInfo>,
+}
+
+/// A tool trust elevation request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ElevationInfo {
+    pub id: u64,
+    pub tool: String,
+    pub preview: String,
+    pub current: String,
+    pub requested: String,
+    pub reason: String,
+    pub conversation: Option<String>,
+    pub status: String,
+    pub decided_by: Option<String>,
+    pub denial_reason: Option<String>,
+    pub age_secs: u64,
+}
+
+/// Elevation request listing response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ElevationsResponse {
+    pub requests: Vec<ElevationInfo>,
+}
+
+/// Body of an approve or deny request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ElevationDecisionRequest {
+    /// Operator making the decision, recorded in the audit trail.
+    pub operator: String,
+    /// Reason for a denial.
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
 /// Generic error response.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ErrorResponse {
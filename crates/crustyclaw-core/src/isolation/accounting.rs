@@ -0,0 +1,266 @@
+//! Resource accounting for sandboxed commands.
+//!
+//! Each backend measures what a command used in the way its platform
+//! allows, filling [`SandboxResult::peak_memory_bytes`] and
+//! [`SandboxResult::cpu_time`](super::SandboxResult::cpu_time):
+//!
+//! - **noop** — the exited process's `rusage` (its own usage plus that of
+//!   the children it waited for), collected with `waitid(WNOWAIT)` before
+//!   the process is reaped. Linux only.
+//! - **linux-ns** — the sandbox cgroup's `memory.peak` and `cpu.stat`.
+//! - **docker** — `docker stats` samples taken while the container runs;
+//!   the peak is the highest sampled usage and CPU time is integrated from
+//!   the sampled CPU percentage, so both are approximate for short runs.
+//!
+//! [`SandboxResult::peak_memory_bytes`]: super::SandboxResult::peak_memory_bytes
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Resources a sandboxed command used, where measurable.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    /// Peak memory in bytes.
+    pub peak_memory_bytes: Option<u64>,
+    /// CPU time, user plus system.
+    pub cpu_time: Option<Duration>,
+}
+
+// ── rusage (noop) ───────────────────────────────────────────────────────
+
+/// Wait for child `pid` to exit, without reaping it, and return its
+/// resource usage. The caller reaps it afterwards as usual.
+#[cfg(target_os = "linux")]
+pub(crate) async fn wait_rusage(pid: u32) -> ResourceUsage {
+    tokio::task::spawn_blocking(move || waitid_rusage(pid))
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+}
+
+/// `rusage` is only available through `waitid` on Linux.
+#[cfg(not(target_os = "linux"))]
+pub(crate) async fn wait_rusage(_pid: u32) -> ResourceUsage {
+    ResourceUsage::default()
+}
+
+#[cfg(target_os = "linux")]
+#[allow(unsafe_code)]
+fn waitid_rusage(pid: u32) -> Option<ResourceUsage> {
+    // SAFETY: both out-pointers reference zeroed locals that outlive the
+    // call. The raw syscall is used because only the kernel's waitid (not
+    // the libc wrapper) fills in the rusage; WNOWAIT leaves the child
+    // waitable, so tokio still reaps it and sees its exit status.
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    loop {
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_waitid,
+                libc::P_PID,
+                pid as libc::id_t,
+                &mut info as *mut libc::siginfo_t,
+                libc::WEXITED | libc::WNOWAIT,
+                &mut usage as *mut libc::rusage,
+            )
+        };
+        if ret == 0 {
+            break;
+        }
+        if std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted {
+            return None;
+        }
+    }
+    let timeval = |tv: libc::timeval| {
+        Duration::from_secs(tv.tv_sec as u64) + Duration::from_micros(tv.tv_usec as u64)
+    };
+    Some(ResourceUsage {
+        // ru_maxrss is in KiB on Linux.
+        peak_memory_bytes: Some(usage.ru_maxrss as u64 * 1024),
+        cpu_time: Some(timeval(usage.ru_utime) + timeval(usage.ru_stime)),
+    })
+}
+
+// ── cgroup v2 (linux-ns) ────────────────────────────────────────────────
+
+/// Read the usage recorded by the cgroup at `dir`: `memory.peak` and the
+/// `usage_usec` line of `cpu.stat`. Missing files leave fields `None`.
+pub(crate) fn read_cgroup(dir: &Path) -> ResourceUsage {
+    let read = |name: &str| std::fs::read_to_string(dir.join(name)).ok();
+    ResourceUsage {
+        peak_memory_bytes: read("memory.peak").and_then(|text| text.trim().parse().ok()),
+        cpu_time: read("cpu.stat").and_then(|text| parse_cpu_stat(&text)),
+    }
+}
+
+/// Total CPU time from a cgroup v2 `cpu.stat` file.
+fn parse_cpu_stat(text: &str) -> Option<Duration> {
+    text.lines().find_map(|line| {
+        let micros = line.strip_prefix("usage_usec ")?;
+        micros.trim().parse().ok().map(Duration::from_micros)
+    })
+}
+
+// ── docker stats (docker) ───────────────────────────────────────────────
+
+/// Interval between `docker stats` samples of a running container.
+pub(crate) const DOCKER_STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Accumulates `docker stats` samples of one container.
+#[derive(Debug)]
+pub(crate) struct DockerStats {
+    usage: ResourceUsage,
+    last_sample: Instant,
+}
+
+impl DockerStats {
+    /// Start accounting for a container started at `started`.
+    pub fn new(started: Instant) -> Self {
+        Self {
+            usage: ResourceUsage::default(),
+            last_sample: started,
+        }
+    }
+
+    /// Take one sample of container `name`. A container that has already
+    /// exited (or a failing `docker stats`) adds nothing.
+    pub async fn sample(&mut self, docker_bin: &Path, name: &str) {
+        let output = tokio::process::Command::new(docker_bin)
+            .args([
+                "stats",
+                "--no-stream",
+                "--format",
+                "{{.MemUsage}}|{{.CPUPerc}}",
+                name,
+            ])
+            .stdin(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .output()
+            .await;
+        let now = Instant::now();
+        if let Ok(output) = output
+            && output.status.success()
+            && let Some((memory, cpu_percent)) =
+                parse_docker_stats(&String::from_utf8_lossy(&output.stdout))
+        {
+            self.record(memory, cpu_percent, now - self.last_sample);
+        }
+        self.last_sample = now;
+    }
+
+    /// Record a sample of `memory` bytes in use and `cpu_percent` of one
+    /// core used over the `period` since the previous sample.
+    fn record(&mut self, memory: u64, cpu_percent: f64, period: Duration) {
+        let peak = self.usage.peak_memory_bytes.unwrap_or(0).max(memory);
+        self.usage.peak_memory_bytes = Some(peak);
+        let cpu = period.mul_f64((cpu_percent / 100.0).max(0.0));
+        self.usage.cpu_time = Some(self.usage.cpu_time.unwrap_or_default() + cpu);
+    }
+
+    /// The usage sampled so far.
+    pub fn usage(&self) -> ResourceUsage {
+        self.usage
+    }
+}
+
+/// Parse a `{{.MemUsage}}|{{.CPUPerc}}` line (`12.5MiB / 1GiB|3.25%`) into
+/// bytes in use and CPU percentage.
+fn parse_docker_stats(line: &str) -> Option<(u64, f64)> {
+    let (memory, cpu) = line.trim().split_once('|')?;
+    let (used, _limit) = memory.split_once('/')?;
+    let cpu = cpu.trim().strip_suffix('%')?.parse().ok()?;
+    Some((parse_size(used.trim())?, cpu))
+}
+
+/// Parse a size as printed by `docker stats` (`512B`, `1.5KiB`, `3MB`).
+fn parse_size(text: &str) -> Option<u64> {
+    let split = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let scale: f64 = match unit {
+        "B" | "" => 1.0,
+        "KiB" => 1024.0,
+        "MiB" => 1024.0 * 1024.0,
+        "GiB" => 1024.0 * 1024.0 * 1024.0,
+        "TiB" => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+        "kB" | "KB" => 1e3,
+        "MB" => 1e6,
+        "GB" => 1e9,
+        "TB" => 1e12,
+        _ => return None,
+    };
+    Some((number * scale) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cpu_stat() {
+        let text = "usage_usec 1500000\nuser_usec 1000000\nsystem_usec 500000\n";
+        assert_eq!(parse_cpu_stat(text), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_cpu_stat("nr_periods 0\n"), None);
+    }
+
+    #[test]
+    fn test_read_cgroup() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(read_cgroup(dir.path()), ResourceUsage::default());
+
+        std::fs::write(dir.path().join("memory.peak"), "4194304\n").unwrap();
+        std::fs::write(dir.path().join("cpu.stat"), "usage_usec 250\n").unwrap();
+        assert_eq!(
+            read_cgroup(dir.path()),
+            ResourceUsage {
+                peak_memory_bytes: Some(4 * 1024 * 1024),
+                cpu_time: Some(Duration::from_micros(250)),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_docker_stats() {
+        assert_eq!(
+            parse_docker_stats("12.5MiB / 1GiB|3.25%\n"),
+            Some((13_107_200, 3.25))
+        );
+        assert_eq!(parse_docker_stats("512B / 64MB|0.00%"), Some((512, 0.0)));
+        assert_eq!(parse_docker_stats("1.5kB / 2GB|150%"), Some((1500, 150.0)));
+        assert_eq!(parse_docker_stats("-- / --|--"), None);
+        assert_eq!(parse_size("3XB"), None);
+    }
+
+    #[test]
+    fn test_docker_stats_accumulates() {
+        let mut stats = DockerStats::new(Instant::now());
+        stats.record(100, 50.0, Duration::from_secs(2));
+        stats.record(300, 200.0, Duration::from_secs(1));
+        stats.record(200, 0.0, Duration::from_secs(1));
+        assert_eq!(
+            stats.usage(),
+            ResourceUsage {
+                peak_memory_bytes: Some(300),
+                cpu_time: Some(Duration::from_secs(3)),
+            }
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_wait_rusage_leaves_child_waitable() {
+        let mut child = tokio::process::Command::new("dd")
+            .args(["if=/dev/zero", "of=/dev/null", "bs=16M", "count=4"])
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .unwrap();
+        let usage = wait_rusage(child.id().unwrap()).await;
+        // dd allocates its 16 MiB block buffer.
+        assert!(usage.peak_memory_bytes.unwrap() >= 16 * 1024 * 1024);
+        assert!(usage.cpu_time.is_some());
+        assert!(child.wait().await.unwrap().success());
+    }
+}
@@ -3,6 +3,8 @@
 //! On macOS, uses `Virtualization.framework` to create lightweight VMs for
 //! skill isolation. Each sandbox is a minimal Linux VM image booted via
 //! `VZLinuxBootLoader` with shared directories exposed as virtio-fs mounts.
+//! Commands run through the guest agent over a virtio socket device (see
+//! [`GuestAgentClient`](super::GuestAgentClient)).
 //!
 //! On non-macOS platforms, [`available()`](super::SandboxBackend::available)
 //! returns `false`.
@@ -63,6 +65,8 @@ impl SandboxBackend for AppleVzBackend {
             // - VZVirtioFileSystemDeviceConfiguration for shared mounts
             // - VZNetworkDeviceConfiguration based on NetworkPolicy
             // - VZMemoryBalloonDeviceConfiguration for memory limits
+            // - VZVirtioSocketDeviceConfiguration; connect to the guest agent
+            //   on GUEST_AGENT_PORT and drive it with GuestAgentClient::handshake
             Err(IsolationError::UnsupportedBackend(
                 "Apple Virtualization FFI not yet implemented; \
                  requires macOS 12+ and Virtualization.framework bridge"
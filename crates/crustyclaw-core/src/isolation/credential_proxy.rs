@@ -61,10 +61,35 @@ impl CredentialProxy {
         }
     }
 
+    /// Build a proxy with one mapping per env-injected secret in `store`.
+    ///
+    /// Called at startup and again after every secrets reload so that the
+    /// sentinel mappings always match the current store contents.
+    pub fn from_store(store: &crate::secrets::SecretStore) -> Self {
+        use crate::secrets::InjectionMethod;
+
+        let mut names = store.names();
+        names.sort_unstable();
+
+        let mut proxy = Self::new();
+        for name in names {
+            let Some(entry) = store.get(name) else {
+                continue;
+            };
+            match &entry.injection {
+                InjectionMethod::Env(env_name) | InjectionMethod::Both { env_name, .. } => {
+                    proxy.add_mapping(name, env_name.clone());
+                }
+                InjectionMethod::File(_) => {}
+            }
+        }
+        proxy
+    }
+
     /// Generate a deterministic sentinel value for a credential name.
     ///
     /// Sentinel format: `__CRUSTYCLAW_SENTINEL_<name>__`
-    fn sentinel_for(name: &str) -> String {
+    pub(crate) fn sentinel_for(name: &str) -> String {
         format!("__CRUSTYCLAW_SENTINEL_{name}__")
     }
 
@@ -290,6 +315,45 @@ mod tests {
         assert!(found.is_empty());
     }
 
+    #[test]
+    fn test_from_store_maps_env_secrets() {
+        let mut store = SecretStore::new();
+        for (name, injection) in [
+            ("b_key", InjectionMethod::Env("B_KEY".to_string())),
+            (
+                "a_file",
+                InjectionMethod::File(std::path::PathBuf::from("/run/secrets/a")),
+            ),
+            (
+                "a_both",
+                InjectionMethod::Both {
+                    env_name: "A_BOTH".to_string(),
+                    file_path: std::path::PathBuf::from("/run/secrets/both"),
+                },
+            ),
+        ] {
+            store
+                .insert(
+                    SecretEntry {
+                        name: name.to_string(),
+                        value: SecretValue::new("value"),
+                        injection,
+                        description: String::new(),
+                    },
+                    SecretSource::Config,
+                )
+                .unwrap();
+        }
+
+        let proxy = CredentialProxy::from_store(&store);
+        let envs: Vec<&str> = proxy
+            .mappings()
+            .iter()
+            .map(|m| m.env_name.as_str())
+            .collect();
+        assert_eq!(envs, vec!["A_BOTH", "B_KEY"]);
+    }
+
     #[test]
     fn test_empty_proxy() {
         let proxy = CredentialProxy::new();
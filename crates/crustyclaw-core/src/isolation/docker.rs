@@ -14,29 +14,100 @@
 //! | Memory limits | `--memory`, `--memory-swap` |
 //! | PID limits | `--pids-limit` |
 //! | Filesystem | `--volume` (ro/rw), `--workdir` |
-//! | Network | `--network none/host/bridge` |
+//! | Network | `--network none/host/bridge`; allowlists via a firewall sidecar |
 //! | Timeout | Container killed after wall-clock deadline |
-//! | Cleanup | Container auto-removed (`--rm`) |
+//! | Cleanup | Container auto-removed (`--rm`); orphans reaped at startup by label |
+//!
+//! Each container gets a unique `--name`. If an execution is dropped before
+//! the container exits — on timeout, or when the pool kills it at shutdown —
+//! the container is stopped with `docker kill`.
+//!
+//! ## Network allowlists
+//!
+//! [`NetworkPolicy::AllowList`] needs a firewall image (see
+//! [`DockerSandboxBackend::with_firewall_image`]) providing `nft`. A
+//! sidecar container from that image is started on the bridge network
+//! with `NET_ADMIN`, the allowlist ruleset is loaded into it, and the
+//! skill container joins its network namespace with
+//! `--network container:<sidecar>`. The skill container does not get
+//! `NET_ADMIN`, so it cannot change the rules. Without a firewall image,
+//! allowlists are rejected.
+//!
+//! ## Images
+//!
+//! With an [`ImagePolicy`] (see [`DockerSandboxBackend::with_image_policy`]),
+//! each image is checked, pulled if missing and allowed, and pinned to its
+//! image ID before its first run; see the [`image`](super::image) module.
+//! Without one, `docker run` pulls missing images itself.
 
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 
 use crate::BoxFuture;
 
+use super::accounting::{DOCKER_STATS_INTERVAL, DockerStats};
+use super::image::{self, ImagePolicy};
+use super::network::{self, Cidr};
 use super::{
-    IsolationError, MountAccess, NetworkPolicy, SandboxBackend, SandboxConfig, SandboxResult,
+    IsolationError, MountAccess, NetworkPolicy, OutputSender, SandboxBackend, SandboxConfig,
+    SandboxResult, wait_streaming,
 };
 
+/// Label key set on every container the backend creates.
+const SANDBOX_LABEL: &str = "crustyclaw.sandbox";
+
+/// Suffix of the next container name.
+static NEXT_CONTAINER: AtomicU64 = AtomicU64::new(1);
+
+/// The container engine CLI a [`DockerSandboxBackend`] drives.
+///
+/// Podman accepts Docker's `run`, `exec`, `stats`, `ps`, `kill`, and `rm`
+/// arguments, so the engines differ only in a few `run` flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Engine {
+    Docker,
+    Podman {
+        /// Containers run in the invoking user's user namespace.
+        rootless: bool,
+        /// Map the invoking user to the same UID in the container
+        /// (`--userns=keep-id`), so files written to rw mounts are theirs.
+        /// Rootless only.
+        keep_id: bool,
+    },
+}
+
+impl Engine {
+    fn name(self) -> &'static str {
+        match self {
+            Engine::Docker => "docker",
+            Engine::Podman { .. } => "podman",
+        }
+    }
+}
+
 /// Docker container sandbox backend.
 ///
 /// Runs skill commands inside Docker containers with resource limits
 /// enforced by the Docker daemon. Provides L1 (container) or L3
 /// (MicroVM via Docker Desktop sandboxes) isolation depending on the
 /// Docker installation.
+#[derive(Clone)]
 pub struct DockerSandboxBackend {
     /// Docker CLI binary path.
     docker_bin: PathBuf,
     /// Default container image for sandboxed skills.
     default_image: String,
+    /// Image with `nft` for network allowlist sidecars.
+    firewall_image: Option<String>,
+    /// The engine `docker_bin` is.
+    engine: Engine,
+    /// Checks and pulls images before first use, if set.
+    image_policy: Option<ImagePolicy>,
+    /// Image IDs each image reference was pinned to on first use, shared
+    /// by clones.
+    pinned: Arc<Mutex<HashMap<String, String>>>,
 }
 
 impl DockerSandboxBackend {
@@ -45,12 +116,114 @@ impl DockerSandboxBackend {
         Self {
             docker_bin: docker_bin.into(),
             default_image: default_image.into(),
+            firewall_image: None,
+            engine: Engine::Docker,
+            image_policy: None,
+            pinned: Arc::default(),
         }
     }
 
-    /// Build the `docker run` argument list from a sandbox config.
-    fn build_args(&self, config: &SandboxConfig, command: &[String]) -> Vec<String> {
-        let mut args = vec!["run".to_string(), "--rm".to_string(), "--init".to_string()];
+    /// A backend running `isolation.docker_image` by default, under the
+    /// image policy of `isolation.pull_images` and `isolation.strict_images`.
+    pub fn from_config(config: &crustyclaw_config::IsolationConfig) -> Self {
+        Self::new("docker", &config.docker_image).with_image_policy(ImagePolicy {
+            pull: config.pull_images,
+            strict: config.strict_images,
+        })
+    }
+
+    /// Builder: enforce network allowlists with sidecars from `image`,
+    /// which must provide `nft`.
+    pub fn with_firewall_image(mut self, image: impl Into<String>) -> Self {
+        self.firewall_image = Some(image.into());
+        self
+    }
+
+    /// Builder: check each image against `policy` before its first run,
+    /// make sure it is present (pulling it if the policy allows), and pin
+    /// it to the image ID it resolves to for later runs.
+    pub fn with_image_policy(mut self, policy: ImagePolicy) -> Self {
+        self.image_policy = Some(policy);
+        self
+    }
+
+    /// The image sandboxes run when their config names none.
+    pub fn default_image(&self) -> &str {
+        &self.default_image
+    }
+
+    /// Pull `image` (or the default image), even if it is present, and
+    /// pin later runs to the result. Returns the reference pinning it by
+    /// registry digest (or its image ID, for an image not from a
+    /// registry).
+    pub async fn pull_image(&self, image: Option<&str>) -> Result<String, IsolationError> {
+        let image = image.unwrap_or(&self.default_image);
+        let reference = image::check_image(image, &self.image_policy.unwrap_or_default())?;
+        let local = image::pull(&self.docker_bin, image).await?;
+        self.pin(image, &local.id);
+        Ok(local
+            .pinned_reference(&reference)
+            .map_or(local.id, |pinned| pinned.to_string()))
+    }
+
+    /// The image ID `image` is pinned to, resolving it under `policy` on
+    /// first use.
+    async fn pinned_image(
+        &self,
+        image: &str,
+        policy: &ImagePolicy,
+    ) -> Result<String, IsolationError> {
+        let pinned = self
+            .pinned
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(image)
+            .cloned();
+        if let Some(id) = pinned {
+            return Ok(id);
+        }
+        let local = image::ensure(&self.docker_bin, image, policy).await?;
+        self.pin(image, &local.id);
+        tracing::debug!(image = %image, id = %local.id, "Sandbox image pinned");
+        Ok(local.id)
+    }
+
+    fn pin(&self, image: &str, id: &str) {
+        self.pinned
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(image.to_string(), id.to_string());
+    }
+
+    /// Builder: drive `engine` instead of Docker.
+    pub(super) fn with_engine(mut self, engine: Engine) -> Self {
+        self.engine = engine;
+        self
+    }
+
+    /// Builder: run the CLI at `bin`. Images pinned so far are forgotten,
+    /// as they were pinned in another engine's store.
+    pub(super) fn with_bin(mut self, bin: impl Into<PathBuf>) -> Self {
+        self.docker_bin = bin.into();
+        self.pinned = Arc::default();
+        self
+    }
+
+    /// Build the `docker run` argument list for container `name` from a
+    /// sandbox config.
+    pub(super) fn build_args(
+        &self,
+        config: &SandboxConfig,
+        name: &str,
+        command: &[String],
+    ) -> Vec<String> {
+        let mut args = vec![
+            "run".to_string(),
+            "--name".to_string(),
+            name.to_string(),
+            "--rm".to_string(),
+            "--init".to_string(),
+        ];
 
         // Resource limits
         let cpu = format!("{:.2}", config.limits.cpu.cpu_fraction);
@@ -70,6 +243,18 @@ impl DockerSandboxBackend {
             args.extend(["--pids-limit".to_string(), max_pids.to_string()]);
         }
 
+        if let Engine::Podman { rootless, keep_id } = self.engine {
+            if rootless && keep_id {
+                args.push("--userns=keep-id".to_string());
+            }
+            // Podman kills the container itself once the timeout passes,
+            // even if the daemon is no longer there to do it.
+            if let Some(timeout) = config.limits.timeout {
+                let secs = timeout.as_secs() + u64::from(timeout.subsec_nanos() > 0);
+                args.push(format!("--timeout={}", secs.max(1)));
+            }
+        }
+
         // Network policy
         match &config.network {
             NetworkPolicy::None => {
@@ -78,9 +263,15 @@ impl DockerSandboxBackend {
             NetworkPolicy::HostOnly => {
                 args.extend(["--network".to_string(), "host".to_string()]);
             }
-            NetworkPolicy::OutboundOnly | NetworkPolicy::AllowList(_) => {
+            NetworkPolicy::OutboundOnly => {
                 args.extend(["--network".to_string(), "bridge".to_string()]);
             }
+            NetworkPolicy::AllowList(_) => {
+                args.extend([
+                    "--network".to_string(),
+                    format!("container:{}", firewall_name(name)),
+                ]);
+            }
         }
 
         // Working directory
@@ -114,105 +305,530 @@ impl DockerSandboxBackend {
         // Container label for tracking
         args.extend([
             "--label".to_string(),
-            format!("crustyclaw.sandbox={}", config.label),
+            format!("{SANDBOX_LABEL}={}", config.label),
         ]);
 
         // Image
-        args.push(self.default_image.clone());
+        args.push(
+            config
+                .image
+                .clone()
+                .unwrap_or_else(|| self.default_image.clone()),
+        );
 
         // Command
         args.extend(command.iter().cloned());
 
         args
     }
-}
 
-impl Default for DockerSandboxBackend {
-    fn default() -> Self {
-        Self {
-            docker_bin: PathBuf::from("docker"),
-            default_image: "alpine:latest".to_string(),
+    /// The parsed allowlist of `policy`, if it is one this backend can
+    /// enforce.
+    fn check_allow_list(
+        &self,
+        policy: &NetworkPolicy,
+    ) -> Result<Option<Vec<Cidr>>, IsolationError> {
+        let cidrs = network::allow_list(policy)?;
+        if cidrs.is_some() && self.firewall_image.is_none() {
+            return Err(IsolationError::NetViolation(format!(
+                "the {} backend needs a firewall image to enforce a network allowlist",
+                self.engine.name()
+            )));
         }
+        Ok(cidrs)
     }
-}
 
-impl SandboxBackend for DockerSandboxBackend {
-    fn name(&self) -> &str {
-        "docker"
-    }
-
-    fn available(&self) -> bool {
-        // Check if docker CLI is on PATH and responsive
-        std::process::Command::new(&self.docker_bin)
-            .arg("version")
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null())
-            .status()
-            .is_ok_and(|s| s.success())
-    }
-
-    fn execute(
+    /// Run `command` in a new container, sending its output lines to
+    /// `output` if given.
+    fn run(
         &self,
         config: &SandboxConfig,
         command: &[String],
+        output: Option<OutputSender>,
     ) -> BoxFuture<'_, Result<SandboxResult, IsolationError>> {
         let label = config.label.clone();
         let timeout = config.limits.timeout;
-        let args = self.build_args(config, command);
+        let name = format!(
+            "crustyclaw-{}-{}",
+            std::process::id(),
+            NEXT_CONTAINER.fetch_add(1, Ordering::Relaxed)
+        );
+        let mut config = config.clone();
+        let command = command.to_vec();
         let docker_bin = self.docker_bin.clone();
+        let allow_list = self.check_allow_list(&config.network);
+        let firewall_image = self.firewall_image.clone();
+        let engine = self.engine.name();
 
         Box::pin(async move {
+            if let Some(policy) = &self.image_policy {
+                let image = config
+                    .image
+                    .clone()
+                    .unwrap_or_else(|| self.default_image.clone());
+                config.image = Some(self.pinned_image(&image, policy).await?);
+            }
+            let args = self.build_args(&config, &name, &command);
             tracing::info!(
-                backend = "docker",
+                backend = engine,
                 label = %label,
                 args = ?args,
-                "Creating Docker sandbox"
+                "Creating container sandbox"
             );
 
+            // Keeps the firewall sidecar (if any) until the container exits.
+            let _firewall = match allow_list? {
+                Some(cidrs) => {
+                    let image = firewall_image.unwrap_or_default();
+                    Some(start_firewall(&docker_bin, &image, &name, &label, &cidrs).await?)
+                }
+                None => None,
+            };
+
             let start = std::time::Instant::now();
 
             let child = tokio::process::Command::new(&docker_bin)
                 .args(&args)
                 .stdout(std::process::Stdio::piped())
                 .stderr(std::process::Stdio::piped())
+                .kill_on_drop(true)
                 .spawn()
-                .map_err(|e| IsolationError::Execution(format!("failed to spawn docker: {e}")))?;
+                .map_err(|e| IsolationError::Execution(format!("failed to spawn {engine}: {e}")))?;
+            let mut guard = ContainerGuard {
+                docker_bin: docker_bin.clone(),
+                name,
+                armed: true,
+            };
 
-            let output = match timeout {
-                Some(dur) => match tokio::time::timeout(dur, child.wait_with_output()).await {
-                    Ok(Ok(output)) => output,
+            // Sample the container's usage while waiting for it to exit.
+            let mut stats = DockerStats::new(start);
+            let wait = async {
+                let wait = wait_streaming(child, output.as_ref(), false);
+                tokio::pin!(wait);
+                let mut ticks = tokio::time::interval_at(
+                    (start + DOCKER_STATS_INTERVAL).into(),
+                    DOCKER_STATS_INTERVAL,
+                );
+                loop {
+                    tokio::select! {
+                        result = &mut wait => break result,
+                        _ = ticks.tick() => stats.sample(&docker_bin, &guard.name).await,
+                    }
+                }
+            };
+            let (output, _) = match timeout {
+                Some(dur) => match tokio::time::timeout(dur, wait).await {
+                    Ok(Ok(done)) => done,
                     Ok(Err(e)) => {
                         return Err(IsolationError::Execution(format!(
-                            "docker wait failed: {e}"
+                            "{engine} wait failed: {e}"
                         )));
                     }
                     Err(_) => {
                         return Err(IsolationError::Timeout(dur));
                     }
                 },
-                None => child
-                    .wait_with_output()
+                None => wait
                     .await
-                    .map_err(|e| IsolationError::Execution(format!("docker wait failed: {e}")))?,
+                    .map_err(|e| IsolationError::Execution(format!("{engine} wait failed: {e}")))?,
             };
 
+            guard.armed = false;
             let elapsed = start.elapsed();
+            let usage = stats.usage();
 
             Ok(SandboxResult {
                 exit_code: output.status.code().unwrap_or(-1),
                 stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
                 stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
                 elapsed,
-                peak_memory_bytes: None,
+                peak_memory_bytes: usage.peak_memory_bytes,
+                cpu_time: usage.cpu_time,
             })
         })
     }
 }
 
+impl Default for DockerSandboxBackend {
+    fn default() -> Self {
+        Self {
+            docker_bin: PathBuf::from("docker"),
+            default_image: "alpine:latest".to_string(),
+            firewall_image: None,
+            engine: Engine::Docker,
+            image_policy: None,
+            pinned: Arc::default(),
+        }
+    }
+}
+
+impl SandboxBackend for DockerSandboxBackend {
+    fn name(&self) -> &str {
+        self.engine.name()
+    }
+
+    fn available(&self) -> bool {
+        // Check if docker CLI is on PATH and responsive
+        std::process::Command::new(&self.docker_bin)
+            .arg("version")
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success())
+    }
+
+    fn execute(
+        &self,
+        config: &SandboxConfig,
+        command: &[String],
+    ) -> BoxFuture<'_, Result<SandboxResult, IsolationError>> {
+        self.run(config, command, None)
+    }
+
+    fn execute_streaming(
+        &self,
+        config: &SandboxConfig,
+        command: &[String],
+        output: OutputSender,
+    ) -> BoxFuture<'_, Result<SandboxResult, IsolationError>> {
+        self.run(config, command, Some(output))
+    }
+
+    /// Force-remove every container carrying the `crustyclaw.sandbox` label.
+    fn reap_orphans(&self) -> BoxFuture<'_, Result<Vec<String>, IsolationError>> {
+        Box::pin(async move {
+            let engine = self.engine.name();
+            let docker = |args: Vec<String>| {
+                let docker_bin = self.docker_bin.clone();
+                async move {
+                    let output = tokio::process::Command::new(&docker_bin)
+                        .args(&args)
+                        .output()
+                        .await
+                        .map_err(|e| {
+                            IsolationError::Execution(format!("failed to spawn {engine}: {e}"))
+                        })?;
+                    if !output.status.success() {
+                        return Err(IsolationError::Execution(format!(
+                            "{engine} {} failed: {}",
+                            args[0],
+                            String::from_utf8_lossy(&output.stderr).trim()
+                        )));
+                    }
+                    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+                }
+            };
+
+            let listed = docker(vec![
+                "ps".to_string(),
+                "-aq".to_string(),
+                "--filter".to_string(),
+                format!("label={SANDBOX_LABEL}"),
+            ])
+            .await?;
+            let ids: Vec<String> = listed.split_whitespace().map(str::to_string).collect();
+            if ids.is_empty() {
+                return Ok(ids);
+            }
+
+            tracing::warn!(
+                count = ids.len(),
+                engine,
+                "Removing orphaned container sandboxes"
+            );
+            let mut rm = vec!["rm".to_string(), "-f".to_string()];
+            rm.extend(ids.iter().cloned());
+            docker(rm).await?;
+            Ok(ids)
+        })
+    }
+
+    /// Allowlists are enforced when a firewall image is configured.
+    fn check_network(&self, policy: &NetworkPolicy) -> Result<(), IsolationError> {
+        self.check_allow_list(policy).map(|_| ())
+    }
+}
+
+/// Name of the firewall sidecar of container `name`.
+fn firewall_name(name: &str) -> String {
+    format!("{name}-net")
+}
+
+/// Start the firewall sidecar for container `name` and load the
+/// allowlist ruleset into its network namespace.
+///
+/// The sidecar carries the sandbox label, so an orphaned one is reaped
+/// with the containers.
+async fn start_firewall(
+    docker_bin: &std::path::Path,
+    image: &str,
+    name: &str,
+    label: &str,
+    cidrs: &[Cidr],
+) -> Result<FirewallGuard, IsolationError> {
+    use tokio::io::AsyncWriteExt;
+
+    let sidecar = firewall_name(name);
+    let docker = |args: &[&str]| {
+        tokio::process::Command::new(docker_bin)
+            .args(args)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| IsolationError::Execution(format!("failed to spawn docker: {e}")))
+    };
+    let failed = |what: &str, output: std::process::Output| {
+        IsolationError::NetViolation(format!(
+            "{what}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    };
+
+    let label = format!("{SANDBOX_LABEL}={label}");
+    let run = docker(&[
+        "run",
+        "-d",
+        "--rm",
+        "--name",
+        &sidecar,
+        "--label",
+        &label,
+        "--cap-add",
+        "NET_ADMIN",
+        "--network",
+        "bridge",
+        image,
+        "tail",
+        "-f",
+        "/dev/null",
+    ])?
+    .wait_with_output()
+    .await
+    .map_err(|e| IsolationError::Execution(format!("docker run failed: {e}")))?;
+    if !run.status.success() {
+        return Err(failed("failed to start the firewall sidecar", run));
+    }
+    let guard = FirewallGuard {
+        docker_bin: docker_bin.to_path_buf(),
+        name: sidecar.clone(),
+    };
+
+    let mut nft = docker(&["exec", "-i", &sidecar, "nft", "-f", "-"])?;
+    if let Some(mut stdin) = nft.stdin.take() {
+        let rules = network::nft_ruleset(cidrs);
+        stdin
+            .write_all(rules.as_bytes())
+            .await
+            .map_err(|e| IsolationError::Execution(format!("docker exec failed: {e}")))?;
+    }
+    let nft = nft
+        .wait_with_output()
+        .await
+        .map_err(|e| IsolationError::Execution(format!("docker exec failed: {e}")))?;
+    if !nft.status.success() {
+        return Err(failed("failed to load the network allowlist", nft));
+    }
+    tracing::debug!(sidecar = %sidecar, ranges = cidrs.len(), "Network allowlist loaded");
+    Ok(guard)
+}
+
+/// Removes a firewall sidecar once its container is done.
+struct FirewallGuard {
+    docker_bin: PathBuf,
+    name: String,
+}
+
+impl Drop for FirewallGuard {
+    fn drop(&mut self) {
+        // Not waited for: this may run outside an async context.
+        let _ = std::process::Command::new(&self.docker_bin)
+            .args(["rm", "-f", &self.name])
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn();
+    }
+}
+
+/// Kills a container whose execution ended before `docker run` returned.
+/// `--rm` then removes it.
+struct ContainerGuard {
+    docker_bin: PathBuf,
+    name: String,
+    armed: bool,
+}
+
+impl Drop for ContainerGuard {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        tracing::warn!(container = %self.name, "Killing Docker sandbox");
+        // Not waited for: this may run outside an async context.
+        let _ = std::process::Command::new(&self.docker_bin)
+            .args(["kill", &self.name])
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// A fake `docker` that lists two containers and logs its arguments.
+    fn fake_docker(dir: &std::path::Path) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let bin = dir.join("docker");
+        let log = dir.join("calls.log");
+        std::fs::write(
+            &bin,
+            format!(
+                "#!/bin/sh\necho \"$@\" >> {}\nif [ \"$1\" = ps ]; then printf 'abc123\\ndef456\\n'; fi\n",
+                log.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&bin, std::fs::Permissions::from_mode(0o755)).unwrap();
+        bin
+    }
+
+    #[tokio::test]
+    async fn test_reap_orphans_removes_labelled_containers() {
+        let tmp = tempfile::tempdir().unwrap();
+        let backend = DockerSandboxBackend::new(fake_docker(tmp.path()), "alpine:latest");
+
+        let reaped = backend.reap_orphans().await.unwrap();
+        assert_eq!(reaped, vec!["abc123".to_string(), "def456".to_string()]);
+
+        let calls = std::fs::read_to_string(tmp.path().join("calls.log")).unwrap();
+        let calls: Vec<&str> = calls.lines().collect();
+        assert_eq!(calls[0], "ps -aq --filter label=crustyclaw.sandbox");
+        assert_eq!(calls[1], "rm -f abc123 def456");
+    }
+
+    #[tokio::test]
+    async fn test_timeout_kills_container() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let bin = tmp.path().join("docker");
+        let log = tmp.path().join("calls.log");
+        std::fs::write(
+            &bin,
+            format!(
+                "#!/bin/sh\necho \"$1 $2 $3\" >> {}\nif [ \"$1\" = run ]; then sleep 5; fi\n",
+                log.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&bin, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let backend = DockerSandboxBackend::new(&bin, "alpine:latest");
+        let config = SandboxConfig::new("slow").with_timeout(std::time::Duration::from_millis(200));
+
+        let result = backend.execute(&config, &["true".to_string()]).await;
+        assert!(matches!(result, Err(IsolationError::Timeout(_))));
+
+        let mut calls = String::new();
+        for _ in 0..50 {
+            calls = std::fs::read_to_string(&log).unwrap_or_default();
+            if calls.lines().count() == 2 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        let calls: Vec<&str> = calls.lines().collect();
+        let name = calls[0].strip_prefix("run --name ").unwrap();
+        assert!(name.starts_with("crustyclaw-"), "{name}");
+        assert_eq!(calls[1], format!("kill {name} "));
+    }
+
+    #[tokio::test]
+    async fn test_samples_container_usage() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let bin = tmp.path().join("docker");
+        std::fs::write(
+            &bin,
+            "#!/bin/sh\ncase \"$1\" in\n\
+             run) sleep 1.5; echo done ;;\n\
+             stats) echo '10MiB / 1GiB|50.00%' ;;\n\
+             esac\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&bin, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let backend = DockerSandboxBackend::new(&bin, "alpine:latest");
+
+        let result = backend
+            .execute(&SandboxConfig::new("sampled"), &["true".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(result.stdout, "done\n");
+        assert_eq!(result.peak_memory_bytes, Some(10 * 1024 * 1024));
+        // Half a core over the first (one second) sample period.
+        let cpu = result.cpu_time.unwrap();
+        assert!(cpu >= std::time::Duration::from_millis(500), "{cpu:?}");
+    }
+
+    #[tokio::test]
+    async fn test_image_policy_pins_image_id() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let bin = tmp.path().join("docker");
+        let log = tmp.path().join("calls.log");
+        std::fs::write(
+            &bin,
+            format!(
+                "#!/bin/sh\necho \"$@\" >> {}\n\
+                 if [ \"$1\" = image ]; then echo 'sha256:1d|'; fi\n",
+                log.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&bin, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let backend = DockerSandboxBackend::new(&bin, "alpine:3.20")
+            .with_image_policy(ImagePolicy::default());
+
+        let config = SandboxConfig::new("pinned");
+        backend
+            .execute(&config, &["true".to_string()])
+            .await
+            .unwrap();
+        backend
+            .execute(&config, &["true".to_string()])
+            .await
+            .unwrap();
+
+        let calls = std::fs::read_to_string(&log).unwrap();
+        let inspects = calls
+            .lines()
+            .filter(|l| l.starts_with("image inspect"))
+            .count();
+        assert_eq!(inspects, 1, "{calls}");
+        let runs: Vec<&str> = calls.lines().filter(|l| l.starts_with("run ")).collect();
+        assert_eq!(runs.len(), 2);
+        assert!(
+            runs.iter().all(|run| run.ends_with("sha256:1d true")),
+            "{calls}"
+        );
+
+        // Strict mode refuses the floating default before running anything.
+        let strict = DockerSandboxBackend::new(&bin, "alpine").with_image_policy(ImagePolicy {
+            strict: true,
+            ..Default::default()
+        });
+        let err = strict
+            .execute(&config, &["true".to_string()])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, IsolationError::Image(_)), "{err}");
+    }
+
     #[test]
     fn test_docker_backend_name() {
         let backend = DockerSandboxBackend::default();
@@ -227,7 +843,7 @@ mod tests {
             .with_env("MY_VAR", "hello")
             .with_workdir("/workspace");
 
-        let args = backend.build_args(&config, &["echo".to_string(), "hi".to_string()]);
+        let args = backend.build_args(&config, "test", &["echo".to_string(), "hi".to_string()]);
 
         assert!(args.contains(&"run".to_string()));
         assert!(args.contains(&"--rm".to_string()));
@@ -247,13 +863,24 @@ mod tests {
             .with_mount(super::super::SharedMount::read_only("/host/src", "/src"))
             .with_mount(super::super::SharedMount::read_write("/host/out", "/out"));
 
-        let args = backend.build_args(&config, &["ls".to_string()]);
+        let args = backend.build_args(&config, "test", &["ls".to_string()]);
 
         assert!(args.contains(&"-v".to_string()));
         assert!(args.iter().any(|a| a.contains("/host/src:/src:ro")));
         assert!(args.iter().any(|a| a == "/host/out:/out"));
     }
 
+    #[test]
+    fn test_docker_build_args_image_override() {
+        let backend = DockerSandboxBackend::default();
+        let config = SandboxConfig::new("image-test").with_image("sha256:0123abcd");
+
+        let args = backend.build_args(&config, "test", &["true".to_string()]);
+
+        assert!(args.contains(&"sha256:0123abcd".to_string()));
+        assert!(!args.contains(&"alpine:latest".to_string()));
+    }
+
     #[test]
     fn test_docker_build_args_resource_limits() {
         let backend = DockerSandboxBackend::default();
@@ -262,7 +889,7 @@ mod tests {
         config.limits.memory.max_bytes = 512 * 1024 * 1024;
         config.limits.max_pids = Some(100);
 
-        let args = backend.build_args(&config, &["true".to_string()]);
+        let args = backend.build_args(&config, "test", &["true".to_string()]);
 
         assert!(args.contains(&"--cpus".to_string()));
         assert!(args.contains(&"0.50".to_string()));
@@ -278,21 +905,154 @@ mod tests {
 
         // None
         let config = SandboxConfig::new("net-none");
-        let args = backend.build_args(&config, &["true".to_string()]);
+        let args = backend.build_args(&config, "test", &["true".to_string()]);
         let net_idx = args.iter().position(|a| a == "--network").unwrap();
         assert_eq!(args[net_idx + 1], "none");
 
         // HostOnly
         let config = SandboxConfig::new("net-host").with_network(NetworkPolicy::HostOnly);
-        let args = backend.build_args(&config, &["true".to_string()]);
+        let args = backend.build_args(&config, "test", &["true".to_string()]);
         let net_idx = args.iter().position(|a| a == "--network").unwrap();
         assert_eq!(args[net_idx + 1], "host");
 
         // OutboundOnly
         let config = SandboxConfig::new("net-out").with_network(NetworkPolicy::OutboundOnly);
-        let args = backend.build_args(&config, &["true".to_string()]);
+        let args = backend.build_args(&config, "test", &["true".to_string()]);
         let net_idx = args.iter().position(|a| a == "--network").unwrap();
         assert_eq!(args[net_idx + 1], "bridge");
+
+        // AllowList: joins the firewall sidecar's namespace
+        let config = SandboxConfig::new("net-allow")
+            .with_network(NetworkPolicy::AllowList(vec!["10.0.0.0/8".to_string()]));
+        let args = backend.build_args(&config, "test", &["true".to_string()]);
+        let net_idx = args.iter().position(|a| a == "--network").unwrap();
+        assert_eq!(args[net_idx + 1], "container:test-net");
+    }
+
+    #[test]
+    fn test_allow_list_needs_firewall_image() {
+        let allow = NetworkPolicy::AllowList(vec!["10.0.0.0/8".to_string()]);
+
+        let backend = DockerSandboxBackend::default();
+        assert!(backend.check_network(&NetworkPolicy::OutboundOnly).is_ok());
+        assert!(matches!(
+            backend.check_network(&allow),
+            Err(IsolationError::NetViolation(_))
+        ));
+
+        let backend = DockerSandboxBackend::default().with_firewall_image("nftables:latest");
+        assert!(backend.check_network(&allow).is_ok());
+        assert!(matches!(
+            backend.check_network(&NetworkPolicy::AllowList(vec!["10.0.0.1/8".to_string()])),
+            Err(IsolationError::NetViolation(_))
+        ));
+    }
+
+    /// A fake `docker` that logs its arguments and saves `exec` stdin to
+    /// `rules.nft`, with `exec` exiting with `nft_status`.
+    fn fake_firewall_docker(dir: &std::path::Path, nft_status: i32) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let bin = dir.join("docker");
+        std::fs::write(
+            &bin,
+            format!(
+                "#!/bin/sh
+echo \"$@\" >> {log}
+\
+                 if [ \"$1\" = exec ]; then cat > {rules}; echo 'syntax error' >&2; exit {nft_status}; fi
+",
+                log = dir.join("calls.log").display(),
+                rules = dir.join("rules.nft").display(),
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&bin, std::fs::Permissions::from_mode(0o755)).unwrap();
+        bin
+    }
+
+    /// Log lines, once `count` have been written.
+    async fn wait_for_calls(log: &std::path::Path, count: usize) -> Vec<String> {
+        let mut calls = String::new();
+        for _ in 0..50 {
+            calls = std::fs::read_to_string(log).unwrap_or_default();
+            if calls.lines().count() >= count {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        calls.lines().map(str::to_string).collect()
+    }
+
+    #[tokio::test]
+    async fn test_allow_list_runs_behind_firewall_sidecar() {
+        let tmp = tempfile::tempdir().unwrap();
+        let backend = DockerSandboxBackend::new(fake_firewall_docker(tmp.path(), 0), "alpine")
+            .with_firewall_image("nftables:latest");
+        let config = SandboxConfig::new("fenced").with_network(NetworkPolicy::AllowList(vec![
+            "10.0.0.0/8".to_string(),
+            "fd00::/8".to_string(),
+        ]));
+
+        let result = backend
+            .execute(&config, &["true".to_string()])
+            .await
+            .unwrap();
+        assert!(result.success());
+
+        let calls = wait_for_calls(&tmp.path().join("calls.log"), 4).await;
+        let sidecar = calls[0]
+            .strip_prefix("run -d --rm --name ")
+            .and_then(|rest| rest.split_whitespace().next())
+            .unwrap();
+        assert!(sidecar.ends_with("-net"), "{sidecar}");
+        assert!(calls[0].contains("--cap-add NET_ADMIN --network bridge nftables:latest"));
+        assert!(calls[0].contains("--label crustyclaw.sandbox=fenced"));
+        assert_eq!(calls[1], format!("exec -i {sidecar} nft -f -"));
+        assert!(calls[2].starts_with("run --name "));
+        assert!(calls[2].contains(&format!("--network container:{sidecar}")));
+        assert!(!calls[2].contains("NET_ADMIN"));
+        assert_eq!(calls[3], format!("rm -f {sidecar}"));
+
+        let rules = std::fs::read_to_string(tmp.path().join("rules.nft")).unwrap();
+        assert!(rules.contains("ip daddr { 10.0.0.0/8 } accept"), "{rules}");
+        assert!(rules.contains("ip6 daddr { fd00::/8 } accept"), "{rules}");
+    }
+
+    #[tokio::test]
+    async fn test_allow_list_fails_when_rules_do_not_load() {
+        let tmp = tempfile::tempdir().unwrap();
+        let backend = DockerSandboxBackend::new(fake_firewall_docker(tmp.path(), 1), "alpine")
+            .with_firewall_image("nftables:latest");
+        let config = SandboxConfig::new("fenced")
+            .with_network(NetworkPolicy::AllowList(vec!["10.0.0.0/8".to_string()]));
+
+        let err = backend
+            .execute(&config, &["true".to_string()])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, IsolationError::NetViolation(_)));
+        assert!(err.to_string().contains("syntax error"), "{err}");
+
+        // The sidecar is removed and the skill container never started.
+        let calls = wait_for_calls(&tmp.path().join("calls.log"), 3).await;
+        assert_eq!(calls.len(), 3, "{calls:?}");
+        assert!(calls[2].starts_with("rm -f "));
+    }
+
+    #[tokio::test]
+    async fn test_allow_list_rejected_without_firewall_image() {
+        let tmp = tempfile::tempdir().unwrap();
+        let backend = DockerSandboxBackend::new(fake_firewall_docker(tmp.path(), 0), "alpine");
+        let config = SandboxConfig::new("fenced")
+            .with_network(NetworkPolicy::AllowList(vec!["10.0.0.0/8".to_string()]));
+
+        let err = backend
+            .execute(&config, &["true".to_string()])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, IsolationError::NetViolation(_)));
+        assert!(!tmp.path().join("calls.log").exists());
     }
 
     #[test]
@@ -302,7 +1062,7 @@ mod tests {
         config.limits.memory.allow_swap = false;
         config.limits.memory.max_bytes = 256 * 1024 * 1024;
 
-        let args = backend.build_args(&config, &["true".to_string()]);
+        let args = backend.build_args(&config, "test", &["true".to_string()]);
         assert!(args.contains(&"--memory-swap".to_string()));
         assert!(args.contains(&"256m".to_string()));
     }
@@ -312,7 +1072,7 @@ mod tests {
         let backend = DockerSandboxBackend::default();
         let config = SandboxConfig::new("my-skill");
 
-        let args = backend.build_args(&config, &["true".to_string()]);
+        let args = backend.build_args(&config, "test", &["true".to_string()]);
         assert!(args.iter().any(|a| a == "crustyclaw.sandbox=my-skill"));
     }
 }
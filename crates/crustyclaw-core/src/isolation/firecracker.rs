@@ -12,12 +12,22 @@
 //! - Linux host with KVM enabled (`/dev/kvm`)
 //! - `firecracker` binary on PATH
 //! - A root filesystem image and kernel image
+//! - `crustyclaw-guest-agent` in the rootfs, started by the guest's init
+//!
+//! Each VM gets a vsock device whose host side is a Unix socket in
+//! `socket_dir`; commands, files and stats go through the guest agent over
+//! it (see [`GuestAgentClient`](super::GuestAgentClient)) rather than the
+//! serial console.
 
 use std::path::PathBuf;
 
 use crate::BoxFuture;
 
-use super::{IsolationError, SandboxBackend, SandboxConfig, SandboxResult};
+use super::{GUEST_AGENT_PORT, IsolationError, SandboxBackend, SandboxConfig, SandboxResult};
+
+/// Guest context ID given to every microVM; each VM has its own vsock
+/// device, so they need not differ.
+const GUEST_CID: u32 = 3;
 
 /// Firecracker microVM backend configuration.
 pub struct FirecrackerBackend {
@@ -27,8 +37,10 @@ pub struct FirecrackerBackend {
     pub kernel_image: PathBuf,
     /// Path to the root filesystem image.
     pub rootfs_image: PathBuf,
-    /// Base directory for Firecracker API sockets.
+    /// Base directory for Firecracker API and vsock sockets.
     pub socket_dir: PathBuf,
+    /// vsock port the guest agent listens on.
+    pub agent_port: u32,
 }
 
 impl FirecrackerBackend {
@@ -44,9 +56,15 @@ impl FirecrackerBackend {
             kernel_image: kernel_image.into(),
             rootfs_image: rootfs_image.into(),
             socket_dir: socket_dir.into(),
+            agent_port: GUEST_AGENT_PORT,
         }
     }
 
+    /// Host-side Unix socket of the vsock device for the sandbox `label`.
+    pub fn vsock_path(&self, label: &str) -> PathBuf {
+        self.socket_dir.join(format!("{label}.vsock"))
+    }
+
     /// Check if KVM is available on this host.
     fn kvm_available() -> bool {
         std::path::Path::new("/dev/kvm").exists()
@@ -74,12 +92,18 @@ impl FirecrackerBackend {
   "machine-config": {{
     "vcpu_count": {},
     "mem_size_mib": {}
+  }},
+  "vsock": {{
+    "guest_cid": {},
+    "uds_path": "{}"
   }}
 }}"#,
             self.kernel_image.display(),
             self.rootfs_image.display(),
             vcpu_count,
             mem_mib,
+            GUEST_CID,
+            self.vsock_path(&config.label).display(),
         )
     }
 }
@@ -91,6 +115,7 @@ impl Default for FirecrackerBackend {
             kernel_image: PathBuf::from("/usr/local/share/crustyclaw/vmlinux"),
             rootfs_image: PathBuf::from("/usr/local/share/crustyclaw/rootfs.ext4"),
             socket_dir: PathBuf::from("/run/crustyclaw/firecracker"),
+            agent_port: GUEST_AGENT_PORT,
         }
     }
 }
@@ -138,11 +163,14 @@ impl SandboxBackend for FirecrackerBackend {
             // 4. PUT /boot-source (kernel, boot_args)
             // 5. PUT /drives/rootfs (root filesystem)
             // 6. PUT /network-interfaces (based on NetworkPolicy)
-            // 7. PUT /actions {"action_type": "InstanceStart"}
-            // 8. Execute command inside VM via vsock or serial
-            // 9. Collect stdout/stderr
-            // 10. PUT /actions {"action_type": "SendCtrlAltDel"}
-            // 11. Cleanup socket and resources
+            // 7. PUT /vsock (guest CID + host socket, as in vm_config_json)
+            // 8. PUT /actions {"action_type": "InstanceStart"}
+            // 9. GuestAgentClient::connect_firecracker(vsock_path, agent_port),
+            //    retrying until the guest has booted
+            // 10. Push inputs, exec the command, pull outputs
+            // 11. GuestAgentClient::shutdown, falling back to
+            //     PUT /actions {"action_type": "SendCtrlAltDel"}
+            // 12. Cleanup sockets and resources
             Err(IsolationError::UnsupportedBackend(
                 "Firecracker microVM integration not yet implemented; \
                  requires KVM, firecracker binary, and kernel/rootfs images"
@@ -175,6 +203,7 @@ mod tests {
             backend.rootfs_image,
             PathBuf::from("/usr/local/share/crustyclaw/rootfs.ext4")
         );
+        assert_eq!(backend.agent_port, GUEST_AGENT_PORT);
     }
 
     #[test]
@@ -189,6 +218,8 @@ mod tests {
         assert!(json.contains("\"mem_size_mib\": 512"));
         assert!(json.contains("vmlinux"));
         assert!(json.contains("rootfs.ext4"));
+        assert!(json.contains("\"guest_cid\": 3"));
+        assert!(json.contains("/run/crustyclaw/firecracker/test-vm.vsock"));
     }
 
     #[test]
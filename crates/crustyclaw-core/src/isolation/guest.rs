@@ -0,0 +1,367 @@
+//! Host side of the guest agent protocol for VM sandboxes.
+//!
+//! The Firecracker and Apple VZ backends boot a guest whose rootfs runs
+//! `crustyclaw-guest-agent` on a vsock port. [`GuestAgentClient`] speaks the
+//! agent's framed protocol (see [`crustyclaw_guest::protocol`]) over any
+//! async byte stream to run commands, move files, read guest stats, and shut
+//! the VM down cleanly.
+//!
+//! Firecracker exposes guest vsock ports through a Unix socket on the host;
+//! [`GuestAgentClient::connect_firecracker`] performs its `CONNECT <port>`
+//! handshake. Apple VZ hands out a connected socket from
+//! `VZVirtioSocketDevice`, which can be passed straight to
+//! [`GuestAgentClient::handshake`].
+
+use std::path::Path;
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+
+use crustyclaw_guest::protocol::{self, MAX_FRAME_BYTES};
+use crustyclaw_guest::{ExecRequest, PROTOCOL_VERSION, Request, Response};
+
+pub use crustyclaw_guest::{DEFAULT_PORT as GUEST_AGENT_PORT, GuestStats};
+
+use super::{IsolationError, SandboxConfig, SandboxResult};
+
+/// Extra time the host waits past a command's timeout for the agent to
+/// report it, before giving up on the agent itself.
+const TIMEOUT_GRACE: Duration = Duration::from_secs(5);
+
+/// A session with the guest agent inside a running VM.
+pub struct GuestAgentClient<S> {
+    stream: S,
+    agent: String,
+}
+
+impl GuestAgentClient<BufReader<UnixStream>> {
+    /// Connect to the agent through Firecracker's vsock Unix socket at
+    /// `uds_path`, then open a session.
+    pub async fn connect_firecracker(uds_path: &Path, port: u32) -> Result<Self, IsolationError> {
+        let stream = UnixStream::connect(uds_path).await.map_err(|e| {
+            agent_error(format!(
+                "cannot connect to vsock socket {}: {e}",
+                uds_path.display()
+            ))
+        })?;
+        let mut stream = BufReader::new(stream);
+        stream
+            .write_all(format!("CONNECT {port}\n").as_bytes())
+            .await
+            .map_err(io_error)?;
+        let mut reply = String::new();
+        stream.read_line(&mut reply).await.map_err(io_error)?;
+        if !reply.starts_with("OK ") {
+            return Err(agent_error(format!(
+                "vsock port {port} refused: {:?}",
+                reply.trim()
+            )));
+        }
+        Self::handshake(stream).await
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin + Send> GuestAgentClient<S> {
+    /// Open a session over a connected stream, checking that the agent
+    /// speaks this protocol version.
+    pub async fn handshake(stream: S) -> Result<Self, IsolationError> {
+        let mut client = Self {
+            stream,
+            agent: String::new(),
+        };
+        match client
+            .call(&Request::Hello {
+                version: PROTOCOL_VERSION,
+            })
+            .await?
+        {
+            Response::Hello { version, agent } if version == PROTOCOL_VERSION => {
+                tracing::debug!(%agent, "Guest agent session opened");
+                client.agent = agent;
+                Ok(client)
+            }
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// The agent's self-reported name and version.
+    pub fn agent(&self) -> &str {
+        &self.agent
+    }
+
+    /// Run `command` in the guest with the config's environment, working
+    /// directory and timeout.
+    pub async fn exec(
+        &mut self,
+        config: &SandboxConfig,
+        command: &[String],
+    ) -> Result<SandboxResult, IsolationError> {
+        let timeout = config.limits.timeout;
+        let request = Request::Exec(ExecRequest {
+            argv: command.to_vec(),
+            env: config
+                .env
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+            workdir: Some(config.workdir.display().to_string()),
+            timeout_ms: timeout.map(|t| t.as_millis() as u64),
+        });
+
+        let response = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout + TIMEOUT_GRACE, self.call(&request))
+                .await
+                .map_err(|_| IsolationError::Timeout(timeout))??,
+            None => self.call(&request).await?,
+        };
+        match response {
+            Response::Exited(result) if result.timed_out => Err(IsolationError::Timeout(
+                timeout.unwrap_or(Duration::from_millis(result.elapsed_ms)),
+            )),
+            Response::Exited(result) => Ok(SandboxResult {
+                exit_code: result.exit_code,
+                stdout: result.stdout,
+                stderr: result.stderr,
+                elapsed: Duration::from_millis(result.elapsed_ms),
+                peak_memory_bytes: None,
+                cpu_time: None,
+            }),
+            Response::Error { message } => Err(IsolationError::Execution(message)),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Write `data` to `guest_path` with permission bits `mode`.
+    pub async fn push_file(
+        &mut self,
+        guest_path: &Path,
+        data: &[u8],
+        mode: u32,
+    ) -> Result<(), IsolationError> {
+        self.send(&Request::PushFile {
+            path: guest_path.display().to_string(),
+            mode,
+        })
+        .await?;
+        write_frame(&mut self.stream, data).await?;
+        match self.receive().await? {
+            Response::Done => Ok(()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Read the file at `guest_path`.
+    pub async fn pull_file(&mut self, guest_path: &Path) -> Result<Vec<u8>, IsolationError> {
+        let request = Request::PullFile {
+            path: guest_path.display().to_string(),
+        };
+        match self.call(&request).await? {
+            Response::File { .. } => read_frame(&mut self.stream).await,
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Report the guest's current resource usage.
+    pub async fn stats(&mut self) -> Result<GuestStats, IsolationError> {
+        match self.call(&Request::Stats).await? {
+            Response::Stats(stats) => Ok(stats),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Ask the agent to power the guest off, ending the session.
+    pub async fn shutdown(mut self) -> Result<(), IsolationError> {
+        match self.call(&Request::Shutdown).await? {
+            Response::Done => Ok(()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    async fn call(&mut self, request: &Request) -> Result<Response, IsolationError> {
+        self.send(request).await?;
+        self.receive().await
+    }
+
+    async fn send(&mut self, request: &Request) -> Result<(), IsolationError> {
+        let data = serde_json::to_vec(request).map_err(|e| agent_error(e.to_string()))?;
+        write_frame(&mut self.stream, &data).await
+    }
+
+    async fn receive(&mut self) -> Result<Response, IsolationError> {
+        let data = read_frame(&mut self.stream).await?;
+        serde_json::from_slice(&data).map_err(|e| agent_error(format!("malformed response: {e}")))
+    }
+}
+
+async fn write_frame<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    data: &[u8],
+) -> Result<(), IsolationError> {
+    if data.len() > MAX_FRAME_BYTES {
+        return Err(agent_error(format!(
+            "{} bytes is larger than the {MAX_FRAME_BYTES}-byte frame limit",
+            data.len()
+        )));
+    }
+    writer
+        .write_all(&(data.len() as u32).to_be_bytes())
+        .await
+        .map_err(io_error)?;
+    writer.write_all(data).await.map_err(io_error)?;
+    writer.flush().await.map_err(io_error)
+}
+
+async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Vec<u8>, IsolationError> {
+    let mut header = [0u8; 4];
+    reader.read_exact(&mut header).await.map_err(io_error)?;
+    let len = protocol::frame_len(header).map_err(|e| agent_error(e.to_string()))?;
+    let mut data = vec![0u8; len];
+    reader.read_exact(&mut data).await.map_err(io_error)?;
+    Ok(data)
+}
+
+fn agent_error(message: String) -> IsolationError {
+    IsolationError::GuestAgent(message)
+}
+
+fn io_error(e: std::io::Error) -> IsolationError {
+    agent_error(format!("connection failed: {e}"))
+}
+
+/// An error for a response that does not answer the request sent.
+fn unexpected(response: Response) -> IsolationError {
+    match response {
+        Response::Error { message } => agent_error(message),
+        other => agent_error(format!("unexpected response: {other:?}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crustyclaw_guest::{Agent, Outcome};
+
+    /// Run `agent` on one end of a socket pair and open a session on the other.
+    async fn session(
+        agent: Agent,
+    ) -> (
+        GuestAgentClient<UnixStream>,
+        std::thread::JoinHandle<Outcome>,
+    ) {
+        let (host, mut guest) = std::os::unix::net::UnixStream::pair().unwrap();
+        let thread = std::thread::spawn(move || agent.serve(&mut guest).unwrap());
+        host.set_nonblocking(true).unwrap();
+        let host = UnixStream::from_std(host).unwrap();
+        (GuestAgentClient::handshake(host).await.unwrap(), thread)
+    }
+
+    fn sh(script: &str) -> Vec<String> {
+        vec!["sh".to_string(), "-c".to_string(), script.to_string()]
+    }
+
+    #[tokio::test]
+    async fn test_exec_returns_sandbox_result() {
+        let (mut client, _thread) = session(Agent::new()).await;
+        assert!(client.agent().starts_with("crustyclaw-guest-agent"));
+
+        let config = SandboxConfig::new("vm")
+            .with_env("SKILL", "weather")
+            .with_workdir("/");
+        let result = client
+            .exec(&config, &sh("echo $SKILL; pwd; echo oops >&2; exit 2"))
+            .await
+            .unwrap();
+        assert_eq!(result.exit_code, 2);
+        assert_eq!(result.stdout, "weather\n/\n");
+        assert_eq!(result.stderr, "oops\n");
+        assert!(!result.success());
+    }
+
+    #[tokio::test]
+    async fn test_exec_timeout() {
+        let (mut client, _thread) = session(Agent::new()).await;
+        let config = SandboxConfig::new("vm")
+            .with_workdir("/")
+            .with_timeout(Duration::from_millis(100));
+        let result = client.exec(&config, &sh("sleep 5")).await;
+        assert!(
+            matches!(result, Err(IsolationError::Timeout(d)) if d == Duration::from_millis(100))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_exec_failure_is_execution_error() {
+        let (mut client, _thread) = session(Agent::new()).await;
+        let config = SandboxConfig::new("vm").with_workdir("/");
+        let result = client
+            .exec(&config, &["/nonexistent/tool".to_string()])
+            .await;
+        assert!(matches!(result, Err(IsolationError::Execution(_))));
+    }
+
+    #[tokio::test]
+    async fn test_file_transfer_stats_and_shutdown() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("skill/input.json");
+        let (mut client, thread) = session(Agent::new()).await;
+
+        client.push_file(&path, b"{\"q\":1}", 0o644).await.unwrap();
+        assert_eq!(client.pull_file(&path).await.unwrap(), b"{\"q\":1}");
+        assert!(matches!(
+            client.pull_file(&tmp.path().join("missing")).await,
+            Err(IsolationError::GuestAgent(_))
+        ));
+
+        if cfg!(target_os = "linux") {
+            let stats = client.stats().await.unwrap();
+            assert!(stats.memory_total_bytes > 0);
+        }
+
+        client.shutdown().await.unwrap();
+        assert_eq!(thread.join().unwrap(), Outcome::Shutdown);
+    }
+
+    #[tokio::test]
+    async fn test_connect_firecracker_handshake() {
+        let tmp = tempfile::tempdir().unwrap();
+        let uds = tmp.path().join("vm.vsock");
+        let listener = std::os::unix::net::UnixListener::bind(&uds).unwrap();
+
+        // Stand in for Firecracker: accept CONNECT, then hand the stream to the agent.
+        let fake = std::thread::spawn(move || {
+            use std::io::{BufRead, Write};
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            assert_eq!(line, format!("CONNECT {GUEST_AGENT_PORT}\n"));
+            let mut stream = stream;
+            stream.write_all(b"OK 1073741824\n").unwrap();
+            Agent::new().serve(&mut stream).unwrap()
+        });
+
+        let client = GuestAgentClient::connect_firecracker(&uds, GUEST_AGENT_PORT)
+            .await
+            .unwrap();
+        client.shutdown().await.unwrap();
+        assert_eq!(fake.join().unwrap(), Outcome::Shutdown);
+    }
+
+    #[tokio::test]
+    async fn test_connect_firecracker_refused() {
+        let tmp = tempfile::tempdir().unwrap();
+        let uds = tmp.path().join("vm.vsock");
+        let listener = std::os::unix::net::UnixListener::bind(&uds).unwrap();
+        let fake = std::thread::spawn(move || {
+            use std::io::Write;
+            let (mut stream, _) = listener.accept().unwrap();
+            // Firecracker closes the connection when nothing listens on the port.
+            let _ = stream.write_all(b"");
+        });
+
+        let result = GuestAgentClient::connect_firecracker(&uds, 9).await;
+        assert!(matches!(result, Err(IsolationError::GuestAgent(_))));
+        fake.join().unwrap();
+    }
+}
@@ -0,0 +1,350 @@
+//! Container image references and their local lifecycle.
+//!
+//! Before the docker backend first runs an image, it checks the image
+//! against an [`ImagePolicy`], makes sure it is present locally (pulling
+//! it if the policy allows), and pins it: later runs use the image ID it
+//! resolved to, so a tag moved on the registry or re-pulled locally does
+//! not change what runs until the daemon restarts.
+//!
+//! References may be pinned by digest (`alpine@sha256:…`), which pulls
+//! exactly that content. In strict mode, unpinned references to `:latest`
+//! (or with no tag, which means `:latest`) are rejected.
+
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use super::IsolationError;
+
+/// How a backend treats the images it runs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImagePolicy {
+    /// Pull images that are not present locally, instead of failing.
+    pub pull: bool,
+    /// Reject images referenced by `:latest` or without a tag, unless
+    /// pinned by digest.
+    pub strict: bool,
+}
+
+/// A parsed image reference: `[registry/]name[:tag][@digest]`, or a bare
+/// image ID (`sha256:…`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageRef {
+    /// Repository name, including any registry; empty for an image ID.
+    pub name: String,
+    pub tag: Option<String>,
+    /// `sha256:<hex>` content digest, or the ID of a bare image ID.
+    pub digest: Option<String>,
+}
+
+impl ImageRef {
+    /// Whether the reference names exact content (a digest or image ID).
+    pub fn is_pinned(&self) -> bool {
+        self.digest.is_some()
+    }
+
+    /// Whether the reference floats with `:latest`.
+    pub fn is_latest(&self) -> bool {
+        !self.is_pinned() && self.tag.as_deref().is_none_or(|tag| tag == "latest")
+    }
+
+    /// This reference pinned to `digest`, dropping the tag.
+    pub fn with_digest(&self, digest: &str) -> Self {
+        Self {
+            name: self.name.clone(),
+            tag: None,
+            digest: Some(digest.to_string()),
+        }
+    }
+}
+
+impl FromStr for ImageRef {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |why: &str| format!("invalid image reference {s:?}: {why}");
+        if s.starts_with("sha256:") {
+            check_digest(s).map_err(|e| invalid(&e))?;
+            return Ok(Self {
+                name: String::new(),
+                tag: None,
+                digest: Some(s.to_string()),
+            });
+        }
+        let (rest, digest) = match s.split_once('@') {
+            Some((rest, digest)) => {
+                check_digest(digest).map_err(|e| invalid(&e))?;
+                (rest, Some(digest.to_string()))
+            }
+            None => (s, None),
+        };
+        // A colon after the last slash starts the tag; one before it is a
+        // registry port.
+        let name_start = rest.rfind('/').map_or(0, |i| i + 1);
+        let (name, tag) = match rest[name_start..].rfind(':') {
+            Some(i) => (&rest[..name_start + i], Some(&rest[name_start + i + 1..])),
+            None => (rest, None),
+        };
+        if name.is_empty() || name.ends_with('/') || name.chars().any(char::is_whitespace) {
+            return Err(invalid("bad repository name"));
+        }
+        if tag.is_some_and(|tag| tag.is_empty()) {
+            return Err(invalid("empty tag"));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            tag: tag.map(str::to_string),
+            digest,
+        })
+    }
+}
+
+impl fmt::Display for ImageRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)?;
+        if let Some(tag) = &self.tag {
+            write!(f, ":{tag}")?;
+        }
+        match &self.digest {
+            Some(digest) if self.name.is_empty() => f.write_str(digest),
+            Some(digest) => write!(f, "@{digest}"),
+            None => Ok(()),
+        }
+    }
+}
+
+fn check_digest(digest: &str) -> Result<(), String> {
+    let hex = digest
+        .strip_prefix("sha256:")
+        .ok_or_else(|| "only sha256 digests are supported".to_string())?;
+    if hex.len() != 64 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err("a sha256 digest is 64 hex digits".to_string());
+    }
+    Ok(())
+}
+
+/// Parse `image` and check it against `policy`.
+pub fn check_image(image: &str, policy: &ImagePolicy) -> Result<ImageRef, IsolationError> {
+    let reference: ImageRef = image.parse().map_err(IsolationError::Image)?;
+    if policy.strict && reference.is_latest() {
+        return Err(IsolationError::Image(format!(
+            "image {image:?} floats with :latest, which strict image mode rejects; \
+             use a specific tag or pin a digest (name@sha256:…)"
+        )));
+    }
+    Ok(reference)
+}
+
+/// A locally present image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalImage {
+    /// Image ID (`sha256:…`).
+    pub id: String,
+    /// Registry digests (`name@sha256:…`) of the image, if it was pulled.
+    pub repo_digests: Vec<String>,
+}
+
+impl LocalImage {
+    /// The registry reference pinning this image by digest, if it came
+    /// from a registry.
+    pub fn pinned_reference(&self, reference: &ImageRef) -> Option<ImageRef> {
+        let digest = self.repo_digests.first()?.split_once('@')?.1;
+        Some(reference.with_digest(digest))
+    }
+}
+
+/// Look `image` up in the local image store of the engine at `bin`.
+/// `None` if it is not present.
+pub(crate) async fn inspect(bin: &Path, image: &str) -> Result<Option<LocalImage>, IsolationError> {
+    let output = tokio::process::Command::new(bin)
+        .args([
+            "image",
+            "inspect",
+            "--format",
+            "{{.Id}}|{{join .RepoDigests \",\"}}",
+            image,
+        ])
+        .stdin(std::process::Stdio::null())
+        .output()
+        .await
+        .map_err(|e| IsolationError::Image(format!("failed to run {}: {e}", bin.display())))?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let line = stdout.lines().next().unwrap_or_default();
+    let (id, digests) = line.split_once('|').unwrap_or((line, ""));
+    if id.trim().is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(LocalImage {
+        id: id.trim().to_string(),
+        repo_digests: digests
+            .split(',')
+            .map(str::trim)
+            .filter(|d| !d.is_empty())
+            .map(str::to_string)
+            .collect(),
+    }))
+}
+
+/// Pull `image` with the engine at `bin` and return it as now present.
+pub(crate) async fn pull(bin: &Path, image: &str) -> Result<LocalImage, IsolationError> {
+    tracing::info!(image = %image, "Pulling sandbox image");
+    let output = tokio::process::Command::new(bin)
+        .args(["pull", image])
+        .stdin(std::process::Stdio::null())
+        .output()
+        .await
+        .map_err(|e| IsolationError::Image(format!("failed to run {}: {e}", bin.display())))?;
+    if !output.status.success() {
+        return Err(IsolationError::Image(format!(
+            "failed to pull {image}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    inspect(bin, image)
+        .await?
+        .ok_or_else(|| IsolationError::Image(format!("{image} is missing after pulling it")))
+}
+
+/// Check `image` against `policy` and make sure it is present locally,
+/// pulling it if allowed. Returns the local image.
+pub(crate) async fn ensure(
+    bin: &Path,
+    image: &str,
+    policy: &ImagePolicy,
+) -> Result<LocalImage, IsolationError> {
+    check_image(image, policy)?;
+    if let Some(local) = inspect(bin, image).await? {
+        return Ok(local);
+    }
+    if policy.pull {
+        return pull(bin, image).await;
+    }
+    Err(IsolationError::Image(format!(
+        "image {image} is not present locally; pull it with \
+         `crustyclaw isolation pull-image {image}` or set isolation.pull_images"
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIGEST: &str = "sha256:3c1f4a5e9b0d2c7f8a6e1b4d9c0f2a3e5b7d8c9a0f1e2d3c4b5a6978e0d1c2b3";
+
+    #[test]
+    fn test_parse_image_ref() {
+        let r: ImageRef = "alpine".parse().unwrap();
+        assert_eq!((r.name.as_str(), r.tag.as_deref()), ("alpine", None));
+        assert!(r.is_latest());
+
+        let r: ImageRef = "registry.local:5000/team/tool:1.2".parse().unwrap();
+        assert_eq!(r.name, "registry.local:5000/team/tool");
+        assert_eq!(r.tag.as_deref(), Some("1.2"));
+        assert!(!r.is_latest());
+        assert_eq!(r.to_string(), "registry.local:5000/team/tool:1.2");
+
+        let r: ImageRef = format!("alpine:latest@{DIGEST}").parse().unwrap();
+        assert!(r.is_pinned());
+        assert!(!r.is_latest());
+        assert_eq!(
+            r.with_digest(DIGEST).to_string(),
+            format!("alpine@{DIGEST}")
+        );
+
+        let r: ImageRef = DIGEST.parse().unwrap();
+        assert!(r.name.is_empty());
+        assert_eq!(r.to_string(), DIGEST);
+
+        for bad in [
+            "",
+            ":1.0",
+            "alpine:",
+            "alpine@sha256:abc",
+            "alpine@md5:00",
+            "a b",
+        ] {
+            assert!(
+                bad.parse::<ImageRef>().is_err(),
+                "{bad:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn test_strict_rejects_latest() {
+        let strict = ImagePolicy {
+            strict: true,
+            ..Default::default()
+        };
+        for image in ["alpine", "alpine:latest"] {
+            assert!(check_image(image, &ImagePolicy::default()).is_ok());
+            let err = check_image(image, &strict).unwrap_err();
+            assert!(matches!(err, IsolationError::Image(_)), "{err}");
+        }
+        assert!(check_image("alpine:3.20", &strict).is_ok());
+        assert!(check_image(&format!("alpine@{DIGEST}"), &strict).is_ok());
+        assert!(check_image(DIGEST, &strict).is_ok());
+    }
+
+    /// A fake engine with `present` images, logging its arguments. Pulling
+    /// makes any image present.
+    fn fake_engine(dir: &Path, present: &[&str]) -> std::path::PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let bin = dir.join("docker");
+        let store = dir.join("images");
+        std::fs::write(&store, present.join("\n") + "\n").unwrap();
+        std::fs::write(
+            &bin,
+            format!(
+                "#!/bin/sh\necho \"$@\" >> {log}\n\
+                 case \"$1\" in\n\
+                 image) for i in $(cat {store}); do\n\
+                   if [ \"$i\" = \"$5\" ]; then echo \"sha256:1d|$i@{DIGEST}\"; exit 0; fi\n\
+                 done; echo 'No such image' >&2; exit 1 ;;\n\
+                 pull) echo \"$2\" >> {store} ;;\n\
+                 esac\n",
+                log = dir.join("calls.log").display(),
+                store = store.display(),
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&bin, std::fs::Permissions::from_mode(0o755)).unwrap();
+        bin
+    }
+
+    #[tokio::test]
+    async fn test_ensure_present_missing_and_pulled() {
+        let tmp = tempfile::tempdir().unwrap();
+        let bin = fake_engine(tmp.path(), &["alpine:3.20"]);
+
+        let local = ensure(&bin, "alpine:3.20", &ImagePolicy::default())
+            .await
+            .unwrap();
+        assert_eq!(local.id, "sha256:1d");
+        let reference: ImageRef = "alpine:3.20".parse().unwrap();
+        assert_eq!(
+            local.pinned_reference(&reference).unwrap().to_string(),
+            format!("alpine@{DIGEST}")
+        );
+
+        let err = ensure(&bin, "debian:12", &ImagePolicy::default())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("pull-image debian:12"), "{err}");
+
+        let pull = ImagePolicy {
+            pull: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            ensure(&bin, "debian:12", &pull).await.unwrap().id,
+            "sha256:1d"
+        );
+        let calls = std::fs::read_to_string(tmp.path().join("calls.log")).unwrap();
+        assert!(calls.lines().any(|l| l == "pull debian:12"), "{calls}");
+    }
+}
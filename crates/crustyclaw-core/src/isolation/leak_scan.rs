@@ -0,0 +1,373 @@
+//! Post-execution scanning of sandbox output for leaked secrets.
+//!
+//! A sandboxed command may print a secret it was given, or copy one into
+//! a file on a read-write mount. [`LeakScanner`] looks for the values held
+//! in the [`SecretStore`] and for their credential proxy sentinels, and
+//! replaces each match with `[REDACTED]` before the result is logged,
+//! returned over IPC, or handed to an LLM.
+//!
+//! Files are only scanned on read-write mounts, and only those modified
+//! since the command started, up to [`MAX_SCANNED_FILE_BYTES`] each.
+//! Files that are not UTF-8 are skipped.
+
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use super::{CredentialProxy, MountAccess, OutputLine, OutputSender, SandboxResult, SharedMount};
+use crate::secrets::{InjectionMethod, SecretStore};
+
+/// What a match is replaced with.
+const REDACTED: &str = "[REDACTED]";
+
+/// Secret values shorter than this are not scanned for, as they would
+/// match ordinary text.
+const MIN_SCANNED_SECRET_LEN: usize = 6;
+
+/// Largest file scanned on a read-write mount.
+pub const MAX_SCANNED_FILE_BYTES: u64 = 1024 * 1024;
+
+/// Most files scanned per mount.
+const MAX_SCANNED_FILES: usize = 1000;
+
+/// How far before the start of a run a file's mtime may be and still be
+/// scanned. File timestamps come from the kernel's coarse clock, which
+/// can lag the system time a run's start is taken from.
+const MTIME_SLACK: Duration = Duration::from_secs(1);
+
+/// What kind of secret material leaked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeakKind {
+    /// A real secret value.
+    Value,
+    /// A credential proxy sentinel standing in for the secret.
+    Sentinel,
+}
+
+/// One secret found (and redacted) in sandbox output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Leak {
+    /// Name of the secret.
+    pub secret: String,
+    pub kind: LeakKind,
+    /// Where it was found: `stdout`, `stderr`, or a host file path.
+    pub location: String,
+}
+
+/// Finds and redacts secret values and sentinels.
+pub struct LeakScanner {
+    /// Name and value of each secret long enough to scan for.
+    values: Vec<(String, String)>,
+    /// One sentinel mapping per secret.
+    sentinels: CredentialProxy,
+}
+
+impl LeakScanner {
+    /// Scan for every secret currently in `store`.
+    pub fn from_store(store: &SecretStore) -> Self {
+        let mut names = store.names();
+        names.sort_unstable();
+
+        let mut values = Vec::new();
+        let mut sentinels = CredentialProxy::new();
+        for name in names {
+            let Some(entry) = store.get(name) else {
+                continue;
+            };
+            let value = entry.value.expose();
+            if value.len() >= MIN_SCANNED_SECRET_LEN {
+                values.push((name.to_string(), value.to_string()));
+            }
+            let env_name = match &entry.injection {
+                InjectionMethod::Env(env_name) | InjectionMethod::Both { env_name, .. } => {
+                    env_name.clone()
+                }
+                InjectionMethod::File(_) => name.to_string(),
+            };
+            sentinels.add_mapping(name, env_name);
+        }
+        Self { values, sentinels }
+    }
+
+    /// Whether there is nothing to scan for.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty() && self.sentinels.is_empty()
+    }
+
+    /// Redact secrets in `text`, returning the name and kind of each one
+    /// found.
+    pub fn redact(&self, text: &mut String) -> Vec<(String, LeakKind)> {
+        let mut found = Vec::new();
+        for (name, value) in &self.values {
+            if text.contains(value.as_str()) {
+                *text = text.replace(value.as_str(), REDACTED);
+                found.push((name.clone(), LeakKind::Value));
+            }
+        }
+        for name in self.sentinels.contains_sentinels(text) {
+            found.push((name.to_string(), LeakKind::Sentinel));
+        }
+        for mapping in self.sentinels.mappings() {
+            *text = text.replace(&mapping.sentinel, REDACTED);
+        }
+        found
+    }
+
+    /// Redact `result`'s stdout and stderr.
+    pub fn scan_result(&self, result: &mut SandboxResult) -> Vec<Leak> {
+        let mut leaks = self.scan_text(&mut result.stdout, "stdout");
+        leaks.extend(self.scan_text(&mut result.stderr, "stderr"));
+        leaks
+    }
+
+    /// Redact the files on read-write `mounts` modified since `since`,
+    /// rewriting those that contained secrets.
+    pub fn scan_mounts(&self, mounts: &[SharedMount], since: SystemTime) -> Vec<Leak> {
+        let since = since.checked_sub(MTIME_SLACK).unwrap_or(since);
+        let mut leaks = Vec::new();
+        for mount in mounts {
+            if mount.access != MountAccess::ReadWrite {
+                continue;
+            }
+            let mut budget = MAX_SCANNED_FILES;
+            self.scan_path(&mount.host_path, since, &mut budget, &mut leaks);
+        }
+        leaks
+    }
+
+    /// Redact a run's output and the files it wrote, logging each leak by
+    /// secret name (never by value).
+    pub fn scan_run(
+        &self,
+        label: &str,
+        mounts: &[SharedMount],
+        result: &mut SandboxResult,
+        since: SystemTime,
+    ) -> Vec<Leak> {
+        if self.is_empty() {
+            return Vec::new();
+        }
+        let mut leaks = self.scan_result(result);
+        leaks.extend(self.scan_mounts(mounts, since));
+        for leak in &leaks {
+            tracing::warn!(
+                sandbox = %label,
+                secret = %leak.secret,
+                kind = ?leak.kind,
+                location = %leak.location,
+                "Secret found in sandbox output; redacted"
+            );
+        }
+        leaks
+    }
+
+    /// A sender that redacts each line before passing it on to `output`.
+    ///
+    /// Forwarding stops when the returned sender is dropped; await the
+    /// handle to be sure every line has been passed on.
+    pub fn redacting_sender(
+        self: std::sync::Arc<Self>,
+        output: OutputSender,
+    ) -> (OutputSender, tokio::task::JoinHandle<()>) {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<OutputLine>();
+        let forward = tokio::spawn(async move {
+            while let Some(mut line) = rx.recv().await {
+                self.redact(&mut line.line);
+                if output.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        (tx, forward)
+    }
+
+    fn scan_text(&self, text: &mut String, location: &str) -> Vec<Leak> {
+        self.redact(text)
+            .into_iter()
+            .map(|(secret, kind)| Leak {
+                secret,
+                kind,
+                location: location.to_string(),
+            })
+            .collect()
+    }
+
+    fn scan_path(&self, path: &Path, since: SystemTime, budget: &mut usize, leaks: &mut Vec<Leak>) {
+        // Symlinks are not followed: they may point outside the mount.
+        let Ok(meta) = std::fs::symlink_metadata(path) else {
+            return;
+        };
+        if meta.is_dir() {
+            let Ok(entries) = std::fs::read_dir(path) else {
+                return;
+            };
+            let mut paths: Vec<_> = entries.filter_map(|e| e.ok().map(|e| e.path())).collect();
+            paths.sort();
+            for path in paths {
+                if *budget == 0 {
+                    return;
+                }
+                self.scan_path(&path, since, budget, leaks);
+            }
+            return;
+        }
+        if !meta.is_file()
+            || meta.len() > MAX_SCANNED_FILE_BYTES
+            || meta.modified().is_ok_and(|modified| modified < since)
+        {
+            return;
+        }
+        *budget -= 1;
+        let Ok(mut text) = std::fs::read_to_string(path) else {
+            return;
+        };
+        let found = self.redact(&mut text);
+        if found.is_empty() {
+            return;
+        }
+        if let Err(e) = std::fs::write(path, text) {
+            tracing::error!(path = %path.display(), error = %e, "Failed to redact secrets in sandbox output file");
+        }
+        leaks.extend(found.into_iter().map(|(secret, kind)| Leak {
+            secret,
+            kind,
+            location: path.display().to_string(),
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secrets::{SecretEntry, SecretSource, SecretValue};
+
+    fn scanner() -> LeakScanner {
+        let mut store = SecretStore::new();
+        for (name, value) in [("api_key", "sk-real-123"), ("pin", "42")] {
+            store
+                .insert(
+                    SecretEntry {
+                        name: name.to_string(),
+                        value: SecretValue::new(value),
+                        injection: InjectionMethod::Env(name.to_uppercase()),
+                        description: String::new(),
+                    },
+                    SecretSource::Config,
+                )
+                .unwrap();
+        }
+        LeakScanner::from_store(&store)
+    }
+
+    fn sandbox_result(stdout: &str, stderr: &str) -> SandboxResult {
+        SandboxResult {
+            exit_code: 0,
+            stdout: stdout.to_string(),
+            stderr: stderr.to_string(),
+            elapsed: Duration::ZERO,
+            peak_memory_bytes: None,
+            cpu_time: None,
+        }
+    }
+
+    #[test]
+    fn test_redacts_values_and_sentinels() {
+        let scanner = scanner();
+        let mut result = sandbox_result(
+            "key=sk-real-123\n",
+            "using __CRUSTYCLAW_SENTINEL_pin__ and 42\n",
+        );
+
+        let leaks = scanner.scan_result(&mut result);
+        assert_eq!(result.stdout, "key=[REDACTED]\n");
+        // Short values are not scanned for, but their sentinels are.
+        assert_eq!(result.stderr, "using [REDACTED] and 42\n");
+        assert_eq!(
+            leaks,
+            vec![
+                Leak {
+                    secret: "api_key".to_string(),
+                    kind: LeakKind::Value,
+                    location: "stdout".to_string(),
+                },
+                Leak {
+                    secret: "pin".to_string(),
+                    kind: LeakKind::Sentinel,
+                    location: "stderr".to_string(),
+                },
+            ]
+        );
+
+        let mut clean = sandbox_result("nothing here", "");
+        assert!(scanner.scan_result(&mut clean).is_empty());
+        assert_eq!(clean.stdout, "nothing here");
+    }
+
+    #[test]
+    fn test_empty_store_scans_nothing() {
+        let scanner = LeakScanner::from_store(&SecretStore::new());
+        assert!(scanner.is_empty());
+        let mut result = sandbox_result("sk-real-123", "");
+        assert!(
+            scanner
+                .scan_run("empty", &[], &mut result, SystemTime::now())
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_scans_files_written_to_rw_mounts() {
+        let scanner = scanner();
+        let rw = tempfile::tempdir().unwrap();
+        let ro = tempfile::tempdir().unwrap();
+        let old = rw.path().join("old.txt");
+        std::fs::write(&old, "sk-real-123").unwrap();
+        let since = SystemTime::now() + MTIME_SLACK * 2;
+        // Written "during the run": modified after `since`.
+        let written = rw.path().join("sub").join("out.txt");
+        std::fs::create_dir(rw.path().join("sub")).unwrap();
+        std::fs::write(&written, "token sk-real-123\n").unwrap();
+        let future = since + Duration::from_secs(1);
+        std::fs::File::options()
+            .write(true)
+            .open(&written)
+            .unwrap()
+            .set_modified(future)
+            .unwrap();
+        std::fs::write(ro.path().join("ro.txt"), "sk-real-123").unwrap();
+
+        let mounts = [
+            SharedMount::read_write(rw.path(), "/out"),
+            SharedMount::read_only(ro.path(), "/in"),
+        ];
+        let leaks = scanner.scan_mounts(&mounts, since);
+        assert_eq!(leaks.len(), 1);
+        assert_eq!(leaks[0].location, written.display().to_string());
+        assert_eq!(
+            std::fs::read_to_string(&written).unwrap(),
+            "token [REDACTED]\n"
+        );
+        // Files from before the run and on read-only mounts are untouched.
+        assert_eq!(std::fs::read_to_string(&old).unwrap(), "sk-real-123");
+        assert_eq!(
+            std::fs::read_to_string(ro.path().join("ro.txt")).unwrap(),
+            "sk-real-123"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_redacting_sender() {
+        use super::super::OutputStream;
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let (redacting, forward) = std::sync::Arc::new(scanner()).redacting_sender(tx);
+        redacting
+            .send(OutputLine {
+                stream: OutputStream::Stdout,
+                line: "sk-real-123".to_string(),
+            })
+            .unwrap();
+        drop(redacting);
+        forward.await.unwrap();
+        assert_eq!(rx.recv().await.unwrap().line, "[REDACTED]");
+    }
+}
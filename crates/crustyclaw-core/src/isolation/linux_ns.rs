@@ -7,17 +7,19 @@
 //! | PID namespace | Process tree isolation |
 //! | Mount namespace | Filesystem isolation + bind mounts |
 //! | Network namespace | Network isolation (veth pair or none) |
+//! | nftables | CIDR allowlist inside the network namespace |
 //! | User namespace | Unprivileged sandboxing (UID mapping) |
 //! | seccomp-BPF | Syscall allowlist |
 //! | Landlock | Filesystem access control |
 //! | cgroups v2 | Resource limits (CPU, memory, PIDs) |
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::BoxFuture;
 
 use super::{
-    IsolationError, MountAccess, ResourceLimits, SandboxBackend, SandboxConfig, SandboxResult,
+    IsolationError, MountAccess, NetworkPolicy, ResourceLimits, ResourceUsage, SandboxBackend,
+    SandboxConfig, SandboxResult, accounting, network,
 };
 
 /// Seccomp syscall filtering profile.
@@ -75,6 +77,18 @@ impl LinuxNamespaceBackend {
         cg
     }
 
+    /// Resources used by a finished sandbox, read from its cgroup
+    /// (`memory.peak` and `cpu.stat`) before the cgroup is removed.
+    pub fn cgroup_usage(cgroup_dir: &Path) -> ResourceUsage {
+        accounting::read_cgroup(cgroup_dir)
+    }
+
+    /// The nftables ruleset to load into the sandbox's network namespace,
+    /// for an allowlist policy.
+    pub(crate) fn network_rules(policy: &NetworkPolicy) -> Result<Option<String>, IsolationError> {
+        Ok(network::allow_list(policy)?.map(|cidrs| network::nft_ruleset(&cidrs)))
+    }
+
     /// Generate landlock filesystem rules from the sandbox mounts.
     pub(crate) fn landlock_rules(config: &SandboxConfig) -> Vec<LandlockRule> {
         config
@@ -134,9 +148,11 @@ impl SandboxBackend for LinuxNamespaceBackend {
         let cgroup_limits = Self::cgroup_limits(&config.limits);
         let landlock_rules = Self::landlock_rules(config);
         let network = config.network.clone();
+        let network_rules = Self::network_rules(&network);
         let cmd = command.to_vec();
 
         Box::pin(async move {
+            let network_rules = network_rules?;
             tracing::info!(
                 backend = "linux-ns",
                 label = %label,
@@ -144,6 +160,7 @@ impl SandboxBackend for LinuxNamespaceBackend {
                 cgroups = ?cgroup_limits,
                 landlock_rules = landlock_rules.len(),
                 network = %network,
+                allowlisted = network_rules.is_some(),
                 "Creating Linux namespace sandbox"
             );
 
@@ -152,8 +169,11 @@ impl SandboxBackend for LinuxNamespaceBackend {
             // 2. Set up mount namespace with bind mounts
             // 3. Apply Landlock ruleset
             // 4. Install seccomp-BPF filter
-            // 5. Set up network namespace (veth or none)
+            // 5. Set up network namespace (veth or none); for an allowlist,
+            //    load network_rules with `nft -f -` inside it before exec
             // 6. exec the command
+            // 7. Wait for exit, fill peak memory and CPU time from
+            //    Self::cgroup_usage, then remove the cgroup
             Err(IsolationError::UnsupportedBackend(
                 "Linux namespace isolation not yet implemented; \
                  requires clone3, seccomp, and landlock syscall integration"
@@ -161,6 +181,12 @@ impl SandboxBackend for LinuxNamespaceBackend {
             ))
         })
     }
+
+    /// Allowlists are enforced with nftables in the sandbox's own network
+    /// namespace.
+    fn check_network(&self, policy: &NetworkPolicy) -> Result<(), IsolationError> {
+        network::allow_list(policy).map(|_| ())
+    }
 }
 
 #[cfg(test)]
@@ -217,6 +243,20 @@ mod tests {
         assert!(cg.iter().any(|(k, _)| k == "pids.max"));
     }
 
+    #[test]
+    fn test_network_rules_for_allow_list() {
+        assert_eq!(
+            LinuxNamespaceBackend::network_rules(&NetworkPolicy::OutboundOnly).unwrap(),
+            None
+        );
+        let policy = NetworkPolicy::AllowList(vec!["10.1.0.0/16".to_string()]);
+        let rules = LinuxNamespaceBackend::network_rules(&policy)
+            .unwrap()
+            .unwrap();
+        assert!(rules.contains("ip daddr { 10.1.0.0/16 } accept"));
+        assert!(LinuxNamespaceBackend::new().check_network(&policy).is_ok());
+    }
+
     #[test]
     fn test_landlock_rules_generation() {
         let config = SandboxConfig::new("ll-test")
@@ -34,20 +34,36 @@
 //! └────────────────────────────────────────────────────┘
 //! ```
 
+mod accounting;
 mod apple_vz;
 mod credential_proxy;
 mod docker;
+mod egress_proxy;
 mod firecracker;
+mod guest;
+mod image;
+mod leak_scan;
 mod linux_ns;
+mod network;
 mod noop;
+mod podman;
+mod pool;
 mod trust;
 
+pub use accounting::ResourceUsage;
 pub use apple_vz::AppleVzBackend;
 pub use credential_proxy::{CredentialProxy, SentinelMapping};
 pub use docker::DockerSandboxBackend;
+pub use egress_proxy::{EgressGrant, EgressProxy, serve as serve_egress_proxy};
 pub use firecracker::FirecrackerBackend;
+pub use guest::{GUEST_AGENT_PORT, GuestAgentClient, GuestStats};
+pub use image::{ImagePolicy, ImageRef, LocalImage, check_image};
+pub use leak_scan::{Leak, LeakKind, LeakScanner};
 pub use linux_ns::{LandlockAccess, LandlockRule, LinuxNamespaceBackend, SeccompProfile};
+pub use network::{Cidr, nft_ruleset, parse_allow_list};
 pub use noop::NoopBackend;
+pub use podman::PodmanSandboxBackend;
+pub use pool::{DrainReport, SandboxPool, SandboxRun, SandboxState};
 pub use trust::{IsolationLevel, TrustBasedSelector, TrustTier};
 
 use std::collections::HashMap;
@@ -55,6 +71,8 @@ use std::fmt;
 use std::path::PathBuf;
 use std::time::Duration;
 
+use tokio::sync::mpsc;
+
 use crate::BoxFuture;
 
 /// Errors from sandbox creation and execution.
@@ -83,6 +101,18 @@ pub enum IsolationError {
 
     #[error("credential proxy error: {0}")]
     CredentialProxy(String),
+
+    #[error("guest agent error: {0}")]
+    GuestAgent(String),
+
+    #[error("sandbox image error: {0}")]
+    Image(String),
+
+    #[error("the daemon is shutting down; no new sandboxes are started")]
+    ShuttingDown,
+
+    #[error("sandbox killed: still running when the shutdown grace period ran out")]
+    Killed,
 }
 
 // ── Resource limits ─────────────────────────────────────────────────────
@@ -201,10 +231,25 @@ pub enum NetworkPolicy {
     HostOnly,
     /// Full outbound network access (no inbound).
     OutboundOnly,
-    /// Allow specific CIDR ranges.
+    /// Outbound access only to specific CIDR ranges (see [`Cidr`]).
+    ///
+    /// Backends that cannot enforce an allowlist reject it rather than
+    /// fall back to broader access.
     AllowList(Vec<String>),
 }
 
+impl NetworkPolicy {
+    /// Parse a named policy ("none", "host-only", "outbound-only").
+    pub fn from_str_loose(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "none" => Some(Self::None),
+            "host-only" | "host_only" => Some(Self::HostOnly),
+            "outbound-only" | "outbound_only" => Some(Self::OutboundOnly),
+            _ => None,
+        }
+    }
+}
+
 impl fmt::Display for NetworkPolicy {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -293,6 +338,9 @@ pub struct SandboxConfig {
     /// the values themselves. The sandbox executor resolves values
     /// from the [`SecretStore`](crate::secrets::SecretStore) at runtime.
     pub secret_injections: Vec<SecretInjection>,
+    /// Container image to run in, overriding the backend's default.
+    /// Ignored by backends that do not run images.
+    pub image: Option<String>,
 }
 
 impl SandboxConfig {
@@ -306,6 +354,7 @@ impl SandboxConfig {
             env: HashMap::new(),
             workdir: PathBuf::from("/workspace"),
             secret_injections: Vec::new(),
+            image: None,
         }
     }
 
@@ -345,6 +394,12 @@ impl SandboxConfig {
         self
     }
 
+    /// Builder: set the container image.
+    pub fn with_image(mut self, image: impl Into<String>) -> Self {
+        self.image = Some(image.into());
+        self
+    }
+
     /// Builder: set working directory.
     pub fn with_workdir(mut self, path: impl Into<PathBuf>) -> Self {
         self.workdir = path.into();
@@ -403,19 +458,13 @@ impl SandboxConfig {
             // Inject as file: stage to host, add read-only mount
             if let Some(guest_path) = &injection.file_path {
                 let host_file = staging_dir.join(&injection.name);
-                std::fs::write(&host_file, entry.value.expose().as_bytes()).map_err(|e| {
-                    IsolationError::Create(format!(
-                        "failed to stage secret '{}': {e}",
-                        injection.name
-                    ))
-                })?;
-
-                #[cfg(unix)]
-                {
-                    use std::os::unix::fs::PermissionsExt;
-                    let perms = std::fs::Permissions::from_mode(0o400);
-                    let _ = std::fs::set_permissions(&host_file, perms);
-                }
+                crate::secrets::write_staged_file(&host_file, entry.value.expose().as_bytes())
+                    .map_err(|e| {
+                        IsolationError::Create(format!(
+                            "failed to stage secret '{}': {e}",
+                            injection.name
+                        ))
+                    })?;
 
                 resolved
                     .mounts
@@ -460,6 +509,7 @@ impl SandboxConfig {
                 )));
             }
         }
+        network::allow_list(&self.network)?;
         Ok(())
     }
 }
@@ -479,6 +529,8 @@ pub struct SandboxResult {
     pub elapsed: Duration,
     /// Peak memory usage in bytes (if measurable).
     pub peak_memory_bytes: Option<u64>,
+    /// CPU time used, user plus system (if measurable).
+    pub cpu_time: Option<Duration>,
 }
 
 impl SandboxResult {
@@ -488,6 +540,112 @@ impl SandboxResult {
     }
 }
 
+// ── Streamed output ─────────────────────────────────────────────────────
+
+/// Which output stream of a sandboxed process a line came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+impl OutputStream {
+    /// Lowercase name (`stdout` or `stderr`).
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputStream::Stdout => "stdout",
+            OutputStream::Stderr => "stderr",
+        }
+    }
+}
+
+/// One line of output from a sandboxed process, without its line ending.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputLine {
+    pub stream: OutputStream,
+    pub line: String,
+}
+
+/// Receives output lines as a streaming execution produces them.
+pub type OutputSender = mpsc::UnboundedSender<OutputLine>;
+
+/// Send each line of already-captured `text` to `output`.
+///
+/// Used where output is only available once the process has exited. A
+/// closed receiver is ignored: the caller still gets the full result.
+pub(crate) fn send_lines(output: &OutputSender, stream: OutputStream, text: &str) {
+    for line in text.lines() {
+        let _ = output.send(OutputLine {
+            stream,
+            line: line.to_string(),
+        });
+    }
+}
+
+/// Wait for `child` to exit, collecting its piped stdout and stderr and
+/// sending each line to `output` (if any) as soon as it is read.
+///
+/// Both pipes are read concurrently, so a process that fills one while
+/// the other is idle cannot block. The collected output is the same as
+/// [`wait_with_output`](tokio::process::Child::wait_with_output) returns.
+/// With `account`, the child's own resource usage is measured as well.
+pub(crate) async fn wait_streaming(
+    mut child: tokio::process::Child,
+    output: Option<&OutputSender>,
+    account: bool,
+) -> std::io::Result<(std::process::Output, ResourceUsage)> {
+    async fn read_lines(
+        pipe: Option<impl tokio::io::AsyncRead + Unpin>,
+        stream: OutputStream,
+        output: Option<&OutputSender>,
+    ) -> std::io::Result<Vec<u8>> {
+        use tokio::io::AsyncBufReadExt;
+
+        let mut buf = Vec::new();
+        let Some(pipe) = pipe else {
+            return Ok(buf);
+        };
+        let mut reader = tokio::io::BufReader::new(pipe);
+        loop {
+            let start = buf.len();
+            if reader.read_until(b'\n', &mut buf).await? == 0 {
+                return Ok(buf);
+            }
+            if let Some(output) = output {
+                let line = String::from_utf8_lossy(&buf[start..]);
+                let line = line.trim_end_matches(['\n', '\r']);
+                let _ = output.send(OutputLine {
+                    stream,
+                    line: line.to_string(),
+                });
+            }
+        }
+    }
+
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    let pid = child.id().filter(|_| account);
+    let wait = async {
+        // Measured before the child is reaped, which discards its usage.
+        let usage = match pid {
+            Some(pid) => accounting::wait_rusage(pid).await,
+            None => ResourceUsage::default(),
+        };
+        Ok((child.wait().await?, usage))
+    };
+    let (stdout, stderr, (status, usage)) = tokio::try_join!(
+        read_lines(stdout, OutputStream::Stdout, output),
+        read_lines(stderr, OutputStream::Stderr, output),
+        wait,
+    )?;
+    let output = std::process::Output {
+        status,
+        stdout,
+        stderr,
+    };
+    Ok((output, usage))
+}
+
 // ── Backend trait ───────────────────────────────────────────────────────
 
 /// Platform-specific isolation backend.
@@ -512,6 +670,51 @@ pub trait SandboxBackend: Send + Sync {
         config: &SandboxConfig,
         command: &[String],
     ) -> BoxFuture<'_, Result<SandboxResult, IsolationError>>;
+
+    /// Like [`execute`](SandboxBackend::execute), but also sends each line
+    /// of output to `output` as the command produces it.
+    ///
+    /// The result still carries the full stdout and stderr. The default
+    /// sends the lines only once the command has finished; backends that
+    /// can read output while the command runs override it.
+    fn execute_streaming(
+        &self,
+        config: &SandboxConfig,
+        command: &[String],
+        output: OutputSender,
+    ) -> BoxFuture<'_, Result<SandboxResult, IsolationError>> {
+        let run = self.execute(config, command);
+        Box::pin(async move {
+            let result = run.await?;
+            send_lines(&output, OutputStream::Stdout, &result.stdout);
+            send_lines(&output, OutputStream::Stderr, &result.stderr);
+            Ok(result)
+        })
+    }
+
+    /// Remove sandboxes left behind by a previous daemon that exited
+    /// without cleaning up, returning their backend-specific IDs.
+    ///
+    /// Called once at startup, before any new sandbox is created. Backends
+    /// whose sandboxes cannot outlive the daemon keep the default no-op.
+    fn reap_orphans(&self) -> BoxFuture<'_, Result<Vec<String>, IsolationError>> {
+        Box::pin(async { Ok(Vec::new()) })
+    }
+
+    /// Check that this backend can enforce `policy`, failing with
+    /// [`IsolationError::NetViolation`] if it cannot.
+    ///
+    /// The default rejects [`NetworkPolicy::AllowList`]; backends that
+    /// filter traffic by destination override it.
+    fn check_network(&self, policy: &NetworkPolicy) -> Result<(), IsolationError> {
+        match policy {
+            NetworkPolicy::AllowList(_) => Err(IsolationError::NetViolation(format!(
+                "backend '{}' cannot enforce a network allowlist",
+                self.name()
+            ))),
+            _ => Ok(()),
+        }
+    }
 }
 
 // ── Sandbox (high-level handle) ─────────────────────────────────────────
@@ -541,6 +744,7 @@ impl Sandbox {
                 backend.name()
             )));
         }
+        backend.check_network(&config.network)?;
         Ok(Self { config, backend })
     }
 
@@ -584,6 +788,8 @@ pub enum BackendPreference {
     LinuxNamespace,
     /// Docker container sandbox (MicroVM-level isolation).
     Docker,
+    /// Podman container sandbox, rootless where possible.
+    Podman,
     /// Firecracker microVM.
     Firecracker,
     /// Force no-op (development only).
@@ -597,6 +803,7 @@ impl fmt::Display for BackendPreference {
             BackendPreference::AppleVz => write!(f, "apple-vz"),
             BackendPreference::LinuxNamespace => write!(f, "linux-ns"),
             BackendPreference::Docker => write!(f, "docker"),
+            BackendPreference::Podman => write!(f, "podman"),
             BackendPreference::Firecracker => write!(f, "firecracker"),
             BackendPreference::Noop => write!(f, "noop"),
         }
@@ -605,24 +812,38 @@ impl fmt::Display for BackendPreference {
 
 /// Select the best available isolation backend for this platform.
 ///
-/// Priority: Docker > Firecracker (Linux) > Linux NS (Linux) >
+/// Priority: Docker > Podman > Firecracker (Linux) > Linux NS (Linux) >
 /// Apple VZ (macOS) > No-op.
 pub fn select_backend(preference: &BackendPreference) -> Box<dyn SandboxBackend> {
+    select_backend_with(preference, DockerSandboxBackend::default())
+}
+
+/// Like [`select_backend`], but use `docker` when the Docker backend is
+/// selected, and its images and image policy for Podman.
+pub fn select_backend_with(
+    preference: &BackendPreference,
+    docker: DockerSandboxBackend,
+) -> Box<dyn SandboxBackend> {
     match preference {
         BackendPreference::AppleVz => Box::new(AppleVzBackend::new(
             "/usr/local/share/crustyclaw/vmlinuz",
             "/usr/local/share/crustyclaw/initrd.img",
         )),
         BackendPreference::LinuxNamespace => Box::new(LinuxNamespaceBackend::new()),
-        BackendPreference::Docker => Box::new(DockerSandboxBackend::default()),
+        BackendPreference::Docker => Box::new(docker),
+        BackendPreference::Podman => Box::new(PodmanSandboxBackend::from_docker(docker)),
         BackendPreference::Firecracker => Box::new(FirecrackerBackend::default()),
         BackendPreference::Noop => Box::new(NoopBackend),
         BackendPreference::Auto => {
             // Prefer Docker if available (MicroVM-level isolation)
-            let docker = DockerSandboxBackend::default();
             if docker.available() {
                 return Box::new(docker);
             }
+            // Podman where Docker is not installed
+            let podman = PodmanSandboxBackend::from_docker(docker);
+            if podman.available() {
+                return Box::new(podman);
+            }
             // Firecracker on Linux with KVM
             if cfg!(target_os = "linux") {
                 let fc = FirecrackerBackend::default();
@@ -718,6 +939,24 @@ mod tests {
         assert!(config.validate().is_err());
     }
 
+    #[test]
+    fn test_sandbox_config_validation_bad_allow_list() {
+        let config = SandboxConfig::new("bad-net")
+            .with_network(NetworkPolicy::AllowList(vec!["10.0.0.0/40".to_string()]));
+        assert!(matches!(
+            config.validate(),
+            Err(IsolationError::NetViolation(_))
+        ));
+    }
+
+    #[test]
+    fn test_sandbox_rejects_unenforceable_allow_list() {
+        let config = SandboxConfig::new("fenced")
+            .with_network(NetworkPolicy::AllowList(vec!["10.0.0.0/8".to_string()]));
+        let result = Sandbox::new(config, Box::new(NoopBackend));
+        assert!(matches!(result, Err(IsolationError::NetViolation(_))));
+    }
+
     #[test]
     fn test_sandbox_creation_with_noop() {
         let config = SandboxConfig::new("test");
@@ -760,6 +999,12 @@ mod tests {
         assert_eq!(backend.name(), "firecracker");
     }
 
+    #[test]
+    fn test_select_podman() {
+        let backend = select_backend(&BackendPreference::Podman);
+        assert_eq!(backend.name(), "podman");
+    }
+
     #[test]
     fn test_select_backend_auto() {
         let backend = select_backend(&BackendPreference::Auto);
@@ -776,6 +1021,11 @@ mod tests {
             NetworkPolicy::AllowList(vec!["10.0.0.0/8".to_string()]).to_string(),
             "allow[10.0.0.0/8]"
         );
+        assert_eq!(
+            NetworkPolicy::from_str_loose("Outbound-Only"),
+            Some(NetworkPolicy::OutboundOnly)
+        );
+        assert_eq!(NetworkPolicy::from_str_loose("anywhere"), None);
     }
 
     #[test]
@@ -792,6 +1042,7 @@ mod tests {
             stderr: String::new(),
             elapsed: Duration::from_millis(100),
             peak_memory_bytes: None,
+            cpu_time: None,
         };
         assert!(result.success());
     }
@@ -804,6 +1055,7 @@ mod tests {
             stderr: "error".to_string(),
             elapsed: Duration::from_millis(50),
             peak_memory_bytes: Some(1024),
+            cpu_time: Some(Duration::from_millis(40)),
         };
         assert!(!result.success());
     }
@@ -814,6 +1066,7 @@ mod tests {
         assert_eq!(BackendPreference::AppleVz.to_string(), "apple-vz");
         assert_eq!(BackendPreference::LinuxNamespace.to_string(), "linux-ns");
         assert_eq!(BackendPreference::Docker.to_string(), "docker");
+        assert_eq!(BackendPreference::Podman.to_string(), "podman");
         assert_eq!(BackendPreference::Firecracker.to_string(), "firecracker");
         assert_eq!(BackendPreference::Noop.to_string(), "noop");
     }
@@ -0,0 +1,202 @@
+//! CIDR allowlists for [`NetworkPolicy::AllowList`].
+//!
+//! An allowlist is enforced with an nftables ruleset applied to the
+//! sandbox's own network namespace: loopback and replies to established
+//! connections are accepted, new outbound connections only to the listed
+//! ranges, and everything else is rejected. The linux-ns backend loads it
+//! into the namespace it creates; the docker backend loads it into a
+//! firewall sidecar whose namespace the skill container joins.
+//!
+//! DNS is not special-cased: a sandbox that resolves names needs its
+//! resolver's address in the list.
+
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use super::{IsolationError, NetworkPolicy};
+
+/// An IPv4 or IPv6 network in CIDR notation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    /// The network address.
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    /// The prefix length in bits.
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    /// Parse `10.0.0.0/8` or `fd00::/8`. A bare address is a single host
+    /// (`/32` or `/128`). Host bits must be zero.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|_| format!("{s:?} is not an IP address or CIDR range"))?;
+        let bits = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse::<u8>()
+                .ok()
+                .filter(|p| *p <= bits)
+                .ok_or_else(|| format!("{s:?} has an invalid prefix length (0-{bits})"))?,
+            None => bits,
+        };
+        let host_bits = match addr {
+            IpAddr::V4(v4) => {
+                u128::from(u32::from(v4))
+                    & u128::MAX.checked_shr(96 + u32::from(prefix)).unwrap_or(0)
+            }
+            IpAddr::V6(v6) => u128::from(v6).checked_shl(u32::from(prefix)).unwrap_or(0),
+        };
+        if host_bits != 0 {
+            return Err(format!("{s:?} has host bits set"));
+        }
+        Ok(Self { addr, prefix })
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+/// Parse the ranges of an allowlist, failing on the first invalid entry.
+pub fn parse_allow_list(entries: &[String]) -> Result<Vec<Cidr>, IsolationError> {
+    entries
+        .iter()
+        .map(|entry| {
+            entry
+                .trim()
+                .parse()
+                .map_err(|e| IsolationError::NetViolation(format!("network allowlist: {e}")))
+        })
+        .collect()
+}
+
+/// The nftables ruleset enforcing an allowlist, for `nft -f -` inside
+/// the sandbox's network namespace.
+pub fn nft_ruleset(cidrs: &[Cidr]) -> String {
+    let set = |v4: bool| {
+        let members: Vec<String> = cidrs
+            .iter()
+            .filter(|c| c.addr.is_ipv4() == v4)
+            .map(Cidr::to_string)
+            .collect();
+        (!members.is_empty()).then(|| members.join(", "))
+    };
+
+    let mut rules = String::from(
+        "table inet crustyclaw {\n\
+         \tchain input {\n\
+         \t\ttype filter hook input priority 0; policy drop;\n\
+         \t\tiifname \"lo\" accept\n\
+         \t\tct state established,related accept\n\
+         \t}\n\
+         \tchain output {\n\
+         \t\ttype filter hook output priority 0; policy drop;\n\
+         \t\toifname \"lo\" accept\n\
+         \t\tct state established,related accept\n",
+    );
+    if let Some(v4) = set(true) {
+        rules.push_str(&format!("\t\tip daddr {{ {v4} }} accept\n"));
+    }
+    if let Some(v6) = set(false) {
+        rules.push_str(&format!("\t\tip6 daddr {{ {v6} }} accept\n"));
+    }
+    rules.push_str("\t\treject\n\t}\n}\n");
+    rules
+}
+
+/// The allowlist of `policy`, parsed, or `None` for other policies.
+pub(crate) fn allow_list(policy: &NetworkPolicy) -> Result<Option<Vec<Cidr>>, IsolationError> {
+    match policy {
+        NetworkPolicy::AllowList(entries) => parse_allow_list(entries).map(Some),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cidr() {
+        let cidr: Cidr = "10.0.0.0/8".parse().unwrap();
+        assert_eq!(cidr.addr(), "10.0.0.0".parse::<IpAddr>().unwrap());
+        assert_eq!(cidr.prefix(), 8);
+        assert_eq!(cidr.to_string(), "10.0.0.0/8");
+
+        assert_eq!(
+            "192.168.1.7".parse::<Cidr>().unwrap().to_string(),
+            "192.168.1.7/32"
+        );
+        assert_eq!("fd00::/8".parse::<Cidr>().unwrap().to_string(), "fd00::/8");
+        assert_eq!("::1".parse::<Cidr>().unwrap().prefix(), 128);
+        assert_eq!("0.0.0.0/0".parse::<Cidr>().unwrap().prefix(), 0);
+    }
+
+    #[test]
+    fn test_parse_cidr_rejects_invalid() {
+        for bad in [
+            "",
+            "example.com",
+            "10.0.0.0/33",
+            "fd00::/129",
+            "10.0.0.0/x",
+            "10.0.0.1/8",
+            "fd00::1/64",
+        ] {
+            assert!(bad.parse::<Cidr>().is_err(), "{bad:?} should be rejected");
+        }
+    }
+
+    #[test]
+    fn test_parse_allow_list() {
+        let cidrs =
+            parse_allow_list(&["10.0.0.0/8".to_string(), " 2001:db8::/32 ".to_string()]).unwrap();
+        assert_eq!(cidrs.len(), 2);
+
+        let err = parse_allow_list(&["10.0.0.0/8".to_string(), "nope".to_string()]).unwrap_err();
+        assert!(matches!(err, IsolationError::NetViolation(_)));
+        assert!(err.to_string().contains("\"nope\""), "{err}");
+    }
+
+    #[test]
+    fn test_nft_ruleset() {
+        let cidrs =
+            parse_allow_list(&["10.0.0.0/8".to_string(), "192.168.1.1".to_string()]).unwrap();
+        let rules = nft_ruleset(&cidrs);
+        assert!(rules.starts_with("table inet crustyclaw {"));
+        assert!(rules.contains("policy drop;"));
+        assert!(rules.contains("oifname \"lo\" accept"));
+        assert!(rules.contains("ip daddr { 10.0.0.0/8, 192.168.1.1/32 } accept"));
+        assert!(!rules.contains("ip6 daddr"));
+        assert!(rules.trim_end().ends_with("reject\n\t}\n}"));
+
+        let rules = nft_ruleset(&["fd00::/8".parse().unwrap()]);
+        assert!(rules.contains("ip6 daddr { fd00::/8 } accept"));
+        assert!(!rules.contains("ip daddr"));
+
+        // An empty allowlist still permits loopback, nothing else.
+        let rules = nft_ruleset(&[]);
+        assert!(!rules.contains("daddr"));
+        assert!(rules.contains("reject"));
+    }
+}
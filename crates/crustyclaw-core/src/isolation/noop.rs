@@ -3,30 +3,28 @@
 //! Runs commands directly on the host with no isolation. Resource limits
 //! are logged but not enforced. **Never use in production.**
 
+use std::path::{Path, PathBuf};
+
 use crate::BoxFuture;
 
-use super::{IsolationError, SandboxBackend, SandboxConfig, SandboxResult};
+use super::{
+    IsolationError, OutputSender, SandboxBackend, SandboxConfig, SandboxResult, wait_streaming,
+};
 
 /// No-op sandbox backend for development and testing.
 pub struct NoopBackend;
 
-impl SandboxBackend for NoopBackend {
-    fn name(&self) -> &str {
-        "noop"
-    }
-
-    fn available(&self) -> bool {
-        true
-    }
-
-    fn execute(
+impl NoopBackend {
+    /// Run `command`, sending its output lines to `output` if given.
+    fn run(
         &self,
         config: &SandboxConfig,
         command: &[String],
+        output: Option<OutputSender>,
     ) -> BoxFuture<'_, Result<SandboxResult, IsolationError>> {
         let label = config.label.clone();
         let timeout = config.limits.timeout;
-        let workdir = config.workdir.clone();
+        let workdir = host_path(config, &config.workdir);
         let env = config.env.clone();
         let cmd = command.to_vec();
 
@@ -49,7 +47,8 @@ impl SandboxBackend for NoopBackend {
             proc.args(&cmd[1..])
                 .current_dir(&workdir)
                 .stdout(std::process::Stdio::piped())
-                .stderr(std::process::Stdio::piped());
+                .stderr(std::process::Stdio::piped())
+                .kill_on_drop(true);
 
             for (k, v) in &env {
                 proc.env(k, v);
@@ -59,11 +58,12 @@ impl SandboxBackend for NoopBackend {
                 .spawn()
                 .map_err(|e| IsolationError::Execution(format!("spawn failed: {e}")))?;
 
-            let output = match timeout {
+            let wait = wait_streaming(child, output.as_ref(), true);
+            let (output, usage) = match timeout {
                 Some(dur) => {
-                    let result = tokio::time::timeout(dur, child.wait_with_output()).await;
+                    let result = tokio::time::timeout(dur, wait).await;
                     match result {
-                        Ok(Ok(output)) => output,
+                        Ok(Ok(done)) => done,
                         Ok(Err(e)) => {
                             return Err(IsolationError::Execution(format!("wait failed: {e}")));
                         }
@@ -72,8 +72,7 @@ impl SandboxBackend for NoopBackend {
                         }
                     }
                 }
-                None => child
-                    .wait_with_output()
+                None => wait
                     .await
                     .map_err(|e| IsolationError::Execution(format!("wait failed: {e}")))?,
             };
@@ -85,17 +84,60 @@ impl SandboxBackend for NoopBackend {
                 stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
                 stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
                 elapsed,
-                peak_memory_bytes: None,
+                peak_memory_bytes: usage.peak_memory_bytes,
+                cpu_time: usage.cpu_time,
             })
         })
     }
 }
 
+impl SandboxBackend for NoopBackend {
+    fn name(&self) -> &str {
+        "noop"
+    }
+
+    fn available(&self) -> bool {
+        true
+    }
+
+    fn execute(
+        &self,
+        config: &SandboxConfig,
+        command: &[String],
+    ) -> BoxFuture<'_, Result<SandboxResult, IsolationError>> {
+        self.run(config, command, None)
+    }
+
+    fn execute_streaming(
+        &self,
+        config: &SandboxConfig,
+        command: &[String],
+        output: OutputSender,
+    ) -> BoxFuture<'_, Result<SandboxResult, IsolationError>> {
+        self.run(config, command, Some(output))
+    }
+}
+
+/// Map a sandbox path to the host: paths under a mount's guest path are
+/// rewritten to its host path, since nothing is actually mounted.
+fn host_path(config: &SandboxConfig, path: &Path) -> PathBuf {
+    config
+        .mounts
+        .iter()
+        .find_map(|m| {
+            path.strip_prefix(&m.guest_path)
+                .ok()
+                .map(|rest| m.host_path.join(rest))
+        })
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
 #[cfg(test)]
 mod tests {
     use std::time::Duration;
 
     use super::*;
+    use crate::isolation::SharedMount;
 
     #[test]
     fn test_noop_backend_available() {
@@ -127,6 +169,23 @@ mod tests {
         assert_eq!(result.stdout.trim(), "hello sandbox");
     }
 
+    #[tokio::test]
+    async fn test_noop_backend_workdir_follows_mounts() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = SandboxConfig::new("mounted")
+            .with_mount(SharedMount::read_only(dir.path(), "/skill"))
+            .with_workdir("/skill");
+
+        let result = NoopBackend
+            .execute(&config, &["pwd".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(
+            Path::new(result.stdout.trim()).canonicalize().unwrap(),
+            dir.path().canonicalize().unwrap()
+        );
+    }
+
     #[tokio::test]
     async fn test_noop_backend_execute_failure() {
         let config = SandboxConfig::new("fail-test").with_workdir("/tmp");
@@ -138,6 +197,21 @@ mod tests {
         assert!(!result.success());
     }
 
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_noop_backend_measures_usage() {
+        let config = SandboxConfig::new("usage-test").with_workdir("/tmp");
+        let command: Vec<String> = ["dd", "if=/dev/zero", "of=/dev/null", "bs=16M", "count=2"]
+            .map(String::from)
+            .to_vec();
+
+        let result = NoopBackend.execute(&config, &command).await.unwrap();
+        assert!(result.success());
+        // dd allocates its 16 MiB block buffer.
+        assert!(result.peak_memory_bytes.unwrap() >= 16 * 1024 * 1024);
+        assert!(result.cpu_time.is_some());
+    }
+
     #[tokio::test]
     async fn test_noop_backend_timeout() {
         let config = SandboxConfig::new("timeout-test")
@@ -0,0 +1,264 @@
+//! Podman container sandbox backend.
+//!
+//! Podman's CLI accepts Docker's arguments, so this backend runs
+//! containers through the [`DockerSandboxBackend`] machinery — limits,
+//! mounts, network policies, firewall sidecars, usage sampling, and
+//! orphan reaping all work the same — and differs only in a few `run`
+//! flags:
+//!
+//! | Flag | When |
+//! |------|------|
+//! | `--userns=keep-id` | Rootless, unless disabled: the invoking user keeps their UID in the container, so files written to rw mounts belong to them |
+//! | `--timeout=<secs>` | The sandbox has a timeout: Podman kills the container itself, even if the daemon has gone |
+//!
+//! Podman needs no daemon and runs rootless by default, so containers run
+//! with the invoking user's privileges. Whether it runs rootless is taken
+//! from the effective UID of the daemon process unless set with
+//! [`PodmanSandboxBackend::with_rootless`].
+
+use std::path::PathBuf;
+
+use crate::BoxFuture;
+
+use super::docker::Engine;
+use super::{
+    DockerSandboxBackend, ImagePolicy, IsolationError, NetworkPolicy, OutputSender, SandboxBackend,
+    SandboxConfig, SandboxResult,
+};
+
+/// Podman container sandbox backend.
+pub struct PodmanSandboxBackend {
+    /// The Docker-compatible backend driving the `podman` CLI.
+    inner: DockerSandboxBackend,
+    rootless: bool,
+    keep_id: bool,
+}
+
+impl PodmanSandboxBackend {
+    /// Create a new Podman sandbox backend.
+    pub fn new(podman_bin: impl Into<PathBuf>, default_image: impl Into<String>) -> Self {
+        Self::with_inner(DockerSandboxBackend::new(podman_bin, default_image))
+    }
+
+    /// A Podman backend with the default image, firewall image, and image
+    /// policy of `docker`, running `podman`.
+    pub fn from_docker(docker: DockerSandboxBackend) -> Self {
+        Self::with_inner(docker.with_bin("podman"))
+    }
+
+    fn with_inner(inner: DockerSandboxBackend) -> Self {
+        let rootless = !running_as_root();
+        Self {
+            inner: inner.with_engine(Engine::Podman {
+                rootless,
+                keep_id: true,
+            }),
+            rootless,
+            keep_id: true,
+        }
+    }
+
+    /// Builder: whether Podman runs rootless, instead of deciding from the
+    /// daemon's effective UID.
+    pub fn with_rootless(mut self, rootless: bool) -> Self {
+        self.rootless = rootless;
+        self.update_engine()
+    }
+
+    /// Builder: whether rootless containers keep the invoking user's UID
+    /// (`--userns=keep-id`). On by default.
+    pub fn with_keep_id(mut self, keep_id: bool) -> Self {
+        self.keep_id = keep_id;
+        self.update_engine()
+    }
+
+    /// Builder: enforce network allowlists with sidecars from `image`,
+    /// which must provide `nft`.
+    pub fn with_firewall_image(mut self, image: impl Into<String>) -> Self {
+        self.inner = self.inner.with_firewall_image(image);
+        self
+    }
+
+    /// Builder: check, pull, and pin images under `policy` (see
+    /// [`DockerSandboxBackend::with_image_policy`]).
+    pub fn with_image_policy(mut self, policy: ImagePolicy) -> Self {
+        self.inner = self.inner.with_image_policy(policy);
+        self
+    }
+
+    /// Pull `image` (or the default image); see
+    /// [`DockerSandboxBackend::pull_image`].
+    pub async fn pull_image(&self, image: Option<&str>) -> Result<String, IsolationError> {
+        self.inner.pull_image(image).await
+    }
+
+    /// Whether containers run rootless.
+    pub fn rootless(&self) -> bool {
+        self.rootless
+    }
+
+    fn update_engine(mut self) -> Self {
+        self.inner = self.inner.with_engine(Engine::Podman {
+            rootless: self.rootless,
+            keep_id: self.keep_id,
+        });
+        self
+    }
+}
+
+impl Default for PodmanSandboxBackend {
+    fn default() -> Self {
+        Self::new("podman", "alpine:latest")
+    }
+}
+
+impl SandboxBackend for PodmanSandboxBackend {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn available(&self) -> bool {
+        self.inner.available()
+    }
+
+    fn execute(
+        &self,
+        config: &SandboxConfig,
+        command: &[String],
+    ) -> BoxFuture<'_, Result<SandboxResult, IsolationError>> {
+        self.inner.execute(config, command)
+    }
+
+    fn execute_streaming(
+        &self,
+        config: &SandboxConfig,
+        command: &[String],
+        output: OutputSender,
+    ) -> BoxFuture<'_, Result<SandboxResult, IsolationError>> {
+        self.inner.execute_streaming(config, command, output)
+    }
+
+    /// Force-remove every container carrying the `crustyclaw.sandbox` label.
+    fn reap_orphans(&self) -> BoxFuture<'_, Result<Vec<String>, IsolationError>> {
+        self.inner.reap_orphans()
+    }
+
+    /// Allowlists are enforced when a firewall image is configured.
+    fn check_network(&self, policy: &NetworkPolicy) -> Result<(), IsolationError> {
+        self.inner.check_network(policy)
+    }
+}
+
+/// Whether the daemon runs with an effective UID of 0.
+#[cfg(unix)]
+#[allow(unsafe_code)]
+fn running_as_root() -> bool {
+    // SAFETY: geteuid takes no arguments and cannot fail.
+    unsafe { libc::geteuid() == 0 }
+}
+
+#[cfg(not(unix))]
+fn running_as_root() -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn args(backend: &PodmanSandboxBackend, config: &SandboxConfig) -> Vec<String> {
+        backend
+            .inner
+            .build_args(config, "test", &["true".to_string()])
+    }
+
+    #[test]
+    fn test_podman_backend_name() {
+        let backend = PodmanSandboxBackend::default();
+        assert_eq!(backend.name(), "podman");
+        assert_eq!(backend.rootless(), !running_as_root());
+    }
+
+    #[test]
+    fn test_podman_rootless_keeps_user_id() {
+        let config = SandboxConfig::new("podman-test");
+
+        let rootless = PodmanSandboxBackend::default().with_rootless(true);
+        assert!(args(&rootless, &config).contains(&"--userns=keep-id".to_string()));
+
+        let no_keep_id = PodmanSandboxBackend::default()
+            .with_rootless(true)
+            .with_keep_id(false);
+        assert!(!args(&no_keep_id, &config).contains(&"--userns=keep-id".to_string()));
+
+        let rootful = PodmanSandboxBackend::default().with_rootless(false);
+        assert!(!args(&rootful, &config).contains(&"--userns=keep-id".to_string()));
+    }
+
+    #[test]
+    fn test_podman_enforces_timeout() {
+        let backend = PodmanSandboxBackend::default();
+
+        let args_for = |timeout| {
+            let config = SandboxConfig::new("podman-test").with_timeout(timeout);
+            args(&backend, &config)
+        };
+        assert!(args_for(Duration::from_secs(30)).contains(&"--timeout=30".to_string()));
+        // Rounded up to whole seconds, at least one.
+        assert!(args_for(Duration::from_millis(1500)).contains(&"--timeout=2".to_string()));
+        assert!(args_for(Duration::from_millis(1)).contains(&"--timeout=1".to_string()));
+
+        let mut config = SandboxConfig::new("podman-test");
+        config.limits.timeout = None;
+        assert!(
+            !args(&backend, &config)
+                .iter()
+                .any(|a| a.starts_with("--timeout"))
+        );
+
+        // Docker has no such flag.
+        let docker = DockerSandboxBackend::default();
+        let config = SandboxConfig::new("docker-test").with_timeout(Duration::from_secs(30));
+        let docker_args = docker.build_args(&config, "test", &["true".to_string()]);
+        assert!(!docker_args.iter().any(|a| a.starts_with("--timeout")));
+    }
+
+    #[tokio::test]
+    async fn test_podman_runs_and_reaps_with_podman_bin() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let bin = tmp.path().join("podman");
+        let log = tmp.path().join("calls.log");
+        std::fs::write(
+            &bin,
+            format!(
+                "#!/bin/sh\necho \"$@\" >> {}\ncase \"$1\" in\n\
+                 run) echo hello ;;\n\
+                 ps) echo abc123 ;;\n\
+                 esac\n",
+                log.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&bin, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let backend = PodmanSandboxBackend::new(&bin, "alpine:latest").with_rootless(true);
+
+        let result = backend
+            .execute(&SandboxConfig::new("podman-run"), &["true".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(result.stdout, "hello\n");
+        assert_eq!(backend.reap_orphans().await.unwrap(), vec!["abc123"]);
+
+        let calls = std::fs::read_to_string(&log).unwrap();
+        let run = calls.lines().find(|l| l.starts_with("run ")).unwrap();
+        assert!(run.contains("--userns=keep-id"), "{run}");
+        assert!(
+            run.contains("--label crustyclaw.sandbox=podman-run"),
+            "{run}"
+        );
+        assert!(calls.contains("rm -f abc123"), "{calls}");
+    }
+}
@@ -0,0 +1,640 @@
+//! Concurrency-limited sandbox pool.
+//!
+//! [`SandboxPool`] enforces `isolation.max_concurrent` by wrapping a
+//! [`Semaphore`]: executions beyond the limit wait in FIFO order until a
+//! running sandbox finishes. Every execution is tracked with a
+//! [`SandboxState`] so the daemon can report which sandboxes are queued,
+//! running, or finished.
+//!
+//! The pool is shared (via `Arc`) between the skill engine and the IPC
+//! server so that all sandboxed work draws from the same budget.
+//!
+//! At shutdown, [`SandboxPool::drain`] refuses new and queued executions,
+//! gives running sandboxes a grace period to exit, and then kills the rest
+//! by dropping their backend futures (backends kill their processes or
+//! containers when dropped mid-run).
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use tokio::sync::{Notify, Semaphore, watch};
+
+use super::{IsolationError, OutputSender, SandboxBackend, SandboxConfig, SandboxResult};
+
+/// Number of finished executions retained for status reporting.
+const FINISHED_HISTORY: usize = 64;
+
+/// How long [`SandboxPool::drain`] waits for killed executions to unwind.
+const KILL_WAIT: Duration = Duration::from_secs(5);
+
+/// Error recorded for an execution whose caller stopped waiting for it.
+const CANCELLED: &str = "cancelled";
+
+/// Lifecycle state of a pooled sandbox execution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SandboxState {
+    /// Waiting for a free slot in the pool.
+    Queued,
+    /// Holding a slot and executing inside its backend.
+    Running,
+    /// Completed (successfully or not) and released its slot.
+    Finished,
+}
+
+impl fmt::Display for SandboxState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SandboxState::Queued => write!(f, "queued"),
+            SandboxState::Running => write!(f, "running"),
+            SandboxState::Finished => write!(f, "finished"),
+        }
+    }
+}
+
+/// A point-in-time view of one pooled sandbox execution.
+#[derive(Debug, Clone)]
+pub struct SandboxRun {
+    /// Pool-unique execution ID.
+    pub id: u64,
+    /// Sandbox label from the [`SandboxConfig`].
+    pub label: String,
+    /// Name of the backend executing the sandbox.
+    pub backend: String,
+    /// Current lifecycle state.
+    pub state: SandboxState,
+    /// Time since the execution was submitted to the pool.
+    pub age: Duration,
+    /// Exit code, once finished (`None` if the backend returned an error).
+    pub exit_code: Option<i32>,
+    /// Error message, if the execution failed before producing a result.
+    pub error: Option<String>,
+}
+
+/// Internal bookkeeping for a tracked execution.
+struct RunRecord {
+    label: String,
+    backend: String,
+    state: SandboxState,
+    submitted_at: Instant,
+    exit_code: Option<i32>,
+    error: Option<String>,
+}
+
+impl RunRecord {
+    fn snapshot(&self, id: u64) -> SandboxRun {
+        SandboxRun {
+            id,
+            label: self.label.clone(),
+            backend: self.backend.clone(),
+            state: self.state,
+            age: self.submitted_at.elapsed(),
+            exit_code: self.exit_code,
+            error: self.error.clone(),
+        }
+    }
+}
+
+/// What [`SandboxPool::drain`] did with the executions in the pool.
+#[derive(Debug, Clone, Default)]
+pub struct DrainReport {
+    /// Executions that finished within the grace period.
+    pub completed: usize,
+    /// Executions still running when the grace period ran out, as they
+    /// were just before being killed.
+    pub killed: Vec<SandboxRun>,
+    /// Queued executions refused without running.
+    pub refused: usize,
+}
+
+#[derive(Default)]
+struct Runs {
+    records: HashMap<u64, RunRecord>,
+    /// Finished execution IDs, oldest first, for history trimming.
+    finished: VecDeque<u64>,
+}
+
+/// A shared pool that bounds the number of concurrently running sandboxes.
+pub struct SandboxPool {
+    max_concurrent: usize,
+    semaphore: Semaphore,
+    next_id: AtomicU64,
+    runs: Mutex<Runs>,
+    /// Set by [`drain`](Self::drain); new executions are refused.
+    draining: AtomicBool,
+    /// Flipped to `true` to kill every running execution.
+    kill: watch::Sender<bool>,
+    /// Notified whenever an execution finishes.
+    finished: Notify,
+}
+
+impl SandboxPool {
+    /// Create a pool allowing at most `max_concurrent` running sandboxes.
+    ///
+    /// A limit of `0` is treated as `1` so that executions can always
+    /// make progress.
+    pub fn new(max_concurrent: usize) -> Self {
+        let max_concurrent = max_concurrent.max(1);
+        Self {
+            max_concurrent,
+            semaphore: Semaphore::new(max_concurrent),
+            next_id: AtomicU64::new(1),
+            runs: Mutex::new(Runs::default()),
+            draining: AtomicBool::new(false),
+            kill: watch::Sender::new(false),
+            finished: Notify::new(),
+        }
+    }
+
+    /// Maximum number of concurrently running sandboxes.
+    pub fn max_concurrent(&self) -> usize {
+        self.max_concurrent
+    }
+
+    /// Execute a command through `backend`, waiting for a free slot first.
+    ///
+    /// The execution is recorded as [`SandboxState::Queued`] until a slot
+    /// is available, [`SandboxState::Running`] while the backend runs, and
+    /// [`SandboxState::Finished`] afterwards — whatever the outcome, and
+    /// also when the returned future is dropped before completing.
+    ///
+    /// Fails with [`IsolationError::ShuttingDown`] once the pool is
+    /// draining, and with [`IsolationError::Killed`] if the drain grace
+    /// period runs out first. A network policy the backend cannot enforce
+    /// is rejected up front (see [`SandboxBackend::check_network`]).
+    pub async fn execute(
+        &self,
+        backend: &dyn SandboxBackend,
+        config: &SandboxConfig,
+        command: &[String],
+    ) -> Result<SandboxResult, IsolationError> {
+        self.run(backend, config, command, None).await
+    }
+
+    /// Like [`execute`](Self::execute), but sends each output line to
+    /// `output` as the backend produces it.
+    pub async fn execute_streaming(
+        &self,
+        backend: &dyn SandboxBackend,
+        config: &SandboxConfig,
+        command: &[String],
+        output: OutputSender,
+    ) -> Result<SandboxResult, IsolationError> {
+        self.run(backend, config, command, Some(output)).await
+    }
+
+    async fn run(
+        &self,
+        backend: &dyn SandboxBackend,
+        config: &SandboxConfig,
+        command: &[String],
+        output: Option<OutputSender>,
+    ) -> Result<SandboxResult, IsolationError> {
+        if self.draining.load(Ordering::Acquire) {
+            return Err(IsolationError::ShuttingDown);
+        }
+        backend.check_network(&config.network)?;
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.with_runs(|runs| {
+            runs.records.insert(
+                id,
+                RunRecord {
+                    label: config.label.clone(),
+                    backend: backend.name().to_string(),
+                    state: SandboxState::Queued,
+                    submitted_at: Instant::now(),
+                    exit_code: None,
+                    error: None,
+                },
+            );
+        });
+        // Finishes the record however this future ends — including being
+        // dropped while queued or running (a cancelled chat turn, a
+        // disconnected IPC client).
+        let mut guard = RunGuard {
+            pool: self,
+            id,
+            outcome: None,
+        };
+
+        let Ok(permit) = self.semaphore.acquire().await else {
+            let err = IsolationError::ShuttingDown;
+            guard.outcome = Some((None, Some(err.to_string())));
+            return Err(err);
+        };
+
+        self.with_runs(|runs| {
+            if let Some(record) = runs.records.get_mut(&id) {
+                record.state = SandboxState::Running;
+            }
+        });
+        tracing::debug!(id, label = %config.label, "Sandbox acquired pool slot");
+
+        let mut kill = self.kill.subscribe();
+        let result = tokio::select! {
+            result = match output {
+                Some(output) => backend.execute_streaming(config, command, output),
+                None => backend.execute(config, command),
+            } => result,
+            _ = kill.wait_for(|kill| *kill) => Err(IsolationError::Killed),
+        };
+        drop(permit);
+
+        guard.outcome = Some(match &result {
+            Ok(res) => (Some(res.exit_code), None),
+            Err(e) => (None, Some(e.to_string())),
+        });
+        result
+    }
+
+    /// Stop accepting executions and wait up to `grace` for running ones
+    /// to finish, then kill the rest.
+    ///
+    /// Queued executions fail at once with [`IsolationError::ShuttingDown`],
+    /// as does every later [`execute`](Self::execute) call.
+    pub async fn drain(&self, grace: Duration) -> DrainReport {
+        self.draining.store(true, Ordering::Release);
+        let refused = self.queued();
+        let in_flight = self.running();
+        self.semaphore.close();
+
+        self.wait_idle(grace).await;
+        let killed: Vec<SandboxRun> = self
+            .runs()
+            .into_iter()
+            .filter(|run| run.state == SandboxState::Running)
+            .collect();
+        if !killed.is_empty() {
+            self.kill.send_replace(true);
+            self.wait_idle(KILL_WAIT).await;
+        }
+        DrainReport {
+            completed: in_flight.saturating_sub(killed.len()),
+            killed,
+            refused,
+        }
+    }
+
+    /// Wait up to `timeout` for no execution to be running.
+    async fn wait_idle(&self, timeout: Duration) {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let finished = self.finished.notified();
+            tokio::pin!(finished);
+            finished.as_mut().enable();
+            if self.running() == 0 || tokio::time::timeout_at(deadline, finished).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Snapshot of all tracked executions, ordered by ID.
+    pub fn runs(&self) -> Vec<SandboxRun> {
+        let mut runs = self.with_runs(|runs| {
+            runs.records
+                .iter()
+                .map(|(id, record)| record.snapshot(*id))
+                .collect::<Vec<_>>()
+        });
+        runs.sort_by_key(|r| r.id);
+        runs
+    }
+
+    /// Number of executions waiting for a slot.
+    pub fn queued(&self) -> usize {
+        self.count(SandboxState::Queued)
+    }
+
+    /// Number of executions currently holding a slot.
+    pub fn running(&self) -> usize {
+        self.count(SandboxState::Running)
+    }
+
+    fn count(&self, state: SandboxState) -> usize {
+        self.with_runs(|runs| {
+            runs.records
+                .values()
+                .filter(|record| record.state == state)
+                .count()
+        })
+    }
+
+    fn finish(&self, id: u64, exit_code: Option<i32>, error: Option<String>) {
+        self.with_runs(|runs| {
+            if let Some(record) = runs.records.get_mut(&id) {
+                record.state = SandboxState::Finished;
+                record.exit_code = exit_code;
+                record.error = error;
+            }
+            runs.finished.push_back(id);
+            while runs.finished.len() > FINISHED_HISTORY {
+                if let Some(old) = runs.finished.pop_front() {
+                    runs.records.remove(&old);
+                }
+            }
+        });
+        self.finished.notify_waiters();
+    }
+
+    fn with_runs<T>(&self, f: impl FnOnce(&mut Runs) -> T) -> T {
+        let mut runs = self.runs.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut runs)
+    }
+}
+
+/// Marks a pooled execution [`SandboxState::Finished`] when dropped.
+struct RunGuard<'a> {
+    pool: &'a SandboxPool,
+    id: u64,
+    /// Exit code and error to record; `None` if the execution was dropped
+    /// before it completed.
+    outcome: Option<(Option<i32>, Option<String>)>,
+}
+
+impl Drop for RunGuard<'_> {
+    fn drop(&mut self) {
+        let (exit_code, error) = self
+            .outcome
+            .take()
+            .unwrap_or_else(|| (None, Some(CANCELLED.to_string())));
+        self.pool.finish(self.id, exit_code, error);
+    }
+}
+
+impl Default for SandboxPool {
+    /// A pool with the default `isolation.max_concurrent` limit (4).
+    fn default() -> Self {
+        Self::new(4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::isolation::{NetworkPolicy, NoopBackend, OutputLine, OutputStream};
+    use std::sync::Arc;
+
+    fn sh(script: &str) -> Vec<String> {
+        vec!["sh".to_string(), "-c".to_string(), script.to_string()]
+    }
+
+    #[test]
+    fn test_zero_limit_clamped() {
+        let pool = SandboxPool::new(0);
+        assert_eq!(pool.max_concurrent(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_execute_records_finished() {
+        let pool = SandboxPool::new(2);
+        let config = SandboxConfig::new("pool-echo").with_workdir("/tmp");
+
+        let result = pool
+            .execute(&NoopBackend, &config, &sh("echo pooled"))
+            .await
+            .unwrap();
+        assert_eq!(result.stdout.trim(), "pooled");
+
+        let runs = pool.runs();
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].label, "pool-echo");
+        assert_eq!(runs[0].backend, "noop");
+        assert_eq!(runs[0].state, SandboxState::Finished);
+        assert_eq!(runs[0].exit_code, Some(0));
+        assert_eq!(pool.running(), 0);
+        assert_eq!(pool.queued(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_execute_streaming_sends_lines_while_running() {
+        let pool = SandboxPool::new(1);
+        let config = SandboxConfig::new("pool-stream").with_workdir("/tmp");
+        let command = sh("echo first; echo oops >&2; sleep 0.3; echo second");
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let run = pool.execute_streaming(&NoopBackend, &config, &command, tx);
+        tokio::pin!(run);
+        // Both early lines arrive before the command has finished.
+        let mut early = Vec::new();
+        while early.len() < 2 {
+            tokio::select! {
+                line = rx.recv() => early.push(line.unwrap()),
+                _ = &mut run => panic!("finished before streaming its output"),
+            }
+        }
+        early.sort_by_key(|l| l.stream.name());
+        assert_eq!(
+            early,
+            [
+                OutputLine {
+                    stream: OutputStream::Stderr,
+                    line: "oops".to_string(),
+                },
+                OutputLine {
+                    stream: OutputStream::Stdout,
+                    line: "first".to_string(),
+                },
+            ]
+        );
+
+        let result = run.await.unwrap();
+        assert_eq!(result.stdout, "first\nsecond\n");
+        assert_eq!(result.stderr, "oops\n");
+        assert_eq!(rx.recv().await.unwrap().line, "second");
+        assert!(rx.recv().await.is_none());
+        assert_eq!(pool.runs()[0].exit_code, Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_execute_records_error() {
+        let pool = SandboxPool::new(1);
+        let config = SandboxConfig::new("pool-missing").with_workdir("/tmp");
+
+        let result = pool
+            .execute(
+                &NoopBackend,
+                &config,
+                &["/nonexistent/crustyclaw-binary".to_string()],
+            )
+            .await;
+        assert!(result.is_err());
+
+        let runs = pool.runs();
+        assert_eq!(runs[0].state, SandboxState::Finished);
+        assert!(runs[0].error.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_execute_rejects_unenforceable_network() {
+        let pool = SandboxPool::new(1);
+        let config = SandboxConfig::new("pool-fenced")
+            .with_network(NetworkPolicy::AllowList(vec!["10.0.0.0/8".to_string()]));
+
+        let result = pool.execute(&NoopBackend, &config, &sh("true")).await;
+        assert!(matches!(result, Err(IsolationError::NetViolation(_))));
+        assert!(pool.runs().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_dropped_execute_finishes_record() {
+        let pool = SandboxPool::new(1);
+        let config = SandboxConfig::new("pool-dropped").with_workdir("/tmp");
+
+        // One execution holds the only slot, another waits behind it;
+        // both futures are dropped mid-flight.
+        let (slow, fast) = (sh("sleep 5"), sh("true"));
+        let mut running = Box::pin(pool.execute(&NoopBackend, &config, &slow));
+        let mut queued = Box::pin(pool.execute(&NoopBackend, &config, &fast));
+        let pending = tokio::time::timeout(Duration::from_millis(100), async {
+            tokio::join!(&mut running, &mut queued)
+        })
+        .await;
+        assert!(pending.is_err());
+        assert_eq!(pool.running(), 1);
+        assert_eq!(pool.queued(), 1);
+        drop(running);
+        drop(queued);
+
+        assert_eq!(pool.running(), 0);
+        assert_eq!(pool.queued(), 0);
+        let runs = pool.runs();
+        assert_eq!(runs.len(), 2);
+        for run in &runs {
+            assert_eq!(run.state, SandboxState::Finished);
+            assert_eq!(run.error.as_deref(), Some(CANCELLED));
+        }
+
+        // Nothing is left for a drain to wait on or kill.
+        let report =
+            tokio::time::timeout(Duration::from_secs(1), pool.drain(Duration::from_secs(30)))
+                .await
+                .unwrap();
+        assert_eq!(report.completed, 0);
+        assert_eq!(report.refused, 0);
+        assert!(report.killed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_limit_queues_excess() {
+        let pool = Arc::new(SandboxPool::new(1));
+        let config = SandboxConfig::new("pool-slow").with_workdir("/tmp");
+
+        let first = tokio::spawn({
+            let pool = pool.clone();
+            let config = config.clone();
+            async move { pool.execute(&NoopBackend, &config, &sh("sleep 0.3")).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let second = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.execute(&NoopBackend, &config, &sh("true")).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert_eq!(pool.running(), 1);
+        assert_eq!(pool.queued(), 1);
+
+        first.await.unwrap().unwrap();
+        second.await.unwrap().unwrap();
+        assert_eq!(pool.running(), 0);
+        assert!(
+            pool.runs()
+                .iter()
+                .all(|r| r.state == SandboxState::Finished)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_finished_history_is_bounded() {
+        let pool = SandboxPool::new(4);
+        let config = SandboxConfig::new("pool-history").with_workdir("/tmp");
+
+        for _ in 0..FINISHED_HISTORY + 5 {
+            pool.execute(&NoopBackend, &config, &sh("true"))
+                .await
+                .unwrap();
+        }
+        let runs = pool.runs();
+        assert_eq!(runs.len(), FINISHED_HISTORY);
+        assert_eq!(runs[0].id, 6);
+    }
+
+    #[tokio::test]
+    async fn test_drain_waits_for_running() {
+        let pool = Arc::new(SandboxPool::new(1));
+        let config = SandboxConfig::new("drain-short").with_workdir("/tmp");
+        let running = tokio::spawn({
+            let (pool, config) = (pool.clone(), config.clone());
+            async move { pool.execute(&NoopBackend, &config, &sh("sleep 0.2")).await }
+        });
+        let queued = tokio::spawn({
+            let (pool, config) = (pool.clone(), config.clone());
+            async move {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                pool.execute(&NoopBackend, &config, &sh("true")).await
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(pool.queued(), 1);
+
+        let report = pool.drain(Duration::from_secs(5)).await;
+        assert_eq!(report.completed, 1);
+        assert_eq!(report.refused, 1);
+        assert!(report.killed.is_empty());
+        assert!(running.await.unwrap().is_ok());
+        assert!(matches!(
+            queued.await.unwrap(),
+            Err(IsolationError::ShuttingDown)
+        ));
+        assert!(matches!(
+            pool.execute(&NoopBackend, &config, &sh("true")).await,
+            Err(IsolationError::ShuttingDown)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_drain_kills_after_grace() {
+        let tmp = tempfile::tempdir().unwrap();
+        let marker = tmp.path().join("finished");
+        let pool = Arc::new(SandboxPool::new(2));
+        let config = SandboxConfig::new("drain-long").with_workdir("/tmp");
+        let script = format!("sleep 0.5 && touch {}", marker.display());
+        let running = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.execute(&NoopBackend, &config, &sh(&script)).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let report = pool.drain(Duration::from_millis(100)).await;
+        assert_eq!(report.completed, 0);
+        assert_eq!(report.killed.len(), 1);
+        assert_eq!(report.killed[0].label, "drain-long");
+        assert!(matches!(
+            running.await.unwrap(),
+            Err(IsolationError::Killed)
+        ));
+        assert_eq!(pool.running(), 0);
+
+        // The process was killed, not left to finish.
+        tokio::time::sleep(Duration::from_millis(700)).await;
+        assert!(!marker.exists());
+    }
+
+    #[tokio::test]
+    async fn test_drain_idle_pool() {
+        let report = SandboxPool::new(1).drain(Duration::from_secs(5)).await;
+        assert_eq!(report.completed, 0);
+        assert_eq!(report.refused, 0);
+        assert!(report.killed.is_empty());
+    }
+
+    #[test]
+    fn test_state_display() {
+        assert_eq!(SandboxState::Queued.to_string(), "queued");
+        assert_eq!(SandboxState::Running.to_string(), "running");
+        assert_eq!(SandboxState::Finished.to_string(), "finished");
+    }
+}
@@ -6,22 +6,27 @@
 //!
 //! | Trust tier | Isolation level | Backend |
 //! |-----------|----------------|---------|
-//! | `Trusted` | L1 (container/noop) | NoopBackend or Docker |
+//! | `Trusted` | L1 (container/noop) | NoopBackend, Docker, or Podman |
 //! | `Internal` | L2 (gVisor/Linux NS) | LinuxNamespaceBackend |
 //! | `Untrusted` | L3 (microVM) | Docker Sandbox or Firecracker |
 //! | `LlmGenerated` | L3 (microVM) | Docker Sandbox or Firecracker |
+//!
+//! Both columns are configurable per tier under `[isolation.trust]`; see
+//! [`TrustBasedSelector::from_config`].
 
+use std::collections::HashMap;
 use std::fmt;
 
 use super::{
     BackendPreference, DockerSandboxBackend, FirecrackerBackend, LinuxNamespaceBackend,
-    NoopBackend, SandboxBackend, select_backend,
+    NoopBackend, PodmanSandboxBackend, SandboxBackend, select_backend_with,
 };
 
 /// Trust level assigned to a skill.
 ///
 /// Determines the minimum isolation boundary required for execution.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Tiers order by the isolation they require, weakest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum TrustTier {
     /// First-party code, fully audited. Minimal isolation.
     Trusted,
@@ -34,6 +39,14 @@ pub enum TrustTier {
 }
 
 impl TrustTier {
+    /// Every tier, weakest requirement first.
+    pub const ALL: [TrustTier; 4] = [
+        TrustTier::Trusted,
+        TrustTier::Internal,
+        TrustTier::Untrusted,
+        TrustTier::LlmGenerated,
+    ];
+
     /// Parse a trust tier from a string.
     pub fn from_str_loose(s: &str) -> Option<Self> {
         match s.to_lowercase().as_str() {
@@ -80,6 +93,16 @@ impl IsolationLevel {
             _ => None,
         }
     }
+
+    /// The isolation level a backend provides, by [`SandboxBackend::name`].
+    pub fn of_backend(name: &str) -> Option<Self> {
+        match name {
+            "noop" | "podman" => Some(Self::L1Container),
+            "linux-ns" => Some(Self::L2Namespace),
+            "docker" | "firecracker" | "apple-vz" => Some(Self::L3MicroVm),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for IsolationLevel {
@@ -95,10 +118,18 @@ impl fmt::Display for IsolationLevel {
 /// Selects the appropriate isolation backend based on trust tier.
 ///
 /// The selector maps trust tiers to isolation levels, then resolves
-/// each level to the best available backend on the current platform.
+/// each level to the best available backend on the current platform,
+/// unless the tier has a backend of its own.
+#[derive(Clone)]
 pub struct TrustBasedSelector {
     /// Override: force a specific backend regardless of trust tier.
     forced_backend: Option<BackendPreference>,
+    /// The Docker backend to select, also the template for Podman.
+    docker: DockerSandboxBackend,
+    /// Tiers requiring a level other than their default.
+    levels: HashMap<TrustTier, IsolationLevel>,
+    /// Tiers running on a specific backend.
+    backends: HashMap<TrustTier, BackendPreference>,
 }
 
 impl TrustBasedSelector {
@@ -106,7 +137,32 @@ impl TrustBasedSelector {
     pub fn new() -> Self {
         Self {
             forced_backend: None,
+            docker: DockerSandboxBackend::default(),
+            levels: HashMap::new(),
+            backends: HashMap::new(),
+        }
+    }
+
+    /// A selector with the Docker backend, tier levels, and tier backends
+    /// of `config` (the `[isolation.trust]` section). Entries that failed
+    /// to parse are ignored; config validation rejects them at load.
+    pub fn from_config(config: &crustyclaw_config::IsolationConfig) -> Self {
+        let mut selector = Self::new().with_docker(DockerSandboxBackend::from_config(config));
+        for tier in TrustTier::ALL {
+            let name = tier.to_string();
+            if let Some(level) = config
+                .trust
+                .levels
+                .get(&name)
+                .and_then(|level| IsolationLevel::from_str_loose(level))
+            {
+                selector = selector.with_required_level(tier, level);
+            }
+            if let Some(pref) = config.trust.backend(&name).and_then(backend_preference) {
+                selector = selector.with_tier_backend(tier, pref);
+            }
         }
+        selector
     }
 
     /// Override: always use a specific backend regardless of trust tier.
@@ -115,8 +171,32 @@ impl TrustBasedSelector {
         self
     }
 
-    /// Map a trust tier to its minimum required isolation level.
-    pub fn required_level(tier: TrustTier) -> IsolationLevel {
+    /// Select (clones of) `docker` where the Docker backend is chosen,
+    /// with its images and image policy for Podman.
+    pub fn with_docker(mut self, docker: DockerSandboxBackend) -> Self {
+        self.docker = docker;
+        self
+    }
+
+    /// Require `level` for `tier` instead of its default level.
+    pub fn with_required_level(mut self, tier: TrustTier, level: IsolationLevel) -> Self {
+        self.levels.insert(tier, level);
+        self
+    }
+
+    /// Run `tier` on `pref` instead of the best backend for its level.
+    /// [`BackendPreference::Auto`] restores the default.
+    pub fn with_tier_backend(mut self, tier: TrustTier, pref: BackendPreference) -> Self {
+        if pref == BackendPreference::Auto {
+            self.backends.remove(&tier);
+        } else {
+            self.backends.insert(tier, pref);
+        }
+        self
+    }
+
+    /// The isolation level a trust tier requires by default.
+    pub fn default_level(tier: TrustTier) -> IsolationLevel {
         match tier {
             TrustTier::Trusted => IsolationLevel::L1Container,
             TrustTier::Internal => IsolationLevel::L2Namespace,
@@ -124,28 +204,56 @@ impl TrustBasedSelector {
         }
     }
 
+    /// Map a trust tier to its minimum required isolation level.
+    pub fn required_level(&self, tier: TrustTier) -> IsolationLevel {
+        self.levels
+            .get(&tier)
+            .copied()
+            .unwrap_or_else(|| Self::default_level(tier))
+    }
+
+    /// The backend configured for `tier`, if it has one.
+    pub fn tier_backend(&self, tier: TrustTier) -> Option<&BackendPreference> {
+        self.backends.get(&tier)
+    }
+
     /// Select the best available backend for the given trust tier.
     ///
-    /// If a forced backend is configured, returns that regardless of tier.
-    /// Otherwise, selects the appropriate backend for the trust level:
+    /// If a forced backend is configured, returns that regardless of tier,
+    /// then the tier's own backend, if it has one. Otherwise, selects the
+    /// appropriate backend for the tier's required level (see
+    /// [`select_level`](Self::select_level)).
+    pub fn select(&self, tier: TrustTier) -> Box<dyn SandboxBackend> {
+        // Forced override
+        if let Some(pref) = &self.forced_backend {
+            return select_backend_with(pref, self.docker.clone());
+        }
+        if let Some(pref) = self.backends.get(&tier) {
+            return select_backend_with(pref, self.docker.clone());
+        }
+        self.select_level(self.required_level(tier), tier)
+    }
+
+    /// Select the best available backend providing `level`, on behalf of
+    /// `tier` (for logging):
     ///
     /// - L1: NoopBackend (dev) or Docker (prod)
     /// - L2: LinuxNamespaceBackend
     /// - L3: Docker Sandbox or Firecracker
-    pub fn select(&self, tier: TrustTier) -> Box<dyn SandboxBackend> {
-        // Forced override
+    pub fn select_level(&self, level: IsolationLevel, tier: TrustTier) -> Box<dyn SandboxBackend> {
         if let Some(pref) = &self.forced_backend {
-            return select_backend(pref);
+            return select_backend_with(pref, self.docker.clone());
         }
 
-        let level = Self::required_level(tier);
-
         match level {
             IsolationLevel::L1Container => {
-                // Prefer Docker for L1 if available, else noop
-                let docker = DockerSandboxBackend::default();
+                // Prefer Docker for L1 if available, then Podman, else noop
+                let docker = self.docker.clone();
+                let podman = PodmanSandboxBackend::from_docker(self.docker.clone());
                 if docker.available() {
                     Box::new(docker)
+                } else if podman.available() {
+                    Box::new(podman)
                 } else {
                     Box::new(NoopBackend)
                 }
@@ -156,7 +264,7 @@ impl TrustBasedSelector {
                     Box::new(ns)
                 } else {
                     // Fall back to Docker
-                    let docker = DockerSandboxBackend::default();
+                    let docker = self.docker.clone();
                     if docker.available() {
                         Box::new(docker)
                     } else {
@@ -176,7 +284,7 @@ impl TrustBasedSelector {
                     return Box::new(fc);
                 }
                 // Docker Sandbox as fallback
-                let docker = DockerSandboxBackend::default();
+                let docker = self.docker.clone();
                 if docker.available() {
                     return Box::new(docker);
                 }
@@ -199,6 +307,20 @@ impl TrustBasedSelector {
     }
 }
 
+/// The backend preference named `name` in config; `None` if unknown.
+fn backend_preference(name: &str) -> Option<BackendPreference> {
+    match name {
+        "auto" => Some(BackendPreference::Auto),
+        "docker" => Some(BackendPreference::Docker),
+        "podman" => Some(BackendPreference::Podman),
+        "firecracker" => Some(BackendPreference::Firecracker),
+        "apple-vz" => Some(BackendPreference::AppleVz),
+        "linux-ns" => Some(BackendPreference::LinuxNamespace),
+        "noop" => Some(BackendPreference::Noop),
+        _ => None,
+    }
+}
+
 impl Default for TrustBasedSelector {
     fn default() -> Self {
         Self::new()
@@ -278,6 +400,27 @@ mod tests {
         assert_eq!(IsolationLevel::from_str_loose("invalid"), None);
     }
 
+    #[test]
+    fn test_isolation_level_of_backend() {
+        assert_eq!(
+            IsolationLevel::of_backend(NoopBackend.name()),
+            Some(IsolationLevel::L1Container)
+        );
+        assert_eq!(
+            IsolationLevel::of_backend(PodmanSandboxBackend::default().name()),
+            Some(IsolationLevel::L1Container)
+        );
+        assert_eq!(
+            IsolationLevel::of_backend(LinuxNamespaceBackend::new().name()),
+            Some(IsolationLevel::L2Namespace)
+        );
+        assert_eq!(
+            IsolationLevel::of_backend(FirecrackerBackend::default().name()),
+            Some(IsolationLevel::L3MicroVm)
+        );
+        assert_eq!(IsolationLevel::of_backend("unknown"), None);
+    }
+
     #[test]
     fn test_isolation_level_ordering() {
         assert!(IsolationLevel::L1Container < IsolationLevel::L2Namespace);
@@ -286,22 +429,61 @@ mod tests {
 
     #[test]
     fn test_required_level_mapping() {
+        let selector = TrustBasedSelector::new();
         assert_eq!(
-            TrustBasedSelector::required_level(TrustTier::Trusted),
+            selector.required_level(TrustTier::Trusted),
             IsolationLevel::L1Container
         );
         assert_eq!(
-            TrustBasedSelector::required_level(TrustTier::Internal),
+            selector.required_level(TrustTier::Internal),
             IsolationLevel::L2Namespace
         );
         assert_eq!(
-            TrustBasedSelector::required_level(TrustTier::Untrusted),
+            selector.required_level(TrustTier::Untrusted),
             IsolationLevel::L3MicroVm
         );
         assert_eq!(
-            TrustBasedSelector::required_level(TrustTier::LlmGenerated),
+            selector.required_level(TrustTier::LlmGenerated),
+            IsolationLevel::L3MicroVm
+        );
+    }
+
+    #[test]
+    fn test_selector_from_config() {
+        let config: crustyclaw_config::AppConfig = crustyclaw_config::AppConfig::parse(
+            r#"
+            [isolation.trust]
+            trusted = "noop"
+            internal = "docker"
+            untrusted = "auto"
+
+            [isolation.trust.levels]
+            internal = "l3"
+            "#,
+        )
+        .unwrap();
+        let selector = TrustBasedSelector::from_config(&config.isolation);
+
+        assert_eq!(
+            selector.required_level(TrustTier::Internal),
             IsolationLevel::L3MicroVm
         );
+        assert_eq!(
+            selector.required_level(TrustTier::Trusted),
+            IsolationLevel::L1Container
+        );
+        assert_eq!(
+            selector.tier_backend(TrustTier::Trusted),
+            Some(&BackendPreference::Noop)
+        );
+        assert_eq!(
+            selector.tier_backend(TrustTier::Internal),
+            Some(&BackendPreference::Docker)
+        );
+        // "auto" leaves the tier to its level.
+        assert_eq!(selector.tier_backend(TrustTier::Untrusted), None);
+        assert_eq!(selector.select(TrustTier::Trusted).name(), "noop");
+        assert_eq!(selector.select(TrustTier::Internal).name(), "docker");
     }
 
     #[test]
@@ -18,15 +18,37 @@ use std::pin::Pin;
 /// alias keeps those signatures readable.
 pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
 
+/// Per-channel sender allowlists, role mapping, quarantine, and access audit.
+pub mod access;
+/// LLM tool-use loop driving conversations through the tool executor.
+pub mod agent;
+/// Attachment spool — quota-checked files from channels, mounted read-only for skills and tools.
+pub mod attachment;
 /// Type-state authentication lifecycle (`Unauthenticated → Authenticated → Authorized`).
 /// Includes transparent local-identity authentication for CLI/TUI.
 pub mod auth;
 /// Compile-time build metadata (version, git hash, profile).
 pub mod build_info;
+/// Interactive chat sessions running the agent loop for `crustyclaw chat`.
+pub mod chat;
+/// Operator-defined chat command aliases that run skills without the LLM.
+pub mod commands;
 /// Context engine — tool registry, codebase indexing, and context window management.
 pub mod context;
+/// Per-conversation chat history (JSONL transcripts) with list, resume, and prune.
+pub mod conversation;
 /// Async daemon runtime and message bus.
 pub mod daemon;
+/// PID file, stale-PID detection, and detaching for `start --daemonize`.
+pub mod daemonize;
+/// Environment diagnostics for `crustyclaw doctor`.
+pub mod doctor;
+/// Live feed of daemon events (reloads, skill runs, denials, messages) for `GET /events`.
+pub mod events;
+/// Liveness and per-component readiness checks for `/health/live` and `/health/ready`.
+pub mod health;
+/// Host metrics sampler (load, memory, disk, file descriptors).
+pub mod host;
 /// IPC layer — Unix domain socket transport for CLI/TUI control.
 pub mod ipc;
 /// Multi-backend sandbox isolation for skills (Docker, Firecracker, Apple VZ, Linux NS, noop).
@@ -35,24 +57,61 @@ pub mod isolation;
 pub mod llm;
 /// In-memory log collector for the TUI.
 pub mod logging;
+/// MCP client importing tools from external Model Context Protocol servers.
+pub mod mcp;
+/// Long-term conversation memory: rolling summaries and key facts.
+pub mod memory;
 /// Message envelope types for the internal bus.
 pub mod message;
-/// Plugin registry for Forgejo Action extensions.
+/// Prometheus metrics (message, sandbox, LLM, and denial counters; latency histograms).
+pub mod metrics;
+/// Plugin registry for Forgejo Action extensions and native plugin loading.
 pub mod plugin;
+/// Fail-fast startup checks (socket dir, staging dir, isolation, secrets, LLM).
+pub mod preflight;
+/// Audit records of the policy bundles the daemon applies.
+pub mod provenance;
+/// Per-role usage quotas (LLM tokens per day, sandbox executions per hour).
+pub mod quota;
+/// Crash recovery — in-flight run journal and orphaned sandbox reaping.
+pub mod recovery;
+/// Outbound response post-processing hooks (scrubbing, secret scan, splitting).
+pub mod response;
+/// Cron-scheduled skill runs and agent prompts from `[[schedules]]`.
+pub mod scheduler;
 /// Secrets management — loading, storage, zeroization, and container injection.
 pub mod secrets;
 /// Compile-time security assertions and key management.
 pub mod security;
 /// Skill trait and runtime registry.
 pub mod skill;
+/// Pluggable daemon state storage (filesystem or SQLite) for namespaced blobs.
+pub mod storage;
+/// Supervised daemon tasks, restarted with exponential backoff when they crash.
+pub mod supervisor;
+/// systemd integration (sd_notify readiness, watchdog keepalives, socket activation).
+pub mod systemd;
+/// Opt-in anonymous usage telemetry with local differential-privacy noise.
+pub mod telemetry;
+/// Token usage accounting — daily totals per model, role, skill, and conversation.
+pub mod usage;
+/// Non-fatal startup diagnostics (deprecations, insecure settings, unavailable backends).
+pub mod warnings;
+/// Generic HTTP webhook channel with HMAC-signed messages in both directions.
+pub mod webhook;
+/// Per-conversation workspaces for operator and agent file transfer.
+pub mod workspace;
 
 pub use auth::LocalIdentity;
 pub use daemon::Daemon;
+pub use host::{HostMetrics, HostSampler};
 pub use ipc::{IpcClient, IpcState};
 pub use isolation::{
     CredentialProxy, DockerSandboxBackend, FirecrackerBackend, IsolationLevel, Sandbox,
-    SandboxBackend, SandboxConfig, TrustBasedSelector, TrustTier,
+    SandboxBackend, SandboxConfig, SandboxPool, TrustBasedSelector, TrustTier,
 };
-pub use logging::{LogCollector, LogReader};
+pub use logging::{DEFAULT_LOG_CAPACITY, LogCollector, LogControl, LogReader};
 pub use plugin::PluginRegistry;
 pub use secrets::SecretStore;
+pub use warnings::WarningCollector;
+pub use workspace::WorkspaceStore;
@@ -9,6 +9,7 @@ use tracing::debug;
 
 use crate::BoxFuture;
 
+use super::http::send_error;
 use super::provider::{LlmError, LlmProvider};
 use super::types::*;
 
@@ -32,6 +33,13 @@ impl AnthropicProvider {
         }
     }
 
+    /// Send requests with `client`, such as the shared one of
+    /// [`http_client`](super::http_client).
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
     /// Set the default model.
     pub fn with_model(mut self, model: impl Into<String>) -> Self {
         self.default_model = model.into();
@@ -82,6 +90,21 @@ impl AnthropicProvider {
                         role: m.role.clone(),
                         content: AnthropicContent::Blocks(blocks),
                     }
+                } else if !m.images.is_empty() {
+                    let mut blocks: Vec<AnthropicBlock> = m
+                        .images
+                        .iter()
+                        .map(|source| AnthropicBlock::Image {
+                            source: source.clone(),
+                        })
+                        .collect();
+                    if let Some(text) = m.content.clone().filter(|text| !text.is_empty()) {
+                        blocks.push(AnthropicBlock::Text { text });
+                    }
+                    AnthropicMessage {
+                        role: m.role.clone(),
+                        content: AnthropicContent::Blocks(blocks),
+                    }
                 } else {
                     AnthropicMessage {
                         role: m.role.clone(),
@@ -129,7 +152,7 @@ impl AnthropicProvider {
                         arguments: input.clone(),
                     });
                 }
-                AnthropicBlock::ToolResult { .. } => {}
+                AnthropicBlock::ToolResult { .. } | AnthropicBlock::Image { .. } => {}
             }
         }
 
@@ -151,6 +174,7 @@ impl AnthropicProvider {
                 } else {
                     Some(tool_calls)
                 },
+                images: Vec::new(),
             },
             finish_reason,
             usage: TokenUsage {
@@ -159,6 +183,7 @@ impl AnthropicProvider {
                 total_tokens: resp.usage.input_tokens + resp.usage.output_tokens,
             },
             model: resp.model,
+            attempts: 1,
         }
     }
 }
@@ -182,7 +207,7 @@ impl LlmProvider for AnthropicProvider {
                 .json(&body)
                 .send()
                 .await
-                .map_err(|e| LlmError::Network(e.to_string()))?;
+                .map_err(send_error)?;
 
             let status = resp.status().as_u16();
             if status == 401 {
@@ -251,6 +276,7 @@ impl LlmProvider for AnthropicProvider {
                     .send(Ok(StreamChunk::Done {
                         finish_reason: response.finish_reason,
                         usage: Some(response.usage),
+                        attempts: 1,
                     }))
                     .await;
             });
@@ -304,6 +330,8 @@ enum AnthropicBlock {
         tool_use_id: String,
         content: String,
     },
+    #[serde(rename = "image")]
+    Image { source: ImageSource },
 }
 
 #[derive(Debug, Serialize)]
@@ -381,6 +409,36 @@ mod tests {
         assert_eq!(body.tools.as_ref().unwrap()[0].name, "search");
     }
 
+    #[test]
+    fn test_build_request_with_images() {
+        let provider = AnthropicProvider::new("test-key");
+        let request = ChatRequest {
+            messages: vec![ChatMessage::user("What is this?").with_images(vec![
+                ImageSource::from_bytes("image/png", b"foo"),
+                ImageSource::Url {
+                    url: "https://example.com/cat.jpg".into(),
+                },
+            ])],
+            ..Default::default()
+        };
+
+        let body = serde_json::to_value(provider.build_request_body(&request)).unwrap();
+        assert_eq!(
+            body["messages"][0]["content"],
+            serde_json::json!([
+                {
+                    "type": "image",
+                    "source": {"type": "base64", "media_type": "image/png", "data": "Zm9v"}
+                },
+                {
+                    "type": "image",
+                    "source": {"type": "url", "url": "https://example.com/cat.jpg"}
+                },
+                {"type": "text", "text": "What is this?"}
+            ])
+        );
+    }
+
     #[test]
     fn test_parse_text_response() {
         let provider = AnthropicProvider::new("test-key");
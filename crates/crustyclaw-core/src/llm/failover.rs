@@ -0,0 +1,757 @@
+//! Provider failover — retries and fallbacks across several providers.
+//!
+//! [`FailoverProvider`] wraps the `[llm]` provider and the
+//! `[[llm.fallbacks]]` after it. Each request goes to the first provider;
+//! a rate limit, network error, timeout, or 5xx response is retried with
+//! jittered exponential backoff up to `[llm.retry]` limits (see
+//! [`RetryPolicy`]), and then the next provider is tried the same way. A
+//! rate limit waits at least the provider's `retry-after`; one that asks
+//! for longer than the longest backoff moves on to the next provider. Any
+//! other error is returned at once.
+//!
+//! Every request is recorded: which provider served it (or that none did),
+//! how many attempts it took, the errors along the way, and the tokens it
+//! used. Records are appended to the [`AUDIT_FILE`] audit log and counted
+//! per provider in [`Metrics`] for cost tracking.
+//!
+//! Streaming requests fail over only until a stream is established; a
+//! stream that breaks part-way reports the error to the caller.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crustyclaw_config::LlmRetryConfig;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+use crate::BoxFuture;
+use crate::metrics::Metrics;
+use crate::storage::AuditLog;
+
+use super::provider::{LlmError, LlmProvider};
+use super::types::{ChatRequest, ChatResponse, StreamChunk, TokenUsage};
+
+/// Key of the request log in the audit namespace; a file in the daemon's
+/// state directory with filesystem storage.
+pub const AUDIT_FILE: &str = "llm-requests.jsonl";
+
+/// How each provider's failed requests are retried.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Retries per provider after the first attempt.
+    pub max_retries: u32,
+    /// Delay before the first retry; doubled for each further one.
+    pub initial_backoff: Duration,
+    /// Longest delay before a retry.
+    pub max_backoff: Duration,
+    /// Fraction of each delay that is random, from 0.0 to 1.0.
+    pub jitter: f64,
+}
+
+impl RetryPolicy {
+    /// The policy for `[llm.retry]`.
+    pub fn from_config(config: &LlmRetryConfig) -> Self {
+        Self {
+            max_retries: config.max_retries,
+            initial_backoff: Duration::from_millis(config.initial_backoff_ms),
+            max_backoff: Duration::from_millis(config.max_backoff_ms),
+            jitter: config.jitter.clamp(0.0, 1.0),
+        }
+    }
+
+    /// The delay before retry `retry` (0 for the first), given a random
+    /// `roll` in `0.0..1.0`: the doubled backoff, capped at `max_backoff`,
+    /// less up to `jitter` of it.
+    fn delay(&self, retry: u32, roll: f64) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_backoff);
+        backoff.mul_f64(1.0 - self.jitter * roll)
+    }
+}
+
+impl Default for RetryPolicy {
+    /// No retries.
+    fn default() -> Self {
+        Self {
+            max_retries: 0,
+            initial_backoff: Duration::ZERO,
+            max_backoff: Duration::from_secs(30),
+            jitter: 0.0,
+        }
+    }
+}
+
+/// A random number in `0.0..1.0`; 0.0 if the system RNG fails.
+fn roll() -> f64 {
+    let mut bytes = [0u8; 8];
+    if SystemRandom::new().fill(&mut bytes).is_err() {
+        return 0.0;
+    }
+    (u64::from_le_bytes(bytes) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Whether `error` is worth retrying, here or on the next provider.
+pub fn is_retryable(error: &LlmError) -> bool {
+    match error {
+        LlmError::RateLimited { .. } | LlmError::Network(_) | LlmError::Timeout => true,
+        LlmError::ProviderError { status, .. } => *status >= 500,
+        _ => false,
+    }
+}
+
+/// One request in the LLM request log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LlmAuditRecord {
+    /// Label of the provider that served the request, e.g.
+    /// `openai/gpt-4o`; `None` if every attempt failed.
+    pub provider: Option<String>,
+    /// Attempts made, across all providers.
+    pub attempts: u32,
+    /// Failed attempts, as `<provider>: <error>`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    /// Unix time the request finished, in milliseconds.
+    pub at_ms: u64,
+}
+
+/// Where request records go.
+#[derive(Clone, Default)]
+struct Recorder {
+    audit_log: Option<AuditLog>,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl Recorder {
+    fn record(&self, record: &LlmAuditRecord) {
+        if let Some(metrics) = &self.metrics {
+            metrics.record_llm_attempts(
+                record.provider.as_deref(),
+                record.attempts,
+                &TokenUsage {
+                    prompt_tokens: record.prompt_tokens,
+                    completion_tokens: record.completion_tokens,
+                    total_tokens: record.prompt_tokens + record.completion_tokens,
+                },
+            );
+        }
+        if let Some(log) = &self.audit_log
+            && let Err(e) = log.append(record)
+        {
+            warn!(log = log.key(), error = %e, "Failed to write LLM request record");
+        }
+    }
+}
+
+/// A request that a provider accepted.
+struct Served<T> {
+    label: String,
+    attempts: u32,
+    errors: Vec<String>,
+    value: T,
+}
+
+/// An [`LlmProvider`] that retries and falls back across several providers.
+pub struct FailoverProvider {
+    /// Providers in order, each with its label for records.
+    providers: Vec<(String, Box<dyn LlmProvider>)>,
+    retry: RetryPolicy,
+    recorder: Recorder,
+}
+
+impl FailoverProvider {
+    /// Wrap `provider`, labelled `label` in records (e.g. `anthropic/<model>`).
+    pub fn new(label: impl Into<String>, provider: Box<dyn LlmProvider>) -> Self {
+        Self {
+            providers: vec![(label.into(), provider)],
+            retry: RetryPolicy::default(),
+            recorder: Recorder::default(),
+        }
+    }
+
+    /// Builder: try `provider` after the ones added so far.
+    ///
+    /// Requests are sent to fallbacks with an empty model, so each uses its
+    /// own configured model.
+    pub fn with_fallback(
+        mut self,
+        label: impl Into<String>,
+        provider: Box<dyn LlmProvider>,
+    ) -> Self {
+        self.providers.push((label.into(), provider));
+        self
+    }
+
+    /// Builder: retry each provider up to `max_retries` times, waiting
+    /// `backoff` before the first retry and doubling it for each further one.
+    pub fn with_retries(mut self, max_retries: u32, backoff: Duration) -> Self {
+        self.retry.max_retries = max_retries;
+        self.retry.initial_backoff = backoff;
+        self
+    }
+
+    /// Builder: retry each provider as `policy` says.
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Builder: append a record of each request to `log`.
+    pub fn with_audit_log(mut self, log: AuditLog) -> Self {
+        self.recorder.audit_log = Some(log);
+        self
+    }
+
+    /// Builder: count each request's attempts and tokens per provider.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.recorder.metrics = Some(metrics);
+        self
+    }
+
+    /// Provider labels, primary first.
+    pub fn labels(&self) -> Vec<&str> {
+        self.providers
+            .iter()
+            .map(|(label, _)| label.as_str())
+            .collect()
+    }
+
+    /// Send `request` with `call`, retrying and falling back on retryable
+    /// errors. A request that fails is recorded here; one that succeeds is
+    /// left for the caller to record once its usage is known.
+    async fn attempt<'a, T>(
+        &'a self,
+        request: &ChatRequest,
+        call: impl Fn(&'a dyn LlmProvider, &ChatRequest) -> BoxFuture<'a, Result<T, LlmError>>,
+    ) -> Result<Served<T>, LlmError> {
+        let mut attempts = 0;
+        let mut errors = Vec::new();
+        let mut last_error = None;
+
+        for (index, (label, provider)) in self.providers.iter().enumerate() {
+            let request = if index == 0 {
+                request.clone()
+            } else {
+                ChatRequest {
+                    model: String::new(),
+                    ..request.clone()
+                }
+            };
+            for retry in 0..=self.retry.max_retries {
+                if request
+                    .deadline
+                    .is_some_and(|deadline| Instant::now() >= deadline)
+                {
+                    warn!(provider = %label, attempts, "LLM request deadline passed");
+                    errors.push(format!("{label}: deadline passed"));
+                    self.record_failure(attempts, errors);
+                    return Err(LlmError::Timeout);
+                }
+                attempts += 1;
+                let result = match request.deadline {
+                    Some(deadline) => {
+                        tokio::time::timeout_at(deadline.into(), call(provider.as_ref(), &request))
+                            .await
+                            .unwrap_or(Err(LlmError::Timeout))
+                    }
+                    None => call(provider.as_ref(), &request).await,
+                };
+                match result {
+                    Ok(value) => {
+                        if index > 0 {
+                            info!(provider = %label, attempts, "LLM request served by fallback");
+                        } else if attempts > 1 {
+                            info!(provider = %label, attempts, "LLM request served after retries");
+                        }
+                        return Ok(Served {
+                            label: label.clone(),
+                            attempts,
+                            errors,
+                            value,
+                        });
+                    }
+                    Err(e) if is_retryable(&e) => {
+                        warn!(
+                            provider = %label,
+                            attempt = retry + 1,
+                            attempts,
+                            error = %e,
+                            "LLM request failed"
+                        );
+                        errors.push(format!("{label}: {e}"));
+                        let retry_after = match &e {
+                            LlmError::RateLimited { retry_after_secs } => {
+                                Duration::from_secs(*retry_after_secs)
+                            }
+                            _ => Duration::ZERO,
+                        };
+                        last_error = Some(e);
+                        if retry == self.retry.max_retries {
+                            break;
+                        }
+                        if retry_after > self.retry.max_backoff {
+                            warn!(
+                                provider = %label,
+                                retry_after_secs = retry_after.as_secs(),
+                                "Rate limit outlasts the longest retry backoff, skipping provider"
+                            );
+                            break;
+                        }
+                        let mut wait = self.retry.delay(retry, roll()).max(retry_after);
+                        if let Some(deadline) = request.deadline {
+                            wait = wait.min(deadline.saturating_duration_since(Instant::now()));
+                        }
+                        debug!(
+                            provider = %label,
+                            attempts,
+                            wait_ms = wait.as_millis() as u64,
+                            "Retrying LLM request"
+                        );
+                        tokio::time::sleep(wait).await;
+                    }
+                    Err(e) => {
+                        errors.push(format!("{label}: {e}"));
+                        self.record_failure(attempts, errors);
+                        return Err(e);
+                    }
+                }
+            }
+        }
+
+        self.record_failure(attempts, errors);
+        Err(last_error.unwrap_or_else(|| LlmError::Request("no LLM provider configured".into())))
+    }
+
+    fn record_failure(&self, attempts: u32, errors: Vec<String>) {
+        self.recorder.record(&LlmAuditRecord {
+            provider: None,
+            attempts,
+            errors,
+            prompt_tokens: 0,
+            completion_tokens: 0,
+            at_ms: now_ms(),
+        });
+    }
+}
+
+/// The record of a request served by `label`.
+fn served_record(
+    label: String,
+    attempts: u32,
+    errors: Vec<String>,
+    usage: &TokenUsage,
+) -> LlmAuditRecord {
+    LlmAuditRecord {
+        provider: Some(label),
+        attempts,
+        errors,
+        prompt_tokens: usage.prompt_tokens,
+        completion_tokens: usage.completion_tokens,
+        at_ms: now_ms(),
+    }
+}
+
+impl LlmProvider for FailoverProvider {
+    /// The primary provider's name.
+    fn name(&self) -> &str {
+        self.providers[0].1.name()
+    }
+
+    fn chat(&self, request: &ChatRequest) -> BoxFuture<'_, Result<ChatResponse, LlmError>> {
+        let request = request.clone();
+        Box::pin(async move {
+            let Served {
+                label,
+                attempts,
+                errors,
+                value: mut response,
+            } = self.attempt(&request, |p, r| p.chat(r)).await?;
+            self.recorder
+                .record(&served_record(label, attempts, errors, &response.usage));
+            response.attempts = attempts;
+            Ok(response)
+        })
+    }
+
+    fn chat_stream(
+        &self,
+        request: &ChatRequest,
+    ) -> BoxFuture<'_, Result<mpsc::Receiver<Result<StreamChunk, LlmError>>, LlmError>> {
+        let request = request.clone();
+        Box::pin(async move {
+            let Served {
+                label,
+                attempts,
+                errors,
+                value: mut inner,
+            } = self.attempt(&request, |p, r| p.chat_stream(r)).await?;
+            let recorder = self.recorder.clone();
+            let (tx, rx) = mpsc::channel(64);
+
+            // Forward the stream, recording the request once its usage arrives.
+            tokio::spawn(async move {
+                let mut pending = Some((label, errors));
+                while let Some(mut chunk) = inner.recv().await {
+                    if let Ok(StreamChunk::Done {
+                        usage,
+                        attempts: tries,
+                        ..
+                    }) = &mut chunk
+                    {
+                        *tries = attempts;
+                        if let Some((label, errors)) = pending.take() {
+                            let usage = usage.clone().unwrap_or_default();
+                            recorder.record(&served_record(label, attempts, errors, &usage));
+                        }
+                    }
+                    if tx.send(chunk).await.is_err() {
+                        break;
+                    }
+                }
+                if let Some((label, errors)) = pending {
+                    let usage = TokenUsage::default();
+                    recorder.record(&served_record(label, attempts, errors, &usage));
+                }
+            });
+            Ok(rx)
+        })
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    use std::path::Path;
+
+    use super::*;
+    use crate::llm::ChatMessage;
+
+    /// Replays scripted results and records the models it was asked for.
+    struct ScriptedProvider {
+        results: Mutex<VecDeque<Result<u32, LlmError>>>,
+        models: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl ScriptedProvider {
+        /// A provider returning `results` in order; `Ok(n)` is a response
+        /// that used `n` prompt tokens.
+        fn new(results: Vec<Result<u32, LlmError>>) -> (Box<Self>, Arc<Mutex<Vec<String>>>) {
+            let models = Arc::new(Mutex::new(Vec::new()));
+            let provider = Box::new(Self {
+                results: Mutex::new(results.into()),
+                models: models.clone(),
+            });
+            (provider, models)
+        }
+
+        fn next(&self, request: &ChatRequest) -> Result<ChatResponse, LlmError> {
+            self.models.lock().unwrap().push(request.model.clone());
+            let tokens = self
+                .results
+                .lock()
+                .unwrap()
+                .pop_front()
+                .unwrap_or(Err(LlmError::Timeout))?;
+            Ok(ChatResponse {
+                message: ChatMessage::assistant("ok"),
+                finish_reason: "stop".into(),
+                usage: TokenUsage {
+                    prompt_tokens: tokens,
+                    completion_tokens: 1,
+                    total_tokens: tokens + 1,
+                },
+                model: request.model.clone(),
+                attempts: 1,
+            })
+        }
+    }
+
+    impl LlmProvider for ScriptedProvider {
+        fn name(&self) -> &str {
+            "scripted"
+        }
+
+        fn chat(&self, request: &ChatRequest) -> BoxFuture<'_, Result<ChatResponse, LlmError>> {
+            let result = self.next(request);
+            Box::pin(async move { result })
+        }
+
+        fn chat_stream(
+            &self,
+            request: &ChatRequest,
+        ) -> BoxFuture<'_, Result<mpsc::Receiver<Result<StreamChunk, LlmError>>, LlmError>>
+        {
+            let result = self.next(request);
+            Box::pin(async move {
+                let response = result?;
+                let (tx, rx) = mpsc::channel(4);
+                let _ = tx.send(Ok(StreamChunk::Text("ok".into()))).await;
+                let _ = tx
+                    .send(Ok(StreamChunk::Done {
+                        finish_reason: response.finish_reason,
+                        usage: Some(response.usage),
+                        attempts: 1,
+                    }))
+                    .await;
+                Ok(rx)
+            })
+        }
+    }
+
+    /// A provider that never answers.
+    struct HangingProvider;
+
+    impl LlmProvider for HangingProvider {
+        fn name(&self) -> &str {
+            "hanging"
+        }
+
+        fn chat(&self, _request: &ChatRequest) -> BoxFuture<'_, Result<ChatResponse, LlmError>> {
+            Box::pin(std::future::pending())
+        }
+
+        fn chat_stream(
+            &self,
+            _request: &ChatRequest,
+        ) -> BoxFuture<'_, Result<mpsc::Receiver<Result<StreamChunk, LlmError>>, LlmError>>
+        {
+            Box::pin(std::future::pending())
+        }
+    }
+
+    fn request() -> ChatRequest {
+        ChatRequest {
+            model: "primary-model".into(),
+            messages: vec![ChatMessage::user("hi")],
+            ..ChatRequest::default()
+        }
+    }
+
+    fn read_audit(path: &Path) -> Vec<serde_json::Value> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    fn server_error() -> LlmError {
+        LlmError::ProviderError {
+            status: 503,
+            message: "overloaded".into(),
+        }
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        assert!(is_retryable(&LlmError::RateLimited {
+            retry_after_secs: 1
+        }));
+        assert!(is_retryable(&LlmError::Network("reset".into())));
+        assert!(is_retryable(&LlmError::Timeout));
+        assert!(is_retryable(&server_error()));
+        assert!(!is_retryable(&LlmError::ProviderError {
+            status: 400,
+            message: "bad".into()
+        }));
+        assert!(!is_retryable(&LlmError::Auth("bad key".into())));
+        assert!(!is_retryable(&LlmError::ContextLength("long".into())));
+    }
+
+    #[tokio::test]
+    async fn test_retries_then_falls_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let audit = dir.path().join(AUDIT_FILE);
+        let metrics = Arc::new(Metrics::new());
+        let (primary, primary_models) = ScriptedProvider::new(vec![
+            Err(LlmError::Network("reset".into())),
+            Err(server_error()),
+        ]);
+        let (fallback, fallback_models) = ScriptedProvider::new(vec![
+            Err(LlmError::RateLimited {
+                retry_after_secs: 0,
+            }),
+            Ok(7),
+        ]);
+        let provider = FailoverProvider::new("anthropic/primary-model", primary)
+            .with_fallback("openai", fallback)
+            .with_retries(1, Duration::ZERO)
+            .with_audit_log(AuditLog::file(&audit))
+            .with_metrics(metrics.clone());
+
+        let response = provider.chat(&request()).await.unwrap();
+        assert_eq!(response.usage.prompt_tokens, 7);
+        assert_eq!(response.attempts, 4);
+        assert_eq!(*primary_models.lock().unwrap(), ["primary-model"; 2]);
+        // Fallbacks use their own configured model.
+        assert_eq!(*fallback_models.lock().unwrap(), ["", ""]);
+
+        let records = read_audit(&audit);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["provider"], "openai");
+        assert_eq!(records[0]["attempts"], 4);
+        assert_eq!(records[0]["errors"].as_array().unwrap().len(), 3);
+        assert_eq!(records[0]["prompt_tokens"], 7);
+
+        let rendered = metrics.render();
+        assert!(rendered.contains("crustyclaw_llm_provider_requests_total{provider=\"openai\"} 1"));
+        assert!(rendered.contains("crustyclaw_llm_retries_total 3"));
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let policy = RetryPolicy {
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(3),
+            jitter: 0.5,
+        };
+        assert_eq!(policy.delay(0, 0.0), Duration::from_millis(500));
+        assert_eq!(policy.delay(1, 0.0), Duration::from_secs(1));
+        assert_eq!(policy.delay(2, 0.0), Duration::from_secs(2));
+        assert_eq!(policy.delay(3, 0.0), Duration::from_secs(3));
+        assert_eq!(policy.delay(u32::MAX, 0.0), Duration::from_secs(3));
+        // Jitter takes off up to half of the delay.
+        assert_eq!(policy.delay(1, 0.5), Duration::from_millis(750));
+        assert!(policy.delay(1, 0.999) > Duration::from_millis(500));
+
+        for _ in 0..100 {
+            let roll = roll();
+            assert!((0.0..1.0).contains(&roll));
+        }
+
+        let config = LlmRetryConfig::default();
+        let policy = RetryPolicy::from_config(&config);
+        assert_eq!(policy.max_retries, config.max_retries);
+        assert_eq!(
+            policy.max_backoff,
+            Duration::from_millis(config.max_backoff_ms)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_long_rate_limit_skips_provider() {
+        let (primary, primary_models) = ScriptedProvider::new(vec![
+            Err(LlmError::RateLimited {
+                retry_after_secs: 60,
+            }),
+            Ok(1),
+        ]);
+        let (fallback, _) = ScriptedProvider::new(vec![Ok(2)]);
+        let provider = FailoverProvider::new("primary", primary)
+            .with_fallback("fallback", fallback)
+            .with_retry_policy(RetryPolicy {
+                max_retries: 3,
+                max_backoff: Duration::from_secs(1),
+                ..RetryPolicy::default()
+            });
+
+        let response = provider.chat(&request()).await.unwrap();
+        assert_eq!(response.usage.prompt_tokens, 2);
+        assert_eq!(response.attempts, 2);
+        assert_eq!(primary_models.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_deadline_stops_retries_and_fallbacks() {
+        let dir = tempfile::tempdir().unwrap();
+        let audit = dir.path().join(AUDIT_FILE);
+        let (fallback, fallback_models) = ScriptedProvider::new(vec![Ok(1)]);
+        let provider = FailoverProvider::new("primary", Box::new(HangingProvider))
+            .with_fallback("fallback", fallback)
+            .with_retries(3, Duration::ZERO)
+            .with_audit_log(AuditLog::file(&audit));
+
+        let request = ChatRequest {
+            deadline: Some(Instant::now() + Duration::from_millis(50)),
+            ..request()
+        };
+        let err = provider.chat(&request).await.unwrap_err();
+        assert!(matches!(err, LlmError::Timeout));
+        assert!(fallback_models.lock().unwrap().is_empty());
+
+        let records = read_audit(&audit);
+        assert_eq!(records[0]["provider"], serde_json::Value::Null);
+        assert_eq!(records[0]["attempts"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_non_retryable_error_is_returned() {
+        let dir = tempfile::tempdir().unwrap();
+        let audit = dir.path().join(AUDIT_FILE);
+        let (primary, primary_models) =
+            ScriptedProvider::new(vec![Err(LlmError::Auth("bad key".into()))]);
+        let (fallback, fallback_models) = ScriptedProvider::new(vec![Ok(1)]);
+        let provider = FailoverProvider::new("primary", primary)
+            .with_fallback("fallback", fallback)
+            .with_retries(3, Duration::ZERO)
+            .with_audit_log(AuditLog::file(&audit));
+
+        let err = provider.chat(&request()).await.unwrap_err();
+        assert!(matches!(err, LlmError::Auth(_)));
+        assert_eq!(primary_models.lock().unwrap().len(), 1);
+        assert!(fallback_models.lock().unwrap().is_empty());
+
+        let records = read_audit(&audit);
+        assert_eq!(records[0]["provider"], serde_json::Value::Null);
+        assert_eq!(records[0]["attempts"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_all_providers_failing_returns_last_error() {
+        let metrics = Arc::new(Metrics::new());
+        let (primary, _) = ScriptedProvider::new(vec![Err(LlmError::Timeout)]);
+        let (fallback, _) = ScriptedProvider::new(vec![Err(server_error())]);
+        let provider = FailoverProvider::new("primary", primary)
+            .with_fallback("fallback", fallback)
+            .with_metrics(metrics.clone());
+
+        let err = provider.chat(&request()).await.unwrap_err();
+        assert!(matches!(err, LlmError::ProviderError { status: 503, .. }));
+        assert!(
+            metrics
+                .render()
+                .contains("crustyclaw_llm_unserved_requests_total 1")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_records_usage_when_done() {
+        let dir = tempfile::tempdir().unwrap();
+        let audit = dir.path().join(AUDIT_FILE);
+        let (primary, _) = ScriptedProvider::new(vec![Err(LlmError::Timeout), Ok(5)]);
+        let provider = FailoverProvider::new("primary", primary)
+            .with_retries(2, Duration::ZERO)
+            .with_audit_log(AuditLog::file(&audit));
+
+        let mut rx = provider.chat_stream(&request()).await.unwrap();
+        let mut chunks = 0;
+        while let Some(chunk) = rx.recv().await {
+            if let Ok(StreamChunk::Done { attempts, .. }) = chunk {
+                assert_eq!(attempts, 2);
+            }
+            chunks += 1;
+        }
+        assert_eq!(chunks, 2);
+
+        let records = read_audit(&audit);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["provider"], "primary");
+        assert_eq!(records[0]["attempts"], 2);
+        assert_eq!(records[0]["prompt_tokens"], 5);
+    }
+}
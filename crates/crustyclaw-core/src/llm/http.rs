@@ -0,0 +1,92 @@
+//! Shared HTTP client of the LLM providers (`[llm.http]`).
+//!
+//! [`http_client`] builds one [`reqwest::Client`] with the configured
+//! timeouts, proxy, and connection pool. [`failover_provider`] hands a clone
+//! of it to the primary provider and every fallback, so they share one pool
+//! of kept-alive connections.
+//!
+//! [`failover_provider`]: super::failover_provider
+
+use std::time::Duration;
+
+use crustyclaw_config::LlmHttpConfig;
+use reqwest::{Client, NoProxy, Proxy};
+
+use super::provider::LlmError;
+
+/// Build the client described by `[llm.http]`.
+pub fn http_client(config: &LlmHttpConfig) -> Result<Client, LlmError> {
+    let mut builder = Client::builder()
+        .connect_timeout(Duration::from_secs(config.connect_timeout_secs))
+        .timeout(Duration::from_secs(config.request_timeout_secs))
+        .pool_max_idle_per_host(config.pool_max_idle_per_host)
+        .pool_idle_timeout(Duration::from_secs(config.pool_idle_timeout_secs))
+        .tcp_keepalive(
+            (config.tcp_keepalive_secs > 0).then(|| Duration::from_secs(config.tcp_keepalive_secs)),
+        );
+    if let Some(url) = &config.proxy {
+        let proxy = Proxy::all(url)
+            .map_err(|e| LlmError::Request(format!("invalid llm.http.proxy {url:?}: {e}")))?
+            .no_proxy(NoProxy::from_string(&config.no_proxy.join(",")));
+        builder = builder.proxy(proxy);
+    }
+    builder
+        .build()
+        .map_err(|e| LlmError::Request(format!("failed to build HTTP client: {e}")))
+}
+
+/// The [`LlmError`] of a request that got no response.
+pub(crate) fn send_error(e: reqwest::Error) -> LlmError {
+    if e.is_timeout() {
+        LlmError::Timeout
+    } else {
+        LlmError::Network(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_http_client() {
+        assert!(http_client(&LlmHttpConfig::default()).is_ok());
+
+        let config = LlmHttpConfig {
+            proxy: Some("http://proxy.internal:3128".into()),
+            no_proxy: vec!["localhost".into(), ".internal".into()],
+            tcp_keepalive_secs: 0,
+            ..LlmHttpConfig::default()
+        };
+        assert!(http_client(&config).is_ok());
+
+        let config = LlmHttpConfig {
+            proxy: Some("http://[bad".into()),
+            ..LlmHttpConfig::default()
+        };
+        assert!(matches!(http_client(&config), Err(LlmError::Request(_))));
+    }
+
+    #[tokio::test]
+    async fn test_request_timeout_is_a_timeout() {
+        let config = LlmHttpConfig {
+            request_timeout_secs: 1,
+            ..LlmHttpConfig::default()
+        };
+        let client = http_client(&config).unwrap();
+        // Accept the connection but never answer.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let _server = tokio::spawn(async move {
+            let (_stream, _) = listener.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(10)).await;
+        });
+
+        let err = client
+            .get(format!("http://{addr}/"))
+            .send()
+            .await
+            .unwrap_err();
+        assert!(matches!(send_error(err), LlmError::Timeout));
+    }
+}
@@ -0,0 +1,770 @@
+//! llama.cpp native server provider.
+//!
+//! Implements the [`LlmProvider`] trait for `llama-server`'s own
+//! `/completion` endpoint rather than its OpenAI-compatible one, so fully
+//! offline deployments need nothing but a GGUF model and the server.
+//!
+//! - The conversation is rendered into a prompt with the model's chat
+//!   template ([`LlamaCppTemplate`]).
+//! - Tools are described in the system prompt, and the model answers with
+//!   `{"response": "..."}` or `{"tool_calls": [{"name", "arguments"}]}`.
+//!   With grammar-constrained output (the default), the server is sent a
+//!   JSON schema allowing only those two shapes and the offered tools'
+//!   parameters, so the model cannot produce a malformed call.
+//! - The context size is read from the server's `/props` (or configured),
+//!   and the prompt is measured with `/tokenize`: a prompt that does not
+//!   fit is refused with [`LlmError::ContextLength`], and the completion is
+//!   capped at the room left.
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::debug;
+
+use crustyclaw_config::LlamaCppTemplate;
+
+use crate::BoxFuture;
+
+use super::http::send_error;
+use super::provider::{LlmError, LlmProvider};
+use super::types::*;
+
+const DEFAULT_BASE_URL: &str = "http://127.0.0.1:8080";
+
+/// llama.cpp server provider.
+pub struct LlamaCppProvider {
+    client: Client,
+    api_key: String,
+    base_url: String,
+    default_model: String,
+    template: LlamaCppTemplate,
+    grammar: bool,
+    /// Configured or detected context size, in tokens.
+    context_size: Mutex<Option<u32>>,
+    /// Source of tool call IDs, which the native API does not assign.
+    next_call: AtomicU64,
+}
+
+impl LlamaCppProvider {
+    /// Create a provider for a server at the default `http://127.0.0.1:8080`.
+    pub fn new() -> Self {
+        Self {
+            client: Client::new(),
+            api_key: String::new(),
+            base_url: DEFAULT_BASE_URL.to_string(),
+            default_model: "llama.cpp".to_string(),
+            template: LlamaCppTemplate::default(),
+            grammar: true,
+            context_size: Mutex::new(None),
+            next_call: AtomicU64::new(1),
+        }
+    }
+
+    /// Send requests with `client`, such as the shared one of
+    /// [`http_client`](super::http_client).
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Set the server's base URL.
+    pub fn with_base_url(mut self, url: impl Into<String>) -> Self {
+        self.base_url = url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Send `key` as a bearer token (`llama-server --api-key`).
+    pub fn with_api_key(mut self, key: impl Into<String>) -> Self {
+        self.api_key = key.into();
+        self
+    }
+
+    /// Set the model name reported when the server does not name its model.
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.default_model = model.into();
+        self
+    }
+
+    /// Set the chat template the prompt is rendered with.
+    pub fn with_template(mut self, template: LlamaCppTemplate) -> Self {
+        self.template = template;
+        self
+    }
+
+    /// Set whether tool use is grammar-constrained.
+    pub fn with_grammar(mut self, grammar: bool) -> Self {
+        self.grammar = grammar;
+        self
+    }
+
+    /// Set the context size instead of detecting it from the server.
+    pub fn with_context_size(self, tokens: u32) -> Self {
+        *self.context_size.lock().unwrap_or_else(|e| e.into_inner()) = Some(tokens);
+        self
+    }
+
+    /// Render the conversation, with any tool instructions, as a prompt
+    /// ending where the assistant's reply begins.
+    fn render_prompt(&self, request: &ChatRequest) -> String {
+        let mut system: Vec<String> = request.system.iter().cloned().collect();
+        system.extend(
+            request
+                .messages
+                .iter()
+                .filter(|m| m.role == "system")
+                .filter_map(|m| m.content.clone()),
+        );
+        if !request.tools.is_empty() {
+            system.push(tool_instructions(&request.tools));
+        }
+
+        let mut prompt = String::new();
+        if !system.is_empty() {
+            self.push_turn(&mut prompt, "system", &system.join("\n\n"));
+        }
+        for (i, msg) in request.messages.iter().enumerate() {
+            match msg.role.as_str() {
+                "system" => {}
+                "assistant" => {
+                    let content = match &msg.tool_calls {
+                        Some(calls) if !calls.is_empty() => json!({
+                            "tool_calls": calls
+                                .iter()
+                                .map(|c| json!({ "name": c.name, "arguments": c.arguments }))
+                                .collect::<Vec<_>>(),
+                        })
+                        .to_string(),
+                        _ => msg.content.clone().unwrap_or_default(),
+                    };
+                    self.push_turn(&mut prompt, "assistant", &content);
+                }
+                "tool" => {
+                    let name = msg
+                        .tool_call_id
+                        .as_deref()
+                        .and_then(|id| tool_name(&request.messages[..i], id))
+                        .unwrap_or("tool");
+                    let content = format!(
+                        "Result of {name}:\n{}",
+                        msg.content.as_deref().unwrap_or_default()
+                    );
+                    let role = match self.template {
+                        LlamaCppTemplate::ChatMl => "tool",
+                        LlamaCppTemplate::Llama3 => "ipython",
+                    };
+                    self.push_turn(&mut prompt, role, &content);
+                }
+                role => self.push_turn(&mut prompt, role, msg.content.as_deref().unwrap_or("")),
+            }
+        }
+        match self.template {
+            LlamaCppTemplate::ChatMl => prompt.push_str("<|im_start|>assistant\n"),
+            LlamaCppTemplate::Llama3 => {
+                prompt.push_str("<|start_header_id|>assistant<|end_header_id|>\n\n")
+            }
+        }
+        prompt
+    }
+
+    fn push_turn(&self, prompt: &mut String, role: &str, content: &str) {
+        match self.template {
+            LlamaCppTemplate::ChatMl => {
+                prompt.push_str(&format!("<|im_start|>{role}\n{content}<|im_end|>\n"));
+            }
+            LlamaCppTemplate::Llama3 => prompt.push_str(&format!(
+                "<|start_header_id|>{role}<|end_header_id|>\n\n{content}<|eot_id|>"
+            )),
+        }
+    }
+
+    /// The template's end-of-turn marker.
+    fn stop(&self) -> &'static str {
+        match self.template {
+            LlamaCppTemplate::ChatMl => "<|im_end|>",
+            LlamaCppTemplate::Llama3 => "<|eot_id|>",
+        }
+    }
+
+    fn build_request_body(
+        &self,
+        request: &ChatRequest,
+        prompt: String,
+        n_predict: u32,
+    ) -> CompletionRequest {
+        CompletionRequest {
+            prompt,
+            n_predict,
+            temperature: request.temperature,
+            stop: vec![self.stop().to_string()],
+            json_schema: (self.grammar && !request.tools.is_empty())
+                .then(|| tool_schema(&request.tools)),
+            cache_prompt: true,
+            stream: false,
+        }
+    }
+
+    /// Parse the server's response into our ChatResponse. With tools
+    /// offered, the content is read as the JSON reply format, falling back
+    /// to plain text if it is not.
+    fn parse_response(&self, resp: CompletionResponse, tools_offered: bool) -> ChatResponse {
+        let text = resp.content.trim();
+        let (content, tool_calls) = match parse_output(text).filter(|_| tools_offered) {
+            Some(Output {
+                tool_calls: Some(calls),
+                ..
+            }) if !calls.is_empty() => {
+                let calls: Vec<ToolCall> = calls
+                    .into_iter()
+                    .map(|call| ToolCall {
+                        id: format!("call_{}", self.next_call.fetch_add(1, Ordering::Relaxed)),
+                        name: call.name,
+                        arguments: call.arguments,
+                    })
+                    .collect();
+                (None, Some(calls))
+            }
+            Some(Output {
+                response: Some(response),
+                ..
+            }) => (Some(response), None),
+            _ => (Some(text.to_string()), None),
+        };
+
+        let finish_reason = if tool_calls.is_some() {
+            "tool_use"
+        } else if resp.stopped_limit {
+            "length"
+        } else {
+            "stop"
+        };
+        // The server reports the model file's path.
+        let model = resp
+            .model
+            .rsplit('/')
+            .next()
+            .filter(|name| !name.is_empty())
+            .unwrap_or(&self.default_model)
+            .to_string();
+
+        ChatResponse {
+            message: ChatMessage {
+                role: "assistant".to_string(),
+                content,
+                tool_call_id: None,
+                tool_calls,
+                images: Vec::new(),
+            },
+            finish_reason: finish_reason.to_string(),
+            usage: TokenUsage {
+                prompt_tokens: resp.tokens_evaluated,
+                completion_tokens: resp.tokens_predicted,
+                total_tokens: resp.tokens_evaluated + resp.tokens_predicted,
+            },
+            model,
+            attempts: 1,
+        }
+    }
+
+    /// The context size, detecting it from `/props` the first time. `None`
+    /// if the server does not report it.
+    async fn context_size(&self) -> Result<Option<u32>, LlmError> {
+        if let Some(size) = *self.context_size.lock().unwrap_or_else(|e| e.into_inner()) {
+            return Ok(Some(size));
+        }
+        let props: Props = self.send(self.client.get(self.url("/props"))).await?;
+        let size = props
+            .default_generation_settings
+            .and_then(|settings| settings.n_ctx)
+            .or(props.n_ctx)
+            .filter(|&size| size > 0);
+        if let Some(size) = size {
+            debug!(n_ctx = size, "Detected llama.cpp context size");
+            *self.context_size.lock().unwrap_or_else(|e| e.into_inner()) = Some(size);
+        }
+        Ok(size)
+    }
+
+    /// Number of tokens in `prompt`, as the server tokenizes it.
+    async fn count_tokens(&self, prompt: &str) -> Result<u32, LlmError> {
+        let body = json!({ "content": prompt });
+        let tokenized: Tokenized = self
+            .send(self.client.post(self.url("/tokenize")).json(&body))
+            .await?;
+        Ok(u32::try_from(tokenized.tokens.len()).unwrap_or(u32::MAX))
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{path}", self.base_url)
+    }
+
+    async fn send<T: DeserializeOwned>(
+        &self,
+        mut builder: reqwest::RequestBuilder,
+    ) -> Result<T, LlmError> {
+        if !self.api_key.is_empty() {
+            builder = builder.header("authorization", format!("Bearer {}", self.api_key));
+        }
+        let resp = builder.send().await.map_err(send_error)?;
+
+        let status = resp.status().as_u16();
+        if status == 401 {
+            return Err(LlmError::Auth("invalid API key".to_string()));
+        }
+        if !resp.status().is_success() {
+            let error_body = resp.text().await.unwrap_or_default();
+            return Err(LlmError::ProviderError {
+                status,
+                message: error_body,
+            });
+        }
+        resp.json()
+            .await
+            .map_err(|e| LlmError::Parse(e.to_string()))
+    }
+}
+
+impl Default for LlamaCppProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LlmProvider for LlamaCppProvider {
+    fn name(&self) -> &str {
+        "llama.cpp"
+    }
+
+    fn chat(&self, request: &ChatRequest) -> BoxFuture<'_, Result<ChatResponse, LlmError>> {
+        let request = request.clone();
+        Box::pin(async move {
+            let prompt = self.render_prompt(&request);
+            let mut n_predict = request.max_tokens;
+            if let Some(n_ctx) = self.context_size().await? {
+                let used = self.count_tokens(&prompt).await?;
+                if used >= n_ctx {
+                    return Err(LlmError::ContextLength(format!(
+                        "prompt is {used} tokens, the context holds {n_ctx}"
+                    )));
+                }
+                n_predict = n_predict.min(n_ctx - used);
+            }
+            let body = self.build_request_body(&request, prompt, n_predict);
+            debug!(
+                n_predict,
+                grammar = body.json_schema.is_some(),
+                "llama.cpp completion request"
+            );
+
+            let resp: CompletionResponse = self
+                .send(self.client.post(self.url("/completion")).json(&body))
+                .await?;
+            if resp.truncated {
+                return Err(LlmError::ContextLength(
+                    "the server truncated the prompt to fit its context".to_string(),
+                ));
+            }
+            Ok(self.parse_response(resp, !request.tools.is_empty()))
+        })
+    }
+
+    fn chat_stream(
+        &self,
+        request: &ChatRequest,
+    ) -> BoxFuture<'_, Result<tokio::sync::mpsc::Receiver<Result<StreamChunk, LlmError>>, LlmError>>
+    {
+        let request = request.clone();
+        Box::pin(async move {
+            let (tx, rx) = tokio::sync::mpsc::channel(64);
+
+            // Tool replies are only usable once complete, so the response is
+            // fetched whole and emitted as chunks.
+            let response = self.chat(&request).await?;
+            tokio::spawn(async move {
+                if let Some(ref text) = response.message.content {
+                    let _ = tx.send(Ok(StreamChunk::Text(text.clone()))).await;
+                }
+                if let Some(ref calls) = response.message.tool_calls {
+                    for call in calls {
+                        let _ = tx
+                            .send(Ok(StreamChunk::ToolCallStart {
+                                id: call.id.clone(),
+                                name: call.name.clone(),
+                            }))
+                            .await;
+                        let _ = tx
+                            .send(Ok(StreamChunk::ToolCallDelta {
+                                id: call.id.clone(),
+                                arguments_delta: call.arguments.to_string(),
+                            }))
+                            .await;
+                    }
+                }
+                let _ = tx
+                    .send(Ok(StreamChunk::Done {
+                        finish_reason: response.finish_reason,
+                        usage: Some(response.usage),
+                        attempts: 1,
+                    }))
+                    .await;
+            });
+
+            Ok(rx)
+        })
+    }
+}
+
+/// System prompt text describing `tools` and the reply format.
+fn tool_instructions(tools: &[ToolDefinition]) -> String {
+    let mut text = String::from(
+        "You can call the following tools. Reply with only a JSON object: \
+         {\"tool_calls\": [{\"name\": <tool name>, \"arguments\": <arguments object>}]} \
+         to call tools, or {\"response\": <your answer>} to answer.\n\nTools:",
+    );
+    for tool in tools {
+        let spec = json!({
+            "name": tool.name,
+            "description": tool.description,
+            "parameters": tool.parameters,
+        });
+        text.push_str(&format!("\n{spec}"));
+    }
+    text
+}
+
+/// JSON schema of a reply: an answer, or calls to the offered tools with
+/// their parameter schemas. llama.cpp turns it into a grammar.
+fn tool_schema(tools: &[ToolDefinition]) -> serde_json::Value {
+    let calls: Vec<serde_json::Value> = tools
+        .iter()
+        .map(|tool| {
+            json!({
+                "type": "object",
+                "properties": {
+                    "name": { "const": tool.name },
+                    "arguments": tool.parameters,
+                },
+                "required": ["name", "arguments"],
+                "additionalProperties": false,
+            })
+        })
+        .collect();
+    json!({
+        "oneOf": [
+            {
+                "type": "object",
+                "properties": { "response": { "type": "string" } },
+                "required": ["response"],
+                "additionalProperties": false,
+            },
+            {
+                "type": "object",
+                "properties": {
+                    "tool_calls": {
+                        "type": "array",
+                        "minItems": 1,
+                        "items": { "oneOf": calls },
+                    },
+                },
+                "required": ["tool_calls"],
+                "additionalProperties": false,
+            },
+        ],
+    })
+}
+
+/// Name of the tool call `id` among the assistant turns of `messages`.
+fn tool_name<'a>(messages: &'a [ChatMessage], id: &str) -> Option<&'a str> {
+    messages
+        .iter()
+        .rev()
+        .filter_map(|m| m.tool_calls.as_ref())
+        .flatten()
+        .find(|call| call.id == id)
+        .map(|call| call.name.as_str())
+}
+
+/// Read a reply in the JSON format, allowing a markdown code fence around
+/// it (common when output is not grammar-constrained).
+fn parse_output(text: &str) -> Option<Output> {
+    let text = text
+        .strip_prefix("```json")
+        .or_else(|| text.strip_prefix("```"))
+        .and_then(|inner| inner.trim_end().strip_suffix("```"))
+        .unwrap_or(text);
+    serde_json::from_str(text.trim()).ok()
+}
+
+// ── llama.cpp API types (private) ───────────────────────────────────────
+
+#[derive(Debug, Serialize)]
+struct CompletionRequest {
+    prompt: String,
+    n_predict: u32,
+    temperature: f32,
+    stop: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    json_schema: Option<serde_json::Value>,
+    cache_prompt: bool,
+    stream: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct CompletionResponse {
+    content: String,
+    #[serde(default)]
+    model: String,
+    #[serde(default)]
+    tokens_evaluated: u32,
+    #[serde(default)]
+    tokens_predicted: u32,
+    #[serde(default)]
+    stopped_limit: bool,
+    #[serde(default)]
+    truncated: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct Props {
+    default_generation_settings: Option<GenerationSettings>,
+    n_ctx: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GenerationSettings {
+    n_ctx: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Tokenized {
+    tokens: Vec<serde_json::Value>,
+}
+
+/// A reply in the JSON format the model is asked for.
+#[derive(Debug, Deserialize)]
+struct Output {
+    response: Option<String>,
+    tool_calls: Option<Vec<OutputCall>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OutputCall {
+    name: String,
+    #[serde(default)]
+    arguments: serde_json::Value,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::Json;
+    use axum::routing::{get, post};
+
+    use super::*;
+
+    fn read_file_tool() -> ToolDefinition {
+        ToolDefinition {
+            name: "read_file".to_string(),
+            description: "Read a file".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": { "path": { "type": "string" } },
+                "required": ["path"],
+            }),
+        }
+    }
+
+    fn completion(content: &str) -> CompletionResponse {
+        CompletionResponse {
+            content: content.to_string(),
+            model: "/models/qwen2.5-7b-instruct-q4_k_m.gguf".to_string(),
+            tokens_evaluated: 12,
+            tokens_predicted: 5,
+            stopped_limit: false,
+            truncated: false,
+        }
+    }
+
+    #[test]
+    fn test_render_chatml_prompt() {
+        let provider = LlamaCppProvider::new();
+        let call = ToolCall {
+            id: "call_1".to_string(),
+            name: "read_file".to_string(),
+            arguments: json!({ "path": "notes.txt" }),
+        };
+        let mut assistant = ChatMessage::assistant("");
+        assistant.tool_calls = Some(vec![call]);
+        let request = ChatRequest {
+            messages: vec![
+                ChatMessage::user("What is in notes.txt?"),
+                assistant,
+                ChatMessage::tool_result("call_1", "42"),
+            ],
+            system: Some("Be brief.".to_string()),
+            tools: vec![read_file_tool()],
+            ..Default::default()
+        };
+
+        let prompt = provider.render_prompt(&request);
+        assert!(prompt.starts_with("<|im_start|>system\nBe brief.\n\nYou can call"));
+        assert!(prompt.contains("\"name\":\"read_file\""));
+        assert!(prompt.contains("<|im_start|>user\nWhat is in notes.txt?<|im_end|>\n"));
+        assert!(prompt.contains("<|im_start|>assistant\n{\"tool_calls\":[{"));
+        assert!(prompt.contains("<|im_start|>tool\nResult of read_file:\n42<|im_end|>\n"));
+        assert!(prompt.ends_with("<|im_start|>assistant\n"));
+    }
+
+    #[test]
+    fn test_render_llama3_prompt() {
+        let provider = LlamaCppProvider::new().with_template(LlamaCppTemplate::Llama3);
+        let request = ChatRequest {
+            messages: vec![ChatMessage::user("Hi")],
+            ..Default::default()
+        };
+        assert_eq!(
+            provider.render_prompt(&request),
+            "<|start_header_id|>user<|end_header_id|>\n\nHi<|eot_id|>\
+             <|start_header_id|>assistant<|end_header_id|>\n\n"
+        );
+        assert_eq!(provider.stop(), "<|eot_id|>");
+    }
+
+    #[test]
+    fn test_grammar_only_with_tools() {
+        let provider = LlamaCppProvider::new();
+        let mut request = ChatRequest::default();
+        let body = provider.build_request_body(&request, String::new(), 16);
+        assert!(body.json_schema.is_none());
+
+        request.tools = vec![read_file_tool()];
+        let body = provider.build_request_body(&request, String::new(), 16);
+        let schema = body.json_schema.unwrap();
+        let call = &schema["oneOf"][1]["properties"]["tool_calls"]["items"]["oneOf"][0];
+        assert_eq!(call["properties"]["name"]["const"], "read_file");
+        assert_eq!(call["properties"]["arguments"]["required"][0], "path");
+
+        let unconstrained = LlamaCppProvider::new().with_grammar(false);
+        let body = unconstrained.build_request_body(&request, String::new(), 16);
+        assert!(body.json_schema.is_none());
+    }
+
+    #[test]
+    fn test_parse_responses() {
+        let provider = LlamaCppProvider::new();
+
+        let resp = provider.parse_response(
+            completion(r#"{"tool_calls": [{"name": "read_file", "arguments": {"path": "a"}}]}"#),
+            true,
+        );
+        assert_eq!(resp.finish_reason, "tool_use");
+        let calls = resp.message.tool_calls.unwrap();
+        assert_eq!(calls[0].name, "read_file");
+        assert_eq!(calls[0].arguments["path"], "a");
+        assert_eq!(resp.model, "qwen2.5-7b-instruct-q4_k_m.gguf");
+        assert_eq!(resp.usage.total_tokens, 17);
+
+        let resp =
+            provider.parse_response(completion("```json\n{\"response\": \"42\"}\n```"), true);
+        assert_eq!(resp.message.content.as_deref(), Some("42"));
+        assert_eq!(resp.finish_reason, "stop");
+
+        // Without tools, JSON-looking text is left alone.
+        let resp = provider.parse_response(completion(r#"{"response": "42"}"#), false);
+        assert_eq!(
+            resp.message.content.as_deref(),
+            Some(r#"{"response": "42"}"#)
+        );
+
+        let mut limited = completion("It is");
+        limited.stopped_limit = true;
+        let resp = provider.parse_response(limited, true);
+        assert_eq!(resp.message.content.as_deref(), Some("It is"));
+        assert_eq!(resp.finish_reason, "length");
+    }
+
+    /// Serve a fake llama.cpp server with a 64-token context, returning the
+    /// base URL and the `n_predict` of each completion request.
+    async fn fake_server() -> (String, Arc<Mutex<Vec<u64>>>) {
+        let n_predict = Arc::new(Mutex::new(Vec::new()));
+        let seen = n_predict.clone();
+        let app = axum::Router::new()
+            .route(
+                "/props",
+                get(|| async { Json(json!({ "default_generation_settings": { "n_ctx": 64 } })) }),
+            )
+            .route(
+                "/tokenize",
+                post(|Json(body): Json<serde_json::Value>| async move {
+                    // One token per four characters.
+                    let len = body["content"].as_str().unwrap_or_default().len();
+                    Json(json!({ "tokens": vec![0; len.div_ceil(4)] }))
+                }),
+            )
+            .route(
+                "/completion",
+                post(move |Json(body): Json<serde_json::Value>| {
+                    let seen = seen.clone();
+                    async move {
+                        seen.lock()
+                            .unwrap()
+                            .push(body["n_predict"].as_u64().unwrap());
+                        Json(json!({
+                            "content": "Hello",
+                            "model": "tiny.gguf",
+                            "tokens_evaluated": 10,
+                            "tokens_predicted": 1,
+                        }))
+                    }
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{addr}"), n_predict)
+    }
+
+    #[tokio::test]
+    async fn test_detects_context_size() {
+        let (url, n_predict) = fake_server().await;
+        let provider = LlamaCppProvider::new().with_base_url(url);
+        let request = ChatRequest {
+            messages: vec![ChatMessage::user("Hi")],
+            max_tokens: 1024,
+            ..Default::default()
+        };
+
+        let resp = provider.chat(&request).await.unwrap();
+        assert_eq!(resp.message.content.as_deref(), Some("Hello"));
+        assert_eq!(resp.model, "tiny.gguf");
+        // The completion is capped at the room the prompt leaves.
+        let prompt_tokens = provider.render_prompt(&request).len().div_ceil(4) as u64;
+        assert_eq!(*n_predict.lock().unwrap(), [64 - prompt_tokens]);
+
+        let long = ChatRequest {
+            messages: vec![ChatMessage::user("x".repeat(400))],
+            ..Default::default()
+        };
+        let err = provider.chat(&long).await.unwrap_err();
+        assert!(matches!(err, LlmError::ContextLength(_)), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_server() {
+        let provider = LlamaCppProvider::new()
+            .with_base_url("http://127.0.0.1:1")
+            .with_context_size(4096);
+        let err = provider
+            .chat(&ChatRequest {
+                messages: vec![ChatMessage::user("Hi")],
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert!(matches!(err, LlmError::Network(_)), "{err}");
+    }
+}
@@ -6,6 +6,8 @@
 //! - **Anthropic** — Claude models via the Messages API
 //! - **OpenAI** — GPT models via the Chat Completions API (also compatible with
 //!   Ollama, vLLM, Together AI, and other OpenAI-compatible endpoints)
+//! - **llama.cpp** — local GGUF models via `llama-server`'s native
+//!   `/completion` endpoint, with grammar-constrained tool calls
 //!
 //! ## Architecture
 //!
@@ -16,18 +18,29 @@
 //!                            │
 //!              ┌─────────────┼─────────────┐
 //!              ▼             ▼             ▼
-//!     ┌──────────────┐ ┌──────────┐ ┌──────────┐
-//!     │  Anthropic   │ │  OpenAI  │ │  Custom  │
-//!     │ (Claude API) │ │ (GPT API)│ │ (future) │
-//!     └──────────────┘ └──────────┘ └──────────┘
+//!     ┌──────────────┐ ┌──────────┐ ┌───────────┐
+//!     │  Anthropic   │ │  OpenAI  │ │ llama.cpp │
+//!     │ (Claude API) │ │ (GPT API)│ │  (local)  │
+//!     └──────────────┘ └──────────┘ └───────────┘
 //! ```
+//!
+//! [`create_provider`] wraps the `[llm]` provider and any
+//! `[[llm.fallbacks]]` in a [`FailoverProvider`], which retries transient
+//! failures and falls back to the next provider in order. All of them send
+//! requests through one client from [`http_client`], set up by `[llm.http]`.
 
 pub mod anthropic;
+pub mod failover;
+pub mod http;
+pub mod llamacpp;
 pub mod openai;
 pub mod provider;
 pub mod types;
 
 pub use anthropic::AnthropicProvider;
+pub use failover::{FailoverProvider, LlmAuditRecord, RetryPolicy};
+pub use http::http_client;
+pub use llamacpp::LlamaCppProvider;
 pub use openai::OpenAiProvider;
 pub use provider::{LlmError, LlmProvider};
 pub use types::*;
@@ -35,31 +48,146 @@ pub use types::*;
 /// Create an LLM provider from config.
 ///
 /// Reads the `[llm]` section of the config to determine which provider
-/// to use and how to authenticate.
+/// to use and how to authenticate, with its retries and fallbacks (see
+/// [`failover_provider`]).
 pub fn create_provider(config: &crustyclaw_config::LlmConfig) -> Box<dyn LlmProvider> {
+    Box::new(failover_provider(config))
+}
+
+/// Create the [`FailoverProvider`] for `[llm]` and `[[llm.fallbacks]]`.
+///
+/// A fallback with an empty `api_key` reads it from `api_key_env`, if set.
+/// Providers are labelled `<provider>/<model>` (just `<provider>` when the
+/// model is left to the provider's default). If `[llm.http]` cannot be
+/// applied, providers fall back to a client with default settings.
+pub fn failover_provider(config: &crustyclaw_config::LlmConfig) -> FailoverProvider {
+    let client = http_client(&config.http).unwrap_or_else(|e| {
+        tracing::warn!(error = %e, "Using default LLM HTTP client settings");
+        reqwest::Client::new()
+    });
+    let primary = build_provider(
+        &client,
+        &config.provider,
+        &config.api_key,
+        &config.model,
+        config.base_url.as_deref(),
+        &config.llamacpp,
+    );
+    let mut failover = FailoverProvider::new(label(&config.provider, &config.model), primary)
+        .with_retry_policy(RetryPolicy::from_config(&config.effective_retry()));
+    for fallback in &config.fallbacks {
+        let api_key = match &fallback.api_key_env {
+            Some(var) if fallback.api_key.is_empty() => std::env::var(var).unwrap_or_default(),
+            _ => fallback.api_key.clone(),
+        };
+        let provider = build_provider(
+            &client,
+            &fallback.provider,
+            &api_key,
+            &fallback.model,
+            fallback.base_url.as_deref(),
+            &config.llamacpp,
+        );
+        failover = failover.with_fallback(label(&fallback.provider, &fallback.model), provider);
+    }
+    failover
+}
+
+/// Names of the `[[secrets.entries]]` holding the API keys of `[llm]` and
+/// its fallbacks.
+pub fn key_secrets(config: &crustyclaw_config::LlmConfig) -> Vec<&str> {
+    std::iter::once(&config.api_key_secret)
+        .chain(config.fallbacks.iter().map(|f| &f.api_key_secret))
+        .filter_map(|name| name.as_deref())
+        .collect()
+}
+
+/// Fill in the `api_key` of `[llm]` and its fallbacks from the secrets
+/// their `api_key_secret` names. A secret missing from `store` leaves the
+/// key as configured.
+pub fn resolve_key_secrets(
+    config: &mut crustyclaw_config::LlmConfig,
+    store: &crate::secrets::SecretStore,
+) {
+    let keys = std::iter::once((&config.api_key_secret, &mut config.api_key)).chain(
+        config
+            .fallbacks
+            .iter_mut()
+            .map(|f| (&f.api_key_secret, &mut f.api_key)),
+    );
+    for (name, key) in keys {
+        let Some(name) = name else { continue };
+        match store.get(name) {
+            Some(entry) => *key = entry.value.expose().to_string(),
+            None => tracing::warn!(secret = %name, "LLM API key secret is not in the secret store"),
+        }
+    }
+}
+
+fn build_provider(
+    client: &reqwest::Client,
+    kind: &crustyclaw_config::LlmProviderKind,
+    api_key: &str,
+    model: &str,
+    base_url: Option<&str>,
+    llamacpp: &crustyclaw_config::LlamaCppConfig,
+) -> Box<dyn LlmProvider> {
     use crustyclaw_config::LlmProviderKind;
 
-    match config.provider {
+    match kind {
         LlmProviderKind::Anthropic => {
-            let mut provider = AnthropicProvider::new(&config.api_key);
-            if !config.model.is_empty() {
-                provider = provider.with_model(&config.model);
+            let mut provider = AnthropicProvider::new(api_key).with_client(client.clone());
+            if !model.is_empty() {
+                provider = provider.with_model(model);
             }
             Box::new(provider)
         }
         LlmProviderKind::OpenAi => {
-            let mut provider = OpenAiProvider::new(&config.api_key);
-            if !config.model.is_empty() {
-                provider = provider.with_model(&config.model);
+            let mut provider = OpenAiProvider::new(api_key).with_client(client.clone());
+            if !model.is_empty() {
+                provider = provider.with_model(model);
+            }
+            if let Some(base_url) = base_url {
+                provider = provider.with_base_url(base_url);
             }
-            if let Some(ref base_url) = config.base_url {
+            Box::new(provider)
+        }
+        LlmProviderKind::LlamaCpp => {
+            let mut provider = LlamaCppProvider::new()
+                .with_client(client.clone())
+                .with_api_key(api_key)
+                .with_template(llamacpp.chat_template)
+                .with_grammar(llamacpp.grammar);
+            if !model.is_empty() {
+                provider = provider.with_model(model);
+            }
+            if let Some(base_url) = base_url {
                 provider = provider.with_base_url(base_url);
             }
+            if let Some(tokens) = llamacpp.context_size {
+                provider = provider.with_context_size(tokens);
+            }
             Box::new(provider)
         }
     }
 }
 
+/// Record label of a provider: `<provider>/<model>`, or `<provider>`.
+fn label(kind: &crustyclaw_config::LlmProviderKind, model: &str) -> String {
+    use crustyclaw_config::LlmProviderKind;
+
+    let kind = match kind {
+        LlmProviderKind::Anthropic => "anthropic",
+        LlmProviderKind::OpenAi => "openai",
+        LlmProviderKind::LlamaCpp => "llamacpp",
+    };
+    if model.is_empty() {
+        kind.to_string()
+    } else {
+        format!("{kind}/{model}")
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -74,6 +202,8 @@ mod tests {
             base_url: None,
             max_tokens: 4096,
             temperature: 0.0,
+            tokenizer_vocab: None,
+            ..LlmConfig::default()
         };
         let provider = create_provider(&config);
         assert_eq!(provider.name(), "Anthropic");
@@ -88,8 +218,95 @@ mod tests {
             base_url: None,
             max_tokens: 4096,
             temperature: 0.7,
+            tokenizer_vocab: None,
+            ..LlmConfig::default()
         };
         let provider = create_provider(&config);
         assert_eq!(provider.name(), "OpenAI");
     }
+
+    #[test]
+    fn test_failover_provider_from_config() {
+        let config = crustyclaw_config::AppConfig::parse(
+            r#"
+            [llm]
+            model = "claude-sonnet-4-20250514"
+
+            [[llm.fallbacks]]
+            provider = "openai"
+            model = "gpt-4o"
+
+            [[llm.fallbacks]]
+            provider = "openai"
+            base_url = "http://localhost:11434/v1"
+        "#,
+        )
+        .unwrap();
+        let provider = failover_provider(&config.llm);
+        assert_eq!(provider.name(), "Anthropic");
+        assert_eq!(
+            provider.labels(),
+            [
+                "anthropic/claude-sonnet-4-20250514",
+                "openai/gpt-4o",
+                "openai"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_key_secrets() {
+        use crate::secrets::{
+            InjectionMethod, SecretEntry, SecretSource, SecretStore, SecretValue,
+        };
+
+        let mut config = crustyclaw_config::AppConfig::parse(
+            r#"
+            [llm]
+            api_key = "inline"
+            api_key_secret = "anthropic_key"
+
+            [[llm.fallbacks]]
+            provider = "openai"
+            api_key_secret = "openai_key"
+
+            [[llm.fallbacks]]
+            provider = "openai"
+            api_key = "fallback-inline"
+
+            [[secrets.entries]]
+            name = "anthropic_key"
+            source = "env"
+            env_var = "ANTHROPIC_API_KEY"
+            inject_env = "ANTHROPIC_API_KEY"
+
+            [[secrets.entries]]
+            name = "openai_key"
+            source = "env"
+            env_var = "OPENAI_API_KEY"
+            inject_env = "OPENAI_API_KEY"
+        "#,
+        )
+        .unwrap()
+        .llm;
+        assert_eq!(key_secrets(&config), ["anthropic_key", "openai_key"]);
+
+        let mut store = SecretStore::new();
+        store
+            .insert(
+                SecretEntry {
+                    name: "anthropic_key".to_string(),
+                    value: SecretValue::new("sk-rotated"),
+                    injection: InjectionMethod::Env("ANTHROPIC_API_KEY".to_string()),
+                    description: String::new(),
+                },
+                SecretSource::Config,
+            )
+            .unwrap();
+        resolve_key_secrets(&mut config, &store);
+        assert_eq!(config.api_key, "sk-rotated");
+        // Missing from the store: left as configured.
+        assert_eq!(config.fallbacks[0].api_key, "");
+        assert_eq!(config.fallbacks[1].api_key, "fallback-inline");
+    }
 }
@@ -10,6 +10,7 @@ use tracing::debug;
 
 use crate::BoxFuture;
 
+use super::http::send_error;
 use super::provider::{LlmError, LlmProvider};
 use super::types::*;
 
@@ -34,6 +35,13 @@ impl OpenAiProvider {
         }
     }
 
+    /// Send requests with `client`, such as the shared one of
+    /// [`http_client`](super::http_client).
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
     /// Set the default model.
     pub fn with_model(mut self, model: impl Into<String>) -> Self {
         self.default_model = model.into();
@@ -60,17 +68,33 @@ impl OpenAiProvider {
         if let Some(ref system) = request.system {
             messages.push(OpenAiMessage {
                 role: "system".to_string(),
-                content: Some(system.clone()),
+                content: Some(OpenAiContent::Text(system.clone())),
                 tool_calls: None,
                 tool_call_id: None,
             });
         }
 
-        // Convert messages
+        // Convert messages; images go with the text as content parts
         for msg in &request.messages {
+            let content = if msg.images.is_empty() {
+                msg.content.clone().map(OpenAiContent::Text)
+            } else {
+                let mut parts: Vec<OpenAiPart> = msg
+                    .content
+                    .iter()
+                    .filter(|text| !text.is_empty())
+                    .map(|text| OpenAiPart::Text { text: text.clone() })
+                    .collect();
+                parts.extend(msg.images.iter().map(|image| OpenAiPart::ImageUrl {
+                    image_url: OpenAiImageUrl {
+                        url: image.to_url(),
+                    },
+                }));
+                Some(OpenAiContent::Parts(parts))
+            };
             messages.push(OpenAiMessage {
                 role: msg.role.clone(),
-                content: msg.content.clone(),
+                content,
                 tool_calls: msg.tool_calls.as_ref().map(|calls| {
                     calls
                         .iter()
@@ -142,9 +166,10 @@ impl OpenAiProvider {
         Ok(ChatResponse {
             message: ChatMessage {
                 role: "assistant".to_string(),
-                content: choice.message.content,
+                content: choice.message.content.map(OpenAiContent::into_text),
                 tool_call_id: None,
                 tool_calls,
+                images: Vec::new(),
             },
             finish_reason,
             usage: resp.usage.map_or_else(TokenUsage::default, |u| TokenUsage {
@@ -153,6 +178,7 @@ impl OpenAiProvider {
                 total_tokens: u.total_tokens,
             }),
             model: resp.model,
+            attempts: 1,
         })
     }
 }
@@ -175,7 +201,7 @@ impl LlmProvider for OpenAiProvider {
                 .json(&body)
                 .send()
                 .await
-                .map_err(|e| LlmError::Network(e.to_string()))?;
+                .map_err(send_error)?;
 
             let status = resp.status().as_u16();
             if status == 401 {
@@ -244,6 +270,7 @@ impl LlmProvider for OpenAiProvider {
                     .send(Ok(StreamChunk::Done {
                         finish_reason: response.finish_reason,
                         usage: Some(response.usage),
+                        attempts: 1,
                     }))
                     .await;
             });
@@ -271,13 +298,49 @@ struct OpenAiRequest {
 struct OpenAiMessage {
     role: String,
     #[serde(skip_serializing_if = "Option::is_none")]
-    content: Option<String>,
+    content: Option<OpenAiContent>,
     #[serde(skip_serializing_if = "Option::is_none")]
     tool_calls: Option<Vec<OpenAiToolCall>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     tool_call_id: Option<String>,
 }
 
+/// Message content: plain text, or text and image parts.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum OpenAiContent {
+    Text(String),
+    Parts(Vec<OpenAiPart>),
+}
+
+impl OpenAiContent {
+    /// The text of the content, with text parts joined.
+    fn into_text(self) -> String {
+        match self {
+            Self::Text(text) => text,
+            Self::Parts(parts) => parts
+                .into_iter()
+                .filter_map(|part| match part {
+                    OpenAiPart::Text { text } => Some(text),
+                    OpenAiPart::ImageUrl { .. } => None,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum OpenAiPart {
+    Text { text: String },
+    ImageUrl { image_url: OpenAiImageUrl },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OpenAiImageUrl {
+    url: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct OpenAiToolCall {
     id: String,
@@ -346,6 +409,29 @@ mod tests {
         assert_eq!(body.messages[1].role, "user");
     }
 
+    #[test]
+    fn test_build_request_with_images() {
+        let provider = OpenAiProvider::new("test-key");
+        let request = ChatRequest {
+            messages: vec![
+                ChatMessage::user("What is this?")
+                    .with_images(vec![ImageSource::from_bytes("image/png", b"foo")]),
+                ChatMessage::assistant("A cat."),
+            ],
+            ..Default::default()
+        };
+
+        let body = serde_json::to_value(provider.build_request_body(&request)).unwrap();
+        assert_eq!(
+            body["messages"][0]["content"],
+            serde_json::json!([
+                {"type": "text", "text": "What is this?"},
+                {"type": "image_url", "image_url": {"url": "data:image/png;base64,Zm9v"}}
+            ])
+        );
+        assert_eq!(body["messages"][1]["content"], "A cat.");
+    }
+
     #[test]
     fn test_parse_text_response() {
         let provider = OpenAiProvider::new("test-key");
@@ -354,7 +440,7 @@ mod tests {
             choices: vec![OpenAiChoice {
                 message: OpenAiMessage {
                     role: "assistant".to_string(),
-                    content: Some("Hello!".to_string()),
+                    content: Some(OpenAiContent::Text("Hello!".to_string())),
                     tool_calls: None,
                     tool_call_id: None,
                 },
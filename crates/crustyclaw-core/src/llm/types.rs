@@ -3,6 +3,10 @@
 //! These types define the shared vocabulary for chat completions,
 //! tool definitions, and streaming across all LLM providers.
 
+use std::time::Instant;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
 use serde::{Deserialize, Serialize};
 
 /// A chat message in a conversation.
@@ -19,6 +23,9 @@ pub struct ChatMessage {
     /// Tool calls requested by the assistant.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_calls: Option<Vec<ToolCall>>,
+    /// Images shown to the model along with a user message's text.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<ImageSource>,
 }
 
 impl ChatMessage {
@@ -28,6 +35,7 @@ impl ChatMessage {
             content: Some(content.into()),
             tool_call_id: None,
             tool_calls: None,
+            images: Vec::new(),
         }
     }
 
@@ -37,6 +45,7 @@ impl ChatMessage {
             content: Some(content.into()),
             tool_call_id: None,
             tool_calls: None,
+            images: Vec::new(),
         }
     }
 
@@ -46,6 +55,7 @@ impl ChatMessage {
             content: Some(content.into()),
             tool_call_id: None,
             tool_calls: None,
+            images: Vec::new(),
         }
     }
 
@@ -55,6 +65,44 @@ impl ChatMessage {
             content: Some(content.into()),
             tool_call_id: Some(tool_call_id.into()),
             tool_calls: None,
+            images: Vec::new(),
+        }
+    }
+
+    /// Builder: show `images` to the model with this message.
+    pub fn with_images(mut self, images: Vec<ImageSource>) -> Self {
+        self.images = images;
+        self
+    }
+}
+
+/// Image media types the providers accept.
+pub const IMAGE_MEDIA_TYPES: &[&str] = &["image/jpeg", "image/png", "image/gif", "image/webp"];
+
+/// An image given to a vision model.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ImageSource {
+    /// Image data of `media_type` (e.g. `image/png`), base64-encoded.
+    Base64 { media_type: String, data: String },
+    /// An image the provider downloads itself.
+    Url { url: String },
+}
+
+impl ImageSource {
+    /// An inline image of `media_type` with contents `bytes`.
+    pub fn from_bytes(media_type: impl Into<String>, bytes: &[u8]) -> Self {
+        Self::Base64 {
+            media_type: media_type.into(),
+            data: BASE64.encode(bytes),
+        }
+    }
+
+    /// The image as a URL: a `data:` URL for inline data.
+    pub fn to_url(&self) -> String {
+        match self {
+            Self::Base64 { media_type, data } => format!("data:{media_type};base64,{data}"),
+            Self::Url { url } => url.clone(),
         }
     }
 }
@@ -96,6 +144,9 @@ pub struct ChatRequest {
     pub temperature: f32,
     /// Optional system prompt (overrides system message in messages).
     pub system: Option<String>,
+    /// When the request must be answered by, retries included; set by the
+    /// agent loop from `chat.timeout_secs`.
+    pub deadline: Option<Instant>,
 }
 
 impl Default for ChatRequest {
@@ -107,6 +158,7 @@ impl Default for ChatRequest {
             max_tokens: 4096,
             temperature: 0.0,
             system: None,
+            deadline: None,
         }
     }
 }
@@ -122,6 +174,10 @@ pub struct ChatResponse {
     pub usage: TokenUsage,
     /// Raw model identifier used.
     pub model: String,
+    /// Requests sent to produce this response, retries and fallbacks
+    /// included. Providers report 1; [`FailoverProvider`](super::FailoverProvider)
+    /// reports the total.
+    pub attempts: u32,
 }
 
 /// Token usage statistics.
@@ -145,7 +201,41 @@ pub enum StreamChunk {
     Done {
         finish_reason: String,
         usage: Option<TokenUsage>,
+        /// Requests sent, as in [`ChatResponse::attempts`].
+        attempts: u32,
     },
 }
 
 // Provider kind is defined in crustyclaw_config::LlmProviderKind
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_source() {
+        let image = ImageSource::from_bytes("image/png", b"foo");
+        assert_eq!(image.to_url(), "data:image/png;base64,Zm9v");
+        assert_eq!(
+            serde_json::to_value(&image).unwrap(),
+            serde_json::json!({"type": "base64", "media_type": "image/png", "data": "Zm9v"})
+        );
+        let url = ImageSource::Url {
+            url: "https://example.com/cat.jpg".into(),
+        };
+        assert_eq!(url.to_url(), "https://example.com/cat.jpg");
+
+        // Messages without images serialize as before.
+        let message = ChatMessage::user("hi");
+        assert!(
+            serde_json::to_value(&message)
+                .unwrap()
+                .get("images")
+                .is_none()
+        );
+        let message = message.with_images(vec![url]);
+        let json = serde_json::to_string(&message).unwrap();
+        let parsed: ChatMessage = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.images, message.images);
+    }
+}
@@ -1,18 +1,35 @@
-//! In-memory log collector for the TUI.
+//! Log capture, log files, and runtime log levels.
 //!
 //! Provides a [`LogCollector`] that captures `tracing` events into a bounded
-//! ring buffer, and a [`LogReader`] handle for reading captured entries.
+//! ring buffer, and a [`LogReader`] handle for reading captured entries or
+//! tailing new ones as they arrive (see [`LogReader::tail`]).
+//!
+//! [`LogControl`] owns the reloadable parts of the process's subscriber:
+//! the level filter, which `PUT /logging/level` replaces while the daemon
+//! runs, and the `[logging] file` output, written through a
+//! [`RollingFileWriter`] once the daemon has read its config.
 
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
+use crustyclaw_config::LoggingConfig;
+use tokio::sync::broadcast;
 use tracing::field::{Field, Visit};
 use tracing::{Event, Level, Subscriber};
-use tracing_subscriber::Layer;
-use tracing_subscriber::layer::Context;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::{Context, Layered};
+use tracing_subscriber::{EnvFilter, Layer, Registry, fmt, reload};
+
+/// Default ring buffer capacity for daemon and TUI collectors.
+pub const DEFAULT_LOG_CAPACITY: usize = 1000;
 
 /// A single captured log entry.
 #[derive(Debug, Clone)]
 pub struct LogEntry {
+    /// Sequence number, starting at 1 and increasing by one per entry.
+    pub seq: u64,
     /// Timestamp as seconds since the collector was created.
     pub elapsed_secs: f64,
     /// Log level.
@@ -29,6 +46,10 @@ struct LogBuffer {
     entries: Vec<LogEntry>,
     capacity: usize,
     start_time: std::time::Instant,
+    next_seq: u64,
+    /// Live subscribers; entries are sent while the buffer lock is held so
+    /// that [`LogReader::tail`] sees neither gaps nor duplicates.
+    live: broadcast::Sender<LogEntry>,
 }
 
 impl LogBuffer {
@@ -37,6 +58,8 @@ impl LogBuffer {
             entries: Vec::with_capacity(capacity),
             capacity,
             start_time: std::time::Instant::now(),
+            next_seq: 1,
+            live: broadcast::channel(capacity.max(1)).0,
         }
     }
 
@@ -44,12 +67,17 @@ impl LogBuffer {
         if self.entries.len() >= self.capacity {
             self.entries.remove(0);
         }
-        self.entries.push(LogEntry {
+        let entry = LogEntry {
+            seq: self.next_seq,
             elapsed_secs: self.start_time.elapsed().as_secs_f64(),
             level,
             target,
             message,
-        });
+        };
+        self.next_seq += 1;
+        // No receivers is the common case; ignore the error.
+        let _ = self.live.send(entry.clone());
+        self.entries.push(entry);
     }
 }
 
@@ -117,6 +145,24 @@ impl LogReader {
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
+
+    /// Return buffered entries with `seq > after` and a receiver for every
+    /// entry logged from now on.
+    ///
+    /// With `after = None` the whole buffer is returned. An `after` at or
+    /// beyond the next sequence number (e.g. from a client that saw a
+    /// previous daemon instance) is treated as `None`.
+    pub fn tail(&self, after: Option<u64>) -> (Vec<LogEntry>, broadcast::Receiver<LogEntry>) {
+        let buf = self.buffer.lock().unwrap_or_else(|e| e.into_inner());
+        let after = after.filter(|&seq| seq < buf.next_seq).unwrap_or(0);
+        let backlog = buf
+            .entries
+            .iter()
+            .filter(|e| e.seq > after)
+            .cloned()
+            .collect();
+        (backlog, buf.live.subscribe())
+    }
 }
 
 /// Visitor that extracts the `message` field from a tracing event.
@@ -139,6 +185,190 @@ impl Visit for MessageVisitor {
     }
 }
 
+/// The level filter [`LogControl::set_level`] replaces. It goes directly
+/// on the registry, before the [`FileLayer`].
+pub type LevelLayer = reload::Layer<EnvFilter, Registry>;
+
+type Leveled = Layered<LevelLayer, Registry>;
+type FileOutput = Option<Box<dyn Layer<Leveled> + Send + Sync>>;
+
+/// The log file output, empty until [`LogControl::open_file`].
+pub type FileLayer = reload::Layer<FileOutput, Leveled>;
+
+/// Errors changing the log level.
+#[derive(Debug, thiserror::Error)]
+pub enum LogControlError {
+    #[error("invalid log level {directives:?}: {reason}")]
+    InvalidLevel { directives: String, reason: String },
+
+    #[error("the log subscriber is gone: {0}")]
+    Reload(#[from] reload::Error),
+}
+
+/// Handle on the reloadable layers of the process's tracing subscriber.
+#[derive(Clone)]
+pub struct LogControl {
+    level: reload::Handle<EnvFilter, Registry>,
+    file: reload::Handle<FileOutput, Leveled>,
+}
+
+impl std::fmt::Debug for LogControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LogControl")
+            .field("level", &self.level())
+            .finish_non_exhaustive()
+    }
+}
+
+impl LogControl {
+    /// Create a control starting at `filter`, and the layers it controls:
+    /// `registry().with(level).with(file)`, then any other layers.
+    pub fn new(filter: EnvFilter) -> (Self, LevelLayer, FileLayer) {
+        let (level_layer, level) = reload::Layer::new(filter);
+        let (file_layer, file) = reload::Layer::new(None);
+        (Self { level, file }, level_layer, file_layer)
+    }
+
+    /// The filter in effect, e.g. `info` or `info,crustyclaw_core=debug`.
+    pub fn level(&self) -> String {
+        self.level
+            .with_current(ToString::to_string)
+            .unwrap_or_default()
+    }
+
+    /// Replace the filter with `directives`, in `RUST_LOG` syntax.
+    pub fn set_level(&self, directives: &str) -> Result<(), LogControlError> {
+        let invalid = |reason: String| LogControlError::InvalidLevel {
+            directives: directives.to_string(),
+            reason,
+        };
+        if directives.trim().is_empty() {
+            return Err(invalid("no directives".to_string()));
+        }
+        let filter = EnvFilter::builder()
+            .parse(directives)
+            .map_err(|e| invalid(e.to_string()))?;
+        self.level.reload(filter)?;
+        Ok(())
+    }
+
+    /// Start writing the log to `config.file`, if set, in `config.format`,
+    /// returning the file's path.
+    pub fn open_file(&self, config: &LoggingConfig) -> io::Result<Option<PathBuf>> {
+        let Some(path) = &config.file else {
+            return Ok(None);
+        };
+        let path = PathBuf::from(path);
+        let writer = RollingFileWriter::open(&path, config.max_size_bytes, config.max_files)?;
+        let layer: Box<dyn Layer<Leveled> + Send + Sync> = match config.format.as_str() {
+            "json" => Box::new(fmt::layer().json().with_writer(writer)),
+            _ => Box::new(fmt::layer().with_ansi(false).with_writer(writer)),
+        };
+        self.file.reload(Some(layer)).map_err(io::Error::other)?;
+        Ok(Some(path))
+    }
+}
+
+/// A log file rotated by size.
+///
+/// When a write would take the file past `max_bytes`, it is renamed to
+/// `<path>.1`, `<path>.1` to `<path>.2`, and so on; `max_files` files are
+/// kept, counting the one being written. Files are created with mode 0600.
+#[derive(Debug)]
+pub struct RollingFileWriter {
+    inner: Mutex<RollingFile>,
+}
+
+#[derive(Debug)]
+struct RollingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_bytes: u64,
+    max_files: usize,
+}
+
+impl RollingFileWriter {
+    /// Open `path` for appending, creating it and its directory if needed.
+    pub fn open(path: &Path, max_bytes: u64, max_files: usize) -> io::Result<Self> {
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let file = open_log_file(path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            inner: Mutex::new(RollingFile {
+                path: path.to_path_buf(),
+                file,
+                size,
+                max_bytes,
+                max_files: max_files.max(1),
+            }),
+        })
+    }
+}
+
+fn open_log_file(path: &Path) -> io::Result<File> {
+    let mut options = std::fs::OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)
+}
+
+/// `<path>.<n>`.
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{n}"));
+    PathBuf::from(name)
+}
+
+impl RollingFile {
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.max_files == 1 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            for n in (1..self.max_files - 1).rev() {
+                match std::fs::rename(rotated_path(&self.path, n), rotated_path(&self.path, n + 1))
+                {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                }
+            }
+            std::fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        }
+        self.file = open_log_file(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for &RollingFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut log = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        // The formatter writes each event at once, so events are not split.
+        if log.size > 0 && log.size + buf.len() as u64 > log.max_bytes {
+            log.rotate()?;
+        }
+        let written = log.file.write(buf)?;
+        log.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut log = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        log.file.flush()
+    }
+}
+
+impl<'a> MakeWriter<'a> for RollingFileWriter {
+    type Writer = &'a RollingFileWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -179,6 +409,118 @@ mod tests {
         assert!(entries[0].message.contains("two"));
     }
 
+    #[test]
+    fn test_log_entries_are_sequenced() {
+        let collector = LogCollector::new(2);
+        let reader = collector.reader();
+
+        let _guard = tracing_subscriber::registry().with(collector).set_default();
+
+        tracing::info!("one");
+        tracing::info!("two");
+        tracing::info!("three");
+
+        let seqs: Vec<u64> = reader.entries().iter().map(|e| e.seq).collect();
+        assert_eq!(seqs, vec![2, 3]);
+    }
+
+    #[test]
+    fn test_tail_returns_backlog_then_live() {
+        let collector = LogCollector::new(10);
+        let reader = collector.reader();
+
+        let _guard = tracing_subscriber::registry().with(collector).set_default();
+
+        tracing::info!("one");
+        tracing::info!("two");
+
+        let (backlog, _) = reader.tail(None);
+        assert_eq!(backlog.len(), 2);
+
+        let (backlog, mut live) = reader.tail(Some(1));
+        assert_eq!(backlog.len(), 1);
+        assert_eq!(backlog[0].message, "two");
+
+        tracing::warn!("three");
+        let entry = live.try_recv().unwrap();
+        assert_eq!(entry.seq, 3);
+        assert_eq!(entry.level, Level::WARN);
+
+        // A cursor from a previous daemon instance replays the whole buffer.
+        let (backlog, _) = reader.tail(Some(100));
+        assert_eq!(backlog.len(), 3);
+    }
+
+    #[test]
+    fn test_rolling_file_rotates_by_size() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("logs/daemon.log");
+        let writer = RollingFileWriter::open(&path, 10, 3).unwrap();
+        for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+            (&writer).write_all(line.as_bytes()).unwrap();
+        }
+        let read = |p: &Path| std::fs::read_to_string(p).unwrap();
+        assert_eq!(read(&path), "fourth\n");
+        assert_eq!(read(&rotated_path(&path, 1)), "third\n");
+        assert_eq!(read(&rotated_path(&path, 2)), "second\n");
+        assert!(!rotated_path(&path, 3).exists());
+
+        // Reopening continues the current file.
+        drop(writer);
+        let writer = RollingFileWriter::open(&path, 10, 1).unwrap();
+        (&writer).write_all(b"x\n").unwrap();
+        assert_eq!(read(&path), "fourth\nx\n");
+        // With one file, rotating starts it over.
+        (&writer).write_all(b"fifth\n").unwrap();
+        assert_eq!(read(&path), "fifth\n");
+    }
+
+    #[test]
+    fn test_log_control_changes_level_and_opens_file() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let (control, level, file) = LogControl::new(EnvFilter::new("info"));
+        let collector = LogCollector::new(10);
+        let reader = collector.reader();
+        let _guard = tracing_subscriber::registry()
+            .with(level)
+            .with(file)
+            .with(collector)
+            .set_default();
+
+        tracing::debug!("hidden");
+        control.set_level("debug").unwrap();
+        assert_eq!(control.level(), "debug");
+        tracing::debug!("shown");
+        let messages: Vec<String> = reader.entries().into_iter().map(|e| e.message).collect();
+        assert_eq!(messages, vec!["shown"]);
+
+        assert!(matches!(
+            control.set_level("info,=bogus"),
+            Err(LogControlError::InvalidLevel { .. })
+        ));
+        assert!(control.set_level(" ").is_err());
+        assert_eq!(control.level(), "debug");
+
+        let config = LoggingConfig {
+            file: Some(tmp.path().join("crustyclaw.log").display().to_string()),
+            format: "json".to_string(),
+            ..LoggingConfig::default()
+        };
+        let path = control.open_file(&config).unwrap().unwrap();
+        tracing::info!(skill = "echo", "to the file");
+        let line = std::fs::read_to_string(path).unwrap();
+        let event: serde_json::Value = serde_json::from_str(line.trim()).unwrap();
+        assert_eq!(event["fields"]["message"], "to the file");
+        assert_eq!(event["fields"]["skill"], "echo");
+
+        assert!(
+            control
+                .open_file(&LoggingConfig::default())
+                .unwrap()
+                .is_none()
+        );
+    }
+
     #[test]
     fn test_log_reader_is_empty() {
         let collector = LogCollector::new(10);
@@ -0,0 +1,713 @@
+//! MCP client — imports tools from external Model Context Protocol servers.
+//!
+//! Each `[[mcp.servers]]` entry is reached over one of the two standard
+//! transports:
+//!
+//! - **stdio** — `command` is spawned and exchanges newline-delimited
+//!   JSON-RPC messages on its stdin/stdout;
+//! - **streamable HTTP** — JSON-RPC requests are POSTed to `url`, and the
+//!   response is read from a JSON body or an SSE stream.
+//!
+//! [`McpHub::import`] connects to every enabled server, lists its tools, and
+//! registers them in the [`ToolRegistry`] as `<server>__<tool>` with the
+//! server's trust level and the tags `<tag_prefix>` and
+//! `<tag_prefix>:<server>`. From there they go through the same trust and
+//! tag scoping as built-in tools; [`McpHub::call`] routes a call back to the
+//! server that owns the tool.
+
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdin, ChildStdout};
+
+use crustyclaw_config::{McpConfig, McpServerConfig};
+
+use crate::BoxFuture;
+use crate::context::{RegisteredTool, ToolRegistry, ToolTrust};
+use crate::llm::types::ToolDefinition;
+
+/// MCP protocol revision sent in `initialize`.
+pub const PROTOCOL_VERSION: &str = "2025-06-18";
+
+/// Separator between the server name and the tool name in registered names.
+pub const TOOL_NAME_SEPARATOR: &str = "__";
+
+/// Errors talking to an MCP server.
+#[derive(Debug, thiserror::Error)]
+pub enum McpError {
+    #[error("failed to start server: {0}")]
+    Spawn(String),
+
+    #[error("transport error: {0}")]
+    Transport(String),
+
+    #[error("no response within {0:?}")]
+    Timeout(Duration),
+
+    #[error("server returned error {code}: {message}")]
+    Rpc { code: i64, message: String },
+
+    #[error("protocol error: {0}")]
+    Protocol(String),
+
+    #[error("unknown MCP tool: {0}")]
+    UnknownTool(String),
+}
+
+/// A tool advertised by a server's `tools/list`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct McpTool {
+    /// Tool name on the server.
+    pub name: String,
+    /// Human-readable description.
+    #[serde(default)]
+    pub description: String,
+    /// JSON Schema for the tool's arguments.
+    #[serde(rename = "inputSchema", default = "empty_schema")]
+    pub input_schema: Value,
+}
+
+fn empty_schema() -> Value {
+    json!({ "type": "object" })
+}
+
+/// The result of a `tools/call`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct McpToolResult {
+    /// Content blocks returned by the tool.
+    #[serde(default)]
+    pub content: Vec<McpContent>,
+    /// Whether the tool reported a failure.
+    #[serde(rename = "isError", default)]
+    pub is_error: bool,
+}
+
+impl McpToolResult {
+    /// The text blocks joined by newlines; other block types are skipped.
+    pub fn text(&self) -> String {
+        self.content
+            .iter()
+            .filter_map(|c| match c {
+                McpContent::Text { text } => Some(text.as_str()),
+                McpContent::Other => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// A content block in a tool result.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum McpContent {
+    /// Plain text.
+    Text { text: String },
+    /// Images, audio, resources, and anything newer.
+    #[serde(other)]
+    Other,
+}
+
+/// Moves JSON-RPC messages to and from a server.
+///
+/// Returns [`BoxFuture`] because transports are used as `dyn McpTransport`.
+trait McpTransport: Send + Sync {
+    /// Send a request and wait for the response with the same `id`.
+    fn request(&self, message: Value) -> BoxFuture<'_, Result<Value, McpError>>;
+
+    /// Send a notification (no response).
+    fn notify(&self, message: Value) -> BoxFuture<'_, Result<(), McpError>>;
+}
+
+/// Newline-delimited JSON-RPC over a child process's stdio.
+struct StdioTransport {
+    /// Kept so the server is killed when the transport is dropped.
+    _child: Child,
+    io: tokio::sync::Mutex<(ChildStdin, Lines<BufReader<ChildStdout>>)>,
+}
+
+impl StdioTransport {
+    fn spawn(config: &McpServerConfig) -> Result<Self, McpError> {
+        let mut child = tokio::process::Command::new(&config.command[0])
+            .args(&config.command[1..])
+            .envs(&config.env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| McpError::Spawn(format!("{}: {e}", config.command[0])))?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        Ok(Self {
+            _child: child,
+            io: tokio::sync::Mutex::new((stdin, BufReader::new(stdout).lines())),
+        })
+    }
+}
+
+async fn write_line(stdin: &mut ChildStdin, message: &Value) -> Result<(), McpError> {
+    let mut line = message.to_string();
+    line.push('\n');
+    stdin
+        .write_all(line.as_bytes())
+        .await
+        .map_err(|e| McpError::Transport(format!("write failed: {e}")))?;
+    stdin
+        .flush()
+        .await
+        .map_err(|e| McpError::Transport(format!("write failed: {e}")))
+}
+
+impl McpTransport for StdioTransport {
+    fn request(&self, message: Value) -> BoxFuture<'_, Result<Value, McpError>> {
+        Box::pin(async move {
+            let mut io = self.io.lock().await;
+            let (stdin, lines) = &mut *io;
+            write_line(stdin, &message).await?;
+            loop {
+                let line = lines
+                    .next_line()
+                    .await
+                    .map_err(|e| McpError::Transport(format!("read failed: {e}")))?
+                    .ok_or_else(|| McpError::Transport("server closed stdout".to_string()))?;
+                let Ok(reply) = serde_json::from_str::<Value>(&line) else {
+                    tracing::debug!(line, "Ignoring non-JSON line from MCP server");
+                    continue;
+                };
+                if reply.get("method").is_some() {
+                    // A server-initiated request or notification; answer
+                    // requests so the server does not wait on us.
+                    if let Some(id) = reply.get("id") {
+                        let error = json!({
+                            "jsonrpc": "2.0",
+                            "id": id,
+                            "error": { "code": -32601, "message": "method not supported" },
+                        });
+                        write_line(stdin, &error).await?;
+                    }
+                    continue;
+                }
+                if reply.get("id") == message.get("id") {
+                    return Ok(reply);
+                }
+            }
+        })
+    }
+
+    fn notify(&self, message: Value) -> BoxFuture<'_, Result<(), McpError>> {
+        Box::pin(async move {
+            let mut io = self.io.lock().await;
+            write_line(&mut io.0, &message).await
+        })
+    }
+}
+
+/// JSON-RPC POSTed to a streamable HTTP endpoint.
+struct HttpTransport {
+    client: reqwest::Client,
+    url: String,
+    /// `Mcp-Session-Id` assigned by the server during initialization.
+    session: std::sync::Mutex<Option<String>>,
+}
+
+impl HttpTransport {
+    fn new(url: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.to_string(),
+            session: std::sync::Mutex::new(None),
+        }
+    }
+
+    async fn post(&self, message: &Value) -> Result<reqwest::Response, McpError> {
+        let session = self
+            .session
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        let mut request = self
+            .client
+            .post(&self.url)
+            .header("Accept", "application/json, text/event-stream")
+            .header("MCP-Protocol-Version", PROTOCOL_VERSION)
+            .json(message);
+        if let Some(session) = session {
+            request = request.header("Mcp-Session-Id", session);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| McpError::Transport(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(McpError::Transport(format!(
+                "HTTP {} from {}",
+                response.status(),
+                self.url
+            )));
+        }
+        if let Some(session) = response
+            .headers()
+            .get("Mcp-Session-Id")
+            .and_then(|v| v.to_str().ok())
+        {
+            *self.session.lock().unwrap_or_else(|e| e.into_inner()) = Some(session.to_string());
+        }
+        Ok(response)
+    }
+}
+
+impl McpTransport for HttpTransport {
+    fn request(&self, message: Value) -> BoxFuture<'_, Result<Value, McpError>> {
+        Box::pin(async move {
+            let response = self.post(&message).await?;
+            let is_sse = response
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|v| v.starts_with("text/event-stream"));
+            let body = response
+                .text()
+                .await
+                .map_err(|e| McpError::Transport(e.to_string()))?;
+            if !is_sse {
+                return serde_json::from_str(&body)
+                    .map_err(|e| McpError::Protocol(format!("invalid JSON response: {e}")));
+            }
+            body.lines()
+                .filter_map(|line| line.strip_prefix("data:"))
+                .filter_map(|data| serde_json::from_str::<Value>(data.trim()).ok())
+                .find(|reply| reply.get("method").is_none() && reply.get("id") == message.get("id"))
+                .ok_or_else(|| {
+                    McpError::Protocol("event stream ended without a response".to_string())
+                })
+        })
+    }
+
+    fn notify(&self, message: Value) -> BoxFuture<'_, Result<(), McpError>> {
+        Box::pin(async move { self.post(&message).await.map(|_| ()) })
+    }
+}
+
+/// A connection to one MCP server.
+pub struct McpClient {
+    server: String,
+    transport: Box<dyn McpTransport>,
+    next_id: AtomicU64,
+    timeout: Duration,
+}
+
+impl McpClient {
+    /// Connect to the server described by `config` and complete the
+    /// `initialize` handshake.
+    pub async fn connect(config: &McpServerConfig) -> Result<Self, McpError> {
+        let transport: Box<dyn McpTransport> = match &config.url {
+            Some(url) => Box::new(HttpTransport::new(url)),
+            None if !config.command.is_empty() => Box::new(StdioTransport::spawn(config)?),
+            None => return Err(McpError::Spawn("no command or url configured".to_string())),
+        };
+        let client = Self {
+            server: config.name.clone(),
+            transport,
+            next_id: AtomicU64::new(1),
+            timeout: Duration::from_secs(config.timeout_secs),
+        };
+        client.initialize().await?;
+        Ok(client)
+    }
+
+    /// The configured server name.
+    pub fn server(&self) -> &str {
+        &self.server
+    }
+
+    async fn initialize(&self) -> Result<(), McpError> {
+        let result = self
+            .call(
+                "initialize",
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": {
+                        "name": "crustyclaw",
+                        "version": env!("CARGO_PKG_VERSION"),
+                    },
+                }),
+            )
+            .await?;
+        tracing::debug!(
+            server = %self.server,
+            protocol = %result["protocolVersion"],
+            "MCP server initialized"
+        );
+        self.transport
+            .notify(json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }))
+            .await
+    }
+
+    /// List every tool the server offers, following pagination cursors.
+    pub async fn list_tools(&self) -> Result<Vec<McpTool>, McpError> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let mut result = self.call("tools/list", params).await?;
+            let page: Vec<McpTool> = serde_json::from_value(result["tools"].take())
+                .map_err(|e| McpError::Protocol(format!("invalid tools/list result: {e}")))?;
+            tools.extend(page);
+            match result["nextCursor"].as_str() {
+                Some(next) if !next.is_empty() => cursor = Some(next.to_string()),
+                _ => return Ok(tools),
+            }
+        }
+    }
+
+    /// Call the server's tool `name` with `arguments`.
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<McpToolResult, McpError> {
+        let result = self
+            .call(
+                "tools/call",
+                json!({ "name": name, "arguments": arguments }),
+            )
+            .await?;
+        serde_json::from_value(result)
+            .map_err(|e| McpError::Protocol(format!("invalid tools/call result: {e}")))
+    }
+
+    /// Send a request and return its `result`.
+    async fn call(&self, method: &str, params: Value) -> Result<Value, McpError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        let mut reply = tokio::time::timeout(self.timeout, self.transport.request(message))
+            .await
+            .map_err(|_| McpError::Timeout(self.timeout))??;
+        if let Some(error) = reply.get("error") {
+            return Err(McpError::Rpc {
+                code: error["code"].as_i64().unwrap_or_default(),
+                message: error["message"].as_str().unwrap_or_default().to_string(),
+            });
+        }
+        match reply.get_mut("result") {
+            Some(result) => Ok(result.take()),
+            None => Err(McpError::Protocol(format!(
+                "{method} response has neither result nor error"
+            ))),
+        }
+    }
+}
+
+/// The outcome of [`McpHub::import`].
+#[derive(Debug, Default)]
+pub struct McpImport {
+    /// Registered tool names per server that connected.
+    pub tools: Vec<(String, Vec<String>)>,
+    /// Servers that could not be imported, with the reason.
+    pub errors: Vec<(String, McpError)>,
+}
+
+/// Owns the MCP server connections and routes calls to imported tools.
+#[derive(Default)]
+pub struct McpHub {
+    /// Registered tool name → owning client and the tool's name on it.
+    routes: RwLock<HashMap<String, (Arc<McpClient>, String)>>,
+}
+
+impl McpHub {
+    /// Create a hub with no servers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Connect to the enabled servers in `config` and register their tools
+    /// in `registry`, replacing any tools imported earlier.
+    ///
+    /// A server that fails to connect or list its tools is reported in the
+    /// result and skipped. Tools whose registered name is taken by a
+    /// built-in tool, or is not a valid tool name, are skipped with a
+    /// warning.
+    pub async fn import(&self, config: &McpConfig, registry: &RwLock<ToolRegistry>) -> McpImport {
+        let mut import = McpImport::default();
+        let mut connected = Vec::new();
+        for server in config.servers.iter().filter(|s| s.enabled) {
+            let listed = async {
+                let client = McpClient::connect(server).await?;
+                let tools = client.list_tools().await?;
+                Ok::<_, McpError>((client, tools))
+            }
+            .await;
+            match listed {
+                Ok((client, tools)) => connected.push((server, Arc::new(client), tools)),
+                Err(e) => import.errors.push((server.name.clone(), e)),
+            }
+        }
+
+        let mut routes = self.routes.write().unwrap_or_else(|e| e.into_inner());
+        let mut registry = registry.write().unwrap_or_else(|e| e.into_inner());
+        for name in routes.keys() {
+            registry.unregister(name);
+        }
+        routes.clear();
+
+        for (server, client, tools) in connected {
+            let trust = ToolTrust::from_name(&server.trust).unwrap_or_default();
+            let tags = vec![
+                server.tag_prefix.clone(),
+                format!("{}:{}", server.tag_prefix, server.name),
+            ];
+            let mut names = Vec::new();
+            for tool in tools {
+                let name = format!("{}{TOOL_NAME_SEPARATOR}{}", server.name, tool.name);
+                if !is_valid_tool_name(&name) {
+                    tracing::warn!(server = %server.name, tool = %tool.name, "Skipping MCP tool with an unusable name");
+                    continue;
+                }
+                if registry.get(&name).is_some() {
+                    tracing::warn!(server = %server.name, tool = %name, "Skipping MCP tool that shadows a registered tool");
+                    continue;
+                }
+                let registered = registry.register(RegisteredTool {
+                    definition: ToolDefinition {
+                        name: name.clone(),
+                        description: tool.description,
+                        parameters: tool.input_schema,
+                    },
+                    trust,
+                    tags: tags.clone(),
+                    enabled: true,
+                });
+                if let Err(e) = registered {
+                    tracing::warn!(server = %server.name, tool = %name, error = %e, "Skipping MCP tool with an invalid input schema");
+                    continue;
+                }
+                routes.insert(name.clone(), (client.clone(), tool.name));
+                names.push(name);
+            }
+            names.sort();
+            tracing::info!(server = %server.name, count = names.len(), "Imported MCP tools");
+            import.tools.push((server.name.clone(), names));
+        }
+        import
+    }
+
+    /// Names of all imported tools, sorted.
+    pub fn tool_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .routes
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .keys()
+            .cloned()
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Call the imported tool registered as `tool`.
+    pub async fn call(&self, tool: &str, arguments: Value) -> Result<McpToolResult, McpError> {
+        let (client, remote) = self
+            .routes
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(tool)
+            .cloned()
+            .ok_or_else(|| McpError::UnknownTool(tool.to_string()))?;
+        client.call_tool(&remote, arguments).await
+    }
+}
+
+/// Whether `name` is accepted as a tool name by the LLM providers.
+fn is_valid_tool_name(name: &str) -> bool {
+    (1..=64).contains(&name.len())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A stdio MCP server in shell: answers initialize, a two-page
+    /// tools/list, and echoes tools/call arguments back as text.
+    fn fake_server(dir: &std::path::Path) -> Vec<String> {
+        let script = dir.join("server.sh");
+        std::fs::write(
+            &script,
+            r#"while IFS= read -r line; do
+  id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9][0-9]*\).*/\1/p')
+  case "$line" in
+    *'"method":"initialize"'*)
+      printf '{"jsonrpc":"2.0","method":"notifications/message","params":{}}\n'
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"protocolVersion":"2025-06-18","capabilities":{"tools":{}},"serverInfo":{"name":"fake","version":"1"}}}\n' "$id" ;;
+    *'"cursor":"p2"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"tools":[{"name":"read_file","description":"Reads remotely"},{"name":"bad.name"}]}}\n' "$id" ;;
+    *'"method":"tools/list"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"tools":[{"name":"echo","description":"Echoes","inputSchema":{"type":"object","properties":{"msg":{"type":"string"}}}}],"nextCursor":"p2"}}\n' "$id" ;;
+    *'"name":"fail"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"error":{"code":-32602,"message":"no such tool"}}\n' "$id" ;;
+    *'"method":"tools/call"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"content":[{"type":"text","text":"echo:%s"},{"type":"image","data":"","mimeType":"image/png"}]}}\n' "$id" "$FAKE_SUFFIX" ;;
+  esac
+done
+"#,
+        )
+        .unwrap();
+        vec!["sh".to_string(), script.display().to_string()]
+    }
+
+    fn server(name: &str, command: Vec<String>) -> McpServerConfig {
+        McpServerConfig {
+            name: name.to_string(),
+            command,
+            env: [("FAKE_SUFFIX".to_string(), "ok".to_string())].into(),
+            url: None,
+            trust: "trusted".to_string(),
+            tag_prefix: "ext".to_string(),
+            timeout_secs: 5,
+            enabled: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stdio_client() {
+        let dir = tempfile::tempdir().unwrap();
+        let client = McpClient::connect(&server("fake", fake_server(dir.path())))
+            .await
+            .unwrap();
+        let tools = client.list_tools().await.unwrap();
+        let names: Vec<&str> = tools.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["echo", "read_file", "bad.name"]);
+        assert_eq!(tools[0].input_schema["properties"]["msg"]["type"], "string");
+        assert_eq!(tools[1].input_schema, empty_schema());
+
+        let result = client
+            .call_tool("echo", json!({ "msg": "hi" }))
+            .await
+            .unwrap();
+        assert_eq!(result.text(), "echo:ok");
+        assert!(!result.is_error);
+
+        let err = client.call_tool("fail", json!({})).await.unwrap_err();
+        assert!(matches!(err, McpError::Rpc { code: -32602, .. }), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_import_registers_scoped_tools() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut broken = server("broken", vec!["/nonexistent/mcp-server".to_string()]);
+        broken.trust = "public".to_string();
+        let mut disabled = server("off", fake_server(dir.path()));
+        disabled.enabled = false;
+        let config = McpConfig {
+            servers: vec![server("fake", fake_server(dir.path())), broken, disabled],
+        };
+
+        let registry = RwLock::new(ToolRegistry::with_defaults());
+        let hub = McpHub::new();
+        let import = hub.import(&config, &registry).await;
+        assert_eq!(import.errors.len(), 1);
+        assert_eq!(import.errors[0].0, "broken");
+        assert_eq!(
+            import.tools,
+            vec![(
+                "fake".to_string(),
+                vec!["fake__echo".to_string(), "fake__read_file".to_string()]
+            )]
+        );
+
+        {
+            let registry = registry.read().unwrap();
+            let echo = registry.get("fake__echo").unwrap();
+            assert_eq!(echo.trust, ToolTrust::Trusted);
+            assert_eq!(echo.tags, vec!["ext", "ext:fake"]);
+            assert_eq!(echo.definition.description, "Echoes");
+
+            // Scoped like any other tool: hidden below its trust level.
+            let visible = |trust, tags: Option<&[&str]>| -> Vec<String> {
+                registry
+                    .scoped_definitions(trust, tags)
+                    .into_iter()
+                    .map(|d| d.name)
+                    .filter(|n| n.starts_with("fake__"))
+                    .collect()
+            };
+            assert!(visible(ToolTrust::Internal, None).is_empty());
+            assert_eq!(visible(ToolTrust::Trusted, Some(&["ext:fake"])).len(), 2);
+            assert!(visible(ToolTrust::Trusted, Some(&["code"])).is_empty());
+        }
+
+        let result = hub.call("fake__echo", json!({})).await.unwrap();
+        assert_eq!(result.text(), "echo:ok");
+        assert!(matches!(
+            hub.call("read_file", json!({})).await,
+            Err(McpError::UnknownTool(_))
+        ));
+
+        // Re-importing without the server removes its tools.
+        hub.import(&McpConfig::default(), &registry).await;
+        assert!(registry.read().unwrap().get("fake__echo").is_none());
+        assert!(registry.read().unwrap().get("read_file").is_some());
+        assert!(hub.tool_names().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_http_client() {
+        use axum::http::HeaderMap;
+        use axum::routing::post;
+
+        async fn handle(
+            headers: HeaderMap,
+            axum::Json(message): axum::Json<Value>,
+        ) -> axum::response::Response {
+            use axum::response::IntoResponse;
+
+            let id = message["id"].clone();
+            match message["method"].as_str().unwrap_or_default() {
+                "initialize" => (
+                    [("Mcp-Session-Id", "s-1")],
+                    axum::Json(json!({ "jsonrpc": "2.0", "id": id, "result": { "protocolVersion": PROTOCOL_VERSION } })),
+                )
+                    .into_response(),
+                "notifications/initialized" => axum::http::StatusCode::ACCEPTED.into_response(),
+                "tools/list" => {
+                    assert_eq!(headers["mcp-session-id"], "s-1");
+                    let body = format!(
+                        "event: message\ndata: {}\n\n",
+                        json!({ "jsonrpc": "2.0", "id": id, "result": { "tools": [{ "name": "search" }] } })
+                    );
+                    ([("Content-Type", "text/event-stream")], body).into_response()
+                }
+                _ => axum::http::StatusCode::BAD_REQUEST.into_response(),
+            }
+        }
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, axum::Router::new().route("/mcp", post(handle)))
+                .await
+                .unwrap();
+        });
+
+        let mut config = server("web", Vec::new());
+        config.url = Some(format!("http://{addr}/mcp"));
+        let client = McpClient::connect(&config).await.unwrap();
+        let tools = client.list_tools().await.unwrap();
+        assert_eq!(tools[0].name, "search");
+    }
+
+    #[test]
+    fn test_valid_tool_names() {
+        assert!(is_valid_tool_name("github__create_issue"));
+        assert!(!is_valid_tool_name("fake__bad.name"));
+        assert!(!is_valid_tool_name(&"x".repeat(65)));
+    }
+}
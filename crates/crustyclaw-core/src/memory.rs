@@ -0,0 +1,581 @@
+//! Long-term conversation memory — a rolling summary and key facts.
+//!
+//! [`MemoryStore`] keeps one JSON file per conversation under its directory.
+//! After each agent run, [`MemoryStore::update`] asks the LLM to fold the
+//! latest exchange into the conversation's summary and to report the facts
+//! worth keeping (names, preferences, identifiers, decisions) as key/value
+//! pairs — a small entity store. Facts past `max_facts` are forgotten least
+//! recently updated first. A failed update leaves the stored memory as it was.
+//!
+//! Before a run, [`MemoryStore::context`] packs the summary and facts into a
+//! [`ContextWindow`] of `max_tokens`, ranked against the new message by a
+//! [`RelevanceScorer`], and renders what fit for the system prompt.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+use crustyclaw_config::{MemoryConfig, RelevanceConfig};
+
+use crate::context::{ContextItem, ContextKind, ContextWindow, RelevanceScorer};
+use crate::llm::{ChatMessage, ChatRequest, LlmError, LlmProvider};
+use crate::workspace::sanitize_name;
+
+/// Source of the summary item in the context window.
+pub const SUMMARY_SOURCE: &str = "memory:summary";
+
+/// Errors from memory store operations.
+#[derive(Debug, thiserror::Error)]
+pub enum MemoryError {
+    #[error("invalid conversation ID {0:?}")]
+    InvalidId(String),
+
+    #[error("no memory stored for conversation {0}")]
+    NotFound(String),
+
+    #[error(transparent)]
+    Llm(#[from] LlmError),
+
+    #[error("memory I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// One remembered fact.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fact {
+    /// The fact's current value.
+    pub value: String,
+    /// When the value was last set, in unix seconds.
+    pub updated_secs: u64,
+}
+
+/// What is remembered about one conversation.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConversationMemory {
+    /// Rolling summary of the conversation so far.
+    #[serde(default)]
+    pub summary: String,
+    /// Key facts, by lowercase key.
+    #[serde(default)]
+    pub facts: BTreeMap<String, Fact>,
+    /// Runs folded into this memory.
+    #[serde(default)]
+    pub runs: u64,
+    /// When the memory last changed, in unix seconds.
+    #[serde(default)]
+    pub updated_secs: u64,
+}
+
+impl ConversationMemory {
+    /// Whether nothing is remembered yet.
+    pub fn is_empty(&self) -> bool {
+        self.summary.is_empty() && self.facts.is_empty()
+    }
+
+    /// The summary and each fact as context items.
+    pub fn items(&self) -> Vec<ContextItem> {
+        let mut items = Vec::with_capacity(self.facts.len() + 1);
+        if !self.summary.is_empty() {
+            let mut item = ContextWindow::item(
+                ContextKind::Memory,
+                self.summary.clone(),
+                1,
+                SUMMARY_SOURCE.to_string(),
+            );
+            item.modified_secs = Some(self.updated_secs);
+            items.push(item);
+        }
+        for (key, fact) in &self.facts {
+            let mut item = ContextWindow::item(
+                ContextKind::Memory,
+                format!("{key}: {}", fact.value),
+                0,
+                format!("memory:{key}"),
+            );
+            item.modified_secs = Some(fact.updated_secs);
+            items.push(item);
+        }
+        items
+    }
+}
+
+/// The changes the LLM reports after a run.
+#[derive(Debug, Deserialize)]
+struct MemoryUpdate {
+    #[serde(default)]
+    summary: Option<String>,
+    /// New or changed facts; `null` forgets one.
+    #[serde(default)]
+    facts: BTreeMap<String, Option<String>>,
+}
+
+/// Per-conversation memory files.
+#[derive(Debug)]
+pub struct MemoryStore {
+    dir: PathBuf,
+    max_tokens: u32,
+    max_facts: usize,
+    summary_tokens: u32,
+    relevance: RelevanceConfig,
+    /// Serializes read-modify-write updates within the daemon.
+    write_lock: Mutex<()>,
+}
+
+impl MemoryStore {
+    /// Create a store keeping memory in `dir`, with the `[memory]` defaults.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        let defaults = MemoryConfig::default();
+        Self {
+            dir: dir.into(),
+            max_tokens: defaults.max_tokens,
+            max_facts: defaults.max_facts,
+            summary_tokens: defaults.summary_tokens,
+            relevance: RelevanceConfig::default(),
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    /// Create a store from the `[memory]` config section.
+    pub fn from_config(config: &MemoryConfig) -> Self {
+        Self::new(&config.dir)
+            .with_max_tokens(config.max_tokens)
+            .with_max_facts(config.max_facts)
+            .with_summary_tokens(config.summary_tokens)
+    }
+
+    /// Builder: inject at most `max_tokens` of memory per run.
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    /// Builder: keep at most `max_facts` facts per conversation.
+    pub fn with_max_facts(mut self, max_facts: usize) -> Self {
+        self.max_facts = max_facts;
+        self
+    }
+
+    /// Builder: ask for summaries of at most `summary_tokens`.
+    pub fn with_summary_tokens(mut self, summary_tokens: u32) -> Self {
+        self.summary_tokens = summary_tokens;
+        self
+    }
+
+    /// Builder: rank memory items with the `[context.relevance]` weights.
+    pub fn with_relevance(mut self, config: &RelevanceConfig) -> Self {
+        self.relevance = config.clone();
+        self
+    }
+
+    /// Directory holding the memory files.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The memory of conversation `id`, or `None` if nothing is stored.
+    pub fn load(&self, id: &str) -> Result<Option<ConversationMemory>, MemoryError> {
+        let data = match std::fs::read(self.path(id)?) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        serde_json::from_slice(&data)
+            .map(Some)
+            .map_err(|e| MemoryError::Io(std::io::Error::other(e)))
+    }
+
+    /// Forget conversation `id`. Returns whether anything was stored.
+    pub fn clear(&self, id: &str) -> Result<bool, MemoryError> {
+        let path = self.path(id)?;
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        match std::fs::remove_file(&path) {
+            Ok(()) => {
+                info!(conversation = id, "Conversation memory cleared");
+                Ok(true)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// The memory of conversation `id` most relevant to `query`, rendered
+    /// for a system prompt within the token budget. `None` when nothing is
+    /// stored or nothing fits.
+    pub fn context(&self, id: &str, query: &str) -> Result<Option<String>, MemoryError> {
+        let Some(memory) = self.load(id)? else {
+            return Ok(None);
+        };
+        let mut window = ContextWindow::new(self.max_tokens, 0);
+        window.pack_ranked(
+            memory.items(),
+            &RelevanceScorer::new(&self.relevance),
+            query,
+        );
+        if window.item_count() == 0 {
+            return Ok(None);
+        }
+        let mut summary = None;
+        let mut facts = Vec::new();
+        for item in window.items() {
+            if item.source == SUMMARY_SOURCE {
+                summary = Some(item.content.as_str());
+            } else {
+                facts.push(format!("- {}", item.content));
+            }
+        }
+        let mut out = "Memory of earlier runs in this conversation:".to_string();
+        if let Some(summary) = summary {
+            out.push_str(&format!("\n\nSummary: {summary}"));
+        }
+        if !facts.is_empty() {
+            out.push_str(&format!("\n\nKnown facts:\n{}", facts.join("\n")));
+        }
+        debug!(
+            conversation = id,
+            items = window.item_count(),
+            tokens = window.used(),
+            "Conversation memory injected"
+        );
+        Ok(Some(out))
+    }
+
+    /// Fold the latest exchange in `messages` into conversation `id`'s
+    /// memory, asking `model` on `provider` for the new summary and facts.
+    pub async fn update(
+        &self,
+        id: &str,
+        provider: &dyn LlmProvider,
+        model: &str,
+        messages: &[ChatMessage],
+    ) -> Result<ConversationMemory, MemoryError> {
+        let current = self.load(id)?.unwrap_or_default();
+        let request = ChatRequest {
+            model: model.to_string(),
+            messages: vec![ChatMessage::user(update_prompt(&current, messages))],
+            max_tokens: self.summary_tokens.saturating_mul(2),
+            system: Some(format!(
+                "You maintain the long-term memory of an assistant's conversation. \
+                 Given the current memory and the latest exchange, reply with one JSON \
+                 object only: {{\"summary\": string, \"facts\": {{key: string or null}}}}. \
+                 The summary replaces the current one; keep it under {} tokens, covering \
+                 goals, decisions, and open questions. In facts, report only new or \
+                 changed facts worth remembering (names, preferences, identifiers, \
+                 decisions) under short lowercase keys, and null for facts that no \
+                 longer hold.",
+                self.summary_tokens
+            )),
+            ..ChatRequest::default()
+        };
+        let response = provider.chat(&request).await?;
+        let update = parse_update(&response.message.content.unwrap_or_default())?;
+
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        // Reload in case another run finished while the model was answering.
+        let mut memory = self.load(id)?.unwrap_or_default();
+        let now = now_secs();
+        if let Some(summary) = update.summary.map(|s| s.trim().to_string())
+            && !summary.is_empty()
+        {
+            memory.summary = summary;
+        }
+        for (key, value) in update.facts {
+            let key = key.trim().to_lowercase();
+            if key.is_empty() {
+                continue;
+            }
+            match value.map(|v| v.trim().to_string()) {
+                Some(value) if !value.is_empty() => {
+                    memory.facts.insert(
+                        key,
+                        Fact {
+                            value,
+                            updated_secs: now,
+                        },
+                    );
+                }
+                _ => {
+                    memory.facts.remove(&key);
+                }
+            }
+        }
+        while memory.facts.len() > self.max_facts {
+            let Some(oldest) = memory
+                .facts
+                .iter()
+                .min_by_key(|(_, fact)| fact.updated_secs)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            memory.facts.remove(&oldest);
+        }
+        memory.runs += 1;
+        memory.updated_secs = now;
+        self.save(id, &memory)?;
+        debug!(
+            conversation = id,
+            facts = memory.facts.len(),
+            "Conversation memory updated"
+        );
+        Ok(memory)
+    }
+
+    /// Write `memory` through a temporary file, readable only by the daemon.
+    fn save(&self, id: &str, memory: &ConversationMemory) -> Result<(), MemoryError> {
+        let path = self.path(id)?;
+        std::fs::create_dir_all(&self.dir)?;
+        let tmp = path.with_extension("json.partial");
+        let mut options = std::fs::OpenOptions::new();
+        options.create(true).write(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&tmp)?;
+        file.write_all(&serde_json::to_vec_pretty(memory).map_err(std::io::Error::other)?)?;
+        drop(file);
+        std::fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    fn path(&self, id: &str) -> Result<PathBuf, MemoryError> {
+        if id.is_empty() || sanitize_name(id) != id {
+            return Err(MemoryError::InvalidId(id.to_string()));
+        }
+        Ok(self.dir.join(format!("{id}.json")))
+    }
+}
+
+/// The update request: the current memory, then the last user message and
+/// the final answer.
+fn update_prompt(memory: &ConversationMemory, messages: &[ChatMessage]) -> String {
+    let last = |role: &str| {
+        messages
+            .iter()
+            .rev()
+            .find(|m| m.role == role && m.content.as_deref().is_some_and(|c| !c.is_empty()))
+            .and_then(|m| m.content.as_deref())
+            .unwrap_or("")
+    };
+    let summary = if memory.summary.is_empty() {
+        "(none)"
+    } else {
+        memory.summary.as_str()
+    };
+    let facts = if memory.facts.is_empty() {
+        "(none)".to_string()
+    } else {
+        memory
+            .facts
+            .iter()
+            .map(|(key, fact)| format!("- {key}: {}", fact.value))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    format!(
+        "Current summary:\n{summary}\n\nKnown facts:\n{facts}\n\nLatest exchange:\nUser: {}\nAssistant: {}",
+        last("user"),
+        last("assistant")
+    )
+}
+
+/// Parse the model's reply, ignoring any text around the JSON object.
+fn parse_update(text: &str) -> Result<MemoryUpdate, LlmError> {
+    let json = match (text.find('{'), text.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &text[start..=end],
+        _ => {
+            return Err(LlmError::Parse(
+                "no JSON object in memory update".to_string(),
+            ));
+        }
+    };
+    serde_json::from_str(json).map_err(|e| LlmError::Parse(format!("memory update: {e}")))
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::BoxFuture;
+    use crate::llm::{ChatResponse, StreamChunk, TokenUsage};
+    use tokio::sync::mpsc;
+
+    /// Answers every request with `reply`, recording the prompts.
+    struct Memorizer {
+        reply: &'static str,
+        prompts: Mutex<Vec<String>>,
+    }
+
+    impl Memorizer {
+        fn new(reply: &'static str) -> Self {
+            Self {
+                reply,
+                prompts: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    impl LlmProvider for Memorizer {
+        fn name(&self) -> &str {
+            "memorizer"
+        }
+
+        fn chat(&self, request: &ChatRequest) -> BoxFuture<'_, Result<ChatResponse, LlmError>> {
+            self.prompts
+                .lock()
+                .unwrap()
+                .push(request.messages[0].content.clone().unwrap_or_default());
+            Box::pin(async move {
+                Ok(ChatResponse {
+                    message: ChatMessage::assistant(self.reply),
+                    finish_reason: "stop".to_string(),
+                    usage: TokenUsage::default(),
+                    model: "test".to_string(),
+                    attempts: 1,
+                })
+            })
+        }
+
+        fn chat_stream(
+            &self,
+            _request: &ChatRequest,
+        ) -> BoxFuture<'_, Result<mpsc::Receiver<Result<StreamChunk, LlmError>>, LlmError>>
+        {
+            Box::pin(async { Err(LlmError::Request("not streamed".to_string())) })
+        }
+    }
+
+    fn exchange(user: &str, assistant: &str) -> Vec<ChatMessage> {
+        vec![ChatMessage::user(user), ChatMessage::assistant(assistant)]
+    }
+
+    #[tokio::test]
+    async fn test_update_persists_summary_and_facts() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = MemoryStore::new(dir.path());
+        assert_eq!(store.load("chat-1").unwrap(), None);
+
+        let provider = Memorizer::new(
+            "Sure:\n{\"summary\": \"Planning the Lisbon trip.\", \
+             \"facts\": {\"Name\": \"Ada\", \"city\": \"Lisbon\"}}",
+        );
+        let memory = store
+            .update(
+                "chat-1",
+                &provider,
+                "test",
+                &exchange("I'm Ada, going to Lisbon", "Noted!"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(memory.summary, "Planning the Lisbon trip.");
+        assert_eq!(memory.facts["name"].value, "Ada");
+        assert_eq!(memory.runs, 1);
+        assert_eq!(store.load("chat-1").unwrap(), Some(memory));
+        assert!(provider.prompts.lock().unwrap()[0].contains("User: I'm Ada, going to Lisbon"));
+
+        // The next update sees the stored memory; null forgets a fact.
+        let provider = Memorizer::new("{\"facts\": {\"city\": null, \"airline\": \"TAP\"}}");
+        let memory = store
+            .update(
+                "chat-1",
+                &provider,
+                "test",
+                &exchange("Actually, Porto", "OK"),
+            )
+            .await
+            .unwrap();
+        assert!(provider.prompts.lock().unwrap()[0].contains("- city: Lisbon"));
+        assert_eq!(memory.summary, "Planning the Lisbon trip.");
+        assert!(!memory.facts.contains_key("city"));
+        assert_eq!(memory.facts["airline"].value, "TAP");
+        assert_eq!(memory.runs, 2);
+
+        // A reply without JSON leaves the memory alone.
+        let provider = Memorizer::new("I could not summarize that.");
+        assert!(
+            store
+                .update("chat-1", &provider, "test", &exchange("hi", "hello"))
+                .await
+                .is_err()
+        );
+        assert_eq!(store.load("chat-1").unwrap().unwrap().runs, 2);
+    }
+
+    #[tokio::test]
+    async fn test_facts_capped() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = MemoryStore::new(dir.path()).with_max_facts(2);
+        let provider = Memorizer::new("{\"facts\": {\"a\": \"1\", \"b\": \"2\", \"c\": \"3\"}}");
+        let memory = store
+            .update("chat-1", &provider, "test", &exchange("x", "y"))
+            .await
+            .unwrap();
+        assert_eq!(memory.facts.len(), 2);
+    }
+
+    #[test]
+    fn test_context_within_budget() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = MemoryStore::new(dir.path());
+        assert_eq!(store.context("chat-1", "anything").unwrap(), None);
+
+        let now = now_secs();
+        let fact = |value: &str| Fact {
+            value: value.to_string(),
+            updated_secs: now,
+        };
+        let memory = ConversationMemory {
+            summary: "Debugging the deploy pipeline.".to_string(),
+            facts: [
+                ("cluster".to_string(), fact("prod-eu-1")),
+                ("favourite editor".to_string(), fact("helix")),
+            ]
+            .into(),
+            runs: 3,
+            updated_secs: now,
+        };
+        store.save("chat-1", &memory).unwrap();
+
+        let context = store.context("chat-1", "which cluster?").unwrap().unwrap();
+        assert!(context.contains("Summary: Debugging the deploy pipeline."));
+        assert!(context.contains("- cluster: prod-eu-1"));
+        assert!(context.contains("- favourite editor: helix"));
+
+        // Only the item most relevant to the query fits a small budget.
+        let store = MemoryStore::new(dir.path()).with_max_tokens(5);
+        let context = store.context("chat-1", "which cluster?").unwrap().unwrap();
+        assert!(context.contains("- cluster: prod-eu-1"));
+        assert!(!context.contains("helix"));
+        assert!(!context.contains("Summary"));
+    }
+
+    #[test]
+    fn test_clear() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = MemoryStore::new(dir.path());
+        store
+            .save("chat-1", &ConversationMemory::default())
+            .unwrap();
+        assert!(store.clear("chat-1").unwrap());
+        assert!(!store.clear("chat-1").unwrap());
+        assert_eq!(store.load("chat-1").unwrap(), None);
+        assert!(matches!(
+            store.clear("../escape"),
+            Err(MemoryError::InvalidId(_))
+        ));
+    }
+}
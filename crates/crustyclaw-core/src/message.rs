@@ -2,6 +2,9 @@
 
 use std::time::SystemTime;
 
+use crate::attachment::Attachment;
+use crate::llm::{ChatMessage, ImageSource};
+
 /// A message envelope routed through the daemon's message bus.
 #[derive(Debug, Clone)]
 pub struct Envelope {
@@ -19,6 +22,21 @@ pub struct Envelope {
 
     /// Direction of the message.
     pub direction: Direction,
+
+    /// The remote party on the channel: the sender of an inbound message or
+    /// the recipient of an outbound one (e.g. a Signal phone number).
+    pub peer: Option<String>,
+
+    /// For a redaction, the ID of the earlier envelope whose content must be
+    /// removed from transcripts (e.g. after a Signal remote delete). The body
+    /// of a redaction is empty.
+    pub redacts: Option<u64>,
+
+    /// Images attached to an inbound message, for a vision model.
+    pub images: Vec<ImageSource>,
+
+    /// Files attached to an inbound message, held in the attachment spool.
+    pub attachments: Vec<Attachment>,
 }
 
 /// Whether a message is inbound (from user) or outbound (to user).
@@ -42,10 +60,51 @@ impl Envelope {
             channel: channel.to_string(),
             body: body.to_string(),
             direction: Direction::Inbound,
+            peer: None,
+            redacts: None,
+            images: Vec::new(),
+            attachments: Vec::new(),
+        }
+    }
+
+    /// Set the remote party for this message.
+    pub fn with_peer(mut self, peer: &str) -> Self {
+        self.peer = Some(peer.to_string());
+        self
+    }
+
+    /// Attach `images` to this message.
+    pub fn with_images(mut self, images: Vec<ImageSource>) -> Self {
+        self.images = images;
+        self
+    }
+
+    /// Attach spooled `attachments` to this message.
+    pub fn with_attachments(mut self, attachments: Vec<Attachment>) -> Self {
+        self.attachments = attachments;
+        self
+    }
+
+    /// The message as a user turn for the agent, with its images and a
+    /// list of its attachments as tools will see them.
+    pub fn chat_message(&self) -> ChatMessage {
+        let mut content = self.body.clone();
+        if !self.attachments.is_empty() {
+            content.push_str("\n\nAttached files (read-only):");
+            for attachment in &self.attachments {
+                content.push_str(&format!(
+                    "\n- {} ({}, {} bytes)",
+                    attachment.guest_path().display(),
+                    attachment.content_type,
+                    attachment.size
+                ));
+            }
         }
+        ChatMessage::user(&content).with_images(self.images.clone())
     }
 
-    /// Create an outbound response envelope for this message.
+    /// Create an outbound response envelope for this message, addressed to
+    /// the same peer.
     pub fn reply(&self, body: &str) -> Self {
         use std::sync::atomic::{AtomicU64, Ordering};
         static NEXT_ID: AtomicU64 = AtomicU64::new(1);
@@ -56,8 +115,25 @@ impl Envelope {
             channel: self.channel.clone(),
             body: body.to_string(),
             direction: Direction::Outbound,
+            peer: self.peer.clone(),
+            redacts: None,
+            images: Vec::new(),
+            attachments: Vec::new(),
+        }
+    }
+
+    /// Create an envelope asking consumers to redact the envelope `target`.
+    pub fn redaction(channel: &str, target: u64) -> Self {
+        Self {
+            redacts: Some(target),
+            ..Self::new(channel, "")
         }
     }
+
+    /// Whether this envelope redacts an earlier one rather than carrying a message.
+    pub fn is_redaction(&self) -> bool {
+        self.redacts.is_some()
+    }
 }
 
 #[cfg(test)]
@@ -83,6 +159,61 @@ mod tests {
         assert_ne!(reply.id, original.id);
     }
 
+    #[test]
+    fn test_chat_message_carries_images() {
+        let image = ImageSource::from_bytes("image/png", b"foo");
+        let envelope = Envelope::new("signal", "what is this?").with_images(vec![image.clone()]);
+        let message = envelope.chat_message();
+        assert_eq!(message.role, "user");
+        assert_eq!(message.content.as_deref(), Some("what is this?"));
+        assert_eq!(message.images, [image]);
+        assert!(envelope.reply("a cat").images.is_empty());
+    }
+
+    #[test]
+    fn test_chat_message_lists_attachments() {
+        let attachment = Attachment {
+            name: "notes.txt".to_string(),
+            content_type: "text/plain".to_string(),
+            size: 13,
+            path: "/spool/m1/notes.txt".into(),
+        };
+        let envelope = Envelope::new("signal", "summarize this").with_attachments(vec![attachment]);
+        assert_eq!(
+            envelope.chat_message().content.as_deref(),
+            Some(
+                "summarize this\n\nAttached files (read-only):\n- /attachments/notes.txt (text/plain, 13 bytes)"
+            )
+        );
+        assert!(envelope.reply("done").attachments.is_empty());
+        assert_eq!(
+            Envelope::new("signal", "hi")
+                .chat_message()
+                .content
+                .as_deref(),
+            Some("hi")
+        );
+    }
+
+    #[test]
+    fn test_reply_keeps_peer() {
+        let original = Envelope::new("signal", "Hello").with_peer("+15550001");
+        assert_eq!(original.peer.as_deref(), Some("+15550001"));
+        assert_eq!(original.reply("Hi").peer.as_deref(), Some("+15550001"));
+        assert!(Envelope::new("cli", "x").peer.is_none());
+    }
+
+    #[test]
+    fn test_redaction() {
+        let original = Envelope::new("signal", "oops").with_peer("+15550001");
+        let redaction = Envelope::redaction("signal", original.id).with_peer("+15550001");
+        assert!(redaction.is_redaction());
+        assert_eq!(redaction.redacts, Some(original.id));
+        assert!(redaction.body.is_empty());
+        assert!(!original.is_redaction());
+        assert!(original.reply("ok").redacts.is_none());
+    }
+
     #[test]
     fn test_unique_ids() {
         let a = Envelope::new("a", "x");
@@ -0,0 +1,609 @@
+//! Prometheus metrics — counters and latency histograms for the daemon.
+//!
+//! [`Metrics`] is shared by the components that do the work: the message bus
+//! recorder ([`spawn`]) counts routed messages, the [`SkillRegistry`] counts
+//! sandbox executions, the agent counts LLM tokens, the
+//! [`FailoverProvider`] counts which provider served each request, the
+//! [`Compactor`] counts context compactions, and the command router counts
+//! denials. [`Metrics::render`] produces the Prometheus text
+//! exposition format served at `GET /metrics` on the IPC socket and, when
+//! `daemon.metrics_addr` is set, on a TCP listener ([`serve`]).
+//!
+//! Everything is kept in memory and starts from zero when the daemon starts.
+//!
+//! [`SkillRegistry`]: crate::skill::SkillRegistry
+//! [`FailoverProvider`]: crate::llm::FailoverProvider
+//! [`Compactor`]: crate::context::Compactor
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::daemon::ShutdownSignal;
+use crate::llm::TokenUsage;
+use crate::message::{Direction, Envelope};
+
+/// Content type of the Prometheus text exposition format.
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Upper bounds, in seconds, of the latency histogram buckets.
+pub const LATENCY_BUCKETS: [f64; 12] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+/// Why a request was refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Denial {
+    /// The sender's role does not allow the command or remote IPC request.
+    Role,
+    /// The sender's role is over its quota.
+    Quota,
+    /// A tool call needed more trust than the caller has.
+    ToolTrust,
+    /// An IPC request had a missing, invalid, or expired session token.
+    Token,
+    /// A webhook channel message had a missing or invalid signature.
+    Webhook,
+}
+
+impl Denial {
+    const ALL: [Denial; 5] = [
+        Self::Role,
+        Self::Quota,
+        Self::ToolTrust,
+        Self::Token,
+        Self::Webhook,
+    ];
+
+    /// The `reason` label value.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Role => "role",
+            Self::Quota => "quota",
+            Self::ToolTrust => "tool_trust",
+            Self::Token => "token",
+            Self::Webhook => "webhook",
+        }
+    }
+}
+
+/// A cumulative latency histogram over [`LATENCY_BUCKETS`].
+#[derive(Debug, Default)]
+struct Histogram {
+    /// Observations per bucket, plus one for `+Inf`; not cumulative.
+    buckets: [AtomicU64; LATENCY_BUCKETS.len() + 1],
+    sum_micros: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Histogram {
+    fn observe(&self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|le| secs <= *le)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        let micros = u64::try_from(elapsed.as_micros()).unwrap_or(u64::MAX);
+        self.sum_micros.fetch_add(micros, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn render(&self, out: &mut String, name: &str) {
+        let mut cumulative = 0;
+        for (le, bucket) in LATENCY_BUCKETS.iter().zip(&self.buckets) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let _ = writeln!(out, "{name}_bucket{{le=\"{le}\"}} {cumulative}");
+        }
+        cumulative += self.buckets[LATENCY_BUCKETS.len()].load(Ordering::Relaxed);
+        let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {cumulative}");
+        let sum = self.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        let _ = writeln!(out, "{name}_sum {sum}");
+        let _ = writeln!(out, "{name}_count {}", self.count.load(Ordering::Relaxed));
+    }
+}
+
+/// Requests and tokens served by one LLM provider.
+#[derive(Debug, Default)]
+struct ProviderUsage {
+    requests: u64,
+    prompt_tokens: u64,
+    completion_tokens: u64,
+}
+
+/// Daemon-wide counters and histograms.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// (channel, direction) → messages seen on the bus.
+    messages: Mutex<BTreeMap<(String, &'static str), u64>>,
+    sandbox_succeeded: AtomicU64,
+    sandbox_failed: AtomicU64,
+    sandbox_latency: Histogram,
+    sandbox_cpu_micros: AtomicU64,
+    llm_requests: AtomicU64,
+    llm_prompt_tokens: AtomicU64,
+    llm_completion_tokens: AtomicU64,
+    llm_latency: Histogram,
+    /// Provider label → requests and tokens it served.
+    llm_providers: Mutex<BTreeMap<String, ProviderUsage>>,
+    llm_retries: AtomicU64,
+    llm_unserved: AtomicU64,
+    compactions_succeeded: AtomicU64,
+    compactions_failed: AtomicU64,
+    compacted_items: AtomicU64,
+    compaction_saved_tokens: AtomicU64,
+    denials: [AtomicU64; Denial::ALL.len()],
+}
+
+impl Metrics {
+    /// Create an empty set of metrics.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a message routed on the bus.
+    pub fn record_message(&self, envelope: &Envelope) {
+        let direction = match envelope.direction {
+            Direction::Inbound => "inbound",
+            Direction::Outbound => "outbound",
+        };
+        let mut messages = self.messages.lock().unwrap_or_else(|e| e.into_inner());
+        *messages
+            .entry((envelope.channel.clone(), direction))
+            .or_default() += 1;
+    }
+
+    /// Count a sandboxed skill execution and its duration.
+    pub fn record_sandbox_execution(&self, success: bool, elapsed: Duration) {
+        let counter = if success {
+            &self.sandbox_succeeded
+        } else {
+            &self.sandbox_failed
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        self.sandbox_latency.observe(elapsed);
+    }
+
+    /// Add the CPU time a sandboxed skill execution used.
+    pub fn record_sandbox_cpu(&self, cpu_time: Duration) {
+        self.sandbox_cpu_micros
+            .fetch_add(cpu_time.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Count an LLM request, its tokens, and its duration.
+    pub fn record_llm_request(&self, usage: &TokenUsage, elapsed: Duration) {
+        self.llm_requests.fetch_add(1, Ordering::Relaxed);
+        self.llm_prompt_tokens
+            .fetch_add(u64::from(usage.prompt_tokens), Ordering::Relaxed);
+        self.llm_completion_tokens
+            .fetch_add(u64::from(usage.completion_tokens), Ordering::Relaxed);
+        self.llm_latency.observe(elapsed);
+    }
+
+    /// Count which provider served an LLM request (`None` if none could),
+    /// the retries it took, and the tokens it used.
+    pub fn record_llm_attempts(&self, provider: Option<&str>, attempts: u32, usage: &TokenUsage) {
+        self.llm_retries
+            .fetch_add(u64::from(attempts.saturating_sub(1)), Ordering::Relaxed);
+        let Some(provider) = provider else {
+            self.llm_unserved.fetch_add(1, Ordering::Relaxed);
+            return;
+        };
+        let mut providers = self.llm_providers.lock().unwrap_or_else(|e| e.into_inner());
+        let served = providers.entry(provider.to_string()).or_default();
+        served.requests += 1;
+        served.prompt_tokens += u64::from(usage.prompt_tokens);
+        served.completion_tokens += u64::from(usage.completion_tokens);
+    }
+
+    /// Count a context compaction that summarized `items` items, saving
+    /// `saved_tokens`.
+    pub fn record_compaction(&self, items: usize, saved_tokens: u32) {
+        self.compactions_succeeded.fetch_add(1, Ordering::Relaxed);
+        self.compacted_items
+            .fetch_add(items as u64, Ordering::Relaxed);
+        self.compaction_saved_tokens
+            .fetch_add(u64::from(saved_tokens), Ordering::Relaxed);
+    }
+
+    /// Count a context compaction whose summary request failed.
+    pub fn record_compaction_failure(&self) {
+        self.compactions_failed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a refused request.
+    pub fn record_denial(&self, reason: Denial) {
+        self.denials[reason as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Render all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "crustyclaw_messages_total",
+            "counter",
+            "Messages routed on the message bus.",
+        );
+        for ((channel, direction), count) in self
+            .messages
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+        {
+            let _ = writeln!(
+                out,
+                "crustyclaw_messages_total{{channel=\"{}\",direction=\"{direction}\"}} {count}",
+                escape(channel)
+            );
+        }
+
+        header(
+            &mut out,
+            "crustyclaw_sandbox_executions_total",
+            "counter",
+            "Skill executions, by outcome.",
+        );
+        for (outcome, counter) in [
+            ("success", &self.sandbox_succeeded),
+            ("failure", &self.sandbox_failed),
+        ] {
+            let _ = writeln!(
+                out,
+                "crustyclaw_sandbox_executions_total{{outcome=\"{outcome}\"}} {}",
+                counter.load(Ordering::Relaxed)
+            );
+        }
+        header(
+            &mut out,
+            "crustyclaw_sandbox_execution_duration_seconds",
+            "histogram",
+            "Skill execution latency.",
+        );
+        self.sandbox_latency
+            .render(&mut out, "crustyclaw_sandbox_execution_duration_seconds");
+        header(
+            &mut out,
+            "crustyclaw_sandbox_cpu_seconds_total",
+            "counter",
+            "CPU time used by skill executions, where the backend measures it.",
+        );
+        let _ = writeln!(
+            out,
+            "crustyclaw_sandbox_cpu_seconds_total {}",
+            self.sandbox_cpu_micros.load(Ordering::Relaxed) as f64 / 1e6
+        );
+
+        header(
+            &mut out,
+            "crustyclaw_llm_requests_total",
+            "counter",
+            "LLM chat requests.",
+        );
+        let _ = writeln!(
+            out,
+            "crustyclaw_llm_requests_total {}",
+            self.llm_requests.load(Ordering::Relaxed)
+        );
+        header(
+            &mut out,
+            "crustyclaw_llm_tokens_total",
+            "counter",
+            "LLM tokens used, by kind.",
+        );
+        for (kind, counter) in [
+            ("prompt", &self.llm_prompt_tokens),
+            ("completion", &self.llm_completion_tokens),
+        ] {
+            let _ = writeln!(
+                out,
+                "crustyclaw_llm_tokens_total{{kind=\"{kind}\"}} {}",
+                counter.load(Ordering::Relaxed)
+            );
+        }
+        header(
+            &mut out,
+            "crustyclaw_llm_request_duration_seconds",
+            "histogram",
+            "LLM chat request latency.",
+        );
+        self.llm_latency
+            .render(&mut out, "crustyclaw_llm_request_duration_seconds");
+
+        let providers = self.llm_providers.lock().unwrap_or_else(|e| e.into_inner());
+        header(
+            &mut out,
+            "crustyclaw_llm_provider_requests_total",
+            "counter",
+            "LLM requests, by the provider that served them.",
+        );
+        for (provider, served) in providers.iter() {
+            let _ = writeln!(
+                out,
+                "crustyclaw_llm_provider_requests_total{{provider=\"{}\"}} {}",
+                escape(provider),
+                served.requests
+            );
+        }
+        header(
+            &mut out,
+            "crustyclaw_llm_provider_tokens_total",
+            "counter",
+            "LLM tokens used, by provider and kind.",
+        );
+        for (provider, served) in providers.iter() {
+            for (kind, count) in [
+                ("prompt", served.prompt_tokens),
+                ("completion", served.completion_tokens),
+            ] {
+                let _ = writeln!(
+                    out,
+                    "crustyclaw_llm_provider_tokens_total{{provider=\"{}\",kind=\"{kind}\"}} {count}",
+                    escape(provider)
+                );
+            }
+        }
+        drop(providers);
+        header(
+            &mut out,
+            "crustyclaw_llm_retries_total",
+            "counter",
+            "LLM attempts repeated on a retryable error, here or on a fallback.",
+        );
+        let _ = writeln!(
+            out,
+            "crustyclaw_llm_retries_total {}",
+            self.llm_retries.load(Ordering::Relaxed)
+        );
+        header(
+            &mut out,
+            "crustyclaw_llm_unserved_requests_total",
+            "counter",
+            "LLM requests that no provider served.",
+        );
+        let _ = writeln!(
+            out,
+            "crustyclaw_llm_unserved_requests_total {}",
+            self.llm_unserved.load(Ordering::Relaxed)
+        );
+
+        header(
+            &mut out,
+            "crustyclaw_context_compactions_total",
+            "counter",
+            "Context compactions, by outcome.",
+        );
+        for (outcome, counter) in [
+            ("success", &self.compactions_succeeded),
+            ("failure", &self.compactions_failed),
+        ] {
+            let _ = writeln!(
+                out,
+                "crustyclaw_context_compactions_total{{outcome=\"{outcome}\"}} {}",
+                counter.load(Ordering::Relaxed)
+            );
+        }
+        header(
+            &mut out,
+            "crustyclaw_context_compacted_items_total",
+            "counter",
+            "Context items replaced by summaries.",
+        );
+        let _ = writeln!(
+            out,
+            "crustyclaw_context_compacted_items_total {}",
+            self.compacted_items.load(Ordering::Relaxed)
+        );
+        header(
+            &mut out,
+            "crustyclaw_context_compaction_saved_tokens_total",
+            "counter",
+            "Context tokens saved by replacing items with summaries.",
+        );
+        let _ = writeln!(
+            out,
+            "crustyclaw_context_compaction_saved_tokens_total {}",
+            self.compaction_saved_tokens.load(Ordering::Relaxed)
+        );
+
+        header(
+            &mut out,
+            "crustyclaw_policy_denials_total",
+            "counter",
+            "Requests refused by role, quota, or tool trust.",
+        );
+        for reason in Denial::ALL {
+            let _ = writeln!(
+                out,
+                "crustyclaw_policy_denials_total{{reason=\"{}\"}} {}",
+                reason.name(),
+                self.denials[reason as usize].load(Ordering::Relaxed)
+            );
+        }
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+/// Escape a label value.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Spawn the bus recorder: every message on `bus` is counted until shutdown.
+pub fn spawn(
+    metrics: Arc<Metrics>,
+    bus: broadcast::Sender<Envelope>,
+    mut shutdown_rx: broadcast::Receiver<ShutdownSignal>,
+) -> JoinHandle<()> {
+    let mut messages = bus.subscribe();
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.recv() => break,
+                msg = messages.recv() => match msg {
+                    Ok(envelope) => metrics.record_message(&envelope),
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!(skipped = n, "Metrics recorder fell behind the message bus");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            }
+        }
+    })
+}
+
+/// Render `metrics` for the TCP listener.
+async fn handle_metrics(State(metrics): State<Arc<Metrics>>) -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, CONTENT_TYPE)], metrics.render())
+}
+
+/// Serve `GET /metrics` on a TCP listener at `addr` until shutdown.
+pub async fn serve(
+    addr: SocketAddr,
+    metrics: Arc<Metrics>,
+    mut shutdown_rx: broadcast::Receiver<ShutdownSignal>,
+) -> Result<(), std::io::Error> {
+    let listener = TcpListener::bind(addr).await?;
+    info!(addr = %listener.local_addr()?, "Metrics listener started");
+    let app = axum::Router::new()
+        .route("/metrics", get(handle_metrics))
+        .with_state(metrics);
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async move {
+            let _ = shutdown_rx.recv().await;
+        })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::new();
+        let question = Envelope::new("signal", "hi");
+        metrics.record_message(&question);
+        metrics.record_message(&question);
+        metrics.record_message(&question.reply("hello"));
+        metrics.record_sandbox_execution(true, Duration::from_millis(40));
+        metrics.record_sandbox_execution(false, Duration::from_secs(60));
+        metrics.record_sandbox_cpu(Duration::from_millis(1500));
+        metrics.record_llm_request(
+            &TokenUsage {
+                prompt_tokens: 80,
+                completion_tokens: 20,
+                total_tokens: 100,
+            },
+            Duration::from_millis(1500),
+        );
+        metrics.record_denial(Denial::Quota);
+        metrics.record_llm_attempts(
+            Some("openai/gpt-4o"),
+            3,
+            &TokenUsage {
+                prompt_tokens: 80,
+                completion_tokens: 20,
+                total_tokens: 100,
+            },
+        );
+        metrics.record_llm_attempts(None, 2, &TokenUsage::default());
+        metrics.record_compaction(3, 120);
+        metrics.record_compaction_failure();
+
+        let text = metrics.render();
+        for line in [
+            "crustyclaw_messages_total{channel=\"signal\",direction=\"inbound\"} 2",
+            "crustyclaw_messages_total{channel=\"signal\",direction=\"outbound\"} 1",
+            "crustyclaw_sandbox_executions_total{outcome=\"success\"} 1",
+            "crustyclaw_sandbox_executions_total{outcome=\"failure\"} 1",
+            "crustyclaw_sandbox_execution_duration_seconds_bucket{le=\"0.025\"} 0",
+            "crustyclaw_sandbox_execution_duration_seconds_bucket{le=\"0.05\"} 1",
+            "crustyclaw_sandbox_execution_duration_seconds_bucket{le=\"30\"} 1",
+            "crustyclaw_sandbox_execution_duration_seconds_bucket{le=\"+Inf\"} 2",
+            "crustyclaw_sandbox_execution_duration_seconds_sum 60.04",
+            "crustyclaw_sandbox_execution_duration_seconds_count 2",
+            "crustyclaw_sandbox_cpu_seconds_total 1.5",
+            "crustyclaw_llm_requests_total 1",
+            "crustyclaw_llm_tokens_total{kind=\"prompt\"} 80",
+            "crustyclaw_llm_tokens_total{kind=\"completion\"} 20",
+            "crustyclaw_llm_request_duration_seconds_bucket{le=\"2.5\"} 1",
+            "crustyclaw_llm_provider_requests_total{provider=\"openai/gpt-4o\"} 1",
+            "crustyclaw_llm_provider_tokens_total{provider=\"openai/gpt-4o\",kind=\"prompt\"} 80",
+            "crustyclaw_llm_retries_total 3",
+            "crustyclaw_llm_unserved_requests_total 1",
+            "crustyclaw_context_compactions_total{outcome=\"success\"} 1",
+            "crustyclaw_context_compactions_total{outcome=\"failure\"} 1",
+            "crustyclaw_context_compacted_items_total 3",
+            "crustyclaw_context_compaction_saved_tokens_total 120",
+            "crustyclaw_policy_denials_total{reason=\"role\"} 0",
+            "crustyclaw_policy_denials_total{reason=\"quota\"} 1",
+            "# TYPE crustyclaw_llm_request_duration_seconds histogram",
+        ] {
+            assert!(
+                text.lines().any(|l| l == line),
+                "missing {line:?} in\n{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_escape_label() {
+        assert_eq!(escape("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+
+    #[tokio::test]
+    async fn test_serve_tcp() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let metrics = Arc::new(Metrics::new());
+        metrics.record_denial(Denial::Role);
+        let (shutdown_tx, shutdown_rx) = broadcast::channel(1);
+        // Reserve a free port, then hand it to the listener.
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let server = tokio::spawn(serve(addr, metrics, shutdown_rx));
+
+        let mut stream = loop {
+            match tokio::net::TcpStream::connect(addr).await {
+                Ok(stream) => break stream,
+                Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        };
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+        assert!(response.contains(CONTENT_TYPE));
+        assert!(response.contains("crustyclaw_policy_denials_total{reason=\"role\"} 1"));
+
+        shutdown_tx.send(ShutdownSignal).unwrap();
+        server.await.unwrap().unwrap();
+    }
+}
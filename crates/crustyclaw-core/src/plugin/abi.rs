@@ -0,0 +1,112 @@
+//! The versioned C ABI between the daemon and native plugins.
+//!
+//! A plugin is a `cdylib` exporting two symbols:
+//!
+//! - [`API_VERSION_SYMBOL`] — a `u32` static holding [`PLUGIN_ABI_VERSION`].
+//!   The daemon reads it before touching anything else, so a plugin built
+//!   against a different layout is rejected rather than misread.
+//! - [`DESCRIPTOR_SYMBOL`] — a function returning a pointer to a
+//!   [`PluginDescriptor`] that stays valid while the library is loaded.
+//!
+//! ```ignore
+//! use std::ffi::{c_char, CStr};
+//! use crustyclaw_core::plugin::abi::*;
+//!
+//! #[unsafe(no_mangle)]
+//! pub static crustyclaw_plugin_api_version: u32 = PLUGIN_ABI_VERSION;
+//!
+//! #[unsafe(no_mangle)]
+//! pub extern "C" fn crustyclaw_plugin_descriptor() -> *const PluginDescriptor {
+//!     &DESCRIPTOR
+//! }
+//! ```
+//!
+//! All strings are NUL-terminated UTF-8. Strings returned by
+//! [`PluginDescriptor::call_tool`] and [`PluginDescriptor::run_skill`] are
+//! owned by the plugin and handed back through
+//! [`PluginDescriptor::free_string`]. Callbacks may be invoked from any
+//! thread, concurrently, and must not unwind across the boundary.
+
+use std::ffi::c_char;
+
+/// ABI version implemented by this daemon. Bumped on any layout change.
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+/// Exported `u32` static holding the plugin's ABI version.
+pub const API_VERSION_SYMBOL: &str = "crustyclaw_plugin_api_version";
+
+/// Exported `extern "C" fn() -> *const PluginDescriptor`.
+pub const DESCRIPTOR_SYMBOL: &str = "crustyclaw_plugin_descriptor";
+
+/// `status` value reporting success from a call.
+pub const STATUS_OK: i32 = 0;
+
+/// Invoke a tool or skill by its unprefixed name with a UTF-8 input (JSON
+/// arguments for tools, the message body for skills). Sets `*status` to
+/// [`STATUS_OK`] or any other value for failure, and returns the output or
+/// error message (or null for an empty one).
+pub type CallFn = unsafe extern "C" fn(
+    name: *const c_char,
+    input: *const c_char,
+    status: *mut i32,
+) -> *mut c_char;
+
+/// Release a string returned by a [`CallFn`].
+pub type FreeStringFn = unsafe extern "C" fn(value: *mut c_char);
+
+/// What a plugin provides, returned by [`DESCRIPTOR_SYMBOL`].
+#[repr(C)]
+pub struct PluginDescriptor {
+    /// Plugin name; must equal the library's file stem (without `lib`).
+    pub name: *const c_char,
+    /// Plugin version (e.g. "1.0.0").
+    pub version: *const c_char,
+    /// Human-readable description; may be null.
+    pub description: *const c_char,
+    /// Array of `tool_count` tools; may be null when the count is zero.
+    pub tools: *const ToolDescriptor,
+    /// Number of entries in `tools`.
+    pub tool_count: usize,
+    /// Array of `skill_count` skills; may be null when the count is zero.
+    pub skills: *const SkillDescriptor,
+    /// Number of entries in `skills`.
+    pub skill_count: usize,
+    /// Run one of `tools`.
+    pub call_tool: CallFn,
+    /// Run one of `skills`.
+    pub run_skill: CallFn,
+    /// Release strings returned by `call_tool` and `run_skill`.
+    pub free_string: FreeStringFn,
+}
+
+// SAFETY: descriptors are immutable once returned and the plugin promises
+// its callbacks are thread-safe, per the module docs.
+#[allow(unsafe_code)]
+unsafe impl Sync for PluginDescriptor {}
+
+/// A tool offered to the LLM.
+#[repr(C)]
+pub struct ToolDescriptor {
+    /// Tool name (letters, digits, `-` and `_`).
+    pub name: *const c_char,
+    /// Description shown to the model.
+    pub description: *const c_char,
+    /// JSON Schema object for the arguments; null means no arguments.
+    pub parameters_json: *const c_char,
+}
+
+/// A skill the daemon can execute.
+#[repr(C)]
+pub struct SkillDescriptor {
+    /// Skill name.
+    pub name: *const c_char,
+    /// Short description.
+    pub description: *const c_char,
+}
+
+// SAFETY: as for `PluginDescriptor`; the strings are never written through.
+#[allow(unsafe_code)]
+unsafe impl Sync for ToolDescriptor {}
+
+#[allow(unsafe_code)]
+unsafe impl Sync for SkillDescriptor {}
@@ -0,0 +1,839 @@
+//! Native plugin loading from shared libraries.
+//!
+//! With `[plugins].enabled`, the daemon scans `[plugins].dir` at startup
+//! for `*.so` (`*.dylib` on macOS) files. Each file's plugin name is its
+//! stem without a `lib` prefix (`libweather.so` → `weather`). Before a
+//! library is opened, the policy must allow role [`LOAD_ROLE`] to perform
+//! [`LOAD_ACTION`] on `plugins/<name>`; opening a library runs its
+//! initializers, so nothing is executed for a denied plugin.
+//!
+//! An opened library must export the [ABI](super::abi) version symbol with
+//! [`PLUGIN_ABI_VERSION`] and a descriptor whose name matches the file.
+//! Its tools are registered in the [`ToolRegistry`] as `<name>__<tool>`,
+//! tagged [`TOOL_TAG`] and `plugin:<name>`, and routed back through
+//! [`PluginHost::call_tool`]; its skills are registered with
+//! [`SkillRegistry::register_loaded`].
+//!
+//! Opening libraries needs the `dylib-plugins` feature (Unix only); without
+//! it every allowed plugin fails with [`PluginError::Unsupported`]. Loaded
+//! libraries are never unloaded.
+
+use std::collections::HashMap;
+use std::ffi::{CStr, CString, c_char};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use serde_json::Value;
+
+use crustyclaw_config::AppConfig;
+
+use super::abi::{CallFn, FreeStringFn, PLUGIN_ABI_VERSION, PluginDescriptor, STATUS_OK};
+use crate::BoxFuture;
+use crate::context::{RegisteredTool, ToolRegistry, ToolSchema, ToolTrust};
+use crate::llm::types::ToolDefinition;
+use crate::mcp::TOOL_NAME_SEPARATOR;
+use crate::message::Envelope;
+use crate::skill::{Skill, SkillError, SkillRegistry};
+
+/// Policy role checked before a plugin library is opened.
+pub const LOAD_ROLE: &str = "daemon";
+
+/// Policy action checked before a plugin library is opened.
+pub const LOAD_ACTION: &str = "load";
+
+/// Tag applied to every plugin tool.
+pub const TOOL_TAG: &str = "plugin";
+
+/// Errors loading or calling a native plugin.
+#[derive(Debug, thiserror::Error)]
+pub enum PluginError {
+    #[error("loading plugins requires a daemon built with the dylib-plugins feature")]
+    Unsupported,
+
+    #[error("cannot open library: {0}")]
+    Open(String),
+
+    #[error("library does not export {0}")]
+    MissingSymbol(&'static str),
+
+    #[error("plugin ABI version {found} does not match the daemon's {expected}")]
+    AbiMismatch { found: u32, expected: u32 },
+
+    #[error("invalid plugin descriptor: {0}")]
+    Invalid(String),
+
+    #[error("plugin is named {found:?} but its file is named {expected:?}")]
+    NameMismatch { found: String, expected: String },
+
+    #[error("unknown plugin tool: {0}")]
+    UnknownTool(String),
+
+    /// The plugin reported a failure; the message is its output.
+    #[error("{0}")]
+    Call(String),
+}
+
+/// A tool declared by a plugin.
+#[derive(Debug, Clone)]
+pub struct PluginTool {
+    /// Name within the plugin (unprefixed).
+    pub name: String,
+    /// Description shown to the model.
+    pub description: String,
+    /// JSON Schema for the arguments.
+    pub parameters: Value,
+}
+
+/// A skill declared by a plugin.
+#[derive(Debug, Clone)]
+pub struct PluginSkillInfo {
+    /// Skill name.
+    pub name: String,
+    /// Short description.
+    pub description: String,
+}
+
+/// The plugin's callbacks.
+struct Entry {
+    call_tool: CallFn,
+    run_skill: CallFn,
+    free_string: FreeStringFn,
+}
+
+impl Entry {
+    /// Call `f` with `name` and `input`, copying out and releasing the
+    /// returned string.
+    #[allow(unsafe_code)]
+    fn call(&self, f: CallFn, name: &str, input: &str) -> Result<String, PluginError> {
+        let name = CString::new(name).map_err(|e| PluginError::Invalid(e.to_string()))?;
+        let input = CString::new(input)
+            .map_err(|_| PluginError::Call("input contains a NUL byte".to_string()))?;
+        let mut status = STATUS_OK;
+        // SAFETY: both pointers are valid NUL-terminated strings for the
+        // duration of the call and `status` is a valid out-pointer, as the
+        // ABI requires.
+        let raw = unsafe { f(name.as_ptr(), input.as_ptr(), &mut status) };
+        let output = if raw.is_null() {
+            String::new()
+        } else {
+            // SAFETY: a non-null result is a NUL-terminated string owned by
+            // the plugin until it is passed back to `free_string`.
+            let text = unsafe { CStr::from_ptr(raw) }
+                .to_string_lossy()
+                .into_owned();
+            // SAFETY: `raw` came from this plugin and is freed exactly once.
+            unsafe { (self.free_string)(raw) };
+            text
+        };
+        if status == STATUS_OK {
+            Ok(output)
+        } else {
+            Err(PluginError::Call(output))
+        }
+    }
+}
+
+/// A validated plugin, ready to register.
+pub struct LoadedPlugin {
+    /// Plugin name.
+    pub name: String,
+    /// Plugin version.
+    pub version: String,
+    /// Human-readable description.
+    pub description: String,
+    /// Tools the plugin provides.
+    pub tools: Vec<PluginTool>,
+    /// Skills the plugin provides.
+    pub skills: Vec<PluginSkillInfo>,
+    entry: Arc<Entry>,
+}
+
+impl std::fmt::Debug for LoadedPlugin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LoadedPlugin")
+            .field("name", &self.name)
+            .field("version", &self.version)
+            .field("tools", &self.tools)
+            .field("skills", &self.skills)
+            .finish_non_exhaustive()
+    }
+}
+
+impl LoadedPlugin {
+    /// Validate a plugin from its exported ABI version and descriptor
+    /// function, copying everything it declares.
+    ///
+    /// `descriptor` is only called once `api_version` matches
+    /// [`PLUGIN_ABI_VERSION`].
+    ///
+    /// # Safety
+    ///
+    /// `descriptor` must follow the [ABI](super::abi) for `api_version`: it
+    /// returns null or a pointer to a descriptor whose pointers are valid
+    /// for the stated counts and whose callbacks stay callable for the rest
+    /// of the process.
+    #[allow(unsafe_code)]
+    pub unsafe fn from_raw(
+        api_version: u32,
+        descriptor: unsafe extern "C" fn() -> *const PluginDescriptor,
+    ) -> Result<Self, PluginError> {
+        if api_version != PLUGIN_ABI_VERSION {
+            return Err(PluginError::AbiMismatch {
+                found: api_version,
+                expected: PLUGIN_ABI_VERSION,
+            });
+        }
+        // SAFETY: the version matched, so the caller guarantees the layout.
+        let desc = unsafe { descriptor().as_ref() }
+            .ok_or_else(|| PluginError::Invalid("descriptor is null".to_string()))?;
+
+        // SAFETY (all `read_str` and slice calls below): the caller
+        // guarantees the descriptor's pointers and counts are valid.
+        let name = unsafe { read_str(desc.name, "name") }?;
+        if !valid_name(&name) {
+            return Err(PluginError::Invalid(format!(
+                "name must be non-empty and contain only letters, digits, '-' and '_', got {name:?}"
+            )));
+        }
+        let version = unsafe { read_str(desc.version, "version") }?;
+        let description = unsafe { read_optional_str(desc.description, "description") }?;
+
+        let mut tools = Vec::new();
+        for tool in unsafe { slice(desc.tools, desc.tool_count, "tools") }? {
+            let tool_name = unsafe { read_str(tool.name, "tool name") }?;
+            if !valid_name(&tool_name) {
+                return Err(PluginError::Invalid(format!(
+                    "tool name must be non-empty and contain only letters, digits, '-' and '_', got {tool_name:?}"
+                )));
+            }
+            let parameters = match unsafe { read_optional_str(tool.parameters_json, "parameters") }?
+            {
+                json if json.is_empty() => serde_json::json!({"type": "object", "properties": {}}),
+                json => match serde_json::from_str::<Value>(&json) {
+                    Ok(value @ Value::Object(_)) => value,
+                    _ => {
+                        return Err(PluginError::Invalid(format!(
+                            "parameters of tool {tool_name} must be a JSON object"
+                        )));
+                    }
+                },
+            };
+            if let Err(e) = ToolSchema::compile(&parameters) {
+                return Err(PluginError::Invalid(format!("tool {tool_name}: {e}")));
+            }
+            tools.push(PluginTool {
+                description: unsafe { read_optional_str(tool.description, "tool description") }?,
+                name: tool_name,
+                parameters,
+            });
+        }
+
+        let mut skills = Vec::new();
+        for skill in unsafe { slice(desc.skills, desc.skill_count, "skills") }? {
+            let skill_name = unsafe { read_str(skill.name, "skill name") }?;
+            if skill_name.trim().is_empty() {
+                return Err(PluginError::Invalid("skill name is empty".to_string()));
+            }
+            skills.push(PluginSkillInfo {
+                description: unsafe { read_optional_str(skill.description, "skill description") }?,
+                name: skill_name,
+            });
+        }
+
+        Ok(Self {
+            name,
+            version,
+            description,
+            tools,
+            skills,
+            entry: Arc::new(Entry {
+                call_tool: desc.call_tool,
+                run_skill: desc.run_skill,
+                free_string: desc.free_string,
+            }),
+        })
+    }
+}
+
+/// Read a required string field.
+///
+/// # Safety
+///
+/// `ptr` is null or a valid NUL-terminated string.
+#[allow(unsafe_code)]
+unsafe fn read_str(ptr: *const c_char, field: &str) -> Result<String, PluginError> {
+    if ptr.is_null() {
+        return Err(PluginError::Invalid(format!("{field} is null")));
+    }
+    // SAFETY: non-null and valid per the function contract.
+    unsafe { CStr::from_ptr(ptr) }
+        .to_str()
+        .map(str::to_string)
+        .map_err(|_| PluginError::Invalid(format!("{field} is not UTF-8")))
+}
+
+/// Read an optional string field; null reads as empty.
+///
+/// # Safety
+///
+/// As for [`read_str`].
+#[allow(unsafe_code)]
+unsafe fn read_optional_str(ptr: *const c_char, field: &str) -> Result<String, PluginError> {
+    if ptr.is_null() {
+        return Ok(String::new());
+    }
+    // SAFETY: forwarded from the caller.
+    unsafe { read_str(ptr, field) }
+}
+
+/// View a descriptor array.
+///
+/// # Safety
+///
+/// `ptr` is null or points to `count` initialized values.
+#[allow(unsafe_code)]
+unsafe fn slice<'a, T>(ptr: *const T, count: usize, field: &str) -> Result<&'a [T], PluginError> {
+    match (ptr.is_null(), count) {
+        (_, 0) => Ok(&[]),
+        (true, _) => Err(PluginError::Invalid(format!(
+            "{field} is null but its count is {count}"
+        ))),
+        // SAFETY: non-null and valid for `count` elements per the contract.
+        (false, _) => Ok(unsafe { std::slice::from_raw_parts(ptr, count) }),
+    }
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// The plugin name for a library file: its stem without a `lib` prefix.
+pub fn plugin_name(path: &Path) -> Option<String> {
+    let stem = path.file_stem()?.to_str()?;
+    let name = stem.strip_prefix("lib").unwrap_or(stem);
+    Some(name.to_string())
+}
+
+/// A skill backed by a plugin's `run_skill` callback.
+struct PluginSkill {
+    info: PluginSkillInfo,
+    entry: Arc<Entry>,
+}
+
+impl Skill for PluginSkill {
+    fn name(&self) -> &str {
+        &self.info.name
+    }
+
+    fn description(&self) -> &str {
+        &self.info.description
+    }
+
+    fn execute(&self, message: &Envelope) -> BoxFuture<'_, Result<String, SkillError>> {
+        let entry = self.entry.clone();
+        let name = self.info.name.clone();
+        let body = message.body.clone();
+        Box::pin(async move {
+            tokio::task::spawn_blocking(move || entry.call(entry.run_skill, &name, &body))
+                .await
+                .map_err(|e| SkillError::Execution(format!("plugin skill panicked: {e}")))?
+                .map_err(|e| SkillError::Execution(e.to_string()))
+        })
+    }
+}
+
+/// Outcome of [`PluginHost::load`].
+#[derive(Debug, Default)]
+pub struct PluginLoad {
+    /// Names of the plugins that were registered.
+    pub loaded: Vec<String>,
+    /// Plugin libraries the policy did not allow to load.
+    pub denied: Vec<PathBuf>,
+    /// Plugin libraries that failed to load, and why.
+    pub errors: Vec<(PathBuf, PluginError)>,
+    /// Plugin skills dropped because their name was already taken.
+    pub shadowed: Vec<String>,
+}
+
+/// Loaded native plugins and the routing for their tools.
+pub struct PluginHost {
+    plugins: RwLock<Vec<PluginSummary>>,
+    /// Registered tool name → (plugin entry, unprefixed tool name).
+    tools: RwLock<HashMap<String, (Arc<Entry>, String)>>,
+}
+
+/// Metadata about a loaded plugin.
+#[derive(Debug, Clone)]
+pub struct PluginSummary {
+    /// Plugin name.
+    pub name: String,
+    /// Plugin version.
+    pub version: String,
+    /// Human-readable description.
+    pub description: String,
+    /// Registered tool names (`<plugin>__<tool>`).
+    pub tools: Vec<String>,
+    /// Registered skill names.
+    pub skills: Vec<String>,
+}
+
+impl PluginHost {
+    /// Create a host with no plugins.
+    pub fn new() -> Self {
+        Self {
+            plugins: RwLock::new(Vec::new()),
+            tools: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Load every allowed plugin in `[plugins].dir` and register its tools
+    /// in `registry` and its skills in `skills`.
+    pub fn load(
+        &self,
+        config: &AppConfig,
+        registry: &RwLock<ToolRegistry>,
+        skills: &SkillRegistry,
+    ) -> PluginLoad {
+        let mut load = PluginLoad::default();
+        if !config.plugins.enabled {
+            return load;
+        }
+        let dir = Path::new(&config.daemon.state_dir).join(&config.plugins.dir);
+        let trust = ToolTrust::from_name(&config.plugins.trust).unwrap_or_default();
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                load.errors.push((
+                    dir,
+                    PluginError::Open(format!("cannot read plugin directory: {e}")),
+                ));
+                return load;
+            }
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|ext| ext == std::env::consts::DLL_EXTENSION)
+            })
+            .collect();
+        paths.sort();
+
+        let mut engine = config.build_policy_engine();
+        for path in paths {
+            let Some(name) = plugin_name(&path) else {
+                continue;
+            };
+            if !engine.is_allowed(LOAD_ROLE, LOAD_ACTION, &format!("plugins/{name}")) {
+                load.denied.push(path);
+                continue;
+            }
+            let plugin = match open_library(&path) {
+                Ok(plugin) if plugin.name != name => Err(PluginError::NameMismatch {
+                    found: plugin.name,
+                    expected: name,
+                }),
+                other => other,
+            };
+            match plugin {
+                Ok(plugin) => {
+                    load.loaded.push(plugin.name.clone());
+                    load.shadowed
+                        .extend(self.register(plugin, trust, registry, skills));
+                }
+                Err(e) => load.errors.push((path, e)),
+            }
+        }
+        load
+    }
+
+    /// Register a validated plugin's tools and skills, returning the names
+    /// of skills dropped because they were already taken.
+    pub fn register(
+        &self,
+        plugin: LoadedPlugin,
+        trust: ToolTrust,
+        registry: &RwLock<ToolRegistry>,
+        skills: &SkillRegistry,
+    ) -> Vec<String> {
+        let mut tool_names = Vec::new();
+        {
+            let mut registry = registry.write().unwrap_or_else(|e| e.into_inner());
+            let mut routes = self.tools.write().unwrap_or_else(|e| e.into_inner());
+            for tool in &plugin.tools {
+                let name = format!("{}{TOOL_NAME_SEPARATOR}{}", plugin.name, tool.name);
+                let registered = registry.register(RegisteredTool {
+                    definition: ToolDefinition {
+                        name: name.clone(),
+                        description: tool.description.clone(),
+                        parameters: tool.parameters.clone(),
+                    },
+                    trust,
+                    tags: vec![TOOL_TAG.to_string(), format!("{TOOL_TAG}:{}", plugin.name)],
+                    enabled: true,
+                });
+                if let Err(e) = registered {
+                    tracing::warn!(plugin = %plugin.name, tool = %name, error = %e, "Skipping plugin tool");
+                    continue;
+                }
+                routes.insert(name.clone(), (plugin.entry.clone(), tool.name.clone()));
+                tool_names.push(name);
+            }
+        }
+
+        let mut skill_names = Vec::new();
+        let mut shadowed = Vec::new();
+        for info in &plugin.skills {
+            let skill = PluginSkill {
+                info: info.clone(),
+                entry: plugin.entry.clone(),
+            };
+            if skills.register_loaded(Box::new(skill)) {
+                skill_names.push(info.name.clone());
+            } else {
+                shadowed.push(info.name.clone());
+            }
+        }
+
+        self.plugins
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(PluginSummary {
+                name: plugin.name,
+                version: plugin.version,
+                description: plugin.description,
+                tools: tool_names,
+                skills: skill_names,
+            });
+        shadowed
+    }
+
+    /// The loaded plugins, in load order.
+    pub fn plugins(&self) -> Vec<PluginSummary> {
+        self.plugins
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Registered names of all plugin tools, sorted.
+    pub fn tool_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .tools
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .keys()
+            .cloned()
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Call a plugin tool by its registered name on a blocking thread.
+    ///
+    /// A failure reported by the plugin is [`PluginError::Call`].
+    pub async fn call_tool(&self, tool: &str, arguments: Value) -> Result<String, PluginError> {
+        let (entry, name) = self
+            .tools
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(tool)
+            .cloned()
+            .ok_or_else(|| PluginError::UnknownTool(tool.to_string()))?;
+        let input = arguments.to_string();
+        tokio::task::spawn_blocking(move || entry.call(entry.call_tool, &name, &input))
+            .await
+            .map_err(|e| PluginError::Call(format!("plugin tool panicked: {e}")))?
+    }
+}
+
+impl Default for PluginHost {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(not(all(feature = "dylib-plugins", unix)))]
+fn open_library(_path: &Path) -> Result<LoadedPlugin, PluginError> {
+    Err(PluginError::Unsupported)
+}
+
+#[cfg(all(feature = "dylib-plugins", unix))]
+#[allow(unsafe_code)]
+fn open_library(path: &Path) -> Result<LoadedPlugin, PluginError> {
+    use std::os::unix::ffi::OsStrExt;
+
+    use super::abi::{API_VERSION_SYMBOL, DESCRIPTOR_SYMBOL};
+
+    fn dl_error() -> String {
+        // SAFETY: dlerror returns null or a NUL-terminated string valid
+        // until the next dl* call on this thread.
+        let err = unsafe { libc::dlerror() };
+        if err.is_null() {
+            "unknown error".to_string()
+        } else {
+            // SAFETY: non-null, see above.
+            unsafe { CStr::from_ptr(err) }
+                .to_string_lossy()
+                .into_owned()
+        }
+    }
+
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| PluginError::Open("path contains a NUL byte".to_string()))?;
+    // SAFETY: `c_path` is a valid C string. The handle is intentionally
+    // never closed: plugin callbacks and thread-locals must outlive every
+    // in-flight call, so libraries stay mapped until the process exits.
+    let handle = unsafe { libc::dlopen(c_path.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+    if handle.is_null() {
+        return Err(PluginError::Open(dl_error()));
+    }
+
+    let symbol = |name: &'static str| {
+        let c_name = CString::new(name).expect("symbol names have no NUL");
+        // SAFETY: `handle` is a live dlopen handle and `c_name` is valid.
+        let ptr = unsafe { libc::dlsym(handle, c_name.as_ptr()) };
+        if ptr.is_null() {
+            Err(PluginError::MissingSymbol(name))
+        } else {
+            Ok(ptr)
+        }
+    };
+    let version = symbol(API_VERSION_SYMBOL)? as *const u32;
+    let descriptor = symbol(DESCRIPTOR_SYMBOL)?;
+
+    // SAFETY: the ABI defines the version symbol as a `u32` static.
+    let api_version = unsafe { version.read() };
+    // SAFETY: the ABI defines the descriptor symbol as this function type;
+    // `from_raw` only calls it once the version matches.
+    let descriptor: unsafe extern "C" fn() -> *const PluginDescriptor =
+        unsafe { std::mem::transmute(descriptor) };
+    // SAFETY: the library stays loaded for the life of the process.
+    unsafe { LoadedPlugin::from_raw(api_version, descriptor) }
+}
+
+#[cfg(test)]
+#[allow(unsafe_code)]
+mod tests {
+    use super::*;
+    use crate::plugin::abi::{SkillDescriptor, ToolDescriptor};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    unsafe extern "C" fn call_tool(
+        name: *const c_char,
+        input: *const c_char,
+        status: *mut i32,
+    ) -> *mut c_char {
+        // SAFETY: the host passes valid strings and out-pointer.
+        let (name, input) = unsafe {
+            (
+                CStr::from_ptr(name).to_str().unwrap(),
+                CStr::from_ptr(input).to_str().unwrap(),
+            )
+        };
+        let (code, output) = match name {
+            "forecast" => (STATUS_OK, format!("sunny for {input}")),
+            _ => (1, format!("no tool {name}")),
+        };
+        // SAFETY: see above.
+        unsafe { *status = code };
+        CString::new(output).unwrap().into_raw()
+    }
+
+    unsafe extern "C" fn run_skill(
+        _name: *const c_char,
+        input: *const c_char,
+        _status: *mut i32,
+    ) -> *mut c_char {
+        // SAFETY: the host passes a valid string.
+        let input = unsafe { CStr::from_ptr(input) }.to_str().unwrap();
+        CString::new(input.to_uppercase()).unwrap().into_raw()
+    }
+
+    unsafe extern "C" fn free_string(value: *mut c_char) {
+        // SAFETY: every returned string came from `CString::into_raw`.
+        drop(unsafe { CString::from_raw(value) });
+    }
+
+    static TOOLS: [ToolDescriptor; 1] = [ToolDescriptor {
+        name: c"forecast".as_ptr(),
+        description: c"Weather forecast".as_ptr(),
+        parameters_json: cr#"{"type":"object","properties":{"city":{"type":"string"}}}"#.as_ptr(),
+    }];
+
+    static SKILLS: [SkillDescriptor; 1] = [SkillDescriptor {
+        name: c"shout".as_ptr(),
+        description: std::ptr::null(),
+    }];
+
+    static DESCRIPTOR: PluginDescriptor = PluginDescriptor {
+        name: c"weather".as_ptr(),
+        version: c"1.2.0".as_ptr(),
+        description: c"Weather tools".as_ptr(),
+        tools: TOOLS.as_ptr(),
+        tool_count: 1,
+        skills: SKILLS.as_ptr(),
+        skill_count: 1,
+        call_tool,
+        run_skill,
+        free_string,
+    };
+
+    extern "C" fn descriptor() -> *const PluginDescriptor {
+        &DESCRIPTOR
+    }
+
+    static BAD_COUNT: PluginDescriptor = PluginDescriptor {
+        tools: std::ptr::null(),
+        ..DESCRIPTOR
+    };
+
+    extern "C" fn bad_count() -> *const PluginDescriptor {
+        &BAD_COUNT
+    }
+
+    static BAD_NAME: PluginDescriptor = PluginDescriptor {
+        name: c"weather tools".as_ptr(),
+        ..DESCRIPTOR
+    };
+
+    extern "C" fn bad_name() -> *const PluginDescriptor {
+        &BAD_NAME
+    }
+
+    static DESCRIPTOR_CALLED: AtomicBool = AtomicBool::new(false);
+
+    extern "C" fn tracked() -> *const PluginDescriptor {
+        DESCRIPTOR_CALLED.store(true, Ordering::SeqCst);
+        &DESCRIPTOR
+    }
+
+    #[test]
+    fn test_from_raw_reads_descriptor() {
+        let plugin = unsafe { LoadedPlugin::from_raw(PLUGIN_ABI_VERSION, descriptor) }.unwrap();
+        assert_eq!(plugin.name, "weather");
+        assert_eq!(plugin.version, "1.2.0");
+        assert_eq!(plugin.tools.len(), 1);
+        assert_eq!(plugin.tools[0].name, "forecast");
+        assert_eq!(
+            plugin.tools[0].parameters["properties"]["city"]["type"],
+            "string"
+        );
+        assert_eq!(plugin.skills[0].name, "shout");
+        assert_eq!(plugin.skills[0].description, "");
+    }
+
+    #[test]
+    fn test_abi_mismatch_is_rejected_before_reading_descriptor() {
+        let err = unsafe { LoadedPlugin::from_raw(PLUGIN_ABI_VERSION + 1, tracked) }.unwrap_err();
+        assert!(matches!(
+            err,
+            PluginError::AbiMismatch { found, expected }
+                if found == PLUGIN_ABI_VERSION + 1 && expected == PLUGIN_ABI_VERSION
+        ));
+        assert!(!DESCRIPTOR_CALLED.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_invalid_descriptors() {
+        let err = unsafe { LoadedPlugin::from_raw(PLUGIN_ABI_VERSION, bad_count) }.unwrap_err();
+        assert!(
+            err.to_string().contains("tools is null but its count is 1"),
+            "{err}"
+        );
+        let err = unsafe { LoadedPlugin::from_raw(PLUGIN_ABI_VERSION, bad_name) }.unwrap_err();
+        assert!(err.to_string().contains("name must be"), "{err}");
+    }
+
+    #[test]
+    fn test_plugin_name() {
+        assert_eq!(
+            plugin_name(Path::new("/p/libweather.so")).unwrap(),
+            "weather"
+        );
+        assert_eq!(
+            plugin_name(Path::new("/p/weather.dylib")).unwrap(),
+            "weather"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_register_routes_tools_and_skills() {
+        let plugin = unsafe { LoadedPlugin::from_raw(PLUGIN_ABI_VERSION, descriptor) }.unwrap();
+        let host = PluginHost::new();
+        let registry = RwLock::new(ToolRegistry::new());
+        let skills = SkillRegistry::new();
+        let shadowed = host.register(plugin, ToolTrust::Internal, &registry, &skills);
+        assert!(shadowed.is_empty());
+
+        let tool = registry
+            .read()
+            .unwrap()
+            .get("weather__forecast")
+            .cloned()
+            .unwrap();
+        assert_eq!(tool.trust, ToolTrust::Internal);
+        assert_eq!(tool.tags, vec!["plugin", "plugin:weather"]);
+        assert_eq!(host.tool_names(), vec!["weather__forecast"]);
+
+        let output = host
+            .call_tool("weather__forecast", serde_json::json!({"city": "Oslo"}))
+            .await
+            .unwrap();
+        assert_eq!(output, r#"sunny for {"city":"Oslo"}"#);
+        assert!(matches!(
+            host.call_tool("weather__nope", Value::Null).await,
+            Err(PluginError::UnknownTool(_))
+        ));
+
+        let skill = skills.get("shout").unwrap();
+        let reply = skill.execute(&Envelope::new("cli", "hello")).await.unwrap();
+        assert_eq!(reply, "HELLO");
+
+        // A second copy of the skill is shadowed by the first.
+        let again = unsafe { LoadedPlugin::from_raw(PLUGIN_ABI_VERSION, descriptor) }.unwrap();
+        let shadowed = host.register(again, ToolTrust::Internal, &registry, &skills);
+        assert_eq!(shadowed, vec!["shout"]);
+        assert_eq!(host.plugins().len(), 2);
+    }
+
+    #[test]
+    fn test_load_is_gated_by_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let plugins = dir.path().join("plugins");
+        std::fs::create_dir(&plugins).unwrap();
+        let lib = format!("libweather.{}", std::env::consts::DLL_EXTENSION);
+        std::fs::write(plugins.join(&lib), b"not a library").unwrap();
+        std::fs::write(plugins.join("README.md"), b"ignored").unwrap();
+
+        let toml = |rules: &str| {
+            format!(
+                "[daemon]\nstate_dir = {:?}\n[plugins]\nenabled = true\n{rules}",
+                dir.path().display().to_string()
+            )
+        };
+        let host = PluginHost::new();
+        let registry = RwLock::new(ToolRegistry::new());
+        let skills = SkillRegistry::new();
+
+        let config = AppConfig::parse(&toml("")).unwrap();
+        let load = host.load(&config, &registry, &skills);
+        assert_eq!(load.denied, vec![plugins.join(&lib)]);
+        assert!(load.errors.is_empty());
+
+        let config = AppConfig::parse(&toml(
+            "[[policy.rules]]\nrole = \"daemon\"\naction = \"load\"\nresource = \"plugins/weather\"\neffect = \"allow\"\n",
+        ))
+        .unwrap();
+        let load = host.load(&config, &registry, &skills);
+        assert!(load.denied.is_empty());
+        assert_eq!(load.errors.len(), 1);
+        assert!(load.loaded.is_empty());
+
+        let disabled = AppConfig::default();
+        assert!(host.load(&disabled, &registry, &skills).errors.is_empty());
+    }
+}
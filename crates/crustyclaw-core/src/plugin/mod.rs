@@ -3,6 +3,15 @@
 //! Provides a runtime registry where action plugins register themselves.
 //! Plugins discovered at startup are stored here and can be looked up
 //! by name for execution.
+//!
+//! Native plugins loaded from shared libraries live in [`loader`], using
+//! the C ABI defined in [`abi`]. Forgejo Action plugins shipped as WASM
+//! modules run in `wasm`, with the `wasm-plugins` feature.
+
+pub mod abi;
+pub mod loader;
+#[cfg(feature = "wasm-plugins")]
+pub mod wasm;
 
 use std::collections::HashMap;
 
@@ -54,7 +63,7 @@ impl PluginRegistry {
     pub fn register_hook(&mut self, entry: HookEntry) {
         self.hooks.push(entry);
         // Keep hooks sorted by priority (highest first)
-        self.hooks.sort_by(|a, b| b.priority.cmp(&a.priority));
+        self.hooks.sort_by_key(|h| std::cmp::Reverse(h.priority));
     }
 
     /// Look up a plugin by name.
@@ -0,0 +1,1050 @@
+//! Forgejo Action plugins shipped as WASM modules.
+//!
+//! With `[plugins.wasm].enabled`, every `*.wasm` module in
+//! `[plugins.wasm].dir` is compiled at startup and registered in the
+//! [`ToolRegistry`] as `action__<name>`, tagged [`TOOL_TAG`] and
+//! `wasm:<name>`. Calls are routed back through
+//! [`WasmPluginHost::run_tool`]. With `hot_reload`, the directory is polled
+//! by [`spawn_reloader`] and changed modules are recompiled; a module that
+//! fails to compile leaves the previously loaded version in place.
+//!
+//! # Module format
+//!
+//! A plugin is a core WASM module that exports `memory` and
+//! `run: () -> i32` (0 for success) and imports nothing outside
+//! [`HOST_MODULE`]: no WASI, so no filesystem, network, or clock. Its
+//! metadata is JSON in a custom section named [`METADATA_SECTION`]:
+//!
+//! ```json
+//! {"name": "greet", "version": "1.0.0", "description": "Greets someone",
+//!  "inputs": [{"name": "who", "required": true}], "outputs": ["greeting"]}
+//! ```
+//!
+//! The name must equal the file stem. Each input may also carry a
+//! `description` and a `default`.
+//!
+//! # Host interface
+//!
+//! All strings are UTF-8 passed as `(ptr, len)` pairs into the plugin's
+//! memory. Imports from [`HOST_MODULE`]:
+//!
+//! | Function | Signature | Meaning |
+//! |----------|-----------|---------|
+//! | `input_len` | `(name, name_len) -> i32` | Byte length of an input, or -1 if unset |
+//! | `input_read` | `(name, name_len, buf) -> i32` | Copy an input to `buf`; returns its length or -1 |
+//! | `set_output` | `(name, name_len, value, value_len) -> i32` | Set a declared output; -1 if undeclared |
+//! | `log` | `(level, msg, msg_len)` | 0 debug, 1 info, 2 warning, 3 error |
+//! | `call_tool` | `(name, name_len, args, args_len) -> i32` | Call a tool with JSON arguments; 0 on success, 1 on failure, -1 for invalid JSON |
+//! | `result_len` | `() -> i32` | Byte length of the last `call_tool` result |
+//! | `result_read` | `(buf) -> i32` | Copy the last result to `buf`; returns its length |
+//!
+//! Tool calls run with the trust of the caller that started the plugin and
+//! cannot reach other WASM plugins. Each run gets a fresh instance, is
+//! stopped with [`WasmError::OutOfFuel`] after `fuel` units, and cannot grow
+//! its memory past `memory_bytes`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+use wasmtime::{
+    Caller, Config, Engine, ExternType, InstancePre, Linker, Module, Store, StoreLimits,
+    StoreLimitsBuilder, Trap,
+};
+
+use crustyclaw_config::WasmPluginsConfig;
+
+use crate::context::{RegisteredTool, ToolRegistry, ToolTrust};
+use crate::daemon::ShutdownSignal;
+use crate::llm::types::ToolDefinition;
+use crate::mcp::TOOL_NAME_SEPARATOR;
+
+/// Import module name of the host interface.
+pub const HOST_MODULE: &str = "crustyclaw";
+
+/// Custom section holding a plugin's JSON metadata.
+pub const METADATA_SECTION: &str = "crustyclaw-plugin";
+
+/// Prefix of the registered tool names (`action__<name>`).
+pub const TOOL_PREFIX: &str = "action";
+
+/// Tag applied to every WASM plugin tool.
+pub const TOOL_TAG: &str = "wasm";
+
+/// Errors loading or running a WASM plugin.
+#[derive(Debug, thiserror::Error)]
+pub enum WasmError {
+    #[error("{path}: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("invalid plugin: {0}")]
+    Invalid(String),
+
+    #[error("plugin is named {found:?} but its file is named {expected:?}")]
+    NameMismatch { found: String, expected: String },
+
+    #[error("unknown WASM plugin: {0}")]
+    UnknownPlugin(String),
+
+    #[error("required input {0} not set")]
+    MissingInput(String),
+
+    #[error("plugin ran out of fuel after {0} units")]
+    OutOfFuel(u64),
+
+    #[error("plugin trapped: {0}")]
+    Trap(String),
+
+    #[error("plugin failed with status {status}: {message}")]
+    Failed { status: i32, message: String },
+}
+
+/// A declared plugin input.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WasmInput {
+    /// Input name.
+    pub name: String,
+    /// Description shown to the model.
+    #[serde(default)]
+    pub description: String,
+    /// Whether the run fails when the input is not given.
+    #[serde(default)]
+    pub required: bool,
+    /// Value used when the input is not given.
+    #[serde(default)]
+    pub default: Option<String>,
+}
+
+/// Metadata from a plugin's [`METADATA_SECTION`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WasmPluginMeta {
+    /// Plugin name; must equal the file stem.
+    pub name: String,
+    /// Plugin version.
+    #[serde(default)]
+    pub version: String,
+    /// Human-readable description.
+    #[serde(default)]
+    pub description: String,
+    /// Declared inputs.
+    #[serde(default)]
+    pub inputs: Vec<WasmInput>,
+    /// Declared output names.
+    #[serde(default)]
+    pub outputs: Vec<String>,
+}
+
+impl WasmPluginMeta {
+    /// The registered tool name, `action__<name>`.
+    pub fn tool_name(&self) -> String {
+        format!("{TOOL_PREFIX}{TOOL_NAME_SEPARATOR}{}", self.name)
+    }
+
+    /// JSON Schema for the tool arguments: one string per input.
+    pub fn parameters(&self) -> Value {
+        let properties: serde_json::Map<String, Value> = self
+            .inputs
+            .iter()
+            .map(|input| {
+                let mut schema = json!({"type": "string"});
+                if !input.description.is_empty() {
+                    schema["description"] = json!(input.description);
+                }
+                (input.name.clone(), schema)
+            })
+            .collect();
+        let required: Vec<&str> = self
+            .inputs
+            .iter()
+            .filter(|input| input.required && input.default.is_none())
+            .map(|input| input.name.as_str())
+            .collect();
+        json!({"type": "object", "properties": properties, "required": required})
+    }
+}
+
+/// Calls tools on behalf of a running plugin.
+///
+/// Called on the plugin's blocking thread; an `Err` is reported to the
+/// plugin as a failed call with the message as its result.
+pub trait ToolCaller: Send + Sync {
+    /// Call `name` with JSON `arguments`.
+    fn call_tool(&self, name: &str, arguments: Value) -> Result<String, String>;
+}
+
+impl<F> ToolCaller for F
+where
+    F: Fn(&str, Value) -> Result<String, String> + Send + Sync,
+{
+    fn call_tool(&self, name: &str, arguments: Value) -> Result<String, String> {
+        self(name, arguments)
+    }
+}
+
+/// A [`ToolCaller`] that refuses every call.
+pub struct NoTools;
+
+impl ToolCaller for NoTools {
+    fn call_tool(&self, name: &str, _arguments: Value) -> Result<String, String> {
+        Err(format!("tool {name} is not available to this plugin"))
+    }
+}
+
+/// The result of a successful run.
+#[derive(Debug, Clone, Default)]
+pub struct WasmRun {
+    /// Outputs the plugin set.
+    pub outputs: BTreeMap<String, String>,
+    /// Log lines, with `::debug::`, `::warning::`, or `::error::` prefixes
+    /// as in workflow commands.
+    pub logs: Vec<String>,
+    /// Fuel the run consumed.
+    pub fuel_used: u64,
+}
+
+/// Outcome of [`WasmPluginHost::load`].
+#[derive(Debug, Default)]
+pub struct WasmLoad {
+    /// Names of the plugins now loaded.
+    pub loaded: Vec<String>,
+    /// Modules that failed to compile, and why.
+    pub errors: Vec<(PathBuf, WasmError)>,
+}
+
+struct WasmPlugin {
+    meta: WasmPluginMeta,
+    instance: InstancePre<RunState>,
+}
+
+/// Per-run store data.
+struct RunState {
+    inputs: HashMap<String, String>,
+    outputs: BTreeMap<String, String>,
+    declared: Vec<String>,
+    logs: Vec<String>,
+    result: Vec<u8>,
+    tools: Arc<dyn ToolCaller>,
+    limits: StoreLimits,
+    plugin: String,
+}
+
+struct HostState {
+    dir: PathBuf,
+    trust: ToolTrust,
+    fuel: u64,
+    memory_bytes: usize,
+    plugins: HashMap<String, Arc<WasmPlugin>>,
+    fingerprint: Vec<(PathBuf, Option<SystemTime>, u64)>,
+}
+
+/// Compiled WASM plugins and the runtime that runs them.
+pub struct WasmPluginHost {
+    engine: Engine,
+    linker: Linker<RunState>,
+    state: RwLock<HostState>,
+}
+
+impl WasmPluginHost {
+    /// Create a host with no plugins.
+    pub fn new() -> Self {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config).expect("fuel-metered engine config is valid");
+        let mut linker = Linker::new(&engine);
+        define_host_interface(&mut linker).expect("host functions have unique names");
+        let defaults = WasmPluginsConfig::default();
+        Self {
+            engine,
+            linker,
+            state: RwLock::new(HostState {
+                dir: PathBuf::from(defaults.dir),
+                trust: ToolTrust::Internal,
+                fuel: defaults.fuel,
+                memory_bytes: defaults.memory_bytes as usize,
+                plugins: HashMap::new(),
+                fingerprint: Vec::new(),
+            }),
+        }
+    }
+
+    /// Compile a module, checking its metadata and imports. `expected`
+    /// is the name its file implies.
+    fn compile(&self, expected: &str, bytes: &[u8]) -> Result<WasmPlugin, WasmError> {
+        let module =
+            Module::new(&self.engine, bytes).map_err(|e| WasmError::Invalid(format!("{e:#}")))?;
+        let section = custom_section(bytes, METADATA_SECTION).ok_or_else(|| {
+            WasmError::Invalid(format!("missing {METADATA_SECTION} custom section"))
+        })?;
+        let meta: WasmPluginMeta = serde_json::from_slice(section)
+            .map_err(|e| WasmError::Invalid(format!("{METADATA_SECTION}: {e}")))?;
+        if meta.name != expected {
+            return Err(WasmError::NameMismatch {
+                found: meta.name,
+                expected: expected.to_string(),
+            });
+        }
+        let exports: HashMap<&str, ExternType> =
+            module.exports().map(|e| (e.name(), e.ty())).collect();
+        if !matches!(exports.get("memory"), Some(ExternType::Memory(_))) {
+            return Err(WasmError::Invalid("module must export memory".to_string()));
+        }
+        match exports.get("run") {
+            Some(ExternType::Func(ty))
+                if ty.params().len() == 0
+                    && ty.results().len() == 1
+                    && ty.results().all(|t| t.is_i32()) => {}
+            _ => {
+                return Err(WasmError::Invalid(
+                    "module must export run: () -> i32".to_string(),
+                ));
+            }
+        }
+        // Resolves every import against the host interface up front.
+        let instance = self
+            .linker
+            .instantiate_pre(&module)
+            .map_err(|e| WasmError::Invalid(format!("{e:#}")))?;
+        Ok(WasmPlugin { meta, instance })
+    }
+
+    /// Compile every module in `config.dir` (relative to `state_dir`),
+    /// replace the loaded set, and update the plugin tools in `registry`.
+    ///
+    /// A module that fails to compile keeps its previously loaded version,
+    /// if any.
+    pub fn load(
+        &self,
+        config: &WasmPluginsConfig,
+        state_dir: &Path,
+        registry: &RwLock<ToolRegistry>,
+    ) -> WasmLoad {
+        let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+        state.dir = state_dir.join(&config.dir);
+        state.trust = ToolTrust::from_name(&config.trust).unwrap_or_default();
+        state.fuel = config.fuel;
+        state.memory_bytes = usize::try_from(config.memory_bytes).unwrap_or(usize::MAX);
+        self.rescan(&mut state, registry)
+    }
+
+    /// Rescan the directory if any module was added, removed, or changed
+    /// since the last load.
+    pub fn reload_if_changed(&self, registry: &RwLock<ToolRegistry>) -> Option<WasmLoad> {
+        let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+        if fingerprint(&state.dir) == state.fingerprint {
+            return None;
+        }
+        Some(self.rescan(&mut state, registry))
+    }
+
+    fn rescan(&self, state: &mut HostState, registry: &RwLock<ToolRegistry>) -> WasmLoad {
+        let mut load = WasmLoad::default();
+        state.fingerprint = fingerprint(&state.dir);
+        let mut plugins = HashMap::new();
+        for (path, _, _) in &state.fingerprint {
+            let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            let compiled = std::fs::read(path)
+                .map_err(|source| WasmError::Io {
+                    path: path.clone(),
+                    source,
+                })
+                .and_then(|bytes| self.compile(name, &bytes));
+            match compiled {
+                Ok(plugin) => {
+                    plugins.insert(name.to_string(), Arc::new(plugin));
+                }
+                Err(e) => {
+                    if let Some(previous) = state.plugins.get(name) {
+                        plugins.insert(name.to_string(), previous.clone());
+                    }
+                    load.errors.push((path.clone(), e));
+                }
+            }
+        }
+
+        let mut registry = registry.write().unwrap_or_else(|e| e.into_inner());
+        for (name, plugin) in &state.plugins {
+            if !plugins.contains_key(name) {
+                registry.unregister(&plugin.meta.tool_name());
+            }
+        }
+        for plugin in plugins.values() {
+            let meta = &plugin.meta;
+            let registered = registry.register(RegisteredTool {
+                definition: ToolDefinition {
+                    name: meta.tool_name(),
+                    description: meta.description.clone(),
+                    parameters: meta.parameters(),
+                },
+                trust: state.trust,
+                tags: vec![TOOL_TAG.to_string(), format!("{TOOL_TAG}:{}", meta.name)],
+                enabled: true,
+            });
+            if let Err(e) = registered {
+                tracing::warn!(plugin = %meta.name, error = %e, "Skipping WASM plugin tool");
+            }
+        }
+        load.loaded = plugins.keys().cloned().collect();
+        load.loaded.sort();
+        state.plugins = plugins;
+        load
+    }
+
+    /// Metadata of the loaded plugins, sorted by name.
+    pub fn plugins(&self) -> Vec<WasmPluginMeta> {
+        let state = self.state.read().unwrap_or_else(|e| e.into_inner());
+        let mut plugins: Vec<WasmPluginMeta> =
+            state.plugins.values().map(|p| p.meta.clone()).collect();
+        plugins.sort_by(|a, b| a.name.cmp(&b.name));
+        plugins
+    }
+
+    /// Registered names of all plugin tools, sorted.
+    pub fn tool_names(&self) -> Vec<String> {
+        self.plugins()
+            .iter()
+            .map(WasmPluginMeta::tool_name)
+            .collect()
+    }
+
+    /// Run a plugin with `inputs`, calling tools through `tools`.
+    ///
+    /// Blocks until the plugin returns; call it from a blocking thread.
+    pub fn run(
+        &self,
+        name: &str,
+        mut inputs: HashMap<String, String>,
+        tools: Arc<dyn ToolCaller>,
+    ) -> Result<WasmRun, WasmError> {
+        let (plugin, fuel, memory_bytes) = {
+            let state = self.state.read().unwrap_or_else(|e| e.into_inner());
+            let plugin = state
+                .plugins
+                .get(name)
+                .cloned()
+                .ok_or_else(|| WasmError::UnknownPlugin(name.to_string()))?;
+            (plugin, state.fuel, state.memory_bytes)
+        };
+        for input in &plugin.meta.inputs {
+            if inputs.contains_key(&input.name) {
+                continue;
+            }
+            match (&input.default, input.required) {
+                (Some(default), _) => {
+                    inputs.insert(input.name.clone(), default.clone());
+                }
+                (None, true) => return Err(WasmError::MissingInput(input.name.clone())),
+                (None, false) => {}
+            }
+        }
+
+        let mut store = Store::new(
+            &self.engine,
+            RunState {
+                inputs,
+                outputs: BTreeMap::new(),
+                declared: plugin.meta.outputs.clone(),
+                logs: Vec::new(),
+                result: Vec::new(),
+                tools,
+                limits: StoreLimitsBuilder::new()
+                    .memory_size(memory_bytes)
+                    .instances(1)
+                    .build(),
+                plugin: name.to_string(),
+            },
+        );
+        store.limiter(|state| &mut state.limits);
+        store
+            .set_fuel(fuel)
+            .map_err(|e| WasmError::Trap(format!("{e:#}")))?;
+
+        let status = plugin
+            .instance
+            .instantiate(&mut store)
+            .and_then(|instance| instance.get_typed_func::<(), i32>(&mut store, "run"))
+            .and_then(|run| run.call(&mut store, ()));
+        let fuel_used = fuel - store.get_fuel().unwrap_or(0);
+        let status = status.map_err(|e| match e.downcast_ref::<Trap>() {
+            Some(Trap::OutOfFuel) => WasmError::OutOfFuel(fuel),
+            _ => WasmError::Trap(format!("{e:#}")),
+        })?;
+
+        let state = store.into_data();
+        if status != 0 {
+            let message = state
+                .logs
+                .iter()
+                .rev()
+                .find_map(|line| line.strip_prefix("::error::"))
+                .unwrap_or("no error was logged")
+                .to_string();
+            return Err(WasmError::Failed { status, message });
+        }
+        Ok(WasmRun {
+            outputs: state.outputs,
+            logs: state.logs,
+            fuel_used,
+        })
+    }
+
+    /// Run the plugin behind a registered tool name with the tool call's
+    /// JSON arguments, returning its outputs as a JSON object.
+    pub fn run_tool(
+        &self,
+        tool: &str,
+        arguments: &Value,
+        tools: Arc<dyn ToolCaller>,
+    ) -> Result<String, WasmError> {
+        let name = tool
+            .strip_prefix(TOOL_PREFIX)
+            .and_then(|rest| rest.strip_prefix(TOOL_NAME_SEPARATOR))
+            .ok_or_else(|| WasmError::UnknownPlugin(tool.to_string()))?;
+        let inputs = arguments
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter_map(|(key, value)| match value {
+                Value::Null => None,
+                Value::String(s) => Some((key.clone(), s.clone())),
+                other => Some((key.clone(), other.to_string())),
+            })
+            .collect();
+        let run = self.run(name, inputs, tools)?;
+        Ok(json!(run.outputs).to_string())
+    }
+}
+
+impl Default for WasmPluginHost {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Poll the plugin directory every `interval` and reload changed modules
+/// until shutdown.
+pub fn spawn_reloader(
+    host: Arc<WasmPluginHost>,
+    registry: Arc<RwLock<ToolRegistry>>,
+    interval: Duration,
+    mut shutdown_rx: broadcast::Receiver<ShutdownSignal>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.recv() => break,
+                _ = ticker.tick() => {
+                    let (host, registry) = (host.clone(), registry.clone());
+                    let reload =
+                        tokio::task::spawn_blocking(move || host.reload_if_changed(&registry)).await;
+                    let Ok(Some(load)) = reload else { continue };
+                    for (path, e) in &load.errors {
+                        warn!(path = %path.display(), error = %e, "WASM plugin failed to reload");
+                    }
+                    info!(loaded = ?load.loaded, "WASM plugins reloaded");
+                }
+            }
+        }
+    })
+}
+
+/// The `*.wasm` files in `dir` with their modification times and sizes,
+/// sorted by path. A missing directory has no files.
+fn fingerprint(dir: &Path) -> Vec<(PathBuf, Option<SystemTime>, u64)> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<_> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "wasm"))
+        .filter_map(|entry| {
+            let meta = entry.metadata().ok()?;
+            meta.is_file()
+                .then(|| (entry.path(), meta.modified().ok(), meta.len()))
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// The payload of the first custom section called `name` in a binary
+/// module.
+fn custom_section<'a>(bytes: &'a [u8], name: &str) -> Option<&'a [u8]> {
+    let mut rest = bytes.strip_prefix(b"\0asm")?.get(4..)?;
+    while let Some((&id, after_id)) = rest.split_first() {
+        let (size, used) = read_leb128(after_id)?;
+        let body = after_id.get(used..used + size)?;
+        rest = &after_id[used + size..];
+        if id == 0 {
+            let (len, used) = read_leb128(body)?;
+            if body.get(used..used + len)? == name.as_bytes() {
+                return Some(&body[used + len..]);
+            }
+        }
+    }
+    None
+}
+
+/// Decode an unsigned 32-bit LEB128 value, returning it and its length.
+fn read_leb128(bytes: &[u8]) -> Option<(usize, usize)> {
+    let mut value = 0usize;
+    for (i, &byte) in bytes.iter().enumerate().take(5) {
+        value |= usize::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+fn guest_memory(caller: &mut Caller<'_, RunState>) -> wasmtime::Result<wasmtime::Memory> {
+    caller
+        .get_export("memory")
+        .and_then(|export| export.into_memory())
+        .ok_or_else(|| wasmtime::Error::msg("plugin does not export memory"))
+}
+
+fn read_guest(caller: &mut Caller<'_, RunState>, ptr: i32, len: i32) -> wasmtime::Result<String> {
+    let memory = guest_memory(caller)?;
+    let mut buf = vec![0; usize::try_from(len)?];
+    memory.read(&*caller, usize::try_from(ptr)?, &mut buf)?;
+    String::from_utf8(buf).map_err(|_| wasmtime::Error::msg("string is not UTF-8"))
+}
+
+fn write_guest(caller: &mut Caller<'_, RunState>, ptr: i32, bytes: &[u8]) -> wasmtime::Result<i32> {
+    let memory = guest_memory(caller)?;
+    memory.write(&mut *caller, usize::try_from(ptr)?, bytes)?;
+    Ok(i32::try_from(bytes.len())?)
+}
+
+fn define_host_interface(linker: &mut Linker<RunState>) -> wasmtime::Result<()> {
+    linker.func_wrap(
+        HOST_MODULE,
+        "input_len",
+        |mut caller: Caller<'_, RunState>, name: i32, name_len: i32| {
+            let name = read_guest(&mut caller, name, name_len)?;
+            Ok(match caller.data().inputs.get(&name) {
+                Some(value) => i32::try_from(value.len())?,
+                None => -1,
+            })
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "input_read",
+        |mut caller: Caller<'_, RunState>, name: i32, name_len: i32, buf: i32| {
+            let name = read_guest(&mut caller, name, name_len)?;
+            match caller.data().inputs.get(&name).cloned() {
+                Some(value) => write_guest(&mut caller, buf, value.as_bytes()),
+                None => Ok(-1),
+            }
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "set_output",
+        |mut caller: Caller<'_, RunState>, name: i32, name_len: i32, value: i32, value_len: i32| {
+            let name = read_guest(&mut caller, name, name_len)?;
+            let value = read_guest(&mut caller, value, value_len)?;
+            let state = caller.data_mut();
+            if !state.declared.contains(&name) {
+                return Ok(-1);
+            }
+            state.outputs.insert(name, value);
+            Ok(0)
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "log",
+        |mut caller: Caller<'_, RunState>, level: i32, msg: i32, msg_len: i32| {
+            let message = read_guest(&mut caller, msg, msg_len)?;
+            let state = caller.data_mut();
+            let line = match level {
+                0 => format!("::debug::{message}"),
+                2 => format!("::warning::{message}"),
+                3.. => format!("::error::{message}"),
+                _ => message,
+            };
+            debug!(plugin = %state.plugin, "{line}");
+            state.logs.push(line);
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "call_tool",
+        |mut caller: Caller<'_, RunState>, name: i32, name_len: i32, args: i32, args_len: i32| {
+            let name = read_guest(&mut caller, name, name_len)?;
+            let args = read_guest(&mut caller, args, args_len)?;
+            let Ok(arguments) = serde_json::from_str::<Value>(&args) else {
+                return Ok(-1);
+            };
+            let tools = caller.data().tools.clone();
+            let (status, result) = match tools.call_tool(&name, arguments) {
+                Ok(output) => (0, output),
+                Err(message) => (1, message),
+            };
+            caller.data_mut().result = result.into_bytes();
+            Ok(status)
+        },
+    )?;
+    linker.func_wrap(HOST_MODULE, "result_len", |caller: Caller<'_, RunState>| {
+        Ok(i32::try_from(caller.data().result.len())?)
+    })?;
+    linker.func_wrap(
+        HOST_MODULE,
+        "result_read",
+        |mut caller: Caller<'_, RunState>, buf: i32| {
+            let result = caller.data().result.clone();
+            write_guest(&mut caller, buf, &result)
+        },
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IMPORTS: &str = r#"
+        (import "crustyclaw" "input_len" (func $input_len (param i32 i32) (result i32)))
+        (import "crustyclaw" "input_read" (func $input_read (param i32 i32 i32) (result i32)))
+        (import "crustyclaw" "set_output" (func $set_output (param i32 i32 i32 i32) (result i32)))
+        (import "crustyclaw" "log" (func $log (param i32 i32 i32)))
+        (import "crustyclaw" "call_tool" (func $call_tool (param i32 i32 i32 i32) (result i32)))
+        (import "crustyclaw" "result_len" (func $result_len (result i32)))
+        (import "crustyclaw" "result_read" (func $result_read (param i32) (result i32)))
+        (memory (export "memory") 1)
+    "#;
+
+    /// A module named `name` with `body` after the host imports.
+    fn module(name: &str, meta: &str, body: &str) -> Vec<u8> {
+        let sep = if meta.is_empty() { "" } else { "," };
+        let meta = format!(r#"{{"name":"{name}"{sep}{meta}}}"#).replace('"', "\\\"");
+        wat::parse_str(format!(
+            "(module {IMPORTS} {body} (@custom \"{METADATA_SECTION}\" \"{meta}\"))"
+        ))
+        .unwrap()
+    }
+
+    /// Sets `greeting` to "hello " followed by the `who` input.
+    fn greet() -> Vec<u8> {
+        module(
+            "greet",
+            r#""version":"1.0.0","description":"Greets someone","inputs":[{"name":"who","required":true},{"name":"punct","default":"!"}],"outputs":["greeting"]"#,
+            r#"
+            (data (i32.const 0) "who")
+            (data (i32.const 8) "punct")
+            (data (i32.const 16) "greeting")
+            (data (i32.const 32) "hello ")
+            (func (export "run") (result i32)
+                (local $n i32) (local $p i32)
+                (local.set $n (call $input_read (i32.const 0) (i32.const 3) (i32.const 38)))
+                (local.set $p (call $input_read (i32.const 8) (i32.const 5)
+                    (i32.add (i32.const 38) (local.get $n))))
+                (drop (call $set_output (i32.const 16) (i32.const 8) (i32.const 32)
+                    (i32.add (i32.const 6) (i32.add (local.get $n) (local.get $p)))))
+                (i32.const 0))
+            "#,
+        )
+    }
+
+    /// Calls the `tool` input (default `echo`) with the `args` input and
+    /// returns its result as `out`.
+    fn relay() -> Vec<u8> {
+        module(
+            "relay",
+            r#""inputs":[{"name":"args"},{"name":"tool","default":"echo"}],"outputs":["out"]"#,
+            r#"
+            (data (i32.const 0) "args")
+            (data (i32.const 8) "tool")
+            (data (i32.const 16) "out")
+            (data (i32.const 24) "tool failed")
+            (func (export "run") (result i32)
+                (local $n i32) (local $t i32) (local $status i32)
+                (local.set $n (call $input_read (i32.const 0) (i32.const 4) (i32.const 64)))
+                (local.set $t (call $input_read (i32.const 8) (i32.const 4) (i32.const 512)))
+                (local.set $status
+                    (call $call_tool (i32.const 512) (local.get $t) (i32.const 64) (local.get $n)))
+                (drop (call $result_read (i32.const 1024)))
+                (drop (call $set_output (i32.const 16) (i32.const 3) (i32.const 1024) (call $result_len)))
+                (if (local.get $status) (then
+                    (call $log (i32.const 3) (i32.const 24) (i32.const 11))
+                    (return (i32.const 2))))
+                (i32.const 0))
+            "#,
+        )
+    }
+
+    fn spin() -> Vec<u8> {
+        module(
+            "spin",
+            "",
+            r#"(func (export "run") (result i32) (loop $l (br $l)) (i32.const 0))"#,
+        )
+    }
+
+    fn host_with(
+        dir: &Path,
+        modules: &[(&str, Vec<u8>)],
+    ) -> (WasmPluginHost, RwLock<ToolRegistry>) {
+        for (file, bytes) in modules {
+            std::fs::write(dir.join(file), bytes).unwrap();
+        }
+        let host = WasmPluginHost::new();
+        let registry = RwLock::new(ToolRegistry::new());
+        let config = WasmPluginsConfig {
+            enabled: true,
+            dir: ".".to_string(),
+            fuel: 1_000_000,
+            ..WasmPluginsConfig::default()
+        };
+        let load = host.load(&config, dir, &registry);
+        assert!(load.errors.is_empty(), "{:?}", load.errors);
+        (host, registry)
+    }
+
+    #[test]
+    fn test_custom_section() {
+        let bytes = greet();
+        let meta: WasmPluginMeta =
+            serde_json::from_slice(custom_section(&bytes, METADATA_SECTION).unwrap()).unwrap();
+        assert_eq!(meta.name, "greet");
+        assert_eq!(meta.outputs, vec!["greeting"]);
+        assert!(custom_section(&bytes, "other").is_none());
+        assert!(custom_section(b"not wasm", METADATA_SECTION).is_none());
+    }
+
+    #[test]
+    fn test_load_registers_tools() {
+        let dir = tempfile::tempdir().unwrap();
+        let (host, registry) = host_with(dir.path(), &[("greet.wasm", greet())]);
+        assert_eq!(host.tool_names(), vec!["action__greet"]);
+
+        let registry = registry.read().unwrap();
+        let tool = registry.get("action__greet").unwrap();
+        assert_eq!(tool.trust, ToolTrust::Internal);
+        assert_eq!(tool.tags, vec!["wasm", "wasm:greet"]);
+        assert_eq!(tool.definition.description, "Greets someone");
+        assert_eq!(tool.definition.parameters["required"], json!(["who"]));
+        assert_eq!(
+            tool.definition.parameters["properties"]["punct"]["type"],
+            "string"
+        );
+    }
+
+    #[test]
+    fn test_run_sets_outputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let (host, _) = host_with(dir.path(), &[("greet.wasm", greet())]);
+
+        let inputs = HashMap::from([("who".to_string(), "world".to_string())]);
+        let run = host.run("greet", inputs, Arc::new(NoTools)).unwrap();
+        assert_eq!(run.outputs["greeting"], "hello world!");
+        assert!(run.fuel_used > 0);
+
+        let output = host
+            .run_tool(
+                "action__greet",
+                &json!({"who": "there", "punct": "?"}),
+                Arc::new(NoTools),
+            )
+            .unwrap();
+        assert_eq!(output, r#"{"greeting":"hello there?"}"#);
+
+        assert!(matches!(
+            host.run("greet", HashMap::new(), Arc::new(NoTools)),
+            Err(WasmError::MissingInput(name)) if name == "who"
+        ));
+        assert!(matches!(
+            host.run("nope", HashMap::new(), Arc::new(NoTools)),
+            Err(WasmError::UnknownPlugin(_))
+        ));
+    }
+
+    #[test]
+    fn test_tool_calls() {
+        let dir = tempfile::tempdir().unwrap();
+        let (host, _) = host_with(dir.path(), &[("relay.wasm", relay())]);
+        let echo = Arc::new(|name: &str, args: Value| match args["fail"].as_bool() {
+            Some(true) => Err(format!("{name} refused")),
+            _ => Ok(format!("{name}: {}", args["text"])),
+        });
+
+        let inputs = HashMap::from([("args".to_string(), r#"{"text":"hi"}"#.to_string())]);
+        let run = host.run("relay", inputs, echo.clone()).unwrap();
+        assert_eq!(run.outputs["out"], r#"echo: "hi""#);
+
+        let inputs = HashMap::from([("args".to_string(), r#"{"fail":true}"#.to_string())]);
+        let err = host.run("relay", inputs, echo).unwrap_err();
+        assert!(
+            matches!(&err, WasmError::Failed { status: 2, message } if message == "tool failed"),
+            "{err}"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_executor_runs_plugin_tools() {
+        use crate::context::ToolExecutor;
+        use crate::llm::types::ToolCall;
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("note.txt"), "remember the milk").unwrap();
+        std::fs::write(dir.path().join("relay.wasm"), relay()).unwrap();
+        let registry = Arc::new(RwLock::new(ToolRegistry::with_defaults()));
+        let host = Arc::new(WasmPluginHost::new());
+        let config = WasmPluginsConfig {
+            dir: ".".to_string(),
+            ..WasmPluginsConfig::default()
+        };
+        host.load(&config, dir.path(), &registry);
+        let executor = ToolExecutor::new(registry, dir.path()).with_wasm(host);
+
+        let call = |tool: &str| ToolCall {
+            id: "call-1".to_string(),
+            name: "action__relay".to_string(),
+            arguments: json!({"tool": tool, "args": r#"{"path":"note.txt"}"#}),
+        };
+        let output = executor
+            .run(&call("read_file"), ToolTrust::Internal)
+            .await
+            .unwrap();
+        assert_eq!(output, r#"{"out":"remember the milk"}"#);
+
+        // Plugins cannot call themselves or other plugins.
+        let err = executor
+            .run(&call("action__relay"), ToolTrust::Internal)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("tool failed"), "{err}");
+
+        // The plugin tool itself is scoped by trust.
+        assert!(
+            executor
+                .run(&call("read_file"), ToolTrust::Public)
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_fuel_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let (host, _) = host_with(dir.path(), &[("spin.wasm", spin())]);
+        let err = host
+            .run("spin", HashMap::new(), Arc::new(NoTools))
+            .unwrap_err();
+        assert!(matches!(err, WasmError::OutOfFuel(1_000_000)), "{err}");
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let big = module(
+            "big",
+            "",
+            r#"(func (export "run") (result i32)
+                (if (i32.lt_s (memory.grow (i32.const 100)) (i32.const 0))
+                    (then (return (i32.const 1))))
+                (i32.const 0))"#,
+        );
+        std::fs::write(dir.path().join("big.wasm"), big).unwrap();
+        let host = WasmPluginHost::new();
+        let registry = RwLock::new(ToolRegistry::new());
+        let config = WasmPluginsConfig {
+            dir: ".".to_string(),
+            memory_bytes: 1024 * 1024,
+            ..WasmPluginsConfig::default()
+        };
+        host.load(&config, dir.path(), &registry);
+        let err = host
+            .run("big", HashMap::new(), Arc::new(NoTools))
+            .unwrap_err();
+        assert!(matches!(err, WasmError::Failed { status: 1, .. }), "{err}");
+    }
+
+    #[test]
+    fn test_rejects_invalid_modules() {
+        let dir = tempfile::tempdir().unwrap();
+        let wasi = wat::parse_str(format!(
+            r#"(module
+                (import "wasi_snapshot_preview1" "fd_write" (func (param i32 i32 i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (func (export "run") (result i32) (i32.const 0))
+                (@custom "{METADATA_SECTION}" "{{\"name\":\"wasi\"}}"))"#
+        ))
+        .unwrap();
+        let no_meta = wat::parse_str(
+            r#"(module (memory (export "memory") 1) (func (export "run") (result i32) (i32.const 0)))"#,
+        )
+        .unwrap();
+        for (file, bytes) in [
+            ("wasi.wasm", wasi),
+            ("no_meta.wasm", no_meta),
+            ("renamed.wasm", greet()),
+            ("garbage.wasm", b"garbage".to_vec()),
+        ] {
+            std::fs::write(dir.path().join(file), bytes).unwrap();
+        }
+        let host = WasmPluginHost::new();
+        let registry = RwLock::new(ToolRegistry::new());
+        let load = host.load(
+            &WasmPluginsConfig {
+                dir: ".".to_string(),
+                ..WasmPluginsConfig::default()
+            },
+            dir.path(),
+            &registry,
+        );
+        assert!(load.loaded.is_empty());
+        let errors: HashMap<String, String> = load
+            .errors
+            .iter()
+            .map(|(path, e)| {
+                (
+                    path.file_name().unwrap().to_string_lossy().into(),
+                    e.to_string(),
+                )
+            })
+            .collect();
+        assert!(errors["wasi.wasm"].contains("wasi_snapshot_preview1::fd_write"));
+        assert!(errors["no_meta.wasm"].contains("missing crustyclaw-plugin"));
+        assert!(errors["renamed.wasm"].contains("named \"greet\""));
+        assert!(errors["garbage.wasm"].contains("invalid plugin"));
+    }
+
+    #[test]
+    fn test_hot_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let (host, registry) = host_with(dir.path(), &[("greet.wasm", greet())]);
+        assert!(host.reload_if_changed(&registry).is_none());
+
+        // A broken update keeps the loaded version.
+        std::fs::write(dir.path().join("greet.wasm"), b"truncated").unwrap();
+        let load = host.reload_if_changed(&registry).unwrap();
+        assert_eq!(load.errors.len(), 1);
+        assert_eq!(load.loaded, vec!["greet"]);
+
+        std::fs::write(dir.path().join("spin.wasm"), spin()).unwrap();
+        std::fs::remove_file(dir.path().join("greet.wasm")).unwrap();
+        let load = host.reload_if_changed(&registry).unwrap();
+        assert!(load.errors.is_empty());
+        assert_eq!(host.tool_names(), vec!["action__spin"]);
+        let registry = registry.read().unwrap();
+        assert!(registry.get("action__greet").is_none());
+        assert!(registry.get("action__spin").is_some());
+    }
+}
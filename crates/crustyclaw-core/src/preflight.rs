@@ -0,0 +1,383 @@
+//! Startup preflight — fail-fast checks run before the daemon starts serving.
+//!
+//! [`Daemon::run`](crate::Daemon::run) runs every check and refuses to start
+//! if any fails, printing one consolidated [`PreflightReport`] instead of
+//! failing piecemeal once a skill first needs the broken component:
+//!
+//! | Check | Fails when |
+//! |-------|-----------|
+//! | `socket_dir` | the IPC socket directory cannot be created or written |
+//! | `staging_dir` | the secrets staging directory is not a directory or is accessible by group/others |
+//! | `isolation` | the configured backend is unavailable, or weaker than `default_trust_tier` requires |
+//! | `secrets` | any `[[secrets.entries]]` source cannot be resolved |
+//! | `llm` | the configured LLM endpoint does not accept a TCP connection |
+//!
+//! Non-fatal issues still go to the [`WarningCollector`](crate::WarningCollector).
+//! Operators can bypass preflight with `crustyclaw start --skip-preflight`.
+
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+
+use crustyclaw_config::{AppConfig, LlmConfig, LlmProviderKind, SecretsConfig};
+
+use crate::ipc::server::socket_path_from_config;
+use crate::isolation::{
+    BackendPreference, IsolationLevel, SandboxBackend, TrustBasedSelector, TrustTier,
+    select_backend,
+};
+use crate::secrets::SecretStore;
+
+/// How long to wait for the LLM endpoint to accept a connection.
+pub const LLM_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Outcome of a single preflight check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreflightCheck {
+    /// Short check name (e.g. `"socket_dir"`).
+    pub name: &'static str,
+    /// Whether the check passed.
+    pub passed: bool,
+    /// What was checked, or why it failed and how to fix it.
+    pub detail: String,
+}
+
+impl PreflightCheck {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            passed: true,
+            detail: detail.into(),
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            passed: false,
+            detail: detail.into(),
+        }
+    }
+}
+
+impl fmt::Display for PreflightCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = if self.passed { "ok" } else { "FAIL" };
+        write!(f, "[{status}] {}: {}", self.name, self.detail)
+    }
+}
+
+/// Results of all preflight checks, in the order they ran.
+#[derive(Debug, Clone, Default)]
+pub struct PreflightReport {
+    /// Every check that ran.
+    pub checks: Vec<PreflightCheck>,
+}
+
+impl PreflightReport {
+    /// Whether every check passed.
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|c| c.passed)
+    }
+
+    /// The checks that failed.
+    pub fn failures(&self) -> impl Iterator<Item = &PreflightCheck> {
+        self.checks.iter().filter(|c| !c.passed)
+    }
+}
+
+impl fmt::Display for PreflightReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failed = self.failures().count();
+        write!(
+            f,
+            "{failed} of {} preflight checks failed",
+            self.checks.len()
+        )?;
+        for check in self.failures() {
+            write!(f, "\n  {check}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Run every preflight check against `config`.
+pub async fn run(config: &AppConfig) -> PreflightReport {
+    PreflightReport {
+        checks: vec![
+            check_socket_dir(&socket_path_from_config(config)),
+            check_staging_dir(Path::new(&config.secrets.staging_dir)),
+            check_isolation(config),
+            check_secrets(&config.secrets),
+            check_llm(&config.llm, LLM_PROBE_TIMEOUT).await,
+        ],
+    }
+}
+
+/// The IPC socket's directory must exist (or be creatable) and be writable.
+pub(crate) fn check_socket_dir(socket_path: &Path) -> PreflightCheck {
+    const NAME: &str = "socket_dir";
+    let Some(dir) = socket_path.parent().filter(|d| !d.as_os_str().is_empty()) else {
+        return PreflightCheck::pass(NAME, "socket in current directory");
+    };
+    if let Err(e) = std::fs::create_dir_all(dir) {
+        return PreflightCheck::fail(NAME, format!("cannot create {}: {e}", dir.display()));
+    }
+    let probe = dir.join(format!(".crustyclaw-preflight-{}", std::process::id()));
+    match std::fs::write(&probe, b"") {
+        Ok(()) => {
+            std::fs::remove_file(&probe).ok();
+            PreflightCheck::pass(NAME, format!("{} is writable", dir.display()))
+        }
+        Err(e) => PreflightCheck::fail(NAME, format!("{} is not writable: {e}", dir.display())),
+    }
+}
+
+/// The secrets staging directory, if it exists, must be private to the owner.
+pub(crate) fn check_staging_dir(dir: &Path) -> PreflightCheck {
+    const NAME: &str = "staging_dir";
+    let metadata = match std::fs::metadata(dir) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return PreflightCheck::pass(NAME, format!("{} not created yet", dir.display()));
+        }
+        Err(e) => {
+            return PreflightCheck::fail(NAME, format!("cannot stat {}: {e}", dir.display()));
+        }
+    };
+    if !metadata.is_dir() {
+        return PreflightCheck::fail(NAME, format!("{} is not a directory", dir.display()));
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = metadata.permissions().mode() & 0o777;
+        if mode & 0o077 != 0 {
+            return PreflightCheck::fail(
+                NAME,
+                format!(
+                    "{} has mode {mode:03o}; it must not be accessible by group or others (chmod 700)",
+                    dir.display()
+                ),
+            );
+        }
+    }
+    PreflightCheck::pass(NAME, format!("{} is private", dir.display()))
+}
+
+/// The backend skills will run on must be available and strong enough for
+/// `isolation.default_trust_tier`.
+pub(crate) fn check_isolation(config: &AppConfig) -> PreflightCheck {
+    let (backend, required) = resolve_isolation(config);
+    check_backend_level(backend.as_ref(), required)
+}
+
+/// The backend skills run on by default, and the isolation level
+/// `isolation.default_trust_tier` requires of it, if set.
+pub(crate) fn resolve_isolation(
+    config: &AppConfig,
+) -> (Box<dyn SandboxBackend>, Option<IsolationLevel>) {
+    let iso = &config.isolation;
+    let tier = iso
+        .default_trust_tier
+        .as_deref()
+        .and_then(TrustTier::from_str_loose);
+    let selector = TrustBasedSelector::from_config(iso);
+    let backend = match tier {
+        Some(tier) => selector.select(tier),
+        None => {
+            let pref = match iso.backend.as_str() {
+                "docker" => BackendPreference::Docker,
+                "podman" => BackendPreference::Podman,
+                "firecracker" => BackendPreference::Firecracker,
+                "apple-vz" => BackendPreference::AppleVz,
+                "linux-ns" => BackendPreference::LinuxNamespace,
+                "noop" => BackendPreference::Noop,
+                _ => BackendPreference::Auto,
+            };
+            select_backend(&pref)
+        }
+    };
+    (backend, tier.map(|tier| selector.required_level(tier)))
+}
+
+pub(crate) fn check_backend_level(
+    backend: &dyn SandboxBackend,
+    required: Option<IsolationLevel>,
+) -> PreflightCheck {
+    const NAME: &str = "isolation";
+    if !backend.available() {
+        return PreflightCheck::fail(
+            NAME,
+            format!("backend '{}' is not available on this host", backend.name()),
+        );
+    }
+    let provided = IsolationLevel::of_backend(backend.name());
+    match (required, provided) {
+        (Some(required), Some(provided)) if provided < required => PreflightCheck::fail(
+            NAME,
+            format!(
+                "backend '{}' provides {provided} but the default trust tier requires {required}",
+                backend.name()
+            ),
+        ),
+        _ => PreflightCheck::pass(NAME, format!("backend '{}' available", backend.name())),
+    }
+}
+
+/// Every configured secret must be resolvable from its source.
+pub(crate) fn check_secrets(config: &SecretsConfig) -> PreflightCheck {
+    const NAME: &str = "secrets";
+    let unresolved = SecretStore::unresolved(config);
+    if unresolved.is_empty() {
+        return PreflightCheck::pass(NAME, format!("{} secrets resolved", config.entries.len()));
+    }
+    let details: Vec<String> = unresolved
+        .iter()
+        .map(|(name, e)| format!("'{name}' ({e})"))
+        .collect();
+    PreflightCheck::fail(NAME, format!("cannot resolve {}", details.join(", ")))
+}
+
+/// Whether an LLM provider is configured: an API key or custom base URL,
+/// or the local `llamacpp` provider.
+pub(crate) fn llm_configured(config: &LlmConfig) -> bool {
+    let has_key =
+        !config.api_key.is_empty() || std::env::var_os("CRUSTYCLAW_LLM_API_KEY").is_some();
+    has_key || config.base_url.is_some() || config.provider == LlmProviderKind::LlamaCpp
+}
+
+/// The LLM endpoint must accept a TCP connection within `timeout`.
+///
+/// Skipped when no provider is configured (no API key and no custom base
+/// URL, for a provider other than the local `llamacpp`). Only reachability is checked — no request is sent, so no tokens
+/// are spent and the API key is not validated.
+pub(crate) async fn check_llm(config: &LlmConfig, timeout: Duration) -> PreflightCheck {
+    const NAME: &str = "llm";
+    if !llm_configured(config) {
+        return PreflightCheck::pass(NAME, "no provider configured; skipped");
+    }
+
+    let url = config.base_url.as_deref().unwrap_or(match config.provider {
+        LlmProviderKind::Anthropic => "https://api.anthropic.com",
+        LlmProviderKind::OpenAi => "https://api.openai.com",
+        LlmProviderKind::LlamaCpp => "http://127.0.0.1:8080",
+    });
+    let parsed = match reqwest::Url::parse(url) {
+        Ok(parsed) => parsed,
+        Err(e) => return PreflightCheck::fail(NAME, format!("invalid URL {url:?}: {e}")),
+    };
+    let (Some(host), Some(port)) = (parsed.host_str(), parsed.port_or_known_default()) else {
+        return PreflightCheck::fail(NAME, format!("URL {url:?} has no host"));
+    };
+    let addr = format!("{host}:{port}");
+
+    match tokio::time::timeout(timeout, tokio::net::TcpStream::connect(&addr)).await {
+        Ok(Ok(_)) => PreflightCheck::pass(NAME, format!("{addr} reachable")),
+        Ok(Err(e)) => PreflightCheck::fail(NAME, format!("{addr} unreachable: {e}")),
+        Err(_) => PreflightCheck::fail(
+            NAME,
+            format!("{addr} did not respond within {}s", timeout.as_secs()),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::isolation::NoopBackend;
+
+    #[test]
+    fn test_socket_dir_writable() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let check = check_socket_dir(&tmp.path().join("run/crustyclaw.sock"));
+        assert!(check.passed, "{check}");
+        assert!(tmp.path().join("run").is_dir());
+    }
+
+    #[test]
+    fn test_socket_dir_under_file_fails() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let file = tmp.path().join("not-a-dir");
+        std::fs::write(&file, b"").unwrap();
+        let check = check_socket_dir(&file.join("crustyclaw.sock"));
+        assert!(!check.passed);
+        assert!(check.detail.contains("cannot create"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_staging_dir_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dir = tmp.path().join("staging");
+        assert!(check_staging_dir(&dir).passed);
+
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let check = check_staging_dir(&dir);
+        assert!(!check.passed);
+        assert!(check.detail.contains("mode 755"));
+
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700)).unwrap();
+        assert!(check_staging_dir(&dir).passed);
+    }
+
+    #[test]
+    fn test_backend_level() {
+        assert!(check_backend_level(&NoopBackend, None).passed);
+        assert!(check_backend_level(&NoopBackend, Some(IsolationLevel::L1Container)).passed);
+
+        let check = check_backend_level(&NoopBackend, Some(IsolationLevel::L3MicroVm));
+        assert!(!check.passed);
+        assert!(check.detail.contains("requires L3 (microVM)"));
+    }
+
+    #[test]
+    fn test_secrets_unresolved() {
+        let config = AppConfig::parse(
+            "[[secrets.entries]]\nname = \"missing\"\nenv_var = \"CRUSTYCLAW_TEST_PREFLIGHT_UNSET\"\ninject_env = \"X\"\n",
+        )
+        .unwrap();
+        let check = check_secrets(&config.secrets);
+        assert!(!check.passed);
+        assert!(check.detail.contains("'missing'"));
+        assert!(check.detail.contains("CRUSTYCLAW_TEST_PREFLIGHT_UNSET"));
+
+        assert!(check_secrets(&SecretsConfig::default()).passed);
+    }
+
+    #[tokio::test]
+    async fn test_llm_reachability() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let config = LlmConfig {
+            base_url: Some(format!("http://127.0.0.1:{port}/v1")),
+            ..LlmConfig::default()
+        };
+        let check = check_llm(&config, LLM_PROBE_TIMEOUT).await;
+        assert!(check.passed, "{check}");
+
+        drop(listener);
+        let check = check_llm(&config, LLM_PROBE_TIMEOUT).await;
+        assert!(!check.passed);
+        assert!(check.detail.contains("unreachable"));
+    }
+
+    #[test]
+    fn test_report_lists_only_failures() {
+        let report = PreflightReport {
+            checks: vec![
+                PreflightCheck::pass("socket_dir", "ok"),
+                PreflightCheck::fail("secrets", "cannot resolve 'a'"),
+                PreflightCheck::fail("llm", "unreachable"),
+            ],
+        };
+        assert!(!report.passed());
+        assert_eq!(
+            report.to_string(),
+            "2 of 3 preflight checks failed\n  [FAIL] secrets: cannot resolve 'a'\n  [FAIL] llm: unreachable"
+        );
+    }
+}
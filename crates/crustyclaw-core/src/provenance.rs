@@ -0,0 +1,85 @@
+//! Policy provenance — which policy bundle the daemon enforces.
+//!
+//! Each time the daemon applies a config whose policy came from a
+//! `policy.bundle`, it appends a [`PolicyAuditRecord`] with the bundle's
+//! digest and signer to the [`AUDIT_FILE`] audit log, so every decision can
+//! be traced to the exact policy file that made it.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use tracing::warn;
+
+use crustyclaw_config::bundle::PolicyBundle;
+
+use crate::storage::AuditLog;
+
+/// Key of the policy audit log in the audit namespace; a file in the
+/// daemon's state directory with filesystem storage.
+pub const AUDIT_FILE: &str = "policy-bundles.jsonl";
+
+/// One entry in the policy audit trail.
+#[derive(Debug, Clone, Serialize)]
+pub struct PolicyAuditRecord {
+    /// `loaded` at startup, `reloaded` after a config reload.
+    pub event: &'static str,
+    /// Path the bundle was read from.
+    pub path: String,
+    /// `sha256:<hex>` of the bundle.
+    pub digest: String,
+    /// Trusted key that signed the bundle; absent for an unsigned bundle.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signer: Option<String>,
+    /// Unix time of the event, in milliseconds.
+    pub at_ms: u64,
+}
+
+/// Append a record of `bundle` being applied to `log`.
+pub fn record(log: &AuditLog, event: &'static str, bundle: &PolicyBundle) {
+    let record = PolicyAuditRecord {
+        event,
+        path: bundle.path.display().to_string(),
+        digest: bundle.digest.clone(),
+        signer: bundle.signer.clone(),
+        at_ms: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0),
+    };
+    if let Err(e) = log.append(&record) {
+        warn!(log = log.key(), error = %e, "Failed to write policy audit record");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(AUDIT_FILE);
+        let log = AuditLog::file(&path);
+        let bundle = PolicyBundle {
+            path: "/etc/crustyclaw/policy.toml".into(),
+            digest: crustyclaw_config::bundle::digest(b"rules"),
+            signer: Some("release".to_string()),
+        };
+        record(&log, "loaded", &bundle);
+        record(
+            &log,
+            "reloaded",
+            &PolicyBundle {
+                signer: None,
+                ..bundle.clone()
+            },
+        );
+
+        let records = log.records().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["event"], "loaded");
+        assert_eq!(records[0]["digest"], bundle.digest.as_str());
+        assert_eq!(records[0]["signer"], "release");
+        assert!(records[1].get("signer").is_none());
+    }
+}
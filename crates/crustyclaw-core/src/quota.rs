@@ -0,0 +1,362 @@
+//! Per-role usage quotas — LLM tokens per day and sandbox executions per hour.
+//!
+//! The [`QuotaManager`] holds the `[quotas]` limits and an in-memory counter
+//! for each role and [`QuotaKind`]. Counters use fixed windows aligned to
+//! UTC (midnight for tokens, the top of the hour for executions) and reset
+//! when the window rolls over; they are not persisted across restarts.
+//!
+//! Callers check before doing the work and record what it actually cost.
+//! Token usage is only known after a model response, so a run may overshoot
+//! its daily limit by one request; the next request is denied.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crustyclaw_config::QuotasConfig;
+
+/// A resource with a per-role limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum QuotaKind {
+    /// LLM tokens (prompt and completion), per UTC day.
+    LlmTokens,
+    /// Sandboxed skill and command executions, per UTC hour.
+    SandboxExecutions,
+}
+
+impl QuotaKind {
+    /// All kinds, in display order.
+    pub const ALL: [QuotaKind; 2] = [Self::LlmTokens, Self::SandboxExecutions];
+
+    /// The kind's name, as shown by `crustyclaw quotas`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::LlmTokens => "llm_tokens",
+            Self::SandboxExecutions => "sandbox_executions",
+        }
+    }
+
+    /// Length of the counting window in seconds.
+    pub fn window_secs(self) -> u64 {
+        match self {
+            Self::LlmTokens => 24 * 60 * 60,
+            Self::SandboxExecutions => 60 * 60,
+        }
+    }
+}
+
+impl std::fmt::Display for QuotaKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Errors from quota checks.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum QuotaError {
+    #[error("{role} has used {used} of its {limit} {kind} quota; it resets in {retry_after_secs}s")]
+    Exceeded {
+        role: String,
+        kind: QuotaKind,
+        limit: u64,
+        used: u64,
+        retry_after_secs: u64,
+    },
+}
+
+/// Consumption of one kind by one role in the current window.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuotaUsage {
+    /// The role.
+    pub role: String,
+    /// What is being counted.
+    pub kind: QuotaKind,
+    /// Amount used in the current window.
+    pub used: u64,
+    /// The configured limit, or `None` when unlimited.
+    pub limit: Option<u64>,
+    /// Length of the window in seconds.
+    pub window_secs: u64,
+    /// Seconds until the window resets.
+    pub resets_in_secs: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Counter {
+    window_start: u64,
+    used: u64,
+}
+
+/// Tracks consumption per role and denies requests over the `[quotas]` limits.
+pub struct QuotaManager {
+    config: RwLock<QuotasConfig>,
+    counters: Mutex<HashMap<(String, QuotaKind), Counter>>,
+}
+
+impl QuotaManager {
+    /// Create a manager for the `[quotas]` section.
+    pub fn from_config(config: &QuotasConfig) -> Self {
+        Self {
+            config: RwLock::new(config.clone()),
+            counters: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Replace the limits, e.g. after a config reload. Counters are kept.
+    pub fn reconfigure(&self, config: &QuotasConfig) {
+        *self.config.write().unwrap_or_else(|e| e.into_inner()) = config.clone();
+    }
+
+    /// The limit for `role` and `kind`, or `None` when unlimited.
+    pub fn limit(&self, role: &str, kind: QuotaKind) -> Option<u64> {
+        let config = self.config.read().unwrap_or_else(|e| e.into_inner());
+        let quota = config.roles.get(role)?;
+        match kind {
+            QuotaKind::LlmTokens => quota.llm_tokens_per_day,
+            QuotaKind::SandboxExecutions => quota.sandbox_executions_per_hour,
+        }
+    }
+
+    /// Fail if `role` has no `kind` quota left in the current window.
+    pub fn check(&self, role: &str, kind: QuotaKind) -> Result<(), QuotaError> {
+        self.check_at(role, kind, now_secs())
+    }
+
+    /// Add `amount` to what `role` has used of `kind`.
+    pub fn record(&self, role: &str, kind: QuotaKind, amount: u64) {
+        self.record_at(role, kind, amount, now_secs());
+    }
+
+    /// Record `amount` only if it fits in what `role` has left of `kind`.
+    pub fn try_consume(&self, role: &str, kind: QuotaKind, amount: u64) -> Result<(), QuotaError> {
+        self.try_consume_at(role, kind, amount, now_secs())
+    }
+
+    /// Usage for every role with a configured limit or recorded consumption,
+    /// sorted by role and kind.
+    pub fn usage(&self) -> Vec<QuotaUsage> {
+        self.usage_at(now_secs())
+    }
+
+    fn check_at(&self, role: &str, kind: QuotaKind, now: u64) -> Result<(), QuotaError> {
+        let Some(limit) = self.limit(role, kind) else {
+            return Ok(());
+        };
+        let used = self.used_at(role, kind, now);
+        if used >= limit {
+            return Err(exceeded(role, kind, limit, used, now));
+        }
+        Ok(())
+    }
+
+    fn record_at(&self, role: &str, kind: QuotaKind, amount: u64, now: u64) {
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        let counter = current(&mut counters, role, kind, now);
+        counter.used = counter.used.saturating_add(amount);
+    }
+
+    fn try_consume_at(
+        &self,
+        role: &str,
+        kind: QuotaKind,
+        amount: u64,
+        now: u64,
+    ) -> Result<(), QuotaError> {
+        let limit = self.limit(role, kind);
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        let counter = current(&mut counters, role, kind, now);
+        if let Some(limit) = limit
+            && counter.used.saturating_add(amount) > limit
+        {
+            return Err(exceeded(role, kind, limit, counter.used, now));
+        }
+        counter.used = counter.used.saturating_add(amount);
+        Ok(())
+    }
+
+    fn used_at(&self, role: &str, kind: QuotaKind, now: u64) -> u64 {
+        let counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        counters
+            .get(&(role.to_string(), kind))
+            .filter(|c| c.window_start == window_start(kind, now))
+            .map_or(0, |c| c.used)
+    }
+
+    fn usage_at(&self, now: u64) -> Vec<QuotaUsage> {
+        let mut keys: Vec<(String, QuotaKind)> = {
+            let config = self.config.read().unwrap_or_else(|e| e.into_inner());
+            config
+                .roles
+                .keys()
+                .flat_map(|role| QuotaKind::ALL.map(|kind| (role.clone(), kind)))
+                .filter(|(role, kind)| {
+                    let quota = &config.roles[role];
+                    match kind {
+                        QuotaKind::LlmTokens => quota.llm_tokens_per_day.is_some(),
+                        QuotaKind::SandboxExecutions => quota.sandbox_executions_per_hour.is_some(),
+                    }
+                })
+                .collect()
+        };
+        keys.extend(
+            self.counters
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .keys()
+                .cloned(),
+        );
+        keys.sort();
+        keys.dedup();
+        keys.into_iter()
+            .map(|(role, kind)| QuotaUsage {
+                used: self.used_at(&role, kind, now),
+                limit: self.limit(&role, kind),
+                window_secs: kind.window_secs(),
+                resets_in_secs: resets_in(kind, now),
+                role,
+                kind,
+            })
+            .collect()
+    }
+}
+
+/// The counter for the current window, reset if the window has rolled over.
+fn current<'a>(
+    counters: &'a mut HashMap<(String, QuotaKind), Counter>,
+    role: &str,
+    kind: QuotaKind,
+    now: u64,
+) -> &'a mut Counter {
+    let start = window_start(kind, now);
+    let counter = counters.entry((role.to_string(), kind)).or_insert(Counter {
+        window_start: start,
+        used: 0,
+    });
+    if counter.window_start != start {
+        *counter = Counter {
+            window_start: start,
+            used: 0,
+        };
+    }
+    counter
+}
+
+fn exceeded(role: &str, kind: QuotaKind, limit: u64, used: u64, now: u64) -> QuotaError {
+    QuotaError::Exceeded {
+        role: role.to_string(),
+        kind,
+        limit,
+        used,
+        retry_after_secs: resets_in(kind, now),
+    }
+}
+
+fn window_start(kind: QuotaKind, now: u64) -> u64 {
+    now - now % kind.window_secs()
+}
+
+fn resets_in(kind: QuotaKind, now: u64) -> u64 {
+    window_start(kind, now) + kind.window_secs() - now
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2026-01-01T00:00:00Z.
+    const MIDNIGHT: u64 = 1_767_225_600;
+
+    fn manager() -> QuotaManager {
+        let config = crustyclaw_config::AppConfig::parse(
+            r#"
+            [quotas.roles.user]
+            llm_tokens_per_day = 1000
+            sandbox_executions_per_hour = 2
+
+            [quotas.roles.viewer]
+            sandbox_executions_per_hour = 0
+        "#,
+        )
+        .unwrap();
+        QuotaManager::from_config(&config.quotas)
+    }
+
+    #[test]
+    fn test_sandbox_executions_reset_hourly() {
+        let quotas = manager();
+        let kind = QuotaKind::SandboxExecutions;
+        let now = MIDNIGHT + 10 * 60;
+        quotas.try_consume_at("user", kind, 1, now).unwrap();
+        quotas.try_consume_at("user", kind, 1, now).unwrap();
+        assert_eq!(
+            quotas.try_consume_at("user", kind, 1, now),
+            Err(QuotaError::Exceeded {
+                role: "user".to_string(),
+                kind,
+                limit: 2,
+                used: 2,
+                retry_after_secs: 50 * 60,
+            })
+        );
+        assert!(quotas.try_consume_at("viewer", kind, 1, now).is_err());
+        assert!(quotas.try_consume_at("admin", kind, 100, now).is_ok());
+
+        quotas
+            .try_consume_at("user", kind, 1, MIDNIGHT + 60 * 60)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_llm_tokens_check_then_record() {
+        let quotas = manager();
+        let kind = QuotaKind::LlmTokens;
+        let now = MIDNIGHT + 60;
+        quotas.check_at("user", kind, now).unwrap();
+        quotas.record_at("user", kind, 1200, now);
+        let err = quotas.check_at("user", kind, now).unwrap_err();
+        assert!(
+            err.to_string().contains("1200 of its 1000 llm_tokens"),
+            "{err}"
+        );
+        assert!(quotas.check_at("viewer", kind, now).is_ok());
+
+        let tomorrow = MIDNIGHT + 24 * 60 * 60;
+        quotas.check_at("user", kind, tomorrow).unwrap();
+    }
+
+    #[test]
+    fn test_usage_and_reconfigure() {
+        let quotas = manager();
+        let now = MIDNIGHT + 30 * 60;
+        quotas.record_at("user", QuotaKind::LlmTokens, 300, now);
+        quotas.record_at("admin", QuotaKind::LlmTokens, 50, now);
+
+        let usage = quotas.usage_at(now);
+        let rows: Vec<(&str, QuotaKind, u64, Option<u64>)> = usage
+            .iter()
+            .map(|u| (u.role.as_str(), u.kind, u.used, u.limit))
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ("admin", QuotaKind::LlmTokens, 50, None),
+                ("user", QuotaKind::LlmTokens, 300, Some(1000)),
+                ("user", QuotaKind::SandboxExecutions, 0, Some(2)),
+                ("viewer", QuotaKind::SandboxExecutions, 0, Some(0)),
+            ]
+        );
+        assert_eq!(usage[1].resets_in_secs, 24 * 60 * 60 - 30 * 60);
+        assert_eq!(usage[2].resets_in_secs, 30 * 60);
+
+        quotas.reconfigure(&QuotasConfig::default());
+        assert_eq!(quotas.limit("user", QuotaKind::LlmTokens), None);
+        assert_eq!(quotas.usage_at(now).len(), 2);
+    }
+}
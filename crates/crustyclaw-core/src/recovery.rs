@@ -0,0 +1,395 @@
+//! Crash recovery — journal of in-flight runs and cleanup after an unclean
+//! restart.
+//!
+//! Every skill run started through the [`SkillRegistry`](crate::skill::SkillRegistry)
+//! is recorded in a [`RunJournal`] persisted at `<daemon.state_dir>/runs.json`
+//! and removed when the run ends. Anything still in the journal when the
+//! daemon starts was interrupted by a crash or kill. At startup the daemon:
+//!
+//! 1. reaps orphaned sandboxes left behind by the isolation backend (e.g.
+//!    containers labelled `crustyclaw.sandbox=*`),
+//! 2. marks each interrupted run failed, appending a [`FailedRun`] with an
+//!    explanation to `<daemon.state_dir>/failed-runs.jsonl`, and
+//! 3. publishes a notice on the message bus to the conversation the run
+//!    came from, if any.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+/// File name of the in-flight run journal inside the state directory.
+pub const JOURNAL_FILE: &str = "runs.json";
+
+/// File name of the failed-run log inside the state directory.
+pub const FAILED_RUNS_FILE: &str = "failed-runs.jsonl";
+
+/// Errors from the run journal.
+#[derive(Debug, thiserror::Error)]
+pub enum RecoveryError {
+    #[error("run journal I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("corrupt run journal {path}: {reason}")]
+    Corrupt { path: PathBuf, reason: String },
+}
+
+/// Where a run was requested from, so a notice can be routed back.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunOrigin {
+    /// Conversation the run belongs to.
+    pub conversation: String,
+    /// Channel the conversation is on (e.g. "signal").
+    pub channel: String,
+    /// Remote party on the channel, if any.
+    #[serde(default)]
+    pub peer: Option<String>,
+}
+
+/// Minimal persisted state of an in-flight run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunRecord {
+    /// Run identifier, unique across daemon restarts.
+    pub run_id: String,
+    /// Skill being run.
+    pub skill: String,
+    /// Originating conversation, if the run was requested from one.
+    #[serde(default)]
+    pub origin: Option<RunOrigin>,
+    /// Last step the run reached (e.g. "queued", "running").
+    pub step: String,
+    /// Sandbox the run executes in (its `crustyclaw.sandbox` label), if any.
+    #[serde(default)]
+    pub sandbox: Option<String>,
+    /// Start time, in milliseconds since the Unix epoch.
+    pub started_at_ms: u64,
+}
+
+/// An interrupted run, marked failed during recovery.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailedRun {
+    #[serde(flatten)]
+    pub run: RunRecord,
+    /// Why the run failed.
+    pub reason: String,
+    /// Recovery time, in milliseconds since the Unix epoch.
+    pub failed_at_ms: u64,
+}
+
+impl FailedRun {
+    /// Mark `run` failed because the daemon stopped without finishing it.
+    pub fn interrupted(run: RunRecord, reaped: &[String]) -> Self {
+        let mut reason = format!("daemon exited unexpectedly while the run was {}", run.step);
+        if let Some(sandbox) = &run.sandbox {
+            if reaped.is_empty() {
+                reason.push_str(&format!("; sandbox '{sandbox}' was not found running"));
+            } else {
+                reason.push_str(&format!("; sandbox '{sandbox}' was reaped"));
+            }
+        }
+        Self {
+            run,
+            reason,
+            failed_at_ms: now_ms(),
+        }
+    }
+
+    /// Text of the notice sent to the originating conversation.
+    pub fn notice(&self) -> String {
+        format!(
+            "Run {} of skill '{}' did not complete: {}. Any partial results were discarded; please retry.",
+            self.run.run_id, self.run.skill, self.reason
+        )
+    }
+}
+
+#[derive(Default)]
+struct JournalState {
+    active: Vec<RunRecord>,
+    /// Runs found at startup that recovery has not yet taken.
+    orphans: Vec<RunRecord>,
+}
+
+/// Persistent journal of in-flight runs.
+pub struct RunJournal {
+    path: Option<PathBuf>,
+    state: Mutex<JournalState>,
+    /// Prefix making run IDs unique across restarts.
+    epoch: u64,
+    next_id: AtomicU64,
+}
+
+impl RunJournal {
+    /// Open the journal at `path`, loading runs left over from a previous
+    /// daemon as orphans (see [`take_orphans`](Self::take_orphans)).
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, RecoveryError> {
+        let path = path.into();
+        let orphans = match std::fs::read_to_string(&path) {
+            Ok(contents) if contents.trim().is_empty() => Vec::new(),
+            Ok(contents) => {
+                serde_json::from_str(&contents).map_err(|e| RecoveryError::Corrupt {
+                    path: path.clone(),
+                    reason: e.to_string(),
+                })?
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            path: Some(path),
+            state: Mutex::new(JournalState {
+                active: Vec::new(),
+                orphans,
+            }),
+            epoch: now_ms(),
+            next_id: AtomicU64::new(1),
+        })
+    }
+
+    /// A journal that is never persisted.
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            state: Mutex::new(JournalState::default()),
+            epoch: now_ms(),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Path the journal is persisted to, if any.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Take the runs left over from a previous daemon. They stay persisted
+    /// until taken, so a second crash before recovery does not lose them.
+    pub fn take_orphans(&self) -> Vec<RunRecord> {
+        let orphans = std::mem::take(&mut self.lock().orphans);
+        if !orphans.is_empty() {
+            self.persist();
+        }
+        orphans
+    }
+
+    /// Record the start of a run. The run is removed from the journal when
+    /// the returned entry is dropped.
+    pub fn begin(
+        self: &Arc<Self>,
+        skill: &str,
+        origin: Option<RunOrigin>,
+        sandbox: Option<String>,
+    ) -> JournalEntry {
+        let run_id = format!(
+            "{}-{}",
+            self.epoch,
+            self.next_id.fetch_add(1, Ordering::Relaxed)
+        );
+        self.lock().active.push(RunRecord {
+            run_id: run_id.clone(),
+            skill: skill.to_string(),
+            origin,
+            step: "queued".to_string(),
+            sandbox,
+            started_at_ms: now_ms(),
+        });
+        self.persist();
+        JournalEntry {
+            journal: self.clone(),
+            run_id,
+        }
+    }
+
+    /// Snapshot of the runs currently in flight.
+    pub fn active(&self) -> Vec<RunRecord> {
+        self.lock().active.clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, JournalState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Write the journal atomically. Failures are logged, not returned: a
+    /// journal write must never fail the run it describes.
+    fn persist(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        // Hold the lock across the write so concurrent updates land in order.
+        let state = self.lock();
+        let records: Vec<&RunRecord> = state.orphans.iter().chain(&state.active).collect();
+        if let Err(e) = write_atomic(
+            path,
+            &serde_json::to_vec_pretty(&records).unwrap_or_default(),
+        ) {
+            tracing::warn!(path = %path.display(), error = %e, "Failed to persist run journal");
+        }
+    }
+}
+
+/// An in-flight run in the journal; removes itself when dropped.
+pub struct JournalEntry {
+    journal: Arc<RunJournal>,
+    run_id: String,
+}
+
+impl JournalEntry {
+    /// The run's identifier.
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    /// Record the step the run has reached.
+    pub fn set_step(&self, step: &str) {
+        let changed = {
+            let mut state = self.journal.lock();
+            let record = state.active.iter_mut().find(|r| r.run_id == self.run_id);
+            record.map(|r| r.step = step.to_string()).is_some()
+        };
+        if changed {
+            self.journal.persist();
+        }
+    }
+}
+
+impl Drop for JournalEntry {
+    fn drop(&mut self) {
+        self.journal
+            .lock()
+            .active
+            .retain(|r| r.run_id != self.run_id);
+        self.journal.persist();
+    }
+}
+
+/// Append `runs` to the failed-run log at `path`.
+pub fn record_failed(path: &Path, runs: &[FailedRun]) -> Result<(), RecoveryError> {
+    if runs.is_empty() {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    for run in runs {
+        let line = serde_json::to_string(run).unwrap_or_default();
+        writeln!(file, "{line}")?;
+    }
+    Ok(())
+}
+
+fn write_atomic(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp = path.with_extension("json.partial");
+    std::fs::write(&tmp, data)?;
+    std::fs::rename(&tmp, path)
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn origin() -> RunOrigin {
+        RunOrigin {
+            conversation: "signal-_15550001".to_string(),
+            channel: "signal".to_string(),
+            peer: Some("+15550001".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_journal_tracks_in_flight_runs() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("state").join(JOURNAL_FILE);
+        let journal = Arc::new(RunJournal::open(&path).unwrap());
+        assert!(journal.take_orphans().is_empty());
+
+        let entry = journal.begin("deploy", Some(origin()), Some("deploy-box".to_string()));
+        entry.set_step("running");
+        let on_disk: Vec<RunRecord> =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(on_disk.len(), 1);
+        assert_eq!(on_disk[0].run_id, entry.run_id());
+        assert_eq!(on_disk[0].step, "running");
+        assert_eq!(on_disk[0].origin, Some(origin()));
+
+        drop(entry);
+        let on_disk: Vec<RunRecord> =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert!(on_disk.is_empty());
+        assert!(journal.active().is_empty());
+    }
+
+    #[test]
+    fn test_unfinished_runs_become_orphans() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join(JOURNAL_FILE);
+
+        let first = Arc::new(RunJournal::open(&path).unwrap());
+        let entry = first.begin("deploy", Some(origin()), None);
+        let run_id = entry.run_id().to_string();
+        // Simulate a crash: the entry is never dropped.
+        std::mem::forget(entry);
+
+        let second = Arc::new(RunJournal::open(&path).unwrap());
+        // A new run does not clobber the orphan before it is taken.
+        let _new = second.begin("backup", None, None);
+        let reopened = RunJournal::open(&path).unwrap();
+        assert_eq!(reopened.take_orphans().len(), 2);
+
+        let orphans = second.take_orphans();
+        assert_eq!(orphans.len(), 1);
+        assert_eq!(orphans[0].run_id, run_id);
+        assert!(second.take_orphans().is_empty());
+    }
+
+    #[test]
+    fn test_corrupt_journal_is_reported() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join(JOURNAL_FILE);
+        std::fs::write(&path, "{not json").unwrap();
+        assert!(matches!(
+            RunJournal::open(&path),
+            Err(RecoveryError::Corrupt { .. })
+        ));
+    }
+
+    #[test]
+    fn test_failed_runs_are_logged_with_reason() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join(FAILED_RUNS_FILE);
+        let run = RunRecord {
+            run_id: "1-1".to_string(),
+            skill: "deploy".to_string(),
+            origin: Some(origin()),
+            step: "running".to_string(),
+            sandbox: Some("deploy-box".to_string()),
+            started_at_ms: 0,
+        };
+        let failed = FailedRun::interrupted(run, &["abc123".to_string()]);
+        assert!(failed.reason.contains("while the run was running"));
+        assert!(failed.reason.contains("'deploy-box' was reaped"));
+        assert!(failed.notice().contains("Run 1-1 of skill 'deploy'"));
+
+        record_failed(&path, std::slice::from_ref(&failed)).unwrap();
+        record_failed(&path, &[failed]).unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(contents.lines().count(), 2);
+        let parsed: FailedRun = serde_json::from_str(contents.lines().next().unwrap()).unwrap();
+        assert_eq!(parsed.run.skill, "deploy");
+    }
+}
@@ -0,0 +1,677 @@
+//! Scheduled runs — skills and agent prompts fired by `[[schedules]]`.
+//!
+//! The [`Scheduler`] wakes at each schedule's next cron time (UTC), waits a
+//! random `0..=jitter_secs`, and runs the schedule's target:
+//!
+//! - `skill` — invoked with the schedule's `args`, like a chat command.
+//! - `prompt` — sent to the agent in a fresh [`ChatService`] session owned
+//!   by `schedule:<name>`, with the tools the schedule's `role` may use.
+//!
+//! A schedule never overlaps itself: a run that comes due while the
+//! previous one is still going is recorded as skipped. The last
+//! [`HISTORY_LEN`] runs of each schedule are listed by `GET /schedules`;
+//! `POST /schedules/{name}/run` starts one immediately. With a [`Storage`],
+//! each schedule's history is saved as `<name>.json` after every run and
+//! loaded again at startup. Schedules follow config reloads.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crustyclaw_config::cron::CronSchedule;
+use crustyclaw_config::{AppConfig, ScheduleConfig};
+
+use crate::chat::{ChatService, ToolFilter};
+use crate::daemon::ShutdownSignal;
+use crate::skill::{SkillInvocation, SkillRegistry};
+use crate::storage::{self, Storage};
+
+/// Runs kept per schedule.
+pub const HISTORY_LEN: usize = 20;
+
+/// Longest sleep between checks, so clock changes are noticed.
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+
+/// Characters of output kept in a run's message.
+const MESSAGE_LIMIT: usize = 512;
+
+/// Errors from starting a run by hand.
+#[derive(Debug, thiserror::Error)]
+pub enum ScheduleError {
+    #[error("schedule not found: {0}")]
+    NotFound(String),
+
+    #[error("schedule {0} is already running")]
+    Running(String),
+}
+
+/// What started a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Trigger {
+    /// The cron expression came due.
+    Cron,
+    /// `crustyclaw schedule run-now`.
+    Manual,
+}
+
+impl Trigger {
+    /// Lowercase name, as shown by `GET /schedules`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Trigger::Cron => "cron",
+            Trigger::Manual => "manual",
+        }
+    }
+}
+
+/// How a run ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunOutcome {
+    Succeeded,
+    Failed,
+    /// Not run because the previous run was still going.
+    Skipped,
+}
+
+impl RunOutcome {
+    /// Lowercase name, as shown by `GET /schedules`.
+    pub fn name(self) -> &'static str {
+        match self {
+            RunOutcome::Succeeded => "succeeded",
+            RunOutcome::Failed => "failed",
+            RunOutcome::Skipped => "skipped",
+        }
+    }
+}
+
+/// One entry of a schedule's run history.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduleRun {
+    /// Unix time the run started (after jitter).
+    pub started_at: u64,
+    pub duration_ms: u64,
+    pub trigger: Trigger,
+    pub outcome: RunOutcome,
+    /// The skill's output, the agent's reply, or why the run failed.
+    pub message: String,
+}
+
+/// A configured schedule and its recent runs.
+#[derive(Debug, Clone)]
+pub struct ScheduleStatus {
+    pub name: String,
+    pub cron: String,
+    /// `skill:<name>` or `prompt`.
+    pub target: String,
+    pub enabled: bool,
+    pub running: bool,
+    /// Unix time of the next cron run, if enabled.
+    pub next_run: Option<u64>,
+    /// Most recent first.
+    pub history: Vec<ScheduleRun>,
+}
+
+#[derive(Debug, Default)]
+struct ScheduleState {
+    /// The cron expression `next_run` was computed from.
+    cron: String,
+    next_run: Option<u64>,
+    running: bool,
+    history: VecDeque<ScheduleRun>,
+}
+
+/// Fires `[[schedules]]` entries and records their runs.
+pub struct Scheduler {
+    config: watch::Receiver<AppConfig>,
+    skills: Arc<SkillRegistry>,
+    chat: Option<Arc<ChatService>>,
+    storage: Option<Arc<dyn Storage>>,
+    state: Mutex<HashMap<String, ScheduleState>>,
+}
+
+impl Scheduler {
+    /// Create a scheduler for the schedules in `config`.
+    pub fn new(config: watch::Receiver<AppConfig>, skills: Arc<SkillRegistry>) -> Self {
+        Self {
+            config,
+            skills,
+            chat: None,
+            storage: None,
+            state: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Builder: persist run histories in the schedules namespace of
+    /// `storage`, loading the histories recorded there.
+    pub fn with_storage(mut self, storage: Arc<dyn Storage>) -> Self {
+        let keys = storage.list(storage::SCHEDULES).unwrap_or_else(|e| {
+            warn!(error = %e, "Failed to list schedule histories");
+            Vec::new()
+        });
+        let mut state = self.state();
+        for key in keys {
+            let Some(name) = key.strip_suffix(".json") else {
+                continue;
+            };
+            let history = match storage.get(storage::SCHEDULES, &key) {
+                Ok(Some(data)) => serde_json::from_slice::<VecDeque<ScheduleRun>>(&data)
+                    .map_err(|e| e.to_string()),
+                Ok(None) => continue,
+                Err(e) => Err(e.to_string()),
+            };
+            match history {
+                Ok(history) => state.entry(name.to_string()).or_default().history = history,
+                Err(e) => warn!(schedule = name, error = %e, "Failed to load schedule history"),
+            }
+        }
+        drop(state);
+        self.storage = Some(storage);
+        self
+    }
+
+    /// Builder: run `prompt` schedules through `chat`. Without it they fail.
+    pub fn with_chat(mut self, chat: Arc<ChatService>) -> Self {
+        self.chat = Some(chat);
+        self
+    }
+
+    /// Every configured schedule, in config order.
+    pub fn status(&self) -> Vec<ScheduleStatus> {
+        let schedules = self.config.borrow().schedules.clone();
+        let state = self.state();
+        schedules
+            .into_iter()
+            .map(|schedule| {
+                let current = state.get(&schedule.name);
+                ScheduleStatus {
+                    target: match &schedule.skill {
+                        Some(skill) => format!("skill:{skill}"),
+                        None => "prompt".to_string(),
+                    },
+                    enabled: schedule.enabled,
+                    running: current.is_some_and(|s| s.running),
+                    next_run: current
+                        .filter(|s| s.cron == schedule.cron && schedule.enabled)
+                        .and_then(|s| s.next_run),
+                    history: current
+                        .map(|s| s.history.iter().rev().cloned().collect())
+                        .unwrap_or_default(),
+                    name: schedule.name,
+                    cron: schedule.cron,
+                }
+            })
+            .collect()
+    }
+
+    /// Start `name` now, in the background. Disabled schedules may be run
+    /// this way too.
+    pub fn run_now(self: &Arc<Self>, name: &str) -> Result<(), ScheduleError> {
+        let schedule = self.claim(name)?;
+        info!(schedule = %name, "Schedule started by hand");
+        let scheduler = self.clone();
+        tokio::spawn(async move {
+            scheduler.execute(&schedule, Trigger::Manual).await;
+        });
+        Ok(())
+    }
+
+    /// Mark `name` running, returning its config.
+    fn claim(&self, name: &str) -> Result<ScheduleConfig, ScheduleError> {
+        let schedule = self
+            .config
+            .borrow()
+            .schedules
+            .iter()
+            .find(|s| s.name == name)
+            .cloned()
+            .ok_or_else(|| ScheduleError::NotFound(name.to_string()))?;
+        let mut state = self.state();
+        let entry = state.entry(name.to_string()).or_default();
+        if entry.running {
+            return Err(ScheduleError::Running(name.to_string()));
+        }
+        entry.running = true;
+        Ok(schedule)
+    }
+
+    /// Run a claimed schedule to completion and record the result.
+    async fn execute(&self, schedule: &ScheduleConfig, trigger: Trigger) -> ScheduleRun {
+        let started_at = now_secs();
+        let start = Instant::now();
+        let (outcome, message) = match self.run_target(schedule).await {
+            Ok(output) => (RunOutcome::Succeeded, output),
+            Err(e) => (RunOutcome::Failed, e),
+        };
+        let run = ScheduleRun {
+            started_at,
+            duration_ms: u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
+            trigger,
+            outcome,
+            message: truncate(&message),
+        };
+        match outcome {
+            RunOutcome::Succeeded => {
+                info!(schedule = %schedule.name, trigger = trigger.name(), ms = run.duration_ms, "Scheduled run succeeded")
+            }
+            _ => {
+                warn!(schedule = %schedule.name, trigger = trigger.name(), error = %run.message, "Scheduled run failed")
+            }
+        }
+        let mut state = self.state();
+        let entry = state.entry(schedule.name.clone()).or_default();
+        entry.running = false;
+        push_history(entry, run.clone());
+        self.save_history(&schedule.name, entry);
+        run
+    }
+
+    async fn run_target(&self, schedule: &ScheduleConfig) -> Result<String, String> {
+        if let Some(skill) = &schedule.skill {
+            let mut invocation = SkillInvocation::new();
+            for (key, value) in &schedule.args {
+                let value = serde_json::to_value(value)
+                    .map_err(|e| format!("invalid argument {key}: {e}"))?;
+                invocation = invocation.with_arg(key, value);
+            }
+            let result = self
+                .skills
+                .invoke(skill, &invocation)
+                .await
+                .map_err(|e| e.to_string())?;
+            return if result.success() {
+                Ok(result.stdout.trim().to_string())
+            } else {
+                Err(format!(
+                    "exit {}: {}",
+                    result.exit_code,
+                    result.stderr.trim()
+                ))
+            };
+        }
+
+        let prompt = schedule.prompt.as_deref().unwrap_or_default();
+        let chat = self
+            .chat
+            .as_ref()
+            .ok_or("agent prompts are not available")?;
+        let owner = format!("schedule:{}", schedule.name);
+        let session = chat
+            .open(&owner, None, &ToolFilter::default())
+            .map_err(|e| e.to_string())?;
+        let (events, _events) = mpsc::unbounded_channel();
+        let reply = chat
+            .send(
+                &owner,
+                std::slice::from_ref(&schedule.role),
+                &session,
+                prompt,
+                events,
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(reply.reply)
+    }
+
+    /// Bring next-run times up to date with the config at `now`, and claim
+    /// the schedules that are due. Overlapping runs are recorded as skipped.
+    fn take_due(&self, now: u64) -> Vec<ScheduleConfig> {
+        let schedules = self.config.borrow().schedules.clone();
+        let mut state = self.state();
+        state.retain(|name, _| schedules.iter().any(|s| &s.name == name));
+
+        let mut due = Vec::new();
+        for schedule in schedules.into_iter().filter(|s| s.enabled) {
+            let entry = state.entry(schedule.name.clone()).or_default();
+            let cron = match CronSchedule::parse(&schedule.cron) {
+                Ok(cron) => cron,
+                Err(e) => {
+                    warn!(schedule = %schedule.name, error = %e, "Skipping schedule");
+                    entry.next_run = None;
+                    continue;
+                }
+            };
+            if entry.cron != schedule.cron || entry.next_run.is_none() {
+                entry.cron = schedule.cron.clone();
+                entry.next_run = cron.next_after(now);
+            }
+            if entry.next_run.is_some_and(|at| at <= now) {
+                entry.next_run = cron.next_after(now);
+                if entry.running {
+                    warn!(schedule = %schedule.name, "Previous run still going; skipping");
+                    push_history(
+                        entry,
+                        ScheduleRun {
+                            started_at: now,
+                            duration_ms: 0,
+                            trigger: Trigger::Cron,
+                            outcome: RunOutcome::Skipped,
+                            message: "previous run still going".to_string(),
+                        },
+                    );
+                    self.save_history(&schedule.name, entry);
+                } else {
+                    entry.running = true;
+                    due.push(schedule);
+                }
+            }
+        }
+        due
+    }
+
+    /// Seconds until the earliest next run, capped at [`MAX_SLEEP`].
+    fn sleep_for(&self, now: u64) -> Duration {
+        self.state()
+            .values()
+            .filter_map(|s| s.next_run)
+            .min()
+            .map(|at| Duration::from_secs(at.saturating_sub(now).max(1)))
+            .unwrap_or(MAX_SLEEP)
+            .min(MAX_SLEEP)
+    }
+
+    /// Persist the run history of `name`, if there is a store.
+    fn save_history(&self, name: &str, state: &ScheduleState) {
+        let Some(storage) = &self.storage else {
+            return;
+        };
+        let data = serde_json::to_vec_pretty(&state.history).unwrap_or_default();
+        if let Err(e) = storage.put(storage::SCHEDULES, &format!("{name}.json"), &data) {
+            warn!(schedule = name, error = %e, "Failed to persist schedule history");
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, HashMap<String, ScheduleState>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Spawn the scheduler loop until shutdown. Runs in progress at shutdown
+/// are not waited for.
+pub fn spawn(
+    scheduler: Arc<Scheduler>,
+    mut shutdown_rx: broadcast::Receiver<ShutdownSignal>,
+) -> JoinHandle<()> {
+    let mut config_rx = scheduler.config.clone();
+    tokio::spawn(async move {
+        loop {
+            let now = now_secs();
+            for schedule in scheduler.take_due(now) {
+                let scheduler = scheduler.clone();
+                tokio::spawn(async move {
+                    let jitter = jitter(schedule.jitter_secs);
+                    if !jitter.is_zero() {
+                        tokio::time::sleep(jitter).await;
+                    }
+                    scheduler.execute(&schedule, Trigger::Cron).await;
+                });
+            }
+
+            tokio::select! {
+                _ = shutdown_rx.recv() => break,
+                changed = config_rx.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                }
+                () = tokio::time::sleep(scheduler.sleep_for(now)) => {}
+            }
+        }
+    })
+}
+
+fn push_history(state: &mut ScheduleState, run: ScheduleRun) {
+    if state.history.len() == HISTORY_LEN {
+        state.history.pop_front();
+    }
+    state.history.push_back(run);
+}
+
+/// A random delay of `0..=max_secs` seconds.
+fn jitter(max_secs: u64) -> Duration {
+    if max_secs == 0 {
+        return Duration::ZERO;
+    }
+    let mut bytes = [0u8; 8];
+    if SystemRandom::new().fill(&mut bytes).is_err() {
+        return Duration::ZERO;
+    }
+    Duration::from_secs(u64::from_le_bytes(bytes) % (max_secs + 1))
+}
+
+fn truncate(message: &str) -> String {
+    match message.char_indices().nth(MESSAGE_LIMIT) {
+        Some((end, _)) => format!("{}…", &message[..end]),
+        None => message.to_string(),
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BoxFuture;
+    use crate::message::Envelope;
+    use crate::skill::{Skill, SkillError};
+    use tokio::sync::Semaphore;
+
+    /// 2024-01-01 13:45:00 UTC.
+    const NOW: u64 = 1_704_116_700;
+
+    /// Echoes its arguments once a permit is available.
+    struct GatedSkill {
+        gate: Arc<Semaphore>,
+    }
+
+    impl Skill for GatedSkill {
+        fn name(&self) -> &str {
+            "gated"
+        }
+
+        fn description(&self) -> &str {
+            "Waits for a permit, then echoes its arguments"
+        }
+
+        fn execute(&self, message: &Envelope) -> BoxFuture<'_, Result<String, SkillError>> {
+            let body = message.body.clone();
+            Box::pin(async move {
+                let _permit = self.gate.acquire().await.unwrap();
+                Ok(body)
+            })
+        }
+    }
+
+    fn scheduler(toml: &str, gate: Arc<Semaphore>) -> (watch::Sender<AppConfig>, Arc<Scheduler>) {
+        let (config_tx, config_rx) = watch::channel(AppConfig::parse(toml).unwrap());
+        let mut skills = SkillRegistry::new();
+        skills.register(Box::new(GatedSkill { gate }));
+        (
+            config_tx,
+            Arc::new(Scheduler::new(config_rx, Arc::new(skills))),
+        )
+    }
+
+    const CONFIG: &str = r#"
+        [[schedules]]
+        name = "hourly"
+        cron = "0 * * * *"
+        skill = "gated"
+        args = { env = "prod" }
+
+        [[schedules]]
+        name = "off"
+        cron = "@daily"
+        skill = "missing"
+        enabled = false
+    "#;
+
+    #[tokio::test]
+    async fn test_due_runs_and_history() {
+        let (_config_tx, scheduler) = scheduler(CONFIG, Arc::new(Semaphore::new(1)));
+
+        // The first pass only computes the next run.
+        assert!(scheduler.take_due(NOW).is_empty());
+        let status = scheduler.status();
+        assert_eq!(status[0].next_run, Some(NOW + 15 * 60));
+        assert_eq!(status[0].target, "skill:gated");
+        assert_eq!(status[1].next_run, None);
+        assert_eq!(scheduler.sleep_for(NOW), MAX_SLEEP);
+        assert_eq!(
+            scheduler.sleep_for(NOW + 15 * 60 - 5),
+            Duration::from_secs(5)
+        );
+
+        let due = scheduler.take_due(NOW + 15 * 60);
+        assert_eq!(due.len(), 1);
+        assert!(scheduler.status()[0].running);
+        assert_eq!(scheduler.status()[0].next_run, Some(NOW + 75 * 60));
+
+        let run = scheduler.execute(&due[0], Trigger::Cron).await;
+        assert_eq!(run.outcome, RunOutcome::Succeeded);
+        assert_eq!(run.message, r#"{"env":"prod"}"#);
+        let status = scheduler.status();
+        assert!(!status[0].running);
+        assert_eq!(status[0].history, vec![run]);
+    }
+
+    #[tokio::test]
+    async fn test_overlapping_run_is_skipped() {
+        let gate = Arc::new(Semaphore::new(0));
+        let (_config_tx, scheduler) = scheduler(CONFIG, gate.clone());
+        scheduler.take_due(NOW);
+        let due = scheduler.take_due(NOW + 15 * 60).pop().unwrap();
+        let running = tokio::spawn({
+            let scheduler = scheduler.clone();
+            async move { scheduler.execute(&due, Trigger::Cron).await }
+        });
+
+        assert!(scheduler.take_due(NOW + 75 * 60).is_empty());
+        assert!(matches!(
+            scheduler.run_now("hourly"),
+            Err(ScheduleError::Running(_))
+        ));
+        let history = &scheduler.status()[0].history;
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].outcome, RunOutcome::Skipped);
+
+        gate.add_permits(1);
+        assert_eq!(running.await.unwrap().outcome, RunOutcome::Succeeded);
+        let history = &scheduler.status()[0].history;
+        assert_eq!(history[0].outcome, RunOutcome::Succeeded);
+        assert_eq!(history[1].outcome, RunOutcome::Skipped);
+    }
+
+    #[tokio::test]
+    async fn test_run_now() {
+        let (_config_tx, scheduler) = scheduler(CONFIG, Arc::new(Semaphore::new(1)));
+        assert!(matches!(
+            scheduler.run_now("nope"),
+            Err(ScheduleError::NotFound(_))
+        ));
+
+        // Disabled schedules can be run by hand; this one's skill is missing.
+        scheduler.run_now("off").unwrap();
+        for _ in 0..100 {
+            if !scheduler.status()[1].history.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let run = scheduler.status()[1].history[0].clone();
+        assert_eq!(run.trigger, Trigger::Manual);
+        assert_eq!(run.outcome, RunOutcome::Failed);
+        assert!(run.message.contains("missing"), "{}", run.message);
+    }
+
+    #[tokio::test]
+    async fn test_prompt_without_chat_fails() {
+        let (_config_tx, scheduler) = scheduler(
+            "[[schedules]]\nname = \"digest\"\ncron = \"@daily\"\nprompt = \"hi\"\n",
+            Arc::new(Semaphore::new(1)),
+        );
+        let schedule = scheduler.claim("digest").unwrap();
+        let run = scheduler.execute(&schedule, Trigger::Manual).await;
+        assert_eq!(run.outcome, RunOutcome::Failed);
+        assert_eq!(run.message, "agent prompts are not available");
+    }
+
+    #[test]
+    fn test_reload_recomputes_and_forgets() {
+        let (config_tx, scheduler) = scheduler(CONFIG, Arc::new(Semaphore::new(1)));
+        scheduler.take_due(NOW);
+        assert_eq!(scheduler.status()[0].next_run, Some(NOW + 15 * 60));
+
+        let mut config = config_tx.borrow().clone();
+        config.schedules[0].cron = "*/5 * * * *".to_string();
+        config.schedules.pop();
+        config_tx.send(config).unwrap();
+        scheduler.take_due(NOW);
+        let status = scheduler.status();
+        assert_eq!(status.len(), 1);
+        assert_eq!(status[0].next_run, Some(NOW + 5 * 60));
+        assert_eq!(scheduler.state().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_history_persists() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage: Arc<dyn Storage> =
+            Arc::new(storage::SqliteStorage::open(dir.path().join("state.db")).unwrap());
+        let (config_tx, scheduler) = scheduler(CONFIG, Arc::new(Semaphore::new(1)));
+        let scheduler = Arc::into_inner(scheduler)
+            .unwrap()
+            .with_storage(storage.clone());
+        let schedule = scheduler.claim("hourly").unwrap();
+        let run = scheduler.execute(&schedule, Trigger::Manual).await;
+        assert_eq!(storage.list(storage::SCHEDULES).unwrap(), ["hourly.json"]);
+
+        let mut skills = SkillRegistry::new();
+        skills.register(Box::new(GatedSkill {
+            gate: Arc::new(Semaphore::new(1)),
+        }));
+        let reloaded =
+            Scheduler::new(config_tx.subscribe(), Arc::new(skills)).with_storage(storage);
+        assert_eq!(reloaded.status()[0].history, vec![run]);
+    }
+
+    #[test]
+    fn test_history_is_capped() {
+        let mut state = ScheduleState::default();
+        for i in 0..HISTORY_LEN as u64 + 5 {
+            push_history(
+                &mut state,
+                ScheduleRun {
+                    started_at: i,
+                    duration_ms: 0,
+                    trigger: Trigger::Cron,
+                    outcome: RunOutcome::Succeeded,
+                    message: String::new(),
+                },
+            );
+        }
+        assert_eq!(state.history.len(), HISTORY_LEN);
+        assert_eq!(state.history[0].started_at, 5);
+    }
+
+    #[test]
+    fn test_jitter_and_truncate() {
+        assert_eq!(jitter(0), Duration::ZERO);
+        assert!(jitter(3) <= Duration::from_secs(3));
+        assert_eq!(truncate("short"), "short");
+        let long = "é".repeat(MESSAGE_LIMIT + 1);
+        assert_eq!(truncate(&long).chars().count(), MESSAGE_LIMIT + 1);
+    }
+}
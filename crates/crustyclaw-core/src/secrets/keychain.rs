@@ -0,0 +1,167 @@
+//! The OS keychain — `source = "keychain"` and the keystore key.
+//!
+//! Items are addressed by a service and an account, and read and written
+//! through the platform's command-line tool so no native bindings are
+//! needed: `security` for the macOS Keychain, and `secret-tool` for the
+//! Secret Service (GNOME Keyring, KWallet) on Linux and other Unixes, where
+//! the item carries the attributes `service` and `account`. Secrets are
+//! passed to the tools on stdin, never in their arguments.
+//!
+//! Windows Credential Manager is not supported; the daemon itself only runs
+//! on Unix.
+
+use std::io::{self, Write};
+use std::process::{Command, ExitStatus, Stdio};
+
+use zeroize::Zeroize;
+
+/// Service of `source = "keychain"` entries that name none.
+pub const DEFAULT_SERVICE: &str = "crustyclaw";
+
+/// Errors reading or writing the keychain.
+#[derive(Debug, thiserror::Error)]
+pub enum KeychainError {
+    #[error("cannot run {tool}: {source}")]
+    Spawn {
+        tool: &'static str,
+        source: io::Error,
+    },
+
+    #[error("{tool} failed ({status})")]
+    Failed {
+        tool: &'static str,
+        status: ExitStatus,
+    },
+
+    #[error("the OS keychain is not supported on this platform")]
+    Unsupported,
+}
+
+#[cfg(target_os = "macos")]
+const TOOL: &str = "security";
+
+#[cfg(all(unix, not(target_os = "macos")))]
+const TOOL: &str = "secret-tool";
+
+/// Read the item `service`/`account`, or `None` if there is none.
+#[cfg(target_os = "macos")]
+pub fn get(service: &str, account: &str) -> Result<Option<String>, KeychainError> {
+    let output = Command::new(TOOL)
+        .args(["find-generic-password", "-s", service, "-a", account, "-w"])
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|source| KeychainError::Spawn { tool: TOOL, source })?;
+    // 44: errSecItemNotFound.
+    match output.status.code() {
+        Some(0) => Ok(Some(into_secret(output.stdout))),
+        Some(44) => Ok(None),
+        _ => Err(KeychainError::Failed {
+            tool: TOOL,
+            status: output.status,
+        }),
+    }
+}
+
+/// Store `secret` as the item `service`/`account`, replacing it if it
+/// exists.
+#[cfg(target_os = "macos")]
+pub fn set(service: &str, account: &str, secret: &str) -> Result<(), KeychainError> {
+    // `security -i` reads commands from stdin, keeping the secret out of argv.
+    run_with_input(
+        Command::new(TOOL).arg("-i"),
+        format!(
+            "add-generic-password -U -s {} -a {} -w {}\n",
+            quote(service),
+            quote(account),
+            quote(secret)
+        ),
+    )
+}
+
+/// Read the item `service`/`account`, or `None` if there is none.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn get(service: &str, account: &str) -> Result<Option<String>, KeychainError> {
+    let output = Command::new(TOOL)
+        .args(["lookup", "service", service, "account", account])
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|source| KeychainError::Spawn { tool: TOOL, source })?;
+    // secret-tool exits 1 with no output when nothing matches.
+    if output.status.success() {
+        Ok(Some(into_secret(output.stdout)))
+    } else if output.stdout.is_empty() {
+        Ok(None)
+    } else {
+        Err(KeychainError::Failed {
+            tool: TOOL,
+            status: output.status,
+        })
+    }
+}
+
+/// Store `secret` as the item `service`/`account`, replacing it if it
+/// exists.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn set(service: &str, account: &str, secret: &str) -> Result<(), KeychainError> {
+    run_with_input(
+        Command::new(TOOL).args([
+            "store",
+            &format!("--label={service}: {account}"),
+            "service",
+            service,
+            "account",
+            account,
+        ]),
+        secret.to_string(),
+    )
+}
+
+/// Read the item `service`/`account`, or `None` if there is none.
+#[cfg(not(unix))]
+pub fn get(_service: &str, _account: &str) -> Result<Option<String>, KeychainError> {
+    Err(KeychainError::Unsupported)
+}
+
+/// Store `secret` as the item `service`/`account`, replacing it if it
+/// exists.
+#[cfg(not(unix))]
+pub fn set(_service: &str, _account: &str, _secret: &str) -> Result<(), KeychainError> {
+    Err(KeychainError::Unsupported)
+}
+
+/// The tool's output as a secret, without the trailing newline `security`
+/// adds, zeroizing the buffer.
+#[cfg(unix)]
+fn into_secret(mut stdout: Vec<u8>) -> String {
+    let secret = String::from_utf8_lossy(&stdout)
+        .trim_end_matches('\n')
+        .to_string();
+    stdout.zeroize();
+    secret
+}
+
+/// Quote `s` for `security -i`.
+#[cfg(target_os = "macos")]
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(unix)]
+fn run_with_input(command: &mut Command, mut input: String) -> Result<(), KeychainError> {
+    let result = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .and_then(|mut child| {
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(input.as_bytes())?;
+            }
+            child.wait()
+        });
+    input.zeroize();
+    let status = result.map_err(|source| KeychainError::Spawn { tool: TOOL, source })?;
+    if !status.success() {
+        return Err(KeychainError::Failed { tool: TOOL, status });
+    }
+    Ok(())
+}
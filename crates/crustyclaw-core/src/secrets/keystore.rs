@@ -0,0 +1,382 @@
+//! Encrypted secrets file — `source = "keystore"`.
+//!
+//! `crustyclaw secrets set/get/rm` keep named secrets in the file at
+//! `secrets.keystore.path`, so they never appear in TOML or the daemon's
+//! environment. The file is the JSON map of names to values sealed with
+//! ChaCha20-Poly1305 under a 256-bit key, written as
+//! `CCKSTOR1 || nonce || ciphertext || tag` with a fresh random nonce on
+//! every save. The key is hex, kept either in `key_file` (mode 0600) or in
+//! the OS keychain (see [`keychain`](super::keychain)) under the service
+//! `crustyclaw` and the keystore's absolute path.
+//!
+//! The first `set` generates the key. The daemon only reads: it unlocks the
+//! keystore when it resolves `[secrets]` at startup and on SIGHUP, and a
+//! missing key is an error rather than a reason to make a new one.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crustyclaw_config::KeystoreConfig;
+use ring::aead::{Aad, CHACHA20_POLY1305, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use ring::rand::{SecureRandom, SystemRandom};
+use zeroize::Zeroize;
+
+use super::SecretValue;
+use super::keychain::{self, DEFAULT_SERVICE as KEYCHAIN_SERVICE, KeychainError};
+use crate::auth::token::{decode_hex, encode_hex, write_private};
+
+/// Start of every keystore file; also the AEAD associated data.
+const MAGIC: &[u8] = b"CCKSTOR1";
+
+/// Length of the key, in bytes.
+const KEY_LEN: usize = 32;
+
+/// Errors opening, reading, or saving the keystore.
+#[derive(Debug, thiserror::Error)]
+pub enum KeystoreError {
+    #[error("keystore {path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+
+    #[error("no keystore key in {0}; `crustyclaw secrets set` creates one")]
+    NoKey(String),
+
+    #[error("keystore key in {0} is not {KEY_LEN} bytes of hex")]
+    BadKey(String),
+
+    #[error("keystore {} exists but its key in {key} is missing", path.display())]
+    KeyLost { path: PathBuf, key: String },
+
+    #[error("keystore {} cannot be decrypted with this key, or is corrupt", .0.display())]
+    Decrypt(PathBuf),
+
+    #[error("keychain: {0}")]
+    Keychain(#[from] KeychainError),
+
+    #[error("no system randomness to generate a key")]
+    Random,
+}
+
+/// Where the keystore key is kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeySource {
+    /// A hex key file.
+    File(PathBuf),
+    /// The OS keychain, under the keystore's absolute path.
+    Keychain { account: String },
+}
+
+impl KeySource {
+    /// The key source `config` names.
+    pub fn from_config(config: &KeystoreConfig) -> Self {
+        match config.key_source.as_str() {
+            "keychain" => {
+                let path = Path::new(&config.path);
+                let account = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+                Self::Keychain {
+                    account: account.display().to_string(),
+                }
+            }
+            _ => Self::File(PathBuf::from(&config.key_file)),
+        }
+    }
+
+    /// Read the key, or `None` if there is none yet.
+    fn load(&self) -> Result<Option<LessSafeKey>, KeystoreError> {
+        let mut text = match self {
+            Self::File(path) => match std::fs::read_to_string(path) {
+                Ok(text) => text,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+                Err(source) => {
+                    return Err(KeystoreError::Io {
+                        path: path.clone(),
+                        source,
+                    });
+                }
+            },
+            Self::Keychain { account } => match keychain::get(KEYCHAIN_SERVICE, account)? {
+                Some(text) => text,
+                None => return Ok(None),
+            },
+        };
+        let bytes = decode_hex(text.trim());
+        text.zeroize();
+        let Some(mut bytes) = bytes else {
+            return Err(KeystoreError::BadKey(self.to_string()));
+        };
+        let key = (bytes.len() == KEY_LEN)
+            .then(|| UnboundKey::new(&CHACHA20_POLY1305, &bytes).ok())
+            .flatten();
+        bytes.zeroize();
+        match key {
+            Some(key) => Ok(Some(LessSafeKey::new(key))),
+            None => Err(KeystoreError::BadKey(self.to_string())),
+        }
+    }
+
+    /// Generate a key and save it here.
+    fn create(&self) -> Result<LessSafeKey, KeystoreError> {
+        let mut bytes = [0u8; KEY_LEN];
+        SystemRandom::new()
+            .fill(&mut bytes)
+            .map_err(|_| KeystoreError::Random)?;
+        let mut text = encode_hex(&bytes);
+        let saved = match self {
+            Self::File(path) => {
+                write_private(path, text.as_bytes(), false).map_err(|source| KeystoreError::Io {
+                    path: path.clone(),
+                    source,
+                })
+            }
+            Self::Keychain { account } => {
+                keychain::set(KEYCHAIN_SERVICE, account, &text).map_err(KeystoreError::from)
+            }
+        };
+        text.zeroize();
+        let key = UnboundKey::new(&CHACHA20_POLY1305, &bytes);
+        bytes.zeroize();
+        saved?;
+        key.map(LessSafeKey::new)
+            .map_err(|_| KeystoreError::BadKey(self.to_string()))
+    }
+}
+
+impl std::fmt::Display for KeySource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::File(path) => write!(f, "{}", path.display()),
+            Self::Keychain { account } => write!(f, "keychain ({KEYCHAIN_SERVICE}/{account})"),
+        }
+    }
+}
+
+/// An unlocked keystore. Values are zeroized when it is dropped.
+#[derive(Debug)]
+pub struct Keystore {
+    path: PathBuf,
+    key: LessSafeKey,
+    entries: BTreeMap<String, SecretValue>,
+}
+
+impl Keystore {
+    /// Unlock the keystore. A missing file is an empty keystore; a missing
+    /// key is an error.
+    pub fn open(config: &KeystoreConfig) -> Result<Self, KeystoreError> {
+        let source = KeySource::from_config(config);
+        let path = PathBuf::from(&config.path);
+        match source.load()? {
+            Some(key) => Self::read(path, key),
+            None if path.exists() => Err(KeystoreError::KeyLost {
+                path,
+                key: source.to_string(),
+            }),
+            None => Err(KeystoreError::NoKey(source.to_string())),
+        }
+    }
+
+    /// Like [`open`](Self::open), generating the key if neither it nor
+    /// the keystore exists yet.
+    pub fn open_or_create(config: &KeystoreConfig) -> Result<Self, KeystoreError> {
+        match Self::open(config) {
+            Err(KeystoreError::NoKey(_)) => {
+                let key = KeySource::from_config(config).create()?;
+                Self::read(PathBuf::from(&config.path), key)
+            }
+            result => result,
+        }
+    }
+
+    fn read(path: PathBuf, key: LessSafeKey) -> Result<Self, KeystoreError> {
+        let mut data = match std::fs::read(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok(Self {
+                    path,
+                    key,
+                    entries: BTreeMap::new(),
+                });
+            }
+            Err(source) => return Err(KeystoreError::Io { path, source }),
+        };
+        let entries = decrypt(&key, &mut data);
+        data.zeroize();
+        match entries {
+            Some(entries) => Ok(Self { path, key, entries }),
+            None => Err(KeystoreError::Decrypt(path)),
+        }
+    }
+
+    /// The keystore file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The value stored under `name`.
+    pub fn get(&self, name: &str) -> Option<&SecretValue> {
+        self.entries.get(name)
+    }
+
+    /// Names of the stored secrets, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        self.entries.keys().map(String::as_str).collect()
+    }
+
+    /// Store `value` under `name`, replacing any previous value. Call
+    /// [`save`](Self::save) to write the change.
+    pub fn set(&mut self, name: &str, value: SecretValue) {
+        self.entries.insert(name.to_string(), value);
+    }
+
+    /// Remove `name`, returning whether it was stored.
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.entries.remove(name).is_some()
+    }
+
+    /// Encrypt and write the keystore, mode 0600, replacing the file
+    /// atomically.
+    pub fn save(&self) -> Result<(), KeystoreError> {
+        let io_error = |source| KeystoreError::Io {
+            path: self.path.clone(),
+            source,
+        };
+        let plain: BTreeMap<&str, &str> = self
+            .entries
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.expose()))
+            .collect();
+        let mut sealed = serde_json::to_vec(&plain).map_err(|e| io_error(e.into()))?;
+        let mut nonce = [0u8; NONCE_LEN];
+        if SystemRandom::new().fill(&mut nonce).is_err()
+            || self
+                .key
+                .seal_in_place_append_tag(
+                    Nonce::assume_unique_for_key(nonce),
+                    Aad::from(MAGIC),
+                    &mut sealed,
+                )
+                .is_err()
+        {
+            sealed.zeroize();
+            return Err(io_error(io::Error::other("encryption failed")));
+        }
+        let mut file = Vec::with_capacity(MAGIC.len() + NONCE_LEN + sealed.len());
+        file.extend_from_slice(MAGIC);
+        file.extend_from_slice(&nonce);
+        file.extend_from_slice(&sealed);
+        write_private(&self.path, &file, true).map_err(io_error)
+    }
+}
+
+/// Open a keystore file's contents in place, zeroizing the plaintext.
+fn decrypt(key: &LessSafeKey, data: &mut [u8]) -> Option<BTreeMap<String, SecretValue>> {
+    if !data.starts_with(MAGIC) || data.len() < MAGIC.len() + NONCE_LEN {
+        return None;
+    }
+    let (nonce, sealed) = data[MAGIC.len()..].split_at_mut(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(nonce).ok()?;
+    let plain = key.open_in_place(nonce, Aad::from(MAGIC), sealed).ok()?;
+    let entries = serde_json::from_slice::<BTreeMap<String, String>>(plain).ok();
+    plain.zeroize();
+    Some(
+        entries?
+            .into_iter()
+            .map(|(name, value)| (name, SecretValue::new(value)))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(dir: &Path) -> KeystoreConfig {
+        KeystoreConfig {
+            path: dir.join("secrets.keystore").display().to_string(),
+            key_source: "file".to_string(),
+            key_file: dir.join("keys/secrets.key").display().to_string(),
+        }
+    }
+
+    #[test]
+    fn test_keystore_roundtrip() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let config = config(tmp.path());
+        assert!(matches!(
+            Keystore::open(&config),
+            Err(KeystoreError::NoKey(_))
+        ));
+
+        let mut store = Keystore::open_or_create(&config).unwrap();
+        assert!(store.names().is_empty());
+        store.set("github_token", SecretValue::new("ghp_abc123"));
+        store.set("db_password", SecretValue::new("hunter2"));
+        store.save().unwrap();
+
+        let raw = std::fs::read(&config.path).unwrap();
+        assert!(raw.starts_with(MAGIC));
+        assert!(!raw.windows(7).any(|w| w == b"hunter2"));
+
+        let mut store = Keystore::open(&config).unwrap();
+        assert_eq!(store.names(), vec!["db_password", "github_token"]);
+        assert_eq!(store.get("github_token").unwrap().expose(), "ghp_abc123");
+        assert!(store.remove("db_password"));
+        assert!(!store.remove("db_password"));
+        store.save().unwrap();
+        assert_eq!(
+            Keystore::open(&config).unwrap().names(),
+            vec!["github_token"]
+        );
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            for path in [&config.path, &config.key_file] {
+                let mode = std::fs::metadata(path).unwrap().permissions().mode();
+                assert_eq!(mode & 0o777, 0o600, "{path}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_keystore_rejects_wrong_key_and_lost_key() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let config = config(tmp.path());
+        let mut store = Keystore::open_or_create(&config).unwrap();
+        store.set("token", SecretValue::new("s3cret"));
+        store.save().unwrap();
+
+        std::fs::write(&config.key_file, encode_hex(&[9; KEY_LEN])).unwrap();
+        assert!(matches!(
+            Keystore::open(&config),
+            Err(KeystoreError::Decrypt(_))
+        ));
+
+        std::fs::write(&config.key_file, "abcd").unwrap();
+        assert!(matches!(
+            Keystore::open(&config),
+            Err(KeystoreError::BadKey(_))
+        ));
+
+        // A new key would make the existing file unreadable.
+        std::fs::remove_file(&config.key_file).unwrap();
+        assert!(matches!(
+            Keystore::open_or_create(&config),
+            Err(KeystoreError::KeyLost { .. })
+        ));
+    }
+
+    #[test]
+    fn test_key_source_from_config() {
+        let mut config = KeystoreConfig::default();
+        assert_eq!(
+            KeySource::from_config(&config),
+            KeySource::File(PathBuf::from("data/secrets.key"))
+        );
+        config.key_source = "keychain".to_string();
+        let KeySource::Keychain { account } = KeySource::from_config(&config) else {
+            panic!("expected keychain");
+        };
+        assert!(Path::new(&account).is_absolute());
+        assert!(account.ends_with("data/secrets.keystore"));
+    }
+}
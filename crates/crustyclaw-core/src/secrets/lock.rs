@@ -0,0 +1,144 @@
+//! Keeping secret values out of swap — the `mlock` feature.
+//!
+//! With the feature, every [`SecretValue`](super::SecretValue) `mlock`s the
+//! pages its buffer spans for as long as it lives. Several values can share
+//! a page and `munlock` does not nest, so pages are reference-counted: a
+//! page is unlocked when the last value on it is dropped, after that value
+//! has been zeroized.
+//!
+//! Locking is best effort. When `RLIMIT_MEMLOCK` is exhausted the value is
+//! kept unlocked and [`SecretValue::is_locked`](super::SecretValue::is_locked)
+//! says so. Without the feature, or off Unix, nothing is locked.
+
+#[cfg(all(unix, feature = "mlock"))]
+mod imp {
+    use std::collections::BTreeMap;
+    use std::sync::{Mutex, OnceLock, PoisonError};
+
+    /// Locked pages, by start address, and how many values use each.
+    static PAGES: Mutex<BTreeMap<usize, usize>> = Mutex::new(BTreeMap::new());
+
+    #[allow(unsafe_code)]
+    pub fn page_size() -> usize {
+        static PAGE_SIZE: OnceLock<usize> = OnceLock::new();
+        // SAFETY: sysconf only reads a system constant.
+        *PAGE_SIZE.get_or_init(|| match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+            size if size > 0 => size as usize,
+            _ => 4096,
+        })
+    }
+
+    /// Start addresses of the pages `ptr..ptr + len` spans.
+    fn pages(ptr: *const u8, len: usize) -> impl Iterator<Item = usize> {
+        let size = page_size();
+        let start = ptr as usize & !(size - 1);
+        let end = (ptr as usize + len - 1) & !(size - 1);
+        (start..=end).step_by(size)
+    }
+
+    #[allow(unsafe_code)]
+    fn mlock(page: usize) -> bool {
+        // SAFETY: mlock only changes the paging of an address range and
+        // fails without effect on unmapped pages; `page` is page-aligned.
+        unsafe { libc::mlock(page as *const libc::c_void, page_size()) == 0 }
+    }
+
+    #[allow(unsafe_code)]
+    fn munlock(page: usize) {
+        // SAFETY: as for mlock.
+        unsafe { libc::munlock(page as *const libc::c_void, page_size()) };
+    }
+
+    pub fn lock(ptr: *const u8, len: usize) -> bool {
+        if len == 0 {
+            return false;
+        }
+        let mut counts = PAGES.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut locked = Vec::new();
+        for page in pages(ptr, len).filter(|page| !counts.contains_key(page)) {
+            if !mlock(page) {
+                locked.into_iter().for_each(munlock);
+                return false;
+            }
+            locked.push(page);
+        }
+        for page in pages(ptr, len) {
+            *counts.entry(page).or_default() += 1;
+        }
+        true
+    }
+
+    pub fn unlock(ptr: *const u8, len: usize) {
+        let mut counts = PAGES.lock().unwrap_or_else(PoisonError::into_inner);
+        for page in pages(ptr, len) {
+            if let Some(count) = counts.get_mut(&page) {
+                *count -= 1;
+                if *count == 0 {
+                    counts.remove(&page);
+                    munlock(page);
+                }
+            }
+        }
+    }
+
+    /// How many values hold the page at `page` locked.
+    #[cfg(test)]
+    pub fn count(page: *const u8) -> usize {
+        let counts = PAGES.lock().unwrap_or_else(PoisonError::into_inner);
+        counts.get(&(page as usize)).copied().unwrap_or(0)
+    }
+}
+
+#[cfg(not(all(unix, feature = "mlock")))]
+mod imp {
+    pub fn lock(_ptr: *const u8, _len: usize) -> bool {
+        false
+    }
+
+    pub fn unlock(_ptr: *const u8, _len: usize) {}
+}
+
+/// Lock the `len` bytes at `ptr` into memory, returning whether they are.
+pub(super) fn lock(ptr: *const u8, len: usize) -> bool {
+    imp::lock(ptr, len)
+}
+
+/// Release a [`lock`] of the same range.
+pub(super) fn unlock(ptr: *const u8, len: usize) {
+    imp::unlock(ptr, len)
+}
+
+#[cfg(all(test, unix, feature = "mlock"))]
+mod tests {
+    use super::*;
+    use std::alloc::{Layout, alloc_zeroed, dealloc};
+
+    #[test]
+    #[allow(unsafe_code)]
+    fn test_shared_pages_stay_locked() {
+        // A page of its own, so no other test's values share it.
+        let size = imp::page_size();
+        let layout = Layout::from_size_align(size, size).unwrap();
+        // SAFETY: the layout has a non-zero size.
+        let page = unsafe { alloc_zeroed(layout) };
+        assert!(!page.is_null());
+
+        // SAFETY: both offsets are within the page.
+        let (a, b) = (page, unsafe { page.add(size / 2) });
+        if lock(a, 32) {
+            assert!(lock(b, 32));
+            assert_eq!(imp::count(page), 2);
+            unlock(a, 32);
+            assert_eq!(imp::count(page), 1);
+            unlock(b, 32);
+            assert_eq!(imp::count(page), 0);
+        } else {
+            // RLIMIT_MEMLOCK is 0 in some sandboxes.
+            assert_eq!(imp::count(page), 0);
+        }
+        assert!(!lock(a, 0));
+
+        // SAFETY: allocated above with the same layout.
+        unsafe { dealloc(page, layout) };
+    }
+}
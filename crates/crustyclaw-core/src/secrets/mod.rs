@@ -0,0 +1,1444 @@
+#![deny(unsafe_code)]
+
+//! Secrets management for CrustyClaw.
+//!
+//! Provides a [`SecretStore`] that holds named secrets in memory with
+//! automatic zeroization on drop. Secrets can be loaded from:
+//!
+//! - TOML config (`[secrets]` section)
+//! - Environment variables (`CRUSTYCLAW_SECRET_<NAME>`)
+//! - Files (one secret per file, path referenced in config)
+//! - The encrypted [`keystore`] (`source = "keystore"`)
+//! - The OS [`keychain`] (`source = "keychain"`)
+//!
+//! Secrets are injected into sandbox containers via two mechanisms:
+//!
+//! - **Environment injection:** secret value set as an env var inside the container
+//! - **File injection:** secret value written to a tmpfs-backed file, mounted read-only
+//!
+//! ## Security Properties
+//!
+//! - All secret values implement `Zeroize` and are cleared on drop.
+//! - With the `mlock` feature, secret values are locked into memory so they
+//!   are never written to swap (see [`SecretValue::is_locked`]).
+//! - Secret values are redacted in `Debug` output (shown as `[REDACTED]`).
+//! - File-injected secrets use restrictive permissions (0o400).
+//! - The store never logs or displays secret values.
+//!
+//! ## Live reload
+//!
+//! On SIGHUP the daemon re-resolves every configured source with
+//! [`SecretStore::from_config`] and merges the result with
+//! [`SecretStore::apply`]. Entries are compared by value; only changed
+//! entries are replaced, and the replaced values are zeroized on drop.
+//!
+//! ## Rotation
+//!
+//! [`SecretStore::rotate`] re-reads a single env-, file-, or keychain-sourced
+//! secret from where it was originally loaded; [`SecretStore::rotate_all`]
+//! does this for every rotatable secret and is run by the daemon every
+//! `secrets.rotation_interval_secs`. Inline config and keystore secrets only
+//! change on reload. After a rotation, [`SecretStore::restage`] rewrites any
+//! already-staged files so sandboxes started afterwards mount the new value.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use zeroize::Zeroize;
+
+pub mod keychain;
+pub mod keystore;
+mod lock;
+
+use keystore::{Keystore, KeystoreError};
+
+/// A single secret value with automatic zeroization.
+///
+/// With the `mlock` feature the value's buffer is also locked into memory
+/// until it is dropped.
+pub struct SecretValue {
+    /// The raw secret bytes.
+    inner: String,
+    /// Whether `inner`'s buffer is locked into memory.
+    locked: bool,
+}
+
+impl SecretValue {
+    /// Create a new secret from a string value.
+    pub fn new(value: impl Into<String>) -> Self {
+        let inner = value.into();
+        let locked = lock::lock(inner.as_ptr(), inner.capacity());
+        Self { inner, locked }
+    }
+
+    /// Read a secret from `reader` until it ends.
+    ///
+    /// Unlike `read_to_string`, every buffer the value passes through is
+    /// zeroized: the buffer grows by copying into a new one and zeroizing the
+    /// old, and bytes that are not UTF-8 are zeroized before the error is
+    /// returned. Suited to large secrets such as certificates and keys.
+    pub fn from_reader(mut reader: impl std::io::Read) -> std::io::Result<Self> {
+        let mut chunk = [0u8; 4096];
+        let mut buf: Vec<u8> = Vec::new();
+        let result = loop {
+            match reader.read(&mut chunk) {
+                Ok(0) => break Ok(()),
+                Ok(n) => {
+                    if buf.len() + n > buf.capacity() {
+                        let mut grown = Vec::with_capacity((buf.len() + n).max(buf.capacity() * 2));
+                        grown.extend_from_slice(&buf);
+                        buf.zeroize();
+                        buf = grown;
+                    }
+                    buf.extend_from_slice(&chunk[..n]);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => break Err(e),
+            }
+        };
+        chunk.zeroize();
+        if let Err(e) = result {
+            buf.zeroize();
+            return Err(e);
+        }
+        String::from_utf8(buf).map(Self::new).map_err(|e| {
+            e.into_bytes().zeroize();
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "secret is not valid UTF-8")
+        })
+    }
+
+    /// Get the secret value as a string slice.
+    ///
+    /// Use sparingly — prefer injection methods over direct access.
+    pub fn expose(&self) -> &str {
+        &self.inner
+    }
+
+    /// Get the secret value length (without exposing the value).
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Whether the secret value is empty.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Whether the value is locked into memory, so it cannot be swapped out.
+    ///
+    /// Always `false` without the `mlock` feature; with it, `false` when
+    /// `RLIMIT_MEMLOCK` did not allow the lock.
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Drop trailing newlines in place, without copying the value.
+    fn trim_end_newlines(&mut self) {
+        let len = self.inner.trim_end_matches('\n').len();
+        self.inner.truncate(len);
+    }
+}
+
+/// Compares the values themselves, for change detection on reload and
+/// rotation.
+impl PartialEq for SecretValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.inner == other.inner
+    }
+}
+
+impl Eq for SecretValue {}
+
+impl fmt::Debug for SecretValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecretValue")
+            .field("inner", &"[REDACTED]")
+            .field("len", &self.inner.len())
+            .finish()
+    }
+}
+
+impl Clone for SecretValue {
+    fn clone(&self) -> Self {
+        Self::new(self.inner.clone())
+    }
+}
+
+impl Drop for SecretValue {
+    fn drop(&mut self) {
+        let (ptr, capacity) = (self.inner.as_ptr(), self.inner.capacity());
+        // Zeroizes the whole buffer, spare capacity included, and keeps it
+        // allocated until the lock is released.
+        self.inner.zeroize();
+        if self.locked {
+            lock::unlock(ptr, capacity);
+        }
+    }
+}
+
+/// How a secret should be injected into a container.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InjectionMethod {
+    /// Inject as an environment variable with the given name.
+    ///
+    /// Example: `InjectionMethod::Env("API_KEY".into())`
+    /// → container sees `API_KEY=<secret_value>`
+    Env(String),
+
+    /// Inject as a read-only file at the given guest path.
+    ///
+    /// Example: `InjectionMethod::File("/run/secrets/api_key".into())`
+    /// → container sees a file at `/run/secrets/api_key` containing the secret
+    File(PathBuf),
+
+    /// Inject as both an env var and a file.
+    Both {
+        /// Environment variable name.
+        env_name: String,
+        /// File path inside the container.
+        file_path: PathBuf,
+    },
+}
+
+/// A named secret with its injection configuration.
+#[derive(Debug, Clone)]
+pub struct SecretEntry {
+    /// The secret name (used as the key in the store).
+    pub name: String,
+    /// The secret value (zeroized on drop).
+    pub value: SecretValue,
+    /// How this secret should be injected into containers.
+    pub injection: InjectionMethod,
+    /// Optional description for operator reference.
+    pub description: String,
+}
+
+/// The source from which a secret was loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecretSource {
+    /// Loaded from the TOML config file.
+    Config,
+    /// Loaded from an environment variable.
+    Environment(String),
+    /// Loaded from a file.
+    File(PathBuf),
+    /// Loaded from the keystore at this path.
+    Keystore(PathBuf),
+    /// Loaded from the OS keychain item `service`/`account`.
+    Keychain { service: String, account: String },
+}
+
+impl fmt::Display for SecretSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretSource::Config => write!(f, "config"),
+            SecretSource::Environment(var) => write!(f, "env:{var}"),
+            SecretSource::File(path) => write!(f, "file:{}", path.display()),
+            SecretSource::Keystore(path) => write!(f, "keystore:{}", path.display()),
+            SecretSource::Keychain { service, account } => {
+                write!(f, "keychain:{service}/{account}")
+            }
+        }
+    }
+}
+
+/// Errors from secret store operations.
+#[derive(Debug, thiserror::Error)]
+pub enum SecretError {
+    #[error("secret not found: {0}")]
+    NotFound(String),
+
+    #[error("failed to read secret file '{path}': {source}")]
+    FileRead {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("secret '{name}' already exists")]
+    Duplicate { name: String },
+
+    #[error("environment variable '{0}' not set")]
+    EnvNotSet(String),
+
+    #[error("secret value is empty for '{0}'")]
+    EmptyValue(String),
+
+    #[error("failed to write secret file: {0}")]
+    FileWrite(std::io::Error),
+
+    #[error(
+        "secret '{0}' is read from the config or keystore and can only change on config reload"
+    )]
+    NotRotatable(String),
+
+    #[error("{0}")]
+    Keystore(String),
+
+    #[error("{0}")]
+    Keychain(String),
+}
+
+/// The outcome of merging freshly resolved secrets into a store.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SecretDiff {
+    /// Secrets that did not exist before.
+    pub added: Vec<String>,
+    /// Secrets whose value or injection changed.
+    pub changed: Vec<String>,
+    /// Secrets that are no longer configured.
+    pub removed: Vec<String>,
+}
+
+impl SecretDiff {
+    /// Whether the reload changed nothing.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+    }
+
+    /// Names of all added, changed, and removed secrets.
+    pub fn affected(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .added
+            .iter()
+            .chain(&self.changed)
+            .chain(&self.removed)
+            .cloned()
+            .collect();
+        names.sort();
+        names
+    }
+}
+
+/// In-memory secret store with automatic zeroization.
+///
+/// All values are cleared from memory when the store is dropped.
+/// The store tracks the source of each secret for audit purposes.
+pub struct SecretStore {
+    secrets: HashMap<String, SecretEntry>,
+    sources: HashMap<String, SecretSource>,
+}
+
+impl SecretStore {
+    /// Create an empty secret store.
+    pub fn new() -> Self {
+        Self {
+            secrets: HashMap::new(),
+            sources: HashMap::new(),
+        }
+    }
+
+    /// Add a secret to the store.
+    pub fn insert(&mut self, entry: SecretEntry, source: SecretSource) -> Result<(), SecretError> {
+        if entry.value.is_empty() {
+            return Err(SecretError::EmptyValue(entry.name.clone()));
+        }
+        let name = entry.name.clone();
+        self.secrets.insert(name.clone(), entry);
+        self.sources.insert(name, source);
+        Ok(())
+    }
+
+    /// Retrieve a secret by name.
+    pub fn get(&self, name: &str) -> Option<&SecretEntry> {
+        self.secrets.get(name)
+    }
+
+    /// Check if a secret exists.
+    pub fn contains(&self, name: &str) -> bool {
+        self.secrets.contains_key(name)
+    }
+
+    /// Get the source of a secret.
+    pub fn source(&self, name: &str) -> Option<&SecretSource> {
+        self.sources.get(name)
+    }
+
+    /// List all secret names (without exposing values).
+    pub fn names(&self) -> Vec<&str> {
+        self.secrets.keys().map(|s| s.as_str()).collect()
+    }
+
+    /// Number of secrets in the store.
+    pub fn len(&self) -> usize {
+        self.secrets.len()
+    }
+
+    /// Whether the store is empty.
+    pub fn is_empty(&self) -> bool {
+        self.secrets.is_empty()
+    }
+
+    /// Remove a secret by name.
+    pub fn remove(&mut self, name: &str) -> Option<SecretEntry> {
+        self.sources.remove(name);
+        self.secrets.remove(name)
+    }
+
+    /// Resolve every entry in the `[secrets]` config section.
+    ///
+    /// Fails on the first entry whose source cannot be read, so a partial
+    /// store is never returned.
+    pub fn from_config(config: &crustyclaw_config::SecretsConfig) -> Result<Self, SecretError> {
+        let mut store = Self::new();
+        let keystore = open_keystore(config);
+
+        for entry in &config.entries {
+            let (secret, source) = resolve_entry(entry, keystore.as_ref())?;
+            store.insert(secret, source)?;
+        }
+
+        Ok(store)
+    }
+
+    /// Resolve every entry in the `[secrets]` config section without
+    /// building a store, returning each entry that cannot be resolved.
+    ///
+    /// Unlike [`from_config`](Self::from_config) this does not stop at the
+    /// first failure, so startup preflight can report them all at once.
+    pub fn unresolved(config: &crustyclaw_config::SecretsConfig) -> Vec<(String, SecretError)> {
+        let keystore = open_keystore(config);
+        config
+            .entries
+            .iter()
+            .filter_map(|entry| {
+                resolve_entry(entry, keystore.as_ref())
+                    .err()
+                    .map(|e| (entry.name.clone(), e))
+            })
+            .collect()
+    }
+
+    /// Merge freshly resolved secrets into this store.
+    ///
+    /// Entries are compared by value and injection target.
+    /// Changed and removed entries are dropped (zeroizing their values);
+    /// unchanged entries keep their existing allocation and the fresh
+    /// duplicate is zeroized instead.
+    pub fn apply(&mut self, mut fresh: SecretStore) -> SecretDiff {
+        let mut diff = SecretDiff::default();
+
+        let removed: Vec<String> = self
+            .secrets
+            .keys()
+            .filter(|name| !fresh.secrets.contains_key(*name))
+            .cloned()
+            .collect();
+        for name in removed {
+            self.remove(&name);
+            diff.removed.push(name);
+        }
+
+        for (name, entry) in std::mem::take(&mut fresh.secrets) {
+            let source = fresh.sources.remove(&name).unwrap_or(SecretSource::Config);
+            match self.secrets.get(&name) {
+                Some(current)
+                    if current.value == entry.value && current.injection == entry.injection =>
+                {
+                    self.sources.insert(name, source);
+                }
+                Some(_) => {
+                    diff.changed.push(name.clone());
+                    self.secrets.insert(name.clone(), entry);
+                    self.sources.insert(name, source);
+                }
+                None => {
+                    diff.added.push(name.clone());
+                    self.secrets.insert(name.clone(), entry);
+                    self.sources.insert(name, source);
+                }
+            }
+        }
+
+        diff.added.sort();
+        diff.changed.sort();
+        diff.removed.sort();
+        diff
+    }
+
+    /// Re-read one secret from its original source, replacing the value if
+    /// it changed.
+    ///
+    /// Returns `true` when the value changed; the previous value is zeroized.
+    /// On error the current value is kept. Inline (config) and keystore
+    /// secrets return [`SecretError::NotRotatable`].
+    pub fn rotate(&mut self, name: &str) -> Result<bool, SecretError> {
+        let source = self
+            .sources
+            .get(name)
+            .ok_or_else(|| SecretError::NotFound(name.to_string()))?;
+        let value = match source {
+            SecretSource::Environment(var) => SecretValue::new(
+                std::env::var(var).map_err(|_| SecretError::EnvNotSet(var.clone()))?,
+            ),
+            SecretSource::File(path) => read_secret_file(path)?,
+            SecretSource::Keychain { service, account } => {
+                SecretValue::new(read_keychain(service, account)?)
+            }
+            SecretSource::Config | SecretSource::Keystore(_) => {
+                return Err(SecretError::NotRotatable(name.to_string()));
+            }
+        };
+        if value.is_empty() {
+            return Err(SecretError::EmptyValue(name.to_string()));
+        }
+
+        let entry = self
+            .secrets
+            .get_mut(name)
+            .ok_or_else(|| SecretError::NotFound(name.to_string()))?;
+        if entry.value == value {
+            return Ok(false);
+        }
+        entry.value = value;
+        Ok(true)
+    }
+
+    /// Rotate every env-, file-, and keychain-sourced secret.
+    ///
+    /// Returns the names whose value changed (in [`SecretDiff::changed`])
+    /// and the secrets whose source could not be re-read, which keep their
+    /// current value.
+    pub fn rotate_all(&mut self) -> (SecretDiff, Vec<(String, SecretError)>) {
+        let mut diff = SecretDiff::default();
+        let mut failures = Vec::new();
+
+        let mut names: Vec<String> = self
+            .sources
+            .iter()
+            .filter(|(_, source)| {
+                matches!(
+                    source,
+                    SecretSource::Environment(_)
+                        | SecretSource::File(_)
+                        | SecretSource::Keychain { .. }
+                )
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        for name in names {
+            match self.rotate(&name) {
+                Ok(true) => diff.changed.push(name),
+                Ok(false) => {}
+                Err(e) => failures.push((name, e)),
+            }
+        }
+        (diff, failures)
+    }
+
+    /// Load a secret from an environment variable.
+    ///
+    /// Convention: looks for `CRUSTYCLAW_SECRET_<NAME>` (uppercased).
+    pub fn load_from_env(
+        &mut self,
+        name: &str,
+        injection: InjectionMethod,
+    ) -> Result<(), SecretError> {
+        let env_key = format!("CRUSTYCLAW_SECRET_{}", name.to_uppercase());
+        let value = std::env::var(&env_key).map_err(|_| SecretError::EnvNotSet(env_key.clone()))?;
+
+        let entry = SecretEntry {
+            name: name.to_string(),
+            value: SecretValue::new(value),
+            injection,
+            description: format!("Loaded from environment variable {env_key}"),
+        };
+
+        self.insert(entry, SecretSource::Environment(env_key))
+    }
+
+    /// Load a secret from a file.
+    ///
+    /// The file contents (trimmed of trailing newlines) become the secret value.
+    pub fn load_from_file(
+        &mut self,
+        name: &str,
+        path: &Path,
+        injection: InjectionMethod,
+    ) -> Result<(), SecretError> {
+        let value = read_secret_file(path)?;
+        let entry = SecretEntry {
+            name: name.to_string(),
+            value,
+            injection,
+            description: format!("Loaded from file {}", path.display()),
+        };
+
+        self.insert(entry, SecretSource::File(path.to_path_buf()))
+    }
+
+    /// Generate environment variables for container injection.
+    ///
+    /// Returns a map of env var name → secret value for all secrets
+    /// configured with [`InjectionMethod::Env`] or [`InjectionMethod::Both`].
+    pub fn env_injections(&self) -> HashMap<String, String> {
+        let mut envs = HashMap::new();
+        for entry in self.secrets.values() {
+            match &entry.injection {
+                InjectionMethod::Env(var_name) => {
+                    envs.insert(var_name.clone(), entry.value.expose().to_string());
+                }
+                InjectionMethod::Both { env_name, .. } => {
+                    envs.insert(env_name.clone(), entry.value.expose().to_string());
+                }
+                InjectionMethod::File(_) => {}
+            }
+        }
+        envs
+    }
+
+    /// Generate file injection specifications.
+    ///
+    /// Returns a list of `(guest_path, secret_value)` tuples for all secrets
+    /// configured with [`InjectionMethod::File`] or [`InjectionMethod::Both`].
+    pub fn file_injections(&self) -> Vec<FileInjection> {
+        let mut files = Vec::new();
+        for entry in self.secrets.values() {
+            match &entry.injection {
+                InjectionMethod::File(path) => {
+                    files.push(FileInjection {
+                        guest_path: path.clone(),
+                        content: entry.value.expose().to_string(),
+                        secret_name: entry.name.clone(),
+                    });
+                }
+                InjectionMethod::Both { file_path, .. } => {
+                    files.push(FileInjection {
+                        guest_path: file_path.clone(),
+                        content: entry.value.expose().to_string(),
+                        secret_name: entry.name.clone(),
+                    });
+                }
+                InjectionMethod::Env(_) => {}
+            }
+        }
+        files
+    }
+
+    /// Write file-injected secrets to a staging directory on the host.
+    ///
+    /// This prepares secrets for bind-mounting into containers. Each secret
+    /// gets its own file under `staging_dir` with restrictive permissions.
+    ///
+    /// Returns the list of created files (host_path, guest_path) for mounting.
+    pub fn stage_file_injections(
+        &self,
+        staging_dir: &Path,
+    ) -> Result<Vec<StagedSecret>, SecretError> {
+        let mut staged = Vec::new();
+
+        for injection in self.file_injections() {
+            // Create a host-side file named after the secret
+            let host_file = staging_dir.join(&injection.secret_name);
+            write_staged_file(&host_file, injection.content.as_bytes())
+                .map_err(SecretError::FileWrite)?;
+
+            staged.push(StagedSecret {
+                host_path: host_file,
+                guest_path: injection.guest_path.clone(),
+                secret_name: injection.secret_name.clone(),
+            });
+        }
+
+        Ok(staged)
+    }
+
+    /// Rewrite the staged files of `names` under `staging_dir` with their
+    /// current values.
+    ///
+    /// Only files that are already staged are rewritten; the rest are staged
+    /// when the next sandbox needs them. Files are replaced atomically, so a
+    /// sandbox that already mounted the old file keeps it until it exits.
+    pub fn restage(
+        &self,
+        staging_dir: &Path,
+        names: &[String],
+    ) -> Result<Vec<StagedSecret>, SecretError> {
+        let mut staged = Vec::new();
+
+        for injection in self.file_injections() {
+            if !names.contains(&injection.secret_name) {
+                continue;
+            }
+            let host_file = staging_dir.join(&injection.secret_name);
+            if !host_file.exists() {
+                continue;
+            }
+            write_staged_file(&host_file, injection.content.as_bytes())
+                .map_err(SecretError::FileWrite)?;
+            staged.push(StagedSecret {
+                host_path: host_file,
+                guest_path: injection.guest_path.clone(),
+                secret_name: injection.secret_name.clone(),
+            });
+        }
+
+        Ok(staged)
+    }
+}
+
+/// Write a staged secret file with owner read-only permissions.
+///
+/// The content goes to a temporary sibling first and is renamed into place,
+/// so an existing (read-only) staged file is replaced rather than rewritten.
+pub(crate) fn write_staged_file(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let tmp = path.with_file_name(format!(".{file_name}.tmp"));
+    let _ = std::fs::remove_file(&tmp);
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o400);
+    }
+    let result = options
+        .open(&tmp)
+        .and_then(|mut file| file.write_all(content))
+        .and_then(|()| std::fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// Read a secret file, dropping trailing newlines.
+fn read_secret_file(path: &Path) -> Result<SecretValue, SecretError> {
+    let mut value = std::fs::File::open(path)
+        .and_then(SecretValue::from_reader)
+        .map_err(|e| SecretError::FileRead {
+            path: path.to_path_buf(),
+            source: e,
+        })?;
+    value.trim_end_newlines();
+    Ok(value)
+}
+
+/// The keychain service and account a `source = "keychain"` entry names.
+pub fn keychain_item(entry: &crustyclaw_config::SecretEntryConfig) -> (String, String) {
+    (
+        entry
+            .keychain_service
+            .clone()
+            .unwrap_or_else(|| keychain::DEFAULT_SERVICE.to_string()),
+        entry
+            .keychain_account
+            .clone()
+            .unwrap_or_else(|| entry.name.clone()),
+    )
+}
+
+/// Read the keychain item `service`/`account`.
+fn read_keychain(service: &str, account: &str) -> Result<String, SecretError> {
+    match keychain::get(service, account) {
+        Ok(Some(value)) => Ok(value),
+        Ok(None) => Err(SecretError::Keychain(format!(
+            "no keychain item {service}/{account}; `crustyclaw secrets import` moves \
+             env-sourced secrets into the keychain"
+        ))),
+        Err(e) => Err(SecretError::Keychain(format!(
+            "keychain item {service}/{account}: {e}"
+        ))),
+    }
+}
+
+/// Unlock the keystore if any entry reads from it.
+fn open_keystore(
+    config: &crustyclaw_config::SecretsConfig,
+) -> Option<Result<Keystore, KeystoreError>> {
+    config
+        .entries
+        .iter()
+        .any(|entry| entry.source == "keystore")
+        .then(|| Keystore::open(&config.keystore))
+}
+
+/// Read one `[[secrets.entries]]` item from its source; `keystore` is the
+/// result of [`open_keystore`].
+fn resolve_entry(
+    entry: &crustyclaw_config::SecretEntryConfig,
+    keystore: Option<&Result<Keystore, KeystoreError>>,
+) -> Result<(SecretEntry, SecretSource), SecretError> {
+    let injection = match entry.inject_as.as_str() {
+        "file" => InjectionMethod::File(entry.inject_path.clone().unwrap_or_default().into()),
+        "both" => InjectionMethod::Both {
+            env_name: entry.inject_env.clone().unwrap_or_default(),
+            file_path: entry.inject_path.clone().unwrap_or_default().into(),
+        },
+        _ => InjectionMethod::Env(entry.inject_env.clone().unwrap_or_default()),
+    };
+
+    let (value, source) = match entry.source.as_str() {
+        "file" => {
+            let path = PathBuf::from(entry.file_path.clone().unwrap_or_default());
+            (read_secret_file(&path)?, SecretSource::File(path))
+        }
+        "inline" => (
+            SecretValue::new(entry.value.clone().unwrap_or_default()),
+            SecretSource::Config,
+        ),
+        "keystore" => {
+            let keystore = match keystore {
+                Some(Ok(keystore)) => keystore,
+                Some(Err(e)) => return Err(SecretError::Keystore(e.to_string())),
+                None => return Err(SecretError::Keystore("keystore not opened".to_string())),
+            };
+            let value = keystore.get(&entry.name).ok_or_else(|| {
+                SecretError::Keystore(format!(
+                    "'{}' is not in keystore {}; add it with `crustyclaw secrets set {}`",
+                    entry.name,
+                    keystore.path().display(),
+                    entry.name
+                ))
+            })?;
+            (
+                value.clone(),
+                SecretSource::Keystore(keystore.path().to_path_buf()),
+            )
+        }
+        "keychain" => {
+            let (service, account) = keychain_item(entry);
+            (
+                SecretValue::new(read_keychain(&service, &account)?),
+                SecretSource::Keychain { service, account },
+            )
+        }
+        _ => {
+            let env_key = entry
+                .env_var
+                .clone()
+                .unwrap_or_else(|| format!("CRUSTYCLAW_SECRET_{}", entry.name.to_uppercase()));
+            let value =
+                std::env::var(&env_key).map_err(|_| SecretError::EnvNotSet(env_key.clone()))?;
+            (SecretValue::new(value), SecretSource::Environment(env_key))
+        }
+    };
+
+    Ok((
+        SecretEntry {
+            name: entry.name.clone(),
+            value,
+            injection,
+            description: entry.description.clone(),
+        },
+        source,
+    ))
+}
+
+impl Default for SecretStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for SecretStore {
+    fn drop(&mut self) {
+        // SecretEntry values are individually zeroized on drop via SecretValue,
+        // but we also clear the maps to ensure no references linger.
+        self.secrets.clear();
+        self.sources.clear();
+    }
+}
+
+impl fmt::Debug for SecretStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecretStore")
+            .field("count", &self.secrets.len())
+            .field("names", &self.names())
+            .finish()
+    }
+}
+
+/// A file injection specification (secret name, content, guest path).
+pub struct FileInjection {
+    /// Path inside the container where the secret file will appear.
+    pub guest_path: PathBuf,
+    /// The secret content to write. Zeroized when this struct is dropped.
+    pub content: String,
+    /// Name of the secret (for logging/audit).
+    pub secret_name: String,
+}
+
+impl Drop for FileInjection {
+    fn drop(&mut self) {
+        self.content.zeroize();
+    }
+}
+
+/// A secret file staged on the host, ready for bind-mounting.
+#[derive(Debug, Clone)]
+pub struct StagedSecret {
+    /// Path on the host where the secret file was written.
+    pub host_path: PathBuf,
+    /// Path inside the container where the secret should appear.
+    pub guest_path: PathBuf,
+    /// Name of the secret (for logging/audit).
+    pub secret_name: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_value_redacted_debug() {
+        let secret = SecretValue::new("super-secret-api-key");
+        let debug = format!("{secret:?}");
+        assert!(debug.contains("[REDACTED]"));
+        assert!(!debug.contains("super-secret-api-key"));
+    }
+
+    #[test]
+    fn test_secret_value_expose() {
+        let secret = SecretValue::new("my-key-123");
+        assert_eq!(secret.expose(), "my-key-123");
+        assert_eq!(secret.len(), 10);
+        assert!(!secret.is_empty());
+    }
+
+    #[test]
+    fn test_secret_store_basic() {
+        let mut store = SecretStore::new();
+        assert!(store.is_empty());
+
+        let entry = SecretEntry {
+            name: "api_key".to_string(),
+            value: SecretValue::new("sk-1234567890"),
+            injection: InjectionMethod::Env("API_KEY".to_string()),
+            description: "Test API key".to_string(),
+        };
+
+        store.insert(entry, SecretSource::Config).unwrap();
+        assert_eq!(store.len(), 1);
+        assert!(store.contains("api_key"));
+        assert_eq!(store.source("api_key"), Some(&SecretSource::Config));
+
+        let retrieved = store.get("api_key").unwrap();
+        assert_eq!(retrieved.value.expose(), "sk-1234567890");
+    }
+
+    #[test]
+    fn test_secret_store_rejects_empty() {
+        let mut store = SecretStore::new();
+        let entry = SecretEntry {
+            name: "empty".to_string(),
+            value: SecretValue::new(""),
+            injection: InjectionMethod::Env("EMPTY".to_string()),
+            description: String::new(),
+        };
+
+        let result = store.insert(entry, SecretSource::Config);
+        assert!(matches!(result, Err(SecretError::EmptyValue(_))));
+    }
+
+    #[test]
+    fn test_secret_store_remove() {
+        let mut store = SecretStore::new();
+        let entry = SecretEntry {
+            name: "temp".to_string(),
+            value: SecretValue::new("temporary"),
+            injection: InjectionMethod::Env("TEMP".to_string()),
+            description: String::new(),
+        };
+        store.insert(entry, SecretSource::Config).unwrap();
+        assert!(store.contains("temp"));
+
+        store.remove("temp");
+        assert!(!store.contains("temp"));
+    }
+
+    #[test]
+    fn test_env_injections() {
+        let mut store = SecretStore::new();
+
+        store
+            .insert(
+                SecretEntry {
+                    name: "key_a".to_string(),
+                    value: SecretValue::new("value_a"),
+                    injection: InjectionMethod::Env("KEY_A".to_string()),
+                    description: String::new(),
+                },
+                SecretSource::Config,
+            )
+            .unwrap();
+
+        store
+            .insert(
+                SecretEntry {
+                    name: "key_b".to_string(),
+                    value: SecretValue::new("value_b"),
+                    injection: InjectionMethod::File(PathBuf::from("/run/secrets/key_b")),
+                    description: String::new(),
+                },
+                SecretSource::Config,
+            )
+            .unwrap();
+
+        store
+            .insert(
+                SecretEntry {
+                    name: "key_c".to_string(),
+                    value: SecretValue::new("value_c"),
+                    injection: InjectionMethod::Both {
+                        env_name: "KEY_C".to_string(),
+                        file_path: PathBuf::from("/run/secrets/key_c"),
+                    },
+                    description: String::new(),
+                },
+                SecretSource::Config,
+            )
+            .unwrap();
+
+        let envs = store.env_injections();
+        assert_eq!(envs.len(), 2); // key_a (Env) + key_c (Both)
+        assert_eq!(envs.get("KEY_A").unwrap(), "value_a");
+        assert_eq!(envs.get("KEY_C").unwrap(), "value_c");
+        assert!(!envs.contains_key("key_b"));
+    }
+
+    #[test]
+    fn test_file_injections() {
+        let mut store = SecretStore::new();
+
+        store
+            .insert(
+                SecretEntry {
+                    name: "cert".to_string(),
+                    value: SecretValue::new("-----BEGIN CERT-----"),
+                    injection: InjectionMethod::File(PathBuf::from("/run/secrets/cert.pem")),
+                    description: String::new(),
+                },
+                SecretSource::Config,
+            )
+            .unwrap();
+
+        store
+            .insert(
+                SecretEntry {
+                    name: "token".to_string(),
+                    value: SecretValue::new("tok-abc"),
+                    injection: InjectionMethod::Env("TOKEN".to_string()),
+                    description: String::new(),
+                },
+                SecretSource::Config,
+            )
+            .unwrap();
+
+        let files = store.file_injections();
+        assert_eq!(files.len(), 1); // only cert (File)
+        assert_eq!(files[0].guest_path, PathBuf::from("/run/secrets/cert.pem"));
+        assert_eq!(files[0].content, "-----BEGIN CERT-----");
+    }
+
+    #[test]
+    fn test_stage_file_injections() {
+        let mut store = SecretStore::new();
+        store
+            .insert(
+                SecretEntry {
+                    name: "db_password".to_string(),
+                    value: SecretValue::new("s3cret!"),
+                    injection: InjectionMethod::File(PathBuf::from("/run/secrets/db_password")),
+                    description: String::new(),
+                },
+                SecretSource::Config,
+            )
+            .unwrap();
+
+        let staging_dir = std::env::temp_dir().join("crustyclaw-test-staging");
+        std::fs::create_dir_all(&staging_dir).unwrap();
+
+        let staged = store.stage_file_injections(&staging_dir).unwrap();
+        assert_eq!(staged.len(), 1);
+        assert_eq!(staged[0].secret_name, "db_password");
+        assert_eq!(
+            staged[0].guest_path,
+            PathBuf::from("/run/secrets/db_password")
+        );
+
+        // Verify the file was written
+        let content = std::fs::read_to_string(&staged[0].host_path).unwrap();
+        assert_eq!(content, "s3cret!");
+
+        // Cleanup
+        std::fs::remove_dir_all(&staging_dir).ok();
+    }
+
+    #[test]
+    fn test_secret_store_debug_redacted() {
+        let mut store = SecretStore::new();
+        store
+            .insert(
+                SecretEntry {
+                    name: "api_key".to_string(),
+                    value: SecretValue::new("super-secret"),
+                    injection: InjectionMethod::Env("API_KEY".to_string()),
+                    description: String::new(),
+                },
+                SecretSource::Config,
+            )
+            .unwrap();
+
+        let debug = format!("{store:?}");
+        assert!(!debug.contains("super-secret"));
+        assert!(debug.contains("api_key"));
+    }
+
+    #[test]
+    fn test_secret_source_display() {
+        assert_eq!(SecretSource::Config.to_string(), "config");
+        assert_eq!(
+            SecretSource::Environment("MY_VAR".to_string()).to_string(),
+            "env:MY_VAR"
+        );
+        assert_eq!(
+            SecretSource::File(PathBuf::from("/etc/secret")).to_string(),
+            "file:/etc/secret"
+        );
+        assert_eq!(
+            SecretSource::Keychain {
+                service: "crustyclaw".to_string(),
+                account: "llm_api_key".to_string(),
+            }
+            .to_string(),
+            "keychain:crustyclaw/llm_api_key"
+        );
+    }
+
+    #[test]
+    #[allow(unsafe_code)]
+    fn test_load_from_env() {
+        // Set an env var for testing
+        // SAFETY: This test is single-threaded and we clean up the var before returning.
+        unsafe { std::env::set_var("CRUSTYCLAW_SECRET_TEST_KEY", "env-secret-value") };
+
+        let mut store = SecretStore::new();
+        store
+            .load_from_env("test_key", InjectionMethod::Env("TEST_KEY".to_string()))
+            .unwrap();
+
+        let entry = store.get("test_key").unwrap();
+        assert_eq!(entry.value.expose(), "env-secret-value");
+        assert_eq!(
+            store.source("test_key"),
+            Some(&SecretSource::Environment(
+                "CRUSTYCLAW_SECRET_TEST_KEY".to_string()
+            ))
+        );
+
+        // Cleanup
+        // SAFETY: This test is single-threaded and the var was set above.
+        unsafe { std::env::remove_var("CRUSTYCLAW_SECRET_TEST_KEY") };
+    }
+
+    #[test]
+    fn test_load_from_env_not_set() {
+        let mut store = SecretStore::new();
+        let result = store.load_from_env(
+            "nonexistent",
+            InjectionMethod::Env("NONEXISTENT".to_string()),
+        );
+        assert!(matches!(result, Err(SecretError::EnvNotSet(_))));
+    }
+
+    fn inline_config(pairs: &[(&str, &str)]) -> crustyclaw_config::SecretsConfig {
+        let mut toml = String::new();
+        for (name, value) in pairs {
+            toml.push_str(&format!(
+                "[[secrets.entries]]\nname = \"{name}\"\nsource = \"inline\"\nvalue = \"{value}\"\ninject_env = \"{}\"\n",
+                name.to_uppercase()
+            ));
+        }
+        crustyclaw_config::AppConfig::parse(&toml).unwrap().secrets
+    }
+
+    #[test]
+    fn test_eq_compares_value() {
+        let a = SecretValue::new("one");
+        let b = SecretValue::new("one");
+        let c = SecretValue::new("two");
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn test_from_reader() {
+        // Larger than one read, so the buffer has to grow.
+        let pem = format!(
+            "-----BEGIN CERTIFICATE-----\n{}\n-----END CERTIFICATE-----\n",
+            "A".repeat(10_000)
+        );
+        let value = SecretValue::from_reader(pem.as_bytes()).unwrap();
+        assert_eq!(value.expose(), pem);
+        assert_eq!(value.clone().expose(), pem);
+        if cfg!(not(feature = "mlock")) {
+            assert!(!value.is_locked());
+        }
+
+        let err = SecretValue::from_reader(&[0xff, 0xfe][..]).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_from_config_inline_and_file() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("token");
+        std::fs::write(&path, "file-secret\n").unwrap();
+
+        let mut config = inline_config(&[("api_key", "inline-secret")]);
+        config.entries.push(crustyclaw_config::SecretEntryConfig {
+            name: "token".to_string(),
+            source: "file".to_string(),
+            env_var: None,
+            file_path: Some(path.display().to_string()),
+            value: None,
+            keychain_service: None,
+            keychain_account: None,
+            inject_as: "file".to_string(),
+            inject_env: None,
+            inject_path: Some("/run/secrets/token".to_string()),
+            egress_hosts: Vec::new(),
+            description: String::new(),
+        });
+
+        let store = SecretStore::from_config(&config).unwrap();
+        assert_eq!(store.len(), 2);
+        assert_eq!(
+            store.get("api_key").unwrap().value.expose(),
+            "inline-secret"
+        );
+        assert_eq!(
+            store.get("api_key").unwrap().injection,
+            InjectionMethod::Env("API_KEY".to_string())
+        );
+        assert_eq!(store.get("token").unwrap().value.expose(), "file-secret");
+        assert_eq!(store.source("token"), Some(&SecretSource::File(path)));
+    }
+
+    #[test]
+    fn test_from_config_keystore() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let mut config = crustyclaw_config::AppConfig::parse(
+            "[[secrets.entries]]\nname = \"github_token\"\nsource = \"keystore\"\ninject_env = \"GITHUB_TOKEN\"\n",
+        )
+        .unwrap()
+        .secrets;
+        config.keystore.path = tmp.path().join("secrets.keystore").display().to_string();
+        config.keystore.key_file = tmp.path().join("secrets.key").display().to_string();
+
+        // No key yet.
+        let unresolved = SecretStore::unresolved(&config);
+        assert_eq!(unresolved.len(), 1);
+        assert!(unresolved[0].1.to_string().contains("no keystore key"));
+
+        let mut keystore = Keystore::open_or_create(&config.keystore).unwrap();
+        keystore.save().unwrap();
+        let err = SecretStore::from_config(&config).unwrap_err();
+        assert!(
+            err.to_string().contains("secrets set github_token"),
+            "{err}"
+        );
+
+        keystore.set("github_token", SecretValue::new("ghp_abc123"));
+        keystore.save().unwrap();
+        let mut store = SecretStore::from_config(&config).unwrap();
+        assert_eq!(
+            store.get("github_token").unwrap().value.expose(),
+            "ghp_abc123"
+        );
+        assert_eq!(
+            store.source("github_token"),
+            Some(&SecretSource::Keystore(keystore.path().to_path_buf()))
+        );
+        assert!(matches!(
+            store.rotate("github_token"),
+            Err(SecretError::NotRotatable(_))
+        ));
+        assert!(store.rotate_all().1.is_empty());
+    }
+
+    #[test]
+    fn test_from_config_missing_env() {
+        let config = crustyclaw_config::AppConfig::parse(
+            "[[secrets.entries]]\nname = \"missing\"\nenv_var = \"CRUSTYCLAW_TEST_UNSET_VAR\"\ninject_env = \"X\"\n",
+        )
+        .unwrap()
+        .secrets;
+        let result = SecretStore::from_config(&config);
+        assert!(
+            matches!(result, Err(SecretError::EnvNotSet(ref v)) if v == "CRUSTYCLAW_TEST_UNSET_VAR")
+        );
+    }
+
+    #[test]
+    fn test_unresolved_reports_every_failure() {
+        let mut config = inline_config(&[("ok", "value")]);
+        for (name, var) in [
+            ("a", "CRUSTYCLAW_TEST_UNSET_A"),
+            ("b", "CRUSTYCLAW_TEST_UNSET_B"),
+        ] {
+            config.entries.push(crustyclaw_config::SecretEntryConfig {
+                name: name.to_string(),
+                source: "env".to_string(),
+                env_var: Some(var.to_string()),
+                file_path: None,
+                value: None,
+                keychain_service: None,
+                keychain_account: None,
+                inject_as: "env".to_string(),
+                inject_env: Some("X".to_string()),
+                inject_path: None,
+                egress_hosts: Vec::new(),
+                description: String::new(),
+            });
+        }
+
+        let unresolved = SecretStore::unresolved(&config);
+        let names: Vec<&str> = unresolved.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, vec!["a", "b"]);
+        assert!(SecretStore::unresolved(&inline_config(&[("ok", "value")])).is_empty());
+    }
+
+    #[test]
+    fn test_apply_diffs_by_value() {
+        let mut store = SecretStore::from_config(&inline_config(&[
+            ("keep", "same"),
+            ("rotate", "old"),
+            ("drop", "x"),
+        ]))
+        .unwrap();
+
+        let fresh = SecretStore::from_config(&inline_config(&[
+            ("keep", "same"),
+            ("rotate", "new"),
+            ("add", "y"),
+        ]))
+        .unwrap();
+        let diff = store.apply(fresh);
+
+        assert_eq!(diff.added, vec!["add"]);
+        assert_eq!(diff.changed, vec!["rotate"]);
+        assert_eq!(diff.removed, vec!["drop"]);
+        assert_eq!(diff.affected(), vec!["add", "drop", "rotate"]);
+
+        assert_eq!(store.len(), 3);
+        assert_eq!(store.get("rotate").unwrap().value.expose(), "new");
+        assert!(!store.contains("drop"));
+        assert!(store.source("drop").is_none());
+    }
+
+    #[test]
+    fn test_apply_unchanged_is_empty() {
+        let config = inline_config(&[("keep", "same")]);
+        let mut store = SecretStore::from_config(&config).unwrap();
+        let diff = store.apply(SecretStore::from_config(&config).unwrap());
+        assert!(diff.is_empty());
+        assert_eq!(store.get("keep").unwrap().value.expose(), "same");
+    }
+
+    fn file_store(dir: &Path, value: &str) -> (SecretStore, PathBuf) {
+        let path = dir.join("token");
+        std::fs::write(&path, format!("{value}\n")).unwrap();
+        let mut store = SecretStore::new();
+        store
+            .load_from_file(
+                "token",
+                &path,
+                InjectionMethod::File(PathBuf::from("/run/secrets/token")),
+            )
+            .unwrap();
+        (store, path)
+    }
+
+    #[test]
+    fn test_rotate_file_secret() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut store, path) = file_store(dir.path(), "v1");
+
+        assert!(!store.rotate("token").unwrap());
+        std::fs::write(&path, "v2\n").unwrap();
+        assert!(store.rotate("token").unwrap());
+        assert_eq!(store.get("token").unwrap().value.expose(), "v2");
+
+        // An unreadable source keeps the current value.
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            store.rotate("token"),
+            Err(SecretError::FileRead { .. })
+        ));
+        assert_eq!(store.get("token").unwrap().value.expose(), "v2");
+
+        assert!(matches!(
+            store.rotate("missing"),
+            Err(SecretError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_rotate_all_skips_inline() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut store, path) = file_store(dir.path(), "v1");
+        store
+            .insert(
+                SecretEntry {
+                    name: "inline_key".to_string(),
+                    value: SecretValue::new("abc"),
+                    injection: InjectionMethod::Env("INLINE_KEY".to_string()),
+                    description: String::new(),
+                },
+                SecretSource::Config,
+            )
+            .unwrap();
+        assert!(matches!(
+            store.rotate("inline_key"),
+            Err(SecretError::NotRotatable(_))
+        ));
+
+        std::fs::write(&path, "v2").unwrap();
+        let (diff, failures) = store.rotate_all();
+        assert_eq!(diff.changed, vec!["token"]);
+        assert!(failures.is_empty());
+        assert_eq!(store.get("inline_key").unwrap().value.expose(), "abc");
+    }
+
+    #[test]
+    fn test_restage_replaces_staged_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let staging = dir.path().join("staging");
+        std::fs::create_dir(&staging).unwrap();
+        let (mut store, path) = file_store(dir.path(), "v1");
+
+        // Nothing staged yet: restage leaves it for the next sandbox.
+        assert!(
+            store
+                .restage(&staging, &["token".to_string()])
+                .unwrap()
+                .is_empty()
+        );
+
+        let staged = store.stage_file_injections(&staging).unwrap();
+        std::fs::write(&path, "v2").unwrap();
+        store.rotate("token").unwrap();
+        let restaged = store.restage(&staging, &["token".to_string()]).unwrap();
+        assert_eq!(restaged.len(), 1);
+        assert_eq!(std::fs::read_to_string(&staged[0].host_path).unwrap(), "v2");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&staged[0].host_path)
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o400);
+        }
+    }
+}
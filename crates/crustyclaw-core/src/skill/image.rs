@@ -0,0 +1,451 @@
+//! Sandbox images built from skill manifests.
+//!
+//! A manifest's `[image]` table names a base image and the system packages
+//! the skill needs. [`build`] renders that into a Containerfile, builds it
+//! with `docker build` or `buildah bud`, and tags the result
+//! `crustyclaw-skill-<name>:<version>-<hash>`, where `<hash>` abbreviates
+//! the [`content_hash`] of the Containerfile. The image ID is then recorded
+//! in the skills directory's [`LOCK_FILE`]:
+//!
+//! ```toml
+//! [skills.weather]
+//! version = "1.2.0"
+//! content_hash = "sha256:9f86d0…"
+//! image = "crustyclaw-skill-weather:1.2.0-9f86d081884c"
+//! digest = "sha256:3c1f4a…"
+//! builder = "docker"
+//! ```
+//!
+//! The [`SkillLoader`](super::manifest::SkillLoader) runs a skill in its
+//! locked image, by digest, as long as the lock entry's content hash still
+//! matches the manifest. A stale or missing entry falls back to the
+//! backend's default image with a warning.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::manifest::SkillManifest;
+
+/// File name of the lockfile inside the skills directory.
+pub const LOCK_FILE: &str = "skills.lock";
+
+/// Errors building a skill image or reading the lockfile.
+#[derive(Debug, thiserror::Error)]
+pub enum ImageError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("skill '{0}' declares no [image] table")]
+    NoImage(String),
+
+    #[error("no image builder available (tried docker and buildah)")]
+    NoBuilder,
+
+    #[error("unknown image builder '{0}' (expected \"docker\" or \"buildah\")")]
+    UnknownBuilder(String),
+
+    #[error("image build failed: {0}")]
+    Build(String),
+
+    #[error("invalid lockfile: {0}")]
+    Lock(String),
+}
+
+/// A tool that builds images from a Containerfile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImageBuilder {
+    /// `docker build`.
+    Docker(PathBuf),
+    /// `buildah bud`.
+    Buildah(PathBuf),
+}
+
+impl ImageBuilder {
+    /// The builder named `name` ("docker" or "buildah"), using the binary on
+    /// `PATH`.
+    pub fn from_name(name: &str) -> Result<Self, ImageError> {
+        match name {
+            "docker" => Ok(Self::Docker(PathBuf::from("docker"))),
+            "buildah" => Ok(Self::Buildah(PathBuf::from("buildah"))),
+            other => Err(ImageError::UnknownBuilder(other.to_string())),
+        }
+    }
+
+    /// The first responsive builder, preferring Docker so the images land
+    /// where the Docker sandbox backend looks for them.
+    pub fn detect() -> Result<Self, ImageError> {
+        [
+            Self::Docker(PathBuf::from("docker")),
+            Self::Buildah(PathBuf::from("buildah")),
+        ]
+        .into_iter()
+        .find(Self::available)
+        .ok_or(ImageError::NoBuilder)
+    }
+
+    /// Short name recorded in the lockfile.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Docker(_) => "docker",
+            Self::Buildah(_) => "buildah",
+        }
+    }
+
+    fn bin(&self) -> &Path {
+        match self {
+            Self::Docker(bin) | Self::Buildah(bin) => bin,
+        }
+    }
+
+    /// Whether the builder's CLI is installed and responsive.
+    pub fn available(&self) -> bool {
+        std::process::Command::new(self.bin())
+            .arg("version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success())
+    }
+
+    /// The build argument list for `containerfile` in `context`.
+    fn build_args(
+        &self,
+        containerfile: &Path,
+        context: &Path,
+        tag: &str,
+        iidfile: &Path,
+    ) -> Vec<String> {
+        let subcommand = match self {
+            Self::Docker(_) => "build",
+            Self::Buildah(_) => "bud",
+        };
+        vec![
+            subcommand.to_string(),
+            "--tag".to_string(),
+            tag.to_string(),
+            "--iidfile".to_string(),
+            iidfile.display().to_string(),
+            "--file".to_string(),
+            containerfile.display().to_string(),
+            context.display().to_string(),
+        ]
+    }
+}
+
+/// A built image as recorded in the lockfile.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedImage {
+    /// Skill version the image was built for.
+    pub version: String,
+    /// [`content_hash`] of the Containerfile the image was built from.
+    pub content_hash: String,
+    /// Tag given to the image.
+    pub image: String,
+    /// Image ID (`sha256:…`) that sandboxes run.
+    pub digest: String,
+    /// Builder that produced the image.
+    pub builder: String,
+}
+
+/// The contents of [`LOCK_FILE`], keyed by skill name.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkillLock {
+    #[serde(default)]
+    pub skills: BTreeMap<String, LockedImage>,
+}
+
+impl SkillLock {
+    /// Read the lockfile in `skills_dir`; a missing file is an empty lock.
+    pub fn load(skills_dir: &Path) -> Result<Self, ImageError> {
+        let path = skills_dir.join(LOCK_FILE);
+        match std::fs::read_to_string(&path) {
+            Ok(content) => toml::from_str(&content).map_err(|e| ImageError::Lock(e.to_string())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Write the lockfile into `skills_dir` atomically.
+    pub fn save(&self, skills_dir: &Path) -> Result<(), ImageError> {
+        let body = toml::to_string(self).map_err(|e| ImageError::Lock(e.to_string()))?;
+        let path = skills_dir.join(LOCK_FILE);
+        let tmp = path.with_extension("lock.partial");
+        std::fs::write(
+            &tmp,
+            format!("# Generated by `crustyclaw skill build-image`. Do not edit.\n\n{body}"),
+        )?;
+        std::fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    /// The entry for `skill`, if any.
+    pub fn get(&self, skill: &str) -> Option<&LockedImage> {
+        self.skills.get(skill)
+    }
+
+    /// The image to run `manifest` in: the locked digest, if the entry was
+    /// built from the manifest's current `[image]` table.
+    pub fn pinned_image(&self, manifest: &SkillManifest) -> Option<&str> {
+        let hash = content_hash(&containerfile(manifest)?);
+        let locked = self.get(&manifest.name)?;
+        (locked.content_hash == hash).then_some(locked.digest.as_str())
+    }
+
+    /// Record `image` as the build of `skill`, replacing any earlier entry.
+    pub fn insert(&mut self, skill: impl Into<String>, image: LockedImage) {
+        self.skills.insert(skill.into(), image);
+    }
+}
+
+/// Render the Containerfile for `manifest`, if it has an `[image]` table.
+pub fn containerfile(manifest: &SkillManifest) -> Option<String> {
+    let image = manifest.image.as_ref()?;
+    let mut out = format!(
+        "# Generated by crustyclaw for skill {:?} {}\nFROM {}\n",
+        manifest.name, manifest.version, image.base
+    );
+    if !image.packages.is_empty() {
+        let packages = image.packages.join(" ");
+        let install = match image.package_manager.as_str() {
+            "apk" => format!("apk add --no-cache {packages}"),
+            "dnf" => format!("dnf install -y {packages} && dnf clean all"),
+            _ => format!(
+                "apt-get update && apt-get install -y --no-install-recommends {packages} \
+                 && rm -rf /var/lib/apt/lists/*"
+            ),
+        };
+        out.push_str(&format!("RUN {install}\n"));
+    }
+    out.push_str(&format!(
+        "LABEL crustyclaw.skill={:?} crustyclaw.skill.version={:?}\n",
+        manifest.name, manifest.version
+    ));
+    Some(out)
+}
+
+/// SHA-256 of a Containerfile, as `sha256:<hex>`.
+pub fn content_hash(containerfile: &str) -> String {
+    let digest = Sha256::digest(containerfile.as_bytes());
+    let hex: String = digest.iter().map(|b| format!("{b:02x}")).collect();
+    format!("sha256:{hex}")
+}
+
+/// The tag for `manifest` built from a Containerfile with `hash`.
+pub fn image_tag(manifest: &SkillManifest, hash: &str) -> String {
+    let hex = hash.strip_prefix("sha256:").unwrap_or(hash);
+    format!(
+        "crustyclaw-skill-{}:{}-{}",
+        manifest.name.to_lowercase(),
+        manifest.version,
+        &hex[..hex.len().min(12)]
+    )
+}
+
+/// Build the image for `manifest` with `builder`.
+///
+/// The build runs in an empty scratch context; the skill directory is
+/// mounted at run time rather than copied in. Builder output is passed
+/// through to this process's stdout and stderr.
+pub async fn build(
+    manifest: &SkillManifest,
+    builder: &ImageBuilder,
+) -> Result<LockedImage, ImageError> {
+    let containerfile =
+        containerfile(manifest).ok_or_else(|| ImageError::NoImage(manifest.name.clone()))?;
+    let hash = content_hash(&containerfile);
+    let tag = image_tag(manifest, &hash);
+
+    let scratch = std::env::temp_dir().join(format!(
+        "crustyclaw-build-{}-{}",
+        manifest.name,
+        std::process::id()
+    ));
+    let context = scratch.join("context");
+    std::fs::create_dir_all(&context)?;
+    let result = run_build(builder, &scratch, &context, &containerfile, &tag).await;
+    let _ = std::fs::remove_dir_all(&scratch);
+
+    Ok(LockedImage {
+        version: manifest.version.clone(),
+        content_hash: hash,
+        image: tag,
+        digest: result?,
+        builder: builder.name().to_string(),
+    })
+}
+
+/// Run the builder and return the built image ID.
+async fn run_build(
+    builder: &ImageBuilder,
+    scratch: &Path,
+    context: &Path,
+    containerfile: &str,
+    tag: &str,
+) -> Result<String, ImageError> {
+    let file = scratch.join("Containerfile");
+    let iidfile = scratch.join("image.id");
+    std::fs::write(&file, containerfile)?;
+
+    let args = builder.build_args(&file, context, tag, &iidfile);
+    tracing::info!(builder = builder.name(), tag, "Building skill image");
+    let status = tokio::process::Command::new(builder.bin())
+        .args(&args)
+        .status()
+        .await
+        .map_err(|e| ImageError::Build(format!("failed to spawn {}: {e}", builder.name())))?;
+    if !status.success() {
+        return Err(ImageError::Build(format!(
+            "{} exited with {status}",
+            builder.name()
+        )));
+    }
+
+    let id = std::fs::read_to_string(&iidfile)
+        .map_err(|e| ImageError::Build(format!("{} wrote no image ID: {e}", builder.name())))?;
+    let id = id.trim();
+    if id.is_empty() {
+        return Err(ImageError::Build(format!(
+            "{} wrote an empty image ID",
+            builder.name()
+        )));
+    }
+    Ok(if id.starts_with("sha256:") {
+        id.to_string()
+    } else {
+        format!("sha256:{id}")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(image: &str) -> SkillManifest {
+        SkillManifest::parse(&format!(
+            "name = \"weather\"\nversion = \"1.2.0\"\ndescription = \"\"\ncommand = [\"true\"]\n[image]\n{image}"
+        ))
+        .unwrap()
+    }
+
+    /// A fake builder that logs its arguments and writes an unprefixed ID.
+    fn fake_builder(dir: &Path) -> ImageBuilder {
+        use std::os::unix::fs::PermissionsExt;
+
+        let bin = dir.join("buildah");
+        std::fs::write(
+            &bin,
+            format!(
+                "#!/bin/sh\necho \"$@\" >> {}\nwhile [ $# -gt 0 ]; do\n  if [ \"$1\" = --iidfile ]; then printf 'feedface\\n' > \"$2\"; fi\n  shift\ndone\n",
+                dir.join("calls.log").display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&bin, std::fs::Permissions::from_mode(0o755)).unwrap();
+        ImageBuilder::Buildah(bin)
+    }
+
+    #[test]
+    fn test_containerfile_per_package_manager() {
+        let apk = manifest(
+            "base = \"alpine:3.20\"\npackages = [\"curl\", \"jq\"]\npackage_manager = \"apk\"\n",
+        );
+        let file = containerfile(&apk).unwrap();
+        assert!(file.contains("FROM alpine:3.20\n"));
+        assert!(file.contains("RUN apk add --no-cache curl jq\n"));
+        assert!(file.contains("crustyclaw.skill.version=\"1.2.0\""));
+
+        let apt = manifest("base = \"debian:12\"\npackages = [\"curl\"]\n");
+        let file = containerfile(&apt).unwrap();
+        assert!(file.contains("apt-get install -y --no-install-recommends curl"));
+
+        let bare = manifest("base = \"python:3.12\"\n");
+        assert!(!containerfile(&bare).unwrap().contains("RUN"));
+    }
+
+    #[test]
+    fn test_hash_and_tag() {
+        let a = manifest("base = \"alpine:3.20\"\n");
+        let b = manifest("base = \"alpine:3.21\"\n");
+        let hash_a = content_hash(&containerfile(&a).unwrap());
+        let hash_b = content_hash(&containerfile(&b).unwrap());
+        assert!(hash_a.starts_with("sha256:"));
+        assert_eq!(hash_a.len(), "sha256:".len() + 64);
+        assert_ne!(hash_a, hash_b);
+        assert_eq!(hash_a, content_hash(&containerfile(&a).unwrap()));
+
+        let tag = image_tag(&a, &hash_a);
+        assert_eq!(
+            tag,
+            format!("crustyclaw-skill-weather:1.2.0-{}", &hash_a[7..19])
+        );
+    }
+
+    #[test]
+    fn test_lock_roundtrip_and_pinning() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(SkillLock::load(dir.path()).unwrap(), SkillLock::default());
+
+        let current = manifest("base = \"alpine:3.20\"\n");
+        let mut lock = SkillLock::default();
+        lock.insert(
+            "weather",
+            LockedImage {
+                version: "1.2.0".to_string(),
+                content_hash: content_hash(&containerfile(&current).unwrap()),
+                image: "crustyclaw-skill-weather:1.2.0-abc".to_string(),
+                digest: "sha256:feedface".to_string(),
+                builder: "docker".to_string(),
+            },
+        );
+        lock.save(dir.path()).unwrap();
+        let loaded = SkillLock::load(dir.path()).unwrap();
+        assert_eq!(loaded, lock);
+        assert_eq!(loaded.pinned_image(&current), Some("sha256:feedface"));
+
+        // Changing the declared dependencies makes the entry stale.
+        let changed =
+            manifest("base = \"alpine:3.20\"\npackages = [\"curl\"]\npackage_manager = \"apk\"\n");
+        assert_eq!(loaded.pinned_image(&changed), None);
+
+        std::fs::write(dir.path().join(LOCK_FILE), "skills = 1").unwrap();
+        assert!(matches!(
+            SkillLock::load(dir.path()),
+            Err(ImageError::Lock(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_build_with_fake_builder() {
+        let dir = tempfile::tempdir().unwrap();
+        let builder = fake_builder(dir.path());
+        let manifest = manifest("base = \"alpine:3.20\"\n");
+
+        let locked = build(&manifest, &builder).await.unwrap();
+        assert_eq!(locked.digest, "sha256:feedface");
+        assert_eq!(locked.builder, "buildah");
+        assert_eq!(locked.version, "1.2.0");
+        assert_eq!(locked.image, image_tag(&manifest, &locked.content_hash));
+
+        let calls = std::fs::read_to_string(dir.path().join("calls.log")).unwrap();
+        assert!(calls.starts_with(&format!("bud --tag {} --iidfile ", locked.image)));
+    }
+
+    #[tokio::test]
+    async fn test_build_requires_image_table() {
+        let manifest =
+            SkillManifest::parse("name = \"a\"\ndescription = \"\"\ncommand = [\"true\"]\n")
+                .unwrap();
+        let err = build(&manifest, &ImageBuilder::Docker(PathBuf::from("docker")))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ImageError::NoImage(_)));
+        assert!(matches!(
+            ImageBuilder::from_name("kaniko"),
+            Err(ImageError::UnknownBuilder(_))
+        ));
+    }
+}
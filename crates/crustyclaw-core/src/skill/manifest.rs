@@ -0,0 +1,842 @@
+//! Skill discovery from `skill.toml` manifests.
+//!
+//! Each subdirectory of the skills directory holding a `skill.toml` is one
+//! skill:
+//!
+//! ```toml
+//! name = "weather"
+//! version = "1.2.0"
+//! description = "Looks up the forecast"
+//! command = ["python3", "/skill/weather.py"]
+//! trust_tier = "untrusted"
+//!
+//! [image]
+//! base = "python:3.12-alpine"
+//! packages = ["curl"]
+//! package_manager = "apk"
+//!
+//! [isolation]
+//! level = "l3"
+//! memory_bytes = 134217728
+//! timeout_secs = 30
+//! network = "outbound-only"
+//!
+//! [[secrets]]
+//! name = "weather_api_key"
+//! env = "WEATHER_API_KEY"
+//! ```
+//!
+//! The skill's directory is mounted read-only at [`SKILL_MOUNT`] and used as
+//! the working directory. Omitted isolation settings fall back to the
+//! `[isolation]` defaults of the daemon config, and an omitted trust tier to
+//! `isolation.default_trust_tier` (or `untrusted`). A secret without `env` or
+//! `file` is injected the way its `[[secrets.entries]]` entry says.
+//!
+//! A manifest with an `[image]` table runs in the image built for it by
+//! `crustyclaw skill build-image` and pinned in the skills directory's
+//! [`LOCK_FILE`](super::image::LOCK_FILE); see [`super::image`].
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crustyclaw_config::IsolationConfig;
+
+use super::IsolatedSkill;
+use super::image::{LOCK_FILE, SkillLock};
+use crate::isolation::{
+    EgressProxy, IsolationLevel, NetworkPolicy, SandboxConfig, SandboxPool, SecretInjection,
+    SharedMount, TrustBasedSelector, TrustTier,
+};
+use crate::secrets::{InjectionMethod, SecretStore};
+
+/// File name of a skill manifest inside its skill directory.
+pub const MANIFEST_FILE: &str = "skill.toml";
+
+/// Where the skill directory appears inside the sandbox.
+pub const SKILL_MOUNT: &str = "/skill";
+
+/// Errors loading a skill manifest.
+#[derive(Debug, thiserror::Error)]
+pub enum ManifestError {
+    #[error("failed to read manifest: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("failed to parse manifest: {0}")]
+    Parse(#[from] toml::de::Error),
+
+    #[error("invalid manifest: {0}")]
+    Invalid(String),
+}
+
+/// A parsed `skill.toml`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SkillManifest {
+    /// Unique skill name (letters, digits, `-` and `_`).
+    pub name: String,
+    /// Skill version, used in image tags.
+    #[serde(default = "default_version")]
+    pub version: String,
+    /// Short description shown in `crustyclaw skills`.
+    pub description: String,
+    /// Program and arguments run inside the sandbox.
+    pub command: Vec<String>,
+    /// Trust tier: "trusted", "internal", "untrusted", or "llm-generated".
+    #[serde(default)]
+    pub trust_tier: Option<String>,
+    /// Sandbox requirements.
+    #[serde(default)]
+    pub isolation: IsolationRequirements,
+    /// Secrets the skill needs.
+    #[serde(default)]
+    pub secrets: Vec<RequiredSecret>,
+    /// Sandbox image to build for the skill.
+    #[serde(default)]
+    pub image: Option<ImageRequirements>,
+}
+
+fn default_version() -> String {
+    "0.0.0".to_string()
+}
+
+/// The `[isolation]` table of a manifest.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IsolationRequirements {
+    /// Minimum isolation level ("l1", "l2", "l3"). May raise, but not lower,
+    /// the level the trust tier requires.
+    #[serde(default)]
+    pub level: Option<String>,
+    /// Memory limit in bytes.
+    #[serde(default)]
+    pub memory_bytes: Option<u64>,
+    /// Execution timeout in seconds (0 = no timeout).
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    /// Network policy: "none", "host-only", or "outbound-only".
+    #[serde(default)]
+    pub network: Option<String>,
+}
+
+/// The `[image]` table of a manifest.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ImageRequirements {
+    /// Base image reference (e.g. "python:3.12-alpine").
+    pub base: String,
+    /// System packages installed on top of the base.
+    #[serde(default)]
+    pub packages: Vec<String>,
+    /// Package manager of the base image: "apk", "apt", or "dnf".
+    #[serde(default = "default_package_manager")]
+    pub package_manager: String,
+}
+
+fn default_package_manager() -> String {
+    "apt".to_string()
+}
+
+/// A `[[secrets]]` entry of a manifest.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RequiredSecret {
+    /// Secret name in the daemon's secret store.
+    pub name: String,
+    /// Inject as this environment variable.
+    #[serde(default)]
+    pub env: Option<String>,
+    /// Inject as a read-only file at this absolute guest path.
+    #[serde(default)]
+    pub file: Option<String>,
+}
+
+impl SkillManifest {
+    /// Parse a manifest from TOML.
+    pub fn parse(toml_str: &str) -> Result<Self, ManifestError> {
+        Ok(toml::from_str(toml_str)?)
+    }
+
+    /// Read and parse `path`.
+    pub fn load(path: &Path) -> Result<Self, ManifestError> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// The declared trust tier, or `default` when omitted.
+    pub fn trust_tier(&self, default: Option<&str>) -> Result<TrustTier, ManifestError> {
+        let tier = self
+            .trust_tier
+            .as_deref()
+            .or(default)
+            .unwrap_or("untrusted");
+        TrustTier::from_str_loose(tier)
+            .ok_or_else(|| ManifestError::Invalid(format!("unknown trust_tier {tier:?}")))
+    }
+
+    /// The isolation level the skill must run at: the stronger of the
+    /// declared level and the one `selector` requires for `tier`.
+    pub fn isolation_level(
+        &self,
+        tier: TrustTier,
+        selector: &TrustBasedSelector,
+    ) -> Result<IsolationLevel, ManifestError> {
+        let required = selector.required_level(tier);
+        let Some(level) = self.isolation.level.as_deref() else {
+            return Ok(required);
+        };
+        let level = IsolationLevel::from_str_loose(level)
+            .ok_or_else(|| ManifestError::Invalid(format!("unknown isolation.level {level:?}")))?;
+        if level < required {
+            return Err(ManifestError::Invalid(format!(
+                "isolation.level {level} is weaker than the {required} that trust tier {tier} requires"
+            )));
+        }
+        Ok(level)
+    }
+
+    /// Check the fields that do not depend on the daemon's state.
+    pub fn validate(&self) -> Result<(), ManifestError> {
+        let invalid = |msg: String| Err(ManifestError::Invalid(msg));
+        if self.name.is_empty()
+            || !self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return invalid(format!(
+                "name {:?} must be non-empty and contain only letters, digits, '-' and '_'",
+                self.name
+            ));
+        }
+        if self.version.is_empty()
+            || !self
+                .version
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+        {
+            return invalid(format!(
+                "version {:?} must be non-empty and contain only letters, digits, '.', '-' and '_'",
+                self.version
+            ));
+        }
+        if self.command.is_empty() || self.command[0].is_empty() {
+            return invalid("command must name a program".to_string());
+        }
+        if self.isolation.memory_bytes == Some(0) {
+            return invalid("isolation.memory_bytes must be non-zero".to_string());
+        }
+        if let Some(network) = &self.isolation.network
+            && NetworkPolicy::from_str_loose(network).is_none()
+        {
+            return invalid(format!(
+                "isolation.network must be one of \"none\", \"host-only\", \"outbound-only\", got {network:?}"
+            ));
+        }
+        if let Some(image) = &self.image {
+            if image.base.is_empty() || image.base.chars().any(char::is_whitespace) {
+                return invalid(format!(
+                    "image.base {:?} must be a non-empty image reference",
+                    image.base
+                ));
+            }
+            if !matches!(image.package_manager.as_str(), "apk" | "apt" | "dnf") {
+                return invalid(format!(
+                    "image.package_manager must be one of \"apk\", \"apt\", \"dnf\", got {:?}",
+                    image.package_manager
+                ));
+            }
+            if let Some(package) = image.packages.iter().find(|p| {
+                p.is_empty()
+                    || p.starts_with('-')
+                    || !p.chars().all(|c| {
+                        c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | '+' | '=' | ':')
+                    })
+            }) {
+                return invalid(format!(
+                    "image.packages entry {package:?} is not a package name"
+                ));
+            }
+        }
+        let mut seen = HashSet::new();
+        for secret in &self.secrets {
+            if !seen.insert(secret.name.as_str()) {
+                return invalid(format!("secret {:?} is listed twice", secret.name));
+            }
+            if let Some(file) = &secret.file
+                && !Path::new(file).is_absolute()
+            {
+                return invalid(format!(
+                    "secret {:?} file path must be absolute, got {file:?}",
+                    secret.name
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Find the manifest of the skill called `name` under `skills_dir`.
+///
+/// Returns the skill's directory with its manifest. Manifests that fail to
+/// parse are skipped.
+pub fn find_manifest(
+    skills_dir: &Path,
+    name: &str,
+) -> Result<Option<(PathBuf, SkillManifest)>, ManifestError> {
+    let mut dirs: Vec<PathBuf> = std::fs::read_dir(skills_dir)?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| is_skill_dir(path))
+        .collect();
+    dirs.sort();
+    Ok(dirs.into_iter().find_map(|dir| {
+        let manifest = SkillManifest::load(&dir.join(MANIFEST_FILE)).ok()?;
+        (manifest.name == name).then_some((dir, manifest))
+    }))
+}
+
+/// Whether `path` is a skill directory: not hidden, and holding a manifest.
+fn is_skill_dir(path: &Path) -> bool {
+    !path
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+        && path.join(MANIFEST_FILE).is_file()
+}
+
+/// The skills found by [`SkillLoader::discover`].
+#[derive(Default)]
+pub struct Discovery {
+    /// Valid skills, in directory order.
+    pub skills: Vec<IsolatedSkill>,
+    /// Manifests that were rejected, with the reason.
+    pub errors: Vec<(PathBuf, ManifestError)>,
+}
+
+/// Builds [`IsolatedSkill`]s from manifests.
+pub struct SkillLoader {
+    defaults: IsolationConfig,
+    selector: Arc<TrustBasedSelector>,
+    pool: Option<Arc<SandboxPool>>,
+    secrets: Option<(Arc<RwLock<SecretStore>>, PathBuf)>,
+    egress: Option<Arc<EgressProxy>>,
+}
+
+impl SkillLoader {
+    /// Create a loader filling omitted settings from `defaults`.
+    pub fn new(defaults: &IsolationConfig) -> Self {
+        Self {
+            defaults: defaults.clone(),
+            selector: Arc::new(TrustBasedSelector::from_config(defaults)),
+            pool: None,
+            secrets: None,
+            egress: None,
+        }
+    }
+
+    /// Pick backends with `selector` instead of the default one.
+    pub fn with_selector(mut self, selector: TrustBasedSelector) -> Self {
+        self.selector = Arc::new(selector);
+        self
+    }
+
+    /// Run loaded skills through a shared sandbox pool.
+    pub fn with_pool(mut self, pool: Arc<SandboxPool>) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Check required secrets against `store`, and resolve them from it at
+    /// each run, staging secret files under `staging_dir`.
+    pub fn with_secrets(
+        mut self,
+        store: Arc<RwLock<SecretStore>>,
+        staging_dir: impl Into<PathBuf>,
+    ) -> Self {
+        self.secrets = Some((store, staging_dir.into()));
+        self
+    }
+
+    /// Force loaded skills' traffic through the egress proxy.
+    pub fn with_egress_proxy(mut self, proxy: Arc<EgressProxy>) -> Self {
+        self.egress = Some(proxy);
+        self
+    }
+
+    /// Load every `*/skill.toml` under `skills_dir`.
+    ///
+    /// Subdirectories without a manifest are skipped, as are hidden ones
+    /// (such as a [package](super::package) being installed). A manifest reusing the
+    /// name of one loaded before it (in directory-name order) is rejected.
+    pub fn discover(&self, skills_dir: &Path) -> Discovery {
+        let mut discovery = Discovery::default();
+        let entries = match std::fs::read_dir(skills_dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                discovery
+                    .errors
+                    .push((skills_dir.to_path_buf(), ManifestError::Io(e)));
+                return discovery;
+            }
+        };
+        let lock = SkillLock::load(skills_dir).unwrap_or_else(|e| {
+            discovery.errors.push((
+                skills_dir.join(LOCK_FILE),
+                ManifestError::Invalid(e.to_string()),
+            ));
+            SkillLock::default()
+        });
+        let mut dirs: Vec<PathBuf> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| is_skill_dir(path))
+            .collect();
+        dirs.sort();
+
+        let mut names = HashSet::new();
+        for dir in dirs {
+            let manifest_path = dir.join(MANIFEST_FILE);
+            match self.load_locked(&dir, &lock) {
+                Ok(skill) => {
+                    if names.insert(skill.skill_name.clone()) {
+                        discovery.skills.push(skill);
+                    } else {
+                        let msg = format!("skill name {:?} is already taken", skill.skill_name);
+                        discovery
+                            .errors
+                            .push((manifest_path, ManifestError::Invalid(msg)));
+                    }
+                }
+                Err(e) => discovery.errors.push((manifest_path, e)),
+            }
+        }
+        discovery
+    }
+
+    /// Load the skill in `skill_dir` from its manifest, pinning its image
+    /// from the lockfile in the parent directory.
+    pub fn load(&self, skill_dir: &Path) -> Result<IsolatedSkill, ManifestError> {
+        let lock = match skill_dir.parent() {
+            Some(parent) => {
+                SkillLock::load(parent).map_err(|e| ManifestError::Invalid(e.to_string()))?
+            }
+            None => SkillLock::default(),
+        };
+        self.load_locked(skill_dir, &lock)
+    }
+
+    fn load_locked(
+        &self,
+        skill_dir: &Path,
+        lock: &SkillLock,
+    ) -> Result<IsolatedSkill, ManifestError> {
+        let manifest = SkillManifest::load(&skill_dir.join(MANIFEST_FILE))?;
+        manifest.validate()?;
+        let tier = manifest.trust_tier(self.defaults.default_trust_tier.as_deref())?;
+        let level = manifest.isolation_level(tier, &self.selector)?;
+
+        let host_dir = skill_dir.canonicalize()?;
+        let mut config = SandboxConfig::new(&manifest.name)
+            .with_mount(SharedMount::read_only(host_dir, SKILL_MOUNT))
+            .with_workdir(SKILL_MOUNT)
+            .with_memory_limit(
+                manifest
+                    .isolation
+                    .memory_bytes
+                    .unwrap_or(self.defaults.default_memory_bytes),
+            );
+        config.limits.cpu.cpu_fraction = self.defaults.default_cpu_fraction;
+        let timeout = manifest
+            .isolation
+            .timeout_secs
+            .unwrap_or(self.defaults.default_timeout_secs);
+        if timeout > 0 {
+            config = config.with_timeout(Duration::from_secs(timeout));
+        }
+        let network = manifest
+            .isolation
+            .network
+            .as_deref()
+            .unwrap_or(&self.defaults.default_network);
+        config = config.with_network(NetworkPolicy::from_str_loose(network).unwrap_or_default());
+        for secret in &manifest.secrets {
+            config = config.with_secret(self.secret_injection(secret)?);
+        }
+        if manifest.image.is_some() {
+            match lock.pinned_image(&manifest) {
+                Some(digest) => config = config.with_image(digest),
+                None => tracing::warn!(
+                    skill = %manifest.name,
+                    "Skill image is not built or out of date; run `crustyclaw skill build-image {}`",
+                    manifest.name
+                ),
+            }
+        }
+
+        // A declared level above the tier's picks a backend for that level,
+        // bypassing any backend configured for the tier.
+        let backend = if level == self.selector.required_level(tier) {
+            self.selector.select(tier)
+        } else {
+            self.selector.select_level(level, tier)
+        };
+        tracing::debug!(skill = %manifest.name, %tier, %level, backend = backend.name(), "Loaded skill manifest");
+        let mut skill = IsolatedSkill::new(
+            manifest.name,
+            manifest.description,
+            manifest.command,
+            config,
+            backend,
+        );
+        skill = skill
+            .with_selector(self.selector.clone())
+            .with_trust_tier(tier);
+        if let Some(pool) = &self.pool {
+            skill = skill.with_pool(pool.clone());
+        }
+        if let Some((store, staging_dir)) = &self.secrets {
+            skill = skill.with_secrets(store.clone(), staging_dir.clone());
+        }
+        if let Some(proxy) = &self.egress {
+            skill = skill.with_egress_proxy(proxy.clone());
+        }
+        Ok(skill)
+    }
+
+    /// How `secret` is injected, checking that the store holds it.
+    fn secret_injection(&self, secret: &RequiredSecret) -> Result<SecretInjection, ManifestError> {
+        let Some((store, _)) = &self.secrets else {
+            return Err(ManifestError::Invalid(format!(
+                "secret {:?} is required but no secret store is available",
+                secret.name
+            )));
+        };
+        let store = store.read().unwrap_or_else(|e| e.into_inner());
+        let entry = store.get(&secret.name).ok_or_else(|| {
+            ManifestError::Invalid(format!(
+                "required secret {:?} is not configured",
+                secret.name
+            ))
+        })?;
+        let mut injection = SecretInjection {
+            name: secret.name.clone(),
+            env_name: secret.env.clone(),
+            file_path: secret.file.as_ref().map(PathBuf::from),
+        };
+        if injection.env_name.is_none() && injection.file_path.is_none() {
+            match &entry.injection {
+                InjectionMethod::Env(env) => injection.env_name = Some(env.clone()),
+                InjectionMethod::File(path) => injection.file_path = Some(path.clone()),
+                InjectionMethod::Both {
+                    env_name,
+                    file_path,
+                } => {
+                    injection.env_name = Some(env_name.clone());
+                    injection.file_path = Some(file_path.clone());
+                }
+            }
+        }
+        Ok(injection)
+    }
+}
+
+/// The weakest trust tier that selects a backend of at least `level`.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::isolation::BackendPreference;
+    use crate::secrets::InjectionMethod;
+    use crate::skill::image::{LockedImage, containerfile, content_hash};
+    use crate::skill::{Skill, SkillInvocation};
+
+    fn write_skill(root: &Path, dir: &str, manifest: &str) -> PathBuf {
+        let dir = root.join(dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(MANIFEST_FILE), manifest).unwrap();
+        dir
+    }
+
+    fn noop_loader() -> SkillLoader {
+        SkillLoader::new(&IsolationConfig::default())
+            .with_selector(TrustBasedSelector::new().with_forced_backend(BackendPreference::Noop))
+    }
+
+    #[test]
+    fn test_parse_full_manifest() {
+        let manifest = SkillManifest::parse(
+            r#"
+            name = "weather"
+            description = "Looks up the forecast"
+            command = ["python3", "/skill/weather.py"]
+            trust_tier = "internal"
+
+            [isolation]
+            level = "l3"
+            memory_bytes = 1024
+            timeout_secs = 30
+            network = "outbound-only"
+
+            [[secrets]]
+            name = "weather_api_key"
+            env = "WEATHER_API_KEY"
+            "#,
+        )
+        .unwrap();
+        manifest.validate().unwrap();
+        let tier = manifest.trust_tier(None).unwrap();
+        assert_eq!(tier, TrustTier::Internal);
+        assert_eq!(
+            manifest
+                .isolation_level(tier, &TrustBasedSelector::new())
+                .unwrap(),
+            IsolationLevel::L3MicroVm
+        );
+        assert_eq!(manifest.secrets[0].env.as_deref(), Some("WEATHER_API_KEY"));
+    }
+
+    #[test]
+    fn test_trust_tier_defaults() {
+        let manifest =
+            SkillManifest::parse("name = \"a\"\ndescription = \"\"\ncommand = [\"true\"]\n")
+                .unwrap();
+        assert_eq!(manifest.trust_tier(None).unwrap(), TrustTier::Untrusted);
+        assert_eq!(
+            manifest.trust_tier(Some("trusted")).unwrap(),
+            TrustTier::Trusted
+        );
+        assert_eq!(
+            manifest
+                .isolation_level(TrustTier::Internal, &TrustBasedSelector::new())
+                .unwrap(),
+            IsolationLevel::L2Namespace
+        );
+    }
+
+    #[test]
+    fn test_invalid_manifests() {
+        let base = "description = \"\"\n";
+        for (manifest, needle) in [
+            ("name = \"a b\"\ncommand = [\"true\"]\n", "name"),
+            ("name = \"a\"\ncommand = []\n", "command"),
+            (
+                "name = \"a\"\ncommand = [\"true\"]\n[isolation]\nnetwork = \"lan\"\n",
+                "network",
+            ),
+            (
+                "name = \"a\"\ncommand = [\"true\"]\n[[secrets]]\nname = \"k\"\nfile = \"k.txt\"\n",
+                "absolute",
+            ),
+            (
+                "name = \"a\"\ncommand = [\"true\"]\n[[secrets]]\nname = \"k\"\n[[secrets]]\nname = \"k\"\n",
+                "twice",
+            ),
+            (
+                "name = \"a\"\nversion = \"1 0\"\ncommand = [\"true\"]\n",
+                "version",
+            ),
+            (
+                "name = \"a\"\ncommand = [\"true\"]\n[image]\nbase = \"alpine\"\npackages = [\"curl; rm -rf /\"]\n",
+                "packages",
+            ),
+            (
+                "name = \"a\"\ncommand = [\"true\"]\n[image]\nbase = \"alpine\"\npackage_manager = \"brew\"\n",
+                "package_manager",
+            ),
+        ] {
+            let err = SkillManifest::parse(&format!("{base}{manifest}"))
+                .unwrap()
+                .validate()
+                .unwrap_err();
+            assert!(err.to_string().contains(needle), "{err}");
+        }
+        // Unknown keys are typos, not extensions.
+        assert!(matches!(
+            SkillManifest::parse(
+                "name = \"a\"\ndescription = \"\"\ncommand = [\"x\"]\ntrust = \"trusted\"\n"
+            ),
+            Err(ManifestError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_level_cannot_weaken_tier() {
+        let manifest = SkillManifest::parse(
+            "name = \"a\"\ndescription = \"\"\ncommand = [\"true\"]\ntrust_tier = \"untrusted\"\n[isolation]\nlevel = \"l1\"\n",
+        )
+        .unwrap();
+        let err = manifest
+            .isolation_level(TrustTier::Untrusted, &TrustBasedSelector::new())
+            .unwrap_err();
+        assert!(err.to_string().contains("weaker"), "{err}");
+    }
+
+    #[test]
+    fn test_load_builds_sandbox_config() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = write_skill(
+            root.path(),
+            "weather",
+            r#"
+            name = "weather"
+            description = "Forecast"
+            command = ["sh", "run.sh"]
+            trust_tier = "trusted"
+            [isolation]
+            timeout_secs = 5
+            "#,
+        );
+        let skill = noop_loader().load(&dir).unwrap();
+        assert_eq!(skill.name(), "weather");
+        assert_eq!(skill.command, vec!["sh", "run.sh"]);
+        let config = &skill.sandbox_config;
+        assert_eq!(config.workdir, PathBuf::from(SKILL_MOUNT));
+        assert_eq!(config.mounts[0].host_path, dir.canonicalize().unwrap());
+        assert_eq!(config.mounts[0].guest_path, PathBuf::from(SKILL_MOUNT));
+        assert_eq!(config.limits.timeout, Some(Duration::from_secs(5)));
+        assert_eq!(
+            config.limits.memory.max_bytes,
+            IsolationConfig::default().default_memory_bytes
+        );
+        assert_eq!(config.network, NetworkPolicy::None);
+    }
+
+    #[tokio::test]
+    async fn test_required_secrets() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = write_skill(
+            root.path(),
+            "show",
+            "name = \"show\"\ndescription = \"\"\ncommand = [\"sh\", \"-c\", \"echo $TOKEN\"]\n[[secrets]]\nname = \"token\"\n",
+        );
+
+        // Without a store, or with the secret missing, the skill is refused.
+        assert!(noop_loader().load(&dir).is_err());
+        let store = Arc::new(RwLock::new(SecretStore::new()));
+        let loader = noop_loader().with_secrets(store.clone(), root.path());
+        let err = loader.load(&dir).err().unwrap();
+        assert!(err.to_string().contains("not configured"), "{err}");
+
+        // The store entry's injection method applies when the manifest
+        // names none.
+        let key_file = root.path().join("token.txt");
+        std::fs::write(&key_file, "s3cret").unwrap();
+        store
+            .write()
+            .unwrap()
+            .load_from_file(
+                "token",
+                &key_file,
+                InjectionMethod::Env("TOKEN".to_string()),
+            )
+            .unwrap();
+        let mut skill = loader.load(&dir).unwrap();
+        let injection = &skill.sandbox_config.secret_injections[0];
+        assert_eq!(injection.env_name.as_deref(), Some("TOKEN"));
+        assert!(injection.file_path.is_none());
+
+        // The noop backend runs on the host, so run from a real directory.
+        skill.sandbox_config.workdir = root.path().to_path_buf();
+        let result = skill.invoke(&SkillInvocation::new()).await.unwrap();
+        // The secret reached the skill, and was redacted from its output.
+        assert_eq!(result.stdout.trim(), "[REDACTED]");
+    }
+
+    #[test]
+    fn test_load_pins_locked_image() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = write_skill(
+            root.path(),
+            "weather",
+            "name = \"weather\"\ndescription = \"\"\ncommand = [\"true\"]\n[image]\nbase = \"alpine:3.20\"\n",
+        );
+        assert!(
+            noop_loader()
+                .load(&dir)
+                .unwrap()
+                .sandbox_config
+                .image
+                .is_none()
+        );
+
+        let (found, manifest) = find_manifest(root.path(), "weather").unwrap().unwrap();
+        assert_eq!(found, dir);
+        assert!(find_manifest(root.path(), "other").unwrap().is_none());
+
+        let mut lock = SkillLock::default();
+        lock.insert(
+            "weather",
+            LockedImage {
+                version: manifest.version.clone(),
+                content_hash: content_hash(&containerfile(&manifest).unwrap()),
+                image: "crustyclaw-skill-weather:0.0.0-abc".to_string(),
+                digest: "sha256:feedface".to_string(),
+                builder: "docker".to_string(),
+            },
+        );
+        lock.save(root.path()).unwrap();
+        let skill = noop_loader().load(&dir).unwrap();
+        assert_eq!(
+            skill.sandbox_config.image.as_deref(),
+            Some("sha256:feedface")
+        );
+
+        // A manifest without an image table never picks up a lock entry.
+        std::fs::write(
+            dir.join(MANIFEST_FILE),
+            "name = \"weather\"\ndescription = \"\"\ncommand = [\"true\"]\n",
+        )
+        .unwrap();
+        assert!(
+            noop_loader().discover(root.path()).skills[0]
+                .sandbox_config
+                .image
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_discover() {
+        let root = tempfile::tempdir().unwrap();
+        write_skill(
+            root.path(),
+            "a-echo",
+            "name = \"echo\"\ndescription = \"\"\ncommand = [\"echo\"]\n",
+        );
+        write_skill(
+            root.path(),
+            "b-echo",
+            "name = \"echo\"\ndescription = \"Duplicate\"\ncommand = [\"echo\"]\n",
+        );
+        write_skill(root.path(), "broken", "name = ");
+        write_skill(
+            root.path(),
+            ".c-echo.installing",
+            "name = \"echo\"\ndescription = \"Staged\"\ncommand = [\"echo\"]\n",
+        );
+        std::fs::create_dir(root.path().join("no-manifest")).unwrap();
+
+        let discovery = noop_loader().discover(root.path());
+        let names: Vec<&str> = discovery.skills.iter().map(|s| s.name()).collect();
+        assert_eq!(names, vec!["echo"]);
+        assert_eq!(discovery.skills[0].description(), "");
+        let failed: Vec<String> = discovery
+            .errors
+            .iter()
+            .map(|(path, _)| {
+                path.parent()
+                    .unwrap()
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+        assert_eq!(failed, vec!["b-echo", "broken"]);
+
+        let missing = noop_loader().discover(&root.path().join("absent"));
+        assert!(missing.skills.is_empty());
+        assert_eq!(missing.errors.len(), 1);
+    }
+}
@@ -0,0 +1,1402 @@
+//! Skill engine — defines the trait and registry for agent skills.
+//!
+//! Skills can run directly (in-process) or inside an isolation
+//! [`Sandbox`](crate::isolation::Sandbox) for untrusted / third-party code.
+//!
+//! A skill may declare a [`ConcurrencyClass`] (e.g. `"deploy"`): invocations
+//! through the [`SkillRegistry`] hold the class's lock while they run, so at
+//! most one skill of each class runs at a time across the daemon. Others
+//! either queue in FIFO order or are rejected, per the class's
+//! [`ExclusionPolicy`].
+//!
+//! Besides skills registered in code, the daemon discovers skills from
+//! `skill.toml` manifests under `daemon.skills_dir`; see [`manifest`].
+//! Discovered skills are swapped out wholesale on each rescan, while
+//! built-in skills stay registered for the daemon's lifetime. Manifests may
+//! also declare a sandbox image, built and pinned by [`image`]. Signed
+//! [`package`]s install new skills into the skills directory. Skills from
+//! native [plugins](crate::plugin::loader) are added once at startup and
+//! also stay for the daemon's lifetime.
+
+pub mod image;
+pub mod manifest;
+pub mod package;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::BoxFuture;
+use crate::events::{DaemonEvent, EventBus};
+use crate::isolation::{
+    self, EgressProxy, LeakScanner, OutputSender, OutputStream, SandboxConfig, SandboxPool,
+    SandboxResult, TrustBasedSelector, TrustTier,
+};
+use crate::message::Envelope;
+use crate::metrics::Metrics;
+use crate::recovery::{RunJournal, RunOrigin};
+use crate::secrets::SecretStore;
+
+/// A direct skill invocation with structured arguments (e.g. from the CLI).
+#[derive(Debug, Clone, Default)]
+pub struct SkillInvocation {
+    /// Named arguments passed to the skill.
+    pub args: serde_json::Map<String, serde_json::Value>,
+    /// Trust tier to execute under; selects the isolation backend for
+    /// sandboxed skills. `None` uses the skill's configured backend.
+    pub trust_tier: Option<TrustTier>,
+    /// Conversation the invocation was requested from, recorded in the run
+    /// journal so it can be notified if the run is lost to a crash.
+    pub origin: Option<RunOrigin>,
+}
+
+impl SkillInvocation {
+    /// Create an invocation with no arguments.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a named argument.
+    pub fn with_arg(mut self, key: impl Into<String>, value: impl Into<serde_json::Value>) -> Self {
+        self.args.insert(key.into(), value.into());
+        self
+    }
+
+    /// Set the trust tier.
+    pub fn with_trust_tier(mut self, tier: TrustTier) -> Self {
+        self.trust_tier = Some(tier);
+        self
+    }
+
+    /// Set the originating conversation.
+    pub fn with_origin(mut self, origin: RunOrigin) -> Self {
+        self.origin = Some(origin);
+        self
+    }
+
+    /// The arguments serialized as a JSON object.
+    pub fn args_json(&self) -> String {
+        serde_json::Value::Object(self.args.clone()).to_string()
+    }
+}
+
+/// A skill that the agent can execute in response to messages.
+///
+/// The [`execute`](Skill::execute) method returns a [`BoxFuture`] because this
+/// trait is used via `dyn Skill` (dynamic dispatch), which requires a concrete
+/// return type. Native `async fn` in traits is not object-safe.
+pub trait Skill: Send + Sync {
+    /// The unique name of this skill.
+    fn name(&self) -> &str;
+
+    /// A short description of what this skill does.
+    fn description(&self) -> &str;
+
+    /// Whether this skill runs inside an isolation sandbox.
+    fn isolated(&self) -> bool {
+        false
+    }
+
+    /// The mutual-exclusion class this skill belongs to, if any. Skills
+    /// sharing a class never run concurrently through the registry.
+    fn concurrency_class(&self) -> Option<&ConcurrencyClass> {
+        None
+    }
+
+    /// Label of the sandbox this skill runs in (the `crustyclaw.sandbox`
+    /// container label), if isolated.
+    fn sandbox_label(&self) -> Option<&str> {
+        None
+    }
+
+    /// The trust tier this skill was loaded at, if it has one.
+    fn trust_tier(&self) -> Option<TrustTier> {
+        None
+    }
+
+    /// Execute the skill with the given message, returning a response body.
+    fn execute(&self, message: &Envelope) -> BoxFuture<'_, Result<String, SkillError>>;
+
+    /// Invoke the skill directly with structured arguments.
+    ///
+    /// Unlike [`execute`](Skill::execute), a non-zero exit is reported in
+    /// the returned [`SandboxResult`] rather than as an error. The default
+    /// implementation passes the arguments as a JSON message body to
+    /// `execute` and wraps the response as stdout.
+    fn invoke(
+        &self,
+        invocation: &SkillInvocation,
+    ) -> BoxFuture<'_, Result<SandboxResult, SkillError>> {
+        let envelope = Envelope::new("ipc", &invocation.args_json());
+        Box::pin(async move {
+            let started = Instant::now();
+            let stdout = self.execute(&envelope).await?;
+            Ok(SandboxResult {
+                exit_code: 0,
+                stdout,
+                stderr: String::new(),
+                elapsed: started.elapsed(),
+                peak_memory_bytes: None,
+                cpu_time: None,
+            })
+        })
+    }
+
+    /// Like [`invoke`](Skill::invoke), but also sends each line of output
+    /// to `output` as the skill produces it.
+    ///
+    /// The default sends the result's lines once the skill has finished;
+    /// sandboxed skills stream them while the command runs.
+    fn invoke_streaming(
+        &self,
+        invocation: &SkillInvocation,
+        output: OutputSender,
+    ) -> BoxFuture<'_, Result<SandboxResult, SkillError>> {
+        let run = self.invoke(invocation);
+        Box::pin(async move {
+            let result = run.await?;
+            isolation::send_lines(&output, OutputStream::Stdout, &result.stdout);
+            isolation::send_lines(&output, OutputStream::Stderr, &result.stderr);
+            Ok(result)
+        })
+    }
+}
+
+/// Errors from skill execution.
+#[derive(Debug, thiserror::Error)]
+pub enum SkillError {
+    #[error("skill execution failed: {0}")]
+    Execution(String),
+
+    #[error("skill not found: {0}")]
+    NotFound(String),
+
+    #[error("sandbox error: {0}")]
+    Isolation(#[from] isolation::IsolationError),
+
+    #[error("concurrency class '{class}' is held by '{holder}'")]
+    Busy { class: String, holder: String },
+}
+
+/// What happens to an invocation whose concurrency class is already held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExclusionPolicy {
+    /// Wait in FIFO order until the holder finishes.
+    #[default]
+    Queue,
+    /// Fail immediately with [`SkillError::Busy`].
+    Reject,
+}
+
+/// A named mutual-exclusion class declared by a skill.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConcurrencyClass {
+    /// Class name; skills with the same name exclude each other.
+    pub name: String,
+    /// Behaviour when the class is already held.
+    pub policy: ExclusionPolicy,
+}
+
+impl ConcurrencyClass {
+    /// A class that queues contending invocations.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            policy: ExclusionPolicy::Queue,
+        }
+    }
+
+    /// Set the exclusion policy.
+    pub fn with_policy(mut self, policy: ExclusionPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+}
+
+/// Point-in-time view of a concurrency class lock.
+#[derive(Debug, Clone)]
+pub struct ClassLockStatus {
+    /// Class name.
+    pub class: String,
+    /// Skill currently holding the lock, and for how long.
+    pub holder: Option<(String, Duration)>,
+    /// Skills waiting for the lock, in the order they will run.
+    pub queued: Vec<String>,
+}
+
+#[derive(Default)]
+struct ClassState {
+    holder: Option<(String, Instant)>,
+    /// Waiting invocations as (ticket, skill name), oldest first.
+    waiting: Vec<(u64, String)>,
+}
+
+struct ClassLock {
+    semaphore: Arc<Semaphore>,
+    state: Mutex<ClassState>,
+}
+
+/// Daemon-wide locks for skill concurrency classes.
+#[derive(Default)]
+pub struct ConcurrencyLocks {
+    classes: Mutex<HashMap<String, Arc<ClassLock>>>,
+    next_ticket: AtomicU64,
+}
+
+impl ConcurrencyLocks {
+    /// Create an empty lock table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Acquire `class` on behalf of `skill`, waiting or failing according
+    /// to the class's policy. The lock is released when the guard drops.
+    pub async fn acquire(
+        &self,
+        class: &ConcurrencyClass,
+        skill: &str,
+    ) -> Result<ClassGuard, SkillError> {
+        let lock = self
+            .classes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(class.name.clone())
+            .or_insert_with(|| {
+                Arc::new(ClassLock {
+                    semaphore: Arc::new(Semaphore::new(1)),
+                    state: Mutex::new(ClassState::default()),
+                })
+            })
+            .clone();
+
+        let permit = match lock.semaphore.clone().try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) if class.policy == ExclusionPolicy::Reject => {
+                let holder = lock
+                    .state()
+                    .holder
+                    .as_ref()
+                    .map(|(name, _)| name.clone())
+                    .unwrap_or_default();
+                return Err(SkillError::Busy {
+                    class: class.name.clone(),
+                    holder,
+                });
+            }
+            Err(_) => {
+                let ticket = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+                lock.state().waiting.push((ticket, skill.to_string()));
+                // Leave the queue even if this future is dropped while waiting.
+                let waiter = Waiter {
+                    lock: lock.clone(),
+                    ticket,
+                };
+                tracing::info!(class = %class.name, skill, "Skill queued behind concurrency class holder");
+                let permit =
+                    lock.semaphore.clone().acquire_owned().await.map_err(|_| {
+                        SkillError::Execution("concurrency lock closed".to_string())
+                    })?;
+                drop(waiter);
+                permit
+            }
+        };
+
+        lock.state().holder = Some((skill.to_string(), Instant::now()));
+        Ok(ClassGuard {
+            lock,
+            _permit: permit,
+        })
+    }
+
+    /// Status of every class that has been used, sorted by name.
+    pub fn status(&self) -> Vec<ClassLockStatus> {
+        let classes = self.classes.lock().unwrap_or_else(|e| e.into_inner());
+        let mut status: Vec<ClassLockStatus> = classes
+            .iter()
+            .map(|(name, lock)| {
+                let state = lock.state();
+                ClassLockStatus {
+                    class: name.clone(),
+                    holder: state
+                        .holder
+                        .as_ref()
+                        .map(|(skill, since)| (skill.clone(), since.elapsed())),
+                    queued: state.waiting.iter().map(|(_, s)| s.clone()).collect(),
+                }
+            })
+            .collect();
+        status.sort_by(|a, b| a.class.cmp(&b.class));
+        status
+    }
+}
+
+impl ClassLock {
+    fn state(&self) -> std::sync::MutexGuard<'_, ClassState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Removes a queued invocation from its class's wait list.
+struct Waiter {
+    lock: Arc<ClassLock>,
+    ticket: u64,
+}
+
+impl Drop for Waiter {
+    fn drop(&mut self) {
+        self.lock.state().waiting.retain(|(t, _)| *t != self.ticket);
+    }
+}
+
+/// Holds a concurrency class lock until dropped.
+pub struct ClassGuard {
+    lock: Arc<ClassLock>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Drop for ClassGuard {
+    fn drop(&mut self) {
+        // Runs before the permit is released, so the next holder's
+        // assignment is never overwritten.
+        self.lock.state().holder = None;
+    }
+}
+
+/// Registry of available skills.
+pub struct SkillRegistry {
+    skills: HashMap<String, Arc<dyn Skill>>,
+    /// Skills provided by native plugins, added after startup.
+    loaded: RwLock<HashMap<String, Arc<dyn Skill>>>,
+    /// Skills loaded from manifests, replaced on every rescan.
+    discovered: RwLock<HashMap<String, Arc<dyn Skill>>>,
+    locks: ConcurrencyLocks,
+    journal: Arc<RunJournal>,
+    metrics: Arc<Metrics>,
+    events: EventBus,
+}
+
+impl SkillRegistry {
+    /// Create an empty skill registry.
+    pub fn new() -> Self {
+        Self {
+            skills: HashMap::new(),
+            loaded: RwLock::new(HashMap::new()),
+            discovered: RwLock::new(HashMap::new()),
+            locks: ConcurrencyLocks::new(),
+            journal: Arc::new(RunJournal::in_memory()),
+            metrics: Arc::new(Metrics::new()),
+            events: EventBus::new(),
+        }
+    }
+
+    /// Record runs in `journal` so they can be recovered after a crash.
+    pub fn with_journal(mut self, journal: Arc<RunJournal>) -> Self {
+        self.journal = journal;
+        self
+    }
+
+    /// Count sandboxed runs and their latency in `metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Publish the start and end of every run on `events`.
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Register a skill.
+    pub fn register(&mut self, skill: Box<dyn Skill>) {
+        let name = skill.name().to_string();
+        self.skills.insert(name, Arc::from(skill));
+    }
+
+    /// Register a skill through a shared registry, as native plugins do
+    /// at startup.
+    ///
+    /// Returns `false`, and drops the skill, when its name is already taken
+    /// by a registered or previously loaded skill.
+    pub fn register_loaded(&self, skill: Box<dyn Skill>) -> bool {
+        let name = skill.name().to_string();
+        let mut loaded = self.loaded.write().unwrap_or_else(|e| e.into_inner());
+        if self.skills.contains_key(&name) || loaded.contains_key(&name) {
+            return false;
+        }
+        loaded.insert(name, Arc::from(skill));
+        true
+    }
+
+    /// Replace all discovered skills with `skills`.
+    ///
+    /// A discovered skill whose name is taken by a registered or loaded
+    /// skill is dropped. Runs already in progress keep the skill they
+    /// started with. Returns the names of the skills that were dropped.
+    pub fn replace_discovered(&self, skills: Vec<Box<dyn Skill>>) -> Vec<String> {
+        let loaded = self.loaded.read().unwrap_or_else(|e| e.into_inner());
+        let mut discovered = HashMap::new();
+        let mut shadowed = Vec::new();
+        for skill in skills {
+            let name = skill.name().to_string();
+            if self.skills.contains_key(&name) || loaded.contains_key(&name) {
+                shadowed.push(name);
+            } else {
+                discovered.insert(name, Arc::from(skill));
+            }
+        }
+        *self.discovered.write().unwrap_or_else(|e| e.into_inner()) = discovered;
+        shadowed
+    }
+
+    /// Look up a skill by name.
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Skill>> {
+        if let Some(skill) = self.skills.get(name) {
+            return Some(skill.clone());
+        }
+        if let Some(skill) = self
+            .loaded
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(name)
+        {
+            return Some(skill.clone());
+        }
+        self.discovered
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(name)
+            .cloned()
+    }
+
+    /// List all registered, loaded, and discovered skill names, sorted.
+    pub fn list(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.skills.keys().cloned().collect();
+        names.extend(
+            self.loaded
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .keys()
+                .cloned(),
+        );
+        names.extend(
+            self.discovered
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .keys()
+                .cloned(),
+        );
+        names.sort();
+        names
+    }
+
+    /// Invoke the skill `name`, holding its concurrency class (if any) for
+    /// the duration of the run. The run is recorded in the journal until it
+    /// finishes.
+    pub async fn invoke(
+        &self,
+        name: &str,
+        invocation: &SkillInvocation,
+    ) -> Result<SandboxResult, SkillError> {
+        self.invoke_with(name, invocation, None).await
+    }
+
+    /// Like [`invoke`](Self::invoke), but sends each output line to
+    /// `output` as the skill produces it.
+    pub async fn invoke_streaming(
+        &self,
+        name: &str,
+        invocation: &SkillInvocation,
+        output: OutputSender,
+    ) -> Result<SandboxResult, SkillError> {
+        self.invoke_with(name, invocation, Some(output)).await
+    }
+
+    async fn invoke_with(
+        &self,
+        name: &str,
+        invocation: &SkillInvocation,
+        output: Option<OutputSender>,
+    ) -> Result<SandboxResult, SkillError> {
+        let skill = self
+            .get(name)
+            .ok_or_else(|| SkillError::NotFound(name.to_string()))?;
+        let entry = self.journal.begin(
+            name,
+            invocation.origin.clone(),
+            skill.sandbox_label().map(str::to_string),
+        );
+        let _guard = match skill.concurrency_class() {
+            Some(class) => Some(self.locks.acquire(class, name).await?),
+            None => None,
+        };
+        entry.set_step("running");
+        self.events.publish(DaemonEvent::SkillStarted {
+            skill: name.to_string(),
+        });
+        let started = Instant::now();
+        let result = match output {
+            Some(output) => skill.invoke_streaming(invocation, output).await,
+            None => skill.invoke(invocation).await,
+        };
+        self.events.publish(DaemonEvent::SkillFinished {
+            skill: name.to_string(),
+            success: result.as_ref().is_ok_and(SandboxResult::success),
+            exit_code: result.as_ref().ok().map(|r| r.exit_code),
+            elapsed_ms: started.elapsed().as_millis() as u64,
+        });
+        if skill.sandbox_label().is_some() {
+            let success = result.as_ref().is_ok_and(SandboxResult::success);
+            self.metrics
+                .record_sandbox_execution(success, started.elapsed());
+            if let Some(cpu_time) = result.as_ref().ok().and_then(|r| r.cpu_time) {
+                self.metrics.record_sandbox_cpu(cpu_time);
+            }
+        }
+        result
+    }
+
+    /// Journal of in-flight runs.
+    pub fn journal(&self) -> &Arc<RunJournal> {
+        &self.journal
+    }
+
+    /// Daemon-wide concurrency class locks.
+    pub fn locks(&self) -> &ConcurrencyLocks {
+        &self.locks
+    }
+}
+
+impl Default for SkillRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A skill that runs a command inside an isolation sandbox.
+///
+/// Wraps an external executable (Forgejo Action, script, binary) so that
+/// it executes inside a platform-appropriate sandbox. The message body is
+/// passed via the `CRUSTYCLAW_MESSAGE` environment variable.
+///
+/// When attached to a [`SandboxPool`] via [`with_pool`](Self::with_pool),
+/// executions wait for a free pool slot so that `max_concurrent` is honoured
+/// across all skills.
+pub struct IsolatedSkill {
+    skill_name: String,
+    skill_description: String,
+    /// The command argv to run inside the sandbox.
+    command: Vec<String>,
+    /// Sandbox configuration (resource limits, mounts, network policy).
+    sandbox_config: SandboxConfig,
+    /// The isolation backend to use.
+    backend: Box<dyn isolation::SandboxBackend>,
+    /// Shared pool bounding concurrent sandboxes, if any.
+    pool: Option<Arc<SandboxPool>>,
+    /// Mutual-exclusion class, if any.
+    concurrency_class: Option<ConcurrencyClass>,
+    /// Live secret store and staging directory for resolving injections.
+    secrets: Option<(Arc<RwLock<SecretStore>>, PathBuf)>,
+    /// Egress proxy the sandbox's traffic is forced through, if any.
+    egress: Option<Arc<EgressProxy>>,
+    /// Picks the backend for invocations carrying a trust tier.
+    selector: Option<Arc<TrustBasedSelector>>,
+    /// Trust tier the skill was loaded at, if known.
+    trust_tier: Option<TrustTier>,
+}
+
+impl IsolatedSkill {
+    /// Create a new isolated skill.
+    pub fn new(
+        name: impl Into<String>,
+        description: impl Into<String>,
+        command: Vec<String>,
+        sandbox_config: SandboxConfig,
+        backend: Box<dyn isolation::SandboxBackend>,
+    ) -> Self {
+        Self {
+            skill_name: name.into(),
+            skill_description: description.into(),
+            command,
+            sandbox_config,
+            backend,
+            pool: None,
+            concurrency_class: None,
+            secrets: None,
+            egress: None,
+            selector: None,
+            trust_tier: None,
+        }
+    }
+
+    /// Pick backends for invocations with a trust tier with `selector`,
+    /// instead of the default one.
+    pub fn with_selector(mut self, selector: Arc<TrustBasedSelector>) -> Self {
+        self.selector = Some(selector);
+        self
+    }
+
+    /// Record the trust tier the skill's backend was chosen for. Invocations
+    /// at this tier or a weaker one run on that backend.
+    pub fn with_trust_tier(mut self, tier: TrustTier) -> Self {
+        self.trust_tier = Some(tier);
+        self
+    }
+
+    /// Run executions through a shared sandbox pool.
+    pub fn with_pool(mut self, pool: Arc<SandboxPool>) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Place the skill in a mutual-exclusion class.
+    pub fn with_concurrency_class(mut self, class: ConcurrencyClass) -> Self {
+        self.concurrency_class = Some(class);
+        self
+    }
+
+    /// Resolve the sandbox's secret injections against `store` at each run,
+    /// staging secret files under `staging_dir`.
+    ///
+    /// The store is read when the sandbox starts, so rotated secrets reach
+    /// the next execution without rebuilding the skill.
+    pub fn with_secrets(
+        mut self,
+        store: Arc<RwLock<SecretStore>>,
+        staging_dir: impl Into<PathBuf>,
+    ) -> Self {
+        self.secrets = Some((store, staging_dir.into()));
+        self
+    }
+
+    /// Force the sandbox's HTTP traffic through `proxy`, which swaps the
+    /// sentinels injected in place of environment secrets for the real
+    /// values (see [`EgressProxy::confine`]).
+    pub fn with_egress_proxy(mut self, proxy: Arc<EgressProxy>) -> Self {
+        self.egress = Some(proxy);
+        self
+    }
+
+    /// Run the command with `config` on `backend`, through the pool if set,
+    /// sending its output lines to `output` if given.
+    async fn run(
+        &self,
+        backend: &dyn isolation::SandboxBackend,
+        config: &SandboxConfig,
+        output: Option<OutputSender>,
+    ) -> Result<SandboxResult, SkillError> {
+        // The grant keeps the sandbox's proxy credential live until the
+        // run ends.
+        let egress = self
+            .egress
+            .as_ref()
+            .map(|proxy| proxy.confine(config.clone()));
+        let config = match &egress {
+            Some((confined, _grant)) => confined,
+            None => config,
+        };
+        config.validate()?;
+        let resolved;
+        let config = match &self.secrets {
+            Some((store, staging_dir)) if !config.secret_injections.is_empty() => {
+                let store = store.read().unwrap_or_else(|e| e.into_inner());
+                resolved = config.resolve_secrets(&store, staging_dir)?;
+                &resolved
+            }
+            _ => config,
+        };
+        // Output is scanned for every secret in the store, not only those
+        // injected: a skill may have read others from a shared mount.
+        let scanner = self.secrets.as_ref().map(|(store, _)| {
+            let store = store.read().unwrap_or_else(|e| e.into_inner());
+            Arc::new(LeakScanner::from_store(&store))
+        });
+        let (output, forward) = match (&scanner, output) {
+            (Some(scanner), Some(output)) => {
+                let (output, forward) = scanner.clone().redacting_sender(output);
+                (Some(output), Some(forward))
+            }
+            (_, output) => (output, None),
+        };
+        let started = SystemTime::now();
+        let mut result = match (&self.pool, output) {
+            (Some(pool), Some(output)) => {
+                pool.execute_streaming(backend, config, &self.command, output)
+                    .await?
+            }
+            (Some(pool), None) => pool.execute(backend, config, &self.command).await?,
+            (None, Some(output)) => {
+                backend
+                    .execute_streaming(config, &self.command, output)
+                    .await?
+            }
+            (None, None) => backend.execute(config, &self.command).await?,
+        };
+        if let Some(forward) = forward {
+            let _ = forward.await;
+        }
+        if let Some(scanner) = scanner {
+            scanner.scan_run(&config.label, &config.mounts, &mut result, started);
+        }
+        Ok(result)
+    }
+
+    /// Run a direct invocation, with the arguments injected as environment
+    /// variables: `CRUSTYCLAW_ARGS` holds the full JSON object, and each
+    /// scalar argument is also exported as `CRUSTYCLAW_ARG_<KEY>`.
+    fn run_invocation(
+        &self,
+        invocation: &SkillInvocation,
+        output: Option<OutputSender>,
+    ) -> BoxFuture<'_, Result<SandboxResult, SkillError>> {
+        let args_json = invocation.args_json();
+        let mut config = self
+            .sandbox_config
+            .clone()
+            .with_env("CRUSTYCLAW_MESSAGE", &args_json)
+            .with_env("CRUSTYCLAW_CHANNEL", "ipc")
+            .with_env("CRUSTYCLAW_ARGS", &args_json);
+        for (key, value) in &invocation.args {
+            let value = match value {
+                serde_json::Value::String(s) => s.clone(),
+                serde_json::Value::Number(_) | serde_json::Value::Bool(_) => value.to_string(),
+                _ => continue,
+            };
+            config = config.with_env(arg_env_name(key), value);
+        }
+        // A tier can raise the isolation the skill was loaded with, never
+        // lower it.
+        let trust_tier = invocation
+            .trust_tier
+            .filter(|tier| self.trust_tier.is_none_or(|own| *tier > own));
+
+        Box::pin(async move {
+            match trust_tier {
+                Some(tier) => {
+                    let backend = match &self.selector {
+                        Some(selector) => selector.select(tier),
+                        None => TrustBasedSelector::new().select(tier),
+                    };
+                    tracing::debug!(skill = %self.skill_name, %tier, backend = backend.name(), "Invoking skill");
+                    self.run(backend.as_ref(), &config, output).await
+                }
+                None => self.run(self.backend.as_ref(), &config, output).await,
+            }
+        })
+    }
+}
+
+/// Environment variable name for a scalar skill argument
+/// (`max-depth` → `CRUSTYCLAW_ARG_MAX_DEPTH`).
+pub(crate) fn arg_env_name(key: &str) -> String {
+    let suffix: String = key
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("CRUSTYCLAW_ARG_{suffix}")
+}
+
+impl Skill for IsolatedSkill {
+    fn name(&self) -> &str {
+        &self.skill_name
+    }
+
+    fn description(&self) -> &str {
+        &self.skill_description
+    }
+
+    fn isolated(&self) -> bool {
+        true
+    }
+
+    fn concurrency_class(&self) -> Option<&ConcurrencyClass> {
+        self.concurrency_class.as_ref()
+    }
+
+    fn sandbox_label(&self) -> Option<&str> {
+        Some(&self.sandbox_config.label)
+    }
+
+    fn trust_tier(&self) -> Option<TrustTier> {
+        self.trust_tier
+    }
+
+    fn execute(&self, message: &Envelope) -> BoxFuture<'_, Result<String, SkillError>> {
+        let body = message.body.clone();
+        let channel = message.channel.clone();
+
+        Box::pin(async move {
+            // Build a per-invocation config with the message injected as env vars
+            let config = self
+                .sandbox_config
+                .clone()
+                .with_env("CRUSTYCLAW_MESSAGE", &body)
+                .with_env("CRUSTYCLAW_CHANNEL", &channel);
+
+            let result = self.run(self.backend.as_ref(), &config, None).await?;
+
+            if result.success() {
+                Ok(result.stdout)
+            } else {
+                Err(SkillError::Execution(format!(
+                    "skill '{}' exited with code {}: {}",
+                    self.skill_name,
+                    result.exit_code,
+                    result.stderr.trim()
+                )))
+            }
+        })
+    }
+
+    /// Runs the command with the arguments injected as environment
+    /// variables: `CRUSTYCLAW_ARGS` holds the full JSON object, and each
+    /// scalar argument is also exported as `CRUSTYCLAW_ARG_<KEY>`.
+    fn invoke(
+        &self,
+        invocation: &SkillInvocation,
+    ) -> BoxFuture<'_, Result<SandboxResult, SkillError>> {
+        self.run_invocation(invocation, None)
+    }
+
+    /// Streams the command's output as it runs.
+    fn invoke_streaming(
+        &self,
+        invocation: &SkillInvocation,
+        output: OutputSender,
+    ) -> BoxFuture<'_, Result<SandboxResult, SkillError>> {
+        self.run_invocation(invocation, Some(output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_registry() {
+        let registry = SkillRegistry::new();
+        assert!(registry.list().is_empty());
+        assert!(registry.get("nonexistent").is_none());
+    }
+
+    #[test]
+    fn test_isolated_skill_properties() {
+        let config = SandboxConfig::new("test-skill").with_workdir("/tmp");
+        let skill = IsolatedSkill::new(
+            "echo-skill",
+            "Echoes the message",
+            vec!["echo".to_string(), "hello".to_string()],
+            config,
+            Box::new(isolation::NoopBackend),
+        );
+
+        assert_eq!(skill.name(), "echo-skill");
+        assert_eq!(skill.description(), "Echoes the message");
+        assert!(skill.isolated());
+    }
+
+    #[tokio::test]
+    async fn test_isolated_skill_execute() {
+        let config = SandboxConfig::new("echo-test").with_workdir("/tmp");
+        let skill = IsolatedSkill::new(
+            "echo-skill",
+            "Echoes via sandbox",
+            vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "echo $CRUSTYCLAW_MESSAGE".to_string(),
+            ],
+            config,
+            Box::new(isolation::NoopBackend),
+        );
+
+        let envelope = Envelope::new("test", "hello from isolation");
+        let result = skill.execute(&envelope).await.unwrap();
+        assert_eq!(result.trim(), "hello from isolation");
+    }
+
+    #[tokio::test]
+    async fn test_isolated_skill_failure() {
+        let config = SandboxConfig::new("fail-test").with_workdir("/tmp");
+        let skill = IsolatedSkill::new(
+            "fail-skill",
+            "Always fails",
+            vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "echo 'error' >&2; exit 1".to_string(),
+            ],
+            config,
+            Box::new(isolation::NoopBackend),
+        );
+
+        let envelope = Envelope::new("test", "trigger");
+        let result = skill.execute(&envelope).await;
+        assert!(result.is_err());
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("fail-skill"));
+        assert!(err.contains("error"));
+    }
+
+    #[tokio::test]
+    async fn test_isolated_skill_with_pool() {
+        let pool = Arc::new(SandboxPool::new(1));
+        let config = SandboxConfig::new("pool-skill").with_workdir("/tmp");
+        let skill = IsolatedSkill::new(
+            "pooled-echo",
+            "Echoes via the sandbox pool",
+            vec!["echo".to_string(), "pooled".to_string()],
+            config,
+            Box::new(isolation::NoopBackend),
+        )
+        .with_pool(pool.clone());
+
+        let envelope = Envelope::new("test", "trigger");
+        let result = skill.execute(&envelope).await.unwrap();
+        assert_eq!(result.trim(), "pooled");
+
+        let runs = pool.runs();
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].label, "pool-skill");
+    }
+
+    #[tokio::test]
+    async fn test_isolated_skill_invoke_with_args() {
+        let config = SandboxConfig::new("invoke-test").with_workdir("/tmp");
+        let skill = IsolatedSkill::new(
+            "greet",
+            "Greets by name",
+            vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "echo \"hi $CRUSTYCLAW_ARG_USER_NAME x$CRUSTYCLAW_ARG_COUNT\"; echo oops >&2; exit 3"
+                    .to_string(),
+            ],
+            config,
+            Box::new(isolation::NoopBackend),
+        );
+
+        let invocation = SkillInvocation::new()
+            .with_arg("user-name", "ada")
+            .with_arg("count", 2);
+        let result = skill.invoke(&invocation).await.unwrap();
+        assert_eq!(result.exit_code, 3);
+        assert_eq!(result.stdout.trim(), "hi ada x2");
+        assert_eq!(result.stderr.trim(), "oops");
+    }
+
+    #[tokio::test]
+    async fn test_isolated_skill_invoke_args_json() {
+        let config = SandboxConfig::new("invoke-json").with_workdir("/tmp");
+        let skill = IsolatedSkill::new(
+            "dump",
+            "Dumps args",
+            vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "echo \"$CRUSTYCLAW_ARGS\"".to_string(),
+            ],
+            config,
+            Box::new(isolation::NoopBackend),
+        );
+
+        let invocation = SkillInvocation::new().with_arg("k", "v");
+        let result = skill.invoke(&invocation).await.unwrap();
+        assert_eq!(result.stdout.trim(), r#"{"k":"v"}"#);
+    }
+
+    #[tokio::test]
+    async fn test_isolated_skill_sees_rotated_secret() {
+        use crate::secrets::InjectionMethod;
+
+        let dir = tempfile::tempdir().unwrap();
+        let key_file = dir.path().join("key");
+        std::fs::write(&key_file, "v1").unwrap();
+        let mut store = SecretStore::new();
+        store
+            .load_from_file("key", &key_file, InjectionMethod::Env("KEY".to_string()))
+            .unwrap();
+        let store = Arc::new(RwLock::new(store));
+
+        let config = SandboxConfig::new("secret-test")
+            .with_workdir("/tmp")
+            .with_secret_env("key", "KEY");
+        let skill = IsolatedSkill::new(
+            "show",
+            "Shows the key",
+            vec!["sh".to_string(), "-c".to_string(), "echo $KEY".to_string()],
+            config,
+            Box::new(isolation::NoopBackend),
+        )
+        .with_secrets(store.clone(), dir.path());
+
+        let invocation = SkillInvocation::new();
+        assert_eq!(skill.invoke(&invocation).await.unwrap().stdout.trim(), "v1");
+
+        std::fs::write(&key_file, "v2").unwrap();
+        assert!(store.write().unwrap().rotate("key").unwrap());
+        assert_eq!(skill.invoke(&invocation).await.unwrap().stdout.trim(), "v2");
+
+        // A secret missing from the store fails the run instead of starting
+        // the sandbox without it.
+        store.write().unwrap().remove("key");
+        assert!(skill.invoke(&invocation).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_isolated_skill_behind_egress_proxy_sees_sentinel() {
+        use crate::secrets::InjectionMethod;
+
+        let dir = tempfile::tempdir().unwrap();
+        let key_file = dir.path().join("key");
+        std::fs::write(&key_file, "sk-real").unwrap();
+        let mut store = SecretStore::new();
+        store
+            .load_from_file("key", &key_file, InjectionMethod::Env("KEY".to_string()))
+            .unwrap();
+        let store = Arc::new(RwLock::new(store));
+        let proxy = Arc::new(EgressProxy::new(
+            "127.0.0.1:3128".parse().unwrap(),
+            store.clone(),
+        ));
+
+        let config = SandboxConfig::new("proxied")
+            .with_workdir("/tmp")
+            .with_secret_env("key", "KEY");
+        let skill = IsolatedSkill::new(
+            "show",
+            "Shows the key",
+            vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                // Sentinels in output are redacted, so print it cut short.
+                "echo ${KEY#__} ${HTTP_PROXY%%:*} ${HTTP_PROXY#*@}".to_string(),
+            ],
+            config,
+            Box::new(isolation::NoopBackend),
+        )
+        .with_secrets(store, dir.path())
+        .with_egress_proxy(proxy);
+
+        let result = skill.invoke(&SkillInvocation::new()).await.unwrap();
+        assert_eq!(
+            result.stdout.trim(),
+            "CRUSTYCLAW_SENTINEL_key__ http 127.0.0.1:3128"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_isolated_skill_redacts_leaked_secrets() {
+        use crate::secrets::InjectionMethod;
+
+        let dir = tempfile::tempdir().unwrap();
+        let out = tempfile::tempdir().unwrap();
+        let key_file = dir.path().join("key");
+        std::fs::write(&key_file, "sk-real-value").unwrap();
+        let mut store = SecretStore::new();
+        store
+            .load_from_file("key", &key_file, InjectionMethod::Env("KEY".to_string()))
+            .unwrap();
+        let store = Arc::new(RwLock::new(store));
+
+        let config = SandboxConfig::new("leaky")
+            .with_workdir("/tmp")
+            .with_secret_env("key", "KEY")
+            .with_mount(isolation::SharedMount::read_write(out.path(), out.path()));
+        let script = format!(
+            "echo key=$KEY; echo $KEY >&2; echo $KEY > {}/copy",
+            out.path().display()
+        );
+        let skill = IsolatedSkill::new(
+            "leak",
+            "Prints its key",
+            vec!["sh".to_string(), "-c".to_string(), script],
+            config,
+            Box::new(isolation::NoopBackend),
+        )
+        .with_secrets(store, dir.path());
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let result = skill
+            .invoke_streaming(&SkillInvocation::new(), tx)
+            .await
+            .unwrap();
+        assert_eq!(result.stdout.trim(), "key=[REDACTED]");
+        assert_eq!(result.stderr.trim(), "[REDACTED]");
+        assert_eq!(
+            std::fs::read_to_string(out.path().join("copy")).unwrap(),
+            "[REDACTED]\n"
+        );
+        let mut streamed = Vec::new();
+        while let Ok(line) = rx.try_recv() {
+            streamed.push(line.line);
+        }
+        assert!(!streamed.is_empty());
+        assert!(streamed.iter().all(|line| !line.contains("sk-real-value")));
+    }
+
+    #[tokio::test]
+    async fn test_default_invoke_wraps_execute() {
+        struct Upper;
+        impl Skill for Upper {
+            fn name(&self) -> &str {
+                "upper"
+            }
+            fn description(&self) -> &str {
+                "Uppercases the message"
+            }
+            fn execute(&self, message: &Envelope) -> BoxFuture<'_, Result<String, SkillError>> {
+                let body = message.body.to_uppercase();
+                Box::pin(async move { Ok(body) })
+            }
+        }
+
+        let result = Upper
+            .invoke(&SkillInvocation::new().with_arg("a", "b"))
+            .await
+            .unwrap();
+        assert!(result.success());
+        assert_eq!(result.stdout, r#"{"A":"B"}"#);
+    }
+
+    #[tokio::test]
+    async fn test_invoke_streaming() {
+        let mut registry = SkillRegistry::new();
+        registry.register(Box::new(IsolatedSkill::new(
+            "greet",
+            "Greets",
+            vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "echo hello $CRUSTYCLAW_ARG_NAME; echo done >&2".to_string(),
+            ],
+            SandboxConfig::new("greet").with_workdir("/tmp"),
+            Box::new(isolation::NoopBackend),
+        )));
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let result = registry
+            .invoke_streaming("greet", &SkillInvocation::new().with_arg("name", "you"), tx)
+            .await
+            .unwrap();
+        assert_eq!(result.stdout, "hello you\n");
+        let mut lines = Vec::new();
+        while let Some(line) = rx.recv().await {
+            lines.push((line.stream, line.line));
+        }
+        lines.sort();
+        assert_eq!(
+            lines,
+            [
+                (OutputStream::Stdout, "hello you".to_string()),
+                (OutputStream::Stderr, "done".to_string()),
+            ]
+        );
+
+        // Skills without native streaming send their result's lines.
+        struct Twice;
+        impl Skill for Twice {
+            fn name(&self) -> &str {
+                "twice"
+            }
+            fn description(&self) -> &str {
+                "Says it twice"
+            }
+            fn execute(&self, _message: &Envelope) -> BoxFuture<'_, Result<String, SkillError>> {
+                Box::pin(async { Ok("a\nb\n".to_string()) })
+            }
+        }
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        Twice
+            .invoke_streaming(&SkillInvocation::new(), tx)
+            .await
+            .unwrap();
+        assert_eq!(rx.recv().await.unwrap().line, "a");
+        assert_eq!(rx.recv().await.unwrap().line, "b");
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[test]
+    fn test_arg_env_name() {
+        assert_eq!(arg_env_name("max-depth"), "CRUSTYCLAW_ARG_MAX_DEPTH");
+        assert_eq!(arg_env_name("path"), "CRUSTYCLAW_ARG_PATH");
+    }
+
+    #[tokio::test]
+    async fn test_isolated_skill_in_registry() {
+        let config = SandboxConfig::new("reg-test").with_workdir("/tmp");
+        let skill = IsolatedSkill::new(
+            "sandbox-echo",
+            "Sandboxed echo",
+            vec!["echo".to_string(), "hi".to_string()],
+            config,
+            Box::new(isolation::NoopBackend),
+        );
+
+        let mut registry = SkillRegistry::new();
+        registry.register(Box::new(skill));
+
+        let found = registry.get("sandbox-echo").unwrap();
+        assert!(found.isolated());
+        assert_eq!(found.description(), "Sandboxed echo");
+    }
+
+    #[test]
+    fn test_replace_discovered() {
+        let echo = |name: &str, description: &str| -> Box<dyn Skill> {
+            Box::new(IsolatedSkill::new(
+                name,
+                description,
+                vec!["echo".to_string()],
+                SandboxConfig::new(name).with_workdir("/tmp"),
+                Box::new(isolation::NoopBackend),
+            ))
+        };
+        let mut registry = SkillRegistry::new();
+        registry.register(echo("builtin", "Built in"));
+
+        let shadowed =
+            registry.replace_discovered(vec![echo("found", ""), echo("builtin", "Shadow")]);
+        assert_eq!(shadowed, vec!["builtin".to_string()]);
+        assert_eq!(
+            registry.list(),
+            vec!["builtin".to_string(), "found".to_string()]
+        );
+        assert_eq!(registry.get("builtin").unwrap().description(), "Built in");
+
+        // A held skill survives the rescan that removes it.
+        let held = registry.get("found").unwrap();
+        registry.replace_discovered(Vec::new());
+        assert_eq!(registry.list(), vec!["builtin".to_string()]);
+        assert_eq!(held.name(), "found");
+
+        // Loaded skills persist across rescans and shadow discovered ones.
+        assert!(registry.register_loaded(echo("native", "From a plugin")));
+        assert!(!registry.register_loaded(echo("builtin", "Shadow")));
+        let shadowed = registry.replace_discovered(vec![echo("native", "Shadow")]);
+        assert_eq!(shadowed, vec!["native".to_string()]);
+        assert_eq!(
+            registry.get("native").unwrap().description(),
+            "From a plugin"
+        );
+        assert_eq!(
+            registry.list(),
+            vec!["builtin".to_string(), "native".to_string()]
+        );
+    }
+
+    fn deploy_skill(name: &str, policy: ExclusionPolicy) -> IsolatedSkill {
+        IsolatedSkill::new(
+            name,
+            "Slow deploy",
+            vec!["sh".to_string(), "-c".to_string(), "sleep 0.3".to_string()],
+            SandboxConfig::new(name).with_workdir("/tmp"),
+            Box::new(isolation::NoopBackend),
+        )
+        .with_concurrency_class(ConcurrencyClass::new("deploy").with_policy(policy))
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_class_queues_runs() {
+        let mut registry = SkillRegistry::new();
+        registry.register(Box::new(deploy_skill("deploy-web", ExclusionPolicy::Queue)));
+        registry.register(Box::new(deploy_skill("deploy-db", ExclusionPolicy::Queue)));
+        let metrics = Arc::new(Metrics::new());
+        let registry = Arc::new(registry.with_metrics(metrics.clone()));
+        let journal = registry.journal().clone();
+
+        let first = tokio::spawn({
+            let registry = registry.clone();
+            async move { registry.invoke("deploy-web", &SkillInvocation::new()).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let second = tokio::spawn({
+            let registry = registry.clone();
+            async move { registry.invoke("deploy-db", &SkillInvocation::new()).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let status = registry.locks().status();
+        assert_eq!(status.len(), 1);
+        assert_eq!(status[0].class, "deploy");
+        assert_eq!(status[0].holder.as_ref().unwrap().0, "deploy-web");
+        assert_eq!(status[0].queued, vec!["deploy-db".to_string()]);
+        let mut steps: Vec<(String, String)> = journal
+            .active()
+            .into_iter()
+            .map(|run| (run.skill, run.step))
+            .collect();
+        steps.sort();
+        assert_eq!(
+            steps,
+            vec![
+                ("deploy-db".to_string(), "queued".to_string()),
+                ("deploy-web".to_string(), "running".to_string())
+            ]
+        );
+
+        let started = Instant::now();
+        first.await.unwrap().unwrap();
+        second.await.unwrap().unwrap();
+        // The second run only started once the first released the class.
+        assert!(started.elapsed() >= Duration::from_millis(400));
+
+        let status = registry.locks().status();
+        assert!(status[0].holder.is_none());
+        assert!(status[0].queued.is_empty());
+        assert!(journal.active().is_empty());
+        assert!(
+            metrics
+                .render()
+                .contains("crustyclaw_sandbox_executions_total{outcome=\"success\"} 2")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_class_reject_policy() {
+        let locks = ConcurrencyLocks::new();
+        let reject = ConcurrencyClass::new("deploy").with_policy(ExclusionPolicy::Reject);
+
+        let guard = locks.acquire(&reject, "deploy-web").await.unwrap();
+        match locks.acquire(&reject, "deploy-db").await {
+            Err(SkillError::Busy { class, holder }) => {
+                assert_eq!(class, "deploy");
+                assert_eq!(holder, "deploy-web");
+            }
+            other => panic!("expected Busy, got {:?}", other.err()),
+        }
+        // Other classes are unaffected.
+        locks
+            .acquire(&ConcurrencyClass::new("backup"), "backup")
+            .await
+            .unwrap();
+
+        drop(guard);
+        assert!(locks.acquire(&reject, "deploy-db").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_waiter_leaves_queue() {
+        let locks = ConcurrencyLocks::new();
+        let class = ConcurrencyClass::new("deploy");
+        let _guard = locks.acquire(&class, "deploy-web").await.unwrap();
+
+        let waited = tokio::time::timeout(
+            Duration::from_millis(20),
+            locks.acquire(&class, "deploy-db"),
+        )
+        .await;
+        assert!(waited.is_err());
+        assert!(locks.status()[0].queued.is_empty());
+    }
+}
@@ -0,0 +1,603 @@
+//! Skill packages — signed `.claw` archives installed into the skills
+//! directory.
+//!
+//! A package is a tar archive (ustar or pax) of one skill directory: its
+//! `skill.toml`, any assets, and optionally a WASM module or binary. Only
+//! regular files and directories are allowed, with relative paths that stay
+//! inside the archive.
+//!
+//! The archive's [`SIGNATURE_FILE`] is a minisign signature of its
+//! [contents list](SkillPackage::contents): one `<sha256>  <mode>  <path>`
+//! line per other file, sorted by path, where the mode is `755` for files
+//! with any execute bit and `644` for the rest, the permissions they are
+//! unpacked with. Signing the list rather than the archive keeps the
+//! signature independent of tar metadata such as timestamps and owners:
+//!
+//! ```sh
+//! cd weather
+//! find . -type f ! -name claw.minisig \
+//!     \( -perm /111 -printf '755 %P\n' -o -printf '644 %P\n' \) \
+//!     | LC_ALL=C sort -k2 | while read -r mode path; do
+//!         printf '%s  %s  %s\n' "$(sha256sum < "$path" | cut -c1-64)" "$mode" "$path"
+//!     done > ../weather.contents
+//! minisign -S -s release.key -m ../weather.contents -x claw.minisig
+//! tar --format=ustar -cf ../weather.claw .
+//! ```
+//!
+//! [`install`] checks the signature against `[security.trusted_keys]` and
+//! unpacks the skill into `<skills_dir>/<name>`, replacing an earlier
+//! version. Unsigned packages are refused unless installing in development
+//! mode, which `security.strict` forbids. A signature that does not verify
+//! is always refused.
+
+use std::collections::BTreeMap;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crustyclaw_config::SecurityConfig;
+use crustyclaw_config::minisign::MinisignError;
+
+use super::manifest::{MANIFEST_FILE, ManifestError, SkillManifest, find_manifest};
+
+/// Extension of skill package files.
+pub const PACKAGE_EXTENSION: &str = "claw";
+
+/// The archive member holding the package signature.
+pub const SIGNATURE_FILE: &str = "claw.minisig";
+
+/// Largest total size of the files in a package.
+pub const MAX_PACKAGE_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Errors reading, verifying, or installing a package.
+#[derive(Debug, thiserror::Error)]
+pub enum PackageError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("malformed package: {0}")]
+    Malformed(String),
+
+    #[error("invalid manifest: {0}")]
+    Manifest(#[from] ManifestError),
+
+    #[error("package is unsigned; install it in development mode to allow that")]
+    Unsigned,
+
+    #[error("development mode is not allowed with security.strict")]
+    StrictMode,
+
+    #[error("bad signature: {0}")]
+    Signature(#[from] MinisignError),
+
+    #[error("a skill named '{name}' is already installed at {}", dir.display())]
+    Conflict { name: String, dir: PathBuf },
+}
+
+/// A parsed skill package.
+#[derive(Debug, Clone)]
+pub struct SkillPackage {
+    manifest: SkillManifest,
+    /// Path → (contents, executable).
+    files: BTreeMap<String, (Vec<u8>, bool)>,
+    signature: Option<String>,
+}
+
+/// The result of [`install`].
+#[derive(Debug, Clone)]
+pub struct Installed {
+    /// Skill name from the manifest.
+    pub name: String,
+    /// Skill version from the manifest.
+    pub version: String,
+    /// Directory the skill was unpacked into.
+    pub dir: PathBuf,
+    /// Trusted key that signed the package, or `None` if it was unsigned.
+    pub signer: Option<String>,
+    /// Version of the skill this one replaced, if any.
+    pub replaced: Option<String>,
+}
+
+impl SkillPackage {
+    /// Parse a package from the bytes of its archive.
+    pub fn parse(archive: &[u8]) -> Result<Self, PackageError> {
+        let mut files = BTreeMap::new();
+        let mut signature = None;
+        for entry in read_tar(archive)? {
+            let path = normalize(&entry.path)?;
+            if entry.directory || path.is_empty() {
+                continue;
+            }
+            if path == SIGNATURE_FILE {
+                let text = String::from_utf8(entry.data)
+                    .map_err(|_| malformed(format!("{SIGNATURE_FILE} is not UTF-8")))?;
+                signature = Some(text);
+                continue;
+            }
+            if files
+                .insert(path.clone(), (entry.data, entry.mode & 0o111 != 0))
+                .is_some()
+            {
+                return Err(malformed(format!("{path} appears more than once")));
+            }
+        }
+
+        let (manifest, _) = files
+            .get(MANIFEST_FILE)
+            .ok_or_else(|| malformed(format!("no {MANIFEST_FILE} at the top level")))?;
+        let manifest = std::str::from_utf8(manifest)
+            .map_err(|_| malformed(format!("{MANIFEST_FILE} is not UTF-8")))?;
+        let manifest = SkillManifest::parse(manifest)?;
+        manifest.validate()?;
+        Ok(Self {
+            manifest,
+            files,
+            signature,
+        })
+    }
+
+    /// Read and parse the package at `path`.
+    pub fn open(path: &Path) -> Result<Self, PackageError> {
+        let size = std::fs::metadata(path)?.len();
+        // Headers and padding add at most a block or two per file.
+        if size > MAX_PACKAGE_BYTES * 2 {
+            return Err(malformed(format!(
+                "archive exceeds {} bytes",
+                MAX_PACKAGE_BYTES * 2
+            )));
+        }
+        Self::parse(&std::fs::read(path)?)
+    }
+
+    /// The skill's manifest.
+    pub fn manifest(&self) -> &SkillManifest {
+        &self.manifest
+    }
+
+    /// Paths of the files in the package, without the signature.
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.files.keys().map(String::as_str)
+    }
+
+    /// Whether the package carries a signature.
+    pub fn is_signed(&self) -> bool {
+        self.signature.is_some()
+    }
+
+    /// The signed contents list: `<sha256>  <mode>  <path>` per file, sorted
+    /// by path, with the mode the file is unpacked with.
+    pub fn contents(&self) -> String {
+        self.files
+            .iter()
+            .map(|(path, (data, executable))| {
+                let hex: String = Sha256::digest(data)
+                    .iter()
+                    .map(|b| format!("{b:02x}"))
+                    .collect();
+                format!("{hex}  {:o}  {path}\n", file_mode(*executable))
+            })
+            .collect()
+    }
+
+    /// Check the signature against `security.trusted_keys`, returning the
+    /// name of the key that made it, or `None` for an unsigned package when
+    /// `allow_unsigned` is set.
+    pub fn verify(
+        &self,
+        security: &SecurityConfig,
+        allow_unsigned: bool,
+    ) -> Result<Option<String>, PackageError> {
+        if allow_unsigned && security.strict {
+            return Err(PackageError::StrictMode);
+        }
+        match &self.signature {
+            Some(signature) => Ok(Some(
+                security.verify(self.contents().as_bytes(), signature)?,
+            )),
+            None if allow_unsigned => Ok(None),
+            None => Err(PackageError::Unsigned),
+        }
+    }
+
+    /// Unpack the skill into `<skills_dir>/<name>`, replacing what is there.
+    ///
+    /// The files are written to a temporary directory beside the target
+    /// and renamed into place, so the daemon never sees half a skill.
+    fn unpack(&self, skills_dir: &Path) -> Result<PathBuf, PackageError> {
+        let name = &self.manifest.name;
+        let dir = skills_dir.join(name);
+        let staging = skills_dir.join(format!(".{name}.installing"));
+        let old = skills_dir.join(format!(".{name}.old"));
+        std::fs::create_dir_all(skills_dir)?;
+        for leftover in [&staging, &old] {
+            if leftover.exists() {
+                std::fs::remove_dir_all(leftover)?;
+            }
+        }
+
+        std::fs::create_dir(&staging)?;
+        for (path, (data, executable)) in &self.files {
+            let target = staging.join(path);
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&target, data)?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let mode = file_mode(*executable);
+                std::fs::set_permissions(&target, std::fs::Permissions::from_mode(mode))?;
+            }
+            #[cfg(not(unix))]
+            let _ = executable;
+        }
+
+        if dir.exists() {
+            std::fs::rename(&dir, &old)?;
+        }
+        std::fs::rename(&staging, &dir)?;
+        if old.exists() {
+            std::fs::remove_dir_all(&old)?;
+        }
+        Ok(dir)
+    }
+}
+
+/// Verify the package at `path` and unpack it into `skills_dir`.
+///
+/// With `allow_unsigned` (development mode) a package without a signature
+/// is installed too. A skill of the same name installed under a different
+/// directory is a conflict.
+pub fn install(
+    path: &Path,
+    skills_dir: &Path,
+    security: &SecurityConfig,
+    allow_unsigned: bool,
+) -> Result<Installed, PackageError> {
+    let package = SkillPackage::open(path)?;
+    let signer = package.verify(security, allow_unsigned)?;
+    let name = &package.manifest.name;
+
+    let replaced = match skills_dir
+        .is_dir()
+        .then(|| find_manifest(skills_dir, name))
+        .transpose()?
+        .flatten()
+    {
+        Some((dir, _)) if dir != skills_dir.join(name) => {
+            return Err(PackageError::Conflict {
+                name: name.clone(),
+                dir,
+            });
+        }
+        Some((_, manifest)) => Some(manifest.version),
+        None => None,
+    };
+
+    let dir = package.unpack(skills_dir)?;
+    Ok(Installed {
+        name: name.clone(),
+        version: package.manifest.version.clone(),
+        dir,
+        signer,
+        replaced,
+    })
+}
+
+/// One member of a tar archive.
+struct TarEntry {
+    path: String,
+    mode: u32,
+    directory: bool,
+    data: Vec<u8>,
+}
+
+/// Read the regular files and directories of a tar archive, refusing
+/// links and special files and stopping once the files exceed
+/// [`MAX_PACKAGE_BYTES`].
+fn read_tar(archive: &[u8]) -> Result<Vec<TarEntry>, PackageError> {
+    let mut entries = Vec::new();
+    let mut total = 0u64;
+    for entry in tar::Archive::new(archive).entries().map_err(bad_tar)? {
+        let mut entry = entry.map_err(bad_tar)?;
+        let path = String::from_utf8(entry.path_bytes().into_owned()).map_err(|e| {
+            let path = String::from_utf8_lossy(e.as_bytes());
+            malformed(format!("{path:?} is not UTF-8"))
+        })?;
+        let directory = match entry.header().entry_type() {
+            tar::EntryType::Regular | tar::EntryType::Continuous => false,
+            tar::EntryType::Directory => true,
+            tar::EntryType::XGlobalHeader => continue,
+            kind => {
+                return Err(malformed(format!(
+                    "{path} has unsupported entry type {:?}; only files and directories are allowed",
+                    kind.as_byte() as char
+                )));
+            }
+        };
+        let size = entry.size();
+        total += size;
+        if total > MAX_PACKAGE_BYTES {
+            return Err(malformed(format!(
+                "contents exceed {MAX_PACKAGE_BYTES} bytes"
+            )));
+        }
+        let mode = entry.header().mode().map_err(bad_tar)?;
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data).map_err(bad_tar)?;
+        if data.len() as u64 != size {
+            return Err(malformed(format!("{path} is truncated")));
+        }
+        entries.push(TarEntry {
+            path,
+            mode,
+            directory,
+            data,
+        });
+    }
+    Ok(entries)
+}
+
+fn bad_tar(e: io::Error) -> PackageError {
+    malformed(format!("bad tar archive: {e}"))
+}
+
+/// The permissions a file is unpacked with.
+fn file_mode(executable: bool) -> u32 {
+    if executable { 0o755 } else { 0o644 }
+}
+
+/// A member path without a leading `./` or trailing `/`, refused if it is
+/// absolute or leaves the archive.
+fn normalize(path: &str) -> Result<String, PackageError> {
+    let mut parts = Vec::new();
+    for component in Path::new(path).components() {
+        match component {
+            Component::Normal(part) => parts.push(
+                part.to_str()
+                    .ok_or_else(|| malformed(format!("{path:?} is not UTF-8")))?,
+            ),
+            Component::CurDir => {}
+            _ => return Err(malformed(format!("{path:?} is outside the package"))),
+        }
+    }
+    Ok(parts.join("/"))
+}
+
+fn malformed(message: String) -> PackageError {
+    PackageError::Malformed(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = "name = \"weather\"\nversion = \"1.0.0\"\ndescription = \"Looks up the forecast\"\ncommand = [\"/skill/weather.sh\"]\n";
+    const SCRIPT: &str = "#!/bin/sh\necho sunny\n";
+    const ICON: &str = "sun\n";
+
+    /// The base64 line of the key that made [`SIGNATURE`].
+    const PUBLIC_KEY: &str = "RWQfLj1MW2p5iAOhB7/zzhC+HXDdGOdLwJln5NYwm6UNXx3chmQSVTG4";
+    /// `minisign -S` signature of the contents list of [`files`].
+    const SIGNATURE: &str = "untrusted comment: signature from minisign secret key\nRUQfLj1MW2p5iDAVANs8GWxl6ysSrR2wRpzmIm5AiRTVOxcgKMx7g7P5kyA/pbDF7gURhIs077923Ll30cErxxYRIhERRNYYLwc=\ntrusted comment: timestamp:1760000000\tfile:weather.contents\thashed\nyb8cemjyO6nJfOar2WEN2coi152lKgG+DqbCuNuC/VM+IEMndzVq/TYu1a//Vj9FZmodgHwSfilSwTleNtP8Bg==\n";
+
+    /// An archive member: path, contents, and mode.
+    type Entry<'a> = (&'a str, &'a [u8], u32);
+
+    fn files() -> Vec<Entry<'static>> {
+        vec![
+            ("./", b"", 0o755),
+            ("./skill.toml", MANIFEST.as_bytes(), 0o644),
+            ("./weather.sh", SCRIPT.as_bytes(), 0o755),
+            ("./assets/", b"", 0o755),
+            ("./assets/icon.txt", ICON.as_bytes(), 0o644),
+        ]
+    }
+
+    /// Build a ustar archive of `entries`; paths ending in `/` are
+    /// directories.
+    fn tar(entries: &[Entry<'_>]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for &(path, data, mode) in entries {
+            let kind = if path.ends_with('/') {
+                tar::EntryType::Directory
+            } else {
+                tar::EntryType::Regular
+            };
+            append(&mut builder, (path, data, mode), kind);
+        }
+        builder.into_inner().unwrap()
+    }
+
+    /// Append a member of type `kind`, writing its path as is: the
+    /// builder's own setters refuse `..` and absolute paths.
+    fn append(builder: &mut tar::Builder<Vec<u8>>, entry: Entry<'_>, kind: tar::EntryType) {
+        let (path, data, mode) = entry;
+        let mut header = tar::Header::new_ustar();
+        header.as_old_mut().name[..path.len()].copy_from_slice(path.as_bytes());
+        header.set_entry_type(kind);
+        header.set_size(data.len() as u64);
+        header.set_mode(mode);
+        header.set_cksum();
+        builder.append(&header, data).unwrap();
+    }
+
+    fn signed() -> Vec<u8> {
+        let mut entries = files();
+        entries.push(("./claw.minisig", SIGNATURE.as_bytes(), 0o644));
+        tar(&entries)
+    }
+
+    fn security(strict: bool) -> SecurityConfig {
+        SecurityConfig {
+            strict,
+            trusted_keys: [("release".to_string(), PUBLIC_KEY.to_string())].into(),
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let package = SkillPackage::parse(&signed()).unwrap();
+        assert_eq!(package.manifest().name, "weather");
+        assert_eq!(
+            package.files().collect::<Vec<_>>(),
+            ["assets/icon.txt", "skill.toml", "weather.sh"]
+        );
+        assert!(package.is_signed());
+        let contents = package.contents();
+        assert_eq!(contents.lines().count(), 3);
+        assert!(contents.ends_with("  755  weather.sh\n"), "{contents}");
+        assert!(contents.contains("  644  skill.toml\n"), "{contents}");
+    }
+
+    #[test]
+    fn test_verify() {
+        let package = SkillPackage::parse(&signed()).unwrap();
+        assert_eq!(
+            package.verify(&security(true), false).unwrap().as_deref(),
+            Some("release")
+        );
+        let untrusted = SecurityConfig::default();
+        assert!(matches!(
+            package.verify(&untrusted, true),
+            Err(PackageError::Signature(MinisignError::UnknownKey(_)))
+        ));
+
+        // A file changed after signing.
+        let mut entries = files();
+        entries[2].1 = b"#!/bin/sh\nrm -rf /\n";
+        entries.push(("./claw.minisig", SIGNATURE.as_bytes(), 0o644));
+        let tampered = SkillPackage::parse(&tar(&entries)).unwrap();
+        assert!(matches!(
+            tampered.verify(&security(false), true),
+            Err(PackageError::Signature(MinisignError::Invalid(_)))
+        ));
+
+        // A mode changed after signing.
+        let mut entries = files();
+        entries[1].2 = 0o755;
+        entries.push(("./claw.minisig", SIGNATURE.as_bytes(), 0o644));
+        let tampered = SkillPackage::parse(&tar(&entries)).unwrap();
+        assert!(matches!(
+            tampered.verify(&security(false), true),
+            Err(PackageError::Signature(MinisignError::Invalid(_)))
+        ));
+
+        // Unsigned packages need development mode, which strict mode forbids.
+        let unsigned = SkillPackage::parse(&tar(&files())).unwrap();
+        assert!(matches!(
+            unsigned.verify(&security(false), false),
+            Err(PackageError::Unsigned)
+        ));
+        assert_eq!(unsigned.verify(&security(false), true).unwrap(), None);
+        assert!(matches!(
+            unsigned.verify(&security(true), true),
+            Err(PackageError::StrictMode)
+        ));
+    }
+
+    #[test]
+    fn test_parse_rejects() {
+        let cases: [(&[Entry<'_>], &str); 5] = [
+            (&[("../evil", b"x", 0o644)], "outside the package"),
+            (&[("/etc/passwd", b"x", 0o644)], "outside the package"),
+            (&[("weather.sh", b"x", 0o644)], "no skill.toml"),
+            (
+                &[
+                    ("skill.toml", MANIFEST.as_bytes(), 0o644),
+                    ("./skill.toml", MANIFEST.as_bytes(), 0o644),
+                ],
+                "more than once",
+            ),
+            (
+                &[("skill.toml", b"name = \"bad name\"\n", 0o644)],
+                "manifest",
+            ),
+        ];
+        for (entries, expected) in cases {
+            let err = SkillPackage::parse(&tar(entries)).unwrap_err();
+            assert!(err.to_string().contains(expected), "{expected}: {err}");
+        }
+
+        let mut symlink = tar::Builder::new(Vec::new());
+        append(&mut symlink, ("link", b"", 0o777), tar::EntryType::Symlink);
+        let err = SkillPackage::parse(&symlink.into_inner().unwrap()).unwrap_err();
+        assert!(err.to_string().contains("unsupported entry type"), "{err}");
+
+        let mut corrupt = signed();
+        corrupt[0] ^= 1;
+        let err = SkillPackage::parse(&corrupt).unwrap_err();
+        assert!(err.to_string().contains("checksum"), "{err}");
+        // The manifest's header is intact, but its contents are cut short.
+        let err = SkillPackage::parse(&signed()[..1024 + 16]).unwrap_err();
+        assert!(err.to_string().contains("truncated"), "{err}");
+    }
+
+    #[test]
+    fn test_pax_path() {
+        let record = "29 path=assets/long/icon.txt\n";
+        let mut archive = tar::Builder::new(Vec::new());
+        let file = tar::EntryType::Regular;
+        append(
+            &mut archive,
+            ("skill.toml", MANIFEST.as_bytes(), 0o644),
+            file,
+        );
+        let pax = ("PaxHeader", record.as_bytes(), 0o644);
+        append(&mut archive, pax, tar::EntryType::XHeader);
+        append(&mut archive, ("short", ICON.as_bytes(), 0o644), file);
+
+        let package = SkillPackage::parse(&archive.into_inner().unwrap()).unwrap();
+        assert_eq!(
+            package.files().collect::<Vec<_>>(),
+            ["assets/long/icon.txt", "skill.toml"]
+        );
+    }
+
+    #[test]
+    fn test_install() {
+        let dir = tempfile::tempdir().unwrap();
+        let skills_dir = dir.path().join("skills");
+        let path = dir.path().join("weather.claw");
+        std::fs::write(&path, signed()).unwrap();
+
+        let installed = install(&path, &skills_dir, &security(false), false).unwrap();
+        assert_eq!(installed.name, "weather");
+        assert_eq!(installed.version, "1.0.0");
+        assert_eq!(installed.dir, skills_dir.join("weather"));
+        assert_eq!(installed.signer.as_deref(), Some("release"));
+        assert_eq!(installed.replaced, None);
+        assert_eq!(
+            std::fs::read_to_string(installed.dir.join("assets/icon.txt")).unwrap(),
+            ICON
+        );
+        assert!(!installed.dir.join(SIGNATURE_FILE).exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |name| {
+                std::fs::metadata(installed.dir.join(name))
+                    .unwrap()
+                    .permissions()
+                    .mode()
+                    & 0o777
+            };
+            assert_eq!(mode("weather.sh"), 0o755);
+            assert_eq!(mode("skill.toml"), 0o644);
+        }
+
+        // Reinstalling replaces the skill, dropping files it no longer has.
+        std::fs::write(installed.dir.join("stale.txt"), "old").unwrap();
+        let again = install(&path, &skills_dir, &security(false), false).unwrap();
+        assert_eq!(again.replaced.as_deref(), Some("1.0.0"));
+        assert!(!again.dir.join("stale.txt").exists());
+        let entries: Vec<_> = std::fs::read_dir(&skills_dir).unwrap().collect();
+        assert_eq!(entries.len(), 1);
+
+        // The same name in another directory is a conflict.
+        std::fs::rename(skills_dir.join("weather"), skills_dir.join("forecast")).unwrap();
+        let err = install(&path, &skills_dir, &security(false), false).unwrap_err();
+        assert!(matches!(err, PackageError::Conflict { .. }), "{err}");
+    }
+}
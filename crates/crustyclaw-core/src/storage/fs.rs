@@ -0,0 +1,159 @@
+//! Filesystem storage — one file per key, one directory per namespace.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+
+use super::{Storage, StorageError, validate_key};
+
+/// Keeps each blob in `<dir>/<key>`, where `dir` is the namespace's
+/// directory: `<root>/<namespace>` unless mapped elsewhere with
+/// [`with_namespace`](Self::with_namespace).
+///
+/// Files are created readable only by the daemon. [`put`](Storage::put)
+/// writes through a hidden temporary file, so readers never see a partial
+/// blob; [`append`](Storage::append) appends in place.
+#[derive(Debug, Clone)]
+pub struct FsStorage {
+    root: PathBuf,
+    namespaces: HashMap<String, PathBuf>,
+}
+
+impl FsStorage {
+    /// Create a store with its namespaces under `root`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            namespaces: HashMap::new(),
+        }
+    }
+
+    /// Builder: keep `namespace` in `dir` instead of `<root>/<namespace>`.
+    pub fn with_namespace(mut self, namespace: &str, dir: impl Into<PathBuf>) -> Self {
+        self.namespaces.insert(namespace.to_string(), dir.into());
+        self
+    }
+
+    /// The directory holding `namespace`.
+    pub fn dir(&self, namespace: &str) -> PathBuf {
+        match self.namespaces.get(namespace) {
+            Some(dir) => dir.clone(),
+            None => self.root.join(namespace),
+        }
+    }
+
+    fn path(&self, namespace: &str, key: &str) -> Result<PathBuf, StorageError> {
+        validate_key(key)?;
+        Ok(self.dir(namespace).join(key))
+    }
+}
+
+impl Storage for FsStorage {
+    fn backend(&self) -> &'static str {
+        "filesystem"
+    }
+
+    fn get(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        match std::fs::read(self.path(namespace, key)?) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn put(&self, namespace: &str, key: &str, value: &[u8]) -> Result<(), StorageError> {
+        let path = self.path(namespace, key)?;
+        let dir = self.dir(namespace);
+        std::fs::create_dir_all(&dir)?;
+        let tmp = dir.join(format!(".{key}.partial"));
+        let mut file = open_options().write(true).truncate(true).open(&tmp)?;
+        file.write_all(value)?;
+        drop(file);
+        std::fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    fn append(&self, namespace: &str, key: &str, data: &[u8]) -> Result<(), StorageError> {
+        let path = self.path(namespace, key)?;
+        std::fs::create_dir_all(self.dir(namespace))?;
+        let mut file = open_options().append(true).open(&path)?;
+        file.write_all(data)?;
+        Ok(())
+    }
+
+    fn list(&self, namespace: &str) -> Result<Vec<String>, StorageError> {
+        let entries = match std::fs::read_dir(self.dir(namespace)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut keys = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            if !entry.file_type()?.is_file() {
+                continue;
+            }
+            if let Some(name) = entry.file_name().to_str()
+                && validate_key(name).is_ok()
+            {
+                keys.push(name.to_string());
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+
+    fn delete(&self, namespace: &str, key: &str) -> Result<bool, StorageError> {
+        match std::fs::remove_file(self.path(namespace, key)?) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Options creating files readable only by the daemon.
+fn open_options() -> std::fs::OpenOptions {
+    let mut options = std::fs::OpenOptions::new();
+    options.create(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fs_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = FsStorage::new(dir.path()).with_namespace("other", dir.path().join("x"));
+        super::super::tests::exercise(&storage);
+        assert!(dir.path().join("ns/b.json").is_file());
+        assert!(dir.path().join("x/a.json").is_file());
+        // No temporary files are left behind or listed.
+        assert_eq!(std::fs::read_dir(dir.path().join("ns")).unwrap().count(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_files_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let storage = FsStorage::new(dir.path());
+        storage.put("ns", "a.json", b"{}").unwrap();
+        storage.append("ns", "b.jsonl", b"{}\n").unwrap();
+        for key in ["a.json", "b.jsonl"] {
+            let mode = std::fs::metadata(dir.path().join("ns").join(key))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600, "{key}");
+        }
+    }
+}
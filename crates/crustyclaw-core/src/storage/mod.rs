@@ -0,0 +1,260 @@
+//! Daemon state storage — namespaced blobs behind one [`Storage`] trait.
+//!
+//! Conversations, token usage, schedule run history, and the audit logs are
+//! kept as blobs: byte strings addressed by a namespace ([`CONVERSATIONS`],
+//! [`USAGE`], [`SCHEDULES`], [`AUDIT`]) and a key within it. `[storage]`
+//! picks the implementation for the whole daemon:
+//!
+//! - [`FsStorage`] — one file per key, one directory per namespace. Keys
+//!   keep their file extensions, so state directories written before the
+//!   store existed are read as they are.
+//! - [`SqliteStorage`] — one table in a single SQLite database file.
+//!
+//! [`AuditLog`] appends JSON lines to one key of the audit namespace.
+
+pub mod fs;
+pub mod sqlite;
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::Serialize;
+
+use crustyclaw_config::{AppConfig, StorageBackend};
+
+pub use fs::FsStorage;
+pub use sqlite::SqliteStorage;
+
+/// Namespace of the conversation transcripts.
+pub const CONVERSATIONS: &str = "conversations";
+
+/// Namespace of the daily token usage totals.
+pub const USAGE: &str = "usage";
+
+/// Namespace of the schedule run histories.
+pub const SCHEDULES: &str = "schedules";
+
+/// Namespace of the audit logs.
+pub const AUDIT: &str = "audit";
+
+/// File name of the SQLite database, relative to the daemon's state directory.
+pub const SQLITE_FILE: &str = "state.db";
+
+/// Longest key, in bytes.
+const MAX_KEY_LEN: usize = 255;
+
+/// Errors from storage operations.
+#[derive(Debug, thiserror::Error)]
+pub enum StorageError {
+    #[error("invalid storage key {0:?}")]
+    InvalidKey(String),
+
+    #[error("storage I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("storage database error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+}
+
+/// A store of byte blobs, addressed by namespace and key.
+///
+/// Keys are file-name-like: non-empty, at most 255 bytes, without path
+/// separators or NUL, and not starting with `.`.
+pub trait Storage: Send + Sync + fmt::Debug {
+    /// The backend's name, as configured (`filesystem` or `sqlite`).
+    fn backend(&self) -> &'static str;
+
+    /// The blob at `key`, or `None` if there is none.
+    fn get(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>, StorageError>;
+
+    /// Store `value` at `key`, replacing any blob there.
+    fn put(&self, namespace: &str, key: &str, value: &[u8]) -> Result<(), StorageError>;
+
+    /// Add `data` to the end of the blob at `key`, creating it if missing.
+    fn append(&self, namespace: &str, key: &str, data: &[u8]) -> Result<(), StorageError> {
+        let mut value = self.get(namespace, key)?.unwrap_or_default();
+        value.extend_from_slice(data);
+        self.put(namespace, key, &value)
+    }
+
+    /// The keys in `namespace`, sorted.
+    fn list(&self, namespace: &str) -> Result<Vec<String>, StorageError>;
+
+    /// Remove the blob at `key`. Returns whether there was one.
+    fn delete(&self, namespace: &str, key: &str) -> Result<bool, StorageError>;
+}
+
+/// Check that `key` may be used as a storage key.
+pub fn validate_key(key: &str) -> Result<(), StorageError> {
+    if key.is_empty()
+        || key.len() > MAX_KEY_LEN
+        || key.starts_with('.')
+        || key.contains(['/', '\\', '\0'])
+    {
+        return Err(StorageError::InvalidKey(key.to_string()));
+    }
+    Ok(())
+}
+
+/// Open the store `[storage]` describes.
+///
+/// The filesystem store keeps conversations in `conversations.dir`, the
+/// audit logs directly in `daemon.state_dir`, and every other namespace in
+/// a directory of that name under it.
+pub fn open(config: &AppConfig) -> Result<Arc<dyn Storage>, StorageError> {
+    let state_dir = Path::new(&config.daemon.state_dir);
+    match config.storage.backend {
+        StorageBackend::Filesystem => Ok(Arc::new(filesystem(config))),
+        StorageBackend::Sqlite => {
+            let path = match &config.storage.path {
+                Some(path) => PathBuf::from(path),
+                None => state_dir.join(SQLITE_FILE),
+            };
+            Ok(Arc::new(SqliteStorage::open(path)?))
+        }
+    }
+}
+
+/// The filesystem store laid out as [`open`] describes, whatever the
+/// configured backend.
+pub fn filesystem(config: &AppConfig) -> FsStorage {
+    let state_dir = Path::new(&config.daemon.state_dir);
+    FsStorage::new(state_dir)
+        .with_namespace(CONVERSATIONS, &config.conversations.dir)
+        .with_namespace(AUDIT, state_dir)
+}
+
+/// An append-only JSON lines log at one key of the [`AUDIT`] namespace.
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    storage: Arc<dyn Storage>,
+    key: String,
+}
+
+impl AuditLog {
+    /// A log stored at `key` in `storage`.
+    pub fn new(storage: Arc<dyn Storage>, key: impl Into<String>) -> Self {
+        Self {
+            storage,
+            key: key.into(),
+        }
+    }
+
+    /// A log written to the file at `path`.
+    pub fn file(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let key = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Self::new(
+            Arc::new(FsStorage::new(dir).with_namespace(AUDIT, dir)),
+            key,
+        )
+    }
+
+    /// The log's key in the audit namespace.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Append `record` as one JSON line.
+    pub fn append(&self, record: &impl Serialize) -> Result<(), StorageError> {
+        let mut line = serde_json::to_vec(record).map_err(std::io::Error::other)?;
+        line.push(b'\n');
+        self.storage.append(AUDIT, &self.key, &line)
+    }
+
+    /// The records written so far, oldest first. Lines that do not parse
+    /// are skipped.
+    pub fn records(&self) -> Result<Vec<serde_json::Value>, StorageError> {
+        let data = self.storage.get(AUDIT, &self.key)?.unwrap_or_default();
+        Ok(String::from_utf8_lossy(&data)
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs the same checks against any backend.
+    pub(super) fn exercise(storage: &dyn Storage) {
+        assert_eq!(storage.get("ns", "a.json").unwrap(), None);
+        assert!(storage.list("ns").unwrap().is_empty());
+
+        storage.put("ns", "b.json", b"{}").unwrap();
+        storage.put("ns", "a.json", b"[1]").unwrap();
+        storage.put("other", "a.json", b"other").unwrap();
+        assert_eq!(storage.get("ns", "a.json").unwrap().unwrap(), b"[1]");
+        assert_eq!(storage.list("ns").unwrap(), ["a.json", "b.json"]);
+
+        storage.put("ns", "a.json", b"[2]").unwrap();
+        assert_eq!(storage.get("ns", "a.json").unwrap().unwrap(), b"[2]");
+
+        storage.append("ns", "log.jsonl", b"one\n").unwrap();
+        storage.append("ns", "log.jsonl", b"two\n").unwrap();
+        assert_eq!(
+            storage.get("ns", "log.jsonl").unwrap().unwrap(),
+            b"one\ntwo\n"
+        );
+
+        assert!(storage.delete("ns", "a.json").unwrap());
+        assert!(!storage.delete("ns", "a.json").unwrap());
+        assert_eq!(storage.list("ns").unwrap(), ["b.json", "log.jsonl"]);
+        assert_eq!(storage.get("other", "a.json").unwrap().unwrap(), b"other");
+
+        for bad in ["", ".hidden", "../escape", "a/b", "a\\b"] {
+            assert!(
+                matches!(
+                    storage.put("ns", bad, b""),
+                    Err(StorageError::InvalidKey(_))
+                ),
+                "{bad:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_open_from_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = AppConfig::default();
+        config.daemon.state_dir = dir.path().join("state").display().to_string();
+        config.conversations.dir = dir.path().join("history").display().to_string();
+
+        let storage = open(&config).unwrap();
+        assert_eq!(storage.backend(), "filesystem");
+        storage.put(CONVERSATIONS, "cli.jsonl", b"{}\n").unwrap();
+        storage.put(USAGE, "2024-01-01.json", b"[]").unwrap();
+        storage.append(AUDIT, "elevations.jsonl", b"{}\n").unwrap();
+        assert!(dir.path().join("history/cli.jsonl").is_file());
+        assert!(dir.path().join("state/usage/2024-01-01.json").is_file());
+        assert!(dir.path().join("state/elevations.jsonl").is_file());
+
+        config.storage.backend = StorageBackend::Sqlite;
+        let storage = open(&config).unwrap();
+        assert_eq!(storage.backend(), "sqlite");
+        assert!(dir.path().join("state").join(SQLITE_FILE).is_file());
+        assert!(storage.list(CONVERSATIONS).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_audit_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let log = AuditLog::file(&path);
+        assert_eq!(log.key(), "audit.jsonl");
+        log.append(&serde_json::json!({ "n": 1 })).unwrap();
+        log.append(&serde_json::json!({ "n": 2 })).unwrap();
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(written, "{\"n\":1}\n{\"n\":2}\n");
+        let records = log.records().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1]["n"], 2);
+    }
+}
@@ -0,0 +1,165 @@
+//! SQLite storage — every namespace in one table of one database file.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use rusqlite::{Connection, OptionalExtension, params};
+
+use super::{Storage, StorageError, validate_key};
+
+/// How long a write waits for another connection's lock.
+const BUSY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Keeps blobs in the `blobs` table of a SQLite database.
+///
+/// The database file is created readable only by the daemon and opened in
+/// WAL mode, so readers do not block the writer.
+#[derive(Debug)]
+pub struct SqliteStorage {
+    path: PathBuf,
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+    /// Open the database at `path`, creating it and its table if needed.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, StorageError> {
+        let path = path.into();
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut options = std::fs::OpenOptions::new();
+        options.create(true).append(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options.open(&path)?;
+
+        let conn = Connection::open(&path)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS blobs (
+                namespace TEXT NOT NULL,
+                key TEXT NOT NULL,
+                value BLOB NOT NULL,
+                PRIMARY KEY (namespace, key)
+            )",
+        )?;
+        Ok(Self {
+            path,
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// The database file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn backend(&self) -> &'static str {
+        "sqlite"
+    }
+
+    fn get(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        validate_key(key)?;
+        Ok(self
+            .conn()
+            .query_row(
+                "SELECT value FROM blobs WHERE namespace = ?1 AND key = ?2",
+                params![namespace, key],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    fn put(&self, namespace: &str, key: &str, value: &[u8]) -> Result<(), StorageError> {
+        validate_key(key)?;
+        self.conn().execute(
+            "INSERT INTO blobs (namespace, key, value) VALUES (?1, ?2, ?3)
+             ON CONFLICT (namespace, key) DO UPDATE SET value = excluded.value",
+            params![namespace, key, value],
+        )?;
+        Ok(())
+    }
+
+    fn append(&self, namespace: &str, key: &str, data: &[u8]) -> Result<(), StorageError> {
+        validate_key(key)?;
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let mut value: Vec<u8> = tx
+            .query_row(
+                "SELECT value FROM blobs WHERE namespace = ?1 AND key = ?2",
+                params![namespace, key],
+                |row| row.get(0),
+            )
+            .optional()?
+            .unwrap_or_default();
+        value.extend_from_slice(data);
+        tx.execute(
+            "INSERT INTO blobs (namespace, key, value) VALUES (?1, ?2, ?3)
+             ON CONFLICT (namespace, key) DO UPDATE SET value = excluded.value",
+            params![namespace, key, value],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn list(&self, namespace: &str) -> Result<Vec<String>, StorageError> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare("SELECT key FROM blobs WHERE namespace = ?1 ORDER BY key")?;
+        let keys = stmt
+            .query_map(params![namespace], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        Ok(keys)
+    }
+
+    fn delete(&self, namespace: &str, key: &str) -> Result<bool, StorageError> {
+        validate_key(key)?;
+        let deleted = self.conn().execute(
+            "DELETE FROM blobs WHERE namespace = ?1 AND key = ?2",
+            params![namespace, key],
+        )?;
+        Ok(deleted > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sqlite_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state/state.db");
+        let storage = SqliteStorage::open(&path).unwrap();
+        assert_eq!(storage.path(), path);
+        super::super::tests::exercise(&storage);
+
+        // Blobs survive reopening the database.
+        drop(storage);
+        let storage = SqliteStorage::open(&path).unwrap();
+        assert_eq!(storage.list("ns").unwrap(), ["b.json", "log.jsonl"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_database_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.db");
+        SqliteStorage::open(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
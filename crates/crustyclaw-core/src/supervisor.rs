@@ -0,0 +1,312 @@
+//! Supervised daemon tasks — restart long-running subsystems that crash.
+//!
+//! The daemon runs the IPC server and the command router (which runs
+//! skills), and the CLI runs the Signal channel, under one [`Supervisor`].
+//! A task that returns an error, panics, or returns before shutdown is
+//! restarted after an exponential backoff: [`Backoff::initial`], doubling
+//! up to [`Backoff::max`]. A task that stayed up for at least
+//! [`Backoff::max`] before failing starts again from the initial delay.
+//!
+//! Each task's state and restart count are listed by `GET /supervisor`:
+//!
+//! | State | Meaning |
+//! |-------|---------|
+//! | `running` | the task is running |
+//! | `backoff` | the task failed and is waiting to restart |
+//! | `stopped` | the daemon shut down |
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::broadcast::{self, error::TryRecvError};
+use tokio::task::{JoinError, JoinHandle};
+use tracing::{error, info, warn};
+
+use crate::daemon::ShutdownSignal;
+
+/// How long a task has to stop after shutdown before it is aborted.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
+
+/// Restart delays for failed tasks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    /// Delay before the first restart.
+    pub initial: Duration,
+    /// Longest delay between restarts.
+    pub max: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(60),
+        }
+    }
+}
+
+impl Backoff {
+    /// The delay before restarting after `failures` consecutive failures.
+    pub fn delay(&self, failures: u32) -> Duration {
+        let factor = 2u32.saturating_pow(failures.saturating_sub(1));
+        self.initial.saturating_mul(factor).min(self.max)
+    }
+}
+
+/// State of a supervised task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskState {
+    Running,
+    Backoff,
+    Stopped,
+}
+
+impl TaskState {
+    /// The state as shown by `GET /supervisor`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Running => "running",
+            Self::Backoff => "backoff",
+            Self::Stopped => "stopped",
+        }
+    }
+}
+
+/// Status of one supervised task.
+#[derive(Debug, Clone)]
+pub struct TaskStatus {
+    /// Task name (e.g. `"ipc"`).
+    pub name: String,
+    /// Its state.
+    pub state: TaskState,
+    /// How many times it has been restarted.
+    pub restarts: u32,
+    /// Why it last failed.
+    pub last_error: Option<String>,
+    /// When it entered `state`.
+    pub since: Instant,
+}
+
+/// Runs tasks and restarts them when they fail, until shutdown.
+pub struct Supervisor {
+    tasks: Mutex<BTreeMap<String, TaskStatus>>,
+    shutdown_tx: broadcast::Sender<ShutdownSignal>,
+    backoff: Backoff,
+}
+
+impl Supervisor {
+    /// Create a supervisor that stops restarting tasks once `shutdown_tx`
+    /// fires.
+    pub fn new(shutdown_tx: broadcast::Sender<ShutdownSignal>) -> Self {
+        Self {
+            tasks: Mutex::new(BTreeMap::new()),
+            shutdown_tx,
+            backoff: Backoff::default(),
+        }
+    }
+
+    /// Use `backoff` for restart delays.
+    pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Run the task `name`, calling `start` for the first run and for each
+    /// restart.
+    ///
+    /// The task should stop by itself on shutdown; the supervisor waits up
+    /// to 10 seconds for it, aborts it if needed, and marks it stopped. The
+    /// returned handle completes once the task is stopped for good.
+    pub fn spawn<F, Fut>(self: &Arc<Self>, name: &str, mut start: F) -> JoinHandle<()>
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        let name = name.to_string();
+        let mut shutdown_rx = self.shutdown_tx.subscribe();
+        let supervisor = self.clone();
+        supervisor.update(&name, |_| {});
+        tokio::spawn(async move {
+            let mut failures = 0;
+            loop {
+                let started = Instant::now();
+                supervisor.update(&name, |task| {
+                    task.state = TaskState::Running;
+                    task.since = started;
+                });
+                let mut run = tokio::spawn(start());
+                let result = tokio::select! {
+                    result = &mut run => result,
+                    _ = shutdown_rx.recv() => {
+                        if tokio::time::timeout(SHUTDOWN_GRACE, &mut run).await.is_err() {
+                            warn!(task = %name, "Supervised task did not stop; aborting it");
+                            run.abort();
+                        }
+                        break;
+                    }
+                };
+                let reason = match result {
+                    Ok(Ok(())) if shutting_down(&mut shutdown_rx) => break,
+                    Ok(Ok(())) => "exited unexpectedly".to_string(),
+                    Ok(Err(e)) => e,
+                    Err(e) => join_error(e),
+                };
+
+                if started.elapsed() >= supervisor.backoff.max {
+                    failures = 0;
+                }
+                failures += 1;
+                let delay = supervisor.backoff.delay(failures);
+                error!(task = %name, error = %reason, retry_in = ?delay, "Supervised task failed");
+                supervisor.update(&name, |task| {
+                    task.state = TaskState::Backoff;
+                    task.last_error = Some(reason);
+                    task.since = Instant::now();
+                });
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    _ = shutdown_rx.recv() => break,
+                }
+                supervisor.update(&name, |task| task.restarts += 1);
+                info!(task = %name, "Restarting supervised task");
+            }
+            supervisor.update(&name, |task| {
+                task.state = TaskState::Stopped;
+                task.since = Instant::now();
+            });
+        })
+    }
+
+    /// Status of every task, sorted by name.
+    pub fn tasks(&self) -> Vec<TaskStatus> {
+        self.lock().values().cloned().collect()
+    }
+
+    /// Status of the task `name`.
+    pub fn get(&self, name: &str) -> Option<TaskStatus> {
+        self.lock().get(name).cloned()
+    }
+
+    fn update(&self, name: &str, f: impl FnOnce(&mut TaskStatus)) {
+        let mut tasks = self.lock();
+        let task = tasks.entry(name.to_string()).or_insert_with(|| TaskStatus {
+            name: name.to_string(),
+            state: TaskState::Running,
+            restarts: 0,
+            last_error: None,
+            since: Instant::now(),
+        });
+        f(task);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, TaskStatus>> {
+        self.tasks.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Whether shutdown has been signalled on `rx`.
+fn shutting_down(rx: &mut broadcast::Receiver<ShutdownSignal>) -> bool {
+    !matches!(rx.try_recv(), Err(TryRecvError::Empty))
+}
+
+fn join_error(e: JoinError) -> String {
+    if !e.is_panic() {
+        return e.to_string();
+    }
+    let panic = e.into_panic();
+    let message = panic
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic payload".to_string());
+    format!("panicked: {message}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn supervisor() -> (Arc<Supervisor>, broadcast::Sender<ShutdownSignal>) {
+        let (shutdown_tx, _) = broadcast::channel(1);
+        let supervisor = Supervisor::new(shutdown_tx.clone()).with_backoff(Backoff {
+            initial: Duration::from_millis(1),
+            max: Duration::from_millis(20),
+        });
+        (Arc::new(supervisor), shutdown_tx)
+    }
+
+    #[test]
+    fn test_backoff_delay() {
+        let backoff = Backoff::default();
+        assert_eq!(backoff.delay(1), Duration::from_secs(1));
+        assert_eq!(backoff.delay(2), Duration::from_secs(2));
+        assert_eq!(backoff.delay(4), Duration::from_secs(8));
+        assert_eq!(backoff.delay(7), Duration::from_secs(60));
+        assert_eq!(backoff.delay(100), Duration::from_secs(60));
+    }
+
+    #[tokio::test]
+    async fn test_restarts_failed_task() {
+        let (supervisor, shutdown_tx) = supervisor();
+        let runs = Arc::new(AtomicU32::new(0));
+        let handle = supervisor.spawn("flaky", {
+            let runs = runs.clone();
+            let shutdown_tx = shutdown_tx.clone();
+            move || {
+                let run = runs.fetch_add(1, Ordering::SeqCst);
+                let mut shutdown_rx = shutdown_tx.subscribe();
+                async move {
+                    match run {
+                        0 => Err("boom".to_string()),
+                        1 => panic!("kaboom"),
+                        _ => {
+                            let _ = shutdown_rx.recv().await;
+                            Ok(())
+                        }
+                    }
+                }
+            }
+        });
+
+        while runs.load(Ordering::SeqCst) < 3 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        let task = supervisor.get("flaky").unwrap();
+        assert_eq!(task.state, TaskState::Running);
+        assert_eq!(task.restarts, 2);
+        assert_eq!(task.last_error.as_deref(), Some("panicked: kaboom"));
+
+        shutdown_tx.send(ShutdownSignal).unwrap();
+        handle.await.unwrap();
+        let task = supervisor.get("flaky").unwrap();
+        assert_eq!(task.state, TaskState::Stopped);
+        assert_eq!(task.restarts, 2);
+    }
+
+    #[tokio::test]
+    async fn test_early_exit_is_a_failure() {
+        let (supervisor, shutdown_tx) = supervisor();
+        let runs = Arc::new(AtomicU32::new(0));
+        let handle = supervisor.spawn("quitter", {
+            let runs = runs.clone();
+            move || {
+                runs.fetch_add(1, Ordering::SeqCst);
+                async { Ok(()) }
+            }
+        });
+
+        while runs.load(Ordering::SeqCst) < 2 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        let task = supervisor.get("quitter").unwrap();
+        assert_eq!(task.last_error.as_deref(), Some("exited unexpectedly"));
+
+        shutdown_tx.send(ShutdownSignal).unwrap();
+        handle.await.unwrap();
+        assert_eq!(supervisor.tasks().len(), 1);
+        assert_eq!(supervisor.tasks()[0].state, TaskState::Stopped);
+    }
+}
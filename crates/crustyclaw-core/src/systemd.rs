@@ -0,0 +1,202 @@
+//! systemd integration — readiness notification, watchdog keepalives, and
+//! socket activation.
+//!
+//! Everything here is driven by the environment systemd sets up for the
+//! service and is a no-op outside systemd:
+//!
+//! | Variable | Used for |
+//! |----------|----------|
+//! | `NOTIFY_SOCKET` | [`notify`] sends `READY=1`, `STOPPING=1`, `WATCHDOG=1`, … (`Type=notify`) |
+//! | `WATCHDOG_USEC` / `WATCHDOG_PID` | [`watchdog_interval`] — how often to send `WATCHDOG=1` (`WatchdogSec=`) |
+//! | `LISTEN_FDS` / `LISTEN_PID` | [`activated_listener`] — the IPC socket passed by a `.socket` unit |
+//!
+//! The protocol is implemented directly over a Unix datagram socket rather
+//! than through `libsystemd`, so no native library is needed.
+
+use std::io;
+use std::os::unix::net::{UnixDatagram, UnixListener};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// First file descriptor passed by socket activation (`SD_LISTEN_FDS_START`).
+pub const LISTEN_FDS_START: i32 = 3;
+
+/// Set once fd [`LISTEN_FDS_START`] has been adopted, so it is never wrapped
+/// (and later closed) twice.
+static LISTENER_TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// Send `state` (newline-separated `KEY=value` assignments) to the service
+/// manager.
+///
+/// Returns `Ok(false)` without doing anything when `NOTIFY_SOCKET` is not
+/// set, i.e. when not running as a `Type=notify` service.
+pub fn notify(state: &str) -> io::Result<bool> {
+    let Some(socket) = std::env::var_os("NOTIFY_SOCKET") else {
+        return Ok(false);
+    };
+    let socket = socket.to_string_lossy();
+    match socket.strip_prefix('@') {
+        Some(name) => send_abstract(name, state)?,
+        None => send_path(Path::new(socket.as_ref()), state)?,
+    }
+    Ok(true)
+}
+
+fn send_path(path: &Path, state: &str) -> io::Result<()> {
+    UnixDatagram::unbound()?.send_to(state.as_bytes(), path)?;
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn send_abstract(name: &str, state: &str) -> io::Result<()> {
+    use std::os::linux::net::SocketAddrExt;
+
+    let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+    UnixDatagram::unbound()?.send_to_addr(state.as_bytes(), &addr)?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn send_abstract(name: &str, _state: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("abstract notification socket @{name} is only supported on Linux"),
+    ))
+}
+
+/// How often to send `WATCHDOG=1`, or `None` when the watchdog is off.
+///
+/// Keepalives go out at half of `WATCHDOG_USEC`, as `sd_watchdog_enabled(3)`
+/// recommends, so a single late tick does not get the daemon killed.
+pub fn watchdog_interval() -> Option<Duration> {
+    parse_watchdog(
+        std::env::var("WATCHDOG_USEC").ok().as_deref(),
+        std::env::var("WATCHDOG_PID").ok().as_deref(),
+        std::process::id(),
+    )
+}
+
+fn parse_watchdog(usec: Option<&str>, pid: Option<&str>, own_pid: u32) -> Option<Duration> {
+    if let Some(pid) = pid
+        && pid.trim().parse::<u32>().ok() != Some(own_pid)
+    {
+        return None;
+    }
+    let usec: u64 = usec?.trim().parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec / 2))
+}
+
+/// Number of sockets passed to this process by socket activation.
+pub fn listen_fds() -> usize {
+    parse_listen_fds(
+        std::env::var("LISTEN_PID").ok().as_deref(),
+        std::env::var("LISTEN_FDS").ok().as_deref(),
+        std::process::id(),
+    )
+}
+
+fn parse_listen_fds(pid: Option<&str>, fds: Option<&str>, own_pid: u32) -> usize {
+    // The variables are inherited by children; only the process systemd
+    // started may use the descriptors.
+    if pid.and_then(|p| p.trim().parse::<u32>().ok()) != Some(own_pid) {
+        return 0;
+    }
+    fds.and_then(|n| n.trim().parse().ok()).unwrap_or(0)
+}
+
+/// Adopt the Unix listener passed by socket activation, if any.
+///
+/// Only the first passed descriptor is used. Returns an error when it is not
+/// a Unix stream socket. The listener is switched to non-blocking mode so it
+/// can be handed to tokio.
+pub fn activated_listener() -> io::Result<Option<UnixListener>> {
+    let count = listen_fds();
+    if count == 0 || LISTENER_TAKEN.swap(true, Ordering::SeqCst) {
+        return Ok(None);
+    }
+    if count > 1 {
+        tracing::warn!(
+            count,
+            "Socket activation passed more than one socket; using the first"
+        );
+    }
+    let listener = adopt_fd(LISTEN_FDS_START);
+    // getsockname() fails for anything that is not an AF_UNIX socket.
+    listener.local_addr().map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("socket-activated fd {LISTEN_FDS_START} is not a Unix socket: {e}"),
+        )
+    })?;
+    listener.set_nonblocking(true)?;
+    Ok(Some(listener))
+}
+
+#[allow(unsafe_code)]
+fn adopt_fd(fd: i32) -> UnixListener {
+    use std::os::fd::FromRawFd;
+
+    // SAFETY: LISTEN_PID names this process, so systemd passed `fd` open to
+    // us and nothing else in the daemon opens or owns it; LISTENER_TAKEN
+    // guarantees it is wrapped at most once, so it is closed exactly once.
+    unsafe { UnixListener::from_raw_fd(fd) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_watchdog() {
+        assert_eq!(
+            parse_watchdog(Some("30000000"), None, 42),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(
+            parse_watchdog(Some("30000000"), Some("42"), 42),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(parse_watchdog(Some("30000000"), Some("7"), 42), None);
+        assert_eq!(parse_watchdog(Some("0"), None, 42), None);
+        assert_eq!(parse_watchdog(Some("soon"), None, 42), None);
+        assert_eq!(parse_watchdog(None, None, 42), None);
+    }
+
+    #[test]
+    fn test_parse_listen_fds() {
+        assert_eq!(parse_listen_fds(Some("42"), Some("1"), 42), 1);
+        assert_eq!(parse_listen_fds(Some("42"), Some("2"), 42), 2);
+        assert_eq!(parse_listen_fds(Some("7"), Some("1"), 42), 0);
+        assert_eq!(parse_listen_fds(None, Some("1"), 42), 0);
+        assert_eq!(parse_listen_fds(Some("42"), None, 42), 0);
+        assert_eq!(parse_listen_fds(Some("42"), Some("x"), 42), 0);
+    }
+
+    #[test]
+    fn test_send_path() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("notify.sock");
+        let server = UnixDatagram::bind(&path).unwrap();
+
+        send_path(&path, "READY=1\nSTATUS=Serving").unwrap();
+        let mut buf = [0u8; 64];
+        let n = server.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"READY=1\nSTATUS=Serving");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_send_abstract() {
+        use std::os::linux::net::SocketAddrExt;
+
+        let name = format!("crustyclaw-test-notify-{}", std::process::id());
+        let addr = std::os::unix::net::SocketAddr::from_abstract_name(&name).unwrap();
+        let server = UnixDatagram::bind_addr(&addr).unwrap();
+
+        send_abstract(&name, "WATCHDOG=1").unwrap();
+        let mut buf = [0u8; 64];
+        let n = server.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"WATCHDOG=1");
+    }
+}
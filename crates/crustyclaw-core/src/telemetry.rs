@@ -0,0 +1,420 @@
+//! Opt-in anonymous usage telemetry with local differential privacy.
+//!
+//! Off by default (`[telemetry] enabled = false`). When enabled, the daemon
+//! periodically POSTs a [`TelemetryReport`] of coarse counters to the
+//! configured endpoint. Noise is applied on the host, before anything is
+//! sent, so no single report reveals the true configuration:
+//!
+//! | Field | Mechanism |
+//! |-------|-----------|
+//! | `version` | Sent as-is (public build metadata) |
+//! | `features` | Randomized response per flag |
+//! | `backend` | k-ary randomized response over [`BACKENDS`] |
+//! | `skills_bucket`, `plugins_bucket` | Power-of-two bucket plus Laplace noise |
+//!
+//! The privacy budget `epsilon` is split evenly across the noised fields.
+//! Each report carries its per-field epsilon so aggregates can be debiased.
+
+use std::collections::BTreeMap;
+use std::hash::{BuildHasher, RandomState};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crustyclaw_config::AppConfig;
+
+use crate::daemon::ShutdownSignal;
+use crate::plugin::PluginRegistry;
+use crate::skill::SkillRegistry;
+
+/// Isolation backend names a report may carry.
+pub const BACKENDS: &[&str] = &[
+    "auto",
+    "docker",
+    "podman",
+    "firecracker",
+    "apple-vz",
+    "linux-ns",
+    "noop",
+];
+
+/// Highest count bucket; bucket `b > 0` covers `2^(b-1) ..< 2^b`.
+pub const MAX_COUNT_BUCKET: u8 = 8;
+
+/// Errors from sending a telemetry report.
+#[derive(Debug, thiserror::Error)]
+pub enum TelemetryError {
+    #[error("telemetry is disabled")]
+    Disabled,
+
+    #[error("network error: {0}")]
+    Network(String),
+
+    #[error("endpoint returned HTTP {0}")]
+    Status(u16),
+}
+
+/// The true, un-noised usage of this daemon. Never leaves the host.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UsageSnapshot {
+    /// Configured isolation backend.
+    pub backend: String,
+    /// Subsystem name → whether it is enabled.
+    pub features: BTreeMap<&'static str, bool>,
+    /// Number of registered skills.
+    pub skills: usize,
+    /// Number of registered plugins.
+    pub plugins: usize,
+}
+
+impl UsageSnapshot {
+    /// Summarize `config` and the registry sizes.
+    pub fn collect(config: &AppConfig, skills: usize, plugins: usize) -> Self {
+        let features = BTreeMap::from([
+            ("signal", config.signal.enabled),
+            ("policy_rules", !config.policy.rules.is_empty()),
+            ("secrets", !config.secrets.entries.is_empty()),
+            ("secret_rotation", config.secrets.rotation_interval_secs > 0),
+            ("token_auth", config.auth.mode == "token"),
+            ("environment", config.context.environment.enabled),
+            ("response_hooks", !config.response.hooks.is_empty()),
+        ]);
+        Self {
+            backend: config.isolation.backend.clone(),
+            features,
+            skills,
+            plugins,
+        }
+    }
+
+    /// Number of fields that consume privacy budget.
+    fn noised_fields(&self) -> usize {
+        self.features.len() + 3
+    }
+}
+
+/// A report as sent to the endpoint.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TelemetryReport {
+    /// Daemon version.
+    pub version: String,
+    /// Isolation backend, after randomized response.
+    pub backend: String,
+    /// Feature flags, after randomized response.
+    pub features: BTreeMap<String, bool>,
+    /// Noised skill count bucket (see [`MAX_COUNT_BUCKET`]).
+    pub skills_bucket: u8,
+    /// Noised plugin count bucket.
+    pub plugins_bucket: u8,
+    /// Epsilon spent on each noised field.
+    pub epsilon_per_field: f64,
+}
+
+/// Applies local differential-privacy noise to a [`UsageSnapshot`].
+pub struct Privatizer {
+    epsilon: f64,
+    rng: NoiseSource,
+}
+
+impl Privatizer {
+    /// Create a privatizer seeded from process entropy.
+    pub fn new(epsilon: f64) -> Self {
+        Self {
+            epsilon,
+            rng: NoiseSource::from_entropy(),
+        }
+    }
+
+    /// Create a privatizer with a fixed seed, for reproducible tests.
+    pub fn with_seed(epsilon: f64, seed: u64) -> Self {
+        Self {
+            epsilon,
+            rng: NoiseSource(seed),
+        }
+    }
+
+    /// Produce a noised report from `snapshot`.
+    pub fn privatize(&mut self, snapshot: &UsageSnapshot) -> TelemetryReport {
+        let eps = self.epsilon / snapshot.noised_fields() as f64;
+        let features = snapshot
+            .features
+            .iter()
+            .map(|(name, &on)| (name.to_string(), self.randomized_bool(on, eps)))
+            .collect();
+        TelemetryReport {
+            version: crate::build_info::VERSION.to_string(),
+            backend: self.randomized_choice(&snapshot.backend, BACKENDS, eps),
+            features,
+            skills_bucket: self.noised_bucket(snapshot.skills, eps),
+            plugins_bucket: self.noised_bucket(snapshot.plugins, eps),
+            epsilon_per_field: eps,
+        }
+    }
+
+    /// Report `value` truthfully with probability `e^ε / (1 + e^ε)`.
+    fn randomized_bool(&mut self, value: bool, eps: f64) -> bool {
+        let keep = eps.exp() / (1.0 + eps.exp());
+        if self.rng.next_f64() < keep {
+            value
+        } else {
+            !value
+        }
+    }
+
+    /// Report `value` with probability `e^ε / (e^ε + k - 1)`, otherwise one
+    /// of the other `k - 1` options uniformly.
+    fn randomized_choice(&mut self, value: &str, options: &[&str], eps: f64) -> String {
+        let others: Vec<&str> = options.iter().copied().filter(|o| *o != value).collect();
+        let k = others.len() as f64 + 1.0;
+        let keep = eps.exp() / (eps.exp() + k - 1.0);
+        if others.is_empty() || self.rng.next_f64() < keep {
+            return value.to_string();
+        }
+        let i = (self.rng.next_u64() % others.len() as u64) as usize;
+        others[i].to_string()
+    }
+
+    /// Bucket `count`, add Laplace(1/ε) noise, and clamp to the bucket range.
+    fn noised_bucket(&mut self, count: usize, eps: f64) -> u8 {
+        let noisy = count_bucket(count) as f64 + self.rng.laplace(1.0 / eps);
+        noisy.round().clamp(0.0, MAX_COUNT_BUCKET as f64) as u8
+    }
+}
+
+/// Map a count to its power-of-two bucket: 0, 1, 2–3, 4–7, ….
+pub fn count_bucket(count: usize) -> u8 {
+    let bucket = usize::BITS - count.leading_zeros();
+    (bucket as u8).min(MAX_COUNT_BUCKET)
+}
+
+/// POST `report` as JSON to `endpoint`.
+pub async fn send_report(
+    client: &reqwest::Client,
+    endpoint: &str,
+    report: &TelemetryReport,
+) -> Result<(), TelemetryError> {
+    let resp = client
+        .post(endpoint)
+        .json(report)
+        .send()
+        .await
+        .map_err(|e| TelemetryError::Network(e.to_string()))?;
+    if !resp.status().is_success() {
+        return Err(TelemetryError::Status(resp.status().as_u16()));
+    }
+    Ok(())
+}
+
+/// Build and send one report from the current config.
+///
+/// Returns [`TelemetryError::Disabled`] without sending when telemetry is off
+/// or has no endpoint.
+pub async fn report_once(
+    client: &reqwest::Client,
+    config: &AppConfig,
+    skills: &SkillRegistry,
+    plugins: &PluginRegistry,
+) -> Result<TelemetryReport, TelemetryError> {
+    let telemetry = &config.telemetry;
+    let Some(endpoint) = telemetry.endpoint.as_deref().filter(|_| telemetry.enabled) else {
+        return Err(TelemetryError::Disabled);
+    };
+    let snapshot = UsageSnapshot::collect(config, skills.list().len(), plugins.plugin_count());
+    let report = Privatizer::new(telemetry.epsilon).privatize(&snapshot);
+    send_report(client, endpoint, &report).await?;
+    Ok(report)
+}
+
+/// Spawn the periodic reporter if telemetry is enabled in the current config.
+///
+/// The first report is sent one interval after startup. `enabled`, the
+/// endpoint, and epsilon are re-read before each report, so disabling
+/// telemetry on reload stops it immediately; the interval is fixed at spawn.
+pub fn spawn(
+    config_rx: watch::Receiver<AppConfig>,
+    skills: Arc<SkillRegistry>,
+    plugins: Arc<PluginRegistry>,
+    mut shutdown_rx: broadcast::Receiver<ShutdownSignal>,
+) -> Option<JoinHandle<()>> {
+    let (enabled, interval_secs, endpoint) = {
+        let config = config_rx.borrow();
+        let telemetry = &config.telemetry;
+        (
+            telemetry.enabled,
+            telemetry.interval_secs,
+            telemetry.endpoint.clone().unwrap_or_default(),
+        )
+    };
+    if !enabled {
+        return None;
+    }
+    info!(endpoint = %endpoint, interval_secs, "Anonymous telemetry enabled");
+
+    Some(tokio::spawn(async move {
+        let client = reqwest::Client::new();
+        let period = Duration::from_secs(interval_secs.max(1));
+        let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.recv() => break,
+                _ = ticker.tick() => {
+                    let config = config_rx.borrow().clone();
+                    match report_once(&client, &config, &skills, &plugins).await {
+                        Ok(_) => debug!("Telemetry report sent"),
+                        Err(TelemetryError::Disabled) => debug!("Telemetry disabled, skipping report"),
+                        Err(e) => warn!(error = %e, "Telemetry report failed"),
+                    }
+                }
+            }
+        }
+    }))
+}
+
+/// SplitMix64 generator for noise sampling.
+struct NoiseSource(u64);
+
+impl NoiseSource {
+    /// Seed from the process's random hasher keys and the current time.
+    fn from_entropy() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        Self(RandomState::new().hash_one(nanos))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Laplace(0, `scale`), as the difference of two Exp(1) draws.
+    fn laplace(&mut self, scale: f64) -> f64 {
+        let a = -(1.0 - self.next_f64()).ln();
+        let b = -(1.0 - self.next_f64()).ln();
+        scale * (a - b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> UsageSnapshot {
+        let mut config = AppConfig::default();
+        config.signal.enabled = true;
+        config.isolation.backend = "docker".to_string();
+        UsageSnapshot::collect(&config, 5, 0)
+    }
+
+    #[test]
+    fn test_snapshot_reflects_config() {
+        let snapshot = snapshot();
+        assert_eq!(snapshot.backend, "docker");
+        assert!(snapshot.features["signal"]);
+        assert!(!snapshot.features["token_auth"]);
+        assert_eq!(snapshot.skills, 5);
+    }
+
+    #[test]
+    fn test_count_bucket() {
+        assert_eq!(count_bucket(0), 0);
+        assert_eq!(count_bucket(1), 1);
+        assert_eq!(count_bucket(3), 2);
+        assert_eq!(count_bucket(4), 3);
+        assert_eq!(count_bucket(usize::MAX), MAX_COUNT_BUCKET);
+    }
+
+    #[test]
+    fn test_report_carries_no_raw_counts() {
+        let report = Privatizer::with_seed(1.0, 7).privatize(&snapshot());
+        let json = serde_json::to_value(&report).unwrap();
+        let keys: Vec<&str> = json
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                "backend",
+                "epsilon_per_field",
+                "features",
+                "plugins_bucket",
+                "skills_bucket",
+                "version"
+            ]
+        );
+        assert!(BACKENDS.contains(&report.backend.as_str()));
+        assert!(report.skills_bucket <= MAX_COUNT_BUCKET);
+        assert!((report.epsilon_per_field - 0.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_noise_is_applied_but_debiasable() {
+        // With a small budget individual reports are unreliable, but the
+        // truthful rate converges to e^ε / (1 + e^ε).
+        let snapshot = snapshot();
+        let mut privatizer = Privatizer::with_seed(1.0, 42);
+        let runs = 20_000;
+        let truthful = (0..runs)
+            .filter(|_| privatizer.privatize(&snapshot).features["signal"])
+            .count() as f64
+            / runs as f64;
+        let eps: f64 = 0.1;
+        let expected = eps.exp() / (1.0 + eps.exp());
+        assert!(
+            (truthful - expected).abs() < 0.02,
+            "{truthful} vs {expected}"
+        );
+        assert!(truthful < 0.99);
+    }
+
+    #[test]
+    fn test_large_budget_is_nearly_exact() {
+        let report = Privatizer::with_seed(1000.0, 3).privatize(&snapshot());
+        assert_eq!(report.backend, "docker");
+        assert!(report.features["signal"]);
+        assert_eq!(report.skills_bucket, count_bucket(5));
+        assert_eq!(report.plugins_bucket, 0);
+    }
+
+    #[tokio::test]
+    async fn test_report_once_disabled_sends_nothing() {
+        let config = AppConfig::default();
+        let result = report_once(
+            &reqwest::Client::new(),
+            &config,
+            &SkillRegistry::new(),
+            &PluginRegistry::new(),
+        )
+        .await;
+        assert!(matches!(result, Err(TelemetryError::Disabled)));
+    }
+
+    #[test]
+    fn test_spawn_disabled_by_default() {
+        let (_tx, config_rx) = watch::channel(AppConfig::default());
+        let (shutdown_tx, _) = broadcast::channel(1);
+        let handle = spawn(
+            config_rx,
+            Arc::new(SkillRegistry::new()),
+            Arc::new(PluginRegistry::new()),
+            shutdown_tx.subscribe(),
+        );
+        assert!(handle.is_none());
+    }
+}
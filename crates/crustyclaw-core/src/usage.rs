@@ -0,0 +1,434 @@
+//! Token usage accounting — daily LLM token totals and cost estimates.
+//!
+//! The [`UsageLedger`] adds up the [`TokenUsage`] of every model request
+//! made by an agent run, per UTC day, model, and the [`UsageAttribution`] of
+//! the run: the role it is charged to, the skill that started it (if any),
+//! and its conversation. Retried attempts are counted alongside. Each day's totals are persisted as
+//! the `<YYYY-MM-DD>.json` key of the daemon's [`Storage`] after every
+//! request, and loaded again at startup.
+//!
+//! [`UsageLedger::report`] groups the totals of recent days by one
+//! [`UsageGroup`] and estimates their cost from `[llm.pricing]`. It backs
+//! `GET /usage` and `crustyclaw usage`.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crustyclaw_config::ModelPricing;
+
+use crate::context::environment::civil_from_days;
+use crate::llm::TokenUsage;
+use crate::storage::{self, FsStorage, Storage, StorageError};
+
+/// Errors from the usage ledger.
+#[derive(Debug, thiserror::Error)]
+pub enum UsageError {
+    #[error("usage ledger storage error: {0}")]
+    Storage(#[from] StorageError),
+
+    #[error("corrupt usage record {key}: {reason}")]
+    Corrupt { key: String, reason: String },
+}
+
+/// Who an agent run's tokens are attributed to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UsageAttribution {
+    /// Role the run is charged to.
+    pub role: String,
+    /// Skill that started the run, if any.
+    pub skill: Option<String>,
+    /// Conversation the run belongs to, if any.
+    pub conversation: Option<String>,
+}
+
+/// Token totals of one model and attribution on one UTC day.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageEntry {
+    /// UTC day, as `YYYY-MM-DD`.
+    pub day: String,
+    pub model: String,
+    pub role: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skill: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conversation: Option<String>,
+    /// Model requests made.
+    pub requests: u64,
+    /// Extra attempts those requests took: retries and fallbacks.
+    #[serde(default)]
+    pub retries: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+/// What [`UsageLedger::report`] groups totals by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsageGroup {
+    Model,
+    Role,
+    Skill,
+    Conversation,
+}
+
+impl UsageGroup {
+    /// The group's name, as accepted by `GET /usage?by=`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Model => "model",
+            Self::Role => "role",
+            Self::Skill => "skill",
+            Self::Conversation => "conversation",
+        }
+    }
+
+    /// Parse a group name.
+    pub fn parse(name: &str) -> Option<Self> {
+        [Self::Model, Self::Role, Self::Skill, Self::Conversation]
+            .into_iter()
+            .find(|group| group.name() == name)
+    }
+
+    /// The key of `entry` in this group; `-` when it has none.
+    fn key(self, entry: &UsageEntry) -> String {
+        let key = match self {
+            Self::Model => Some(&entry.model),
+            Self::Role => Some(&entry.role),
+            Self::Skill => entry.skill.as_ref(),
+            Self::Conversation => entry.conversation.as_ref(),
+        };
+        match key {
+            Some(key) if !key.is_empty() => key.clone(),
+            _ => "-".to_string(),
+        }
+    }
+}
+
+/// Totals of one group in a [`UsageLedger::report`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UsageRow {
+    /// The model, role, skill, or conversation; `-` for none.
+    pub key: String,
+    pub requests: u64,
+    pub retries: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Estimated cost in USD of the tokens of priced models.
+    pub cost_usd: f64,
+    /// Tokens of models without an `[llm.pricing]` entry, not in `cost_usd`.
+    pub unpriced_tokens: u64,
+}
+
+/// Records token usage and persists daily totals.
+pub struct UsageLedger {
+    storage: Option<Arc<dyn Storage>>,
+    /// Entries per day, oldest day first.
+    days: Mutex<BTreeMap<String, Vec<UsageEntry>>>,
+}
+
+impl UsageLedger {
+    /// Open the ledger persisted as files in `dir`, loading the days
+    /// recorded there.
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self, UsageError> {
+        let dir = dir.into();
+        Self::from_storage(Arc::new(
+            FsStorage::new(&dir).with_namespace(storage::USAGE, dir),
+        ))
+    }
+
+    /// Open the ledger persisted in the usage namespace of `storage`,
+    /// loading the days recorded there.
+    pub fn from_storage(storage: Arc<dyn Storage>) -> Result<Self, UsageError> {
+        let mut days = BTreeMap::new();
+        for key in storage.list(storage::USAGE)? {
+            let Some(day) = key.strip_suffix(".json") else {
+                continue;
+            };
+            let Some(contents) = storage.get(storage::USAGE, &key)? else {
+                continue;
+            };
+            let entries: Vec<UsageEntry> =
+                serde_json::from_slice(&contents).map_err(|e| UsageError::Corrupt {
+                    key: key.clone(),
+                    reason: e.to_string(),
+                })?;
+            days.insert(day.to_string(), entries);
+        }
+        Ok(Self {
+            storage: Some(storage),
+            days: Mutex::new(days),
+        })
+    }
+
+    /// A ledger that is never persisted.
+    pub fn in_memory() -> Self {
+        Self {
+            storage: None,
+            days: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Add one model request's `usage` to today's totals; it took
+    /// `attempts` tries to serve.
+    pub fn record(
+        &self,
+        attribution: &UsageAttribution,
+        model: &str,
+        usage: &TokenUsage,
+        attempts: u32,
+    ) {
+        self.record_at(attribution, model, usage, attempts, now_secs());
+    }
+
+    /// Totals of the last `days` UTC days, today included, grouped by
+    /// `group` and sorted by key.
+    pub fn report(
+        &self,
+        days: u32,
+        group: UsageGroup,
+        pricing: &BTreeMap<String, ModelPricing>,
+    ) -> Vec<UsageRow> {
+        self.report_at(days, group, pricing, now_secs())
+    }
+
+    /// The first day covered by a report of the last `days` days.
+    pub fn since(days: u32) -> String {
+        since_at(days, now_secs())
+    }
+
+    fn record_at(
+        &self,
+        attribution: &UsageAttribution,
+        model: &str,
+        usage: &TokenUsage,
+        attempts: u32,
+        now: u64,
+    ) {
+        let day = utc_day(now);
+        let model = if model.is_empty() { "unknown" } else { model };
+        // Hold the lock across the write so concurrent updates land in order.
+        let mut days = self.days.lock().unwrap_or_else(|e| e.into_inner());
+        let entries = days.entry(day.clone()).or_default();
+        let index = entries.iter().position(|e| {
+            e.model == model
+                && e.role == attribution.role
+                && e.skill == attribution.skill
+                && e.conversation == attribution.conversation
+        });
+        let entry = match index {
+            Some(index) => &mut entries[index],
+            None => {
+                entries.push(UsageEntry {
+                    day: day.clone(),
+                    model: model.to_string(),
+                    role: attribution.role.clone(),
+                    skill: attribution.skill.clone(),
+                    conversation: attribution.conversation.clone(),
+                    requests: 0,
+                    retries: 0,
+                    prompt_tokens: 0,
+                    completion_tokens: 0,
+                });
+                entries.last_mut().expect("just pushed")
+            }
+        };
+        entry.requests += 1;
+        entry.retries += u64::from(attempts.saturating_sub(1));
+        entry.prompt_tokens += u64::from(usage.prompt_tokens);
+        entry.completion_tokens += u64::from(usage.completion_tokens);
+
+        if let Some(storage) = &self.storage {
+            let key = format!("{day}.json");
+            if let Err(e) = storage.put(
+                storage::USAGE,
+                &key,
+                &serde_json::to_vec_pretty(entries).unwrap_or_default(),
+            ) {
+                tracing::warn!(key, error = %e, "Failed to persist token usage");
+            }
+        }
+    }
+
+    fn report_at(
+        &self,
+        days: u32,
+        group: UsageGroup,
+        pricing: &BTreeMap<String, ModelPricing>,
+        now: u64,
+    ) -> Vec<UsageRow> {
+        let since = since_at(days, now);
+        let mut rows: BTreeMap<String, UsageRow> = BTreeMap::new();
+        let ledger = self.days.lock().unwrap_or_else(|e| e.into_inner());
+        for entry in ledger.range(since..).flat_map(|(_, entries)| entries) {
+            let key = group.key(entry);
+            let row = rows.entry(key.clone()).or_insert_with(|| UsageRow {
+                key,
+                ..UsageRow::default()
+            });
+            row.requests += entry.requests;
+            row.retries += entry.retries;
+            row.prompt_tokens += entry.prompt_tokens;
+            row.completion_tokens += entry.completion_tokens;
+            match pricing.get(&entry.model) {
+                Some(price) => {
+                    row.cost_usd += price.cost(entry.prompt_tokens, entry.completion_tokens)
+                }
+                None => row.unpriced_tokens += entry.prompt_tokens + entry.completion_tokens,
+            }
+        }
+        rows.into_values().collect()
+    }
+}
+
+/// The UTC day of unix time `secs`, as `YYYY-MM-DD`.
+fn utc_day(secs: u64) -> String {
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+fn since_at(days: u32, now: u64) -> String {
+    let back = u64::from(days.max(1) - 1) * 86_400;
+    utc_day(now.saturating_sub(back))
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2026-03-01T12:00:00Z.
+    const NOW: u64 = 1_772_366_400;
+    const DAY: u64 = 86_400;
+
+    fn usage(prompt_tokens: u32, completion_tokens: u32) -> TokenUsage {
+        TokenUsage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+        }
+    }
+
+    fn attribution(role: &str, conversation: &str) -> UsageAttribution {
+        UsageAttribution {
+            role: role.to_string(),
+            skill: None,
+            conversation: Some(conversation.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_utc_day() {
+        assert_eq!(utc_day(NOW), "2026-03-01");
+        assert_eq!(utc_day(NOW - DAY), "2026-02-28");
+        assert_eq!(since_at(7, NOW), "2026-02-23");
+        assert_eq!(since_at(0, NOW), "2026-03-01");
+    }
+
+    #[test]
+    fn test_report_groups_and_prices() {
+        let ledger = UsageLedger::in_memory();
+        let admin = attribution("admin", "chat-a");
+        ledger.record_at(&admin, "sonnet", &usage(1_000_000, 100_000), 1, NOW);
+        ledger.record_at(&admin, "sonnet", &usage(1_000_000, 0), 3, NOW);
+        ledger.record_at(
+            &attribution("operator", "chat-b"),
+            "local",
+            &usage(500, 50),
+            1,
+            NOW,
+        );
+        // Outside a two-day report.
+        ledger.record_at(&admin, "sonnet", &usage(9, 9), 2, NOW - 2 * DAY);
+
+        let pricing = BTreeMap::from([(
+            "sonnet".to_string(),
+            ModelPricing {
+                prompt_per_mtok: 3.0,
+                completion_per_mtok: 15.0,
+            },
+        )]);
+
+        let by_model = ledger.report_at(2, UsageGroup::Model, &pricing, NOW);
+        assert_eq!(by_model.len(), 2);
+        assert_eq!(by_model[0].key, "local");
+        assert_eq!(by_model[0].cost_usd, 0.0);
+        assert_eq!(by_model[0].unpriced_tokens, 550);
+        assert_eq!(by_model[1].key, "sonnet");
+        assert_eq!(by_model[1].requests, 2);
+        assert_eq!(by_model[1].retries, 2);
+        assert_eq!(by_model[1].prompt_tokens, 2_000_000);
+        assert_eq!(by_model[1].cost_usd, 7.5);
+
+        let by_role = ledger.report_at(3, UsageGroup::Role, &pricing, NOW);
+        assert_eq!(by_role[0].key, "admin");
+        assert_eq!(by_role[0].requests, 3);
+        assert_eq!(by_role[0].retries, 3);
+
+        let by_skill = ledger.report_at(2, UsageGroup::Skill, &pricing, NOW);
+        assert_eq!(by_skill.len(), 1);
+        assert_eq!(by_skill[0].key, "-");
+        assert_eq!(by_skill[0].requests, 3);
+    }
+
+    #[test]
+    fn test_daily_totals_persist() {
+        let dir = tempfile::tempdir().unwrap();
+        let ledger = UsageLedger::open(dir.path().join(storage::USAGE)).unwrap();
+        ledger.record_at(
+            &attribution("admin", "chat-a"),
+            "gpt-4o",
+            &usage(10, 5),
+            2,
+            NOW,
+        );
+        ledger.record_at(
+            &attribution("admin", "chat-a"),
+            "",
+            &usage(1, 1),
+            1,
+            NOW - DAY,
+        );
+        assert!(
+            dir.path()
+                .join(storage::USAGE)
+                .join("2026-03-01.json")
+                .exists()
+        );
+        assert!(
+            dir.path()
+                .join(storage::USAGE)
+                .join("2026-02-28.json")
+                .exists()
+        );
+
+        let reopened = UsageLedger::open(dir.path().join(storage::USAGE)).unwrap();
+        let rows = reopened.report_at(2, UsageGroup::Model, &BTreeMap::new(), NOW);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].key, "gpt-4o");
+        assert_eq!(rows[0].prompt_tokens, 10);
+        assert_eq!(rows[0].retries, 1);
+        assert_eq!(rows[1].key, "unknown");
+    }
+
+    #[test]
+    fn test_group_names() {
+        for group in [
+            UsageGroup::Model,
+            UsageGroup::Role,
+            UsageGroup::Skill,
+            UsageGroup::Conversation,
+        ] {
+            assert_eq!(UsageGroup::parse(group.name()), Some(group));
+        }
+        assert_eq!(UsageGroup::parse("day"), None);
+    }
+}
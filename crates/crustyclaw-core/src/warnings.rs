@@ -0,0 +1,293 @@
+//! Startup warnings — non-fatal diagnostics surfaced to operators.
+//!
+//! Issues that should not stop the daemon but that an operator needs to
+//! know about (deprecated config keys, insecure settings, a preferred
+//! isolation backend that is unavailable) are accumulated in a
+//! [`WarningCollector`] instead of being logged once and forgotten. The
+//! collected warnings are exposed via `/status`, `crustyclaw status`, and
+//! the TUI banner.
+
+use std::fmt;
+use std::sync::Mutex;
+
+use crustyclaw_config::AppConfig;
+use crustyclaw_config::lint::LintHost;
+
+/// Category of a startup warning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WarningKind {
+    /// A config key that is deprecated and will be removed.
+    Deprecated,
+    /// A setting that weakens the daemon's security posture.
+    Insecure,
+    /// A configured component or backend that is unavailable on this host.
+    Unavailable,
+    /// Work lost to an unclean shutdown of the previous daemon.
+    Recovered,
+}
+
+impl fmt::Display for WarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WarningKind::Deprecated => write!(f, "deprecated"),
+            WarningKind::Insecure => write!(f, "insecure"),
+            WarningKind::Unavailable => write!(f, "unavailable"),
+            WarningKind::Recovered => write!(f, "recovered"),
+        }
+    }
+}
+
+/// A single non-fatal diagnostic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    /// Warning category.
+    pub kind: WarningKind,
+    /// Dotted config key (or component name) the warning refers to.
+    pub key: String,
+    /// Human-readable explanation, including how to resolve it.
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}: {}", self.kind, self.key, self.message)
+    }
+}
+
+/// A deprecated config key and its replacement.
+#[derive(Debug, Clone, Copy)]
+pub struct DeprecatedKey {
+    /// Dotted path of the deprecated key (e.g. `"daemon.socket"`).
+    pub key: &'static str,
+    /// Dotted path of the replacement key, if any.
+    pub replacement: Option<&'static str>,
+}
+
+/// Config keys that are still accepted but scheduled for removal.
+///
+/// Add an entry here when renaming or retiring a key, and keep accepting
+/// the old key (e.g. via `#[serde(alias)]`) for at least one release.
+pub const DEPRECATED_KEYS: &[DeprecatedKey] = &[];
+
+/// Thread-safe accumulator of startup warnings.
+///
+/// Duplicate warnings (same kind and key) are recorded once.
+#[derive(Default)]
+pub struct WarningCollector {
+    warnings: Mutex<Vec<Warning>>,
+}
+
+impl WarningCollector {
+    /// Create an empty collector.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a warning and log it.
+    pub fn push(&self, kind: WarningKind, key: impl Into<String>, message: impl Into<String>) {
+        let warning = Warning {
+            kind,
+            key: key.into(),
+            message: message.into(),
+        };
+        let mut warnings = self.warnings.lock().unwrap_or_else(|e| e.into_inner());
+        if warnings
+            .iter()
+            .any(|w| w.kind == warning.kind && w.key == warning.key)
+        {
+            return;
+        }
+        tracing::warn!(kind = %warning.kind, key = %warning.key, "{}", warning.message);
+        warnings.push(warning);
+    }
+
+    /// Snapshot of all recorded warnings, in insertion order.
+    pub fn list(&self) -> Vec<Warning> {
+        self.warnings
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Number of recorded warnings.
+    pub fn len(&self) -> usize {
+        self.warnings
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .len()
+    }
+
+    /// Whether no warnings have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Check raw config TOML for keys listed in `deprecated`.
+pub fn check_deprecated_keys(raw: &str, deprecated: &[DeprecatedKey], out: &WarningCollector) {
+    let Ok(value) = raw.parse::<toml::Table>() else {
+        return;
+    };
+
+    for entry in deprecated {
+        let mut parts = entry.key.split('.');
+        let Some(first) = parts.next() else {
+            continue;
+        };
+        let mut current = value.get(first);
+        for part in parts {
+            current = current.and_then(|v| v.get(part));
+        }
+        if current.is_some() {
+            let message = match entry.replacement {
+                Some(replacement) => format!("deprecated, use `{replacement}` instead"),
+                None => "deprecated and ignored; remove it from the config".to_string(),
+            };
+            out.push(WarningKind::Deprecated, entry.key, message);
+        }
+    }
+}
+
+/// Record the warnings of [`AppConfig::lint`] as insecure settings.
+pub fn check_lint(config: &AppConfig, out: &WarningCollector) {
+    for warning in config.lint() {
+        out.push(WarningKind::Insecure, warning.key, warning.message);
+    }
+}
+
+/// Record the warnings of [`AppConfig::lint_host`] for `host` as insecure
+/// settings.
+pub fn check_host_lint(config: &AppConfig, host: &LintHost, out: &WarningCollector) {
+    for warning in config.lint_host(host) {
+        out.push(WarningKind::Insecure, warning.key, warning.message);
+    }
+}
+
+/// Check whether an explicitly configured isolation backend is available.
+///
+/// `"auto"` is skipped — auto-detection picks whatever is available.
+pub fn check_backend_availability(config: &AppConfig, out: &WarningCollector) {
+    let pref = match config.isolation.backend.as_str() {
+        "docker" => crate::isolation::BackendPreference::Docker,
+        "podman" => crate::isolation::BackendPreference::Podman,
+        "firecracker" => crate::isolation::BackendPreference::Firecracker,
+        "apple-vz" => crate::isolation::BackendPreference::AppleVz,
+        "linux-ns" => crate::isolation::BackendPreference::LinuxNamespace,
+        _ => return,
+    };
+    let backend = crate::isolation::select_backend(&pref);
+    if !backend.available() {
+        out.push(
+            WarningKind::Unavailable,
+            "isolation.backend",
+            format!(
+                "preferred backend '{}' is not available on this host; isolated skills will fail",
+                backend.name()
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collector_dedups() {
+        let collector = WarningCollector::new();
+        assert!(collector.is_empty());
+
+        collector.push(WarningKind::Insecure, "llm.api_key", "first");
+        collector.push(WarningKind::Insecure, "llm.api_key", "second");
+        collector.push(WarningKind::Deprecated, "llm.api_key", "other kind");
+
+        let warnings = collector.list();
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0].message, "first");
+    }
+
+    #[test]
+    fn test_warning_display() {
+        let warning = Warning {
+            kind: WarningKind::Unavailable,
+            key: "isolation.backend".to_string(),
+            message: "missing".to_string(),
+        };
+        assert_eq!(
+            warning.to_string(),
+            "[unavailable] isolation.backend: missing"
+        );
+    }
+
+    #[test]
+    fn test_default_config_is_clean() {
+        let collector = WarningCollector::new();
+        check_lint(&AppConfig::default(), &collector);
+        check_host_lint(&AppConfig::default(), &LintHost::default(), &collector);
+        assert!(collector.is_empty());
+    }
+
+    #[test]
+    fn test_lint_warnings_are_insecure() {
+        let config = AppConfig::parse(
+            r#"
+[isolation]
+backend = "noop"
+
+[secrets]
+staging_dir = "/var/lib/crustyclaw/staging"
+"#,
+        )
+        .unwrap();
+
+        let collector = WarningCollector::new();
+        check_lint(&config, &collector);
+        let host = LintHost::default().with_mounts("/dev/sda1 / ext4 rw 0 0\n");
+        check_host_lint(&config, &host, &collector);
+        let warnings = collector.list();
+        let keys: Vec<&str> = warnings.iter().map(|w| w.key.as_str()).collect();
+        assert_eq!(keys, ["isolation.backend", "secrets.staging_dir"]);
+        assert!(warnings.iter().all(|w| w.kind == WarningKind::Insecure));
+    }
+
+    #[test]
+    fn test_deprecated_keys() {
+        const TABLE: &[DeprecatedKey] = &[
+            DeprecatedKey {
+                key: "daemon.socket",
+                replacement: Some("daemon.socket_path"),
+            },
+            DeprecatedKey {
+                key: "signal.phone",
+                replacement: None,
+            },
+        ];
+        let raw = "[daemon]\nsocket = \"/tmp/x.sock\"\n";
+
+        let collector = WarningCollector::new();
+        check_deprecated_keys(raw, TABLE, &collector);
+        let warnings = collector.list();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].kind, WarningKind::Deprecated);
+        assert_eq!(warnings[0].key, "daemon.socket");
+        assert!(warnings[0].message.contains("daemon.socket_path"));
+    }
+
+    #[test]
+    fn test_deprecated_keys_ignores_invalid_toml() {
+        let collector = WarningCollector::new();
+        check_deprecated_keys("not [valid", DEPRECATED_KEYS, &collector);
+        assert!(collector.is_empty());
+    }
+
+    #[test]
+    fn test_backend_availability_skips_auto_and_noop() {
+        let collector = WarningCollector::new();
+        check_backend_availability(&AppConfig::default(), &collector);
+
+        let mut config = AppConfig::default();
+        config.isolation.backend = "noop".to_string();
+        check_backend_availability(&config, &collector);
+        assert!(collector.is_empty());
+    }
+}
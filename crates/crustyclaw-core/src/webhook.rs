@@ -0,0 +1,520 @@
+//! Generic HTTP webhook channel.
+//!
+//! An integration posts a JSON message to `POST /channels/webhook` on the
+//! IPC API:
+//!
+//! ```json
+//! {"sender": "ticket-42", "text": "/deploy status"}
+//! ```
+//!
+//! with the headers `X-CrustyClaw-Timestamp: <unix seconds>` and
+//! `X-CrustyClaw-Signature: sha256=<hex>`, the HMAC-SHA256 of
+//! `<timestamp>.<raw body>` under the `[[secrets.entries]]` value named by
+//! `webhook.secret`. Messages timestamped more than [`REPLAY_WINDOW`] away
+//! from the daemon's clock, or whose signature was already accepted, are
+//! rejected as replays. Verified messages are published on the bus as
+//! inbound envelopes on the `webhook` channel, with `sender` as the peer.
+//!
+//! Outbound envelopes on the `webhook` channel (replies to those messages)
+//! run through the [`ResponsePipeline`], and each part is `POST`ed to
+//! `webhook.url` as `{"id": …, "recipient": …, "text": …}`, signed the same
+//! way. The secret and URL are read on every request, so rotated secrets and
+//! reloaded config take effect immediately.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crustyclaw_config::AppConfig;
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::daemon::ShutdownSignal;
+use crate::message::{Direction, Envelope};
+use crate::response::ResponsePipeline;
+use crate::secrets::SecretStore;
+
+/// Channel name of webhook envelopes.
+pub const CHANNEL: &str = "webhook";
+
+/// Header carrying the body signature, on requests in both directions.
+pub const SIGNATURE_HEADER: &str = "X-CrustyClaw-Signature";
+
+/// Header carrying the Unix time a request was signed at, in seconds.
+pub const TIMESTAMP_HEADER: &str = "X-CrustyClaw-Timestamp";
+
+/// How far a message's timestamp may be from the daemon's clock, either
+/// way. Signatures accepted within the window are remembered until it has
+/// passed, so each delivery is accepted once.
+pub const REPLAY_WINDOW: Duration = Duration::from_secs(300);
+
+/// Errors from the webhook channel.
+#[derive(Debug, thiserror::Error)]
+pub enum WebhookError {
+    #[error("the webhook channel is disabled")]
+    Disabled,
+
+    #[error("webhook secret {0:?} is not in the secret store")]
+    NoSecret(String),
+
+    #[error("missing or invalid {SIGNATURE_HEADER} header")]
+    BadSignature,
+
+    #[error("missing, invalid, or stale {TIMESTAMP_HEADER} header")]
+    Stale,
+
+    #[error("webhook message already received")]
+    Replayed,
+
+    #[error("malformed webhook message: {0}")]
+    Malformed(String),
+
+    #[error("webhook delivery failed: {0}")]
+    Delivery(String),
+}
+
+/// A message posted to the channel.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InboundMessage {
+    /// Who sent it, as the integration identifies them; replies are
+    /// addressed to the same sender.
+    pub sender: String,
+    /// Message text.
+    pub text: String,
+}
+
+/// A reply posted to `webhook.url`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboundMessage {
+    /// Envelope ID of the reply; a split reply repeats it for each part.
+    pub id: u64,
+    /// The sender the reply is addressed to.
+    pub recipient: Option<String>,
+    /// One part of the reply text.
+    pub text: String,
+}
+
+/// Verifies inbound messages and delivers replies.
+pub struct WebhookChannel {
+    config: watch::Receiver<AppConfig>,
+    secrets: Arc<RwLock<SecretStore>>,
+    bus: broadcast::Sender<Envelope>,
+    responses: Arc<ResponsePipeline>,
+    client: reqwest::Client,
+    /// Signatures accepted within the replay window, with their timestamps.
+    seen: Mutex<HashMap<String, u64>>,
+}
+
+impl WebhookChannel {
+    /// Create the channel over the daemon's config, secrets, bus, and
+    /// response pipeline.
+    pub fn new(
+        config: watch::Receiver<AppConfig>,
+        secrets: Arc<RwLock<SecretStore>>,
+        bus: broadcast::Sender<Envelope>,
+        responses: Arc<ResponsePipeline>,
+    ) -> Self {
+        Self {
+            config,
+            secrets,
+            bus,
+            responses,
+            client: reqwest::Client::new(),
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Verify `signature` over `timestamp` and `body`, then publish the
+    /// message on the bus.
+    ///
+    /// Returns the ID of the published envelope.
+    pub fn receive(
+        &self,
+        timestamp: Option<&str>,
+        signature: Option<&str>,
+        body: &[u8],
+    ) -> Result<u64, WebhookError> {
+        self.receive_at(unix_now(), timestamp, signature, body)
+    }
+
+    /// [`receive`](Self::receive) with the daemon's clock at `now`.
+    fn receive_at(
+        &self,
+        now: u64,
+        timestamp: Option<&str>,
+        signature: Option<&str>,
+        body: &[u8],
+    ) -> Result<u64, WebhookError> {
+        if !self.config.borrow().webhook.enabled {
+            return Err(WebhookError::Disabled);
+        }
+        let window = REPLAY_WINDOW.as_secs();
+        let timestamp = timestamp
+            .and_then(|t| t.trim().parse::<u64>().ok())
+            .filter(|t| t.abs_diff(now) <= window)
+            .ok_or(WebhookError::Stale)?;
+        let key = self.key()?;
+        let signature = signature.unwrap_or_default().trim();
+        if !verify(&key, timestamp, body, signature) {
+            return Err(WebhookError::BadSignature);
+        }
+        {
+            let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+            seen.retain(|_, t| t.abs_diff(now) <= window);
+            if seen
+                .insert(signature.to_ascii_lowercase(), timestamp)
+                .is_some()
+            {
+                return Err(WebhookError::Replayed);
+            }
+        }
+        let message: InboundMessage =
+            serde_json::from_slice(body).map_err(|e| WebhookError::Malformed(e.to_string()))?;
+        if message.sender.trim().is_empty() {
+            return Err(WebhookError::Malformed("sender is empty".to_string()));
+        }
+        if message.text.trim().is_empty() {
+            return Err(WebhookError::Malformed("text is empty".to_string()));
+        }
+
+        let envelope = Envelope::new(CHANNEL, &message.text).with_peer(&message.sender);
+        let id = envelope.id;
+        debug!(sender = %message.sender, id, "Webhook message received");
+        let _ = self.bus.send(envelope);
+        Ok(id)
+    }
+
+    /// Run `envelope` through the response pipeline and `POST` each part to
+    /// `webhook.url`.
+    ///
+    /// Does nothing when no URL is configured or the pipeline drops the
+    /// whole message.
+    pub async fn deliver(&self, envelope: &Envelope) -> Result<(), WebhookError> {
+        let (url, timeout) = {
+            let config = self.config.borrow();
+            (
+                config.webhook.url.clone(),
+                Duration::from_secs(config.webhook.timeout_secs),
+            )
+        };
+        let Some(url) = url else {
+            debug!(id = envelope.id, "No webhook.url set; dropping reply");
+            return Ok(());
+        };
+        let parts = self
+            .responses
+            .process(CHANNEL, envelope.peer.as_deref(), &envelope.body)
+            .map_err(|e| WebhookError::Delivery(e.to_string()))?;
+        let key = self.key()?;
+
+        for text in parts {
+            let message = OutboundMessage {
+                id: envelope.id,
+                recipient: envelope.peer.clone(),
+                text,
+            };
+            let body = serde_json::to_vec(&message).unwrap_or_default();
+            let timestamp = unix_now();
+            let resp = self
+                .client
+                .post(&url)
+                .timeout(timeout)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(TIMESTAMP_HEADER, timestamp.to_string())
+                .header(SIGNATURE_HEADER, sign(&key, timestamp, &body))
+                .body(body)
+                .send()
+                .await
+                .map_err(|e| WebhookError::Delivery(e.to_string()))?;
+            if !resp.status().is_success() {
+                return Err(WebhookError::Delivery(format!(
+                    "{url} returned {}",
+                    resp.status()
+                )));
+            }
+        }
+        info!(id = envelope.id, "Webhook reply delivered");
+        Ok(())
+    }
+
+    /// The HMAC key named by `webhook.secret`.
+    fn key(&self) -> Result<hmac::Key, WebhookError> {
+        let name = self
+            .config
+            .borrow()
+            .webhook
+            .secret
+            .clone()
+            .unwrap_or_default();
+        let store = self.secrets.read().unwrap_or_else(|e| e.into_inner());
+        let entry = store
+            .get(&name)
+            .ok_or_else(|| WebhookError::NoSecret(name.clone()))?;
+        Ok(hmac::Key::new(
+            hmac::HMAC_SHA256,
+            entry.value.expose().as_bytes(),
+        ))
+    }
+}
+
+/// The `sha256=<hex>` signature of `body` sent at `timestamp` under `key`.
+pub fn sign(key: &hmac::Key, timestamp: u64, body: &[u8]) -> String {
+    let tag = hmac::sign(key, &signed_message(timestamp, body));
+    let hex: String = tag.as_ref().iter().map(|b| format!("{b:02x}")).collect();
+    format!("sha256={hex}")
+}
+
+/// Whether `signature` is the `sha256=<hex>` signature of `body` sent at
+/// `timestamp` under `key`, compared in constant time.
+pub fn verify(key: &hmac::Key, timestamp: u64, body: &[u8], signature: &str) -> bool {
+    let Some(hex) = signature.trim().strip_prefix("sha256=") else {
+        return false;
+    };
+    if !hex.len().is_multiple_of(2) {
+        return false;
+    }
+    let tag: Option<Vec<u8>> = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect();
+    tag.is_some_and(|tag| hmac::verify(key, &signed_message(timestamp, body), &tag).is_ok())
+}
+
+/// What a signature covers: `<timestamp>.<body>`.
+fn signed_message(timestamp: u64, body: &[u8]) -> Vec<u8> {
+    let mut message = format!("{timestamp}.").into_bytes();
+    message.extend_from_slice(body);
+    message
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Deliver outbound `webhook` envelopes from the bus until shutdown.
+///
+/// Failed deliveries are logged and not retried.
+pub fn spawn(
+    channel: Arc<WebhookChannel>,
+    mut shutdown_rx: broadcast::Receiver<ShutdownSignal>,
+) -> JoinHandle<()> {
+    let mut outbound = channel.bus.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let envelope = tokio::select! {
+                _ = shutdown_rx.recv() => break,
+                msg = outbound.recv() => match msg {
+                    Ok(envelope) => envelope,
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!(skipped = n, "Webhook channel fell behind the message bus");
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            };
+            if envelope.channel != CHANNEL
+                || envelope.direction != Direction::Outbound
+                || envelope.is_redaction()
+            {
+                continue;
+            }
+            if let Err(e) = channel.deliver(&envelope).await {
+                warn!(id = envelope.id, error = %e, "Webhook reply not delivered");
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secrets::{InjectionMethod, SecretEntry, SecretSource, SecretValue};
+
+    fn channel(url: Option<String>) -> (Arc<WebhookChannel>, broadcast::Receiver<Envelope>) {
+        let mut config = AppConfig::default();
+        config.webhook.enabled = true;
+        config.webhook.secret = Some("webhook_key".to_string());
+        config.webhook.url = url;
+        config.response.hooks = Vec::new();
+        let mut store = SecretStore::new();
+        store
+            .insert(
+                SecretEntry {
+                    name: "webhook_key".to_string(),
+                    value: SecretValue::new("s3cret-key"),
+                    injection: InjectionMethod::Env("WEBHOOK_KEY".to_string()),
+                    description: String::new(),
+                },
+                SecretSource::Config,
+            )
+            .unwrap();
+        let secrets = Arc::new(RwLock::new(store));
+        let responses = Arc::new(ResponsePipeline::from_config(
+            &config.response,
+            secrets.clone(),
+        ));
+        let (bus, bus_rx) = broadcast::channel(16);
+        let (_, config_rx) = watch::channel(config);
+        let channel = WebhookChannel::new(config_rx, secrets, bus, responses);
+        (Arc::new(channel), bus_rx)
+    }
+
+    fn key() -> hmac::Key {
+        hmac::Key::new(hmac::HMAC_SHA256, b"s3cret-key")
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let signature = sign(&key(), 1000, b"body");
+        assert!(signature.starts_with("sha256="));
+        assert!(verify(&key(), 1000, b"body", &signature));
+        assert!(!verify(&key(), 1000, b"other", &signature));
+        assert!(!verify(&key(), 1001, b"body", &signature));
+        let other = hmac::Key::new(hmac::HMAC_SHA256, b"other-key");
+        assert!(!verify(&other, 1000, b"body", &signature));
+        for bad in ["", "sha256=", "sha256=zz", "md5=00", &signature[7..]] {
+            assert!(!verify(&key(), 1000, b"body", bad), "{bad}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_receive_publishes_verified_messages() {
+        let (channel, mut bus_rx) = channel(None);
+        let body = br#"{"sender": "ticket-42", "text": "hello"}"#;
+        let now = unix_now();
+        let ts = now.to_string();
+        let ts = Some(ts.as_str());
+        let sign = |body: &[u8]| sign(&key(), now, body);
+
+        let id = channel.receive(ts, Some(&sign(body)), body).unwrap();
+        let envelope = bus_rx.recv().await.unwrap();
+        assert_eq!(envelope.id, id);
+        assert_eq!(envelope.channel, "webhook");
+        assert_eq!(envelope.body, "hello");
+        assert_eq!(envelope.direction, Direction::Inbound);
+        assert_eq!(envelope.peer.as_deref(), Some("ticket-42"));
+
+        assert!(matches!(
+            channel.receive(ts, None, body),
+            Err(WebhookError::BadSignature)
+        ));
+        let forged = br#"{"sender": "ticket-42", "text": "rm -rf"}"#;
+        assert!(matches!(
+            channel.receive(ts, Some(&sign(body)), forged),
+            Err(WebhookError::BadSignature)
+        ));
+        let empty = br#"{"sender": "ticket-42", "text": " "}"#;
+        assert!(matches!(
+            channel.receive(ts, Some(&sign(empty)), empty),
+            Err(WebhookError::Malformed(_))
+        ));
+        assert!(bus_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_receive_rejects_replays() {
+        let (channel, mut bus_rx) = channel(None);
+        let body = br#"{"sender": "ticket-42", "text": "/deploy"}"#;
+        let sent = 1_700_000_000;
+        let ts = sent.to_string();
+        let ts = Some(ts.as_str());
+        let signature = sign(&key(), sent, body);
+        let receive = |now, ts: Option<&str>, signature: &str| {
+            channel.receive_at(now, ts, Some(signature), body)
+        };
+
+        receive(sent + 1, ts, &signature).unwrap();
+        bus_rx.recv().await.unwrap();
+
+        // The same delivery again, within the window.
+        let replayed = receive(sent + 2, ts, &signature);
+        assert!(matches!(replayed, Err(WebhookError::Replayed)));
+        let upper = signature.to_uppercase().replace("SHA256=", "sha256=");
+        assert!(matches!(
+            receive(sent + 2, ts, &upper),
+            Err(WebhookError::Replayed)
+        ));
+        // After the window, its timestamp gives it away.
+        let window = REPLAY_WINDOW.as_secs();
+        let late = receive(sent + window + 1, ts, &signature);
+        assert!(matches!(late, Err(WebhookError::Stale)));
+        // Re-timestamping it breaks the signature.
+        let fresh = (sent + window + 1).to_string();
+        let retimed = receive(sent + window + 1, Some(&fresh), &signature);
+        assert!(matches!(retimed, Err(WebhookError::BadSignature)));
+        // Too far ahead, or no timestamp at all.
+        let early = receive(sent - window - 1, ts, &signature);
+        assert!(matches!(early, Err(WebhookError::Stale)));
+        assert!(matches!(
+            receive(sent, None, &signature),
+            Err(WebhookError::Stale)
+        ));
+        assert!(bus_rx.try_recv().is_err());
+
+        // Remembered signatures are dropped once the window has passed.
+        let later = sent + 2 * window;
+        let body2 = br#"{"sender": "ticket-42", "text": "again"}"#;
+        let ts2 = later.to_string();
+        channel
+            .receive_at(later, Some(&ts2), Some(&sign(&key(), later, body2)), body2)
+            .unwrap();
+        assert_eq!(channel.seen.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_deliver_posts_signed_reply() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            loop {
+                let n = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request);
+                if let Some((head, body)) = text.split_once("\r\n\r\n")
+                    && head
+                        .lines()
+                        .find_map(|l| l.strip_prefix("content-length: "))
+                        .and_then(|len| len.parse::<usize>().ok())
+                        .is_some_and(|len| body.len() >= len)
+                {
+                    break;
+                }
+            }
+            stream
+                .write_all(b"HTTP/1.1 204 No Content\r\ncontent-length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let (channel, _bus_rx) = channel(Some(url));
+        let reply = Envelope::new(CHANNEL, "hi")
+            .with_peer("ticket-42")
+            .reply("on it");
+        channel.deliver(&reply).await.unwrap();
+
+        let request = server.await.unwrap();
+        let (head, body) = request.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("POST /hook "));
+        let header = |name| head.lines().find_map(|l| l.strip_prefix(name)).unwrap();
+        let timestamp: u64 = header("x-crustyclaw-timestamp: ").parse().unwrap();
+        assert!(timestamp.abs_diff(unix_now()) <= 5);
+        let signature = header("x-crustyclaw-signature: ");
+        assert!(verify(&key(), timestamp, body.as_bytes(), signature));
+        let message: OutboundMessage = serde_json::from_str(body).unwrap();
+        assert_eq!(message.id, reply.id);
+        assert_eq!(message.recipient.as_deref(), Some("ticket-42"));
+        assert_eq!(message.text, "on it");
+    }
+}
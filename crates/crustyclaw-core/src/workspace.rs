@@ -0,0 +1,533 @@
+//! Conversation workspaces — file transfer between operators and the agent.
+//!
+//! Each conversation gets a directory under `[files] workspace_root`. Files
+//! arrive from `crustyclaw files put` or as channel attachments, and the
+//! agent can leave files there for operators to fetch with
+//! `crustyclaw files get`.
+//!
+//! [`WorkspaceStore`] is the single place where transfers are checked, so
+//! every entry point enforces the same rules:
+//!
+//! - conversation IDs and file names are limited to `[A-Za-z0-9._-]`, may
+//!   not start with `.`, and never name a path outside the workspace
+//! - files larger than `max_file_bytes` are rejected
+//! - the content type (declared, else guessed from the extension) must be in
+//!   `allowed_content_types`, and must agree with the file's magic bytes
+
+use std::path::{Path, PathBuf};
+
+use crustyclaw_config::FilesConfig;
+
+/// Longest accepted conversation ID or file name.
+const MAX_NAME_LEN: usize = 128;
+
+/// Content type used when nothing better is known.
+const OCTET_STREAM: &str = "application/octet-stream";
+
+/// Errors from workspace file operations.
+#[derive(Debug, thiserror::Error)]
+pub enum WorkspaceError {
+    #[error("invalid {kind} {name:?}: use only letters, digits, '.', '_' and '-'")]
+    InvalidName { kind: &'static str, name: String },
+
+    #[error("file is {size} bytes, larger than the {limit}-byte limit")]
+    TooLarge { size: u64, limit: u64 },
+
+    #[error("content type {0:?} is not allowed")]
+    ContentTypeNotAllowed(String),
+
+    #[error("content does not match type {declared:?} (looks like {detected:?})")]
+    ContentMismatch { declared: String, detected: String },
+
+    #[error("file not found: {conversation}/{name}")]
+    NotFound { conversation: String, name: String },
+
+    #[error("workspace I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Metadata for a file in a conversation workspace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkspaceFile {
+    /// File name within the workspace.
+    pub name: String,
+    /// Size in bytes.
+    pub size: u64,
+    /// Content type. Listings guess it from the extension alone; `put` and
+    /// `get` also check the magic bytes.
+    pub content_type: String,
+}
+
+/// Per-conversation file storage with centrally enforced limits.
+#[derive(Debug, Clone)]
+pub struct WorkspaceStore {
+    root: PathBuf,
+    max_file_bytes: u64,
+    allowed_content_types: Vec<String>,
+}
+
+impl WorkspaceStore {
+    /// Create a store rooted at `root` with the default limits.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        let defaults = FilesConfig::default();
+        Self {
+            root: root.into(),
+            max_file_bytes: defaults.max_file_bytes,
+            allowed_content_types: defaults.allowed_content_types,
+        }
+    }
+
+    /// Create a store from the `[files]` config section.
+    pub fn from_config(config: &FilesConfig) -> Self {
+        Self::new(&config.workspace_root)
+            .with_max_file_bytes(config.max_file_bytes)
+            .with_allowed_content_types(config.allowed_content_types.clone())
+    }
+
+    /// Set the largest file accepted or served.
+    pub fn with_max_file_bytes(mut self, limit: u64) -> Self {
+        self.max_file_bytes = limit;
+        self
+    }
+
+    /// Set the allowed content types (`"type/*"` matches any subtype).
+    pub fn with_allowed_content_types(mut self, types: Vec<String>) -> Self {
+        self.allowed_content_types = types;
+        self
+    }
+
+    /// Directory holding all workspaces.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Largest file accepted or served, in bytes.
+    pub fn max_file_bytes(&self) -> u64 {
+        self.max_file_bytes
+    }
+
+    /// Directory of a conversation's workspace.
+    pub fn workspace_dir(&self, conversation: &str) -> Result<PathBuf, WorkspaceError> {
+        validate_name("conversation ID", conversation)?;
+        Ok(self.root.join(conversation))
+    }
+
+    /// Store `data` as `name` in a conversation's workspace, replacing any
+    /// existing file of that name.
+    ///
+    /// `content_type` is the type declared by the sender, if any. Returns
+    /// the stored file's metadata.
+    pub fn put(
+        &self,
+        conversation: &str,
+        name: &str,
+        content_type: Option<&str>,
+        data: &[u8],
+    ) -> Result<WorkspaceFile, WorkspaceError> {
+        let dir = self.workspace_dir(conversation)?;
+        validate_name("file name", name)?;
+        self.check_size(data.len() as u64)?;
+        let content_type = self.check_content(name, content_type, data)?;
+
+        std::fs::create_dir_all(&dir)?;
+        // Write to a temporary name first so readers never see a partial file.
+        let tmp = dir.join(format!(".{name}.partial"));
+        std::fs::write(&tmp, data)?;
+        std::fs::rename(&tmp, dir.join(name))?;
+
+        tracing::info!(conversation, name, size = data.len(), %content_type, "Workspace file stored");
+        Ok(WorkspaceFile {
+            name: name.to_string(),
+            size: data.len() as u64,
+            content_type,
+        })
+    }
+
+    /// Copy a file from the host (e.g. a channel attachment) into a
+    /// conversation's workspace, subject to the same checks as [`put`](Self::put).
+    pub fn import(
+        &self,
+        conversation: &str,
+        name: &str,
+        content_type: Option<&str>,
+        source: &Path,
+    ) -> Result<WorkspaceFile, WorkspaceError> {
+        self.check_size(std::fs::metadata(source)?.len())?;
+        let data = std::fs::read(source)?;
+        self.put(conversation, name, content_type, &data)
+    }
+
+    /// Read a file from a conversation's workspace.
+    pub fn get(
+        &self,
+        conversation: &str,
+        name: &str,
+    ) -> Result<(WorkspaceFile, Vec<u8>), WorkspaceError> {
+        let dir = self.workspace_dir(conversation)?;
+        validate_name("file name", name)?;
+        let path = dir.join(name);
+
+        let metadata = match std::fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.is_file() => metadata,
+            Ok(_) => return Err(not_found(conversation, name)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(not_found(conversation, name));
+            }
+            Err(e) => return Err(e.into()),
+        };
+        self.check_size(metadata.len())?;
+
+        let data = std::fs::read(&path)?;
+        let content_type = self.check_content(name, None, &data)?;
+        Ok((
+            WorkspaceFile {
+                name: name.to_string(),
+                size: data.len() as u64,
+                content_type,
+            },
+            data,
+        ))
+    }
+
+    /// List the regular files in a conversation's workspace, sorted by name.
+    ///
+    /// A conversation with no workspace yet has no files.
+    pub fn list(&self, conversation: &str) -> Result<Vec<WorkspaceFile>, WorkspaceError> {
+        let dir = self.workspace_dir(conversation)?;
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut files = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let metadata = entry.metadata()?;
+            if !metadata.file_type().is_file() || validate_name("file name", &name).is_err() {
+                continue;
+            }
+            files.push(WorkspaceFile {
+                content_type: guess_from_extension(&name)
+                    .unwrap_or(OCTET_STREAM)
+                    .to_string(),
+                name,
+                size: metadata.len(),
+            });
+        }
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(files)
+    }
+
+    fn check_size(&self, size: u64) -> Result<(), WorkspaceError> {
+        if size > self.max_file_bytes {
+            return Err(WorkspaceError::TooLarge {
+                size,
+                limit: self.max_file_bytes,
+            });
+        }
+        Ok(())
+    }
+
+    /// Resolve the content type of `data` and check it is allowed.
+    ///
+    /// The declared type wins unless it is missing or generic, in which case
+    /// the extension is used. Either way it must agree with the magic bytes.
+    fn check_content(
+        &self,
+        name: &str,
+        declared: Option<&str>,
+        data: &[u8],
+    ) -> Result<String, WorkspaceError> {
+        let declared = declared
+            .map(normalize_content_type)
+            .filter(|t| !t.is_empty() && t != OCTET_STREAM);
+        let content_type = declared
+            .or_else(|| guess_from_extension(name).map(str::to_string))
+            .or_else(|| sniff(data).map(str::to_string))
+            .unwrap_or_else(|| OCTET_STREAM.to_string());
+
+        let detected = match sniff(data) {
+            Some(detected) => Some(detected),
+            None if content_type.starts_with("text/") && std::str::from_utf8(data).is_err() => {
+                Some(OCTET_STREAM)
+            }
+            None => None,
+        };
+        if let Some(detected) = detected
+            && detected != content_type
+        {
+            return Err(WorkspaceError::ContentMismatch {
+                declared: content_type,
+                detected: detected.to_string(),
+            });
+        }
+
+        if !self
+            .allowed_content_types
+            .iter()
+            .any(|allowed| content_type_matches(allowed, &content_type))
+        {
+            return Err(WorkspaceError::ContentTypeNotAllowed(content_type));
+        }
+        Ok(content_type)
+    }
+}
+
+fn not_found(conversation: &str, name: &str) -> WorkspaceError {
+    WorkspaceError::NotFound {
+        conversation: conversation.to_string(),
+        name: name.to_string(),
+    }
+}
+
+/// Check a conversation ID or file name is a single safe path component.
+fn validate_name(kind: &'static str, name: &str) -> Result<(), WorkspaceError> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    if valid {
+        Ok(())
+    } else {
+        Err(WorkspaceError::InvalidName {
+            kind,
+            name: name.to_string(),
+        })
+    }
+}
+
+/// Turn an arbitrary string (a sender number, an attachment's original file
+/// name) into a valid conversation ID or file name.
+///
+/// Disallowed characters become `_`, leading dots are dropped, and the result
+/// is truncated to the maximum name length.
+pub fn sanitize_name(raw: &str) -> String {
+    let name: String = raw
+        .trim_start_matches('.')
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(MAX_NAME_LEN)
+        .collect();
+    if name.is_empty() {
+        "file".to_string()
+    } else {
+        name
+    }
+}
+
+/// Lowercase a content type and strip parameters (`; charset=...`).
+fn normalize_content_type(content_type: &str) -> String {
+    let base = content_type.split(';').next().unwrap_or("").trim();
+    match base.to_ascii_lowercase().as_str() {
+        "image/jpg" => "image/jpeg".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Whether `content_type` matches an allow-list entry (`"type/*"` or exact).
+fn content_type_matches(allowed: &str, content_type: &str) -> bool {
+    match allowed.strip_suffix("/*") {
+        Some(prefix) => content_type
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.starts_with('/')),
+        None => allowed.eq_ignore_ascii_case(content_type),
+    }
+}
+
+/// Guess a content type from a file name's extension.
+pub fn guess_from_extension(name: &str) -> Option<&'static str> {
+    let ext = Path::new(name).extension()?.to_str()?.to_ascii_lowercase();
+    Some(match ext.as_str() {
+        "txt" | "log" => "text/plain",
+        "md" => "text/markdown",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "json" => "application/json",
+        "pdf" => "application/pdf",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        _ => return None,
+    })
+}
+
+/// Identify a few formats by their magic bytes.
+///
+/// Only formats that are unambiguous are recognised; executables are
+/// included so they cannot be smuggled in under an allowed type.
+pub fn sniff(data: &[u8]) -> Option<&'static str> {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"%PDF-", "application/pdf"),
+        (b"\x1f\x8b", "application/gzip"),
+        (b"\x7fELF", "application/x-executable"),
+        (b"\xcf\xfa\xed\xfe", "application/x-mach-binary"),
+    ];
+    if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        return Some("image/webp");
+    }
+    SIGNATURES
+        .iter()
+        .find(|(magic, _)| data.starts_with(magic))
+        .map(|(_, content_type)| *content_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    fn store() -> (tempfile::TempDir, WorkspaceStore) {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let store = WorkspaceStore::new(tmp.path());
+        (tmp, store)
+    }
+
+    #[test]
+    fn test_put_get_list() {
+        let (_tmp, store) = store();
+        let file = store.put("conv-1", "notes.txt", None, b"hello").unwrap();
+        assert_eq!(file.content_type, "text/plain");
+        assert_eq!(file.size, 5);
+        store
+            .put("conv-1", "chart.png", Some("image/png"), PNG)
+            .unwrap();
+
+        let (info, data) = store.get("conv-1", "notes.txt").unwrap();
+        assert_eq!(data, b"hello");
+        assert_eq!(info.content_type, "text/plain");
+
+        let names: Vec<String> = store
+            .list("conv-1")
+            .unwrap()
+            .into_iter()
+            .map(|f| f.name)
+            .collect();
+        assert_eq!(names, vec!["chart.png", "notes.txt"]);
+        assert!(store.list("conv-2").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rejects_unsafe_names() {
+        let (_tmp, store) = store();
+        for name in ["../escape", "a/b", ".env", "", "sp ace"] {
+            assert!(
+                matches!(
+                    store.put("conv", name, None, b"x"),
+                    Err(WorkspaceError::InvalidName { .. })
+                ),
+                "{name:?} accepted"
+            );
+        }
+        assert!(matches!(
+            store.put("..", "a.txt", None, b"x"),
+            Err(WorkspaceError::InvalidName { .. })
+        ));
+        assert!(matches!(
+            store.get("conv", "../../etc/passwd"),
+            Err(WorkspaceError::InvalidName { .. })
+        ));
+    }
+
+    #[test]
+    fn test_size_limit() {
+        let (_tmp, store) = store();
+        let store = store.with_max_file_bytes(4);
+        assert!(matches!(
+            store.put("conv", "big.txt", None, b"12345"),
+            Err(WorkspaceError::TooLarge { size: 5, limit: 4 })
+        ));
+        assert!(store.put("conv", "ok.txt", None, b"1234").is_ok());
+    }
+
+    #[test]
+    fn test_content_type_checks() {
+        let (_tmp, store) = store();
+        let store = store.with_allowed_content_types(vec!["text/*".into(), "image/png".into()]);
+
+        // Not in the allow list.
+        assert!(matches!(
+            store.put("conv", "doc.pdf", None, b"%PDF-1.7"),
+            Err(WorkspaceError::ContentTypeNotAllowed(t)) if t == "application/pdf"
+        ));
+        // An executable disguised by its name.
+        assert!(matches!(
+            store.put("conv", "photo.png", None, b"\x7fELF\x02\x01"),
+            Err(WorkspaceError::ContentMismatch { .. })
+        ));
+        // Binary data declared as text.
+        assert!(matches!(
+            store.put(
+                "conv",
+                "data.txt",
+                Some("text/plain; charset=utf-8"),
+                b"\xff\xfe\0"
+            ),
+            Err(WorkspaceError::ContentMismatch { .. })
+        ));
+        // Unknown extension falls back to sniffing.
+        let file = store.put("conv", "upload", None, PNG).unwrap();
+        assert_eq!(file.content_type, "image/png");
+        // Unknown and unrecognisable content is octet-stream, which is not allowed.
+        assert!(matches!(
+            store.put("conv", "blob", None, b"\0\x01\x02"),
+            Err(WorkspaceError::ContentTypeNotAllowed(_))
+        ));
+    }
+
+    #[test]
+    fn test_get_missing() {
+        let (_tmp, store) = store();
+        assert!(matches!(
+            store.get("conv", "nope.txt"),
+            Err(WorkspaceError::NotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_import() {
+        let (tmp, store) = store();
+        let source = tmp.path().join("attachment-1");
+        std::fs::write(&source, PNG).unwrap();
+        let file = store
+            .import("signal-15550001", "image.png", Some("image/png"), &source)
+            .unwrap();
+        assert_eq!(file.size, PNG.len() as u64);
+        assert_eq!(store.get("signal-15550001", "image.png").unwrap().1, PNG);
+    }
+
+    #[test]
+    fn test_sanitize_name() {
+        assert_eq!(sanitize_name("+1 555 0001"), "_1_555_0001");
+        assert_eq!(sanitize_name("../../etc/passwd"), "_.._etc_passwd");
+        assert_eq!(sanitize_name(".env"), "env");
+        assert_eq!(sanitize_name("résumé.pdf"), "r_sum_.pdf");
+        assert_eq!(sanitize_name(""), "file");
+        assert!(validate_name("file name", &sanitize_name(&"x".repeat(500))).is_ok());
+    }
+
+    #[test]
+    fn test_content_type_matches() {
+        assert!(content_type_matches("text/*", "text/plain"));
+        assert!(!content_type_matches("text/*", "textual/plain"));
+        assert!(content_type_matches("image/png", "image/png"));
+        assert!(!content_type_matches("image/png", "image/jpeg"));
+    }
+}
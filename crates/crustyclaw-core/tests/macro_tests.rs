@@ -62,6 +62,82 @@ fn test_redact_no_redacted_fields() {
     assert!(debug.contains("42"));
 }
 
+#[derive(Redact)]
+struct Masked {
+    #[redact(partial = 4)]
+    pub api_key: String,
+    #[redact(partial = 4)]
+    pub pin: String,
+    #[redact(hash)]
+    pub session: String,
+    #[redact(with = "mask_email")]
+    pub email: String,
+}
+
+fn mask_email(email: &str) -> String {
+    match email.split_once('@') {
+        Some((_, domain)) => format!("***@{domain}"),
+        None => "***".to_string(),
+    }
+}
+
+#[test]
+fn test_redact_partial_hash_with() {
+    let masked = Masked {
+        api_key: "sk-live-abcdef".to_string(),
+        pin: "1234".to_string(),
+        session: "sess-42".to_string(),
+        email: "alice@example.com".to_string(),
+    };
+    let debug = format!("{masked:?}");
+    assert!(debug.contains(r#"api_key: "****cdef""#), "{debug}");
+    assert!(!debug.contains("sk-live"));
+    assert!(debug.contains(r#"pin: "[REDACTED]""#), "{debug}");
+    assert!(debug.contains(r#"email: "***@example.com""#), "{debug}");
+    assert!(!debug.contains("sess-42"));
+
+    // Equal values hash identically so log lines can be correlated.
+    let other = Masked {
+        session: "sess-42".to_string(),
+        ..masked
+    };
+    let hash = |d: &str| d.split("sha256:").nth(1).unwrap()[..12].to_string();
+    assert_eq!(hash(&debug), hash(&format!("{other:?}")));
+    let different = Masked {
+        session: "sess-43".to_string(),
+        ..other
+    };
+    assert_ne!(hash(&debug), hash(&format!("{different:?}")));
+}
+
+#[derive(Redact)]
+enum Auth {
+    Token(#[redact] String),
+    Basic {
+        user: String,
+        #[redact(partial = 2)]
+        password: String,
+    },
+    Anonymous,
+}
+
+#[test]
+fn test_redact_enum_variants() {
+    let token = format!("{:?}", Auth::Token("tok_abc".to_string()));
+    assert_eq!(token, r#"Token("[REDACTED]")"#);
+
+    let basic = format!(
+        "{:?}",
+        Auth::Basic {
+            user: "bob".to_string(),
+            password: "hunter2".to_string(),
+        }
+    );
+    assert_eq!(basic, r#"Basic { user: "bob", password: "****r2" }"#);
+
+    assert_eq!(format!("{:?}", Auth::Anonymous), "Anonymous");
+}
+
 // ── Validate tests ────────────────────────────────────────────────
 
 #[derive(Validate)]
@@ -149,6 +225,82 @@ fn test_validate_multiple_errors() {
     assert!(errors.len() >= 3, "should have multiple errors: {errors:?}");
 }
 
+#[derive(Validate)]
+struct AgentSpec {
+    #[validate(regex = "^[a-z][a-z0-9-]*$")]
+    pub name: String,
+    #[validate(custom = "check_not_root")]
+    pub workdir: String,
+    #[validate(nested)]
+    pub server: ServerConfig,
+}
+
+fn check_not_root(path: &String) -> Result<(), String> {
+    if path == "/" {
+        Err("must not be the filesystem root".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+fn valid_server() -> ServerConfig {
+    ServerConfig {
+        host: "localhost".to_string(),
+        port: 8080,
+        api_key: "abcdefgh".to_string(),
+        description: "desc".to_string(),
+    }
+}
+
+#[test]
+fn test_validate_regex_custom_nested_ok() {
+    let spec = AgentSpec {
+        name: "build-bot".to_string(),
+        workdir: "/srv/agent".to_string(),
+        server: valid_server(),
+    };
+    assert!(spec.validate().is_ok());
+}
+
+#[test]
+fn test_validate_regex_mismatch() {
+    let spec = AgentSpec {
+        name: "Build Bot".to_string(),
+        workdir: "/srv/agent".to_string(),
+        server: valid_server(),
+    };
+    let errors = spec.validate().unwrap_err();
+    assert_eq!(errors, vec!["name: must match /^[a-z][a-z0-9-]*$/"]);
+}
+
+#[test]
+fn test_validate_custom_fn() {
+    let spec = AgentSpec {
+        name: "bot".to_string(),
+        workdir: "/".to_string(),
+        server: valid_server(),
+    };
+    let errors = spec.validate().unwrap_err();
+    assert_eq!(errors, vec!["workdir: must not be the filesystem root"]);
+}
+
+#[test]
+fn test_validate_nested_prefixes_errors() {
+    let spec = AgentSpec {
+        name: "bot".to_string(),
+        workdir: "/srv/agent".to_string(),
+        server: ServerConfig {
+            host: "".to_string(),
+            port: 0,
+            ..valid_server()
+        },
+    };
+    let errors = spec.validate().unwrap_err();
+    assert_eq!(errors.len(), 2, "{errors:?}");
+    assert!(errors.contains(&"server.host: must not be empty".to_string()));
+    assert!(errors.iter().any(|e| e.starts_with("server.port: ")));
+}
+
 // ── SecureZeroize tests ───────────────────────────────────────────
 
 #[derive(SecureZeroize)]
@@ -245,6 +397,120 @@ fn test_action_plugin_defaults() {
     assert_eq!(MinimalPlugin::plugin_version(), "0.1.0");
 }
 
+#[derive(Debug, PartialEq, Default)]
+enum Channel {
+    #[default]
+    Stable,
+    Nightly,
+}
+
+impl std::str::FromStr for Channel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stable" => Ok(Channel::Stable),
+            "nightly" => Ok(Channel::Nightly),
+            other => Err(format!("unknown channel `{other}`")),
+        }
+    }
+}
+
+#[derive(ActionPlugin)]
+struct BuildAction {
+    #[action_input(required)]
+    pub target: String,
+    #[action_input(default = "false")]
+    pub release: bool,
+    #[action_input(default = "2")]
+    pub jobs: u32,
+    pub channel: Channel,
+    pub timeout: Option<u64>,
+    #[action_output]
+    pub artifact: String,
+    #[action_output]
+    pub log: String,
+    #[action_output]
+    pub digest: Option<String>,
+}
+
+fn lookup(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+    let vars: Vec<(String, String)> = vars
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    move |key| vars.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone())
+}
+
+#[test]
+fn test_action_plugin_typed_inputs() {
+    let action = BuildAction::try_from_lookup(lookup(&[
+        ("INPUT_TARGET", "x86_64"),
+        ("INPUT_RELEASE", " true "),
+        ("INPUT_CHANNEL", "nightly"),
+        ("INPUT_TIMEOUT", ""),
+    ]))
+    .unwrap();
+    assert_eq!(action.target, "x86_64");
+    assert!(action.release);
+    assert_eq!(action.jobs, 2);
+    assert_eq!(action.channel, Channel::Nightly);
+    assert_eq!(action.timeout, None, "blank input counts as unset");
+    assert_eq!(action.artifact, "");
+
+    assert_eq!(
+        BuildAction::input_names(),
+        &["target", "release", "jobs", "channel", "timeout"]
+    );
+    assert_eq!(BuildAction::output_names(), &["artifact", "log", "digest"]);
+}
+
+#[test]
+fn test_action_plugin_input_errors() {
+    let err = BuildAction::try_from_lookup(lookup(&[])).err().unwrap();
+    assert_eq!(err, "required input INPUT_TARGET not set");
+
+    let err = BuildAction::try_from_lookup(lookup(&[
+        ("INPUT_TARGET", "x86_64"),
+        ("INPUT_JOBS", "many"),
+    ]))
+    .err()
+    .unwrap();
+    assert!(
+        err.starts_with("input jobs: invalid value \"many\""),
+        "{err}"
+    );
+
+    let err = BuildAction::try_from_lookup(lookup(&[
+        ("INPUT_TARGET", "x86_64"),
+        ("INPUT_CHANNEL", "beta"),
+    ]))
+    .err()
+    .unwrap();
+    assert!(err.ends_with("unknown channel `beta`"), "{err}");
+}
+
+#[test]
+fn test_action_plugin_write_outputs() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("output");
+    let mut action = BuildAction::try_from_lookup(lookup(&[("INPUT_TARGET", "x86_64")])).unwrap();
+    action.artifact = "target/app".to_string();
+    action.log = "line one\nline two".to_string();
+    action.write_outputs_to(&path).unwrap();
+
+    let written = std::fs::read_to_string(&path).unwrap();
+    let mut lines = written.lines();
+    assert_eq!(lines.next(), Some("artifact=target/app"));
+    let header = lines.next().unwrap();
+    let delimiter = header.strip_prefix("log<<").unwrap();
+    assert_eq!(lines.next(), Some("line one"));
+    assert_eq!(lines.next(), Some("line two"));
+    assert_eq!(lines.next(), Some(delimiter));
+    // `None` outputs are skipped.
+    assert_eq!(lines.next(), None);
+}
+
 // ── action_hook tests ───────────────────────────────────────────
 
 #[action_hook(event = "on_message", priority = 10)]
@@ -296,3 +562,40 @@ fn test_security_policy_macro_empty() {
     // No rules = no match = not allowed
     assert!(!engine.is_allowed("admin", "read", "anything"));
 }
+
+#[test]
+fn test_security_policy_macro_groups_and_conditions() {
+    use crustyclaw_config::policy::RequestContext;
+
+    let mut engine = crustyclaw_macros::security_policy! {
+        group ops = [alice, oncall];
+        group deployers = [@ops, release];
+        allow @deployers deploy * when "time.hour >= 9 && time.hour < 17";
+        allow admin read "skills/git-*";
+    };
+
+    assert!(engine.is_allowed("admin", "read", "skills/git-log"));
+    assert!(!engine.is_allowed("admin", "read", "skills/shell"));
+
+    let office = RequestContext::new().with("time.hour", 10.0);
+    let night = RequestContext::new().with("time.hour", 23.0);
+    for role in ["alice", "oncall", "release"] {
+        assert!(engine.is_allowed_with_context(role, "deploy", "prod", &office));
+        assert!(!engine.is_allowed_with_context(role, "deploy", "prod", &night));
+    }
+    assert!(!engine.is_allowed_with_context("bob", "deploy", "prod", &office));
+}
+
+#[test]
+fn test_security_policy_macro_include() {
+    let mut engine = crustyclaw_macros::security_policy! {
+        include "tests/policies/base.policy";
+        allow @ops write config [priority = 10];
+    };
+
+    // Rules and the `ops` group both come from the included files.
+    assert!(engine.is_allowed("alice", "read", "logs"));
+    assert!(engine.is_allowed("oncall", "write", "config"));
+    assert!(!engine.is_allowed("alice", "write", "secrets"));
+    assert!(!engine.is_allowed("bob", "read", "logs"));
+}
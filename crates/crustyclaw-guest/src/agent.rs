@@ -0,0 +1,540 @@
+//! Guest side of the protocol: serves host requests inside the sandbox VM.
+
+use std::io::{Read, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use tracing::{debug, info, warn};
+
+use crate::protocol::{
+    ExecRequest, ExecResult, GuestStats, MAX_FRAME_BYTES, PROTOCOL_VERSION, ProtocolError, Request,
+    Response, read_frame, read_message, write_frame, write_message,
+};
+
+/// Default cap on captured stdout and stderr, each.
+pub const DEFAULT_MAX_OUTPUT_BYTES: usize = 8 * 1024 * 1024;
+
+/// How often a running command is polled for exit.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How a host connection ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The host closed the connection (or never completed the handshake).
+    Closed,
+    /// The host asked the guest to power off.
+    Shutdown,
+}
+
+/// The guest agent: runs commands, moves files and reports stats for the host.
+#[derive(Debug, Clone)]
+pub struct Agent {
+    max_output_bytes: usize,
+    poweroff: Vec<String>,
+}
+
+impl Default for Agent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Agent {
+    /// Create an agent with default limits that powers off with
+    /// `/sbin/poweroff -f`.
+    pub fn new() -> Self {
+        Self {
+            max_output_bytes: DEFAULT_MAX_OUTPUT_BYTES,
+            poweroff: vec!["/sbin/poweroff".to_string(), "-f".to_string()],
+        }
+    }
+
+    /// Cap captured stdout and stderr at `bytes` each; the rest is discarded.
+    pub fn with_max_output_bytes(mut self, bytes: usize) -> Self {
+        self.max_output_bytes = bytes;
+        self
+    }
+
+    /// Run `argv` to power the guest off after a shutdown request.
+    pub fn with_poweroff_command(mut self, argv: Vec<String>) -> Self {
+        self.poweroff = argv;
+        self
+    }
+
+    /// The command run to power the guest off.
+    pub fn poweroff_command(&self) -> &[String] {
+        &self.poweroff
+    }
+
+    /// Serve one host connection until it closes or asks for a shutdown.
+    ///
+    /// Failed requests are answered with [`Response::Error`] and the session
+    /// continues; only transport errors end it early.
+    pub fn serve<S: Read + Write>(&self, stream: &mut S) -> Result<Outcome, ProtocolError> {
+        match read_message::<Request>(stream)? {
+            Some(Request::Hello { version }) if version == PROTOCOL_VERSION => {
+                write_message(
+                    stream,
+                    &Response::Hello {
+                        version: PROTOCOL_VERSION,
+                        agent: format!("crustyclaw-guest-agent {}", env!("CARGO_PKG_VERSION")),
+                    },
+                )?;
+            }
+            Some(Request::Hello { version }) => {
+                warn!(version, "Host speaks an unsupported protocol version");
+                write_message(
+                    stream,
+                    &error(format!(
+                        "unsupported protocol version {version} (agent speaks {PROTOCOL_VERSION})"
+                    )),
+                )?;
+                return Ok(Outcome::Closed);
+            }
+            Some(_) => {
+                write_message(stream, &error("expected hello".to_string()))?;
+                return Ok(Outcome::Closed);
+            }
+            None => return Ok(Outcome::Closed),
+        }
+
+        while let Some(request) = read_message::<Request>(stream)? {
+            match request {
+                Request::Hello { .. } => {
+                    write_message(stream, &error("session already open".to_string()))?
+                }
+                Request::Exec(exec) => {
+                    let response = match self.exec(&exec) {
+                        Ok(result) => Response::Exited(result),
+                        Err(e) => error(e),
+                    };
+                    write_message(stream, &response)?;
+                }
+                Request::PushFile { path, mode } => {
+                    let data = read_frame(stream)?.ok_or_else(|| {
+                        std::io::Error::new(
+                            std::io::ErrorKind::UnexpectedEof,
+                            "connection closed before file contents",
+                        )
+                    })?;
+                    let response = match push_file(Path::new(&path), mode, &data) {
+                        Ok(()) => Response::Done,
+                        Err(e) => error(format!("cannot write {path}: {e}")),
+                    };
+                    write_message(stream, &response)?;
+                }
+                Request::PullFile { path } => match pull_file(Path::new(&path)) {
+                    Ok(data) => {
+                        write_message(
+                            stream,
+                            &Response::File {
+                                size: data.len() as u64,
+                            },
+                        )?;
+                        write_frame(stream, &data)?;
+                    }
+                    Err(e) => write_message(stream, &error(format!("cannot read {path}: {e}")))?,
+                },
+                Request::Stats => {
+                    let response = match read_stats() {
+                        Ok(stats) => Response::Stats(stats),
+                        Err(e) => error(format!("cannot read stats: {e}")),
+                    };
+                    write_message(stream, &response)?;
+                }
+                Request::Shutdown => {
+                    info!("Shutdown requested by host");
+                    write_message(stream, &Response::Done)?;
+                    return Ok(Outcome::Shutdown);
+                }
+            }
+        }
+        Ok(Outcome::Closed)
+    }
+
+    /// Run a command to completion (or its timeout), capturing its output.
+    pub fn exec(&self, request: &ExecRequest) -> Result<ExecResult, String> {
+        let Some((program, args)) = request.argv.split_first() else {
+            return Err("command must not be empty".to_string());
+        };
+        let mut command = Command::new(program);
+        command
+            .args(args)
+            .envs(&request.env)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            // Its own process group, so a timeout kills everything it started.
+            .process_group(0);
+        if let Some(workdir) = &request.workdir {
+            command.current_dir(workdir);
+        }
+
+        debug!(argv = ?request.argv, "Running command");
+        let start = Instant::now();
+        let mut child = command
+            .spawn()
+            .map_err(|e| format!("failed to start {program}: {e}"))?;
+        let stdout = capture(child.stdout.take(), self.max_output_bytes);
+        let stderr = capture(child.stderr.take(), self.max_output_bytes);
+
+        let deadline = request
+            .timeout_ms
+            .map(|ms| start + Duration::from_millis(ms));
+        let (status, timed_out) = wait(&mut child, deadline)?;
+
+        Ok(ExecResult {
+            exit_code: status.and_then(|s| s.code()).unwrap_or(-1),
+            stdout: String::from_utf8_lossy(&stdout.join().unwrap_or_default()).into_owned(),
+            stderr: String::from_utf8_lossy(&stderr.join().unwrap_or_default()).into_owned(),
+            elapsed_ms: start.elapsed().as_millis() as u64,
+            timed_out,
+        })
+    }
+}
+
+fn error(message: String) -> Response {
+    Response::Error { message }
+}
+
+/// Read a child's pipe on a background thread, keeping the first `limit`
+/// bytes and draining the rest so the child never blocks on a full pipe.
+fn capture(
+    pipe: Option<impl Read + Send + 'static>,
+    limit: usize,
+) -> std::thread::JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.by_ref().take(limit as u64).read_to_end(&mut buf);
+            let _ = std::io::copy(&mut pipe, &mut std::io::sink());
+        }
+        buf
+    })
+}
+
+/// Wait for `child` to exit, killing its process group at `deadline`.
+///
+/// Returns the exit status (`None` if it could not be collected) and whether
+/// the deadline was hit.
+fn wait(
+    child: &mut Child,
+    deadline: Option<Instant>,
+) -> Result<(Option<std::process::ExitStatus>, bool), String> {
+    loop {
+        if let Some(status) = child.try_wait().map_err(|e| format!("wait failed: {e}"))? {
+            return Ok((Some(status), false));
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            warn!(
+                pid = child.id(),
+                "Command timed out; killing its process group"
+            );
+            let killed_group = Command::new("kill")
+                .args(["-KILL", "--", &format!("-{}", child.id())])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|s| s.success());
+            if !killed_group {
+                let _ = child.kill();
+            }
+            return Ok((child.wait().ok(), true));
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Write `data` to `path` with permission bits `mode`, replacing it
+/// atomically.
+fn push_file(path: &Path, mode: u32, data: &[u8]) -> std::io::Result<()> {
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return Err(invalid_path());
+    };
+    if !path.is_absolute() {
+        return Err(invalid_path());
+    }
+    std::fs::create_dir_all(parent)?;
+    let tmp = parent.join(format!(".{}.partial", name.to_string_lossy()));
+    std::fs::write(&tmp, data)?;
+    std::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(mode & 0o7777))?;
+    std::fs::rename(&tmp, path)
+}
+
+/// Read the regular file at `path`, refusing anything too large for a frame.
+fn pull_file(path: &Path) -> std::io::Result<Vec<u8>> {
+    if !path.is_absolute() {
+        return Err(invalid_path());
+    }
+    let metadata = std::fs::metadata(path)?;
+    if !metadata.is_file() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "not a regular file",
+        ));
+    }
+    if metadata.len() > MAX_FRAME_BYTES as u64 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!(
+                "file is {} bytes, larger than the {MAX_FRAME_BYTES}-byte limit",
+                metadata.len()
+            ),
+        ));
+    }
+    std::fs::read(path)
+}
+
+fn invalid_path() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        "path must be an absolute file path",
+    )
+}
+
+/// Read guest-wide resource usage from `/proc`.
+fn read_stats() -> std::io::Result<GuestStats> {
+    Ok(parse_stats(
+        &std::fs::read_to_string("/proc/uptime")?,
+        &std::fs::read_to_string("/proc/meminfo")?,
+        &std::fs::read_to_string("/proc/loadavg")?,
+    ))
+}
+
+/// Build [`GuestStats`] from the contents of `/proc/uptime`, `/proc/meminfo`
+/// and `/proc/loadavg`. Fields that cannot be parsed are left at zero.
+fn parse_stats(uptime: &str, meminfo: &str, loadavg: &str) -> GuestStats {
+    let mut stats = GuestStats {
+        uptime_ms: uptime
+            .split_whitespace()
+            .next()
+            .and_then(|secs| secs.parse::<f64>().ok())
+            .map(|secs| (secs * 1000.0) as u64)
+            .unwrap_or_default(),
+        ..Default::default()
+    };
+
+    for line in meminfo.lines() {
+        let mut fields = line.split_whitespace();
+        let (Some(key), Some(kib)) = (
+            fields.next(),
+            fields.next().and_then(|v| v.parse::<u64>().ok()),
+        ) else {
+            continue;
+        };
+        match key {
+            "MemTotal:" => stats.memory_total_bytes = kib * 1024,
+            "MemAvailable:" => stats.memory_available_bytes = kib * 1024,
+            _ => {}
+        }
+    }
+
+    // "0.10 0.20 0.30 2/345 6789": three load averages, then running/total.
+    let fields: Vec<&str> = loadavg.split_whitespace().collect();
+    for (slot, value) in stats.load_average.iter_mut().zip(&fields) {
+        *slot = value.parse().unwrap_or_default();
+    }
+    stats.processes = fields
+        .get(3)
+        .and_then(|tasks| tasks.split_once('/'))
+        .and_then(|(_, total)| total.parse().ok())
+        .unwrap_or_default();
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::net::UnixStream;
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// Start `agent` on one end of a socket pair and open a session on the
+    /// other, returning the host end and the agent's thread.
+    fn session(agent: Agent) -> (UnixStream, std::thread::JoinHandle<Outcome>) {
+        let (mut host, mut guest) = UnixStream::pair().unwrap();
+        let thread = std::thread::spawn(move || agent.serve(&mut guest).unwrap());
+        write_message(
+            &mut host,
+            &Request::Hello {
+                version: PROTOCOL_VERSION,
+            },
+        )
+        .unwrap();
+        let hello: Response = read_message(&mut host).unwrap().unwrap();
+        assert!(matches!(
+            hello,
+            Response::Hello {
+                version: PROTOCOL_VERSION,
+                ..
+            }
+        ));
+        (host, thread)
+    }
+
+    fn call(host: &mut UnixStream, request: &Request) -> Response {
+        write_message(host, request).unwrap();
+        read_message(host).unwrap().unwrap()
+    }
+
+    fn sh(script: &str) -> Vec<String> {
+        vec!["sh".to_string(), "-c".to_string(), script.to_string()]
+    }
+
+    #[test]
+    fn test_exec_captures_output_and_exit_code() {
+        let (mut host, thread) = session(Agent::new());
+        let mut exec = ExecRequest {
+            argv: sh("echo \"out $GREETING\"; echo err >&2; pwd; exit 3"),
+            workdir: Some("/".to_string()),
+            ..Default::default()
+        };
+        exec.env.insert("GREETING".to_string(), "hi".to_string());
+
+        let Response::Exited(result) = call(&mut host, &Request::Exec(exec)) else {
+            panic!("expected exit");
+        };
+        assert_eq!(result.exit_code, 3);
+        assert_eq!(result.stdout, "out hi\n/\n");
+        assert_eq!(result.stderr, "err\n");
+        assert!(!result.timed_out);
+
+        drop(host);
+        assert_eq!(thread.join().unwrap(), Outcome::Closed);
+    }
+
+    #[test]
+    fn test_exec_timeout_kills_command() {
+        let (mut host, _thread) = session(Agent::new());
+        let exec = ExecRequest {
+            argv: sh("sleep 5 & sleep 5"),
+            timeout_ms: Some(100),
+            ..Default::default()
+        };
+        let Response::Exited(result) = call(&mut host, &Request::Exec(exec)) else {
+            panic!("expected exit");
+        };
+        assert!(result.timed_out);
+        assert!(result.elapsed_ms < 5000);
+    }
+
+    #[test]
+    fn test_exec_output_capped() {
+        let (mut host, _thread) = session(Agent::new().with_max_output_bytes(4));
+        let exec = ExecRequest {
+            argv: sh("echo 0123456789"),
+            ..Default::default()
+        };
+        let Response::Exited(result) = call(&mut host, &Request::Exec(exec)) else {
+            panic!("expected exit");
+        };
+        assert_eq!(result.stdout, "0123");
+        assert_eq!(result.exit_code, 0);
+    }
+
+    #[test]
+    fn test_exec_errors_keep_session_open() {
+        let (mut host, _thread) = session(Agent::new());
+        let missing = ExecRequest {
+            argv: vec!["/nonexistent/binary".to_string()],
+            ..Default::default()
+        };
+        assert!(matches!(
+            call(&mut host, &Request::Exec(missing)),
+            Response::Error { .. }
+        ));
+        assert!(matches!(
+            call(&mut host, &Request::Exec(ExecRequest::default())),
+            Response::Error { .. }
+        ));
+        assert!(matches!(
+            call(
+                &mut host,
+                &Request::Exec(ExecRequest {
+                    argv: vec!["true".to_string()],
+                    ..Default::default()
+                })
+            ),
+            Response::Exited(ExecResult { exit_code: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_push_and_pull_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("nested/input.txt").display().to_string();
+        let (mut host, _thread) = session(Agent::new());
+
+        write_message(
+            &mut host,
+            &Request::PushFile {
+                path: path.clone(),
+                mode: 0o600,
+            },
+        )
+        .unwrap();
+        write_frame(&mut host, b"payload").unwrap();
+        assert_eq!(
+            read_message::<Response>(&mut host).unwrap(),
+            Some(Response::Done)
+        );
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        assert_eq!(
+            call(&mut host, &Request::PullFile { path }),
+            Response::File { size: 7 }
+        );
+        assert_eq!(read_frame(&mut host).unwrap().unwrap(), b"payload");
+
+        let missing = tmp.path().join("missing").display().to_string();
+        assert!(matches!(
+            call(&mut host, &Request::PullFile { path: missing }),
+            Response::Error { .. }
+        ));
+        assert!(matches!(
+            call(
+                &mut host,
+                &Request::PullFile {
+                    path: "relative".to_string()
+                }
+            ),
+            Response::Error { .. }
+        ));
+    }
+
+    #[test]
+    fn test_shutdown_ends_session() {
+        let (mut host, thread) = session(Agent::new());
+        assert_eq!(call(&mut host, &Request::Shutdown), Response::Done);
+        assert_eq!(thread.join().unwrap(), Outcome::Shutdown);
+    }
+
+    #[test]
+    fn test_version_mismatch_rejected() {
+        let (mut host, mut guest) = UnixStream::pair().unwrap();
+        let thread = std::thread::spawn(move || Agent::new().serve(&mut guest).unwrap());
+        let response = call(&mut host, &Request::Hello { version: 99 });
+        assert!(matches!(response, Response::Error { message } if message.contains("99")));
+        assert_eq!(thread.join().unwrap(), Outcome::Closed);
+    }
+
+    #[test]
+    fn test_parse_stats() {
+        let stats = parse_stats(
+            "1234.56 4000.00\n",
+            "MemTotal:        2048 kB\nMemFree:          512 kB\nMemAvailable:    1024 kB\n",
+            "0.10 0.20 0.30 2/345 6789\n",
+        );
+        assert_eq!(stats.uptime_ms, 1234560);
+        assert_eq!(stats.memory_total_bytes, 2048 * 1024);
+        assert_eq!(stats.memory_available_bytes, 1024 * 1024);
+        assert_eq!(stats.load_average, [0.10, 0.20, 0.30]);
+        assert_eq!(stats.processes, 345);
+
+        assert_eq!(parse_stats("", "", ""), GuestStats::default());
+    }
+}
@@ -0,0 +1,32 @@
+#![deny(unsafe_code)]
+
+//! Guest agent for CrustyClaw VM sandboxes.
+//!
+//! The Firecracker and Apple Virtualization backends boot a minimal Linux
+//! guest whose root filesystem ships the `crustyclaw-guest-agent` binary
+//! (built statically, e.g. for `x86_64-unknown-linux-musl`). The agent
+//! listens on a vsock port and serves the host over a small framed protocol,
+//! so sandbox results come back structured instead of scraped from the
+//! serial console.
+//!
+//! ## Architecture
+//!
+//! - **Protocol**: [`protocol`] defines the length-prefixed frames and the
+//!   [`Request`]/[`Response`] messages for command execution, file push and
+//!   pull, resource stats, and graceful shutdown.
+//! - **Agent**: [`Agent`] serves one host connection over any byte stream;
+//!   the binary accepts connections on vsock (or a Unix socket for local
+//!   testing) and powers the guest off when the host asks it to.
+//!
+//! The host side lives in `crustyclaw_core::isolation::GuestAgentClient`.
+
+/// Request handling inside the guest.
+pub mod agent;
+/// Framing and message types shared by host and guest.
+pub mod protocol;
+
+pub use agent::{Agent, Outcome};
+pub use protocol::{
+    DEFAULT_PORT, ExecRequest, ExecResult, GuestStats, PROTOCOL_VERSION, ProtocolError, Request,
+    Response,
+};
@@ -0,0 +1,225 @@
+#![deny(unsafe_code)]
+
+//! `crustyclaw-guest-agent` — serves the host from inside a sandbox VM.
+//!
+//! ```text
+//! crustyclaw-guest-agent [--port <PORT>] [--unix <PATH>] [--poweroff <COMMAND>...]
+//! ```
+//!
+//! The guest's init starts the agent once `/proc` is mounted. It listens on
+//! vsock port `--port` (default 5123), or on a Unix socket with `--unix` for
+//! testing outside a VM. When the host requests a shutdown the agent runs
+//! the `--poweroff` command (everything after the flag; default
+//! `/sbin/poweroff -f`) and exits.
+
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::{Arc, mpsc};
+
+use tracing::{info, warn};
+
+use crustyclaw_guest::{Agent, DEFAULT_PORT, Outcome};
+
+/// Where the agent accepts host connections.
+#[derive(Debug, PartialEq, Eq)]
+enum Listen {
+    Vsock(u32),
+    Unix(PathBuf),
+}
+
+/// Parsed command-line options.
+#[derive(Debug, PartialEq, Eq)]
+struct Options {
+    listen: Listen,
+    poweroff: Option<Vec<String>>,
+}
+
+fn main() {
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .init();
+
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("crustyclaw-guest-agent: {e}");
+            eprintln!(
+                "usage: crustyclaw-guest-agent [--port <PORT>] [--unix <PATH>] [--poweroff <COMMAND>...]"
+            );
+            std::process::exit(2);
+        }
+    };
+
+    let mut agent = Agent::new();
+    if let Some(poweroff) = options.poweroff {
+        agent = agent.with_poweroff_command(poweroff);
+    }
+    let agent = Arc::new(agent);
+
+    let served = match &options.listen {
+        Listen::Unix(path) => {
+            let _ = std::fs::remove_file(path);
+            std::os::unix::net::UnixListener::bind(path).map(|listener| {
+                info!(path = %path.display(), "Guest agent listening on Unix socket");
+                let incoming =
+                    std::iter::from_fn(move || Some(listener.accept().map(|(stream, _)| stream)));
+                serve(agent.clone(), incoming)
+            })
+        }
+        Listen::Vsock(port) => listen_vsock(*port).map(|incoming| {
+            info!(port, "Guest agent listening on vsock");
+            serve(agent.clone(), incoming)
+        }),
+    };
+    if let Err(e) = served {
+        eprintln!("crustyclaw-guest-agent: cannot listen: {e}");
+        std::process::exit(1);
+    }
+
+    power_off(agent.poweroff_command());
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        listen: Listen::Vsock(DEFAULT_PORT),
+        poweroff: None,
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--port" => {
+                let port = args.next().ok_or("--port needs a value")?;
+                let port = port.parse().map_err(|_| format!("invalid port {port:?}"))?;
+                options.listen = Listen::Vsock(port);
+            }
+            "--unix" => {
+                let path = args.next().ok_or("--unix needs a path")?;
+                options.listen = Listen::Unix(PathBuf::from(path));
+            }
+            "--poweroff" => {
+                let command: Vec<String> = args.by_ref().collect();
+                if command.is_empty() {
+                    return Err("--poweroff needs a command".to_string());
+                }
+                options.poweroff = Some(command);
+            }
+            other => return Err(format!("unknown argument {other:?}")),
+        }
+    }
+    Ok(options)
+}
+
+/// Serve each incoming connection on its own thread until one of them asks
+/// for a shutdown.
+fn serve<S, I>(agent: Arc<Agent>, incoming: I)
+where
+    S: Read + Write + Send + 'static,
+    I: Iterator<Item = std::io::Result<S>> + Send + 'static,
+{
+    let (shutdown_tx, shutdown_rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for stream in incoming {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!(error = %e, "Failed to accept host connection");
+                    continue;
+                }
+            };
+            let agent = agent.clone();
+            let shutdown_tx = shutdown_tx.clone();
+            std::thread::spawn(move || match agent.serve(&mut stream) {
+                Ok(Outcome::Shutdown) => {
+                    let _ = shutdown_tx.send(());
+                }
+                Ok(Outcome::Closed) => {}
+                Err(e) => warn!(error = %e, "Host connection failed"),
+            });
+        }
+    });
+    let _ = shutdown_rx.recv();
+}
+
+#[cfg(target_os = "linux")]
+fn listen_vsock(
+    port: u32,
+) -> std::io::Result<impl Iterator<Item = std::io::Result<socket2::Socket>> + Send + 'static> {
+    use socket2::{Domain, SockAddr, Socket, Type};
+
+    /// Accept connections addressed to any of this guest's CIDs.
+    const VMADDR_CID_ANY: u32 = u32::MAX;
+
+    let socket = Socket::new(Domain::VSOCK, Type::STREAM, None)?;
+    socket.bind(&SockAddr::vsock(VMADDR_CID_ANY, port))?;
+    socket.listen(16)?;
+    Ok(std::iter::from_fn(move || {
+        Some(socket.accept().map(|(stream, _)| stream))
+    }))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn listen_vsock(
+    _port: u32,
+) -> std::io::Result<std::iter::Empty<std::io::Result<std::os::unix::net::UnixStream>>> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "vsock needs a Linux guest; use --unix",
+    ))
+}
+
+/// Run the power-off command, then exit in case the guest is still up.
+fn power_off(command: &[String]) -> ! {
+    info!(command = ?command, "Powering off");
+    if let Some((program, args)) = command.split_first() {
+        match Command::new(program).args(args).status() {
+            Ok(status) if !status.success() => warn!(%status, "Power-off command failed"),
+            Ok(_) => {}
+            Err(e) => warn!(error = %e, "Power-off command could not be run"),
+        }
+    }
+    std::process::exit(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> impl Iterator<Item = String> {
+        list.iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(
+            parse_args(args(&[])).unwrap(),
+            Options {
+                listen: Listen::Vsock(DEFAULT_PORT),
+                poweroff: None,
+            }
+        );
+        assert_eq!(
+            parse_args(args(&[
+                "--unix",
+                "/tmp/agent.sock",
+                "--poweroff",
+                "reboot",
+                "-f"
+            ]))
+            .unwrap(),
+            Options {
+                listen: Listen::Unix(PathBuf::from("/tmp/agent.sock")),
+                poweroff: Some(vec!["reboot".to_string(), "-f".to_string()]),
+            }
+        );
+        assert_eq!(
+            parse_args(args(&["--port", "7000"])).unwrap().listen,
+            Listen::Vsock(7000)
+        );
+        assert!(parse_args(args(&["--port", "x"])).is_err());
+        assert!(parse_args(args(&["--poweroff"])).is_err());
+        assert!(parse_args(args(&["--verbose"])).is_err());
+    }
+}
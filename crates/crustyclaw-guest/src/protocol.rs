@@ -0,0 +1,247 @@
+//! Wire protocol between the host and the guest agent.
+//!
+//! Every message travels as a frame: a 4-byte big-endian length followed by
+//! that many bytes. Requests and responses are JSON frames; file contents are
+//! sent as one raw frame directly after a [`Request::PushFile`] or a
+//! [`Response::File`]. Each connection opens with [`Request::Hello`] so either
+//! side can refuse a protocol version it does not speak.
+
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// Protocol version spoken by this build.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// vsock port the agent listens on unless told otherwise.
+pub const DEFAULT_PORT: u32 = 5123;
+
+/// Largest frame either side accepts, and so the largest transferable file.
+pub const MAX_FRAME_BYTES: usize = 64 * 1024 * 1024;
+
+/// Errors reading or writing protocol frames.
+#[derive(Debug, thiserror::Error)]
+pub enum ProtocolError {
+    #[error("connection I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("frame is {size} bytes, larger than the {limit}-byte limit")]
+    FrameTooLarge { size: usize, limit: usize },
+
+    #[error("malformed message: {0}")]
+    Malformed(#[from] serde_json::Error),
+}
+
+/// A request from the host to the agent.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Request {
+    /// Open the session, announcing the host's protocol version.
+    Hello { version: u32 },
+    /// Run a command and wait for it to exit.
+    Exec(ExecRequest),
+    /// Write the raw frame that follows to `path`, creating parent
+    /// directories as needed.
+    PushFile { path: String, mode: u32 },
+    /// Read `path`; answered with [`Response::File`] and a raw frame.
+    PullFile { path: String },
+    /// Report guest resource usage.
+    Stats,
+    /// Acknowledge, then power the guest off.
+    Shutdown,
+}
+
+/// A command for the agent to run.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecRequest {
+    /// Program and arguments; the program is looked up on the guest's `PATH`.
+    pub argv: Vec<String>,
+    /// Variables added to the agent's own environment.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Working directory, if not the agent's.
+    #[serde(default)]
+    pub workdir: Option<String>,
+    /// Kill the command after this many milliseconds.
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+}
+
+/// The agent's answer to a [`Request`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Response {
+    /// Session accepted.
+    Hello { version: u32, agent: String },
+    /// The command ran to completion or was killed.
+    Exited(ExecResult),
+    /// The request succeeded and has nothing to report.
+    Done,
+    /// The requested file follows as a raw frame of `size` bytes.
+    File { size: u64 },
+    /// Current guest resource usage.
+    Stats(GuestStats),
+    /// The request failed.
+    Error { message: String },
+}
+
+/// Outcome of an [`ExecRequest`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecResult {
+    /// Exit code, or -1 if the command was killed by a signal.
+    pub exit_code: i32,
+    /// Captured stdout (lossily decoded, possibly truncated).
+    pub stdout: String,
+    /// Captured stderr (lossily decoded, possibly truncated).
+    pub stderr: String,
+    /// Wall-clock run time in milliseconds.
+    pub elapsed_ms: u64,
+    /// Whether the command was killed for exceeding its timeout.
+    pub timed_out: bool,
+}
+
+/// Resource usage of the guest as a whole.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GuestStats {
+    /// Time since the guest kernel booted.
+    pub uptime_ms: u64,
+    /// Total usable memory.
+    pub memory_total_bytes: u64,
+    /// Memory available to new processes without swapping.
+    pub memory_available_bytes: u64,
+    /// 1-, 5- and 15-minute load averages.
+    pub load_average: [f64; 3],
+    /// Number of processes and threads.
+    pub processes: u64,
+}
+
+/// Check a frame length read from the wire against [`MAX_FRAME_BYTES`].
+pub fn frame_len(header: [u8; 4]) -> Result<usize, ProtocolError> {
+    let size = u32::from_be_bytes(header) as usize;
+    if size > MAX_FRAME_BYTES {
+        return Err(ProtocolError::FrameTooLarge {
+            size,
+            limit: MAX_FRAME_BYTES,
+        });
+    }
+    Ok(size)
+}
+
+/// Write one frame.
+pub fn write_frame(writer: &mut impl Write, data: &[u8]) -> Result<(), ProtocolError> {
+    if data.len() > MAX_FRAME_BYTES {
+        return Err(ProtocolError::FrameTooLarge {
+            size: data.len(),
+            limit: MAX_FRAME_BYTES,
+        });
+    }
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(data)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Read one frame. Returns `None` if the peer closed the connection cleanly
+/// between frames.
+pub fn read_frame(reader: &mut impl Read) -> Result<Option<Vec<u8>>, ProtocolError> {
+    let mut header = [0u8; 4];
+    match reader.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let mut data = vec![0u8; frame_len(header)?];
+    reader.read_exact(&mut data)?;
+    Ok(Some(data))
+}
+
+/// Write a message as a JSON frame.
+pub fn write_message<T: Serialize>(
+    writer: &mut impl Write,
+    message: &T,
+) -> Result<(), ProtocolError> {
+    write_frame(writer, &serde_json::to_vec(message)?)
+}
+
+/// Read a JSON frame as a message. Returns `None` at a clean close.
+pub fn read_message<T: DeserializeOwned>(
+    reader: &mut impl Read,
+) -> Result<Option<T>, ProtocolError> {
+    match read_frame(reader)? {
+        Some(data) => Ok(Some(serde_json::from_slice(&data)?)),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_message_round_trip() {
+        let request = Request::Exec(ExecRequest {
+            argv: vec!["echo".to_string(), "hi".to_string()],
+            timeout_ms: Some(500),
+            ..Default::default()
+        });
+        let mut wire = Vec::new();
+        write_message(&mut wire, &request).unwrap();
+        write_message(&mut wire, &Request::Stats).unwrap();
+
+        let mut reader = wire.as_slice();
+        assert_eq!(read_message::<Request>(&mut reader).unwrap(), Some(request));
+        assert_eq!(
+            read_message::<Request>(&mut reader).unwrap(),
+            Some(Request::Stats)
+        );
+        assert_eq!(read_message::<Request>(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn test_wire_format() {
+        let json = serde_json::to_value(Request::PullFile {
+            path: "/tmp/out".to_string(),
+        })
+        .unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "op": "pull_file", "path": "/tmp/out" })
+        );
+
+        let json = serde_json::to_value(Response::File { size: 3 }).unwrap();
+        assert_eq!(json, serde_json::json!({ "status": "file", "size": 3 }));
+
+        let exec: Request = serde_json::from_str(r#"{"op":"exec","argv":["true"]}"#).unwrap();
+        assert_eq!(
+            exec,
+            Request::Exec(ExecRequest {
+                argv: vec!["true".to_string()],
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_oversized_frame_rejected() {
+        let header = ((MAX_FRAME_BYTES + 1) as u32).to_be_bytes();
+        let mut reader = header.as_slice();
+        assert!(matches!(
+            read_frame(&mut reader),
+            Err(ProtocolError::FrameTooLarge { .. })
+        ));
+    }
+
+    #[test]
+    fn test_truncated_frame_is_an_error() {
+        let mut wire = Vec::new();
+        write_frame(&mut wire, b"hello").unwrap();
+        wire.truncate(6);
+        assert!(matches!(
+            read_frame(&mut wire.as_slice()),
+            Err(ProtocolError::Io(_))
+        ));
+    }
+}
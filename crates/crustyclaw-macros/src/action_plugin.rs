@@ -1,9 +1,10 @@
 //! Implementation of `#[derive(ActionPlugin)]`.
 //!
 //! Generates the boilerplate for a Forgejo Action plugin:
-//! - Implements the `ActionPlugin` trait
-//! - Parses input from environment variables or TOML
 //! - Generates metadata (name, version, description) from struct-level attributes
+//! - Parses typed inputs from `INPUT_<NAME>` environment variables
+//! - Writes `#[action_output]` fields to the runner's output file
+//! - Provides a `run_from_env()` harness for the action's `main`
 //!
 //! # Example
 //!
@@ -15,6 +16,17 @@
 //!     pub name: String,
 //!     #[action_input(default = "Hello")]
 //!     pub greeting: String,
+//!     #[action_input(default = "1")]
+//!     pub repeat: u32,
+//!     #[action_output]
+//!     pub message: String,
+//! }
+//!
+//! fn main() -> std::process::ExitCode {
+//!     GreetAction::run_from_env(|action| {
+//!         action.message = format!("{}, {}!", action.greeting, action.name);
+//!         Ok::<_, String>(())
+//!     })
 //! }
 //! ```
 
@@ -66,24 +78,44 @@ impl PluginMeta {
     }
 }
 
-struct InputField {
+/// How a field is populated by `from_env()`.
+enum FieldRole {
+    /// An `INPUT_<NAME>` environment variable.
+    Input {
+        required: bool,
+        default_value: Option<String>,
+    },
+    /// An `#[action_output]` field, written by `write_outputs()`.
+    Output,
+}
+
+struct PluginField {
     field_name: syn::Ident,
-    required: bool,
-    default_value: Option<String>,
+    ty: syn::Type,
+    role: FieldRole,
 }
 
-impl InputField {
-    fn parse(field: &syn::Field) -> Result<Option<Self>> {
+impl PluginField {
+    fn parse(field: &syn::Field) -> Result<Self> {
         let field_name = field.ident.clone().unwrap();
-        let mut is_action_input = false;
         let mut required = false;
         let mut default_value = None;
+        let mut output = None;
 
         for attr in &field.attrs {
+            if attr.path().is_ident("action_output") {
+                if let syn::Meta::List(list) = &attr.meta {
+                    return Err(syn::Error::new_spanned(
+                        list,
+                        "#[action_output] takes no arguments",
+                    ));
+                }
+                output = Some(attr);
+                continue;
+            }
             if !attr.path().is_ident("action_input") {
                 continue;
             }
-            is_action_input = true;
             attr.parse_nested_meta(|meta: ParseNestedMeta| {
                 if meta.path.is_ident("required") {
                     required = true;
@@ -99,23 +131,128 @@ impl InputField {
             })?;
         }
 
-        if is_action_input {
-            Ok(Some(InputField {
+        if let Some(attr) = output {
+            if required || default_value.is_some() {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    "a field cannot be both #[action_input] and #[action_output]",
+                ));
+            }
+            return Ok(PluginField {
                 field_name,
+                ty: field.ty.clone(),
+                role: FieldRole::Output,
+            });
+        }
+
+        if required && option_inner(&field.ty).is_some() {
+            return Err(syn::Error::new_spanned(
+                &field.ty,
+                "an Option input is already optional; drop `required` or the Option",
+            ));
+        }
+
+        // Fields without #[action_input] are optional inputs too.
+        Ok(PluginField {
+            field_name,
+            ty: field.ty.clone(),
+            role: FieldRole::Input {
+                required,
+                default_value,
+            },
+        })
+    }
+
+    /// Initializer expression for `try_from_lookup()`.
+    fn init(&self) -> TokenStream {
+        let (required, default_value) = match &self.role {
+            FieldRole::Output => return quote! { ::std::default::Default::default() },
+            FieldRole::Input {
                 required,
                 default_value,
-            }))
+            } => (*required, default_value),
+        };
+
+        let input_name = self.field_name.to_string();
+        let env_key = format!("INPUT_{}", input_name.to_uppercase());
+        let raw = quote! {
+            lookup(#env_key).filter(|v| !v.trim().is_empty())
+        };
+
+        if let Some(inner) = option_inner(&self.ty) {
+            let parse = parse_value(inner, &input_name);
+            return quote! {
+                match #raw {
+                    ::std::option::Option::Some(raw) => ::std::option::Option::Some(#parse),
+                    ::std::option::Option::None => ::std::option::Option::None,
+                }
+            };
+        }
+
+        let parse = parse_value(&self.ty, &input_name);
+        if required {
+            quote! {{
+                let raw = #raw.ok_or_else(|| format!("required input {} not set", #env_key))?;
+                #parse
+            }}
+        } else if let Some(default) = default_value {
+            quote! {{
+                let raw = #raw.unwrap_or_else(|| #default.to_string());
+                #parse
+            }}
         } else {
-            // Treat all fields as inputs (optional with empty default)
-            Ok(Some(InputField {
-                field_name,
-                required: false,
-                default_value: None,
-            }))
+            quote! {
+                match #raw {
+                    ::std::option::Option::Some(raw) => #parse,
+                    ::std::option::Option::None => ::std::default::Default::default(),
+                }
+            }
         }
     }
 }
 
+/// Expression converting the `raw: String` in scope into `ty`, returning a
+/// conversion error from the enclosing function on failure. Strings pass
+/// through untouched; everything else goes through `FromStr` on the trimmed
+/// value, which covers `bool`, integers, and enums implementing `FromStr`.
+fn parse_value(ty: &syn::Type, input_name: &str) -> TokenStream {
+    if is_string(ty) {
+        return quote! { raw };
+    }
+    quote! {
+        <#ty as ::std::str::FromStr>::from_str(raw.trim()).map_err(|e| {
+            format!("input {}: invalid value {:?}: {}", #input_name, raw, e)
+        })?
+    }
+}
+
+/// The last path segment of a type, e.g. `String` for `std::string::String`.
+fn last_segment(ty: &syn::Type) -> Option<&syn::PathSegment> {
+    match ty {
+        syn::Type::Path(path) if path.qself.is_none() => path.path.segments.last(),
+        _ => None,
+    }
+}
+
+fn is_string(ty: &syn::Type) -> bool {
+    last_segment(ty).is_some_and(|seg| seg.ident == "String")
+}
+
+/// `T` for an `Option<T>` field.
+fn option_inner(ty: &syn::Type) -> Option<&syn::Type> {
+    let seg = last_segment(ty)?;
+    if seg.ident != "Option" {
+        return None;
+    }
+    match &seg.arguments {
+        syn::PathArguments::AngleBracketed(args) => match args.args.first()? {
+            syn::GenericArgument::Type(inner) => Some(inner),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
 pub fn expand(input: DeriveInput) -> Result<TokenStream> {
     let name = &input.ident;
     let meta = PluginMeta::parse(&input)?;
@@ -138,42 +275,53 @@ pub fn expand(input: DeriveInput) -> Result<TokenStream> {
         }
     };
 
-    let mut input_fields = Vec::new();
+    let mut plugin_fields = Vec::new();
     for field in fields {
-        if let Some(input_field) = InputField::parse(field)? {
-            input_fields.push(input_field);
-        }
+        plugin_fields.push(PluginField::parse(field)?);
     }
 
     let plugin_name = &meta.name;
     let plugin_version = &meta.version;
     let plugin_description = &meta.description;
 
-    // Generate input_names() items
-    let input_name_strs: Vec<String> = input_fields
+    // Generate input_names() and output_names() items
+    let input_name_strs: Vec<String> = plugin_fields
         .iter()
+        .filter(|f| matches!(f.role, FieldRole::Input { .. }))
+        .map(|f| f.field_name.to_string())
+        .collect();
+    let output_name_strs: Vec<String> = plugin_fields
+        .iter()
+        .filter(|f| matches!(f.role, FieldRole::Output))
         .map(|f| f.field_name.to_string())
         .collect();
 
-    // Generate from_env() field initializers
-    let field_inits: Vec<TokenStream> = input_fields
+    // Generate try_from_lookup() field initializers
+    let field_inits: Vec<TokenStream> = plugin_fields
         .iter()
         .map(|f| {
             let field_name = &f.field_name;
-            let env_key = format!("INPUT_{}", f.field_name.to_string().to_uppercase());
+            let init = f.init();
+            quote! { #field_name: #init }
+        })
+        .collect();
 
-            if f.required {
-                quote! {
-                    #field_name: ::std::env::var(#env_key)
-                        .unwrap_or_else(|_| panic!("required input {} not set", #env_key))
-                }
-            } else if let Some(default) = &f.default_value {
+    // Generate write_outputs_to() statements; `None` outputs are skipped.
+    let output_writes: Vec<TokenStream> = plugin_fields
+        .iter()
+        .filter(|f| matches!(f.role, FieldRole::Output))
+        .map(|f| {
+            let field_name = &f.field_name;
+            let output_name = field_name.to_string();
+            if option_inner(&f.ty).is_some() {
                 quote! {
-                    #field_name: ::std::env::var(#env_key).unwrap_or_else(|_| #default.to_string())
+                    if let ::std::option::Option::Some(value) = &self.#field_name {
+                        Self::set_output_to(path, #output_name, &value.to_string())?;
+                    }
                 }
             } else {
                 quote! {
-                    #field_name: ::std::env::var(#env_key).unwrap_or_default()
+                    Self::set_output_to(path, #output_name, &self.#field_name.to_string())?;
                 }
             }
         })
@@ -203,13 +351,127 @@ pub fn expand(input: DeriveInput) -> Result<TokenStream> {
                 &[#(#input_name_strs),*]
             }
 
+            /// List of output names written by `write_outputs()`.
+            pub fn output_names() -> &'static [&'static str] {
+                &[#(#output_name_strs),*]
+            }
+
             /// Construct this plugin from environment variables.
             ///
             /// Environment variables are named `INPUT_<FIELD_NAME>` (uppercase).
+            ///
+            /// # Panics
+            ///
+            /// Panics if a required input is missing or an input fails to
+            /// convert; use `try_from_env()` to handle that instead.
             pub fn from_env() -> Self {
-                Self {
+                Self::try_from_env().unwrap_or_else(|e| panic!("{}", e))
+            }
+
+            /// Construct this plugin from `INPUT_<FIELD_NAME>` environment
+            /// variables, reporting missing or malformed inputs.
+            pub fn try_from_env() -> ::std::result::Result<Self, ::std::string::String> {
+                Self::try_from_lookup(|key| ::std::env::var(key).ok())
+            }
+
+            /// Construct this plugin from inputs supplied by `lookup`, keyed
+            /// by `INPUT_<FIELD_NAME>`. Blank values count as unset.
+            pub fn try_from_lookup<F>(lookup: F) -> ::std::result::Result<Self, ::std::string::String>
+            where
+                F: Fn(&str) -> ::std::option::Option<::std::string::String>,
+            {
+                ::std::result::Result::Ok(Self {
                     #(#field_inits),*
+                })
+            }
+
+            /// The runner's output file: `FORGEJO_OUTPUT`, falling back to
+            /// `GITHUB_OUTPUT`.
+            pub fn output_path() -> ::std::io::Result<::std::path::PathBuf> {
+                ::std::env::var_os("FORGEJO_OUTPUT")
+                    .or_else(|| ::std::env::var_os("GITHUB_OUTPUT"))
+                    .map(::std::path::PathBuf::from)
+                    .ok_or_else(|| {
+                        ::std::io::Error::new(
+                            ::std::io::ErrorKind::NotFound,
+                            "neither FORGEJO_OUTPUT nor GITHUB_OUTPUT is set",
+                        )
+                    })
+            }
+
+            /// Append one output to the runner's output file.
+            pub fn set_output(name: &str, value: &str) -> ::std::io::Result<()> {
+                Self::set_output_to(&Self::output_path()?, name, value)
+            }
+
+            /// Append one output to the output file at `path`. Multi-line
+            /// values use the `name<<DELIMITER` heredoc form.
+            pub fn set_output_to(
+                path: &::std::path::Path,
+                name: &str,
+                value: &str,
+            ) -> ::std::io::Result<()> {
+                use ::std::io::Write as _;
+                let mut file = ::std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)?;
+                if value.contains('\n') || value.contains('\r') {
+                    let nanos = ::std::time::SystemTime::now()
+                        .duration_since(::std::time::UNIX_EPOCH)
+                        .map(|d| d.as_nanos())
+                        .unwrap_or_default();
+                    let mut delimiter = format!("EOF_{}", nanos);
+                    while value.contains(&delimiter) {
+                        delimiter.push('_');
+                    }
+                    writeln!(file, "{}<<{}\n{}\n{}", name, delimiter, value, delimiter)
+                } else {
+                    writeln!(file, "{}={}", name, value)
+                }
+            }
+
+            /// Write every `#[action_output]` field to the runner's output file.
+            pub fn write_outputs(&self) -> ::std::io::Result<()> {
+                if Self::output_names().is_empty() {
+                    return ::std::result::Result::Ok(());
+                }
+                self.write_outputs_to(&Self::output_path()?)
+            }
+
+            /// Write every `#[action_output]` field to the output file at `path`.
+            #[allow(unused_variables)]
+            pub fn write_outputs_to(&self, path: &::std::path::Path) -> ::std::io::Result<()> {
+                #(#output_writes)*
+                ::std::result::Result::Ok(())
+            }
+
+            /// Harness for an action's `main`: parse inputs from the
+            /// environment, call `run`, then write outputs.
+            ///
+            /// Any failure is printed as an `::error::` workflow command and
+            /// turned into a failing exit code.
+            pub fn run_from_env<F, E>(run: F) -> ::std::process::ExitCode
+            where
+                F: FnOnce(&mut Self) -> ::std::result::Result<(), E>,
+                E: ::std::fmt::Display,
+            {
+                let mut plugin = match Self::try_from_env() {
+                    ::std::result::Result::Ok(plugin) => plugin,
+                    ::std::result::Result::Err(e) => {
+                        println!("::error::{}", e);
+                        return ::std::process::ExitCode::FAILURE;
+                    }
+                };
+                if let ::std::result::Result::Err(e) = run(&mut plugin) {
+                    println!("::error::{}", e);
+                    return ::std::process::ExitCode::FAILURE;
+                }
+                if let ::std::result::Result::Err(e) = plugin.write_outputs() {
+                    println!("::error::failed to write outputs: {}", e);
+                    return ::std::process::ExitCode::FAILURE;
                 }
+                ::std::process::ExitCode::SUCCESS
             }
         }
     })
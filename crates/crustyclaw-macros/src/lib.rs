@@ -26,7 +26,18 @@ use syn::{DeriveInput, parse_macro_input};
 /// Derive macro for redacting sensitive fields in Debug output.
 ///
 /// Fields annotated with `#[redact]` will display as `[REDACTED]` in the
-/// generated `Debug` implementation.
+/// generated `Debug` implementation. Works on structs and enums.
+///
+/// Supported attributes:
+/// - `#[redact]` — replace the value with `[REDACTED]`
+/// - `#[redact(partial = N)]` — show only the last N characters
+///   (`****cdef`); values no longer than N are fully redacted
+/// - `#[redact(hash)]` — show a short SHA-256 prefix
+///   (`[REDACTED sha256:1a2b3c4d5e6f]`) so the same value can be correlated
+///   across log lines; the consuming crate must depend on `sha2`
+/// - `#[redact(with = "path::to::fn")]` — render with `fn(&T) -> String`
+///
+/// `partial` and `hash` require the field to implement `AsRef<str>`.
 ///
 /// # Example
 ///
@@ -38,6 +49,10 @@ use syn::{DeriveInput, parse_macro_input};
 ///     pub username: String,
 ///     #[redact]
 ///     pub password: String,
+///     #[redact(partial = 4)]
+///     pub api_key: String,
+///     #[redact(hash)]
+///     pub session_id: String,
 /// }
 /// ```
 #[proc_macro_derive(Redact, attributes(redact))]
@@ -59,6 +74,13 @@ pub fn derive_redact(input: TokenStream) -> TokenStream {
 /// - `#[validate(range(min = N, max = M))]` — numeric value in [N, M]
 /// - `#[validate(min_len = N)]` — minimum length for strings/collections
 /// - `#[validate(max_len = N)]` — maximum length for strings/collections
+/// - `#[validate(regex = "...")]` — string must match the pattern; the
+///   pattern is compiled during expansion and the consuming crate must
+///   depend on `regex`
+/// - `#[validate(custom = "path::to::fn")]` — calls
+///   `fn(&FieldType) -> Result<(), String>`; the `Err` message is reported
+/// - `#[validate(nested)]` — runs the field's own `validate()` and prefixes
+///   its errors with `field.`
 ///
 /// # Example
 ///
@@ -73,6 +95,12 @@ pub fn derive_redact(input: TokenStream) -> TokenStream {
 ///     pub port: u16,
 ///     #[validate(min_len = 8)]
 ///     pub api_key: String,
+///     #[validate(regex = "^[a-z][a-z0-9-]*$")]
+///     pub name: String,
+///     #[validate(custom = "check_path")]
+///     pub state_dir: String,
+///     #[validate(nested)]
+///     pub tls: TlsConfig,
 /// }
 /// ```
 #[proc_macro_derive(Validate, attributes(validate))]
@@ -113,7 +141,22 @@ pub fn derive_secure_zeroize(input: TokenStream) -> TokenStream {
 /// Derive macro for Forgejo Action plugin scaffolding.
 ///
 /// Generates `plugin_name()`, `plugin_version()`, `plugin_description()`,
-/// `input_names()`, and `from_env()` methods.
+/// `input_names()`, `output_names()`, `from_env()`, `try_from_env()`,
+/// `set_output()`, `write_outputs()`, and `run_from_env()` methods.
+///
+/// Inputs are read from `INPUT_<FIELD_NAME>`; blank values count as unset.
+/// `String` fields take the value as-is, `Option<T>` fields are `None` when
+/// unset, and any other type is parsed with `FromStr` (so `bool`, integers,
+/// and enums implementing `FromStr` all work), with conversion failures
+/// reported by `try_from_env()`.
+///
+/// Supported field attributes:
+/// - `#[action_input(required)]` — error if the input is unset
+/// - `#[action_input(default = "...")]` — value used when unset, parsed like
+///   any other input
+/// - `#[action_output]` — not an input; starts as `Default::default()` and is
+///   written by `write_outputs()` as `name=value` (using `Display`) to
+///   `FORGEJO_OUTPUT` or `GITHUB_OUTPUT`
 ///
 /// # Example
 ///
@@ -127,9 +170,20 @@ pub fn derive_secure_zeroize(input: TokenStream) -> TokenStream {
 ///     pub name: String,
 ///     #[action_input(default = "Hello")]
 ///     pub greeting: String,
+///     #[action_input(default = "false")]
+///     pub shout: bool,
+///     #[action_output]
+///     pub message: String,
+/// }
+///
+/// fn main() -> std::process::ExitCode {
+///     GreetAction::run_from_env(|action| {
+///         action.message = format!("{}, {}!", action.greeting, action.name);
+///         Ok::<_, String>(())
+///     })
 /// }
 /// ```
-#[proc_macro_derive(ActionPlugin, attributes(action, action_input))]
+#[proc_macro_derive(ActionPlugin, attributes(action, action_input, action_output))]
 pub fn derive_action_plugin(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     action_plugin::expand(input)
@@ -161,14 +215,26 @@ pub fn action_hook(attr: TokenStream, item: TokenStream) -> TokenStream {
 ///
 /// Parses a list of `allow`/`deny` rules and generates a `PolicyEngine`.
 ///
+/// Besides rules, the DSL accepts:
+/// - `group NAME = [role, ...];` — a role group, referenced as `@NAME`
+/// - `when "expr"` after a rule — a `Condition`, parsed at compile time
+/// - `include "file.policy";` — splice in another file's statements,
+///   resolved relative to the crate's `Cargo.toml`
+///
+/// Conditions and patterns are checked during expansion, so mistakes are
+/// compile errors rather than rules that never match.
+///
 /// # Example
 ///
 /// ```ignore
 /// use crustyclaw_macros::security_policy;
 ///
 /// let engine = security_policy! {
+///     include "policies/base.policy";
+///     group ops = [alice, oncall];
 ///     allow admin * *;
 ///     allow user read config;
+///     allow @ops deploy * when "time.hour >= 9 && time.hour < 17";
 ///     deny * write secrets [priority = 100];
 /// };
 /// ```
@@ -1,59 +1,206 @@
 //! Implementation of `#[derive(Redact)]`.
+//!
+//! Works on structs (named, tuple, or unit) and enums. Every shape is
+//! rendered through a `match self` so struct fields and variant fields share
+//! the same per-field redaction code.
 
-use proc_macro2::TokenStream;
-use quote::quote;
-use syn::{DeriveInput, Result};
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote, quote_spanned};
+use syn::ext::IdentExt;
+use syn::{DeriveInput, Fields, LitInt, LitStr, Meta, Result};
 
-pub fn expand(input: DeriveInput) -> Result<TokenStream> {
-    let name = &input.ident;
+/// How a single field is rendered in `Debug` output.
+enum RedactMode {
+    /// Not annotated: the field's own `Debug`.
+    Plain,
+    /// `#[redact]`: the literal `[REDACTED]`.
+    Full,
+    /// `#[redact(partial = N)]`: mask all but the last N characters.
+    Partial(usize),
+    /// `#[redact(with = "path::to::fn")]`: `fn(&T) -> String`.
+    With(syn::Path, Span),
+    /// `#[redact(hash)]`: a short SHA-256 prefix for correlation.
+    Hash,
+}
 
-    let fields = match &input.data {
-        syn::Data::Struct(data) => match &data.fields {
-            syn::Fields::Named(fields) => &fields.named,
-            _ => {
+impl RedactMode {
+    fn parse(field: &syn::Field) -> Result<Self> {
+        let mut mode = RedactMode::Plain;
+        for attr in &field.attrs {
+            if !attr.path().is_ident("redact") {
+                continue;
+            }
+            if !matches!(mode, RedactMode::Plain) {
                 return Err(syn::Error::new_spanned(
-                    name,
-                    "Redact only supports structs with named fields",
+                    attr,
+                    "duplicate #[redact] attribute on field",
                 ));
             }
-        },
-        _ => {
-            return Err(syn::Error::new_spanned(
-                name,
-                "Redact can only be derived for structs",
-            ));
+            if let Meta::Path(_) = attr.meta {
+                mode = RedactMode::Full;
+                continue;
+            }
+            attr.parse_nested_meta(|meta| {
+                if !matches!(mode, RedactMode::Plain) {
+                    return Err(meta.error("only one redact mode may be given per field"));
+                }
+                if meta.path.is_ident("hash") {
+                    mode = RedactMode::Hash;
+                    return Ok(());
+                }
+                if meta.path.is_ident("partial") {
+                    let lit: LitInt = meta.value()?.parse()?;
+                    let n: usize = lit.base10_parse()?;
+                    if n == 0 {
+                        return Err(syn::Error::new(
+                            lit.span(),
+                            "partial must show at least one character; use #[redact] to hide the value entirely",
+                        ));
+                    }
+                    mode = RedactMode::Partial(n);
+                    return Ok(());
+                }
+                if meta.path.is_ident("with") {
+                    let lit: LitStr = meta.value()?.parse()?;
+                    let path: syn::Path = lit.parse().map_err(|_| {
+                        syn::Error::new(
+                            lit.span(),
+                            "expected a function path, e.g. `with = \"path::to::fn\"`",
+                        )
+                    })?;
+                    mode = RedactMode::With(path, lit.span());
+                    return Ok(());
+                }
+                Err(meta.error("unknown redact mode; expected partial, with, or hash"))
+            })?;
         }
-    };
-
-    let field_debug: Vec<_> = fields
-        .iter()
-        .map(|f| {
-            let field_name = f.ident.as_ref().unwrap();
-            let field_name_str = field_name.to_string();
-            let is_redacted = f.attrs.iter().any(|a| a.path().is_ident("redact"));
+        Ok(mode)
+    }
 
-            if is_redacted {
-                quote! {
-                    .field(#field_name_str, &"[REDACTED]")
+    /// Expression producing the `&dyn Debug` value for a field bound as
+    /// `binding` (a reference to the field).
+    fn render(&self, binding: &syn::Ident) -> TokenStream {
+        match self {
+            RedactMode::Plain => quote! { #binding },
+            // Touch the binding so fully redacted fields still count as read.
+            RedactMode::Full => quote! {
+                {
+                    let _ = #binding;
+                    &"[REDACTED]"
                 }
-            } else {
-                quote! {
-                    .field(#field_name_str, &self.#field_name)
+            },
+            RedactMode::Partial(n) => quote! {
+                &{
+                    let value: &str = ::std::convert::AsRef::<str>::as_ref(#binding);
+                    let count = value.chars().count();
+                    if count <= #n {
+                        ::std::string::String::from("[REDACTED]")
+                    } else {
+                        let tail: ::std::string::String = value.chars().skip(count - #n).collect();
+                        format!("****{}", tail)
+                    }
                 }
+            },
+            RedactMode::With(path, span) => quote_spanned! {*span=>
+                &#path(#binding)
+            },
+            RedactMode::Hash => quote! {
+                &{
+                    use ::sha2::Digest as _;
+                    let value: &str = ::std::convert::AsRef::<str>::as_ref(#binding);
+                    let digest = ::sha2::Sha256::digest(value.as_bytes());
+                    let hex: ::std::string::String =
+                        digest[..6].iter().map(|b| format!("{:02x}", b)).collect();
+                    format!("[REDACTED sha256:{}]", hex)
+                }
+            },
+        }
+    }
+}
+
+/// Generate the match arm body for one struct or variant shape. `path` is
+/// `Self` or `Self::Variant`; `label` is the name shown in the output.
+fn expand_fields(path: TokenStream, label: &str, fields: &Fields) -> Result<TokenStream> {
+    match fields {
+        Fields::Named(named) => {
+            let mut bindings = Vec::new();
+            let mut entries = Vec::new();
+            for (i, field) in named.named.iter().enumerate() {
+                let ident = field.ident.as_ref().unwrap();
+                let name_str = ident.unraw().to_string();
+                let mode = RedactMode::parse(field)?;
+                let binding = format_ident!("__field{}", i);
+                bindings.push(quote! { #ident: #binding });
+                let value = mode.render(&binding);
+                entries.push(quote! { .field(#name_str, #value) });
             }
-        })
-        .collect();
+            Ok(quote! {
+                #path { #(#bindings),* } => f.debug_struct(#label) #(#entries)* .finish()
+            })
+        }
+        Fields::Unnamed(unnamed) => {
+            let mut bindings = Vec::new();
+            let mut entries = Vec::new();
+            for (i, field) in unnamed.unnamed.iter().enumerate() {
+                let mode = RedactMode::parse(field)?;
+                let binding = format_ident!("__field{}", i);
+                bindings.push(quote! { #binding });
+                let value = mode.render(&binding);
+                entries.push(quote! { .field(#value) });
+            }
+            Ok(quote! {
+                #path( #(#bindings),* ) => f.debug_tuple(#label) #(#entries)* .finish()
+            })
+        }
+        Fields::Unit => Ok(quote! {
+            #path => f.write_str(#label)
+        }),
+    }
+}
 
+pub fn expand(input: DeriveInput) -> Result<TokenStream> {
+    let name = &input.ident;
     let name_str = name.to_string();
 
+    let arms = match &input.data {
+        syn::Data::Struct(data) => vec![expand_fields(quote! { Self }, &name_str, &data.fields)?],
+        syn::Data::Enum(data) => {
+            let mut arms = Vec::new();
+            for variant in &data.variants {
+                let ident = &variant.ident;
+                arms.push(expand_fields(
+                    quote! { Self::#ident },
+                    &ident.to_string(),
+                    &variant.fields,
+                )?);
+            }
+            arms
+        }
+        syn::Data::Union(_) => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "Redact can only be derived for structs and enums",
+            ));
+        }
+    };
+
+    // An empty enum has no values to format; `match *self {}` proves it.
+    let body = if arms.is_empty() {
+        quote! { match *self {} }
+    } else {
+        quote! {
+            match self {
+                #(#arms,)*
+            }
+        }
+    };
+
     let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
 
     Ok(quote! {
         impl #impl_generics ::std::fmt::Debug for #name #ty_generics #where_clause {
             fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
-                f.debug_struct(#name_str)
-                    #(#field_debug)*
-                    .finish()
+                #body
             }
         }
     })
@@ -4,58 +4,152 @@
 //!
 //! ```text
 //! security_policy! {
+//!     include "policies/base.policy";
+//!     group ops = [alice, oncall];
 //!     allow admin * *;
 //!     allow user read config;
+//!     allow @ops deploy * when "time.hour >= 9 && time.hour < 17";
 //!     deny user write secrets [priority = 100];
 //!     deny * * * [priority = 0];
 //! }
 //! ```
 //!
 //! Expands to a `PolicyEngine` construction with compile-time validated rules.
+//!
+//! - Role, action, and resource are identifiers, `*`, or string literals
+//!   holding a [pattern](crustyclaw_config::pattern) (`"skills/git-*"`).
+//! - `group NAME = [member, ...];` defines a role group; `@NAME` in the role
+//!   position expands to the `member|member` alternation. Groups must be
+//!   defined before use and may reference earlier groups.
+//! - `when "expr"` attaches a [`Condition`]; the expression is parsed here so
+//!   syntax errors are reported against the literal.
+//! - `include "file.policy";` splices in the statements from another file.
+//!   Paths are relative to the crate's `Cargo.toml` for the macro body and
+//!   to the including file for nested includes. Included files are tracked
+//!   with `include_bytes!` so edits trigger a rebuild.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
-use proc_macro2::TokenStream;
+use crustyclaw_config::policy::{Condition, Pattern};
+use proc_macro2::{Span, TokenStream};
 use quote::quote;
 use syn::parse::{Parse, ParseStream};
-use syn::{Ident, LitInt, Result, Token};
+use syn::{Ident, LitInt, LitStr, Result, Token};
 
 /// A parsed security policy block.
 struct PolicyBlock {
-    rules: Vec<RuleDef>,
+    statements: Vec<Statement>,
+}
+
+/// One top-level statement of the DSL.
+enum Statement {
+    Rule(RuleDef),
+    Group(GroupDef),
+    Include(LitStr),
 }
 
 /// A single rule definition from the DSL.
 struct RuleDef {
-    effect: Ident,    // "allow" or "deny"
-    role: String,     // e.g. "admin", "*"
-    action: String,   // e.g. "read", "*"
-    resource: String, // e.g. "config", "*"
+    effect: Ident,         // "allow" or "deny"
+    role: RoleTerm,        // e.g. "admin", "*", "@ops"
+    action: PolicyToken,   // e.g. "read", "*"
+    resource: PolicyToken, // e.g. "config", "*"
+    condition: Option<LitStr>,
     priority: u32,
 }
 
+/// `group NAME = [member, ...];`
+struct GroupDef {
+    name: Ident,
+    members: Vec<RoleTerm>,
+}
+
+/// A role position: a plain token or a `@group` reference.
+enum RoleTerm {
+    Token(PolicyToken),
+    Group(Ident),
+}
+
+/// An identifier, `*`, or string literal, with its span for error reporting.
+struct PolicyToken {
+    value: String,
+    span: Span,
+}
+
 impl Parse for PolicyBlock {
     fn parse(input: ParseStream) -> Result<Self> {
-        let mut rules = Vec::new();
+        let mut statements = Vec::new();
         while !input.is_empty() {
-            rules.push(input.parse::<RuleDef>()?);
+            statements.push(input.parse::<Statement>()?);
         }
-        Ok(PolicyBlock { rules })
+        Ok(PolicyBlock { statements })
+    }
+}
+
+impl Parse for Statement {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let lookahead = input.fork().parse::<Ident>();
+        match lookahead {
+            Ok(ident) if ident == "include" => {
+                input.parse::<Ident>()?;
+                let path: LitStr = input.parse()?;
+                input.parse::<Token![;]>()?;
+                Ok(Statement::Include(path))
+            }
+            Ok(ident) if ident == "group" => Ok(Statement::Group(input.parse()?)),
+            _ => Ok(Statement::Rule(input.parse()?)),
+        }
+    }
+}
+
+impl Parse for GroupDef {
+    fn parse(input: ParseStream) -> Result<Self> {
+        // Parse: group NAME = [member, ...];
+        input.parse::<Ident>()?;
+        let name: Ident = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let content;
+        let brackets = syn::bracketed!(content in input);
+        let members = content
+            .parse_terminated(RoleTerm::parse, Token![,])?
+            .into_iter()
+            .collect::<Vec<_>>();
+        if members.is_empty() {
+            return Err(syn::Error::new(
+                brackets.span.join(),
+                "a role group needs at least one member",
+            ));
+        }
+        input.parse::<Token![;]>()?;
+        Ok(GroupDef { name, members })
     }
 }
 
 impl Parse for RuleDef {
     fn parse(input: ParseStream) -> Result<Self> {
-        // Parse: allow|deny role action resource [priority = N];
-        let effect: Ident = input.parse()?;
+        // Parse: allow|deny role action resource [when "expr"] [priority = N];
+        let effect: Ident = input.parse().map_err(|e| {
+            syn::Error::new(e.span(), "expected `allow`, `deny`, `group`, or `include`")
+        })?;
         if effect != "allow" && effect != "deny" {
             return Err(syn::Error::new_spanned(
                 &effect,
-                "expected `allow` or `deny`",
+                "expected `allow`, `deny`, `group`, or `include`",
             ));
         }
 
-        let role = parse_policy_token(input)?;
-        let action = parse_policy_token(input)?;
-        let resource = parse_policy_token(input)?;
+        let role: RoleTerm = input.parse()?;
+        let action: PolicyToken = input.parse()?;
+        let resource: PolicyToken = input.parse()?;
+
+        // Optional when "expr"
+        let condition = if input.peek(Ident) && input.fork().parse::<Ident>()? == "when" {
+            input.parse::<Ident>()?;
+            Some(input.parse::<LitStr>()?)
+        } else {
+            None
+        };
 
         // Optional [priority = N]
         let priority = if input.peek(syn::token::Bracket) {
@@ -80,26 +174,197 @@ impl Parse for RuleDef {
             role,
             action,
             resource,
+            condition,
             priority,
         })
     }
 }
 
-/// Parse a single policy token — either an identifier or `*`.
-fn parse_policy_token(input: ParseStream) -> Result<String> {
-    if input.peek(Token![*]) {
-        input.parse::<Token![*]>()?;
-        Ok("*".to_string())
-    } else {
-        let ident: Ident = input.parse()?;
-        Ok(ident.to_string())
+impl Parse for RoleTerm {
+    fn parse(input: ParseStream) -> Result<Self> {
+        if input.peek(Token![@]) {
+            input.parse::<Token![@]>()?;
+            Ok(RoleTerm::Group(input.parse()?))
+        } else {
+            Ok(RoleTerm::Token(input.parse()?))
+        }
+    }
+}
+
+/// Parse a single policy token — an identifier, `*`, or a string literal.
+impl Parse for PolicyToken {
+    fn parse(input: ParseStream) -> Result<Self> {
+        if input.peek(Token![*]) {
+            let star = input.parse::<Token![*]>()?;
+            Ok(PolicyToken {
+                value: "*".to_string(),
+                span: star.span,
+            })
+        } else if input.peek(LitStr) {
+            let lit: LitStr = input.parse()?;
+            Ok(PolicyToken {
+                value: lit.value(),
+                span: lit.span(),
+            })
+        } else {
+            let ident: Ident = input.parse()?;
+            Ok(PolicyToken {
+                value: ident.to_string(),
+                span: ident.span(),
+            })
+        }
     }
 }
 
-pub fn expand(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    let block = syn::parse_macro_input!(input as PolicyBlock);
+/// A rule with groups expanded and its condition validated.
+struct ResolvedRule {
+    allow: bool,
+    role: String,
+    action: String,
+    resource: String,
+    condition: Option<String>,
+    priority: u32,
+}
+
+/// Expands groups and includes into a flat rule list.
+struct Resolver {
+    groups: HashMap<String, String>,
+    rules: Vec<ResolvedRule>,
+    /// Canonical paths of every included file, for rebuild tracking.
+    included: Vec<PathBuf>,
+    /// Files currently being expanded, to detect include cycles.
+    stack: Vec<PathBuf>,
+}
+
+impl Resolver {
+    fn resolve(&mut self, statements: Vec<Statement>, base_dir: &Path) -> Result<()> {
+        for statement in statements {
+            match statement {
+                Statement::Rule(rule) => self.add_rule(rule)?,
+                Statement::Group(group) => {
+                    let name = group.name.to_string();
+                    if self.groups.contains_key(&name) {
+                        return Err(syn::Error::new_spanned(
+                            &group.name,
+                            format!("role group `{name}` is already defined"),
+                        ));
+                    }
+                    let members = group
+                        .members
+                        .iter()
+                        .map(|m| self.role(m))
+                        .collect::<Result<Vec<_>>>()?;
+                    self.groups.insert(name, members.join("|"));
+                }
+                Statement::Include(lit) => self.include(&lit, base_dir)?,
+            }
+        }
+        Ok(())
+    }
 
-    let rule_exprs: Vec<TokenStream> = block
+    fn add_rule(&mut self, rule: RuleDef) -> Result<()> {
+        let role = self.role(&rule.role)?;
+        check_pattern(&rule.action)?;
+        check_pattern(&rule.resource)?;
+        let condition = match &rule.condition {
+            Some(lit) => {
+                let source = lit.value();
+                Condition::parse(&source).map_err(|e| syn::Error::new(lit.span(), e))?;
+                Some(source)
+            }
+            None => None,
+        };
+        self.rules.push(ResolvedRule {
+            allow: rule.effect == "allow",
+            role,
+            action: rule.action.value,
+            resource: rule.resource.value,
+            condition,
+            priority: rule.priority,
+        });
+        Ok(())
+    }
+
+    /// Resolve a role term to the pattern string the runtime engine sees.
+    fn role(&self, term: &RoleTerm) -> Result<String> {
+        match term {
+            RoleTerm::Token(token) => {
+                check_pattern(token)?;
+                Ok(token.value.clone())
+            }
+            RoleTerm::Group(name) => self.groups.get(&name.to_string()).cloned().ok_or_else(|| {
+                syn::Error::new_spanned(
+                    name,
+                    format!(
+                        "unknown role group `{name}`; define it with `group {name} = [...];` first"
+                    ),
+                )
+            }),
+        }
+    }
+
+    fn include(&mut self, lit: &LitStr, base_dir: &Path) -> Result<()> {
+        let path = base_dir.join(lit.value());
+        let canonical = path.canonicalize().map_err(|e| {
+            syn::Error::new(
+                lit.span(),
+                format!("cannot read policy file {}: {e}", path.display()),
+            )
+        })?;
+        if self.stack.contains(&canonical) {
+            return Err(syn::Error::new(
+                lit.span(),
+                format!("include cycle through {}", canonical.display()),
+            ));
+        }
+        let source = std::fs::read_to_string(&canonical).map_err(|e| {
+            syn::Error::new(
+                lit.span(),
+                format!("cannot read policy file {}: {e}", canonical.display()),
+            )
+        })?;
+
+        // Spans inside the included file can't point into it, so errors
+        // from the file are reported on the include literal instead.
+        let display = lit.value();
+        let block: PolicyBlock = syn::parse_str(&source)
+            .map_err(|e| syn::Error::new(lit.span(), format!("{display}: {e}")))?;
+
+        if !self.included.contains(&canonical) {
+            self.included.push(canonical.clone());
+        }
+        self.stack.push(canonical.clone());
+        let nested_dir = canonical.parent().unwrap_or(Path::new("."));
+        let result = self
+            .resolve(block.statements, nested_dir)
+            .map_err(|e| syn::Error::new(lit.span(), format!("{display}: {e}")));
+        self.stack.pop();
+        result
+    }
+}
+
+/// Reject patterns the runtime engine would fail to compile (and then
+/// silently never match).
+fn check_pattern(token: &PolicyToken) -> Result<()> {
+    Pattern::parse(&token.value)
+        .map(|_| ())
+        .map_err(|e| syn::Error::new(token.span, format!("`{}`: {e}", token.value)))
+}
+
+fn expand_block(block: PolicyBlock) -> Result<TokenStream> {
+    let base_dir = std::env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."));
+
+    let mut resolver = Resolver {
+        groups: HashMap::new(),
+        rules: Vec::new(),
+        included: Vec::new(),
+        stack: Vec::new(),
+    };
+    resolver.resolve(block.statements, &base_dir)?;
+
+    let rule_exprs: Vec<TokenStream> = resolver
         .rules
         .iter()
         .map(|rule| {
@@ -108,21 +373,43 @@ pub fn expand(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
             let resource = &rule.resource;
             let priority = rule.priority;
 
-            let constructor = if rule.effect == "allow" {
+            let constructor = if rule.allow {
                 quote! { ::crustyclaw_config::policy::PolicyRule::allow(#role, #action, #resource) }
             } else {
                 quote! { ::crustyclaw_config::policy::PolicyRule::deny(#role, #action, #resource) }
             };
 
-            quote! { #constructor.with_priority(#priority) }
+            let condition = rule.condition.as_ref().map(|source| {
+                quote! {
+                    .with_condition(
+                        ::crustyclaw_config::policy::Condition::parse(#source)
+                            .expect("condition validated at compile time"),
+                    )
+                }
+            });
+
+            quote! { #constructor.with_priority(#priority) #condition }
         })
         .collect();
 
-    let expanded = quote! {
-        ::crustyclaw_config::policy::build_policy(
-            ::std::vec![#(#rule_exprs),*]
-        )
-    };
+    let tracked = resolver.included.iter().map(|path| {
+        let path = path.to_string_lossy();
+        quote! { const _: &[u8] = ::std::include_bytes!(#path); }
+    });
 
-    expanded.into()
+    Ok(quote! {
+        {
+            #(#tracked)*
+            ::crustyclaw_config::policy::build_policy(
+                ::std::vec![#(#rule_exprs),*]
+            )
+        }
+    })
+}
+
+pub fn expand(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let block = syn::parse_macro_input!(input as PolicyBlock);
+    expand_block(block)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
 }
@@ -2,11 +2,14 @@
 //!
 //! Parses `#[validate(...)]` attributes on struct fields and generates a
 //! `validate(&self) -> Result<(), Vec<String>>` method.
+//!
+//! `regex` patterns are compiled at expansion time so a malformed pattern is
+//! reported against the attribute literal rather than panicking at runtime.
 
 use proc_macro2::TokenStream;
-use quote::quote;
+use quote::{quote, quote_spanned};
 use syn::meta::ParseNestedMeta;
-use syn::{DeriveInput, LitInt, Result};
+use syn::{DeriveInput, LitInt, LitStr, Result};
 
 /// Parsed validation rules for a single field.
 struct FieldRules {
@@ -16,6 +19,9 @@ struct FieldRules {
     range_max: Option<i64>,
     min_len: Option<usize>,
     max_len: Option<usize>,
+    regex: Option<LitStr>,
+    custom: Option<(syn::Path, LitStr)>,
+    nested: bool,
 }
 
 impl FieldRules {
@@ -29,6 +35,9 @@ impl FieldRules {
             range_max: None,
             min_len: None,
             max_len: None,
+            regex: None,
+            custom: None,
+            nested: false,
         };
 
         let mut has_validate = false;
@@ -67,6 +76,34 @@ impl FieldRules {
             return Ok(());
         }
 
+        if meta.path.is_ident("nested") {
+            self.nested = true;
+            return Ok(());
+        }
+
+        if meta.path.is_ident("regex") {
+            let value = meta.value()?;
+            let lit: LitStr = value.parse()?;
+            if let Err(e) = regex::Regex::new(&lit.value()) {
+                return Err(syn::Error::new(lit.span(), format!("invalid regex: {e}")));
+            }
+            self.regex = Some(lit);
+            return Ok(());
+        }
+
+        if meta.path.is_ident("custom") {
+            let value = meta.value()?;
+            let lit: LitStr = value.parse()?;
+            let path: syn::Path = lit.parse().map_err(|_| {
+                syn::Error::new(
+                    lit.span(),
+                    "expected a function path, e.g. `custom = \"path::to::fn\"`",
+                )
+            })?;
+            self.custom = Some((path, lit));
+            return Ok(());
+        }
+
         if meta.path.is_ident("range") {
             meta.parse_nested_meta(|nested| {
                 if nested.path.is_ident("min") {
@@ -86,7 +123,7 @@ impl FieldRules {
             return Ok(());
         }
 
-        Err(meta.error("unknown validate rule; expected non_empty, range, min_len, or max_len"))
+        Err(meta.error("unknown validate rule; expected non_empty, range, min_len, max_len, regex, custom, or nested"))
     }
 
     fn generate_checks(&self) -> TokenStream {
@@ -134,6 +171,41 @@ impl FieldRules {
             });
         }
 
+        if let Some(pattern) = &self.regex {
+            checks.push(quote_spanned! {pattern.span()=>
+                {
+                    static RE: ::std::sync::OnceLock<::regex::Regex> = ::std::sync::OnceLock::new();
+                    let re = RE.get_or_init(|| {
+                        ::regex::Regex::new(#pattern).expect("pattern checked at compile time")
+                    });
+                    let value: &str = ::std::convert::AsRef::<str>::as_ref(&self.#field_name);
+                    if !re.is_match(value) {
+                        errors.push(format!("{}: must match /{}/", #field_str, #pattern));
+                    }
+                }
+            });
+        }
+
+        if let Some((path, lit)) = &self.custom {
+            checks.push(quote_spanned! {lit.span()=>
+                if let ::std::result::Result::Err(e) = #path(&self.#field_name) {
+                    errors.push(format!("{}: {}", #field_str, e));
+                }
+            });
+        }
+
+        if self.nested {
+            checks.push(quote! {
+                if let ::std::result::Result::Err(nested) = self.#field_name.validate() {
+                    errors.extend(
+                        nested
+                            .into_iter()
+                            .map(|e| format!("{}.{}", #field_str, e)),
+                    );
+                }
+            });
+        }
+
         quote! { #(#checks)* }
     }
 }
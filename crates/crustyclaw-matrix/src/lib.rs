@@ -0,0 +1,56 @@
+#![deny(unsafe_code)]
+#![recursion_limit = "256"]
+
+//! Matrix channel adapter for CrustyClaw.
+//!
+//! This crate bridges the CrustyClaw daemon and Matrix rooms. Messages posted
+//! in the rooms the bot serves are routed into the daemon's message bus, and
+//! outbound replies are sent back to the room they answer.
+//!
+//! ## Architecture
+//!
+//! - **Message types**: [`MatrixMessage`] is a text message received in a room.
+//! - **Transport**: [`MatrixTransport`] sends and receives room messages;
+//!   [`SdkTransport`] drives a `matrix-sdk` client that logs in from
+//!   `[matrix]`, keeps its session and end-to-end encryption keys in
+//!   `MatrixConfig.data_dir`, and runs the sync loop.
+//! - **Service runner**: [`MatrixService`] is the async task that bridges
+//!   room messages to and from the core daemon's message bus, applying the
+//!   `[matrix.rooms]` allow-list and `encrypted_only`.
+//!
+//! Envelopes use the `matrix` channel with the room ID as the peer, so
+//! replies go to the room and `[matrix.rooms."<room id>"] role` assigns a
+//! command role to everyone in it.
+
+/// Matrix message types.
+pub mod message;
+/// Async service bridging Matrix rooms to the daemon message bus.
+pub mod service;
+/// Message transports (matrix-sdk).
+pub mod transport;
+
+pub use message::MatrixMessage;
+pub use service::MatrixService;
+pub use transport::{MatrixCredentials, MatrixTransport, SdkTransport};
+
+/// Channel name of Matrix envelopes.
+pub const CHANNEL: &str = "matrix";
+
+/// Errors from the Matrix adapter.
+#[derive(Debug, thiserror::Error)]
+pub enum MatrixError {
+    #[error("invalid Matrix configuration: {0}")]
+    Config(String),
+
+    #[error("Matrix login failed: {0}")]
+    LoginFailed(String),
+
+    #[error("Matrix store error: {0}")]
+    Store(String),
+
+    #[error("message send failed: {0}")]
+    SendFailed(String),
+
+    #[error("message receive failed: {0}")]
+    ReceiveFailed(String),
+}
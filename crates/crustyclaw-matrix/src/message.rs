@@ -0,0 +1,33 @@
+//! Matrix message types.
+
+/// A text message received in a Matrix room.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatrixMessage {
+    /// Room the message was posted in (e.g. `!ops:example.org`).
+    pub room_id: String,
+
+    /// User who posted it (e.g. `@alice:example.org`).
+    pub sender: String,
+
+    /// Message text.
+    pub body: String,
+
+    /// Event ID of the message.
+    pub event_id: String,
+
+    /// Whether the room is end-to-end encrypted.
+    pub encrypted: bool,
+}
+
+impl MatrixMessage {
+    /// Create a text message in an encrypted room.
+    pub fn text(room_id: &str, sender: &str, body: &str) -> Self {
+        Self {
+            room_id: room_id.to_string(),
+            sender: sender.to_string(),
+            body: body.to_string(),
+            event_id: String::new(),
+            encrypted: true,
+        }
+    }
+}
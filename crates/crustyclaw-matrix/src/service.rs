@@ -0,0 +1,351 @@
+//! Matrix service — bridges room messages to and from the daemon message bus.
+//!
+//! Incoming messages become inbound envelopes on the `matrix` channel, with
+//! the room ID as the peer. Outbound envelopes on the channel run through
+//! the response pipeline and are sent to the room named by their peer.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use crustyclaw_config::MatrixConfig;
+use crustyclaw_core::daemon::ShutdownSignal;
+use crustyclaw_core::health::{ComponentStatus, HealthRegistry};
+use crustyclaw_core::message::{Direction, Envelope};
+use crustyclaw_core::response::ResponsePipeline;
+use tokio::sync::broadcast;
+use tracing::{debug, info, warn};
+
+use crate::message::MatrixMessage;
+use crate::transport::MatrixTransport;
+use crate::{CHANNEL, MatrixError};
+
+/// Bridges a [`MatrixTransport`] to the daemon's message bus.
+pub struct MatrixService {
+    /// Sender for the daemon's message bus.
+    bus_tx: broadcast::Sender<Envelope>,
+
+    /// Transport used to receive and deliver messages, if attached.
+    transport: Option<Arc<dyn MatrixTransport>>,
+
+    /// Rooms served; empty serves every joined room.
+    rooms: BTreeSet<String>,
+
+    /// Whether messages in unencrypted rooms are ignored.
+    encrypted_only: bool,
+
+    /// Hook pipeline applied to outbound messages before delivery, if attached.
+    responses: Option<Arc<ResponsePipeline>>,
+
+    /// Registry the connection state is reported to, if attached.
+    health: Option<Arc<HealthRegistry>>,
+}
+
+impl MatrixService {
+    /// Create a service for the rooms and encryption setting in `config`.
+    pub fn new(bus_tx: broadcast::Sender<Envelope>, config: &MatrixConfig) -> Self {
+        Self {
+            bus_tx,
+            transport: None,
+            rooms: config.rooms.keys().cloned().collect(),
+            encrypted_only: config.encrypted_only,
+            responses: None,
+            health: None,
+        }
+    }
+
+    /// Attach the transport messages are received from and sent through.
+    pub fn with_transport(mut self, transport: Arc<dyn MatrixTransport>) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    /// Run outbound messages through a response hook pipeline.
+    ///
+    /// Each part the pipeline returns is sent as its own message; a blocked
+    /// response is dropped.
+    pub fn with_response_pipeline(mut self, pipeline: Arc<ResponsePipeline>) -> Self {
+        self.responses = Some(pipeline);
+        self
+    }
+
+    /// Report the connection state as the `matrix` component.
+    pub fn with_health(mut self, health: Arc<HealthRegistry>) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    /// Run until shutdown.
+    ///
+    /// Fails if no transport is attached, or when the transport's incoming
+    /// stream closes, so a supervisor can restart the channel with a new
+    /// transport.
+    pub async fn run(
+        self,
+        mut shutdown_rx: broadcast::Receiver<ShutdownSignal>,
+    ) -> Result<(), MatrixError> {
+        let Some(transport) = self.transport.clone() else {
+            return Err(MatrixError::Config("no transport attached".to_string()));
+        };
+        let Some(mut incoming) = transport.take_incoming() else {
+            self.report(ComponentStatus::Down, "incoming stream unavailable");
+            return Err(MatrixError::ReceiveFailed(
+                "incoming stream already taken".to_string(),
+            ));
+        };
+        let mut outbound = self.bus_tx.subscribe();
+        self.report(
+            ComponentStatus::Up,
+            format!("syncing as {}", transport.user_id()),
+        );
+        info!(user = transport.user_id(), "Matrix service started");
+
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.recv() => break,
+                msg = incoming.recv() => match msg {
+                    Some(msg) => {
+                        self.process_inbound(&msg);
+                    }
+                    None => {
+                        warn!("Matrix sync stopped; no longer receiving messages");
+                        self.report(ComponentStatus::Down, "sync stopped");
+                        return Err(MatrixError::ReceiveFailed("sync stopped".to_string()));
+                    }
+                },
+                envelope = outbound.recv() => match envelope {
+                    Ok(envelope) => self.handle_outbound(transport.as_ref(), &envelope).await,
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!(skipped = n, "Matrix service fell behind the message bus");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            }
+        }
+
+        self.report(ComponentStatus::Down, "stopped");
+        info!("Matrix service stopped");
+        Ok(())
+    }
+
+    /// Publish `msg` on the bus if it comes from a served room.
+    ///
+    /// Returns whether it was published.
+    pub fn process_inbound(&self, msg: &MatrixMessage) -> bool {
+        if !self.rooms.is_empty() && !self.rooms.contains(&msg.room_id) {
+            debug!(room = %msg.room_id, "Ignoring message from unlisted Matrix room");
+            return false;
+        }
+        if self.encrypted_only && !msg.encrypted {
+            warn!(room = %msg.room_id, "Ignoring message from unencrypted Matrix room");
+            return false;
+        }
+        let envelope = Envelope::new(CHANNEL, &msg.body).with_peer(&msg.room_id);
+        debug!(room = %msg.room_id, sender = %msg.sender, id = envelope.id, "Matrix message received");
+        let _ = self.bus_tx.send(envelope);
+        true
+    }
+
+    /// Send an outbound `matrix` envelope to its room.
+    async fn handle_outbound(&self, transport: &dyn MatrixTransport, envelope: &Envelope) {
+        if envelope.channel != CHANNEL
+            || envelope.direction != Direction::Outbound
+            || envelope.is_redaction()
+        {
+            return;
+        }
+        let Some(room_id) = envelope.peer.as_deref() else {
+            warn!(id = envelope.id, "Outbound Matrix message has no room");
+            return;
+        };
+        let parts = match &self.responses {
+            Some(pipeline) => match pipeline.process(CHANNEL, Some(room_id), &envelope.body) {
+                Ok(parts) => parts,
+                Err(e) => {
+                    warn!(room = %room_id, error = %e, "Outbound Matrix message blocked");
+                    return;
+                }
+            },
+            None => vec![envelope.body.clone()],
+        };
+        for part in parts {
+            match transport.send(room_id, &part).await {
+                Ok(event_id) => {
+                    info!(room = %room_id, event = %event_id, "Outbound Matrix message delivered");
+                }
+                Err(e) => {
+                    warn!(room = %room_id, error = %e, "Outbound Matrix message failed");
+                    return;
+                }
+            }
+        }
+    }
+
+    fn report(&self, status: ComponentStatus, detail: impl Into<String>) {
+        if let Some(health) = &self.health {
+            health.report(CHANNEL, status, detail);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crustyclaw_config::{MatrixRoomConfig, ResponseConfig};
+    use crustyclaw_core::BoxFuture;
+    use crustyclaw_core::secrets::SecretStore;
+    use std::sync::{Mutex, RwLock};
+    use tokio::sync::mpsc;
+
+    struct MockTransport {
+        sent: Mutex<Vec<(String, String)>>,
+        incoming: Mutex<Option<mpsc::Receiver<MatrixMessage>>>,
+    }
+
+    impl MatrixTransport for MockTransport {
+        fn user_id(&self) -> &str {
+            "@crustyclaw:example.org"
+        }
+
+        fn send<'a>(
+            &'a self,
+            room_id: &'a str,
+            text: &'a str,
+        ) -> BoxFuture<'a, Result<String, MatrixError>> {
+            self.sent
+                .lock()
+                .unwrap()
+                .push((room_id.to_string(), text.to_string()));
+            Box::pin(async { Ok("$event".to_string()) })
+        }
+
+        fn take_incoming(&self) -> Option<mpsc::Receiver<MatrixMessage>> {
+            self.incoming.lock().unwrap().take()
+        }
+    }
+
+    fn mock_transport() -> (Arc<MockTransport>, mpsc::Sender<MatrixMessage>) {
+        let (tx, rx) = mpsc::channel(16);
+        let transport = Arc::new(MockTransport {
+            sent: Mutex::new(Vec::new()),
+            incoming: Mutex::new(Some(rx)),
+        });
+        (transport, tx)
+    }
+
+    fn config(rooms: &[&str], encrypted_only: bool) -> MatrixConfig {
+        MatrixConfig {
+            rooms: rooms
+                .iter()
+                .map(|room| (room.to_string(), MatrixRoomConfig::default()))
+                .collect(),
+            encrypted_only,
+            ..MatrixConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_inbound_room_filtering() {
+        let (bus_tx, mut bus_rx) = broadcast::channel(16);
+        let service = MatrixService::new(bus_tx, &config(&["!ops:example.org"], true));
+
+        let msg = MatrixMessage::text("!ops:example.org", "@alice:example.org", "status");
+        assert!(service.process_inbound(&msg));
+        let envelope = bus_rx.recv().await.unwrap();
+        assert_eq!(envelope.channel, "matrix");
+        assert_eq!(envelope.body, "status");
+        assert_eq!(envelope.direction, Direction::Inbound);
+        assert_eq!(envelope.peer.as_deref(), Some("!ops:example.org"));
+
+        let other = MatrixMessage::text("!lobby:example.org", "@alice:example.org", "hi");
+        assert!(!service.process_inbound(&other));
+        let plain = MatrixMessage {
+            encrypted: false,
+            ..msg
+        };
+        assert!(!service.process_inbound(&plain));
+        assert!(bus_rx.try_recv().is_err());
+
+        // With no rooms listed, every joined room is served.
+        let (bus_tx, _bus_rx) = broadcast::channel(16);
+        let service = MatrixService::new(bus_tx, &config(&[], false));
+        assert!(service.process_inbound(&other));
+        assert!(service.process_inbound(&plain));
+    }
+
+    #[tokio::test]
+    async fn test_run_bridges_both_directions() {
+        let (bus_tx, mut bus_rx) = broadcast::channel(16);
+        let (shutdown_tx, _) = broadcast::channel(1);
+        let (transport, incoming_tx) = mock_transport();
+        let health = Arc::new(HealthRegistry::new());
+        let responses = ResponseConfig {
+            hooks: vec!["split".to_string()],
+            max_message_chars: 5,
+            ..ResponseConfig::default()
+        };
+        let pipeline = Arc::new(ResponsePipeline::from_config(
+            &responses,
+            Arc::new(RwLock::new(SecretStore::new())),
+        ));
+        let service = MatrixService::new(bus_tx.clone(), &config(&[], false))
+            .with_transport(transport.clone())
+            .with_response_pipeline(pipeline)
+            .with_health(health.clone());
+        let task = tokio::spawn(service.run(shutdown_tx.subscribe()));
+
+        incoming_tx
+            .send(MatrixMessage::text(
+                "!ops:example.org",
+                "@alice:example.org",
+                "ping",
+            ))
+            .await
+            .unwrap();
+        let inbound = bus_rx.recv().await.unwrap();
+        assert_eq!(inbound.body, "ping");
+        let matrix = health.get("matrix").unwrap();
+        assert_eq!(matrix.status, ComponentStatus::Up);
+        assert_eq!(matrix.detail, "syncing as @crustyclaw:example.org");
+
+        // Replies on other channels are not sent.
+        bus_tx
+            .send(Envelope::new("signal", "x").with_peer("+1").reply("no"))
+            .unwrap();
+        bus_tx.send(inbound.reply("pong pong")).unwrap();
+        while transport.sent.lock().unwrap().len() < 2 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(
+            *transport.sent.lock().unwrap(),
+            [
+                ("!ops:example.org".to_string(), "pong".to_string()),
+                ("!ops:example.org".to_string(), "pong".to_string()),
+            ]
+        );
+
+        shutdown_tx.send(ShutdownSignal).unwrap();
+        task.await.unwrap().unwrap();
+        assert_eq!(health.get("matrix").unwrap().status, ComponentStatus::Down);
+    }
+
+    #[tokio::test]
+    async fn test_sync_stop_fails_the_service() {
+        let (bus_tx, _bus_rx) = broadcast::channel(16);
+        let (_shutdown_tx, shutdown_rx) = broadcast::channel(1);
+        let (transport, incoming_tx) = mock_transport();
+        let health = Arc::new(HealthRegistry::new());
+        let service = MatrixService::new(bus_tx, &config(&[], false))
+            .with_transport(transport)
+            .with_health(health.clone());
+        let task = tokio::spawn(service.run(shutdown_rx));
+
+        drop(incoming_tx);
+        assert!(matches!(
+            task.await.unwrap(),
+            Err(MatrixError::ReceiveFailed(_))
+        ));
+        let matrix = health.get("matrix").unwrap();
+        assert_eq!(matrix.status, ComponentStatus::Down);
+        assert_eq!(matrix.detail, "sync stopped");
+    }
+}
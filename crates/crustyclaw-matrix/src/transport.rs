@@ -0,0 +1,444 @@
+//! Matrix transport — message delivery and reception via `matrix-sdk`.
+//!
+//! [`SdkTransport::connect`] opens the SQLite state and crypto stores in
+//! `MatrixConfig.data_dir` (encrypted with the store passphrase, if set),
+//! then resumes the session saved in `session.json` there. Without one, it
+//! adopts the configured access token, or logs in with the password and
+//! saves the new session (mode `0600`) so later starts reuse the same device
+//! and its encryption keys.
+//!
+//! After an initial sync, which skips the room history, the sync loop runs
+//! in the background and delivers text messages from other users on a
+//! channel obtained once via [`MatrixTransport::take_incoming`]. Messages in
+//! encrypted rooms arrive decrypted. Invites to rooms listed in
+//! `[matrix.rooms]` are accepted. When the sync loop fails, the channel
+//! closes.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crustyclaw_config::MatrixConfig;
+use crustyclaw_core::BoxFuture;
+use crustyclaw_core::secrets::SecretStore;
+use matrix_sdk::authentication::matrix::MatrixSession;
+use matrix_sdk::config::SyncSettings;
+use matrix_sdk::ruma::events::room::member::StrippedRoomMemberEvent;
+use matrix_sdk::ruma::events::room::message::{
+    MessageType, OriginalSyncRoomMessageEvent, RoomMessageEventContent,
+};
+use matrix_sdk::ruma::{OwnedDeviceId, OwnedUserId, RoomId};
+use matrix_sdk::{Client, Room, RoomState, SessionMeta, SessionTokens};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::MatrixError;
+use crate::message::MatrixMessage;
+
+/// Capacity of the incoming message channel.
+const INCOMING_CAPACITY: usize = 256;
+
+/// Session file under `data_dir`.
+const SESSION_FILE: &str = "session.json";
+
+/// Display name of the device created by a password login.
+const DEVICE_NAME: &str = "CrustyClaw";
+
+/// A connection to a Matrix homeserver that can send and receive room
+/// messages.
+pub trait MatrixTransport: Send + Sync {
+    /// The bot's user ID.
+    fn user_id(&self) -> &str;
+
+    /// Send a text message to a room the bot has joined. Returns the event
+    /// ID of the sent message.
+    fn send<'a>(
+        &'a self,
+        room_id: &'a str,
+        text: &'a str,
+    ) -> BoxFuture<'a, Result<String, MatrixError>>;
+
+    /// Take the stream of incoming messages. Returns `None` if it has
+    /// already been taken.
+    fn take_incoming(&self) -> Option<mpsc::Receiver<MatrixMessage>>;
+}
+
+/// Secrets named by `[matrix]`, resolved from the secret store.
+#[derive(Default, Zeroize, ZeroizeOnDrop)]
+pub struct MatrixCredentials {
+    /// Account password (`password_secret`).
+    pub password: Option<String>,
+    /// Existing access token (`access_token_secret`).
+    pub access_token: Option<String>,
+    /// Store passphrase (`store_passphrase_secret`).
+    pub store_passphrase: Option<String>,
+}
+
+impl MatrixCredentials {
+    /// Names of the secrets `config` reads credentials from.
+    pub fn secret_names(config: &MatrixConfig) -> Vec<&str> {
+        [
+            &config.password_secret,
+            &config.access_token_secret,
+            &config.store_passphrase_secret,
+        ]
+        .into_iter()
+        .filter_map(|name| name.as_deref())
+        .collect()
+    }
+
+    /// Resolve the secrets `config` names from `store`.
+    pub fn from_store(config: &MatrixConfig, store: &SecretStore) -> Result<Self, MatrixError> {
+        let resolve = |key: &str, name: &Option<String>| -> Result<Option<String>, MatrixError> {
+            let Some(name) = name else {
+                return Ok(None);
+            };
+            store
+                .get(name)
+                .map(|entry| Some(entry.value.expose().to_string()))
+                .ok_or_else(|| {
+                    MatrixError::Config(format!("matrix.{key} {name:?} is not in the secret store"))
+                })
+        };
+        Ok(Self {
+            password: resolve("password_secret", &config.password_secret)?,
+            access_token: resolve("access_token_secret", &config.access_token_secret)?,
+            store_passphrase: resolve("store_passphrase_secret", &config.store_passphrase_secret)?,
+        })
+    }
+}
+
+/// [`MatrixTransport`] backed by a `matrix-sdk` client.
+pub struct SdkTransport {
+    client: Client,
+    user_id: String,
+    incoming: Mutex<Option<mpsc::Receiver<MatrixMessage>>>,
+    sync: JoinHandle<()>,
+}
+
+impl SdkTransport {
+    /// Log in as `config.user_id` and start syncing.
+    pub async fn connect(
+        config: &MatrixConfig,
+        credentials: &MatrixCredentials,
+    ) -> Result<Self, MatrixError> {
+        let homeserver = config
+            .homeserver_url
+            .as_deref()
+            .ok_or_else(|| MatrixError::Config("matrix.homeserver_url is not set".to_string()))?;
+        let user_id: OwnedUserId = config
+            .user_id
+            .as_deref()
+            .ok_or_else(|| MatrixError::Config("matrix.user_id is not set".to_string()))?
+            .try_into()
+            .map_err(|e| MatrixError::Config(format!("matrix.user_id: {e}")))?;
+
+        let data_dir = Path::new(&config.data_dir);
+        create_private_dir(data_dir)
+            .map_err(|e| MatrixError::Store(format!("{}: {e}", data_dir.display())))?;
+        let client = Client::builder()
+            .homeserver_url(homeserver)
+            .sqlite_store(
+                data_dir.join("store"),
+                credentials.store_passphrase.as_deref(),
+            )
+            .build()
+            .await
+            .map_err(|e| MatrixError::Store(e.to_string()))?;
+
+        restore_or_login(&client, config, credentials, &user_id).await?;
+        info!(user = %user_id, "Matrix session ready");
+
+        // Skip the room history: only messages sent from now on are routed.
+        let response = client
+            .sync_once(SyncSettings::default())
+            .await
+            .map_err(|e| MatrixError::ReceiveFailed(e.to_string()))?;
+
+        let (tx, rx) = mpsc::channel(INCOMING_CAPACITY);
+        let messages = client.add_event_handler({
+            let own_id = user_id.clone();
+            move |event: OriginalSyncRoomMessageEvent, room: Room| {
+                let (tx, own_id) = (tx.clone(), own_id.clone());
+                async move {
+                    if event.sender == own_id || room.state() != RoomState::Joined {
+                        return;
+                    }
+                    let MessageType::Text(text) = event.content.msgtype else {
+                        return;
+                    };
+                    let message = MatrixMessage {
+                        room_id: room.room_id().to_string(),
+                        sender: event.sender.to_string(),
+                        body: text.body,
+                        event_id: event.event_id.to_string(),
+                        encrypted: room.encryption_state().is_encrypted(),
+                    };
+                    let _ = tx.send(message).await;
+                }
+            }
+        });
+        let invites = client.add_event_handler({
+            let own_id = user_id.clone();
+            let rooms: Arc<BTreeSet<String>> = Arc::new(config.rooms.keys().cloned().collect());
+            move |event: StrippedRoomMemberEvent, room: Room| {
+                let (own_id, rooms) = (own_id.clone(), rooms.clone());
+                async move {
+                    if event.state_key != own_id || room.state() != RoomState::Invited {
+                        return;
+                    }
+                    let room_id = room.room_id().to_string();
+                    if !rooms.contains(&room_id) {
+                        info!(room = %room_id, inviter = %event.sender, "Ignoring invite to unlisted Matrix room");
+                        return;
+                    }
+                    match room.join().await {
+                        Ok(()) => info!(room = %room_id, "Joined Matrix room"),
+                        Err(e) => warn!(room = %room_id, error = %e, "Failed to join Matrix room"),
+                    }
+                }
+            }
+        });
+
+        let sync = tokio::spawn({
+            let client = client.clone();
+            let settings = SyncSettings::default().token(response.next_batch);
+            async move {
+                if let Err(e) = client.sync(settings).await {
+                    warn!(error = %e, "Matrix sync stopped");
+                }
+                // Dropping the handlers drops their senders, closing the
+                // incoming channel.
+                client.remove_event_handler(messages);
+                client.remove_event_handler(invites);
+            }
+        });
+
+        Ok(Self {
+            client,
+            user_id: user_id.to_string(),
+            incoming: Mutex::new(Some(rx)),
+            sync,
+        })
+    }
+}
+
+impl Drop for SdkTransport {
+    fn drop(&mut self) {
+        self.sync.abort();
+    }
+}
+
+impl MatrixTransport for SdkTransport {
+    fn user_id(&self) -> &str {
+        &self.user_id
+    }
+
+    fn send<'a>(
+        &'a self,
+        room_id: &'a str,
+        text: &'a str,
+    ) -> BoxFuture<'a, Result<String, MatrixError>> {
+        Box::pin(async move {
+            let room_id = <&RoomId>::try_from(room_id)
+                .map_err(|e| MatrixError::SendFailed(format!("{room_id}: {e}")))?;
+            let room = self
+                .client
+                .get_room(room_id)
+                .filter(|room| room.state() == RoomState::Joined)
+                .ok_or_else(|| MatrixError::SendFailed(format!("not joined to {room_id}")))?;
+            let result = room
+                .send(RoomMessageEventContent::text_plain(text))
+                .await
+                .map_err(|e| MatrixError::SendFailed(e.to_string()))?;
+            Ok(result.response.event_id.to_string())
+        })
+    }
+
+    fn take_incoming(&self) -> Option<mpsc::Receiver<MatrixMessage>> {
+        self.incoming
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+    }
+}
+
+/// Resume the saved session, adopt the configured access token, or log in
+/// with the password and save the new session.
+async fn restore_or_login(
+    client: &Client,
+    config: &MatrixConfig,
+    credentials: &MatrixCredentials,
+    user_id: &OwnedUserId,
+) -> Result<(), MatrixError> {
+    let session_path = session_path(config);
+    if let Some(session) = load_session(&session_path)? {
+        if session.meta.user_id != *user_id {
+            return Err(MatrixError::Config(format!(
+                "{} belongs to {}, not matrix.user_id {user_id}; remove it to log in again",
+                session_path.display(),
+                session.meta.user_id
+            )));
+        }
+        return client
+            .restore_session(session)
+            .await
+            .map_err(|e| MatrixError::LoginFailed(e.to_string()));
+    }
+
+    if let Some(access_token) = &credentials.access_token {
+        let device_id: OwnedDeviceId = config
+            .device_id
+            .as_deref()
+            .ok_or_else(|| {
+                MatrixError::Config("matrix.device_id is required with an access token".to_string())
+            })?
+            .into();
+        let session = MatrixSession {
+            meta: SessionMeta {
+                user_id: user_id.clone(),
+                device_id,
+            },
+            tokens: SessionTokens {
+                access_token: access_token.clone(),
+                refresh_token: None,
+            },
+        };
+        return client
+            .restore_session(session)
+            .await
+            .map_err(|e| MatrixError::LoginFailed(e.to_string()));
+    }
+
+    let password = credentials.password.as_deref().ok_or_else(|| {
+        MatrixError::Config("matrix needs password_secret or access_token_secret".to_string())
+    })?;
+    let auth = client.matrix_auth();
+    auth.login_username(user_id, password)
+        .initial_device_display_name(DEVICE_NAME)
+        .await
+        .map_err(|e| MatrixError::LoginFailed(e.to_string()))?;
+    let session = auth
+        .session()
+        .ok_or_else(|| MatrixError::LoginFailed("no session after login".to_string()))?;
+    save_session(&session_path, &session)?;
+    info!(user = %user_id, device = %session.meta.device_id, "Logged in to Matrix");
+    Ok(())
+}
+
+/// The session file for `config`.
+pub fn session_path(config: &MatrixConfig) -> PathBuf {
+    Path::new(&config.data_dir).join(SESSION_FILE)
+}
+
+fn load_session(path: &Path) -> Result<Option<MatrixSession>, MatrixError> {
+    match std::fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes)
+            .map(Some)
+            .map_err(|e| MatrixError::Store(format!("{}: {e}", path.display()))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(MatrixError::Store(format!("{}: {e}", path.display()))),
+    }
+}
+
+fn save_session(path: &Path, session: &MatrixSession) -> Result<(), MatrixError> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let mut json = serde_json::to_vec(session).unwrap_or_default();
+    let written = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+        .and_then(|mut file| file.write_all(&json));
+    json.zeroize();
+    written.map_err(|e| MatrixError::Store(format!("{}: {e}", path.display())))
+}
+
+/// Create `dir` (and parents) readable only by the owner.
+fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)?;
+    std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crustyclaw_core::secrets::{InjectionMethod, SecretEntry, SecretSource, SecretValue};
+
+    #[test]
+    fn test_credentials_from_store() {
+        let mut store = SecretStore::new();
+        store
+            .insert(
+                SecretEntry {
+                    name: "pw".to_string(),
+                    value: SecretValue::new("hunter22"),
+                    injection: InjectionMethod::Env("PW".to_string()),
+                    description: String::new(),
+                },
+                SecretSource::Config,
+            )
+            .unwrap();
+        let config = MatrixConfig {
+            password_secret: Some("pw".to_string()),
+            ..MatrixConfig::default()
+        };
+        let credentials = MatrixCredentials::from_store(&config, &store).unwrap();
+        assert_eq!(credentials.password.as_deref(), Some("hunter22"));
+        assert!(credentials.access_token.is_none());
+
+        let config = MatrixConfig {
+            store_passphrase_secret: Some("missing".to_string()),
+            ..config
+        };
+        assert_eq!(MatrixCredentials::secret_names(&config), ["pw", "missing"]);
+        assert!(matches!(
+            MatrixCredentials::from_store(&config, &store),
+            Err(MatrixError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn test_session_round_trip() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dir = tmp.path().join("matrix");
+        create_private_dir(&dir).unwrap();
+        let config = MatrixConfig {
+            data_dir: dir.display().to_string(),
+            ..MatrixConfig::default()
+        };
+        let path = session_path(&config);
+        assert!(load_session(&path).unwrap().is_none());
+
+        let session = MatrixSession {
+            meta: SessionMeta {
+                user_id: "@bot:example.org".try_into().unwrap(),
+                device_id: "DEVICE".into(),
+            },
+            tokens: SessionTokens {
+                access_token: "token".to_string(),
+                refresh_token: None,
+            },
+        };
+        save_session(&path, &session).unwrap();
+        let loaded = load_session(&path).unwrap().unwrap();
+        assert_eq!(loaded.meta, session.meta);
+        assert_eq!(loaded.tokens.access_token, "token");
+
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let mode = std::fs::metadata(&dir).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+    }
+}
@@ -2,10 +2,23 @@
 //!
 //! State transitions (`link`, `verify`) are async because a real Signal protocol
 //! implementation performs network I/O during account linking and verification.
+//!
+//! [`SignalAdapter::link`] links a new secondary device through a
+//! [`SignalProvisioner`]; [`SignalAdapter::restore`] resumes a device linked
+//! earlier whose session keys are stored in `SignalConfig.data_dir`.
+//!
+//! Only a `Verified` adapter can send and receive messages, through the
+//! [`SignalTransport`] attached with [`SignalAdapter::with_transport`].
+
+use std::path::Path;
+use std::sync::Arc;
 
+use tokio::sync::mpsc;
 use tracing::info;
 
 use crate::SignalError;
+use crate::message::SignalMessage;
+use crate::transport::{SignalProvisioner, SignalTransport};
 
 /// Signal adapter session states.
 pub mod session {
@@ -30,6 +43,15 @@ pub mod session {
 /// first going through `Unlinked → Linked → Verified`.
 pub struct SignalAdapter<S> {
     state: S,
+    transport: Option<Arc<dyn SignalTransport>>,
+}
+
+impl<S> SignalAdapter<S> {
+    /// Attach the transport used once the adapter is verified.
+    pub fn with_transport(mut self, transport: Arc<dyn SignalTransport>) -> Self {
+        self.transport = Some(transport);
+        self
+    }
 }
 
 impl SignalAdapter<session::Unlinked> {
@@ -38,21 +60,65 @@ impl SignalAdapter<session::Unlinked> {
         info!("Creating new Signal adapter (unlinked)");
         Self {
             state: session::Unlinked,
+            transport: None,
         }
     }
 
-    /// Link to a Signal account. Returns the adapter in `Linked` state.
+    /// Link as a new secondary device of an existing Signal account.
     ///
-    /// This is async because the real Signal protocol performs network I/O
-    /// during the linking handshake.
+    /// `show_uri` receives the provisioning URI, which the operator scans
+    /// from the primary device (Settings → Linked devices), usually rendered
+    /// with [`crate::qr::QrCode`]. Resolves once the primary device approves;
+    /// the provisioner persists the session keys before returning.
     pub async fn link(
         self,
+        provisioner: &dyn SignalProvisioner,
+        device_name: &str,
+        show_uri: impl FnOnce(&str),
+    ) -> Result<SignalAdapter<session::Linked>, SignalError> {
+        info!(device = %device_name, "Linking Signal device");
+        let uri = provisioner.start_link().await?;
+        show_uri(&uri);
+        let phone_number = provisioner.finish_link(&uri, device_name).await?;
+        info!(phone = %phone_number, "Signal device linked");
+        Ok(SignalAdapter {
+            state: session::Linked { phone_number },
+            transport: self.transport,
+        })
+    }
+
+    /// Resume the previously linked account `phone_number`, whose session is
+    /// stored by `signal-cli` under `data_dir`.
+    pub async fn restore(
+        self,
+        data_dir: &Path,
         phone_number: String,
     ) -> Result<SignalAdapter<session::Linked>, SignalError> {
-        info!(phone = %phone_number, "Linking Signal account");
-        // TODO: actual Signal protocol linking handshake (network I/O)
+        info!(phone = %phone_number, "Restoring Signal session");
+        let accounts = data_dir.join("data").join("accounts.json");
+        let not_linked = || {
+            SignalError::LinkingFailed(format!(
+                "{phone_number} is not linked in {}; run `crustyclaw-cli signal link`",
+                data_dir.display()
+            ))
+        };
+        let contents = tokio::fs::read_to_string(&accounts)
+            .await
+            .map_err(|_| not_linked())?;
+        let index: serde_json::Value = serde_json::from_str(&contents).map_err(|e| {
+            SignalError::LinkingFailed(format!("invalid {}: {e}", accounts.display()))
+        })?;
+        let linked = index["accounts"].as_array().is_some_and(|accounts| {
+            accounts
+                .iter()
+                .any(|a| a["number"].as_str() == Some(phone_number.as_str()))
+        });
+        if !linked {
+            return Err(not_linked());
+        }
         Ok(SignalAdapter {
             state: session::Linked { phone_number },
+            transport: self.transport,
         })
     }
 }
@@ -75,6 +141,7 @@ impl SignalAdapter<session::Linked> {
             state: session::Verified {
                 phone_number: self.state.phone_number,
             },
+            transport: self.transport,
         })
     }
 
@@ -89,21 +156,139 @@ impl SignalAdapter<session::Verified> {
     pub fn phone_number(&self) -> &str {
         &self.state.phone_number
     }
+
+    /// Send a text message to a phone number or UUID.
+    ///
+    /// Returns the Signal timestamp of the sent message.
+    pub async fn send(&self, recipient: &str, text: &str) -> Result<u64, SignalError> {
+        let transport = self
+            .transport
+            .as_ref()
+            .ok_or_else(|| SignalError::SendFailed("no transport attached".to_string()))?;
+        transport.send(recipient, text).await
+    }
+
+    /// Send a read receipt for `sender`'s message sent at `timestamp`.
+    pub async fn send_receipt(&self, sender: &str, timestamp: u64) -> Result<(), SignalError> {
+        let transport = self
+            .transport
+            .as_ref()
+            .ok_or_else(|| SignalError::SendFailed("no transport attached".to_string()))?;
+        transport.send_receipt(sender, timestamp).await
+    }
+
+    /// Start or stop showing `recipient` a typing indicator.
+    pub async fn send_typing(&self, recipient: &str, typing: bool) -> Result<(), SignalError> {
+        let transport = self
+            .transport
+            .as_ref()
+            .ok_or_else(|| SignalError::SendFailed("no transport attached".to_string()))?;
+        transport.send_typing(recipient, typing).await
+    }
+
+    /// Take the stream of incoming messages.
+    ///
+    /// The stream can only be taken once; it ends when the transport closes.
+    pub fn incoming(&self) -> Result<mpsc::Receiver<SignalMessage>, SignalError> {
+        self.transport
+            .as_ref()
+            .ok_or_else(|| SignalError::ReceiveFailed("no transport attached".to_string()))?
+            .take_incoming()
+            .ok_or_else(|| SignalError::ReceiveFailed("incoming stream already taken".to_string()))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crustyclaw_core::BoxFuture;
+
+    /// Provisioner that approves immediately as `+1234567890`.
+    struct MockProvisioner;
+
+    impl SignalProvisioner for MockProvisioner {
+        fn start_link(&self) -> BoxFuture<'_, Result<String, SignalError>> {
+            Box::pin(async { Ok("sgnl://linkdevice?uuid=u&pub_key=k".to_string()) })
+        }
+
+        fn finish_link<'a>(
+            &'a self,
+            uri: &'a str,
+            device_name: &'a str,
+        ) -> BoxFuture<'a, Result<String, SignalError>> {
+            Box::pin(async move {
+                assert_eq!(uri, "sgnl://linkdevice?uuid=u&pub_key=k");
+                assert_eq!(device_name, "crustyclaw");
+                Ok("+1234567890".to_string())
+            })
+        }
+    }
+
+    async fn verified() -> SignalAdapter<session::Verified> {
+        SignalAdapter::new()
+            .link(&MockProvisioner, "crustyclaw", |_| {})
+            .await
+            .unwrap()
+            .verify()
+            .await
+            .unwrap()
+    }
 
     #[tokio::test]
     async fn test_type_state_lifecycle() {
         let adapter = SignalAdapter::new();
-        let linked = adapter.link("+1234567890".to_string()).await.unwrap();
+        let mut shown = None;
+        let linked = adapter
+            .link(&MockProvisioner, "crustyclaw", |uri| {
+                shown = Some(uri.to_string())
+            })
+            .await
+            .unwrap();
+        assert_eq!(shown.as_deref(), Some("sgnl://linkdevice?uuid=u&pub_key=k"));
         assert_eq!(linked.phone_number(), "+1234567890");
         let verified = linked.verify().await.unwrap();
         assert_eq!(verified.phone_number(), "+1234567890");
     }
 
+    #[tokio::test]
+    async fn test_restore_requires_stored_session() {
+        let tmp = tempfile::tempdir().unwrap();
+        let err = SignalAdapter::new()
+            .restore(tmp.path(), "+1234567890".to_string())
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("signal link"));
+
+        std::fs::create_dir(tmp.path().join("data")).unwrap();
+        std::fs::write(
+            tmp.path().join("data/accounts.json"),
+            r#"{"accounts":[{"path":"123","number":"+1234567890"}],"version":2}"#,
+        )
+        .unwrap();
+        let linked = SignalAdapter::new()
+            .restore(tmp.path(), "+1234567890".to_string())
+            .await
+            .unwrap();
+        assert_eq!(linked.phone_number(), "+1234567890");
+        assert!(
+            SignalAdapter::new()
+                .restore(tmp.path(), "+15550000".to_string())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verified_send_requires_transport() {
+        let verified = verified().await;
+        assert!(matches!(
+            verified.send("+1", "hi").await,
+            Err(SignalError::SendFailed(_))
+        ));
+        assert!(verified.incoming().is_err());
+    }
+
     #[test]
     fn test_default() {
         let _adapter = SignalAdapter::default();
@@ -14,6 +14,12 @@
 //!   the Signal messaging domain.
 //! - **Service runner**: [`SignalService`] is the async task that bridges Signal
 //!   messages to/from the core daemon's message bus.
+//! - **Transport**: [`SignalTransport`] sends and receives messages for a
+//!   verified adapter; [`SignalCliTransport`] drives `signal-cli` in JSON-RPC
+//!   mode against the account stored in `SignalConfig.data_dir`.
+//! - **Device linking**: [`SignalAdapter::link`] links CrustyClaw as a
+//!   secondary device through a [`SignalProvisioner`], showing the
+//!   provisioning URI as a [`qr::QrCode`] for the primary device to scan.
 //! - **Rate limiter**: [`RateLimiter`] protects against abuse with a token-bucket
 //!   algorithm.
 
@@ -21,15 +27,20 @@
 pub mod adapter;
 /// Signal message, attachment, and group types.
 pub mod message;
+/// QR code encoding for device-link URIs.
+pub mod qr;
 /// Token-bucket rate limiter for abuse protection.
 pub mod rate_limit;
 /// Async service bridging Signal to the daemon message bus.
 pub mod service;
+/// Message transports (signal-cli JSON-RPC).
+pub mod transport;
 
 pub use adapter::SignalAdapter;
 pub use message::{Attachment, GroupInfo, SignalMessage};
 pub use rate_limit::RateLimiter;
-pub use service::SignalService;
+pub use service::{PresenceConfig, SignalService};
+pub use transport::{SignalCliTransport, SignalProvisioner, SignalTransport};
 
 /// Errors from the Signal adapter.
 #[derive(Debug, thiserror::Error)]
@@ -49,6 +60,9 @@ pub enum SignalError {
     #[error("rate limited: {0}")]
     RateLimited(String),
 
+    #[error("sender not allowed: {0}")]
+    SenderNotAllowed(String),
+
     #[error("unsupported media type: {0}")]
     UnsupportedMedia(String),
 
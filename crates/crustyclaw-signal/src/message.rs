@@ -2,6 +2,8 @@
 
 use std::time::SystemTime;
 
+use crustyclaw_core::workspace::sanitize_name;
+
 /// A message received from or sent via Signal.
 #[derive(Debug, Clone)]
 pub struct SignalMessage {
@@ -22,6 +24,10 @@ pub struct SignalMessage {
 
     /// Attached media files.
     pub attachments: Vec<Attachment>,
+
+    /// For a remote delete, the timestamp (milliseconds since the epoch) of
+    /// the sender's earlier message they asked to delete.
+    pub remote_delete: Option<u64>,
 }
 
 impl SignalMessage {
@@ -34,6 +40,7 @@ impl SignalMessage {
             timestamp: SystemTime::now(),
             group: None,
             attachments: Vec::new(),
+            remote_delete: None,
         }
     }
 
@@ -46,6 +53,7 @@ impl SignalMessage {
             timestamp: SystemTime::now(),
             group: None,
             attachments: Vec::new(),
+            remote_delete: None,
         }
     }
 
@@ -54,10 +62,27 @@ impl SignalMessage {
         self.group.is_some()
     }
 
+    /// The message timestamp in milliseconds since the epoch, as Signal
+    /// identifies messages in receipts and remote deletes.
+    pub fn timestamp_millis(&self) -> u64 {
+        self.timestamp
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default()
+    }
+
     /// Whether this message has attachments.
     pub fn has_attachments(&self) -> bool {
         !self.attachments.is_empty()
     }
+
+    /// Workspace conversation ID: one per group, or one per direct sender.
+    pub fn conversation_id(&self) -> String {
+        match &self.group {
+            Some(group) => sanitize_name(&format!("signal-group-{}", group.id)),
+            None => sanitize_name(&format!("signal-{}", self.sender)),
+        }
+    }
 }
 
 /// Information about a Signal group.
@@ -184,6 +209,23 @@ mod tests {
         assert!(msg.has_attachments());
     }
 
+    #[test]
+    fn test_conversation_id() {
+        let direct = SignalMessage::text("+15550001", "hi");
+        assert_eq!(direct.conversation_id(), "signal-_15550001");
+
+        let mut group = SignalMessage::text("+15550001", "hi");
+        group.group = Some(GroupInfo::new("ab+c/d==", "Ops"));
+        assert_eq!(group.conversation_id(), "signal-group-ab_c_d__");
+    }
+
+    #[test]
+    fn test_timestamp_millis() {
+        let mut msg = SignalMessage::text("+1", "hi");
+        msg.timestamp = SystemTime::UNIX_EPOCH + std::time::Duration::from_millis(1700000000123);
+        assert_eq!(msg.timestamp_millis(), 1700000000123);
+    }
+
     #[test]
     fn test_group_message() {
         let mut msg = SignalMessage::text("+1", "hello group");
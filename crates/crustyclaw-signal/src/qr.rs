@@ -0,0 +1,557 @@
+//! Minimal QR code encoder for showing device-link URIs in a terminal.
+//!
+//! Encodes arbitrary bytes in byte mode at error-correction level M, picking
+//! the smallest version (1–40) that fits and the mask with the lowest
+//! penalty score. [`QrCode::to_terminal_string`] renders two module rows per
+//! text line with Unicode half blocks, so the code stays square in a typical
+//! terminal font.
+
+/// Error-correction codewords per block, indexed by version (level M).
+const ECC_CODEWORDS_PER_BLOCK: [usize; 41] = [
+    0, 10, 16, 26, 18, 24, 16, 18, 22, 22, 26, 30, 22, 22, 24, 24, 28, 28, 26, 26, 26, 26, 28, 28,
+    28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28,
+];
+
+/// Number of error-correction blocks, indexed by version (level M).
+const NUM_ECC_BLOCKS: [usize; 41] = [
+    0, 1, 1, 1, 2, 2, 4, 4, 4, 5, 5, 5, 8, 9, 9, 10, 10, 11, 13, 14, 16, 17, 17, 18, 20, 21, 23,
+    25, 26, 28, 29, 31, 33, 35, 37, 38, 40, 43, 45, 47, 49,
+];
+
+/// Format-information bits for error-correction level M.
+const ECC_LEVEL_M_BITS: u32 = 0b00;
+
+/// Light modules around the symbol required by scanners.
+const QUIET_ZONE: usize = 2;
+
+/// An encoded QR symbol.
+#[derive(Debug, Clone)]
+pub struct QrCode {
+    version: usize,
+    size: usize,
+    /// Module colours, row-major; `true` is dark.
+    modules: Vec<bool>,
+    /// Modules occupied by function patterns, which masking must skip.
+    function: Vec<bool>,
+}
+
+impl QrCode {
+    /// Encode `data`, returning `None` if it exceeds the capacity of a
+    /// version 40 symbol.
+    pub fn encode(data: &[u8]) -> Option<Self> {
+        let version = (1..=40).find(|&v| {
+            let count_bits = if v < 10 { 8 } else { 16 };
+            data.len() < (1 << count_bits)
+                && 4 + count_bits + data.len() * 8 <= data_codewords(v) * 8
+        })?;
+
+        let mut code = Self::blank(version);
+        code.draw_function_patterns();
+        code.draw_codewords(&add_ecc_and_interleave(version, &data_bits(version, data)));
+
+        let mask = (0..8)
+            .min_by_key(|&mask| {
+                code.apply_mask(mask);
+                code.draw_format_bits(mask);
+                let penalty = code.penalty();
+                code.apply_mask(mask); // XOR again to undo
+                penalty
+            })
+            .unwrap_or(0);
+        code.apply_mask(mask);
+        code.draw_format_bits(mask);
+        Some(code)
+    }
+
+    /// The symbol version (1–40).
+    pub fn version(&self) -> usize {
+        self.version
+    }
+
+    /// Width and height in modules, excluding the quiet zone.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Whether the module at column `x`, row `y` is dark. Coordinates
+    /// outside the symbol are light.
+    pub fn is_dark(&self, x: usize, y: usize) -> bool {
+        x < self.size && y < self.size && self.modules[y * self.size + x]
+    }
+
+    /// Render the symbol, including its quiet zone, as lines of half-block
+    /// characters.
+    ///
+    /// Light modules are drawn as filled blocks and dark modules as blanks,
+    /// which scans correctly on the light-on-dark colour scheme of most
+    /// terminals.
+    pub fn to_terminal_string(&self) -> String {
+        let span = self.size + 2 * QUIET_ZONE;
+        let light = |x: usize, y: usize| {
+            let inside = |c: usize| (QUIET_ZONE..QUIET_ZONE + self.size).contains(&c);
+            !(inside(x) && inside(y) && self.is_dark(x - QUIET_ZONE, y - QUIET_ZONE))
+        };
+
+        let mut out = String::with_capacity(span * span.div_ceil(2) * 3);
+        for y in (0..span).step_by(2) {
+            for x in 0..span {
+                let top = light(x, y);
+                let bottom = y + 1 < span && light(x, y + 1);
+                out.push(match (top, bottom) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                });
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    fn blank(version: usize) -> Self {
+        let size = version * 4 + 17;
+        Self {
+            version,
+            size,
+            modules: vec![false; size * size],
+            function: vec![false; size * size],
+        }
+    }
+
+    fn set_function(&mut self, x: usize, y: usize, dark: bool) {
+        let i = y * self.size + x;
+        self.modules[i] = dark;
+        self.function[i] = true;
+    }
+
+    fn draw_function_patterns(&mut self) {
+        let size = self.size;
+        for i in 0..size {
+            self.set_function(6, i, i % 2 == 0);
+            self.set_function(i, 6, i % 2 == 0);
+        }
+
+        self.draw_finder(3, 3);
+        self.draw_finder(size - 4, 3);
+        self.draw_finder(3, size - 4);
+
+        let positions = alignment_positions(self.version);
+        let last = positions.len().saturating_sub(1);
+        for (i, &x) in positions.iter().enumerate() {
+            for (j, &y) in positions.iter().enumerate() {
+                // Skip the three corners occupied by finder patterns.
+                if (i == 0 && (j == 0 || j == last)) || (i == last && j == 0) {
+                    continue;
+                }
+                self.draw_alignment(x, y);
+            }
+        }
+
+        // Reserve the format areas; the real bits are drawn after masking.
+        self.draw_format_bits(0);
+        self.draw_version_bits();
+    }
+
+    fn draw_finder(&mut self, cx: usize, cy: usize) {
+        for dy in -4i32..=4 {
+            for dx in -4i32..=4 {
+                let (x, y) = (cx as i32 + dx, cy as i32 + dy);
+                if (0..self.size as i32).contains(&x) && (0..self.size as i32).contains(&y) {
+                    let dist = dx.abs().max(dy.abs());
+                    self.set_function(x as usize, y as usize, dist != 2 && dist != 4);
+                }
+            }
+        }
+    }
+
+    fn draw_alignment(&mut self, cx: usize, cy: usize) {
+        for dy in -2i32..=2 {
+            for dx in -2i32..=2 {
+                let dark = dx.abs().max(dy.abs()) != 1;
+                self.set_function((cx as i32 + dx) as usize, (cy as i32 + dy) as usize, dark);
+            }
+        }
+    }
+
+    fn draw_format_bits(&mut self, mask: u32) {
+        let data = (ECC_LEVEL_M_BITS << 3) | mask;
+        let bits = format_bits(data);
+        let bit = |i: u32| (bits >> i) & 1 != 0;
+        let size = self.size;
+
+        // Copy around the top-left finder.
+        for i in 0..=5 {
+            self.set_function(8, i as usize, bit(i));
+        }
+        self.set_function(8, 7, bit(6));
+        self.set_function(8, 8, bit(7));
+        self.set_function(7, 8, bit(8));
+        for i in 9..15 {
+            self.set_function(14 - i as usize, 8, bit(i));
+        }
+
+        // Copy split between the other two finders.
+        for i in 0..8 {
+            self.set_function(size - 1 - i as usize, 8, bit(i));
+        }
+        for i in 8..15 {
+            self.set_function(8, size - 15 + i as usize, bit(i));
+        }
+        self.set_function(8, size - 8, true); // always-dark module
+    }
+
+    fn draw_version_bits(&mut self) {
+        if self.version < 7 {
+            return;
+        }
+        let version = self.version as u32;
+        let mut rem = version;
+        for _ in 0..12 {
+            rem = (rem << 1) ^ ((rem >> 11) * 0x1F25);
+        }
+        let bits = (version << 12) | rem;
+        for i in 0..18 {
+            let dark = (bits >> i) & 1 != 0;
+            let a = self.size - 11 + i % 3;
+            let b = i / 3;
+            self.set_function(a, b, dark);
+            self.set_function(b, a, dark);
+        }
+    }
+
+    /// Place codewords in the zig-zag column-pair order, skipping function
+    /// modules.
+    fn draw_codewords(&mut self, codewords: &[u8]) {
+        let size = self.size;
+        let total_bits = codewords.len() * 8;
+        let mut i = 0;
+        let mut right = size - 1;
+        loop {
+            if right == 6 {
+                right = 5; // skip the vertical timing pattern
+            }
+            for vert in 0..size {
+                for j in 0..2 {
+                    let x = right - j;
+                    let upward = (right + 1) & 2 == 0;
+                    let y = if upward { size - 1 - vert } else { vert };
+                    let idx = y * size + x;
+                    if !self.function[idx] && i < total_bits {
+                        self.modules[idx] = (codewords[i >> 3] >> (7 - (i & 7))) & 1 != 0;
+                        i += 1;
+                    }
+                }
+            }
+            if right < 2 {
+                break;
+            }
+            right -= 2;
+        }
+    }
+
+    /// XOR the data modules with mask pattern `mask`; applying it twice
+    /// restores the original.
+    fn apply_mask(&mut self, mask: u32) {
+        for y in 0..self.size {
+            for x in 0..self.size {
+                let invert = match mask {
+                    0 => (x + y) % 2 == 0,
+                    1 => y % 2 == 0,
+                    2 => x % 3 == 0,
+                    3 => (x + y) % 3 == 0,
+                    4 => (x / 3 + y / 2) % 2 == 0,
+                    5 => x * y % 2 + x * y % 3 == 0,
+                    6 => (x * y % 2 + x * y % 3) % 2 == 0,
+                    _ => ((x + y) % 2 + x * y % 3) % 2 == 0,
+                };
+                let idx = y * self.size + x;
+                if invert && !self.function[idx] {
+                    self.modules[idx] = !self.modules[idx];
+                }
+            }
+        }
+    }
+
+    /// Penalty score from the spec's four mask-evaluation rules.
+    fn penalty(&self) -> usize {
+        let size = self.size;
+        let row = |y: usize| {
+            (0..size)
+                .map(move |x| self.is_dark(x, y))
+                .collect::<Vec<_>>()
+        };
+        let col = |x: usize| {
+            (0..size)
+                .map(move |y| self.is_dark(x, y))
+                .collect::<Vec<_>>()
+        };
+        let lines = (0..size).map(row).chain((0..size).map(col));
+
+        let finder_like = [
+            true, false, true, true, true, false, true, false, false, false, false,
+        ];
+        let mut penalty = 0;
+        for line in lines {
+            // Rule 1: runs of five or more same-coloured modules.
+            let mut run = 1;
+            for i in 1..=size {
+                if i < size && line[i] == line[i - 1] {
+                    run += 1;
+                } else {
+                    if run >= 5 {
+                        penalty += run - 2;
+                    }
+                    run = 1;
+                }
+            }
+            // Rule 3: 1:1:3:1:1 finder-like patterns with light space on a side.
+            for window in line.windows(finder_like.len()) {
+                let forward = window == finder_like;
+                let backward = window.iter().rev().eq(finder_like.iter());
+                if forward || backward {
+                    penalty += 40;
+                }
+            }
+        }
+
+        // Rule 2: 2x2 blocks of the same colour.
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let c = self.is_dark(x, y);
+                if c == self.is_dark(x + 1, y)
+                    && c == self.is_dark(x, y + 1)
+                    && c == self.is_dark(x + 1, y + 1)
+                {
+                    penalty += 3;
+                }
+            }
+        }
+
+        // Rule 4: deviation of the dark proportion from 50%, in 5% steps.
+        let total = size * size;
+        let dark = self.modules.iter().filter(|&&m| m).count();
+        let deviation = (dark * 20).abs_diff(total * 10);
+        penalty += deviation.div_ceil(total).saturating_sub(1) * 10;
+        penalty
+    }
+}
+
+/// Number of data modules available in a symbol of `version`.
+fn raw_data_modules(version: usize) -> usize {
+    let mut modules = (16 * version + 128) * version + 64;
+    if version >= 2 {
+        let num_align = version / 7 + 2;
+        modules -= (25 * num_align - 10) * num_align - 55;
+        if version >= 7 {
+            modules -= 36;
+        }
+    }
+    modules
+}
+
+/// Number of data (non-ECC) codewords in a symbol of `version`.
+fn data_codewords(version: usize) -> usize {
+    raw_data_modules(version) / 8 - ECC_CODEWORDS_PER_BLOCK[version] * NUM_ECC_BLOCKS[version]
+}
+
+/// Centre coordinates of the alignment patterns for `version`.
+fn alignment_positions(version: usize) -> Vec<usize> {
+    if version == 1 {
+        return Vec::new();
+    }
+    let num_align = version / 7 + 2;
+    let step = (version * 8 + num_align * 3 + 5) / (num_align * 4 - 4) * 2;
+    let size = version * 4 + 17;
+    let mut positions: Vec<usize> = (0..num_align - 1).map(|i| size - 7 - i * step).collect();
+    positions.push(6);
+    positions.reverse();
+    positions
+}
+
+/// Format information (5 data bits plus BCH code, masked) for `data`.
+fn format_bits(data: u32) -> u32 {
+    let mut rem = data;
+    for _ in 0..10 {
+        rem = (rem << 1) ^ ((rem >> 9) * 0x537);
+    }
+    ((data << 10) | rem) ^ 0x5412
+}
+
+/// Byte-mode segment, terminator, and padding, packed into data codewords.
+fn data_bits(version: usize, data: &[u8]) -> Vec<u8> {
+    let capacity = data_codewords(version) * 8;
+    let count_bits = if version < 10 { 8 } else { 16 };
+
+    fn push(bits: &mut Vec<bool>, value: usize, len: usize) {
+        bits.extend((0..len).rev().map(|i| (value >> i) & 1 != 0));
+    }
+    let mut bits = Vec::with_capacity(capacity);
+    push(&mut bits, 0b0100, 4);
+    push(&mut bits, data.len(), count_bits);
+    for &byte in data {
+        push(&mut bits, byte as usize, 8);
+    }
+    let terminator = (capacity - bits.len()).min(4);
+    push(&mut bits, 0, terminator);
+    let pad = (8 - bits.len() % 8) % 8;
+    push(&mut bits, 0, pad);
+
+    let mut codewords: Vec<u8> = bits
+        .chunks(8)
+        .map(|chunk| chunk.iter().fold(0u8, |acc, &b| (acc << 1) | b as u8))
+        .collect();
+    for pad in [0xEC, 0x11].into_iter().cycle() {
+        if codewords.len() * 8 >= capacity {
+            break;
+        }
+        codewords.push(pad);
+    }
+    codewords
+}
+
+/// Split data into blocks, append Reed-Solomon ECC to each, and interleave.
+fn add_ecc_and_interleave(version: usize, data: &[u8]) -> Vec<u8> {
+    let num_blocks = NUM_ECC_BLOCKS[version];
+    let ecc_len = ECC_CODEWORDS_PER_BLOCK[version];
+    let raw_codewords = raw_data_modules(version) / 8;
+    let num_short = num_blocks - raw_codewords % num_blocks;
+    let short_len = raw_codewords / num_blocks;
+
+    let divisor = rs_divisor(ecc_len);
+    let mut blocks = Vec::with_capacity(num_blocks);
+    let mut offset = 0;
+    for i in 0..num_blocks {
+        let data_len = short_len - ecc_len + usize::from(i >= num_short);
+        let mut block = data[offset..offset + data_len].to_vec();
+        offset += data_len;
+        let ecc = rs_remainder(&block, &divisor);
+        if i < num_short {
+            block.push(0); // placeholder keeping all blocks the same length
+        }
+        block.extend(ecc);
+        blocks.push(block);
+    }
+
+    let mut result = Vec::with_capacity(raw_codewords);
+    for i in 0..blocks[0].len() {
+        for (j, block) in blocks.iter().enumerate() {
+            if i != short_len - ecc_len || j >= num_short {
+                result.push(block[i]);
+            }
+        }
+    }
+    result
+}
+
+/// Generator polynomial of `degree` for Reed-Solomon over GF(256),
+/// highest-order coefficient (always 1) omitted.
+fn rs_divisor(degree: usize) -> Vec<u8> {
+    let mut result = vec![0u8; degree];
+    result[degree - 1] = 1;
+    let mut root = 1u8;
+    for _ in 0..degree {
+        for j in 0..degree {
+            result[j] = gf_mul(result[j], root);
+            if j + 1 < degree {
+                result[j] ^= result[j + 1];
+            }
+        }
+        root = gf_mul(root, 0x02);
+    }
+    result
+}
+
+/// Reed-Solomon remainder of `data` divided by `divisor`.
+fn rs_remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
+    let mut result = vec![0u8; divisor.len()];
+    for &byte in data {
+        let factor = byte ^ result.remove(0);
+        result.push(0);
+        for (r, &d) in result.iter_mut().zip(divisor) {
+            *r ^= gf_mul(d, factor);
+        }
+    }
+    result
+}
+
+/// Multiply in GF(256) modulo x^8 + x^4 + x^3 + x^2 + 1.
+fn gf_mul(x: u8, y: u8) -> u8 {
+    let mut z: u32 = 0;
+    for i in (0..8).rev() {
+        z = (z << 1) ^ ((z >> 7) * 0x11D);
+        z ^= ((y as u32 >> i) & 1) * x as u32;
+    }
+    z as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reed_solomon_matches_reference() {
+        // "HELLO WORLD" at 1-M, from the ISO 18004 worked example.
+        let data = [
+            32, 91, 11, 120, 209, 114, 220, 77, 67, 64, 236, 17, 236, 17, 236, 17,
+        ];
+        let ecc = rs_remainder(&data, &rs_divisor(10));
+        assert_eq!(ecc, vec![196, 35, 39, 119, 235, 215, 231, 226, 93, 23]);
+    }
+
+    #[test]
+    fn test_format_and_layout_tables() {
+        // Level L (01), mask 4.
+        assert_eq!(format_bits(0b01100), 0b110011000101111);
+        assert_eq!(data_codewords(1), 16);
+        assert_eq!(data_codewords(7), 124);
+        assert_eq!(alignment_positions(2), vec![6, 18]);
+        assert_eq!(alignment_positions(7), vec![6, 22, 38]);
+        assert_eq!(alignment_positions(32), vec![6, 34, 60, 86, 112, 138]);
+    }
+
+    #[test]
+    fn test_encode_picks_smallest_version() {
+        let short = QrCode::encode(b"hi").unwrap();
+        assert_eq!(short.version(), 1);
+        assert_eq!(short.size(), 21);
+
+        // A typical provisioning URI needs a version with version-info bits.
+        let uri = format!(
+            "sgnl://linkdevice?uuid={}&pub_key={}",
+            "a".repeat(22),
+            "B".repeat(60)
+        );
+        let code = QrCode::encode(uri.as_bytes()).unwrap();
+        assert_eq!(code.version(), 7);
+        assert_eq!(code.size(), 45);
+
+        assert!(QrCode::encode(&[0u8; 4000]).is_none());
+    }
+
+    #[test]
+    fn test_function_patterns_are_drawn() {
+        let code = QrCode::encode(b"sgnl://linkdevice").unwrap();
+        // Finder corners and centres are dark; the separators are light.
+        for (x, y) in [(0, 0), (3, 3), (code.size() - 1, 0), (0, code.size() - 1)] {
+            assert!(code.is_dark(x, y));
+        }
+        assert!(!code.is_dark(7, 0));
+        assert!(!code.is_dark(0, 7));
+        // Timing pattern alternates, and the fixed dark module is present.
+        assert!(code.is_dark(8, 6) && !code.is_dark(9, 6));
+        assert!(code.is_dark(8, code.size() - 8));
+    }
+
+    #[test]
+    fn test_terminal_rendering_includes_quiet_zone() {
+        let code = QrCode::encode(b"hi").unwrap();
+        let rendered = code.to_terminal_string();
+        let lines: Vec<&str> = rendered.lines().collect();
+        let span = code.size() + 2 * QUIET_ZONE;
+        assert_eq!(lines.len(), span.div_ceil(2));
+        assert!(lines.iter().all(|l| l.chars().count() == span));
+        // The top quiet-zone line is entirely light.
+        assert!(lines[0].chars().all(|c| c == '█'));
+    }
+}
@@ -1,14 +1,57 @@
 //! Async Signal service — bridges Signal messages to the core daemon message bus.
 
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
 use tokio::sync::{broadcast, mpsc};
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
 
+use crustyclaw_core::access::{AccessDecision, ChannelAccess};
+use crustyclaw_core::attachment::{self, Attachment, AttachmentSpool};
+use crustyclaw_core::health::{ComponentStatus, HealthRegistry};
+use crustyclaw_core::llm::{IMAGE_MEDIA_TYPES, ImageSource};
 use crustyclaw_core::message::{Direction, Envelope};
+use crustyclaw_core::response::ResponsePipeline;
+use crustyclaw_core::workspace::{WorkspaceStore, sanitize_name, sniff};
 
 use crate::SignalError;
+use crate::adapter::{SignalAdapter, session::Verified};
 use crate::message::SignalMessage;
 use crate::rate_limit::{RateLimitConfig, RateLimiter};
 
+/// How often an active typing indicator is re-sent; Signal clients hide one
+/// after about 15 seconds without a refresh.
+const TYPING_REFRESH: Duration = Duration::from_secs(10);
+
+/// How long a typing indicator is kept up while no reply is sent.
+const TYPING_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Number of recent inbound messages that can still be remotely deleted.
+const REDACTABLE_MESSAGES: usize = 1024;
+
+/// Largest image attachment forwarded to the agent, in bytes.
+const MAX_IMAGE_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Presence signals the service sends back to Signal senders.
+#[derive(Debug, Clone, Copy)]
+pub struct PresenceConfig {
+    /// Send a read receipt for each accepted message.
+    pub read_receipts: bool,
+    /// Show the sender a typing indicator until the reply is sent.
+    pub typing_indicators: bool,
+}
+
+impl Default for PresenceConfig {
+    fn default() -> Self {
+        Self {
+            read_receipts: true,
+            typing_indicators: true,
+        }
+    }
+}
+
 /// Commands that can be sent to the Signal service.
 #[derive(Debug)]
 pub enum ServiceCommand {
@@ -31,6 +74,34 @@ pub struct SignalService {
 
     /// Rate limiter for inbound messages.
     rate_limiter: RateLimiter,
+
+    /// Sender allowlist checked before anything else, if attached.
+    access: Option<Arc<ChannelAccess>>,
+
+    /// Verified adapter used to deliver and receive messages, if attached.
+    adapter: Option<SignalAdapter<Verified>>,
+
+    /// Workspace store that incoming attachments are copied into, if attached.
+    workspaces: Option<Arc<WorkspaceStore>>,
+
+    /// Spool that incoming attachments are passed to skills and tools through, if attached.
+    attachments: Option<Arc<AttachmentSpool>>,
+
+    /// Hook pipeline applied to outbound messages before delivery, if attached.
+    responses: Option<Arc<ResponsePipeline>>,
+
+    /// Read receipt and typing indicator settings.
+    presence: PresenceConfig,
+
+    /// Registry the connection state is reported to, if attached.
+    health: Option<Arc<HealthRegistry>>,
+
+    /// Peers currently shown a typing indicator, with when it was started.
+    typing: HashMap<String, Instant>,
+
+    /// Recent inbound messages as (sender, Signal timestamp, envelope ID),
+    /// oldest first, so a remote delete can be mapped to its envelope.
+    inbound_ids: VecDeque<(String, u64, u64)>,
 }
 
 /// Handle for interacting with a running SignalService.
@@ -68,6 +139,15 @@ impl SignalService {
             command_rx,
             bus_tx,
             rate_limiter: RateLimiter::new(rate_limit_config),
+            access: None,
+            adapter: None,
+            workspaces: None,
+            attachments: None,
+            responses: None,
+            presence: PresenceConfig::default(),
+            health: None,
+            typing: HashMap::new(),
+            inbound_ids: VecDeque::new(),
         };
 
         let handle = SignalServiceHandle { command_tx };
@@ -75,31 +155,157 @@ impl SignalService {
         (service, handle)
     }
 
+    /// Attach a verified adapter.
+    ///
+    /// Outbound messages are delivered through the adapter's transport, and
+    /// its incoming messages are published to the bus while the service runs.
+    pub fn with_adapter(mut self, adapter: SignalAdapter<Verified>) -> Self {
+        self.adapter = Some(adapter);
+        self
+    }
+
+    /// Admit inbound messages according to a sender access policy.
+    ///
+    /// Messages the policy refuses are dropped or quarantined before they
+    /// are rate limited, stored, acknowledged, or published to the bus.
+    pub fn with_access(mut self, access: Arc<ChannelAccess>) -> Self {
+        self.access = Some(access);
+        self
+    }
+
+    /// Copy incoming attachments into each conversation's workspace.
+    ///
+    /// Attachments are subject to the store's size and content-type checks;
+    /// rejected ones are logged and skipped.
+    pub fn with_workspaces(mut self, workspaces: Arc<WorkspaceStore>) -> Self {
+        self.workspaces = Some(workspaces);
+        self
+    }
+
+    /// Spool incoming attachments and send them with the message's
+    /// envelope, so skills and tools run for it can read them.
+    ///
+    /// Attachments over the spool's quotas are logged and skipped.
+    pub fn with_attachment_spool(mut self, spool: Arc<AttachmentSpool>) -> Self {
+        self.attachments = Some(spool);
+        self
+    }
+
+    /// Run outbound messages through a response hook pipeline.
+    ///
+    /// Each part the pipeline returns is sent as its own message; a blocked
+    /// response is dropped.
+    pub fn with_response_pipeline(mut self, pipeline: Arc<ResponsePipeline>) -> Self {
+        self.responses = Some(pipeline);
+        self
+    }
+
+    /// Choose which read receipts and typing indicators are sent.
+    ///
+    /// Both are on by default and only take effect with an adapter attached.
+    pub fn with_presence(mut self, presence: PresenceConfig) -> Self {
+        self.presence = presence;
+        self
+    }
+
+    /// Report the connection state as the `signal` component.
+    ///
+    /// The service reports `up` once it is receiving, and `down` when the
+    /// transport closes or the service stops.
+    pub fn with_health(mut self, health: Arc<HealthRegistry>) -> Self {
+        self.health = Some(health);
+        self
+    }
+
     /// Run the service event loop until shutdown.
-    pub async fn run(mut self) {
+    ///
+    /// Fails if the adapter's transport cannot deliver incoming messages or
+    /// closes, so a supervisor can restart the channel with a new transport.
+    pub async fn run(mut self) -> Result<(), SignalError> {
         info!("Signal service started");
 
-        while let Some(cmd) = self.command_rx.recv().await {
-            match cmd {
-                ServiceCommand::Send(msg) => {
-                    self.handle_outbound(msg);
-                }
-                ServiceCommand::Shutdown => {
-                    info!("Signal service shutting down");
-                    break;
-                }
+        let mut incoming = match self.adapter.as_ref().map(|a| a.incoming()) {
+            Some(Ok(rx)) => {
+                let account = self.adapter.as_ref().map_or("", |a| a.phone_number());
+                let detail = format!("receiving as {account}");
+                self.report(ComponentStatus::Up, detail);
+                Some(rx)
+            }
+            Some(Err(e)) => {
+                warn!(error = %e, "Signal incoming stream unavailable");
+                self.report(ComponentStatus::Down, e.to_string());
+                return Err(e);
+            }
+            None => None,
+        };
+
+        let mut refresh =
+            tokio::time::interval_at(tokio::time::Instant::now() + TYPING_REFRESH, TYPING_REFRESH);
+
+        loop {
+            tokio::select! {
+                cmd = self.command_rx.recv() => match cmd {
+                    Some(ServiceCommand::Send(msg)) => self.handle_outbound(msg).await,
+                    Some(ServiceCommand::Shutdown) | None => {
+                        info!("Signal service shutting down");
+                        break;
+                    }
+                },
+                msg = next_incoming(&mut incoming) => match msg {
+                    Some(msg) => {
+                        if self.process_inbound(&msg).is_ok() {
+                            self.acknowledge(&msg).await;
+                        }
+                    }
+                    None => {
+                        warn!("Signal transport closed; no longer receiving messages");
+                        self.report(ComponentStatus::Down, "transport closed");
+                        return Err(SignalError::ReceiveFailed("transport closed".to_string()));
+                    }
+                },
+                _ = refresh.tick() => self.refresh_typing().await,
             }
         }
 
+        self.report(ComponentStatus::Down, "stopped");
         info!("Signal service stopped");
+        Ok(())
+    }
+
+    fn report(&self, status: ComponentStatus, detail: impl Into<String>) {
+        if let Some(health) = &self.health {
+            health.report("signal", status, detail);
+        }
     }
 
     /// Process an inbound Signal message (from Signal → daemon bus).
     ///
-    /// This would be called when the Signal protocol layer receives a message.
-    /// Currently exposed for testing; in production, the Signal protocol
-    /// integration would call this internally.
+    /// Called by [`run`](Self::run) for each message from the adapter's
+    /// transport; also public so other receive paths can feed the bus.
+    ///
+    /// A remote delete is not routed as a message: if it names one of the
+    /// sender's recent messages, a redaction envelope for that message is
+    /// published instead.
     pub fn process_inbound(&mut self, msg: &SignalMessage) -> Result<(), SignalError> {
+        if let Some(timestamp) = msg.remote_delete {
+            self.redact(&msg.sender, timestamp);
+            return Ok(());
+        }
+
+        if let Some(access) = &self.access {
+            let group = msg.group.as_ref().map(|g| g.id.as_str());
+            match access.check(&msg.sender, group) {
+                AccessDecision::Allow { .. } => {}
+                decision => {
+                    if decision == AccessDecision::Quarantine {
+                        access
+                            .quarantine(&Envelope::new("signal", &msg.body).with_peer(&msg.sender));
+                    }
+                    return Err(SignalError::SenderNotAllowed(msg.sender.clone()));
+                }
+            }
+        }
+
         // Rate limit check
         if !self.rate_limiter.check(&msg.sender) {
             warn!(sender = %msg.sender, "Rate limited");
@@ -109,32 +315,366 @@ impl SignalService {
             )));
         }
 
+        if let Some(workspaces) = &self.workspaces {
+            store_attachments(workspaces, msg);
+        }
+
+        let spooled = match &self.attachments {
+            Some(spool) => spool_attachments(spool, msg),
+            None => Vec::new(),
+        };
+
         // Convert to Envelope and publish to bus
-        let envelope = Envelope::new("signal", &msg.body);
+        let envelope = Envelope::new("signal", &msg.body)
+            .with_peer(&msg.sender)
+            .with_images(image_attachments(msg))
+            .with_attachments(spooled);
+        if self.inbound_ids.len() == REDACTABLE_MESSAGES {
+            self.inbound_ids.pop_front();
+        }
+        self.inbound_ids
+            .push_back((msg.sender.clone(), msg.timestamp_millis(), envelope.id));
         let _ = self.bus_tx.send(envelope);
 
         info!(sender = %msg.sender, "Inbound Signal message routed to bus");
         Ok(())
     }
 
-    fn handle_outbound(&self, msg: SignalMessage) {
+    /// Publish a redaction for `sender`'s message sent at `timestamp`.
+    ///
+    /// Only the original sender can delete a message, and only while it is
+    /// among the recent inbound messages.
+    fn redact(&mut self, sender: &str, timestamp: u64) {
+        let Some(pos) = self
+            .inbound_ids
+            .iter()
+            .position(|(s, ts, _)| s == sender && *ts == timestamp)
+        else {
+            debug!(
+                sender,
+                timestamp, "Remote delete for unknown message ignored"
+            );
+            return;
+        };
+        if let Some((_, _, id)) = self.inbound_ids.remove(pos) {
+            let _ = self
+                .bus_tx
+                .send(Envelope::redaction("signal", id).with_peer(sender));
+            info!(
+                sender,
+                envelope = id,
+                "Signal message deleted by sender; redacted"
+            );
+        }
+    }
+
+    /// Send the configured read receipt and typing indicator for an
+    /// accepted inbound message.
+    ///
+    /// Typing indicators are only shown in direct conversations. Failures
+    /// are logged and otherwise ignored.
+    async fn acknowledge(&mut self, msg: &SignalMessage) {
+        let Some(adapter) = &self.adapter else {
+            return;
+        };
+        if msg.remote_delete.is_some() {
+            return;
+        }
+        if self.presence.read_receipts
+            && let Err(e) = adapter
+                .send_receipt(&msg.sender, msg.timestamp_millis())
+                .await
+        {
+            debug!(sender = %msg.sender, error = %e, "Signal read receipt failed");
+        }
+        if self.presence.typing_indicators && !msg.is_group() {
+            match adapter.send_typing(&msg.sender, true).await {
+                Ok(()) => {
+                    self.typing.insert(msg.sender.clone(), Instant::now());
+                }
+                Err(e) => {
+                    debug!(sender = %msg.sender, error = %e, "Signal typing indicator failed")
+                }
+            }
+        }
+    }
+
+    /// Re-send active typing indicators, giving up on those that have been
+    /// shown for longer than [`TYPING_TIMEOUT`].
+    async fn refresh_typing(&mut self) {
+        let Some(adapter) = &self.adapter else {
+            return;
+        };
+        let expired: Vec<String> = self
+            .typing
+            .iter()
+            .filter(|(_, since)| since.elapsed() >= TYPING_TIMEOUT)
+            .map(|(peer, _)| peer.clone())
+            .collect();
+        for peer in expired {
+            self.typing.remove(&peer);
+            debug!(peer = %peer, "No reply in time; stopping typing indicator");
+            let _ = adapter.send_typing(&peer, false).await;
+        }
+        for peer in self.typing.keys() {
+            if let Err(e) = adapter.send_typing(peer, true).await {
+                debug!(peer = %peer, error = %e, "Signal typing indicator failed");
+            }
+        }
+    }
+
+    /// Stop the typing indicator shown to `peer`, if any.
+    async fn stop_typing(&mut self, peer: &str) {
+        if self.typing.remove(peer).is_some()
+            && let Some(adapter) = &self.adapter
+            && let Err(e) = adapter.send_typing(peer, false).await
+        {
+            debug!(peer, error = %e, "Signal typing indicator failed");
+        }
+    }
+
+    async fn handle_outbound(&mut self, msg: SignalMessage) {
         let recipient = msg.recipient.as_deref().unwrap_or("unknown");
-        info!(
-            recipient = %recipient,
-            body_len = msg.body.len(),
-            "Outbound Signal message queued"
-        );
-        // TODO: When Signal protocol is integrated, actually deliver the message.
-        // For now, publish to the bus as an outbound envelope for TUI visibility.
-        let mut envelope = Envelope::new("signal", &msg.body);
+        self.stop_typing(recipient).await;
+        let parts = match &self.responses {
+            Some(pipeline) => match pipeline.process("signal", Some(recipient), &msg.body) {
+                Ok(parts) => parts,
+                Err(e) => {
+                    warn!(recipient = %recipient, error = %e, "Outbound Signal message blocked");
+                    return;
+                }
+            },
+            None => vec![msg.body.clone()],
+        };
+        for part in parts {
+            if !self.deliver(recipient, &part).await {
+                return;
+            }
+        }
+    }
+
+    /// Send one message part and publish it to the bus.
+    ///
+    /// Returns `false` when delivery failed, so remaining parts are not sent.
+    async fn deliver(&self, recipient: &str, body: &str) -> bool {
+        // Deliver through the transport when one is attached; without it the
+        // message is only published to the bus.
+        if let Some(adapter) = &self.adapter {
+            match adapter.send(recipient, body).await {
+                Ok(timestamp) => {
+                    info!(recipient = %recipient, timestamp, "Outbound Signal message delivered");
+                }
+                Err(e) => {
+                    warn!(recipient = %recipient, error = %e, "Outbound Signal message failed");
+                    return false;
+                }
+            }
+        } else {
+            info!(
+                recipient = %recipient,
+                body_len = body.len(),
+                "Outbound Signal message queued"
+            );
+        }
+        // Publish to the bus as an outbound envelope for TUI visibility.
+        let mut envelope = Envelope::new("signal", body).with_peer(recipient);
         envelope.direction = Direction::Outbound;
         let _ = self.bus_tx.send(envelope);
+        true
+    }
+}
+
+/// Copy a message's downloaded attachments into its conversation workspace.
+fn store_attachments(workspaces: &WorkspaceStore, msg: &SignalMessage) {
+    let conversation = msg.conversation_id();
+    for (i, attachment) in msg.attachments.iter().enumerate() {
+        let Some(path) = &attachment.local_path else {
+            debug!(sender = %msg.sender, "Attachment has no local file, skipping");
+            continue;
+        };
+        let name = match &attachment.filename {
+            Some(filename) => sanitize_name(filename),
+            None => format!("attachment-{}", i + 1),
+        };
+        if let Err(e) = workspaces.import(
+            &conversation,
+            &name,
+            Some(&attachment.content_type),
+            Path::new(path),
+        ) {
+            warn!(%conversation, %name, error = %e, "Signal attachment rejected");
+        }
+    }
+}
+
+/// Copy a message's downloaded attachments into the attachment spool.
+fn spool_attachments(spool: &AttachmentSpool, msg: &SignalMessage) -> Vec<Attachment> {
+    let key = attachment::message_key("signal", &msg.sender, msg.timestamp_millis());
+    let mut spooled = Vec::new();
+    for (i, attachment) in msg.attachments.iter().enumerate() {
+        let Some(path) = &attachment.local_path else {
+            continue;
+        };
+        let name = match &attachment.filename {
+            Some(filename) => sanitize_name(filename),
+            None => format!("attachment-{}", i + 1),
+        };
+        match spool.store(&key, &name, Some(&attachment.content_type), Path::new(path)) {
+            Ok(stored) => spooled.push(stored),
+            Err(e) => {
+                warn!(sender = %msg.sender, %name, error = %e, "Signal attachment not spooled")
+            }
+        }
+    }
+    spooled
+}
+
+/// A message's downloaded image attachments, as vision input for the agent.
+///
+/// Only formats the providers accept are forwarded, identified by their
+/// magic bytes rather than the declared content type.
+fn image_attachments(msg: &SignalMessage) -> Vec<ImageSource> {
+    let mut images = Vec::new();
+    for attachment in msg.attachments.iter().filter(|a| a.is_image()) {
+        let Some(path) = &attachment.local_path else {
+            continue;
+        };
+        if attachment.size > MAX_IMAGE_BYTES {
+            debug!(sender = %msg.sender, size = attachment.size, "Image attachment too large for the model, skipping");
+            continue;
+        }
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) if bytes.len() as u64 <= MAX_IMAGE_BYTES => bytes,
+            Ok(_) => continue,
+            Err(e) => {
+                warn!(sender = %msg.sender, error = %e, "Failed to read image attachment");
+                continue;
+            }
+        };
+        match sniff(&bytes).filter(|media_type| IMAGE_MEDIA_TYPES.contains(media_type)) {
+            Some(media_type) => images.push(ImageSource::from_bytes(media_type, &bytes)),
+            None => debug!(
+                sender = %msg.sender,
+                content_type = %attachment.content_type,
+                "Image attachment in an unsupported format, skipping"
+            ),
+        }
+    }
+    images
+}
+
+/// Receive the next incoming message, or wait forever if there is no stream.
+async fn next_incoming(rx: &mut Option<mpsc::Receiver<SignalMessage>>) -> Option<SignalMessage> {
+    match rx {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crustyclaw_core::BoxFuture;
+
     use super::*;
+    use crate::message::{Attachment, GroupInfo};
+    use crate::transport::SignalTransport;
+
+    /// Transport that records sends and replays a fixed set of incoming messages.
+    struct MockTransport {
+        sent: Mutex<Vec<(String, String)>>,
+        receipts: Mutex<Vec<(String, u64)>>,
+        typing: Mutex<Vec<(String, bool)>>,
+        incoming: Mutex<Option<mpsc::Receiver<SignalMessage>>>,
+        fail: bool,
+    }
+
+    impl SignalTransport for MockTransport {
+        fn send<'a>(
+            &'a self,
+            recipient: &'a str,
+            text: &'a str,
+        ) -> BoxFuture<'a, Result<u64, SignalError>> {
+            Box::pin(async move {
+                if self.fail {
+                    return Err(SignalError::SendFailed("Unregistered user".to_string()));
+                }
+                let mut sent = self.sent.lock().unwrap();
+                sent.push((recipient.to_string(), text.to_string()));
+                Ok(sent.len() as u64)
+            })
+        }
+
+        fn take_incoming(&self) -> Option<mpsc::Receiver<SignalMessage>> {
+            self.incoming.lock().unwrap().take()
+        }
+
+        fn send_receipt<'a>(
+            &'a self,
+            sender: &'a str,
+            timestamp: u64,
+        ) -> BoxFuture<'a, Result<(), SignalError>> {
+            self.receipts
+                .lock()
+                .unwrap()
+                .push((sender.to_string(), timestamp));
+            Box::pin(async { Ok(()) })
+        }
+
+        fn send_typing<'a>(
+            &'a self,
+            recipient: &'a str,
+            typing: bool,
+        ) -> BoxFuture<'a, Result<(), SignalError>> {
+            self.typing
+                .lock()
+                .unwrap()
+                .push((recipient.to_string(), typing));
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    async fn verified_with(
+        transport: Arc<MockTransport>,
+    ) -> SignalAdapter<crate::adapter::session::Verified> {
+        SignalAdapter::new()
+            .with_transport(transport)
+            .restore(account_dir(), "+15559999".to_string())
+            .await
+            .unwrap()
+            .verify()
+            .await
+            .unwrap()
+    }
+
+    /// A `signal-cli` data directory holding a linked session for `+15559999`.
+    fn account_dir() -> &'static std::path::Path {
+        static DIR: std::sync::OnceLock<tempfile::TempDir> = std::sync::OnceLock::new();
+        DIR.get_or_init(|| {
+            let dir = tempfile::tempdir().unwrap();
+            std::fs::create_dir(dir.path().join("data")).unwrap();
+            std::fs::write(
+                dir.path().join("data/accounts.json"),
+                r#"{"accounts":[{"number":"+15559999"}]}"#,
+            )
+            .unwrap();
+            dir
+        })
+        .path()
+    }
+
+    fn mock_transport(fail: bool) -> (Arc<MockTransport>, mpsc::Sender<SignalMessage>) {
+        let (tx, rx) = mpsc::channel(8);
+        let transport = Arc::new(MockTransport {
+            sent: Mutex::new(Vec::new()),
+            receipts: Mutex::new(Vec::new()),
+            typing: Mutex::new(Vec::new()),
+            incoming: Mutex::new(Some(rx)),
+            fail,
+        });
+        (transport, tx)
+    }
 
     #[tokio::test]
     async fn test_service_creation() {
@@ -151,7 +691,7 @@ mod tests {
 
         let service_task = tokio::spawn(service.run());
         handle.shutdown().await.unwrap();
-        service_task.await.unwrap();
+        service_task.await.unwrap().unwrap();
     }
 
     #[tokio::test]
@@ -186,6 +726,50 @@ mod tests {
         assert!(service.process_inbound(&msg).is_err()); // rate limited
     }
 
+    #[tokio::test]
+    async fn test_inbound_access_policy() {
+        use crustyclaw_config::{SignalConfig, UnknownSenderAction};
+        use crustyclaw_core::access::QuarantineHandler;
+
+        #[derive(Default)]
+        struct Held(std::sync::Mutex<Vec<String>>);
+        impl QuarantineHandler for Held {
+            fn quarantine(&self, envelope: &Envelope) {
+                self.0.lock().unwrap().push(envelope.body.clone());
+            }
+        }
+
+        let config = SignalConfig {
+            allowed_senders: [("+15550001".to_string(), "operator".to_string())].into(),
+            allowed_groups: vec!["group-1".to_string()],
+            unknown_senders: UnknownSenderAction::Quarantine,
+            ..SignalConfig::default()
+        };
+        let held = Arc::new(Held::default());
+        let access = ChannelAccess::signal(&config).with_quarantine(held.clone());
+        let (bus_tx, mut bus_rx) = broadcast::channel(16);
+        let (service, _handle) = SignalService::new(bus_tx, RateLimitConfig::default());
+        let mut service = service.with_access(Arc::new(access));
+
+        assert!(
+            service
+                .process_inbound(&SignalMessage::text("+15550001", "known"))
+                .is_ok()
+        );
+        let mut group_msg = SignalMessage::text("+15559999", "in group");
+        group_msg.group = Some(GroupInfo::new("group-1", "Ops"));
+        assert!(service.process_inbound(&group_msg).is_ok());
+        assert!(matches!(
+            service.process_inbound(&SignalMessage::text("+15559999", "stranger")),
+            Err(SignalError::SenderNotAllowed(_))
+        ));
+
+        assert_eq!(bus_rx.recv().await.unwrap().body, "known");
+        assert_eq!(bus_rx.recv().await.unwrap().body, "in group");
+        assert!(bus_rx.try_recv().is_err());
+        assert_eq!(*held.0.lock().unwrap(), ["stranger"]);
+    }
+
     #[tokio::test]
     async fn test_outbound_message() {
         let (bus_tx, mut bus_rx) = broadcast::channel(16);
@@ -201,6 +785,336 @@ mod tests {
         assert_eq!(envelope.direction, Direction::Outbound);
 
         handle.shutdown().await.unwrap();
-        service_task.await.unwrap();
+        service_task.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_outbound_delivered_through_transport() {
+        let (bus_tx, mut bus_rx) = broadcast::channel(16);
+        let (transport, _incoming_tx) = mock_transport(false);
+        let (service, handle) = SignalService::new(bus_tx, RateLimitConfig::default());
+        let service = service.with_adapter(verified_with(transport.clone()).await);
+        let service_task = tokio::spawn(service.run());
+
+        let msg = SignalMessage::outbound("+15550001", "Reply from agent");
+        handle.send_message(msg).await.unwrap();
+
+        let envelope = bus_rx.recv().await.unwrap();
+        assert_eq!(envelope.direction, Direction::Outbound);
+        assert_eq!(envelope.peer.as_deref(), Some("+15550001"));
+        assert_eq!(
+            *transport.sent.lock().unwrap(),
+            vec![("+15550001".to_string(), "Reply from agent".to_string())]
+        );
+
+        handle.shutdown().await.unwrap();
+        service_task.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_failed_delivery_not_published() {
+        let (bus_tx, mut bus_rx) = broadcast::channel(16);
+        let (transport, _incoming_tx) = mock_transport(true);
+        let (service, handle) = SignalService::new(bus_tx, RateLimitConfig::default());
+        let service = service.with_adapter(verified_with(transport).await);
+        let service_task = tokio::spawn(service.run());
+
+        handle
+            .send_message(SignalMessage::outbound("+15550001", "lost"))
+            .await
+            .unwrap();
+        handle.shutdown().await.unwrap();
+        service_task.await.unwrap().unwrap();
+
+        assert!(bus_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_outbound_runs_response_pipeline() {
+        let (bus_tx, _bus_rx) = broadcast::channel(16);
+        let (transport, _incoming_tx) = mock_transport(false);
+        let config = crustyclaw_config::ResponseConfig {
+            hooks: vec!["signature".to_string(), "split".to_string()],
+            signature: Some("-- bot".to_string()),
+            max_message_chars: 12,
+            ..Default::default()
+        };
+        let secrets = Arc::new(std::sync::RwLock::new(
+            crustyclaw_core::secrets::SecretStore::new(),
+        ));
+        let pipeline = Arc::new(ResponsePipeline::from_config(&config, secrets));
+        let (service, handle) = SignalService::new(bus_tx, RateLimitConfig::default());
+        let service = service
+            .with_adapter(verified_with(transport.clone()).await)
+            .with_response_pipeline(pipeline);
+        let service_task = tokio::spawn(service.run());
+
+        handle
+            .send_message(SignalMessage::outbound("+15550001", "hello there"))
+            .await
+            .unwrap();
+        handle.shutdown().await.unwrap();
+        service_task.await.unwrap().unwrap();
+
+        let sent = transport.sent.lock().unwrap();
+        assert!(sent.len() > 1);
+        let joined: String = sent.iter().map(|(_, text)| text.as_str()).collect();
+        assert!(joined.contains("hello"));
+        assert!(joined.contains("-- bot"));
+        assert!(sent.iter().all(|(_, text)| text.chars().count() <= 12));
+    }
+
+    #[tokio::test]
+    async fn test_incoming_published_to_bus() {
+        let (bus_tx, mut bus_rx) = broadcast::channel(16);
+        let (transport, incoming_tx) = mock_transport(false);
+        let (service, _handle) = SignalService::new(bus_tx, RateLimitConfig::default());
+        let health = Arc::new(HealthRegistry::new());
+        let service = service
+            .with_adapter(verified_with(transport).await)
+            .with_health(health.clone());
+        let service_task = tokio::spawn(service.run());
+
+        incoming_tx
+            .send(SignalMessage::text("+15550001", "Hello daemon"))
+            .await
+            .unwrap();
+
+        let envelope = bus_rx.recv().await.unwrap();
+        let signal = health.get("signal").unwrap();
+        assert_eq!(signal.status, ComponentStatus::Up);
+        assert_eq!(signal.detail, "receiving as +15559999");
+        assert_eq!(envelope.body, "Hello daemon");
+        assert_eq!(envelope.direction, Direction::Inbound);
+        assert_eq!(envelope.peer.as_deref(), Some("+15550001"));
+
+        // The service fails when the transport closes, so it can be
+        // restarted with a new one.
+        drop(incoming_tx);
+        assert!(matches!(
+            service_task.await.unwrap(),
+            Err(SignalError::ReceiveFailed(_))
+        ));
+        let signal = health.get("signal").unwrap();
+        assert_eq!(signal.status, ComponentStatus::Down);
+        assert_eq!(signal.detail, "transport closed");
+    }
+
+    #[tokio::test]
+    async fn test_inbound_attachments_land_in_workspace() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let downloaded = tmp.path().join("att1");
+        std::fs::write(&downloaded, "meeting notes").unwrap();
+        let executable = tmp.path().join("att2");
+        std::fs::write(&executable, b"\x7fELF\x02\x01").unwrap();
+
+        let workspaces = Arc::new(WorkspaceStore::new(tmp.path().join("workspaces")));
+        let (bus_tx, _bus_rx) = broadcast::channel(16);
+        let (service, _handle) = SignalService::new(bus_tx, RateLimitConfig::default());
+        let mut service = service.with_workspaces(workspaces.clone());
+
+        let mut msg = SignalMessage::text("+15550001", "");
+        let mut notes = Attachment::new("text/plain", 13);
+        notes.filename = Some("notes.txt".to_string());
+        notes.local_path = Some(downloaded.display().to_string());
+        let mut disguised = Attachment::new("image/png", 6);
+        disguised.filename = Some("cat.png".to_string());
+        disguised.local_path = Some(executable.display().to_string());
+        msg.attachments = vec![notes, disguised];
+        service.process_inbound(&msg).unwrap();
+
+        let files = workspaces.list(&msg.conversation_id()).unwrap();
+        let names: Vec<&str> = files.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["notes.txt"]);
+    }
+
+    #[tokio::test]
+    async fn test_inbound_attachments_spooled_for_skills() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let downloaded = tmp.path().join("att1");
+        std::fs::write(&downloaded, "meeting notes").unwrap();
+        let large = tmp.path().join("att2");
+        std::fs::write(&large, vec![b'x'; 64]).unwrap();
+
+        let spool = Arc::new(
+            AttachmentSpool::new(tmp.path().join("attachments")).with_max_attachment_bytes(32),
+        );
+        let (bus_tx, mut bus_rx) = broadcast::channel(16);
+        let (service, _handle) = SignalService::new(bus_tx, RateLimitConfig::default());
+        let mut service = service.with_attachment_spool(spool.clone());
+
+        let mut msg = SignalMessage::text("+15550001", "summarize this");
+        let mut notes = Attachment::new("text/plain", 13);
+        notes.filename = Some("notes.txt".to_string());
+        notes.local_path = Some(downloaded.display().to_string());
+        let mut oversized = Attachment::new("text/plain", 64);
+        oversized.local_path = Some(large.display().to_string());
+        msg.attachments = vec![notes, oversized];
+        service.process_inbound(&msg).unwrap();
+
+        let envelope = bus_rx.recv().await.unwrap();
+        assert_eq!(envelope.attachments.len(), 1);
+        let spooled = &envelope.attachments[0];
+        assert_eq!(spooled.name, "notes.txt");
+        assert_eq!(spooled.content_type, "text/plain");
+        assert!(spooled.path.starts_with(spool.dir()));
+        assert_eq!(
+            std::fs::read_to_string(&spooled.path).unwrap(),
+            "meeting notes"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_inbound_images_forwarded_to_agent() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let photo = tmp.path().join("att1");
+        std::fs::write(&photo, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        let executable = tmp.path().join("att2");
+        std::fs::write(&executable, b"\x7fELF\x02\x01").unwrap();
+
+        let (bus_tx, mut bus_rx) = broadcast::channel(16);
+        let (mut service, _handle) = SignalService::new(bus_tx, RateLimitConfig::default());
+
+        let mut msg = SignalMessage::text("+15550001", "what is this?");
+        let mut png = Attachment::new("image/png", 16);
+        png.local_path = Some(photo.display().to_string());
+        let mut disguised = Attachment::new("image/png", 6);
+        disguised.local_path = Some(executable.display().to_string());
+        let missing = Attachment::new("image/jpeg", 100);
+        msg.attachments = vec![png, disguised, missing];
+        service.process_inbound(&msg).unwrap();
+
+        let envelope = bus_rx.recv().await.unwrap();
+        assert_eq!(envelope.images.len(), 1);
+        assert!(matches!(
+            &envelope.images[0],
+            ImageSource::Base64 { media_type, .. } if media_type == "image/png"
+        ));
+        assert_eq!(envelope.chat_message().images, envelope.images);
+    }
+
+    fn message_at(sender: &str, body: &str, millis: u64) -> SignalMessage {
+        let mut msg = SignalMessage::text(sender, body);
+        msg.timestamp = std::time::SystemTime::UNIX_EPOCH + Duration::from_millis(millis);
+        msg
+    }
+
+    #[tokio::test]
+    async fn test_inbound_acknowledged_until_reply() {
+        let (bus_tx, mut bus_rx) = broadcast::channel(16);
+        let (transport, incoming_tx) = mock_transport(false);
+        let (service, handle) = SignalService::new(bus_tx, RateLimitConfig::default());
+        let service = service.with_adapter(verified_with(transport.clone()).await);
+        let service_task = tokio::spawn(service.run());
+
+        incoming_tx
+            .send(message_at("+15550001", "Hello daemon", 1700000000000))
+            .await
+            .unwrap();
+        bus_rx.recv().await.unwrap();
+        handle
+            .send_message(SignalMessage::outbound("+15550001", "Hi"))
+            .await
+            .unwrap();
+        assert_eq!(bus_rx.recv().await.unwrap().direction, Direction::Outbound);
+
+        assert_eq!(
+            *transport.receipts.lock().unwrap(),
+            vec![("+15550001".to_string(), 1700000000000)]
+        );
+        assert_eq!(
+            *transport.typing.lock().unwrap(),
+            vec![
+                ("+15550001".to_string(), true),
+                ("+15550001".to_string(), false)
+            ]
+        );
+
+        handle.shutdown().await.unwrap();
+        service_task.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_presence_disabled() {
+        let (bus_tx, mut bus_rx) = broadcast::channel(16);
+        let (transport, incoming_tx) = mock_transport(false);
+        let (service, handle) = SignalService::new(bus_tx, RateLimitConfig::default());
+        let service = service
+            .with_adapter(verified_with(transport.clone()).await)
+            .with_presence(PresenceConfig {
+                read_receipts: false,
+                typing_indicators: false,
+            });
+        let service_task = tokio::spawn(service.run());
+
+        incoming_tx
+            .send(SignalMessage::text("+15550001", "Hello daemon"))
+            .await
+            .unwrap();
+        bus_rx.recv().await.unwrap();
+        handle.shutdown().await.unwrap();
+        service_task.await.unwrap().unwrap();
+
+        assert!(transport.receipts.lock().unwrap().is_empty());
+        assert!(transport.typing.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_typing_refreshed_then_expired() {
+        let (bus_tx, _bus_rx) = broadcast::channel(16);
+        let (transport, _incoming_tx) = mock_transport(false);
+        let (service, _handle) = SignalService::new(bus_tx, RateLimitConfig::default());
+        let mut service = service.with_adapter(verified_with(transport.clone()).await);
+
+        let now = Instant::now();
+        service.typing.insert("+15550001".to_string(), now);
+        service.typing.insert(
+            "+15550002".to_string(),
+            now.checked_sub(TYPING_TIMEOUT).unwrap(),
+        );
+        service.refresh_typing().await;
+
+        assert_eq!(
+            *transport.typing.lock().unwrap(),
+            vec![
+                ("+15550002".to_string(), false),
+                ("+15550001".to_string(), true)
+            ]
+        );
+        assert!(service.typing.contains_key("+15550001"));
+        assert!(!service.typing.contains_key("+15550002"));
+    }
+
+    #[tokio::test]
+    async fn test_remote_delete_publishes_redaction() {
+        let (bus_tx, mut bus_rx) = broadcast::channel(16);
+        let (mut service, _handle) = SignalService::new(bus_tx, RateLimitConfig::default());
+
+        service
+            .process_inbound(&message_at(
+                "+15550001",
+                "my password is hunter2",
+                1700000000000,
+            ))
+            .unwrap();
+        let original = bus_rx.recv().await.unwrap();
+
+        // Another sender cannot delete the message.
+        let mut forged = SignalMessage::text("+15550002", "");
+        forged.remote_delete = Some(1700000000000);
+        service.process_inbound(&forged).unwrap();
+        assert!(bus_rx.try_recv().is_err());
+
+        let mut delete = SignalMessage::text("+15550001", "");
+        delete.remote_delete = Some(1700000000000);
+        service.process_inbound(&delete).unwrap();
+        let redaction = bus_rx.recv().await.unwrap();
+        assert_eq!(redaction.redacts, Some(original.id));
+        assert_eq!(redaction.peer.as_deref(), Some("+15550001"));
+        assert!(redaction.body.is_empty());
+
+        // A repeated delete has nothing left to redact.
+        service.process_inbound(&delete).unwrap();
+        assert!(bus_rx.try_recv().is_err());
     }
 }
@@ -0,0 +1,720 @@
+//! Signal transport — message delivery and reception via `signal-cli`.
+//!
+//! [`SignalCliTransport`] drives `signal-cli` in JSON-RPC mode
+//! (`signal-cli --config <data_dir> -a <account> jsonRpc`), writing one
+//! request per line to its stdin and reading responses and `receive`
+//! notifications from its stdout. Incoming data messages are converted to
+//! [`SignalMessage`]s and delivered on a channel obtained once via
+//! [`SignalTransport::take_incoming`].
+//!
+//! Without an account, `signal-cli` runs in multi-account mode and serves as
+//! a [`SignalProvisioner`] for linking a new device
+//! (see [`SignalCliTransport::spawn_provisioning`]).
+//!
+//! The transport is generic over its I/O so tests can stand in for
+//! `signal-cli` with an in-memory pipe (see [`SignalCliTransport::from_io`]).
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use serde_json::{Value, json};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, warn};
+
+use crustyclaw_core::BoxFuture;
+
+use crate::SignalError;
+use crate::message::{Attachment, GroupInfo, SignalMessage};
+
+/// Capacity of the incoming message channel.
+const INCOMING_CAPACITY: usize = 256;
+
+/// A connection to the Signal network that can send and receive messages.
+pub trait SignalTransport: Send + Sync {
+    /// Send a text message to a phone number or UUID. Returns the Signal
+    /// timestamp of the sent message.
+    fn send<'a>(
+        &'a self,
+        recipient: &'a str,
+        text: &'a str,
+    ) -> BoxFuture<'a, Result<u64, SignalError>>;
+
+    /// Take the stream of incoming messages. Returns `None` if it has
+    /// already been taken.
+    fn take_incoming(&self) -> Option<mpsc::Receiver<SignalMessage>>;
+
+    /// Tell `sender` that their message sent at `timestamp` has been read.
+    ///
+    /// Transports without receipt support accept and ignore the request.
+    fn send_receipt<'a>(
+        &'a self,
+        sender: &'a str,
+        timestamp: u64,
+    ) -> BoxFuture<'a, Result<(), SignalError>> {
+        let _ = (sender, timestamp);
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Start (or, with `typing == false`, stop) showing `recipient` a typing
+    /// indicator.
+    ///
+    /// Transports without typing support accept and ignore the request.
+    fn send_typing<'a>(
+        &'a self,
+        recipient: &'a str,
+        typing: bool,
+    ) -> BoxFuture<'a, Result<(), SignalError>> {
+        let _ = (recipient, typing);
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// A connection that can link a new secondary device to an existing Signal
+/// account.
+pub trait SignalProvisioner: Send + Sync {
+    /// Begin linking. Returns the `sgnl://linkdevice?...` provisioning URI
+    /// for the primary device to scan.
+    fn start_link(&self) -> BoxFuture<'_, Result<String, SignalError>>;
+
+    /// Wait for the primary device to approve the link started for `uri`,
+    /// registering this device as `device_name`. Returns the linked
+    /// account's phone number once the session keys have been stored.
+    fn finish_link<'a>(
+        &'a self,
+        uri: &'a str,
+        device_name: &'a str,
+    ) -> BoxFuture<'a, Result<String, SignalError>>;
+}
+
+/// In-flight requests keyed by JSON-RPC id; `None` once `signal-cli` has exited.
+type PendingMap = Option<HashMap<u64, oneshot::Sender<Result<Value, SignalError>>>>;
+
+/// [`SignalTransport`] backed by a `signal-cli` JSON-RPC process.
+pub struct SignalCliTransport {
+    /// Lines queued for `signal-cli`'s stdin.
+    writer_tx: mpsc::Sender<String>,
+    /// In-flight requests awaiting a response, keyed by JSON-RPC id.
+    pending: Arc<Mutex<PendingMap>>,
+    next_id: AtomicU64,
+    incoming: Mutex<Option<mpsc::Receiver<SignalMessage>>>,
+    /// The child process, killed when the transport is dropped.
+    _child: Option<tokio::process::Child>,
+}
+
+impl SignalCliTransport {
+    /// Spawn `signal-cli` for `account`, storing its state in `data_dir`.
+    pub fn spawn(cli_path: &str, data_dir: &Path, account: &str) -> Result<Self, SignalError> {
+        Self::spawn_with(cli_path, data_dir, Some(account))
+    }
+
+    /// Spawn `signal-cli` without an account, for linking a new device with
+    /// [`SignalProvisioner`]. The session keys of the linked account are
+    /// stored in `data_dir`, which is created owner-only if it is missing.
+    pub fn spawn_provisioning(cli_path: &str, data_dir: &Path) -> Result<Self, SignalError> {
+        create_private_dir(data_dir).map_err(|e| {
+            SignalError::LinkingFailed(format!("cannot create {}: {e}", data_dir.display()))
+        })?;
+        Self::spawn_with(cli_path, data_dir, None)
+    }
+
+    fn spawn_with(
+        cli_path: &str,
+        data_dir: &Path,
+        account: Option<&str>,
+    ) -> Result<Self, SignalError> {
+        let mut command = tokio::process::Command::new(cli_path);
+        command.arg("--config").arg(data_dir);
+        if let Some(account) = account {
+            command.arg("-a").arg(account);
+        }
+        let mut child = command
+            .arg("jsonRpc")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| SignalError::ReceiveFailed(format!("failed to start {cli_path}: {e}")))?;
+
+        let stdin = child.stdin.take().ok_or_else(|| {
+            SignalError::ReceiveFailed("signal-cli stdin unavailable".to_string())
+        })?;
+        let stdout = child.stdout.take().ok_or_else(|| {
+            SignalError::ReceiveFailed("signal-cli stdout unavailable".to_string())
+        })?;
+
+        // signal-cli saves received attachments as `<config>/attachments/<id>`.
+        let mut transport = Self::from_io_with(stdout, stdin, Some(data_dir.join("attachments")));
+        transport._child = Some(child);
+        Ok(transport)
+    }
+
+    /// Build a transport over an existing JSON-RPC connection.
+    pub fn from_io<R, W>(reader: R, writer: W) -> Self
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        Self::from_io_with(reader, writer, None)
+    }
+
+    /// Build a transport whose incoming attachments resolve to files in
+    /// `attachments_dir`.
+    fn from_io_with<R, W>(reader: R, writer: W, attachments_dir: Option<PathBuf>) -> Self
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let (writer_tx, writer_rx) = mpsc::channel(64);
+        let (incoming_tx, incoming_rx) = mpsc::channel(INCOMING_CAPACITY);
+        let pending = Arc::new(Mutex::new(Some(HashMap::new())));
+
+        tokio::spawn(write_loop(writer, writer_rx));
+        tokio::spawn(read_loop(
+            reader,
+            pending.clone(),
+            incoming_tx,
+            attachments_dir,
+        ));
+
+        Self {
+            writer_tx,
+            pending,
+            next_id: AtomicU64::new(1),
+            incoming: Mutex::new(Some(incoming_rx)),
+            _child: None,
+        }
+    }
+
+    /// Issue a JSON-RPC call and wait for its result.
+    async fn call(&self, method: &str, params: Value) -> Result<Value, SignalError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        match self
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_mut()
+        {
+            Some(pending) => pending.insert(id, tx),
+            None => return Err(SignalError::SendFailed("signal-cli exited".to_string())),
+        };
+
+        let request = json!({ "jsonrpc": "2.0", "method": method, "params": params, "id": id });
+        if self.writer_tx.send(request.to_string()).await.is_err() {
+            if let Some(pending) = self
+                .pending
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .as_mut()
+            {
+                pending.remove(&id);
+            }
+            return Err(SignalError::SendFailed(
+                "signal-cli is not running".to_string(),
+            ));
+        }
+
+        rx.await
+            .map_err(|_| SignalError::SendFailed("signal-cli exited".to_string()))?
+    }
+}
+
+impl SignalTransport for SignalCliTransport {
+    fn send<'a>(
+        &'a self,
+        recipient: &'a str,
+        text: &'a str,
+    ) -> BoxFuture<'a, Result<u64, SignalError>> {
+        Box::pin(async move {
+            let result = self
+                .call("send", json!({ "recipient": [recipient], "message": text }))
+                .await?;
+            Ok(result
+                .get("timestamp")
+                .and_then(Value::as_u64)
+                .unwrap_or_default())
+        })
+    }
+
+    fn take_incoming(&self) -> Option<mpsc::Receiver<SignalMessage>> {
+        self.incoming
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+    }
+
+    fn send_receipt<'a>(
+        &'a self,
+        sender: &'a str,
+        timestamp: u64,
+    ) -> BoxFuture<'a, Result<(), SignalError>> {
+        Box::pin(async move {
+            self.call(
+                "sendReceipt",
+                json!({ "recipient": sender, "targetTimestamp": [timestamp], "type": "read" }),
+            )
+            .await
+            .map(drop)
+        })
+    }
+
+    fn send_typing<'a>(
+        &'a self,
+        recipient: &'a str,
+        typing: bool,
+    ) -> BoxFuture<'a, Result<(), SignalError>> {
+        Box::pin(async move {
+            self.call(
+                "sendTyping",
+                json!({ "recipient": [recipient], "stop": !typing }),
+            )
+            .await
+            .map(drop)
+        })
+    }
+}
+
+impl SignalProvisioner for SignalCliTransport {
+    fn start_link(&self) -> BoxFuture<'_, Result<String, SignalError>> {
+        Box::pin(async move {
+            let result = self
+                .call("startLink", json!({}))
+                .await
+                .map_err(|e| SignalError::LinkingFailed(e.to_string()))?;
+            result
+                .get("deviceLinkUri")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .ok_or_else(|| {
+                    SignalError::LinkingFailed("signal-cli returned no link URI".to_string())
+                })
+        })
+    }
+
+    fn finish_link<'a>(
+        &'a self,
+        uri: &'a str,
+        device_name: &'a str,
+    ) -> BoxFuture<'a, Result<String, SignalError>> {
+        Box::pin(async move {
+            let result = self
+                .call(
+                    "finishLink",
+                    json!({ "deviceLinkUri": uri, "deviceName": device_name }),
+                )
+                .await
+                .map_err(|e| SignalError::LinkingFailed(e.to_string()))?;
+            result
+                .get("number")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .ok_or_else(|| {
+                    SignalError::LinkingFailed("signal-cli returned no account number".to_string())
+                })
+        })
+    }
+}
+
+/// Create `dir` (and parents) readable only by the owner.
+fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)?;
+    std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))
+}
+
+/// Write queued lines to `signal-cli` until the transport is dropped.
+async fn write_loop<W: AsyncWrite + Unpin>(mut writer: W, mut rx: mpsc::Receiver<String>) {
+    while let Some(line) = rx.recv().await {
+        let written = async {
+            writer.write_all(line.as_bytes()).await?;
+            writer.write_all(b"\n").await?;
+            writer.flush().await
+        };
+        if let Err(e) = written.await {
+            warn!(error = %e, "Failed to write to signal-cli");
+            return;
+        }
+    }
+}
+
+/// Dispatch responses and notifications until `signal-cli` closes its output.
+async fn read_loop<R: AsyncRead + Unpin>(
+    reader: R,
+    pending: Arc<Mutex<PendingMap>>,
+    incoming_tx: mpsc::Sender<SignalMessage>,
+    attachments_dir: Option<PathBuf>,
+) {
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let Ok(value) = serde_json::from_str::<Value>(&line) else {
+            debug!(line = %line, "Ignoring non-JSON output from signal-cli");
+            continue;
+        };
+
+        if let Some(id) = value.get("id").and_then(Value::as_u64) {
+            let waiter = pending
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .as_mut()
+                .and_then(|pending| pending.remove(&id));
+            if let Some(waiter) = waiter {
+                let _ = waiter.send(parse_response(&value));
+            }
+        } else if value.get("method").and_then(Value::as_str) == Some("receive")
+            && let Some(msg) = value
+                .get("params")
+                .and_then(|params| parse_receive(params, attachments_dir.as_deref()))
+            && incoming_tx.send(msg).await.is_err()
+        {
+            debug!("Incoming Signal message dropped: no receiver");
+        }
+    }
+
+    // Fail anything still waiting; dropping `incoming_tx` ends the stream.
+    let waiters = pending.lock().unwrap_or_else(|e| e.into_inner()).take();
+    for (_, waiter) in waiters.into_iter().flatten() {
+        let _ = waiter.send(Err(SignalError::ReceiveFailed(
+            "signal-cli exited".to_string(),
+        )));
+    }
+}
+
+/// Turn a JSON-RPC response into its result or a [`SignalError`].
+fn parse_response(value: &Value) -> Result<Value, SignalError> {
+    if let Some(error) = value.get("error") {
+        let message = error
+            .get("message")
+            .and_then(Value::as_str)
+            .unwrap_or("unknown error");
+        return Err(SignalError::SendFailed(message.to_string()));
+    }
+    Ok(value.get("result").cloned().unwrap_or(Value::Null))
+}
+
+/// Convert the params of a `receive` notification into a [`SignalMessage`].
+///
+/// Only data messages are converted; receipts, typing indicators, and sync
+/// messages yield `None`. A remote delete yields a message with an empty body
+/// and [`SignalMessage::remote_delete`] set.
+fn parse_receive(params: &Value, attachments_dir: Option<&Path>) -> Option<SignalMessage> {
+    let envelope = params.get("envelope")?;
+    let data = envelope.get("dataMessage")?;
+
+    let sender = ["sourceNumber", "source", "sourceUuid"]
+        .iter()
+        .find_map(|key| envelope.get(*key).and_then(Value::as_str))?;
+    let mut msg = SignalMessage::text(
+        sender,
+        data.get("message").and_then(Value::as_str).unwrap_or(""),
+    );
+    msg.recipient = params
+        .get("account")
+        .and_then(Value::as_str)
+        .map(str::to_string);
+
+    if let Some(ms) = data
+        .get("timestamp")
+        .or_else(|| envelope.get("timestamp"))
+        .and_then(Value::as_u64)
+    {
+        msg.timestamp = SystemTime::UNIX_EPOCH + Duration::from_millis(ms);
+    }
+
+    if let Some(group_id) = data
+        .get("groupInfo")
+        .and_then(|g| g.get("groupId"))
+        .and_then(Value::as_str)
+    {
+        let name = data
+            .get("groupInfo")
+            .and_then(|g| g.get("groupName"))
+            .and_then(Value::as_str)
+            .unwrap_or("");
+        msg.group = Some(GroupInfo::new(group_id, name));
+    }
+
+    for attachment in data
+        .get("attachments")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        let mut a = Attachment::new(
+            attachment
+                .get("contentType")
+                .and_then(Value::as_str)
+                .unwrap_or("application/octet-stream"),
+            attachment.get("size").and_then(Value::as_u64).unwrap_or(0),
+        );
+        a.filename = attachment
+            .get("filename")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        a.local_path = attachments_dir
+            .zip(attachment.get("id").and_then(Value::as_str))
+            .map(|(dir, id)| dir.join(id).display().to_string());
+        msg.attachments.push(a);
+    }
+
+    msg.remote_delete = data
+        .get("remoteDelete")
+        .and_then(|d| d.get("timestamp"))
+        .and_then(Value::as_u64);
+
+    if msg.body.is_empty() && msg.attachments.is_empty() && msg.remote_delete.is_none() {
+        return None;
+    }
+    Some(msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Connect a transport to an in-memory fake `signal-cli`, returning the
+    /// fake's side as (lines from the transport, writer to the transport).
+    fn fake_cli() -> (
+        SignalCliTransport,
+        tokio::io::Lines<BufReader<tokio::io::ReadHalf<tokio::io::DuplexStream>>>,
+        tokio::io::WriteHalf<tokio::io::DuplexStream>,
+    ) {
+        let (ours, theirs) = tokio::io::duplex(4096);
+        let (our_read, our_write) = tokio::io::split(ours);
+        let (their_read, their_write) = tokio::io::split(theirs);
+        let transport = SignalCliTransport::from_io(our_read, our_write);
+        (transport, BufReader::new(their_read).lines(), their_write)
+    }
+
+    #[test]
+    fn test_parse_receive_data_message() {
+        let params = json!({
+            "envelope": {
+                "sourceNumber": "+15550001",
+                "timestamp": 1700000000000u64,
+                "dataMessage": {
+                    "timestamp": 1700000000000u64,
+                    "message": "hello",
+                    "groupInfo": { "groupId": "g==", "groupName": "Ops" },
+                    "attachments": [
+                        { "contentType": "image/png", "filename": "a.png", "size": 42, "id": "att1.png" }
+                    ]
+                }
+            },
+            "account": "+15559999"
+        });
+        let msg = parse_receive(&params, Some(Path::new("/var/lib/signal/attachments"))).unwrap();
+        assert_eq!(msg.sender, "+15550001");
+        assert_eq!(msg.recipient.as_deref(), Some("+15559999"));
+        assert_eq!(msg.body, "hello");
+        assert_eq!(msg.group.as_ref().unwrap().id, "g==");
+        assert_eq!(msg.attachments[0].filename.as_deref(), Some("a.png"));
+        assert_eq!(
+            msg.attachments[0].local_path.as_deref(),
+            Some("/var/lib/signal/attachments/att1.png")
+        );
+        assert_eq!(
+            msg.timestamp,
+            SystemTime::UNIX_EPOCH + Duration::from_millis(1700000000000)
+        );
+    }
+
+    #[test]
+    fn test_parse_receive_ignores_receipts() {
+        let params = json!({
+            "envelope": {
+                "sourceNumber": "+15550001",
+                "receiptMessage": { "isDelivery": true, "timestamps": [1] }
+            }
+        });
+        assert!(parse_receive(&params, None).is_none());
+    }
+
+    #[test]
+    fn test_parse_receive_remote_delete() {
+        let params = json!({
+            "envelope": {
+                "sourceNumber": "+15550001",
+                "dataMessage": {
+                    "timestamp": 1700000005000u64,
+                    "remoteDelete": { "timestamp": 1700000000000u64 }
+                }
+            }
+        });
+        let msg = parse_receive(&params, None).unwrap();
+        assert_eq!(msg.sender, "+15550001");
+        assert_eq!(msg.remote_delete, Some(1700000000000));
+        assert!(msg.body.is_empty());
+    }
+
+    #[test]
+    fn test_parse_response_error() {
+        let value = json!({ "jsonrpc": "2.0", "id": 1, "error": { "code": -1, "message": "Unregistered user" } });
+        let err = parse_response(&value).unwrap_err();
+        assert!(err.to_string().contains("Unregistered user"));
+    }
+
+    #[tokio::test]
+    async fn test_send_round_trip() {
+        let (transport, mut requests, mut responses) = fake_cli();
+
+        let fake = tokio::spawn(async move {
+            let line = requests.next_line().await.unwrap().unwrap();
+            let request: Value = serde_json::from_str(&line).unwrap();
+            assert_eq!(request["method"], "send");
+            assert_eq!(request["params"]["recipient"][0], "+15550001");
+            assert_eq!(request["params"]["message"], "hi there");
+            let response =
+                json!({ "jsonrpc": "2.0", "id": request["id"], "result": { "timestamp": 1234 } });
+            responses
+                .write_all(format!("{response}\n").as_bytes())
+                .await
+                .unwrap();
+            (requests, responses)
+        });
+
+        let timestamp = transport.send("+15550001", "hi there").await.unwrap();
+        assert_eq!(timestamp, 1234);
+        drop(fake.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_receipt_and_typing_requests() {
+        let (transport, mut requests, mut responses) = fake_cli();
+
+        let fake = tokio::spawn(async move {
+            let mut methods = Vec::new();
+            for _ in 0..3 {
+                let line = requests.next_line().await.unwrap().unwrap();
+                let request: Value = serde_json::from_str(&line).unwrap();
+                let response = json!({ "jsonrpc": "2.0", "id": request["id"], "result": {} });
+                responses
+                    .write_all(format!("{response}\n").as_bytes())
+                    .await
+                    .unwrap();
+                methods.push(request);
+            }
+            methods
+        });
+
+        transport
+            .send_receipt("+15550001", 1700000000000)
+            .await
+            .unwrap();
+        transport.send_typing("+15550001", true).await.unwrap();
+        transport.send_typing("+15550001", false).await.unwrap();
+
+        let requests = fake.await.unwrap();
+        assert_eq!(requests[0]["method"], "sendReceipt");
+        assert_eq!(requests[0]["params"]["recipient"], "+15550001");
+        assert_eq!(
+            requests[0]["params"]["targetTimestamp"][0],
+            1700000000000u64
+        );
+        assert_eq!(requests[0]["params"]["type"], "read");
+        assert_eq!(requests[1]["method"], "sendTyping");
+        assert_eq!(requests[1]["params"]["recipient"][0], "+15550001");
+        assert_eq!(requests[1]["params"]["stop"], false);
+        assert_eq!(requests[2]["params"]["stop"], true);
+    }
+
+    #[tokio::test]
+    async fn test_incoming_notifications() {
+        let (transport, _requests, mut responses) = fake_cli();
+        let mut incoming = transport.take_incoming().unwrap();
+        assert!(transport.take_incoming().is_none());
+
+        let notification = json!({
+            "jsonrpc": "2.0",
+            "method": "receive",
+            "params": { "envelope": { "source": "+15550001", "dataMessage": { "message": "ping" } } }
+        });
+        responses
+            .write_all(format!("not json\n{notification}\n").as_bytes())
+            .await
+            .unwrap();
+
+        let msg = incoming.recv().await.unwrap();
+        assert_eq!(msg.sender, "+15550001");
+        assert_eq!(msg.body, "ping");
+
+        // Closing signal-cli's output ends the stream.
+        drop(responses);
+        drop(_requests);
+        assert!(incoming.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_link_round_trip() {
+        let (transport, mut requests, mut responses) = fake_cli();
+        let uri = "sgnl://linkdevice?uuid=abc&pub_key=def";
+
+        let fake = tokio::spawn(async move {
+            let line = requests.next_line().await.unwrap().unwrap();
+            let request: Value = serde_json::from_str(&line).unwrap();
+            assert_eq!(request["method"], "startLink");
+            let response = json!({ "jsonrpc": "2.0", "id": request["id"], "result": { "deviceLinkUri": uri } });
+            responses
+                .write_all(format!("{response}\n").as_bytes())
+                .await
+                .unwrap();
+
+            let line = requests.next_line().await.unwrap().unwrap();
+            let request: Value = serde_json::from_str(&line).unwrap();
+            assert_eq!(request["method"], "finishLink");
+            assert_eq!(request["params"]["deviceLinkUri"], uri);
+            assert_eq!(request["params"]["deviceName"], "crustyclaw");
+            let response = json!({ "jsonrpc": "2.0", "id": request["id"], "result": { "number": "+15559999" } });
+            responses
+                .write_all(format!("{response}\n").as_bytes())
+                .await
+                .unwrap();
+            (requests, responses)
+        });
+
+        assert_eq!(transport.start_link().await.unwrap(), uri);
+        let number = transport.finish_link(uri, "crustyclaw").await.unwrap();
+        assert_eq!(number, "+15559999");
+        drop(fake.await.unwrap());
+    }
+
+    #[test]
+    fn test_create_private_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("signal/data");
+        create_private_dir(&dir).unwrap();
+        let mode = std::fs::metadata(&dir).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+    }
+
+    #[tokio::test]
+    async fn test_send_fails_when_cli_exits() {
+        let (transport, requests, responses) = fake_cli();
+        drop(requests);
+        drop(responses);
+        assert!(transport.send("+15550001", "hi").await.is_err());
+    }
+
+    #[test]
+    fn test_spawn_missing_binary() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let _guard = rt.enter();
+        let result =
+            SignalCliTransport::spawn("/nonexistent/signal-cli", Path::new("/tmp"), "+15550001");
+        assert!(matches!(result, Err(SignalError::ReceiveFailed(_))));
+    }
+}
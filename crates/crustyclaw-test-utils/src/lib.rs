@@ -14,4 +14,5 @@
 
 pub mod config;
 pub mod daemon;
+pub mod tls;
 pub mod tracing_setup;
@@ -2,13 +2,17 @@
 
 use std::time::Instant;
 
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use crustyclaw_config::AppConfig;
-use crustyclaw_core::LogReader;
+use crustyclaw_core::ipc::{ChatEvent, MessageEvent, SkillRunEvent};
+use tokio::sync::{mpsc, watch};
 
+use crate::connection::{
+    ChatCommand, ConfigCommand, ConfigUpdate, ConnectionState, DaemonSnapshot, LogEvent,
+};
 use crate::keymap::{Action, KeyMapper};
 use crate::panels::{
-    ConfigPanel, DashboardPanel, LogsPanel, MessageDirection, MessageEntry, MessagesPanel,
-    PanelState,
+    ChatPanel, ConfigPanel, DashboardPanel, LogsPanel, MessagesPanel, MessagesView, PanelState,
 };
 
 /// The panels available in the TUI.
@@ -18,6 +22,7 @@ pub enum Panel {
     Logs,
     Messages,
     Config,
+    Chat,
 }
 
 impl Panel {
@@ -27,6 +32,7 @@ impl Panel {
             Panel::Logs => "Logs",
             Panel::Messages => "Messages",
             Panel::Config => "Config",
+            Panel::Chat => "Chat",
         }
     }
 
@@ -36,6 +42,7 @@ impl Panel {
             Panel::Logs => 1,
             Panel::Messages => 2,
             Panel::Config => 3,
+            Panel::Chat => 4,
         }
     }
 
@@ -44,25 +51,28 @@ impl Panel {
             Panel::Dashboard => Panel::Logs,
             Panel::Logs => Panel::Messages,
             Panel::Messages => Panel::Config,
-            Panel::Config => Panel::Dashboard,
+            Panel::Config => Panel::Chat,
+            Panel::Chat => Panel::Dashboard,
         }
     }
 
     pub fn prev(self) -> Self {
         match self {
-            Panel::Dashboard => Panel::Config,
+            Panel::Dashboard => Panel::Chat,
             Panel::Logs => Panel::Dashboard,
             Panel::Messages => Panel::Logs,
             Panel::Config => Panel::Messages,
+            Panel::Chat => Panel::Config,
         }
     }
 }
 
-const ALL_PANELS: [Panel; 4] = [
+const ALL_PANELS: [Panel; 5] = [
     Panel::Dashboard,
     Panel::Logs,
     Panel::Messages,
     Panel::Config,
+    Panel::Chat,
 ];
 
 /// TUI application state.
@@ -79,6 +89,9 @@ pub struct App {
     /// Key mapper for vim-style bindings.
     pub keymap: KeyMapper,
 
+    /// Whether the keybinding help overlay is shown.
+    pub show_help: bool,
+
     /// Dashboard panel state.
     pub dashboard: DashboardPanel,
 
@@ -90,40 +103,163 @@ pub struct App {
 
     /// Config panel state.
     pub config_panel: ConfigPanel,
+
+    /// Chat panel state.
+    pub chat: ChatPanel,
+
+    /// Non-fatal configuration warnings shown in the banner.
+    pub warnings: Vec<String>,
+
+    /// Latest data polled from the daemon.
+    pub daemon: DaemonSnapshot,
+
+    /// Conversation chosen in the Messages panel, read by the poller.
+    conversation_tx: watch::Sender<Option<String>>,
+
+    /// Chat panel requests, read by the chat task.
+    chat_tx: mpsc::UnboundedSender<ChatCommand>,
+
+    /// Config panel requests, read by the config task.
+    config_tx: mpsc::UnboundedSender<ConfigCommand>,
 }
 
 impl App {
-    /// Create a new App with the given configuration and log reader.
-    pub fn new(config: AppConfig, log_reader: LogReader) -> Self {
+    /// Create a new App with the given configuration.
+    pub fn new(config: AppConfig) -> Self {
         let config_toml =
-            toml::to_string_pretty(&config).unwrap_or_else(|e| format!("(error: {e})"));
-
-        let mut messages = MessagesPanel::new();
-        messages.push(MessageEntry {
-            timestamp: "00:00:00".to_string(),
-            channel: "system".to_string(),
-            direction: MessageDirection::Inbound,
-            body: "CrustyClaw TUI started".to_string(),
-        });
-        messages.push(MessageEntry {
-            timestamp: "00:00:00".to_string(),
-            channel: "system".to_string(),
-            direction: MessageDirection::Outbound,
-            body: format!(
-                "Daemon ready on {}:{}",
-                config.daemon.listen_addr, config.daemon.listen_port
-            ),
-        });
+            toml::to_string_pretty(&config.redacted()).unwrap_or_else(|e| format!("(error: {e})"));
+
+        let collector = crustyclaw_core::WarningCollector::new();
+        crustyclaw_core::warnings::check_lint(&config, &collector);
+        let warnings = collector.list().iter().map(|w| w.to_string()).collect();
 
         Self {
             should_quit: false,
             active_panel: Panel::Dashboard,
             start_time: Instant::now(),
-            keymap: KeyMapper::new(),
+            keymap: KeyMapper::from_config(&config.tui),
+            show_help: false,
             dashboard: DashboardPanel::new(&config),
-            logs: LogsPanel::new(log_reader),
-            messages,
+            logs: LogsPanel::new(),
+            messages: MessagesPanel::new(),
             config_panel: ConfigPanel::new(config_toml),
+            chat: ChatPanel::new(),
+            warnings,
+            daemon: DaemonSnapshot::default(),
+            conversation_tx: watch::channel(None).0,
+            chat_tx: mpsc::unbounded_channel().0,
+            config_tx: mpsc::unbounded_channel().0,
+        }
+    }
+
+    /// The Messages panel's conversation selection, for the daemon poller.
+    pub fn conversation_selection(&self) -> watch::Receiver<Option<String>> {
+        self.conversation_tx.subscribe()
+    }
+
+    /// The Chat panel's requests, for the chat task. Requests made before
+    /// this is called are dropped.
+    pub fn chat_commands(&mut self) -> mpsc::UnboundedReceiver<ChatCommand> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.chat_tx = tx;
+        rx
+    }
+
+    /// The Config panel's requests, for the config task. Requests made
+    /// before this is called are dropped.
+    pub fn config_commands(&mut self) -> mpsc::UnboundedReceiver<ConfigCommand> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.config_tx = tx;
+        rx
+    }
+
+    /// Process a key press.
+    ///
+    /// On the Chat panel, typing edits the input line, `Enter` sends it,
+    /// `Esc` cancels the message in progress, and the arrow and page keys
+    /// scroll. While the Config panel's field editor or the Messages
+    /// panel's search or detail view is open, every key goes to it. Other
+    /// keys (e.g. `Tab`) go through the key map as usual. Any key closes
+    /// the help overlay.
+    pub fn handle_key(&mut self, key: KeyEvent) {
+        if self.show_help {
+            self.show_help = false;
+            return;
+        }
+        if self.active_panel == Panel::Chat && self.handle_chat_key(key) {
+            return;
+        }
+        if self.active_panel == Panel::Config && self.config_panel.is_editing() {
+            self.handle_config_edit_key(key);
+            return;
+        }
+        if self.active_panel == Panel::Messages && self.messages.is_searching() {
+            self.handle_message_search_key(key);
+            return;
+        }
+        if self.active_panel == Panel::Messages && self.messages.is_showing_detail() {
+            if matches!(key.code, KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q')) {
+                self.messages.close_detail();
+            }
+            return;
+        }
+        let action = self.keymap.resolve(key.code);
+        self.handle_action(action);
+    }
+
+    /// Handle a key on the Chat panel; returns whether it was consumed.
+    fn handle_chat_key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Enter => {
+                if let Some(command) = self.chat.submit() {
+                    let _ = self.chat_tx.send(command);
+                }
+            }
+            KeyCode::Esc => {
+                if self.chat.cancel() {
+                    let _ = self.chat_tx.send(ChatCommand::Cancel);
+                }
+            }
+            KeyCode::Backspace => self.chat.backspace(),
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.chat.insert(c)
+            }
+            KeyCode::Up => self.chat.scroll_up(1),
+            KeyCode::Down => self.chat.scroll_down(1),
+            KeyCode::PageUp => self.chat.scroll_up(10),
+            KeyCode::PageDown => self.chat.scroll_down(10),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Handle a key in the Config panel's field editor.
+    fn handle_config_edit_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Enter => {
+                if let Some(command) = self.config_panel.submit_edit() {
+                    let _ = self.config_tx.send(command);
+                }
+            }
+            KeyCode::Esc => self.config_panel.cancel_edit(),
+            KeyCode::Backspace => self.config_panel.backspace(),
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.config_panel.insert(c)
+            }
+            _ => {}
+        }
+    }
+
+    /// Handle a key while typing a search in the Messages panel.
+    fn handle_message_search_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Enter => self.messages.finish_search(),
+            KeyCode::Esc => self.messages.clear_search(),
+            KeyCode::Backspace => self.messages.backspace(),
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.messages.insert(c)
+            }
+            _ => {}
         }
     }
 
@@ -131,11 +267,12 @@ impl App {
     pub fn handle_action(&mut self, action: Action) {
         match action {
             Action::Quit => self.should_quit = true,
-            Action::NextPanel => self.active_panel = self.active_panel.next(),
-            Action::PrevPanel => self.active_panel = self.active_panel.prev(),
+            Action::Help => self.show_help = !self.show_help,
+            Action::NextPanel => self.show_panel(self.active_panel.next()),
+            Action::PrevPanel => self.show_panel(self.active_panel.prev()),
             Action::GoToPanel(n) => {
                 if let Some(&panel) = ALL_PANELS.get(n) {
-                    self.active_panel = panel;
+                    self.show_panel(panel);
                 }
             }
             Action::ScrollDown => self.active_panel_state_mut().scroll_down(1),
@@ -144,14 +281,123 @@ impl App {
             Action::HalfPageUp => self.active_panel_state_mut().scroll_up(10),
             Action::ScrollToTop => self.active_panel_state_mut().scroll_to_top(),
             Action::ScrollToBottom => self.active_panel_state_mut().scroll_to_bottom(),
-            Action::None => {}
+            Action::CycleLogLevel => {
+                if self.active_panel == Panel::Logs {
+                    self.logs.cycle_level_filter();
+                }
+            }
+            Action::NextConversation => self.cycle_conversation(1),
+            Action::PrevConversation => self.cycle_conversation(-1),
+            Action::NextSection if self.active_panel == Panel::Config => {
+                self.config_panel.next_section()
+            }
+            Action::PrevSection if self.active_panel == Panel::Config => {
+                self.config_panel.prev_section()
+            }
+            Action::EditField | Action::Open if self.active_panel == Panel::Config => {
+                self.config_panel.start_edit()
+            }
+            Action::TogglePause if self.active_panel == Panel::Messages => {
+                self.messages.toggle_pause()
+            }
+            Action::Search if self.active_panel == Panel::Messages => self.messages.start_search(),
+            Action::ToggleView if self.active_panel == Panel::Messages => {
+                self.messages.toggle_view()
+            }
+            Action::Open if self.active_panel == Panel::Messages => self.messages.open_detail(),
+            Action::NextSection
+            | Action::PrevSection
+            | Action::EditField
+            | Action::TogglePause
+            | Action::Search
+            | Action::ToggleView
+            | Action::Open
+            | Action::None => {}
+        }
+    }
+
+    /// Switch to `panel`, refetching the config when it is the Config
+    /// panel.
+    fn show_panel(&mut self, panel: Panel) {
+        if panel == Panel::Config && self.active_panel != Panel::Config {
+            let _ = self.config_tx.send(ConfigCommand::Refresh);
         }
+        self.active_panel = panel;
     }
 
-    /// Tick: refresh data from live sources (log reader, etc).
+    /// Select the conversation `step` places from the one shown, wrapping.
+    fn cycle_conversation(&mut self, step: isize) {
+        if self.active_panel != Panel::Messages {
+            return;
+        }
+        self.messages.set_view(MessagesView::History);
+        let Some(conversations) = self.daemon.conversations.as_deref() else {
+            return;
+        };
+        if conversations.is_empty() {
+            return;
+        }
+        let current = self
+            .daemon
+            .history
+            .as_ref()
+            .and_then(|h| conversations.iter().position(|c| c.id == h.id))
+            .unwrap_or_default();
+        let next = (current as isize + step).rem_euclid(conversations.len() as isize) as usize;
+        self.conversation_tx
+            .send_replace(Some(conversations[next].id.clone()));
+    }
+
+    /// Record a new snapshot from the daemon poller.
+    pub fn apply_snapshot(&mut self, snapshot: DaemonSnapshot) {
+        self.dashboard.apply_snapshot(&snapshot);
+        if let (Some(conversations), Some(history)) = (&snapshot.conversations, &snapshot.history) {
+            self.messages.show_history(conversations, history);
+        }
+        self.daemon = snapshot;
+    }
+
+    /// Record an update from the log stream.
+    pub fn apply_log_event(&mut self, event: LogEvent) {
+        match event {
+            LogEvent::Entry(entry) => self.logs.push(entry),
+            LogEvent::State(state) => self.logs.set_connection(state),
+        }
+    }
+
+    /// Record a message from the message stream.
+    pub fn apply_message(&mut self, message: MessageEvent) {
+        self.messages.push_live(message);
+    }
+
+    /// Record an update from the config task.
+    pub fn apply_config_update(&mut self, update: ConfigUpdate) {
+        match update {
+            ConfigUpdate::Loaded(toml) => self.config_panel.set_toml(&toml),
+            ConfigUpdate::Failed(error) => self.config_panel.set_error(error),
+        }
+    }
+
+    /// Record an event from the chat task.
+    pub fn apply_chat_event(&mut self, event: ChatEvent) {
+        self.chat.apply_event(event);
+    }
+
+    /// Record a skill run's output from the chat task.
+    pub fn apply_skill_event(&mut self, event: SkillRunEvent) {
+        self.chat.apply_skill_event(event);
+    }
+
+    /// Tick: refresh time-derived state between daemon polls.
     pub fn tick(&mut self) {
-        self.logs.refresh();
-        self.dashboard.uptime = self.start_time.elapsed();
+        // Interpolate daemon uptime between polls; fall back to TUI uptime.
+        self.dashboard.uptime = match &self.daemon.status {
+            Some(status) => {
+                std::time::Duration::from_secs(status.uptime_secs)
+                    + self.daemon.received_at.elapsed()
+            }
+            None => self.start_time.elapsed(),
+        };
     }
 
     fn active_panel_state_mut(&mut self) -> &mut dyn PanelState {
@@ -160,13 +406,41 @@ impl App {
             Panel::Logs => &mut self.logs,
             Panel::Messages => &mut self.messages,
             Panel::Config => &mut self.config_panel,
+            Panel::Chat => &mut self.chat,
+        }
+    }
+
+    /// Get the warning banner text, if there are any warnings.
+    pub fn warning_banner(&self) -> Option<String> {
+        match self.warnings.as_slice() {
+            [] => None,
+            [only] => Some(format!(" ⚠ {only}")),
+            [first, rest @ ..] => Some(format!(" ⚠ {first} (+{} more)", rest.len())),
         }
     }
 
     /// Get the status line text.
     pub fn status_line(&self) -> String {
+        let connection = match &self.daemon.state {
+            ConnectionState::Connecting => "daemon: connecting…".to_string(),
+            ConnectionState::Connected => "daemon: connected".to_string(),
+            ConnectionState::Disconnected { error, retry_in } => format!(
+                "daemon: disconnected ({error}) — retrying in {}s",
+                retry_in.as_secs()
+            ),
+        };
+        let hint = |action, name| {
+            self.keymap
+                .keys_for(action)
+                .map(|keys| format!("{keys}:{name}  "))
+                .unwrap_or_default()
+        };
         format!(
-            " q:quit  Tab/l:next  BackTab/h:prev  j/k:scroll  g/G:top/bottom  1-4:panels  [{panel}]",
+            " {}{}{}{}[{panel}]  {connection}",
+            hint(Action::Quit, "quit"),
+            hint(Action::Help, "help"),
+            hint(Action::NextPanel, "next"),
+            hint(Action::ScrollDown, "scroll"),
             panel = self.active_panel.title()
         )
     }
@@ -178,10 +452,7 @@ mod tests {
     use crate::keymap::Action;
 
     fn make_app() -> App {
-        let config = AppConfig::default();
-        let collector = crustyclaw_core::LogCollector::new(100);
-        let reader = collector.reader();
-        App::new(config, reader)
+        App::new(AppConfig::default())
     }
 
     // ── Panel enum tests ──────────────────────────────────────────
@@ -192,6 +463,7 @@ mod tests {
         assert_eq!(Panel::Logs.title(), "Logs");
         assert_eq!(Panel::Messages.title(), "Messages");
         assert_eq!(Panel::Config.title(), "Config");
+        assert_eq!(Panel::Chat.title(), "Chat");
     }
 
     #[test]
@@ -200,6 +472,7 @@ mod tests {
         assert_eq!(Panel::Logs.index(), 1);
         assert_eq!(Panel::Messages.index(), 2);
         assert_eq!(Panel::Config.index(), 3);
+        assert_eq!(Panel::Chat.index(), 4);
     }
 
     #[test]
@@ -207,12 +480,14 @@ mod tests {
         assert_eq!(Panel::Dashboard.next(), Panel::Logs);
         assert_eq!(Panel::Logs.next(), Panel::Messages);
         assert_eq!(Panel::Messages.next(), Panel::Config);
-        assert_eq!(Panel::Config.next(), Panel::Dashboard);
+        assert_eq!(Panel::Config.next(), Panel::Chat);
+        assert_eq!(Panel::Chat.next(), Panel::Dashboard);
     }
 
     #[test]
     fn test_panel_prev_wraps() {
-        assert_eq!(Panel::Dashboard.prev(), Panel::Config);
+        assert_eq!(Panel::Dashboard.prev(), Panel::Chat);
+        assert_eq!(Panel::Chat.prev(), Panel::Config);
         assert_eq!(Panel::Config.prev(), Panel::Messages);
         assert_eq!(Panel::Messages.prev(), Panel::Logs);
         assert_eq!(Panel::Logs.prev(), Panel::Dashboard);
@@ -266,7 +541,7 @@ mod tests {
     #[test]
     fn test_full_panel_cycle() {
         let mut app = make_app();
-        for _ in 0..4 {
+        for _ in 0..5 {
             app.handle_action(Action::NextPanel);
         }
         // Should wrap back to Dashboard
@@ -277,7 +552,7 @@ mod tests {
     fn test_scroll_actions_no_panic() {
         let mut app = make_app();
         // Scroll on each panel to exercise all PanelState impls
-        for i in 0..4 {
+        for i in 0..5 {
             app.handle_action(Action::GoToPanel(i));
             app.handle_action(Action::ScrollDown);
             app.handle_action(Action::ScrollUp);
@@ -297,6 +572,211 @@ mod tests {
         assert!(!app.should_quit);
     }
 
+    #[test]
+    fn test_cycle_log_level_only_on_logs_panel() {
+        let mut app = make_app();
+        app.handle_action(Action::CycleLogLevel);
+        assert_eq!(app.logs.min_level, None);
+
+        app.handle_action(Action::GoToPanel(1));
+        app.handle_action(Action::CycleLogLevel);
+        assert_eq!(app.logs.min_level, Some(tracing::Level::DEBUG));
+    }
+
+    #[test]
+    fn test_apply_log_event() {
+        let mut app = make_app();
+        app.apply_log_event(LogEvent::State(ConnectionState::Connected));
+        app.apply_log_event(LogEvent::Entry(crustyclaw_core::ipc::LogEntry {
+            seq: 1,
+            elapsed_secs: 0.5,
+            level: "INFO".to_string(),
+            target: "crustyclaw_core::daemon".to_string(),
+            message: "daemon started".to_string(),
+        }));
+        // Exercise scrolling over the received entry.
+        app.handle_action(Action::GoToPanel(1));
+        app.handle_action(Action::ScrollToTop);
+    }
+
+    #[test]
+    fn test_cycle_conversation() {
+        let mut app = make_app();
+        let selection = app.conversation_selection();
+        let conversation = |id: &str| crustyclaw_core::ipc::ConversationInfo {
+            id: id.to_string(),
+            channel: "signal".to_string(),
+            peer: None,
+            messages: 1,
+            started_secs: 0,
+            updated_secs: 0,
+            preview: String::new(),
+        };
+        app.apply_snapshot(DaemonSnapshot {
+            state: ConnectionState::Connected,
+            conversations: Some(vec![conversation("a"), conversation("b")]),
+            history: Some(crustyclaw_core::ipc::ConversationResponse {
+                id: "a".to_string(),
+                messages: vec![],
+            }),
+            ..DaemonSnapshot::default()
+        });
+
+        // Only the Messages panel reacts.
+        app.handle_action(Action::NextConversation);
+        assert_eq!(*selection.borrow(), None);
+
+        app.handle_action(Action::GoToPanel(2));
+        app.handle_action(Action::NextConversation);
+        assert_eq!(selection.borrow().as_deref(), Some("b"));
+        app.handle_action(Action::PrevConversation);
+        app.handle_action(Action::PrevConversation);
+        assert_eq!(selection.borrow().as_deref(), Some("b"));
+        assert_eq!(app.messages.view, MessagesView::History);
+    }
+
+    #[test]
+    fn test_help_overlay() {
+        let mut app = make_app();
+        let press = |app: &mut App, code| app.handle_key(KeyEvent::from(code));
+        press(&mut app, KeyCode::Char('?'));
+        assert!(app.show_help);
+        // Any key closes the overlay without doing anything else.
+        press(&mut app, KeyCode::Char('q'));
+        assert!(!app.show_help);
+        assert!(!app.should_quit);
+        assert!(app.status_line().contains("?:help"));
+    }
+
+    #[test]
+    fn test_configured_keys() {
+        let config = AppConfig::parse("[tui.keys]\nquit = [\"Q\"]\nhelp = [\"H\"]\n").unwrap();
+        let mut app = App::new(config);
+        app.handle_key(KeyEvent::from(KeyCode::Char('q')));
+        assert!(!app.should_quit);
+        assert!(app.status_line().contains("Q:quit  H:help"));
+        app.handle_key(KeyEvent::from(KeyCode::Char('Q')));
+        assert!(app.should_quit);
+    }
+
+    #[test]
+    fn test_messages_keys() {
+        let mut app = make_app();
+        let press = |app: &mut App, code| app.handle_key(KeyEvent::from(code));
+        app.apply_message(MessageEvent {
+            id: 7,
+            timestamp_secs: 0,
+            channel: "signal".to_string(),
+            inbound: true,
+            peer: Some("+15550001".to_string()),
+            body: "hello".to_string(),
+            redacts: None,
+            route: crustyclaw_core::ipc::MessageRoute::Unhandled,
+        });
+        press(&mut app, KeyCode::Char('3'));
+
+        // While searching, `q` is typed rather than quitting.
+        press(&mut app, KeyCode::Char('/'));
+        assert!(app.messages.is_searching());
+        press(&mut app, KeyCode::Char('q'));
+        assert!(!app.should_quit);
+        press(&mut app, KeyCode::Esc);
+        assert!(!app.messages.is_searching());
+
+        // `Enter` opens the detail view; `q` closes it.
+        press(&mut app, KeyCode::Enter);
+        assert!(app.messages.is_showing_detail());
+        press(&mut app, KeyCode::Char('q'));
+        assert!(!app.messages.is_showing_detail());
+        assert!(!app.should_quit);
+
+        press(&mut app, KeyCode::Char('v'));
+        assert_eq!(app.messages.view, MessagesView::History);
+        press(&mut app, KeyCode::Char('q'));
+        assert!(app.should_quit);
+    }
+
+    #[test]
+    fn test_chat_keys() {
+        let mut app = make_app();
+        let mut commands = app.chat_commands();
+        let press = |app: &mut App, code| app.handle_key(KeyEvent::from(code));
+
+        // Outside the Chat panel, keys go through the key map.
+        press(&mut app, KeyCode::Char('5'));
+        assert_eq!(app.active_panel, Panel::Chat);
+
+        // On it, `q` and digits are typed rather than acted on.
+        for c in "q1".chars() {
+            press(&mut app, KeyCode::Char(c));
+        }
+        press(&mut app, KeyCode::Enter);
+        assert!(!app.should_quit);
+        assert_eq!(app.active_panel, Panel::Chat);
+        assert_eq!(
+            commands.try_recv().unwrap(),
+            ChatCommand::Send("q1".to_string())
+        );
+
+        press(&mut app, KeyCode::Esc);
+        press(&mut app, KeyCode::Esc);
+        assert_eq!(commands.try_recv().unwrap(), ChatCommand::Cancel);
+        assert!(commands.try_recv().is_err());
+
+        app.apply_chat_event(ChatEvent::Error {
+            error: "chat message cancelled".to_string(),
+        });
+        assert!(!app.chat.is_running());
+
+        press(&mut app, KeyCode::Tab);
+        assert_eq!(app.active_panel, Panel::Dashboard);
+    }
+
+    #[test]
+    fn test_config_keys() {
+        let mut app = make_app();
+        let mut commands = app.config_commands();
+        let press = |app: &mut App, code| app.handle_key(KeyEvent::from(code));
+
+        // Entering the panel refetches the config.
+        press(&mut app, KeyCode::Char('4'));
+        assert_eq!(commands.try_recv().unwrap(), ConfigCommand::Refresh);
+
+        // The config starts with `[daemon]`; its first field is listen_addr.
+        press(&mut app, KeyCode::Char(']'));
+        press(&mut app, KeyCode::Char('['));
+        press(&mut app, KeyCode::Char('j'));
+        press(&mut app, KeyCode::Char('e'));
+        assert!(app.config_panel.is_editing());
+
+        // While editing, `q` is typed rather than quitting.
+        press(&mut app, KeyCode::Char('q'));
+        assert!(!app.should_quit);
+        press(&mut app, KeyCode::Esc);
+        assert!(!app.config_panel.is_editing());
+        assert!(commands.try_recv().is_err());
+
+        press(&mut app, KeyCode::Enter);
+        for _ in 0.."\"127.0.0.1\"".len() {
+            press(&mut app, KeyCode::Backspace);
+        }
+        for c in "\"0.0.0.0\"".chars() {
+            press(&mut app, KeyCode::Char(c));
+        }
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(
+            commands.try_recv().unwrap(),
+            ConfigCommand::Set {
+                key: "daemon.listen_addr".to_string(),
+                value: "0.0.0.0".to_string(),
+            }
+        );
+
+        app.apply_config_update(ConfigUpdate::Failed("rejected".to_string()));
+        press(&mut app, KeyCode::Char('q'));
+        assert!(app.should_quit);
+    }
+
     // ── Tick ──────────────────────────────────────────────────────
 
     #[test]
@@ -307,8 +787,73 @@ mod tests {
         assert!(app.dashboard.uptime.as_millis() > 0);
     }
 
+    // ── Warning banner ────────────────────────────────────────────
+
+    #[test]
+    fn test_no_banner_for_default_config() {
+        let app = make_app();
+        assert!(app.warnings.is_empty());
+        assert!(app.warning_banner().is_none());
+    }
+
+    #[test]
+    fn test_banner_for_insecure_config() {
+        let mut config = AppConfig::default();
+        config.isolation.backend = "noop".to_string();
+        config.policy.default_effect = "allow".to_string();
+        let app = App::new(config);
+
+        assert_eq!(app.warnings.len(), 2);
+        let banner = app.warning_banner().unwrap();
+        assert!(banner.contains("isolation.backend"));
+        assert!(banner.contains("(+1 more)"));
+    }
+
     // ── Status line ───────────────────────────────────────────────
 
+    #[test]
+    fn test_status_line_shows_connection_failure() {
+        let mut app = make_app();
+        assert!(app.status_line().contains("daemon: connecting"));
+
+        app.apply_snapshot(DaemonSnapshot {
+            state: ConnectionState::Disconnected {
+                error: "daemon is not running".to_string(),
+                retry_in: std::time::Duration::from_secs(4),
+            },
+            ..DaemonSnapshot::default()
+        });
+        let line = app.status_line();
+        assert!(line.contains("disconnected (daemon is not running)"));
+        assert!(line.contains("retrying in 4s"));
+    }
+
+    #[test]
+    fn test_tick_uses_daemon_uptime() {
+        let mut app = make_app();
+        app.apply_snapshot(DaemonSnapshot {
+            state: ConnectionState::Connected,
+            status: Some(crustyclaw_core::ipc::StatusResponse {
+                running: true,
+                version: "0.1.0".to_string(),
+                git_hash: "abc123".to_string(),
+                uptime_secs: 3600,
+                listen_addr: "127.0.0.1".to_string(),
+                listen_port: 9100,
+                signal_enabled: false,
+                log_level: "info".to_string(),
+                isolation_backend: "auto".to_string(),
+                skills_count: 0,
+                plugins_count: 0,
+                pid: 1,
+                warnings: vec![],
+            }),
+            ..DaemonSnapshot::default()
+        });
+        app.tick();
+        assert!(app.dashboard.uptime.as_secs() >= 3600);
+    }
+
     #[test]
     fn test_status_line_contains_panel_name() {
         let mut app = make_app();
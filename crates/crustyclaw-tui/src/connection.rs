@@ -0,0 +1,589 @@
+//! Live daemon connection — polls the daemon over IPC for dashboard data.
+//!
+//! A background task ([`poll_daemon`]) fetches the daemon's status,
+//! isolation, and host metrics on a fixed interval, in one `GET /snapshot`
+//! round trip, and publishes a [`DaemonSnapshot`] on a `watch` channel. When
+//! the daemon is unreachable the snapshot carries
+//! [`ConnectionState::Disconnected`] and the task retries with exponential
+//! backoff, reconnecting automatically once the daemon comes back.
+//!
+//! The same request also fetches the conversation list and the history of
+//! the conversation selected in the Messages panel (the most recent one
+//! when nothing is selected).
+//!
+//! Alongside it, [`watch_events`] holds `GET /events` open and wakes the
+//! poller as soon as the daemon reports something (a config reload, a
+//! skill run, a received message), so panels update without waiting for
+//! the next interval.
+//!
+//! A second task ([`stream_logs`]) holds `GET /logs/stream` open and forwards
+//! entries to the Logs panel, resuming after the last seen sequence number
+//! when it reconnects. [`stream_messages`] does the same for the Messages
+//! panel's live view with `GET /messages/stream`; messages sent while it is
+//! disconnected are not replayed.
+//!
+//! A third task ([`run_chat`]) sends the Chat panel's messages over
+//! `POST /chat`, forwards the streamed events back, and cancels the message
+//! in progress on request. It also runs the panel's `/run` skill commands
+//! over `POST /skills/execute/stream`, forwarding output lines as the skill
+//! writes them.
+//!
+//! A fourth task ([`run_config`]) fetches the Config panel's TOML from
+//! `GET /config` and sends its field edits over `PUT /config`.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crustyclaw_core::chat::ToolFilter;
+use crustyclaw_core::ipc::{
+    ChatEvent, ConversationInfo, ConversationResponse, HostStatusResponse, IpcClient,
+    IsolationStatusResponse, LogEntry, MessageEvent, SkillRunEvent, StatusResponse,
+};
+use tokio::sync::{Notify, mpsc, watch};
+
+/// Interval between polls while connected.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// `/snapshot` sections the dashboard shows.
+const SECTIONS: [&str; 5] = ["status", "isolation", "host", "conversations", "history"];
+
+/// Initial reconnect delay after a failure.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Maximum reconnect delay.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// State of the TUI's connection to the daemon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// No poll has completed yet.
+    Connecting,
+    /// The last poll succeeded.
+    Connected,
+    /// The last poll failed; the next attempt is scheduled after `retry_in`.
+    Disconnected { error: String, retry_in: Duration },
+}
+
+/// Latest data fetched from the daemon.
+#[derive(Debug, Clone)]
+pub struct DaemonSnapshot {
+    /// Connection state as of this snapshot.
+    pub state: ConnectionState,
+    /// When this snapshot was taken (used to interpolate uptime).
+    pub received_at: Instant,
+    /// Daemon status, from the last successful poll.
+    pub status: Option<StatusResponse>,
+    /// Isolation status, from the last successful poll.
+    pub isolation: Option<IsolationStatusResponse>,
+    /// Host metrics, from the last successful poll.
+    pub host: Option<HostStatusResponse>,
+    /// Stored conversations, most recently active first.
+    pub conversations: Option<Vec<ConversationInfo>>,
+    /// History of the selected conversation.
+    pub history: Option<ConversationResponse>,
+}
+
+impl Default for DaemonSnapshot {
+    fn default() -> Self {
+        Self {
+            state: ConnectionState::Connecting,
+            received_at: Instant::now(),
+            status: None,
+            isolation: None,
+            host: None,
+            conversations: None,
+            history: None,
+        }
+    }
+}
+
+/// Double `current`, capped at [`MAX_BACKOFF`].
+fn next_backoff(current: Duration) -> Duration {
+    (current * 2).min(MAX_BACKOFF)
+}
+
+/// Poll the daemon until the receiving side is dropped.
+///
+/// `selected` names the conversation whose history to fetch. A
+/// notification on `refresh` polls right away instead of waiting out the
+/// interval. After a failure the previous data is kept (so panels can show
+/// the last known values) and only the connection state changes.
+pub async fn poll_daemon(
+    client: IpcClient,
+    tx: watch::Sender<DaemonSnapshot>,
+    selected: watch::Receiver<Option<String>>,
+    refresh: Arc<Notify>,
+) {
+    let mut backoff = INITIAL_BACKOFF;
+
+    loop {
+        let history_id = selected.borrow().clone();
+        let delay = match client
+            .snapshot(&SECTIONS, history_id.as_deref(), None)
+            .await
+        {
+            Ok(daemon) => {
+                backoff = INITIAL_BACKOFF;
+                tx.send_modify(|snapshot| {
+                    *snapshot = DaemonSnapshot {
+                        state: ConnectionState::Connected,
+                        received_at: Instant::now(),
+                        status: daemon.status,
+                        isolation: daemon.isolation,
+                        host: daemon.host,
+                        conversations: daemon.conversations.map(|r| r.conversations),
+                        history: daemon.history,
+                    };
+                });
+                POLL_INTERVAL
+            }
+            Err(e) => {
+                let retry_in = backoff;
+                backoff = next_backoff(backoff);
+                tx.send_modify(|snapshot| {
+                    snapshot.state = ConnectionState::Disconnected {
+                        error: e.to_string(),
+                        retry_in,
+                    };
+                });
+                retry_in
+            }
+        };
+
+        if tx.is_closed() {
+            return;
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = refresh.notified() => {}
+        }
+    }
+}
+
+/// Notify `refresh` for every daemon event until the poller drops its
+/// handle, reconnecting with backoff while the daemon is unreachable.
+pub async fn watch_events(client: IpcClient, refresh: Arc<Notify>) {
+    let mut backoff = INITIAL_BACKOFF;
+
+    while Arc::strong_count(&refresh) > 1 {
+        if let Ok(mut events) = client.events().await {
+            backoff = INITIAL_BACKOFF;
+            while let Ok(Some(_)) = events.next().await {
+                refresh.notify_one();
+            }
+        }
+        tokio::time::sleep(backoff).await;
+        backoff = next_backoff(backoff);
+    }
+}
+
+/// An update from the log stream task.
+#[derive(Debug, Clone)]
+pub enum LogEvent {
+    /// A log entry from the daemon.
+    Entry(LogEntry),
+    /// The stream connected or was lost.
+    State(ConnectionState),
+}
+
+/// Stream daemon logs into `tx` until the receiving side is dropped.
+pub async fn stream_logs(client: IpcClient, tx: mpsc::Sender<LogEvent>) {
+    let mut backoff = INITIAL_BACKOFF;
+    let mut last_seq = None;
+
+    loop {
+        let error = match client.logs_stream(last_seq).await {
+            Ok(mut stream) => {
+                backoff = INITIAL_BACKOFF;
+                if tx
+                    .send(LogEvent::State(ConnectionState::Connected))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+                loop {
+                    match stream.next().await {
+                        Ok(Some(entry)) => {
+                            last_seq = Some(entry.seq);
+                            if tx.send(LogEvent::Entry(entry)).await.is_err() {
+                                return;
+                            }
+                        }
+                        Ok(None) => break "log stream closed by daemon".to_string(),
+                        Err(e) => break e.to_string(),
+                    }
+                }
+            }
+            Err(e) => e.to_string(),
+        };
+
+        let retry_in = backoff;
+        backoff = next_backoff(backoff);
+        let state = ConnectionState::Disconnected { error, retry_in };
+        if tx.send(LogEvent::State(state)).await.is_err() {
+            return;
+        }
+        tokio::time::sleep(retry_in).await;
+    }
+}
+
+/// Stream the daemon's message traffic into `tx` until the receiving side
+/// is dropped, reconnecting with backoff while the daemon is unreachable.
+pub async fn stream_messages(client: IpcClient, tx: mpsc::Sender<MessageEvent>) {
+    let mut backoff = INITIAL_BACKOFF;
+
+    while !tx.is_closed() {
+        if let Ok(mut stream) = client.messages_stream().await {
+            backoff = INITIAL_BACKOFF;
+            while let Ok(Some(message)) = stream.next().await {
+                if tx.send(message).await.is_err() {
+                    return;
+                }
+            }
+        }
+        tokio::time::sleep(backoff).await;
+        backoff = next_backoff(backoff);
+    }
+}
+
+/// A request from the Config panel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigCommand {
+    /// Fetch the daemon's current config.
+    Refresh,
+    /// Set `key` to `value`, as `--set key=value` would, and hot-reload.
+    Set { key: String, value: String },
+}
+
+/// An update for the Config panel from the config task.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigUpdate {
+    /// The daemon's config as TOML, after a fetch or an applied change.
+    Loaded(String),
+    /// The fetch failed or the change was rejected.
+    Failed(String),
+}
+
+/// Serve the Config panel's requests until the command channel closes,
+/// fetching the config once at the start.
+pub async fn run_config(
+    client: IpcClient,
+    mut commands: mpsc::UnboundedReceiver<ConfigCommand>,
+    tx: mpsc::Sender<ConfigUpdate>,
+) {
+    let mut command = ConfigCommand::Refresh;
+    loop {
+        let result = match &command {
+            ConfigCommand::Refresh => client.config().await,
+            ConfigCommand::Set { key, value } => {
+                client.update_config(&[format!("{key}={value}")]).await
+            }
+        };
+        let update = match result {
+            Ok(config) => ConfigUpdate::Loaded(config.toml),
+            Err(e) => ConfigUpdate::Failed(e.to_string()),
+        };
+        if tx.send(update).await.is_err() {
+            return;
+        }
+        match commands.recv().await {
+            Some(next) => command = next,
+            None => return,
+        }
+    }
+}
+
+/// A request from the Chat panel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChatCommand {
+    /// Send a message, continuing the session.
+    Send(String),
+    /// Run a skill, streaming its output.
+    RunSkill {
+        name: String,
+        args: serde_json::Map<String, serde_json::Value>,
+    },
+    /// Cancel the message or skill run in progress.
+    Cancel,
+}
+
+/// An update for the Chat panel from the chat task.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChatUpdate {
+    /// Progress of a message.
+    Chat(ChatEvent),
+    /// Output of a skill run.
+    Skill(SkillRunEvent),
+}
+
+/// Run the Chat panel's messages and skill runs until the command channel
+/// closes.
+///
+/// Each message's events are forwarded to `tx`. A message that cannot be
+/// sent, or whose stream breaks, is reported as an [`ChatEvent::Error`];
+/// when the message could not be sent the session is dropped, so the next
+/// one starts a new session (e.g. after a daemon restart).
+pub async fn run_chat(
+    client: IpcClient,
+    mut commands: mpsc::UnboundedReceiver<ChatCommand>,
+    tx: mpsc::Sender<ChatUpdate>,
+) {
+    let mut session: Option<String> = None;
+
+    while let Some(command) = commands.recv().await {
+        let message = match command {
+            ChatCommand::Send(message) => message,
+            ChatCommand::RunSkill { name, args } => {
+                if !run_skill(&client, &name, args, &mut commands, &tx).await {
+                    return;
+                }
+                continue;
+            }
+            ChatCommand::Cancel => continue,
+        };
+        let mut stream = match client
+            .chat(session.as_deref(), &message, &ToolFilter::default())
+            .await
+        {
+            Ok(stream) => stream,
+            Err(e) => {
+                session = None;
+                if tx
+                    .send(ChatUpdate::Chat(ChatEvent::Error {
+                        error: e.to_string(),
+                    }))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+                continue;
+            }
+        };
+        loop {
+            tokio::select! {
+                event = stream.next() => {
+                    let event = match event {
+                        Ok(Some(event)) => event,
+                        Ok(None) => break,
+                        Err(e) => ChatEvent::Error { error: e.to_string() },
+                    };
+                    if let ChatEvent::Session { id } = &event {
+                        session = Some(id.clone());
+                    }
+                    let last = matches!(event, ChatEvent::Done { .. } | ChatEvent::Error { .. });
+                    if tx.send(ChatUpdate::Chat(event)).await.is_err() {
+                        return;
+                    }
+                    if last {
+                        break;
+                    }
+                }
+                command = commands.recv() => match command {
+                    Some(ChatCommand::Cancel) => {
+                        // The stream then ends with a cancellation error.
+                        if let Some(id) = &session
+                            && let Err(e) = client.chat_cancel(id).await
+                        {
+                            let error = format!("cancel failed: {e}");
+                            if tx.send(ChatUpdate::Chat(ChatEvent::Error { error })).await.is_err() {
+                                return;
+                            }
+                            break;
+                        }
+                    }
+                    // The panel sends nothing while a message is in progress.
+                    Some(ChatCommand::Send(_) | ChatCommand::RunSkill { .. }) => {}
+                    None => return,
+                },
+            }
+        }
+    }
+}
+
+/// Run skill `name`, forwarding its output to `tx` until it finishes or a
+/// cancel arrives. Returns `false` once the panel has gone away.
+///
+/// Cancelling closes the stream, which stops the run on the daemon.
+async fn run_skill(
+    client: &IpcClient,
+    name: &str,
+    args: serde_json::Map<String, serde_json::Value>,
+    commands: &mut mpsc::UnboundedReceiver<ChatCommand>,
+    tx: &mpsc::Sender<ChatUpdate>,
+) -> bool {
+    let send = |event| tx.send(ChatUpdate::Skill(event));
+    let mut stream = match client.execute_skill_stream(name, args, None).await {
+        Ok(stream) => stream,
+        Err(e) => {
+            let error = e.to_string();
+            return send(SkillRunEvent::Error { error }).await.is_ok();
+        }
+    };
+    loop {
+        tokio::select! {
+            event = stream.next() => {
+                let event = match event {
+                    Ok(Some(event)) => event,
+                    Ok(None) => SkillRunEvent::Error {
+                        error: "skill output ended before the skill finished".to_string(),
+                    },
+                    Err(e) => SkillRunEvent::Error { error: e.to_string() },
+                };
+                let last = matches!(event, SkillRunEvent::Done { .. } | SkillRunEvent::Error { .. });
+                if send(event).await.is_err() {
+                    return false;
+                }
+                if last {
+                    return true;
+                }
+            }
+            command = commands.recv() => match command {
+                Some(ChatCommand::Cancel) => {
+                    let error = "skill run cancelled".to_string();
+                    return send(SkillRunEvent::Error { error }).await.is_ok();
+                }
+                Some(ChatCommand::Send(_) | ChatCommand::RunSkill { .. }) => {}
+                None => return false,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_and_caps() {
+        assert_eq!(next_backoff(Duration::from_secs(1)), Duration::from_secs(2));
+        assert_eq!(next_backoff(Duration::from_secs(16)), MAX_BACKOFF);
+        assert_eq!(next_backoff(MAX_BACKOFF), MAX_BACKOFF);
+    }
+
+    #[tokio::test]
+    async fn test_poll_reports_disconnected() {
+        let client = IpcClient::new("/tmp/nonexistent-crustyclaw-tui.sock");
+        let (tx, mut rx) = watch::channel(DaemonSnapshot::default());
+        let (_selected_tx, selected) = watch::channel(None);
+        let refresh = Arc::new(Notify::new());
+        let handle = tokio::spawn(poll_daemon(client, tx, selected, refresh));
+
+        rx.changed().await.unwrap();
+        match &rx.borrow().state {
+            ConnectionState::Disconnected { error, retry_in } => {
+                assert!(error.contains("not running"));
+                assert_eq!(*retry_in, INITIAL_BACKOFF);
+            }
+            other => panic!("expected disconnected, got {other:?}"),
+        }
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_stream_messages_stops_without_receiver() {
+        let client = IpcClient::new("/tmp/nonexistent-crustyclaw-tui-messages.sock");
+        let (tx, rx) = mpsc::channel(1);
+        drop(rx);
+        tokio::time::timeout(Duration::from_secs(1), stream_messages(client, tx))
+            .await
+            .expect("stream_messages should return once the panel is gone");
+    }
+
+    #[tokio::test]
+    async fn test_refresh_polls_before_the_interval() {
+        let client = IpcClient::new("/tmp/nonexistent-crustyclaw-tui-refresh.sock");
+        let (tx, mut rx) = watch::channel(DaemonSnapshot::default());
+        let (_selected_tx, selected) = watch::channel(None);
+        let refresh = Arc::new(Notify::new());
+        let handle = tokio::spawn(poll_daemon(client, tx, selected, refresh.clone()));
+
+        rx.changed().await.unwrap();
+        refresh.notify_one();
+        tokio::time::timeout(INITIAL_BACKOFF / 2, rx.changed())
+            .await
+            .expect("refresh should poll before the retry delay")
+            .unwrap();
+        match &rx.borrow().state {
+            ConnectionState::Disconnected { retry_in, .. } => {
+                assert_eq!(*retry_in, next_backoff(INITIAL_BACKOFF));
+            }
+            other => panic!("expected disconnected, got {other:?}"),
+        }
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_run_chat_reports_send_failure() {
+        let client = IpcClient::new("/tmp/nonexistent-crustyclaw-tui-chat.sock");
+        let (commands_tx, commands) = mpsc::unbounded_channel();
+        let (tx, mut rx) = mpsc::channel(8);
+        let handle = tokio::spawn(run_chat(client, commands, tx));
+
+        // A cancel with nothing in progress is ignored.
+        commands_tx.send(ChatCommand::Cancel).unwrap();
+        commands_tx
+            .send(ChatCommand::Send("hi".to_string()))
+            .unwrap();
+        match rx.recv().await.unwrap() {
+            ChatUpdate::Chat(ChatEvent::Error { error }) => assert!(error.contains("not running")),
+            other => panic!("expected error, got {other:?}"),
+        }
+
+        commands_tx
+            .send(ChatCommand::RunSkill {
+                name: "greet".to_string(),
+                args: serde_json::Map::new(),
+            })
+            .unwrap();
+        match rx.recv().await.unwrap() {
+            ChatUpdate::Skill(SkillRunEvent::Error { error }) => {
+                assert!(error.contains("not running"))
+            }
+            other => panic!("expected error, got {other:?}"),
+        }
+
+        drop(commands_tx);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_run_config_reports_failures() {
+        let client = IpcClient::new("/tmp/nonexistent-crustyclaw-tui-config.sock");
+        let (commands_tx, commands) = mpsc::unbounded_channel();
+        let (tx, mut rx) = mpsc::channel(8);
+        let handle = tokio::spawn(run_config(client, commands, tx));
+
+        // The initial fetch.
+        assert!(
+            matches!(rx.recv().await.unwrap(), ConfigUpdate::Failed(e) if e.contains("not running"))
+        );
+        commands_tx
+            .send(ConfigCommand::Set {
+                key: "daemon.listen_port".to_string(),
+                value: "9200".to_string(),
+            })
+            .unwrap();
+        assert!(matches!(rx.recv().await.unwrap(), ConfigUpdate::Failed(_)));
+
+        drop(commands_tx);
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stream_logs_reports_disconnected() {
+        let client = IpcClient::new("/tmp/nonexistent-crustyclaw-tui-logs.sock");
+        let (tx, mut rx) = mpsc::channel(8);
+        let handle = tokio::spawn(stream_logs(client, tx));
+
+        match rx.recv().await.unwrap() {
+            LogEvent::State(ConnectionState::Disconnected { error, retry_in }) => {
+                assert!(error.contains("not running"));
+                assert_eq!(retry_in, INITIAL_BACKOFF);
+            }
+            other => panic!("expected disconnected, got {other:?}"),
+        }
+        handle.abort();
+    }
+}
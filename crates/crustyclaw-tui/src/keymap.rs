@@ -1,14 +1,17 @@
 //! Vim-style keybinding system.
 //!
 //! Maps key events to actions. Supports single keys and simple two-key
-//! sequences (e.g. `gg` for scroll-to-top).
+//! sequences (e.g. `gg` for scroll-to-top). Every action has default keys,
+//! which `[tui.keys]` in the config can replace per action.
 
 use crossterm::event::KeyCode;
+use crustyclaw_config::TuiConfig;
 
 /// An action the TUI can perform.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Action {
     Quit,
+    Help,
     NextPanel,
     PrevPanel,
     GoToPanel(usize),
@@ -18,18 +21,223 @@ pub enum Action {
     HalfPageUp,
     ScrollToTop,
     ScrollToBottom,
+    CycleLogLevel,
+    NextConversation,
+    PrevConversation,
+    TogglePause,
+    Search,
+    ToggleView,
+    NextSection,
+    PrevSection,
+    EditField,
+    Open,
     None,
 }
 
+/// Every bindable action: its `[tui.keys]` name, default keys, and help
+/// text, in the order the help overlay lists them.
+const ACTIONS: &[(&str, Action, &[&str], &str)] = &[
+    ("quit", Action::Quit, &["q"], "Quit the TUI"),
+    ("help", Action::Help, &["?"], "Show or hide this help"),
+    (
+        "next_panel",
+        Action::NextPanel,
+        &["Tab", "l"],
+        "Switch to next panel",
+    ),
+    (
+        "prev_panel",
+        Action::PrevPanel,
+        &["BackTab", "h"],
+        "Switch to previous panel",
+    ),
+    ("panel_1", Action::GoToPanel(0), &["1"], "Jump to Dashboard"),
+    ("panel_2", Action::GoToPanel(1), &["2"], "Jump to Logs"),
+    ("panel_3", Action::GoToPanel(2), &["3"], "Jump to Messages"),
+    ("panel_4", Action::GoToPanel(3), &["4"], "Jump to Config"),
+    ("panel_5", Action::GoToPanel(4), &["5"], "Jump to Chat"),
+    (
+        "scroll_down",
+        Action::ScrollDown,
+        &["j", "Down"],
+        "Scroll down 1 line",
+    ),
+    (
+        "scroll_up",
+        Action::ScrollUp,
+        &["k", "Up"],
+        "Scroll up 1 line",
+    ),
+    (
+        "half_page_down",
+        Action::HalfPageDown,
+        &["d", "PageDown"],
+        "Scroll down half a page",
+    ),
+    (
+        "half_page_up",
+        Action::HalfPageUp,
+        &["u", "PageUp"],
+        "Scroll up half a page",
+    ),
+    (
+        "scroll_to_top",
+        Action::ScrollToTop,
+        &["gg", "Home"],
+        "Scroll to top",
+    ),
+    (
+        "scroll_to_bottom",
+        Action::ScrollToBottom,
+        &["G", "End"],
+        "Scroll to bottom",
+    ),
+    (
+        "cycle_log_level",
+        Action::CycleLogLevel,
+        &["f"],
+        "Cycle the Logs level filter",
+    ),
+    (
+        "next_conversation",
+        Action::NextConversation,
+        &["c"],
+        "Next conversation (Messages)",
+    ),
+    (
+        "prev_conversation",
+        Action::PrevConversation,
+        &["C"],
+        "Previous conversation (Messages)",
+    ),
+    (
+        "toggle_view",
+        Action::ToggleView,
+        &["v"],
+        "Live traffic / history (Messages)",
+    ),
+    (
+        "toggle_pause",
+        Action::TogglePause,
+        &["p"],
+        "Pause / resume live traffic (Messages)",
+    ),
+    (
+        "search",
+        Action::Search,
+        &["/"],
+        "Search live traffic (Messages)",
+    ),
+    (
+        "next_section",
+        Action::NextSection,
+        &["]"],
+        "Next section (Config)",
+    ),
+    (
+        "prev_section",
+        Action::PrevSection,
+        &["["],
+        "Previous section (Config)",
+    ),
+    (
+        "edit_field",
+        Action::EditField,
+        &["e"],
+        "Edit the highlighted field (Config)",
+    ),
+    (
+        "open",
+        Action::Open,
+        &["Enter"],
+        "Edit field (Config) / open message (Messages)",
+    ),
+];
+
+/// A key, or two-key sequence, bound to an action.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Binding {
+    keys: Vec<KeyCode>,
+    /// The key as written in the config, e.g. `gg` or `PageDown`.
+    label: String,
+    action: Action,
+}
+
+/// Parse a `[tui.keys]` key: a name from `TUI_KEY_NAMES`, a character, or
+/// a two-character sequence.
+fn parse_key(key: &str) -> Option<Vec<KeyCode>> {
+    let named = match key {
+        "Tab" => KeyCode::Tab,
+        "BackTab" => KeyCode::BackTab,
+        "Enter" => KeyCode::Enter,
+        "Esc" => KeyCode::Esc,
+        "Backspace" => KeyCode::Backspace,
+        "Space" => KeyCode::Char(' '),
+        "Up" => KeyCode::Up,
+        "Down" => KeyCode::Down,
+        "Left" => KeyCode::Left,
+        "Right" => KeyCode::Right,
+        "PageUp" => KeyCode::PageUp,
+        "PageDown" => KeyCode::PageDown,
+        "Home" => KeyCode::Home,
+        "End" => KeyCode::End,
+        _ => {
+            let keys: Vec<KeyCode> = key.chars().map(KeyCode::Char).collect();
+            return matches!(keys.len(), 1 | 2).then_some(keys);
+        }
+    };
+    Some(vec![named])
+}
+
 /// Key mapper with support for multi-key sequences.
 pub struct KeyMapper {
+    bindings: Vec<Binding>,
     /// Pending first key of a two-key sequence (e.g. the first `g` in `gg`).
     pending: Option<KeyCode>,
 }
 
 impl KeyMapper {
+    /// A mapper with the default bindings.
     pub fn new() -> Self {
-        Self { pending: None }
+        Self::from_config(&TuiConfig::default())
+    }
+
+    /// A mapper with the default bindings, except for the actions listed in
+    /// `config.keys`, which get exactly the keys given there. A key given
+    /// there is taken away from any action that has it by default.
+    ///
+    /// Keys that do not parse are skipped; `AppConfig::validate` rejects
+    /// them when the config is loaded.
+    pub fn from_config(config: &TuiConfig) -> Self {
+        let configured: Vec<Binding> = ACTIONS
+            .iter()
+            .filter_map(|(name, action, _, _)| Some((config.keys.get(*name)?, *action)))
+            .flat_map(|(keys, action)| keys.iter().map(move |key| (key, action)))
+            .filter_map(|(label, action)| {
+                Some(Binding {
+                    keys: parse_key(label)?,
+                    label: label.clone(),
+                    action,
+                })
+            })
+            .collect();
+        let defaults = ACTIONS
+            .iter()
+            .filter(|(name, ..)| !config.keys.contains_key(*name))
+            .flat_map(|(_, action, keys, _)| keys.iter().map(move |key| (*key, *action)))
+            .filter_map(|(label, action)| {
+                Some(Binding {
+                    keys: parse_key(label)?,
+                    label: label.to_string(),
+                    action,
+                })
+            })
+            .filter(|default| !configured.iter().any(|b| b.keys == default.keys))
+            .collect::<Vec<_>>();
+        Self {
+            bindings: configured.into_iter().chain(defaults).collect(),
+            pending: None,
+        }
     }
 
     /// Feed a key code and return the resolved action.
@@ -38,45 +246,52 @@ impl KeyMapper {
     /// waits for the next key. If the sequence is invalid, the pending key
     /// is discarded.
     pub fn resolve(&mut self, key: KeyCode) -> Action {
-        // Check if we have a pending key from a previous press.
-        if let Some(prev) = self.pending.take() {
-            return self.resolve_sequence(prev, key);
+        // Check if we have a pending key from a previous press. An unknown
+        // sequence drops it and interprets the second key as a fresh press.
+        if let Some(prev) = self.pending.take()
+            && let Some(action) = self.bound(&[prev, key])
+        {
+            return action;
+        }
+        if let Some(action) = self.bound(&[key]) {
+            return action;
+        }
+        // Start of multi-key sequence
+        if self
+            .bindings
+            .iter()
+            .any(|b| b.keys.len() == 2 && b.keys[0] == key)
+        {
+            self.pending = Some(key);
         }
+        Action::None
+    }
 
-        match key {
-            KeyCode::Char('q') => Action::Quit,
-
-            // Panel switching
-            KeyCode::Tab | KeyCode::Char('l') => Action::NextPanel,
-            KeyCode::BackTab | KeyCode::Char('h') => Action::PrevPanel,
-            KeyCode::Char('1') => Action::GoToPanel(0),
-            KeyCode::Char('2') => Action::GoToPanel(1),
-            KeyCode::Char('3') => Action::GoToPanel(2),
-            KeyCode::Char('4') => Action::GoToPanel(3),
-
-            // Vim scrolling
-            KeyCode::Char('j') | KeyCode::Down => Action::ScrollDown,
-            KeyCode::Char('k') | KeyCode::Up => Action::ScrollUp,
-            KeyCode::Char('d') => Action::HalfPageDown,
-            KeyCode::Char('u') => Action::HalfPageUp,
-            KeyCode::Char('G') => Action::ScrollToBottom,
-
-            // Start of multi-key sequence
-            KeyCode::Char('g') => {
-                self.pending = Some(key);
-                Action::None
-            }
+    fn bound(&self, keys: &[KeyCode]) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|b| b.keys == keys)
+            .map(|b| b.action)
+    }
 
-            _ => Action::None,
-        }
+    /// The keys bound to `action`, e.g. `j / Down`, or `None` when it has
+    /// none.
+    pub fn keys_for(&self, action: Action) -> Option<String> {
+        let labels: Vec<&str> = self
+            .bindings
+            .iter()
+            .filter(|b| b.action == action)
+            .map(|b| b.label.as_str())
+            .collect();
+        (!labels.is_empty()).then(|| labels.join(" / "))
     }
 
-    fn resolve_sequence(&mut self, first: KeyCode, second: KeyCode) -> Action {
-        match (first, second) {
-            (KeyCode::Char('g'), KeyCode::Char('g')) => Action::ScrollToTop,
-            // Unknown sequence — try to interpret the second key as a fresh keypress
-            _ => self.resolve(second),
-        }
+    /// Each bound action's keys and description, for the help overlay.
+    pub fn help(&self) -> Vec<(String, &'static str)> {
+        ACTIONS
+            .iter()
+            .filter_map(|(_, action, _, description)| Some((self.keys_for(*action)?, *description)))
+            .collect()
     }
 }
 
@@ -100,6 +315,16 @@ mod tests {
         assert_eq!(km.resolve(KeyCode::Tab), Action::NextPanel);
         assert_eq!(km.resolve(KeyCode::Char('l')), Action::NextPanel);
         assert_eq!(km.resolve(KeyCode::Char('h')), Action::PrevPanel);
+        assert_eq!(km.resolve(KeyCode::Char('f')), Action::CycleLogLevel);
+        assert_eq!(km.resolve(KeyCode::Char('c')), Action::NextConversation);
+        assert_eq!(km.resolve(KeyCode::Char('C')), Action::PrevConversation);
+        assert_eq!(km.resolve(KeyCode::Char(']')), Action::NextSection);
+        assert_eq!(km.resolve(KeyCode::Char('[')), Action::PrevSection);
+        assert_eq!(km.resolve(KeyCode::Char('e')), Action::EditField);
+        assert_eq!(km.resolve(KeyCode::Enter), Action::Open);
+        assert_eq!(km.resolve(KeyCode::Char('p')), Action::TogglePause);
+        assert_eq!(km.resolve(KeyCode::Char('/')), Action::Search);
+        assert_eq!(km.resolve(KeyCode::Char('v')), Action::ToggleView);
     }
 
     #[test]
@@ -124,6 +349,7 @@ mod tests {
         assert_eq!(km.resolve(KeyCode::Char('2')), Action::GoToPanel(1));
         assert_eq!(km.resolve(KeyCode::Char('3')), Action::GoToPanel(2));
         assert_eq!(km.resolve(KeyCode::Char('4')), Action::GoToPanel(3));
+        assert_eq!(km.resolve(KeyCode::Char('5')), Action::GoToPanel(4));
     }
 
     #[test]
@@ -132,4 +358,69 @@ mod tests {
         assert_eq!(km.resolve(KeyCode::Char('d')), Action::HalfPageDown);
         assert_eq!(km.resolve(KeyCode::Char('u')), Action::HalfPageUp);
     }
+
+    #[test]
+    fn test_navigation_keys() {
+        let mut km = KeyMapper::new();
+        assert_eq!(km.resolve(KeyCode::Down), Action::ScrollDown);
+        assert_eq!(km.resolve(KeyCode::PageDown), Action::HalfPageDown);
+        assert_eq!(km.resolve(KeyCode::PageUp), Action::HalfPageUp);
+        assert_eq!(km.resolve(KeyCode::Home), Action::ScrollToTop);
+        assert_eq!(km.resolve(KeyCode::End), Action::ScrollToBottom);
+        assert_eq!(km.resolve(KeyCode::Char('?')), Action::Help);
+    }
+
+    #[test]
+    fn test_actions_match_config() {
+        let names: Vec<&str> = ACTIONS.iter().map(|(name, ..)| *name).collect();
+        assert_eq!(names, crustyclaw_config::TUI_ACTIONS);
+        for (name, _, keys, _) in ACTIONS {
+            for key in *keys {
+                assert!(crustyclaw_config::is_tui_key(key), "{name}: {key}");
+                assert!(parse_key(key).is_some(), "{name}: {key}");
+            }
+        }
+        for key in crustyclaw_config::TUI_KEY_NAMES {
+            assert_eq!(parse_key(key).map(|keys| keys.len()), Some(1), "{key}");
+        }
+    }
+
+    #[test]
+    fn test_configured_keys_replace_defaults() {
+        let config = crustyclaw_config::AppConfig::parse(
+            r#"
+            [tui.keys]
+            scroll_down = ["n"]
+            quit = ["j"]
+            scroll_to_top = ["tt"]
+            help = []
+        "#,
+        )
+        .unwrap();
+        let mut km = KeyMapper::from_config(&config.tui);
+        assert_eq!(km.resolve(KeyCode::Char('n')), Action::ScrollDown);
+        assert_eq!(km.resolve(KeyCode::Down), Action::None);
+        // `j` moved to quit; `q` is no longer bound.
+        assert_eq!(km.resolve(KeyCode::Char('j')), Action::Quit);
+        assert_eq!(km.resolve(KeyCode::Char('q')), Action::None);
+        assert_eq!(km.resolve(KeyCode::Char('t')), Action::None);
+        assert_eq!(km.resolve(KeyCode::Char('t')), Action::ScrollToTop);
+        assert_eq!(km.resolve(KeyCode::Char('g')), Action::None);
+        assert_eq!(km.resolve(KeyCode::Char('g')), Action::None);
+        assert_eq!(km.resolve(KeyCode::Char('?')), Action::None);
+
+        assert_eq!(km.keys_for(Action::ScrollDown).as_deref(), Some("n"));
+        assert_eq!(km.keys_for(Action::Help), None);
+        let help = km.help();
+        assert!(help.contains(&("j".to_string(), "Quit the TUI")));
+        assert!(!help.iter().any(|(_, d)| *d == "Show or hide this help"));
+    }
+
+    #[test]
+    fn test_default_help() {
+        let help = KeyMapper::new().help();
+        assert_eq!(help.len(), ACTIONS.len());
+        assert_eq!(help[0], ("q".to_string(), "Quit the TUI"));
+        assert!(help.contains(&("gg / Home".to_string(), "Scroll to top")));
+    }
 }
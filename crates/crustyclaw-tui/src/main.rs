@@ -2,16 +2,19 @@
 
 //! CrustyClaw TUI — interactive terminal control plane.
 //!
-//! Renders a four-panel interface (Dashboard, Logs, Messages, Config) with
-//! vim-style keybindings. Connects to the daemon's log collector for live
-//! log streaming.
+//! Renders a five-panel interface (Dashboard, Logs, Messages, Config, Chat)
+//! with vim-style keybindings. Streams the daemon's logs over IPC, polls the
+//! daemon for live dashboard data, and chats with the daemon's agent and
+//! runs skills with live output (see [`connection`]).
 
 mod app;
+mod connection;
 mod keymap;
 mod panels;
 
 use std::io;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use anyhow::Result;
 use crossterm::{
@@ -21,25 +24,16 @@ use crossterm::{
 };
 use ratatui::{
     prelude::*,
-    widgets::{Block, Borders, Paragraph, Tabs},
+    widgets::{Block, Borders, Clear, Paragraph, Tabs},
 };
-use tracing_subscriber::EnvFilter;
-use tracing_subscriber::layer::SubscriberExt;
-use tracing_subscriber::util::SubscriberInitExt;
+use tokio::sync::{Notify, mpsc, watch};
 
 use app::{App, Panel};
+use connection::{ChatUpdate, ConfigUpdate, DaemonSnapshot, LogEvent};
+use crustyclaw_core::ipc::{IpcClient, MessageEvent};
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Set up log collector so the TUI can display logs
-    let log_collector = crustyclaw_core::LogCollector::new(1000);
-    let log_reader = log_collector.reader();
-
-    tracing_subscriber::registry()
-        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
-        .with(log_collector)
-        .init();
-
     // Load config (best-effort, async I/O)
     let config_path = PathBuf::from("crustyclaw.toml");
     let config = if tokio::fs::try_exists(&config_path).await.unwrap_or(false) {
@@ -50,17 +44,59 @@ async fn main() -> Result<()> {
         crustyclaw_config::AppConfig::default()
     };
 
-    tracing::info!("Starting CrustyClaw TUI");
+    let socket_path = crustyclaw_core::ipc::server::socket_path_from_config(&config);
+    let token = crustyclaw_core::auth::token::load_token(&config);
+    let mut app = App::new(config);
+    let (daemon_tx, daemon_rx) = watch::channel(DaemonSnapshot::default());
+    let refresh = Arc::new(Notify::new());
+    tokio::spawn(connection::poll_daemon(
+        IpcClient::new(&socket_path).with_token(token.clone()),
+        daemon_tx,
+        app.conversation_selection(),
+        refresh.clone(),
+    ));
+    tokio::spawn(connection::watch_events(
+        IpcClient::new(&socket_path).with_token(token.clone()),
+        refresh,
+    ));
+    let (log_tx, log_rx) = mpsc::channel(1024);
+    tokio::spawn(connection::stream_logs(
+        IpcClient::new(&socket_path).with_token(token.clone()),
+        log_tx,
+    ));
+    let (message_tx, message_rx) = mpsc::channel(1024);
+    tokio::spawn(connection::stream_messages(
+        IpcClient::new(&socket_path).with_token(token.clone()),
+        message_tx,
+    ));
+    let (chat_tx, chat_rx) = mpsc::channel(1024);
+    tokio::spawn(connection::run_chat(
+        IpcClient::new(&socket_path).with_token(token.clone()),
+        app.chat_commands(),
+        chat_tx,
+    ));
+    let (config_tx, config_rx) = mpsc::channel(16);
+    tokio::spawn(connection::run_config(
+        IpcClient::new(&socket_path).with_token(token),
+        app.config_commands(),
+        config_tx,
+    ));
 
     // Set up terminal
     enable_raw_mode()?;
     io::stdout().execute(EnterAlternateScreen)?;
     let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
 
-    let mut app = App::new(config, log_reader);
-
     // Main event loop
-    let result = run_loop(&mut terminal, &mut app);
+    let result = run_loop(
+        &mut terminal,
+        &mut app,
+        daemon_rx,
+        log_rx,
+        message_rx,
+        chat_rx,
+        config_rx,
+    );
 
     // Restore terminal (always, even on error)
     disable_raw_mode()?;
@@ -69,8 +105,34 @@ async fn main() -> Result<()> {
     result
 }
 
-fn run_loop(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App) -> Result<()> {
+fn run_loop(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    app: &mut App,
+    mut daemon_rx: watch::Receiver<DaemonSnapshot>,
+    mut log_rx: mpsc::Receiver<LogEvent>,
+    mut message_rx: mpsc::Receiver<MessageEvent>,
+    mut chat_rx: mpsc::Receiver<ChatUpdate>,
+    mut config_rx: mpsc::Receiver<ConfigUpdate>,
+) -> Result<()> {
     loop {
+        if daemon_rx.has_changed().unwrap_or(false) {
+            app.apply_snapshot(daemon_rx.borrow_and_update().clone());
+        }
+        while let Ok(event) = log_rx.try_recv() {
+            app.apply_log_event(event);
+        }
+        while let Ok(message) = message_rx.try_recv() {
+            app.apply_message(message);
+        }
+        while let Ok(update) = chat_rx.try_recv() {
+            match update {
+                ChatUpdate::Chat(event) => app.apply_chat_event(event),
+                ChatUpdate::Skill(event) => app.apply_skill_event(event),
+            }
+        }
+        while let Ok(update) = config_rx.try_recv() {
+            app.apply_config_update(update);
+        }
         app.tick();
         terminal.draw(|frame| render(frame, app))?;
 
@@ -78,8 +140,7 @@ fn run_loop(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App
             && let Event::Key(key) = event::read()?
             && key.kind == KeyEventKind::Press
         {
-            let action = app.keymap.resolve(key.code);
-            app.handle_action(action);
+            app.handle_key(key);
         }
 
         if app.should_quit {
@@ -89,33 +150,86 @@ fn run_loop(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App
 }
 
 fn render(frame: &mut Frame, app: &App) {
+    let banner = app.warning_banner();
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Length(3), // header + tabs
-            Constraint::Min(1),    // main content
-            Constraint::Length(2), // status bar
+            Constraint::Length(3),                           // header + tabs
+            Constraint::Length(u16::from(banner.is_some())), // warning banner
+            Constraint::Min(1),                              // main content
+            Constraint::Length(2),                           // status bar
         ])
         .split(frame.area());
 
     // Header with tab bar
     render_header(frame, app, chunks[0]);
 
+    // Warning banner (only takes space when there are warnings)
+    if let Some(text) = banner {
+        let banner = Paragraph::new(text).style(
+            Style::default()
+                .fg(Color::Black)
+                .bg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        );
+        frame.render_widget(banner, chunks[1]);
+    }
+
     // Main panel content
     match app.active_panel {
-        Panel::Dashboard => app.dashboard.render(frame, chunks[1]),
-        Panel::Logs => app.logs.render(frame, chunks[1]),
-        Panel::Messages => app.messages.render(frame, chunks[1]),
-        Panel::Config => app.config_panel.render(frame, chunks[1]),
+        Panel::Dashboard => app.dashboard.render(frame, chunks[2]),
+        Panel::Logs => app.logs.render(frame, chunks[2]),
+        Panel::Messages => app.messages.render(frame, chunks[2]),
+        Panel::Config => app.config_panel.render(frame, chunks[2]),
+        Panel::Chat => app.chat.render(frame, chunks[2]),
     }
 
     // Status bar
     let status = Paragraph::new(app.status_line()).style(Style::default().fg(Color::DarkGray));
-    frame.render_widget(status, chunks[2]);
+    frame.render_widget(status, chunks[3]);
+
+    if app.show_help {
+        render_help(frame, app, chunks[2]);
+    }
+}
+
+/// Draw the active keybindings as a box over the middle of `area`.
+fn render_help(frame: &mut Frame, app: &App, area: Rect) {
+    let help = app.keymap.help();
+    let key_width = help.iter().map(|(keys, _)| keys.len()).max().unwrap_or(0);
+    let lines: Vec<Line> = help
+        .iter()
+        .map(|(keys, description)| {
+            Line::from(vec![
+                Span::styled(
+                    format!(" {keys:<key_width$}  "),
+                    Style::default().fg(Color::Cyan),
+                ),
+                Span::raw(*description),
+            ])
+        })
+        .collect();
+    let width = area.width.saturating_sub(4).min(72);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let modal = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+    frame.render_widget(Clear, modal);
+    frame.render_widget(
+        Paragraph::new(lines).block(
+            Block::default()
+                .title(" Keys — press any key to close ")
+                .borders(Borders::ALL),
+        ),
+        modal,
+    );
 }
 
 fn render_header(frame: &mut Frame, app: &App, area: Rect) {
-    let titles: Vec<Line> = ["1:Dashboard", "2:Logs", "3:Messages", "4:Config"]
+    let titles: Vec<Line> = ["1:Dashboard", "2:Logs", "3:Messages", "4:Config", "5:Chat"]
         .iter()
         .map(|t| Line::from(*t))
         .collect();
@@ -0,0 +1,588 @@
+//! Chat panel — interactive chat with the daemon's agent.
+//!
+//! Messages typed on the input line are sent over `POST /chat` by the chat
+//! task in [`connection`](crate::connection); the reply streams into the
+//! transcript as it is generated, with a line per tool call that is marked
+//! when the call finishes. `Esc` cancels the message in progress.
+//!
+//! `/run <skill> [key=value ...]` runs a skill instead, with its output
+//! lines appearing in the transcript as the skill writes them.
+
+use std::cell::Cell;
+
+use crustyclaw_core::ipc::{ChatEvent, SkillRunEvent};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, Paragraph},
+};
+
+use super::PanelState;
+use crate::connection::ChatCommand;
+
+/// Usage of the `/run` command.
+const RUN_USAGE: &str = "usage: /run <skill> [key=value ...]";
+
+/// What a transcript entry is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatRole {
+    /// A message the operator sent.
+    You,
+    /// Text from the agent.
+    Agent,
+    /// A tool call, while running or finished.
+    Tool,
+    /// A failed or cancelled run.
+    Error,
+    /// Run statistics.
+    Info,
+    /// A line a skill wrote to stdout.
+    Stdout,
+    /// A line a skill wrote to stderr.
+    Stderr,
+}
+
+/// One transcript entry; the text may span several lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatEntry {
+    pub role: ChatRole,
+    pub text: String,
+}
+
+/// Chat panel state — transcript, input line, and the run in progress.
+pub struct ChatPanel {
+    entries: Vec<ChatEntry>,
+    input: String,
+    /// Session the daemon assigned, once the first message started it.
+    session: Option<String>,
+    /// Model request number of the run in progress, `Some(0)` until the
+    /// first request.
+    running: Option<u32>,
+    /// Whether the last entry is agent text still being streamed.
+    streaming: bool,
+    /// Whether the run in progress produced any text.
+    replied: bool,
+    /// Whether a cancel was requested for the run in progress.
+    cancelling: bool,
+    /// Skill being run, if the run in progress is a `/run`.
+    skill: Option<String>,
+    scroll_offset: usize,
+    auto_follow: bool,
+    /// Transcript height in lines at the last render, to bound scrolling.
+    rendered_lines: Cell<usize>,
+}
+
+impl ChatPanel {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            input: String::new(),
+            session: None,
+            running: None,
+            streaming: false,
+            replied: false,
+            cancelling: false,
+            skill: None,
+            scroll_offset: 0,
+            auto_follow: true,
+            rendered_lines: Cell::new(0),
+        }
+    }
+
+    /// Whether a message is in progress.
+    pub fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    /// Type a character on the input line.
+    pub fn insert(&mut self, c: char) {
+        self.input.push(c);
+    }
+
+    /// Delete the last character of the input line.
+    pub fn backspace(&mut self) {
+        self.input.pop();
+    }
+
+    /// Take the input line as a command for the chat task, unless it is
+    /// blank or a run is already in progress.
+    ///
+    /// A `/run` line becomes a skill run; a malformed one is reported in
+    /// the transcript instead. Anything else is a message.
+    pub fn submit(&mut self) -> Option<ChatCommand> {
+        let message = self.input.trim().to_string();
+        if message.is_empty() || self.is_running() {
+            return None;
+        }
+        self.input.clear();
+        self.push(ChatRole::You, message.clone());
+        self.scroll_to_bottom();
+        let command = match message.strip_prefix("/run") {
+            Some(rest) if rest.is_empty() || rest.starts_with(' ') => match parse_run(rest) {
+                Ok(command) => command,
+                Err(error) => {
+                    self.push(ChatRole::Error, error);
+                    return None;
+                }
+            },
+            _ => ChatCommand::Send(message),
+        };
+        if let ChatCommand::RunSkill { name, .. } = &command {
+            self.skill = Some(name.clone());
+        }
+        self.running = Some(0);
+        self.streaming = false;
+        self.replied = false;
+        self.cancelling = false;
+        Some(command)
+    }
+
+    /// Note that a cancel was requested; returns whether one should be
+    /// sent (a message is in progress and not already being cancelled).
+    pub fn cancel(&mut self) -> bool {
+        if !self.is_running() || self.cancelling {
+            return false;
+        }
+        self.cancelling = true;
+        true
+    }
+
+    /// Apply an event from the chat stream.
+    pub fn apply_event(&mut self, event: ChatEvent) {
+        match event {
+            ChatEvent::Session { id } => self.session = Some(id),
+            ChatEvent::Thinking { iteration } => {
+                self.running = Some(iteration);
+                self.streaming = false;
+            }
+            ChatEvent::Text { delta } => {
+                self.replied = true;
+                match self.entries.last_mut() {
+                    Some(entry) if self.streaming => entry.text.push_str(&delta),
+                    _ => {
+                        self.push(ChatRole::Agent, delta);
+                        self.streaming = true;
+                    }
+                }
+            }
+            ChatEvent::ToolCall { name } => {
+                self.streaming = false;
+                self.push(ChatRole::Tool, format!("{name} …"));
+            }
+            ChatEvent::ToolResult { name, ok } => {
+                let pending = format!("{name} …");
+                let mark = if ok { "✓" } else { "✗" };
+                match self
+                    .entries
+                    .iter_mut()
+                    .rev()
+                    .find(|e| e.role == ChatRole::Tool && e.text == pending)
+                {
+                    Some(entry) => entry.text = format!("{name} {mark}"),
+                    None => self.push(ChatRole::Tool, format!("{name} {mark}")),
+                }
+            }
+            ChatEvent::Done {
+                reply,
+                iterations,
+                total_tokens,
+            } => {
+                if !self.replied {
+                    self.push(ChatRole::Agent, reply);
+                }
+                self.push(
+                    ChatRole::Info,
+                    format!("{iterations} iteration(s), {total_tokens} tokens"),
+                );
+                self.finish();
+            }
+            ChatEvent::Error { error } => {
+                self.push(ChatRole::Error, error);
+                self.finish();
+            }
+        }
+    }
+
+    /// Apply an event from a skill run's output stream.
+    pub fn apply_skill_event(&mut self, event: SkillRunEvent) {
+        match event {
+            SkillRunEvent::Output { stream, line } => {
+                let role = match stream.as_str() {
+                    "stderr" => ChatRole::Stderr,
+                    _ => ChatRole::Stdout,
+                };
+                self.push(role, line);
+            }
+            SkillRunEvent::Done {
+                exit_code,
+                elapsed_ms,
+                ..
+            } => {
+                let skill = self.skill.take().unwrap_or_default();
+                let role = if exit_code == 0 {
+                    ChatRole::Info
+                } else {
+                    ChatRole::Error
+                };
+                self.push(
+                    role,
+                    format!("{skill} exited with code {exit_code} in {elapsed_ms}ms"),
+                );
+                self.finish();
+            }
+            SkillRunEvent::Error { error } => {
+                self.push(ChatRole::Error, error);
+                self.finish();
+            }
+        }
+    }
+
+    fn finish(&mut self) {
+        self.running = None;
+        self.streaming = false;
+        self.cancelling = false;
+        self.skill = None;
+    }
+
+    fn push(&mut self, role: ChatRole, text: String) {
+        self.entries.push(ChatEntry { role, text });
+        if self.auto_follow {
+            self.scroll_offset = 0;
+        }
+    }
+
+    /// Transcript lines wrapped to `width` columns.
+    fn lines(&self, width: usize) -> Vec<Line<'_>> {
+        let mut lines = Vec::new();
+        for entry in &self.entries {
+            let (prefix, style) = match entry.role {
+                ChatRole::You => ("you  ", Style::default().fg(Color::Green)),
+                ChatRole::Agent => ("agent", Style::default().fg(Color::Cyan)),
+                ChatRole::Tool => ("  ⚙  ", Style::default().fg(Color::DarkGray)),
+                ChatRole::Error => ("  !  ", Style::default().fg(Color::Red)),
+                ChatRole::Info => ("     ", Style::default().fg(Color::DarkGray)),
+                ChatRole::Stdout => ("  │  ", Style::default().fg(Color::DarkGray)),
+                ChatRole::Stderr => ("  │  ", Style::default().fg(Color::Yellow)),
+            };
+            let text_style = match entry.role {
+                ChatRole::You | ChatRole::Agent | ChatRole::Stdout => Style::default(),
+                _ => style,
+            };
+            let mut first = true;
+            for text in wrap(
+                &entry.text,
+                width.saturating_sub(prefix.chars().count() + 1),
+            ) {
+                let prefix = if first { prefix } else { "     " };
+                first = false;
+                lines.push(Line::from(vec![
+                    Span::styled(format!("{prefix} "), style),
+                    Span::styled(text, text_style),
+                ]));
+            }
+        }
+        lines
+    }
+
+    pub fn render(&self, frame: &mut Frame, area: Rect) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(3)])
+            .split(area);
+
+        let mut title = match &self.session {
+            Some(id) => format!(" Chat — {} ", &id[..id.len().min(8)]),
+            None => " Chat ".to_string(),
+        };
+        match self.running {
+            Some(_) if self.cancelling => title.push_str("(cancelling…) "),
+            Some(_) if self.skill.is_some() => {
+                let skill = self.skill.as_deref().unwrap_or_default();
+                title.push_str(&format!("(running {skill}…) "));
+            }
+            Some(0) => title.push_str("(sending…) "),
+            Some(n) => title.push_str(&format!("(thinking, step {n}…) ")),
+            None => {}
+        }
+        let block = Block::default().title(title).borders(Borders::ALL);
+
+        if self.entries.is_empty() {
+            let empty = Paragraph::new(
+                "  (type a message, or /run <skill> [key=value ...], and press Enter)",
+            )
+            .style(Style::default().fg(Color::DarkGray))
+            .block(block);
+            frame.render_widget(empty, chunks[0]);
+        } else {
+            let visible_height = chunks[0].height.saturating_sub(2) as usize;
+            let lines = self.lines(chunks[0].width.saturating_sub(2) as usize);
+            let total = lines.len();
+            self.rendered_lines.set(total);
+            let skip = total.saturating_sub(visible_height + self.scroll_offset);
+            let lines: Vec<Line> = lines.into_iter().skip(skip).take(visible_height).collect();
+            frame.render_widget(Paragraph::new(lines).block(block), chunks[0]);
+        }
+
+        let hint = if self.is_running() {
+            " Message (Esc: cancel) "
+        } else {
+            " Message (Enter: send) "
+        };
+        let input = Paragraph::new(format!("> {}█", self.input))
+            .block(Block::default().title(hint).borders(Borders::ALL));
+        frame.render_widget(input, chunks[1]);
+    }
+}
+
+impl Default for ChatPanel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PanelState for ChatPanel {
+    fn scroll_down(&mut self, n: usize) {
+        if self.scroll_offset >= n {
+            self.scroll_offset -= n;
+        } else {
+            self.scroll_offset = 0;
+            self.auto_follow = true;
+        }
+    }
+
+    fn scroll_up(&mut self, n: usize) {
+        self.auto_follow = false;
+        let max_offset = self.rendered_lines.get().saturating_sub(1);
+        self.scroll_offset = (self.scroll_offset + n).min(max_offset);
+    }
+
+    fn scroll_to_top(&mut self) {
+        self.auto_follow = false;
+        self.scroll_offset = self.rendered_lines.get().saturating_sub(1);
+    }
+
+    fn scroll_to_bottom(&mut self) {
+        self.scroll_offset = 0;
+        self.auto_follow = true;
+    }
+}
+
+/// Parse the arguments of a `/run` line into a skill run. Argument values
+/// that parse as JSON are passed typed; anything else as a string.
+fn parse_run(rest: &str) -> Result<ChatCommand, String> {
+    let mut words = rest.split_whitespace();
+    let name = words.next().ok_or(RUN_USAGE)?.to_string();
+    let mut args = serde_json::Map::new();
+    for word in words {
+        let (key, value) = word
+            .split_once('=')
+            .filter(|(key, _)| !key.is_empty())
+            .ok_or_else(|| format!("expected key=value, got {word:?} ({RUN_USAGE})"))?;
+        let value = serde_json::from_str(value)
+            .unwrap_or_else(|_| serde_json::Value::String(value.to_string()));
+        args.insert(key.to_string(), value);
+    }
+    Ok(ChatCommand::RunSkill { name, args })
+}
+
+/// Split `text` into lines of at most `width` characters, breaking at
+/// spaces where possible and at newlines always.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    for paragraph in text.split('\n') {
+        let mut line = String::new();
+        let mut len = 0;
+        for word in paragraph.split(' ') {
+            let word_len = word.chars().count();
+            if len > 0 && len + 1 + word_len > width {
+                lines.push(std::mem::take(&mut line));
+                len = 0;
+            }
+            if len > 0 {
+                line.push(' ');
+                len += 1;
+            }
+            for c in word.chars() {
+                if len == width {
+                    lines.push(std::mem::take(&mut line));
+                    len = 0;
+                }
+                line.push(c);
+                len += 1;
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roles(panel: &ChatPanel) -> Vec<ChatRole> {
+        panel.entries.iter().map(|e| e.role).collect()
+    }
+
+    #[test]
+    fn test_submit() {
+        let mut panel = ChatPanel::new();
+        assert_eq!(panel.submit(), None);
+        for c in "  hi  ".chars() {
+            panel.insert(c);
+        }
+        panel.insert('x');
+        panel.backspace();
+        assert_eq!(panel.submit(), Some(ChatCommand::Send("hi".to_string())));
+        assert!(panel.is_running());
+        assert_eq!(roles(&panel), [ChatRole::You]);
+
+        // Nothing else is sent while the message is in progress.
+        panel.insert('y');
+        assert_eq!(panel.submit(), None);
+        assert_eq!(panel.input, "y");
+    }
+
+    #[test]
+    fn test_streamed_run() {
+        let mut panel = ChatPanel::new();
+        panel.insert('q');
+        panel.submit().unwrap();
+        for event in [
+            ChatEvent::Session {
+                id: "0123456789abcdef".to_string(),
+            },
+            ChatEvent::Thinking { iteration: 1 },
+            ChatEvent::Text {
+                delta: "Let me ".to_string(),
+            },
+            ChatEvent::Text {
+                delta: "check.".to_string(),
+            },
+            ChatEvent::ToolCall {
+                name: "read_file".to_string(),
+            },
+            ChatEvent::ToolResult {
+                name: "read_file".to_string(),
+                ok: true,
+            },
+            ChatEvent::Thinking { iteration: 2 },
+            ChatEvent::Text {
+                delta: "Done.".to_string(),
+            },
+        ] {
+            panel.apply_event(event);
+        }
+        assert_eq!(panel.running, Some(2));
+        assert_eq!(panel.session.as_deref(), Some("0123456789abcdef"));
+        assert_eq!(
+            roles(&panel),
+            [
+                ChatRole::You,
+                ChatRole::Agent,
+                ChatRole::Tool,
+                ChatRole::Agent
+            ]
+        );
+        assert_eq!(panel.entries[1].text, "Let me check.");
+        assert_eq!(panel.entries[2].text, "read_file ✓");
+
+        panel.apply_event(ChatEvent::Done {
+            reply: "Done.".to_string(),
+            iterations: 2,
+            total_tokens: 40,
+        });
+        assert!(!panel.is_running());
+        assert_eq!(panel.entries.len(), 5);
+        assert_eq!(panel.entries[4].text, "2 iteration(s), 40 tokens");
+    }
+
+    #[test]
+    fn test_cancel() {
+        let mut panel = ChatPanel::new();
+        assert!(!panel.cancel());
+        panel.insert('q');
+        panel.submit().unwrap();
+        assert!(panel.cancel());
+        // A second Esc does not send another cancel.
+        assert!(!panel.cancel());
+
+        panel.apply_event(ChatEvent::Error {
+            error: "chat message cancelled".to_string(),
+        });
+        assert!(!panel.is_running());
+        assert_eq!(roles(&panel), [ChatRole::You, ChatRole::Error]);
+    }
+
+    #[test]
+    fn test_skill_run() {
+        let mut panel = ChatPanel::new();
+        for c in "/run greet who=you n=2".chars() {
+            panel.insert(c);
+        }
+        let mut args = serde_json::Map::new();
+        args.insert("who".to_string(), "you".into());
+        args.insert("n".to_string(), 2.into());
+        assert_eq!(
+            panel.submit(),
+            Some(ChatCommand::RunSkill {
+                name: "greet".to_string(),
+                args,
+            })
+        );
+        assert!(panel.is_running());
+        assert_eq!(panel.skill.as_deref(), Some("greet"));
+
+        for (stream, line) in [("stdout", "hello you"), ("stderr", "warning")] {
+            panel.apply_skill_event(SkillRunEvent::Output {
+                stream: stream.to_string(),
+                line: line.to_string(),
+            });
+        }
+        panel.apply_skill_event(SkillRunEvent::Done {
+            exit_code: 1,
+            elapsed_ms: 12,
+            peak_memory_bytes: None,
+            cpu_time_ms: None,
+        });
+        assert!(!panel.is_running());
+        assert_eq!(
+            roles(&panel),
+            [
+                ChatRole::You,
+                ChatRole::Stdout,
+                ChatRole::Stderr,
+                ChatRole::Error
+            ]
+        );
+        assert_eq!(panel.entries[3].text, "greet exited with code 1 in 12ms");
+    }
+
+    #[test]
+    fn test_run_usage_errors() {
+        let mut panel = ChatPanel::new();
+        for line in ["/run", "/run greet =x", "/run greet who"] {
+            for c in line.chars() {
+                panel.insert(c);
+            }
+            assert_eq!(panel.submit(), None);
+            assert!(!panel.is_running());
+            assert_eq!(panel.entries.last().unwrap().role, ChatRole::Error);
+        }
+        // Only `/run` itself is a command.
+        for c in "/running late".chars() {
+            panel.insert(c);
+        }
+        assert_eq!(
+            panel.submit(),
+            Some(ChatCommand::Send("/running late".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(wrap("one two three", 7), ["one two", "three"]);
+        assert_eq!(wrap("abcdefgh", 3), ["abc", "def", "gh"]);
+        assert_eq!(wrap("a\n\nb", 10), ["a", "", "b"]);
+    }
+}
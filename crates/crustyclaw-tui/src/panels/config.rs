@@ -1,18 +1,59 @@
-//! Config panel — display current configuration as formatted TOML.
+//! Config panel — view the daemon's configuration and edit single fields.
+//!
+//! The resolved TOML comes from `GET /config` (the local config file until
+//! the daemon answers). `j`/`k` move the cursor line, `]`/`[` jump between
+//! sections, and `e` or `Enter` opens an editor for the field under the
+//! cursor. The new value is a TOML literal; it is checked by parsing the
+//! whole edited document with [`AppConfig::parse`] before it is sent, as a
+//! `--set`-style override, over `PUT /config`, which hot-reloads the daemon.
 
+use std::cell::Cell;
+
+use crustyclaw_config::AppConfig;
 use ratatui::{
     prelude::*,
-    widgets::{Block, Borders, Paragraph, Wrap},
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
 };
 
 use super::PanelState;
+use crate::connection::ConfigCommand;
+
+/// An edit in progress.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FieldEditor {
+    /// Line being edited.
+    line: usize,
+    /// Dotted key path, e.g. `daemon.listen_port`.
+    key: String,
+    /// The key as written on the line.
+    name: String,
+    input: String,
+    /// Why the last submission was rejected.
+    error: Option<String>,
+}
+
+/// Outcome of the last change sent to the daemon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ChangeStatus {
+    /// Sent, waiting for the daemon to reload.
+    Pending(String),
+    /// The daemon reloaded with the change.
+    Applied(String),
+    /// The change or the config fetch failed.
+    Failed(String),
+}
 
-/// Configuration viewer panel with TOML syntax highlighting.
+/// Configuration viewer panel with TOML syntax highlighting and a field
+/// editor.
 pub struct ConfigPanel {
     /// The rendered TOML text (split into lines).
     lines: Vec<String>,
-    /// Scroll offset from top.
-    scroll_offset: usize,
+    /// Line under the cursor.
+    cursor: usize,
+    /// First visible line, kept so the cursor stays in view.
+    scroll_offset: Cell<usize>,
+    editor: Option<FieldEditor>,
+    status: Option<ChangeStatus>,
 }
 
 impl ConfigPanel {
@@ -20,21 +61,154 @@ impl ConfigPanel {
         let lines: Vec<String> = toml_text.lines().map(String::from).collect();
         Self {
             lines,
-            scroll_offset: 0,
+            cursor: 0,
+            scroll_offset: Cell::new(0),
+            editor: None,
+            status: None,
+        }
+    }
+
+    /// Show `toml_text` fetched from the daemon, keeping the cursor line.
+    pub fn set_toml(&mut self, toml_text: &str) {
+        self.lines = toml_text.lines().map(String::from).collect();
+        self.cursor = self.cursor.min(self.lines.len().saturating_sub(1));
+        if let Some(ChangeStatus::Pending(key)) = self.status.take() {
+            self.status = Some(ChangeStatus::Applied(key));
+        }
+    }
+
+    /// Report a failed fetch or rejected change.
+    pub fn set_error(&mut self, error: String) {
+        self.status = Some(ChangeStatus::Failed(error));
+    }
+
+    /// Whether the field editor is open.
+    pub fn is_editing(&self) -> bool {
+        self.editor.is_some()
+    }
+
+    /// Move the cursor to the next section header.
+    pub fn next_section(&mut self) {
+        if let Some(i) = (self.cursor + 1..self.lines.len()).find(|&i| is_header(&self.lines[i])) {
+            self.cursor = i;
+        }
+    }
+
+    /// Move the cursor to the previous section header.
+    pub fn prev_section(&mut self) {
+        if let Some(i) = (0..self.cursor).rev().find(|&i| is_header(&self.lines[i])) {
+            self.cursor = i;
+        }
+    }
+
+    /// Open the editor on the field under the cursor, or explain in the
+    /// status why it cannot be edited.
+    pub fn start_edit(&mut self) {
+        match self.field(self.cursor) {
+            Ok((key, name, value)) => {
+                self.editor = Some(FieldEditor {
+                    line: self.cursor,
+                    key,
+                    name,
+                    input: value,
+                    error: None,
+                });
+            }
+            Err(e) => self.status = Some(ChangeStatus::Failed(e)),
+        }
+    }
+
+    /// Type a character in the editor.
+    pub fn insert(&mut self, c: char) {
+        if let Some(editor) = &mut self.editor {
+            editor.input.push(c);
         }
     }
 
+    /// Delete the editor's last character.
+    pub fn backspace(&mut self) {
+        if let Some(editor) = &mut self.editor {
+            editor.input.pop();
+        }
+    }
+
+    /// Close the editor without changing anything.
+    pub fn cancel_edit(&mut self) {
+        self.editor = None;
+    }
+
+    /// Check the edited value and, if the config still parses, close the
+    /// editor and return the change for the config task. Otherwise the
+    /// editor stays open showing the error.
+    pub fn submit_edit(&mut self) -> Option<ConfigCommand> {
+        let editor = self.editor.as_mut()?;
+        let input = editor.input.trim();
+        let value = match parse_value(input) {
+            Ok(value) => value,
+            Err(e) => {
+                editor.error = Some(format!("not a TOML value: {e}"));
+                return None;
+            }
+        };
+        let mut lines = self.lines.clone();
+        lines[editor.line] = format!("{} = {input}", editor.name);
+        if let Err(e) = AppConfig::parse(&lines.join("\n")) {
+            editor.error = Some(e.to_string());
+            return None;
+        }
+        let value = match value {
+            toml::Value::String(s) => s,
+            _ => input.to_string(),
+        };
+        let key = editor.key.clone();
+        self.editor = None;
+        self.status = Some(ChangeStatus::Pending(key.clone()));
+        Some(ConfigCommand::Set { key, value })
+    }
+
+    /// The dotted key, the key as written, and the value of the field on
+    /// `line`.
+    fn field(&self, line: usize) -> Result<(String, String, String), String> {
+        let text = self.lines.get(line).ok_or("nothing to edit")?;
+        let Some((name, value)) = text.split_once(" = ") else {
+            return Err("not a field; move to a `key = value` line".to_string());
+        };
+        if name.starts_with([' ', '\t', '#']) || parse_value(value).is_err() {
+            return Err("only single-line values can be edited here".to_string());
+        }
+        let mut path = Vec::new();
+        if let Some(header) = self.lines[..line].iter().rev().find(|l| is_header(l)) {
+            if header.starts_with("[[") {
+                return Err(format!(
+                    "entries of {} cannot be edited here; edit the config file",
+                    header.trim()
+                ));
+            }
+            path = key_segments(header.trim().trim_start_matches('[').trim_end_matches(']'))?;
+        }
+        path.extend(key_segments(name)?);
+        Ok((path.join("."), name.to_string(), value.to_string()))
+    }
+
     pub fn render(&self, frame: &mut Frame, area: Rect) {
         let visible_height = area.height.saturating_sub(2) as usize;
+        let mut offset = self.scroll_offset.get();
+        if self.cursor < offset {
+            offset = self.cursor;
+        } else if visible_height > 0 && self.cursor >= offset + visible_height {
+            offset = self.cursor + 1 - visible_height;
+        }
+        self.scroll_offset.set(offset);
 
         let visible_lines: Vec<Line> = self
             .lines
             .iter()
-            .skip(self.scroll_offset)
+            .enumerate()
+            .skip(offset)
             .take(visible_height)
-            .map(|line| {
+            .map(|(i, line)| {
                 // Colorize TOML sections and keys
-                if line.starts_with('[') {
+                let line = if line.starts_with('[') {
                     Line::from(Span::styled(
                         line.as_str(),
                         Style::default()
@@ -49,36 +223,125 @@ impl ConfigPanel {
                     ])
                 } else {
                     Line::from(line.as_str())
+                };
+                if i == self.cursor {
+                    line.style(Style::default().add_modifier(Modifier::REVERSED))
+                } else {
+                    line
                 }
             })
             .collect();
 
         let total = self.lines.len();
-        let title = format!(" Config ({total} lines) ");
+        let mut title = format!(" Config ({total} lines) ");
+        let mut title_style = Style::default();
+        match &self.status {
+            Some(ChangeStatus::Pending(key)) => title.push_str(&format!("(applying {key}…) ")),
+            Some(ChangeStatus::Applied(key)) => {
+                title.push_str(&format!("(applied {key}) "));
+                title_style = title_style.fg(Color::Green);
+            }
+            Some(ChangeStatus::Failed(e)) => {
+                title.push_str(&format!("({e}) "));
+                title_style = title_style.fg(Color::Red);
+            }
+            None => {}
+        }
 
         let paragraph = Paragraph::new(visible_lines)
-            .block(Block::default().title(title).borders(Borders::ALL))
+            .block(
+                Block::default()
+                    .title(Span::styled(title, title_style))
+                    .borders(Borders::ALL),
+            )
             .wrap(Wrap { trim: false });
         frame.render_widget(paragraph, area);
+
+        if let Some(editor) = &self.editor {
+            render_editor(frame, area, editor);
+        }
+    }
+}
+
+/// Draw the field editor as a box over the middle of `area`.
+fn render_editor(frame: &mut Frame, area: Rect, editor: &FieldEditor) {
+    let width = area.width.saturating_sub(4).min(72);
+    let height = 6.min(area.height);
+    let modal = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+    let mut lines = vec![Line::from(format!("> {}█", editor.input))];
+    match &editor.error {
+        Some(e) => lines.push(Line::from(Span::styled(
+            e.as_str(),
+            Style::default().fg(Color::Red),
+        ))),
+        None => lines.push(Line::from("")),
     }
+    lines.push(Line::from(Span::styled(
+        "Enter: apply · Esc: cancel · strings need quotes",
+        Style::default().fg(Color::DarkGray),
+    )));
+    let title = format!(" Edit {} ", editor.key);
+    frame.render_widget(Clear, modal);
+    frame.render_widget(
+        Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .block(Block::default().title(title).borders(Borders::ALL)),
+        modal,
+    );
+}
+
+/// Whether `line` starts a TOML table or array of tables.
+fn is_header(line: &str) -> bool {
+    line.starts_with('[')
+}
+
+/// Parse `value` as a single TOML value.
+fn parse_value(value: &str) -> Result<toml::Value, String> {
+    let mut table: toml::Table =
+        toml::from_str(&format!("v = {value}")).map_err(|e| e.message().to_string())?;
+    table.remove("v").ok_or_else(|| "missing value".to_string())
+}
+
+/// Split a dotted TOML key into its segments, unquoting quoted ones.
+/// Segments that contain a dot cannot be named by an override.
+fn key_segments(key: &str) -> Result<Vec<String>, String> {
+    let mut segments = Vec::new();
+    let mut segment = String::new();
+    let mut quote = None;
+    for c in key.trim().chars() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), '.') => return Err(format!("{key} cannot be set over IPC")),
+            (None, '.') => segments.push(std::mem::take(&mut segment).trim().to_string()),
+            (_, c) => segment.push(c),
+        }
+    }
+    segments.push(segment.trim().to_string());
+    Ok(segments)
 }
 
 impl PanelState for ConfigPanel {
     fn scroll_down(&mut self, n: usize) {
         let max = self.lines.len().saturating_sub(1);
-        self.scroll_offset = (self.scroll_offset + n).min(max);
+        self.cursor = (self.cursor + n).min(max);
     }
 
     fn scroll_up(&mut self, n: usize) {
-        self.scroll_offset = self.scroll_offset.saturating_sub(n);
+        self.cursor = self.cursor.saturating_sub(n);
     }
 
     fn scroll_to_top(&mut self) {
-        self.scroll_offset = 0;
+        self.cursor = 0;
     }
 
     fn scroll_to_bottom(&mut self) {
-        self.scroll_offset = self.lines.len().saturating_sub(1);
+        self.cursor = self.lines.len().saturating_sub(1);
     }
 }
 
@@ -86,6 +349,14 @@ impl PanelState for ConfigPanel {
 mod tests {
     use super::*;
 
+    fn panel() -> ConfigPanel {
+        ConfigPanel::new(toml::to_string_pretty(&AppConfig::default()).unwrap())
+    }
+
+    fn go_to(panel: &mut ConfigPanel, line: &str) {
+        panel.cursor = panel.lines.iter().position(|l| l == line).unwrap();
+    }
+
     #[test]
     fn test_config_panel_lines() {
         let toml = "[daemon]\nlisten_addr = \"127.0.0.1\"\nlisten_port = 9100\n";
@@ -100,18 +371,103 @@ mod tests {
             .collect::<Vec<_>>()
             .join("\n");
         let mut panel = ConfigPanel::new(toml);
-        assert_eq!(panel.scroll_offset, 0);
+        assert_eq!(panel.cursor, 0);
 
         panel.scroll_down(5);
-        assert_eq!(panel.scroll_offset, 5);
+        assert_eq!(panel.cursor, 5);
 
         panel.scroll_up(3);
-        assert_eq!(panel.scroll_offset, 2);
+        assert_eq!(panel.cursor, 2);
 
         panel.scroll_to_top();
-        assert_eq!(panel.scroll_offset, 0);
+        assert_eq!(panel.cursor, 0);
 
         panel.scroll_to_bottom();
-        assert_eq!(panel.scroll_offset, 19);
+        assert_eq!(panel.cursor, 19);
+    }
+
+    #[test]
+    fn test_section_navigation() {
+        let toml = "top = 1\n[a]\nx = 1\n[b]\ny = 2\n";
+        let mut panel = ConfigPanel::new(toml.to_string());
+        panel.next_section();
+        assert_eq!(panel.cursor, 1);
+        panel.next_section();
+        assert_eq!(panel.cursor, 3);
+        panel.next_section();
+        assert_eq!(panel.cursor, 3);
+        panel.prev_section();
+        assert_eq!(panel.cursor, 1);
+    }
+
+    #[test]
+    fn test_edit_field() {
+        let mut panel = panel();
+        go_to(&mut panel, "listen_port = 9100");
+        panel.start_edit();
+        assert_eq!(panel.editor.as_ref().unwrap().key, "daemon.listen_port");
+        assert_eq!(panel.editor.as_ref().unwrap().input, "9100");
+
+        // A value of the wrong type fails client-side and keeps the editor.
+        panel.editor.as_mut().unwrap().input = "\"high\"".to_string();
+        assert_eq!(panel.submit_edit(), None);
+        assert!(panel.editor.as_ref().unwrap().error.is_some());
+
+        panel.editor.as_mut().unwrap().input.clear();
+        "9200".chars().for_each(|c| panel.insert(c));
+        assert_eq!(
+            panel.submit_edit(),
+            Some(ConfigCommand::Set {
+                key: "daemon.listen_port".to_string(),
+                value: "9200".to_string(),
+            })
+        );
+        assert!(!panel.is_editing());
+        assert_eq!(
+            panel.status,
+            Some(ChangeStatus::Pending("daemon.listen_port".to_string()))
+        );
+        panel.set_toml("[daemon]\nlisten_port = 9200\n");
+        assert_eq!(
+            panel.status,
+            Some(ChangeStatus::Applied("daemon.listen_port".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_edit_string_field_sends_raw_text() {
+        let mut panel = panel();
+        go_to(&mut panel, "listen_addr = \"127.0.0.1\"");
+        panel.start_edit();
+        panel.editor.as_mut().unwrap().input = "\"0.0.0.0\"".to_string();
+        assert_eq!(
+            panel.submit_edit(),
+            Some(ConfigCommand::Set {
+                key: "daemon.listen_addr".to_string(),
+                value: "0.0.0.0".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_uneditable_lines() {
+        let toml = "[daemon]\n\n[[policy.rules]]\nrole = \"admin\"\n[x]\nlist = [\n    \"a\",\n]\n";
+        let mut panel = ConfigPanel::new(toml.to_string());
+        for line in [1, 3, 5, 6] {
+            panel.cursor = line;
+            panel.start_edit();
+            assert!(!panel.is_editing(), "line {line}");
+            assert!(matches!(panel.status, Some(ChangeStatus::Failed(_))));
+        }
+    }
+
+    #[test]
+    fn test_key_segments() {
+        assert_eq!(key_segments("daemon.tls").unwrap(), ["daemon", "tls"]);
+        assert_eq!(
+            key_segments("auth.role_map.\"uid:4243\"").unwrap(),
+            ["auth", "role_map", "uid:4243"]
+        );
+        assert!(key_segments("skills.\"a.b\"").is_err());
     }
 }
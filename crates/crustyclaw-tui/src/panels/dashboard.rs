@@ -1,14 +1,16 @@
-//! Dashboard panel — daemon status, uptime, channel info.
+//! Dashboard panel — daemon status, uptime, host metrics, channel info.
 
 use std::time::Duration;
 
 use crustyclaw_config::AppConfig;
+use crustyclaw_core::ipc::{HostStatusResponse, IsolationStatusResponse, StatusResponse};
 use ratatui::{
     prelude::*,
     widgets::{Block, Borders, Cell, Paragraph, Row, Table},
 };
 
 use super::PanelState;
+use crate::connection::{ConnectionState, DaemonSnapshot};
 
 /// Dashboard panel state — shows daemon uptime, listen address, channel status.
 pub struct DashboardPanel {
@@ -17,6 +19,14 @@ pub struct DashboardPanel {
     pub listen_port: u16,
     pub signal_enabled: bool,
     pub log_level: String,
+    /// Connection state to the daemon.
+    pub connection: ConnectionState,
+    /// Latest daemon status (`None` until the first poll succeeds).
+    pub status: Option<StatusResponse>,
+    /// Latest isolation status from the daemon.
+    pub isolation: Option<IsolationStatusResponse>,
+    /// Latest host metrics from the daemon.
+    pub host: Option<HostStatusResponse>,
     pub scroll_offset: usize,
 }
 
@@ -28,36 +38,52 @@ impl DashboardPanel {
             listen_port: config.daemon.listen_port,
             signal_enabled: config.signal.enabled,
             log_level: config.logging.level.clone(),
+            connection: ConnectionState::Connecting,
+            status: None,
+            isolation: None,
+            host: None,
             scroll_offset: 0,
         }
     }
 
+    /// Update the panel from a daemon poll.
+    ///
+    /// Values reported by the daemon replace the ones read from the local
+    /// config file; the last known values are kept while disconnected.
+    pub fn apply_snapshot(&mut self, snapshot: &DaemonSnapshot) {
+        self.connection = snapshot.state.clone();
+        if let Some(status) = &snapshot.status {
+            self.listen_addr = status.listen_addr.clone();
+            self.listen_port = status.listen_port;
+            self.signal_enabled = status.signal_enabled;
+            self.log_level = status.log_level.clone();
+        }
+        self.status = snapshot.status.clone();
+        self.isolation = snapshot.isolation.clone();
+        self.host = snapshot.host.clone();
+    }
+
     pub fn render(&self, frame: &mut Frame, area: Rect) {
+        let host_lines = self.host_lines();
         let chunks = Layout::default()
             .direction(Direction::Vertical)
-            .constraints([Constraint::Length(5), Constraint::Min(0)])
+            .constraints([
+                Constraint::Length(7),
+                Constraint::Length(host_lines.len() as u16 + 2),
+                Constraint::Min(0),
+            ])
             .split(area);
 
         // Status block
-        let uptime = format_duration(self.uptime);
-        let status_text = vec![
-            Line::from(vec![
-                Span::styled("Status: ", Style::default().fg(Color::Gray)),
-                Span::styled("running", Style::default().fg(Color::Green)),
-            ]),
-            Line::from(vec![
-                Span::styled("Uptime: ", Style::default().fg(Color::Gray)),
-                Span::raw(&uptime),
-            ]),
-            Line::from(vec![
-                Span::styled("Listen: ", Style::default().fg(Color::Gray)),
-                Span::raw(format!("{}:{}", self.listen_addr, self.listen_port)),
-            ]),
-        ];
-        let status = Paragraph::new(status_text)
+        let status = Paragraph::new(self.status_lines())
             .block(Block::default().title(" Status ").borders(Borders::ALL));
         frame.render_widget(status, chunks[0]);
 
+        // Host metrics block
+        let host = Paragraph::new(host_lines)
+            .block(Block::default().title(" Host ").borders(Borders::ALL));
+        frame.render_widget(host, chunks[1]);
+
         // Channels table
         let signal_status = if self.signal_enabled {
             Span::styled("enabled", Style::default().fg(Color::Green))
@@ -79,7 +105,122 @@ impl DashboardPanel {
                     .style(Style::default().add_modifier(Modifier::BOLD)),
             )
             .block(Block::default().title(" Channels ").borders(Borders::ALL));
-        frame.render_widget(table, chunks[1]);
+        frame.render_widget(table, chunks[2]);
+    }
+
+    /// Lines for the status block.
+    fn status_lines(&self) -> Vec<Line<'static>> {
+        let label =
+            |text: &str| Span::styled(format!("{text:<10}"), Style::default().fg(Color::Gray));
+
+        let state = match (&self.connection, &self.status) {
+            (ConnectionState::Connected, Some(status)) => Span::styled(
+                format!("running (PID {}, v{})", status.pid, status.version),
+                Style::default().fg(Color::Green),
+            ),
+            (ConnectionState::Disconnected { .. }, _) => {
+                Span::styled("unreachable", Style::default().fg(Color::Red))
+            }
+            _ => Span::styled("connecting…", Style::default().fg(Color::Yellow)),
+        };
+
+        // Daemon uptime when known, otherwise how long this TUI has been open.
+        let uptime_label = if self.status.is_some() {
+            "Uptime:"
+        } else {
+            "TUI up:"
+        };
+
+        let counts = match &self.status {
+            Some(status) => format!(
+                "{} skills, {} plugins",
+                status.skills_count, status.plugins_count
+            ),
+            None => "n/a".to_string(),
+        };
+
+        let isolation = match &self.isolation {
+            Some(iso) => format!(
+                "{} ({}) — {} running, {} queued, max {}",
+                iso.backend,
+                if iso.available {
+                    "available"
+                } else {
+                    "unavailable"
+                },
+                iso.running,
+                iso.queued,
+                iso.max_concurrent
+            ),
+            None => "n/a".to_string(),
+        };
+
+        vec![
+            Line::from(vec![label("Status:"), state]),
+            Line::from(vec![
+                label(uptime_label),
+                Span::raw(format_duration(self.uptime)),
+            ]),
+            Line::from(vec![
+                label("Listen:"),
+                Span::raw(format!("{}:{}", self.listen_addr, self.listen_port)),
+            ]),
+            Line::from(vec![label("Loaded:"), Span::raw(counts)]),
+            Line::from(vec![label("Isolation:"), Span::raw(isolation)]),
+        ]
+    }
+
+    /// Lines for the host metrics block.
+    fn host_lines(&self) -> Vec<Line<'static>> {
+        let label =
+            |text: &str| Span::styled(format!("{text:<8}"), Style::default().fg(Color::Gray));
+        let unknown = || "n/a".to_string();
+
+        let Some(host) = &self.host else {
+            return vec![Line::from(Span::styled(
+                "waiting for daemon…",
+                Style::default().fg(Color::DarkGray),
+            ))];
+        };
+
+        let load = host
+            .load_avg
+            .map_or_else(unknown, |[a, b, c]| format!("{a:.2} {b:.2} {c:.2}"));
+        let memory = match (host.memory_available_bytes, host.memory_total_bytes) {
+            (Some(avail), Some(total)) => {
+                format!(
+                    "{} available / {}",
+                    format_bytes(avail),
+                    format_bytes(total)
+                )
+            }
+            _ => unknown(),
+        };
+
+        let mut lines = vec![
+            Line::from(vec![
+                label("Load:"),
+                Span::raw(format!("{load} ({} CPUs)", host.cpu_count)),
+            ]),
+            Line::from(vec![label("Memory:"), Span::raw(memory)]),
+        ];
+        for disk in &host.disks {
+            let usage = match (disk.free_bytes, disk.total_bytes) {
+                (Some(free), Some(total)) => {
+                    format!("{} free / {}", format_bytes(free), format_bytes(total))
+                }
+                _ => unknown(),
+            };
+            lines.push(Line::from(vec![
+                label("Disk:"),
+                Span::raw(format!("{usage}  {} ({})", disk.label, disk.path)),
+            ]));
+        }
+        lines.push(Line::from(vec![
+            label("FDs:"),
+            Span::raw(host.open_fds.map_or_else(unknown, |n| n.to_string())),
+        ]));
+        lines
     }
 }
 
@@ -98,6 +239,21 @@ impl PanelState for DashboardPanel {
     }
 }
 
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
 fn format_duration(d: Duration) -> String {
     let total_secs = d.as_secs();
     let hours = total_secs / 3600;
@@ -117,6 +273,109 @@ mod tests {
         assert_eq!(format_duration(Duration::from_secs(3661)), "01:01:01");
     }
 
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(5 * 1024 * 1024 * 1024), "5.0 GiB");
+    }
+
+    #[test]
+    fn test_host_lines() {
+        let config = AppConfig::default();
+        let mut panel = DashboardPanel::new(&config);
+        assert_eq!(panel.host_lines().len(), 1); // waiting placeholder
+
+        panel.host = Some(HostStatusResponse {
+            load_avg: Some([0.5, 0.25, 0.1]),
+            cpu_count: 4,
+            memory_total_bytes: Some(8 * 1024 * 1024 * 1024),
+            memory_available_bytes: None,
+            disks: vec![crustyclaw_core::ipc::DiskInfo {
+                label: "signal.data_dir".to_string(),
+                path: "data/signal".to_string(),
+                total_bytes: Some(100 * 1024 * 1024 * 1024),
+                free_bytes: Some(40 * 1024 * 1024 * 1024),
+            }],
+            open_fds: Some(17),
+        });
+        let text = line_text(&panel.host_lines());
+        assert_eq!(text.len(), 4);
+        assert!(text[0].contains("0.50 0.25 0.10 (4 CPUs)"));
+        assert!(text[1].contains("n/a"));
+        assert!(text[2].contains("40.0 GiB free / 100.0 GiB"));
+        assert!(text[3].contains("17"));
+    }
+
+    fn line_text(lines: &[Line]) -> Vec<String> {
+        lines
+            .iter()
+            .map(|line| line.spans.iter().map(|s| s.content.as_ref()).collect())
+            .collect()
+    }
+
+    fn sample_status() -> StatusResponse {
+        StatusResponse {
+            running: true,
+            version: "0.1.0".to_string(),
+            git_hash: "abc123".to_string(),
+            uptime_secs: 42,
+            listen_addr: "127.0.0.1".to_string(),
+            listen_port: 7000,
+            signal_enabled: true,
+            log_level: "debug".to_string(),
+            isolation_backend: "noop".to_string(),
+            skills_count: 3,
+            plugins_count: 1,
+            pid: 1234,
+            warnings: vec![],
+        }
+    }
+
+    #[test]
+    fn test_apply_snapshot_updates_live_fields() {
+        let config = AppConfig::default();
+        let mut panel = DashboardPanel::new(&config);
+        assert!(line_text(&panel.status_lines())[0].contains("connecting"));
+
+        let snapshot = DaemonSnapshot {
+            state: ConnectionState::Connected,
+            status: Some(sample_status()),
+            ..DaemonSnapshot::default()
+        };
+        panel.apply_snapshot(&snapshot);
+        assert_eq!(panel.listen_port, 7000);
+        assert!(panel.signal_enabled);
+
+        let text = line_text(&panel.status_lines());
+        assert!(text[0].contains("running (PID 1234"));
+        assert!(text[1].starts_with("Uptime:"));
+        assert!(text[3].contains("3 skills, 1 plugins"));
+    }
+
+    #[test]
+    fn test_disconnected_keeps_last_values() {
+        let config = AppConfig::default();
+        let mut panel = DashboardPanel::new(&config);
+        panel.apply_snapshot(&DaemonSnapshot {
+            state: ConnectionState::Connected,
+            status: Some(sample_status()),
+            ..DaemonSnapshot::default()
+        });
+        panel.apply_snapshot(&DaemonSnapshot {
+            state: ConnectionState::Disconnected {
+                error: "gone".to_string(),
+                retry_in: Duration::from_secs(2),
+            },
+            status: Some(sample_status()),
+            ..DaemonSnapshot::default()
+        });
+
+        let text = line_text(&panel.status_lines());
+        assert!(text[0].contains("unreachable"));
+        assert_eq!(panel.listen_port, 7000);
+    }
+
     #[test]
     fn test_dashboard_from_config() {
         let config = AppConfig::default();
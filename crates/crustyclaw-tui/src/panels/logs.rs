@@ -1,6 +1,9 @@
-//! Logs panel — scrollable live log viewer.
+//! Logs panel — scrollable live viewer for the daemon's log stream.
 
-use crustyclaw_core::LogReader;
+use std::collections::VecDeque;
+use std::str::FromStr;
+
+use crustyclaw_core::ipc::LogEntry;
 use ratatui::{
     prelude::*,
     widgets::{Block, Borders, List, ListItem},
@@ -8,16 +11,23 @@ use ratatui::{
 use tracing::Level;
 
 use super::PanelState;
+use crate::connection::ConnectionState;
+
+/// Maximum number of entries kept in the panel's scrollback.
+const SCROLLBACK: usize = 5000;
 
-/// Scrollable log viewer panel with auto-follow.
+/// Scrollable log viewer panel with auto-follow and a minimum-level filter.
 pub struct LogsPanel {
-    reader: LogReader,
-    /// Cached snapshot of log entries (refreshed on tick).
-    entries: Vec<LogLine>,
-    /// Scroll offset (0 = bottom/latest).
+    /// Received log entries, oldest first.
+    entries: VecDeque<LogLine>,
+    /// Scroll offset into the filtered entries (0 = bottom/latest).
     scroll_offset: usize,
     /// Whether to auto-follow (stick to bottom).
     auto_follow: bool,
+    /// Least severe level shown; `None` shows everything.
+    pub min_level: Option<Level>,
+    /// State of the log stream connection.
+    connection: ConnectionState,
 }
 
 struct LogLine {
@@ -28,56 +38,111 @@ struct LogLine {
 }
 
 impl LogsPanel {
-    pub fn new(reader: LogReader) -> Self {
+    pub fn new() -> Self {
         Self {
-            reader,
-            entries: Vec::new(),
+            entries: VecDeque::new(),
             scroll_offset: 0,
             auto_follow: true,
+            min_level: None,
+            connection: ConnectionState::Connecting,
         }
     }
 
-    /// Refresh cached entries from the log reader.
-    pub fn refresh(&mut self) {
-        self.entries = self
-            .reader
-            .entries()
-            .into_iter()
-            .map(|e| LogLine {
-                elapsed: format!("{:>8.2}s", e.elapsed_secs),
-                level: e.level,
-                target: e.target,
-                message: e.message,
-            })
-            .collect();
-
-        // If auto-following, keep scroll at bottom
-        if self.auto_follow {
-            self.scroll_offset = 0;
+    /// Append an entry from the daemon's log stream.
+    pub fn push(&mut self, entry: LogEntry) {
+        let line = LogLine {
+            elapsed: format!("{:>8.2}s", entry.elapsed_secs),
+            level: Level::from_str(&entry.level).unwrap_or(Level::INFO),
+            target: entry.target,
+            message: entry.message,
+        };
+        // Keep the viewport anchored when the user has scrolled up.
+        if !self.auto_follow && self.shows(line.level) {
+            self.scroll_offset += 1;
         }
+        if self.entries.len() >= SCROLLBACK {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(line);
+        self.clamp_scroll();
+    }
+
+    /// Record the state of the log stream connection.
+    pub fn set_connection(&mut self, state: ConnectionState) {
+        self.connection = state;
+    }
+
+    /// Cycle the level filter: all → DEBUG → INFO → WARN → ERROR → all.
+    pub fn cycle_level_filter(&mut self) {
+        self.min_level = match self.min_level {
+            None => Some(Level::DEBUG),
+            Some(Level::DEBUG) => Some(Level::INFO),
+            Some(Level::INFO) => Some(Level::WARN),
+            Some(Level::WARN) => Some(Level::ERROR),
+            Some(_) => None,
+        };
+        self.clamp_scroll();
+    }
+
+    /// Whether an entry at `level` passes the filter.
+    fn shows(&self, level: Level) -> bool {
+        // `Level` orders more verbose levels as greater (TRACE > ERROR).
+        self.min_level.is_none_or(|min| level <= min)
+    }
+
+    fn visible(&self) -> impl Iterator<Item = &LogLine> {
+        self.entries.iter().filter(|e| self.shows(e.level))
+    }
+
+    fn visible_count(&self) -> usize {
+        self.visible().count()
+    }
+
+    fn clamp_scroll(&mut self) {
+        let max_offset = self.visible_count().saturating_sub(1);
+        self.scroll_offset = self.scroll_offset.min(max_offset);
+    }
+
+    fn title(&self, shown: usize) -> String {
+        let total = self.entries.len();
+        let count = if shown == total {
+            format!("{total}")
+        } else {
+            format!("{shown}/{total}")
+        };
+        let filter = match self.min_level {
+            Some(level) => format!(" [>= {level}]"),
+            None => String::new(),
+        };
+        let follow = if self.auto_follow { " [follow]" } else { "" };
+        let connection = match &self.connection {
+            ConnectionState::Connected => "",
+            ConnectionState::Connecting => " (connecting…)",
+            ConnectionState::Disconnected { .. } => " (disconnected)",
+        };
+        format!(" Logs ({count}){filter}{follow}{connection} ")
     }
 
     pub fn render(&self, frame: &mut Frame, area: Rect) {
         let visible_height = area.height.saturating_sub(2) as usize; // minus borders
+        let shown = self.visible_count();
 
-        if self.entries.is_empty() {
+        if shown == 0 {
             let empty = ratatui::widgets::Paragraph::new("  (no log entries yet)")
                 .style(Style::default().fg(Color::DarkGray))
-                .block(Block::default().title(" Logs (0) ").borders(Borders::ALL));
+                .block(Block::default().title(self.title(0)).borders(Borders::ALL));
             frame.render_widget(empty, area);
             return;
         }
 
-        let total = self.entries.len();
-        let skip = if total > visible_height + self.scroll_offset {
-            total - visible_height - self.scroll_offset
+        let skip = if shown > visible_height + self.scroll_offset {
+            shown - visible_height - self.scroll_offset
         } else {
             0
         };
 
         let items: Vec<ListItem> = self
-            .entries
-            .iter()
+            .visible()
             .skip(skip)
             .take(visible_height)
             .map(|entry| {
@@ -105,14 +170,21 @@ impl LogsPanel {
             })
             .collect();
 
-        let follow_indicator = if self.auto_follow { " [follow]" } else { "" };
-        let title = format!(" Logs ({total}){follow_indicator} ");
-
-        let list = List::new(items).block(Block::default().title(title).borders(Borders::ALL));
+        let list = List::new(items).block(
+            Block::default()
+                .title(self.title(shown))
+                .borders(Borders::ALL),
+        );
         frame.render_widget(list, area);
     }
 }
 
+impl Default for LogsPanel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl PanelState for LogsPanel {
     fn scroll_down(&mut self, n: usize) {
         if self.scroll_offset >= n {
@@ -125,13 +197,13 @@ impl PanelState for LogsPanel {
 
     fn scroll_up(&mut self, n: usize) {
         self.auto_follow = false;
-        let max_offset = self.entries.len().saturating_sub(1);
+        let max_offset = self.visible_count().saturating_sub(1);
         self.scroll_offset = (self.scroll_offset + n).min(max_offset);
     }
 
     fn scroll_to_top(&mut self) {
         self.auto_follow = false;
-        self.scroll_offset = self.entries.len().saturating_sub(1);
+        self.scroll_offset = self.visible_count().saturating_sub(1);
     }
 
     fn scroll_to_bottom(&mut self) {
@@ -143,39 +215,46 @@ impl PanelState for LogsPanel {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tracing_subscriber::layer::SubscriberExt;
 
-    fn make_logs_panel_with_entries(count: usize) -> LogsPanel {
-        let collector = crustyclaw_core::LogCollector::new(1000);
-        let reader = collector.reader();
+    fn entry(seq: u64, level: &str) -> LogEntry {
+        LogEntry {
+            seq,
+            elapsed_secs: seq as f64,
+            level: level.to_string(),
+            target: "crustyclaw_core".to_string(),
+            message: format!("test log entry {seq}"),
+        }
+    }
 
-        // Emit tracing events so the collector captures them
-        let subscriber = tracing_subscriber::registry().with(collector);
-        let _guard = tracing::subscriber::set_default(subscriber);
+    fn make_logs_panel_with_entries(count: usize) -> LogsPanel {
+        let mut panel = LogsPanel::new();
         for i in 0..count {
-            tracing::info!("test log entry {i}");
+            panel.push(entry(i as u64 + 1, "INFO"));
         }
-
-        let mut panel = LogsPanel::new(reader);
-        panel.refresh();
         panel
     }
 
     #[test]
     fn test_new_panel_starts_empty() {
-        let collector = crustyclaw_core::LogCollector::new(100);
-        let panel = LogsPanel::new(collector.reader());
+        let panel = LogsPanel::new();
         assert_eq!(panel.entries.len(), 0);
         assert!(panel.auto_follow);
         assert_eq!(panel.scroll_offset, 0);
     }
 
     #[test]
-    fn test_refresh_captures_entries() {
+    fn test_push_captures_entries() {
         let panel = make_logs_panel_with_entries(5);
         assert_eq!(panel.entries.len(), 5);
     }
 
+    #[test]
+    fn test_scrollback_is_bounded() {
+        let panel = make_logs_panel_with_entries(SCROLLBACK + 10);
+        assert_eq!(panel.entries.len(), SCROLLBACK);
+        assert_eq!(panel.entries[0].message, "test log entry 11");
+    }
+
     #[test]
     fn test_auto_follow_enabled_by_default() {
         let panel = make_logs_panel_with_entries(3);
@@ -193,6 +272,14 @@ mod tests {
         assert_eq!(panel.scroll_offset, 5);
     }
 
+    #[test]
+    fn test_new_entries_keep_scrolled_view_anchored() {
+        let mut panel = make_logs_panel_with_entries(20);
+        panel.scroll_up(5);
+        panel.push(entry(21, "INFO"));
+        assert_eq!(panel.scroll_offset, 6);
+    }
+
     #[test]
     fn test_scroll_down_re_enables_auto_follow_at_bottom() {
         let mut panel = make_logs_panel_with_entries(20);
@@ -232,12 +319,74 @@ mod tests {
 
     #[test]
     fn test_scroll_on_empty_panel() {
-        let collector = crustyclaw_core::LogCollector::new(100);
-        let mut panel = LogsPanel::new(collector.reader());
+        let mut panel = LogsPanel::new();
         // Should not panic
         panel.scroll_up(5);
         panel.scroll_down(5);
         panel.scroll_to_top();
         panel.scroll_to_bottom();
     }
+
+    #[test]
+    fn test_level_filter_cycles() {
+        let mut panel = LogsPanel::new();
+        let mut seen = Vec::new();
+        for _ in 0..5 {
+            panel.cycle_level_filter();
+            seen.push(panel.min_level);
+        }
+        assert_eq!(
+            seen,
+            vec![
+                Some(Level::DEBUG),
+                Some(Level::INFO),
+                Some(Level::WARN),
+                Some(Level::ERROR),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn test_level_filter_hides_verbose_entries() {
+        let mut panel = LogsPanel::new();
+        for (seq, level) in ["TRACE", "DEBUG", "INFO", "WARN", "ERROR"]
+            .iter()
+            .enumerate()
+        {
+            panel.push(entry(seq as u64 + 1, level));
+        }
+        assert_eq!(panel.visible_count(), 5);
+
+        panel.cycle_level_filter(); // DEBUG
+        panel.cycle_level_filter(); // INFO
+        panel.cycle_level_filter(); // WARN
+        assert_eq!(panel.visible_count(), 2);
+        assert!(panel.title(2).contains("2/5"));
+        assert!(panel.title(2).contains("[>= WARN]"));
+
+        // Scroll bounds follow the filtered view.
+        panel.scroll_to_top();
+        assert_eq!(panel.scroll_offset, 1);
+    }
+
+    #[test]
+    fn test_unknown_level_defaults_to_info() {
+        let mut panel = LogsPanel::new();
+        panel.push(entry(1, "bogus"));
+        assert_eq!(panel.entries[0].level, Level::INFO);
+    }
+
+    #[test]
+    fn test_title_shows_connection_state() {
+        let mut panel = LogsPanel::new();
+        assert!(panel.title(0).contains("connecting"));
+        panel.set_connection(ConnectionState::Connected);
+        assert!(!panel.title(0).contains("connect"));
+        panel.set_connection(ConnectionState::Disconnected {
+            error: "gone".to_string(),
+            retry_in: std::time::Duration::from_secs(1),
+        });
+        assert!(panel.title(0).contains("disconnected"));
+    }
 }
@@ -1,12 +1,28 @@
-//! Messages panel — live view of incoming/outgoing messages.
-
+//! Messages panel — live message traffic and recorded conversations.
+//!
+//! The live view shows every message on the daemon's bus as it is sent or
+//! received, streamed from `GET /messages/stream`, with how each one was
+//! routed. Traffic can be paused, searched, and opened in a detail view.
+//! The history view shows the conversation selected with `c`/`C` (the most
+//! recently active one by default), refreshed from the daemon's
+//! `/conversations` endpoints on every poll.
+
+use std::collections::VecDeque;
+
+use crustyclaw_core::ipc::{ConversationInfo, ConversationResponse, MessageEvent, MessageRoute};
 use ratatui::{
     prelude::*,
-    widgets::{Block, Borders, List, ListItem, Paragraph},
+    widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Wrap},
 };
 
 use super::PanelState;
 
+/// Live messages kept, oldest dropped first.
+const TRAFFIC_CAPACITY: usize = 1000;
+
+/// Characters of a message body shown in the live list.
+const BODY_PREVIEW: usize = 60;
+
 /// A displayable message entry.
 #[derive(Debug, Clone)]
 pub struct MessageEntry {
@@ -23,23 +39,115 @@ pub enum MessageDirection {
     Outbound,
 }
 
-/// Message panel state — scrollable list of inbound/outbound messages.
+impl MessageEntry {
+    /// Convert a recorded message, showing its UTC time of day.
+    fn from_history(message: &crustyclaw_core::ipc::ConversationMessage) -> Self {
+        Self {
+            timestamp: time_of_day(message.timestamp_secs),
+            channel: message.channel.clone(),
+            direction: match message.inbound {
+                true => MessageDirection::Inbound,
+                false => MessageDirection::Outbound,
+            },
+            body: message.body.clone(),
+        }
+    }
+}
+
+/// Which of the panel's views is shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessagesView {
+    /// Messages on the bus as they happen.
+    Live,
+    /// The selected conversation's recorded history.
+    History,
+}
+
+/// Message panel state — live traffic and a conversation's history.
 pub struct MessagesPanel {
+    /// The view shown.
+    pub view: MessagesView,
     entries: Vec<MessageEntry>,
     scroll_offset: usize,
     auto_follow: bool,
+    /// ID of the conversation shown, with its position in the listing.
+    conversation: Option<(String, usize, usize)>,
+    /// Live messages, oldest first.
+    traffic: VecDeque<MessageEvent>,
+    /// Messages received while paused, shown on resume.
+    held: VecDeque<MessageEvent>,
+    paused: bool,
+    /// ID of the highlighted live message; `None` follows the newest.
+    selected: Option<u64>,
+    /// Live messages must contain this (case-insensitive) to be shown.
+    search: String,
+    /// Whether keys are typed into the search.
+    searching: bool,
+    /// The message open in the detail view.
+    detail: Option<MessageEvent>,
 }
 
 impl MessagesPanel {
     pub fn new() -> Self {
         Self {
+            view: MessagesView::Live,
             entries: Vec::new(),
             scroll_offset: 0,
             auto_follow: true,
+            conversation: None,
+            traffic: VecDeque::new(),
+            held: VecDeque::new(),
+            paused: false,
+            selected: None,
+            search: String::new(),
+            searching: false,
+            detail: None,
+        }
+    }
+
+    /// Show `view`.
+    pub fn set_view(&mut self, view: MessagesView) {
+        self.view = view;
+    }
+
+    /// Switch between the live and history views.
+    pub fn toggle_view(&mut self) {
+        self.view = match self.view {
+            MessagesView::Live => MessagesView::History,
+            MessagesView::History => MessagesView::Live,
+        };
+    }
+
+    /// Show `history`, one of the stored `conversations`.
+    ///
+    /// The scroll position is kept while the same conversation is shown.
+    pub fn show_history(
+        &mut self,
+        conversations: &[ConversationInfo],
+        history: &ConversationResponse,
+    ) {
+        let position = conversations
+            .iter()
+            .position(|c| c.id == history.id)
+            .unwrap_or_default();
+        let changed = self
+            .conversation
+            .as_ref()
+            .is_none_or(|(id, _, _)| *id != history.id);
+        self.conversation = Some((history.id.clone(), position + 1, conversations.len()));
+        self.entries.clear();
+        for message in &history.messages {
+            self.push(MessageEntry::from_history(message));
+        }
+        if changed || self.auto_follow {
+            self.scroll_history_to_bottom();
+        } else {
+            let max_offset = self.entries.len().saturating_sub(1);
+            self.scroll_offset = self.scroll_offset.min(max_offset);
         }
     }
 
-    /// Push a new message into the panel.
+    /// Push a new message into the history view.
     pub fn push(&mut self, entry: MessageEntry) {
         self.entries.push(entry);
         if self.auto_follow {
@@ -47,17 +155,207 @@ impl MessagesPanel {
         }
     }
 
+    /// Record a message from the live stream; held back while paused.
+    pub fn push_live(&mut self, message: MessageEvent) {
+        let queue = match self.paused {
+            true => &mut self.held,
+            false => &mut self.traffic,
+        };
+        queue.push_back(message);
+        if queue.len() > TRAFFIC_CAPACITY {
+            queue.pop_front();
+        }
+    }
+
+    /// Pause or resume the live view. Resuming shows the messages held
+    /// while paused.
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        if !self.paused {
+            for message in std::mem::take(&mut self.held) {
+                self.push_live(message);
+            }
+        }
+    }
+
+    /// Whether keys are typed into the search.
+    pub fn is_searching(&self) -> bool {
+        self.searching
+    }
+
+    /// Start typing a search, replacing the current one.
+    pub fn start_search(&mut self) {
+        self.view = MessagesView::Live;
+        self.search.clear();
+        self.searching = true;
+    }
+
+    /// Add `c` to the search.
+    pub fn insert(&mut self, c: char) {
+        self.search.push(c);
+    }
+
+    /// Remove the last character of the search.
+    pub fn backspace(&mut self) {
+        self.search.pop();
+    }
+
+    /// Stop typing, keeping the search.
+    pub fn finish_search(&mut self) {
+        self.searching = false;
+    }
+
+    /// Stop typing and show all messages again.
+    pub fn clear_search(&mut self) {
+        self.searching = false;
+        self.search.clear();
+    }
+
+    /// Whether a message is open in the detail view.
+    pub fn is_showing_detail(&self) -> bool {
+        self.detail.is_some()
+    }
+
+    /// Open the highlighted live message, or the newest one, in the detail
+    /// view.
+    pub fn open_detail(&mut self) {
+        if self.view != MessagesView::Live {
+            return;
+        }
+        let matching = self.matching();
+        self.detail = self
+            .selected_position(&matching)
+            .map(|i| matching[i].clone());
+    }
+
+    /// Close the detail view.
+    pub fn close_detail(&mut self) {
+        self.detail = None;
+    }
+
+    /// The live messages that match the search, oldest first.
+    fn matching(&self) -> Vec<&MessageEvent> {
+        let search = self.search.to_lowercase();
+        self.traffic
+            .iter()
+            .filter(|m| {
+                search.is_empty()
+                    || [&m.channel, &m.body, m.peer.as_deref().unwrap_or_default()]
+                        .iter()
+                        .any(|field| field.to_lowercase().contains(&search))
+            })
+            .collect()
+    }
+
+    /// Index in `matching` of the highlighted message; the newest when
+    /// following or when the highlighted one is no longer shown.
+    fn selected_position(&self, matching: &[&MessageEvent]) -> Option<usize> {
+        self.selected
+            .and_then(|id| matching.iter().position(|m| m.id == id))
+            .or_else(|| matching.len().checked_sub(1))
+    }
+
+    /// Move the live highlight `step` messages, following the newest when
+    /// it moves past the end.
+    fn move_selection(&mut self, step: isize) {
+        let matching = self.matching();
+        let Some(current) = self.selected_position(&matching) else {
+            return;
+        };
+        let next = current.saturating_add_signed(step);
+        self.selected = (next < matching.len() - 1).then(|| matching[next].id);
+    }
+
+    fn scroll_history_to_bottom(&mut self) {
+        self.scroll_offset = 0;
+        self.auto_follow = true;
+    }
+
     pub fn render(&self, frame: &mut Frame, area: Rect) {
+        match self.view {
+            MessagesView::Live => self.render_live(frame, area),
+            MessagesView::History => self.render_history(frame, area),
+        }
+        if let Some(message) = &self.detail {
+            render_detail(frame, area, message);
+        }
+    }
+
+    fn render_live(&self, frame: &mut Frame, area: Rect) {
+        let visible_height = area.height.saturating_sub(2) as usize;
+        let matching = self.matching();
+
+        let mut title = format!(" Messages — live ({}) ", matching.len());
+        if self.paused {
+            title.push_str(&format!("[paused, {} held] ", self.held.len()));
+        }
+        if self.searching {
+            title.push_str(&format!("/{}█ ", self.search));
+        } else if !self.search.is_empty() {
+            title.push_str(&format!("/{} ", self.search));
+        }
+        let block = Block::default().title(title).borders(Borders::ALL);
+
+        if matching.is_empty() {
+            let empty = Paragraph::new("  (no messages yet)")
+                .style(Style::default().fg(Color::DarkGray))
+                .block(block);
+            frame.render_widget(empty, area);
+            return;
+        }
+
+        let selected = self.selected_position(&matching).unwrap_or_default();
+        let skip = (selected + 1).saturating_sub(visible_height);
+        let items: Vec<ListItem> = matching
+            .iter()
+            .enumerate()
+            .skip(skip)
+            .take(visible_height)
+            .map(|(i, message)| {
+                let (arrow, arrow_style) = direction_arrow(message.inbound);
+                let line = Line::from(vec![
+                    Span::styled(
+                        format!("{} ", time_of_day(message.timestamp_secs)),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                    Span::styled(format!("{arrow} "), arrow_style),
+                    Span::styled(
+                        format!("[{}] ", message.channel),
+                        Style::default().fg(Color::Yellow),
+                    ),
+                    Span::styled(
+                        format!("{} ", message.peer.as_deref().unwrap_or("-")),
+                        Style::default().fg(Color::Blue),
+                    ),
+                    Span::styled(
+                        format!("{} ", route_label(message)),
+                        route_style(&message.route),
+                    ),
+                    Span::raw(preview(&message.body)),
+                ]);
+                let item = ListItem::new(line);
+                match self.selected.is_some() && i == selected {
+                    true => item.style(Style::default().add_modifier(Modifier::REVERSED)),
+                    false => item,
+                }
+            })
+            .collect();
+
+        frame.render_widget(List::new(items).block(block), area);
+    }
+
+    fn render_history(&self, frame: &mut Frame, area: Rect) {
         let visible_height = area.height.saturating_sub(2) as usize;
 
+        let title = match &self.conversation {
+            Some((id, n, of)) => format!(" Messages — {id} ({n}/{of}) [{}] ", self.entries.len()),
+            None => format!(" Messages ({}) ", self.entries.len()),
+        };
+
         if self.entries.is_empty() {
             let empty = Paragraph::new("  (no messages yet)")
                 .style(Style::default().fg(Color::DarkGray))
-                .block(
-                    Block::default()
-                        .title(" Messages (0) ")
-                        .borders(Borders::ALL),
-                );
+                .block(Block::default().title(title).borders(Borders::ALL));
             frame.render_widget(empty, area);
             return;
         }
@@ -75,10 +373,8 @@ impl MessagesPanel {
             .skip(skip)
             .take(visible_height)
             .map(|entry| {
-                let (arrow, arrow_style) = match entry.direction {
-                    MessageDirection::Inbound => (">>", Style::default().fg(Color::Green)),
-                    MessageDirection::Outbound => ("<<", Style::default().fg(Color::Cyan)),
-                };
+                let (arrow, arrow_style) =
+                    direction_arrow(entry.direction == MessageDirection::Inbound);
 
                 let line = Line::from(vec![
                     Span::styled(
@@ -96,12 +392,118 @@ impl MessagesPanel {
             })
             .collect();
 
-        let title = format!(" Messages ({total}) ");
         let list = List::new(items).block(Block::default().title(title).borders(Borders::ALL));
         frame.render_widget(list, area);
     }
 }
 
+/// Draw `message` in full as a box over the middle of `area`.
+fn render_detail(frame: &mut Frame, area: Rect, message: &MessageEvent) {
+    let width = area.width.saturating_sub(4).min(96);
+    let height = area.height.saturating_sub(2).min(20);
+    let modal = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+    let field = |name: &str, value: String| {
+        Line::from(vec![
+            Span::styled(format!("{name:<10}"), Style::default().fg(Color::DarkGray)),
+            Span::raw(value),
+        ])
+    };
+    let mut lines = vec![
+        field(
+            "Time",
+            format!("{} UTC", time_of_day(message.timestamp_secs)),
+        ),
+        field("Channel", message.channel.clone()),
+        field(
+            "Direction",
+            match message.inbound {
+                true => "inbound",
+                false => "outbound",
+            }
+            .to_string(),
+        ),
+        field("Peer", message.peer.clone().unwrap_or_else(|| "-".into())),
+        field("Route", route_label(message)),
+        Line::from(""),
+    ];
+    lines.extend(
+        message
+            .body
+            .lines()
+            .map(|line| Line::from(line.to_string())),
+    );
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        "Esc: close",
+        Style::default().fg(Color::DarkGray),
+    )));
+    frame.render_widget(Clear, modal);
+    frame.render_widget(
+        Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+            Block::default()
+                .title(format!(" Message {} ", message.id))
+                .borders(Borders::ALL),
+        ),
+        modal,
+    );
+}
+
+/// `HH:MM:SS` (UTC) of a Unix time.
+fn time_of_day(secs: u64) -> String {
+    format!(
+        "{:02}:{:02}:{:02}",
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// The arrow for a message's direction: `>>` inbound, `<<` outbound.
+fn direction_arrow(inbound: bool) -> (&'static str, Style) {
+    match inbound {
+        true => (">>", Style::default().fg(Color::Green)),
+        false => ("<<", Style::default().fg(Color::Cyan)),
+    }
+}
+
+/// How `message` was routed, e.g. `cmd:deploy`.
+fn route_label(message: &MessageEvent) -> String {
+    match &message.route {
+        MessageRoute::Help => "help".to_string(),
+        MessageRoute::Command { name } => format!("cmd:{name}"),
+        MessageRoute::Denied { name } => format!("denied:{name}"),
+        MessageRoute::Unhandled => "unhandled".to_string(),
+        MessageRoute::Outbound => "reply".to_string(),
+        MessageRoute::Redaction => match message.redacts {
+            Some(id) => format!("redacts:{id}"),
+            None => "redaction".to_string(),
+        },
+    }
+}
+
+fn route_style(route: &MessageRoute) -> Style {
+    match route {
+        MessageRoute::Help | MessageRoute::Command { .. } => Style::default().fg(Color::Magenta),
+        MessageRoute::Denied { .. } => Style::default().fg(Color::Red),
+        _ => Style::default().fg(Color::DarkGray),
+    }
+}
+
+/// The start of `body` on one line, truncated to [`BODY_PREVIEW`]
+/// characters.
+fn preview(body: &str) -> String {
+    let line = body.replace(['\n', '\r'], " ");
+    match line.char_indices().nth(BODY_PREVIEW) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None => line,
+    }
+}
+
 impl Default for MessagesPanel {
     fn default() -> Self {
         Self::new()
@@ -110,6 +512,9 @@ impl Default for MessagesPanel {
 
 impl PanelState for MessagesPanel {
     fn scroll_down(&mut self, n: usize) {
+        if self.view == MessagesView::Live {
+            return self.move_selection(n as isize);
+        }
         if self.scroll_offset >= n {
             self.scroll_offset -= n;
         } else {
@@ -119,19 +524,29 @@ impl PanelState for MessagesPanel {
     }
 
     fn scroll_up(&mut self, n: usize) {
+        if self.view == MessagesView::Live {
+            return self.move_selection(-(n as isize));
+        }
         self.auto_follow = false;
         let max_offset = self.entries.len().saturating_sub(1);
         self.scroll_offset = (self.scroll_offset + n).min(max_offset);
     }
 
     fn scroll_to_top(&mut self) {
+        if self.view == MessagesView::Live {
+            self.selected = self.matching().first().map(|m| m.id);
+            return;
+        }
         self.auto_follow = false;
         self.scroll_offset = self.entries.len().saturating_sub(1);
     }
 
     fn scroll_to_bottom(&mut self) {
-        self.scroll_offset = 0;
-        self.auto_follow = true;
+        if self.view == MessagesView::Live {
+            self.selected = None;
+            return;
+        }
+        self.scroll_history_to_bottom();
     }
 }
 
@@ -148,6 +563,25 @@ mod tests {
         }
     }
 
+    fn history_panel() -> MessagesPanel {
+        let mut panel = MessagesPanel::new();
+        panel.set_view(MessagesView::History);
+        panel
+    }
+
+    fn live(id: u64, peer: &str, body: &str, route: MessageRoute) -> MessageEvent {
+        MessageEvent {
+            id,
+            timestamp_secs: 3_723,
+            channel: "signal".to_string(),
+            inbound: route != MessageRoute::Outbound,
+            peer: Some(peer.to_string()),
+            body: body.to_string(),
+            redacts: None,
+            route,
+        }
+    }
+
     #[test]
     fn test_new_panel_is_empty() {
         let panel = MessagesPanel::new();
@@ -164,7 +598,7 @@ mod tests {
 
     #[test]
     fn test_push_adds_entry() {
-        let mut panel = MessagesPanel::new();
+        let mut panel = history_panel();
         panel.push(sample_entry("hello", MessageDirection::Inbound));
         assert_eq!(panel.entries.len(), 1);
         assert_eq!(panel.entries[0].body, "hello");
@@ -172,7 +606,7 @@ mod tests {
 
     #[test]
     fn test_push_preserves_auto_follow() {
-        let mut panel = MessagesPanel::new();
+        let mut panel = history_panel();
         panel.push(sample_entry("a", MessageDirection::Inbound));
         assert!(panel.auto_follow);
         assert_eq!(panel.scroll_offset, 0);
@@ -180,7 +614,7 @@ mod tests {
 
     #[test]
     fn test_push_after_scroll_up_keeps_offset() {
-        let mut panel = MessagesPanel::new();
+        let mut panel = history_panel();
         for i in 0..10 {
             panel.push(sample_entry(&format!("msg {i}"), MessageDirection::Inbound));
         }
@@ -194,7 +628,7 @@ mod tests {
 
     #[test]
     fn test_scroll_up_disables_auto_follow() {
-        let mut panel = MessagesPanel::new();
+        let mut panel = history_panel();
         for i in 0..5 {
             panel.push(sample_entry(&format!("msg {i}"), MessageDirection::Inbound));
         }
@@ -205,7 +639,7 @@ mod tests {
 
     #[test]
     fn test_scroll_down_re_enables_auto_follow() {
-        let mut panel = MessagesPanel::new();
+        let mut panel = history_panel();
         for i in 0..5 {
             panel.push(sample_entry(&format!("msg {i}"), MessageDirection::Inbound));
         }
@@ -217,7 +651,7 @@ mod tests {
 
     #[test]
     fn test_scroll_to_top_and_bottom() {
-        let mut panel = MessagesPanel::new();
+        let mut panel = history_panel();
         for i in 0..20 {
             panel.push(sample_entry(&format!("msg {i}"), MessageDirection::Inbound));
         }
@@ -233,7 +667,7 @@ mod tests {
 
     #[test]
     fn test_scroll_up_clamped() {
-        let mut panel = MessagesPanel::new();
+        let mut panel = history_panel();
         for i in 0..5 {
             panel.push(sample_entry(&format!("msg {i}"), MessageDirection::Inbound));
         }
@@ -243,7 +677,7 @@ mod tests {
 
     #[test]
     fn test_scroll_on_empty_panel() {
-        let mut panel = MessagesPanel::new();
+        let mut panel = history_panel();
         panel.scroll_up(5);
         panel.scroll_down(5);
         panel.scroll_to_top();
@@ -251,6 +685,57 @@ mod tests {
         // No panic
     }
 
+    fn conversation(id: &str) -> ConversationInfo {
+        ConversationInfo {
+            id: id.to_string(),
+            channel: "signal".to_string(),
+            peer: None,
+            messages: 2,
+            started_secs: 0,
+            updated_secs: 0,
+            preview: String::new(),
+        }
+    }
+
+    fn history(id: &str, bodies: &[&str]) -> ConversationResponse {
+        ConversationResponse {
+            id: id.to_string(),
+            messages: bodies
+                .iter()
+                .enumerate()
+                .map(|(i, body)| crustyclaw_core::ipc::ConversationMessage {
+                    id: i as u64,
+                    timestamp_secs: 86_400 + 3_723,
+                    inbound: i % 2 == 0,
+                    channel: "signal".to_string(),
+                    body: body.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_show_history() {
+        let mut panel = history_panel();
+        let conversations = [conversation("a"), conversation("b")];
+        panel.show_history(&conversations, &history("b", &["hi", "hello", "ok"]));
+        assert_eq!(panel.entries.len(), 3);
+        assert_eq!(panel.entries[0].timestamp, "01:02:03");
+        assert_eq!(panel.entries[0].direction, MessageDirection::Inbound);
+        assert_eq!(panel.entries[1].direction, MessageDirection::Outbound);
+        assert_eq!(panel.conversation, Some(("b".to_string(), 2, 2)));
+
+        // Refreshing the same conversation keeps the reader's place.
+        panel.scroll_up(1);
+        panel.show_history(&conversations, &history("b", &["hi", "hello", "ok", "new"]));
+        assert_eq!(panel.scroll_offset, 1);
+
+        // Switching conversations jumps to the latest message.
+        panel.show_history(&conversations, &history("a", &["x"]));
+        assert_eq!(panel.scroll_offset, 0);
+        assert!(panel.auto_follow);
+    }
+
     #[test]
     fn test_message_directions() {
         let inbound = sample_entry("in", MessageDirection::Inbound);
@@ -258,4 +743,134 @@ mod tests {
         assert_eq!(inbound.direction, MessageDirection::Inbound);
         assert_eq!(outbound.direction, MessageDirection::Outbound);
     }
+
+    #[test]
+    fn test_live_view_is_default() {
+        let mut panel = MessagesPanel::new();
+        assert_eq!(panel.view, MessagesView::Live);
+        panel.toggle_view();
+        assert_eq!(panel.view, MessagesView::History);
+        panel.toggle_view();
+        assert_eq!(panel.view, MessagesView::Live);
+    }
+
+    #[test]
+    fn test_live_traffic_is_capped() {
+        let mut panel = MessagesPanel::new();
+        for i in 0..TRAFFIC_CAPACITY as u64 + 5 {
+            panel.push_live(live(i, "+1", "hi", MessageRoute::Unhandled));
+        }
+        assert_eq!(panel.traffic.len(), TRAFFIC_CAPACITY);
+        assert_eq!(panel.traffic.front().unwrap().id, 5);
+    }
+
+    #[test]
+    fn test_pause_holds_messages_until_resumed() {
+        let mut panel = MessagesPanel::new();
+        panel.push_live(live(1, "+1", "before", MessageRoute::Unhandled));
+        panel.toggle_pause();
+        panel.push_live(live(2, "+1", "during", MessageRoute::Unhandled));
+        assert_eq!(panel.traffic.len(), 1);
+        assert_eq!(panel.held.len(), 1);
+
+        panel.toggle_pause();
+        assert!(panel.held.is_empty());
+        let ids: Vec<u64> = panel.traffic.iter().map(|m| m.id).collect();
+        assert_eq!(ids, [1, 2]);
+    }
+
+    #[test]
+    fn test_search_filters_live_messages() {
+        let mut panel = MessagesPanel::new();
+        panel.push_live(live(
+            1,
+            "+15550001",
+            "Deploy staging",
+            MessageRoute::Unhandled,
+        ));
+        panel.push_live(live(2, "+15550002", "hello", MessageRoute::Unhandled));
+        panel.push_live(live(3, "+15550001", "done", MessageRoute::Outbound));
+
+        panel.start_search();
+        assert!(panel.is_searching());
+        for c in "DEPLOY".chars() {
+            panel.insert(c);
+        }
+        assert_eq!(panel.matching().len(), 1);
+        panel.backspace();
+        panel.finish_search();
+        assert!(!panel.is_searching());
+        assert_eq!(panel.search, "DEPLO");
+
+        // Peers match too.
+        panel.start_search();
+        "0001".chars().for_each(|c| panel.insert(c));
+        let ids: Vec<u64> = panel.matching().iter().map(|m| m.id).collect();
+        assert_eq!(ids, [1, 3]);
+
+        panel.clear_search();
+        assert_eq!(panel.matching().len(), 3);
+    }
+
+    #[test]
+    fn test_live_selection_and_detail() {
+        let mut panel = MessagesPanel::new();
+        panel.open_detail();
+        assert!(!panel.is_showing_detail());
+
+        for i in 1..=4 {
+            panel.push_live(live(i, "+1", &format!("msg {i}"), MessageRoute::Unhandled));
+        }
+        // Following: the newest message opens.
+        panel.open_detail();
+        assert_eq!(panel.detail.as_ref().unwrap().id, 4);
+        panel.close_detail();
+        assert!(!panel.is_showing_detail());
+
+        panel.scroll_up(2);
+        assert_eq!(panel.selected, Some(2));
+        panel.push_live(live(5, "+1", "msg 5", MessageRoute::Unhandled));
+        panel.open_detail();
+        assert_eq!(panel.detail.as_ref().unwrap().body, "msg 2");
+        panel.close_detail();
+
+        panel.scroll_to_top();
+        assert_eq!(panel.selected, Some(1));
+        panel.scroll_down(10);
+        assert_eq!(panel.selected, None);
+        panel.scroll_up(1);
+        panel.scroll_to_bottom();
+        assert_eq!(panel.selected, None);
+
+        // The history view has no detail view.
+        panel.toggle_view();
+        panel.open_detail();
+        assert!(panel.detail.is_none());
+    }
+
+    #[test]
+    fn test_route_labels_and_preview() {
+        let label = |route| route_label(&live(1, "+1", "x", route));
+        assert_eq!(label(MessageRoute::Help), "help");
+        assert_eq!(
+            label(MessageRoute::Command {
+                name: "deploy".to_string()
+            }),
+            "cmd:deploy"
+        );
+        assert_eq!(
+            label(MessageRoute::Denied {
+                name: "deploy".to_string()
+            }),
+            "denied:deploy"
+        );
+        assert_eq!(label(MessageRoute::Outbound), "reply");
+        let mut redaction = live(2, "+1", "", MessageRoute::Redaction);
+        redaction.redacts = Some(1);
+        assert_eq!(route_label(&redaction), "redacts:1");
+
+        assert_eq!(preview("two\nlines"), "two lines");
+        let long = "é".repeat(BODY_PREVIEW + 1);
+        assert_eq!(preview(&long), format!("{}…", "é".repeat(BODY_PREVIEW)));
+    }
 }
@@ -1,14 +1,16 @@
 //! TUI panel implementations.
 
+mod chat;
 mod config;
 mod dashboard;
 mod logs;
 mod messages;
 
+pub use chat::ChatPanel;
 pub use config::ConfigPanel;
 pub use dashboard::DashboardPanel;
 pub use logs::LogsPanel;
-pub use messages::{MessageDirection, MessageEntry, MessagesPanel};
+pub use messages::{MessagesPanel, MessagesView};
 
 /// Trait for panels that support scrolling.
 pub trait PanelState {